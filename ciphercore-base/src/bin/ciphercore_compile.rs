@@ -10,6 +10,7 @@ use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
 use ciphercore_base::graphs::Context;
 use ciphercore_base::inline::inline_common::DepthOptimizationLevel;
 use ciphercore_base::inline::inline_ops::{InlineConfig, InlineMode};
+use ciphercore_base::mpc::compilation_cache::CompilationCache;
 use ciphercore_base::mpc::mpc_compiler::{compile_context, IOStatus};
 use ciphercore_utils::execute_main::execute_main;
 
@@ -39,6 +40,13 @@ struct Args {
     #[clap(value_parser)]
     /// String comprising comma separated list of output parties' IDs, which could be `0`, `1`, and `2` OR `public` OR `secret-shared`.
     output_parties: String,
+    #[clap(short, long, value_parser)]
+    /// Path to the output file for the compiled context. If not given, the compiled context is printed to stdout.
+    // All the parties execute the same compiled context, so a single file is written.
+    output_path: Option<String>,
+    #[clap(long, value_parser)]
+    /// Path to a directory caching compiled contexts. If given, the context is compiled only if it isn't found in the cache.
+    cache_dir: Option<String>,
 }
 
 /// Returns tokens from given stringed input consisting of (1) comma separated party IDs, OR (2) "public", OR (3) "secret-shared".
@@ -176,10 +184,14 @@ fn get_evaluator() -> Result<SimpleEvaluator> {
 /// * `inline_mode` - argument to specify the inline mode required, valid values are `simple`, `depth-optimized-default` and  `depth-optimized-extreme`
 /// * `input_parties` - string comprising of either a comma separated list of input parties' IDs, valid ID values include `0`, `1`, `2` OR `public` OR `secret-shared`
 /// * `output_parties` - string comprising comma separated list of output parties' IDs, which could be `0`, `1`, and `2` OR `public` OR `secret-shared`
+/// * `output_path` - optional path to the output file for the compiled context
+/// * `cache_dir` - optional path to a directory caching compiled contexts
+///
+/// The compiled context is the same for all the parties; every party executes it with its own party ID.
 ///
 /// # Usage
 ///
-/// < this_binary > <context_path> <inline_mode> <input_parties> <output_parties>
+/// < this_binary > <context_path> <inline_mode> <input_parties> <output_parties> [-o <output_path>] [--cache-dir <cache_dir>]
 fn main() {
     // Initialize a logger that collects information about errors and panics within CipherCore.
     // This information can be accessed via RUST_LOG.
@@ -196,19 +208,35 @@ fn main() {
         let input_parties = parse_input_parties(args.input_parties)?;
         // Parse the output party information
         let output_parties = parse_output_parties(args.output_parties)?;
+        let inline_config = InlineConfig {
+            default_mode: get_inline_mode(args.inline_mode),
+            ..Default::default()
+        };
         // Obtain the compiled context
-        let compiled_context = compile_context(
-            context,
-            input_parties,
-            output_parties,
-            InlineConfig {
-                default_mode: get_inline_mode(args.inline_mode),
-                ..Default::default()
-            },
-            get_evaluator,
-        )?;
-        // Print the serialized and compiled context to stdout
-        println!("{}", serde_json::to_string(&compiled_context).unwrap());
+        let compiled_context = match args.cache_dir {
+            // Load the compiled context from the cache or compile it and store it in the cache
+            Some(cache_dir) => CompilationCache::new(cache_dir)?.compile_context(
+                context,
+                input_parties,
+                output_parties,
+                inline_config,
+                get_evaluator,
+            )?,
+            None => compile_context(
+                context,
+                input_parties,
+                output_parties,
+                inline_config,
+                get_evaluator,
+            )?,
+        };
+        let serialized_compiled_context = serde_json::to_string(&compiled_context)?;
+        match args.output_path {
+            // Write the serialized and compiled context to the output file
+            Some(output_path) => fs::write(output_path, serialized_compiled_context)?,
+            // Print the serialized and compiled context to stdout
+            None => println!("{}", serialized_compiled_context),
+        }
         Ok(())
     });
 }
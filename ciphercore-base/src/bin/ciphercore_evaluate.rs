@@ -3,6 +3,7 @@
 
 use ciphercore_base::errors::Result;
 use ciphercore_base::evaluators::get_result_util::get_evaluator_result;
+use ciphercore_base::evaluators::profiling_evaluator::ProfilingEvaluator;
 use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
 use ciphercore_base::graphs::Context;
 use ciphercore_base::typed_value::TypedValue;
@@ -23,6 +24,12 @@ struct Args {
     #[clap(long, value_parser)]
     /// (optional) Boolean to indicate if the output is to be revealed for secret-shared outputs
     reveal_output: bool,
+    #[clap(long, value_parser)]
+    /// (optional) Path to a file where a JSON profile of the evaluation is written
+    profile_json: Option<String>,
+    #[clap(long, value_parser)]
+    /// (optional) Path to a file where a profile of the evaluation is written in the folded stack format used by flamegraph tools
+    profile_folded: Option<String>,
 }
 
 /// This binary evaluates a given context over the provided inputs using a simple evaluator.
@@ -34,10 +41,12 @@ struct Args {
 /// * `context_path` - path to a serialized context
 /// * `inputs_path` - path to serialized input(s)
 /// * `reveal_output` - boolean to indicate if output is to be revealed
+/// * `profile_json` - (optional) path to write a JSON profile with per-node timing and communication
+/// * `profile_folded` - (optional) path to write a profile in the flamegraph folded stack format
 ///
 /// # Usage
 ///
-/// < this_binary > [--reveal-output] <CONTEXT_PATH> <INPUTS_PATH>
+/// < this_binary > [--reveal-output] [--profile-json <PATH>] [--profile-folded <PATH>] <CONTEXT_PATH> <INPUTS_PATH>
 fn main() {
     // Initialize a logger that collects information about errors and panics within CipherCore.
     // This information can be accessed via RUST_LOG.
@@ -54,13 +63,17 @@ fn main() {
         let json_inputs = fs::read_to_string(&args.inputs_path)?;
         // Parse inputs to obtain a vector of typed values, i.e., pair of type, and its value
         let inputs = serde_json::from_str::<Vec<TypedValue>>(&json_inputs)?;
-        // Use the simple evaluator to obtain the typed result value
-        let result = get_evaluator_result(
-            raw_context,
-            inputs,
-            args.reveal_output,
-            SimpleEvaluator::new(None)?,
-        )?;
+        // Use the simple evaluator wrapped into a profiler to obtain the typed result value
+        let mut evaluator = ProfilingEvaluator::new(SimpleEvaluator::new(None)?);
+        let result = get_evaluator_result(raw_context, inputs, args.reveal_output, &mut evaluator)?;
+        // Write the profiles if requested
+        let report = evaluator.get_report();
+        if let Some(path) = &args.profile_json {
+            fs::write(path, report.to_json()?)?;
+        }
+        if let Some(path) = &args.profile_folded {
+            fs::write(path, report.to_folded_stacks()?)?;
+        }
         // Depending on the input argument, print whether the output is revealed
         if args.reveal_output {
             eprintln!("Revealing the output");
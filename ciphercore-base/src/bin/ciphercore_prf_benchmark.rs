@@ -0,0 +1,71 @@
+//! Code of a binary that measures the throughput of PRNG and PRF backends
+#![cfg_attr(feature = "nightly-features", feature(backtrace))]
+extern crate ciphercore_base;
+
+use ciphercore_base::errors::Result;
+use ciphercore_base::random::{get_prf_backend_by_name, PRF_BACKEND_NAMES, PRNG, SEED_SIZE};
+use ciphercore_utils::execute_main::execute_main;
+
+use clap::Parser;
+use std::time::Instant;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about=None)]
+struct Args {
+    #[clap(long, value_parser, default_value_t = 64)]
+    /// number of mebibytes generated by every backend
+    mebibytes: usize,
+    #[clap(long, value_parser)]
+    /// (optional) backends to measure; all the built-in backends are measured by default
+    backends: Vec<String>,
+}
+
+/// This binary generates pseudo-random bytes with PRF backends and prints their throughput.
+///
+/// Backends that are not supported by the CPU (e.g. AES-NI) are reported and skipped.
+///
+/// # Arguments
+///
+/// * `mebibytes` - number of mebibytes generated by every backend
+/// * `backends` - names of backends to measure
+///
+/// # Usage
+///
+/// < this_binary > [--mebibytes <mebibytes>] [--backends <backend>...]
+fn main() {
+    // Initialize a logger that collects information about errors and panics within CipherCore.
+    // This information can be accessed via RUST_LOG.
+    env_logger::init();
+    // Execute CipherCore code such that all the internal errors are properly formatted and logged.
+    execute_main(|| -> Result<()> {
+        let args = Args::parse();
+        let names = if args.backends.is_empty() {
+            PRF_BACKEND_NAMES
+                .iter()
+                .map(|name| name.to_string())
+                .collect()
+        } else {
+            args.backends
+        };
+        let num_bytes = args.mebibytes << 20;
+        for name in names {
+            let backend = get_prf_backend_by_name(&name)?;
+            let mut prng = match PRNG::new_with_backend(Some([0; SEED_SIZE]), backend.as_ref()) {
+                Ok(prng) => prng,
+                Err(e) => {
+                    println!("{}: unavailable ({})", backend.get_name(), e);
+                    continue;
+                }
+            };
+            let start = Instant::now();
+            prng.get_random_bytes(num_bytes)?;
+            let seconds = start.elapsed().as_secs_f64();
+            println!(
+                "{}: {:.1} MiB/s",
+                backend.get_name(),
+                args.mebibytes as f64 / seconds
+            );
+        }
+        Ok(())
+    });
+}
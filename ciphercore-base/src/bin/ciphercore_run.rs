@@ -0,0 +1,292 @@
+//! Code of a binary that runs a (compiled) context locally on JSON or CSV inputs and prints the result in a human-readable format
+#![cfg_attr(feature = "nightly-features", feature(backtrace))]
+#[macro_use]
+extern crate ciphercore_base;
+
+use ciphercore_base::data_types::{ScalarType, Type};
+use ciphercore_base::data_values::{ToNdarray, Value};
+use ciphercore_base::errors::Result;
+use ciphercore_base::evaluators::get_result_util::get_evaluator_result;
+use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
+use ciphercore_base::graphs::{Context, Operation};
+use ciphercore_base::io::csv::{load_csv, CsvColumn};
+use ciphercore_base::random::{get_prf_backend_by_name, set_default_prf_backend};
+use ciphercore_base::type_inference::NULL_HEADER;
+use ciphercore_base::typed_value::TypedValue;
+use ciphercore_utils::execute_main::execute_main;
+
+use clap::Parser;
+use std::fs;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about=None)]
+struct Args {
+    #[clap(value_parser)]
+    /// Path to a file containing serialized context
+    context_path: String,
+    #[clap(value_parser)]
+    /// Paths to files containing inputs: a CSV file contains one input, a JSON file contains a list of serialized inputs
+    inputs_paths: Vec<String>,
+    #[clap(long, value_parser)]
+    /// (optional) Boolean to indicate if the output is to be revealed for secret-shared outputs
+    reveal_output: bool,
+    #[clap(long, value_parser)]
+    /// (optional) Boolean to indicate if the result is to be printed in the JSON format instead of the human-readable one
+    json: bool,
+    #[clap(long, value_parser, default_value = "aes")]
+    /// (optional) Backend of PRNGs and PRFs: aes, aes-ni, chacha20 or deterministic-test (insecure, for debugging only)
+    prf_backend: String,
+}
+
+/// Returns the plaintext types of the inputs of the main graph of a context.
+///
+/// The input types of compiled contexts are tuples of 3 shares if inputs are secret-shared; such types are replaced by the type of a share.
+///
+/// # Arguments
+///
+/// `context` - context whose main graph is to be evaluated
+///
+/// # Returns
+///
+/// Vector of input types
+fn get_input_types(context: &Context) -> Result<Vec<Type>> {
+    let mut input_types = vec![];
+    for node in context.get_main_graph()?.get_nodes() {
+        if let Operation::Input(t) = node.get_operation() {
+            let plaintext_t = match &t {
+                Type::Tuple(v) if v.len() == 3 && v[0] == v[1] && v[0] == v[2] => (*v[0]).clone(),
+                _ => t,
+            };
+            input_types.push(plaintext_t);
+        }
+    }
+    Ok(input_types)
+}
+
+/// Loads a CSV file into a named tuple of a given type.
+///
+/// Columns are selected and ordered according to the headers of the type, and the number of rows is padded or truncated to the length of the columns.
+///
+/// # Arguments
+///
+/// * `path` - path to a CSV file
+/// * `t` - named tuple type of one-dimensional arrays containing the null column
+///
+/// # Returns
+///
+/// Loaded input
+fn load_csv_input(path: &str, t: &Type) -> Result<TypedValue> {
+    let header_types = match t {
+        Type::NamedTuple(header_types) => header_types,
+        _ => {
+            return Err(runtime_error!(
+                "CSV file {} can't be loaded into an input of type {}",
+                path,
+                t
+            ))
+        }
+    };
+    let mut columns = vec![];
+    let mut num_rows = None;
+    for (header, column_t) in header_types {
+        if !column_t.is_array() || column_t.get_shape().len() != 1 {
+            return Err(runtime_error!(
+                "CSV file {} can be loaded only into named tuples of one-dimensional arrays",
+                path
+            ));
+        }
+        num_rows = Some(column_t.get_shape()[0]);
+        if header != NULL_HEADER {
+            columns.push(CsvColumn::new(header, column_t.get_scalar_type()));
+        }
+    }
+    let (_, value) = load_csv(path, Some(columns), num_rows)?;
+    // The null column is the last one in the loaded value
+    let mut loaded_columns = value.to_vector()?;
+    let null_column = loaded_columns
+        .pop()
+        .ok_or_else(|| runtime_error!("Empty CSV input"))?;
+    let mut loaded_columns = loaded_columns.into_iter();
+    let mut result_columns = vec![];
+    for (header, _) in header_types {
+        if header == NULL_HEADER {
+            result_columns.push(null_column.clone());
+        } else {
+            result_columns.push(loaded_columns.next().unwrap());
+        }
+    }
+    TypedValue::new(t.clone(), Value::from_vector(result_columns))
+}
+
+/// Returns a string representation of an integer given modulo 2^64, interpreted according to a given scalar type.
+fn format_entry(x: u64, st: &ScalarType) -> String {
+    if st.get_signed() {
+        // Extend the sign bit of the entry
+        let shift = 64 - st.size_in_bits();
+        (((x << shift) as i64) >> shift).to_string()
+    } else {
+        x.to_string()
+    }
+}
+
+/// Returns the entries of an array as strings.
+fn format_entries(value: &Value, t: Type) -> Result<Vec<String>> {
+    let st = t.get_scalar_type();
+    Ok(value
+        .to_flattened_array_u64(t)?
+        .into_iter()
+        .map(|x| format_entry(x, &st))
+        .collect())
+}
+
+/// Returns a multi-line representation of an array.
+fn format_array(value: &Value, t: Type, st: &ScalarType) -> Result<String> {
+    let array = ToNdarray::<u64>::to_ndarray(value, t)?;
+    Ok(format!("{}", array.map(|x| format_entry(*x, st))))
+}
+
+/// Returns a human-readable representation of a value of a given type.
+///
+/// Named tuples of one-dimensional arrays of the same length (e.g. databases used in set intersection) are printed as tables.
+///
+/// # Arguments
+///
+/// * `value` - value to be formatted
+/// * `t` - type of the value
+/// * `indent` - indentation of all lines except for the first one
+///
+/// # Returns
+///
+/// Formatted value
+fn format_value(value: &Value, t: &Type, indent: usize) -> Result<String> {
+    let pad = " ".repeat(indent);
+    let format_elements = |elements: Vec<(String, Value, Type)>| -> Result<String> {
+        let mut lines = vec![];
+        for (prefix, v, element_t) in elements {
+            let formatted = format_value(&v, &element_t, indent + 2)?;
+            lines.push(format!("{}  {}{}", pad, prefix, formatted));
+        }
+        Ok(lines.join("\n"))
+    };
+    Ok(match t {
+        Type::Scalar(st) => format_entry(value.to_u64(st.clone())?, st),
+        Type::Array(_, st) => {
+            format_array(value, t.clone(), st)?.replace('\n', &format!("\n{}", pad))
+        }
+        Type::NamedTuple(header_types) => {
+            let values = value.to_vector()?;
+            let shapes: Vec<Vec<u64>> = header_types
+                .iter()
+                .filter(|(_, column_t)| column_t.is_array())
+                .map(|(_, column_t)| column_t.get_shape())
+                .collect();
+            let is_table = !header_types.is_empty()
+                && shapes.len() == header_types.len()
+                && shapes
+                    .iter()
+                    .all(|shape| shape.len() == 1 && shape == &shapes[0]);
+            if is_table {
+                let mut rows = vec![header_types
+                    .iter()
+                    .map(|(header, _)| header.clone())
+                    .collect::<Vec<String>>()];
+                let mut columns = vec![];
+                for (v, (_, column_t)) in values.iter().zip(header_types.iter()) {
+                    columns.push(format_entries(v, (**column_t).clone())?);
+                }
+                for i in 0..shapes[0][0] as usize {
+                    rows.push(columns.iter().map(|column| column[i].clone()).collect());
+                }
+                rows.iter()
+                    .map(|row| row.join("\t"))
+                    .collect::<Vec<String>>()
+                    .join(&format!("\n{}", pad))
+            } else {
+                let elements = values
+                    .into_iter()
+                    .zip(header_types.iter())
+                    .map(|(v, (header, element_t))| {
+                        (format!("{}: ", header), v, (**element_t).clone())
+                    })
+                    .collect();
+                format!("{{\n{}\n{}}}", format_elements(elements)?, pad)
+            }
+        }
+        Type::Tuple(element_types) => {
+            let elements = value
+                .to_vector()?
+                .into_iter()
+                .zip(element_types.iter())
+                .map(|(v, element_t)| ("".to_owned(), v, (**element_t).clone()))
+                .collect();
+            format!("(\n{}\n{})", format_elements(elements)?, pad)
+        }
+        Type::Vector(_, element_t) => {
+            let elements = value
+                .to_vector()?
+                .into_iter()
+                .map(|v| ("".to_owned(), v, (**element_t).clone()))
+                .collect();
+            format!("[\n{}\n{}]", format_elements(elements)?, pad)
+        }
+    })
+}
+
+/// This binary evaluates a given context locally over the provided inputs using a simple evaluator and prints the result in a human-readable format.
+///
+/// If the context is compiled, the simple evaluator executes the protocol of all the three parties within one process, so the result is the same as of the networked execution.
+/// Secret-shared inputs can be given in the plaintext form; they are secret-shared automatically.
+/// For a secret-shared output, output is revealed if the `reveal_output` boolean binary argument is set to `true`.
+///
+/// Inputs are read from the given files in order:
+/// a JSON file contains a list of serialized typed values (as for `ciphercore_evaluate`), while a CSV file contains one input, which must be a named tuple of one-dimensional arrays with the null column.
+/// Columns of a CSV file are selected, converted and padded according to the type of the corresponding input.
+///
+/// # Arguments
+///
+/// * `context_path` - path to a serialized context
+/// * `inputs_paths` - paths to JSON or CSV files containing inputs
+/// * `reveal_output` - boolean to indicate if output is to be revealed
+/// * `json` - boolean to indicate if the result is to be printed in the JSON format
+///
+/// # Usage
+///
+/// < this_binary > [--reveal-output] [--json] <CONTEXT_PATH> [INPUTS_PATHS]...
+fn main() {
+    // Initialize a logger that collects information about errors and panics within CipherCore.
+    // This information can be accessed via RUST_LOG.
+    env_logger::init();
+    // Execute CipherCore code such that all the internal errors are properly formatted and logged.
+    execute_main(|| -> Result<()> {
+        let args = Args::parse();
+        set_default_prf_backend(get_prf_backend_by_name(&args.prf_backend)?)?;
+        let serialized_context = fs::read_to_string(&args.context_path)?;
+        let context = serde_json::from_str::<Context>(&serialized_context)?;
+        let input_types = get_input_types(&context)?;
+        // Read the inputs in the given order
+        let mut inputs = vec![];
+        for path in &args.inputs_paths {
+            if path.to_lowercase().ends_with(".csv") {
+                let t = input_types.get(inputs.len()).ok_or_else(|| {
+                    runtime_error!("Too many inputs: {} expected", input_types.len())
+                })?;
+                inputs.push(load_csv_input(path, t)?);
+            } else {
+                let json_inputs = fs::read_to_string(path)?;
+                inputs.extend(serde_json::from_str::<Vec<TypedValue>>(&json_inputs)?);
+            }
+        }
+        let result = get_evaluator_result(
+            context,
+            inputs,
+            args.reveal_output,
+            SimpleEvaluator::new(None)?,
+        )?;
+        if args.json {
+            println!("{}", serde_json::to_string(&result)?);
+        } else {
+            println!("{}", format_value(&result.value, &result.t, 0)?);
+        }
+        Ok(())
+    });
+}
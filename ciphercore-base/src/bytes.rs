@@ -4,6 +4,17 @@ use crate::errors::Result;
 
 use std::ops::Not;
 
+// The modulus of 128-bit scalar types is encoded as `Some(0)` and can't be used here (see `get_u64_modulus`)
+fn check_u64_modulus(modulus: Option<u64>) -> Result<()> {
+    if modulus == Some(0) {
+        return Err(runtime_error!(
+            "Arithmetic on u64 entries doesn't support the modulus of 128-bit scalar types"
+        ));
+    }
+    Ok(())
+}
+
+/// `modulus` must be obtained via [get_u64_modulus](crate::data_types::get_u64_modulus).
 pub(super) fn add_u64(val1: u64, val2: u64, modulus: Option<u64>) -> u64 {
     match modulus {
         Some(m) => {
@@ -14,6 +25,7 @@ pub(super) fn add_u64(val1: u64, val2: u64, modulus: Option<u64>) -> u64 {
     }
 }
 
+/// `modulus` must be obtained via [get_u64_modulus](crate::data_types::get_u64_modulus).
 pub(super) fn multiply_u64(val1: u64, val2: u64, modulus: Option<u64>) -> u64 {
     match modulus {
         Some(m) => {
@@ -25,6 +37,7 @@ pub(super) fn multiply_u64(val1: u64, val2: u64, modulus: Option<u64>) -> u64 {
 }
 
 pub fn add_vectors_u64(vec1: &[u64], vec2: &[u64], modulus: Option<u64>) -> Result<Vec<u64>> {
+    check_u64_modulus(modulus)?;
     if vec1.len() != vec2.len() {
         return Err(runtime_error!(
             "Vectors of different lengths can't be summed"
@@ -37,6 +50,7 @@ pub fn add_vectors_u64(vec1: &[u64], vec2: &[u64], modulus: Option<u64>) -> Resu
     Ok(res)
 }
 
+/// `modulus` must be obtained via [get_u64_modulus](crate::data_types::get_u64_modulus).
 pub fn sum_vector_u64(vec: &[u64], modulus: Option<u64>) -> u64 {
     let mut res = 0;
     for a in vec {
@@ -46,6 +60,7 @@ pub fn sum_vector_u64(vec: &[u64], modulus: Option<u64>) -> u64 {
 }
 
 pub fn dot_vectors_u64(vec1: &[u64], vec2: &[u64], modulus: Option<u64>) -> Result<u64> {
+    check_u64_modulus(modulus)?;
     if vec1.len() != vec2.len() {
         return Err(runtime_error!(
             "Vectors of different lengths can't be summed"
@@ -59,6 +74,7 @@ pub fn dot_vectors_u64(vec1: &[u64], vec2: &[u64], modulus: Option<u64>) -> Resu
 }
 
 pub fn subtract_vectors_u64(vec1: &[u64], vec2: &[u64], modulus: Option<u64>) -> Result<Vec<u64>> {
+    check_u64_modulus(modulus)?;
     if vec1.len() != vec2.len() {
         return Err(runtime_error!(
             "Vectors of different lengths can't be subtracted"
@@ -82,6 +98,7 @@ pub fn subtract_vectors_u64(vec1: &[u64], vec2: &[u64], modulus: Option<u64>) ->
     Ok(res)
 }
 pub fn multiply_vectors_u64(vec1: &[u64], vec2: &[u64], modulus: Option<u64>) -> Result<Vec<u64>> {
+    check_u64_modulus(modulus)?;
     if vec1.len() != vec2.len() {
         return Err(runtime_error!(
             "Vectors of different lengths can't be multiplied"
@@ -117,8 +134,10 @@ fn as_u64<T: TryInto<u64> + Not<Output = T> + Copy>(x: T, st: ScalarType) -> Res
             // of the signed type of x
             // e.g. 0..011111111 for INT8 (i8)
             let mask = match st.get_modulus() {
-                Some(_) => (1 << scalar_size_in_bits(st)) - 1,
-                None => u64::MAX,
+                Some(_) if scalar_size_in_bits(st.clone()) < 64 => {
+                    (1 << scalar_size_in_bits(st)) - 1
+                }
+                _ => u64::MAX,
             };
             // flip the negated bits of x by XORing with the mask
             // e.g. 0..001000111^0..011111111 -> 0..010111000
@@ -127,6 +146,24 @@ fn as_u64<T: TryInto<u64> + Not<Output = T> + Copy>(x: T, st: ScalarType) -> Res
     }
 }
 
+/// Converts any integer of standard type to u128, negative integers are sign-extended
+/// to 128 bits.
+fn as_u128<T: TryInto<u64> + Not<Output = T> + Copy>(x: T) -> Result<u128> {
+    match x.try_into() {
+        Ok(ux) => Ok(ux as u128),
+        Err(_) => {
+            // !x = -x - 1 is non-negative, so flipping its bits back in 128 bits yields x
+            let neg_x: u64 = match (!x).try_into() {
+                Ok(m) => m,
+                Err(_) => {
+                    return Err(runtime_error!("The integer of this size is not supported"));
+                }
+            };
+            Ok(!(neg_x as u128))
+        }
+    }
+}
+
 /// Bytes are presented in the little-endian order
 pub fn vec_to_bytes<T: TryInto<u64> + Not<Output = T> + TryInto<u8> + Copy>(
     x: &[T],
@@ -156,6 +193,11 @@ pub fn vec_to_bytes<T: TryInto<u64> + Not<Output = T> + TryInto<u8> + Copy>(
                 x_bytes.push(x_byte);
             }
         }
+        _ if scalar_size_in_bits(st.clone()) == 128 => {
+            for xi in x {
+                x_bytes.extend_from_slice(&as_u128(*xi)?.to_le_bytes());
+            }
+        }
         _ => {
             let byte_length = scalar_size_in_bytes(st.clone()) as usize;
             let x_u64s = vec_to_u64(x, st)?;
@@ -174,6 +216,9 @@ pub fn vec_to_u64<T: TryInto<u64> + Not<Output = T> + Copy>(
     x: &[T],
     st: ScalarType,
 ) -> Result<Vec<u64>> {
+    if scalar_size_in_bits(st.clone()) > 64 {
+        return Err(runtime_error!("Entries of {} don't fit into u64", st));
+    }
     let mut x_u64s = vec![];
     for xi in x {
         x_u64s.push(as_u64(*xi, st.clone())?);
@@ -185,6 +230,12 @@ pub fn vec_to_u64<T: TryInto<u64> + Not<Output = T> + Copy>(
 /// the number of bits in bytes is bigger than the actual number of packed bits
 pub fn vec_from_bytes(x: &[u8], st: ScalarType) -> Result<Vec<u64>> {
     let mut x_u64s = vec![];
+    if scalar_size_in_bits(st.clone()) > 64 {
+        return Err(runtime_error!(
+            "Entries of {} don't fit into u64, use vec_u128_from_bytes",
+            st
+        ));
+    }
     match st {
         BIT => {
             for byte in x {
@@ -211,10 +262,27 @@ pub fn vec_from_bytes(x: &[u8], st: ScalarType) -> Result<Vec<u64>> {
     Ok(x_u64s)
 }
 
+/// Bytes are presented in the little-endian order
+pub fn vec_u128_to_bytes(x: &[u128]) -> Vec<u8> {
+    x.iter().flat_map(|xi| xi.to_le_bytes()).collect()
+}
+
+pub fn vec_u128_from_bytes(x: &[u8]) -> Result<Vec<u128>> {
+    let chunks = x.chunks_exact(16);
+    if !chunks.remainder().is_empty() {
+        return Err(runtime_error!("Incompatible vector and scalar type"));
+    }
+    Ok(chunks
+        .map(|chunk| u128::from_le_bytes(chunk.try_into().unwrap()))
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::data_types::{BIT, INT16, INT32, INT64, INT8, UINT16, UINT32, UINT64, UINT8};
+    use crate::data_types::{
+        BIT, INT128, INT16, INT32, INT64, INT8, UINT128, UINT16, UINT32, UINT64, UINT8,
+    };
 
     #[test]
     fn test_as_u64() {
@@ -477,4 +545,28 @@ mod tests {
         let e = vec_from_bytes(&vec![0u8, 0u8, 0u8], UINT16);
         assert!(e.is_err());
     }
+
+    #[test]
+    fn test_128_bit_bytes() {
+        let bytes = vec_to_bytes(&[-2i64, 3i64], INT128).unwrap();
+        assert_eq!(bytes.len(), 32);
+        assert_eq!(
+            vec_u128_from_bytes(&bytes).unwrap(),
+            vec![-2i128 as u128, 3u128]
+        );
+        assert_eq!(vec_u128_to_bytes(&[-2i128 as u128, 3u128]), bytes);
+        assert_eq!(
+            vec_to_bytes(&[u64::MAX], UINT128).unwrap(),
+            vec_u128_to_bytes(&[u64::MAX as u128])
+        );
+        assert!(vec_from_bytes(&bytes, INT128).is_err());
+        assert!(vec_u128_from_bytes(&bytes[1..]).is_err());
+        assert!(vec_to_u64(&[-2i64], INT128).is_err());
+        // The modulus of 128-bit scalar types can't be used in u64 arithmetic
+        let modulus = UINT128.get_modulus();
+        assert!(add_vectors_u64(&[1], &[2], modulus).is_err());
+        assert!(subtract_vectors_u64(&[1], &[2], modulus).is_err());
+        assert!(multiply_vectors_u64(&[1], &[2], modulus).is_err());
+        assert!(dot_vectors_u64(&[1], &[2], modulus).is_err());
+    }
 }
@@ -1,10 +1,19 @@
 //! Structs and traits necessary to implement custom operations.
 //! A custom operation can be thought of as a polymorphic function, i.e., where the number of inputs and their types can vary.
 //! Two basic examples of custom operations are provided: [Not] and [Or].
+//!
+//! # Plugins
+//!
+//! Custom operations are not limited to this crate.
+//! A downstream crate can implement [CustomOperationBody] for its own structs (e.g. new MPC protocols) annotating the implementations with `#[typetag::serde]`.
+//! Such operations are serialized and deserialized along with the built-in ones and can be used in graphs passed to the compiler and evaluators.
+//! Registering them with [register_custom_operation](crate::register_custom_operation) makes them visible in [get_registered_custom_operations].
 use crate::data_types::{scalar_type, Type, BIT};
 use crate::data_values::Value;
 use crate::errors::Result;
-use crate::graphs::{copy_node_name, create_context, Context, Graph, Node, Operation};
+use crate::graphs::{
+    copy_node_name, copy_node_provenance, create_context, Context, Graph, Node, Operation,
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -127,6 +136,138 @@ pub trait CustomOperationBody: 'static + Debug + DynEqHash + Send + Sync {
     fn get_name(&self) -> String;
 }
 
+#[doc(hidden)]
+pub use typetag::inventory;
+
+/// Kind of an argument expected by a custom operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ArgumentKind {
+    /// Array or scalar of any scalar type.
+    Array,
+    /// Binary array or scalar, e.g. bits of integers.
+    Binary,
+    /// Named tuple containing a database, whose columns are arrays with the same first dimension.
+    NamedTuple,
+    /// Value of an MPC protocol, which is either public or a tuple of 3 shares of a private value.
+    Shared,
+    /// Tuple of PRF keys of the parties used by MPC protocols.
+    PrfKeys,
+    /// Value of any type.
+    Any,
+}
+
+/// Description of an implementation of [CustomOperationBody] registered with [register_custom_operation](crate::register_custom_operation).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CustomOperationInfo {
+    /// Name of the implementing struct, which is used as a type tag during serialization.
+    pub type_name: &'static str,
+    /// Sequences of argument kinds accepted by the operation.
+    pub signatures: &'static [&'static [ArgumentKind]],
+    /// Whether the last argument of every signature can be repeated any number of times.
+    pub variadic: bool,
+}
+
+inventory::collect!(CustomOperationInfo);
+
+impl CustomOperationInfo {
+    /// Checks whether the operation accepts a given number of arguments.
+    pub fn accepts_arguments_number(&self, number: usize) -> bool {
+        self.signatures.iter().any(|signature| {
+            signature.len() == number
+                || (self.variadic && !signature.is_empty() && number >= signature.len())
+        })
+    }
+}
+
+/// Registers an implementation of [CustomOperationBody] along with the kinds of its arguments.
+///
+/// The first argument is the name of the implementing struct followed by the accepted sequences of argument kinds (variants of [ArgumentKind]).
+/// If the keyword `variadic` precedes the sequences, the last argument of every sequence can be repeated.
+/// The macro must be invoked outside of any function body, e.g. next to the implementation.
+/// Registered implementations are returned by [get_registered_custom_operations].
+///
+/// # Example
+///
+/// A downstream crate depending on `ciphercore-base`, `serde` and `typetag` can ship its own operation as follows.
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// # use ciphercore_base::data_types::Type;
+/// # use ciphercore_base::graphs::{Context, Graph};
+/// # use ciphercore_base::custom_ops::{get_custom_operation_info, CustomOperationBody};
+/// # use ciphercore_base::errors::Result;
+/// # use ciphercore_base::{register_custom_operation, runtime_error};
+///
+/// #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+/// pub struct Identity {}
+///
+/// #[typetag::serde]
+/// impl CustomOperationBody for Identity {
+///    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+///        if arguments_types.len() != 1 {
+///           return Err(runtime_error!("Invalid number of arguments for Identity"));
+///        }
+///        let g = context.create_graph()?;
+///        g.input(arguments_types[0].clone())?.set_as_output()?;
+///        g.finalize()?;
+///        Ok(g)
+///    }
+///    fn get_name(&self) -> String {
+///        "Identity".to_owned()
+///    }
+/// }
+///
+/// register_custom_operation!(Identity, [Any]);
+///
+/// let info = get_custom_operation_info("Identity").unwrap().unwrap();
+/// assert!(info.accepts_arguments_number(1));
+/// assert!(!info.accepts_arguments_number(2));
+/// ```
+#[macro_export]
+macro_rules! register_custom_operation {
+    ($op:ident, variadic $([$($kind:ident),*]),+ $(,)?) => {
+        $crate::register_custom_operation!(@submit $op, true, $([$($kind),*]),+);
+    };
+    ($op:ident, $([$($kind:ident),*]),+ $(,)?) => {
+        $crate::register_custom_operation!(@submit $op, false, $([$($kind),*]),+);
+    };
+    (@submit $op:ident, $variadic:expr, $([$($kind:ident),*]),+) => {
+        $crate::custom_ops::inventory::submit! {
+            $crate::custom_ops::CustomOperationInfo {
+                type_name: stringify!($op),
+                signatures: &[$(&[$($crate::custom_ops::ArgumentKind::$kind),*]),+],
+                variadic: $variadic,
+            }
+        }
+    };
+}
+
+/// Returns descriptions of all the implementations of [CustomOperationBody] registered with [register_custom_operation](crate::register_custom_operation) sorted by their type names.
+///
+/// The list includes the built-in custom operations and the operations registered by other crates linked into the binary.
+pub fn get_registered_custom_operations() -> Result<Vec<CustomOperationInfo>> {
+    let mut operations: Vec<CustomOperationInfo> = inventory::iter::<CustomOperationInfo>
+        .into_iter()
+        .cloned()
+        .collect();
+    operations.sort_by_key(|info| info.type_name);
+    for pair in operations.windows(2) {
+        if pair[0].type_name == pair[1].type_name {
+            return Err(runtime_error!(
+                "Custom operation {} is registered more than once",
+                pair[0].type_name
+            ));
+        }
+    }
+    Ok(operations)
+}
+
+/// Returns the description of a registered implementation of [CustomOperationBody] with a given type name or `None` if there is no such implementation.
+pub fn get_custom_operation_info(type_name: &str) -> Result<Option<CustomOperationInfo>> {
+    Ok(get_registered_custom_operations()?
+        .into_iter()
+        .find(|info| info.type_name == type_name))
+}
+
 /// A structure that stores a pointer to a custom operation.
 ///
 /// A custom operation can be thought of as a polymorphic function, i.e., where the number of inputs and their types can vary.
@@ -211,11 +352,28 @@ impl CustomOperation {
     pub fn get_name(&self) -> String {
         self.body.get_name()
     }
+
+    /// Returns the name of the struct implementing the underlying custom operation, e.g. `Not`.
+    ///
+    /// Unlike [CustomOperation::get_name], it doesn't depend on the parameters of the operation.
+    pub fn get_type_name(&self) -> String {
+        self.body.typetag_name().to_owned()
+    }
 }
 
 impl CustomOperation {
     #[doc(hidden)]
     pub fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        let _span = tracing::debug_span!(
+            "instantiate_custom_operation",
+            operation = %self.get_name(),
+            arguments_types = %arguments_types
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .entered();
         self.body.instantiate(context, arguments_types)
     }
 }
@@ -299,6 +457,8 @@ impl CustomOperationBody for Not {
     }
 }
 
+register_custom_operation!(Not, [Binary]);
+
 /// A structure that defines the custom operation Or that is equivalent to the binary Or applied elementwise.
 ///
 /// This operation accepts only binary arrays or scalars as input.
@@ -355,6 +515,8 @@ impl CustomOperationBody for Or {
     }
 }
 
+register_custom_operation!(Or, [Binary, Binary]);
+
 #[doc(hidden)]
 /// Data structure for storing maps between two contexts.
 /// Can be used conveniently to glue one context into another.
@@ -538,7 +700,8 @@ fn process_instantiation(
     let fake_context = create_context()?;
     let graph = instantiation
         .op
-        .instantiate(fake_context.clone(), instantiation.arguments_types.clone())?;
+        .instantiate(fake_context.clone(), instantiation.arguments_types.clone())
+        .map_err(|e| e.with_custom_operation(&instantiation.get_name()))?;
     // `instantiate()` may potentially create some auxiliary graphs, which we now need to process
     // TODO: add a test that check that this is properly done
     for fake_graph in fake_context.get_graphs() {
@@ -594,35 +757,44 @@ pub fn run_instantiation_pass(context: Context) -> Result<MappedContext> {
     for graph in context.get_graphs() {
         for node in graph.get_nodes() {
             if let Operation::Custom(_) = node.get_operation() {
-                needed_instantiations.push(Instantiation::create_from_node(node)?);
+                needed_instantiations.push((Instantiation::create_from_node(node.clone())?, node));
             }
         }
     }
     let mut instantiations_graph_mapping = InstantiationsGraphMapping::default();
     let mut instantiations_graph = InstantiationsGraph::default();
-    for instantiation in needed_instantiations {
+    for (instantiation, node) in needed_instantiations {
         let (_, already_existed) = get_instantiations_graph_node(
             &instantiation,
             &mut instantiations_graph_mapping,
             &mut instantiations_graph,
         );
         if !already_existed {
+            // Errors are supplemented with the node whose custom operation can't be instantiated
             process_instantiation(
                 &instantiation,
                 &mut instantiations_graph_mapping,
                 &mut instantiations_graph,
-            )?;
+            )
+            .map_err(|e| e.with_node(&node))?;
         }
     }
     /* =============================== */
     let result_context = create_context()?;
     // Glues a given context into the final one
+    // Nodes of the main graph of an instantiation are attributed to the instantiated custom operation.
     let glue_context = |glued_instantiations_cache: &HashMap<Instantiation, Graph>,
-                        context_to_glue: Context|
+                        context_to_glue: Context,
+                        custom_op: Option<&CustomOperation>|
      -> Result<ContextMappings> {
         let mut mapping = ContextMappings::default();
+        let main_graph_to_glue = context_to_glue.get_main_graph()?;
         for graph_to_glue in context_to_glue.get_graphs() {
             let glued_graph = result_context.create_graph()?;
+            let is_instantiated_graph = custom_op.is_some() && graph_to_glue == main_graph_to_glue;
+            if let (Some(op), true) = (custom_op, is_instantiated_graph) {
+                glued_graph.push_region(&op.get_type_name())?;
+            }
             for annotation in graph_to_glue.get_annotations()? {
                 glued_graph.add_annotation(annotation)?;
             }
@@ -660,6 +832,7 @@ pub fn run_instantiation_pass(context: Context) -> Result<MappedContext> {
                     }
                 };
                 copy_node_name(node.clone(), new_node.clone())?;
+                copy_node_provenance(node.clone(), new_node.clone())?;
                 let node_annotations = context_to_glue.get_node_annotations(node.clone())?;
                 if !node_annotations.is_empty() {
                     for node_annotation in node_annotations {
@@ -668,6 +841,9 @@ pub fn run_instantiation_pass(context: Context) -> Result<MappedContext> {
                 }
                 mapping.insert_node(node, new_node);
             }
+            if is_instantiated_graph {
+                glued_graph.pop_region()?;
+            }
             glued_graph.set_output_node(mapping.get_node(graph_to_glue.get_output_node()?))?;
             glued_graph.finalize()?;
         }
@@ -689,14 +865,18 @@ pub fn run_instantiation_pass(context: Context) -> Result<MappedContext> {
             .instantiate(fake_context.clone(), instantiation.arguments_types.clone())?
             .set_as_main()?;
         fake_context.finalize()?;
-        let mapping = glue_context(&glued_instantiations_cache, fake_context)?;
+        let mapping = glue_context(
+            &glued_instantiations_cache,
+            fake_context,
+            Some(&instantiation.op),
+        )?;
         let mapped_graph = mapping.get_graph(g);
         mapped_graph.set_name(&instantiation.get_name())?;
         glued_instantiations_cache.insert(instantiation.clone(), mapped_graph);
     }
     // Glue the final context.
     let mut result = MappedContext::new(result_context.clone());
-    result.mappings = glue_context(&glued_instantiations_cache, context.clone())?;
+    result.mappings = glue_context(&glued_instantiations_cache, context.clone(), None)?;
     result_context.set_main_graph(result.mappings.get_graph(context.get_main_graph()?))?;
     result_context.finalize()?;
     Ok(result)
@@ -710,7 +890,11 @@ mod tests {
     use crate::data_types::array_type;
     use crate::data_values::Value;
     use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::evaluators::Evaluator;
     use crate::graphs::{contexts_deep_equal, NodeAnnotation};
+    use crate::inline::inline_ops::{inline_operations, InlineConfig, InlineMode};
+    use crate::optimizer::pass_manager::{InstantiationPass, PassManager};
 
     fn get_hash(custom_op: &CustomOperation) -> u64 {
         let mut h = DefaultHasher::new();
@@ -754,6 +938,47 @@ mod tests {
         .is_err());
     }
 
+    #[test]
+    fn test_registered_custom_operations() {
+        || -> Result<()> {
+            let operations = get_registered_custom_operations()?;
+            let names: Vec<&str> = operations.iter().map(|info| info.type_name).collect();
+            for name in [
+                "Not",
+                "Or",
+                "BinaryAdd",
+                "MultiplyMPC",
+                "SetIntersectionMPC",
+            ] {
+                assert!(names.contains(&name));
+            }
+            let mut sorted_names = names.clone();
+            sorted_names.sort();
+            assert_eq!(names, sorted_names);
+
+            let not_info = get_custom_operation_info("Not")?.unwrap();
+            assert_eq!(not_info.signatures, &[&[ArgumentKind::Binary][..]]);
+            assert!(not_info.accepts_arguments_number(1));
+            assert!(!not_info.accepts_arguments_number(2));
+            let multiply_info = get_custom_operation_info("MultiplyMPC")?.unwrap();
+            assert!(multiply_info.accepts_arguments_number(2));
+            assert!(multiply_info.accepts_arguments_number(3));
+            assert!(!multiply_info.accepts_arguments_number(4));
+            assert!(get_custom_operation_info("NonExistent")?.is_none());
+
+            let variadic_info = CustomOperationInfo {
+                type_name: "Variadic",
+                signatures: &[&[ArgumentKind::PrfKeys, ArgumentKind::Shared]],
+                variadic: true,
+            };
+            assert!(!variadic_info.accepts_arguments_number(1));
+            assert!(variadic_info.accepts_arguments_number(2));
+            assert!(variadic_info.accepts_arguments_number(5));
+            Ok(())
+        }()
+        .unwrap();
+    }
+
     #[test]
     fn test_not() {
         || -> Result<()> {
@@ -910,6 +1135,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_provenance() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![4], BIT);
+            let x = g.input(t.clone())?;
+            let y = g.input(t)?;
+            g.push_region("user")?;
+            g.custom_op(CustomOperation::new(Or {}), vec![x, y])?
+                .set_as_output()?;
+            g.pop_region()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+
+            let instantiated_c = run_instantiation_pass(c)?.context;
+            let or_g = instantiated_c.retrieve_graph("__Or::<b[4], b[4]>")?;
+            for node in or_g.get_nodes() {
+                assert_eq!(node.get_provenance()?, vec!["Or"]);
+            }
+            let main_g = instantiated_c.get_main_graph()?;
+            assert_eq!(main_g.get_output_node()?.get_provenance()?, vec!["user"]);
+
+            let inlined_c = inline_operations(
+                instantiated_c,
+                InlineConfig {
+                    default_mode: InlineMode::Simple,
+                    ..Default::default()
+                },
+            )?;
+            let inlined_g = inlined_c.get_main_graph()?;
+            let multiplications: Vec<Node> = inlined_g
+                .get_nodes_in_region(&["Or"])?
+                .into_iter()
+                .filter(|node| node.get_operation() == Operation::Multiply)
+                .collect();
+            assert_eq!(multiplications.len(), 1);
+            assert_eq!(multiplications[0].get_provenance()?, vec!["user", "Or"]);
+            let not_nodes = inlined_g.get_nodes_in_region(&["Or", "Not"])?;
+            assert!(!not_nodes.is_empty());
+            for node in not_nodes {
+                assert_eq!(node.get_provenance()?, vec!["user", "Or", "Not"]);
+            }
+            for node in inlined_g.get_nodes() {
+                if let Operation::Input(_) = node.get_operation() {
+                    assert!(node.get_provenance()?.is_empty());
+                }
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
     #[test]
     fn test_instantiation_pass() {
         || -> Result<()> {
@@ -1150,4 +1428,99 @@ mod tests {
         }()
         .unwrap();
     }
+
+    // Subscriber recording the names and the fields of all the created spans
+    #[derive(Default)]
+    struct SpanRecorder {
+        spans: Arc<std::sync::Mutex<Vec<(String, String)>>>,
+    }
+
+    struct FieldsVisitor<'a>(&'a mut String);
+
+    impl tracing::field::Visit for FieldsVisitor<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn Debug) {
+            write!(self.0, "{}={:?};", field.name(), value).unwrap();
+        }
+    }
+
+    impl tracing::Subscriber for SpanRecorder {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut fields = String::new();
+            attributes.record(&mut FieldsVisitor(&mut fields));
+            let mut spans = self.spans.lock().unwrap();
+            spans.push((attributes.metadata().name().to_owned(), fields));
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+            let mut spans = self.spans.lock().unwrap();
+            values.record(&mut FieldsVisitor(
+                &mut spans[span.into_u64() as usize - 1].1,
+            ));
+        }
+
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+        fn event(&self, _event: &tracing::Event<'_>) {}
+
+        fn enter(&self, _span: &tracing::span::Id) {}
+
+        fn exit(&self, _span: &tracing::span::Id) {}
+    }
+
+    #[test]
+    fn test_tracing_spans() {
+        let recorder = SpanRecorder::default();
+        let spans = recorder.spans.clone();
+        let t = array_type(vec![2, 3], BIT);
+        tracing::subscriber::with_default(recorder, || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let i = g.input(t.clone())?;
+            g.custom_op(CustomOperation::new(Not {}), vec![i])?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let instantiated_c = PassManager::new().add_pass(InstantiationPass).run(c)?;
+            let mut evaluator = SimpleEvaluator::new(None)?;
+            evaluator.evaluate_batch(instantiated_c, vec![vec![Value::zero_of_type(t.clone())]])?;
+            Ok(())
+        })
+        .unwrap();
+        let spans = spans.lock().unwrap();
+        let get_fields = |name: &str| -> Vec<String> {
+            spans
+                .iter()
+                .filter(|(span_name, _)| span_name == name)
+                .map(|(_, fields)| fields.clone())
+                .collect()
+        };
+        assert_eq!(
+            get_fields("compiler_pass"),
+            vec!["pass=Instantiation;nodes_before=2;"]
+        );
+        let type_fields = format!("output_type={};shape=[2, 3];", t);
+        assert!(get_fields("instantiate_custom_operation")
+            .iter()
+            .all(|fields| *fields == format!("operation=Not;arguments_types={};", t)));
+        assert_eq!(get_fields("evaluate_batch"), vec!["batch_size=1;"]);
+        assert_eq!(
+            get_fields("graph_call"),
+            vec![format!(
+                "operation=Call;graph=__Not::<{}>;iteration=0;{}",
+                t, type_fields
+            )]
+        );
+        // The main graph and the instantiated graph
+        assert_eq!(get_fields("evaluate_graph").len(), 2);
+        // The nodes of the instantiated graph, i.e. Constant and Add
+        let nodes_fields = get_fields("evaluate_node");
+        assert_eq!(nodes_fields.len(), 2);
+        assert!(nodes_fields[1].starts_with("operation=Add;"));
+        assert!(nodes_fields[1].ends_with(&type_fields));
+    }
 }
@@ -67,7 +67,9 @@ pub struct ScalarType {
     pub signed: bool,
 
     /// Provides an upper-bound on the scalar values.
-    /// `None` value indicates a modulo 2<sup>64</sup> scalar value.
+    /// `None` value indicates a modulo 2<sup>64</sup> scalar value,
+    /// `Some(0)` indicates a modulo 2<sup>128</sup> scalar value, as 2<sup>128</sup> doesn't fit into `u64`;
+    /// code doing arithmetic modulo `modulus` must obtain it via [get_u64_modulus], which rejects this value.
     pub modulus: Option<u64>,
 }
 
@@ -85,7 +87,9 @@ pub struct ScalarType {
 ///
 /// For obtaining 64-bit scalars, the input `modulus` value given should be `None`.
 ///
-/// Supported scalars are [BIT], [UINT8], [INT8], [UINT16], [INT16], [UINT32], [INT32], [UINT64], and [INT64]
+/// For obtaining 128-bit scalars, the input `modulus` value given should be `Some(0)`.
+///
+/// Supported scalars are [BIT], [UINT8], [INT8], [UINT16], [INT16], [UINT32], [INT32], [UINT64], [INT64], [UINT128] and [INT128]
 ///
 /// # Arguments
 ///
@@ -119,7 +123,7 @@ pub(super) const fn create_scalar_type(signed: bool, modulus: Option<u64>) -> Sc
 impl ScalarType {
     /// Tests whether a scalar type is supported.
     ///
-    /// Supported scalar types: [BIT], [UINT8], [INT8], [UINT16], [INT16], [UINT32], [INT32], [UINT64], and [INT64]
+    /// Supported scalar types: [BIT], [UINT8], [INT8], [UINT16], [INT16], [UINT32], [INT32], [UINT64], [INT64], [UINT128] and [INT128]
     ///
     /// # Returns
     ///
@@ -135,6 +139,9 @@ impl ScalarType {
     /// assert!(!ScalarType{modulus: Some(3), signed: true}.is_valid());
     /// ```
     pub fn is_valid(&self) -> bool {
+        if self.modulus == MODULUS_128_BIT {
+            return true;
+        }
         if let Some(m) = self.modulus {
             //Currently our evaluator only supports bit_size = 1,8,16,32,64
             let supported_modulus = vec![TWO, TWO.pow(8), TWO.pow(16), TWO.pow(32)];
@@ -168,6 +175,8 @@ impl ScalarType {
     /// Returns scalar's modulus value, which defines the range of integers.
     ///
     /// If `modulus` equals `None`, then `modulus` value should be considered as 2<sup>64<sup/>.
+    /// If `modulus` equals `Some(0)`, then `modulus` value should be considered as 2<sup>128<sup/> ([UINT128] and [INT128]).
+    /// Use [get_u64_modulus] to get a modulus that can be used in arithmetic on `u64` entries.
     ///
     /// # Returns
     ///
@@ -201,6 +210,9 @@ impl ScalarType {
 
 const TWO: u64 = 2;
 
+// Modulus 2^128 of 128-bit scalar types, which doesn't fit into u64
+const MODULUS_128_BIT: Option<u64> = Some(0);
+
 /// Scalar type corresponding to bits 0 or 1.
 ///
 /// BIT scalar type corresponds to either 0 or 1 bit.
@@ -349,6 +361,38 @@ pub const UINT64: ScalarType = create_scalar_type(false, None);
 /// ```
 pub const INT64: ScalarType = create_scalar_type(true, None);
 
+/// Scalar type corresponding to unsigned 128-bit integers.
+///
+/// UINT128 corresponds to integers from 0 to 2<sup>128</sup>-1, both inclusive.
+/// It is meant for accumulators that would overflow [UINT64];
+/// only arithmetic (Add, Subtract, Multiply, Sum) and data movement not touching
+/// individual entries are supported for it.
+///
+/// This scalar type has field `signed` set to `false` and `modulus` value set to `Some(0)`.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::data_types::{UINT128, ScalarType};
+/// assert_eq!(UINT128, ScalarType{ signed: false, modulus: Some(0)});
+/// ```
+pub const UINT128: ScalarType = create_scalar_type(false, MODULUS_128_BIT);
+
+/// Scalar type corresponding to signed 128-bit integers.
+///
+/// INT128 corresponds to integers from -2<sup>127</sup> to 2<sup>127</sup>-1, both inclusive.
+/// The same restrictions as for [UINT128] apply.
+///
+/// This scalar type has field `signed` set to `true` and `modulus` value set to `Some(0)`.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::data_types::{INT128, ScalarType};
+/// assert_eq!(INT128, ScalarType{ signed: true, modulus: Some(0)});
+/// ```
+pub const INT128: ScalarType = create_scalar_type(true, MODULUS_128_BIT);
+
 /// Vector of dimension lengths for each axis of an array.
 ///
 /// ArrayShape type could be used for array oriented graph operations such as [Sum](crate::graphs::Operation::Sum), [PermuteAxes](crate::graphs::Operation::PermuteAxes), [Get](crate::graphs::Operation::Get), [Stack](crate::graphs::Operation::Get) etc.
@@ -387,7 +431,7 @@ pub type ArrayShape = Vec<u64>;
 pub enum Type {
     /// Each scalar corresponds to a signed or an unsigned number modulo `m`, where `m` = {2, 2<sup>8</sup>, 2<sup>16</sup>, 2<sup>32</sup>, 2<sup>64</sup>}.
     ///
-    /// Scalar types supported by CipherCore are provided as parameters. These scalar types could be [BIT], [UINT8], [INT8], [UINT16], [INT16], [UINT32], [INT32], [UINT64] or [INT64].
+    /// Scalar types supported by CipherCore are provided as parameters. These scalar types could be [BIT], [UINT8], [INT8], [UINT16], [INT16], [UINT32], [INT32], [UINT64], [INT64], [UINT128] or [INT128].
     ///
     /// # Example
     ///
@@ -978,6 +1022,48 @@ pub fn named_tuple_type(v: Vec<(String, Type)>) -> Type {
     Type::NamedTuple(vp)
 }
 
+/// Number of bytes storing the length of every string of a [string column](string_column_type).
+pub const STRING_LENGTH_SIZE: u64 = 2;
+
+/// Returns the type of a column containing strings of fixed maximal length.
+///
+/// Such a column is a [UINT8] array of shape `[num_entries, max_length + STRING_LENGTH_SIZE]`.
+/// Every row contains the UTF-8 bytes of a string padded with zeros up to `max_length` bytes followed by the little-endian length of the string.
+/// Storing the length distinguishes strings that differ only in trailing zero bytes, so string columns can be used as keys of [set intersection](crate::graphs::Graph::set_intersection).
+///
+/// Values of string columns can be created by [Value::from_strings](crate::data_values::Value::from_strings).
+///
+/// Returns a runtime error if `max_length` is zero or doesn't fit into [STRING_LENGTH_SIZE] bytes.
+///
+/// # Arguments
+///
+/// * `num_entries` - number of strings in the column
+/// * `max_length` - maximal length of a string in bytes
+///
+/// # Returns
+///
+/// Type of the string column
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::data_types::{string_column_type, array_type, UINT8};
+/// let t = string_column_type(10, 30).unwrap();
+/// assert_eq!(t, array_type(vec![10, 32], UINT8));
+/// ```
+pub fn string_column_type(num_entries: u64, max_length: u64) -> Result<Type> {
+    if max_length == 0 || max_length >= 1 << (8 * STRING_LENGTH_SIZE) {
+        return Err(runtime_error!(
+            "Maximal string length must be between 1 and {}",
+            (1u64 << (8 * STRING_LENGTH_SIZE)) - 1
+        ));
+    }
+    Ok(array_type(
+        vec![num_entries, max_length + STRING_LENGTH_SIZE],
+        UINT8,
+    ))
+}
+
 fn form_array_shape_str(array_shape: ArrayShape) -> String {
     let mut array_shape_str = String::from("");
     array_shape_str.push('[');
@@ -1061,6 +1147,8 @@ impl FromStr for ScalarType {
             "i32" => Ok(INT32),
             "u64" => Ok(UINT64),
             "i64" => Ok(INT64),
+            "u128" => Ok(UINT128),
+            "i128" => Ok(INT128),
             _ => Err(runtime_error!("Unknown scalar type")),
         }
     }
@@ -1110,6 +1198,9 @@ impl fmt::Display for Type {
 /// ```
 pub fn scalar_size_in_bits(t: ScalarType) -> u64 {
     let modulus = t.get_modulus();
+    if modulus == MODULUS_128_BIT {
+        return 128;
+    }
     match modulus {
         Some(m) => {
             let mut sz = 0;
@@ -1128,6 +1219,38 @@ pub fn scalar_size_in_bits(t: ScalarType) -> u64 {
     }
 }
 
+/// Returns the modulus of a scalar type whose entries are processed as `u64` words.
+///
+/// In contrast to [ScalarType::get_modulus], the result can be safely used in arithmetic modulo `modulus`:
+/// `None` means 2<sup>64</sup>, and 128-bit scalar types ([UINT128] and [INT128]), whose modulus doesn't fit into `u64`, result in an error.
+///
+/// # Arguments
+///
+/// `t` - scalar type
+///
+/// # Returns
+///
+/// Modulus of the scalar type or `None` for 64-bit scalar types
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::data_types::{get_u64_modulus, INT128, UINT8, UINT64};
+/// assert_eq!(get_u64_modulus(&UINT8).unwrap(), Some(256));
+/// assert_eq!(get_u64_modulus(&UINT64).unwrap(), None);
+/// assert!(get_u64_modulus(&INT128).is_err());
+/// ```
+pub fn get_u64_modulus(t: &ScalarType) -> Result<Option<u64>> {
+    let modulus = t.get_modulus();
+    if modulus == MODULUS_128_BIT {
+        return Err(runtime_error!(
+            "Entries of {} can't be processed modulo a u64 modulus",
+            t
+        ));
+    }
+    Ok(modulus)
+}
+
 pub(crate) fn scalar_size_in_bytes(t: ScalarType) -> u64 {
     (scalar_size_in_bits(t) + 7) / 8
 }
@@ -1446,6 +1569,12 @@ mod tests {
         assert_eq!(format!("{}", a), "u64");
         assert_eq!(format!("{}", b), "b");
         assert_eq!(format!("{}", c), "i32");
+        assert_eq!(format!("{}", INT128), "i128");
+        assert_eq!("u128".parse::<ScalarType>().unwrap(), UINT128);
+        assert!(UINT128.is_valid());
+        assert_eq!(scalar_size_in_bytes(INT128), 16);
+        assert!(get_u64_modulus(&INT128).is_err());
+        assert_eq!(get_u64_modulus(&UINT32).unwrap(), Some(1 << 32));
     }
 
     #[test]
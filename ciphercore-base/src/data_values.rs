@@ -10,8 +10,11 @@ use std::sync::Arc;
 
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::bytes::{vec_from_bytes, vec_to_bytes};
-use crate::data_types::{array_type, get_size_in_bits, get_types_vector, ScalarType, Type, BIT};
+use crate::bytes::{vec_from_bytes, vec_to_bytes, vec_u128_from_bytes, vec_u128_to_bytes};
+use crate::data_types::{
+    array_type, get_size_in_bits, get_types_vector, string_column_type, ScalarType, Type, BIT,
+    INT16, INT32, INT64, INT8, STRING_LENGTH_SIZE, UINT16, UINT32, UINT64, UINT8,
+};
 use crate::errors::Result;
 
 use crate::version::{VersionedData, DATA_VERSION};
@@ -275,6 +278,16 @@ impl Value {
     }
 }
 
+/// Returns the number of bytes in a row of a string column of a given type.
+fn get_string_row_length(t: Type) -> Result<usize> {
+    if let Type::Array(shape, st) = t {
+        if st == UINT8 && shape.len() == 2 && shape[1] > STRING_LENGTH_SIZE {
+            return Ok(shape[1] as usize);
+        }
+    }
+    Err(runtime_error!("Not a string column type"))
+}
+
 impl Value {
     /// Constructs a value from a given bit or integer scalar.
     ///
@@ -335,6 +348,35 @@ impl Value {
         Ok(Value::from_bytes(vec_to_bytes(x, st)?))
     }
 
+    /// Constructs a value from a flattened array of 128-bit integers.
+    ///
+    /// Signed entries should be given in the two's complement form (e.g., `-1i128 as u128`).
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - array to be converted to a value
+    /// * `st` - 128-bit scalar type corresponding to the entries of `x`
+    ///
+    /// # Returns
+    ///
+    /// New value constructed from `x`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use ciphercore_base::data_values::Value;
+    /// # use ciphercore_base::data_types::{array_type, INT128};
+    /// let v = Value::from_flattened_array_u128(&[-1i128 as u128, 1 << 100], INT128).unwrap();
+    /// let a = v.to_flattened_array_i128(array_type(vec![2], INT128)).unwrap();
+    /// assert_eq!(a, vec![-1, 1 << 100]);
+    /// ```
+    pub fn from_flattened_array_u128(x: &[u128], st: ScalarType) -> Result<Value> {
+        if st.size_in_bits() != 128 {
+            return Err(runtime_error!("128-bit scalar type expected"));
+        }
+        Ok(Value::from_bytes(vec_u128_to_bytes(x)))
+    }
+
     /// Constructs a value from a multi-dimensional bit or integer array.
     ///
     /// # Arguments
@@ -369,6 +411,114 @@ impl Value {
         }
     }
 
+    /// Constructs a value of a given array type from a multi-dimensional array, checking that the shape of the array coincides with the shape of the type.
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - array to be converted to a value, can have entries of any standard integer type
+    /// * `t` - array type of the result
+    ///
+    /// # Returns
+    ///
+    /// New value constructed from `a`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use ciphercore_base::data_values::Value;
+    /// # use ciphercore_base::data_types::{array_type, INT32};
+    /// # use ndarray::array;
+    /// let a = array![[1, 2, 3], [4, 5, 6]].into_dyn();
+    /// assert!(Value::from_ndarray_with_type(a.clone(), array_type(vec![2, 3], INT32)).is_ok());
+    /// assert!(Value::from_ndarray_with_type(a, array_type(vec![3, 2], INT32)).is_err());
+    /// ```
+    pub fn from_ndarray_with_type<T: TryInto<u64> + Not<Output = T> + TryInto<u8> + Copy>(
+        a: ndarray::ArrayD<T>,
+        t: Type,
+    ) -> Result<Value> {
+        let shape = match &t {
+            Type::Array(shape, _) => shape,
+            _ => return Err(runtime_error!("Array type expected, got {}", t)),
+        };
+        if a.shape()
+            .iter()
+            .map(|x| *x as u64)
+            .ne(shape.iter().copied())
+        {
+            return Err(runtime_error!(
+                "Shape {:?} of the ndarray doesn't match type {}",
+                a.shape(),
+                t
+            ));
+        }
+        Value::from_ndarray(a, t.get_scalar_type())
+    }
+
+    /// Runs a given closure on a multi-dimensional view of the entries of an array or scalar value of a given type.
+    ///
+    /// The view borrows the bytes of the value without copying them if they are suitably aligned for `T` (as buffers allocated by the global allocator are on little-endian platforms); otherwise, the entries are copied.
+    /// The type `T` must correspond to the scalar type of `t` (e.g. `i32` to [INT32]).
+    /// Binary entries are packed into bytes, so they can't be viewed; use [ToNdarray] instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `t` - array or scalar type used to interpret `self`
+    /// * `f` - closure taking the view of the entries of `self`
+    ///
+    /// # Returns
+    ///
+    /// Return value of `f`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use ciphercore_base::data_values::Value;
+    /// # use ciphercore_base::data_types::{array_type, INT32};
+    /// # use ndarray::array;
+    /// let v = Value::from_ndarray(array![[-1, 2], [3, -4]].into_dyn(), INT32).unwrap();
+    /// let sum = v
+    ///     .with_ndarray_view(array_type(vec![2, 2], INT32), |view: ndarray::ArrayViewD<i32>| {
+    ///         assert_eq!(view[[1, 1]], -4);
+    ///         Ok(view.sum())
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(sum, 0);
+    /// ```
+    pub fn with_ndarray_view<T, F, R>(&self, t: Type, f: F) -> Result<R>
+    where
+        T: NdarrayViewElement,
+        F: FnOnce(ndarray::ArrayViewD<T>) -> Result<R>,
+    {
+        let (shape, st) = match &t {
+            Type::Scalar(st) => (vec![], st.clone()),
+            Type::Array(shape, st) => (shape.iter().map(|x| *x as usize).collect(), st.clone()),
+            _ => return Err(runtime_error!("Array or scalar type expected, got {}", t)),
+        };
+        if st != T::SCALAR_TYPE {
+            return Err(runtime_error!(
+                "Entries of scalar type {} can't be viewed as {}",
+                st,
+                T::SCALAR_TYPE
+            ));
+        }
+        if !self.check_type(t.clone())? {
+            return Err(runtime_error!("Value doesn't match type {}", t));
+        }
+        self.access_bytes(|bytes| {
+            // SAFETY: every bit pattern is a valid value of a primitive integer type
+            let (prefix, entries, suffix) = unsafe { bytes.align_to::<T>() };
+            if cfg!(target_endian = "little") && prefix.is_empty() && suffix.is_empty() {
+                f(ndarray::ArrayViewD::from_shape(shape, entries)?)
+            } else {
+                let entries: Vec<T> = bytes
+                    .chunks_exact(std::mem::size_of::<T>())
+                    .map(T::from_le_bytes)
+                    .collect();
+                f(ndarray::ArrayViewD::from_shape(shape, &entries)?)
+            }
+        })
+    }
+
     /// Converts `self` to a scalar if it is a byte vector, then casts the result to `u8`.
     ///
     /// # Arguments
@@ -832,6 +982,150 @@ impl Value {
             .collect())
     }
 
+    /// Converts `self` to a flattened array of 128-bit integers if it is a byte vector.
+    ///
+    /// # Arguments
+    ///
+    /// `t` - array type with a 128-bit scalar type used to interpret `self`
+    ///
+    /// # Result
+    ///
+    /// Resulting flattened array
+    pub fn to_flattened_array_u128(&self, t: Type) -> Result<Vec<u128>> {
+        if !t.is_array() || t.get_scalar_type().size_in_bits() != 128 {
+            return Err(runtime_error!(
+                "Trying to extract array from a value of a wrong type"
+            ));
+        }
+        if !self.check_type(t)? {
+            return Err(runtime_error!("Type and value mismatch"));
+        }
+        self.access_bytes(vec_u128_from_bytes)
+    }
+
+    /// Converts `self` to a flattened array of 128-bit integers if it is a byte vector,
+    /// then cast the array entries to `i128`.
+    ///
+    /// # Arguments
+    ///
+    /// `t` - array type with a 128-bit scalar type used to interpret `self`
+    ///
+    /// # Result
+    ///
+    /// Resulting flattened array with entries cast to `i128`
+    pub fn to_flattened_array_i128(&self, t: Type) -> Result<Vec<i128>> {
+        Ok(self
+            .to_flattened_array_u128(t)?
+            .into_iter()
+            .map(|x| x as i128)
+            .collect())
+    }
+
+    /// Converts `self` to a scalar if it is a byte vector, then cast the result to `u128`.
+    ///
+    /// # Arguments
+    ///
+    /// `st` - 128-bit scalar type used to interpret `self`
+    ///
+    /// # Result
+    ///
+    /// Resulting scalar cast to `u128`
+    pub fn to_u128(&self, st: ScalarType) -> Result<u128> {
+        if st.size_in_bits() != 128 {
+            return Err(runtime_error!("128-bit scalar type expected"));
+        }
+        let v = self.access_bytes(vec_u128_from_bytes)?;
+        if v.len() != 1 {
+            return Err(runtime_error!("Not a scalar"));
+        }
+        Ok(v[0])
+    }
+
+    /// Converts `self` to a scalar if it is a byte vector, then cast the result to `i128`.
+    ///
+    /// # Arguments
+    ///
+    /// `st` - 128-bit scalar type used to interpret `self`
+    ///
+    /// # Result
+    ///
+    /// Resulting scalar cast to `i128`
+    pub fn to_i128(&self, st: ScalarType) -> Result<i128> {
+        Ok(self.to_u128(st)? as i128)
+    }
+
+    /// Constructs a value of a [string column](crate::data_types::string_column_type) from given strings.
+    ///
+    /// Returns a runtime error if some string is longer than `max_length` bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `strings` - strings to be stored in the column
+    /// * `max_length` - maximal length of a string in bytes
+    ///
+    /// # Returns
+    ///
+    /// New value of type `string_column_type(strings.len(), max_length)`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use ciphercore_base::data_values::Value;
+    /// # use ciphercore_base::data_types::string_column_type;
+    /// let v = Value::from_strings(&["alice@example.com", "bob@example.com"], 20).unwrap();
+    /// let t = string_column_type(2, 20).unwrap();
+    /// assert_eq!(v.to_strings(t).unwrap(), vec!["alice@example.com", "bob@example.com"]);
+    /// ```
+    pub fn from_strings<S: AsRef<str>>(strings: &[S], max_length: u64) -> Result<Value> {
+        let row_length =
+            get_string_row_length(string_column_type(strings.len() as u64, max_length)?)?;
+        let mut bytes = vec![];
+        for s in strings {
+            let s = s.as_ref().as_bytes();
+            if s.len() as u64 > max_length {
+                return Err(runtime_error!(
+                    "String of length {} doesn't fit into {} bytes",
+                    s.len(),
+                    max_length
+                ));
+            }
+            let mut row = vec![0u8; row_length];
+            row[..s.len()].copy_from_slice(s);
+            row[max_length as usize..]
+                .copy_from_slice(&(s.len() as u64).to_le_bytes()[..STRING_LENGTH_SIZE as usize]);
+            bytes.extend(row);
+        }
+        Ok(Value::from_bytes(bytes))
+    }
+
+    /// Converts `self` to strings if it is a value of a [string column](crate::data_types::string_column_type).
+    ///
+    /// # Arguments
+    ///
+    /// `t` - type of the string column used to interpret `self`
+    ///
+    /// # Result
+    ///
+    /// Strings stored in the column
+    pub fn to_strings(&self, t: Type) -> Result<Vec<String>> {
+        let row_length = get_string_row_length(t.clone())?;
+        let max_length = row_length - STRING_LENGTH_SIZE as usize;
+        let bytes = self.to_flattened_array_u8(t)?;
+        let mut strings = vec![];
+        for row in bytes.chunks_exact(row_length) {
+            let mut length_bytes = [0u8; 8];
+            length_bytes[..STRING_LENGTH_SIZE as usize].copy_from_slice(&row[max_length..]);
+            let length = u64::from_le_bytes(length_bytes) as usize;
+            if length > max_length {
+                return Err(runtime_error!("Invalid string length: {}", length));
+            }
+            let s = String::from_utf8(row[..length].to_vec())
+                .map_err(|_| runtime_error!("String is not valid UTF-8"))?;
+            strings.push(s);
+        }
+        Ok(strings)
+    }
+
     /// Checks if `self` is a valid value for a given type.
     ///
     /// # Arguments
@@ -1002,6 +1296,24 @@ impl Value {
         }
     }
 
+    /// Calls a given closure for every byte buffer of the value with an identifier and the size of the buffer.
+    ///
+    /// Clones of a value share its buffers, so they pass the same identifiers.
+    /// This allows to count the memory held by several values without counting shared buffers twice.
+    pub(crate) fn for_each_buffer<F>(&self, f: &mut F)
+    where
+        F: FnMut(usize, u64),
+    {
+        match *self.body.0.borrow() {
+            ValueBody::Bytes(ref bytes) => f(Arc::as_ptr(&self.body) as usize, bytes.len() as u64),
+            ValueBody::Vector(ref v) => {
+                for sub_value in v {
+                    sub_value.for_each_buffer(f);
+                }
+            }
+        }
+    }
+
     /// Generates a value of a given type with all-zero bytes.
     ///
     /// # Arguments
@@ -1068,6 +1380,36 @@ impl Value {
     }
 }
 
+/// Primitive integer type whose entries can be viewed in the bytes of a value (see [Value::with_ndarray_view]).
+pub trait NdarrayViewElement: Copy {
+    /// Scalar type whose entries are stored as this type
+    const SCALAR_TYPE: ScalarType;
+
+    /// Reads an entry from its little-endian bytes.
+    fn from_le_bytes(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_ndarray_view_element {
+    ($t:ty, $st:expr) => {
+        impl NdarrayViewElement for $t {
+            const SCALAR_TYPE: ScalarType = $st;
+
+            fn from_le_bytes(bytes: &[u8]) -> Self {
+                <$t>::from_le_bytes(bytes.try_into().unwrap())
+            }
+        }
+    };
+}
+
+impl_ndarray_view_element!(u8, UINT8);
+impl_ndarray_view_element!(i8, INT8);
+impl_ndarray_view_element!(u16, UINT16);
+impl_ndarray_view_element!(i16, INT16);
+impl_ndarray_view_element!(u32, UINT32);
+impl_ndarray_view_element!(i32, INT32);
+impl_ndarray_view_element!(u64, UINT64);
+impl_ndarray_view_element!(i64, INT64);
+
 pub trait ToNdarray<T> {
     fn to_ndarray(&self, t: Type) -> Result<ndarray::ArrayD<T>>;
 }
@@ -1906,4 +2248,108 @@ mod tests {
         }()
         .unwrap();
     }
+
+    #[test]
+    fn test_strings() {
+        || -> Result<()> {
+            let t = string_column_type(3, 4)?;
+            let v = Value::from_strings(&["ab", "ab\0", "abcd"], 4)?;
+            assert!(v.check_type(t.clone())?);
+            assert_eq!(
+                v.to_flattened_array_u8(t.clone())?,
+                vec![97, 98, 0, 0, 2, 0, 97, 98, 0, 0, 3, 0, 97, 98, 99, 100, 4, 0]
+            );
+            assert_eq!(v.to_strings(t)?, vec!["ab", "ab\0", "abcd"]);
+            assert!(Value::from_strings(&["abcde"], 4).is_err());
+            assert!(Value::from_strings(&["a"], 0).is_err());
+            assert!(v.to_strings(array_type(vec![3, 6], INT8)).is_err());
+            let invalid_length = Value::from_flattened_array(&[97, 98, 5, 0], UINT8)?;
+            assert!(invalid_length
+                .to_strings(array_type(vec![1, 4], UINT8))
+                .is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_ndarray_view() {
+        fn check_view<T>(entries: Vec<T>, st: ScalarType)
+        where
+            T: NdarrayViewElement
+                + Debug
+                + PartialEq
+                + TryInto<u64>
+                + Not<Output = T>
+                + TryInto<u8>,
+        {
+            let t = array_type(vec![2, 2], st);
+            let v = Value::from_flattened_array(&entries, t.get_scalar_type()).unwrap();
+            let viewed = v
+                .with_ndarray_view(t, |view: ndarray::ArrayViewD<T>| {
+                    assert_eq!(view.shape(), [2, 2]);
+                    Ok(view.iter().copied().collect::<Vec<T>>())
+                })
+                .unwrap();
+            assert_eq!(viewed, entries);
+        }
+        check_view(vec![0u8, 1, 254, 255], UINT8);
+        check_view(vec![0i8, -1, 127, -128], INT8);
+        check_view(vec![0u16, 1, 1000, u16::MAX], UINT16);
+        check_view(vec![0i16, -1, 1000, i16::MIN], INT16);
+        check_view(vec![0u32, 1, 100000, u32::MAX], UINT32);
+        check_view(vec![0i32, -1, 100000, i32::MIN], INT32);
+        check_view(vec![0u64, 1, 1 << 40, u64::MAX], UINT64);
+        check_view(vec![0i64, -1, 1 << 40, i64::MIN], INT64);
+
+        || -> Result<()> {
+            // The view borrows the bytes of the value if they are aligned
+            let v = Value::from_flattened_array(&[1, 2, 3], INT64)?;
+            let bytes_ptr = v.access_bytes(|bytes| Ok(bytes.as_ptr()))?;
+            let view_ptr = v.with_ndarray_view(
+                array_type(vec![3], INT64),
+                |view: ndarray::ArrayViewD<i64>| Ok(view.as_ptr() as *const u8),
+            )?;
+            if bytes_ptr.align_offset(std::mem::align_of::<i64>()) == 0 {
+                assert_eq!(bytes_ptr, view_ptr);
+            }
+            // Scalars are viewed as zero-dimensional arrays
+            let v = Value::from_scalar(-5, INT16)?;
+            let entry = v
+                .with_ndarray_view(scalar_type(INT16), |view: ndarray::ArrayViewD<i16>| {
+                    Ok(view[[]])
+                })?;
+            assert_eq!(entry, -5);
+
+            let view_i32 = |v: &Value, t: Type| {
+                v.with_ndarray_view(t, |view: ndarray::ArrayViewD<i32>| Ok(view.len()))
+            };
+            let v = Value::from_flattened_array(&[1, 2, 3, 4], INT32)?;
+            assert_eq!(view_i32(&v, array_type(vec![4], INT32))?, 4);
+            // Wrong scalar types, sizes and kinds of values
+            assert!(view_i32(&v, array_type(vec![4], UINT32)).is_err());
+            assert!(view_i32(&v, array_type(vec![4], INT64)).is_err());
+            assert!(view_i32(&v, array_type(vec![5], INT32)).is_err());
+            assert!(view_i32(&v, tuple_type(vec![])).is_err());
+            assert!(view_i32(&Value::from_vector(vec![]), array_type(vec![4], INT32)).is_err());
+            let bits = Value::from_flattened_array(&[1, 0], BIT)?;
+            assert!(bits
+                .with_ndarray_view(array_type(vec![2], BIT), |view: ndarray::ArrayViewD<u8>| {
+                    Ok(view.len())
+                })
+                .is_err());
+
+            // Conversion from ndarrays checks shapes
+            let a = ndarray::Array::from_shape_vec((2, 3), vec![1, 2, 3, 4, 5, 6])?.into_dyn();
+            let v = Value::from_ndarray_with_type(a.clone(), array_type(vec![2, 3], INT32))?;
+            assert_eq!(
+                v.to_flattened_array_i32(array_type(vec![2, 3], INT32))?,
+                vec![1, 2, 3, 4, 5, 6]
+            );
+            assert!(Value::from_ndarray_with_type(a.clone(), array_type(vec![6], INT32)).is_err());
+            assert!(Value::from_ndarray_with_type(a, scalar_type(INT32)).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
 }
@@ -1,5 +1,6 @@
 //! Wrapper of the Rust [Result](https://doc.rust-lang.org/std/result/) type within CipherCore used for error handling.
-use ciphercore_utils::errors::{CiphercoreErrorBody, ErrorWithBody};
+use crate::graphs::Node;
+use ciphercore_utils::errors::{CiphercoreErrorBody, CiphercoreErrorKind, ErrorWithBody};
 use json::JsonError;
 use ndarray::ShapeError;
 use std::num::ParseIntError;
@@ -8,21 +9,173 @@ use serde::{Deserialize, Serialize};
 
 use std::fmt;
 
+/// Place in a computation context where an error occurred.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorContext {
+    /// Node that was being instantiated or evaluated.
+    Node {
+        graph_id: u64,
+        node_id: u64,
+        name: Option<String>,
+        operation: String,
+        /// Backtrace of the node creation (see [set_node_backtrace_recording](crate::graphs::set_node_backtrace_recording))
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        construction_backtrace: Option<String>,
+    },
+    /// Custom operation that was being instantiated.
+    CustomOperation { name: String },
+}
+
+impl ErrorContext {
+    pub fn from_node(node: &Node) -> Self {
+        let (graph_id, node_id) = node.get_global_id();
+        ErrorContext::Node {
+            graph_id,
+            node_id,
+            name: node.try_get_name(),
+            operation: node.get_operation().to_string(),
+            construction_backtrace: node.get_construction_backtrace(),
+        }
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorContext::Node {
+                graph_id,
+                node_id,
+                name,
+                operation,
+                ..
+            } => {
+                write!(f, "node ({}, {})", graph_id, node_id)?;
+                if let Some(name) = name {
+                    write!(f, " (name: {})", name)?;
+                }
+                write!(f, " with operation {}", operation)
+            }
+            ErrorContext::CustomOperation { name } => write!(f, "custom operation {}", name),
+        }
+    }
+}
+
 #[doc(hidden)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CiphercoreBaseError {
     body: CiphercoreErrorBody,
+    /// Places where the error occurred starting from the innermost one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    context: Vec<ErrorContext>,
 }
 
 impl CiphercoreBaseError {
     pub fn new(body: CiphercoreErrorBody) -> Self {
-        Self { body }
+        Self {
+            body,
+            context: vec![],
+        }
+    }
+
+    /// Attaches the node, during whose processing the error occurred, to the error.
+    pub fn with_node(self, node: &Node) -> Self {
+        self.with_context(ErrorContext::from_node(node))
+    }
+
+    /// Attaches the custom operation, during whose instantiation the error occurred, to the error.
+    pub fn with_custom_operation(self, name: &str) -> Self {
+        self.with_context(ErrorContext::CustomOperation {
+            name: name.to_owned(),
+        })
+    }
+
+    /// Attaches an enclosing place where the error occurred to the error.
+    /// The kind, message and location of the error are preserved.
+    pub fn with_context(mut self, context: ErrorContext) -> Self {
+        self.context.push(context);
+        self
+    }
+
+    pub fn get_kind(&self) -> CiphercoreErrorKind {
+        self.body.kind.clone()
+    }
+
+    /// Returns the error message without the context.
+    pub fn get_message(&self) -> String {
+        self.body.message.clone()
+    }
+
+    /// Returns the places where the error occurred starting from the innermost one.
+    pub fn get_context(&self) -> Vec<ErrorContext> {
+        self.context.clone()
+    }
+
+    /// Returns the global id of the innermost node where the error occurred.
+    pub fn get_node_global_id(&self) -> Option<(u64, u64)> {
+        self.context.iter().find_map(|c| match c {
+            ErrorContext::Node {
+                graph_id, node_id, ..
+            } => Some((*graph_id, *node_id)),
+            _ => None,
+        })
+    }
+
+    /// Returns the name of the innermost custom operation where the error occurred.
+    pub fn get_custom_operation_name(&self) -> Option<String> {
+        self.context.iter().find_map(|c| match c {
+            ErrorContext::CustomOperation { name } => Some(name.clone()),
+            _ => None,
+        })
+    }
+
+    fn get_message_with_context(&self) -> String {
+        let mut message = self.body.message.clone();
+        for context in &self.context {
+            message = format!("{}: {}", context, message);
+        }
+        message
+    }
+
+    /// Returns a human-readable description of the error.
+    ///
+    /// Unlike the JSON produced by [Display](fmt::Display), the description contains the error kind and message,
+    /// the place in the CipherCore code where the error was raised, all the nodes and custom operations
+    /// within which the error occurred and the backtrace of the creation of the innermost node, if it was recorded.
+    pub fn pretty_print(&self) -> String {
+        let mut result = format!(
+            "{:?}: {}\n  raised at {}:{}:{} ({})\n",
+            self.body.kind,
+            self.body.message,
+            self.body.file,
+            self.body.line,
+            self.body.column,
+            self.body.module
+        );
+        for context in &self.context {
+            result.push_str(&format!("  in {}\n", context));
+        }
+        let backtrace = self.context.iter().find_map(|c| match c {
+            ErrorContext::Node {
+                construction_backtrace: Some(backtrace),
+                ..
+            } => Some(backtrace),
+            _ => None,
+        });
+        if let Some(backtrace) = backtrace {
+            result.push_str("Node construction backtrace:\n");
+            result.push_str(backtrace);
+        }
+        result
     }
 }
 
 impl ErrorWithBody for CiphercoreBaseError {
+    /// Returns the body of the error, whose message is supplemented with the context of the error.
     fn get_body(&self) -> CiphercoreErrorBody {
-        self.body.clone()
+        CiphercoreErrorBody {
+            message: self.get_message_with_context(),
+            ..self.body.clone()
+        }
     }
 }
 
@@ -100,6 +253,19 @@ impl From<std::str::Utf8Error> for CiphercoreBaseError {
         runtime_error!("Utf8Error: {}", err)
     }
 }
+
+impl From<csv::Error> for CiphercoreBaseError {
+    fn from(err: csv::Error) -> CiphercoreBaseError {
+        runtime_error!("CSV error: {}", err)
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl From<prometheus::Error> for CiphercoreBaseError {
+    fn from(err: prometheus::Error) -> CiphercoreBaseError {
+        runtime_error!("Prometheus error: {}", err)
+    }
+}
 /// Result type within CipherCore that is used for error handling.
 ///
 /// This is a wrapper of the Rust [Result](https://doc.rust-lang.org/std/result/) type that is effectively an enum with the variants, `Ok(T)` and `Err(E)`, where `E` is a CipherCore error containing lots of useful information.
@@ -107,7 +273,12 @@ pub type Result<T> = std::result::Result<T, CiphercoreBaseError>;
 
 #[cfg(test)]
 mod tests {
-    use crate::{errors::CiphercoreBaseError, typed_value::TypedValue};
+    use super::*;
+    use crate::data_types::{array_type, UINT64};
+    use crate::data_values::Value;
+    use crate::evaluators::random_evaluate;
+    use crate::graphs::{create_context, set_node_backtrace_recording};
+    use crate::typed_value::TypedValue;
     #[test]
     fn test_serialization_error_conversion() {
         let s = r#"{"kind":"vector","value":[{"kind":"scalar","type":"i32","value":-123456},{"kind":"scalar","type":"u32","value":123456}]}"#;
@@ -117,4 +288,82 @@ mod tests {
             assert!(err.to_string().find("serde_json::Error: ").is_some())
         }
     }
+
+    #[test]
+    // Keeps the test function in the construction backtrace in optimized builds
+    #[inline(never)]
+    fn test_node_provenance() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let a = g.input(array_type(vec![3], UINT64))?;
+            let i = g.input(array_type(vec![2], UINT64))?;
+            set_node_backtrace_recording(true);
+            let gathered = a.gather(i, 0)?;
+            set_node_backtrace_recording(false);
+            gathered.set_name("Gathered")?;
+            gathered.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let e = random_evaluate(
+                g,
+                vec![
+                    Value::from_flattened_array(&[1, 2, 3], UINT64)?,
+                    Value::from_flattened_array(&[0, 7], UINT64)?,
+                ],
+            )
+            .err()
+            .unwrap();
+            assert_eq!(e.get_kind(), CiphercoreErrorKind::RuntimeError);
+            assert_eq!(e.get_node_global_id(), Some((0, 2)));
+            assert_eq!(e.get_custom_operation_name(), None);
+            match &e.get_context()[0] {
+                ErrorContext::Node {
+                    name,
+                    operation,
+                    construction_backtrace,
+                    ..
+                } => {
+                    assert_eq!(name.as_deref(), Some("Gathered"));
+                    assert_eq!(operation, "Gather");
+                    assert!(construction_backtrace.is_some());
+                }
+                _ => panic!("Node context expected"),
+            }
+            assert!(e
+                .get_body()
+                .message
+                .starts_with("node (0, 2) (name: Gathered) with operation Gather: "));
+            let pretty = e.pretty_print();
+            assert!(pretty.contains("in node (0, 2) (name: Gathered) with operation Gather"));
+            assert!(pretty.contains("Node construction backtrace:"));
+            assert!(pretty.contains("test_node_provenance"));
+            // The context survives serialization
+            let e2: CiphercoreBaseError = serde_json::from_str(&e.to_string())?;
+            assert_eq!(e2.get_context(), e.get_context());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_custom_operation_provenance() {
+        let e = runtime_error!("Test")
+            .with_custom_operation("Op")
+            .with_context(ErrorContext::Node {
+                graph_id: 1,
+                node_id: 2,
+                name: None,
+                operation: "Custom".to_owned(),
+                construction_backtrace: None,
+            });
+        assert_eq!(e.get_custom_operation_name(), Some("Op".to_owned()));
+        assert_eq!(e.get_node_global_id(), Some((1, 2)));
+        assert_eq!(
+            e.get_body().message,
+            "node (1, 2) with operation Custom: custom operation Op: Test"
+        );
+        assert_eq!(e.get_message(), "Test");
+        assert!(!e.pretty_print().contains("backtrace"));
+    }
 }
@@ -1,11 +1,24 @@
+#[cfg(not(target_arch = "wasm32"))]
+pub mod audit_evaluator;
+pub mod caching_evaluator;
+pub mod constant_time;
 pub mod get_result_util;
+#[cfg(feature = "metrics")]
+pub mod metrics_evaluator;
+pub mod profiling_evaluator;
 pub mod simple_evaluator;
+pub mod transcript_evaluator;
 
+use crate::data_types::{Type, BIT};
 use crate::data_values::Value;
 use crate::errors::Result;
 use crate::graphs::{Context, Operation};
 use crate::graphs::{Graph, Node};
-use crate::random::SEED_SIZE;
+use crate::random::{PRNG, SEED_SIZE};
+
+use ciphercore_utils::errors::{CiphercoreErrorKind, ErrorWithBody};
+use ciphercore_utils::execute_main::extract_panic_message;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 
 pub trait Evaluator {
     fn preprocess(&mut self, context: Context) -> Result<()> {
@@ -25,41 +38,19 @@ pub trait Evaluator {
         node: Node,
         dependencies_values: Vec<Value>,
     ) -> Result<Value> {
-        match node.get_operation() {
-            Operation::Call => {
-                let graphs = node.get_graph_dependencies();
-                self.evaluate_graph(graphs[0].clone(), dependencies_values)
-            }
-            Operation::Iterate => {
-                let graphs = node.get_graph_dependencies();
-                let initial_state_value = dependencies_values[0].clone();
-                let inputs_value = dependencies_values[1].clone();
-                let mut current_state_value = initial_state_value;
-                let mut output_values = vec![];
-                for input_value in inputs_value.to_vector()? {
-                    let result = self.evaluate_graph(
-                        graphs[0].clone(),
-                        vec![current_state_value.clone(), input_value],
-                    )?;
-                    let result = result.to_vector()?;
-                    current_state_value = result[0].clone();
-                    output_values.push(result[1].clone());
-                }
-                Ok(Value::from_vector(vec![
-                    current_state_value,
-                    Value::from_vector(output_values),
-                ]))
-            }
-            _ => {
-                panic!("Should not be here!");
-            }
-        }
+        evaluate_call_iterate_default(self, node, dependencies_values)
     }
 
     fn evaluate_graph(&mut self, graph: Graph, inputs_values: Vec<Value>) -> Result<Value> {
         graph.get_context().check_finalized()?;
         let mut num_input_nodes = 0;
         let nodes = graph.get_nodes();
+        let _span = tracing::debug_span!(
+            "evaluate_graph",
+            graph = %get_graph_display_name(&graph),
+            nodes = nodes.len()
+        )
+        .entered();
 
         for node in nodes.iter() {
             if let Operation::Input(_) = node.get_operation() {
@@ -74,6 +65,8 @@ pub trait Evaluator {
             ));
         }
 
+        // Inputs are taken by the input nodes, so that they can be freed once consumed
+        let mut inputs_values: Vec<Option<Value>> = inputs_values.into_iter().map(Some).collect();
         let mut node_option_values: Vec<Option<Value>> = vec![];
 
         let num_nodes = nodes.len();
@@ -86,56 +79,309 @@ pub trait Evaluator {
 
         let output_node = graph.get_output_node()?;
         let output_id = output_node.get_id() as usize;
-        let mut update_consumed_option_nodes = |node: Node, values: &mut [Option<Value>]| {
-            for dep in node.get_node_dependencies() {
-                let dep_id = dep.get_id() as usize;
-                to_consume_option[dep_id] -= 1;
-                if to_consume_option[dep_id] == 0 && dep_id != output_id {
-                    values[dep_id] = None;
-                }
-            }
-        };
-        let mut input_id: u64 = 0;
-        for node in nodes.iter() {
-            let mut dependencies_values = vec![];
-            for dependency in node.get_node_dependencies() {
-                let node_value = node_option_values[dependency.get_id() as usize].clone();
-                match node_value {
-                    Some(value) => dependencies_values.push(value.clone()),
-                    None => {
-                        panic!("Dependency is already removed. Shouldn't be here.");
+        let mut evaluate_nodes = |evaluator: &mut Self,
+                                  values: &mut Vec<Option<Value>>|
+         -> Result<()> {
+            let mut input_id = 0;
+            for node in nodes.iter() {
+                let mut dependencies_values = vec![];
+                for dependency in node.get_node_dependencies() {
+                    let dep_id = dependency.get_id() as usize;
+                    to_consume_option[dep_id] -= 1;
+                    // The last consumer of a value takes it instead of a copy of the reference,
+                    // so that the value can be freed during the evaluation of the consumer
+                    // (e.g. inside a called graph).
+                    let node_value = if to_consume_option[dep_id] == 0 && dep_id != output_id {
+                        let node_value = values[dep_id].take();
+                        if let Some(value) = &node_value {
+                            evaluator.on_value_released(value);
+                        }
+                        node_value
+                    } else {
+                        values[dep_id].clone()
+                    };
+                    match node_value {
+                        Some(value) => dependencies_values.push(value),
+                        None => {
+                            panic!("Dependency is already removed. Shouldn't be here.");
+                        }
                     }
                 }
-            }
-            match node.get_operation() {
-                Operation::Input(t) => {
-                    if !inputs_values[input_id as usize].check_type(t)? {
-                        return Err(runtime_error!("Invalid input type"));
+                let res = match node.get_operation() {
+                    Operation::Input(t) => {
+                        let input_value = inputs_values[input_id].take().unwrap();
+                        if !input_value.check_type(t)? {
+                            return Err(runtime_error!("Invalid input type"));
+                        }
+                        input_id += 1;
+                        input_value
                     }
-                    node_option_values.push(Some(inputs_values[input_id as usize].clone()));
-                    input_id += 1;
-                }
-                Operation::Call | Operation::Iterate => {
-                    let res = self.evaluate_call_iterate(node.clone(), dependencies_values)?;
-                    node_option_values.push(Some(res));
-                    update_consumed_option_nodes((*node).clone(), &mut node_option_values);
-                }
-                _ => {
-                    let res = self.evaluate_node(node.clone(), dependencies_values)?;
-                    node_option_values.push(Some(res.clone()));
-                    update_consumed_option_nodes((*node).clone(), &mut node_option_values);
+                    Operation::Call | Operation::Iterate | Operation::If => {
+                        evaluator.evaluate_call_iterate(node.clone(), dependencies_values)?
+                    }
+                    _ => {
+                        evaluate_node_catching_panics(evaluator, node.clone(), dependencies_values)?
+                    }
+                };
+                evaluator
+                    .on_value_stored(&res)
+                    .map_err(|e| e.with_node(node))?;
+                // Values without consumers are freed right away
+                let node_id = node.get_id() as usize;
+                if to_consume_option[node_id] == 0 && node_id != output_id {
+                    evaluator.on_value_released(&res);
+                    values.push(None);
+                } else {
+                    values.push(Some(res));
                 }
             }
+            Ok(())
+        };
+        let result = evaluate_nodes(self, &mut node_option_values);
+        // Release the output value as well as the values left after a failure
+        for value in node_option_values.iter().flatten() {
+            self.on_value_released(value);
         }
-        Ok(node_option_values[output_node.get_id() as usize]
-            .clone()
-            .unwrap())
+        result?;
+        Ok(node_option_values[output_id].take().unwrap())
+    }
+
+    /// Called by [Evaluator::evaluate_graph] when the value of a node is stored until the evaluation of the last consumer of the node starts.
+    ///
+    /// Evaluators can use it to account for the memory held by the values of nodes.
+    /// If an error is returned, the evaluation is aborted.
+    fn on_value_stored(&mut self, _value: &Value) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called by [Evaluator::evaluate_graph] when a value previously passed to [Evaluator::on_value_stored] is no longer stored.
+    fn on_value_released(&mut self, _value: &Value) {}
+
+    /// Called by [Evaluator::evaluate_call_iterate] before a graph is evaluated on behalf of a Call, Iterate or If node.
+    ///
+    /// `iteration` is the index of the current iteration of an Iterate node and 0 for other nodes.
+    /// Evaluators can use it to track the stack of graph calls, e.g. to derive randomness of called graphs (see [SimpleEvaluator::new](simple_evaluator::SimpleEvaluator::new)).
+    /// If an error is returned, the evaluation is aborted.
+    fn on_graph_call_started(&mut self, _node: &Node, _iteration: u64) -> Result<()> {
+        Ok(())
     }
 
+    /// Called by [Evaluator::evaluate_call_iterate] after a graph passed to [Evaluator::on_graph_call_started] is evaluated, even if the evaluation fails.
+    fn on_graph_call_finished(&mut self) {}
+
     fn evaluate_context(&mut self, context: Context, inputs_values: Vec<Value>) -> Result<Value> {
         context.check_finalized()?;
         self.evaluate_graph(context.get_main_graph()?, inputs_values)
     }
+
+    /// Evaluates the main graph of a context on several independent sets of inputs.
+    ///
+    /// The context is preprocessed once (in particular, types of all nodes are computed only once),
+    /// and the state of the evaluator is reused for all input sets, e.g. PRFs instantiated by [SimpleEvaluator](simple_evaluator::SimpleEvaluator).
+    /// Note that a seeded [SimpleEvaluator](simple_evaluator::SimpleEvaluator) samples the same random values for every input set.
+    ///
+    /// See [evaluate_batch_parallel] to evaluate input sets in several threads.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - finalized context with a main graph
+    /// * `inputs_batch` - sets of input values of the main graph
+    ///
+    /// # Returns
+    ///
+    /// Output values of the main graph in the order of input sets
+    fn evaluate_batch(
+        &mut self,
+        context: Context,
+        inputs_batch: Vec<Vec<Value>>,
+    ) -> Result<Vec<Value>> {
+        let _span =
+            tracing::info_span!("evaluate_batch", batch_size = inputs_batch.len()).entered();
+        self.preprocess(context.clone())?;
+        let mut results = vec![];
+        for inputs_values in inputs_batch {
+            results.push(self.evaluate_context(context.clone(), inputs_values)?);
+        }
+        Ok(results)
+    }
+}
+
+/// Evaluates a Call, Iterate or If node by evaluating the called graphs with a given evaluator.
+///
+/// This is the default implementation of [Evaluator::evaluate_call_iterate], which can be reused by evaluators wrapping it with extra logic.
+pub(crate) fn evaluate_call_iterate_default<E: Evaluator + ?Sized>(
+    evaluator: &mut E,
+    node: Node,
+    dependencies_values: Vec<Value>,
+) -> Result<Value> {
+    match node.get_operation() {
+        Operation::Call => {
+            let graphs = node.get_graph_dependencies();
+            evaluate_called_graph(evaluator, &node, 0, graphs[0].clone(), dependencies_values)
+        }
+        Operation::Iterate => {
+            let graphs = node.get_graph_dependencies();
+            let mut dependencies_values = dependencies_values;
+            // The vector of inputs is split into elements, which are consumed one by one
+            let inputs_values = dependencies_values.pop().unwrap().to_vector()?;
+            let mut current_state_value = dependencies_values.pop().unwrap();
+            let mut output_values = vec![];
+            for (iteration, input_value) in inputs_values.into_iter().enumerate() {
+                let result = evaluate_called_graph(
+                    evaluator,
+                    &node,
+                    iteration as u64,
+                    graphs[0].clone(),
+                    vec![current_state_value, input_value],
+                )?;
+                let mut result = result.to_vector()?;
+                output_values.push(result.pop().unwrap());
+                current_state_value = result.pop().unwrap();
+            }
+            Ok(Value::from_vector(vec![
+                current_state_value,
+                Value::from_vector(output_values),
+            ]))
+        }
+        Operation::If => {
+            let graphs = node.get_graph_dependencies();
+            let mut dependencies_values = dependencies_values;
+            let branch = if dependencies_values.remove(0).to_u64(BIT)? == 1 {
+                graphs[0].clone()
+            } else {
+                graphs[1].clone()
+            };
+            evaluate_called_graph(evaluator, &node, 0, branch, dependencies_values)
+        }
+        _ => Err(runtime_error!("Call, Iterate or If node expected")),
+    }
+}
+
+/// Evaluates a graph called by a given Call, Iterate or If node surrounding the evaluation by the graph call hooks of an evaluator.
+fn evaluate_called_graph<E: Evaluator + ?Sized>(
+    evaluator: &mut E,
+    node: &Node,
+    iteration: u64,
+    graph: Graph,
+    inputs_values: Vec<Value>,
+) -> Result<Value> {
+    let span = tracing::debug_span!(
+        "graph_call",
+        operation = %node.get_operation(),
+        graph = %get_graph_display_name(&graph),
+        iteration,
+        output_type = tracing::field::Empty,
+        shape = tracing::field::Empty
+    );
+    record_node_type(&span, node)?;
+    let _guard = span.enter();
+    evaluator.on_graph_call_started(node, iteration)?;
+    let result = evaluator.evaluate_graph(graph, inputs_values);
+    evaluator.on_graph_call_finished();
+    result
+}
+
+/// Evaluates a node with a given evaluator converting any panic inside the evaluator kernel
+/// into an error identifying the node.
+///
+/// This keeps long-running evaluation services alive if a kernel panics on malformed data
+/// and prevents the panic from poisoning the evaluator thread.
+/// Errors returned by the kernel are supplemented with the description of the node as well.
+pub fn evaluate_node_catching_panics<E: Evaluator + ?Sized>(
+    evaluator: &mut E,
+    node: Node,
+    dependencies_values: Vec<Value>,
+) -> Result<Value> {
+    let span = tracing::trace_span!(
+        "evaluate_node",
+        operation = %node.get_operation(),
+        node = ?node.get_global_id(),
+        output_type = tracing::field::Empty,
+        shape = tracing::field::Empty
+    );
+    record_node_type(&span, &node)?;
+    let _guard = span.enter();
+    match catch_unwind(AssertUnwindSafe(|| {
+        evaluator.evaluate_node(node.clone(), dependencies_values)
+    })) {
+        Ok(result) => result.map_err(|e| e.with_node(&node)),
+        Err(e) => {
+            let message = extract_panic_message(e).unwrap_or_else(|| "unknown panic".to_owned());
+            Err(runtime_error!("Panic during evaluation: {}", message).with_node(&node))
+        }
+    }
+}
+
+/// Returns the name of a graph or `graph_<id>` if the graph has no name.
+pub(crate) fn get_graph_display_name(graph: &Graph) -> String {
+    graph
+        .get_name()
+        .unwrap_or_else(|_| format!("graph_{}", graph.get_id()))
+}
+
+// Records the output type of a node and, for arrays, its shape in the empty `output_type` and `shape` fields of a span.
+// The type isn't computed if the span is disabled, i.e. no subscriber is interested in it.
+fn record_node_type(span: &tracing::Span, node: &Node) -> Result<()> {
+    if !span.is_disabled() {
+        let t = node.get_type()?;
+        span.record("output_type", tracing::field::display(&t));
+        if let Type::Array(shape, _) = &t {
+            span.record("shape", tracing::field::debug(shape));
+        }
+    }
+    Ok(())
+}
+
+/// Allows passing a mutable reference to an evaluator wherever an evaluator is consumed,
+/// e.g. to inspect the state of the evaluator afterwards.
+impl<T: Evaluator> Evaluator for &mut T {
+    fn preprocess(&mut self, context: Context) -> Result<()> {
+        (**self).preprocess(context)
+    }
+
+    fn evaluate_node(&mut self, node: Node, dependencies_values: Vec<Value>) -> Result<Value> {
+        (**self).evaluate_node(node, dependencies_values)
+    }
+
+    fn evaluate_call_iterate(
+        &mut self,
+        node: Node,
+        dependencies_values: Vec<Value>,
+    ) -> Result<Value> {
+        (**self).evaluate_call_iterate(node, dependencies_values)
+    }
+
+    fn evaluate_graph(&mut self, graph: Graph, inputs_values: Vec<Value>) -> Result<Value> {
+        (**self).evaluate_graph(graph, inputs_values)
+    }
+
+    fn evaluate_context(&mut self, context: Context, inputs_values: Vec<Value>) -> Result<Value> {
+        (**self).evaluate_context(context, inputs_values)
+    }
+
+    fn evaluate_batch(
+        &mut self,
+        context: Context,
+        inputs_batch: Vec<Vec<Value>>,
+    ) -> Result<Vec<Value>> {
+        (**self).evaluate_batch(context, inputs_batch)
+    }
+
+    fn on_value_stored(&mut self, value: &Value) -> Result<()> {
+        (**self).on_value_stored(value)
+    }
+
+    fn on_value_released(&mut self, value: &Value) {
+        (**self).on_value_released(value)
+    }
+
+    fn on_graph_call_started(&mut self, node: &Node, iteration: u64) -> Result<()> {
+        (**self).on_graph_call_started(node, iteration)
+    }
+
+    fn on_graph_call_finished(&mut self) {
+        (**self).on_graph_call_finished()
+    }
 }
 
 pub fn evaluate_simple_evaluator(
@@ -148,6 +394,139 @@ pub fn evaluate_simple_evaluator(
     evaluator.evaluate_graph(graph, inputs)
 }
 
+/// Evaluates a given graph with the simple evaluator retrying the evaluation if Cuckoo hashing fails.
+///
+/// Hash functions of Cuckoo hashing (e.g. in set intersection protocols) are sampled during evaluation,
+/// so every new attempt uses fresh hash functions.
+/// Other errors are returned immediately.
+/// If `prng_seed` is given, the first attempt uses this seed and the seeds of the next ones are derived from it.
+///
+/// # Arguments
+///
+/// * `graph` - graph to evaluate
+/// * `inputs` - input values of the graph
+/// * `prng_seed` - optional seed of the evaluator PRNG
+/// * `max_attempts` - maximal number of evaluation attempts
+///
+/// # Returns
+///
+/// Output value of the graph or the error of the last attempt
+pub fn evaluate_simple_evaluator_with_retries(
+    graph: Graph,
+    inputs: Vec<Value>,
+    prng_seed: Option<[u8; SEED_SIZE]>,
+    max_attempts: u64,
+) -> Result<Value> {
+    if max_attempts == 0 {
+        return Err(runtime_error!("Number of attempts must be positive"));
+    }
+    let mut seed_prng = PRNG::new(prng_seed)?;
+    let mut attempt_seed = prng_seed;
+    let mut attempt = 1;
+    loop {
+        match evaluate_simple_evaluator(graph.clone(), inputs.clone(), attempt_seed) {
+            Err(e)
+                if attempt < max_attempts
+                    && e.get_body().kind == CiphercoreErrorKind::CuckooHashingFailure =>
+            {
+                attempt += 1;
+                if prng_seed.is_some() {
+                    let mut seed = [0u8; SEED_SIZE];
+                    seed.copy_from_slice(&seed_prng.get_random_bytes(SEED_SIZE)?);
+                    attempt_seed = Some(seed);
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Evaluates the main graph of a context on several independent sets of inputs in parallel.
+///
+/// Input sets are split into contiguous chunks, one per thread, and every chunk is evaluated by [Evaluator::evaluate_batch] with its own evaluator.
+/// Hence, the context is preprocessed once per thread rather than once per input set.
+///
+/// # Arguments
+///
+/// * `context` - finalized context with a main graph
+/// * `inputs_batch` - sets of input values of the main graph
+/// * `num_threads` - maximal number of threads; 1 evaluates all input sets in the current thread
+/// * `new_evaluator` - function creating an evaluator for every thread
+///
+/// # Returns
+///
+/// Output values of the main graph in the order of input sets
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{scalar_type, INT32};
+/// # use ciphercore_base::data_values::Value;
+/// # use ciphercore_base::evaluators::evaluate_batch_parallel;
+/// # use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let x = g.input(scalar_type(INT32)).unwrap();
+/// x.multiply(x.clone()).unwrap().set_as_output().unwrap();
+/// g.finalize().unwrap().set_as_main().unwrap();
+/// c.finalize().unwrap();
+/// let inputs_batch = (0..10)
+///     .map(|i| vec![Value::from_scalar(i, INT32).unwrap()])
+///     .collect();
+/// let results =
+///     evaluate_batch_parallel(c, inputs_batch, 4, || SimpleEvaluator::new(None)).unwrap();
+/// assert_eq!(results[9].to_i32(INT32).unwrap(), 81);
+/// ```
+pub fn evaluate_batch_parallel<E, F>(
+    context: Context,
+    inputs_batch: Vec<Vec<Value>>,
+    num_threads: usize,
+    new_evaluator: F,
+) -> Result<Vec<Value>>
+where
+    E: Evaluator,
+    F: Fn() -> Result<E> + Sync,
+{
+    if num_threads == 0 {
+        return Err(runtime_error!("Number of threads must be positive"));
+    }
+    if num_threads == 1 || inputs_batch.len() <= 1 {
+        return new_evaluator()?.evaluate_batch(context, inputs_batch);
+    }
+    let chunk_size = inputs_batch.len().div_ceil(num_threads);
+    let mut chunks = vec![];
+    let mut inputs_iter = inputs_batch.into_iter().peekable();
+    while inputs_iter.peek().is_some() {
+        chunks.push(inputs_iter.by_ref().take(chunk_size).collect::<Vec<_>>());
+    }
+    let chunk_results = std::thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| {
+                let context = context.clone();
+                let new_evaluator = &new_evaluator;
+                scope.spawn(move || new_evaluator()?.evaluate_batch(context, chunk))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle.join().unwrap_or_else(|e| {
+                    let message =
+                        extract_panic_message(e).unwrap_or_else(|| "unknown panic".to_owned());
+                    Err(runtime_error!("Panic during batch evaluation: {}", message))
+                })
+            })
+            .collect::<Vec<Result<Vec<Value>>>>()
+    });
+    let mut results = vec![];
+    for chunk_result in chunk_results {
+        results.extend(chunk_result?);
+    }
+    Ok(results)
+}
+
 /// Evaluate a given graph on a given set of inputs with a random PRNG seed.
 pub fn random_evaluate(graph: Graph, inputs: Vec<Value>) -> Result<Value> {
     evaluate_simple_evaluator(graph, inputs, None)
@@ -0,0 +1,324 @@
+//! Evaluator wrapper that logs the values revealed to the parties of an MPC protocol.
+use crate::data_types::{ArrayShape, Type};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::evaluators::Evaluator;
+use crate::graphs::{Context, Node, NodeAnnotation};
+use crate::mpc::key_setup::{verify_signature, PartyIdentity};
+
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::path::Path;
+
+const AUDIT_SIGNATURE_DOMAIN: &[u8] = b"ciphercore audit log signature";
+
+/// Record of a value revealed in plaintext to a party.
+///
+/// Entries describe revealed data without containing it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// Party obtaining the value.
+    pub party: u64,
+    /// Global ID `(graph_id, node_id)` of the node.
+    pub global_id: (u64, u64),
+    /// Name of the node, if any.
+    pub name: Option<String>,
+    pub value_type: Type,
+    /// Shape of the value if it is an array.
+    pub shape: Option<ArrayShape>,
+    /// Time of the evaluation of the node in the RFC 3339 format.
+    pub timestamp: String,
+}
+
+/// Log of the values revealed during evaluation produced by [AuditEvaluator].
+///
+/// Entries are stored in the order of evaluation of the corresponding nodes.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AuditLog {
+    pub entries: Vec<AuditEntry>,
+}
+
+impl AuditLog {
+    /// Returns the entries of the values revealed to a given party.
+    pub fn get_party_entries(&self, party: u64) -> Vec<AuditEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.party == party)
+            .cloned()
+            .collect()
+    }
+
+    /// Signs the log with the identity key of a party (e.g. the party running the evaluation).
+    pub fn sign(&self, identity: &PartyIdentity) -> Result<SignedAuditLog> {
+        Ok(SignedAuditLog {
+            log: self.clone(),
+            signer: identity.get_party_id(),
+            signature: identity.sign(&self.get_signed_data(identity.get_party_id())?)?,
+        })
+    }
+
+    fn get_signed_data(&self, signer: u64) -> Result<Vec<u8>> {
+        let mut data = AUDIT_SIGNATURE_DOMAIN.to_vec();
+        data.extend_from_slice(&signer.to_le_bytes());
+        data.extend(serde_json::to_vec(&self.entries)?);
+        Ok(data)
+    }
+}
+
+/// Audit log with the Ed25519 signature of the party that produced it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SignedAuditLog {
+    pub log: AuditLog,
+    /// Index of the signing party.
+    pub signer: u64,
+    pub signature: Vec<u8>,
+}
+
+impl SignedAuditLog {
+    /// Checks the signature of the log given the public identity key of the signer and returns the log.
+    pub fn verify(&self, public_key: &[u8]) -> Result<AuditLog> {
+        let data = self.log.get_signed_data(self.signer)?;
+        if !verify_signature(public_key, &data, &self.signature)? {
+            return Err(runtime_error!(
+                "Invalid signature of the audit log of party {}",
+                self.signer
+            ));
+        }
+        Ok(self.log.clone())
+    }
+
+    /// Writes the signed log to a given file in the JSON format.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Reads a signed log from a given file written by [SignedAuditLog::save].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+/// Evaluator that wraps another evaluator and logs every node annotated with [NodeAnnotation::Reveal].
+///
+/// The MPC compiler annotates the nodes revealing the output of a computation to the output parties (see [prepare_for_mpc_evaluation](crate::mpc::mpc_compiler::prepare_for_mpc_evaluation)).
+/// Thus, the log of a compiled context lists all the data leaving the secret-shared domain.
+/// For each party obtaining a revealed value, the log contains an entry with the node, the type and the shape of the value and the time of its evaluation.
+/// The log can then be signed with the identity key of a party and verified later.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT32};
+/// # use ciphercore_base::data_values::Value;
+/// # use ciphercore_base::evaluators::Evaluator;
+/// # use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
+/// # use ciphercore_base::evaluators::audit_evaluator::AuditEvaluator;
+/// # use ciphercore_base::inline::inline_ops::InlineConfig;
+/// # use ciphercore_base::mpc::key_setup::PartyIdentity;
+/// # use ciphercore_base::mpc::mpc_compiler::{prepare_for_mpc_evaluation, IOStatus};
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let t = array_type(vec![3], INT32);
+/// let a = g.input(t.clone()).unwrap();
+/// let b = g.input(t).unwrap();
+/// a.add(b).unwrap().set_as_output().unwrap();
+/// g.finalize().unwrap().set_as_main().unwrap();
+/// c.finalize().unwrap();
+/// let compiled = prepare_for_mpc_evaluation(
+///     c,
+///     vec![vec![IOStatus::Party(0), IOStatus::Party(1)]],
+///     vec![vec![IOStatus::Party(2)]],
+///     InlineConfig::default(),
+/// )
+/// .unwrap();
+///
+/// let mut evaluator = AuditEvaluator::new(SimpleEvaluator::new(None).unwrap());
+/// evaluator.preprocess(compiled.clone()).unwrap();
+/// let inputs = vec![
+///     Value::from_flattened_array(&[1, 2, 3], INT32).unwrap(),
+///     Value::from_flattened_array(&[4, 5, 6], INT32).unwrap(),
+/// ];
+/// evaluator.evaluate_context(compiled, inputs).unwrap();
+/// let log = evaluator.get_audit_log();
+/// assert_eq!(log.entries.len(), 1);
+/// assert_eq!(log.entries[0].party, 2);
+/// assert_eq!(log.entries[0].shape, Some(vec![3]));
+///
+/// let identity = PartyIdentity::generate(2).unwrap();
+/// let signed = log.sign(&identity).unwrap();
+/// assert_eq!(signed.verify(&identity.get_public_key().unwrap()).unwrap(), log);
+/// ```
+pub struct AuditEvaluator<E: Evaluator> {
+    evaluator: E,
+    log: AuditLog,
+}
+
+impl<E: Evaluator> AuditEvaluator<E> {
+    pub fn new(evaluator: E) -> Self {
+        AuditEvaluator {
+            evaluator,
+            log: AuditLog::default(),
+        }
+    }
+
+    /// Returns the log of the values revealed so far.
+    pub fn get_audit_log(&self) -> AuditLog {
+        self.log.clone()
+    }
+
+    /// Returns the wrapped evaluator.
+    pub fn into_inner(self) -> E {
+        self.evaluator
+    }
+}
+
+impl<E: Evaluator> Evaluator for AuditEvaluator<E> {
+    fn preprocess(&mut self, context: Context) -> Result<()> {
+        self.evaluator.preprocess(context)
+    }
+
+    fn evaluate_node(&mut self, node: Node, dependencies_values: Vec<Value>) -> Result<Value> {
+        let value = self
+            .evaluator
+            .evaluate_node(node.clone(), dependencies_values)?;
+        for annotation in node.get_annotations()? {
+            if let NodeAnnotation::Reveal(party) = annotation {
+                let value_type = node.get_type()?;
+                let shape = if value_type.is_array() {
+                    Some(value_type.get_shape())
+                } else {
+                    None
+                };
+                self.log.entries.push(AuditEntry {
+                    party,
+                    global_id: node.get_global_id(),
+                    name: node.get_name().ok(),
+                    value_type,
+                    shape,
+                    timestamp: chrono::Utc::now().to_rfc3339(),
+                });
+            }
+        }
+        Ok(value)
+    }
+
+    fn on_value_stored(&mut self, value: &Value) -> Result<()> {
+        self.evaluator.on_value_stored(value)
+    }
+
+    fn on_value_released(&mut self, value: &Value) {
+        self.evaluator.on_value_released(value)
+    }
+
+    fn on_graph_call_started(&mut self, node: &Node, iteration: u64) -> Result<()> {
+        self.evaluator.on_graph_call_started(node, iteration)
+    }
+
+    fn on_graph_call_finished(&mut self) {
+        self.evaluator.on_graph_call_finished()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{array_type, scalar_type, INT32};
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::create_context;
+    use crate::inline::inline_ops::InlineConfig;
+    use crate::mpc::mpc_compiler::{prepare_for_mpc_evaluation, IOStatus};
+
+    #[test]
+    fn test_audit_log() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let a = g.input(array_type(vec![2, 2], INT32))?;
+            let b = g.input(scalar_type(INT32))?;
+            a.multiply(b)?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let compiled = prepare_for_mpc_evaluation(
+                c,
+                vec![vec![IOStatus::Party(0), IOStatus::Party(1)]],
+                vec![vec![IOStatus::Party(0), IOStatus::Party(2)]],
+                InlineConfig::default(),
+            )?;
+            let mut evaluator = AuditEvaluator::new(SimpleEvaluator::new(None)?);
+            evaluator.preprocess(compiled.clone())?;
+            let result = evaluator.evaluate_context(
+                compiled.clone(),
+                vec![
+                    Value::from_flattened_array(&[1, 2, 3, 4], INT32)?,
+                    Value::from_scalar(3, INT32)?,
+                ],
+            )?;
+            assert_eq!(
+                result.to_flattened_array_i32(array_type(vec![2, 2], INT32))?,
+                vec![3, 6, 9, 12]
+            );
+            let log = evaluator.get_audit_log();
+            let parties: Vec<u64> = log.entries.iter().map(|entry| entry.party).collect();
+            assert_eq!(parties, vec![0, 2]);
+            let output_id = compiled
+                .get_main_graph()?
+                .get_output_node()?
+                .get_global_id();
+            for entry in &log.entries {
+                assert_eq!(entry.global_id, output_id);
+                assert_eq!(entry.value_type, array_type(vec![2, 2], INT32));
+                assert_eq!(entry.shape, Some(vec![2, 2]));
+            }
+            assert!(log.get_party_entries(1).is_empty());
+            assert_eq!(log.get_party_entries(2).len(), 1);
+
+            let identity = PartyIdentity::generate(0)?;
+            let signed = log.sign(&identity)?;
+            let path = std::env::temp_dir().join(format!(
+                "ciphercore_audit_log_test_{}.json",
+                std::process::id()
+            ));
+            signed.save(&path)?;
+            let loaded = SignedAuditLog::load(&path)?;
+            fs::remove_file(&path)?;
+            assert_eq!(loaded.verify(&identity.get_public_key()?)?, log);
+
+            // Tampered logs and foreign keys are rejected
+            let mut tampered = loaded.clone();
+            tampered.log.entries.pop();
+            assert!(tampered.verify(&identity.get_public_key()?).is_err());
+            let other = PartyIdentity::generate(1)?;
+            assert!(loaded.verify(&other.get_public_key()?).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_no_reveal() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let a = g.input(scalar_type(INT32))?;
+            a.add(a.clone())?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            // The output remains secret-shared
+            let compiled = prepare_for_mpc_evaluation(
+                c,
+                vec![vec![IOStatus::Party(0)]],
+                vec![vec![]],
+                InlineConfig::default(),
+            )?;
+            let mut evaluator = AuditEvaluator::new(SimpleEvaluator::new(None)?);
+            evaluator.preprocess(compiled.clone())?;
+            evaluator.evaluate_context(compiled, vec![Value::from_scalar(1, INT32)?])?;
+            assert!(evaluator.get_audit_log().entries.is_empty());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -0,0 +1,272 @@
+//! Evaluator wrapper that memoizes results of deterministic nodes across evaluations.
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::evaluators::Evaluator;
+use crate::graphs::{Context, Node, Operation};
+
+use sha2::{Digest, Sha256};
+
+use std::collections::{HashMap, VecDeque};
+
+/// Cache key: global ID of a node and SHA-256 hashes of the values of its dependencies.
+type CacheKey = ((u64, u64), Vec<[u8; 32]>);
+
+/// Statistics of the cache of [CachingEvaluator].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStatistics {
+    /// Number of node evaluations answered from the cache.
+    pub hits: u64,
+    /// Number of evaluations of deterministic nodes that weren't found in the cache.
+    pub misses: u64,
+    /// Number of cached results.
+    pub entries: u64,
+}
+
+/// Evaluator that wraps another evaluator and caches the results of deterministic nodes
+/// keyed by the node and the hashes of the values of its dependencies.
+///
+/// Cached results are reused across evaluations with the same evaluator, so a node is recomputed only if some of its dependencies changed.
+/// For example, when a set intersection is computed repeatedly with a static set `Y` and varying queries `X`,
+/// the nodes depending only on `Y` (e.g. hashing of `Y`) are evaluated once.
+///
+/// Nodes sampling randomness (Random, RandomBeacon, RandomPermutation, CuckooToPermutation and DecomposeSwitchingMap) are never cached.
+/// Since keys contain SHA-256 hashes rather than values, a result can only be wrongly reused in case of a hash collision.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{scalar_type, INT32};
+/// # use ciphercore_base::data_values::Value;
+/// # use ciphercore_base::evaluators::Evaluator;
+/// # use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
+/// # use ciphercore_base::evaluators::caching_evaluator::CachingEvaluator;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let a = g.input(scalar_type(INT32)).unwrap();
+/// let b = g.input(scalar_type(INT32)).unwrap();
+/// a.multiply(a.clone()).unwrap().add(b).unwrap().set_as_output().unwrap();
+/// g.finalize().unwrap().set_as_main().unwrap();
+/// c.finalize().unwrap();
+///
+/// let mut evaluator = CachingEvaluator::new(SimpleEvaluator::new(None).unwrap());
+/// let inputs = vec![
+///     vec![Value::from_scalar(3, INT32).unwrap(), Value::from_scalar(1, INT32).unwrap()],
+///     vec![Value::from_scalar(3, INT32).unwrap(), Value::from_scalar(2, INT32).unwrap()],
+/// ];
+/// evaluator.evaluate_batch(c, inputs).unwrap();
+/// // The product depends only on the first input, so it's computed once
+/// assert_eq!(evaluator.get_statistics().hits, 1);
+/// ```
+pub struct CachingEvaluator<E: Evaluator> {
+    evaluator: E,
+    cache: HashMap<CacheKey, Value>,
+    // Keys in the order of insertion, used to evict the oldest entries
+    order: VecDeque<CacheKey>,
+    max_entries: Option<u64>,
+    statistics: CacheStatistics,
+}
+
+impl<E: Evaluator> CachingEvaluator<E> {
+    pub fn new(evaluator: E) -> Self {
+        CachingEvaluator {
+            evaluator,
+            cache: HashMap::new(),
+            order: VecDeque::new(),
+            max_entries: None,
+            statistics: CacheStatistics::default(),
+        }
+    }
+
+    /// Limits the number of cached results; when the limit is reached, the oldest result is evicted.
+    ///
+    /// By default, the number of cached results is unlimited, so the cache holds results of all the deterministic nodes of all evaluations.
+    pub fn with_max_entries(mut self, max_entries: u64) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Returns the cache statistics collected so far.
+    pub fn get_statistics(&self) -> CacheStatistics {
+        CacheStatistics {
+            entries: self.cache.len() as u64,
+            ..self.statistics.clone()
+        }
+    }
+
+    /// Removes all cached results and resets the statistics.
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.order.clear();
+        self.statistics = CacheStatistics::default();
+    }
+
+    /// Returns the wrapped evaluator.
+    pub fn into_inner(self) -> E {
+        self.evaluator
+    }
+
+    fn insert(&mut self, key: CacheKey, value: Value) {
+        if self.max_entries == Some(0) {
+            return;
+        }
+        if let Some(max_entries) = self.max_entries {
+            while self.cache.len() as u64 >= max_entries {
+                match self.order.pop_front() {
+                    Some(oldest) => {
+                        self.cache.remove(&oldest);
+                    }
+                    None => break,
+                }
+            }
+        }
+        self.order.push_back(key.clone());
+        self.cache.insert(key, value);
+    }
+}
+
+fn is_deterministic(operation: &Operation) -> bool {
+    !matches!(
+        operation,
+        Operation::Random(_)
+            | Operation::RandomBeacon(_)
+            | Operation::RandomPermutation(_)
+            | Operation::CuckooToPermutation
+            | Operation::DecomposeSwitchingMap(_)
+    )
+}
+
+fn update_hash(hasher: &mut Sha256, value: &Value) -> Result<()> {
+    let elements = value.access(
+        |bytes| {
+            hasher.update([0]);
+            hasher.update((bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+            Ok(None)
+        },
+        |vector| Ok(Some(vector.clone())),
+    )?;
+    if let Some(elements) = elements {
+        hasher.update([1]);
+        hasher.update((elements.len() as u64).to_le_bytes());
+        for element in &elements {
+            update_hash(hasher, element)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the SHA-256 hash of the structure and the bytes of a value.
+fn hash_value(value: &Value) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    update_hash(&mut hasher, value)?;
+    Ok(hasher.finalize().into())
+}
+
+impl<E: Evaluator> Evaluator for CachingEvaluator<E> {
+    fn preprocess(&mut self, context: Context) -> Result<()> {
+        self.evaluator.preprocess(context)
+    }
+
+    fn evaluate_node(&mut self, node: Node, dependencies_values: Vec<Value>) -> Result<Value> {
+        if !is_deterministic(&node.get_operation()) {
+            return self.evaluator.evaluate_node(node, dependencies_values);
+        }
+        let hashes = dependencies_values
+            .iter()
+            .map(hash_value)
+            .collect::<Result<Vec<_>>>()?;
+        let key = (node.get_global_id(), hashes);
+        if let Some(value) = self.cache.get(&key) {
+            self.statistics.hits += 1;
+            return Ok(value.clone());
+        }
+        self.statistics.misses += 1;
+        let result = self.evaluator.evaluate_node(node, dependencies_values)?;
+        self.insert(key, result.clone());
+        Ok(result)
+    }
+
+    fn on_value_stored(&mut self, value: &Value) -> Result<()> {
+        self.evaluator.on_value_stored(value)
+    }
+
+    fn on_value_released(&mut self, value: &Value) {
+        self.evaluator.on_value_released(value)
+    }
+
+    fn on_graph_call_started(&mut self, node: &Node, iteration: u64) -> Result<()> {
+        self.evaluator.on_graph_call_started(node, iteration)
+    }
+
+    fn on_graph_call_finished(&mut self) {
+        self.evaluator.on_graph_call_finished()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{array_type, scalar_type, UINT64};
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::create_context;
+
+    #[test]
+    fn test_caching() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let callee = c.create_graph()?;
+            let z = callee.input(array_type(vec![4], UINT64))?;
+            z.sum(vec![0])?.set_as_output()?;
+            callee.finalize()?;
+            let g = c.create_graph()?;
+            let x = g.input(array_type(vec![4], UINT64))?;
+            let y = g.input(array_type(vec![4], UINT64))?;
+            // Static part depending only on y, including a called graph
+            let y_part = g.call(callee, vec![y.multiply(y.clone())?])?;
+            let random = g.random(scalar_type(UINT64))?;
+            let output = x.add(y_part)?;
+            g.create_tuple(vec![output, random])?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+
+            let y_value = Value::from_flattened_array(&[1, 2, 3, 4], UINT64)?;
+            let inputs = vec![
+                vec![
+                    Value::from_flattened_array(&[0, 0, 0, 0], UINT64)?,
+                    y_value.clone(),
+                ],
+                vec![Value::from_flattened_array(&[1, 1, 1, 1], UINT64)?, y_value],
+            ];
+            let mut evaluator = CachingEvaluator::new(SimpleEvaluator::new(None)?);
+            let results = evaluator.evaluate_batch(c.clone(), inputs.clone())?;
+            let outputs: Vec<Vec<u64>> = results
+                .iter()
+                .map(|result| {
+                    result.to_vector()?[0].to_flattened_array_u64(array_type(vec![4], UINT64))
+                })
+                .collect::<Result<_>>()?;
+            assert_eq!(outputs, vec![vec![30; 4], vec![31; 4]]);
+            let randoms: Vec<u64> = results
+                .iter()
+                .map(|result| result.to_vector()?[1].to_u64(UINT64))
+                .collect::<Result<_>>()?;
+            assert_ne!(randoms[0], randoms[1]);
+            // Multiply and Sum are reused, while Add and CreateTuple are recomputed and Random isn't cached
+            let statistics = evaluator.get_statistics();
+            assert_eq!(statistics.hits, 2);
+            assert_eq!(statistics.misses, 6);
+            assert_eq!(statistics.entries, 6);
+
+            let mut evaluator =
+                CachingEvaluator::new(SimpleEvaluator::new(None)?).with_max_entries(1);
+            evaluator.evaluate_batch(c.clone(), inputs.clone())?;
+            assert_eq!(evaluator.get_statistics().hits, 0);
+            assert_eq!(evaluator.get_statistics().entries, 1);
+            evaluator.clear();
+            assert_eq!(evaluator.get_statistics(), CacheStatistics::default());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
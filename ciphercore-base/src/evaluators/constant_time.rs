@@ -0,0 +1,788 @@
+//! Branchless kernels of the constant-time strict mode of [SimpleEvaluator](super::simple_evaluator::SimpleEvaluator)
+//! and a statistical harness detecting timing leakage of evaluation in the style of [dudect](https://eprint.iacr.org/2016/1123.pdf).
+//!
+//! Kernels of this module never branch on the values of their inputs and access memory only at positions that depend on public shapes.
+//! An array element at a secret position is read or written by scanning the whole array and selecting the right element with a mask.
+//! Thus, most kernels are asymptotically slower than their counterparts in [SimpleEvaluator](super::simple_evaluator::SimpleEvaluator).
+//! Invalid inputs are detected with accumulated error flags that are checked only once a kernel is done.
+//!
+//! **WARNING**: branchless code doesn't guarantee constant-time execution, since the compiler can introduce branches
+//! and some CPUs have data-dependent latency of instructions (e.g. integer division).
+//! Use [check_evaluation_timing_leakage] to test evaluation on a particular platform.
+use crate::data_values::Value;
+use crate::errors::{CiphercoreBaseError, Result};
+use crate::evaluators::simple_evaluator::{hash_binary_string, CUCKOO_DUMMY_ELEMENT};
+use crate::evaluators::Evaluator;
+use crate::graphs::Context;
+use crate::random::PRNG;
+
+use ciphercore_utils::errors::{CiphercoreErrorBody, CiphercoreErrorKind};
+use ciphercore_utils::runtime_error_body;
+use std::time::Instant;
+
+/// Absolute value of the t-statistic above which timings of two classes of inputs are considered different.
+///
+/// This threshold is taken from [dudect](https://github.com/oreparaz/dudect).
+pub const TIMING_LEAKAGE_THRESHOLD: f64 = 4.5;
+
+// Hides the value from the compiler, so that it can't replace arithmetic on masks with branches.
+#[inline(never)]
+fn optimization_barrier(x: u64) -> u64 {
+    // Tells the compiler that the memory at &x is volatile and that it cannot make any assumptions about it.
+    unsafe { core::ptr::read_volatile(&x as *const u64) }
+}
+
+// Choose `a` if `c = 1` and `b` if `c=0` in constant time.
+//
+// `c` must be equal to `0` or `1`.
+//
+// **WARNING**: This approach might have potential problems when compiled to WASM,
+// see <https://blog.trailofbits.com/2022/01/26/part-1-the-life-of-an-optimization-barrier/>
+#[inline(never)]
+pub(super) fn constant_time_select(a: u64, b: u64, c: u64) -> u64 {
+    let c_per_bit = optimization_barrier(c).wrapping_mul(u64::MAX);
+    c_per_bit & (a ^ b) ^ b
+}
+
+// Returns 1 if `x = 0` and 0 otherwise.
+fn constant_time_is_zero(x: u64) -> u64 {
+    let x = optimization_barrier(x);
+    ((x | x.wrapping_neg()) >> 63) ^ 1
+}
+
+// Returns 1 if `a = b` and 0 otherwise.
+fn constant_time_eq(a: u64, b: u64) -> u64 {
+    constant_time_is_zero(a ^ b)
+}
+
+// Returns 1 if `a < b` and 0 otherwise (see Hacker's Delight, Section 2-12).
+fn constant_time_lt(a: u64, b: u64) -> u64 {
+    let a = optimization_barrier(a);
+    (a ^ ((a ^ b) | (a.wrapping_sub(b) ^ b))) >> 63
+}
+
+// Reads `array[index]` accessing all the elements of the array.
+// Returns 0 if the index is out of range.
+fn oblivious_read(array: &[u64], index: u64) -> u64 {
+    let mut result = 0;
+    for (i, element) in array.iter().enumerate() {
+        result = constant_time_select(*element, result, constant_time_eq(i as u64, index));
+    }
+    result
+}
+
+// Writes `value` to `array[index]` if `write_bit = 1` accessing all the elements of the array.
+fn oblivious_write(array: &mut [u64], index: u64, value: u64, write_bit: u64) {
+    for (i, element) in array.iter_mut().enumerate() {
+        let bit = constant_time_eq(i as u64, index) & write_bit;
+        *element = constant_time_select(value, *element, bit);
+    }
+}
+
+// Fisher-Yates shuffle of the first `active_length` elements of an array.
+//
+// Random indices are drawn for every position of the array, so that the PRNG use doesn't depend on `active_length`.
+// If `active_length` is equal to the length of the array, the result coincides with the shuffle of SimpleEvaluator.
+pub(super) fn shuffle_array(array: &mut [u64], active_length: u64, prng: &mut PRNG) -> Result<()> {
+    for i in (1..array.len() as u64).rev() {
+        let j = prng.get_random_in_range(Some(i + 1))?;
+        let active_bit = constant_time_lt(i, active_length);
+        let element_i = array[i as usize];
+        let element_j = oblivious_read(array, j);
+        oblivious_write(array, j, element_i, active_bit);
+        array[i as usize] = constant_time_select(element_j, element_i, active_bit);
+    }
+    Ok(())
+}
+
+pub(super) fn gather(
+    input_entries: &[u64],
+    input_shape: &[u64],
+    indices_entries: &[u64],
+    axis: u64,
+) -> Result<Vec<u64>> {
+    let num_arrays = input_shape[..axis as usize].iter().product::<u64>() as usize;
+    let axis_size = input_shape[axis as usize] as usize;
+    let row_size = input_shape[(axis + 1) as usize..].iter().product::<u64>() as usize;
+
+    let mut output_entries = vec![0; num_arrays * indices_entries.len() * row_size];
+    let mut invalid_bit = 0;
+    for index_entry in indices_entries {
+        invalid_bit |= constant_time_lt(*index_entry, axis_size as u64) ^ 1;
+    }
+    for (array_i, output_array) in output_entries
+        .chunks_mut(indices_entries.len() * row_size)
+        .enumerate()
+    {
+        let input_array =
+            &input_entries[array_i * axis_size * row_size..(array_i + 1) * axis_size * row_size];
+        for (index_entry, output_row) in indices_entries
+            .iter()
+            .zip(output_array.chunks_mut(row_size))
+        {
+            for (row_i, input_row) in input_array.chunks(row_size).enumerate() {
+                let bit = constant_time_eq(row_i as u64, *index_entry);
+                for (output, input) in output_row.iter_mut().zip(input_row.iter()) {
+                    *output = constant_time_select(*input, *output, bit);
+                }
+            }
+        }
+    }
+    if invalid_bit == 1 {
+        return Err(runtime_error!("Incorrect index"));
+    }
+    Ok(output_entries)
+}
+
+// Writes the rows of `updates_entries` to `input_entries` at the positions of `indices_entries` along a given axis.
+// Every row of the input is rewritten, so that memory accesses don't depend on the indices.
+pub(super) fn scatter(
+    input_entries: &mut [u64],
+    input_shape: &[u64],
+    indices_entries: &[u64],
+    updates_entries: &[u64],
+    axis: u64,
+) -> Result<()> {
+    let axis_size = input_shape[axis as usize] as usize;
+    let row_size = input_shape[(axis + 1) as usize..].iter().product::<u64>() as usize;
+    let num_indices = indices_entries.len();
+
+    let mut invalid_bit = 0;
+    for (i, index_entry) in indices_entries.iter().enumerate() {
+        invalid_bit |= constant_time_lt(*index_entry, axis_size as u64) ^ 1;
+        for other_index_entry in &indices_entries[i + 1..] {
+            invalid_bit |= constant_time_eq(*index_entry, *other_index_entry);
+        }
+    }
+    for (input_array, updates_array) in input_entries
+        .chunks_mut(axis_size * row_size)
+        .zip(updates_entries.chunks(num_indices * row_size))
+    {
+        for (index_entry, update_row) in indices_entries.iter().zip(updates_array.chunks(row_size))
+        {
+            for (row_i, input_row) in input_array.chunks_mut(row_size).enumerate() {
+                let bit = constant_time_eq(row_i as u64, *index_entry);
+                for (input, update) in input_row.iter_mut().zip(update_row.iter()) {
+                    *input = constant_time_select(*update, *input, bit);
+                }
+            }
+        }
+    }
+    if invalid_bit == 1 {
+        return Err(runtime_error!("Indices must be unique and in range"));
+    }
+    Ok(())
+}
+
+pub(super) fn inverse_permutation(values: &[u64]) -> Result<Vec<u64>> {
+    let n = values.len() as u64;
+    let mut invalid_bit = 0;
+    for (i, value) in values.iter().enumerate() {
+        invalid_bit |= constant_time_lt(*value, n) ^ 1;
+        for other_value in &values[i + 1..] {
+            invalid_bit |= constant_time_eq(*value, *other_value);
+        }
+    }
+    let mut result = vec![0u64; values.len()];
+    for (i, value) in values.iter().enumerate() {
+        oblivious_write(&mut result, *value, i as u64, 1);
+    }
+    if invalid_bit == 1 {
+        return Err(runtime_error!(
+            "Input array doesn't contain a valid permutation"
+        ));
+    }
+    Ok(result)
+}
+
+// Signed entries are interpreted as in SimpleEvaluator: an entry 0 <= x < modulus is treated as x - modulus if x >= modulus / 2.
+pub(super) fn truncate(entries: &mut [u64], scale: u64, signed: bool, modulus: Option<u64>) {
+    for entry in entries.iter_mut() {
+        if !signed {
+            *entry /= scale;
+            continue;
+        }
+        let (negative_bit, absolute_value) = match modulus {
+            Some(modulus) => {
+                let negative_bit = constant_time_lt(*entry, modulus / 2) ^ 1;
+                let absolute_value = constant_time_select(modulus - *entry, *entry, negative_bit);
+                (negative_bit, absolute_value)
+            }
+            None => {
+                let negative_bit = *entry >> 63;
+                let absolute_value =
+                    constant_time_select(entry.wrapping_neg(), *entry, negative_bit);
+                (negative_bit, absolute_value)
+            }
+        };
+        // Division rounds towards zero
+        let quotient = absolute_value / scale;
+        // A negative result is mapped to the range of the modulus if it is nonzero
+        let negated_quotient = match modulus {
+            Some(modulus) => constant_time_select(
+                0,
+                modulus.wrapping_sub(quotient),
+                constant_time_is_zero(quotient),
+            ),
+            None => quotient.wrapping_neg(),
+        };
+        *entry = constant_time_select(negated_quotient, quotient, negative_bit);
+    }
+}
+
+pub(super) fn segment_cumsum(
+    input_array: &[u64],
+    binary_array: &[u64],
+    first_row: Vec<u64>,
+    modulus: Option<u64>,
+) -> Result<Vec<u64>> {
+    let row_size = first_row.len();
+    let mut result_array = first_row;
+    for (i, b) in binary_array.iter().enumerate() {
+        let input_row = &input_array[i * row_size..(i + 1) * row_size];
+        let previous_row = &result_array[i * row_size..(i + 1) * row_size];
+        let sum_row = crate::bytes::add_vectors_u64(input_row, previous_row, modulus)?;
+        let mut result_row = vec![];
+        for (sum, input) in sum_row.iter().zip(input_row.iter()) {
+            result_row.push(constant_time_select(*sum, *input, *b));
+        }
+        result_array.extend(result_row);
+    }
+    Ok(result_array)
+}
+
+pub(super) fn cuckoo_to_permutation(
+    input_array: &[u64],
+    table_size: u64,
+    prng: &mut PRNG,
+) -> Result<Vec<u64>> {
+    let mut result_array = vec![0; input_array.len()];
+    let mut duplicate_bit = 0;
+    let mut incorrect_index_bit = 0;
+    for (table, result_table) in input_array
+        .chunks(table_size as usize)
+        .zip(result_array.chunks_mut(table_size as usize))
+    {
+        let mut num_dummies = 0;
+        for element in table {
+            num_dummies += constant_time_eq(*element, CUCKOO_DUMMY_ELEMENT);
+        }
+        for (i, element) in table.iter().enumerate() {
+            let not_dummy_bit = constant_time_eq(*element, CUCKOO_DUMMY_ELEMENT) ^ 1;
+            for other_element in &table[i + 1..] {
+                duplicate_bit |= constant_time_eq(*element, *other_element) & not_dummy_bit;
+            }
+            incorrect_index_bit |=
+                (constant_time_lt(*element, table_size - num_dummies) ^ 1) & not_dummy_bit;
+        }
+        // The number of dummy elements is public, since it is equal to the table size minus the number of hashed elements.
+        let mut remaining_indices: Vec<u64> = (table_size - num_dummies..table_size).collect();
+        if remaining_indices.is_empty() {
+            remaining_indices.push(CUCKOO_DUMMY_ELEMENT);
+        }
+        let num_remaining_indices = remaining_indices.len() as u64;
+        shuffle_array(&mut remaining_indices, num_remaining_indices, prng)?;
+        let mut current_index = 0;
+        for (element, result) in table.iter().zip(result_table.iter_mut()) {
+            let dummy_bit = constant_time_eq(*element, CUCKOO_DUMMY_ELEMENT);
+            let remaining_index = oblivious_read(&remaining_indices, current_index);
+            *result = constant_time_select(remaining_index, *element, dummy_bit);
+            let next_index = current_index + dummy_bit;
+            current_index = constant_time_select(
+                next_index,
+                num_remaining_indices - 1,
+                constant_time_lt(next_index, num_remaining_indices),
+            );
+        }
+    }
+    if duplicate_bit == 1 {
+        return Err(runtime_error!("Input array contains duplicate indices"));
+    }
+    if incorrect_index_bit == 1 {
+        return Err(runtime_error!("Indices are incorrect"));
+    }
+    Ok(result_array)
+}
+
+/// Returns the permutation with deletion, the duplication map, the duplication bits and the permutation without deletion
+/// of switching maps of size `map_size` with indices in the range `[0, n)` as flattened arrays.
+///
+/// A group of equal indices of a switching map is placed in the permutation with deletion at the position
+/// equal to the total size of the groups whose first element precedes the first element of this group.
+/// All positions are computed by pairwise comparisons of the indices.
+pub(super) fn decompose_switching_map(
+    input_array: &[u64],
+    map_size: usize,
+    n: u64,
+    prng: &mut PRNG,
+) -> Result<[Vec<u64>; 4]> {
+    let mut perm1_array = vec![0; input_array.len()];
+    let mut duplication_map = vec![0; input_array.len()];
+    let mut duplication_bits = vec![0; input_array.len()];
+    let mut perm2_array = vec![0; input_array.len()];
+    let mut invalid_bit = 0;
+    for (map_i, map) in input_array.chunks(map_size).enumerate() {
+        let map_start = map_i * map_size;
+        for index in map {
+            invalid_bit |= constant_time_lt(*index, n) ^ 1;
+        }
+        // Number of previous occurrences of every index and the size of its group
+        let mut ranks = vec![0; map_size];
+        let mut group_sizes = vec![0; map_size];
+        for (i, index) in map.iter().enumerate() {
+            for (j, other_index) in map.iter().enumerate() {
+                let equal_bit = constant_time_eq(*index, *other_index);
+                ranks[i] += equal_bit & constant_time_lt(j as u64, i as u64);
+                group_sizes[i] += equal_bit;
+            }
+        }
+        let first_bits: Vec<u64> = ranks.iter().map(|r| constant_time_is_zero(*r)).collect();
+        // Position of the first occurrence of every index
+        let mut first_positions = vec![0; map_size];
+        for (i, index) in map.iter().enumerate() {
+            for (j, other_index) in map.iter().enumerate() {
+                let bit = constant_time_eq(*index, *other_index) & first_bits[j];
+                first_positions[i] = constant_time_select(j as u64, first_positions[i], bit);
+            }
+        }
+
+        // Indices missing in the switching map are sorted and put at the beginning of an array of size n
+        let mut missing_indices = vec![0; n as usize];
+        let mut num_missing_indices = 0;
+        for index in 0..n {
+            let mut present_bit = 0;
+            for map_index in map {
+                present_bit |= constant_time_eq(index, *map_index);
+            }
+            oblivious_write(
+                &mut missing_indices,
+                num_missing_indices,
+                index,
+                present_bit ^ 1,
+            );
+            num_missing_indices += present_bit ^ 1;
+        }
+        shuffle_array(&mut missing_indices, num_missing_indices, prng)?;
+
+        let little_perm1_array = &mut perm1_array[map_start..map_start + map_size];
+        let little_duplication_map = &mut duplication_map[map_start..map_start + map_size];
+        let little_duplication_bits = &mut duplication_bits[map_start..map_start + map_size];
+        for i in 0..map_size {
+            // Total size of the preceding groups and the number of groups up to the current one
+            let mut group_start = 0;
+            let mut num_groups = 0;
+            for j in 0..map_size {
+                let preceding_bit = constant_time_lt(j as u64, first_positions[i]) & first_bits[j];
+                group_start += constant_time_select(group_sizes[j], 0, preceding_bit);
+                num_groups += preceding_bit;
+            }
+            num_groups += 1;
+            let position = group_start + ranks[i];
+            // Copies of an index are replaced by the missing indices in the order of their positions
+            let missing_index = oblivious_read(&missing_indices, position.wrapping_sub(num_groups));
+            let perm1_element = constant_time_select(map[i], missing_index, first_bits[i]);
+            oblivious_write(little_perm1_array, position, perm1_element, 1);
+            oblivious_write(little_duplication_map, position, group_start, 1);
+            oblivious_write(little_duplication_bits, position, first_bits[i] ^ 1, 1);
+            perm2_array[map_start + i] = position;
+        }
+    }
+    if invalid_bit == 1 {
+        return Err(runtime_error!("Switching map has incorrect indices"));
+    }
+    Ok([perm1_array, duplication_map, duplication_bits, perm2_array])
+}
+
+pub(super) fn bloom_filter(
+    input_bits: &[u64],
+    input_string_length: usize,
+    num_input_strings_per_set: usize,
+    hash_matrices_bits: &[u64],
+    hash_matrix_columns: usize,
+    hash_matrix_size: usize,
+    size_of_filter: usize,
+) -> Vec<u64> {
+    let num_sets = input_bits.len() / (input_string_length * num_input_strings_per_set);
+    let mut filter = vec![0u64; num_sets * size_of_filter];
+    for (string_i, input_string) in input_bits.chunks(input_string_length).enumerate() {
+        let filter_start = (string_i / num_input_strings_per_set) * size_of_filter;
+        for hash_matrix in hash_matrices_bits.chunks(hash_matrix_size) {
+            let index = hash_binary_string(input_string, hash_matrix, hash_matrix_columns);
+            oblivious_write(
+                &mut filter[filter_start..filter_start + size_of_filter],
+                index,
+                1,
+                1,
+            );
+        }
+    }
+    filter
+}
+
+/// Computes Cuckoo hash tables with the same result as SimpleEvaluator.
+///
+/// Hashes of all the strings are computed in advance.
+/// Every string is inserted within exactly 100 attempts, which are ignored once the string has found its place.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn cuckoo_hash(
+    input_bits: &[u64],
+    input_string_length: usize,
+    num_input_strings_per_set: usize,
+    hash_matrices_bits: &[u64],
+    hash_functions: usize,
+    hash_matrix_columns: usize,
+    size_of_output_table: usize,
+    stash_size: u64,
+) -> Result<Vec<u64>> {
+    let hash_matrix_size = hash_matrices_bits.len() / hash_functions;
+    let num_input_sets = input_bits.len() / (num_input_strings_per_set * input_string_length);
+    let stash_start = size_of_output_table - stash_size as usize;
+
+    let mut hash_table = vec![CUCKOO_DUMMY_ELEMENT; num_input_sets * size_of_output_table];
+    let mut used_hash_functions = vec![u64::MAX; num_input_sets * size_of_output_table];
+    let mut overflow_bit = 0;
+    for set_i in 0..num_input_sets {
+        let set_bits = &input_bits[set_i * num_input_strings_per_set * input_string_length
+            ..(set_i + 1) * num_input_strings_per_set * input_string_length];
+        // Hash indices of strings ordered by strings and then by hash functions
+        let mut hashes = vec![];
+        for input_string in set_bits.chunks(input_string_length) {
+            for hash_matrix in hash_matrices_bits.chunks(hash_matrix_size) {
+                hashes.push(hash_binary_string(
+                    input_string,
+                    hash_matrix,
+                    hash_matrix_columns,
+                ));
+            }
+        }
+        let table_start = set_i * size_of_output_table;
+        let table = &mut hash_table[table_start..table_start + size_of_output_table];
+        let used_functions =
+            &mut used_hash_functions[table_start..table_start + size_of_output_table];
+        let mut num_stashed_strings = 0;
+        for string_i in 0..num_input_strings_per_set {
+            let mut current_string_index = string_i as u64;
+            let mut current_hash_function_index = 0;
+            let mut inserted_bit = 0;
+            for _ in 0..100 {
+                let active_bit = inserted_bit ^ 1;
+                let new_index = oblivious_read(
+                    &hashes,
+                    current_string_index * hash_functions as u64 + current_hash_function_index,
+                );
+                let old_string_index = oblivious_read(table, new_index);
+                let old_hash_function_index = oblivious_read(used_functions, new_index);
+                oblivious_write(table, new_index, current_string_index, active_bit);
+                oblivious_write(
+                    used_functions,
+                    new_index,
+                    current_hash_function_index,
+                    active_bit,
+                );
+                let empty_bit = constant_time_eq(old_string_index, CUCKOO_DUMMY_ELEMENT);
+                let reinsert_bit = active_bit & (empty_bit ^ 1);
+                let next_hash_function_index = old_hash_function_index.wrapping_add(1);
+                let next_hash_function_index = constant_time_select(
+                    0,
+                    next_hash_function_index,
+                    constant_time_eq(next_hash_function_index, hash_functions as u64),
+                );
+                current_string_index =
+                    constant_time_select(old_string_index, current_string_index, reinsert_bit);
+                current_hash_function_index = constant_time_select(
+                    next_hash_function_index,
+                    current_hash_function_index,
+                    reinsert_bit,
+                );
+                inserted_bit |= active_bit & empty_bit;
+            }
+            let failed_bit = inserted_bit ^ 1;
+            overflow_bit |= failed_bit & constant_time_eq(num_stashed_strings, stash_size);
+            oblivious_write(
+                &mut table[stash_start..],
+                num_stashed_strings,
+                current_string_index,
+                failed_bit,
+            );
+            num_stashed_strings += failed_bit;
+        }
+    }
+    if overflow_bit == 1 {
+        return Err(CiphercoreBaseError::new(CiphercoreErrorBody {
+            kind: CiphercoreErrorKind::CuckooHashingFailure,
+            ..runtime_error_body!(
+                "Cuckoo hashing failed: more than {} strings can't be inserted",
+                stash_size
+            )
+        }));
+    }
+    Ok(hash_table)
+}
+
+/// Result of a timing leakage test (see [check_timing_leakage]).
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimingLeakageReport {
+    /// Welch's t-statistic with the largest absolute value among all tested subsets of measurements.
+    pub t_statistic: f64,
+    /// Number of measurements of the fixed and the random classes of inputs.
+    pub num_measurements: [u64; 2],
+}
+
+impl TimingLeakageReport {
+    /// Returns `true` if the timings of the two classes of inputs differ, i.e. the absolute value of the t-statistic exceeds [TIMING_LEAKAGE_THRESHOLD].
+    pub fn is_leaking(&self) -> bool {
+        self.t_statistic.abs() > TIMING_LEAKAGE_THRESHOLD
+    }
+}
+
+// Welford's online mean and variance of a class of measurements
+#[derive(Default)]
+struct TimingStatistics {
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl TimingStatistics {
+    fn push(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+}
+
+fn welch_t_statistic(statistics: &[TimingStatistics; 2]) -> f64 {
+    let standard_error = (statistics[0].variance() / statistics[0].count as f64
+        + statistics[1].variance() / statistics[1].count as f64)
+        .sqrt();
+    if statistics[0].count < 2 || statistics[1].count < 2 || standard_error == 0.0 {
+        return 0.0;
+    }
+    (statistics[0].mean - statistics[1].mean) / standard_error
+}
+
+/// Tests whether the running time of a computation depends on its inputs following [dudect](https://eprint.iacr.org/2016/1123.pdf).
+///
+/// Measurements are taken for two classes of inputs: inputs of the class 0 are usually fixed and inputs of the class 1 are random.
+/// The class of every measurement is chosen at random to avoid interference with the state of the machine.
+/// Then, Welch's t-test is applied to all the measurements as well as to the measurements below several percentiles
+/// to get rid of the outliers caused by interruptions.
+///
+/// # Arguments
+///
+/// * `num_measurements` - number of measurements of both classes
+/// * `prepare` - function returning inputs of a given class; its running time isn't measured
+/// * `run` - function performing the measured computation on given inputs
+///
+/// # Returns
+///
+/// Report with the t-statistic with the largest absolute value
+pub fn check_timing_leakage<T, P, R>(
+    num_measurements: u64,
+    mut prepare: P,
+    mut run: R,
+) -> Result<TimingLeakageReport>
+where
+    P: FnMut(usize) -> Result<T>,
+    R: FnMut(T) -> Result<()>,
+{
+    let mut prng = PRNG::new(None)?;
+    let mut measurements = vec![];
+    for _ in 0..num_measurements {
+        let class = prng.get_random_in_range(Some(2))? as usize;
+        let inputs = prepare(class)?;
+        let start = Instant::now();
+        run(inputs)?;
+        measurements.push((class, start.elapsed().as_nanos() as f64));
+    }
+
+    let mut sorted_timings: Vec<f64> = measurements.iter().map(|(_, t)| *t).collect();
+    sorted_timings.sort_by(|a, b| a.total_cmp(b));
+    let mut thresholds = vec![f64::INFINITY];
+    for percentile in [0.5, 0.75, 0.9, 0.95, 0.99] {
+        if let Some(threshold) =
+            sorted_timings.get((percentile * sorted_timings.len() as f64) as usize)
+        {
+            thresholds.push(*threshold);
+        }
+    }
+
+    let mut report = TimingLeakageReport {
+        t_statistic: 0.0,
+        num_measurements: [0, 0],
+    };
+    for (class, _) in &measurements {
+        report.num_measurements[*class] += 1;
+    }
+    for threshold in thresholds {
+        let mut statistics = [TimingStatistics::default(), TimingStatistics::default()];
+        for (class, timing) in &measurements {
+            if *timing <= threshold {
+                statistics[*class].push(*timing);
+            }
+        }
+        let t_statistic = welch_t_statistic(&statistics);
+        if t_statistic.abs() > report.t_statistic.abs() {
+            report.t_statistic = t_statistic;
+        }
+    }
+    Ok(report)
+}
+
+/// Tests whether the evaluation of the main graph of a context by a given evaluator leaks its inputs via timing (see [check_timing_leakage]).
+///
+/// The fixed inputs are compared against random inputs returned by a given function.
+/// Use a [SimpleEvaluator](super::simple_evaluator::SimpleEvaluator) in the constant-time strict mode
+/// (see [SimpleEvaluator::with_constant_time_strict](super::simple_evaluator::SimpleEvaluator::with_constant_time_strict)) to audit its kernels.
+///
+/// # Arguments
+///
+/// * `evaluator` - evaluator to be tested
+/// * `context` - finalized context with a main graph
+/// * `fixed_inputs` - inputs of the fixed class
+/// * `random_inputs` - function returning inputs of the random class
+/// * `num_measurements` - number of evaluations of both classes
+///
+/// # Returns
+///
+/// Report with the t-statistic with the largest absolute value
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, scalar_type, UINT64};
+/// # use ciphercore_base::data_values::Value;
+/// # use ciphercore_base::evaluators::constant_time::check_evaluation_timing_leakage;
+/// # use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
+/// # use ciphercore_base::random::PRNG;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let a = g.input(array_type(vec![16], UINT64)).unwrap();
+/// let i = g.input(array_type(vec![4], UINT64)).unwrap();
+/// a.gather(i, 0).unwrap().set_as_output().unwrap();
+/// g.finalize().unwrap().set_as_main().unwrap();
+/// c.finalize().unwrap();
+///
+/// let data = Value::from_flattened_array(&[7; 16], UINT64).unwrap();
+/// let fixed_indices = Value::from_flattened_array(&[0, 0, 0, 0], UINT64).unwrap();
+/// let mut prng = PRNG::new(None).unwrap();
+/// let mut evaluator = SimpleEvaluator::new(None).unwrap().with_constant_time_strict();
+/// let report = check_evaluation_timing_leakage(
+///     &mut evaluator,
+///     c,
+///     vec![data.clone(), fixed_indices],
+///     || {
+///         let indices = prng.get_random_value(array_type(vec![4], UINT64))?;
+///         let indices = indices.to_flattened_array_u64(array_type(vec![4], UINT64))?;
+///         let indices: Vec<u64> = indices.iter().map(|x| x % 16).collect();
+///         Ok(vec![data.clone(), Value::from_flattened_array(&indices, UINT64)?])
+///     },
+///     100,
+/// ).unwrap();
+/// assert_eq!(report.num_measurements.iter().sum::<u64>(), 100);
+/// ```
+pub fn check_evaluation_timing_leakage<E, R>(
+    evaluator: &mut E,
+    context: Context,
+    fixed_inputs: Vec<Value>,
+    mut random_inputs: R,
+    num_measurements: u64,
+) -> Result<TimingLeakageReport>
+where
+    E: Evaluator,
+    R: FnMut() -> Result<Vec<Value>>,
+{
+    evaluator.preprocess(context.clone())?;
+    check_timing_leakage(
+        num_measurements,
+        |class| {
+            if class == 0 {
+                Ok(fixed_inputs.clone())
+            } else {
+                random_inputs()
+            }
+        },
+        |inputs| {
+            evaluator.evaluate_context(context.clone(), inputs)?;
+            Ok(())
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comparisons() {
+        let values = [
+            0,
+            1,
+            2,
+            1 << 62,
+            (1 << 63) - 1,
+            1 << 63,
+            u64::MAX - 1,
+            u64::MAX,
+        ];
+        for a in values {
+            assert_eq!(constant_time_is_zero(a), (a == 0) as u64);
+            for b in values {
+                assert_eq!(constant_time_eq(a, b), (a == b) as u64);
+                assert_eq!(constant_time_lt(a, b), (a < b) as u64);
+                assert_eq!(constant_time_select(a, b, 1), a);
+                assert_eq!(constant_time_select(a, b, 0), b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_oblivious_access() {
+        let mut array = vec![5, 6, 7];
+        assert_eq!(oblivious_read(&array, 1), 6);
+        assert_eq!(oblivious_read(&array, 3), 0);
+        oblivious_write(&mut array, 2, 8, 1);
+        oblivious_write(&mut array, 0, 9, 0);
+        assert_eq!(array, vec![5, 6, 8]);
+
+        let mut prng = PRNG::new(Some([0; 16])).unwrap();
+        let mut array: Vec<u64> = (0..10).collect();
+        shuffle_array(&mut array, 4, &mut prng).unwrap();
+        let mut head = array[..4].to_vec();
+        head.sort_unstable();
+        assert_eq!(head, vec![0, 1, 2, 3]);
+        assert_eq!(array[4..], [4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_check_timing_leakage() {
+        || -> Result<()> {
+            // Inputs of the class 1 take a lot longer to process
+            let report = check_timing_leakage(
+                200,
+                |class| Ok(if class == 0 { 10 } else { 100000 }),
+                |n: u64| {
+                    let mut x = 0;
+                    for i in 0..n {
+                        x = optimization_barrier(x ^ i);
+                    }
+                    Ok(())
+                },
+            )?;
+            assert_eq!(report.num_measurements.iter().sum::<u64>(), 200);
+            assert!(report.is_leaking());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -129,19 +129,14 @@ pub fn get_evaluator_result<T: Evaluator>(
     mut evaluator: T,
 ) -> Result<TypedValue> {
     let context = run_instantiation_pass(context)?.get_context();
+    // Report all the mismatching inputs before evaluation
+    context.validate_inputs(&inputs)?;
     let mut input_types = vec![];
     for node in context.get_main_graph()?.get_nodes() {
         if let Operation::Input(t) = node.get_operation() {
             input_types.push(t);
         }
     }
-    if inputs.len() != input_types.len() {
-        return Err(runtime_error!(
-            "Invalid number of inputs: {} expected, {} received",
-            input_types.len(),
-            inputs.len()
-        ));
-    }
     let mut input_values = vec![];
     let mut prng = PRNG::new(None)?;
     for i in 0..inputs.len() {
@@ -0,0 +1,177 @@
+//! Evaluator wrapper that reports evaluation metrics to a [Metrics] facade.
+use crate::data_types::get_size_in_bits;
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::evaluators::simple_evaluator::MemoryUsage;
+use crate::evaluators::Evaluator;
+use crate::graphs::{Context, Node, NodeAnnotation, Operation};
+use crate::metrics::Metrics;
+
+use std::time::Instant;
+
+/// Evaluator that wraps another evaluator and records the number and the time of evaluated nodes,
+/// the bytes annotated with [NodeAnnotation::Send], the number of PRF invocations and the memory high-water mark in given [Metrics].
+///
+/// The memory held by the values of nodes is measured in the same way as by [SimpleEvaluator::with_memory_limit](crate::evaluators::simple_evaluator::SimpleEvaluator::with_memory_limit).
+/// The throughput gauge is updated by every call of [Evaluator::evaluate_context].
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{scalar_type, INT32};
+/// # use ciphercore_base::data_values::Value;
+/// # use ciphercore_base::evaluators::Evaluator;
+/// # use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
+/// # use ciphercore_base::evaluators::metrics_evaluator::MetricsEvaluator;
+/// # use ciphercore_base::metrics::Metrics;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let a = g.input(scalar_type(INT32)).unwrap();
+/// let b = g.input(scalar_type(INT32)).unwrap();
+/// g.add(a, b).unwrap().set_as_output().unwrap();
+/// g.finalize().unwrap().set_as_main().unwrap();
+/// c.finalize().unwrap();
+///
+/// let metrics = Metrics::new().unwrap();
+/// let mut evaluator = MetricsEvaluator::new(SimpleEvaluator::new(None).unwrap(), metrics.clone());
+/// evaluator.preprocess(c.clone()).unwrap();
+/// let inputs = vec![Value::from_scalar(1, INT32).unwrap(), Value::from_scalar(2, INT32).unwrap()];
+/// evaluator.evaluate_context(c, inputs).unwrap();
+/// assert_eq!(metrics.get_nodes_evaluated(), 1);
+/// assert!(metrics.export().unwrap().contains("ciphercore_nodes_evaluated_total 1"));
+/// ```
+pub struct MetricsEvaluator<E: Evaluator> {
+    evaluator: E,
+    metrics: Metrics,
+    memory_usage: MemoryUsage,
+}
+
+impl<E: Evaluator> MetricsEvaluator<E> {
+    pub fn new(evaluator: E, metrics: Metrics) -> Self {
+        MetricsEvaluator {
+            evaluator,
+            metrics,
+            memory_usage: MemoryUsage::new(u64::MAX),
+        }
+    }
+
+    /// Returns the metrics updated by this evaluator.
+    pub fn get_metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Returns the wrapped evaluator.
+    pub fn into_inner(self) -> E {
+        self.evaluator
+    }
+
+    fn record(&mut self, node: &Node) -> Result<()> {
+        for annotation in node.get_annotations()? {
+            if let NodeAnnotation::Send(sender, receiver) = annotation {
+                let bytes = get_size_in_bits(node.get_type()?)?.div_ceil(8);
+                self.metrics.record_sent_bytes(sender, receiver, bytes);
+            }
+        }
+        if let Operation::PRF(_, _) = node.get_operation() {
+            self.metrics.record_prf_invocations(1);
+        }
+        Ok(())
+    }
+}
+
+impl<E: Evaluator> Evaluator for MetricsEvaluator<E> {
+    fn preprocess(&mut self, context: Context) -> Result<()> {
+        self.evaluator.preprocess(context)
+    }
+
+    fn evaluate_node(&mut self, node: Node, dependencies_values: Vec<Value>) -> Result<Value> {
+        let start = Instant::now();
+        let result = self
+            .evaluator
+            .evaluate_node(node.clone(), dependencies_values)?;
+        self.metrics.record_node_evaluations(1, start.elapsed());
+        self.record(&node)?;
+        Ok(result)
+    }
+
+    fn evaluate_context(&mut self, context: Context, inputs_values: Vec<Value>) -> Result<Value> {
+        context.check_finalized()?;
+        let nodes_before = self.metrics.get_nodes_evaluated();
+        let start = Instant::now();
+        let result = self.evaluate_graph(context.get_main_graph()?, inputs_values)?;
+        self.metrics.record_throughput(
+            self.metrics.get_nodes_evaluated() - nodes_before,
+            start.elapsed(),
+        );
+        Ok(result)
+    }
+
+    fn on_value_stored(&mut self, value: &Value) -> Result<()> {
+        self.memory_usage.store(value)?;
+        self.metrics
+            .record_memory_usage(self.memory_usage.get_peak());
+        self.evaluator.on_value_stored(value)
+    }
+
+    fn on_value_released(&mut self, value: &Value) {
+        self.memory_usage.release(value);
+        self.evaluator.on_value_released(value)
+    }
+
+    fn on_graph_call_started(&mut self, node: &Node, iteration: u64) -> Result<()> {
+        self.evaluator.on_graph_call_started(node, iteration)
+    }
+
+    fn on_graph_call_finished(&mut self) {
+        self.evaluator.on_graph_call_finished()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::data_types::{array_type, scalar_type, BIT, UINT64};
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::create_context;
+
+    #[test]
+    fn test_metrics_evaluator() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let callee = c.create_graph()?;
+            let z = callee.input(array_type(vec![4], UINT64))?;
+            z.sum(vec![0])?.set_as_output()?;
+            callee.finalize()?;
+            let g = c.create_graph()?;
+            let x = g.input(array_type(vec![4], UINT64))?;
+            let key = g.random(array_type(vec![128], BIT))?;
+            let prf = key.prf(0, array_type(vec![4], UINT64))?;
+            let sent = x.add(prf)?;
+            sent.add_annotation(NodeAnnotation::Send(0, 1))?;
+            g.call(callee, vec![sent])?
+                .multiply(g.constant(scalar_type(UINT64), Value::from_scalar(0, UINT64)?)?)?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+
+            let metrics = Metrics::new()?;
+            let mut evaluator = MetricsEvaluator::new(SimpleEvaluator::new(None)?, metrics);
+            let inputs = vec![Value::from_flattened_array(&[1, 2, 3, 4], UINT64)?];
+            let results = evaluator.evaluate_batch(c, vec![inputs.clone(), inputs])?;
+            assert_eq!(results[0].to_u64(UINT64)?, 0);
+            let metrics = evaluator.get_metrics();
+            // Random, PRF, Add, Constant, Multiply and Sum of the called graph; Input and Call nodes aren't evaluated by evaluate_node
+            assert_eq!(metrics.get_nodes_evaluated(), 12);
+            assert_eq!(metrics.get_prf_invocations(), 2);
+            assert_eq!(metrics.get_sent_bytes(0, 1), 64);
+            // At least the input and the PRF output are held at the same time
+            assert!(metrics.get_memory_high_water_mark() >= 64);
+            let exported = metrics.export()?;
+            assert!(exported.contains("ciphercore_nodes_evaluated_per_second"));
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
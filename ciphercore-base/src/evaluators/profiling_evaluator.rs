@@ -0,0 +1,464 @@
+//! Evaluator wrapper that collects per-node timing and communication statistics.
+use crate::data_types::{get_size_in_bits, Type};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::evaluators::{evaluate_call_iterate_default, get_graph_display_name, Evaluator};
+use crate::graphs::{Context, Node, NodeAnnotation};
+use crate::value_compression::{compress_value, CompressionConfig};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Instant;
+
+/// Statistics collected for a single node evaluated within a given stack of called graphs.
+///
+/// Nodes of graphs invoked via Call or Iterate can be evaluated several times;
+/// all these evaluations are accumulated in one record per call stack.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NodeProfile {
+    /// Names of the graphs on the call stack, starting from the graph evaluated at the top level.
+    pub stack: Vec<String>,
+    /// Global ID `(graph_id, node_id)` of the node.
+    pub global_id: (u64, u64),
+    /// Name of the operation performed by the node.
+    pub operation: String,
+    /// Name of the node, if any.
+    pub name: Option<String>,
+    /// Number of times the node has been evaluated.
+    pub evaluations: u64,
+    /// Total wall time spent in the node in nanoseconds.
+    pub time_nanos: u64,
+    /// Total number of scalar elements produced by the node.
+    pub num_elements: u64,
+    /// Total number of bytes sent between parties due to `Send` annotations of the node.
+    pub sent_bytes: u64,
+}
+
+/// Profile report produced by [ProfilingEvaluator].
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProfileReport {
+    /// Node records in the order of their first evaluation.
+    pub nodes: Vec<NodeProfile>,
+}
+
+impl ProfileReport {
+    /// Returns the total wall time spent in all the profiled nodes in nanoseconds.
+    pub fn get_total_time_nanos(&self) -> u64 {
+        self.nodes.iter().map(|n| n.time_nanos).sum()
+    }
+
+    /// Returns the total number of bytes sent between parties.
+    pub fn get_total_sent_bytes(&self) -> u64 {
+        self.nodes.iter().map(|n| n.sent_bytes).sum()
+    }
+
+    /// Aggregates node records by the name of their operation.
+    ///
+    /// # Returns
+    ///
+    /// Vector of aggregated records sorted by decreasing time; stacks and IDs of the records are empty
+    pub fn summarize_by_operation(&self) -> Vec<NodeProfile> {
+        let mut positions = HashMap::<String, usize>::new();
+        let mut result: Vec<NodeProfile> = vec![];
+        for node in &self.nodes {
+            let position = *positions.entry(node.operation.clone()).or_insert_with(|| {
+                result.push(NodeProfile {
+                    operation: node.operation.clone(),
+                    ..Default::default()
+                });
+                result.len() - 1
+            });
+            let entry = &mut result[position];
+            entry.evaluations += node.evaluations;
+            entry.time_nanos += node.time_nanos;
+            entry.num_elements += node.num_elements;
+            entry.sent_bytes += node.sent_bytes;
+        }
+        result.sort_by_key(|e| std::cmp::Reverse(e.time_nanos));
+        result
+    }
+
+    /// Returns the report in the JSON format.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Returns the report in the folded stack format accepted by flamegraph tools
+    /// (e.g. [inferno](https://github.com/jonhoo/inferno) or `flamegraph.pl`).
+    ///
+    /// Every line has the form `graph_0;graph_1;...;operation nanoseconds`.
+    pub fn to_folded_stacks(&self) -> Result<String> {
+        let mut folded = HashMap::<String, u64>::new();
+        let mut order = vec![];
+        for node in &self.nodes {
+            let mut frames = node.stack.clone();
+            frames.push(node.operation.clone());
+            let key = frames.join(";");
+            if !folded.contains_key(&key) {
+                order.push(key.clone());
+            }
+            *folded.entry(key).or_insert(0) += node.time_nanos;
+        }
+        let mut result = String::new();
+        for key in order {
+            writeln!(result, "{} {}", key, folded[&key])
+                .map_err(|_| runtime_error!("Can't format the folded stacks"))?;
+        }
+        Ok(result)
+    }
+}
+
+/// Evaluator that wraps another evaluator and records, for every evaluated node,
+/// the wall time, the number of produced elements and the number of bytes annotated with
+/// [NodeAnnotation::Send].
+///
+/// Graphs invoked via Call and Iterate (including instantiated custom operations) are
+/// attributed to a stack frame named after the graph, so the report shows which
+/// stage of a computation dominates.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{scalar_type, INT32};
+/// # use ciphercore_base::data_values::Value;
+/// # use ciphercore_base::evaluators::Evaluator;
+/// # use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
+/// # use ciphercore_base::evaluators::profiling_evaluator::ProfilingEvaluator;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let a = g.input(scalar_type(INT32)).unwrap();
+/// let b = g.input(scalar_type(INT32)).unwrap();
+/// g.add(a, b).unwrap().set_as_output().unwrap();
+/// g.finalize().unwrap().set_as_main().unwrap();
+/// c.finalize().unwrap();
+///
+/// let mut evaluator = ProfilingEvaluator::new(SimpleEvaluator::new(None).unwrap());
+/// evaluator.preprocess(c.clone()).unwrap();
+/// let inputs = vec![Value::from_scalar(1, INT32).unwrap(), Value::from_scalar(2, INT32).unwrap()];
+/// evaluator.evaluate_context(c, inputs).unwrap();
+/// let report = evaluator.get_report();
+/// assert_eq!(report.nodes.len(), 1);
+/// assert_eq!(report.nodes[0].operation, "Add");
+/// ```
+pub struct ProfilingEvaluator<E: Evaluator> {
+    evaluator: E,
+    stack: Vec<String>,
+    report: ProfileReport,
+    positions: HashMap<(Vec<String>, (u64, u64)), usize>,
+    compression: Option<CompressionConfig>,
+}
+
+impl<E: Evaluator> ProfilingEvaluator<E> {
+    pub fn new(evaluator: E) -> Self {
+        ProfilingEvaluator {
+            evaluator,
+            stack: vec![],
+            report: ProfileReport::default(),
+            positions: HashMap::new(),
+            compression: None,
+        }
+    }
+
+    /// Makes the evaluator count the bytes of `Send`-annotated values after encoding them with
+    /// [compress_value], instead of their plain size.
+    pub fn with_compression(mut self, config: CompressionConfig) -> Self {
+        self.compression = Some(config);
+        self
+    }
+
+    /// Returns the statistics collected so far.
+    pub fn get_report(&self) -> ProfileReport {
+        self.report.clone()
+    }
+
+    /// Clears the statistics collected so far.
+    pub fn reset(&mut self) {
+        self.report = ProfileReport::default();
+        self.positions.clear();
+    }
+
+    /// Returns the wrapped evaluator.
+    pub fn into_inner(self) -> E {
+        self.evaluator
+    }
+
+    fn record(&mut self, node: Node, value: &Value, time_nanos: u64) -> Result<()> {
+        let t = node.get_type()?;
+        let mut sent_bytes = 0;
+        for annotation in node.get_annotations()? {
+            if let NodeAnnotation::Send(_, _) = annotation {
+                sent_bytes += match &self.compression {
+                    Some(config) => compress_value(value, t.clone(), config)?.len() as u64,
+                    None => get_size_in_bits(t.clone())?.div_ceil(8),
+                };
+            }
+        }
+        let num_elements = get_num_elements(&t);
+        let stack = if self.stack.is_empty() {
+            vec![get_graph_display_name(&node.get_graph())]
+        } else {
+            self.stack.clone()
+        };
+        let key = (stack.clone(), node.get_global_id());
+        let position = match self.positions.get(&key) {
+            Some(position) => *position,
+            None => {
+                self.report.nodes.push(NodeProfile {
+                    stack,
+                    global_id: node.get_global_id(),
+                    operation: format!("{}", node.get_operation()),
+                    name: node.get_name().ok(),
+                    ..Default::default()
+                });
+                self.positions.insert(key, self.report.nodes.len() - 1);
+                self.report.nodes.len() - 1
+            }
+        };
+        let entry = &mut self.report.nodes[position];
+        entry.evaluations += 1;
+        entry.time_nanos += time_nanos;
+        entry.num_elements += num_elements;
+        entry.sent_bytes += sent_bytes;
+        Ok(())
+    }
+}
+
+fn get_num_elements(t: &Type) -> u64 {
+    match t {
+        Type::Scalar(_) => 1,
+        Type::Array(shape, _) => shape.iter().product(),
+        Type::Vector(n, element_type) => n * get_num_elements(element_type),
+        Type::Tuple(types) => types.iter().map(|t| get_num_elements(t)).sum(),
+        Type::NamedTuple(names_types) => names_types.iter().map(|(_, t)| get_num_elements(t)).sum(),
+    }
+}
+
+impl<E: Evaluator> Evaluator for ProfilingEvaluator<E> {
+    fn preprocess(&mut self, context: Context) -> Result<()> {
+        self.evaluator.preprocess(context)
+    }
+
+    fn evaluate_node(&mut self, node: Node, dependencies_values: Vec<Value>) -> Result<Value> {
+        let start = Instant::now();
+        let result = self
+            .evaluator
+            .evaluate_node(node.clone(), dependencies_values)?;
+        let elapsed = start.elapsed().as_nanos() as u64;
+        self.record(node, &result, elapsed)?;
+        Ok(result)
+    }
+
+    fn evaluate_call_iterate(
+        &mut self,
+        node: Node,
+        dependencies_values: Vec<Value>,
+    ) -> Result<Value> {
+        let frame = match node.get_graph_dependencies().first() {
+            Some(graph) => get_graph_display_name(graph),
+            None => format!("{}", node.get_operation()),
+        };
+        let is_top_level = self.stack.is_empty();
+        if is_top_level {
+            self.stack.push(get_graph_display_name(&node.get_graph()));
+        }
+        self.stack.push(frame);
+        // `evaluate_called_graph` calls back `self.evaluate_graph` and `self.evaluate_node`,
+        // so the nodes of the called graphs are profiled as well.
+        // The result isn't propagated before the frames are popped, so a failed call doesn't leave stale frames.
+        let result = evaluate_call_iterate_default(self, node, dependencies_values);
+        self.stack.pop();
+        if is_top_level {
+            self.stack.pop();
+        }
+        result
+    }
+
+    fn on_value_stored(&mut self, value: &Value) -> Result<()> {
+        self.evaluator.on_value_stored(value)
+    }
+
+    fn on_value_released(&mut self, value: &Value) {
+        self.evaluator.on_value_released(value)
+    }
+
+    fn on_graph_call_started(&mut self, node: &Node, iteration: u64) -> Result<()> {
+        self.evaluator.on_graph_call_started(node, iteration)
+    }
+
+    fn on_graph_call_finished(&mut self) {
+        self.evaluator.on_graph_call_finished()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::{run_instantiation_pass, CustomOperation, Not};
+    use crate::data_types::{array_type, scalar_type, BIT, INT32, INT64};
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::create_context;
+
+    #[test]
+    fn test_profiling_evaluator() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![4], BIT);
+            let i = g.input(t.clone())?;
+            let n = g.custom_op(CustomOperation::new(Not {}), vec![i])?;
+            let o = n.nop()?;
+            o.add_annotation(crate::graphs::NodeAnnotation::Send(0, 1))?;
+            o.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let c = run_instantiation_pass(c)?.get_context();
+
+            let mut evaluator = ProfilingEvaluator::new(SimpleEvaluator::new(None)?);
+            evaluator.preprocess(c.clone())?;
+            let result = evaluator.evaluate_context(
+                c.clone(),
+                vec![Value::from_flattened_array(&[0, 1, 1, 0], BIT)?],
+            )?;
+            assert_eq!(result.to_flattened_array_u64(t)?, vec![1, 0, 0, 1]);
+
+            let report = evaluator.get_report();
+            let operations: Vec<String> =
+                report.nodes.iter().map(|n| n.operation.clone()).collect();
+            assert_eq!(operations, vec!["Constant", "Add", "NOP"]);
+            let add = &report.nodes[1];
+            assert_eq!(add.stack, vec!["graph_1", "__Not::<b[4]>"]);
+            assert_eq!(add.num_elements, 4);
+            assert_eq!(add.sent_bytes, 0);
+            let nop = &report.nodes[2];
+            assert_eq!(nop.stack, vec!["graph_1"]);
+            assert_eq!(nop.sent_bytes, 1);
+            assert_eq!(report.get_total_sent_bytes(), 1);
+
+            let folded = report.to_folded_stacks()?;
+            assert!(folded.contains("graph_1;__Not::<b[4]>;Add "));
+            let json = report.to_json()?;
+            assert_eq!(serde_json::from_str::<ProfileReport>(&json)?, report);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_compressed_sent_bytes() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![10], INT64);
+            let o = g.input(t.clone())?.nop()?;
+            o.add_annotation(crate::graphs::NodeAnnotation::Send(0, 1))?;
+            o.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let input = Value::from_flattened_array(&[-3, 1, 4, 1, -5, 9, 2, 6, 5, 3], INT64)?;
+            let mut evaluator = ProfilingEvaluator::new(SimpleEvaluator::new(None)?);
+            evaluator.preprocess(c.clone())?;
+            evaluator.evaluate_context(c.clone(), vec![input.clone()])?;
+            assert_eq!(evaluator.get_report().get_total_sent_bytes(), 80);
+            let mut evaluator = ProfilingEvaluator::new(SimpleEvaluator::new(None)?)
+                .with_compression(CompressionConfig::default());
+            evaluator.preprocess(c.clone())?;
+            evaluator.evaluate_context(c, vec![input])?;
+            // Flags, packing width and one byte per entry
+            assert_eq!(evaluator.get_report().get_total_sent_bytes(), 12);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_profiling_iterate() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g_body = c.create_graph()?;
+            let state = g_body.input(scalar_type(INT32))?;
+            let input = g_body.input(scalar_type(INT32))?;
+            let sum = state.add(input)?;
+            g_body
+                .create_tuple(vec![sum.clone(), sum])?
+                .set_as_output()?;
+            g_body.finalize()?.set_name("body")?;
+            let g = c.create_graph()?;
+            let init = g.input(scalar_type(INT32))?;
+            let inputs = g.input(crate::data_types::vector_type(3, scalar_type(INT32)))?;
+            g.iterate(g_body, init, inputs)?
+                .tuple_get(0)?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+
+            let mut evaluator = ProfilingEvaluator::new(SimpleEvaluator::new(None)?);
+            evaluator.preprocess(c.clone())?;
+            let result = evaluator.evaluate_context(
+                c,
+                vec![
+                    Value::from_scalar(1, INT32)?,
+                    Value::from_vector(vec![
+                        Value::from_scalar(2, INT32)?,
+                        Value::from_scalar(3, INT32)?,
+                        Value::from_scalar(4, INT32)?,
+                    ]),
+                ],
+            )?;
+            assert_eq!(result.to_i32(INT32)?, 10);
+            let summary = evaluator.get_report().summarize_by_operation();
+            let add = summary.iter().find(|e| e.operation == "Add").unwrap();
+            assert_eq!(add.evaluations, 3);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_profiling_failed_call() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let callee = c.create_graph()?;
+            let v = callee.input(crate::data_types::vector_type(2, scalar_type(INT32)))?;
+            let id = callee.input(scalar_type(crate::data_types::UINT64))?;
+            v.vector_get(id)?.set_as_output()?;
+            callee.finalize()?.set_name("callee")?;
+            let g = c.create_graph()?;
+            let v = g.input(crate::data_types::vector_type(2, scalar_type(INT32)))?;
+            let id = g.input(scalar_type(crate::data_types::UINT64))?;
+            g.call(callee, vec![v, id])?.nop()?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+
+            let mut evaluator = ProfilingEvaluator::new(SimpleEvaluator::new(None)?);
+            evaluator.preprocess(c.clone())?;
+            let v = Value::from_vector(vec![
+                Value::from_scalar(2, INT32)?,
+                Value::from_scalar(3, INT32)?,
+            ]);
+            let out_of_range = Value::from_scalar(2, crate::data_types::UINT64)?;
+            assert!(evaluator
+                .evaluate_context(c.clone(), vec![v.clone(), out_of_range])
+                .is_err());
+            let id = Value::from_scalar(1, crate::data_types::UINT64)?;
+            let result = evaluator.evaluate_context(c, vec![v, id])?;
+            assert_eq!(result.to_i32(INT32)?, 3);
+            // The frames of the failed call must not remain on the stack
+            let report = evaluator.get_report();
+            let nop = report.nodes.iter().find(|n| n.operation == "NOP").unwrap();
+            assert_eq!(nop.stack.len(), 1);
+            let vector_get = report
+                .nodes
+                .iter()
+                .rev()
+                .find(|n| n.operation == "VectorGet")
+                .unwrap();
+            assert_eq!(vector_get.stack.len(), 2);
+            assert_eq!(vector_get.stack[1], "callee");
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -3,16 +3,22 @@ use crate::bytes::{
     add_u64, add_vectors_u64, dot_vectors_u64, multiply_u64, multiply_vectors_u64,
     subtract_vectors_u64,
 };
-use crate::bytes::{vec_from_bytes, vec_to_bytes};
-use crate::data_types::{array_type, get_size_in_bits, ArrayShape, Type, BIT, UINT64};
+use crate::bytes::{vec_from_bytes, vec_to_bytes, vec_u128_from_bytes, vec_u128_to_bytes};
+use crate::data_types::{
+    array_type, get_size_in_bits, get_u64_modulus, ArrayShape, Type, BIT, UINT64,
+};
 use crate::data_values::Value;
-use crate::errors::Result;
+use crate::errors::{CiphercoreBaseError, Result};
+use crate::evaluators::constant_time;
+use crate::evaluators::constant_time::constant_time_select;
 use crate::evaluators::Evaluator;
 use crate::graphs::{Node, Operation};
-use crate::random::{Prf, PRNG, SEED_SIZE};
+use crate::random::{derive_seed, Prf, PRNG, SEED_SIZE};
 use crate::slices::slice_index;
 use crate::type_inference::{transpose_shape, NULL_HEADER};
 
+use ciphercore_utils::errors::{CiphercoreErrorBody, CiphercoreErrorKind};
+use ciphercore_utils::runtime_error_body;
 use std::cmp::{min, Ordering};
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
@@ -20,7 +26,7 @@ use std::iter::repeat;
 use std::sync::Arc;
 
 /// It is assumed that shape can be broadcast to shape_res
-fn broadcast_to_shape(arr: &[u64], shape: &[u64], shape_res: &[u64]) -> Vec<u64> {
+fn broadcast_to_shape<T: Copy>(arr: &[T], shape: &[u64], shape_res: &[u64]) -> Vec<T> {
     let res_length: u64 = shape_res.iter().product();
     let mut result = vec![];
     let offset = shape_res.len() - shape.len();
@@ -90,29 +96,67 @@ pub(crate) fn evaluate_add_subtract_multiply(
         | (Type::Array(_, st), Type::Scalar(_))
         | (Type::Scalar(_), Type::Array(_, st))
         | (Type::Array(_, st), Type::Array(_, _)) => {
+            let shape1 = type1.get_dimensions();
+            let shape2 = type2.get_dimensions();
+            let shape_res = result_type.get_dimensions();
+            if st == BIT {
+                // Bits are processed 64 at a time as XOR/AND of machine words without unpacking them
+                let num_bits: u64 = shape_res.iter().product();
+                let bytes1 = bit_words_to_shape(&value1, &shape1, &shape_res)?;
+                let bytes2 = bit_words_to_shape(&value2, &shape2, &shape_res)?;
+                if let (Some(bytes1), Some(bytes2)) = (bytes1, bytes2) {
+                    return Ok(Value::from_bytes(evaluate_bitwise(
+                        &bytes1, &bytes2, operation, num_bits,
+                    )));
+                }
+            }
+            if st.size_in_bits() == 8 {
+                // 8-bit entries are processed as bytes without widening them to u64
+                let bytes1 = value1.access_bytes(|ref_bytes| Ok(ref_bytes.to_vec()))?;
+                let bytes2 = value2.access_bytes(|ref_bytes| Ok(ref_bytes.to_vec()))?;
+                let result_bytes = evaluate_elementwise(
+                    &broadcast_to_shape(&bytes1, &shape1, &shape_res),
+                    &broadcast_to_shape(&bytes2, &shape2, &shape_res),
+                    operation,
+                    u8::wrapping_add,
+                    u8::wrapping_sub,
+                    u8::wrapping_mul,
+                );
+                return Ok(Value::from_bytes(result_bytes));
+            }
+            if st.size_in_bits() == 128 {
+                let entries1 = value1.access_bytes(vec_u128_from_bytes)?;
+                let entries2 = value2.access_bytes(vec_u128_from_bytes)?;
+                let result = evaluate_elementwise(
+                    &broadcast_to_shape(&entries1, &shape1, &shape_res),
+                    &broadcast_to_shape(&entries2, &shape2, &shape_res),
+                    operation,
+                    u128::wrapping_add,
+                    u128::wrapping_sub,
+                    u128::wrapping_mul,
+                );
+                return Ok(Value::from_bytes(vec_u128_to_bytes(&result)));
+            }
             //pack bytes into vectors of u64
             let bytes1_u64 = value1
                 .access_bytes(|ref_bytes| Ok(vec_from_bytes(ref_bytes, st.clone())?.to_vec()))?;
             let bytes2_u64 = value2
                 .access_bytes(|ref_bytes| Ok(vec_from_bytes(ref_bytes, st.clone())?.to_vec()))?;
-            let shape1 = type1.get_dimensions();
-            let shape2 = type2.get_dimensions();
-            let shape_res = result_type.get_dimensions();
             let result_u64 = match operation {
                 Operation::Add => add_vectors_u64(
                     &broadcast_to_shape(&bytes1_u64, &shape1, &shape_res),
                     &broadcast_to_shape(&bytes2_u64, &shape2, &shape_res),
-                    st.get_modulus(),
+                    get_u64_modulus(&st)?,
                 )?,
                 Operation::Subtract => subtract_vectors_u64(
                     &broadcast_to_shape(&bytes1_u64, &shape1, &shape_res),
                     &broadcast_to_shape(&bytes2_u64, &shape2, &shape_res),
-                    st.get_modulus(),
+                    get_u64_modulus(&st)?,
                 )?,
                 Operation::Multiply => multiply_vectors_u64(
                     &broadcast_to_shape(&bytes1_u64, &shape1, &shape_res),
                     &broadcast_to_shape(&bytes2_u64, &shape2, &shape_res),
-                    st.get_modulus(),
+                    get_u64_modulus(&st)?,
                 )?,
                 _ => panic!("Should not be here"),
             };
@@ -127,6 +171,75 @@ pub(crate) fn evaluate_add_subtract_multiply(
     Ok(result_value)
 }
 
+/// Returns packed bits of a binary value broadcast to a given shape.
+///
+/// Only values of the result shape and single bits are supported; `None` is returned in other cases.
+fn bit_words_to_shape(value: &Value, shape: &[u64], shape_res: &[u64]) -> Result<Option<Vec<u8>>> {
+    let num_bits: u64 = shape_res.iter().product();
+    let num_bytes = num_bits.div_ceil(8) as usize;
+    value.access_bytes(|ref_bytes| {
+        if shape == shape_res && ref_bytes.len() == num_bytes {
+            return Ok(Some(ref_bytes.to_vec()));
+        }
+        if shape.iter().product::<u64>() == 1 && !ref_bytes.is_empty() {
+            let filler = if ref_bytes[0] & 1 == 1 { u8::MAX } else { 0 };
+            return Ok(Some(vec![filler; num_bytes]));
+        }
+        Ok(None)
+    })
+}
+
+/// Applies Add, Subtract or Multiply to packed bits, i.e. XOR or AND of 64-bit words.
+///
+/// Bits beyond `num_bits` in the last byte of the result are set to zero.
+fn evaluate_bitwise(bytes1: &[u8], bytes2: &[u8], operation: Operation, num_bits: u64) -> Vec<u8> {
+    let f = match operation {
+        Operation::Add | Operation::Subtract => |x: u64, y: u64| x ^ y,
+        Operation::Multiply => |x: u64, y: u64| x & y,
+        _ => panic!("Should not be here"),
+    };
+    let mut result = Vec::with_capacity(bytes1.len());
+    let mut chunks1 = bytes1.chunks_exact(8);
+    let mut chunks2 = bytes2.chunks_exact(8);
+    for (chunk1, chunk2) in (&mut chunks1).zip(&mut chunks2) {
+        let word1 = u64::from_le_bytes(chunk1.try_into().unwrap());
+        let word2 = u64::from_le_bytes(chunk2.try_into().unwrap());
+        result.extend_from_slice(&f(word1, word2).to_le_bytes());
+    }
+    for (byte1, byte2) in chunks1.remainder().iter().zip(chunks2.remainder()) {
+        result.push(f(*byte1 as u64, *byte2 as u64) as u8);
+    }
+    let trailing_bits = num_bits % 8;
+    if trailing_bits != 0 {
+        if let Some(last_byte) = result.last_mut() {
+            *last_byte &= (1u8 << trailing_bits) - 1;
+        }
+    }
+    result
+}
+
+/// Applies Add, Subtract or Multiply to entries of native integer types, wrapping around on overflow.
+fn evaluate_elementwise<T: Copy>(
+    entries1: &[T],
+    entries2: &[T],
+    operation: Operation,
+    add: fn(T, T) -> T,
+    subtract: fn(T, T) -> T,
+    multiply: fn(T, T) -> T,
+) -> Vec<T> {
+    let f = match operation {
+        Operation::Add => add,
+        Operation::Subtract => subtract,
+        Operation::Multiply => multiply,
+        _ => panic!("Should not be here"),
+    };
+    entries1
+        .iter()
+        .zip(entries2)
+        .map(|(x, y)| f(*x, *y))
+        .collect()
+}
+
 pub(crate) fn evaluate_mixed_multiply(
     type1: Type,
     value1: Value,
@@ -151,7 +264,7 @@ pub(crate) fn evaluate_mixed_multiply(
             let result_u64 = multiply_vectors_u64(
                 &broadcast_to_shape(&bytes1_u64, &shape1, &shape_res),
                 &broadcast_to_shape(&bytes2_u64, &shape2, &shape_res),
-                st.get_modulus(),
+                get_u64_modulus(&st)?,
             )?;
             //unpack bytes from vectors of u64
             vec_to_bytes(&result_u64, st)?
@@ -172,7 +285,7 @@ fn evaluate_dot(
     result_type: Type,
 ) -> Result<Value> {
     let st = type0.get_scalar_type();
-    let modulus = st.get_modulus();
+    let modulus = get_u64_modulus(&st)?;
     if type0.is_array() && type1.is_array() {
         let shape0 = type0.get_shape();
         let shape1 = type1.get_shape();
@@ -244,7 +357,7 @@ fn evaluate_matmul(
     result_type: Type,
 ) -> Result<Value> {
     let st = type0.get_scalar_type();
-    let modulus = st.get_modulus();
+    let modulus = get_u64_modulus(&st)?;
     if !type0.is_array() || !type1.is_array() {
         panic!("Inconsistency with type checker");
     }
@@ -360,7 +473,7 @@ fn general_gemm(
     let row_size = shape1[shape1.len() - 1] as usize;
 
     let st = trans_t0.get_scalar_type();
-    let modulus = st.get_modulus();
+    let modulus = get_u64_modulus(&st)?;
 
     let result_length = {
         let result_shape = result_type.get_shape();
@@ -400,6 +513,25 @@ fn general_gemm(
     Value::from_flattened_array(&result_entries, st)
 }
 
+// Reads a native-endian word starting at a given, possibly unaligned, byte position
+fn read_u64(bytes: &[u8], start: usize) -> u64 {
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&bytes[start..start + 8]);
+    u64::from_ne_bytes(word)
+}
+
+fn read_u32(bytes: &[u8], start: usize) -> u32 {
+    let mut word = [0u8; 4];
+    word.copy_from_slice(&bytes[start..start + 4]);
+    u32::from_ne_bytes(word)
+}
+
+fn read_u16(bytes: &[u8], start: usize) -> u16 {
+    let mut word = [0u8; 2];
+    word.copy_from_slice(&bytes[start..start + 2]);
+    u16::from_ne_bytes(word)
+}
+
 // Computes dot product of two binary strings of equal length
 fn binary_dot(bytes0: &[u8], bytes1: &[u8]) -> u8 {
     let mut byte_i = 0;
@@ -410,8 +542,8 @@ fn binary_dot(bytes0: &[u8], bytes1: &[u8]) -> u8 {
         let words_to_read = num_bytes / 8;
         let mut sum_word = 0;
         for word_i in 0..words_to_read {
-            let word0 = unsafe { *(&bytes0[byte_i + word_i * 8] as *const u8 as *const u64) };
-            let word1 = unsafe { *(&bytes1[byte_i + word_i * 8] as *const u8 as *const u64) };
+            let word0 = read_u64(bytes0, byte_i + word_i * 8);
+            let word1 = read_u64(bytes1, byte_i + word_i * 8);
             sum_word ^= word0 & word1;
         }
         res_word = sum_word;
@@ -419,16 +551,16 @@ fn binary_dot(bytes0: &[u8], bytes1: &[u8]) -> u8 {
     }
     // read 32-bit words
     if byte_i + 4 <= num_bytes {
-        let word0 = unsafe { *(&bytes0[byte_i] as *const u8 as *const u32) };
-        let word1 = unsafe { *(&bytes1[byte_i] as *const u8 as *const u32) };
+        let word0 = read_u32(bytes0, byte_i);
+        let word1 = read_u32(bytes1, byte_i);
         let sum_word = word0 & word1;
         res_word ^= sum_word as u64;
         byte_i += 4;
     }
     // read 16-bit words
     if byte_i + 2 <= num_bytes {
-        let word0 = unsafe { *(&bytes0[byte_i] as *const u8 as *const u16) };
-        let word1 = unsafe { *(&bytes1[byte_i] as *const u8 as *const u16) };
+        let word0 = read_u16(bytes0, byte_i);
+        let word1 = read_u16(bytes1, byte_i);
         let sum_word = word0 & word1;
         res_word ^= sum_word as u64;
         byte_i += 2;
@@ -465,7 +597,7 @@ fn read_binary_row(destination: &mut [u8], source: &[u8], row_size: usize, start
             u64::MAX
         };
         for word_i in 0..num_words {
-            let word = unsafe { *(&source[byte_start + word_i * 8] as *const u8 as *const u64) };
+            let word = read_u64(source, byte_start + word_i * 8);
             let word_to_copy = if offset_size > 0 {
                 // extract 64 - offset_size LSBs
                 let top_bits = (word & top_mask) << offset_size;
@@ -475,17 +607,14 @@ fn read_binary_row(destination: &mut [u8], source: &[u8], row_size: usize, start
             } else {
                 word
             };
-            let ptr = &mut destination[word_i * 8] as *mut u8 as *mut u64;
-            unsafe {
-                *ptr = word_to_copy;
-            }
+            destination[word_i * 8..word_i * 8 + 8].copy_from_slice(&word_to_copy.to_ne_bytes());
         }
         writing_point += 64 * num_words;
         reading_point += 64 * num_words;
     }
     if writing_point + 32 <= row_size {
         let byte_start = reading_point / 8;
-        let word = unsafe { *(&source[byte_start] as *const u8 as *const u32) };
+        let word = read_u32(source, byte_start);
         let word_to_copy = if offset_size > 0 {
             // extract 32 - offset_size LSBs
             let top_bits = (word & ((1 << (32 - offset_size)) - 1)) << offset_size;
@@ -495,16 +624,14 @@ fn read_binary_row(destination: &mut [u8], source: &[u8], row_size: usize, start
         } else {
             word
         };
-        let ptr = &mut destination[writing_point / 8] as *mut u8 as *mut u32;
-        unsafe {
-            *ptr = word_to_copy;
-        }
+        destination[writing_point / 8..writing_point / 8 + 4]
+            .copy_from_slice(&word_to_copy.to_ne_bytes());
         writing_point += 32;
         reading_point += 32;
     }
     if writing_point + 16 <= row_size {
         let byte_start = reading_point / 8;
-        let word = unsafe { *(&source[byte_start] as *const u8 as *const u16) };
+        let word = read_u16(source, byte_start);
         let word_to_copy = if offset_size > 0 {
             // extract 16 - offset_size LSBs
             let top_bits = (word & ((1 << (16 - offset_size)) - 1)) << offset_size;
@@ -514,10 +641,8 @@ fn read_binary_row(destination: &mut [u8], source: &[u8], row_size: usize, start
         } else {
             word
         };
-        let ptr = &mut destination[writing_point / 8] as *mut u8 as *mut u16;
-        unsafe {
-            *ptr = word_to_copy;
-        }
+        destination[writing_point / 8..writing_point / 8 + 2]
+            .copy_from_slice(&word_to_copy.to_ne_bytes());
         writing_point += 16;
         reading_point += 16;
     }
@@ -692,18 +817,37 @@ fn evaluate_gemm(
 }
 
 // Dummy value in Cuckoo hash tables that contain indices of arrays
-const CUCKOO_DUMMY_ELEMENT: u64 = u64::MAX;
+pub(super) const CUCKOO_DUMMY_ELEMENT: u64 = u64::MAX;
+
+// Multiplies a binary matrix with a given number of columns by a binary string and returns the result as an integer.
+pub(super) fn hash_binary_string(input_string: &[u64], hash_matrix: &[u64], columns: usize) -> u64 {
+    let mut hash = 0;
+    // TODO: this matrix-vector product can be optimized
+    for (row, hash_row) in hash_matrix.chunks(columns).enumerate() {
+        let mut hash_index_bit = 0;
+        for (hash_bit, input_bit) in hash_row.iter().zip(input_string.iter()) {
+            hash_index_bit ^= hash_bit & input_bit;
+        }
+        hash ^= hash_index_bit << row;
+    }
+    hash
+}
 
-// Cuckoo hashing is computed as in <https://eprint.iacr.org/2018/579.pdf>, Section 3.2
+// Cuckoo hashing is computed as in <https://eprint.iacr.org/2018/579.pdf>, Section 3.2.
+// Strings that can't be inserted are put into a stash of a given size at the end of the hash table.
 fn evaluate_cuckoo(
     input_type: Type,
     input_value: Value,
     hash_matrices_type: Type,
     hash_matrices_value: Value,
+    stash_size: u64,
     result_type: Type,
+    constant_time_strict: bool,
 ) -> Result<Value> {
     if !input_type.is_array() || !hash_matrices_type.is_array() {
-        panic!("Inconsistency with type checker");
+        return Err(runtime_error!(
+            "Cuckoo hashing can be applied only to arrays"
+        ));
     }
     let input_shape = input_type.get_shape();
     let hash_matrices_shape = hash_matrices_type.get_shape();
@@ -720,6 +864,8 @@ fn evaluate_cuckoo(
 
     let hash_functions = hash_matrices_shape[0] as usize;
     let hash_matrix_rows = hash_matrices_shape[1] as usize;
+    // The stash is located after the hash map
+    let stash_start = size_of_output_table - stash_size as usize;
     let hash_matrix_columns = hash_matrices_shape[2] as usize;
     let hash_matrix_size = (hash_matrix_rows * hash_matrix_columns) as usize;
 
@@ -730,7 +876,22 @@ fn evaluate_cuckoo(
     let num_input_strings_per_set = input_shape[input_shape.len() - 2] as usize;
     let input_string_length = input_shape[input_shape.len() - 1] as usize;
 
+    if constant_time_strict {
+        let hash_table = constant_time::cuckoo_hash(
+            &input_bits,
+            input_string_length,
+            num_input_strings_per_set,
+            &hash_matrices_bits,
+            hash_functions,
+            hash_matrix_columns,
+            size_of_output_table,
+            stash_size,
+        )?;
+        return Value::from_flattened_array(&hash_table, UINT64);
+    }
+
     for set_i in 0..num_input_sets {
+        let mut num_stashed_strings = 0;
         for string_i in 0..num_input_strings_per_set {
             let mut current_string_index = string_i;
             let mut current_hash_function_index = 0;
@@ -745,21 +906,12 @@ fn evaluate_cuckoo(
                 let input_string = &input_bits[string_start..string_start + input_string_length];
 
                 // Compute the hash of the input string
-                let mut new_index = 0;
-                // TODO: this matrix-vector product can be optimized
-                for row in 0..hash_matrix_rows {
-                    let mut hash_index_bit = 0;
-                    for (column, input_bit) in
-                        input_string.iter().enumerate().take(hash_matrix_columns)
-                    {
-                        hash_index_bit ^= hash_matrices_bits[hash_matrix_size
-                            * current_hash_function_index
-                            + row * hash_matrix_columns
-                            + column]
-                            & input_bit;
-                    }
-                    new_index ^= hash_index_bit << row;
-                }
+                let hash_matrix_start = hash_matrix_size * current_hash_function_index;
+                let new_index = hash_binary_string(
+                    input_string,
+                    &hash_matrices_bits[hash_matrix_start..hash_matrix_start + hash_matrix_size],
+                    hash_matrix_columns,
+                );
 
                 // Check that the hash table is empty at the hash index
                 let result_index = set_i * size_of_output_table + new_index as usize;
@@ -786,7 +938,19 @@ fn evaluate_cuckoo(
                 }
             }
             if insertion_failed {
-                panic!("Cuckoo hashing failed");
+                // Put the last extracted string into the stash if it's not full
+                if num_stashed_strings == stash_size as usize {
+                    return Err(CiphercoreBaseError::new(CiphercoreErrorBody {
+                        kind: CiphercoreErrorKind::CuckooHashingFailure,
+                        ..runtime_error_body!(
+                            "Cuckoo hashing failed: more than {} strings can't be inserted",
+                            stash_size
+                        )
+                    }));
+                }
+                hash_table[set_i * size_of_output_table + stash_start + num_stashed_strings] =
+                    current_string_index as u64;
+                num_stashed_strings += 1;
             }
         }
     }
@@ -794,6 +958,52 @@ fn evaluate_cuckoo(
     Value::from_flattened_array(&hash_table, UINT64)
 }
 
+fn evaluate_bloom_filter(
+    input_type: Type,
+    input_value: Value,
+    hash_matrices_type: Type,
+    hash_matrices_value: Value,
+    result_type: Type,
+    constant_time_strict: bool,
+) -> Result<Value> {
+    let input_shape = input_type.get_shape();
+    let hash_matrices_shape = hash_matrices_type.get_shape();
+    let input_bits = input_value.to_flattened_array_u64(input_type)?;
+    let hash_matrices_bits = hash_matrices_value.to_flattened_array_u64(hash_matrices_type)?;
+    let result_shape = result_type.get_shape();
+
+    let size_of_filter = result_shape[result_shape.len() - 1] as usize;
+    let mut filter = vec![0u64; result_shape.iter().product::<u64>() as usize];
+
+    let hash_matrix_columns = hash_matrices_shape[2] as usize;
+    let hash_matrix_size = (hash_matrices_shape[1] * hash_matrices_shape[2]) as usize;
+    let input_string_length = input_shape[input_shape.len() - 1] as usize;
+    let num_input_strings_per_set = input_shape[input_shape.len() - 2] as usize;
+
+    if constant_time_strict {
+        let filter = constant_time::bloom_filter(
+            &input_bits,
+            input_string_length,
+            num_input_strings_per_set,
+            &hash_matrices_bits,
+            hash_matrix_columns,
+            hash_matrix_size,
+            size_of_filter,
+        );
+        return Value::from_flattened_array(&filter, BIT);
+    }
+
+    for (string_i, input_string) in input_bits.chunks(input_string_length).enumerate() {
+        let filter_start = (string_i / num_input_strings_per_set) * size_of_filter;
+        for hash_matrix in hash_matrices_bits.chunks(hash_matrix_size) {
+            let index = hash_binary_string(input_string, hash_matrix, hash_matrix_columns);
+            filter[filter_start + index as usize] = 1;
+        }
+    }
+
+    Value::from_flattened_array(&filter, BIT)
+}
+
 // Fisher-Yates shuffle (<https://en.wikipedia.org/wiki/Fisher%E2%80%93Yates_shuffle>)
 fn shuffle_array(array: &mut Vec<u64>, prng: &mut PRNG) -> Result<()> {
     for i in (1..array.len() as u64).rev() {
@@ -806,13 +1016,16 @@ fn shuffle_array(array: &mut Vec<u64>, prng: &mut PRNG) -> Result<()> {
 fn evaluate_sum(node: Node, input_value: Value, axes: ArrayShape) -> Result<Value> {
     let dependency = node.get_node_dependencies()[0].clone();
     let inp_t = dependency.get_type()?;
+    if inp_t.get_scalar_type().size_in_bits() == 128 {
+        return evaluate_sum_u128(node, input_value, axes);
+    }
     let values = input_value.to_flattened_array_u64(inp_t.clone())?;
     let res_t = node.get_type()?;
     match res_t {
         Type::Scalar(st) => {
             let mut result = 0u64;
             for v in values {
-                result = add_u64(result, v, st.get_modulus());
+                result = add_u64(result, v, get_u64_modulus(&st)?);
             }
             Value::from_scalar(result, st)
         }
@@ -837,7 +1050,7 @@ fn evaluate_sum(node: Node, input_value: Value, axes: ArrayShape) -> Result<Valu
                         new_index.push(inp_index[*ax]);
                     }
                     let new_i = index_to_number(&new_index, &res_shape) as usize;
-                    result[new_i] = add_u64(result[new_i], *value, st.get_modulus());
+                    result[new_i] = add_u64(result[new_i], *value, get_u64_modulus(&st)?);
                 }
                 Value::from_flattened_array(&result, st)
             }
@@ -848,6 +1061,44 @@ fn evaluate_sum(node: Node, input_value: Value, axes: ArrayShape) -> Result<Valu
     }
 }
 
+fn evaluate_sum_u128(node: Node, input_value: Value, axes: ArrayShape) -> Result<Value> {
+    let inp_t = node.get_node_dependencies()[0].get_type()?;
+    let values = input_value.to_flattened_array_u128(inp_t.clone())?;
+    let res_t = node.get_type()?;
+    if res_t.is_array() && axes.is_empty() {
+        return Ok(input_value);
+    }
+    let inp_shape = inp_t.get_shape();
+    let res_shape = if res_t.is_array() {
+        res_t.get_shape()
+    } else {
+        vec![]
+    };
+    let res_len: u64 = res_shape.iter().product();
+    let mut result = vec![0u128; res_len as usize];
+    for (i, value) in values.iter().enumerate() {
+        let inp_index = number_to_index(i as u64, &inp_shape);
+        let new_index: Vec<u64> = (0..inp_shape.len())
+            .filter(|j| !axes.contains(&(*j as u64)))
+            .map(|j| inp_index[j])
+            .collect();
+        let new_i = index_to_number(&new_index, &res_shape) as usize;
+        result[new_i] = result[new_i].wrapping_add(*value);
+    }
+    Value::from_flattened_array_u128(&result, res_t.get_scalar_type())
+}
+
+// Replaces the entries of an array by their cumulative sums along a given axis
+fn cumsum_entries<T: Copy>(entries: &mut [T], shape: &[u64], axis: u64, add: impl Fn(T, T) -> T) {
+    let axis_size = shape[axis as usize] as usize;
+    let row_size = shape[(axis + 1) as usize..].iter().product::<u64>() as usize;
+    for array in entries.chunks_mut(axis_size * row_size) {
+        for i in row_size..array.len() {
+            array[i] = add(array[i - row_size], array[i]);
+        }
+    }
+}
+
 fn sum_bits_along_last_dimension(input_t: Type, input_value: Value) -> Result<Value> {
     let input_shape = input_t.get_shape();
     let res_bytes = input_value.access_bytes(|bytes| {
@@ -871,8 +1122,7 @@ fn sum_bits_along_last_dimension(input_t: Type, input_value: Value) -> Result<Va
                         let start = current_bit / 8;
                         let mut word = 0;
                         for word_i in 0..words_to_read {
-                            let ptr = &bytes[start + word_i * 8] as *const u8 as *const u64;
-                            word ^= unsafe { *ptr };
+                            word ^= read_u64(bytes, start + word_i * 8);
                         }
                         num_bits_to_read -= 64 * words_to_read;
                         current_bit += 64 * words_to_read;
@@ -881,16 +1131,14 @@ fn sum_bits_along_last_dimension(input_t: Type, input_value: Value) -> Result<Va
                     // 32-bit words
                     if current_bit + 32 <= row_end {
                         let start = current_bit / 8;
-                        let ptr = &bytes[start] as *const u8 as *const u32;
-                        sum_byte ^= unsafe { ((*ptr).count_ones() % 2) as u8 };
+                        sum_byte ^= (read_u32(bytes, start).count_ones() % 2) as u8;
                         num_bits_to_read -= 32;
                         current_bit += 32;
                     }
                     // 16-bit words
                     if current_bit + 16 <= row_end {
                         let start = current_bit / 8;
-                        let ptr = &bytes[start] as *const u8 as *const u16;
-                        sum_byte ^= unsafe { ((*ptr).count_ones() % 2) as u8 };
+                        sum_byte ^= (read_u16(bytes, start).count_ones() % 2) as u8;
                         num_bits_to_read -= 16;
                         current_bit += 16;
                     }
@@ -950,39 +1198,224 @@ fn get_named_types(t: Type) -> Vec<(String, Arc<Type>)> {
     }
 }
 
-// Choose `a` if `c = 1` and `b` if `c=0` in constant time.
-//
-// `c` must be equal to `0` or `1`.
-//
-// **WARNING**: This approach might have potential problems when compiled to WASM,
-// see <https://blog.trailofbits.com/2022/01/26/part-1-the-life-of-an-optimization-barrier/>
-#[inline(never)]
-fn constant_time_select(a: u64, b: u64, c: u64) -> u64 {
-    // Tells the compiler that the memory at &c is volatile and that it cannot make any assumptions about it.
-    let mut c_per_bit = unsafe { core::ptr::read_volatile(&c as *const u64) };
-    c_per_bit *= u64::MAX;
-    c_per_bit & (a ^ b) ^ b
+// Packs the permutation with deletion, the duplication map along with duplication bits and the permutation without deletion into a value.
+fn switching_map_decomposition_value(
+    perm1_array: &[u64],
+    duplication_map: &[u64],
+    duplication_bits: &[u64],
+    perm2_array: &[u64],
+) -> Result<Value> {
+    let perm1_val = Value::from_flattened_array(perm1_array, UINT64)?;
+    let dup_map_val = Value::from_flattened_array(duplication_map, UINT64)?;
+    let dup_bits_val = Value::from_flattened_array(duplication_bits, BIT)?;
+    let perm2_val = Value::from_flattened_array(perm2_array, UINT64)?;
+    Ok(Value::from_vector(vec![
+        perm1_val,
+        Value::from_vector(vec![dup_map_val, dup_bits_val]),
+        perm2_val,
+    ]))
+}
+
+/// Bytes held by the stored values of nodes.
+///
+/// Buffers shared by several values are counted once.
+#[derive(Default)]
+pub(crate) struct MemoryUsage {
+    limit: u64,
+    // Number of stored references and size of every buffer
+    buffers: HashMap<usize, (u64, u64)>,
+    current: u64,
+    peak: u64,
+}
+
+impl MemoryUsage {
+    pub(crate) fn new(limit: u64) -> Self {
+        MemoryUsage {
+            limit,
+            ..MemoryUsage::default()
+        }
+    }
+
+    pub(crate) fn get_peak(&self) -> u64 {
+        self.peak
+    }
+
+    pub(crate) fn store(&mut self, value: &Value) -> Result<()> {
+        let mut new_buffers = HashMap::new();
+        value.for_each_buffer(&mut |id, size| {
+            if !self.buffers.contains_key(&id) {
+                new_buffers.insert(id, size);
+            }
+        });
+        let new_bytes: u64 = new_buffers.values().sum();
+        if self.current + new_bytes > self.limit {
+            return Err(CiphercoreBaseError::new(CiphercoreErrorBody {
+                kind: CiphercoreErrorKind::MemoryLimitExceeded,
+                ..runtime_error_body!(
+                    "Memory limit exceeded: {} bytes are held, {} more bytes are needed, the limit is {} bytes",
+                    self.current,
+                    new_bytes,
+                    self.limit
+                )
+            }));
+        }
+        value.for_each_buffer(&mut |id, size| {
+            self.buffers.entry(id).or_insert((0, size)).0 += 1;
+        });
+        self.current += new_bytes;
+        self.peak = self.peak.max(self.current);
+        Ok(())
+    }
+
+    pub(crate) fn release(&mut self, value: &Value) {
+        value.for_each_buffer(&mut |id, _| {
+            if let Entry::Occupied(mut entry) = self.buffers.entry(id) {
+                entry.get_mut().0 -= 1;
+                if entry.get().0 == 0 {
+                    self.current -= entry.remove().1;
+                }
+            }
+        });
+    }
 }
 
 pub struct SimpleEvaluator {
     prng: PRNG,
+    // Seeds of the graphs being evaluated if the evaluator is seeded; the last seed belongs to the innermost called graph
+    scope_seeds: Option<Vec<[u8; SEED_SIZE]>>,
     prfs: HashMap<Vec<u8>, Prf>,
+    memory_usage: Option<MemoryUsage>,
+    constant_time_strict: bool,
+}
+
+/// Returns the seed of a node derived from the seed of the evaluation of its graph.
+fn get_node_seed(scope_seed: [u8; SEED_SIZE], node: &Node) -> Result<[u8; SEED_SIZE]> {
+    derive_seed(
+        derive_seed(scope_seed, node.get_graph().get_id())?,
+        node.get_id(),
+    )
 }
 
 impl SimpleEvaluator {
+    /// Creates a new evaluator.
+    ///
+    /// If `prng_seed` is given, evaluation is reproducible bit-for-bit.
+    /// Every node sampling randomness (e.g. Random or RandomPermutation) uses its own PRNG with a seed derived hierarchically
+    /// from `prng_seed`, the IDs of the node and its graph, and the Call, Iterate or If nodes (along with iteration indices) leading to the evaluation of its graph.
+    /// Thus, random values don't depend on the order, in which nodes are evaluated, and on other random nodes.
+    /// In particular, every evaluation of the same graph by the same evaluator yields the same random values.
+    ///
+    /// Otherwise, all the nodes draw randomness from one PRNG with a random seed.
     pub fn new(prng_seed: Option<[u8; SEED_SIZE]>) -> Result<Self> {
         Ok(SimpleEvaluator {
             prng: PRNG::new(prng_seed)?,
+            scope_seeds: prng_seed.map(|seed| vec![seed]),
             prfs: HashMap::new(),
+            memory_usage: None,
+            constant_time_strict: false,
         })
     }
+
+    // Returns the PRNG of a node if the evaluator is seeded.
+    fn get_node_prng(&self, node: &Node) -> Result<Option<PRNG>> {
+        match self.scope_seeds.as_ref().and_then(|seeds| seeds.last()) {
+            Some(scope_seed) => Ok(Some(PRNG::new(Some(get_node_seed(*scope_seed, node)?))?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Limits the memory held by the values of nodes during evaluation of graphs.
+    ///
+    /// The value of a node is held until the evaluation of the last node depending on it starts (the output value is held until the end of evaluation of its graph).
+    /// Buffers shared by several values, e.g. the elements of a tuple and the tuple itself, are counted once.
+    /// Temporary memory used by operations is not counted.
+    ///
+    /// If storing the value of a node would exceed the limit, evaluation fails with an error of the [MemoryLimitExceeded](CiphercoreErrorKind::MemoryLimitExceeded) kind.
+    /// Pass `u64::MAX` to only measure the memory usage.
+    ///
+    /// # Arguments
+    ///
+    /// `limit` - maximal number of bytes held by the values of nodes
+    ///
+    /// # Returns
+    ///
+    /// Evaluator with the memory limit
+    pub fn with_memory_limit(mut self, limit: u64) -> Self {
+        self.memory_usage = Some(MemoryUsage::new(limit));
+        self
+    }
+
+    /// Switches the evaluator to the constant-time strict mode.
+    ///
+    /// The local simulator holds all values in plaintext, so in this mode every value is treated as secret.
+    /// Operations branching on their inputs or accessing memory at input-dependent positions
+    /// (Gather, Scatter, InversePermutation, Truncate, SegmentCumSum, RandomPermutation, CuckooHash, CuckooToPermutation, DecomposeSwitchingMap and BloomFilter)
+    /// are evaluated by the branchless kernels of [constant_time](crate::evaluators::constant_time).
+    /// These kernels are considerably slower and are meant for auditing timing leakage (see [check_evaluation_timing_leakage](crate::evaluators::constant_time::check_evaluation_timing_leakage)).
+    /// Set intersection has no branchless kernel and fails in this mode.
+    ///
+    /// Results of seeded evaluators coincide with those of the default mode except for the permutations with deletion of DecomposeSwitchingMap,
+    /// whose missing indices are shuffled with a different use of the PRNG.
+    ///
+    /// # Returns
+    ///
+    /// Evaluator in the constant-time strict mode
+    pub fn with_constant_time_strict(mut self) -> Self {
+        self.constant_time_strict = true;
+        self
+    }
+
+    /// Returns the number of bytes currently held by the values of nodes or `None` if the memory usage is not tracked (see [SimpleEvaluator::with_memory_limit]).
+    pub fn get_memory_usage(&self) -> Option<u64> {
+        self.memory_usage.as_ref().map(|usage| usage.current)
+    }
+
+    /// Returns the maximal number of bytes held by the values of nodes so far or `None` if the memory usage is not tracked (see [SimpleEvaluator::with_memory_limit]).
+    pub fn get_peak_memory_usage(&self) -> Option<u64> {
+        self.memory_usage.as_ref().map(|usage| usage.get_peak())
+    }
 }
 
 impl Evaluator for SimpleEvaluator {
+    fn on_value_stored(&mut self, value: &Value) -> Result<()> {
+        match &mut self.memory_usage {
+            Some(usage) => usage.store(value),
+            None => Ok(()),
+        }
+    }
+
+    fn on_value_released(&mut self, value: &Value) {
+        if let Some(usage) = &mut self.memory_usage {
+            usage.release(value);
+        }
+    }
+
+    fn on_graph_call_started(&mut self, node: &Node, iteration: u64) -> Result<()> {
+        if let Some(seeds) = &mut self.scope_seeds {
+            if let Some(scope_seed) = seeds.last() {
+                let call_seed = derive_seed(get_node_seed(*scope_seed, node)?, iteration)?;
+                seeds.push(call_seed);
+            }
+        }
+        Ok(())
+    }
+
+    fn on_graph_call_finished(&mut self) {
+        if let Some(seeds) = &mut self.scope_seeds {
+            // The seed of the evaluation itself is never removed
+            if seeds.len() > 1 {
+                seeds.pop();
+            }
+        }
+    }
+
     fn evaluate_node(&mut self, node: Node, dependencies_values: Vec<Value>) -> Result<Value> {
         match node.get_operation() {
-            Operation::Input(_) | Operation::Call | Operation::Iterate => {
-                panic!("Should not be here!");
+            Operation::Input(_) => Err(runtime_error!(
+                "Input nodes are evaluated only as a part of a graph"
+            )),
+            Operation::Call | Operation::Iterate | Operation::If => {
+                self.evaluate_call_iterate(node, dependencies_values)
             }
             Operation::Add
             | Operation::Subtract
@@ -1007,6 +1440,9 @@ impl Evaluator for SimpleEvaluator {
                 };
                 Ok(result_value)
             }
+            Operation::SetIntersection(_) if self.constant_time_strict => Err(runtime_error!(
+                "Set intersection can't be evaluated in the constant-time strict mode"
+            )),
             Operation::SetIntersection(headers) => {
                 let dependencies = node.get_node_dependencies();
                 let set0 = dependencies_values[0].clone();
@@ -1222,9 +1658,109 @@ impl Evaluator for SimpleEvaluator {
                 }
                 Value::from_flattened_array(&res_entries, res_type.get_scalar_type())
             }
+            Operation::Concatenate(axis) => {
+                // Every array is a sequence of blocks of elements with the same indices in the dimensions before the axis;
+                // blocks of the result are concatenations of the respective blocks of the arrays.
+                let dependencies = node.get_node_dependencies();
+                let res_type = node.get_type()?;
+                let num_blocks: u64 = res_type.get_shape()[..axis as usize].iter().product();
+                let mut dependencies_entries = vec![];
+                for (dependency, value) in dependencies.iter().zip(dependencies_values.iter()) {
+                    let t = dependency.get_type()?;
+                    let block_size: u64 = t.get_shape()[axis as usize..].iter().product();
+                    let entries = value.to_flattened_array_u64(t)?;
+                    dependencies_entries.push((entries, block_size as usize));
+                }
+                let mut res_entries = vec![];
+                for block in 0..num_blocks as usize {
+                    for (entries, block_size) in &dependencies_entries {
+                        res_entries.extend_from_slice(
+                            &entries[block * block_size..(block + 1) * block_size],
+                        );
+                    }
+                }
+                Value::from_flattened_array(&res_entries, res_type.get_scalar_type())
+            }
+            Operation::Split(axis, parts) => {
+                let dependency = node.get_node_dependencies()[0].clone();
+                let t = dependency.get_type()?;
+                let shape = t.get_shape();
+                let st = t.get_scalar_type();
+                let entries = dependencies_values[0].to_flattened_array_u64(t)?;
+                let num_blocks: u64 = shape[..axis as usize].iter().product();
+                let element_size: u64 = shape[axis as usize + 1..].iter().product();
+                let block_size = (shape[axis as usize] * element_size) as usize;
+                let mut result = vec![];
+                let mut offset = 0;
+                for part in parts {
+                    let part_size = (part * element_size) as usize;
+                    let mut part_entries = vec![];
+                    for block in 0..num_blocks as usize {
+                        let start = block * block_size + offset;
+                        part_entries.extend_from_slice(&entries[start..start + part_size]);
+                    }
+                    result.push(Value::from_flattened_array(&part_entries, st.clone())?);
+                    offset += part_size;
+                }
+                Ok(Value::from_vector(result))
+            }
+            Operation::CumSum(axis) => {
+                let input_t = node.get_node_dependencies()[0].get_type()?;
+                let st = input_t.get_scalar_type();
+                let shape = input_t.get_shape();
+                if st.size_in_bits() == 128 {
+                    let mut entries = dependencies_values[0].to_flattened_array_u128(input_t)?;
+                    cumsum_entries(&mut entries, &shape, axis, |a, b| a.wrapping_add(b));
+                    Value::from_flattened_array_u128(&entries, st)
+                } else {
+                    let mut entries = dependencies_values[0].to_flattened_array_u64(input_t)?;
+                    let modulus = get_u64_modulus(&st)?;
+                    cumsum_entries(&mut entries, &shape, axis, |a, b| add_u64(a, b, modulus));
+                    Value::from_flattened_array(&entries, st)
+                }
+            }
+            Operation::BroadcastTo(_) | Operation::Tile(_) => {
+                // Indices of the input are taken modulo its dimensions, which both broadcasts dimensions equal to 1 and repeats tiled dimensions
+                let dependency_type = node.get_node_dependencies()[0].get_type()?;
+                let st = dependency_type.get_scalar_type();
+                let entries = if dependency_type.is_scalar() {
+                    vec![dependencies_values[0].to_u64(st.clone())?]
+                } else {
+                    dependencies_values[0].to_flattened_array_u64(dependency_type.clone())?
+                };
+                let result = broadcast_to_shape(
+                    &entries,
+                    &dependency_type.get_dimensions(),
+                    &node.get_type()?.get_shape(),
+                );
+                Value::from_flattened_array(&result, st)
+            }
             Operation::A2B | Operation::B2A(_) | Operation::NOP => {
                 Ok(dependencies_values[0].clone())
             }
+            Operation::ConvertRing(st) => {
+                let dependency_type = node.get_node_dependencies()[0].get_type()?;
+                let input_st = dependency_type.get_scalar_type();
+                let dependency_value = dependencies_values[0].clone();
+                let mut entries = if dependency_type.is_scalar() {
+                    vec![dependency_value.to_u64(input_st.clone())?]
+                } else {
+                    dependency_value.to_flattened_array_u64(dependency_type.clone())?
+                };
+                // Entries are stored in the two's complement form, so signed entries are extended by their sign bit.
+                // Excess bits are dropped when the result is converted to bytes.
+                let extra_bits = 64 - input_st.size_in_bits();
+                if input_st.get_signed() && extra_bits > 0 {
+                    for entry in &mut entries {
+                        *entry = (((*entry << extra_bits) as i64) >> extra_bits) as u64;
+                    }
+                }
+                if dependency_type.is_scalar() {
+                    Value::from_scalar(entries[0], st)
+                } else {
+                    Value::from_flattened_array(&entries, st)
+                }
+            }
             Operation::ArrayToVector => {
                 let dependency = node.get_node_dependencies()[0].clone();
                 let t = dependency.get_type()?;
@@ -1301,17 +1837,25 @@ impl Evaluator for SimpleEvaluator {
                 let dependency = node.get_node_dependencies()[0].clone();
                 let t = dependency.get_type()?;
                 let values = dependencies_values[0].to_flattened_array_u64(t.clone())?;
+                if self.constant_time_strict {
+                    let result = constant_time::inverse_permutation(&values)?;
+                    return Value::from_flattened_array(&result, t.get_scalar_type());
+                }
                 let mut values_without_dup = values.clone();
                 values_without_dup.sort_unstable();
                 values_without_dup.dedup();
                 if values.len() != values_without_dup.len() {
-                    panic!("Input array doesn't contain a valid permutation");
+                    return Err(runtime_error!(
+                        "Input array doesn't contain a valid permutation"
+                    ));
                 }
                 let mut result = vec![0u64; values.len()];
                 for i in 0..values.len() {
                     let value = values[i] as usize;
                     if value >= values.len() {
-                        panic!("Input array doesn't contain a valid permutation");
+                        return Err(runtime_error!(
+                            "Input array doesn't contain a valid permutation"
+                        ));
                     }
                     result[value] = i as u64;
                 }
@@ -1348,26 +1892,35 @@ impl Evaluator for SimpleEvaluator {
                 } else {
                     dependency_value.to_flattened_array_u64(dependency_type.clone())?
                 };
-                for entry in &mut entries {
-                    if scalar_type.get_signed() {
-                        match scalar_type.get_modulus() {
-                            Some(modulus) => {
-                                let mut val = *entry as i64;
-                                if val >= (modulus / 2) as i64 {
-                                    val -= modulus as i64;
+                if self.constant_time_strict {
+                    constant_time::truncate(
+                        &mut entries,
+                        scale,
+                        scalar_type.get_signed(),
+                        get_u64_modulus(&scalar_type)?,
+                    );
+                } else {
+                    for entry in &mut entries {
+                        if scalar_type.get_signed() {
+                            match get_u64_modulus(&scalar_type)? {
+                                Some(modulus) => {
+                                    let mut val = *entry as i64;
+                                    if val >= (modulus / 2) as i64 {
+                                        val -= modulus as i64;
+                                    }
+                                    let mut res = val / (scale as i64);
+                                    if res < 0 {
+                                        res += modulus as i64;
+                                    }
+                                    *entry = res as u64;
                                 }
-                                let mut res = val / (scale as i64);
-                                if res < 0 {
-                                    res += modulus as i64;
+                                None => {
+                                    *entry = ((*entry as i64) / (scale as i64)) as u64;
                                 }
-                                *entry = res as u64;
-                            }
-                            None => {
-                                *entry = ((*entry as i64) / (scale as i64)) as u64;
                             }
+                        } else {
+                            *entry /= scale;
                         }
-                    } else {
-                        *entry /= scale;
                     }
                 }
                 let new_value = if dependency_type.is_scalar() {
@@ -1423,14 +1976,22 @@ impl Evaluator for SimpleEvaluator {
                     result_type,
                 )
             }
-            Operation::Random(t) => {
-                let new_value = self.prng.get_random_value(t)?;
+            Operation::Random(t) | Operation::RandomBeacon(t) => {
+                let mut node_prng = self.get_node_prng(&node)?;
+                let prng = node_prng.as_mut().unwrap_or(&mut self.prng);
+                let new_value = prng.get_random_value(t)?;
                 Ok(new_value)
             }
             Operation::RandomPermutation(n) => {
                 let mut result_array: Vec<u64> = (0..n).collect();
 
-                shuffle_array(&mut result_array, &mut self.prng)?;
+                let mut node_prng = self.get_node_prng(&node)?;
+                let prng = node_prng.as_mut().unwrap_or(&mut self.prng);
+                if self.constant_time_strict {
+                    constant_time::shuffle_array(&mut result_array, n, prng)?;
+                } else {
+                    shuffle_array(&mut result_array, prng)?;
+                }
 
                 Value::from_flattened_array(&result_array, UINT64)
             }
@@ -1446,6 +2007,18 @@ impl Evaluator for SimpleEvaluator {
                     .take(input_shape.len() - 1)
                     .product::<u64>() as usize;
                 let map_size = input_shape[input_shape.len() - 1] as usize;
+                let mut node_prng = self.get_node_prng(&node)?;
+                let prng = node_prng.as_mut().unwrap_or(&mut self.prng);
+                if self.constant_time_strict {
+                    let [perm1_array, duplication_map, duplication_bits, perm2_array] =
+                        constant_time::decompose_switching_map(&input_array, map_size, n, prng)?;
+                    return switching_map_decomposition_value(
+                        &perm1_array,
+                        &duplication_map,
+                        &duplication_bits,
+                        &perm2_array,
+                    );
+                }
                 // Permutation with deletion map
                 let mut perm1_array = vec![];
                 // Duplication map
@@ -1476,7 +2049,7 @@ impl Evaluator for SimpleEvaluator {
                     for i in 0..map_size {
                         let input_index = input_array[map_start + i];
                         if input_index >= n {
-                            panic!("Switching map has incorrect indices");
+                            return Err(runtime_error!("Switching map has incorrect indices"));
                         }
                         if let Some(v) = switch_indexes.get_mut(&input_index) {
                             v.push(i as u64);
@@ -1495,7 +2068,7 @@ impl Evaluator for SimpleEvaluator {
                         }
                     }
                     // Randomize the order of remaining indices
-                    shuffle_array(&mut missing_indices, &mut self.prng)?;
+                    shuffle_array(&mut missing_indices, prng)?;
 
                     // Indices that didn't appear in the switching map
                     let mut missing_indices_index = 0;
@@ -1519,7 +2092,7 @@ impl Evaluator for SimpleEvaluator {
                     // Invert permutation that was used for grouping identical indices of the input switching map
                     let mut little_perm2_array = vec![0; map_size];
                     for i in 0..map_size {
-                        little_perm2_array[perm_from_switch_to_perm1[i] as usize] = i;
+                        little_perm2_array[perm_from_switch_to_perm1[i] as usize] = i as u64;
                     }
 
                     perm1_array.extend_from_slice(&little_perm1_array);
@@ -1527,16 +2100,12 @@ impl Evaluator for SimpleEvaluator {
                     duplication_bits.extend_from_slice(&little_duplication_bits);
                     perm2_array.extend_from_slice(&little_perm2_array);
                 }
-
-                let perm1_val = Value::from_flattened_array(&perm1_array, UINT64)?;
-                let dup_map_val = Value::from_flattened_array(&duplication_map, UINT64)?;
-                let dup_bits_val = Value::from_flattened_array(&duplication_bits, BIT)?;
-                let perm2_val = Value::from_flattened_array(&perm2_array, UINT64)?;
-                Ok(Value::from_vector(vec![
-                    perm1_val,
-                    Value::from_vector(vec![dup_map_val, dup_bits_val]),
-                    perm2_val,
-                ]))
+                switching_map_decomposition_value(
+                    &perm1_array,
+                    &duplication_map,
+                    &duplication_bits,
+                    &perm2_array,
+                )
             }
             Operation::CuckooToPermutation => {
                 let input_node = node.get_node_dependencies()[0].clone();
@@ -1552,6 +2121,13 @@ impl Evaluator for SimpleEvaluator {
                 let table_size = input_shape[input_shape.len() - 1];
                 let mut result_array = vec![0; (num_cuckoo_tables * table_size) as usize];
 
+                let mut node_prng = self.get_node_prng(&node)?;
+                let prng = node_prng.as_mut().unwrap_or(&mut self.prng);
+                if self.constant_time_strict {
+                    let result_array =
+                        constant_time::cuckoo_to_permutation(&input_array, table_size, prng)?;
+                    return Value::from_flattened_array(&result_array, UINT64);
+                }
                 for table_i in 0..num_cuckoo_tables as usize {
                     let mut num_dummies = 0;
                     let table_start = table_i * table_size as usize;
@@ -1566,10 +2142,10 @@ impl Evaluator for SimpleEvaluator {
                     input_wout_dup.dedup();
                     if num_dummies > 1 {
                         if input_wout_dup.len() as u64 + num_dummies - 1 != table_size {
-                            panic!("Input array contains duplicate indices");
+                            return Err(runtime_error!("Input array contains duplicate indices"));
                         }
                     } else if input_wout_dup.len() as u64 != table_size {
-                        panic!("Input array contains duplicate indices");
+                        return Err(runtime_error!("Input array contains duplicate indices"));
                     }
                     let mut remaining_indices: Vec<u64> =
                         (table_size - num_dummies..table_size).collect();
@@ -1578,14 +2154,14 @@ impl Evaluator for SimpleEvaluator {
                         remaining_indices.push(CUCKOO_DUMMY_ELEMENT);
                     }
                     // Shuffle remaining indices
-                    shuffle_array(&mut remaining_indices, &mut self.prng)?;
+                    shuffle_array(&mut remaining_indices, prng)?;
                     let mut current_index = 0;
                     for i in 0..table_size as usize {
                         // Check that non-dummy elements of the Cuckoo table are correct indices of an array of length `table_size - num_dummies`.
                         if input_array[table_start + i] >= table_size - num_dummies
                             && input_array[table_start + i] != CUCKOO_DUMMY_ELEMENT
                         {
-                            panic!("Indices are incorrect");
+                            return Err(runtime_error!("Indices are incorrect"));
                         }
                         // Compute the bit input element == CUCKOO_DUMMY_ELEMENT using the fact that CUCKOO_DUMMY_ELEMENT = u64::MAX
                         let is_dummy = input_array[table_start + i] / CUCKOO_DUMMY_ELEMENT;
@@ -1624,7 +2200,7 @@ impl Evaluator for SimpleEvaluator {
                 };
                 Ok(new_value)
             }
-            Operation::CuckooHash => {
+            Operation::CuckooHash(stash_size) => {
                 let input_value = dependencies_values[0].clone();
                 let hash_matrices_value = dependencies_values[1].clone();
 
@@ -1637,7 +2213,22 @@ impl Evaluator for SimpleEvaluator {
                     input_value,
                     hash_matrices_type,
                     hash_matrices_value,
+                    stash_size,
                     result_type,
+                    self.constant_time_strict,
+                )
+            }
+            Operation::BloomFilter => {
+                let input_type = node.get_node_dependencies()[0].get_type()?;
+                let hash_matrices_type = node.get_node_dependencies()[1].get_type()?;
+
+                evaluate_bloom_filter(
+                    input_type,
+                    dependencies_values[0].clone(),
+                    hash_matrices_type,
+                    dependencies_values[1].clone(),
+                    node.get_type()?,
+                    self.constant_time_strict,
                 )
             }
             Operation::SegmentCumSum => {
@@ -1660,6 +2251,16 @@ impl Evaluator for SimpleEvaluator {
 
                 let row_size = first_row_t.get_dimensions().iter().product::<u64>() as usize;
 
+                if self.constant_time_strict {
+                    let result_array = constant_time::segment_cumsum(
+                        &input_array,
+                        &binary_array,
+                        first_row,
+                        get_u64_modulus(&input_st)?,
+                    )?;
+                    return Value::from_flattened_array(&result_array, input_st);
+                }
+
                 let mut result_array = first_row;
                 for (i, b) in binary_array.iter().enumerate() {
                     let mut result_row = if *b == 0 {
@@ -1668,7 +2269,7 @@ impl Evaluator for SimpleEvaluator {
                         // Extract an input row and sum it with the previous output row
                         let previous_row = &result_array[i * row_size..(i + 1) * row_size];
                         let input_row = &input_array[i * row_size..(i + 1) * row_size];
-                        add_vectors_u64(input_row, previous_row, input_st.get_modulus())?
+                        add_vectors_u64(input_row, previous_row, get_u64_modulus(&input_st)?)?
                     };
                     result_array.append(&mut result_row);
                 }
@@ -1688,6 +2289,19 @@ impl Evaluator for SimpleEvaluator {
 
                 let input_shape = input_t.get_shape();
 
+                if self.constant_time_strict {
+                    let output_entries = constant_time::gather(
+                        &input_entries,
+                        &input_shape,
+                        &indices_entries,
+                        axis,
+                    )?;
+                    return Value::from_flattened_array(
+                        &output_entries,
+                        node.get_type()?.get_scalar_type(),
+                    );
+                }
+
                 // Number of subarrays whose indices are selected
                 let num_arrays = input_shape[..axis as usize]
                     .to_vec()
@@ -1703,7 +2317,7 @@ impl Evaluator for SimpleEvaluator {
                 for array_i in 0..num_arrays {
                     for index_entry in indices_entries.iter() {
                         if *index_entry >= input_shape[axis as usize] {
-                            panic!("Incorrect index");
+                            return Err(runtime_error!("Incorrect index"));
                         }
                         let input_flat_index =
                             (array_i * input_shape[axis as usize] + index_entry) * row_size;
@@ -1717,6 +2331,58 @@ impl Evaluator for SimpleEvaluator {
                 let result_type = node.get_type()?;
                 Value::from_flattened_array(&output_entries, result_type.get_scalar_type())
             }
+            Operation::Scatter(axis) => {
+                let dependencies = node.get_node_dependencies();
+                let input_t = dependencies[0].get_type()?;
+                let mut output_entries =
+                    dependencies_values[0].to_flattened_array_u64(input_t.clone())?;
+                let indices_entries =
+                    dependencies_values[1].to_flattened_array_u64(dependencies[1].get_type()?)?;
+                let updates_entries =
+                    dependencies_values[2].to_flattened_array_u64(dependencies[2].get_type()?)?;
+
+                let input_shape = input_t.get_shape();
+
+                if self.constant_time_strict {
+                    constant_time::scatter(
+                        &mut output_entries,
+                        &input_shape,
+                        &indices_entries,
+                        &updates_entries,
+                        axis,
+                    )?;
+                    return Value::from_flattened_array(&output_entries, input_t.get_scalar_type());
+                }
+
+                let axis_size = input_shape[axis as usize];
+                let mut is_written = vec![false; axis_size as usize];
+                for index_entry in indices_entries.iter() {
+                    if *index_entry >= axis_size || is_written[*index_entry as usize] {
+                        return Err(runtime_error!("Indices must be unique and in range"));
+                    }
+                    is_written[*index_entry as usize] = true;
+                }
+
+                // Number of subarrays whose rows are written
+                let num_arrays = input_shape[..axis as usize].iter().product::<u64>() as usize;
+                // Number of elements in each row indexed by the indices
+                let row_size = input_shape[(axis + 1) as usize..].iter().product::<u64>() as usize;
+
+                let num_indices = indices_entries.len();
+                for array_i in 0..num_arrays {
+                    for (update_i, index_entry) in indices_entries.iter().enumerate() {
+                        let output_flat_index =
+                            (array_i * axis_size as usize + *index_entry as usize) * row_size;
+                        let update_flat_index = (array_i * num_indices + update_i) * row_size;
+                        output_entries[output_flat_index..output_flat_index + row_size]
+                            .copy_from_slice(
+                                &updates_entries[update_flat_index..update_flat_index + row_size],
+                            );
+                    }
+                }
+
+                Value::from_flattened_array(&output_entries, input_t.get_scalar_type())
+            }
             _ => Err(runtime_error!("Not implemented")),
         }
     }
@@ -1724,61 +2390,473 @@ impl Evaluator for SimpleEvaluator {
 
 #[cfg(test)]
 mod tests {
-    use std::panic::{catch_unwind, AssertUnwindSafe};
 
     use ndarray::array;
 
     use crate::{
         data_types::{
-            named_tuple_type, scalar_type, tuple_type, vector_type, ArrayShape, ScalarType, INT32,
-            UINT32, UINT64, UINT8,
+            named_tuple_type, scalar_type, tuple_type, vector_type, ArrayShape, ScalarType, INT128,
+            INT16, INT32, INT64, INT8, UINT32, UINT64, UINT8,
         },
-        evaluators::{evaluate_simple_evaluator, random_evaluate},
-        graphs::create_context,
+        evaluators::{
+            evaluate_batch_parallel, evaluate_simple_evaluator,
+            evaluate_simple_evaluator_with_retries, random_evaluate,
+        },
+        graphs::{create_context, Context},
         random::chi_statistics,
     };
 
     use super::*;
+    use ciphercore_utils::errors::ErrorWithBody;
 
     #[test]
-    fn test_prf() {
-        let helper = |iv: u64, t: Type| -> Result<()> {
+    fn test_iterate_fixed() {
+        || -> Result<()> {
             let c = create_context()?;
+            let t = array_type(vec![2], INT32);
+            let step = c.create_graph()?;
+            let x = step.input(t.clone())?;
+            let i = step.input(scalar_type(UINT64))?;
+            let new_x = x.multiply(x.clone())?.add(x)?;
+            step.create_tuple(vec![new_x, i])?.set_as_output()?;
+            step.finalize()?;
             let g = c.create_graph()?;
-            let i1 = g.random(array_type(vec![128], BIT))?;
-            let i2 = g.random(array_type(vec![128], BIT))?;
-            let p1 = g.prf(i1.clone(), iv, t.clone())?;
-            let p2 = g.prf(i2, iv, t.clone())?;
-            let p3 = g.prf(i1, iv, t.clone())?;
-            let o = g.create_vector(t.clone(), vec![p1, p2, p3])?;
-            g.set_output_node(o)?;
-            g.finalize()?;
-            c.set_main_graph(g)?;
+            let x = g.input(t.clone())?;
+            let iterations = g.iterate_fixed(step.clone(), x.clone(), 3)?;
+            iterations.set_as_output()?;
+            assert!(g.iterate_fixed(step, x, 0).is_err());
+            g.finalize()?.set_as_main()?;
             c.finalize()?;
-            let mut evaluator = SimpleEvaluator {
-                prng: PRNG::new(None)?,
-                prfs: HashMap::new(),
-            };
-            let v = evaluator.evaluate_context(c, Vec::new())?;
-            let ot = vector_type(3, t.clone());
-            assert_eq!(evaluator.prfs.len(), 2);
-            assert!(v.check_type(ot)?);
+            let input = Value::from_flattened_array(&[1, -1], INT32)?;
+            let result = random_evaluate(g, vec![input.clone()])?.to_vector()?;
+            // 1 -> 2 -> 6 -> 42, -1 -> 0 -> 0 -> 0
+            assert_eq!(result[0].to_flattened_array_i32(t.clone())?, vec![42, 0]);
+            let indices = result[1].to_vector()?;
+            assert_eq!(indices.len(), 3);
+            assert_eq!(indices[2].to_u64(UINT64)?, 2);
+            // Iterate nodes can also be evaluated on their own
+            let counter = Value::from_vector(
+                (0..3)
+                    .map(|i| Value::from_scalar(i, UINT64))
+                    .collect::<Result<_>>()?,
+            );
+            let mut evaluator = SimpleEvaluator::new(None)?;
+            evaluator.preprocess(c.clone())?;
+            let state = evaluator.evaluate_node(iterations, vec![input, counter])?;
+            assert_eq!(
+                state.to_vector()?[0].to_flattened_array_i32(t)?,
+                vec![42, 0]
+            );
             Ok(())
-        };
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_8_bit_arithmetic() {
         || -> Result<()> {
-            helper(0, scalar_type(BIT))?;
-            helper(1, scalar_type(UINT8))?;
-            helper(2, scalar_type(INT32))?;
-            helper(3, array_type(vec![2, 5], BIT))?;
-            helper(4, array_type(vec![2, 5], UINT8))?;
-            helper(5, array_type(vec![2, 5], INT32))?;
-            helper(6, tuple_type(vec![scalar_type(BIT), scalar_type(INT32)]))?;
-            helper(
-                7,
-                tuple_type(vec![
-                    vector_type(3, scalar_type(BIT)),
-                    vector_type(5, scalar_type(BIT)),
-                    scalar_type(BIT),
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let a = g.input(array_type(vec![2, 2], INT8))?;
+            let b = g.input(array_type(vec![2], INT8))?;
+            let sum = a.add(b.clone())?;
+            let difference = a.subtract(b.clone())?;
+            let product = a.multiply(b)?;
+            g.create_tuple(vec![sum, difference, product])?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let result = random_evaluate(
+                g,
+                vec![
+                    Value::from_flattened_array(&[100, -100, 3, 4], INT8)?,
+                    Value::from_flattened_array(&[100, 30], INT8)?,
+                ],
+            )?
+            .to_vector()?;
+            let t = array_type(vec![2, 2], INT8);
+            assert_eq!(
+                result[0].to_flattened_array_i8(t.clone())?,
+                vec![-56, -70, 103, 34]
+            );
+            assert_eq!(
+                result[1].to_flattened_array_i8(t.clone())?,
+                vec![0, 126, -97, -26]
+            );
+            assert_eq!(result[2].to_flattened_array_i8(t)?, vec![16, 72, 44, 120]);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_bit_arithmetic() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![3, 25], BIT);
+            let a = g.input(t.clone())?;
+            let b = g.input(t.clone())?;
+            let s = g.input(scalar_type(BIT))?;
+            let row = g.input(array_type(vec![25], BIT))?;
+            g.create_tuple(vec![
+                a.add(b.clone())?,
+                a.multiply(b)?,
+                a.add(s.clone())?,
+                a.multiply(s)?,
+                a.subtract(row)?,
+            ])?
+            .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let a_bits: Vec<u64> = (0..75).map(|i| (i * 7 % 3 == 0) as u64).collect();
+            let b_bits: Vec<u64> = (0..75).map(|i| (i % 5 < 2) as u64).collect();
+            let row_bits: Vec<u64> = (0..25).map(|i| (i % 2) as u64).collect();
+            let result = random_evaluate(
+                g,
+                vec![
+                    Value::from_flattened_array(&a_bits, BIT)?,
+                    Value::from_flattened_array(&b_bits, BIT)?,
+                    Value::from_scalar(1, BIT)?,
+                    Value::from_flattened_array(&row_bits, BIT)?,
+                ],
+            )?
+            .to_vector()?;
+            let expected_xor: Vec<u64> = a_bits.iter().zip(&b_bits).map(|(x, y)| x ^ y).collect();
+            let expected_and: Vec<u64> = a_bits.iter().zip(&b_bits).map(|(x, y)| x & y).collect();
+            let expected_not: Vec<u64> = a_bits.iter().map(|x| x ^ 1).collect();
+            let expected_row: Vec<u64> = a_bits
+                .iter()
+                .enumerate()
+                .map(|(i, x)| x ^ row_bits[i % 25])
+                .collect();
+            assert_eq!(result[0].to_flattened_array_u64(t.clone())?, expected_xor);
+            assert_eq!(result[1].to_flattened_array_u64(t.clone())?, expected_and);
+            assert_eq!(result[2], Value::from_flattened_array(&expected_not, BIT)?);
+            assert_eq!(result[3], Value::from_flattened_array(&a_bits, BIT)?);
+            assert_eq!(result[4].to_flattened_array_u64(t)?, expected_row);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_convert_ring() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let a = g.input(array_type(vec![4], INT64))?;
+            let b = g.input(array_type(vec![4], INT16))?;
+            let u = g.input(scalar_type(UINT8))?;
+            g.create_tuple(vec![
+                a.convert_ring(INT16)?,
+                a.convert_ring(UINT32)?,
+                b.convert_ring(INT64)?,
+                b.convert_ring(UINT32)?,
+                u.convert_ring(INT32)?,
+            ])?
+            .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let result = random_evaluate(
+                g,
+                vec![
+                    Value::from_flattened_array(&[-1i64, 70000, -70000, 1 << 40], INT64)?,
+                    Value::from_flattened_array(&[-1i16, 12345, -32768, 32767], INT16)?,
+                    Value::from_scalar(200, UINT8)?,
+                ],
+            )?
+            .to_vector()?;
+            assert_eq!(
+                result[0].to_flattened_array_i16(array_type(vec![4], INT16))?,
+                vec![-1, 4464, -4464, 0]
+            );
+            assert_eq!(
+                result[1].to_flattened_array_u32(array_type(vec![4], UINT32))?,
+                vec![u32::MAX, 70000, (-70000i32) as u32, 0]
+            );
+            assert_eq!(
+                result[2].to_flattened_array_i64(array_type(vec![4], INT64))?,
+                vec![-1, 12345, -32768, 32767]
+            );
+            assert_eq!(
+                result[3].to_flattened_array_u32(array_type(vec![4], UINT32))?,
+                vec![u32::MAX, 12345, (-32768i32) as u32, 32767]
+            );
+            assert_eq!(result[4].to_i32(INT32)?, 200);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_concatenate_split() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let a = g.input(array_type(vec![2, 3], INT32))?;
+            let b = g.input(array_type(vec![2, 1], INT32))?;
+            let concatenated = g.concatenate(vec![a.clone(), b.clone(), a.clone()], 1)?;
+            let rows = g.concatenate(vec![a.clone(), a], 0)?;
+            let parts = concatenated.split(1, vec![1, 4, 2])?;
+            g.create_tuple(vec![concatenated, rows, parts])?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let result = random_evaluate(
+                g,
+                vec![
+                    Value::from_flattened_array(&[1, 2, 3, 4, 5, 6], INT32)?,
+                    Value::from_flattened_array(&[7, 8], INT32)?,
+                ],
+            )?
+            .to_vector()?;
+            assert_eq!(
+                result[0].to_flattened_array_u64(array_type(vec![2, 7], INT32))?,
+                vec![1, 2, 3, 7, 1, 2, 3, 4, 5, 6, 8, 4, 5, 6]
+            );
+            assert_eq!(
+                result[1].to_flattened_array_u64(array_type(vec![4, 3], INT32))?,
+                vec![1, 2, 3, 4, 5, 6, 1, 2, 3, 4, 5, 6]
+            );
+            let parts = result[2].to_vector()?;
+            assert_eq!(
+                parts[0].to_flattened_array_u64(array_type(vec![2, 1], INT32))?,
+                vec![1, 4]
+            );
+            assert_eq!(
+                parts[1].to_flattened_array_u64(array_type(vec![2, 4], INT32))?,
+                vec![2, 3, 7, 1, 5, 6, 8, 4]
+            );
+            assert_eq!(
+                parts[2].to_flattened_array_u64(array_type(vec![2, 2], INT32))?,
+                vec![2, 3, 5, 6]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_broadcast_to_tile() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let a = g.input(array_type(vec![2, 1], INT32))?;
+            let s = g.input(scalar_type(INT32))?;
+            g.create_tuple(vec![
+                a.broadcast_to(vec![2, 2, 3])?,
+                s.broadcast_to(vec![2])?,
+                a.tile(vec![2, 3])?,
+                a.tile(vec![2])?,
+                s.tile(vec![1, 3])?,
+            ])?
+            .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let result = random_evaluate(
+                g,
+                vec![
+                    Value::from_flattened_array(&[1, 2], INT32)?,
+                    Value::from_scalar(5, INT32)?,
+                ],
+            )?
+            .to_vector()?;
+            assert_eq!(
+                result[0].to_flattened_array_u64(array_type(vec![2, 2, 3], INT32))?,
+                vec![1, 1, 1, 2, 2, 2, 1, 1, 1, 2, 2, 2]
+            );
+            assert_eq!(
+                result[1].to_flattened_array_u64(array_type(vec![2], INT32))?,
+                vec![5, 5]
+            );
+            assert_eq!(
+                result[2].to_flattened_array_u64(array_type(vec![4, 3], INT32))?,
+                vec![1, 1, 1, 2, 2, 2, 1, 1, 1, 2, 2, 2]
+            );
+            assert_eq!(
+                result[3].to_flattened_array_u64(array_type(vec![2, 2], INT32))?,
+                vec![1, 1, 2, 2]
+            );
+            assert_eq!(
+                result[4].to_flattened_array_u64(array_type(vec![1, 3], INT32))?,
+                vec![5, 5, 5]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_cumsum() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let a = g.input(array_type(vec![2, 3], INT32))?;
+            let b = g.input(array_type(vec![5], BIT))?;
+            let u = g.input(array_type(vec![3], UINT8))?;
+            let l = g.input(array_type(vec![2], INT128))?;
+            g.create_tuple(vec![
+                a.cumsum(0)?,
+                a.cumsum(1)?,
+                b.cumsum(0)?,
+                u.cumsum(0)?,
+                l.cumsum(0)?,
+            ])?
+            .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let result = random_evaluate(
+                g,
+                vec![
+                    Value::from_flattened_array(&[1, -2, 3, 4, 5, -6], INT32)?,
+                    Value::from_flattened_array(&[1, 0, 1, 1, 0], BIT)?,
+                    Value::from_flattened_array(&[200, 100, 7], UINT8)?,
+                    Value::from_flattened_array_u128(&[u128::MAX, 3], INT128)?,
+                ],
+            )?
+            .to_vector()?;
+            assert_eq!(
+                result[0].to_flattened_array_i32(array_type(vec![2, 3], INT32))?,
+                vec![1, -2, 3, 5, 3, -3]
+            );
+            assert_eq!(
+                result[1].to_flattened_array_i32(array_type(vec![2, 3], INT32))?,
+                vec![1, -1, 2, 4, 9, 3]
+            );
+            assert_eq!(
+                result[2].to_flattened_array_u64(array_type(vec![5], BIT))?,
+                vec![1, 1, 0, 1, 1]
+            );
+            assert_eq!(
+                result[3].to_flattened_array_u64(array_type(vec![3], UINT8))?,
+                vec![200, 44, 51]
+            );
+            assert_eq!(
+                result[4].to_flattened_array_u128(array_type(vec![2], INT128))?,
+                vec![u128::MAX, 2]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_128_bit_arithmetic() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![3], INT128);
+            let a = g.input(t.clone())?;
+            let b = g.input(scalar_type(INT128))?;
+            let product = a.multiply(b.clone())?;
+            let total = product.add(a.clone())?.subtract(b)?.sum(vec![0])?;
+            g.create_tuple(vec![product, total])?.set_as_output()?;
+            assert!(a.permute_axes(vec![0]).is_err());
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let big = i64::MAX as i128;
+            let result = random_evaluate(
+                g,
+                vec![
+                    Value::from_flattened_array(&[i64::MAX, -1, 2], INT128)?,
+                    Value::from_flattened_array_u128(&[big as u128 * 2], INT128)?,
+                ],
+            )?
+            .to_vector()?;
+            assert_eq!(
+                result[0].to_flattened_array_i128(t)?,
+                vec![big * big * 2, -big * 2, big * 4]
+            );
+            // The first product doesn't fit into 64 bits
+            let expected = big * big * 2 - big * 3 + 1;
+            assert_eq!(result[1].to_i128(INT128)?, expected);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_if_else() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let t = array_type(vec![2], INT32);
+            let double = c.create_graph()?;
+            let x = double.input(t.clone())?;
+            x.add(x.clone())?.set_as_output()?;
+            double.finalize()?;
+            let square = c.create_graph()?;
+            let x = square.input(t.clone())?;
+            x.multiply(x.clone())?.set_as_output()?;
+            square.finalize()?;
+            let identity = c.create_graph()?;
+            let x = identity.input(scalar_type(INT32))?;
+            x.set_as_output()?;
+            identity.finalize()?;
+            let g = c.create_graph()?;
+            let flag = g.input(scalar_type(BIT))?;
+            let x = g.input(t.clone())?;
+            assert!(g
+                .if_else(x.clone(), double.clone(), square.clone(), vec![x.clone()])
+                .is_err());
+            assert!(g
+                .if_else(flag.clone(), double.clone(), identity, vec![x.clone()])
+                .is_err());
+            g.if_else(flag, double, square, vec![x])?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let x = Value::from_flattened_array(&[3, -5], INT32)?;
+            let result = random_evaluate(g.clone(), vec![Value::from_scalar(1, BIT)?, x.clone()])?;
+            assert_eq!(result.to_flattened_array_i32(t.clone())?, vec![6, -10]);
+            let result = random_evaluate(g, vec![Value::from_scalar(0, BIT)?, x])?;
+            assert_eq!(result.to_flattened_array_i32(t)?, vec![9, 25]);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_prf() {
+        let helper = |iv: u64, t: Type| -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let i1 = g.random(array_type(vec![128], BIT))?;
+            let i2 = g.random(array_type(vec![128], BIT))?;
+            let p1 = g.prf(i1.clone(), iv, t.clone())?;
+            let p2 = g.prf(i2, iv, t.clone())?;
+            let p3 = g.prf(i1, iv, t.clone())?;
+            let o = g.create_vector(t.clone(), vec![p1, p2, p3])?;
+            g.set_output_node(o)?;
+            g.finalize()?;
+            c.set_main_graph(g)?;
+            c.finalize()?;
+            let mut evaluator = SimpleEvaluator {
+                prng: PRNG::new(None)?,
+                scope_seeds: None,
+                prfs: HashMap::new(),
+                memory_usage: None,
+                constant_time_strict: false,
+            };
+            let v = evaluator.evaluate_context(c, Vec::new())?;
+            let ot = vector_type(3, t.clone());
+            assert_eq!(evaluator.prfs.len(), 2);
+            assert!(v.check_type(ot)?);
+            Ok(())
+        };
+        || -> Result<()> {
+            helper(0, scalar_type(BIT))?;
+            helper(1, scalar_type(UINT8))?;
+            helper(2, scalar_type(INT32))?;
+            helper(3, array_type(vec![2, 5], BIT))?;
+            helper(4, array_type(vec![2, 5], UINT8))?;
+            helper(5, array_type(vec![2, 5], INT32))?;
+            helper(6, tuple_type(vec![scalar_type(BIT), scalar_type(INT32)]))?;
+            helper(
+                7,
+                tuple_type(vec![
+                    vector_type(3, scalar_type(BIT)),
+                    vector_type(5, scalar_type(BIT)),
+                    scalar_type(BIT),
                     scalar_type(INT32),
                 ]),
             )?;
@@ -1797,12 +2875,21 @@ mod tests {
         input_shape: ArrayShape,
         hash_shape: ArrayShape,
         inputs: Vec<Value>,
+    ) -> Result<Vec<u64>> {
+        cuckoo_with_stash_helper(input_shape, hash_shape, 0, inputs)
+    }
+
+    fn cuckoo_with_stash_helper(
+        input_shape: ArrayShape,
+        hash_shape: ArrayShape,
+        stash_size: u64,
+        inputs: Vec<Value>,
     ) -> Result<Vec<u64>> {
         let c = create_context()?;
         let g = c.create_graph()?;
         let i = g.input(array_type(input_shape.clone(), BIT))?;
         let hash_matrix = g.input(array_type(hash_shape.clone(), BIT))?;
-        let o = i.cuckoo_hash(hash_matrix)?;
+        let o = i.cuckoo_hash_with_stash(hash_matrix, stash_size)?;
         g.set_output_node(o.clone())?;
         g.finalize()?;
         c.set_main_graph(g.clone())?;
@@ -1852,6 +2939,23 @@ mod tests {
                     expected
                 );
             }
+            // collision with 2 hash functions
+            {
+                // [2,3]-array
+                let input = Value::from_flattened_array(&[1, 0, 1, 0, 0, 0], BIT)?;
+                // [2,2,3]-array
+                let hash_matrix =
+                    Value::from_flattened_array(&[1, 0, 1, 0, 1, 0, 1, 1, 0, 0, 0, 1], BIT)?;
+                // output [4]-array
+                // Hashing results in:
+                // h_0(input[0]) = 00, h_0(input[1]) = 00
+                // h_1(input[0]) = 11
+                let expected = vec![1, u64::MAX, u64::MAX, 0];
+                assert_eq!(
+                    cuckoo_helper(vec![2, 3], vec![2, 2, 3], vec![input, hash_matrix])?,
+                    expected
+                );
+            }
             // failure
             {
                 // [2,3]-array
@@ -1859,10 +2963,41 @@ mod tests {
                 // [3,2,3]-array
                 // Hashes everything to 0
                 let hash_matrix = Value::from_flattened_array(&[0; 18], BIT)?;
-                let e = catch_unwind(AssertUnwindSafe(|| {
-                    cuckoo_helper(vec![2, 3], vec![3, 2, 3], vec![input, hash_matrix])
-                }));
-                assert!(e.is_err());
+                let e = cuckoo_helper(
+                    vec![2, 3],
+                    vec![3, 2, 3],
+                    vec![input.clone(), hash_matrix.clone()],
+                );
+                assert_eq!(
+                    e.unwrap_err().get_body().kind,
+                    CiphercoreErrorKind::CuckooHashingFailure
+                );
+                // The string that can't be inserted is put into the stash
+                assert_eq!(
+                    cuckoo_with_stash_helper(
+                        vec![2, 3],
+                        vec![3, 2, 3],
+                        2,
+                        vec![input.clone(), hash_matrix.clone()]
+                    )?,
+                    vec![0, u64::MAX, u64::MAX, u64::MAX, 1, u64::MAX]
+                );
+            }
+            // failure with a full stash
+            {
+                // [3,3]-array
+                let input = Value::from_flattened_array(&[1, 0, 1, 0, 0, 0, 1, 1, 1], BIT)?;
+                let hash_matrix = Value::from_flattened_array(&[0; 18], BIT)?;
+                let e = cuckoo_with_stash_helper(
+                    vec![3, 3],
+                    vec![3, 2, 3],
+                    1,
+                    vec![input, hash_matrix],
+                );
+                assert_eq!(
+                    e.unwrap_err().get_body().kind,
+                    CiphercoreErrorKind::CuckooHashingFailure
+                );
             }
             // somewhat big example
             for _ in 0..1000 {
@@ -1883,6 +3018,87 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn test_cuckoo_hash_with_retries() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            // 6 distinct non-zero strings are hashed into a table of size 8 by random linear maps, which fails often
+            let i = g.input(array_type(vec![6, 3], BIT))?;
+            let hash_matrix = g.random(array_type(vec![3, 3, 3], BIT))?;
+            let o = i.cuckoo_hash(hash_matrix)?;
+            g.set_output_node(o.clone())?;
+            g.finalize()?;
+            c.set_main_graph(g.clone())?;
+            c.finalize()?;
+            let input = Value::from_flattened_array(
+                &[1, 0, 0, 0, 1, 0, 1, 1, 0, 0, 0, 1, 1, 0, 1, 0, 1, 1],
+                BIT,
+            )?;
+            let seed = Some([0; SEED_SIZE]);
+            let result =
+                evaluate_simple_evaluator_with_retries(g.clone(), vec![input.clone()], seed, 100)?
+                    .to_flattened_array_u64(o.get_type()?)?;
+            let mut indices: Vec<u64> = result.into_iter().filter(|x| *x != u64::MAX).collect();
+            indices.sort_unstable();
+            assert_eq!(indices, (0..6).collect::<Vec<u64>>());
+
+            // Other errors aren't retried
+            let e = evaluate_simple_evaluator_with_retries(g.clone(), vec![], seed, 100);
+            assert_eq!(
+                e.unwrap_err().get_body().kind,
+                CiphercoreErrorKind::RuntimeError
+            );
+            assert!(evaluate_simple_evaluator_with_retries(g, vec![input], seed, 0).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    fn bloom_filter_helper(
+        input_shape: ArrayShape,
+        hash_shape: ArrayShape,
+        inputs: Vec<Value>,
+    ) -> Result<Vec<u64>> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let i = g.input(array_type(input_shape, BIT))?;
+        let hash_matrix = g.input(array_type(hash_shape, BIT))?;
+        let o = i.bloom_filter(hash_matrix)?;
+        g.set_output_node(o.clone())?;
+        g.finalize()?;
+        c.set_main_graph(g.clone())?;
+        c.finalize()?;
+        let result_value = random_evaluate(g, inputs)?;
+        let result_type = o.get_type()?;
+        result_value.to_flattened_array_u64(result_type)
+    }
+
+    #[test]
+    fn test_bloom_filter() {
+        || -> Result<()> {
+            // [2,2,3]-array
+            let hash_matrix =
+                Value::from_flattened_array(&[1, 0, 1, 0, 1, 0, 0, 1, 0, 1, 0, 1], BIT)?;
+            // Hashing results in:
+            // h_0([1,0,1]) = 00, h_1([1,0,1]) = 00
+            // h_0([0,0,1]) = 01, h_1([0,0,1]) = 10
+            let input = Value::from_flattened_array(&[1, 0, 1, 0, 0, 1], BIT)?;
+            assert_eq!(
+                bloom_filter_helper(vec![2, 3], vec![2, 2, 3], vec![input, hash_matrix.clone()])?,
+                vec![1, 1, 1, 0]
+            );
+            // Every subarray has its own filter
+            let input = Value::from_flattened_array(&[1, 0, 1, 0, 0, 1], BIT)?;
+            assert_eq!(
+                bloom_filter_helper(vec![2, 1, 3], vec![2, 2, 3], vec![input, hash_matrix])?,
+                vec![1, 0, 0, 0, 0, 1, 1, 0]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
     fn segment_cumsum_helper(
         input_shape: ArrayShape,
         st: ScalarType,
@@ -2045,16 +3261,12 @@ mod tests {
             // malformed input
             {
                 let input = Value::from_flattened_array(&[2, 0, 1, 4, 4], UINT64)?;
-                let e = catch_unwind(AssertUnwindSafe(|| {
-                    inverse_permutation_helper(5, vec![input])
-                }));
+                let e = inverse_permutation_helper(5, vec![input]);
                 assert!(e.is_err());
             }
             {
                 let input = Value::from_flattened_array(&[2, 0, 1, 4, 5], UINT64)?;
-                let e = catch_unwind(AssertUnwindSafe(|| {
-                    inverse_permutation_helper(5, vec![input])
-                }));
+                let e = inverse_permutation_helper(5, vec![input]);
                 assert!(e.is_err());
             }
             Ok(())
@@ -2135,10 +3347,76 @@ mod tests {
                 let input = Value::from_flattened_array(&[1, 2, 3, 4, 5], UINT32)?;
                 // [3]-array
                 let indices = Value::from_flattened_array(&[2, 5, 0], UINT64)?;
-                let e = catch_unwind(AssertUnwindSafe(|| {
-                    gather_helper(vec![5], vec![3], 0, vec![input, indices])
-                }));
-                assert!(e.is_err());
+                let e = gather_helper(vec![5], vec![3], 0, vec![input, indices]);
+                // The error of the kernel points to the node
+                let e = e.unwrap_err();
+                assert_eq!(e.get_node_global_id(), Some((0, 2)));
+                assert!(e.get_message().contains("Incorrect index"));
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    fn scatter_helper(
+        input_shape: ArrayShape,
+        num_indices: u64,
+        axis: u64,
+        inputs: Vec<Value>,
+    ) -> Result<Vec<u64>> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let mut updates_shape = input_shape.clone();
+        updates_shape[axis as usize] = num_indices;
+        let inp = g.input(array_type(input_shape, UINT32))?;
+        let ind = g.input(array_type(vec![num_indices], UINT64))?;
+        let upd = g.input(array_type(updates_shape, UINT32))?;
+        let o = inp.scatter(ind, upd, axis)?;
+        g.set_output_node(o.clone())?;
+        g.finalize()?;
+        c.set_main_graph(g.clone())?;
+        c.finalize()?;
+        let result_value = random_evaluate(g, inputs)?;
+        result_value.to_flattened_array_u64(o.get_type()?)
+    }
+
+    #[test]
+    fn test_scatter() {
+        || -> Result<()> {
+            {
+                // [5]-array
+                let input = Value::from_flattened_array(&[1, 2, 3, 4, 5], UINT32)?;
+                // [3]-array
+                let indices = Value::from_flattened_array(&[2, 0, 4], UINT64)?;
+                // [3]-array
+                let updates = Value::from_flattened_array(&[6, 7, 8], UINT32)?;
+                let expected = vec![7, 2, 6, 4, 8];
+                assert_eq!(
+                    scatter_helper(vec![5], 3, 0, vec![input, indices, updates])?,
+                    expected
+                );
+            }
+            {
+                // [2,3,2]-array
+                let input =
+                    Value::from_flattened_array(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12], UINT32)?;
+                // [2]-array
+                let indices = Value::from_flattened_array(&[2, 0], UINT64)?;
+                // [2,2,2]-array
+                let updates =
+                    Value::from_flattened_array(&[13, 14, 15, 16, 17, 18, 19, 20], UINT32)?;
+                let expected = vec![15, 16, 3, 4, 13, 14, 19, 20, 9, 10, 17, 18];
+                assert_eq!(
+                    scatter_helper(vec![2, 3, 2], 2, 1, vec![input, indices, updates])?,
+                    expected
+                );
+            }
+            // malformed indices
+            for indices in [[2, 0, 2], [2, 5, 0]] {
+                let input = Value::from_flattened_array(&[1, 2, 3, 4, 5], UINT32)?;
+                let indices = Value::from_flattened_array(&indices, UINT64)?;
+                let updates = Value::from_flattened_array(&[6, 7, 8], UINT32)?;
+                assert!(scatter_helper(vec![5], 3, 0, vec![input, indices, updates]).is_err());
             }
             Ok(())
         }()
@@ -2248,7 +3526,7 @@ mod tests {
             }
             {
                 let input_value = Value::from_flattened_array(&[0, x, 2, 1, x, 3, 4, x], UINT64)?;
-                let expected = vec![0, 6, 2, 1, 5, 3, 4, 7];
+                let expected = vec![0, 7, 2, 1, 6, 3, 4, 5];
                 assert_eq!(
                     cuckoo_to_permutation_helper(vec![8], input_value, seed)?,
                     expected
@@ -2256,7 +3534,7 @@ mod tests {
             }
             {
                 let input_value = Value::from_flattened_array(&[0, x, 2, 1, x, 0, 1, x], UINT64)?;
-                let expected = vec![0, 3, 2, 1, 2, 0, 1, 3];
+                let expected = vec![0, 3, 2, 1, 3, 0, 1, 2];
                 assert_eq!(
                     cuckoo_to_permutation_helper(vec![2, 4], input_value, seed)?,
                     expected
@@ -2264,16 +3542,12 @@ mod tests {
             }
             {
                 let input_value = Value::from_flattened_array(&[0, x, 2, 1, x, 4, 4, x], UINT64)?;
-                let e = catch_unwind(AssertUnwindSafe(|| {
-                    cuckoo_to_permutation_helper(vec![8], input_value, seed)
-                }));
+                let e = cuckoo_to_permutation_helper(vec![8], input_value, seed);
                 assert!(e.is_err());
             }
             {
                 let input_value = Value::from_flattened_array(&[0, x, 2, 1, x, 5, 4, x], UINT64)?;
-                let e = catch_unwind(AssertUnwindSafe(|| {
-                    cuckoo_to_permutation_helper(vec![8], input_value, seed)
-                }));
+                let e = cuckoo_to_permutation_helper(vec![8], input_value, seed);
                 assert!(e.is_err());
             }
             // random seed
@@ -2329,12 +3603,15 @@ mod tests {
         .unwrap();
     }
 
+    // Flattened components of the output of DecomposeSwitchingMap
+    type SwitchingMapDecomposition = (Vec<u64>, Vec<u64>, Vec<u64>, Vec<u64>);
+
     fn decompose_switching_map_helper(
         shape: ArrayShape,
         n: u64,
         input_value: Value,
         seed: Option<[u8; 16]>,
-    ) -> Result<(Vec<u64>, Vec<u64>, Vec<u64>, Vec<u64>)> {
+    ) -> Result<SwitchingMapDecomposition> {
         let c = create_context()?;
         let g = c.create_graph()?;
         let input_type = array_type(shape.clone(), UINT64);
@@ -2434,7 +3711,7 @@ mod tests {
             {
                 let input_map = vec![2, 0, 1, 3, 2, 4, 3, 8];
 
-                let expected_perm1 = vec![2, 6, 0, 1, 3, 5, 4, 8];
+                let expected_perm1 = vec![2, 7, 0, 1, 3, 6, 4, 8];
                 let expected_dup_map = vec![0, 1, 0, 0, 0, 1, 0, 0];
                 let expected_perm2 = vec![0, 2, 3, 4, 1, 6, 5, 7];
 
@@ -2462,7 +3739,7 @@ mod tests {
             }
             {
                 let input_map = vec![6, 6, 6, 6, 6, 6, 6];
-                let expected_perm1 = vec![6, 0, 1, 3, 4, 2, 5];
+                let expected_perm1 = vec![6, 4, 2, 3, 1, 5, 0];
                 let expected_dup_map = vec![0, 1, 1, 1, 1, 1, 1];
                 let expected_perm2 = vec![0, 1, 2, 3, 4, 5, 6];
 
@@ -2476,9 +3753,7 @@ mod tests {
             }
             {
                 let input_map = Value::from_flattened_array(&[0, 1, 5], UINT64)?;
-                let e = catch_unwind(AssertUnwindSafe(|| {
-                    decompose_switching_map_helper(vec![3], 5, input_map, seed)
-                }));
+                let e = decompose_switching_map_helper(vec![3], 5, input_map, seed);
                 assert!(e.is_err());
             }
             // random seed
@@ -2962,4 +4237,419 @@ mod tests {
         }()
         .unwrap();
     }
+
+    #[test]
+    fn test_memory_limit() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![1000], INT64);
+            let x = g.input(t.clone())?;
+            let a = x.add(x.clone())?;
+            let b = a.multiply(a.clone())?;
+            b.add(x)?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let input = Value::from_flattened_array(&vec![1; 1000], INT64)?;
+
+            let evaluator = SimpleEvaluator::new(None)?;
+            assert_eq!(evaluator.get_memory_usage(), None);
+            let mut evaluator = evaluator.with_memory_limit(u64::MAX);
+            evaluator.preprocess(c.clone())?;
+            let result = evaluator.evaluate_context(c.clone(), vec![input.clone()])?;
+            assert_eq!(result.to_flattened_array_i64(t.clone())?, vec![5; 1000]);
+            // At most two arrays of 8000 bytes are held at the same time
+            assert_eq!(evaluator.get_peak_memory_usage(), Some(16000));
+            assert_eq!(evaluator.get_memory_usage(), Some(0));
+
+            let mut evaluator = SimpleEvaluator::new(None)?.with_memory_limit(16000);
+            evaluator.preprocess(c.clone())?;
+            evaluator.evaluate_context(c.clone(), vec![input.clone()])?;
+
+            let mut evaluator = SimpleEvaluator::new(None)?.with_memory_limit(12000);
+            evaluator.preprocess(c.clone())?;
+            let e = evaluator
+                .evaluate_context(c.clone(), vec![input.clone()])
+                .unwrap_err();
+            assert_eq!(e.get_kind(), CiphercoreErrorKind::MemoryLimitExceeded);
+            assert_eq!(e.get_node_global_id(), Some((0, 1)));
+            // Values stored before the failure are released
+            assert_eq!(evaluator.get_memory_usage(), Some(0));
+
+            // Buffers shared by several values are counted once
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(t.clone())?;
+            let tuple = g.create_tuple(vec![x.clone(), x])?;
+            tuple.tuple_get(1)?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let mut evaluator = SimpleEvaluator::new(None)?.with_memory_limit(8000);
+            evaluator.preprocess(c.clone())?;
+            evaluator.evaluate_context(c, vec![input])?;
+            assert_eq!(evaluator.get_peak_memory_usage(), Some(8000));
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_values_freed_after_last_use() {
+        || -> Result<()> {
+            let t = array_type(vec![1000], INT64);
+            let input = Value::from_flattened_array(&vec![1; 1000], INT64)?;
+            let get_peak_memory_usage = |c: Context, inputs: Vec<Value>| -> Result<u64> {
+                let mut evaluator = SimpleEvaluator::new(None)?.with_memory_limit(u64::MAX);
+                evaluator.preprocess(c.clone())?;
+                evaluator.evaluate_context(c, inputs)?;
+                assert_eq!(evaluator.get_memory_usage(), Some(0));
+                Ok(evaluator.get_peak_memory_usage().unwrap())
+            };
+
+            // Only the last value of a chain is held, including the input and the values inside a called graph
+            let c = create_context()?;
+            let chain = c.create_graph()?;
+            let mut y = chain.input(t.clone())?;
+            for _ in 0..10 {
+                y = y.add(y.clone())?;
+            }
+            y.set_as_output()?;
+            chain.finalize()?;
+            let g = c.create_graph()?;
+            let x = g.input(t.clone())?;
+            g.call(chain.clone(), vec![x])?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            assert_eq!(get_peak_memory_usage(c.clone(), vec![input.clone()])?, 8000);
+            // Unused values are freed right away
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(t.clone())?;
+            x.add(x.clone())?;
+            x.multiply(x.clone())?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            assert_eq!(get_peak_memory_usage(c, vec![input])?, 16000);
+
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_hierarchical_seeding() {
+        || -> Result<()> {
+            let t = array_type(vec![8], UINT64);
+            // If `sample_first` is false, the first random node is replaced by a constant, which must not change other random values.
+            let build_context = |sample_first: bool| -> Result<Context> {
+                let c = create_context()?;
+                let sampler = c.create_graph()?;
+                sampler.random(t.clone())?.set_as_output()?;
+                sampler.finalize()?;
+                let step = c.create_graph()?;
+                let state = step.input(scalar_type(UINT64))?;
+                let input = step.input(scalar_type(UINT64))?;
+                let output = step.random(scalar_type(UINT64))?.add(input)?;
+                step.create_tuple(vec![state, output])?.set_as_output()?;
+                step.finalize()?;
+                let g = c.create_graph()?;
+                if sample_first {
+                    g.random(t.clone())?;
+                } else {
+                    g.constant(t.clone(), Value::zero_of_type(t.clone()))?;
+                }
+                let r = g.random(t.clone())?;
+                let permutation = g.random_permutation(8)?;
+                let call0 = g.call(sampler.clone(), vec![])?;
+                let call1 = g.call(sampler, vec![])?;
+                let zero = g.constant(scalar_type(UINT64), Value::from_scalar(0, UINT64)?)?;
+                let inputs = g.create_vector(scalar_type(UINT64), vec![zero.clone(); 3])?;
+                let outputs = g.iterate(step, zero, inputs)?.tuple_get(1)?;
+                g.create_tuple(vec![r, permutation, call0, call1, outputs])?
+                    .set_as_output()?;
+                g.finalize()?.set_as_main()?;
+                c.finalize()?;
+                Ok(c)
+            };
+            let seed = [3u8; SEED_SIZE];
+            let c = build_context(true)?;
+            let result = evaluate_simple_evaluator(c.get_main_graph()?, vec![], Some(seed))?;
+            // Evaluation is reproducible by another evaluator and repeatable by the same evaluator
+            let mut evaluator = SimpleEvaluator::new(Some(seed))?;
+            evaluator.preprocess(c.clone())?;
+            assert_eq!(evaluator.evaluate_context(c.clone(), vec![])?, result);
+            assert_eq!(evaluator.evaluate_context(c.clone(), vec![])?, result);
+            let c_without_first = build_context(false)?;
+            assert_eq!(
+                evaluate_simple_evaluator(c_without_first.get_main_graph()?, vec![], Some(seed))?,
+                result
+            );
+            assert_ne!(
+                evaluate_simple_evaluator(c.get_main_graph()?, vec![], Some([4u8; SEED_SIZE]))?,
+                result
+            );
+            // Different calls of the same graph and different iterations sample different values
+            let result = result.to_vector()?;
+            assert_ne!(result[2], result[3]);
+            let outputs = result[4].to_vector()?;
+            assert_ne!(outputs[0], outputs[1]);
+            assert_ne!(outputs[1], outputs[2]);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_evaluate_batch() {
+        || -> Result<()> {
+            // The output is the input masked by a PRF with a constant key
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![4], UINT64);
+            let x = g.input(t.clone())?;
+            let key = g.constant(
+                array_type(vec![128], BIT),
+                Value::from_flattened_array(&[1; 128], BIT)?,
+            )?;
+            x.add(g.prf(key, 0, t.clone())?)?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let inputs_batch: Vec<Vec<Value>> = (0..7)
+                .map(|i| {
+                    Ok(vec![Value::from_flattened_array(
+                        &[i, i + 1, i + 2, i + 3],
+                        UINT64,
+                    )?])
+                })
+                .collect::<Result<_>>()?;
+
+            let mut evaluator = SimpleEvaluator::new(None)?;
+            let results = evaluator.evaluate_batch(c.clone(), inputs_batch.clone())?;
+            assert_eq!(results.len(), 7);
+            // The PRF is instantiated once for all input sets
+            assert_eq!(evaluator.prfs.len(), 1);
+            let mask = results[0].to_flattened_array_u64(t.clone())?;
+            for (i, result) in results.iter().enumerate() {
+                let expected: Vec<u64> = (0..4).map(|j| mask[j].wrapping_add(i as u64)).collect();
+                assert_eq!(result.to_flattened_array_u64(t.clone())?, expected);
+            }
+            assert_eq!(
+                evaluator.evaluate_context(c.clone(), inputs_batch[3].clone())?,
+                results[3]
+            );
+
+            for num_threads in [1, 3, 10] {
+                let parallel_results =
+                    evaluate_batch_parallel(c.clone(), inputs_batch.clone(), num_threads, || {
+                        SimpleEvaluator::new(None)
+                    })?;
+                assert_eq!(parallel_results, results);
+            }
+            assert!(
+                evaluate_batch_parallel(c.clone(), vec![], 3, || SimpleEvaluator::new(None))?
+                    .is_empty()
+            );
+
+            // Errors of any input set are returned
+            let mut malformed_batch = inputs_batch;
+            malformed_batch[5] = vec![Value::from_scalar(1, UINT64)?];
+            assert!(SimpleEvaluator::new(None)?
+                .evaluate_batch(c.clone(), malformed_batch.clone())
+                .is_err());
+            assert!(
+                evaluate_batch_parallel(c.clone(), malformed_batch.clone(), 3, || {
+                    SimpleEvaluator::new(None)
+                })
+                .is_err()
+            );
+            assert!(
+                evaluate_batch_parallel(c, malformed_batch, 0, || SimpleEvaluator::new(None))
+                    .is_err()
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_evaluate_batch_parallel_preprocessing() {
+        use std::sync::atomic::{self, AtomicUsize};
+
+        // Counts the contexts preprocessed by all the evaluators
+        struct CountingEvaluator {
+            evaluator: SimpleEvaluator,
+            preprocessed: Arc<AtomicUsize>,
+        }
+
+        impl Evaluator for CountingEvaluator {
+            fn preprocess(&mut self, context: Context) -> Result<()> {
+                self.preprocessed.fetch_add(1, atomic::Ordering::SeqCst);
+                self.evaluator.preprocess(context)
+            }
+
+            fn evaluate_node(
+                &mut self,
+                node: Node,
+                dependencies_values: Vec<Value>,
+            ) -> Result<Value> {
+                self.evaluator.evaluate_node(node, dependencies_values)
+            }
+        }
+
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(scalar_type(UINT64))?;
+            x.add(x.clone())?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let inputs_batch: Vec<Vec<Value>> = (0..10)
+                .map(|i| Ok(vec![Value::from_scalar(i, UINT64)?]))
+                .collect::<Result<_>>()?;
+            // Every thread preprocesses the context once for its chunk of input sets
+            for (num_threads, expected_preprocessed) in [(1, 1), (3, 3), (20, 10)] {
+                let preprocessed = Arc::new(AtomicUsize::new(0));
+                let results =
+                    evaluate_batch_parallel(c.clone(), inputs_batch.clone(), num_threads, || {
+                        Ok(CountingEvaluator {
+                            evaluator: SimpleEvaluator::new(None)?,
+                            preprocessed: preprocessed.clone(),
+                        })
+                    })?;
+                assert_eq!(results[9].to_u64(UINT64)?, 18);
+                assert_eq!(
+                    preprocessed.load(atomic::Ordering::SeqCst),
+                    expected_preprocessed
+                );
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_constant_time_strict() {
+        || -> Result<()> {
+            let seed = Some([3; SEED_SIZE]);
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let data = g.input(array_type(vec![4, 3], INT32))?;
+            let indices = g.input(array_type(vec![3], UINT64))?;
+            let perm = g.input(array_type(vec![4], UINT64))?;
+            let bits = g.input(array_type(vec![4], BIT))?;
+            let strings = g.input(array_type(vec![6, 5], BIT))?;
+            let hash_matrices = g.input(array_type(vec![3, 3, 5], BIT))?;
+            let switching_map = g.input(array_type(vec![2, 6], UINT64))?;
+            let cuckoo = strings.cuckoo_hash_with_stash(hash_matrices.clone(), 2)?;
+            g.create_tuple(vec![
+                data.gather(indices, 0)?,
+                data.scatter(perm.clone(), data.truncate(3)?, 0)?,
+                perm.inverse_permutation()?,
+                data.truncate(3)?,
+                data.segment_cumsum(bits, data.get(vec![0])?)?,
+                g.random_permutation(5)?,
+                cuckoo.clone(),
+                cuckoo.cuckoo_to_permutation()?,
+                strings.bloom_filter(hash_matrices)?,
+                switching_map.decompose_switching_map(8)?,
+            ])?
+            .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+
+            let inputs = vec![
+                Value::from_flattened_array(&[-7, 5, 0, 8, -1, -3, 2, 9, -9, 4, 6, -6], INT32)?,
+                Value::from_flattened_array(&[3, 0, 3], UINT64)?,
+                Value::from_flattened_array(&[2, 0, 3, 1], UINT64)?,
+                Value::from_flattened_array(&[1, 0, 1, 1], BIT)?,
+                Value::from_flattened_array(
+                    &[
+                        1, 0, 1, 0, 0, 0, 1, 1, 0, 1, 1, 1, 1, 0, 0, 0, 0, 0, 1, 1, 1, 0, 0, 0, 1,
+                        0, 1, 1, 1, 1,
+                    ],
+                    BIT,
+                )?,
+                Value::from_flattened_array(
+                    &[
+                        1, 0, 1, 0, 1, 0, 0, 1, 0, 1, 0, 1, 1, 0, 0, 0, 0, 1, 1, 1, 0, 0, 1, 0, 1,
+                        1, 0, 1, 0, 0, 0, 1, 1, 1, 0, 1, 0, 0, 1, 1, 0, 0, 1, 0, 1,
+                    ],
+                    BIT,
+                )?,
+                Value::from_flattened_array(&[3, 3, 0, 5, 3, 0, 7, 6, 5, 4, 3, 2], UINT64)?,
+            ];
+            let expected =
+                SimpleEvaluator::new(seed)?.evaluate_context(c.clone(), inputs.clone())?;
+            let result = SimpleEvaluator::new(seed)?
+                .with_constant_time_strict()
+                .evaluate_context(c.clone(), inputs.clone())?;
+            let mut expected = expected.to_vector()?;
+            let mut result = result.to_vector()?;
+            let expected_decomposition = expected.pop().unwrap().to_vector()?;
+            let decomposition = result.pop().unwrap().to_vector()?;
+            assert_eq!(result, expected);
+            // Only the missing indices of the permutation with deletion differ
+            assert_eq!(decomposition[1..], expected_decomposition[1..]);
+            let map_t = array_type(vec![2, 6], UINT64);
+            let perm1 = decomposition[0].to_flattened_array_u64(map_t.clone())?;
+            let duplication = decomposition[1].to_vector()?;
+            let duplication_map = duplication[0].to_flattened_array_u64(map_t.clone())?;
+            let duplication_bits =
+                duplication[1].to_flattened_array_u64(array_type(vec![2, 6], BIT))?;
+            let perm2 = decomposition[2].to_flattened_array_u64(map_t)?;
+            for map_i in 0..2 {
+                let range = map_i * 6..(map_i + 1) * 6;
+                let mut sorted_perm1 = perm1[range.clone()].to_vec();
+                sorted_perm1.sort_unstable();
+                sorted_perm1.dedup();
+                assert_eq!(sorted_perm1.len(), 6);
+                assert!(sorted_perm1.iter().all(|x| *x < 8));
+                let composition = compose_maps(
+                    &perm1[range.clone()],
+                    &duplication_map[range.clone()],
+                    &duplication_bits[range.clone()],
+                    &perm2[range.clone()],
+                )?;
+                assert_eq!(
+                    composition,
+                    inputs[6].to_flattened_array_u64(array_type(vec![12], UINT64))?[range]
+                );
+            }
+
+            // Errors are detected once the kernels are done
+            let mut invalid_inputs = inputs.clone();
+            invalid_inputs[1] = Value::from_flattened_array(&[3, 4, 2], UINT64)?;
+            let mut evaluator = SimpleEvaluator::new(seed)?.with_constant_time_strict();
+            assert!(evaluator
+                .evaluate_context(c.clone(), invalid_inputs)
+                .is_err());
+            let mut invalid_inputs = inputs.clone();
+            invalid_inputs[5] = Value::from_flattened_array(&[0; 45], BIT)?;
+            let e = evaluator.evaluate_context(c.clone(), invalid_inputs);
+            assert_eq!(
+                e.unwrap_err().get_body().kind,
+                CiphercoreErrorKind::CuckooHashingFailure
+            );
+
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = named_tuple_type(vec![
+                (NULL_HEADER.to_owned(), array_type(vec![2], BIT)),
+                ("ID".to_owned(), array_type(vec![2], UINT64)),
+            ]);
+            let set = g.input(t)?;
+            let headers = HashMap::from([("ID".to_owned(), "ID".to_owned())]);
+            set.set_intersection(set.clone(), headers)?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let set_value = Value::from_vector(vec![
+                Value::from_flattened_array(&[1, 1], BIT)?,
+                Value::from_flattened_array(&[4, 5], UINT64)?,
+            ]);
+            assert!(evaluator.evaluate_context(c, vec![set_value]).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
 }
@@ -0,0 +1,343 @@
+//! Evaluator wrapper that records the randomness and the communication of an evaluation and replays it.
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::evaluators::Evaluator;
+use crate::graphs::{Context, Node, NodeAnnotation, Operation};
+
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::path::Path;
+
+/// Reason for recording a node value in a transcript.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TranscriptEventKind {
+    /// Value of an operation that consumes the randomness of the evaluator (e.g. `Random` or `RandomPermutation`).
+    Random,
+    /// Output of a PRF.
+    Prf,
+    /// Value transferred between parties due to [NodeAnnotation::Send] annotations of the node.
+    Transfer,
+}
+
+/// Value of a node recorded during evaluation.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TranscriptEntry {
+    /// Global ID `(graph_id, node_id)` of the node.
+    pub global_id: (u64, u64),
+    /// Name of the operation performed by the node.
+    pub operation: String,
+    pub kind: TranscriptEventKind,
+    /// Pairs `(sender, receiver)` of the [NodeAnnotation::Send] annotations of the node.
+    pub transfers: Vec<(u64, u64)>,
+    pub value: Value,
+}
+
+/// Transcript of an evaluation produced by [TranscriptEvaluator].
+///
+/// Entries are stored in the order of evaluation of the corresponding nodes.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Transcript {
+    pub entries: Vec<TranscriptEntry>,
+}
+
+impl Transcript {
+    /// Writes the transcript to a given file in the JSON format.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Reads a transcript from a given file written by [Transcript::save].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+enum TranscriptMode {
+    Recording,
+    Replaying(usize),
+}
+
+/// Evaluator that wraps another evaluator and records a transcript of evaluation or replays a recorded one.
+///
+/// In the recording mode, the evaluator logs the values of all the nodes that consume randomness
+/// (`Random`, `RandomPermutation`, `DecomposeSwitchingMap` and `CuckooToPermutation`),
+/// the outputs of all the PRF nodes and the values of all the nodes annotated with [NodeAnnotation::Send].
+///
+/// In the replay mode, the values of the randomized nodes are taken from the transcript instead of being computed,
+/// so the evaluation of the same context on the same inputs reproduces the recorded one exactly, regardless of the seed of the wrapped evaluator.
+/// PRF outputs and transferred values are recomputed and compared to the transcript;
+/// the first divergence results in an error pointing to the corresponding node.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, UINT64};
+/// # use ciphercore_base::evaluators::Evaluator;
+/// # use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
+/// # use ciphercore_base::evaluators::transcript_evaluator::TranscriptEvaluator;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// g.random(array_type(vec![4], UINT64)).unwrap().set_as_output().unwrap();
+/// g.finalize().unwrap().set_as_main().unwrap();
+/// c.finalize().unwrap();
+///
+/// let mut evaluator = TranscriptEvaluator::recording(SimpleEvaluator::new(None).unwrap());
+/// evaluator.preprocess(c.clone()).unwrap();
+/// let recorded = evaluator.evaluate_context(c.clone(), vec![]).unwrap();
+///
+/// let transcript = evaluator.get_transcript();
+/// let mut evaluator = TranscriptEvaluator::replaying(SimpleEvaluator::new(None).unwrap(), transcript);
+/// evaluator.preprocess(c.clone()).unwrap();
+/// assert_eq!(evaluator.evaluate_context(c, vec![]).unwrap(), recorded);
+/// ```
+pub struct TranscriptEvaluator<E: Evaluator> {
+    evaluator: E,
+    transcript: Transcript,
+    mode: TranscriptMode,
+}
+
+impl<E: Evaluator> TranscriptEvaluator<E> {
+    /// Creates an evaluator recording a new transcript.
+    pub fn recording(evaluator: E) -> Self {
+        TranscriptEvaluator {
+            evaluator,
+            transcript: Transcript::default(),
+            mode: TranscriptMode::Recording,
+        }
+    }
+
+    /// Creates an evaluator replaying a given transcript.
+    pub fn replaying(evaluator: E, transcript: Transcript) -> Self {
+        TranscriptEvaluator {
+            evaluator,
+            transcript,
+            mode: TranscriptMode::Replaying(0),
+        }
+    }
+
+    /// Returns the transcript recorded (or being replayed) so far.
+    pub fn get_transcript(&self) -> Transcript {
+        self.transcript.clone()
+    }
+
+    /// Returns the number of transcript entries that have not been replayed yet.
+    ///
+    /// In the recording mode, returns 0.
+    pub fn get_num_remaining_entries(&self) -> usize {
+        match self.mode {
+            TranscriptMode::Recording => 0,
+            TranscriptMode::Replaying(position) => self.transcript.entries.len() - position,
+        }
+    }
+
+    /// Returns the wrapped evaluator.
+    pub fn into_inner(self) -> E {
+        self.evaluator
+    }
+
+    fn next_entry(&mut self, node: &Node, kind: &TranscriptEventKind) -> Result<TranscriptEntry> {
+        let position = match &mut self.mode {
+            TranscriptMode::Replaying(position) => position,
+            TranscriptMode::Recording => {
+                return Err(runtime_error!("Evaluator is not in the replay mode"));
+            }
+        };
+        let entry = self
+            .transcript
+            .entries
+            .get(*position)
+            .ok_or_else(|| runtime_error!("Transcript is exhausted").with_node(node))?
+            .clone();
+        if entry.global_id != node.get_global_id() || entry.kind != *kind {
+            return Err(runtime_error!(
+                "Transcript diverged: {:?} {:?} at node {:?} expected",
+                entry.kind,
+                entry.operation,
+                entry.global_id
+            )
+            .with_node(node));
+        }
+        *position += 1;
+        Ok(entry)
+    }
+}
+
+fn get_event_kind(node: &Node, transfers: &[(u64, u64)]) -> Option<TranscriptEventKind> {
+    match node.get_operation() {
+        Operation::Random(_)
+        | Operation::RandomPermutation(_)
+        | Operation::DecomposeSwitchingMap(_)
+        | Operation::CuckooToPermutation => Some(TranscriptEventKind::Random),
+        Operation::PRF(_, _) => Some(TranscriptEventKind::Prf),
+        _ if !transfers.is_empty() => Some(TranscriptEventKind::Transfer),
+        _ => None,
+    }
+}
+
+impl<E: Evaluator> Evaluator for TranscriptEvaluator<E> {
+    fn preprocess(&mut self, context: Context) -> Result<()> {
+        self.evaluator.preprocess(context)
+    }
+
+    fn evaluate_node(&mut self, node: Node, dependencies_values: Vec<Value>) -> Result<Value> {
+        let mut transfers = vec![];
+        for annotation in node.get_annotations()? {
+            if let NodeAnnotation::Send(sender, receiver) = annotation {
+                transfers.push((sender, receiver));
+            }
+        }
+        let kind = match get_event_kind(&node, &transfers) {
+            Some(kind) => kind,
+            None => {
+                return self.evaluator.evaluate_node(node, dependencies_values);
+            }
+        };
+        match self.mode {
+            TranscriptMode::Recording => {
+                let value = self
+                    .evaluator
+                    .evaluate_node(node.clone(), dependencies_values)?;
+                self.transcript.entries.push(TranscriptEntry {
+                    global_id: node.get_global_id(),
+                    operation: format!("{}", node.get_operation()),
+                    kind,
+                    transfers,
+                    value: value.clone(),
+                });
+                Ok(value)
+            }
+            TranscriptMode::Replaying(_) => {
+                let entry = self.next_entry(&node, &kind)?;
+                if kind == TranscriptEventKind::Random {
+                    if !entry.value.check_type(node.get_type()?)? {
+                        return Err(runtime_error!(
+                            "Recorded value doesn't match the type of the node"
+                        )
+                        .with_node(&node));
+                    }
+                    return Ok(entry.value);
+                }
+                let value = self
+                    .evaluator
+                    .evaluate_node(node.clone(), dependencies_values)?;
+                if value != entry.value {
+                    return Err(runtime_error!(
+                        "Transcript diverged: the value of the node differs from the recorded one"
+                    )
+                    .with_node(&node));
+                }
+                Ok(value)
+            }
+        }
+    }
+
+    fn on_value_stored(&mut self, value: &Value) -> Result<()> {
+        self.evaluator.on_value_stored(value)
+    }
+
+    fn on_value_released(&mut self, value: &Value) {
+        self.evaluator.on_value_released(value)
+    }
+
+    fn on_graph_call_started(&mut self, node: &Node, iteration: u64) -> Result<()> {
+        self.evaluator.on_graph_call_started(node, iteration)
+    }
+
+    fn on_graph_call_finished(&mut self) {
+        self.evaluator.on_graph_call_finished()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{array_type, BIT, UINT64};
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::create_context;
+
+    fn create_randomized_context() -> Result<Context> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let t = array_type(vec![5], UINT64);
+        let i = g.input(t.clone())?;
+        let key = g.random(array_type(vec![128], BIT))?;
+        let mask = g.prf(key, 0, t.clone())?;
+        let masked = i.add(mask)?;
+        masked.add_annotation(NodeAnnotation::Send(0, 1))?;
+        let permutation = g.random_permutation(5)?;
+        let o = masked.gather(permutation, 0)?;
+        o.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        Ok(c)
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        || -> Result<()> {
+            let c = create_randomized_context()?;
+            let input = Value::from_flattened_array(&[1, 2, 3, 4, 5], UINT64)?;
+
+            let mut evaluator = TranscriptEvaluator::recording(SimpleEvaluator::new(None)?);
+            evaluator.preprocess(c.clone())?;
+            let recorded = evaluator.evaluate_context(c.clone(), vec![input.clone()])?;
+            let transcript = evaluator.get_transcript();
+            let kinds: Vec<TranscriptEventKind> =
+                transcript.entries.iter().map(|e| e.kind.clone()).collect();
+            assert_eq!(
+                kinds,
+                vec![
+                    TranscriptEventKind::Random,
+                    TranscriptEventKind::Prf,
+                    TranscriptEventKind::Transfer,
+                    TranscriptEventKind::Random
+                ]
+            );
+            assert_eq!(transcript.entries[2].transfers, vec![(0, 1)]);
+
+            let path = std::env::temp_dir().join(format!(
+                "ciphercore_transcript_test_{}.json",
+                std::process::id()
+            ));
+            transcript.save(&path)?;
+            let loaded = Transcript::load(&path)?;
+            fs::remove_file(&path)?;
+            assert_eq!(loaded, transcript);
+
+            // Replay with a different seed gives the same result
+            let mut evaluator =
+                TranscriptEvaluator::replaying(SimpleEvaluator::new(Some([7; 16]))?, loaded);
+            evaluator.preprocess(c.clone())?;
+            let replayed = evaluator.evaluate_context(c.clone(), vec![input.clone()])?;
+            assert_eq!(replayed, recorded);
+            assert_eq!(evaluator.get_num_remaining_entries(), 0);
+
+            // Replay on different inputs diverges at the transferred value
+            let mut evaluator =
+                TranscriptEvaluator::replaying(SimpleEvaluator::new(None)?, transcript.clone());
+            evaluator.preprocess(c.clone())?;
+            let e = evaluator
+                .evaluate_context(
+                    c.clone(),
+                    vec![Value::from_flattened_array(&[1, 2, 3, 4, 6], UINT64)?],
+                )
+                .unwrap_err();
+            assert_eq!(e.get_node_global_id(), Some((0, 3)));
+            assert!(e.get_message().contains("Transcript diverged"));
+
+            // Truncated transcript
+            let mut truncated = transcript;
+            truncated.entries.pop();
+            let mut evaluator =
+                TranscriptEvaluator::replaying(SimpleEvaluator::new(None)?, truncated);
+            evaluator.preprocess(c.clone())?;
+            let e = evaluator.evaluate_context(c, vec![input]).unwrap_err();
+            assert!(e.get_message().contains("Transcript is exhausted"));
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -0,0 +1,364 @@
+//! Fluent interface for building graphs with arithmetic operators.
+//!
+//! Building graphs node by node requires checking the result of every call.
+//! This module provides [Expr], a handle to a node that supports the `+`, `-` and `*` operators and chained method calls, e.g.
+//!
+//! ```
+//! # use ciphercore_base::data_types::{scalar_type, INT32};
+//! # use ciphercore_base::graphs::create_context;
+//! # use ciphercore_base::graph_builder::GraphBuilder;
+//! let c = create_context().unwrap();
+//! let b = GraphBuilder::new(c.create_graph().unwrap());
+//! let x = b.input(scalar_type(INT32));
+//! let y = b.input(scalar_type(INT32));
+//! let z = b.input(scalar_type(INT32));
+//! let g = b.finalize((&x * &y + z).a2b()).unwrap();
+//! ```
+//!
+//! Every operation is lowered into the corresponding [Node] method right away, so types are inferred and checked while the expression is built.
+//! If an operation fails, the error is carried by the resulting expression and all the expressions depending on it,
+//! and it is returned by the first call that extracts a node, e.g. [Expr::into_node] or [GraphBuilder::finalize].
+use crate::custom_ops::CustomOperation;
+use crate::data_types::{ArrayShape, ScalarType, Type};
+use crate::data_values::Value;
+use crate::errors::{CiphercoreBaseError, Result};
+use crate::graphs::{Graph, Node};
+
+use ciphercore_utils::errors::ErrorWithBody;
+
+use std::ops::{Add, Mul, Sub};
+
+fn clone_error(e: &CiphercoreBaseError) -> CiphercoreBaseError {
+    CiphercoreBaseError::new(e.get_body())
+}
+
+/// Handle to a node of a graph under construction or to an error that occurred while building it.
+///
+/// Expressions are created by [GraphBuilder] or from existing nodes via `Expr::from(node)`.
+pub struct Expr {
+    node: Result<Node>,
+}
+
+impl Clone for Expr {
+    fn clone(&self) -> Self {
+        Expr {
+            node: match &self.node {
+                Ok(node) => Ok(node.clone()),
+                Err(e) => Err(clone_error(e)),
+            },
+        }
+    }
+}
+
+impl From<Node> for Expr {
+    fn from(node: Node) -> Self {
+        Expr { node: Ok(node) }
+    }
+}
+
+impl From<Result<Node>> for Expr {
+    fn from(node: Result<Node>) -> Self {
+        Expr { node }
+    }
+}
+
+impl Expr {
+    /// Returns the node of the expression or the first error occurred while building it.
+    pub fn node(&self) -> Result<Node> {
+        self.clone().into_node()
+    }
+
+    /// Converts the expression into its node or the first error occurred while building it.
+    pub fn into_node(self) -> Result<Node> {
+        self.node
+    }
+
+    /// Returns the type of the expression.
+    pub fn get_type(&self) -> Result<Type> {
+        self.node()?.get_type()
+    }
+
+    /// Applies a unary node operation to the expression.
+    pub fn apply<F>(&self, f: F) -> Expr
+    where
+        F: FnOnce(Node) -> Result<Node>,
+    {
+        Expr::from(self.node().and_then(f))
+    }
+
+    /// Applies a binary node operation to the expression and another one.
+    ///
+    /// On failure, the error message is prefixed with the name of the operation and the types of its arguments.
+    pub fn apply2<F>(&self, op_name: &str, other: &Expr, f: F) -> Expr
+    where
+        F: FnOnce(Node, Node) -> Result<Node>,
+    {
+        let build = || -> Result<Node> {
+            let a = self.node()?;
+            let b = other.node()?;
+            let (a_type, b_type) = (a.get_type()?, b.get_type()?);
+            f(a, b).map_err(|e| {
+                runtime_error!(
+                    "{} can't be applied to {} and {}: {}",
+                    op_name,
+                    a_type,
+                    b_type,
+                    e.get_body().message
+                )
+            })
+        };
+        Expr::from(build())
+    }
+
+    /// Multiplies the expression elementwise by a binary expression, see [Node::mixed_multiply].
+    pub fn mixed_multiply(&self, b: &Expr) -> Expr {
+        self.apply2("MixedMultiply", b, |a, b| a.mixed_multiply(b))
+    }
+
+    /// Computes the dot product of the expression and another one, see [Node::dot].
+    pub fn dot(&self, b: &Expr) -> Expr {
+        self.apply2("Dot", b, |a, b| a.dot(b))
+    }
+
+    /// Computes the matrix product of the expression and another one, see [Node::matmul].
+    pub fn matmul(&self, b: &Expr) -> Expr {
+        self.apply2("Matmul", b, |a, b| a.matmul(b))
+    }
+
+    /// Converts the expression to the binary form, see [Node::a2b].
+    pub fn a2b(&self) -> Expr {
+        self.apply(|a| a.a2b())
+    }
+
+    /// Converts the binary expression to a given scalar type, see [Node::b2a].
+    pub fn b2a(&self, scalar_type: ScalarType) -> Expr {
+        self.apply(|a| a.b2a(scalar_type))
+    }
+
+    /// Divides the expression by `scale` rounding down, see [Node::truncate].
+    pub fn truncate(&self, scale: u64) -> Expr {
+        self.apply(|a| a.truncate(scale))
+    }
+
+    /// Sums the expression along given axes, see [Node::sum].
+    pub fn sum(&self, axes: ArrayShape) -> Expr {
+        self.apply(|a| a.sum(axes))
+    }
+
+    /// Permutes the axes of the expression, see [Node::permute_axes].
+    pub fn permute_axes(&self, axes: ArrayShape) -> Expr {
+        self.apply(|a| a.permute_axes(axes))
+    }
+
+    /// Extracts a subarray of the expression, see [Node::get].
+    pub fn get(&self, index: ArrayShape) -> Expr {
+        self.apply(|a| a.get(index))
+    }
+
+    /// Reshapes the expression, see [Node::reshape].
+    pub fn reshape(&self, new_type: Type) -> Expr {
+        self.apply(|a| a.reshape(new_type))
+    }
+
+    /// Extracts an element of the tuple expression, see [Node::tuple_get].
+    pub fn tuple_get(&self, index: u64) -> Expr {
+        self.apply(|a| a.tuple_get(index))
+    }
+
+    /// Applies a custom operation to the expression followed by other arguments, see [Graph::custom_op].
+    pub fn custom_op(&self, op: CustomOperation, other_arguments: &[Expr]) -> Expr {
+        let build = || -> Result<Node> {
+            let mut arguments = vec![self.node()?];
+            for argument in other_arguments {
+                arguments.push(argument.node()?);
+            }
+            arguments[0].get_graph().custom_op(op, arguments)
+        };
+        Expr::from(build())
+    }
+
+    /// Sets the name of the node of the expression, see [Node::set_name].
+    pub fn set_name(self, name: &str) -> Expr {
+        let node = self.into_node().and_then(|node| node.set_name(name));
+        Expr::from(node)
+    }
+}
+
+macro_rules! impl_binary_operator {
+    ($trait:ident, $method:ident, $op_name:expr, $node_method:ident) => {
+        impl $trait<&Expr> for &Expr {
+            type Output = Expr;
+
+            fn $method(self, rhs: &Expr) -> Expr {
+                self.apply2($op_name, rhs, |a, b| a.$node_method(b))
+            }
+        }
+
+        impl $trait<Expr> for &Expr {
+            type Output = Expr;
+
+            fn $method(self, rhs: Expr) -> Expr {
+                $trait::$method(self, &rhs)
+            }
+        }
+
+        impl $trait<&Expr> for Expr {
+            type Output = Expr;
+
+            fn $method(self, rhs: &Expr) -> Expr {
+                $trait::$method(&self, rhs)
+            }
+        }
+
+        impl $trait<Expr> for Expr {
+            type Output = Expr;
+
+            fn $method(self, rhs: Expr) -> Expr {
+                $trait::$method(&self, &rhs)
+            }
+        }
+    };
+}
+
+impl_binary_operator!(Add, add, "Add", add);
+impl_binary_operator!(Sub, sub, "Subtract", subtract);
+impl_binary_operator!(Mul, mul, "Multiply", multiply);
+
+/// Builder of a graph producing [Expr] handles.
+pub struct GraphBuilder {
+    graph: Graph,
+}
+
+impl GraphBuilder {
+    /// Creates a builder adding nodes to a given graph.
+    pub fn new(graph: Graph) -> Self {
+        GraphBuilder { graph }
+    }
+
+    /// Returns the graph under construction.
+    pub fn get_graph(&self) -> Graph {
+        self.graph.clone()
+    }
+
+    /// Adds an input node of a given type, see [Graph::input].
+    pub fn input(&self, input_type: Type) -> Expr {
+        Expr::from(self.graph.input(input_type))
+    }
+
+    /// Adds an input node of a given type and name, see [Graph::input] and [Node::set_name].
+    pub fn named_input(&self, name: &str, input_type: Type) -> Expr {
+        self.input(input_type).set_name(name)
+    }
+
+    /// Adds a constant node, see [Graph::constant].
+    pub fn constant(&self, output_type: Type, value: Value) -> Expr {
+        Expr::from(self.graph.constant(output_type, value))
+    }
+
+    /// Adds a tuple of given expressions, see [Graph::create_tuple].
+    pub fn tuple(&self, elements: &[Expr]) -> Expr {
+        let build = || -> Result<Node> {
+            let nodes = elements.iter().map(|e| e.node()).collect::<Result<_>>()?;
+            self.graph.create_tuple(nodes)
+        };
+        Expr::from(build())
+    }
+
+    /// Sets a given expression as the output of the graph and finalizes the graph.
+    ///
+    /// # Returns
+    ///
+    /// Finalized graph or the first error occurred while building the output expression
+    pub fn finalize(self, output: Expr) -> Result<Graph> {
+        let output = output.into_node()?;
+        if output.get_graph() != self.graph {
+            return Err(runtime_error!(
+                "Output expression belongs to a different graph"
+            ));
+        }
+        self.graph.set_output_node(output)?;
+        self.graph.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::Not;
+    use crate::data_types::{array_type, scalar_type, BIT, INT32, UINT8};
+    use crate::evaluators::random_evaluate;
+    use crate::graphs::{create_context, Operation};
+
+    #[test]
+    fn test_arithmetic() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let b = GraphBuilder::new(c.create_graph()?);
+            let x = b.named_input("x", scalar_type(INT32));
+            let y = b.named_input("y", scalar_type(INT32));
+            let z = b.input(scalar_type(INT32));
+            let w = b.constant(scalar_type(INT32), Value::from_scalar(2, INT32)?);
+            let result = (&x * &y + z - w).a2b();
+            assert_eq!(result.get_type()?, array_type(vec![32], BIT));
+            let g = b.finalize(result.b2a(INT32))?;
+            g.set_as_main()?;
+            c.finalize()?;
+            let output = random_evaluate(
+                g,
+                vec![
+                    Value::from_scalar(3, INT32)?,
+                    Value::from_scalar(5, INT32)?,
+                    Value::from_scalar(-7, INT32)?,
+                ],
+            )?;
+            assert_eq!(output.to_i32(INT32)?, 6);
+            assert_eq!(x.node()?.get_name()?, "x");
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_array_operations() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let b = GraphBuilder::new(c.create_graph()?);
+            let m = b.input(array_type(vec![2, 3], UINT8));
+            let v = b.input(array_type(vec![3], UINT8));
+            let bits = b.input(array_type(vec![2], BIT));
+            let prod = m.matmul(&m.permute_axes(vec![1, 0])).get(vec![0]);
+            let masked = prod.mixed_multiply(&bits).sum(vec![0]);
+            let out = b.tuple(&[
+                masked,
+                m.dot(&v),
+                bits.custom_op(CustomOperation::new(Not {}), &[]),
+            ]);
+            let g = b.finalize(out.tuple_get(0))?;
+            assert_eq!(g.get_output_node()?.get_type()?, scalar_type(UINT8));
+            assert_eq!(g.get_output_node()?.get_operation(), Operation::TupleGet(0));
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let b = GraphBuilder::new(c.create_graph()?);
+            let x = b.input(scalar_type(INT32));
+            let y = b.input(array_type(vec![3], BIT));
+            let e = (&x + &y) * &x;
+            let message = e.node().err().unwrap().get_body().message;
+            assert!(message.starts_with("Add can't be applied to i32 and b[3]"));
+            // The error propagates to dependent expressions
+            assert!(e.a2b().truncate(2).into_node().is_err());
+            assert!(b.finalize(e).is_err());
+
+            let b2 = GraphBuilder::new(c.create_graph()?);
+            assert!(b2.finalize(x).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
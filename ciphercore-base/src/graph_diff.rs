@@ -0,0 +1,341 @@
+//! Structural comparison of contexts.
+//!
+//! [diff_contexts] matches the nodes of two contexts by their structure and reports the nodes present only in one of them and the matched nodes whose types differ.
+//! It helps to review changes of generated graphs, e.g. before and after an optimization pass or between two versions of the compiler,
+//! and to find the source of nondeterministic compilation.
+//!
+//! Two nodes match if they have the same operation, annotations and matching dependencies (and called graphs).
+//! Input nodes match if they have the same position among the inputs of their graphs, regardless of their types.
+//! Thus, changing the type of an input results in retyped nodes depending on it, while changing an operation results in removed and added nodes depending on it.
+//! Node IDs, graph IDs and names are ignored, so contexts can be compared even if nodes are created in a different order.
+use crate::data_types::Type;
+use crate::errors::Result;
+use crate::graphs::{Context, Node, Operation};
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Node of a compared context.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeDescription {
+    pub graph_id: u64,
+    pub node_id: u64,
+    pub operation: String,
+    pub node_type: Type,
+    pub name: Option<String>,
+}
+
+impl NodeDescription {
+    fn new(node: &Node) -> Result<Self> {
+        Ok(NodeDescription {
+            graph_id: node.get_graph().get_id(),
+            node_id: node.get_id(),
+            operation: format!("{}", node.get_operation()),
+            node_type: node.get_type()?,
+            name: node.get_name().ok(),
+        })
+    }
+}
+
+impl fmt::Display for NodeDescription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "graph {}, node {}: {} of type {}",
+            self.graph_id, self.node_id, self.operation, self.node_type
+        )?;
+        if let Some(name) = &self.name {
+            write!(f, " named {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Differences between two contexts found by [diff_contexts].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContextDiff {
+    /// Nodes of the first context without a match in the second one
+    pub removed: Vec<NodeDescription>,
+    /// Nodes of the second context without a match in the first one
+    pub added: Vec<NodeDescription>,
+    /// Matched nodes of the first and second contexts having different types
+    pub retyped: Vec<(NodeDescription, NodeDescription)>,
+}
+
+impl ContextDiff {
+    /// Returns `true` if the compared contexts are structurally equal.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty() && self.retyped.is_empty()
+    }
+}
+
+impl fmt::Display for ContextDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for node in &self.removed {
+            writeln!(f, "- {}", node)?;
+        }
+        for node in &self.added {
+            writeln!(f, "+ {}", node)?;
+        }
+        for (node1, node2) in &self.retyped {
+            writeln!(f, "~ {} -> {}", node1, node2.node_type)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct NodeKey {
+    operation: String,
+    annotations: String,
+    dependencies: Vec<u64>,
+    graph_dependencies: Vec<u64>,
+}
+
+// Assigns the same signature to the nodes of both contexts with the same structure
+#[derive(Default)]
+struct SignatureTable {
+    signatures: HashMap<NodeKey, u64>,
+}
+
+impl SignatureTable {
+    // Returns the signatures of all the nodes of a context grouped by signature in the order of nodes
+    fn get_context_signatures(&mut self, context: &Context) -> Result<HashMap<u64, Vec<Node>>> {
+        // Signatures of nodes indexed by their global IDs
+        let mut node_signatures = HashMap::<(u64, u64), u64>::new();
+        let mut graph_signatures = HashMap::<u64, u64>::new();
+        let mut nodes_by_signature = HashMap::<u64, Vec<Node>>::new();
+        for graph in context.get_graphs() {
+            let mut input_index = 0;
+            for node in graph.get_nodes() {
+                // Hash is not implemented for Operation, so its JSON representation is used as in the duplicates optimizer.
+                let operation = match node.get_operation() {
+                    Operation::Input(_) => {
+                        input_index += 1;
+                        format!("Input#{}", input_index - 1)
+                    }
+                    op => serde_json::to_string(&op)?,
+                };
+                let mut dependencies = vec![];
+                for dependency in node.get_node_dependencies() {
+                    dependencies.push(node_signatures[&dependency.get_global_id()]);
+                }
+                let mut graph_dependencies = vec![];
+                for dependency in node.get_graph_dependencies() {
+                    graph_dependencies.push(graph_signatures[&dependency.get_id()]);
+                }
+                let key = NodeKey {
+                    operation,
+                    annotations: serde_json::to_string(&node.get_annotations()?)?,
+                    dependencies,
+                    graph_dependencies,
+                };
+                let num_signatures = self.signatures.len() as u64;
+                let signature = *self.signatures.entry(key).or_insert(num_signatures);
+                node_signatures.insert(node.get_global_id(), signature);
+                nodes_by_signature.entry(signature).or_default().push(node);
+            }
+            // Graphs are identified by their output nodes
+            let output_signature = node_signatures[&graph.get_output_node()?.get_global_id()];
+            graph_signatures.insert(graph.get_id(), output_signature);
+        }
+        Ok(nodes_by_signature)
+    }
+}
+
+/// Compares two finalized contexts structurally.
+///
+/// Nodes with the same structure are matched in the order of graphs and nodes.
+/// Nodes in every list of the result are sorted by graph and node IDs.
+///
+/// # Arguments
+///
+/// * `context1` - first context, e.g. before a change
+/// * `context2` - second context, e.g. after a change
+///
+/// # Returns
+///
+/// Differences between the contexts
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{scalar_type, INT32};
+/// # use ciphercore_base::graph_diff::diff_contexts;
+/// let build_context = |multiply: bool| {
+///     let c = create_context().unwrap();
+///     let g = c.create_graph().unwrap();
+///     let x = g.input(scalar_type(INT32)).unwrap();
+///     let y = g.input(scalar_type(INT32)).unwrap();
+///     let z = if multiply { x.multiply(y) } else { x.add(y) };
+///     z.unwrap().set_as_output().unwrap();
+///     g.finalize().unwrap().set_as_main().unwrap();
+///     c.finalize().unwrap();
+///     c
+/// };
+/// let diff = diff_contexts(build_context(false), build_context(true)).unwrap();
+/// assert_eq!(diff.removed[0].operation, "Add");
+/// assert_eq!(diff.added[0].operation, "Multiply");
+/// ```
+pub fn diff_contexts(context1: Context, context2: Context) -> Result<ContextDiff> {
+    context1.check_finalized()?;
+    context2.check_finalized()?;
+    let mut table = SignatureTable::default();
+    let nodes1 = table.get_context_signatures(&context1)?;
+    let nodes2 = table.get_context_signatures(&context2)?;
+    let mut removed = vec![];
+    let mut added = vec![];
+    let mut retyped = vec![];
+    let empty = vec![];
+    for (signature, signature_nodes1) in &nodes1 {
+        let signature_nodes2 = nodes2.get(signature).unwrap_or(&empty);
+        for (node1, node2) in signature_nodes1.iter().zip(signature_nodes2.iter()) {
+            if node1.get_type()? != node2.get_type()? {
+                retyped.push((node1.clone(), node2.clone()));
+            }
+        }
+        removed.extend(
+            signature_nodes1
+                .iter()
+                .skip(signature_nodes2.len())
+                .cloned(),
+        );
+    }
+    for (signature, signature_nodes2) in &nodes2 {
+        let num_nodes1 = nodes1.get(signature).map_or(0, |nodes| nodes.len());
+        added.extend(signature_nodes2.iter().skip(num_nodes1).cloned());
+    }
+    removed.sort_by_key(|node| node.get_global_id());
+    added.sort_by_key(|node| node.get_global_id());
+    retyped.sort_by_key(|(node1, _)| node1.get_global_id());
+    let describe = |nodes: Vec<Node>| -> Result<Vec<NodeDescription>> {
+        nodes.iter().map(NodeDescription::new).collect()
+    };
+    Ok(ContextDiff {
+        removed: describe(removed)?,
+        added: describe(added)?,
+        retyped: retyped
+            .iter()
+            .map(|(node1, node2)| Ok((NodeDescription::new(node1)?, NodeDescription::new(node2)?)))
+            .collect::<Result<_>>()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{array_type, INT32};
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::create_context;
+    use crate::inline::inline_ops::{InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{compile_context, IOStatus};
+
+    fn build_context(n: u64, subtract: bool, reorder: bool) -> Result<Context> {
+        let c = create_context()?;
+        let helper = c.create_graph()?;
+        let a = helper.input(array_type(vec![n], INT32))?;
+        a.sum(vec![0])?.set_as_output()?;
+        helper.finalize()?;
+        let g = c.create_graph()?;
+        let x = g.input(array_type(vec![n], INT32))?;
+        let y = g.input(array_type(vec![n], INT32))?;
+        let (product, sum) = if reorder {
+            let sum = x.add(y.clone())?;
+            (x.multiply(y.clone())?, sum)
+        } else {
+            (x.multiply(y.clone())?, x.add(y.clone())?)
+        };
+        let difference = if subtract { x.subtract(y)? } else { x.add(y)? };
+        let total = g.call(helper, vec![difference])?;
+        g.create_tuple(vec![product, sum, total])?.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        Ok(c)
+    }
+
+    #[test]
+    fn test_equal_contexts() {
+        || -> Result<()> {
+            let diff = diff_contexts(
+                build_context(3, true, false)?,
+                build_context(3, true, true)?,
+            )?;
+            assert!(diff.is_empty());
+            assert_eq!(format!("{}", diff), "");
+            // Compilation is deterministic
+            let compile = || -> Result<Context> {
+                compile_context(
+                    build_context(3, true, false)?,
+                    vec![IOStatus::Party(0), IOStatus::Shared],
+                    vec![IOStatus::Party(1)],
+                    InlineConfig {
+                        default_mode: InlineMode::Simple,
+                        ..Default::default()
+                    },
+                    || SimpleEvaluator::new(None),
+                )
+            };
+            assert!(diff_contexts(compile()?, compile()?)?.is_empty());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_changed_operation() {
+        || -> Result<()> {
+            let diff = diff_contexts(
+                build_context(3, true, false)?,
+                build_context(3, false, false)?,
+            )?;
+            // Subtract is replaced by the duplicate of Add, which changes the Call and CreateTuple nodes depending on it
+            let operations = |nodes: &[NodeDescription]| -> Vec<String> {
+                nodes.iter().map(|node| node.operation.clone()).collect()
+            };
+            assert_eq!(
+                operations(&diff.removed),
+                vec!["Subtract", "Call", "CreateTuple"]
+            );
+            assert_eq!(operations(&diff.added), vec!["Add", "Call", "CreateTuple"]);
+            assert!(diff.retyped.is_empty());
+            assert_eq!(diff.removed[0].graph_id, 1);
+            assert_eq!(diff.removed[0].node_id, 4);
+            assert_eq!(
+                format!("{}", diff).lines().next(),
+                Some("- graph 1, node 4: Subtract of type i32[3]")
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_retyped() {
+        || -> Result<()> {
+            let diff = diff_contexts(
+                build_context(3, true, false)?,
+                build_context(5, true, false)?,
+            )?;
+            assert!(diff.removed.is_empty());
+            assert!(diff.added.is_empty());
+            // Inputs and arithmetic nodes of both graphs and the tuple; sums and Call nodes are scalars in both contexts.
+            assert_eq!(diff.retyped.len(), 7);
+            assert_eq!(diff.retyped[0].0.node_type, array_type(vec![3], INT32));
+            assert_eq!(diff.retyped[0].1.node_type, array_type(vec![5], INT32));
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed() {
+        || -> Result<()> {
+            let c = create_context()?;
+            assert!(diff_contexts(c.clone(), build_context(3, true, false)?).is_err());
+            assert!(diff_contexts(build_context(3, true, false)?, c).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -1,10 +1,12 @@
 //! Crucial structs, enums, functions and types to create computation graphs.
 use atomic_refcell::AtomicRefCell;
+use std::backtrace::Backtrace;
 use std::collections::HashMap;
 use std::fmt;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::sync::Weak;
 
@@ -12,12 +14,15 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::constants::type_size_limit_constants;
 use crate::custom_ops::CustomOperation;
-use crate::data_types::{get_size_estimation_in_bits, ArrayShape, ScalarType, Type};
+use crate::data_types::{get_size_estimation_in_bits, ArrayShape, ScalarType, Type, UINT64};
 use crate::data_values::Value;
-use crate::errors::Result;
-use crate::type_inference::{create_type_inference_worker, TypeInferenceWorker};
+use crate::errors::{CiphercoreBaseError, Result};
+use crate::io::padding::PaddingPolicy;
+use crate::ops::table::{Filter, Predicate};
+use crate::type_inference::{create_type_inference_worker, TypeInferenceWorker, NULL_HEADER};
+use crate::typed_value::TypedValue;
 
-use crate::version::{VersionedData, DATA_VERSION};
+use crate::version::{get_data_hash, HashedVersionedData, DATA_VERSION};
 
 #[cfg(feature = "py-binding")]
 use crate::custom_ops::PyBindingCustomOperation;
@@ -80,11 +85,26 @@ pub enum Operation {
     Reshape(Type),
     NOP,
     Random(Type),
+    // Public randomness agreed by all parties; unlike Random, it can be used before MPC compilation.
+    RandomBeacon(Type),
     PRF(u64, Type),
     Stack(ArrayShape),
+    // Concatenation of arrays along a given axis as in numpy.concatenate.
+    Concatenate(u64),
+    // Splitting of an array along a given axis into a tuple of arrays with given sizes along this axis.
+    Split(u64, ArrayShape),
+    // Broadcasting of a scalar or an array to a given shape following the NumPy broadcasting rules.
+    BroadcastTo(ArrayShape),
+    // Repetition of a scalar or an array along its axes as in numpy.tile.
+    Tile(ArrayShape),
+    // Cumulative sums of an array along a given axis as in numpy.cumsum.
+    CumSum(u64),
     Constant(Type, Value),
     A2B,
     B2A(ScalarType),
+    // Conversion of integers between rings Z_{2^k} of different sizes.
+    // Downcasting keeps the least significant bits, upcasting extends signed integers by copies of the sign bit and unsigned ones by zeros.
+    ConvertRing(ScalarType),
     CreateTuple,
     CreateNamedTuple(Vec<String>),
     CreateVector(Type),
@@ -95,11 +115,15 @@ pub enum Operation {
     Repeat(u64),
     Call,
     Iterate,
+    If,
     ArrayToVector,
     VectorToArray,
     RandomPermutation(u64),
     Gather(u64),
-    CuckooHash,
+    // Replacement of the rows of an array along a given axis with the rows of another array at given unique indices.
+    Scatter(u64),
+    CuckooHash(u64),
+    BloomFilter,
     InversePermutation,
     CuckooToPermutation,
     DecomposeSwitchingMap(u64),
@@ -133,6 +157,21 @@ struct NodeBody {
     graph_dependencies: Vec<WeakGraph>,
     operation: Operation,
     id: u64,
+    /// Backtrace of the call that created the node, recorded only if enabled via [set_node_backtrace_recording]
+    construction_backtrace: Option<Arc<Backtrace>>,
+}
+
+static NODE_BACKTRACE_RECORDING: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables recording of backtraces of node creation.
+///
+/// If enabled, every node created afterwards stores the backtrace of the call that added it to its graph.
+/// This backtrace is attached to the errors that occur during the instantiation or evaluation of the node (see [CiphercoreBaseError::pretty_print](crate::errors::CiphercoreBaseError::pretty_print)),
+/// which helps to find the code that built a malformed node in large compiled contexts.
+///
+/// Recording is disabled by default since capturing a backtrace for every node slows down graph construction significantly.
+pub fn set_node_backtrace_recording(enabled: bool) {
+    NODE_BACKTRACE_RECORDING.store(enabled, Ordering::Relaxed);
 }
 
 #[derive(Serialize, Deserialize)]
@@ -531,6 +570,37 @@ impl Node {
         self.get_graph().set_intersection(self.clone(), b, headers)
     }
 
+    /// Adds a node to the parent graph that removes rows of the database associated with the node that don't satisfy a given predicate.
+    ///
+    /// Applies the custom operation [Filter] to the parent graph and `this` node.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{array_type, named_tuple_type, BIT, INT32};
+    /// # use ciphercore_base::ops::table::{Comparison, Operand, Predicate};
+    /// # use ciphercore_base::type_inference::NULL_HEADER;
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let t = named_tuple_type(vec![
+    ///     (NULL_HEADER.to_owned(), array_type(vec![10], BIT)),
+    ///     ("Age".to_owned(), array_type(vec![10], INT32)),
+    ///     ("Height".to_owned(), array_type(vec![10], INT32)),
+    /// ]);
+    /// let n1 = g.input(t).unwrap();
+    /// let n2 = n1.filter(
+    ///     Predicate::compare("Age", Comparison::LessThan, Operand::Constant(18))
+    ///         .or(Predicate::compare("Height", Comparison::GreaterThan, Operand::Constant(180)))
+    /// ).unwrap();
+    /// ```
+    pub fn filter(&self, predicate: Predicate) -> Result<Node> {
+        self.get_graph().custom_op(
+            CustomOperation::new(Filter { predicate }),
+            vec![self.clone()],
+        )
+    }
+
     /// Adds a node to the parent graph that divides a scalar or each entry of the array associated with the node by a positive constant integer `scale`.
     ///
     /// Applies [Graph::add] to the parent graph, `this` node and `scale`.
@@ -638,6 +708,82 @@ impl Node {
         self.get_graph().get_slice(self.clone(), slice)
     }
 
+    /// Adds a node that splits the array associated with the node along a given axis into a tuple of consecutive sub-arrays of given sizes.
+    ///
+    /// Applies [Graph::split] to the parent graph, `this` node, `axis` and `parts`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{INT32, array_type};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let t = array_type(vec![3, 5], INT32);
+    /// let n1 = g.input(t).unwrap();
+    /// let n2 = n1.split(1, vec![2, 3]).unwrap();
+    /// ```
+    pub fn split(&self, axis: u64, parts: ArrayShape) -> Result<Node> {
+        self.get_graph().split(self.clone(), axis, parts)
+    }
+
+    /// Adds a node that broadcasts the scalar or array associated with the node to a given shape.
+    ///
+    /// Applies [Graph::broadcast_to] to the parent graph, `this` node and `shape`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{INT32, array_type};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let t = array_type(vec![3, 1], INT32);
+    /// let n1 = g.input(t).unwrap();
+    /// let n2 = n1.broadcast_to(vec![3, 5]).unwrap();
+    /// ```
+    pub fn broadcast_to(&self, shape: ArrayShape) -> Result<Node> {
+        self.get_graph().broadcast_to(self.clone(), shape)
+    }
+
+    /// Adds a node that repeats the scalar or array associated with the node a given number of times along each axis.
+    ///
+    /// Applies [Graph::tile] to the parent graph, `this` node and `reps`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{INT32, array_type};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let t = array_type(vec![3, 2], INT32);
+    /// let n1 = g.input(t).unwrap();
+    /// let n2 = n1.tile(vec![2, 2]).unwrap();
+    /// ```
+    pub fn tile(&self, reps: ArrayShape) -> Result<Node> {
+        self.get_graph().tile(self.clone(), reps)
+    }
+
+    /// Adds a node that computes cumulative sums of the array associated with the node along a given axis.
+    ///
+    /// Applies [Graph::cumsum] to the parent graph, `this` node and `axis`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{INT32, array_type};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let t = array_type(vec![3, 2], INT32);
+    /// let n1 = g.input(t).unwrap();
+    /// let n2 = n1.cumsum(0).unwrap();
+    /// ```
+    pub fn cumsum(&self, axis: u64) -> Result<Node> {
+        self.get_graph().cumsum(self.clone(), axis)
+    }
+
     /// Adds a node to the parent graph that reshapes a value associated with the node to a given compatible type.
     ///
     /// Applies [Graph::reshape] to the parent graph, `this` node and `new_type`.
@@ -706,6 +852,25 @@ impl Node {
         self.get_graph().b2a(self.clone(), scalar_type)
     }
 
+    /// Adds a node to the parent graph converting an integer array or scalar associated with the node to a given integer scalar type.
+    ///
+    /// Applies [Graph::convert_ring] to the parent graph, `this` node and `scalar_type`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{INT32, INT64, array_type};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let t = array_type(vec![3, 2], INT64);
+    /// let n1 = g.input(t).unwrap();
+    /// let n2 = n1.convert_ring(INT32).unwrap();
+    /// ```
+    pub fn convert_ring(&self, scalar_type: ScalarType) -> Result<Node> {
+        self.get_graph().convert_ring(self.clone(), scalar_type)
+    }
+
     /// Adds a node that extracts an element of a tuple associated with the node.
     ///
     /// Applies [Graph::tuple_get] to the parent graph, `this` node and `index`.
@@ -828,6 +993,28 @@ impl Node {
         self.get_graph().gather(self.clone(), indices, axis)
     }
 
+    /// Adds a node to the parent graph writing the rows of `updates` to the rows of this node at given indices.
+    ///
+    /// Applies [Graph::scatter] to the parent graph, `this` node, `indices` and `updates`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{array_type, INT32, UINT64};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let n1 = g.input(array_type(vec![5, 3], INT32)).unwrap();
+    /// let n2 = g.input(array_type(vec![2], UINT64)).unwrap();
+    /// let n3 = g.input(array_type(vec![2, 3], INT32)).unwrap();
+    /// let n4 = n1.scatter(n2, n3, 0).unwrap();
+    /// assert_eq!(n4.get_type().unwrap(), array_type(vec![5, 3], INT32));
+    /// ```
+    pub fn scatter(&self, indices: Node, updates: Node, axis: u64) -> Result<Node> {
+        self.get_graph()
+            .scatter(self.clone(), indices, updates, axis)
+    }
+
     /// Adds a node that creates a vector with `n` copies of a value of this node.
     ///
     /// Applies [Graph::repeat] to the parent graph, `this` node and `n`.
@@ -855,6 +1042,23 @@ impl Node {
         self.get_graph().cuckoo_hash(self.clone(), hash_matrices)
     }
 
+    /// Adds a node returning the Cuckoo hash map with a stash of an input array of binary strings using provided hash functions.
+    ///
+    /// Applies [Graph::cuckoo_hash_with_stash] to the parent graph, `this` node, `hash_matrices` and `stash_size`.
+    #[doc(hidden)]
+    pub fn cuckoo_hash_with_stash(&self, hash_matrices: Node, stash_size: u64) -> Result<Node> {
+        self.get_graph()
+            .cuckoo_hash_with_stash(self.clone(), hash_matrices, stash_size)
+    }
+
+    /// Adds a node returning the Bloom filter of an input array of binary strings using provided hash functions.
+    ///
+    /// Applies [Graph::bloom_filter] to the parent graph, `this` node and `hash_matrices`.
+    #[doc(hidden)]
+    pub fn bloom_filter(&self, hash_matrices: Node) -> Result<Node> {
+        self.get_graph().bloom_filter(self.clone(), hash_matrices)
+    }
+
     /// Adds a node that, given an input multidimensional array A, binary one-dimensional array B (first dimension is n in both array) and starting value v, computes the following iteration
     ///
     /// output[i] = A[i-1] + B[i-1] * output[i-1]
@@ -953,6 +1157,40 @@ impl Node {
             .get_context()
             .get_node_annotations(self.clone())
     }
+
+    /// Returns the name of the node if it is named and can be accessed, i.e. its context is not being modified at the moment (e.g. during type inference).
+    pub(crate) fn try_get_name(&self) -> Option<String> {
+        let context = self.get_graph().get_context();
+        let cell = context.body.try_borrow().ok()?;
+        cell.nodes_names.get(&self.get_global_id()).cloned()
+    }
+
+    /// Returns the custom operations and regions the node originates from, outermost first.
+    ///
+    /// When [run_instantiation_pass](crate::custom_ops::run_instantiation_pass) replaces a custom operation with a graph,
+    /// the nodes of this graph are attributed to the type name of the custom operation (e.g. `SetIntersectionMPC`), followed by the regions open when the node was created (see [Graph::push_region]).
+    /// Inlining prepends the provenance of the Call or Iterate node to the provenance of the inlined nodes,
+    /// so the nodes of a fully inlined context carry the whole stack of custom operations and regions, e.g. `["SetIntersectionMPC", "step 13", "SwitchingMPC"]`.
+    ///
+    /// # Returns
+    ///
+    /// Stack of custom operation type names and region names; empty if the node was created by the user outside of any region
+    pub fn get_provenance(&self) -> Result<Vec<String>> {
+        self.get_graph().get_context().get_node_provenance(self)
+    }
+
+    /// Returns the backtrace of the call that created the node.
+    ///
+    /// # Returns
+    ///
+    /// Backtrace of the node creation or `None` if backtrace recording was disabled when the node was created (see [set_node_backtrace_recording])
+    pub fn get_construction_backtrace(&self) -> Option<String> {
+        self.body
+            .borrow()
+            .construction_backtrace
+            .as_ref()
+            .map(|backtrace| backtrace.to_string())
+    }
 }
 type WeakNodeBodyPointer = Weak<AtomicRefCell<NodeBody>>;
 
@@ -983,6 +1221,8 @@ struct GraphBody {
     output_node: Option<WeakNode>,
     id: u64,
     context: WeakContext,
+    /// Stack of regions open at the moment (see [Graph::push_region])
+    regions: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -1404,6 +1644,8 @@ impl Graph {
     /// In addition, each named tuple should have a binary array named with NULL_HEADER that contains zeros in rows void of content; otherwise, it contains ones.
     /// This column is called the null column.
     ///
+    /// Key columns can contain strings stored as [string columns](crate::data_types::string_column_type).
+    ///
     /// This operation returns a named tuple that contains rows whose content is equal in the key columns named by given key headers.
     /// The content of non-key columns is merged.
     /// The order of these rows is the same as in the first named tuple.
@@ -1556,7 +1798,7 @@ impl Graph {
     /// However, the Scatter operation poses a security risk as the corresponding map should hide empty output positions.
     /// This is usually done by padding an input array with dummy values such that its size is equal to the output size.
     /// Then, the Scatter map can be turned into a permutation, which can be easily split into a composition of random permutation maps.
-    /// But permutation maps can be performed by Gather, thus making Scatter unnecessary.
+    /// But permutation maps can be performed by Gather, thus making [Graph::scatter] unnecessary here.
     ///
     /// **WARNING**: this function should not be used before MPC compilation.
     ///
@@ -1708,6 +1950,36 @@ impl Graph {
         self.add_node(vec![], vec![], Operation::Random(output_type))
     }
 
+    /// Adds a node creating a uniformly random value of a given type that is public, i.e. known to all parties.
+    ///
+    /// In the plaintext evaluation, this node is equivalent to sampling a random value.
+    /// After MPC compilation, the value is generated jointly by all parties via a coin-tossing protocol, so that no party can predict or bias it.
+    /// It can be used for sampling that shouldn't depend on the random generator of one party, e.g. for seeds of differentially private noise or random projections.
+    ///
+    /// Note that the value isn't secret: it's revealed to all the parties after MPC compilation.
+    ///
+    /// # Arguments
+    ///
+    /// `output_type` - scalar or array type of the random value
+    ///
+    /// # Returns
+    ///
+    /// New random beacon node
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{INT64, array_type};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let t = array_type(vec![10, 3], INT64);
+    /// let n = g.random_beacon(t).unwrap();
+    /// ```
+    pub fn random_beacon(&self, output_type: Type) -> Result<Node> {
+        self.add_node(vec![], vec![], Operation::RandomBeacon(output_type))
+    }
+
     /// Adds a node creating a random permutation map of a one-dimensional array of length `n`.
     ///
     /// This operation generates a random array of all 64-bit integers from 0 to n-1 in random order.
@@ -1738,9 +2010,10 @@ impl Graph {
     /// then the hash map is an array of shape `[..., 2^m]`.
     /// The hash table element with index `[..., i]` is equal to `j` if the `[..., j]`-th input `b`-bit string is hashed to `i` by some of the given hash functions.
     ///
-    /// The number of hash matrices (the first dimension of hash matrices) must be at least 3.
+    /// The number of hash matrices (the first dimension of hash matrices) must be at least 2.
     ///
-    /// A bigger ratio `m/n` leads to higher success probability (recommended one is `>=2`)    
+    /// A bigger ratio `2^m/n` leads to higher success probability (recommended one is `>=2`).
+    /// If hashing fails, evaluation returns an error of the [CuckooHashingFailure](ciphercore_utils::errors::CiphercoreErrorKind::CuckooHashingFailure) kind.
     ///
     /// **WARNING**: this function should not be used before MPC compilation.
     ///
@@ -1754,7 +2027,64 @@ impl Graph {
     /// New CuckooHash node
     #[doc(hidden)]
     pub fn cuckoo_hash(&self, array: Node, hash_matrices: Node) -> Result<Node> {
-        self.add_node(vec![array, hash_matrices], vec![], Operation::CuckooHash)
+        self.cuckoo_hash_with_stash(array, hash_matrices, 0)
+    }
+
+    /// Adds a node returning the Cuckoo hash map with a stash of an input array of binary strings using provided hash functions.
+    ///
+    /// This operation works as [Graph::cuckoo_hash], but input strings that can't be placed in the hash map are put into a stash of a given size instead of failing.
+    /// The stash is appended to the hash map, i.e. if the input array has shape `[..., n, b]` and hash matrices are given as an `[h, m, b]`-array,
+    /// then the result is an array of shape `[..., 2^m + s]`, where `s` is the stash size.
+    /// Unused stash elements are equal to `u64::MAX` as empty hash map elements.
+    ///
+    /// Even a small stash drastically reduces the failure probability of Cuckoo hashing (see <https://eprint.iacr.org/2018/579.pdf>, Appendix B).
+    ///
+    /// **WARNING**: this function should not be used before MPC compilation.
+    ///
+    /// # Arguments
+    ///
+    /// - `array` - input array of binary strings of shape [..., n, b]
+    /// - `hash_matrices` - random binary [h, m, b]-array.
+    /// - `stash_size` - maximal number of input strings that can be put into the stash
+    ///
+    /// # Returns
+    ///
+    /// New CuckooHash node
+    #[doc(hidden)]
+    pub fn cuckoo_hash_with_stash(
+        &self,
+        array: Node,
+        hash_matrices: Node,
+        stash_size: u64,
+    ) -> Result<Node> {
+        self.add_node(
+            vec![array, hash_matrices],
+            vec![],
+            Operation::CuckooHash(stash_size),
+        )
+    }
+
+    /// Adds a node returning the Bloom filter of an input array of binary strings using provided hash functions.
+    ///
+    /// Hash functions are defined as an array of binary matrices as in [Graph::cuckoo_hash].
+    ///
+    /// If the input array has shape `[..., n, b]` and hash matrices are given as an `[h, m, b]`-array,
+    /// then the Bloom filter is a binary array of shape `[..., 2^m]`.
+    /// The filter element with index `[..., i]` is equal to 1 if some input `b`-bit string of the corresponding subarray is hashed to `i` by some of the given hash functions.
+    ///
+    /// **WARNING**: this function should not be used before MPC compilation.
+    ///
+    /// # Arguments
+    ///
+    /// - `array` - input array of binary strings of shape [..., n, b]
+    /// - `hash_matrices` - random binary [h, m, b]-array.
+    ///
+    /// # Returns
+    ///
+    /// New BloomFilter node
+    #[doc(hidden)]
+    pub fn bloom_filter(&self, array: Node, hash_matrices: Node) -> Result<Node> {
+        self.add_node(vec![array, hash_matrices], vec![], Operation::BloomFilter)
     }
 
     /// Adds a node that, given an input multidimensional array A, binary one-dimensional array B (first dimension is n in both array) and starting value v, computes the following iteration
@@ -1900,6 +2230,178 @@ impl Graph {
         self.add_node(nodes, vec![], Operation::Stack(outer_shape))
     }
 
+    /// Adds a node that joins a sequence of arrays along a given axis (see [numpy.concatenate](https://numpy.org/doc/stable/reference/generated/numpy.concatenate.html)).
+    ///
+    /// The input arrays should have the same scalar type and the same shape except for the dimension corresponding to `axis`.
+    ///
+    /// For example, concatenating arrays of shapes `[2,3]` and `[2,1]` along the axis 1 results in an array of shape `[2,4]`:
+    ///
+    /// `concatenate(arrays=[[[1,2,3],[4,5,6]], [[7],[8]]], axis=1) = [[1,2,3,7],[4,5,6,8]]`
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes` - vector of nodes containing arrays
+    /// * `axis` - axis along which the arrays are joined
+    ///
+    /// # Returns
+    ///
+    /// New concatenate node
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{INT32, array_type};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let n1 = g.input(array_type(vec![2, 3], INT32)).unwrap();
+    /// let n2 = g.input(array_type(vec![2, 1], INT32)).unwrap();
+    /// let n3 = g.concatenate(vec![n1, n2], 1).unwrap();
+    /// assert_eq!(n3.get_type().unwrap(), array_type(vec![2, 4], INT32));
+    /// ```
+    pub fn concatenate(&self, nodes: Vec<Node>, axis: u64) -> Result<Node> {
+        self.add_node(nodes, vec![], Operation::Concatenate(axis))
+    }
+
+    /// Adds a node that splits an array along a given axis into consecutive sub-arrays of given sizes (see [numpy.split](https://numpy.org/doc/stable/reference/generated/numpy.split.html)).
+    ///
+    /// The sizes of the parts should be positive and sum up to the dimension of the array corresponding to `axis`.
+    /// This operation is the inverse of [Graph::concatenate].
+    ///
+    /// For example, splitting an array of shape `[2,4]` along the axis 1 into parts of sizes `[3,1]` results in a tuple of arrays of shapes `[2,3]` and `[2,1]`:
+    ///
+    /// `split(array=[[1,2,3,7],[4,5,6,8]], axis=1, parts=[3,1]) = ([[1,2,3],[4,5,6]], [[7],[8]])`
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - node containing an array
+    /// * `axis` - axis along which the array is split
+    /// * `parts` - sizes of the resulting sub-arrays along `axis`
+    ///
+    /// # Returns
+    ///
+    /// New split node containing a tuple of sub-arrays
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{INT32, array_type, tuple_type};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let n1 = g.input(array_type(vec![2, 4], INT32)).unwrap();
+    /// let n2 = g.split(n1, 1, vec![3, 1]).unwrap();
+    /// assert_eq!(
+    ///     n2.get_type().unwrap(),
+    ///     tuple_type(vec![array_type(vec![2, 3], INT32), array_type(vec![2, 1], INT32)])
+    /// );
+    /// ```
+    pub fn split(&self, a: Node, axis: u64, parts: ArrayShape) -> Result<Node> {
+        self.add_node(vec![a], vec![], Operation::Split(axis, parts))
+    }
+
+    /// Adds a node that broadcasts a scalar or an array to a given shape (see [numpy.broadcast_to](https://numpy.org/doc/stable/reference/generated/numpy.broadcast_to.html)).
+    ///
+    /// The input shape is aligned with the given shape from the right; every input dimension must be either equal to the respective dimension of the given shape or 1.
+    /// Binary operations like [Graph::add] or [Graph::multiply] broadcast their inputs implicitly;
+    /// this operation materializes a broadcast explicitly, e.g. to store or send the broadcast array.
+    /// Broadcasting doesn't require communication in MPC, since it's applied to every share locally.
+    ///
+    /// For example, broadcasting an array of shape `[3,1]` to the shape `[2,3,4]` results in an array of shape `[2,3,4]` with `result[i,j,k] = a[j,0]`.
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - node containing a scalar or an array
+    /// * `shape` - shape of the result
+    ///
+    /// # Returns
+    ///
+    /// New broadcast node
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{INT32, array_type};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let n1 = g.input(array_type(vec![3, 1], INT32)).unwrap();
+    /// let n2 = g.broadcast_to(n1, vec![2, 3, 4]).unwrap();
+    /// assert_eq!(n2.get_type().unwrap(), array_type(vec![2, 3, 4], INT32));
+    /// ```
+    pub fn broadcast_to(&self, a: Node, shape: ArrayShape) -> Result<Node> {
+        self.add_node(vec![a], vec![], Operation::BroadcastTo(shape))
+    }
+
+    /// Adds a node that repeats a scalar or an array a given number of times along each axis (see [numpy.tile](https://numpy.org/doc/stable/reference/generated/numpy.tile.html)).
+    ///
+    /// If `reps` has fewer elements than the number of input dimensions, it is prepended by ones; if it has more elements, the input shape is prepended by ones.
+    /// The dimensions of the result are products of the respective input dimensions and repetition numbers.
+    /// Like [Graph::broadcast_to], tiling is applied to every share locally in MPC.
+    ///
+    /// For example, tiling an array `[[1,2],[3,4]]` with `reps=[2,3]` works as follows
+    ///
+    /// `tile([[1,2],[3,4]], [2,3]) = [[1,2,1,2,1,2],[3,4,3,4,3,4],[1,2,1,2,1,2],[3,4,3,4,3,4]]`
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - node containing a scalar or an array
+    /// * `reps` - positive numbers of repetitions along each axis
+    ///
+    /// # Returns
+    ///
+    /// New tile node
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{INT32, array_type};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let n1 = g.input(array_type(vec![2, 2], INT32)).unwrap();
+    /// let n2 = g.tile(n1, vec![3, 2, 3]).unwrap();
+    /// assert_eq!(n2.get_type().unwrap(), array_type(vec![3, 4, 6], INT32));
+    /// ```
+    pub fn tile(&self, a: Node, reps: ArrayShape) -> Result<Node> {
+        self.add_node(vec![a], vec![], Operation::Tile(reps))
+    }
+
+    /// Adds a node that computes cumulative sums of an array along a given axis (see [numpy.cumsum](https://numpy.org/doc/stable/reference/generated/numpy.cumsum.html)).
+    ///
+    /// The `i`-th element of the result along `axis` is the sum of the elements of the input with indices from 0 to `i` along this axis.
+    /// Sums are computed modulo the size of the input scalar type; for binary arrays, they are prefix XORs.
+    ///
+    /// For example, cumulative sums along the axis 1 work as follows
+    ///
+    /// `cumsum([[1,2,3],[4,5,6]], axis=1) = [[1,3,6],[4,9,15]]`
+    ///
+    /// Cumulative sums are linear, so they are applied to every share locally in MPC and don't require communication.
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - node containing an array
+    /// * `axis` - axis along which the sums are computed
+    ///
+    /// # Returns
+    ///
+    /// New CumSum node
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{INT32, array_type};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let n1 = g.input(array_type(vec![2, 3], INT32)).unwrap();
+    /// let n2 = g.cumsum(n1, 1).unwrap();
+    /// assert_eq!(n2.get_type().unwrap(), array_type(vec![2, 3], INT32));
+    /// ```
+    pub fn cumsum(&self, a: Node, axis: u64) -> Result<Node> {
+        self.add_node(vec![a], vec![], Operation::CumSum(axis))
+    }
+
     /// Adds a node creating a constant of a given type and value.
     ///
     /// # Arguments
@@ -1982,6 +2484,37 @@ impl Graph {
         self.add_node(vec![a], vec![], Operation::B2A(scalar_type))
     }
 
+    /// Adds a node converting an integer array or scalar to another integer scalar type, i.e. mapping it from one ring Z_{2^k} to another.
+    ///
+    /// If the target type is smaller, only the least significant bits of each entry are kept, e.g. INT64 entries are reduced modulo 2^32 when converted to INT32.
+    /// If the target type is larger, signed entries are extended by copies of their sign bit and unsigned entries by zeros, so that their values are preserved.
+    ///
+    /// Unlike a combination of [Graph::a2b] and [Graph::b2a], downcasting of secret-shared data compiles to local operations on shares without any communication.
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - node containing an array or scalar of integers with at most 64 bits
+    /// * `scalar_type` - scalar type of the result (an integer type with at most 64 bits)
+    ///
+    /// # Returns
+    ///
+    /// New node converting an array/scalar to the given ring
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{INT32, INT64, array_type};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let t = array_type(vec![3, 2], INT64);
+    /// let n1 = g.input(t).unwrap();
+    /// let n2 = g.convert_ring(n1, INT32).unwrap();
+    /// ```
+    pub fn convert_ring(&self, a: Node, scalar_type: ScalarType) -> Result<Node> {
+        self.add_node(vec![a], vec![], Operation::ConvertRing(scalar_type))
+    }
+
     /// Adds a node that creates a tuple from several (possibly, zero) elements.
     ///
     /// # Arguments
@@ -2255,52 +2788,165 @@ impl Graph {
     /// graph(state_0, V[0]) -> (state1, W[0]),
     /// graph(state_1, V[1]) -> (state2, W[1]),
     /// ...
-    /// graph(state_k, V[k]) -> (final_state, W[k]).
+    /// graph(state_k, V[k]) -> (final_state, W[k]).
+    /// ```
+    /// The output is a [tuple](Type::Tuple) `(final_state, W)`. The initial state `state_0` should be provided as an argument.
+    ///
+    /// This node generalize `map` and `reduce` procedures (see [MapReduce](https://en.wikipedia.org/wiki/MapReduce) for more details).
+    ///
+    /// For example, let `G` be a graph implementing the function `max(x,0)` and incrementing `state` if its output is negative, then `iterate(G, 0, [-1,2,0,3,2]) = (1, [0,2,0,3,2])`. The final state is equal to the number of negative values in the input vector.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph` - graph with 2 input nodes of types T<sub>s</sub> and T<sub>i</sub> and returning a tuple of type (T<sub>s</sub>, T<sub>o</sub>)
+    /// * `state` - node containing an initial state of type T<sub>s</sub>
+    /// * `input` - node containing a vector with elements of type T<sub>i</sub>
+    ///
+    /// # Returns
+    ///
+    /// New iterate node
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::data_types::{INT32, scalar_type, vector_type};
+    /// # use ciphercore_base::graphs::create_context;
+    /// let c = create_context().unwrap();
+    ///
+    /// let t_s = scalar_type(INT32);
+    /// let t = scalar_type(INT32);
+    /// let vec_t = vector_type(10, t.clone());
+    ///
+    /// let g1 = c.create_graph().unwrap();
+    /// {
+    ///     let old_state = g1.input(t_s.clone()).unwrap();
+    ///     let input = g1.input(t.clone()).unwrap();
+    ///     let sum = g1.add(old_state.clone(), input).unwrap();
+    ///     let out_tuple = g1.create_tuple(vec![sum, old_state]).unwrap();
+    ///     out_tuple.set_as_output().unwrap();
+    ///     g1.finalize().unwrap();
+    /// }
+    ///
+    /// let g2 = c.create_graph().unwrap();
+    /// let initial_state = g2.input(t).unwrap();
+    /// let input_vector = g2.input(vec_t).unwrap();
+    /// g2.iterate(g1, initial_state, input_vector).unwrap();
+    /// ```
+    pub fn iterate(&self, graph: Graph, state: Node, input: Node) -> Result<Node> {
+        self.add_node(vec![state, input], vec![graph], Operation::Iterate)
+    }
+
+    /// Adds a node that computes one of two finalized graphs depending on a private or public bit.
+    ///
+    /// If `condition` is 1, the output is `then_graph(arguments)`; otherwise, it is `else_graph(arguments)`.
+    ///
+    /// In plaintext evaluation, only the chosen graph is computed.
+    /// In MPC compilation, the condition is usually secret-shared, so both graphs are computed
+    /// and their outputs are obliviously selected (see [select](crate::ops::utils::select)).
+    ///
+    /// # Arguments
+    ///
+    /// * `condition` - node containing a binary scalar
+    /// * `then_graph` - graph computed if `condition` is 1
+    /// * `else_graph` - graph with the same input and output types as `then_graph` computed if `condition` is 0
+    /// * `arguments` - nodes passed to the inputs of the chosen graph
+    ///
+    /// # Returns
+    ///
+    /// New If node
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::data_types::{BIT, INT32, scalar_type};
+    /// # use ciphercore_base::data_values::Value;
+    /// # use ciphercore_base::graphs::create_context;
+    /// let c = create_context().unwrap();
+    /// let t = scalar_type(INT32);
+    ///
+    /// let increment = c.create_graph().unwrap();
+    /// let x = increment.input(t.clone()).unwrap();
+    /// let one = increment.constant(t.clone(), Value::from_scalar(1, INT32).unwrap()).unwrap();
+    /// x.add(one).unwrap().set_as_output().unwrap();
+    /// increment.finalize().unwrap();
+    ///
+    /// let negate = c.create_graph().unwrap();
+    /// let x = negate.input(t.clone()).unwrap();
+    /// let zero = negate.constant(t.clone(), Value::zero_of_type(t.clone())).unwrap();
+    /// zero.subtract(x).unwrap().set_as_output().unwrap();
+    /// negate.finalize().unwrap();
+    ///
+    /// let g = c.create_graph().unwrap();
+    /// let b = g.input(scalar_type(BIT)).unwrap();
+    /// let x = g.input(t).unwrap();
+    /// g.if_else(b, increment, negate, vec![x]).unwrap();
+    /// ```
+    pub fn if_else(
+        &self,
+        condition: Node,
+        then_graph: Graph,
+        else_graph: Graph,
+        arguments: Vec<Node>,
+    ) -> Result<Node> {
+        let mut dependencies = vec![condition];
+        dependencies.extend(arguments);
+        self.add_node(dependencies, vec![then_graph, else_graph], Operation::If)
+    }
+
+    /// Adds a node that iteratively computes a given finalized graph a fixed number of times, i.e. a bounded loop with a public counter.
+    ///
+    /// This is [Graph::iterate] over the vector of iteration indices `[0, 1, ..., num_iterations - 1]`:
+    /// ```text
+    /// graph(state_0, 0) -> (state_1, W[0]),
+    /// ...
+    /// graph(state_{n-1}, n-1) -> (final_state, W[n-1]).
     /// ```
-    /// The output is a [tuple](Type::Tuple) `(final_state, W)`. The initial state `state_0` should be provided as an argument.
-    ///
-    /// This node generalize `map` and `reduce` procedures (see [MapReduce](https://en.wikipedia.org/wiki/MapReduce) for more details).
-    ///
-    /// For example, let `G` be a graph implementing the function `max(x,0)` and incrementing `state` if its output is negative, then `iterate(G, 0, [-1,2,0,3,2]) = (1, [0,2,0,3,2])`. The final state is equal to the number of negative values in the input vector.
+    /// It expresses iterative algorithms with a fixed trip count (e.g. gradient descent or Newton's method) without manual unrolling;
+    /// evaluation and MPC compilation are the same as for [Graph::iterate].
     ///
     /// # Arguments
     ///
-    /// * `graph` - graph with 2 input nodes of types T<sub>s</sub> and T<sub>i</sub> and returning a tuple of type (T<sub>s</sub>, T<sub>o</sub>)
+    /// * `graph` - graph with 2 input nodes of types T<sub>s</sub> and UINT64 scalar (iteration index) and returning a tuple of type (T<sub>s</sub>, T<sub>o</sub>)
     /// * `state` - node containing an initial state of type T<sub>s</sub>
-    /// * `input` - node containing a vector with elements of type T<sub>i</sub>
+    /// * `num_iterations` - positive number of iterations
     ///
     /// # Returns
     ///
-    /// New iterate node
+    /// New iterate node containing the tuple `(final_state, W)`
     ///
     /// # Example
     ///
     /// ```
-    /// # use ciphercore_base::data_types::{INT32, scalar_type, vector_type};
+    /// # use ciphercore_base::data_types::{INT32, UINT64, scalar_type};
     /// # use ciphercore_base::graphs::create_context;
     /// let c = create_context().unwrap();
-    ///
-    /// let t_s = scalar_type(INT32);
     /// let t = scalar_type(INT32);
-    /// let vec_t = vector_type(10, t.clone());
     ///
     /// let g1 = c.create_graph().unwrap();
-    /// {
-    ///     let old_state = g1.input(t_s.clone()).unwrap();
-    ///     let input = g1.input(t.clone()).unwrap();
-    ///     let sum = g1.add(old_state.clone(), input).unwrap();
-    ///     let out_tuple = g1.create_tuple(vec![sum, old_state]).unwrap();
-    ///     out_tuple.set_as_output().unwrap();
-    ///     g1.finalize().unwrap();
-    /// }
+    /// let x = g1.input(t.clone()).unwrap();
+    /// let _i = g1.input(scalar_type(UINT64)).unwrap();
+    /// let doubled = x.add(x.clone()).unwrap();
+    /// let empty = g1.create_tuple(vec![]).unwrap();
+    /// g1.create_tuple(vec![doubled, empty]).unwrap().set_as_output().unwrap();
+    /// g1.finalize().unwrap();
     ///
     /// let g2 = c.create_graph().unwrap();
     /// let initial_state = g2.input(t).unwrap();
-    /// let input_vector = g2.input(vec_t).unwrap();
-    /// g2.iterate(g1, initial_state, input_vector).unwrap();
+    /// // Multiplies the initial state by 2^10
+    /// g2.iterate_fixed(g1, initial_state, 10).unwrap().tuple_get(0).unwrap();
     /// ```
-    pub fn iterate(&self, graph: Graph, state: Node, input: Node) -> Result<Node> {
-        self.add_node(vec![state, input], vec![graph], Operation::Iterate)
+    pub fn iterate_fixed(&self, graph: Graph, state: Node, num_iterations: u64) -> Result<Node> {
+        if num_iterations == 0 {
+            return Err(runtime_error!("Number of iterations must be positive"));
+        }
+        let indices: Vec<u64> = (0..num_iterations).collect();
+        let indices = self
+            .constant(
+                Type::Array(vec![num_iterations], UINT64),
+                Value::from_flattened_array(&indices, UINT64)?,
+            )?
+            .array_to_vector()?;
+        self.iterate(graph, state, indices)
     }
 
     /// Adds a node converting an array to a vector.
@@ -2392,6 +3038,55 @@ impl Graph {
         self.add_node(vec![input, indices], vec![], Operation::Gather(axis))
     }
 
+    /// Adds a node that replaces the rows of an input array along a given axis with the rows of another array at given indices.
+    ///
+    /// This operation complements [Graph::gather].
+    /// Given an input array of shape `[..., n, ...]` with `n` at position `axis`, a one-dimensional UINT64 array `indices` of length `k <= n` and an array `updates` of shape `[..., k, ...]`,
+    /// the result coincides with the input except for the rows along `axis` with numbers `indices[i]`, which are replaced by the `i`-th row of `updates`.
+    /// Indices must be unique and smaller than `n`.
+    ///
+    /// For example, scattering along the axis 0 works as follows
+    ///
+    /// `scatter(input=[[1,2],[3,4],[5,6]], indices=[2,0], updates=[[7,8],[9,10]], axis=0) = [[9,10],[3,4],[7,8]]`
+    ///
+    /// This operation is linear in the input and the updates.
+    /// Thus, it is applied to every share locally in MPC if the indices are public.
+    /// If the indices are private, the scattered rows are hidden from all the parties using an oblivious protocol based on sorting and shared permutations.
+    /// The communication of this protocol grows as O(N log<sup>2</sup> N) with N = n + k.
+    /// In MPC, the result is undefined if private indices are not unique or out of range.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - node containing an input array
+    /// * `indices` - node containing a one-dimensional UINT64 array with unique indices
+    /// * `updates` - node containing an array with rows written to the input array
+    /// * `axis` - index of the axis along which rows are written
+    ///
+    /// # Returns
+    ///
+    /// New Scatter node
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{array_type, INT32, UINT64};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let n1 = g.input(array_type(vec![2, 5], INT32)).unwrap();
+    /// let n2 = g.input(array_type(vec![3], UINT64)).unwrap();
+    /// let n3 = g.input(array_type(vec![2, 3], INT32)).unwrap();
+    /// let n4 = g.scatter(n1, n2, n3, 1).unwrap();
+    /// assert_eq!(n4.get_type().unwrap(), array_type(vec![2, 5], INT32));
+    /// ```
+    pub fn scatter(&self, input: Node, indices: Node, updates: Node, axis: u64) -> Result<Node> {
+        self.add_node(
+            vec![input, indices, updates],
+            vec![],
+            Operation::Scatter(axis),
+        )
+    }
+
     /// Checks that the graph has an output node and finalizes the graph.
     ///
     /// After finalization the graph can't be changed.
@@ -2415,6 +3110,9 @@ impl Graph {
     /// g.finalize().unwrap();
     /// ```
     pub fn finalize(&self) -> Result<Graph> {
+        if !self.body.borrow().regions.is_empty() {
+            return Err(runtime_error!("Can't finalize a graph with open regions"));
+        }
         let output_node = self.body.borrow_mut().output_node.clone();
         match output_node {
             Some(_) => {
@@ -2609,6 +3307,11 @@ impl Graph {
                 graph_dependencies: graph_dependencies.iter().map(|g| g.downgrade()).collect(),
                 operation,
                 id,
+                construction_backtrace: if NODE_BACKTRACE_RECORDING.load(Ordering::Relaxed) {
+                    Some(Arc::new(Backtrace::force_capture()))
+                } else {
+                    None
+                },
             })),
         };
         {
@@ -2651,6 +3354,83 @@ impl Graph {
                 return Err(size_checking_result.expect_err("Should not be here"));
             }
         }
+        let regions = self.body.borrow().regions.clone();
+        if !regions.is_empty() {
+            self.get_context()
+                .append_node_provenance(&result, regions)?;
+        }
+        Ok(result)
+    }
+
+    /// Opens a named region of the graph.
+    ///
+    /// Every node added to the graph until the region is closed by [Graph::pop_region] is attributed to this region (see [Node::get_provenance]).
+    /// Regions can be nested, and all of them must be closed before the graph is finalized.
+    ///
+    /// # Arguments
+    ///
+    /// `name` - name of the region, e.g. the step of a protocol
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{scalar_type, INT32};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let x = g.input(scalar_type(INT32)).unwrap();
+    /// g.push_region("step 1").unwrap();
+    /// let y = x.multiply(x.clone()).unwrap();
+    /// g.pop_region().unwrap();
+    /// y.add(x).unwrap().set_as_output().unwrap();
+    /// g.finalize().unwrap();
+    /// assert_eq!(y.get_provenance().unwrap(), vec!["step 1".to_owned()]);
+    /// assert!(g.get_nodes_in_region(&["step 1"]).unwrap() == vec![y]);
+    /// ```
+    pub fn push_region(&self, name: &str) -> Result<Graph> {
+        if self.is_finalized() {
+            return Err(runtime_error!("Can't open a region in a finalized graph"));
+        }
+        self.body.borrow_mut().regions.push(name.to_owned());
+        Ok(self.clone())
+    }
+
+    /// Closes the innermost region opened by [Graph::push_region].
+    pub fn pop_region(&self) -> Result<Graph> {
+        self.body
+            .borrow_mut()
+            .regions
+            .pop()
+            .ok_or_else(|| runtime_error!("There is no open region"))?;
+        Ok(self.clone())
+    }
+
+    /// Returns the nodes of the graph whose provenance contains a given path of custom operations and regions.
+    ///
+    /// For instance, the path `["SetIntersectionMPC", "step 13"]` selects all the nodes generated by step 13 of set intersection including the nodes of the custom operations called by this step,
+    /// provided that the graph is instantiated and inlined.
+    ///
+    /// # Arguments
+    ///
+    /// `path` - consecutive entries of the provenance (see [Node::get_provenance])
+    ///
+    /// # Returns
+    ///
+    /// Nodes with matching provenance in order of construction
+    pub fn get_nodes_in_region(&self, path: &[&str]) -> Result<Vec<Node>> {
+        if path.is_empty() {
+            return Err(runtime_error!("Region path can't be empty"));
+        }
+        let mut result = vec![];
+        for node in self.get_nodes() {
+            let provenance = node.get_provenance()?;
+            if provenance
+                .windows(path.len())
+                .any(|window| window.iter().zip(path).all(|(frame, name)| frame == name))
+            {
+                result.push(node);
+            }
+        }
         Ok(result)
     }
 
@@ -2805,6 +3585,16 @@ pub enum NodeAnnotation {
     PRFMultiplication,
     PRFB2A,
     PRFTruncate,
+    BeaverTriple,
+    BloomFilterIntersection(u64, u64), // (number of hash functions, filter bits per entry of the second set); compiles set intersection to SetIntersectionBloomMPC
+    UnbalancedIntersection, // compiles set intersection to SetIntersectionMPC with the second set preprocessed once for all such intersections
+    ObliviousIntersection, // compiles set intersection to SetIntersectionObliviousMPC, which reveals no PRF outputs to parties
+    PsiLowMC(u64, u64, u64), // (block size, number of S-boxes per round, number of rounds); sets LowMC as the PRF of set intersection
+    PsiAes128,               // sets AES-128 as the PRF of set intersection
+    PsiCuckooParameters(u64, u64), // (expansion factor, stash size); sets the parameters of Cuckoo hashing in set intersection
+    PsiCuckooHashFunctions(u64), // sets the number of hash functions (from 2 to 5) of Cuckoo hashing in set intersection
+    PsiPadding(PaddingPolicy), // sets the padding policy that the numbers of rows of intersected databases must comply with
+    Reveal(u64), // value of the node is revealed in plaintext to the party with this index; recorded by the audit evaluator
 }
 
 #[doc(hidden)]
@@ -2833,6 +3623,8 @@ struct ContextBody {
     nodes_annotations: HashMap<(u64, u64), Vec<NodeAnnotation>>,
     /// (graph_id) -> GraphAnnotation's
     graphs_annotations: HashMap<u64, Vec<GraphAnnotation>>,
+    /// (graph_id, node_id) -> custom operations and regions the node originates from (see [Node::get_provenance])
+    nodes_provenance: HashMap<(u64, u64), Vec<String>>,
     total_size_nodes: u64,
     type_checker: Option<TypeInferenceWorker>,
 }
@@ -2941,6 +3733,9 @@ struct SerializableContextBody {
     nodes_annotations: Vec<((u64, u64), Vec<NodeAnnotation>)>,
     /// (graph_id) -> GraphAnnotation's
     graphs_annotations: Vec<(u64, Vec<GraphAnnotation>)>,
+    /// (graph_id, node_id) -> provenance; omitted if empty to keep the format of contexts without provenance intact
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    nodes_provenance: Vec<((u64, u64), Vec<String>)>,
 }
 
 impl SerializableContextBody {
@@ -3055,6 +3850,24 @@ impl SerializableContextBody {
                 result_context.add_node_annotation(&current_node, annotation.clone())?;
             }
         }
+        for ((graph_id, node_id), provenance) in &self.nodes_provenance {
+            let current_node = {
+                let current_graphs = &result_context.body.borrow().graphs;
+                if *graph_id >= current_graphs.len() as u64 {
+                    return Err(runtime_error!(
+                        "nodes_provenance contain an invalid graph ID"
+                    ));
+                }
+                let current_nodes = &current_graphs[*graph_id as usize].body.borrow().nodes;
+                if *node_id >= current_nodes.len() as u64 {
+                    return Err(runtime_error!(
+                        "nodes_provenance contain an invalid node ID"
+                    ));
+                }
+                current_nodes[*node_id as usize].clone()
+            };
+            result_context.append_node_provenance(&current_node, provenance.clone())?;
+        }
         if self.finalized {
             result_context.finalize()?;
         }
@@ -3118,6 +3931,7 @@ impl Context {
                 output_node: None,
                 id,
                 context: self.downgrade(),
+                regions: vec![],
             })),
         };
         self.body.borrow_mut().graphs.push(result.clone());
@@ -3536,6 +4350,106 @@ impl Context {
 
 /// Methods which aren't supposed to be imported in Python.
 impl Context {
+    /// Returns the structural hash of this context.
+    ///
+    /// The hash is the hex-encoded SHA-256 hash of the serialized graphs, names and annotations of the context.
+    /// Contexts with the same structure have the same hash, independently of the order of adding names and annotations.
+    /// The hash is stored in the serialized context and verified on deserialization.
+    ///
+    /// # Returns
+    ///
+    /// Structural hash of this context
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{scalar_type, BIT};
+    /// let create = |name| {
+    ///     let c = create_context().unwrap();
+    ///     let g = c.create_graph().unwrap();
+    ///     g.input(scalar_type(BIT)).unwrap().set_name(name).unwrap();
+    ///     c
+    /// };
+    /// assert_eq!(create("a").get_structural_hash().unwrap(), create("a").get_structural_hash().unwrap());
+    /// assert_ne!(create("a").get_structural_hash().unwrap(), create("b").get_structural_hash().unwrap());
+    /// ```
+    pub fn get_structural_hash(&self) -> Result<String> {
+        Ok(get_data_hash(&serde_json::to_string(
+            &self.make_serializable(),
+        )?))
+    }
+
+    /// Checks that given typed values can be passed as inputs to the main graph of this context.
+    ///
+    /// Every value should have the type of the corresponding input node.
+    /// If an input node expects a tuple of 3 secret shares, a value of the share type is also accepted, since [get_evaluator_result](crate::evaluators::get_result_util::get_evaluator_result) secret-shares such values.
+    ///
+    /// All the mismatches are collected into one error, which describes each of them with the index (and the name, if any) of the input and the path to the mismatching element,
+    /// e.g. a wrong shape, a wrong scalar type or a missing column of a named tuple.
+    ///
+    /// # Arguments
+    ///
+    /// `inputs` - typed values of inputs in the order of input nodes of the main graph
+    ///
+    /// # Returns
+    ///
+    /// Runtime error if the inputs don't match the input nodes
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{array_type, INT32};
+    /// # use ciphercore_base::data_values::Value;
+    /// # use ciphercore_base::typed_value::TypedValue;
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let n = g.input(array_type(vec![2, 3], INT32)).unwrap();
+    /// n.set_as_output().unwrap();
+    /// g.finalize().unwrap();
+    /// g.set_as_main().unwrap();
+    /// c.finalize().unwrap();
+    /// let input = |shape: Vec<u64>| {
+    ///     TypedValue::new(array_type(shape, INT32), Value::from_flattened_array(&[0; 6], INT32).unwrap()).unwrap()
+    /// };
+    /// assert!(c.validate_inputs(&[input(vec![2, 3])]).is_ok());
+    /// let error = c.validate_inputs(&[input(vec![3, 2])]).unwrap_err();
+    /// assert!(error.to_string().contains("wrong shape"));
+    /// ```
+    pub fn validate_inputs(&self, inputs: &[TypedValue]) -> Result<()> {
+        let mut input_nodes = vec![];
+        for node in self.get_main_graph()?.get_nodes() {
+            if let Operation::Input(t) = node.get_operation() {
+                input_nodes.push((node.get_name().ok(), t));
+            }
+        }
+        if inputs.len() != input_nodes.len() {
+            return Err(runtime_error!(
+                "Invalid number of inputs: {} expected, {} received",
+                input_nodes.len(),
+                inputs.len()
+            ));
+        }
+        let mut mismatches = vec![];
+        for (i, ((name, t), input)) in input_nodes.iter().zip(inputs).enumerate() {
+            if input.t == *t || is_share_type_of(&input.t, t) {
+                continue;
+            }
+            let input_description = match name {
+                Some(name) => format!("input {} ({})", i, name),
+                None => format!("input {}", i),
+            };
+            for mismatch in describe_type_mismatch(t, &input.t) {
+                mismatches.push(format!("{}: {}", input_description, mismatch));
+            }
+        }
+        if !mismatches.is_empty() {
+            return Err(runtime_error!("Invalid inputs:\n{}", mismatches.join("\n")));
+        }
+        Ok(())
+    }
+
     pub(super) fn is_finalized(&self) -> bool {
         self.body.borrow().finalized
     }
@@ -3546,6 +4460,12 @@ impl Context {
             Err(_) => None,
         };
         let cell = self.body.borrow();
+        // Entries are sorted to make the serialization (and the structural hash) deterministic
+        fn sorted<K: Ord + Clone, V: Clone>(map: &HashMap<K, V>) -> Vec<(K, V)> {
+            let mut entries: Vec<(K, V)> = map.clone().into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            entries
+        }
         Arc::new(SerializableContextBody {
             finalized: self.is_finalized(),
             graphs: self
@@ -3554,10 +4474,11 @@ impl Context {
                 .map(|g| g.make_serializable())
                 .collect(),
             main_graph,
-            graphs_names: cell.graphs_names.clone().into_iter().collect(),
-            nodes_names: cell.nodes_names.clone().into_iter().collect(),
-            graphs_annotations: cell.graphs_annotations.clone().into_iter().collect(),
-            nodes_annotations: cell.nodes_annotations.clone().into_iter().collect(),
+            graphs_names: sorted(&cell.graphs_names),
+            nodes_names: sorted(&cell.nodes_names),
+            graphs_annotations: sorted(&cell.graphs_annotations),
+            nodes_annotations: sorted(&cell.nodes_annotations),
+            nodes_provenance: sorted(&cell.nodes_provenance),
         })
     }
 
@@ -3627,6 +4548,7 @@ impl Context {
         let mut cell = self.body.borrow_mut();
         let name_option = cell.nodes_names.remove(&(graph_id, node_id));
         cell.nodes_annotations.remove(&(graph_id, node_id));
+        cell.nodes_provenance.remove(&(graph_id, node_id));
         if cell.nodes_names_inverse.get(&graph_id).is_none() {
             return Ok(());
         }
@@ -3640,11 +4562,11 @@ impl Context {
         Ok(())
     }
 
-    fn to_versioned_data(&self) -> Result<VersionedData> {
-        VersionedData::create_versioned_data(
+    fn to_versioned_data(&self) -> Result<HashedVersionedData> {
+        Ok(HashedVersionedData::create_hashed_data(
             DATA_VERSION,
             serde_json::to_string(&self.make_serializable())?,
-        )
+        ))
     }
     fn prepare_input_values<T: Clone>(
         &self,
@@ -3709,6 +4631,46 @@ impl Context {
         Ok(self.clone())
     }
 
+    pub(super) fn append_node_provenance(
+        &self,
+        node: &Node,
+        provenance: Vec<String>,
+    ) -> Result<Context> {
+        if node.get_graph().get_context() != *self {
+            return Err(runtime_error!("The node is in a different context"));
+        }
+        if self.is_finalized() {
+            return Err(runtime_error!(
+                "Can't change the node provenance in a finalized context"
+            ));
+        }
+        if provenance.is_empty() {
+            return Ok(self.clone());
+        }
+        let key = (node.get_graph().get_id(), node.get_id());
+        self.body
+            .borrow_mut()
+            .nodes_provenance
+            .entry(key)
+            .or_default()
+            .extend(provenance);
+        Ok(self.clone())
+    }
+
+    pub(super) fn get_node_provenance(&self, node: &Node) -> Result<Vec<String>> {
+        if node.get_graph().get_context() != *self {
+            return Err(runtime_error!("The node is in a different context"));
+        }
+        let key = (node.get_graph().get_id(), node.get_id());
+        Ok(self
+            .body
+            .borrow()
+            .nodes_provenance
+            .get(&key)
+            .cloned()
+            .unwrap_or_default())
+    }
+
     pub(super) fn get_node_annotations(&self, node: Node) -> Result<Vec<NodeAnnotation>> {
         if node.get_graph().get_context() != *self {
             return Err(runtime_error!("The node is in a different context"));
@@ -3782,20 +4744,194 @@ impl<'de> Deserialize<'de> for Context {
     where
         D: Deserializer<'de>,
     {
-        let versioned_context = VersionedData::deserialize(deserializer)?;
+        let versioned_context = HashedVersionedData::deserialize(deserializer)?;
         if !versioned_context.check_version(DATA_VERSION) {
-            Err(runtime_error!(
-                "Context version doesn't match the requirement"
+            return Err(runtime_error!(
+                "Context version doesn't match the requirement: got {}, supported {}",
+                versioned_context.get_version(),
+                DATA_VERSION
+            ))
+            .map_err(serde::de::Error::custom);
+        }
+        if !versioned_context.check_hash() {
+            return Err(runtime_error!(
+                "Context hash mismatch: serialized data is corrupted"
             ))
+            .map_err(serde::de::Error::custom);
+        }
+        let data = versioned_context.get_data_string();
+        let serializable_context = serde_json::from_str::<SerializableContext>(data)
+            .map_err(|e| describe_incompatibility(data, e))
+            .map_err(serde::de::Error::custom)?;
+        serializable_context
+            .recover_original_context()
             .map_err(serde::de::Error::custom)
-        } else {
-            let serializable_context =
-                serde_json::from_str::<SerializableContext>(versioned_context.get_data_string())
-                    .expect("Error during deserialization of SerializableContext");
-            serializable_context
-                .recover_original_context()
-                .map_err(serde::de::Error::custom)
+    }
+}
+
+// Explains why serialized context data can't be deserialized.
+// If some operations are unknown to this version of CipherCore (e.g. custom operations, which are not registered in this binary), their names are listed.
+// Otherwise, the original parsing error is returned.
+fn describe_incompatibility(data: &str, error: serde_json::Error) -> CiphercoreBaseError {
+    let mut unsupported_operations = vec![];
+    let mut unsupported_custom_operations = vec![];
+    if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
+        let graphs = json["graphs"].as_array().cloned().unwrap_or_default();
+        for graph in graphs {
+            let nodes = graph["nodes"].as_array().cloned().unwrap_or_default();
+            for node in nodes {
+                let operation = &node["operation"];
+                if serde_json::from_value::<Operation>(operation.clone()).is_ok() {
+                    continue;
+                }
+                let custom_name = operation["Custom"]["body"]["type"].as_str();
+                let (name, names) = match custom_name {
+                    Some(name) => (name, &mut unsupported_custom_operations),
+                    None => {
+                        let name = match operation {
+                            serde_json::Value::String(name) => Some(name.as_str()),
+                            serde_json::Value::Object(fields) => {
+                                fields.keys().next().map(|name| name.as_str())
+                            }
+                            _ => None,
+                        };
+                        match name {
+                            Some(name) => (name, &mut unsupported_operations),
+                            None => continue,
+                        }
+                    }
+                };
+                if !names.iter().any(|n| n == name) {
+                    names.push(name.to_owned());
+                }
+            }
+        }
+    }
+    if unsupported_operations.is_empty() && unsupported_custom_operations.is_empty() {
+        return runtime_error!("Failed to deserialize context: {}", error);
+    }
+    let mut reasons = vec![];
+    if !unsupported_operations.is_empty() {
+        reasons.push(format!(
+            "unsupported operations: {}",
+            unsupported_operations.join(", ")
+        ));
+    }
+    if !unsupported_custom_operations.is_empty() {
+        reasons.push(format!(
+            "unsupported custom operations: {}",
+            unsupported_custom_operations.join(", ")
+        ));
+    }
+    runtime_error!(
+        "Context is incompatible with this version of CipherCore: {}",
+        reasons.join("; ")
+    )
+}
+
+// Checks whether a type is the type of a share of a tuple of secret shares
+fn is_share_type_of(t: &Type, shared_t: &Type) -> bool {
+    match shared_t {
+        Type::Tuple(share_types) => {
+            share_types.len() == 3 && share_types.iter().all(|share_t| **share_t == *t)
+        }
+        _ => false,
+    }
+}
+
+// Returns human-readable descriptions of the differences between an expected type and a given one.
+// Each description starts with the path to the differing element (e.g. a column of a named tuple), if it is nested.
+fn describe_type_mismatch(expected: &Type, actual: &Type) -> Vec<String> {
+    let nested = |path: String, expected: &Type, actual: &Type| -> Vec<String> {
+        describe_type_mismatch(expected, actual)
+            .into_iter()
+            .map(|mismatch| format!("{}: {}", path, mismatch))
+            .collect()
+    };
+    match (expected, actual) {
+        (Type::Scalar(_) | Type::Array(_, _), Type::Scalar(_) | Type::Array(_, _)) => {
+            let mut mismatches = vec![];
+            if expected.get_shape() != actual.get_shape() {
+                mismatches.push(format!(
+                    "wrong shape: expected {:?}, got {:?}",
+                    expected.get_shape(),
+                    actual.get_shape()
+                ));
+            }
+            if expected.get_scalar_type() != actual.get_scalar_type() {
+                mismatches.push(format!(
+                    "wrong scalar type: expected {}, got {}",
+                    expected.get_scalar_type(),
+                    actual.get_scalar_type()
+                ));
+            }
+            mismatches
+        }
+        (Type::NamedTuple(expected_elements), Type::NamedTuple(actual_elements)) => {
+            let mut mismatches = vec![];
+            for (header, expected_t) in expected_elements {
+                match actual_elements.iter().find(|(h, _)| h == header) {
+                    Some((_, actual_t)) => mismatches.extend(nested(
+                        format!("column {}", header),
+                        expected_t,
+                        actual_t,
+                    )),
+                    None if header == NULL_HEADER => {
+                        mismatches.push(format!("missing {} column", NULL_HEADER))
+                    }
+                    None => mismatches.push(format!("missing column {}", header)),
+                }
+            }
+            for (header, _) in actual_elements {
+                if !expected_elements.iter().any(|(h, _)| h == header) {
+                    mismatches.push(format!("unexpected column {}", header));
+                }
+            }
+            let expected_headers: Vec<&String> = expected_elements.iter().map(|(h, _)| h).collect();
+            let actual_headers: Vec<&String> = actual_elements.iter().map(|(h, _)| h).collect();
+            if mismatches.is_empty() && expected_headers != actual_headers {
+                mismatches.push(format!(
+                    "wrong order of columns: expected {:?}, got {:?}",
+                    expected_headers, actual_headers
+                ));
+            }
+            mismatches
+        }
+        (Type::Tuple(expected_elements), Type::Tuple(actual_elements)) => {
+            if expected_elements.len() != actual_elements.len() {
+                return vec![format!(
+                    "wrong number of tuple elements: expected {}, got {}",
+                    expected_elements.len(),
+                    actual_elements.len()
+                )];
+            }
+            let mut mismatches = vec![];
+            for (i, (expected_t, actual_t)) in
+                expected_elements.iter().zip(actual_elements).enumerate()
+            {
+                mismatches.extend(nested(format!("element {}", i), expected_t, actual_t));
+            }
+            mismatches
+        }
+        (
+            Type::Vector(expected_length, expected_element_t),
+            Type::Vector(actual_length, actual_element_t),
+        ) => {
+            let mut mismatches = vec![];
+            if expected_length != actual_length {
+                mismatches.push(format!(
+                    "wrong vector length: expected {}, got {}",
+                    expected_length, actual_length
+                ));
+            }
+            mismatches.extend(nested(
+                "element".to_owned(),
+                expected_element_t,
+                actual_element_t,
+            ));
+            mismatches
         }
+        _ => vec![format!("expected {}, got {}", expected, actual)],
     }
 }
 
@@ -3813,6 +4949,7 @@ pub(super) fn create_unchecked_context() -> Result<Context> {
             nodes_names_inverse: HashMap::new(),
             graphs_annotations: HashMap::new(),
             nodes_annotations: HashMap::new(),
+            nodes_provenance: HashMap::new(),
             type_checker: None,
             total_size_nodes: 0,
         })),
@@ -3947,6 +5084,16 @@ pub(crate) fn copy_node_name(in_node: Node, out_node: Node) -> Result<()> {
     Ok(())
 }
 
+// Pass the provenance of `in_node` to `out_node` on top of the regions open in the graph of `out_node`.
+pub(crate) fn copy_node_provenance(in_node: Node, out_node: Node) -> Result<()> {
+    let provenance = in_node.get_provenance()?;
+    out_node
+        .get_graph()
+        .get_context()
+        .append_node_provenance(&out_node, provenance)?;
+    Ok(())
+}
+
 type WeakContextBodyPointer = Weak<AtomicRefCell<ContextBody>>;
 
 pub(super) struct WeakContext {
@@ -3990,7 +5137,8 @@ impl PyBindingSliceElement {
 mod tests {
     use super::*;
     use crate::data_types::{
-        array_type, scalar_type, tuple_type, vector_type, BIT, UINT16, UINT64,
+        array_type, named_tuple_type, scalar_type, tuple_type, vector_type, BIT, INT32, INT64,
+        UINT16, UINT32, UINT64,
     };
     use crate::inline::inline_ops::InlineConfig;
     use crate::mpc::mpc_compiler::{prepare_for_mpc_evaluation, IOStatus};
@@ -4126,6 +5274,7 @@ mod tests {
                 graph_dependencies: vec![],
                 operation: Operation::Input(scalar_type(BIT)),
                 id: 0,
+                construction_backtrace: None,
             })),
         };
         let e2 = graph.add(fake_node.clone(), input1.clone());
@@ -4137,6 +5286,7 @@ mod tests {
                 graph_dependencies: vec![],
                 operation: Operation::Input(scalar_type(BIT)),
                 id: 31337,
+                construction_backtrace: None,
             })),
         };
         let e3 = graph.add(fake_node_2.clone(), input1.clone());
@@ -4568,7 +5718,61 @@ mod tests {
         );
     }
 
-    use crate::data_types::INT32;
+    #[test]
+    fn test_context_compatibility() {
+        || -> Result<()> {
+            let create = |annotation_first: bool| -> Result<Context> {
+                let c = create_context()?;
+                let g = c.create_graph()?;
+                let i = g.input(scalar_type(BIT))?;
+                let o = g.custom_op(CustomOperation::new(crate::custom_ops::Not {}), vec![i])?;
+                if annotation_first {
+                    g.add_annotation(GraphAnnotation::AssociativeOperation)?;
+                    g.set_name("main")?;
+                } else {
+                    g.set_name("main")?;
+                    g.add_annotation(GraphAnnotation::AssociativeOperation)?;
+                }
+                o.set_name("output")?;
+                o.set_as_output()?;
+                g.finalize()?.set_as_main()?;
+                c.finalize()?;
+                Ok(c)
+            };
+            let c = create(true)?;
+            let hash = c.get_structural_hash()?;
+            assert_eq!(hash, create(false)?.get_structural_hash()?);
+            let c2 = create_context()?;
+            c2.create_graph()?;
+            assert_ne!(hash, c2.get_structural_hash()?);
+
+            let serialized = serde_json::to_string(&c)?;
+            assert!(serialized.contains(&hash));
+            let deserialized = serde_json::from_str::<Context>(&serialized)?;
+            assert_eq!(deserialized.get_structural_hash()?, hash);
+
+            let tampered = serialized.replace("output", "outpvt");
+            deserialize_error_lenient(&tampered, "Context hash mismatch");
+
+            // Data without a hash is accepted, but unknown operations are reported
+            let mut json = serde_json::from_str::<serde_json::Value>(&serialized)?;
+            json.as_object_mut().unwrap().remove("hash");
+            let data = json["data"].as_str().unwrap().to_owned();
+            json["data"] = serde_json::Value::String(data.clone());
+            assert!(serde_json::from_value::<Context>(json.clone()).is_ok());
+            json["data"] = serde_json::Value::String(
+                data.replace("\"Not\"", "\"UnknownOp\"")
+                    .replace("\"Input\"", "\"Teleport\""),
+            );
+            let error = serde_json::from_value::<Context>(json).unwrap_err();
+            assert!(error.to_string().contains(
+                "unsupported operations: Teleport; unsupported custom operations: UnknownOp"
+            ));
+            Ok(())
+        }()
+        .unwrap();
+    }
+
     use crate::data_values::Value;
     use crate::evaluators::random_evaluate;
     use std::iter::FromIterator;
@@ -4846,6 +6050,84 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn test_validate_inputs() {
+        || -> Result<()> {
+            let database_t = |columns: Vec<(&str, Type)>| {
+                named_tuple_type(
+                    columns
+                        .into_iter()
+                        .map(|(header, t)| (header.to_owned(), t))
+                        .collect(),
+                )
+            };
+            let expected_database_t = database_t(vec![
+                (NULL_HEADER, array_type(vec![4], BIT)),
+                ("a", array_type(vec![4], INT32)),
+            ]);
+            let context = create_context()?;
+            let g = context.create_graph()?;
+            let x = g.input(array_type(vec![2, 3], INT32))?.set_name("x")?;
+            let d = g.input(expected_database_t.clone())?;
+            let s = g.input(tuple_type(vec![scalar_type(UINT64); 3]))?;
+            g.create_tuple(vec![x, d, s])?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            context.finalize()?;
+
+            let input = |t: Type| TypedValue::new(t.clone(), Value::zero_of_type(t));
+            let x_input = input(array_type(vec![2, 3], INT32))?;
+            let d_input = input(expected_database_t.clone())?;
+            // A plaintext value is accepted for a secret-shared input
+            let s_input = input(scalar_type(UINT64))?;
+            context.validate_inputs(&[x_input.clone(), d_input.clone(), s_input.clone()])?;
+            context.validate_inputs(&[
+                x_input.clone(),
+                d_input.clone(),
+                input(tuple_type(vec![scalar_type(UINT64); 3]))?,
+            ])?;
+
+            let error_message = |inputs: Vec<TypedValue>| -> String {
+                context.validate_inputs(&inputs).unwrap_err().to_string()
+            };
+            let message = error_message(vec![x_input.clone(), d_input.clone()]);
+            assert!(message.contains("Invalid number of inputs: 3 expected, 2 received"));
+            let message = error_message(vec![
+                input(array_type(vec![3, 2], INT64))?,
+                input(database_t(vec![("a", array_type(vec![4], INT32))]))?,
+                input(scalar_type(UINT32))?,
+            ]);
+            assert!(message.contains("input 0 (x): wrong shape: expected [2, 3], got [3, 2]"));
+            assert!(message.contains("input 0 (x): wrong scalar type: expected i32, got i64"));
+            assert!(message.contains(&format!("input 1: missing {} column", NULL_HEADER)));
+            assert!(message.contains("input 2: expected (u64, u64, u64), got u32"));
+            let message = error_message(vec![
+                x_input.clone(),
+                input(database_t(vec![
+                    (NULL_HEADER, array_type(vec![5], BIT)),
+                    ("b", array_type(vec![5], INT32)),
+                ]))?,
+                s_input.clone(),
+            ]);
+            assert!(message.contains(&format!(
+                "input 1: column {}: wrong shape: expected [4], got [5]",
+                NULL_HEADER
+            )));
+            assert!(message.contains("input 1: missing column a"));
+            assert!(message.contains("input 1: unexpected column b"));
+            let message = error_message(vec![
+                x_input,
+                input(database_t(vec![
+                    ("a", array_type(vec![4], INT32)),
+                    (NULL_HEADER, array_type(vec![4], BIT)),
+                ]))?,
+                s_input,
+            ]);
+            assert!(message.contains("input 1: wrong order of columns"));
+            Ok(())
+        }()
+        .unwrap();
+    }
+
     #[test]
     fn test_operation_fmt_display() {
         let test_operation_fmt_display_helper = || -> Result<()> {
@@ -4892,6 +6174,48 @@ mod tests {
         test_annotations_helper().unwrap();
     }
 
+    #[test]
+    fn test_regions() {
+        || -> Result<()> {
+            let context = create_context()?;
+            let g = context.create_graph()?;
+            let i = g.input(scalar_type(INT32))?;
+            g.push_region("outer")?;
+            let a = i.add(i.clone())?;
+            g.push_region("inner")?;
+            let m = a.multiply(i.clone())?;
+            g.pop_region()?;
+            let s = m.subtract(a.clone())?;
+            assert!(g.finalize().is_err());
+            g.pop_region()?;
+            assert!(g.pop_region().is_err());
+            s.set_as_output()?;
+            g.finalize()?;
+            assert!(g.push_region("late").is_err());
+            g.set_as_main()?;
+            context.finalize()?;
+            assert!(i.get_provenance()?.is_empty());
+            assert_eq!(m.get_provenance()?, vec!["outer", "inner"]);
+            assert_eq!(s.get_provenance()?, vec!["outer"]);
+            assert!(g.get_nodes_in_region(&["outer"])? == vec![a.clone(), m.clone(), s]);
+            assert!(g.get_nodes_in_region(&["inner"])? == vec![m.clone()]);
+            assert!(g.get_nodes_in_region(&["outer", "inner"])? == vec![m]);
+            assert!(g.get_nodes_in_region(&["inner", "outer"])?.is_empty());
+            assert!(g.get_nodes_in_region(&[]).is_err());
+            // Provenance survives serialization
+            let serialized = serde_json::to_string(&context)?;
+            let deserialized = serde_json::from_str::<Context>(&serialized)?;
+            let deserialized_nodes = deserialized.get_main_graph()?.get_nodes();
+            assert_eq!(
+                deserialized_nodes[2].get_provenance()?,
+                vec!["outer", "inner"]
+            );
+            assert!(deserialized_nodes[0].get_provenance()?.is_empty());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
     async fn parallel_get_type(output: Node) -> Result<Type> {
         output.get_type()
     }
@@ -0,0 +1,290 @@
+//! Rendering of contexts in the [Graphviz](https://graphviz.org/) DOT language.
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, Node, NodeAnnotation, Operation};
+
+use std::collections::HashSet;
+
+fn get_graphviz_node_ref(node: &Node) -> String {
+    format!("node_{}_{}", node.get_id(), node.get_graph().get_id())
+}
+
+fn get_graphviz_graph_ref(graph: &Graph) -> String {
+    format!("cluster{}", graph.get_id())
+}
+
+/// Returns the name of the custom operation if a given graph is produced by the instantiation pass,
+/// i.e. its name has the form `__<op_name>::<<argument_types>>`.
+fn get_custom_op_name(graph: &Graph) -> Option<String> {
+    let name = graph.get_name().ok()?;
+    if name.starts_with("__") && name.contains("::<") && name.ends_with('>') {
+        Some(name[2..].to_owned())
+    } else {
+        None
+    }
+}
+
+/// Escapes a string to be used within a double-quoted DOT label.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Returns the sender and receiver party information of a node formatted as
+// `\nSend[sender_id->receiver_id], ...` or an empty string if there are no `Send(_, _)` annotations.
+fn get_send_annotations_str(node: &Node) -> Result<String> {
+    let sends: Vec<String> = node
+        .get_annotations()?
+        .into_iter()
+        .filter_map(|annotation| match annotation {
+            NodeAnnotation::Send(sender_id, receiver_id) => {
+                Some(format!("Send[{}->{}]", sender_id, receiver_id))
+            }
+            _ => None,
+        })
+        .collect();
+    if sends.is_empty() {
+        Ok("".to_owned())
+    } else {
+        Ok(format!("\\n{}", sends.join(", ")))
+    }
+}
+
+fn get_graphviz_node_def(node: &Node, collapsed_op: Option<String>) -> Result<String> {
+    let is_input_node = matches!(node.get_operation(), Operation::Input(_));
+    let is_output_node = *node == node.get_graph().get_output_node()?;
+    let node_style = if is_input_node && is_output_node {
+        ", shape=box, style=filled, color=magenta"
+    } else if is_input_node {
+        ", shape=box, style=filled, color=royalblue"
+    } else if is_output_node {
+        ", shape=box, style=filled, color=crimson"
+    } else if collapsed_op.is_some() {
+        ", shape=box3d"
+    } else {
+        ""
+    };
+    let operation = match collapsed_op {
+        Some(op_name) => escape(&op_name),
+        None => escape(&node.get_operation().to_string()),
+    };
+    let node_name = if let Ok(s) = node.get_name() {
+        format!("\\n{}\\n", escape(&s))
+    } else {
+        "".to_owned()
+    };
+    Ok(format!(
+        "\t{} [label=\"{}{}\\n{}{}\"{}]\n",
+        get_graphviz_node_ref(node),
+        operation,
+        get_send_annotations_str(node)?,
+        escape(&node.get_type()?.to_string()),
+        node_name,
+        node_style
+    ))
+}
+
+fn get_graphviz_open_subgraph(graph: &Graph) -> String {
+    let graph_name = graph.get_name().unwrap_or_default();
+    format!(
+        "\n\tsubgraph {} {{\nlabel = \"{}\"\n",
+        get_graphviz_graph_ref(graph),
+        escape(&graph_name)
+    )
+}
+
+/// Returns the IDs of graphs that should be hidden when custom operations are collapsed,
+/// i.e. instantiated custom operations and the graphs used exclusively by them.
+fn get_hidden_graphs(context: &Context) -> HashSet<u64> {
+    let graphs = context.get_graphs();
+    let is_custom_op = |graph: &Graph| get_custom_op_name(graph).is_some();
+    // Graphs reachable from instantiated custom operations
+    let mut hidden = HashSet::new();
+    let mut stack: Vec<Graph> = graphs.iter().filter(|g| is_custom_op(g)).cloned().collect();
+    while let Some(graph) = stack.pop() {
+        if hidden.insert(graph.get_id()) {
+            for node in graph.get_nodes() {
+                stack.extend(node.get_graph_dependencies());
+            }
+        }
+    }
+    // Graphs reachable from the remaining graphs without entering custom operations
+    let mut visible = HashSet::new();
+    let mut stack: Vec<Graph> = graphs
+        .iter()
+        .filter(|g| !hidden.contains(&g.get_id()))
+        .cloned()
+        .collect();
+    while let Some(graph) = stack.pop() {
+        if visible.insert(graph.get_id()) {
+            for node in graph.get_nodes() {
+                stack.extend(
+                    node.get_graph_dependencies()
+                        .into_iter()
+                        .filter(|g| !is_custom_op(g)),
+                );
+            }
+        }
+    }
+    hidden.difference(&visible).cloned().collect()
+}
+
+impl Context {
+    /// Renders the graphs of the context in the [Graphviz](https://graphviz.org/) DOT language.
+    ///
+    /// Every graph is rendered as a cluster of nodes labeled with their operations, output types, names and `Send` annotations.
+    /// Input nodes are colored blue, output nodes are colored red.
+    /// Nodes that call other graphs (e.g. Call and Iterate) are connected to the input nodes of these graphs.
+    ///
+    /// If `collapse_custom_ops` is true, graphs produced by the instantiation of custom operations are omitted
+    /// and the Call nodes referring to them are rendered as single nodes labeled with the custom operation name.
+    /// This makes large compiled contexts much easier to inspect.
+    ///
+    /// The result can be converted to an image by `dot -Tpng <file_name>.gv -o <image_name>.png`.
+    ///
+    /// # Arguments
+    ///
+    /// `collapse_custom_ops` - if true, instantiated custom operations are rendered as single nodes
+    ///
+    /// # Returns
+    ///
+    /// DOT code of the context
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ciphercore_base::graphs::create_context;
+    /// # use ciphercore_base::data_types::{scalar_type, BIT};
+    /// let c = create_context().unwrap();
+    /// let g = c.create_graph().unwrap();
+    /// let a = g.input(scalar_type(BIT)).unwrap();
+    /// let b = g.input(scalar_type(BIT)).unwrap();
+    /// a.add(b).unwrap().set_as_output().unwrap();
+    /// g.finalize().unwrap();
+    /// let dot = c.to_dot(false).unwrap();
+    /// assert!(dot.starts_with("digraph{"));
+    /// assert!(dot.contains("Add"));
+    /// ```
+    pub fn to_dot(&self, collapse_custom_ops: bool) -> Result<String> {
+        let hidden_graphs = if collapse_custom_ops {
+            get_hidden_graphs(self)
+        } else {
+            HashSet::new()
+        };
+        let mut dot = String::from("digraph{\n");
+        for graph in self.get_graphs() {
+            if hidden_graphs.contains(&graph.get_id()) {
+                continue;
+            }
+            dot.push_str(&get_graphviz_open_subgraph(&graph));
+            for node in graph.get_nodes() {
+                let graph_dependencies = node.get_graph_dependencies();
+                let collapsed_op = match (node.get_operation(), graph_dependencies.first()) {
+                    (Operation::Call, Some(callee)) if collapse_custom_ops => {
+                        get_custom_op_name(callee)
+                    }
+                    _ => None,
+                };
+                let is_collapsed = collapsed_op.is_some();
+                dot.push_str(&get_graphviz_node_def(&node, collapsed_op)?);
+                // Dependencies have already been rendered since the nodes are topologically sorted
+                for dependency in node.get_node_dependencies() {
+                    dot.push_str(&format!(
+                        "\t\t{} -> {}\n",
+                        get_graphviz_node_ref(&dependency),
+                        get_graphviz_node_ref(&node)
+                    ));
+                }
+                if is_collapsed {
+                    continue;
+                }
+                // Connect the node to the inputs of the graphs it calls
+                for dependency_graph in graph_dependencies {
+                    if hidden_graphs.contains(&dependency_graph.get_id()) {
+                        continue;
+                    }
+                    for input_node in dependency_graph.get_nodes() {
+                        if let Operation::Input(_) = input_node.get_operation() {
+                            dot.push_str(&format!(
+                                "\t{} -> {} [lhead={}];\n",
+                                get_graphviz_node_ref(&node),
+                                get_graphviz_node_ref(&input_node),
+                                get_graphviz_graph_ref(&dependency_graph)
+                            ));
+                        }
+                    }
+                }
+            }
+            dot.push_str("\t}\n");
+        }
+        dot.push('}');
+        Ok(dot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::{run_instantiation_pass, CustomOperation, Not};
+    use crate::data_types::{array_type, scalar_type, BIT};
+    use crate::graphs::create_context;
+
+    #[test]
+    fn test_to_dot() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            g.set_name("main")?;
+            let a = g.input(scalar_type(BIT))?;
+            a.set_name("a")?;
+            let b = g.input(scalar_type(BIT))?;
+            let s = a.add(b)?.nop()?;
+            s.add_annotation(NodeAnnotation::Send(0, 2))?;
+            s.add_annotation(NodeAnnotation::Send(1, 2))?;
+            s.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let dot = c.to_dot(false)?;
+            assert_eq!(
+                dot,
+                "digraph{\n\n\tsubgraph cluster0 {\nlabel = \"main\"\n\
+                 \tnode_0_0 [label=\"Input\\nb\\na\\n\", shape=box, style=filled, color=royalblue]\n\
+                 \tnode_1_0 [label=\"Input\\nb\", shape=box, style=filled, color=royalblue]\n\
+                 \tnode_2_0 [label=\"Add\\nb\"]\n\
+                 \t\tnode_0_0 -> node_2_0\n\
+                 \t\tnode_1_0 -> node_2_0\n\
+                 \tnode_3_0 [label=\"NOP\\nSend[0->2], Send[1->2]\\nb\", shape=box, style=filled, color=crimson]\n\
+                 \t\tnode_2_0 -> node_3_0\n\
+                 \t}\n}"
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_to_dot_collapse_custom_ops() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let a = g.input(array_type(vec![4], BIT))?;
+            let n = g.custom_op(CustomOperation::new(Not {}), vec![a])?;
+            g.custom_op(CustomOperation::new(Not {}), vec![n])?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let instantiated = run_instantiation_pass(c)?.get_context();
+
+            let full = instantiated.to_dot(false)?;
+            assert!(full.contains("label = \"__Not::<b[4]>\""));
+            assert!(full.contains("[lhead=cluster"));
+
+            let collapsed = instantiated.to_dot(true)?;
+            assert!(!collapsed.contains("__Not"));
+            assert!(!collapsed.contains("lhead"));
+            assert_eq!(collapsed.matches("Not::<b[4]>").count(), 2);
+            assert_eq!(collapsed.matches("subgraph").count(), 1);
+            assert!(collapsed.contains("shape=box3d"));
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
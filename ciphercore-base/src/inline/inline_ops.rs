@@ -1,7 +1,7 @@
 use crate::custom_ops::ContextMappings;
 use crate::data_types::Type;
 use crate::errors::Result;
-use crate::graphs::{copy_node_name, create_context, Context};
+use crate::graphs::{copy_node_name, copy_node_provenance, create_context, Context};
 use crate::graphs::{Graph, GraphAnnotation, Node, Operation};
 use crate::inline::associative_iterate_inliner::inline_iterate_associative;
 use crate::inline::empty_state_iterate_inliner::inline_iterate_empty_state;
@@ -9,8 +9,10 @@ use crate::inline::exponential_inliner::inline_iterate_small_state;
 pub use crate::inline::inline_common::DepthOptimizationLevel;
 use crate::inline::inline_common::InlineState;
 use crate::inline::simple_iterate_inliner::inline_iterate_simple;
+use crate::mpc::cost::estimate;
+use crate::ops::utils::select;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub enum InlineMode {
@@ -25,6 +27,9 @@ pub struct InlineConfig {
     pub default_mode: InlineMode,
     pub override_call_mode: Option<InlineMode>,
     pub override_iterate_mode: Option<InlineMode>,
+    /// Modes of Iterate nodes iterating given graphs (indexed by graph IDs); they take precedence over `override_iterate_mode`.
+    #[serde(default)]
+    pub override_graph_modes: HashMap<u64, InlineMode>,
 }
 
 impl Default for InlineConfig {
@@ -33,6 +38,7 @@ impl Default for InlineConfig {
             default_mode: InlineMode::Noop,
             override_call_mode: None,
             override_iterate_mode: None,
+            override_graph_modes: HashMap::new(),
         }
     }
 }
@@ -41,12 +47,18 @@ impl Default for InlineConfig {
 /// like associative operations in Iterate, or per-node/per-operation overrides).
 fn get_mode_for_node(node: Node, config: InlineConfig) -> InlineMode {
     match node.get_operation() {
-        Operation::Call => {
+        Operation::Call | Operation::If => {
             if let Some(mode) = config.override_call_mode {
                 return mode;
             }
         }
         Operation::Iterate => {
+            if let Some(mode) = config
+                .override_graph_modes
+                .get(&node.get_graph_dependencies()[0].get_id())
+            {
+                return mode.clone();
+            }
             if let Some(mode) = config.override_iterate_mode {
                 return mode;
             }
@@ -177,6 +189,151 @@ pub fn inline_operations(context: Context, config: InlineConfig) -> Result<Conte
     Ok(output_context)
 }
 
+/// Summary of the inlining chosen by [inline_operations_with_depth_budget].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InlineReport {
+    /// Configuration resulting in the returned context; the modes of iterated graphs are indexed by graph IDs of the input context.
+    pub config: InlineConfig,
+    /// Multiplicative depth of the inlined context (see [estimate]).
+    pub multiplicative_depth: u64,
+    /// Number of nodes of the main graph of the inlined context.
+    pub num_nodes: u64,
+}
+
+// Inlining modes of Iterate nodes in the ascending order of their size and descending order of their depth
+const ITERATE_MODES: [InlineMode; 3] = [
+    InlineMode::Simple,
+    InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+    InlineMode::DepthOptimized(DepthOptimizationLevel::Extreme),
+];
+
+fn inline_and_estimate(context: Context, config: InlineConfig) -> Result<(Context, InlineReport)> {
+    let inlined_context = inline_operations(context, config.clone())?;
+    let cost = estimate(inlined_context.clone())?;
+    let num_nodes = inlined_context.get_main_graph()?.get_nodes().len() as u64;
+    Ok((
+        inlined_context,
+        InlineReport {
+            config,
+            multiplicative_depth: cost.multiplicative_depth,
+            num_nodes,
+        },
+    ))
+}
+
+fn get_depth_budget_config(levels: &HashMap<u64, usize>) -> InlineConfig {
+    InlineConfig {
+        default_mode: InlineMode::Simple,
+        override_graph_modes: levels
+            .iter()
+            .map(|(id, level)| (*id, ITERATE_MODES[*level].clone()))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+// Inlined context, its report and the levels of iterated graphs resulting in it
+type InliningCandidate = (Context, InlineReport, HashMap<u64, usize>);
+
+// Returns the levels of iterated graphs resulting in the minimal multiplicative depth and then in the minimal number of nodes
+fn get_best_levels(
+    context: Context,
+    candidates: Vec<HashMap<u64, usize>>,
+) -> Result<Option<InliningCandidate>> {
+    let mut best: Option<InliningCandidate> = None;
+    for levels in candidates {
+        let (inlined_context, report) =
+            inline_and_estimate(context.clone(), get_depth_budget_config(&levels))?;
+        let is_better = match &best {
+            Some((_, best_report, _)) => {
+                (report.multiplicative_depth, report.num_nodes)
+                    < (best_report.multiplicative_depth, best_report.num_nodes)
+            }
+            None => true,
+        };
+        if is_better {
+            best = Some((inlined_context, report, levels));
+        }
+    }
+    Ok(best)
+}
+
+/// Fully inlines a given context choosing between the simple and depth-optimized inlining of every iterated graph to meet a given budget of multiplicative depth.
+///
+/// In MPC, every layer of multiplications takes a communication round, so the multiplicative depth of a plaintext context estimates the number of rounds of the compiled protocol.
+/// Depth-optimized inlining of Iterate nodes decreases the depth at the cost of larger graphs, which results in more computation and communication.
+/// Thus, the budget trades latency for bandwidth.
+///
+/// Starting from the simple inlining of all the graphs, the depth optimization level (see [InlineMode]) of iterated graphs is greedily increased one graph at a time, choosing the graph that decreases the depth the most (and results in fewer nodes in case of a tie).
+/// If no graph alone decreases the depth (e.g. when several iterations are evaluated in parallel), the levels of all the graphs are increased at once.
+/// The search stops as soon as the budget is met or the depth can't be decreased.
+/// The context is inlined for every considered configuration, i.e. at most a quadratic number of times in the number of iterated graphs.
+///
+/// Custom operations should be instantiated before inlining, since their depth is not accounted for otherwise.
+///
+/// # Arguments
+///
+/// * `context` - context to inline
+/// * `max_depth` - maximal multiplicative depth
+///
+/// # Returns
+///
+/// Inlined context and the report of its configuration and cost; the reported depth exceeds `max_depth` if the budget can't be met
+pub fn inline_operations_with_depth_budget(
+    context: Context,
+    max_depth: u64,
+) -> Result<(Context, InlineReport)> {
+    context.check_finalized()?;
+    let mut iterated_graph_ids = vec![];
+    for graph in context.get_graphs() {
+        for node in graph.get_nodes() {
+            if let Operation::Iterate = node.get_operation() {
+                let graph_id = node.get_graph_dependencies()[0].get_id();
+                if !iterated_graph_ids.contains(&graph_id) {
+                    iterated_graph_ids.push(graph_id);
+                }
+            }
+        }
+    }
+    let mut levels: HashMap<u64, usize> = iterated_graph_ids.iter().map(|id| (*id, 0)).collect();
+    let mut best = inline_and_estimate(context.clone(), get_depth_budget_config(&levels))?;
+    while best.1.multiplicative_depth > max_depth {
+        // Increase the level of one graph
+        let mut candidates = vec![];
+        for id in &iterated_graph_ids {
+            if levels[id] + 1 < ITERATE_MODES.len() {
+                let mut candidate_levels = levels.clone();
+                candidate_levels.insert(*id, levels[id] + 1);
+                candidates.push(candidate_levels);
+            }
+        }
+        let mut step = get_best_levels(context.clone(), candidates)?;
+        if !matches!(&step, Some((_, report, _)) if report.multiplicative_depth < best.1.multiplicative_depth)
+        {
+            // Increase the levels of all graphs at once
+            let all_levels: HashMap<u64, usize> = levels
+                .iter()
+                .map(|(id, level)| (*id, (*level + 1).min(ITERATE_MODES.len() - 1)))
+                .collect();
+            step = if all_levels != levels {
+                get_best_levels(context.clone(), vec![all_levels])?
+            } else {
+                None
+            };
+        }
+        match step {
+            Some((step_context, report, step_levels))
+                if report.multiplicative_depth < best.1.multiplicative_depth =>
+            {
+                best = (step_context, report);
+                levels = step_levels;
+            }
+            _ => break,
+        }
+    }
+    Ok(best)
+}
+
 /// Determines all subgraphs reachable from graph which won't be inlined.
 fn collect_graphs(
     graph: Graph,
@@ -255,12 +412,18 @@ fn recursively_inline_graph(
                 }
             }
 
+            copy_node_provenance(node.clone(), new_node.clone())?;
             // Every node name in the main graph is copied
             if is_main_graph {
                 copy_node_name(node, new_node)?;
             }
             continue;
         }
+        // Inlined nodes are attributed to the provenance of the inlined node on top of their own provenance
+        let provenance = node.get_provenance()?;
+        for frame in &provenance {
+            output_graph.push_region(frame)?;
+        }
         match node.get_operation() {
             Operation::Call => {
                 let output_node = inline_call(
@@ -290,12 +453,38 @@ fn recursively_inline_graph(
                 }
                 inlining_context.insert_node(node.clone(), output_node.clone());
             }
+            Operation::If => {
+                // Both branches are inlined and the result is selected obliviously.
+                let mut branch_outputs = vec![];
+                for branch in node.get_graph_dependencies() {
+                    branch_outputs.push(inline_call(
+                        branch,
+                        output_graph.clone(),
+                        new_dependencies[1..].to_vec(),
+                        mode.clone(),
+                        inlining_context,
+                    )?);
+                }
+                let output_node = select(
+                    new_dependencies[0].clone(),
+                    branch_outputs[0].clone(),
+                    branch_outputs[1].clone(),
+                )?;
+                // Every node name in the main graph is copied
+                if is_main_graph {
+                    copy_node_name(node.clone(), output_node.clone())?;
+                }
+                inlining_context.insert_node(node.clone(), output_node.clone());
+            }
             _ => {
                 return Err(runtime_error!(
                     "Inlining is not implemented for the operation"
                 ));
             }
         }
+        for _ in &provenance {
+            output_graph.pop_region()?;
+        }
     }
     Ok(inlining_context.get_node(graph.get_output_node()?))
 }
@@ -456,7 +645,7 @@ mod tests {
     use super::*;
     use crate::custom_ops::run_instantiation_pass;
     use crate::custom_ops::CustomOperation;
-    use crate::data_types::{array_type, scalar_type, tuple_type, BIT, UINT64};
+    use crate::data_types::{array_type, scalar_type, tuple_type, vector_type, BIT, UINT64};
     use crate::data_values::Value;
     use crate::evaluators::random_evaluate;
     use crate::graphs::create_context;
@@ -1208,4 +1397,97 @@ mod tests {
         .unwrap();
         assert_eq!(res_equal, vec![0, 0, 1, 0, 0]);
     }
+
+    #[test]
+    fn test_inline_with_depth_budget() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let t = array_type(vec![8], BIT);
+            let create_body = || -> Result<Graph> {
+                let g = c.create_graph()?;
+                let state = g.input(t.clone())?;
+                let input = g.input(t.clone())?;
+                g.create_tuple(vec![state.multiply(input)?, g.create_tuple(vec![])?])?
+                    .set_as_output()?;
+                g.add_annotation(GraphAnnotation::AssociativeOperation)?;
+                g.finalize()
+            };
+            let long_body = create_body()?;
+            let short_body = create_body()?;
+            let g = c.create_graph()?;
+            let init = g.input(t.clone())?;
+            let long_inputs = g.input(vector_type(16, t.clone()))?;
+            let short_inputs = g.input(vector_type(2, t.clone()))?;
+            let long_product = g
+                .iterate(long_body.clone(), init.clone(), long_inputs)?
+                .tuple_get(0)?;
+            let short_product = g
+                .iterate(short_body.clone(), init, short_inputs)?
+                .tuple_get(0)?;
+            g.create_tuple(vec![long_product, short_product])?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+
+            let (simple_c, simple_report) = inline_operations_with_depth_budget(c.clone(), 100)?;
+            assert_eq!(simple_report.multiplicative_depth, 16);
+            assert_eq!(
+                simple_report.config.override_graph_modes,
+                HashMap::from([
+                    (long_body.get_id(), InlineMode::Simple),
+                    (short_body.get_id(), InlineMode::Simple)
+                ])
+            );
+            assert_eq!(simple_c.get_graphs().len(), 1);
+
+            // Only the longer iteration is optimized
+            let (_, report) = inline_operations_with_depth_budget(c.clone(), 8)?;
+            assert!(report.multiplicative_depth <= 8);
+            assert_ne!(
+                report.config.override_graph_modes[&long_body.get_id()],
+                InlineMode::Simple
+            );
+            assert_eq!(
+                report.config.override_graph_modes[&short_body.get_id()],
+                InlineMode::Simple
+            );
+
+            // The budget can't be met, so the lowest depth is reported
+            let (optimized_c, optimized_report) =
+                inline_operations_with_depth_budget(c.clone(), 0)?;
+            assert!(optimized_report.multiplicative_depth > 0);
+            assert!(optimized_report.multiplicative_depth <= report.multiplicative_depth);
+            let result = random_evaluate(
+                optimized_c.get_main_graph()?,
+                vec![
+                    Value::from_flattened_array(&[1; 8], BIT)?,
+                    Value::from_vector(vec![
+                        Value::from_flattened_array(
+                            &[1, 0, 1, 1, 1, 1, 1, 1],
+                            BIT
+                        )?;
+                        16
+                    ]),
+                    Value::from_vector(vec![
+                        Value::from_flattened_array(
+                            &[1, 1, 1, 1, 1, 1, 1, 0],
+                            BIT
+                        )?;
+                        2
+                    ]),
+                ],
+            )?
+            .to_vector()?;
+            assert_eq!(
+                result[0].to_flattened_array_u64(t.clone())?,
+                vec![1, 0, 1, 1, 1, 1, 1, 1]
+            );
+            assert_eq!(
+                result[1].to_flattened_array_u64(t)?,
+                vec![1, 1, 1, 1, 1, 1, 1, 0]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
 }
@@ -0,0 +1,6 @@
+//! Conversion of external data formats into CipherCore values.
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod csv;
+pub mod numpy;
+pub mod padding;
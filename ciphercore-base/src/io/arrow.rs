@@ -0,0 +1,340 @@
+//! Conversion between [Arrow](https://arrow.apache.org/) record batches and named tuples of columns used by [set intersection](crate::graphs::Graph::set_intersection).
+//!
+//! A set intersection input is a named tuple of arrays with the same number of rows (the first dimension).
+//! Besides data columns, it contains the binary column [NULL_HEADER](crate::type_inference::NULL_HEADER) whose zero bits mark empty rows.
+//!
+//! Arrow columns of Boolean, (U)Int8, (U)Int16, (U)Int32 and (U)Int64 types are converted to one-dimensional arrays of the corresponding scalar types;
+//! fixed-size lists of these types are converted to two-dimensional arrays.
+//! Arrow null values are represented by the null column: a row is empty if any of its values is null.
+use crate::data_types::{
+    array_type, named_tuple_type, ScalarType, Type, BIT, INT16, INT32, INT64, INT8, UINT16, UINT32,
+    UINT64, UINT8,
+};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::type_inference::NULL_HEADER;
+use crate::typed_value::TypedValue;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Int16Type, Int32Type, Int64Type, Int8Type, UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+};
+use arrow_array::{Array, ArrayRef, BooleanArray, FixedSizeListArray, PrimitiveArray, RecordBatch};
+use arrow_buffer::{BooleanBuffer, NullBuffer, ScalarBuffer};
+use arrow_schema::{DataType, Field, Schema};
+
+use std::sync::Arc;
+
+fn arrow_to_scalar_type(data_type: &DataType) -> Result<ScalarType> {
+    Ok(match data_type {
+        DataType::Boolean => BIT,
+        DataType::UInt8 => UINT8,
+        DataType::Int8 => INT8,
+        DataType::UInt16 => UINT16,
+        DataType::Int16 => INT16,
+        DataType::UInt32 => UINT32,
+        DataType::Int32 => INT32,
+        DataType::UInt64 => UINT64,
+        DataType::Int64 => INT64,
+        _ => return Err(runtime_error!("Unsupported Arrow type: {}", data_type)),
+    })
+}
+
+/// Converts a flattened array of a given type into an Arrow array without nulls.
+fn value_to_flat_arrow_array(value: &Value, t: Type) -> Result<ArrayRef> {
+    macro_rules! primitive_array {
+        ($arrow_type:ty, $values:expr) => {
+            Arc::new(PrimitiveArray::<$arrow_type>::new(
+                ScalarBuffer::from($values),
+                None,
+            ))
+        };
+    }
+    let st = t.get_scalar_type();
+    Ok(match st {
+        BIT => {
+            let bits = value.to_flattened_array_u8(t)?;
+            Arc::new(BooleanArray::new(
+                BooleanBuffer::from_iter(bits.iter().map(|b| *b != 0)),
+                None,
+            ))
+        }
+        UINT8 => primitive_array!(UInt8Type, value.to_flattened_array_u8(t)?),
+        INT8 => primitive_array!(Int8Type, value.to_flattened_array_i8(t)?),
+        UINT16 => primitive_array!(UInt16Type, value.to_flattened_array_u16(t)?),
+        INT16 => primitive_array!(Int16Type, value.to_flattened_array_i16(t)?),
+        UINT32 => primitive_array!(UInt32Type, value.to_flattened_array_u32(t)?),
+        INT32 => primitive_array!(Int32Type, value.to_flattened_array_i32(t)?),
+        UINT64 => primitive_array!(UInt64Type, value.to_flattened_array_u64(t)?),
+        INT64 => primitive_array!(Int64Type, value.to_flattened_array_i64(t)?),
+        _ => return Err(runtime_error!("Scalar type {} has no Arrow equivalent", st)),
+    })
+}
+
+/// Converts a flat Arrow array without nulls into a flattened array value.
+fn flat_arrow_array_to_value(array: &dyn Array) -> Result<(ScalarType, Value)> {
+    let st = arrow_to_scalar_type(array.data_type())?;
+    let value = match st {
+        BIT => {
+            let bits: Vec<u8> = array.as_boolean().values().iter().map(u8::from).collect();
+            Value::from_flattened_array(&bits, BIT)?
+        }
+        UINT8 => {
+            Value::from_flattened_array(array.as_primitive::<UInt8Type>().values(), st.clone())?
+        }
+        INT8 => Value::from_flattened_array(array.as_primitive::<Int8Type>().values(), st.clone())?,
+        UINT16 => {
+            Value::from_flattened_array(array.as_primitive::<UInt16Type>().values(), st.clone())?
+        }
+        INT16 => {
+            Value::from_flattened_array(array.as_primitive::<Int16Type>().values(), st.clone())?
+        }
+        UINT32 => {
+            Value::from_flattened_array(array.as_primitive::<UInt32Type>().values(), st.clone())?
+        }
+        INT32 => {
+            Value::from_flattened_array(array.as_primitive::<Int32Type>().values(), st.clone())?
+        }
+        UINT64 => {
+            Value::from_flattened_array(array.as_primitive::<UInt64Type>().values(), st.clone())?
+        }
+        INT64 => {
+            Value::from_flattened_array(array.as_primitive::<Int64Type>().values(), st.clone())?
+        }
+        _ => return Err(runtime_error!("Unsupported scalar type: {}", st)),
+    };
+    Ok((st, value))
+}
+
+/// Converts an Arrow column into an array type and value; nulls are ignored.
+fn arrow_column_to_value(array: &dyn Array) -> Result<(Type, Value)> {
+    let num_rows = array.len() as u64;
+    if let DataType::FixedSizeList(_, row_size) = array.data_type() {
+        let list = array.as_fixed_size_list();
+        let (st, value) = flat_arrow_array_to_value(list.values().as_ref())?;
+        Ok((array_type(vec![num_rows, *row_size as u64], st), value))
+    } else {
+        let (st, value) = flat_arrow_array_to_value(array)?;
+        Ok((array_type(vec![num_rows], st), value))
+    }
+}
+
+/// Converts an Arrow record batch into a named tuple that can be used as an input of set intersection.
+///
+/// Every column of the record batch becomes an element of the named tuple with the same name.
+/// If the record batch contains a Boolean column named [NULL_HEADER](crate::type_inference::NULL_HEADER),
+/// it is used as the null column; otherwise, the null column is appended to the named tuple.
+/// In both cases, rows containing Arrow nulls are marked as empty.
+///
+/// # Arguments
+///
+/// `batch` - Arrow record batch with columns of Boolean, (U)Int8, (U)Int16, (U)Int32, (U)Int64 types or fixed-size lists of these types
+///
+/// # Returns
+///
+/// Typed value of a named tuple with the null column
+pub fn record_batch_to_typed_value(batch: &RecordBatch) -> Result<TypedValue> {
+    let num_rows = batch.num_rows();
+    if num_rows == 0 {
+        return Err(runtime_error!("Record batch must contain at least one row"));
+    }
+    let mut row_present = vec![true; num_rows];
+    let mut types = vec![];
+    let mut values = vec![];
+    let mut has_null_column = false;
+    for (field, column) in batch.schema().fields().iter().zip(batch.columns()) {
+        if let Some(nulls) = column.nulls() {
+            for (i, present) in row_present.iter_mut().enumerate() {
+                *present &= nulls.is_valid(i);
+            }
+        }
+        if field.name() == NULL_HEADER {
+            if column.data_type() != &DataType::Boolean {
+                return Err(runtime_error!("Null column must be Boolean"));
+            }
+            for (i, present) in row_present.iter_mut().enumerate() {
+                *present &= column.as_boolean().value(i);
+            }
+            has_null_column = true;
+            continue;
+        }
+        let (t, value) = arrow_column_to_value(column.as_ref())?;
+        types.push((field.name().clone(), t));
+        values.push(value);
+    }
+    let null_bits: Vec<u8> = row_present.into_iter().map(u8::from).collect();
+    let null_column = Value::from_flattened_array(&null_bits, BIT)?;
+    let null_type = array_type(vec![num_rows as u64], BIT);
+    if has_null_column {
+        // Keep the null column at its original position
+        let position = batch
+            .schema()
+            .fields()
+            .iter()
+            .position(|f| f.name() == NULL_HEADER)
+            .unwrap();
+        types.insert(position, (NULL_HEADER.to_owned(), null_type));
+        values.insert(position, null_column);
+    } else {
+        types.push((NULL_HEADER.to_owned(), null_type));
+        values.push(null_column);
+    }
+    TypedValue::new(named_tuple_type(types), Value::from_vector(values))
+}
+
+/// Converts a named tuple of columns (e.g. the output of set intersection) into an Arrow record batch.
+///
+/// One- and two-dimensional arrays are converted to Arrow arrays and fixed-size lists, respectively.
+/// If the named tuple contains the [NULL_HEADER](crate::type_inference::NULL_HEADER) column,
+/// it is exported as a Boolean column and the values of all the other columns in empty rows are marked as Arrow nulls.
+///
+/// # Arguments
+///
+/// `typed_value` - typed value of a named tuple of arrays with the same number of rows
+///
+/// # Returns
+///
+/// Arrow record batch with the same columns
+pub fn typed_value_to_record_batch(typed_value: &TypedValue) -> Result<RecordBatch> {
+    let columns_types = match &typed_value.t {
+        Type::NamedTuple(v) => v.clone(),
+        _ => {
+            return Err(runtime_error!(
+                "Only named tuples can be converted to Arrow"
+            ))
+        }
+    };
+    let columns_values = typed_value.value.to_vector()?;
+    let mut nulls = None;
+    for ((name, t), value) in columns_types.iter().zip(columns_values.iter()) {
+        if name == NULL_HEADER {
+            let bits = value.to_flattened_array_u8((**t).clone())?;
+            nulls = Some(NullBuffer::from_iter(bits.iter().map(|b| *b != 0)));
+        }
+    }
+    let mut fields = vec![];
+    let mut columns = vec![];
+    for ((name, t), value) in columns_types.iter().zip(columns_values.iter()) {
+        let t = (**t).clone();
+        let shape = match &t {
+            Type::Array(shape, _) => shape.clone(),
+            _ => return Err(runtime_error!("Column {} is not an array", name)),
+        };
+        let column_nulls = if name == NULL_HEADER {
+            None
+        } else {
+            nulls.clone()
+        };
+        let flat_array = value_to_flat_arrow_array(value, t.clone())?;
+        let column: ArrayRef = match shape.len() {
+            1 => flat_array
+                .to_data()
+                .into_builder()
+                .nulls(column_nulls)
+                .build()
+                .map(arrow_array::make_array)
+                .map_err(|e| runtime_error!("Arrow error: {}", e))?,
+            2 => {
+                let item = Arc::new(Field::new("item", flat_array.data_type().clone(), false));
+                Arc::new(
+                    FixedSizeListArray::try_new(item, shape[1] as i32, flat_array, column_nulls)
+                        .map_err(|e| runtime_error!("Arrow error: {}", e))?,
+                )
+            }
+            _ => {
+                return Err(runtime_error!(
+                    "Column {} must be one- or two-dimensional",
+                    name
+                ))
+            }
+        };
+        let nullable = column.null_count() > 0;
+        fields.push(Field::new(name, column.data_type().clone(), nullable));
+        columns.push(column);
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+        .map_err(|e| runtime_error!("Arrow error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Int32Array, UInt64Array};
+
+    fn get_test_batch() -> RecordBatch {
+        let ids: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), None, Some(3)]));
+        let flags: ArrayRef = Arc::new(BooleanArray::from(vec![true, false, true]));
+        let pairs = FixedSizeListArray::try_new(
+            Arc::new(Field::new("item", DataType::UInt64, false)),
+            2,
+            Arc::new(UInt64Array::from(vec![10, 11, 20, 21, 30, 31])),
+            None,
+        )
+        .unwrap();
+        RecordBatch::try_from_iter(vec![
+            ("id", ids),
+            ("flag", flags),
+            ("pair", Arc::new(pairs) as ArrayRef),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_record_batch_to_typed_value() {
+        || -> Result<()> {
+            let tv = record_batch_to_typed_value(&get_test_batch())?;
+            assert_eq!(
+                tv.t,
+                named_tuple_type(vec![
+                    ("id".to_owned(), array_type(vec![3], INT32)),
+                    ("flag".to_owned(), array_type(vec![3], BIT)),
+                    ("pair".to_owned(), array_type(vec![3, 2], UINT64)),
+                    (NULL_HEADER.to_owned(), array_type(vec![3], BIT)),
+                ])
+            );
+            let columns = tv.value.to_vector()?;
+            assert_eq!(
+                columns[0].to_flattened_array_i32(array_type(vec![3], INT32))?[0],
+                1
+            );
+            assert_eq!(
+                columns[2].to_flattened_array_u64(array_type(vec![3, 2], UINT64))?,
+                vec![10, 11, 20, 21, 30, 31]
+            );
+            assert_eq!(
+                columns[3].to_flattened_array_u8(array_type(vec![3], BIT))?,
+                vec![1, 0, 1]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_round_trip() {
+        || -> Result<()> {
+            let tv = record_batch_to_typed_value(&get_test_batch())?;
+            let batch = typed_value_to_record_batch(&tv)?;
+            assert_eq!(batch.num_columns(), 4);
+            assert_eq!(batch.column(0).null_count(), 1);
+            assert!(batch.column(2).is_null(1));
+            assert_eq!(batch.column(3).null_count(), 0);
+            assert_eq!(record_batch_to_typed_value(&batch)?, tv);
+
+            // Errors
+            let not_tuple = TypedValue::new(
+                array_type(vec![1], INT32),
+                Value::from_flattened_array(&[1], INT32)?,
+            )?;
+            assert!(typed_value_to_record_batch(&not_tuple).is_err());
+            let bad_null = RecordBatch::try_from_iter(vec![(
+                NULL_HEADER,
+                Arc::new(Int32Array::from(vec![1])) as ArrayRef,
+            )])
+            .unwrap();
+            assert!(record_batch_to_typed_value(&bad_null).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -0,0 +1,335 @@
+//! Loading of CSV files into named tuples of columns used by [set intersection](crate::graphs::Graph::set_intersection).
+//!
+//! The first line of a CSV file must contain column names.
+//! Every selected column is converted to a one-dimensional array of a given or inferred scalar type.
+//! The resulting named tuple contains the binary column [NULL_HEADER](crate::type_inference::NULL_HEADER) whose zero bits mark empty rows.
+//! A row is empty if it contains an empty cell or if it is a padding row.
+use crate::data_types::{array_type, named_tuple_type, ScalarType, Type, BIT, INT64};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::io::padding::{pad_database, PaddingPolicy};
+use crate::type_inference::NULL_HEADER;
+
+use std::fs::File;
+use std::io::Read;
+
+/// Column of a CSV file to be loaded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsvColumn {
+    /// Name of the column in the header of the CSV file
+    pub name: String,
+    /// Scalar type of the column entries; if `None`, it is inferred from the data
+    pub scalar_type: Option<ScalarType>,
+}
+
+impl CsvColumn {
+    /// Creates a column of a given name and scalar type.
+    pub fn new(name: &str, scalar_type: ScalarType) -> Self {
+        CsvColumn {
+            name: name.to_owned(),
+            scalar_type: Some(scalar_type),
+        }
+    }
+
+    /// Creates a column of a given name whose scalar type is inferred from the data.
+    pub fn inferred(name: &str) -> Self {
+        CsvColumn {
+            name: name.to_owned(),
+            scalar_type: None,
+        }
+    }
+}
+
+fn parse_bool(cell: &str) -> Option<u64> {
+    match cell.to_lowercase().as_str() {
+        "0" | "false" => Some(0),
+        "1" | "true" => Some(1),
+        _ => None,
+    }
+}
+
+/// Infers the scalar type of a column: columns containing only `true` and `false` are binary, other columns are INT64.
+fn infer_scalar_type(cells: &[&str]) -> ScalarType {
+    let is_bool = |cell: &&str| matches!(cell.to_lowercase().as_str(), "true" | "false");
+    if !cells.is_empty() && cells.iter().all(is_bool) {
+        BIT
+    } else {
+        INT64
+    }
+}
+
+/// Parses a cell as an integer of a given scalar type and returns its representation modulo 2^64.
+fn parse_cell(cell: &str, st: &ScalarType) -> Result<u64> {
+    if *st == BIT {
+        return parse_bool(cell).ok_or_else(|| runtime_error!("{} is not a bit", cell));
+    }
+    let x: i128 = cell
+        .parse()
+        .map_err(|_| runtime_error!("{} is not an integer", cell))?;
+    let bits = st.size_in_bits();
+    let (min, max) = if st.get_signed() {
+        (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+    } else {
+        (0, (1i128 << bits) - 1)
+    };
+    if x < min || x > max {
+        return Err(runtime_error!("{} doesn't fit into {}", cell, st));
+    }
+    Ok(x as u64)
+}
+
+/// Reads CSV data into a named tuple of columns that can be used as an input of set intersection.
+///
+/// # Arguments
+///
+/// * `reader` - source of CSV data with a header line
+/// * `columns` - columns to be loaded in the given order; if `None`, all the columns are loaded with inferred types
+/// * `num_rows` - number of rows of the result; if the data contains fewer rows, it is padded with empty rows, otherwise extra rows are dropped; if `None`, all the rows are loaded
+///
+/// # Returns
+///
+/// Named tuple type and value of the loaded columns followed by the null column
+pub fn read_csv<R: Read>(
+    reader: R,
+    columns: Option<Vec<CsvColumn>>,
+    num_rows: Option<u64>,
+) -> Result<(Type, Value)> {
+    let mut csv_reader = ::csv::ReaderBuilder::new()
+        .trim(::csv::Trim::All)
+        .from_reader(reader);
+    let headers: Vec<String> = csv_reader.headers()?.iter().map(|h| h.to_owned()).collect();
+    let columns = columns.unwrap_or_else(|| {
+        headers
+            .iter()
+            .filter(|h| h.as_str() != NULL_HEADER)
+            .map(|h| CsvColumn::inferred(h))
+            .collect()
+    });
+    if columns.is_empty() {
+        return Err(runtime_error!("No columns to load"));
+    }
+    let mut column_indices = vec![];
+    for column in &columns {
+        if column.name == NULL_HEADER {
+            return Err(runtime_error!(
+                "{} column can't be loaded explicitly",
+                NULL_HEADER
+            ));
+        }
+        let index = headers
+            .iter()
+            .position(|h| *h == column.name)
+            .ok_or_else(|| runtime_error!("Column {} is not found", column.name))?;
+        column_indices.push(index);
+    }
+    // An existing null column marks empty rows of the input
+    let null_index = headers.iter().position(|h| h == NULL_HEADER);
+
+    let mut records = vec![];
+    for record in csv_reader.records() {
+        if let Some(n) = num_rows {
+            if records.len() as u64 == n {
+                break;
+            }
+        }
+        records.push(record?);
+    }
+    let num_rows = num_rows.unwrap_or(records.len() as u64);
+    if num_rows == 0 {
+        return Err(runtime_error!("Number of rows must be positive"));
+    }
+
+    let mut row_present = vec![0u64; num_rows as usize];
+    for (i, record) in records.iter().enumerate() {
+        let cells_present = column_indices.iter().all(|j| !record[*j].is_empty());
+        let marked_present = match null_index {
+            Some(j) => {
+                parse_bool(&record[j])
+                    .ok_or_else(|| runtime_error!("{} is not a bit", &record[j]))?
+                    == 1
+            }
+            None => true,
+        };
+        row_present[i] = u64::from(cells_present && marked_present);
+    }
+
+    let mut types = vec![];
+    let mut values = vec![];
+    for (column, j) in columns.iter().zip(column_indices) {
+        let cells: Vec<&str> = records
+            .iter()
+            .map(|record| &record[j])
+            .filter(|cell| !cell.is_empty())
+            .collect();
+        let st = match &column.scalar_type {
+            Some(st) => st.clone(),
+            None => infer_scalar_type(&cells),
+        };
+        let mut entries = vec![0u64; num_rows as usize];
+        for (i, record) in records.iter().enumerate() {
+            if row_present[i] == 1 {
+                entries[i] = parse_cell(&record[j], &st)
+                    .map_err(|e| runtime_error!("Column {}, row {}: {}", column.name, i + 1, e))?;
+            }
+        }
+        types.push((column.name.clone(), array_type(vec![num_rows], st.clone())));
+        values.push(Value::from_flattened_array(&entries, st)?);
+    }
+    types.push((NULL_HEADER.to_owned(), array_type(vec![num_rows], BIT)));
+    values.push(Value::from_flattened_array(&row_present, BIT)?);
+    Ok((named_tuple_type(types), Value::from_vector(values)))
+}
+
+/// Loads a CSV file into a named tuple of columns that can be used as an input of set intersection.
+///
+/// See [read_csv] for the description of the arguments and the result.
+pub fn load_csv(
+    path: &str,
+    columns: Option<Vec<CsvColumn>>,
+    num_rows: Option<u64>,
+) -> Result<(Type, Value)> {
+    read_csv(File::open(path)?, columns, num_rows)
+}
+
+/// Reads CSV data as [read_csv] does and pads the result with empty rows according to a given policy, so that the number of rows is revealed only up to a bucket.
+///
+/// # Arguments
+///
+/// * `reader` - source of CSV data with a header line
+/// * `columns` - columns to be loaded in the given order; if `None`, all the columns are loaded with inferred types
+/// * `policy` - padding policy applied to all the rows of the data
+///
+/// # Returns
+///
+/// Named tuple type and value of the loaded columns followed by the null column
+pub fn read_padded_csv<R: Read>(
+    reader: R,
+    columns: Option<Vec<CsvColumn>>,
+    policy: &PaddingPolicy,
+) -> Result<(Type, Value)> {
+    let (t, value) = read_csv(reader, columns, None)?;
+    pad_database(t, value, policy)
+}
+
+/// Loads a CSV file and pads it with empty rows according to a given policy.
+///
+/// See [read_padded_csv] for the description of the arguments and the result.
+pub fn load_padded_csv(
+    path: &str,
+    columns: Option<Vec<CsvColumn>>,
+    policy: &PaddingPolicy,
+) -> Result<(Type, Value)> {
+    read_padded_csv(File::open(path)?, columns, policy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{INT32, UINT8};
+
+    const DATA: &str = "id, flag, score\n\
+                        3, true, -5\n\
+                        7, false,\n\
+                        11, true, 200\n";
+
+    #[test]
+    fn test_read_csv_inferred() {
+        || -> Result<()> {
+            let (t, v) = read_csv(DATA.as_bytes(), None, Some(4))?;
+            assert_eq!(
+                t,
+                named_tuple_type(vec![
+                    ("id".to_owned(), array_type(vec![4], INT64)),
+                    ("flag".to_owned(), array_type(vec![4], BIT)),
+                    ("score".to_owned(), array_type(vec![4], INT64)),
+                    (NULL_HEADER.to_owned(), array_type(vec![4], BIT)),
+                ])
+            );
+            let columns = v.to_vector()?;
+            assert_eq!(
+                columns[0].to_flattened_array_i64(array_type(vec![4], INT64))?,
+                vec![3, 0, 11, 0]
+            );
+            assert_eq!(
+                columns[1].to_flattened_array_u8(array_type(vec![4], BIT))?,
+                vec![1, 0, 1, 0]
+            );
+            assert_eq!(
+                columns[2].to_flattened_array_i64(array_type(vec![4], INT64))?,
+                vec![-5, 0, 200, 0]
+            );
+            assert_eq!(
+                columns[3].to_flattened_array_u8(array_type(vec![4], BIT))?,
+                vec![1, 0, 1, 0]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_read_csv_schema() {
+        || -> Result<()> {
+            let columns = vec![CsvColumn::new("score", INT32), CsvColumn::new("id", UINT8)];
+            let (t, v) = read_csv(DATA.as_bytes(), Some(columns), Some(2))?;
+            assert_eq!(
+                t,
+                named_tuple_type(vec![
+                    ("score".to_owned(), array_type(vec![2], INT32)),
+                    ("id".to_owned(), array_type(vec![2], UINT8)),
+                    (NULL_HEADER.to_owned(), array_type(vec![2], BIT)),
+                ])
+            );
+            let columns = v.to_vector()?;
+            assert_eq!(
+                columns[0].to_flattened_array_i32(array_type(vec![2], INT32))?,
+                vec![-5, 0]
+            );
+            assert_eq!(
+                columns[2].to_flattened_array_u8(array_type(vec![2], BIT))?,
+                vec![1, 0]
+            );
+
+            let data_with_nulls = "null,x\n1,5\n0,6\n";
+            let (_, v) = read_csv(data_with_nulls.as_bytes(), None, None)?;
+            assert_eq!(
+                v.to_vector()?[1].to_flattened_array_u8(array_type(vec![2], BIT))?,
+                vec![1, 0]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_read_padded_csv() {
+        || -> Result<()> {
+            let (t, v) = read_padded_csv(DATA.as_bytes(), None, &PaddingPolicy::PowerOfTwo)?;
+            let types = match t {
+                Type::NamedTuple(types) => types,
+                _ => panic!("Named tuple expected"),
+            };
+            assert!(types.iter().all(|(_, t)| t.get_shape() == vec![4]));
+            assert_eq!(
+                v.to_vector()?[3].to_flattened_array_u8(array_type(vec![4], BIT))?,
+                vec![1, 0, 1, 0]
+            );
+            assert!(
+                read_padded_csv(DATA.as_bytes(), None, &PaddingPolicy::Buckets(vec![2])).is_err()
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_read_csv_errors() {
+        let read = |columns: Vec<CsvColumn>| read_csv(DATA.as_bytes(), Some(columns), None);
+        assert!(read(vec![CsvColumn::inferred("unknown")]).is_err());
+        assert!(read(vec![CsvColumn::new("score", UINT8)]).is_err());
+        assert!(read(vec![CsvColumn::new("flag", INT32)]).is_err());
+        assert!(read(vec![CsvColumn::new(NULL_HEADER, BIT)]).is_err());
+        assert!(read(vec![]).is_err());
+        assert!(read_csv("id\n".as_bytes(), None, None).is_err());
+    }
+}
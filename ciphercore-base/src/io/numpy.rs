@@ -0,0 +1,566 @@
+//! Conversion between [NumPy](https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html) `.npy`/`.npz` files and CipherCore values.
+//!
+//! A `.npy` file contains a single array, which is converted to an array or scalar typed value.
+//! NumPy arrays of types `bool`, `int8`, `uint8`, `int16`, `uint16`, `int32`, `uint32`, `int64` and `uint64` are converted to arrays of the corresponding scalar types ([BIT], [INT8], [UINT8] etc.);
+//! zero-dimensional NumPy arrays are converted to scalars.
+//!
+//! A `.npz` file is a ZIP archive of `.npy` files, which is converted to a named tuple of arrays with the names of the archived files (without the `.npy` extension).
+//! Reading and writing `.npz` files requires the `npz` feature.
+use crate::data_types::{
+    array_type, scalar_type, ScalarType, Type, BIT, INT16, INT32, INT64, INT8, UINT16, UINT32,
+    UINT64, UINT8,
+};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::typed_value::TypedValue;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+#[cfg(feature = "npz")]
+use crate::data_types::named_tuple_type;
+#[cfg(feature = "npz")]
+use std::io::Seek;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+fn scalar_type_to_descr(st: &ScalarType) -> Result<&'static str> {
+    Ok(match *st {
+        BIT => "|b1",
+        INT8 => "|i1",
+        UINT8 => "|u1",
+        INT16 => "<i2",
+        UINT16 => "<u2",
+        INT32 => "<i4",
+        UINT32 => "<u4",
+        INT64 => "<i8",
+        UINT64 => "<u8",
+        _ => return Err(runtime_error!("{} is not supported by NumPy", st)),
+    })
+}
+
+fn descr_to_scalar_type(descr: &str) -> Result<ScalarType> {
+    let unsupported = || {
+        runtime_error!(
+            "Unsupported NumPy data type {}; only little-endian booleans and integers are supported",
+            descr
+        )
+    };
+    if descr == "?" {
+        return Ok(BIT);
+    }
+    let (byte_order, code) = match descr.chars().next() {
+        Some(c @ ('<' | '>' | '|' | '=')) => (c, &descr[1..]),
+        _ => ('=', descr),
+    };
+    let st = match code {
+        "b1" => BIT,
+        "i1" => INT8,
+        "u1" => UINT8,
+        "i2" => INT16,
+        "u2" => UINT16,
+        "i4" => INT32,
+        "u4" => UINT32,
+        "i8" => INT64,
+        "u8" => UINT64,
+        _ => return Err(unsupported()),
+    };
+    // Single-byte types can be given with any byte order
+    if byte_order == '>' && get_entry_size(&st) > 1 {
+        return Err(unsupported());
+    }
+    Ok(st)
+}
+
+// Returns the value of a given key in the header dictionary, e.g. `'<i4'` for `descr`
+fn get_header_entry<'a>(header: &'a str, key: &str) -> Result<&'a str> {
+    let pattern = format!("'{}':", key);
+    let start = header
+        .find(&pattern)
+        .ok_or_else(|| runtime_error!("NumPy header doesn't contain {}", key))?
+        + pattern.len();
+    let rest = header[start..].trim_start();
+    let end = if rest.starts_with('(') {
+        rest.find(')').map(|i| i + 1)
+    } else {
+        rest.find([',', '}'])
+    }
+    .ok_or_else(|| runtime_error!("Malformed NumPy header: {}", header))?;
+    Ok(rest[..end].trim())
+}
+
+struct NpyHeader {
+    st: ScalarType,
+    fortran_order: bool,
+    shape: Vec<u64>,
+}
+
+fn parse_header(header: &str) -> Result<NpyHeader> {
+    let descr = get_header_entry(header, "descr")?.trim_matches(['\'', '"']);
+    let st = descr_to_scalar_type(descr)?;
+    let fortran_order = match get_header_entry(header, "fortran_order")? {
+        "True" => true,
+        "False" => false,
+        s => return Err(runtime_error!("Invalid fortran_order: {}", s)),
+    };
+    let shape_str = get_header_entry(header, "shape")?;
+    let shape = shape_str
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .map(|d| d.trim())
+        .filter(|d| !d.is_empty())
+        .map(|d| {
+            d.parse::<u64>()
+                .map_err(|_| runtime_error!("Invalid NumPy shape: {}", shape_str))
+        })
+        .collect::<Result<Vec<u64>>>()?;
+    if shape.contains(&0) {
+        return Err(runtime_error!("Empty NumPy arrays are not supported"));
+    }
+    Ok(NpyHeader {
+        st,
+        fortran_order,
+        shape,
+    })
+}
+
+// Reads exactly `len` bytes; the buffer grows with the data actually read, so a malformed length can't cause a huge allocation
+fn read_exact<R: Read>(reader: &mut R, len: u64) -> Result<Vec<u8>> {
+    let mut buffer = vec![];
+    reader
+        .take(len)
+        .read_to_end(&mut buffer)
+        .map_err(|e| runtime_error!("Failed to read NumPy data: {}", e))?;
+    if buffer.len() as u64 != len {
+        return Err(runtime_error!(
+            "Failed to read NumPy data: {} bytes expected, but only {} available",
+            len,
+            buffer.len()
+        ));
+    }
+    Ok(buffer)
+}
+
+// Converts column-major entries of an array of a given shape to row-major ones
+fn fortran_to_c_order(entries: Vec<u64>, shape: &[u64]) -> Vec<u64> {
+    let mut c_strides = vec![1; shape.len()];
+    for i in (0..shape.len() - 1).rev() {
+        c_strides[i] = c_strides[i + 1] * shape[i + 1];
+    }
+    (0..entries.len() as u64)
+        .map(|c_index| {
+            let mut fortran_index = 0;
+            let mut fortran_stride = 1;
+            for (dim, c_stride) in shape.iter().zip(c_strides.iter()) {
+                fortran_index += (c_index / c_stride % dim) * fortran_stride;
+                fortran_stride *= dim;
+            }
+            entries[fortran_index as usize]
+        })
+        .collect()
+}
+
+// NumPy stores booleans in bytes
+fn get_entry_size(st: &ScalarType) -> usize {
+    if *st == BIT {
+        1
+    } else {
+        st.size_in_bits() as usize / 8
+    }
+}
+
+/// Reads a `.npy` file from a reader and converts it into a typed value.
+///
+/// # Arguments
+///
+/// `reader` - reader of the `.npy` file contents
+///
+/// # Returns
+///
+/// Typed value of an array, or of a scalar if the NumPy array is zero-dimensional
+pub fn read_npy<R: Read>(mut reader: R) -> Result<TypedValue> {
+    let prefix = read_exact(&mut reader, MAGIC.len() as u64 + 2)?;
+    if &prefix[..MAGIC.len()] != MAGIC {
+        return Err(runtime_error!("Not a NumPy file"));
+    }
+    let header_len = match prefix[MAGIC.len()] {
+        1 => u16::from_le_bytes(read_exact(&mut reader, 2)?.try_into().unwrap()) as usize,
+        2 | 3 => u32::from_le_bytes(read_exact(&mut reader, 4)?.try_into().unwrap()) as usize,
+        v => return Err(runtime_error!("Unsupported NumPy format version {}", v)),
+    };
+    let header_bytes = read_exact(&mut reader, header_len as u64)?;
+    let header = String::from_utf8(header_bytes)
+        .map_err(|_| runtime_error!("NumPy header is not a valid string"))?;
+    let NpyHeader {
+        st,
+        fortran_order,
+        shape,
+    } = parse_header(&header)?;
+    let entry_size = get_entry_size(&st);
+    let data_len = shape
+        .iter()
+        .try_fold(entry_size as u64, |len, d| len.checked_mul(*d))
+        .ok_or_else(|| runtime_error!("NumPy shape is too large: {:?}", shape))?;
+    let data = read_exact(&mut reader, data_len)?;
+    let mut entries: Vec<u64> = data
+        .chunks(entry_size)
+        .map(|chunk| {
+            let mut bytes = [0u8; 8];
+            bytes[..entry_size].copy_from_slice(chunk);
+            u64::from_le_bytes(bytes)
+        })
+        .collect();
+    if st == BIT && entries.iter().any(|x| *x > 1) {
+        return Err(runtime_error!("Invalid NumPy boolean value"));
+    }
+    if fortran_order && shape.len() > 1 {
+        entries = fortran_to_c_order(entries, &shape);
+    }
+    let t = if shape.is_empty() {
+        scalar_type(st.clone())
+    } else {
+        array_type(shape, st.clone())
+    };
+    TypedValue::new(t, Value::from_flattened_array(&entries, st)?)
+}
+
+/// Converts a typed value into a `.npy` file and writes it to a writer.
+///
+/// # Arguments
+///
+/// * `typed_value` - typed value of an array or scalar of a scalar type supported by NumPy (see the [module documentation](self))
+/// * `writer` - writer of the `.npy` file contents
+pub fn write_npy<W: Write>(typed_value: &TypedValue, mut writer: W) -> Result<()> {
+    let (shape, st) = match &typed_value.t {
+        Type::Scalar(st) => (vec![], st.clone()),
+        Type::Array(shape, st) => (shape.clone(), st.clone()),
+        t => {
+            return Err(runtime_error!(
+                "Only arrays and scalars can be converted to NumPy, got {}",
+                t
+            ))
+        }
+    };
+    let shape_str = match shape.len() {
+        1 => format!("({},)", shape[0]),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        scalar_type_to_descr(&st)?,
+        shape_str
+    );
+    // The data must be aligned to 64 bytes; the header ends with a newline
+    let unpadded_len = MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded_len % 64) % 64));
+    header.push('\n');
+    let entry_size = get_entry_size(&st);
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&[1, 0]);
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    let entries = if shape.is_empty() {
+        vec![typed_value.value.to_u64(st)?]
+    } else {
+        typed_value
+            .value
+            .to_flattened_array_u64(typed_value.t.clone())?
+    };
+    for entry in entries {
+        bytes.extend_from_slice(&entry.to_le_bytes()[..entry_size]);
+    }
+    writer
+        .write_all(&bytes)
+        .map_err(|e| runtime_error!("Failed to write NumPy data: {}", e))
+}
+
+/// Loads a `.npy` file from a given path (see [read_npy]).
+pub fn load_npy<P: AsRef<Path>>(path: P) -> Result<TypedValue> {
+    let file = File::open(path).map_err(|e| runtime_error!("Failed to open file: {}", e))?;
+    read_npy(BufReader::new(file))
+}
+
+/// Saves a typed value to a `.npy` file at a given path (see [write_npy]).
+pub fn save_npy<P: AsRef<Path>>(typed_value: &TypedValue, path: P) -> Result<()> {
+    let file = File::create(path).map_err(|e| runtime_error!("Failed to create file: {}", e))?;
+    write_npy(typed_value, BufWriter::new(file))
+}
+
+/// Reads a `.npz` file from a reader and converts it into a named tuple of arrays.
+///
+/// The elements of the named tuple follow the order of the files in the archive; compressed archives created by `numpy.savez_compressed` are supported.
+///
+/// # Arguments
+///
+/// `reader` - reader of the `.npz` file contents
+///
+/// # Returns
+///
+/// Typed value of a named tuple whose elements are the arrays of the archive
+#[cfg(feature = "npz")]
+pub fn read_npz<R: Read + Seek>(reader: R) -> Result<TypedValue> {
+    let mut archive = zip::ZipArchive::new(reader)
+        .map_err(|e| runtime_error!("Failed to read NumPy archive: {}", e))?;
+    let mut types = vec![];
+    let mut values = vec![];
+    for i in 0..archive.len() {
+        let file = archive
+            .by_index(i)
+            .map_err(|e| runtime_error!("Failed to read NumPy archive: {}", e))?;
+        let name = file.name();
+        let name = name.strip_suffix(".npy").unwrap_or(name).to_owned();
+        let typed_value = read_npy(file)?;
+        types.push((name, typed_value.t));
+        values.push(typed_value.value);
+    }
+    TypedValue::new(named_tuple_type(types), Value::from_vector(values))
+}
+
+/// Converts a named tuple of arrays into a `.npz` file and writes it to a writer.
+///
+/// Every element of the named tuple is stored without compression as a `.npy` file named after the element, like `numpy.savez` does.
+///
+/// # Arguments
+///
+/// * `typed_value` - typed value of a named tuple of arrays or scalars of scalar types supported by NumPy
+/// * `writer` - writer of the `.npz` file contents
+#[cfg(feature = "npz")]
+pub fn write_npz<W: Write + Seek>(typed_value: &TypedValue, writer: W) -> Result<()> {
+    let elements_types = match &typed_value.t {
+        Type::NamedTuple(v) => v.clone(),
+        t => {
+            return Err(runtime_error!(
+                "Only named tuples can be converted to NumPy archives, got {}",
+                t
+            ))
+        }
+    };
+    let elements_values = typed_value.value.to_vector()?;
+    let mut archive = zip::ZipWriter::new(writer);
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for ((name, t), value) in elements_types.iter().zip(elements_values) {
+        archive
+            .start_file(format!("{}.npy", name), options)
+            .map_err(|e| runtime_error!("Failed to write NumPy archive: {}", e))?;
+        write_npy(&TypedValue::new((**t).clone(), value)?, &mut archive)?;
+    }
+    archive
+        .finish()
+        .map_err(|e| runtime_error!("Failed to write NumPy archive: {}", e))?;
+    Ok(())
+}
+
+/// Loads a `.npz` file from a given path (see [read_npz]).
+#[cfg(feature = "npz")]
+pub fn load_npz<P: AsRef<Path>>(path: P) -> Result<TypedValue> {
+    let file = File::open(path).map_err(|e| runtime_error!("Failed to open file: {}", e))?;
+    read_npz(BufReader::new(file))
+}
+
+/// Saves a named tuple of arrays to a `.npz` file at a given path (see [write_npz]).
+#[cfg(feature = "npz")]
+pub fn save_npz<P: AsRef<Path>>(typed_value: &TypedValue, path: P) -> Result<()> {
+    let file = File::create(path).map_err(|e| runtime_error!("Failed to create file: {}", e))?;
+    write_npz(typed_value, BufWriter::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::data_types::INT128;
+    use std::io::Cursor;
+
+    // Builds a .npy file with a given header as NumPy does
+    fn npy_bytes(header: &str, data: &[u8]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[1, 0]);
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn round_trip(tv: TypedValue) -> Result<()> {
+        let mut bytes = vec![];
+        write_npy(&tv, &mut bytes)?;
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        assert_eq!(read_npy(bytes.as_slice())?, tv);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_npy() {
+        || -> Result<()> {
+            let data: Vec<u8> = [1i32, -2, 3, 4, 5, -6]
+                .iter()
+                .flat_map(|x| x.to_le_bytes())
+                .collect();
+            let bytes = npy_bytes(
+                "{'descr': '<i4', 'fortran_order': False, 'shape': (2, 3), }          \n",
+                &data,
+            );
+            let tv = read_npy(bytes.as_slice())?;
+            assert_eq!(tv.t, array_type(vec![2, 3], INT32));
+            assert_eq!(
+                tv.value.to_flattened_array_i32(tv.t.clone())?,
+                vec![1, -2, 3, 4, 5, -6]
+            );
+
+            let bytes = npy_bytes(
+                "{'descr': '<i4', 'fortran_order': True, 'shape': (2, 3), }\n",
+                &data,
+            );
+            let tv = read_npy(bytes.as_slice())?;
+            assert_eq!(
+                tv.value.to_flattened_array_i32(tv.t.clone())?,
+                vec![1, 3, 5, -2, 4, -6]
+            );
+
+            let bytes = npy_bytes(
+                "{'descr': '|b1', 'fortran_order': False, 'shape': (3,), }\n",
+                &[1, 0, 1],
+            );
+            let tv = read_npy(bytes.as_slice())?;
+            assert_eq!(tv.t, array_type(vec![3], BIT));
+            assert_eq!(tv.value.to_flattened_array_u8(tv.t.clone())?, vec![1, 0, 1]);
+
+            let bytes = npy_bytes(
+                "{'descr': '<u8', 'fortran_order': False, 'shape': (), }\n",
+                &u64::MAX.to_le_bytes(),
+            );
+            let tv = read_npy(bytes.as_slice())?;
+            assert_eq!(tv.t, scalar_type(UINT64));
+            assert_eq!(tv.value.to_u64(UINT64)?, u64::MAX);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_npy_round_trip() {
+        || -> Result<()> {
+            round_trip(TypedValue::new(
+                array_type(vec![2, 2], INT16),
+                Value::from_flattened_array(&[-1, 2, -300, 4], INT16)?,
+            )?)?;
+            round_trip(TypedValue::new(
+                array_type(vec![5], BIT),
+                Value::from_flattened_array(&[1, 1, 0, 1, 0], BIT)?,
+            )?)?;
+            round_trip(TypedValue::new(
+                array_type(vec![1, 2, 1], UINT8),
+                Value::from_flattened_array(&[255, 7], UINT8)?,
+            )?)?;
+            round_trip(TypedValue::new(
+                scalar_type(INT64),
+                Value::from_scalar(-5, INT64)?,
+            )?)?;
+
+            // Single-byte types written with the big-endian prefix
+            for (descr, t) in [
+                (">u1", array_type(vec![3], UINT8)),
+                (">i1", array_type(vec![3], INT8)),
+                (">b1", array_type(vec![3], BIT)),
+            ] {
+                let bytes = npy_bytes(
+                    &format!(
+                        "{{'descr': '{}', 'fortran_order': False, 'shape': (3,), }}\n",
+                        descr
+                    ),
+                    &[1, 0, 1],
+                );
+                let tv = read_npy(bytes.as_slice())?;
+                assert_eq!(tv.t, t);
+                round_trip(tv)?;
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_npy() {
+        let check = |header: &str, data: &[u8]| read_npy(npy_bytes(header, data).as_slice());
+        assert!(check(
+            "{'descr': '>i4', 'fortran_order': False, 'shape': (1,), }",
+            &[0; 4]
+        )
+        .is_err());
+        assert!(check(
+            "{'descr': '<f8', 'fortran_order': False, 'shape': (1,), }",
+            &[0; 8]
+        )
+        .is_err());
+        assert!(check(
+            "{'descr': '<i4', 'fortran_order': False, 'shape': (2,), }",
+            &[0; 4]
+        )
+        .is_err());
+        assert!(check(
+            "{'descr': '<i4', 'fortran_order': False, 'shape': (0,), }",
+            &[]
+        )
+        .is_err());
+        assert!(check(
+            "{'descr': '|b1', 'fortran_order': False, 'shape': (1,), }",
+            &[2]
+        )
+        .is_err());
+        assert!(check("{'descr': '<i4', 'shape': (1,), }", &[0; 4]).is_err());
+        // Shapes whose size overflows or exceeds the available data
+        assert!(check(
+            "{'descr': '<i8', 'fortran_order': False, 'shape': (4294967296, 4294967296), }",
+            &[0; 8]
+        )
+        .is_err());
+        assert!(check(
+            "{'descr': '<i8', 'fortran_order': False, 'shape': (1152921504606846976,), }",
+            &[0; 8]
+        )
+        .is_err());
+        assert!(check(
+            "{'descr': '|u1', 'fortran_order': False, 'shape': (1000000000000,), }",
+            &[0; 8]
+        )
+        .is_err());
+        assert!(read_npy(Cursor::new(b"not a numpy file")).is_err());
+        let int128_value =
+            TypedValue::new(scalar_type(INT128), Value::from_scalar(1, INT128).unwrap());
+        assert!(write_npy(&int128_value.unwrap(), vec![]).is_err());
+    }
+
+    #[cfg(feature = "npz")]
+    #[test]
+    fn test_npz_round_trip() {
+        || -> Result<()> {
+            let tv = TypedValue::new(
+                named_tuple_type(vec![
+                    ("x".to_owned(), array_type(vec![3], INT32)),
+                    ("flags".to_owned(), array_type(vec![2, 2], BIT)),
+                ]),
+                Value::from_vector(vec![
+                    Value::from_flattened_array(&[1, -2, 3], INT32)?,
+                    Value::from_flattened_array(&[0, 1, 1, 0], BIT)?,
+                ]),
+            )?;
+            let mut cursor = Cursor::new(vec![]);
+            write_npz(&tv, &mut cursor)?;
+            cursor.set_position(0);
+            assert_eq!(read_npz(cursor)?, tv);
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -0,0 +1,314 @@
+//! Padding of databases that hides their exact numbers of rows.
+//!
+//! The shapes of inputs of a graph are public, so every party learns the exact number of rows of a database used in [set intersection](crate::graphs::Graph::set_intersection) or other database operations.
+//! A [PaddingPolicy] rounds this number up, such that it is revealed only up to a bucket.
+//! Loaded databases are padded with empty rows (whose null bits are zero) by [pad_database]; CSV files can be padded while loading by [load_padded_csv](crate::io::csv::load_padded_csv).
+//! The same policy can be set in [PsiConfig](crate::mpc::mpc_psi::PsiConfig) to make set intersection protocols reject databases whose numbers of rows don't comply with it.
+//!
+//! Padding also allows one compiled context to serve databases of variable sizes.
+//! Graphs are specialized to fixed shapes, so the context is built and compiled for the maximal numbers of rows,
+//! and smaller databases are padded to these numbers before evaluation by [pad_database_to_type] (see also [Pipeline::evaluate_padded](crate::pipeline::Pipeline::evaluate_padded)).
+use crate::data_types::{array_type, named_tuple_type, Type};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::type_inference::NULL_HEADER;
+
+use serde::{Deserialize, Serialize};
+
+/// Rule choosing the number of rows of a padded database.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub enum PaddingPolicy {
+    /// No padding, i.e. the number of rows is revealed exactly.
+    #[default]
+    Exact,
+    /// The number of rows is rounded up to a power of two.
+    PowerOfTwo,
+    /// The number of rows is rounded up to the smallest of the given bucket sizes that is not smaller than it.
+    Buckets(Vec<u64>),
+}
+
+impl PaddingPolicy {
+    /// Returns the number of rows of a padded database.
+    ///
+    /// # Arguments
+    ///
+    /// `num_rows` - number of rows of a database
+    ///
+    /// # Returns
+    ///
+    /// Number of rows after padding; an error is returned if the number of rows exceeds all the buckets
+    pub fn get_padded_num_rows(&self, num_rows: u64) -> Result<u64> {
+        match self {
+            PaddingPolicy::Exact => Ok(num_rows),
+            PaddingPolicy::PowerOfTwo => num_rows
+                .checked_next_power_of_two()
+                .ok_or_else(|| runtime_error!("Too many rows: {}", num_rows)),
+            PaddingPolicy::Buckets(buckets) => buckets
+                .iter()
+                .filter(|bucket| **bucket >= num_rows)
+                .min()
+                .copied()
+                .ok_or_else(|| {
+                    runtime_error!(
+                        "Number of rows {} exceeds all the buckets {:?}",
+                        num_rows,
+                        buckets
+                    )
+                }),
+        }
+    }
+
+    /// Checks that a given number of rows is a possible number of rows of a padded database.
+    pub fn check_num_rows(&self, num_rows: u64) -> Result<()> {
+        if self.get_padded_num_rows(num_rows)? != num_rows {
+            return Err(runtime_error!(
+                "Number of rows {} doesn't comply with the padding policy {:?}",
+                num_rows,
+                self
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Pads a database with empty rows according to a given policy.
+///
+/// # Arguments
+///
+/// * `t` - named tuple type of the database; it should contain arrays with the same number of rows (first dimension) including the binary null column
+/// * `value` - value of the database
+/// * `policy` - padding policy
+///
+/// # Returns
+///
+/// Type and value of the padded database; padding rows contain zeros
+pub fn pad_database(t: Type, value: Value, policy: &PaddingPolicy) -> Result<(Type, Value)> {
+    let header_types = match &t {
+        Type::NamedTuple(header_types) => header_types.clone(),
+        _ => return Err(runtime_error!("Database must be a named tuple, got {}", t)),
+    };
+    if !header_types.iter().any(|(header, _)| header == NULL_HEADER) {
+        return Err(runtime_error!(
+            "Database must contain the {} column",
+            NULL_HEADER
+        ));
+    }
+    let mut num_rows = None;
+    for (header, column_t) in &header_types {
+        if !column_t.is_array() {
+            return Err(runtime_error!("Column {} must be an array", header));
+        }
+        let column_num_rows = column_t.get_shape()[0];
+        if *num_rows.get_or_insert(column_num_rows) != column_num_rows {
+            return Err(runtime_error!(
+                "Columns of a database must have the same number of rows"
+            ));
+        }
+    }
+    let num_rows = num_rows.ok_or_else(|| runtime_error!("Database has no columns"))?;
+    let padded_num_rows = policy.get_padded_num_rows(num_rows)?;
+    let mut padded_header_types = vec![];
+    let mut padded_columns = vec![];
+    for ((header, column_t), column) in header_types.into_iter().zip(value.to_vector()?) {
+        let mut shape = column_t.get_shape();
+        let st = column_t.get_scalar_type();
+        let mut entries = column.to_flattened_array_u64((*column_t).clone())?;
+        let row_size: u64 = shape[1..].iter().product();
+        entries.resize((padded_num_rows * row_size) as usize, 0);
+        shape[0] = padded_num_rows;
+        padded_columns.push(Value::from_flattened_array(&entries, st.clone())?);
+        padded_header_types.push((header, array_type(shape, st)));
+    }
+    Ok((
+        named_tuple_type(padded_header_types),
+        Value::from_vector(padded_columns),
+    ))
+}
+
+/// Pads a database with empty rows, such that its type becomes equal to a given one.
+///
+/// This binds a database with a variable number of rows to an input of a graph built for the maximal number of rows.
+///
+/// # Arguments
+///
+/// * `t` - named tuple type of the database; it should contain arrays with the same number of rows (first dimension) including the binary null column
+/// * `value` - value of the database
+/// * `target_t` - type of the padded database; it must have the same columns as `t` and at least as many rows
+///
+/// # Returns
+///
+/// Value of the padded database of type `target_t`; padding rows contain zeros
+pub fn pad_database_to_type(t: Type, value: Value, target_t: Type) -> Result<Value> {
+    let target_num_rows = match &target_t {
+        Type::NamedTuple(header_types) => match header_types.first() {
+            Some((_, column_t)) if column_t.is_array() => column_t.get_shape()[0],
+            _ => {
+                return Err(runtime_error!(
+                    "Target type must be a named tuple of arrays, got {}",
+                    target_t
+                ))
+            }
+        },
+        _ => {
+            return Err(runtime_error!(
+                "Target type must be a named tuple of arrays, got {}",
+                target_t
+            ))
+        }
+    };
+    let (padded_t, padded_value) = pad_database(
+        t.clone(),
+        value,
+        &PaddingPolicy::Buckets(vec![target_num_rows]),
+    )?;
+    if padded_t != target_t {
+        return Err(runtime_error!(
+            "Database of type {} can't be padded to type {}",
+            t,
+            target_t
+        ));
+    }
+    Ok(padded_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{scalar_type, BIT, INT32};
+
+    #[test]
+    fn test_padded_num_rows() {
+        assert_eq!(PaddingPolicy::Exact.get_padded_num_rows(5).unwrap(), 5);
+        assert_eq!(PaddingPolicy::PowerOfTwo.get_padded_num_rows(5).unwrap(), 8);
+        assert_eq!(PaddingPolicy::PowerOfTwo.get_padded_num_rows(8).unwrap(), 8);
+        let buckets = PaddingPolicy::Buckets(vec![100, 10, 1000]);
+        assert_eq!(buckets.get_padded_num_rows(5).unwrap(), 10);
+        assert_eq!(buckets.get_padded_num_rows(11).unwrap(), 100);
+        assert_eq!(buckets.get_padded_num_rows(1000).unwrap(), 1000);
+        assert!(buckets.get_padded_num_rows(1001).is_err());
+        assert!(PaddingPolicy::PowerOfTwo
+            .get_padded_num_rows(u64::MAX)
+            .is_err());
+
+        assert!(PaddingPolicy::Exact.check_num_rows(7).is_ok());
+        assert!(PaddingPolicy::PowerOfTwo.check_num_rows(16).is_ok());
+        assert!(PaddingPolicy::PowerOfTwo.check_num_rows(7).is_err());
+        assert!(buckets.check_num_rows(100).is_ok());
+        assert!(buckets.check_num_rows(50).is_err());
+    }
+
+    #[test]
+    fn test_pad_database() {
+        || -> Result<()> {
+            let t = named_tuple_type(vec![
+                ("a".to_owned(), array_type(vec![3], INT32)),
+                (NULL_HEADER.to_owned(), array_type(vec![3], BIT)),
+                ("b".to_owned(), array_type(vec![3, 2], INT32)),
+            ]);
+            let value = Value::from_vector(vec![
+                Value::from_flattened_array(&[1, 2, 3], INT32)?,
+                Value::from_flattened_array(&[1, 0, 1], BIT)?,
+                Value::from_flattened_array(&[1, 2, 3, 4, 5, 6], INT32)?,
+            ]);
+            let (padded_t, padded_value) =
+                pad_database(t.clone(), value.clone(), &PaddingPolicy::PowerOfTwo)?;
+            assert_eq!(
+                padded_t,
+                named_tuple_type(vec![
+                    ("a".to_owned(), array_type(vec![4], INT32)),
+                    (NULL_HEADER.to_owned(), array_type(vec![4], BIT)),
+                    ("b".to_owned(), array_type(vec![4, 2], INT32)),
+                ])
+            );
+            let columns = padded_value.to_vector()?;
+            assert_eq!(
+                columns[0].to_flattened_array_u64(array_type(vec![4], INT32))?,
+                vec![1, 2, 3, 0]
+            );
+            assert_eq!(
+                columns[1].to_flattened_array_u64(array_type(vec![4], BIT))?,
+                vec![1, 0, 1, 0]
+            );
+            assert_eq!(
+                columns[2].to_flattened_array_u64(array_type(vec![4, 2], INT32))?,
+                vec![1, 2, 3, 4, 5, 6, 0, 0]
+            );
+            // Databases complying with the policy aren't changed
+            let (exact_t, exact_value) =
+                pad_database(t.clone(), value.clone(), &PaddingPolicy::Exact)?;
+            assert_eq!(exact_t, t);
+            assert_eq!(exact_value, value);
+
+            // Malformed databases
+            assert!(
+                pad_database(scalar_type(INT32), value.clone(), &PaddingPolicy::Exact).is_err()
+            );
+            let no_null_t = named_tuple_type(vec![("a".to_owned(), array_type(vec![3], INT32))]);
+            assert!(pad_database(
+                no_null_t,
+                Value::from_vector(vec![Value::from_flattened_array(&[1, 2, 3], INT32)?]),
+                &PaddingPolicy::Exact
+            )
+            .is_err());
+            let mismatched_t = named_tuple_type(vec![
+                ("a".to_owned(), array_type(vec![2], INT32)),
+                (NULL_HEADER.to_owned(), array_type(vec![3], BIT)),
+            ]);
+            assert!(pad_database(
+                mismatched_t,
+                Value::from_vector(vec![
+                    Value::from_flattened_array(&[1, 2], INT32)?,
+                    Value::from_flattened_array(&[1, 0, 1], BIT)?,
+                ]),
+                &PaddingPolicy::Exact
+            )
+            .is_err());
+            assert!(pad_database(t, value, &PaddingPolicy::Buckets(vec![2])).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_pad_database_to_type() {
+        || -> Result<()> {
+            let database_type = |num_rows: u64| {
+                named_tuple_type(vec![
+                    (NULL_HEADER.to_owned(), array_type(vec![num_rows], BIT)),
+                    ("a".to_owned(), array_type(vec![num_rows, 2], INT32)),
+                ])
+            };
+            let value = Value::from_vector(vec![
+                Value::from_flattened_array(&[1, 1], BIT)?,
+                Value::from_flattened_array(&[1, 2, 3, 4], INT32)?,
+            ]);
+            let padded_value =
+                pad_database_to_type(database_type(2), value.clone(), database_type(3))?;
+            assert!(padded_value.check_type(database_type(3))?);
+            let columns = padded_value.to_vector()?;
+            assert_eq!(
+                columns[1].to_flattened_array_u64(array_type(vec![3, 2], INT32))?,
+                vec![1, 2, 3, 4, 0, 0]
+            );
+            assert_eq!(
+                pad_database_to_type(database_type(2), value.clone(), database_type(2))?,
+                value
+            );
+            // Fewer rows than in the database
+            assert!(
+                pad_database_to_type(database_type(2), value.clone(), database_type(1)).is_err()
+            );
+            // Other columns
+            let other_t = named_tuple_type(vec![
+                (NULL_HEADER.to_owned(), array_type(vec![3], BIT)),
+                ("a".to_owned(), array_type(vec![3, 3], INT32)),
+            ]);
+            assert!(pad_database_to_type(database_type(2), value.clone(), other_t).is_err());
+            assert!(
+                pad_database_to_type(database_type(2), value, array_type(vec![3], BIT)).is_err()
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -116,6 +116,7 @@
 //! For the former, add `ciphercore-base` to the dependencies of your Rust project, for the latter, run `cargo install ciphercore-base`.
 //! We support most Linux and macOS systems (as well as Windows via WSL) with an Intel CPU.
 //! For the crates to build, we require a system-wide install of [OpenSSL](https://www.openssl.org/) discoverable by the Rust [`openssl` crate](https://docs.rs/openssl/latest/openssl/) (the latter typically means the availability of [pkg-config](https://en.wikipedia.org/wiki/Pkg-config)).
+//! OpenSSL is not needed for the `wasm32-unknown-unknown` target, which can be used to evaluate compiled graphs in the browser (see the `ciphercore-wasm` crate).
 //!
 //! In addition, you can check out the Python package for the computation graph building API and the Docker image with pre-installed Python package and CLI tools including the *fast evaluator*, whose source code is *not* available in this repository.
 //! More information about these parts of CipherCore can be found in [the CipherCore GitHub repo](https://github.com/ciphermodelabs/ciphercore).
@@ -911,23 +912,37 @@ pub mod broadcast;
 #[doc(hidden)]
 pub mod bytes;
 mod constants;
+#[macro_use]
 pub mod custom_ops;
 pub mod data_types;
 pub mod data_values;
 #[doc(hidden)]
 pub mod evaluators;
+pub mod graph_builder;
+pub mod graph_diff;
 pub mod graphs;
+mod graphviz;
 #[doc(hidden)]
 pub mod inline;
+pub mod io;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 #[doc(hidden)]
 pub mod mpc;
 pub mod ops;
 #[doc(hidden)]
 pub mod optimizer;
+pub mod pipeline;
+pub mod prelude;
 #[doc(hidden)]
 pub mod random;
 #[doc(hidden)]
 pub mod slices;
+#[cfg(feature = "sql")]
+pub mod sql;
+pub mod templates;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 #[doc(hidden)]
 pub mod type_inference;
 pub mod typed_value;
@@ -937,8 +952,10 @@ pub mod typed_value_operations;
 pub mod typed_value_secret_shared;
 #[doc(hidden)]
 mod typed_value_serialization;
+pub mod value_compression;
 #[doc(hidden)]
 pub mod version;
+pub mod zk;
 
 #[cfg(test)]
 #[macro_use]
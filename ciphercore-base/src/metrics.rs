@@ -0,0 +1,230 @@
+//! Metrics of evaluation exported in the [Prometheus](https://prometheus.io) text format.
+//!
+//! [Metrics] is a facade over a Prometheus registry that can be shared by evaluators (see [MetricsEvaluator](crate::evaluators::metrics_evaluator::MetricsEvaluator))
+//! and by runtimes executing compiled contexts, so that MPC services can be monitored.
+//! This module is available with the `metrics` feature.
+use crate::errors::Result;
+
+use prometheus::{
+    Counter, Gauge, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+
+use std::time::Duration;
+
+/// Collection of metrics describing evaluation of graphs.
+///
+/// The following metrics are registered:
+/// * `ciphercore_nodes_evaluated_total` - number of evaluated nodes,
+/// * `ciphercore_evaluation_seconds_total` - time spent evaluating nodes,
+/// * `ciphercore_nodes_evaluated_per_second` - throughput of the last evaluation,
+/// * `ciphercore_sent_bytes_total` - number of bytes sent due to `Send` annotations, labeled by `sender` and `receiver`,
+/// * `ciphercore_prf_invocations_total` - number of evaluated PRF nodes,
+/// * `ciphercore_memory_high_water_mark_bytes` - maximal number of bytes held by the values of nodes.
+///
+/// Clones share the underlying metrics, so a clone can be passed to every component reporting metrics.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::metrics::Metrics;
+/// let metrics = Metrics::new().unwrap();
+/// metrics.record_sent_bytes(0, 1, 128);
+/// let exported = metrics.export().unwrap();
+/// assert!(exported.contains("ciphercore_sent_bytes_total{receiver=\"1\",sender=\"0\"} 128"));
+/// ```
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    nodes_evaluated: IntCounter,
+    evaluation_seconds: Counter,
+    nodes_per_second: Gauge,
+    sent_bytes: IntCounterVec,
+    prf_invocations: IntCounter,
+    memory_high_water_mark: IntGauge,
+}
+
+impl Metrics {
+    /// Creates metrics registered in a new registry.
+    pub fn new() -> Result<Self> {
+        Metrics::with_registry(Registry::new())
+    }
+
+    /// Creates metrics registered in a given registry, e.g. the registry exported by a service.
+    ///
+    /// # Arguments
+    ///
+    /// `registry` - Prometheus registry that must not contain metrics with the same names
+    ///
+    /// # Returns
+    ///
+    /// New metrics
+    pub fn with_registry(registry: Registry) -> Result<Self> {
+        let metrics = Metrics {
+            nodes_evaluated: IntCounter::with_opts(Opts::new(
+                "ciphercore_nodes_evaluated_total",
+                "Number of evaluated nodes",
+            ))?,
+            evaluation_seconds: Counter::with_opts(Opts::new(
+                "ciphercore_evaluation_seconds_total",
+                "Time spent evaluating nodes in seconds",
+            ))?,
+            nodes_per_second: Gauge::with_opts(Opts::new(
+                "ciphercore_nodes_evaluated_per_second",
+                "Number of nodes evaluated per second during the last evaluation",
+            ))?,
+            sent_bytes: IntCounterVec::new(
+                Opts::new(
+                    "ciphercore_sent_bytes_total",
+                    "Number of bytes sent between parties due to Send annotations",
+                ),
+                &["sender", "receiver"],
+            )?,
+            prf_invocations: IntCounter::with_opts(Opts::new(
+                "ciphercore_prf_invocations_total",
+                "Number of evaluated PRF nodes",
+            ))?,
+            memory_high_water_mark: IntGauge::with_opts(Opts::new(
+                "ciphercore_memory_high_water_mark_bytes",
+                "Maximal number of bytes held by the values of nodes",
+            ))?,
+            registry,
+        };
+        metrics
+            .registry
+            .register(Box::new(metrics.nodes_evaluated.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.evaluation_seconds.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.nodes_per_second.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.sent_bytes.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.prf_invocations.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.memory_high_water_mark.clone()))?;
+        Ok(metrics)
+    }
+
+    /// Records evaluation of a given number of nodes that took a given time.
+    pub fn record_node_evaluations(&self, nodes: u64, duration: Duration) {
+        self.nodes_evaluated.inc_by(nodes);
+        self.evaluation_seconds.inc_by(duration.as_secs_f64());
+    }
+
+    /// Sets the throughput of the last evaluation of a graph, in which a given number of nodes was evaluated in a given time.
+    pub fn record_throughput(&self, nodes: u64, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if seconds > 0.0 {
+            self.nodes_per_second.set(nodes as f64 / seconds);
+        }
+    }
+
+    /// Records a given number of bytes sent from one party to another.
+    pub fn record_sent_bytes(&self, sender: u64, receiver: u64, bytes: u64) {
+        self.sent_bytes
+            .with_label_values(&[&sender.to_string(), &receiver.to_string()])
+            .inc_by(bytes);
+    }
+
+    /// Records a given number of PRF invocations.
+    pub fn record_prf_invocations(&self, count: u64) {
+        self.prf_invocations.inc_by(count);
+    }
+
+    /// Records a given number of bytes held in memory; the high-water mark is updated if this number exceeds it.
+    pub fn record_memory_usage(&self, bytes: u64) {
+        let bytes = i64::try_from(bytes).unwrap_or(i64::MAX);
+        if bytes > self.memory_high_water_mark.get() {
+            self.memory_high_water_mark.set(bytes);
+        }
+    }
+
+    /// Returns the total number of evaluated nodes.
+    pub fn get_nodes_evaluated(&self) -> u64 {
+        self.nodes_evaluated.get()
+    }
+
+    /// Returns the total number of bytes sent from one party to another.
+    pub fn get_sent_bytes(&self, sender: u64, receiver: u64) -> u64 {
+        self.sent_bytes
+            .with_label_values(&[&sender.to_string(), &receiver.to_string()])
+            .get()
+    }
+
+    /// Returns the total number of PRF invocations.
+    pub fn get_prf_invocations(&self) -> u64 {
+        self.prf_invocations.get()
+    }
+
+    /// Returns the memory high-water mark in bytes.
+    pub fn get_memory_high_water_mark(&self) -> u64 {
+        self.memory_high_water_mark.get() as u64
+    }
+
+    /// Returns the registry containing the metrics.
+    pub fn get_registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Returns all the metrics of the registry in the Prometheus text format.
+    pub fn export(&self) -> Result<String> {
+        Ok(TextEncoder::new().encode_to_string(&self.registry.gather())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        || -> Result<()> {
+            let metrics = Metrics::new()?;
+            let shared_metrics = metrics.clone();
+            shared_metrics.record_node_evaluations(3, Duration::from_millis(20));
+            metrics.record_node_evaluations(1, Duration::from_millis(10));
+            metrics.record_throughput(4, Duration::from_millis(40));
+            metrics.record_sent_bytes(0, 1, 10);
+            metrics.record_sent_bytes(0, 1, 5);
+            metrics.record_sent_bytes(2, 0, 7);
+            metrics.record_prf_invocations(2);
+            metrics.record_memory_usage(100);
+            metrics.record_memory_usage(50);
+            assert_eq!(metrics.get_nodes_evaluated(), 4);
+            assert_eq!(metrics.get_sent_bytes(0, 1), 15);
+            assert_eq!(metrics.get_sent_bytes(1, 0), 0);
+            assert_eq!(metrics.get_prf_invocations(), 2);
+            assert_eq!(metrics.get_memory_high_water_mark(), 100);
+
+            let exported = metrics.export()?;
+            for line in [
+                "# TYPE ciphercore_nodes_evaluated_total counter",
+                "ciphercore_nodes_evaluated_total 4",
+                "ciphercore_evaluation_seconds_total 0.03",
+                "ciphercore_nodes_evaluated_per_second 100",
+                "ciphercore_sent_bytes_total{receiver=\"1\",sender=\"0\"} 15",
+                "ciphercore_sent_bytes_total{receiver=\"0\",sender=\"2\"} 7",
+                "ciphercore_prf_invocations_total 2",
+                "# TYPE ciphercore_memory_high_water_mark_bytes gauge",
+                "ciphercore_memory_high_water_mark_bytes 100",
+            ] {
+                assert!(
+                    exported.lines().any(|l| l == line),
+                    "{} not found in\n{}",
+                    line,
+                    exported
+                );
+            }
+
+            // Metrics with the same names can't be registered twice
+            assert!(Metrics::with_registry(metrics.get_registry().clone()).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
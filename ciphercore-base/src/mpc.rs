@@ -1,8 +1,25 @@
+pub mod aes;
+pub mod compilation_cache;
+pub mod cost;
+mod garbled_circuits;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod key_setup;
 pub mod low_mc;
+pub mod mpc_aggregation;
 mod mpc_arithmetic;
+mod mpc_beacon;
 pub mod mpc_compiler;
 mod mpc_conversion;
-mod mpc_equivalence_class;
-mod mpc_psi;
+pub mod mpc_equivalence_class;
+pub mod mpc_partition;
+pub mod mpc_psi;
 mod mpc_truncate;
+pub mod mpc_two_party;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod oblivious_transfer;
+pub mod output_policy;
+pub mod preprocessing;
+pub mod send_batching;
+pub mod shamir;
+pub mod spdz;
 pub mod utils;
@@ -0,0 +1,471 @@
+use crate::custom_ops::CustomOperationBody;
+use crate::data_types::{array_type, vector_type, Type, BIT};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, Node, SliceElement};
+use crate::ops::utils::{pull_out_bits, put_in_bits, zeros};
+
+use serde::{Deserialize, Serialize};
+
+pub(super) const AES_BLOCK_SIZE: u64 = 128;
+pub(super) const AES_KEY_SIZE: u64 = 128;
+
+// Number of rounds of AES-128
+const AES_ROUNDS: u64 = 10;
+// Bit size of a byte, the basic unit of AES
+const BYTE_SIZE: u64 = 8;
+// Number of bytes in the AES state
+const STATE_BYTES: u64 = AES_BLOCK_SIZE / BYTE_SIZE;
+// Round constants of the key schedule
+const ROUND_CONSTANTS: [u8; AES_ROUNDS as usize] =
+    [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+// Constant of the affine transformation of the S-box
+const AFFINE_CONSTANT: u8 = 0x63;
+
+/// Implements AES-128 block cipher encryption according to [FIPS 197](https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.197-upd1.pdf) as a bitsliced Boolean circuit.
+///
+/// Two inputs should be provided:
+/// - binary input array of shape `[..., b]` with `b <= 128`,
+/// - binary encryption key of length 128.
+///
+/// Inputs shorter than 128 bits are padded with zeros.
+/// Bits of every byte of the input, the key and the output are ordered from the least significant to the most significant one as in [Value::from_bytes].
+///
+/// Every AES round is computed as follows:
+/// - SubBytes inverts every byte of the state in GF(2^8) by computing its 254-th power with 4 multiplications and applies a public affine transformation,
+/// - ShiftRows and MixColumns are merged into one multiplication by a public binary matrix,
+/// - AddRoundKey adds a round key.
+///
+/// Round keys are computed obliviously from the key using the same S-box circuit.
+///
+/// In contrast to [LowMC](super::low_mc::LowMC), AES is a standard and well-studied cipher, but it needs many more AND gates.
+/// Namely, every S-box needs 256 AND gates, so encryption of one block needs 40960 AND gates plus 10240 AND gates for the key schedule.
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct Aes128;
+
+// Converts bytes to bits ordered from the least significant one
+fn bytes_to_bits(bytes: &[u8]) -> Vec<u64> {
+    let mut bits = vec![];
+    for byte in bytes {
+        for i in 0..BYTE_SIZE {
+            bits.push(((byte >> i) & 1) as u64);
+        }
+    }
+    bits
+}
+
+fn bits_to_bytes(bits: &[u64]) -> Vec<u8> {
+    bits.chunks(BYTE_SIZE as usize)
+        .map(|byte_bits| {
+            byte_bits
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, bit)| byte | ((*bit as u8) << i))
+        })
+        .collect()
+}
+
+// Multiplies two elements of GF(2^8) with the AES modulus x^8 + x^4 + x^3 + x + 1
+fn gf_multiply(mut a: u8, mut b: u8) -> u8 {
+    let mut result = 0;
+    while b != 0 {
+        if b & 1 == 1 {
+            result ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    result
+}
+
+fn gf_power(a: u8, exponent: u64) -> u8 {
+    let mut result = 1;
+    for _ in 0..exponent {
+        result = gf_multiply(result, a);
+    }
+    result
+}
+
+// Returns the binary matrix M of shape [output_size, input_size] of a linear map f over GF(2), i.e. f(x) = M * x.
+// The map is defined on bytes; the input and output sizes must be multiples of 8.
+fn get_linear_map_matrix(
+    input_size: u64,
+    output_size: u64,
+    f: impl Fn(&[u8]) -> Vec<u8>,
+) -> Result<Value> {
+    let mut matrix = vec![0; (output_size * input_size) as usize];
+    for i in 0..input_size {
+        let mut basis_vector = vec![0; input_size as usize];
+        basis_vector[i as usize] = 1;
+        let image = bytes_to_bits(&f(&bits_to_bytes(&basis_vector)));
+        for (j, bit) in image.iter().enumerate() {
+            matrix[j * input_size as usize + i as usize] = *bit;
+        }
+    }
+    Value::from_flattened_array(&matrix, BIT)
+}
+
+// ShiftRows of the state whose bytes are ordered column-wise
+fn shift_rows(state: &[u8]) -> Vec<u8> {
+    let mut result = vec![0; STATE_BYTES as usize];
+    for row in 0..4 {
+        for column in 0..4 {
+            result[row + 4 * column] = state[row + 4 * ((column + row) % 4)];
+        }
+    }
+    result
+}
+
+fn mix_columns(state: &[u8]) -> Vec<u8> {
+    let mut result = vec![0; STATE_BYTES as usize];
+    for column in 0..4 {
+        let s = &state[4 * column..4 * column + 4];
+        for row in 0..4 {
+            result[4 * column + row] = gf_multiply(2, s[row])
+                ^ gf_multiply(3, s[(row + 1) % 4])
+                ^ s[(row + 2) % 4]
+                ^ s[(row + 3) % 4];
+        }
+    }
+    result
+}
+
+// Public constants of the AES circuit added to a graph
+struct AesConstants {
+    // Matrices of the maps x -> x^2, x -> x^4 and x -> x^16 in GF(2^8)
+    square: Node,
+    fourth_power: Node,
+    sixteenth_power: Node,
+    // Matrix mapping the outer product of two bit vectors to the bits of their product in GF(2^8)
+    multiplication: Node,
+    affine_matrix: Node,
+    affine_constant: Node,
+}
+
+impl AesConstants {
+    fn new(g: &Graph) -> Result<Self> {
+        let byte_matrix_t = array_type(vec![BYTE_SIZE, BYTE_SIZE], BIT);
+        let power_matrix = |exponent: u64| -> Result<Node> {
+            g.constant(
+                byte_matrix_t.clone(),
+                get_linear_map_matrix(BYTE_SIZE, BYTE_SIZE, |x| vec![gf_power(x[0], exponent)])?,
+            )
+        };
+        // Bit i * 8 + j of the outer product is the product of bits i and j of the factors
+        let multiplication = g.constant(
+            array_type(vec![BYTE_SIZE, BYTE_SIZE * BYTE_SIZE], BIT),
+            get_linear_map_matrix(BYTE_SIZE * BYTE_SIZE, BYTE_SIZE, |x| {
+                let mut result = 0;
+                for i in 0..BYTE_SIZE {
+                    for j in 0..BYTE_SIZE {
+                        let bit_index = i * BYTE_SIZE + j;
+                        if (x[(bit_index / BYTE_SIZE) as usize] >> (bit_index % BYTE_SIZE)) & 1 == 1
+                        {
+                            result ^= gf_multiply(1 << i, 1 << j);
+                        }
+                    }
+                }
+                vec![result]
+            })?,
+        )?;
+        let affine_matrix = g.constant(
+            byte_matrix_t.clone(),
+            get_linear_map_matrix(BYTE_SIZE, BYTE_SIZE, |x| {
+                vec![
+                    x[0] ^ x[0].rotate_left(1)
+                        ^ x[0].rotate_left(2)
+                        ^ x[0].rotate_left(3)
+                        ^ x[0].rotate_left(4),
+                ]
+            })?,
+        )?;
+        let affine_constant = g.constant(
+            array_type(vec![BYTE_SIZE], BIT),
+            Value::from_flattened_array(&bytes_to_bits(&[AFFINE_CONSTANT]), BIT)?,
+        )?;
+        Ok(AesConstants {
+            square: power_matrix(2)?,
+            fourth_power: power_matrix(4)?,
+            sixteenth_power: power_matrix(16)?,
+            multiplication,
+            affine_matrix,
+            affine_constant,
+        })
+    }
+}
+
+// Multiplies a binary array of shape [..., n] by a public binary matrix of shape [m, n] along the last axis
+fn apply_linear_map(x: Node, matrix: Node) -> Result<Node> {
+    if x.get_type()?.get_shape().len() == 1 {
+        matrix.matmul(x)
+    } else {
+        x.gemm(matrix, false, true)
+    }
+}
+
+// Multiplies bytes of shape [..., 8] in GF(2^8)
+fn multiply_bytes(a: Node, b: Node, constants: &AesConstants) -> Result<Node> {
+    let shape = a.get_type()?.get_shape();
+    let prefix = shape[0..shape.len() - 1].to_vec();
+    let mut a_shape = prefix.clone();
+    a_shape.extend([BYTE_SIZE, 1]);
+    let mut b_shape = prefix.clone();
+    b_shape.extend([1, BYTE_SIZE]);
+    let mut outer_product_shape = prefix;
+    outer_product_shape.push(BYTE_SIZE * BYTE_SIZE);
+    let outer_product = a
+        .reshape(array_type(a_shape, BIT))?
+        .multiply(b.reshape(array_type(b_shape, BIT))?)?
+        .reshape(array_type(outer_product_shape, BIT))?;
+    apply_linear_map(outer_product, constants.multiplication.clone())
+}
+
+// Applies the AES S-box to bytes of shape [..., 8]
+fn sub_bytes(x: Node, constants: &AesConstants) -> Result<Node> {
+    // Compute the inverse x^254 with the multiplicative depth 3
+    let x2 = apply_linear_map(x.clone(), constants.square.clone())?;
+    let x3 = multiply_bytes(x2.clone(), x, constants)?;
+    let x12 = apply_linear_map(x3.clone(), constants.fourth_power.clone())?;
+    let x14 = multiply_bytes(x12.clone(), x2, constants)?;
+    let x15 = multiply_bytes(x12, x3, constants)?;
+    let x240 = apply_linear_map(x15, constants.sixteenth_power.clone())?;
+    let inverse = multiply_bytes(x240, x14, constants)?;
+    apply_linear_map(inverse, constants.affine_matrix.clone())?
+        .add(constants.affine_constant.clone())
+}
+
+// Applies the S-box to every byte of a binary array of shape [..., 8 * k]
+fn sub_all_bytes(x: Node, constants: &AesConstants) -> Result<Node> {
+    let t = x.get_type()?;
+    let shape = t.get_shape();
+    let mut bytes_shape = shape[0..shape.len() - 1].to_vec();
+    bytes_shape.extend([shape[shape.len() - 1] / BYTE_SIZE, BYTE_SIZE]);
+    sub_bytes(x.reshape(array_type(bytes_shape, BIT))?, constants)?.reshape(t)
+}
+
+// Computes all the round keys of AES-128 from the key; the result has shape [AES_ROUNDS + 1, 128]
+fn get_key_schedule(g: &Graph, key: Node, constants: &AesConstants) -> Result<Vec<Node>> {
+    // Given the sub-bytes of the last word of the previous round key, the next round key is a linear function of the previous round key and these bytes:
+    // w_4i+j = w_4(i-1)+j XOR ... XOR w_4(i-1) XOR RotWord(SubWord(w_4(i-1)+3)) XOR Rcon_i
+    let word_size = AES_KEY_SIZE / 4;
+    let previous_key_matrix = g.constant(
+        array_type(vec![AES_KEY_SIZE, AES_KEY_SIZE], BIT),
+        get_linear_map_matrix(AES_KEY_SIZE, AES_KEY_SIZE, |key| {
+            let mut result = key.to_vec();
+            for byte in 4..STATE_BYTES as usize {
+                result[byte] ^= result[byte - 4];
+            }
+            result
+        })?,
+    )?;
+    let last_word_matrix = g.constant(
+        array_type(vec![AES_KEY_SIZE, word_size], BIT),
+        get_linear_map_matrix(word_size, AES_KEY_SIZE, |word| {
+            let rotated_word = [word[1], word[2], word[3], word[0]];
+            rotated_word.repeat(4)
+        })?,
+    )?;
+    let mut round_keys = vec![key];
+    for round_constant in ROUND_CONSTANTS {
+        let previous_key = round_keys[round_keys.len() - 1].clone();
+        let last_word = previous_key.get_slice(vec![SliceElement::SubArray(
+            Some((AES_KEY_SIZE - word_size) as i64),
+            None,
+            None,
+        )])?;
+        let substituted_word = sub_all_bytes(last_word, constants)?;
+        let round_constant_bits = g.constant(
+            array_type(vec![AES_KEY_SIZE], BIT),
+            Value::from_flattened_array(&bytes_to_bits(&[round_constant, 0, 0, 0].repeat(4)), BIT)?,
+        )?;
+        let round_key = previous_key_matrix
+            .clone()
+            .matmul(previous_key)?
+            .add(last_word_matrix.clone().matmul(substituted_word)?)?
+            .add(round_constant_bits)?;
+        round_keys.push(round_key);
+    }
+    Ok(round_keys)
+}
+
+#[typetag::serde]
+impl CustomOperationBody for Aes128 {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 2 {
+            return Err(runtime_error!(
+                "AES should have 2 inputs: input and an encryption key"
+            ));
+        }
+        if !argument_types[0].is_array() || argument_types[0].get_scalar_type() != BIT {
+            return Err(runtime_error!("Input of AES must be a binary array"));
+        }
+        let input_shape = argument_types[0].get_shape();
+        let input_element_len = input_shape[input_shape.len() - 1];
+        if input_element_len > AES_BLOCK_SIZE {
+            return Err(runtime_error!(
+                "Input bitstrings should be of length at most {}",
+                AES_BLOCK_SIZE
+            ));
+        }
+        if argument_types[1] != array_type(vec![AES_KEY_SIZE], BIT) {
+            return Err(runtime_error!(
+                "AES key must be a binary array of length {}",
+                AES_KEY_SIZE
+            ));
+        }
+
+        let g = context.create_graph()?;
+
+        let input = g.input(argument_types[0].clone())?;
+        let key = g.input(argument_types[1].clone())?;
+
+        // Pad input with zeros
+        let padded_input = if input_element_len < AES_BLOCK_SIZE {
+            let length_to_pad = AES_BLOCK_SIZE - input_element_len;
+            let bits = pull_out_bits(input)?.array_to_vector()?;
+            let zeros_shape = input_shape[0..input_shape.len() - 1].to_vec();
+            let zeros_type = vector_type(length_to_pad, array_type(zeros_shape.clone(), BIT));
+            put_in_bits(
+                g.create_tuple(vec![bits, zeros(&g, zeros_type)?])?
+                    .reshape(vector_type(AES_BLOCK_SIZE, array_type(zeros_shape, BIT)))?
+                    .vector_to_array()?,
+            )?
+        } else {
+            input
+        };
+
+        let constants = AesConstants::new(&g)?;
+        let state_matrix_t = array_type(vec![AES_BLOCK_SIZE, AES_BLOCK_SIZE], BIT);
+        // ShiftRows followed by MixColumns
+        let round_matrix = g.constant(
+            state_matrix_t.clone(),
+            get_linear_map_matrix(AES_BLOCK_SIZE, AES_BLOCK_SIZE, |state| {
+                mix_columns(&shift_rows(state))
+            })?,
+        )?;
+        // The last round has no MixColumns
+        let last_round_matrix = g.constant(
+            state_matrix_t,
+            get_linear_map_matrix(AES_BLOCK_SIZE, AES_BLOCK_SIZE, shift_rows)?,
+        )?;
+
+        let round_keys = get_key_schedule(&g, key, &constants)?;
+
+        let mut state = padded_input.add(round_keys[0].clone())?;
+        for (round, round_key) in round_keys.iter().enumerate().skip(1) {
+            state = sub_all_bytes(state, &constants)?;
+            state = if round < AES_ROUNDS as usize {
+                apply_linear_map(state, round_matrix.clone())?
+            } else {
+                apply_linear_map(state, last_round_matrix.clone())?
+            };
+            state = state.add(round_key.clone())?;
+        }
+
+        state.set_as_output()?;
+
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        "AES128".to_owned()
+    }
+}
+
+register_custom_operation!(Aes128, [Shared, Shared]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::custom_ops::run_instantiation_pass;
+    use crate::custom_ops::CustomOperation;
+    use crate::data_types::UINT8;
+    use crate::evaluators::random_evaluate;
+    use crate::graphs::create_context;
+
+    fn aes_helper(input_shape: Vec<u64>, input: Vec<u8>, key: Vec<u8>) -> Result<Vec<u8>> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let i = g.input(array_type(input_shape, BIT))?;
+        let k = g.input(array_type(vec![AES_KEY_SIZE], BIT))?;
+        g.custom_op(CustomOperation::new(Aes128), vec![i, k])?
+            .set_as_output()?;
+        g.finalize()?;
+        g.set_as_main()?;
+        c.finalize()?;
+        let mapped_c = run_instantiation_pass(c)?;
+        let result = random_evaluate(
+            mapped_c.get_context().get_main_graph()?,
+            vec![Value::from_bytes(input), Value::from_bytes(key)],
+        )?;
+        result.access_bytes(|bytes| Ok(bytes.to_vec()))
+    }
+
+    #[test]
+    fn test_aes_with_reference() {
+        || -> Result<()> {
+            // Test vectors from FIPS 197 (Appendices B and C.1)
+            let result = aes_helper(
+                vec![2, AES_BLOCK_SIZE],
+                [
+                    0x32, 0x43, 0xf6, 0xa8, 0x88, 0x5a, 0x30, 0x8d, 0x31, 0x31, 0x98, 0xa2, 0xe0,
+                    0x37, 0x07, 0x34,
+                ]
+                .repeat(2),
+                vec![
+                    0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09,
+                    0xcf, 0x4f, 0x3c,
+                ],
+            )?;
+            assert_eq!(
+                result,
+                [
+                    0x39, 0x25, 0x84, 0x1d, 0x02, 0xdc, 0x09, 0xfb, 0xdc, 0x11, 0x85, 0x97, 0x19,
+                    0x6a, 0x0b, 0x32,
+                ]
+                .repeat(2)
+            );
+            let result = aes_helper(
+                vec![AES_BLOCK_SIZE],
+                (0..16).map(|i| i * 0x11).collect(),
+                (0..16).collect(),
+            )?;
+            assert_eq!(
+                result,
+                vec![
+                    0x69, 0xc4, 0xe0, 0xd8, 0x6a, 0x7b, 0x04, 0x30, 0xd8, 0xcd, 0xb7, 0x80, 0x70,
+                    0xb4, 0xc5, 0x5a,
+                ]
+            );
+            // Short inputs are padded with zeros
+            let result = aes_helper(vec![2, 80], vec![0; 20], (0..16).collect())?;
+            let expected = aes_helper(vec![2, AES_BLOCK_SIZE], vec![0; 32], (0..16).collect())?;
+            assert_eq!(result, expected);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_aes_malformed() {
+        let helper = |argument_types: Vec<Type>| -> Result<Graph> {
+            let c = create_context()?;
+            Aes128.instantiate(c, argument_types)
+        };
+        let key_t = array_type(vec![AES_KEY_SIZE], BIT);
+        assert!(helper(vec![array_type(vec![AES_BLOCK_SIZE], BIT)]).is_err());
+        assert!(helper(vec![array_type(vec![129], BIT), key_t.clone()]).is_err());
+        assert!(helper(vec![array_type(vec![AES_BLOCK_SIZE], UINT8), key_t]).is_err());
+        assert!(helper(vec![
+            array_type(vec![AES_BLOCK_SIZE], BIT),
+            array_type(vec![80], BIT)
+        ])
+        .is_err());
+    }
+}
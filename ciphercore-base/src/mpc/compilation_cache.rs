@@ -0,0 +1,385 @@
+//! On-disk cache of compiled contexts.
+//!
+//! Instantiation, inlining and MPC compilation of large contexts (e.g. PSI) can take a long time,
+//! while the same context is often compiled with the same settings across many runs.
+//! [CompilationCache] stores compiled contexts in a directory and loads them instead of compiling again.
+use crate::errors::Result;
+use crate::evaluators::Evaluator;
+use crate::graphs::Context;
+use crate::inline::inline_ops::InlineConfig;
+use crate::mpc::mpc_compiler::{compile_context_with_backend, IOStatus, MpcBackend};
+use crate::version::{get_data_hash, DATA_VERSION};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Statistics of lookups in a [CompilationCache].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompilationCacheStatistics {
+    /// Number of compiled contexts loaded from the cache.
+    pub hits: u64,
+    /// Number of contexts compiled because they weren't found in the cache.
+    pub misses: u64,
+}
+
+/// Cache of compiled contexts stored in a directory.
+///
+/// Every compiled context is stored in a separate file named after its cache key (see [CompilationCache::get_key]).
+/// The key is computed from the [structural hash](Context::get_structural_hash) of the source context,
+/// the compiler settings, the data version and the version of this crate,
+/// so a context is recompiled whenever any of them changes.
+///
+/// The evaluator used for constant folding is not a part of the key, since it doesn't affect the semantics of the compiled context.
+///
+/// Cached files are deserialized with the hash check of [Context] deserialization;
+/// corrupted files are treated as missing and overwritten.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{scalar_type, INT32};
+/// # use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
+/// # use ciphercore_base::inline::inline_ops::InlineConfig;
+/// # use ciphercore_base::mpc::compilation_cache::CompilationCache;
+/// # use ciphercore_base::mpc::mpc_compiler::IOStatus;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let a = g.input(scalar_type(INT32)).unwrap();
+/// let b = g.input(scalar_type(INT32)).unwrap();
+/// a.multiply(b).unwrap().set_as_output().unwrap();
+/// g.finalize().unwrap().set_as_main().unwrap();
+/// c.finalize().unwrap();
+///
+/// let directory = std::env::temp_dir().join("ciphercore_compilation_cache_doc");
+/// let mut cache = CompilationCache::new(&directory).unwrap();
+/// cache.clear().unwrap();
+/// for _ in 0..2 {
+///     cache
+///         .compile_context(
+///             c.clone(),
+///             vec![IOStatus::Party(0), IOStatus::Party(1)],
+///             vec![IOStatus::Party(2)],
+///             InlineConfig::default(),
+///             || SimpleEvaluator::new(None),
+///         )
+///         .unwrap();
+/// }
+/// assert_eq!(cache.get_statistics().misses, 1);
+/// assert_eq!(cache.get_statistics().hits, 1);
+/// # std::fs::remove_dir_all(directory).unwrap();
+/// ```
+pub struct CompilationCache {
+    directory: PathBuf,
+    statistics: CompilationCacheStatistics,
+}
+
+impl CompilationCache {
+    /// Creates a cache stored in a given directory; the directory is created if it doesn't exist.
+    ///
+    /// # Arguments
+    ///
+    /// `directory` - path to the directory containing cached contexts
+    ///
+    /// # Returns
+    ///
+    /// New compilation cache
+    pub fn new<P: AsRef<Path>>(directory: P) -> Result<Self> {
+        fs::create_dir_all(directory.as_ref())?;
+        Ok(CompilationCache {
+            directory: directory.as_ref().to_path_buf(),
+            statistics: CompilationCacheStatistics::default(),
+        })
+    }
+
+    /// Same as [compile_context](crate::mpc::mpc_compiler::compile_context), but the compiled context is loaded from the cache if present
+    /// and stored in the cache otherwise.
+    pub fn compile_context<T, E>(
+        &mut self,
+        context: Context,
+        input_parties: Vec<IOStatus>,
+        output_parties: Vec<IOStatus>,
+        inline_config: InlineConfig,
+        get_evaluator: T,
+    ) -> Result<Context>
+    where
+        T: Fn() -> Result<E>,
+        E: Evaluator + Sized,
+    {
+        self.compile_context_with_backend(
+            context,
+            input_parties,
+            output_parties,
+            inline_config,
+            get_evaluator,
+            MpcBackend::ABY3,
+        )
+    }
+
+    /// Same as [compile_context_with_backend], but the compiled context is loaded from the cache if present
+    /// and stored in the cache otherwise.
+    pub fn compile_context_with_backend<T, E>(
+        &mut self,
+        context: Context,
+        input_parties: Vec<IOStatus>,
+        output_parties: Vec<IOStatus>,
+        inline_config: InlineConfig,
+        get_evaluator: T,
+        backend: MpcBackend,
+    ) -> Result<Context>
+    where
+        T: Fn() -> Result<E>,
+        E: Evaluator + Sized,
+    {
+        let key = CompilationCache::get_key(
+            &context,
+            &input_parties,
+            &output_parties,
+            &inline_config,
+            backend,
+        )?;
+        let path = self.get_path(&key);
+        if let Some(compiled_context) = load_context(&path) {
+            self.statistics.hits += 1;
+            return Ok(compiled_context);
+        }
+        self.statistics.misses += 1;
+        let compiled_context = compile_context_with_backend(
+            context,
+            input_parties,
+            output_parties,
+            inline_config,
+            get_evaluator,
+            backend,
+        )?;
+        // The context is written to a temporary file first, so concurrent readers never see a partially written file
+        let temporary_path = self
+            .directory
+            .join(format!("{}.{}.tmp", key, std::process::id()));
+        fs::write(&temporary_path, serde_json::to_string(&compiled_context)?)?;
+        fs::rename(&temporary_path, &path)?;
+        Ok(compiled_context)
+    }
+
+    /// Returns the cache key of a context compiled with given settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - source context
+    /// * `input_parties` - parties providing the inputs of the main graph
+    /// * `output_parties` - parties receiving the output of the main graph
+    /// * `inline_config` - inlining configuration
+    /// * `backend` - target MPC protocol
+    ///
+    /// # Returns
+    ///
+    /// Hex-encoded SHA-256 hash identifying the compiled context
+    pub fn get_key(
+        context: &Context,
+        input_parties: &[IOStatus],
+        output_parties: &[IOStatus],
+        inline_config: &InlineConfig,
+        backend: MpcBackend,
+    ) -> Result<String> {
+        // Graph modes are sorted to make the key independent of the hash map order
+        let mut graph_modes: Vec<_> = inline_config.override_graph_modes.iter().collect();
+        graph_modes.sort_by_key(|(graph_id, _)| **graph_id);
+        let settings = format!(
+            "{}|{}|{}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}",
+            context.get_structural_hash()?,
+            DATA_VERSION,
+            env!("CARGO_PKG_VERSION"),
+            input_parties,
+            output_parties,
+            backend,
+            inline_config.default_mode,
+            inline_config.override_call_mode,
+            inline_config.override_iterate_mode,
+            graph_modes
+        );
+        Ok(get_data_hash(&settings))
+    }
+
+    /// Removes all the cached contexts.
+    pub fn clear(&self) -> Result<()> {
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the statistics of lookups performed by this cache object.
+    pub fn get_statistics(&self) -> CompilationCacheStatistics {
+        self.statistics.clone()
+    }
+
+    fn get_path(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{}.json", key))
+    }
+}
+
+fn load_context(path: &Path) -> Option<Context> {
+    let serialized_context = fs::read_to_string(path).ok()?;
+    serde_json::from_str::<Context>(&serialized_context).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::data_types::{scalar_type, INT32};
+    use crate::data_values::Value;
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::{contexts_deep_equal, create_context};
+    use crate::inline::inline_ops::InlineMode;
+    use crate::mpc::mpc_compiler::compile_context;
+
+    fn get_test_context(output_name: &str) -> Result<Context> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let a = g.input(scalar_type(INT32))?;
+        let b = g.input(scalar_type(INT32))?;
+        a.multiply(b)?.set_as_output()?.set_name(output_name)?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        Ok(c)
+    }
+
+    fn get_temporary_directory(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "ciphercore_compilation_cache_{}_{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn compile(cache: &mut CompilationCache, c: Context, backend: MpcBackend) -> Result<Context> {
+        cache.compile_context_with_backend(
+            c,
+            vec![IOStatus::Party(0), IOStatus::Party(1)],
+            vec![IOStatus::Party(0)],
+            InlineConfig::default(),
+            || SimpleEvaluator::new(None),
+            backend,
+        )
+    }
+
+    #[test]
+    fn test_compilation_cache() {
+        let directory = get_temporary_directory("hits");
+        || -> Result<()> {
+            let mut cache = CompilationCache::new(&directory)?;
+            let c = get_test_context("product")?;
+            let compiled_c = compile(&mut cache, c.clone(), MpcBackend::ABY3)?;
+            let cached_c = compile(&mut cache, get_test_context("product")?, MpcBackend::ABY3)?;
+            assert!(contexts_deep_equal(compiled_c, cached_c.clone()));
+            assert_eq!(
+                cache.get_statistics(),
+                CompilationCacheStatistics { hits: 1, misses: 1 }
+            );
+            let inputs = vec![Value::from_scalar(6, INT32)?, Value::from_scalar(7, INT32)?];
+            let result = random_evaluate(cached_c.get_main_graph()?, inputs)?;
+            assert_eq!(result.to_i32(INT32)?, 42);
+
+            // A new cache object in the same directory uses files written by the previous one
+            let mut cache = CompilationCache::new(&directory)?;
+            compile(&mut cache, c.clone(), MpcBackend::ABY3)?;
+            assert_eq!(cache.get_statistics().hits, 1);
+
+            // Different contexts or settings miss the cache
+            compile(&mut cache, get_test_context("other")?, MpcBackend::ABY3)?;
+            compile(&mut cache, c.clone(), MpcBackend::TwoParty)?;
+            assert_eq!(
+                cache.get_statistics(),
+                CompilationCacheStatistics { hits: 1, misses: 2 }
+            );
+
+            cache.clear()?;
+            compile(&mut cache, c, MpcBackend::ABY3)?;
+            assert_eq!(cache.get_statistics().misses, 3);
+            Ok(())
+        }()
+        .unwrap();
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_compilation_cache_corrupted_file() {
+        let directory = get_temporary_directory("corrupted");
+        || -> Result<()> {
+            let mut cache = CompilationCache::new(&directory)?;
+            let c = get_test_context("product")?;
+            compile(&mut cache, c.clone(), MpcBackend::ABY3)?;
+            let key = CompilationCache::get_key(
+                &c,
+                &[IOStatus::Party(0), IOStatus::Party(1)],
+                &[IOStatus::Party(0)],
+                &InlineConfig::default(),
+                MpcBackend::ABY3,
+            )?;
+            let path = directory.join(format!("{}.json", key));
+            let serialized_c = fs::read_to_string(&path)?;
+            fs::write(&path, &serialized_c[..serialized_c.len() / 2])?;
+
+            let compiled_c = compile(&mut cache, c.clone(), MpcBackend::ABY3)?;
+            assert_eq!(cache.get_statistics().misses, 2);
+            let expected_c = compile_context(
+                c,
+                vec![IOStatus::Party(0), IOStatus::Party(1)],
+                vec![IOStatus::Party(0)],
+                InlineConfig::default(),
+                || SimpleEvaluator::new(None),
+            )?;
+            assert!(contexts_deep_equal(compiled_c, expected_c));
+            // The corrupted file is overwritten
+            compile(&mut cache, get_test_context("product")?, MpcBackend::ABY3)?;
+            assert_eq!(cache.get_statistics().hits, 1);
+            Ok(())
+        }()
+        .unwrap();
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_compilation_cache_key() {
+        || -> Result<()> {
+            let c = get_test_context("product")?;
+            let key = |inline_config: &InlineConfig| {
+                CompilationCache::get_key(
+                    &c,
+                    &[IOStatus::Party(0), IOStatus::Party(1)],
+                    &[IOStatus::Party(0)],
+                    inline_config,
+                    MpcBackend::ABY3,
+                )
+            };
+            let mut config1 = InlineConfig::default();
+            config1.override_graph_modes.insert(0, InlineMode::Simple);
+            config1.override_graph_modes.insert(1, InlineMode::Noop);
+            let mut config2 = InlineConfig::default();
+            config2.override_graph_modes.insert(1, InlineMode::Noop);
+            config2.override_graph_modes.insert(0, InlineMode::Simple);
+            assert_eq!(key(&config1)?, key(&config2)?);
+            assert_ne!(key(&config1)?, key(&InlineConfig::default())?);
+            assert_ne!(
+                key(&InlineConfig::default())?,
+                CompilationCache::get_key(
+                    &c,
+                    &[IOStatus::Party(0), IOStatus::Party(1)],
+                    &[IOStatus::Party(1)],
+                    &InlineConfig::default(),
+                    MpcBackend::ABY3,
+                )?
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -0,0 +1,312 @@
+//! Static estimation of round and communication complexity of MPC protocols.
+use crate::data_types::{get_size_in_bits, Type};
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, NodeAnnotation, Operation};
+use crate::mpc::mpc_compiler::PARTIES;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+
+/// Complexity estimate of a graph or a context computed by [estimate].
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CostEstimate {
+    /// Number of sequential communication rounds on the longest path to the output.
+    pub rounds: u64,
+    /// Maximal number of non-constant multiplications (Multiply, MixedMultiply, Dot, Matmul, Gemm) on a path to the output.
+    pub multiplicative_depth: u64,
+    /// Total number of bytes sent by each party.
+    pub bytes_sent: Vec<u64>,
+    /// Total number of bytes received by each party.
+    pub bytes_received: Vec<u64>,
+    /// Total number of messages, i.e. evaluations of nodes annotated with `Send`.
+    pub messages: u64,
+}
+
+impl CostEstimate {
+    fn new() -> Self {
+        CostEstimate {
+            bytes_sent: vec![0; PARTIES],
+            bytes_received: vec![0; PARTIES],
+            ..Default::default()
+        }
+    }
+
+    /// Returns the total number of bytes sent by all the parties.
+    pub fn get_total_bytes(&self) -> u64 {
+        self.bytes_sent.iter().sum()
+    }
+
+    fn add_communication(&mut self, other: &CostEstimate, times: u64) -> Result<()> {
+        for i in 0..PARTIES {
+            self.bytes_sent[i] =
+                checked_add(self.bytes_sent[i], checked_mul(other.bytes_sent[i], times)?)?;
+            self.bytes_received[i] = checked_add(
+                self.bytes_received[i],
+                checked_mul(other.bytes_received[i], times)?,
+            )?;
+        }
+        self.messages = checked_add(self.messages, checked_mul(other.messages, times)?)?;
+        Ok(())
+    }
+}
+
+fn checked_add(a: u64, b: u64) -> Result<u64> {
+    a.checked_add(b)
+        .ok_or_else(|| runtime_error!("add overflow!"))
+}
+
+fn checked_mul(a: u64, b: u64) -> Result<u64> {
+    a.checked_mul(b)
+        .ok_or_else(|| runtime_error!("multiply overflow!"))
+}
+
+fn is_multiplication(op: &Operation) -> bool {
+    matches!(
+        op,
+        Operation::Multiply
+            | Operation::MixedMultiply
+            | Operation::Dot
+            | Operation::Matmul
+            | Operation::Gemm(_, _)
+    )
+}
+
+fn is_random(op: &Operation) -> bool {
+    matches!(
+        op,
+        Operation::Input(_)
+            | Operation::Random(_)
+            | Operation::RandomBeacon(_)
+            | Operation::PRF(_, _)
+            | Operation::RandomPermutation(_)
+    )
+}
+
+/// Per-node quantities propagated along the graph.
+#[derive(Clone, Copy, Default)]
+struct NodeDepths {
+    rounds: u64,
+    multiplicative_depth: u64,
+    is_constant: bool,
+}
+
+fn estimate_graph(graph: Graph, cache: &mut HashMap<u64, CostEstimate>) -> Result<CostEstimate> {
+    if let Some(cost) = cache.get(&graph.get_id()) {
+        return Ok(cost.clone());
+    }
+    let mut result = CostEstimate::new();
+    let mut depths: Vec<NodeDepths> = vec![];
+    for node in graph.get_nodes() {
+        let op = node.get_operation();
+        let dependencies: Vec<NodeDepths> = node
+            .get_node_dependencies()
+            .iter()
+            .map(|dep| depths[dep.get_id() as usize])
+            .collect();
+        let mut current = NodeDepths {
+            rounds: dependencies.iter().map(|d| d.rounds).max().unwrap_or(0),
+            multiplicative_depth: dependencies
+                .iter()
+                .map(|d| d.multiplicative_depth)
+                .max()
+                .unwrap_or(0),
+            is_constant: !is_random(&op) && dependencies.iter().all(|d| d.is_constant),
+        };
+        match op {
+            Operation::Call | Operation::Iterate => {
+                let callee = estimate_graph(node.get_graph_dependencies()[0].clone(), cache)?;
+                let times = if let Operation::Iterate = op {
+                    match node.get_node_dependencies()[1].get_type()? {
+                        Type::Vector(n, _) => n,
+                        _ => return Err(runtime_error!("Iterate expects a vector of inputs")),
+                    }
+                } else {
+                    1
+                };
+                current.rounds = checked_add(current.rounds, checked_mul(callee.rounds, times)?)?;
+                current.multiplicative_depth = checked_add(
+                    current.multiplicative_depth,
+                    checked_mul(callee.multiplicative_depth, times)?,
+                )?;
+                current.is_constant = false;
+                result.add_communication(&callee, times)?;
+            }
+            Operation::If => {
+                // Branches are evaluated obliviously, so both of them are paid for.
+                let mut branch_rounds = 0;
+                let mut branch_depth = 0;
+                for graph in node.get_graph_dependencies() {
+                    let callee = estimate_graph(graph, cache)?;
+                    branch_rounds = branch_rounds.max(callee.rounds);
+                    branch_depth = branch_depth.max(callee.multiplicative_depth);
+                    result.add_communication(&callee, 1)?;
+                }
+                current.rounds = checked_add(current.rounds, branch_rounds)?;
+                current.multiplicative_depth =
+                    checked_add(current.multiplicative_depth, branch_depth)?;
+                current.is_constant = false;
+            }
+            _ => {
+                if is_multiplication(&op) && dependencies.iter().all(|d| !d.is_constant) {
+                    current.multiplicative_depth += 1;
+                }
+            }
+        }
+        let mut has_send = false;
+        for annotation in node.get_annotations()? {
+            if let NodeAnnotation::Send(sender, receiver) = annotation {
+                if sender as usize >= PARTIES || receiver as usize >= PARTIES {
+                    return Err(runtime_error!("Send annotation with an invalid party ID"));
+                }
+                let bytes = get_size_in_bits(node.get_type()?)?.div_ceil(8);
+                result.bytes_sent[sender as usize] =
+                    checked_add(result.bytes_sent[sender as usize], bytes)?;
+                result.bytes_received[receiver as usize] =
+                    checked_add(result.bytes_received[receiver as usize], bytes)?;
+                result.messages += 1;
+                has_send = true;
+            }
+        }
+        if has_send {
+            current.rounds += 1;
+        }
+        depths.push(current);
+    }
+    let output = depths[graph.get_output_node()?.get_id() as usize];
+    result.rounds = output.rounds;
+    result.multiplicative_depth = output.multiplicative_depth;
+    cache.insert(graph.get_id(), result.clone());
+    Ok(result)
+}
+
+/// Statically estimates the complexity of the main graph of a given finalized context without evaluating it.
+///
+/// The context is typically produced by the MPC compiler (e.g. [prepare_for_mpc_evaluation](crate::mpc::mpc_compiler::prepare_for_mpc_evaluation)), so that communication is marked by [NodeAnnotation::Send].
+/// Nodes annotated with `Send` are assumed to transmit their whole value and to take one communication round.
+/// Graphs called via Call and Iterate are accounted for at every call site; iterations are assumed to run sequentially.
+///
+/// This allows to compare different inlining modes and protocol parameters without running the protocol.
+///
+/// # Arguments
+///
+/// `context` - finalized context
+///
+/// # Returns
+///
+/// Estimated number of rounds, multiplicative depth and communication per party
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{scalar_type, INT32};
+/// # use ciphercore_base::mpc::cost::estimate;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let a = g.input(scalar_type(INT32)).unwrap();
+/// let b = g.input(scalar_type(INT32)).unwrap();
+/// a.multiply(b).unwrap().set_as_output().unwrap();
+/// g.finalize().unwrap().set_as_main().unwrap();
+/// c.finalize().unwrap();
+/// let cost = estimate(c).unwrap();
+/// assert_eq!(cost.multiplicative_depth, 1);
+/// assert_eq!(cost.rounds, 0);
+/// ```
+pub fn estimate(context: Context) -> Result<CostEstimate> {
+    context.check_finalized()?;
+    estimate_graph(context.get_main_graph()?, &mut HashMap::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{scalar_type, vector_type, INT32};
+    use crate::graphs::create_context;
+    use crate::inline::inline_ops::{InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{prepare_for_mpc_evaluation, IOStatus};
+
+    fn multiplication_chain(length: u64) -> Result<Context> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let a = g.input(scalar_type(INT32))?;
+        let b = g.input(scalar_type(INT32))?;
+        let mut result = a.clone();
+        for _ in 0..length {
+            result = result.multiply(b.clone())?;
+        }
+        result.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        Ok(c)
+    }
+
+    #[test]
+    fn test_estimate_compiled() {
+        || -> Result<()> {
+            let compile = |length: u64| -> Result<CostEstimate> {
+                let compiled = prepare_for_mpc_evaluation(
+                    multiplication_chain(length)?,
+                    vec![vec![IOStatus::Party(0), IOStatus::Party(1)]],
+                    vec![vec![IOStatus::Party(2)]],
+                    InlineConfig::default(),
+                )?;
+                estimate(compiled)
+            };
+            let cost1 = compile(1)?;
+            let cost3 = compile(3)?;
+            // Every multiplication requires one round of resharing
+            assert_eq!(cost3.rounds, cost1.rounds + 2);
+            assert!(cost3.get_total_bytes() > cost1.get_total_bytes());
+            // Revealing the output sends the missing share to party 2
+            assert!(cost1.bytes_received[2] >= 4);
+            assert_eq!(
+                cost1.bytes_sent.iter().sum::<u64>(),
+                cost1.bytes_received.iter().sum::<u64>()
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_estimate_iterate() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g_body = c.create_graph()?;
+            let state = g_body.input(scalar_type(INT32))?;
+            let input = g_body.input(scalar_type(INT32))?;
+            let product = state.multiply(input)?;
+            let sent = product.nop()?;
+            sent.add_annotation(NodeAnnotation::Send(0, 1))?;
+            g_body
+                .create_tuple(vec![sent.clone(), sent])?
+                .set_as_output()?;
+            g_body.finalize()?;
+            let g = c.create_graph()?;
+            let init = g.input(scalar_type(INT32))?;
+            let inputs = g.input(vector_type(5, scalar_type(INT32)))?;
+            g.iterate(g_body, init, inputs)?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let cost = estimate(c.clone())?;
+            assert_eq!(cost.rounds, 5);
+            assert_eq!(cost.multiplicative_depth, 5);
+            assert_eq!(cost.messages, 5);
+            assert_eq!(cost.bytes_sent, vec![20, 0, 0]);
+            assert_eq!(cost.bytes_received, vec![0, 20, 0]);
+
+            // Inlining doesn't change the estimate
+            let inlined = crate::inline::inline_ops::inline_operations(
+                c,
+                InlineConfig {
+                    default_mode: InlineMode::Simple,
+                    ..Default::default()
+                },
+            )?;
+            assert_eq!(estimate(inlined)?, cost);
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
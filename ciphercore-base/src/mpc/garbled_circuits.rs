@@ -0,0 +1,618 @@
+//! Lowering of deep binary subgraphs to garbled circuits evaluated in a constant number of rounds.
+//!
+//! Parties 0 and 1 act as garblers and party 2 as the evaluator.
+//! Both garblers derive all the randomness of garbling from the PRF key k_1, which is unknown to party 2.
+//! Every wire carries a 128-bit label: the garblers know the zero label `L_0` of a wire and the global offset `R` (free XOR),
+//! while the evaluator knows the label `L_0 + v * R` of the actual wire value `v`.
+//! The last bit of `R` is equal to 1, so the last bit of a label serves as a point-and-permute bit.
+//!
+//! - XOR gates, as well as reshaping, slicing and summation of wires, are applied to labels locally.
+//! - AND gates are garbled with half-gates [ZRE15](https://eprint.iacr.org/2014/756.pdf).
+//!   The hash function is H(L, j) = π(L + T_j) + L + T_j, where π is [LowMC](super::low_mc::LowMC) with a fixed public key and T_j is a public tweak unique for every input wire of every AND gate.
+//! - Inputs are converted from binary shares (s_0, s_1, s_2) to labels as follows.
+//!   Party 0 sends A = M + (s_0 + s_1) * R and party 1 sends B = M + L_0 + s_2 * R to party 2, where M is a random mask.
+//!   Then, party 2 computes A + B = L_0 + (s_0 + s_1 + s_2) * R.
+//!   Public inputs are garbled by party 0 as fresh wires.
+//! - Outputs are converted back to binary shares (r, q, p + r), where `q` is the point-and-permute bit of `L_0` known to the garblers,
+//!   `p` is the point-and-permute bit of the evaluator label and `r` is a random bit known to parties 0 and 2.
+//!
+//! Garbled tables don't depend on evaluator labels, so all of them are sent in one round.
+//! Thus, a garbled subgraph takes 3 communication rounds regardless of its multiplicative depth.
+use crate::custom_ops::{ContextMappings, CustomOperation};
+use crate::data_types::{array_type, Type, BIT};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{Graph, Node, NodeAnnotation, Operation, SliceElement};
+use crate::mpc::low_mc::{LowMC, LowMCBlockSize, LOW_MC_KEY_SIZE};
+
+use std::collections::{HashMap, HashSet};
+
+/// Bit size of wire labels.
+const LABEL_SIZE: u64 = 128;
+
+// Garbler and evaluator labels of a wire
+type Labels = (Node, Node);
+
+fn is_garbling_supported(node: &Node, private_nodes: &HashSet<u64>) -> Result<bool> {
+    if !private_nodes.contains(&node.get_id()) {
+        return Ok(false);
+    }
+    let t = node.get_type()?;
+    if !(t.is_scalar() || t.is_array()) || t.get_scalar_type() != BIT {
+        return Ok(false);
+    }
+    Ok(matches!(
+        node.get_operation(),
+        Operation::Add
+            | Operation::Subtract
+            | Operation::Multiply
+            | Operation::Reshape(_)
+            | Operation::PermuteAxes(_)
+            | Operation::GetSlice(_)
+            | Operation::Get(_)
+            | Operation::Sum(_)
+            | Operation::Stack(_)
+            | Operation::Concatenate(_)
+            | Operation::BroadcastTo(_)
+            | Operation::Tile(_)
+            | Operation::CumSum(_)
+    ))
+}
+
+fn find_root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Returns the IDs of the nodes of a graph that should be garbled and of the garbled nodes whose values should be converted back to shares.
+///
+/// Private nodes are given by their IDs.
+/// Connected subgraphs of private binary nodes are garbled if their multiplicative depth, i.e. the maximal number of private AND gates on a path, is at least `depth_threshold`.
+pub(super) fn get_garbled_nodes(
+    graph: Graph,
+    private_nodes: &HashSet<u64>,
+    depth_threshold: u64,
+) -> Result<(HashSet<u64>, HashSet<u64>)> {
+    let nodes = graph.get_nodes();
+    // Indices of garbling candidates in the union-find structure indexed by node IDs
+    let mut indices: HashMap<u64, usize> = HashMap::new();
+    let mut parents = vec![];
+    let mut depths = vec![];
+    for node in &nodes {
+        if !is_garbling_supported(node, private_nodes)? {
+            continue;
+        }
+        let index = parents.len();
+        parents.push(index);
+        let mut depth = 0;
+        let dependencies = node.get_node_dependencies();
+        for dependency in &dependencies {
+            if let Some(dependency_index) = indices.get(&dependency.get_id()) {
+                depth = depth.max(depths[*dependency_index]);
+                let root = find_root(&mut parents, *dependency_index);
+                parents[root] = index;
+            }
+        }
+        if node.get_operation() == Operation::Multiply
+            && dependencies
+                .iter()
+                .all(|d| private_nodes.contains(&d.get_id()))
+        {
+            depth += 1;
+        }
+        depths.push(depth);
+        indices.insert(node.get_id(), index);
+    }
+    let mut component_depths = vec![0; parents.len()];
+    for (index, depth) in depths.iter().enumerate() {
+        let root = find_root(&mut parents, index);
+        component_depths[root] = component_depths[root].max(*depth);
+    }
+    let mut garbled_nodes = HashSet::new();
+    for (node_id, index) in &indices {
+        if component_depths[find_root(&mut parents, *index)] >= depth_threshold {
+            garbled_nodes.insert(*node_id);
+        }
+    }
+    let mut decoded_nodes = HashSet::new();
+    for node in &nodes {
+        if garbled_nodes.contains(&node.get_id()) {
+            continue;
+        }
+        for dependency in node.get_node_dependencies() {
+            if garbled_nodes.contains(&dependency.get_id()) {
+                decoded_nodes.insert(dependency.get_id());
+            }
+        }
+    }
+    let output_node_id = graph.get_output_node()?.get_id();
+    if garbled_nodes.contains(&output_node_id) {
+        decoded_nodes.insert(output_node_id);
+    }
+    Ok((garbled_nodes, decoded_nodes))
+}
+
+fn get_label_type(t: &Type) -> Type {
+    let mut shape = if t.is_array() { t.get_shape() } else { vec![] };
+    shape.push(LABEL_SIZE);
+    array_type(shape, BIT)
+}
+
+fn bits_to_value(bits: &[u64]) -> Result<Value> {
+    Value::from_flattened_array(bits, BIT)
+}
+
+fn send(node: Node, source_party: u64, destination_party: u64) -> Result<Node> {
+    node.nop()?
+        .add_annotation(NodeAnnotation::Send(source_party, destination_party))
+}
+
+/// Garbles binary nodes of a graph compiled to MPC.
+pub(super) struct Garbler {
+    graph: Graph,
+    prf_keys: Node,
+    // Global offset of labels of the free XOR technique
+    offset: Node,
+    // Public key of the fixed-key permutation
+    permutation_key: Node,
+    num_tweaks: u64,
+    // Labels of garbled and encoded nodes of the input graph indexed by node IDs
+    labels: HashMap<u64, Labels>,
+}
+
+impl Garbler {
+    pub(super) fn new(graph: Graph, prf_keys: Node) -> Result<Self> {
+        let label_t = array_type(vec![LABEL_SIZE], BIT);
+        let random_offset = prf_keys.tuple_get(1)?.prf(0, label_t.clone())?;
+        // Set the point-and-permute bit of the offset to 1
+        let mut mask = vec![1; LABEL_SIZE as usize];
+        mask[LABEL_SIZE as usize - 1] = 0;
+        let mut last_bit = vec![0; LABEL_SIZE as usize];
+        last_bit[LABEL_SIZE as usize - 1] = 1;
+        let offset = random_offset
+            .multiply(graph.constant(label_t.clone(), bits_to_value(&mask)?)?)?
+            .add(graph.constant(label_t.clone(), bits_to_value(&last_bit)?)?)?;
+        // Binary expansion of the first digits of pi
+        let key_bits: Vec<u64> = (0..LOW_MC_KEY_SIZE)
+            .map(|i| (0x243F6A8885A308D313198A2E03707344u128 >> i) as u64 & 1)
+            .collect();
+        let permutation_key = graph.constant(
+            array_type(vec![LOW_MC_KEY_SIZE], BIT),
+            bits_to_value(&key_bits)?,
+        )?;
+        Ok(Garbler {
+            graph,
+            prf_keys,
+            offset,
+            permutation_key,
+            num_tweaks: 0,
+            labels: HashMap::new(),
+        })
+    }
+
+    // Multiplies every label by the corresponding bit
+    fn multiply_by_bits(bits: Node, labels: Node) -> Result<Node> {
+        let mut shape = if bits.get_type()?.is_array() {
+            bits.get_type()?.get_shape()
+        } else {
+            vec![]
+        };
+        shape.push(1);
+        bits.reshape(array_type(shape, BIT))?.multiply(labels)
+    }
+
+    fn get_point_and_permute_bits(labels: Node) -> Result<Node> {
+        labels.get_slice(vec![
+            SliceElement::Ellipsis,
+            SliceElement::SingleIndex(LABEL_SIZE as i64 - 1),
+        ])
+    }
+
+    fn garbler_random(&self, t: Type) -> Result<Node> {
+        self.prf_keys.tuple_get(1)?.prf(0, t)
+    }
+
+    fn encode_shares(&self, shares: Node, t: &Type) -> Result<Labels> {
+        let label_t = get_label_type(t);
+        let zero_labels = self.garbler_random(label_t.clone())?;
+        let mask = self.garbler_random(label_t)?;
+        let share01 = shares.tuple_get(0)?.add(shares.tuple_get(1)?)?;
+        let a = mask.add(Garbler::multiply_by_bits(share01, self.offset.clone())?)?;
+        let b = mask
+            .add(zero_labels.clone())?
+            .add(Garbler::multiply_by_bits(
+                shares.tuple_get(2)?,
+                self.offset.clone(),
+            )?)?;
+        let evaluator_labels = send(a, 0, 2)?.add(send(b, 1, 2)?)?;
+        Ok((zero_labels, evaluator_labels))
+    }
+
+    fn encode_public(&self, value: Node, t: &Type) -> Result<Labels> {
+        let zero_labels = self.garbler_random(get_label_type(t))?;
+        let labels = zero_labels.add(Garbler::multiply_by_bits(value, self.offset.clone())?)?;
+        Ok((zero_labels, send(labels, 0, 2)?))
+    }
+
+    // Returns public tweaks T_j for every wire of a given type, where j is unique for every wire of every AND gate
+    fn next_tweaks(&mut self, t: &Type) -> Result<Node> {
+        let num_wires = if t.is_array() {
+            t.get_shape().iter().product()
+        } else {
+            1
+        };
+        let mut tweak_bits = vec![];
+        for j in self.num_tweaks..self.num_tweaks + num_wires {
+            for i in 0..LABEL_SIZE {
+                tweak_bits.push(if i < 64 { (j >> i) & 1 } else { 0 });
+            }
+        }
+        self.num_tweaks += num_wires;
+        self.graph
+            .constant(get_label_type(t), bits_to_value(&tweak_bits)?)
+    }
+
+    // Computes H(L, j) = π(L + T_j) + L + T_j for every label L
+    fn hash(&self, labels: Node, tweaks: Node) -> Result<Node> {
+        let tweaked_labels = labels.add(tweaks)?;
+        let permuted_labels = self.graph.custom_op(
+            CustomOperation::new(LowMC {
+                s_boxes_per_round: 10,
+                rounds: 20,
+                block_size: LowMCBlockSize::SIZE128,
+            }),
+            vec![tweaked_labels.clone(), self.permutation_key.clone()],
+        )?;
+        permuted_labels.add(tweaked_labels)
+    }
+
+    // Broadcasts labels to a given type, so that every AND gate has its own input labels
+    fn broadcast(&self, labels: Labels, t: &Type) -> Result<Labels> {
+        let label_t = get_label_type(t);
+        if labels.0.get_type()? == label_t {
+            return Ok(labels);
+        }
+        let zeros = self
+            .graph
+            .constant(label_t.clone(), Value::zero_of_type(label_t))?;
+        Ok((labels.0.add(zeros.clone())?, labels.1.add(zeros)?))
+    }
+
+    // Garbles AND gates of a given type with half-gates; garbled tables are sent by party 0
+    fn and_gate(&mut self, a: Labels, b: Labels, t: &Type) -> Result<Labels> {
+        let (zero_a, labels_a) = self.broadcast(a, t)?;
+        let (zero_b, labels_b) = self.broadcast(b, t)?;
+        let tweaks_a = self.next_tweaks(t)?;
+        let tweaks_b = self.next_tweaks(t)?;
+        let hash_pair = |zero_labels: Node, tweaks: Node| -> Result<(Node, Node)> {
+            let one_labels = zero_labels.add(self.offset.clone())?;
+            let hashes = self.hash(
+                self.graph.stack(vec![zero_labels, one_labels], vec![2])?,
+                tweaks,
+            )?;
+            Ok((hashes.get(vec![0])?, hashes.get(vec![1])?))
+        };
+        let (hash_a0, hash_a1) = hash_pair(zero_a.clone(), tweaks_a.clone())?;
+        let (hash_b0, hash_b1) = hash_pair(zero_b.clone(), tweaks_b.clone())?;
+        let permute_a = Garbler::get_point_and_permute_bits(zero_a.clone())?;
+        let permute_b = Garbler::get_point_and_permute_bits(zero_b)?;
+        // Garbler half-gate
+        let table_g = hash_a0.add(hash_a1)?.add(Garbler::multiply_by_bits(
+            permute_b.clone(),
+            self.offset.clone(),
+        )?)?;
+        let zero_g = hash_a0.add(Garbler::multiply_by_bits(permute_a, table_g.clone())?)?;
+        // Evaluator half-gate
+        let table_e = hash_b0.add(hash_b1)?.add(zero_a.clone())?;
+        let zero_e = hash_b0.add(Garbler::multiply_by_bits(permute_b, table_e.add(zero_a)?)?)?;
+        let zero_labels = zero_g.add(zero_e)?;
+
+        let table_g = send(table_g, 0, 2)?;
+        let table_e = send(table_e, 0, 2)?;
+        let select_a = Garbler::get_point_and_permute_bits(labels_a.clone())?;
+        let select_b = Garbler::get_point_and_permute_bits(labels_b.clone())?;
+        let labels_g = self
+            .hash(labels_a.clone(), tweaks_a)?
+            .add(Garbler::multiply_by_bits(select_a, table_g)?)?;
+        let labels_e = self
+            .hash(labels_b, tweaks_b)?
+            .add(Garbler::multiply_by_bits(select_b, table_e.add(labels_a)?)?)?;
+        Ok((zero_labels, labels_g.add(labels_e)?))
+    }
+
+    fn get_labels(
+        &mut self,
+        node: Node,
+        out_mapping: &ContextMappings,
+        private_nodes: &HashSet<u64>,
+    ) -> Result<Labels> {
+        if let Some(labels) = self.labels.get(&node.get_id()) {
+            return Ok(labels.clone());
+        }
+        let t = node.get_type()?;
+        let new_node = out_mapping.get_node(node.clone());
+        let labels = if private_nodes.contains(&node.get_id()) {
+            self.encode_shares(new_node, &t)?
+        } else {
+            self.encode_public(new_node, &t)?
+        };
+        self.labels.insert(node.get_id(), labels.clone());
+        Ok(labels)
+    }
+
+    /// Computes labels of a given node whose dependencies are either garbled or compiled to MPC and contained in `out_mapping`.
+    pub(super) fn garble(
+        &mut self,
+        node: Node,
+        out_mapping: &ContextMappings,
+        private_nodes: &HashSet<u64>,
+    ) -> Result<()> {
+        let mut dependencies = vec![];
+        for dependency in node.get_node_dependencies() {
+            dependencies.push(self.get_labels(dependency, out_mapping, private_nodes)?);
+        }
+        let apply = |f: &dyn Fn(Node) -> Result<Node>| -> Result<Labels> {
+            let (zero_labels, labels) = dependencies[0].clone();
+            Ok((f(zero_labels)?, f(labels)?))
+        };
+        let t = node.get_type()?;
+        let labels = match node.get_operation() {
+            Operation::Add | Operation::Subtract => {
+                let (zero0, labels0) = dependencies[0].clone();
+                let (zero1, labels1) = dependencies[1].clone();
+                (zero0.add(zero1)?, labels0.add(labels1)?)
+            }
+            Operation::Multiply => {
+                self.and_gate(dependencies[0].clone(), dependencies[1].clone(), &t)?
+            }
+            Operation::Reshape(new_t) => apply(&|x| x.reshape(get_label_type(&new_t)))?,
+            Operation::PermuteAxes(mut axes) => {
+                axes.push(axes.len() as u64);
+                apply(&|x| x.permute_axes(axes.clone()))?
+            }
+            Operation::GetSlice(mut slice) => {
+                slice.push(SliceElement::SubArray(None, None, None));
+                apply(&|x| x.get_slice(slice.clone()))?
+            }
+            Operation::Get(index) => apply(&|x| x.get(index.clone()))?,
+            Operation::Sum(axes) => apply(&|x| x.sum(axes.clone()))?,
+            Operation::CumSum(axis) => apply(&|x| x.cumsum(axis))?,
+            Operation::BroadcastTo(mut shape) => {
+                shape.push(LABEL_SIZE);
+                apply(&|x| x.broadcast_to(shape.clone()))?
+            }
+            Operation::Tile(mut reps) => {
+                reps.push(1);
+                apply(&|x| x.tile(reps.clone()))?
+            }
+            Operation::Stack(outer_shape) => {
+                let (zero_labels, labels): (Vec<Node>, Vec<Node>) =
+                    dependencies.into_iter().unzip();
+                (
+                    self.graph.stack(zero_labels, outer_shape.clone())?,
+                    self.graph.stack(labels, outer_shape)?,
+                )
+            }
+            Operation::Concatenate(axis) => {
+                let (zero_labels, labels): (Vec<Node>, Vec<Node>) =
+                    dependencies.into_iter().unzip();
+                (
+                    self.graph.concatenate(zero_labels, axis)?,
+                    self.graph.concatenate(labels, axis)?,
+                )
+            }
+            op => {
+                return Err(runtime_error!("Garbling of {} is not supported", op));
+            }
+        };
+        self.labels.insert(node.get_id(), labels);
+        Ok(())
+    }
+
+    /// Converts labels of a garbled node to binary shares.
+    pub(super) fn decode(&self, node: Node) -> Result<Node> {
+        let (zero_labels, labels) = self
+            .labels
+            .get(&node.get_id())
+            .ok_or_else(|| runtime_error!("Node wasn't garbled"))?
+            .clone();
+        let t = node.get_type()?;
+        let garbler_bits = Garbler::get_point_and_permute_bits(zero_labels)?;
+        let evaluator_bits = Garbler::get_point_and_permute_bits(labels)?;
+        // Random bits known to parties 0 and 2
+        let random_bits = self.prf_keys.tuple_get(0)?.prf(0, t)?;
+        let masked_bits = send(evaluator_bits.add(random_bits.clone())?, 2, 1)?;
+        self.graph
+            .create_tuple(vec![random_bits, garbler_bits, masked_bits])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::scalar_type;
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::{create_context, Context};
+    use crate::inline::inline_ops::InlineConfig;
+    use crate::mpc::cost::estimate;
+    use crate::mpc::mpc_compiler::{compile_context, compile_context_with_garbling, IOStatus};
+    use crate::ops::comparisons::LessThan;
+
+    fn comparison_context() -> Result<Context> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let t = array_type(vec![5, 8], BIT);
+        let x = g.input(t.clone())?;
+        let y = g.input(t)?;
+        let less = g.custom_op(
+            CustomOperation::new(LessThan {
+                signed_comparison: false,
+            }),
+            vec![x.clone(), y.clone()],
+        )?;
+        // The first bit of x XOR y is computed without garbling
+        let xor = x
+            .add(y)?
+            .get_slice(vec![SliceElement::Ellipsis, SliceElement::SingleIndex(0)])?;
+        g.create_tuple(vec![less, xor])?.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        Ok(c)
+    }
+
+    #[test]
+    fn test_get_garbled_nodes() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(array_type(vec![4], BIT))?;
+            let y = g.input(scalar_type(BIT))?;
+            let xy = x.multiply(y.clone())?;
+            let xyy = xy.multiply(y.clone())?;
+            let z = xyy.add(g.constant(scalar_type(BIT), Value::from_scalar(1, BIT)?)?)?;
+            let shallow = x.multiply(x.clone())?;
+            let output = g.create_tuple(vec![z.clone(), shallow.clone()])?;
+            output.set_as_output()?;
+            g.finalize()?;
+            let private_nodes: HashSet<u64> = [&x, &y, &xy, &xyy, &z, &shallow]
+                .iter()
+                .map(|node| node.get_id())
+                .collect();
+            let ids = |nodes: Vec<&Node>| -> HashSet<u64> {
+                nodes.iter().map(|node| node.get_id()).collect()
+            };
+            let (garbled_nodes, decoded_nodes) = get_garbled_nodes(g.clone(), &private_nodes, 2)?;
+            assert_eq!(garbled_nodes, ids(vec![&xy, &xyy, &z]));
+            assert_eq!(decoded_nodes, ids(vec![&z]));
+            let (garbled_nodes, decoded_nodes) = get_garbled_nodes(g, &private_nodes, 1)?;
+            assert_eq!(garbled_nodes, ids(vec![&xy, &xyy, &z, &shallow]));
+            assert_eq!(decoded_nodes, ids(vec![&z, &shallow]));
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_garbled_broadcast_and_tile() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(array_type(vec![3], BIT))?;
+            let y = g.input(array_type(vec![2, 1], BIT))?;
+            let tiled = x.tile(vec![2, 1])?;
+            let broadcast = y.broadcast_to(vec![2, 3])?;
+            let product = tiled
+                .multiply(broadcast.clone())?
+                .multiply(broadcast.tile(vec![1])?)?;
+            g.concatenate(vec![product, x.broadcast_to(vec![1, 3])?], 0)?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let garbled_c = compile_context_with_garbling(
+                c,
+                vec![IOStatus::Party(0), IOStatus::Party(1)],
+                vec![IOStatus::Party(0)],
+                InlineConfig::default(),
+                1,
+                || SimpleEvaluator::new(None),
+            )?;
+            let result = random_evaluate(
+                garbled_c.get_main_graph()?,
+                vec![
+                    Value::from_flattened_array(&[1, 0, 1], BIT)?,
+                    Value::from_flattened_array(&[1, 0], BIT)?,
+                ],
+            )?;
+            assert_eq!(
+                result.to_flattened_array_u64(array_type(vec![3, 3], BIT))?,
+                vec![1, 0, 1, 0, 0, 0, 1, 0, 1]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_garbled_cumsum() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(array_type(vec![2, 3], BIT))?;
+            let y = g.input(array_type(vec![2, 3], BIT))?;
+            x.multiply(y)?.cumsum(1)?.multiply(x)?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let garbled_c = compile_context_with_garbling(
+                c,
+                vec![IOStatus::Party(0), IOStatus::Party(1)],
+                vec![IOStatus::Party(0)],
+                InlineConfig::default(),
+                1,
+                || SimpleEvaluator::new(None),
+            )?;
+            let result = random_evaluate(
+                garbled_c.get_main_graph()?,
+                vec![
+                    Value::from_flattened_array(&[1, 0, 1, 1, 1, 0], BIT)?,
+                    Value::from_flattened_array(&[1, 1, 1, 0, 1, 1], BIT)?,
+                ],
+            )?;
+            assert_eq!(
+                result.to_flattened_array_u64(array_type(vec![2, 3], BIT))?,
+                vec![1, 0, 0, 0, 1, 0]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_garbled_comparison() {
+        || -> Result<()> {
+            let input_parties = vec![IOStatus::Party(0), IOStatus::Party(1)];
+            let output_parties = vec![IOStatus::Party(2)];
+            let garbled_c = compile_context_with_garbling(
+                comparison_context()?,
+                input_parties.clone(),
+                output_parties.clone(),
+                InlineConfig::default(),
+                2,
+                || SimpleEvaluator::new(None),
+            )?;
+            let plain_c = compile_context(
+                comparison_context()?,
+                input_parties,
+                output_parties,
+                InlineConfig::default(),
+                || SimpleEvaluator::new(None),
+            )?;
+            assert!(estimate(garbled_c.clone())?.rounds < estimate(plain_c)?.rounds);
+
+            let x: Vec<u64> = vec![0, 200, 17, 255, 128];
+            let y: Vec<u64> = vec![1, 100, 17, 254, 129];
+            let to_bits = |v: &[u64]| -> Result<Value> {
+                let bits: Vec<u64> = v
+                    .iter()
+                    .flat_map(|e| (0..8).map(move |i| (e >> i) & 1))
+                    .collect();
+                Value::from_flattened_array(&bits, BIT)
+            };
+            let result = random_evaluate(
+                garbled_c.get_main_graph()?,
+                vec![to_bits(&x)?, to_bits(&y)?],
+            )?
+            .to_vector()?;
+            let t = array_type(vec![5], BIT);
+            let expected_less: Vec<u64> = x.iter().zip(&y).map(|(a, b)| (a < b) as u64).collect();
+            let expected_xor: Vec<u64> = x.iter().zip(&y).map(|(a, b)| (a ^ b) & 1).collect();
+            assert_eq!(result[0].to_flattened_array_u64(t.clone())?, expected_less);
+            assert_eq!(result[1].to_flattened_array_u64(t)?, expected_xor);
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -0,0 +1,511 @@
+//! Setup of the PRF keys of the MPC protocol via authenticated key agreement.
+//!
+//! Compiled graphs use a triple of PRF keys (k_0, k_1, k_2) such that party i knows k_i and k_{i+1} (the index is taken modulo 3), i.e. key k_i is shared by parties i and i-1.
+//! Within a graph, these keys are generated by one party and sent to another one (see [prepare_for_mpc_evaluation](crate::mpc::mpc_compiler::prepare_for_mpc_evaluation)), which presumes a secure channel between them.
+//!
+//! This module lets every pair of parties derive its key at the start of a session instead.
+//! Every party holds a long-term Ed25519 identity key whose public part is known to the other parties.
+//! Parties exchange ephemeral X25519 public keys signed by their identity keys and derive the shared key from the Diffie-Hellman secret with HKDF-SHA256 bound to the session ID and the exchanged messages.
+//! Compromise of identity keys doesn't reveal the keys of past sessions.
+//!
+//! # Example
+//!
+//! ```
+//! # use ciphercore_base::mpc::key_setup::{get_prf_key_triple, KeySetup, PartyIdentity};
+//! # use ciphercore_base::mpc::mpc_compiler::PARTIES;
+//! let identities: Vec<PartyIdentity> = (0..PARTIES as u64)
+//!     .map(|i| PartyIdentity::generate(i).unwrap())
+//!     .collect();
+//! let public_keys: Vec<Vec<u8>> = identities
+//!     .iter()
+//!     .map(|identity| identity.get_public_key().unwrap())
+//!     .collect();
+//! let mut setups: Vec<KeySetup> = identities
+//!     .into_iter()
+//!     .map(|identity| KeySetup::new(identity, b"session".to_vec(), public_keys.clone()).unwrap())
+//!     .collect();
+//! // Every party sends messages to the two other parties
+//! let mut messages = vec![];
+//! for setup in setups.iter_mut() {
+//!     messages.extend(setup.get_messages().unwrap());
+//! }
+//! for message in messages {
+//!     setups[message.receiver as usize].process_message(message).unwrap();
+//! }
+//! let party_keys: Vec<_> = setups.iter().map(|setup| setup.get_prf_keys().unwrap()).collect();
+//! // Party 0 knows k_0 and k_1, but not k_2
+//! assert!(party_keys[0][0].is_some() && party_keys[0][1].is_some() && party_keys[0][2].is_none());
+//! let triple = get_prf_key_triple(party_keys).unwrap();
+//! ```
+use crate::data_types::{array_type, tuple_type, BIT};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::mpc::mpc_compiler::{KEY_LENGTH, PARTIES};
+use crate::typed_value::TypedValue;
+
+use openssl::md::Md;
+use openssl::pkey::{Id, PKey, Private, Public};
+use openssl::pkey_ctx::PkeyCtx;
+use openssl::sign::{Signer, Verifier};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+
+const SIGNATURE_DOMAIN: &[u8] = b"ciphercore prf key setup signature";
+const DERIVATION_DOMAIN: &[u8] = b"ciphercore prf key setup derivation";
+
+/// Long-term identity of a party used to authenticate key agreement.
+pub struct PartyIdentity {
+    party_id: u64,
+    signing_key: PKey<Private>,
+}
+
+impl PartyIdentity {
+    /// Generates a fresh Ed25519 identity key of a given party.
+    pub fn generate(party_id: u64) -> Result<Self> {
+        check_party_id(party_id)?;
+        let signing_key = PKey::generate_ed25519()
+            .map_err(|e| runtime_error!("Can't generate an identity key: {}", e))?;
+        Ok(PartyIdentity {
+            party_id,
+            signing_key,
+        })
+    }
+
+    /// Restores the identity of a given party from the raw bytes of its Ed25519 private key.
+    pub fn from_private_key(party_id: u64, private_key: &[u8]) -> Result<Self> {
+        check_party_id(party_id)?;
+        let signing_key = PKey::private_key_from_raw_bytes(private_key, Id::ED25519)
+            .map_err(|e| runtime_error!("Invalid identity key: {}", e))?;
+        Ok(PartyIdentity {
+            party_id,
+            signing_key,
+        })
+    }
+
+    /// Returns the raw bytes of the Ed25519 private key, which should be stored securely.
+    pub fn get_private_key(&self) -> Result<Vec<u8>> {
+        self.signing_key
+            .raw_private_key()
+            .map_err(|e| runtime_error!("Can't export the identity key: {}", e))
+    }
+
+    /// Returns the raw bytes of the Ed25519 public key, which should be distributed to the other parties.
+    pub fn get_public_key(&self) -> Result<Vec<u8>> {
+        self.signing_key
+            .raw_public_key()
+            .map_err(|e| runtime_error!("Can't export the identity key: {}", e))
+    }
+
+    pub fn get_party_id(&self) -> u64 {
+        self.party_id
+    }
+
+    /// Signs given data with the identity key.
+    ///
+    /// The signature can be checked with [verify_signature] given the public key of this identity.
+    pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut signer = Signer::new_without_digest(&self.signing_key)
+            .map_err(|e| runtime_error!("Can't sign a message: {}", e))?;
+        signer
+            .sign_oneshot_to_vec(data)
+            .map_err(|e| runtime_error!("Can't sign a message: {}", e))
+    }
+}
+
+/// Checks an Ed25519 signature produced by [PartyIdentity::sign].
+///
+/// # Arguments
+///
+/// * `public_key` - raw bytes of the Ed25519 public key of the signer
+/// * `data` - signed data
+/// * `signature` - signature to check
+///
+/// # Returns
+///
+/// `true` if the signature is valid, otherwise `false`
+pub fn verify_signature(public_key: &[u8], data: &[u8], signature: &[u8]) -> Result<bool> {
+    let public_key = PKey::public_key_from_raw_bytes(public_key, Id::ED25519)
+        .map_err(|e| runtime_error!("Invalid identity key: {}", e))?;
+    verify_with_key(&public_key, data, signature)
+}
+
+fn verify_with_key(public_key: &PKey<Public>, data: &[u8], signature: &[u8]) -> Result<bool> {
+    let mut verifier = Verifier::new_without_digest(public_key)
+        .map_err(|e| runtime_error!("Can't verify a message: {}", e))?;
+    verifier
+        .verify_oneshot(signature, data)
+        .map_err(|e| runtime_error!("Can't verify a message: {}", e))
+}
+
+/// Message with an ephemeral public key sent from one party to another.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyExchangeMessage {
+    pub sender: u64,
+    pub receiver: u64,
+    pub session_id: Vec<u8>,
+    /// Raw X25519 public key
+    pub ephemeral_public_key: Vec<u8>,
+    /// Ed25519 signature of the other fields by the identity key of the sender
+    pub signature: Vec<u8>,
+}
+
+impl KeyExchangeMessage {
+    fn get_signed_data(&self) -> Vec<u8> {
+        let mut data = SIGNATURE_DOMAIN.to_vec();
+        data.extend_from_slice(&self.sender.to_le_bytes());
+        data.extend_from_slice(&self.receiver.to_le_bytes());
+        append_with_length(&mut data, &self.session_id);
+        append_with_length(&mut data, &self.ephemeral_public_key);
+        data
+    }
+}
+
+/// State of a party during the setup of the PRF keys of a session.
+///
+/// Key k_i is agreed between parties i and i-1, so every party runs the key agreement with both other parties.
+pub struct KeySetup {
+    identity: PartyIdentity,
+    session_id: Vec<u8>,
+    public_keys: Vec<PKey<Public>>,
+    // Ephemeral keys and messages sent to the other parties
+    sent: HashMap<u64, (PKey<Private>, KeyExchangeMessage)>,
+    keys: Vec<Option<Vec<u8>>>,
+}
+
+impl KeySetup {
+    /// Starts the key setup of a session.
+    ///
+    /// # Arguments
+    ///
+    /// * `identity` - identity of the party running the setup
+    /// * `session_id` - unique identifier of the session agreed by all the parties; keys of different sessions are independent
+    /// * `public_keys` - raw Ed25519 public keys of the identities of all the parties
+    ///
+    /// # Returns
+    ///
+    /// New key setup state
+    pub fn new(
+        identity: PartyIdentity,
+        session_id: Vec<u8>,
+        public_keys: Vec<Vec<u8>>,
+    ) -> Result<Self> {
+        if public_keys.len() != PARTIES {
+            return Err(runtime_error!(
+                "Public keys of {} parties expected, got {}",
+                PARTIES,
+                public_keys.len()
+            ));
+        }
+        let public_keys = public_keys
+            .iter()
+            .map(|key| {
+                PKey::public_key_from_raw_bytes(key, Id::ED25519)
+                    .map_err(|e| runtime_error!("Invalid identity public key: {}", e))
+            })
+            .collect::<Result<Vec<PKey<Public>>>>()?;
+        if !public_keys[identity.party_id as usize].public_eq(&identity.signing_key) {
+            return Err(runtime_error!(
+                "Identity doesn't match the public key of party {}",
+                identity.party_id
+            ));
+        }
+        Ok(KeySetup {
+            identity,
+            session_id,
+            public_keys,
+            sent: HashMap::new(),
+            keys: vec![None; PARTIES],
+        })
+    }
+
+    /// Returns the messages that should be sent to the other parties; new ephemeral keys are generated on the first call.
+    pub fn get_messages(&mut self) -> Result<Vec<KeyExchangeMessage>> {
+        let party_id = self.identity.party_id;
+        let mut messages = vec![];
+        for receiver in (0..PARTIES as u64).filter(|id| *id != party_id) {
+            if !self.sent.contains_key(&receiver) {
+                let ephemeral_key = PKey::generate_x25519()
+                    .map_err(|e| runtime_error!("Can't generate an ephemeral key: {}", e))?;
+                let mut message = KeyExchangeMessage {
+                    sender: party_id,
+                    receiver,
+                    session_id: self.session_id.clone(),
+                    ephemeral_public_key: ephemeral_key
+                        .raw_public_key()
+                        .map_err(|e| runtime_error!("Can't export an ephemeral key: {}", e))?,
+                    signature: vec![],
+                };
+                message.signature = self.identity.sign(&message.get_signed_data())?;
+                self.sent.insert(receiver, (ephemeral_key, message));
+            }
+            messages.push(self.sent[&receiver].1.clone());
+        }
+        Ok(messages)
+    }
+
+    /// Verifies a message received from another party and derives the key shared with this party.
+    ///
+    /// The messages of this party must be generated by [KeySetup::get_messages] beforehand.
+    pub fn process_message(&mut self, message: KeyExchangeMessage) -> Result<()> {
+        let party_id = self.identity.party_id;
+        if message.receiver != party_id {
+            return Err(runtime_error!(
+                "Message is addressed to party {}, not {}",
+                message.receiver,
+                party_id
+            ));
+        }
+        if message.sender as usize >= PARTIES || message.sender == party_id {
+            return Err(runtime_error!("Invalid sender {}", message.sender));
+        }
+        if message.session_id != self.session_id {
+            return Err(runtime_error!("Message belongs to another session"));
+        }
+        let is_valid = verify_with_key(
+            &self.public_keys[message.sender as usize],
+            &message.get_signed_data(),
+            &message.signature,
+        )?;
+        if !is_valid {
+            return Err(runtime_error!(
+                "Invalid signature of party {}",
+                message.sender
+            ));
+        }
+        let (ephemeral_key, sent_message) = self.sent.get(&message.sender).ok_or_else(|| {
+            runtime_error!("Messages must be generated before processing received ones")
+        })?;
+        let peer_key = PKey::public_key_from_raw_bytes(&message.ephemeral_public_key, Id::X25519)
+            .map_err(|e| runtime_error!("Invalid ephemeral key: {}", e))?;
+        let mut ctx = PkeyCtx::new(ephemeral_key)
+            .map_err(|e| runtime_error!("Key agreement failed: {}", e))?;
+        ctx.derive_init()
+            .and_then(|_| ctx.derive_set_peer(&peer_key))
+            .map_err(|e| runtime_error!("Key agreement failed: {}", e))?;
+        let mut secret = vec![];
+        ctx.derive_to_vec(&mut secret)
+            .map_err(|e| runtime_error!("Key agreement failed: {}", e))?;
+
+        // Key k_i is shared by parties i and i-1
+        let key_index = if (message.sender + 1) % PARTIES as u64 == party_id {
+            party_id
+        } else {
+            message.sender
+        };
+        // Both parties must use the same info, so messages are ordered by sender
+        let (first, second) = if message.sender < party_id {
+            (&message, sent_message)
+        } else {
+            (sent_message, &message)
+        };
+        let mut info = DERIVATION_DOMAIN.to_vec();
+        info.extend_from_slice(&key_index.to_le_bytes());
+        append_with_length(&mut info, &self.session_id);
+        append_with_length(&mut info, &first.get_signed_data());
+        append_with_length(&mut info, &second.get_signed_data());
+        self.keys[key_index as usize] = Some(derive_key(&secret, &info)?);
+        Ok(())
+    }
+
+    /// Returns the PRF keys known to this party in the form of bit arrays of length [KEY_LENGTH].
+    ///
+    /// # Returns
+    ///
+    /// Vector of length [PARTIES], whose i-th element is key k_i or `None` if this key is not known to the party
+    pub fn get_prf_keys(&self) -> Result<Vec<Option<Value>>> {
+        let party_id = self.identity.party_id as usize;
+        let mut result = vec![None; PARTIES];
+        for key_index in [party_id, (party_id + 1) % PARTIES] {
+            let key = self.keys[key_index].as_ref().ok_or_else(|| {
+                runtime_error!("Key agreement for key {} isn't finished", key_index)
+            })?;
+            result[key_index] = Some(Value::from_bytes(key.clone()));
+        }
+        Ok(result)
+    }
+}
+
+/// Combines the PRF keys known to the parties into a key triple in the format of compiled graphs, i.e. a tuple of [PARTIES] bit arrays of length [KEY_LENGTH].
+///
+/// Keys known to several parties must coincide.
+/// This is useful to evaluate the computation graph of a compiled context in one process.
+///
+/// # Arguments
+///
+/// `party_keys` - keys returned by [KeySetup::get_prf_keys] for every party
+///
+/// # Returns
+///
+/// Key triple
+pub fn get_prf_key_triple(party_keys: Vec<Vec<Option<Value>>>) -> Result<TypedValue> {
+    let mut keys: Vec<Option<Value>> = vec![None; PARTIES];
+    for party_key in party_keys {
+        if party_key.len() != PARTIES {
+            return Err(runtime_error!("Keys of {} parties expected", PARTIES));
+        }
+        for (key, party_key) in keys.iter_mut().zip(party_key) {
+            if let Some(party_key) = party_key {
+                match key {
+                    Some(key) if *key != party_key => {
+                        return Err(runtime_error!("Parties derived different keys"));
+                    }
+                    _ => *key = Some(party_key),
+                }
+            }
+        }
+    }
+    let keys = keys
+        .into_iter()
+        .map(|key| key.ok_or_else(|| runtime_error!("Key isn't known to any party")))
+        .collect::<Result<Vec<Value>>>()?;
+    let key_t = array_type(vec![KEY_LENGTH], BIT);
+    TypedValue::new(tuple_type(vec![key_t; PARTIES]), Value::from_vector(keys))
+}
+
+fn check_party_id(party_id: u64) -> Result<()> {
+    if party_id as usize >= PARTIES {
+        return Err(runtime_error!("Invalid party ID {}", party_id));
+    }
+    Ok(())
+}
+
+fn append_with_length(data: &mut Vec<u8>, bytes: &[u8]) {
+    data.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+    data.extend_from_slice(bytes);
+}
+
+fn derive_key(secret: &[u8], info: &[u8]) -> Result<Vec<u8>> {
+    let mut key = vec![0u8; KEY_LENGTH as usize / 8];
+    let mut ctx =
+        PkeyCtx::new_id(Id::HKDF).map_err(|e| runtime_error!("Key derivation failed: {}", e))?;
+    ctx.derive_init()
+        .and_then(|_| ctx.set_hkdf_md(Md::sha256()))
+        .and_then(|_| ctx.set_hkdf_key(secret))
+        .and_then(|_| ctx.add_hkdf_info(info))
+        .and_then(|_| ctx.derive(Some(&mut key)))
+        .map_err(|e| runtime_error!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_parties(session_id: &[u8]) -> Result<Vec<KeySetup>> {
+        let identities = (0..PARTIES as u64)
+            .map(PartyIdentity::generate)
+            .collect::<Result<Vec<PartyIdentity>>>()?;
+        let public_keys = identities
+            .iter()
+            .map(|identity| identity.get_public_key())
+            .collect::<Result<Vec<Vec<u8>>>>()?;
+        identities
+            .into_iter()
+            .map(|identity| KeySetup::new(identity, session_id.to_vec(), public_keys.clone()))
+            .collect()
+    }
+
+    fn run_setup(setups: &mut [KeySetup]) -> Result<Vec<Vec<Option<Value>>>> {
+        let mut messages = vec![];
+        for setup in setups.iter_mut() {
+            messages.extend(setup.get_messages()?);
+        }
+        for message in messages {
+            setups[message.receiver as usize].process_message(message)?;
+        }
+        setups.iter().map(|setup| setup.get_prf_keys()).collect()
+    }
+
+    #[test]
+    fn test_key_setup() {
+        || -> Result<()> {
+            let mut setups = setup_parties(b"session 1")?;
+            let party_keys = run_setup(&mut setups)?;
+            for (party_id, keys) in party_keys.iter().enumerate() {
+                for (key_index, key) in keys.iter().enumerate() {
+                    let is_known = key_index == party_id || key_index == (party_id + 1) % PARTIES;
+                    assert_eq!(key.is_some(), is_known);
+                }
+            }
+            // Key k_i is shared by parties i and i-1
+            assert_eq!(party_keys[0][1], party_keys[1][1]);
+            assert_eq!(party_keys[1][2], party_keys[2][2]);
+            assert_eq!(party_keys[2][0], party_keys[0][0]);
+            let triple = get_prf_key_triple(party_keys.clone())?;
+            let key_t = array_type(vec![KEY_LENGTH], BIT);
+            assert_eq!(triple.t, tuple_type(vec![key_t; PARTIES]));
+            let keys = triple.value.to_vector()?;
+            assert_ne!(keys[0], keys[1]);
+            assert_ne!(keys[1], keys[2]);
+
+            // Keys of another session are independent
+            let other_keys = run_setup(&mut setup_parties(b"session 2")?)?;
+            assert_ne!(other_keys[0][0], party_keys[0][0]);
+
+            // Inconsistent keys are detected
+            let mut bad_keys = party_keys.clone();
+            bad_keys[1][1] = bad_keys[0][0].clone();
+            assert!(get_prf_key_triple(bad_keys).is_err());
+            assert!(get_prf_key_triple(party_keys[..1].to_vec()).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_key_setup_rejects_invalid_messages() {
+        || -> Result<()> {
+            let mut setups = setup_parties(b"session")?;
+            let messages0 = setups[0].get_messages()?;
+            setups[1].get_messages()?;
+            // Keys aren't available before the exchange
+            assert!(setups[1].get_prf_keys().is_err());
+            let message = messages0
+                .iter()
+                .find(|message| message.receiver == 1)
+                .unwrap()
+                .clone();
+
+            let mut tampered = message.clone();
+            tampered.ephemeral_public_key[0] ^= 1;
+            assert!(setups[1].process_message(tampered).is_err());
+            let mut impersonated = message.clone();
+            impersonated.sender = 2;
+            assert!(setups[1].process_message(impersonated).is_err());
+            let mut other_session = message.clone();
+            other_session.session_id = b"other".to_vec();
+            assert!(setups[1].process_message(other_session).is_err());
+            assert!(setups[2].process_message(message.clone()).is_err());
+            setups[1].process_message(message)?;
+
+            // Messages signed by a key of another party are rejected
+            let mut impostor_setups = setup_parties(b"session")?;
+            let impostor_message = impostor_setups[0]
+                .get_messages()?
+                .into_iter()
+                .find(|message| message.receiver == 1)
+                .unwrap();
+            assert!(setups[1].process_message(impostor_message).is_err());
+
+            // Identities must match their public keys
+            let identity = PartyIdentity::generate(0)?;
+            let public_key = identity.get_public_key()?;
+            let restored = PartyIdentity::from_private_key(0, &identity.get_private_key()?)?;
+            assert_eq!(restored.get_public_key()?, public_key);
+            let other = PartyIdentity::generate(1)?.get_public_key()?;
+            assert!(KeySetup::new(
+                restored,
+                vec![],
+                vec![other.clone(), other.clone(), other.clone()]
+            )
+            .is_err());
+            assert!(PartyIdentity::generate(3).is_err());
+            let identity = PartyIdentity::generate(0)?;
+            assert!(KeySetup::new(identity, vec![], vec![public_key]).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
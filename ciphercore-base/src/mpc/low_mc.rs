@@ -9,7 +9,7 @@ use serde::{Deserialize, Serialize};
 
 pub(super) const LOW_MC_KEY_SIZE: u64 = 128;
 
-#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub enum LowMCBlockSize {
     SIZE80,
     SIZE128,
@@ -290,6 +290,8 @@ impl CustomOperationBody for LowMC {
     }
 }
 
+register_custom_operation!(LowMC, [Shared, Shared]);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,323 @@
+//! Secure aggregation of private vectors of many contributors, e.g. model updates in federated learning.
+//!
+//! Contributions are secret-shared among the three computing parties and summed locally,
+//! so the parties learn nothing except the (optionally revealed) sum.
+//! Contributors that dropped out are excluded from the sum according to a public participation mask;
+//! their shares can be set to zero without affecting the result.
+use crate::custom_ops::{run_instantiation_pass, CustomOperation, CustomOperationBody};
+use crate::data_types::{array_type, tuple_type, Type, BIT};
+use crate::errors::Result;
+use crate::graphs::{create_context, Context, Graph, Node};
+use crate::inline::inline_ops::{inline_operations, InlineConfig};
+use crate::mpc::mpc_compiler::{
+    check_private_tuple, generate_prf_key_triple, get_zero_shares, reveal_output, share_node,
+    uniquify_prf_id, IOStatus, PARTIES,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Sums secret-shared contributions of participating contributors.
+///
+/// Takes the following inputs:
+///
+/// * shares of the contributions stacked along the first axis, i.e. a tuple of 3 arrays of shape `[k, ...]`, where `k` is the number of contributors,
+/// * public binary array of shape `[k]` indicating which contributors participate in the aggregation,
+/// * PRF key triple (see [generate_prf_key_triple]).
+///
+/// Returns shares of the sum of the participating contributions (a tuple of 3 arrays of shape `[...]` or scalars).
+/// The shares are re-randomized, so they don't reveal which parts of the input shares were summed.
+/// Contributions of non-participating contributors (e.g. those that dropped out) are ignored.
+///
+/// Contributions must be arithmetically shared, i.e. their scalar type can't be BIT.
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct SecureAggregationMPC {}
+
+#[typetag::serde]
+impl CustomOperationBody for SecureAggregationMPC {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 3 {
+            return Err(runtime_error!(
+                "SecureAggregationMPC should have 3 inputs: contributions, participation mask and PRF keys"
+            ));
+        }
+        let share_t = match argument_types[0].clone() {
+            Type::Tuple(shares_t) => {
+                check_private_tuple(shares_t.clone())?;
+                (*shares_t[0]).clone()
+            }
+            _ => {
+                return Err(runtime_error!(
+                    "Contributions must be given as a tuple of shares"
+                ));
+            }
+        };
+        if !share_t.is_array() || share_t.get_scalar_type() == BIT {
+            return Err(runtime_error!(
+                "Contributions must be stacked in an array of a non-binary type"
+            ));
+        }
+        let shape = share_t.get_shape();
+        let num_contributors = shape[0];
+        if argument_types[1] != array_type(vec![num_contributors], BIT) {
+            return Err(runtime_error!(
+                "Participation mask must be a binary array of length {}",
+                num_contributors
+            ));
+        }
+
+        let g = context.create_graph()?;
+        let contributions = g.input(argument_types[0].clone())?;
+        let participation = g.input(argument_types[1].clone())?;
+        let prf_keys = g.input(argument_types[2].clone())?;
+
+        // Reshape the mask to broadcast it along all the axes of contributions except for the first one
+        let mut mask_shape = vec![1; shape.len()];
+        mask_shape[0] = num_contributors;
+        let mask = participation.reshape(array_type(mask_shape, BIT))?;
+
+        let sum_t = if shape.len() == 1 {
+            Type::Scalar(share_t.get_scalar_type())
+        } else {
+            array_type(shape[1..].to_vec(), share_t.get_scalar_type())
+        };
+        let zero_shares = get_zero_shares(g.clone(), prf_keys, sum_t)?;
+        let mut result_shares = vec![];
+        for (i, zero_share) in zero_shares.into_iter().enumerate() {
+            // Multiplication by a public mask and summation are local
+            let share_sum = contributions
+                .tuple_get(i as u64)?
+                .mixed_multiply(mask.clone())?
+                .sum(vec![0])?;
+            result_shares.push(share_sum.add(zero_share)?);
+        }
+        g.create_tuple(result_shares)?.set_as_output()?;
+        g.finalize()
+    }
+
+    fn get_name(&self) -> String {
+        "SecureAggregationMPC".to_owned()
+    }
+}
+
+register_custom_operation!(SecureAggregationMPC, [Shared, Any, PrfKeys]);
+
+// Adds an input node containing a contribution and secret-shares it according to its status
+fn add_contribution_input(
+    g: &Graph,
+    t: Type,
+    status: IOStatus,
+    prf_keys: Node,
+) -> Result<Vec<Node>> {
+    let shares = match status {
+        IOStatus::Party(_) => {
+            let plain_input = g.input(t)?;
+            share_node(g.clone(), plain_input, prf_keys, status)?
+        }
+        IOStatus::Shared => g.input(tuple_type(vec![t; PARTIES]))?,
+        IOStatus::Public => {
+            return Err(runtime_error!("Contributions can't be public"));
+        }
+    };
+    (0..PARTIES as u64).map(|i| shares.tuple_get(i)).collect()
+}
+
+/// Creates a context securely summing contributions of several contributors.
+///
+/// The main graph of the resulting context takes the contributions in the order of `input_statuses` followed by
+/// a public binary array of length `input_statuses.len()` indicating which contributors participate.
+/// Contributors are usually external clients secret-sharing their data among the computing parties ([IOStatus::Shared]),
+/// but a contribution can also be owned by one of the computing parties.
+/// The input of a contributor that dropped out can contain arbitrary values (e.g. zeros); it is excluded from the sum by the participation mask.
+///
+/// # Arguments
+///
+/// * `contribution_t` - type of every contribution (a scalar or an array of a non-binary type)
+/// * `input_statuses` - statuses of the contributions (shared or owned by a party)
+/// * `output_parties` - parties obtaining the revealed sum; if empty, the sum is returned in the shared form
+/// * `inline_config` - configuration of inlining
+///
+/// # Returns
+///
+/// Compiled and fully inlined context
+pub fn get_secure_aggregation_context(
+    contribution_t: Type,
+    input_statuses: Vec<IOStatus>,
+    output_parties: Vec<IOStatus>,
+    inline_config: InlineConfig,
+) -> Result<Context> {
+    if input_statuses.is_empty() {
+        return Err(runtime_error!("There should be at least one contributor"));
+    }
+    if !contribution_t.is_scalar() && !contribution_t.is_array() {
+        return Err(runtime_error!(
+            "Contributions must be scalars or arrays, but {} is given",
+            contribution_t
+        ));
+    }
+    let num_contributors = input_statuses.len() as u64;
+    let context = create_context()?;
+    let g = context.create_graph()?;
+    let prf_keys = g.create_tuple(generate_prf_key_triple(g.clone())?)?;
+    let mut contributions_shares = vec![vec![]; PARTIES];
+    for status in input_statuses {
+        let shares = add_contribution_input(&g, contribution_t.clone(), status, prf_keys.clone())?;
+        for (i, share) in shares.into_iter().enumerate() {
+            contributions_shares[i].push(share);
+        }
+    }
+    let mut stacked_shares = vec![];
+    for shares in contributions_shares {
+        stacked_shares.push(g.stack(shares, vec![num_contributors])?);
+    }
+    let participation = g.input(array_type(vec![num_contributors], BIT))?;
+    let sum = g.custom_op(
+        CustomOperation::new(SecureAggregationMPC {}),
+        vec![g.create_tuple(stacked_shares)?, participation, prf_keys],
+    )?;
+    reveal_output(g.clone(), sum, output_parties)?.set_as_output()?;
+    g.finalize()?.set_as_main()?;
+    context.finalize()?;
+    let instantiated_context = run_instantiation_pass(context)?.get_context();
+    let inlined_context = inline_operations(instantiated_context, inline_config)?;
+    uniquify_prf_id(inlined_context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{scalar_type, INT32, INT64};
+    use crate::data_values::Value;
+    use crate::evaluators::random_evaluate;
+    use crate::inline::inline_ops::InlineMode;
+    use crate::mpc::mpc_equivalence_class::verify_privacy;
+    use crate::typed_value::TypedValue;
+
+    fn simple_inline_config() -> InlineConfig {
+        InlineConfig {
+            default_mode: InlineMode::Simple,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_secure_aggregation() {
+        || -> Result<()> {
+            let t = array_type(vec![3], INT64);
+            let input_statuses = vec![
+                IOStatus::Shared,
+                IOStatus::Party(0),
+                IOStatus::Shared,
+                IOStatus::Party(2),
+            ];
+            let contributions = [
+                vec![1, -2, 3],
+                vec![10, 20, 30],
+                vec![-100, 0, 100],
+                vec![1000, 1000, -1000],
+            ];
+            let mut prng = crate::random::PRNG::new(None)?;
+            let mut inputs = vec![];
+            for (contribution, status) in contributions.iter().zip(input_statuses.iter()) {
+                let value = Value::from_flattened_array(contribution, INT64)?;
+                if *status == IOStatus::Shared {
+                    inputs.push(
+                        TypedValue::new(t.clone(), value)?
+                            .secret_share(&mut prng)?
+                            .value,
+                    );
+                } else {
+                    inputs.push(value);
+                }
+            }
+            // The third contributor dropped out
+            inputs.push(Value::from_flattened_array(&[1, 1, 0, 1], BIT)?);
+
+            let c = get_secure_aggregation_context(
+                t.clone(),
+                input_statuses.clone(),
+                vec![IOStatus::Party(1)],
+                simple_inline_config(),
+            )?;
+            verify_privacy(
+                c.clone(),
+                vec![
+                    IOStatus::Shared,
+                    IOStatus::Party(0),
+                    IOStatus::Shared,
+                    IOStatus::Party(2),
+                    IOStatus::Public,
+                ],
+                vec![IOStatus::Party(1)],
+            )?;
+            let result = random_evaluate(c.get_main_graph()?, inputs.clone())?;
+            assert_eq!(
+                result.to_flattened_array_i64(t.clone())?,
+                vec![1011, 1018, -967]
+            );
+
+            // Shared output
+            let c = get_secure_aggregation_context(
+                t.clone(),
+                input_statuses,
+                vec![],
+                simple_inline_config(),
+            )?;
+            let result = random_evaluate(c.get_main_graph()?, inputs)?;
+            let result = TypedValue::new(tuple_type(vec![t.clone(); PARTIES]), result)?
+                .secret_share_reveal()?;
+            assert_eq!(
+                result.value.to_flattened_array_i64(t)?,
+                vec![1011, 1018, -967]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_secure_aggregation_scalars() {
+        || -> Result<()> {
+            let t = scalar_type(INT32);
+            let c = get_secure_aggregation_context(
+                t.clone(),
+                vec![IOStatus::Party(0), IOStatus::Party(1)],
+                vec![IOStatus::Party(0), IOStatus::Party(2)],
+                simple_inline_config(),
+            )?;
+            let result = random_evaluate(
+                c.get_main_graph()?,
+                vec![
+                    Value::from_scalar(5, INT32)?,
+                    Value::from_scalar(-7, INT32)?,
+                    Value::from_flattened_array(&[1, 1], BIT)?,
+                ],
+            )?;
+            assert_eq!(result.to_i32(INT32)?, -2);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_secure_aggregation() {
+        let t = array_type(vec![3], INT32);
+        assert!(
+            get_secure_aggregation_context(t.clone(), vec![], vec![], simple_inline_config())
+                .is_err()
+        );
+        assert!(get_secure_aggregation_context(
+            t,
+            vec![IOStatus::Public],
+            vec![],
+            simple_inline_config()
+        )
+        .is_err());
+        assert!(get_secure_aggregation_context(
+            array_type(vec![3], BIT),
+            vec![IOStatus::Shared],
+            vec![],
+            simple_inline_config()
+        )
+        .is_err());
+    }
+}
@@ -82,6 +82,8 @@ impl CustomOperationBody for AddMPC {
     }
 }
 
+register_custom_operation!(AddMPC, [Shared, Shared]);
+
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub(super) struct SubtractMPC {}
 
@@ -166,6 +168,8 @@ impl CustomOperationBody for SubtractMPC {
     }
 }
 
+register_custom_operation!(SubtractMPC, [Shared, Shared]);
+
 fn bilinear_product(l: Node, r: Node, op: Operation) -> Result<Node> {
     match op {
         Operation::Multiply => l.multiply(r),
@@ -330,6 +334,8 @@ impl CustomOperationBody for MultiplyMPC {
     }
 }
 
+register_custom_operation!(MultiplyMPC, [Shared, Shared], [Shared, Shared, PrfKeys]);
+
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub(super) struct DotMPC {}
 
@@ -344,6 +350,8 @@ impl CustomOperationBody for DotMPC {
     }
 }
 
+register_custom_operation!(DotMPC, [Shared, Shared], [Shared, Shared, PrfKeys]);
+
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub(super) struct MatmulMPC {}
 
@@ -358,6 +366,8 @@ impl CustomOperationBody for MatmulMPC {
     }
 }
 
+register_custom_operation!(MatmulMPC, [Shared, Shared], [Shared, Shared, PrfKeys]);
+
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub(super) struct GemmMPC {
     pub transpose_a: bool,
@@ -379,6 +389,8 @@ impl CustomOperationBody for GemmMPC {
     }
 }
 
+register_custom_operation!(GemmMPC, [Shared, Shared], [Shared, Shared, PrfKeys]);
+
 // Accepts at least 2 arguments including integer arrays/scalars and binary arrays/scalars that must be multiplied.
 // If bits are in the secret shared form, then PRF keys must be provided.
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
@@ -558,6 +570,12 @@ impl CustomOperationBody for MixedMultiplyMPC {
     }
 }
 
+register_custom_operation!(
+    MixedMultiplyMPC,
+    [Shared, Shared],
+    [Shared, Shared, PrfKeys]
+);
+
 #[cfg(test)]
 mod tests {
     use super::*;
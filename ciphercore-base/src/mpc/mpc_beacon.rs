@@ -0,0 +1,163 @@
+//! Public randomness agreed by all parties (random beacon) via coin tossing.
+//!
+//! Recall that compiled graphs use a triple of PRF keys (k_0, k_1, k_2) such that key k_i is known only to parties i and i-1.
+//! The beacon value is the sum R = PRF(k_0) + PRF(k_1) + PRF(k_2).
+//! Each key is fixed before any PRF output is computed and isn't known to one of the parties,
+//! so the keys serve as commitments to the three contributions and no party can predict or bias R.
+//! To reveal R, every party i receives the only contribution it lacks, PRF(k_{i+2}), from party i+1, which knows k_{i+2}.
+use crate::custom_ops::CustomOperationBody;
+use crate::data_types::Type;
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, NodeAnnotation};
+use crate::mpc::mpc_compiler::{check_private_tuple, PARTIES};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub(super) struct RandomBeaconMPC {
+    pub t: Type,
+}
+
+/// RandomBeacon MPC operation with the following argument:
+/// 1. PRF keys for MPC multiplication.
+///
+/// Returns a public value of the type `t`.
+#[typetag::serde]
+impl CustomOperationBody for RandomBeaconMPC {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 1 {
+            // Panics since:
+            // - the user has no direct access to this function.
+            // - the MPC compiler should pass the correct number of arguments
+            // and this panic should never happen.
+            panic!("RandomBeaconMPC should have 1 input.");
+        }
+        if let Type::Tuple(v) = argument_types[0].clone() {
+            check_private_tuple(v)?;
+        } else {
+            panic!("RandomBeaconMPC should have a tuple of PRF keys as input");
+        }
+        let g = context.create_graph()?;
+        let prf_keys = g.input(argument_types[0].clone())?;
+        let mut contributions = vec![];
+        for key_id in 0..PARTIES {
+            let contribution = g.prf(prf_keys.tuple_get(key_id as u64)?, 0, self.t.clone())?;
+            // Key k_i is known to parties i and i-1, so party i-1 sends its contribution to party i+1.
+            let sender = (key_id + PARTIES - 1) % PARTIES;
+            let receiver = (key_id + 1) % PARTIES;
+            let revealed_contribution = contribution.nop()?;
+            revealed_contribution
+                .add_annotation(NodeAnnotation::Send(sender as u64, receiver as u64))?;
+            contributions.push(revealed_contribution);
+        }
+        contributions[0]
+            .add(contributions[1].clone())?
+            .add(contributions[2].clone())?
+            .set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!("RandomBeaconMPC({})", self.t)
+    }
+}
+
+register_custom_operation!(RandomBeaconMPC, [PrfKeys]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{array_type, scalar_type, tuple_type, BIT, INT64, UINT32};
+    use crate::data_values::Value;
+    use crate::evaluators::random_evaluate;
+    use crate::graphs::create_context;
+    use crate::inline::inline_ops::{InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{prepare_for_mpc_evaluation, IOStatus};
+    use crate::mpc::mpc_equivalence_class::verify_privacy;
+
+    #[test]
+    fn test_random_beacon() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![100], INT64);
+            let x = g.input(t.clone())?;
+            let r1 = g.random_beacon(t.clone())?;
+            let r2 = g.random_beacon(t.clone())?;
+            let bit = g.random_beacon(scalar_type(BIT))?;
+            g.create_tuple(vec![r1.clone(), r2, x.add(r1)?, bit])?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let mpc_c = prepare_for_mpc_evaluation(
+                c,
+                vec![vec![IOStatus::Shared]],
+                vec![vec![
+                    IOStatus::Party(0),
+                    IOStatus::Party(1),
+                    IOStatus::Party(2),
+                ]],
+                InlineConfig {
+                    default_mode: InlineMode::Simple,
+                    ..Default::default()
+                },
+            )?;
+            let x_value: Vec<i64> = (0..100).map(|i| i * 1000 - 50000).collect();
+            let shares = vec![
+                Value::from_flattened_array(&x_value, INT64)?,
+                Value::from_flattened_array(&[0; 100], INT64)?,
+                Value::from_flattened_array(&[0; 100], INT64)?,
+            ];
+            let result =
+                random_evaluate(mpc_c.get_main_graph()?, vec![Value::from_vector(shares)])?
+                    .to_vector()?;
+            let r1 = result[0].to_flattened_array_i64(t.clone())?;
+            let r2 = result[1].to_flattened_array_i64(t.clone())?;
+            let masked = result[2].to_flattened_array_i64(t)?;
+            // Different beacons are independent
+            assert_ne!(r1, r2);
+            for i in 0..100 {
+                assert_eq!(masked[i].wrapping_sub(r1[i]), x_value[i]);
+            }
+            assert!(result[3].to_u64(BIT)? < 2);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_random_beacon_privacy() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![10], INT64);
+            let x = g.input(t.clone())?;
+            x.add(g.random_beacon(t)?)?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            for output_parties in [vec![], vec![IOStatus::Party(1)]] {
+                let mpc_c = prepare_for_mpc_evaluation(
+                    c.clone(),
+                    vec![vec![IOStatus::Party(0)]],
+                    vec![output_parties.clone()],
+                    InlineConfig {
+                        default_mode: InlineMode::Simple,
+                        ..Default::default()
+                    },
+                )?;
+                verify_privacy(mpc_c, vec![IOStatus::Party(0)], output_parties)?;
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed() {
+        let c = create_context().unwrap();
+        let g = c.create_graph().unwrap();
+        let r = g.random_beacon(tuple_type(vec![scalar_type(UINT32)]));
+        assert!(r.is_err());
+    }
+}
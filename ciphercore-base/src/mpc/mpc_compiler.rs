@@ -4,21 +4,34 @@ use crate::data_values::Value;
 use crate::errors::Result;
 use crate::evaluators::Evaluator;
 use crate::graphs::{
-    copy_node_name, create_context, Context, Graph, Node, NodeAnnotation, Operation,
+    copy_node_name, copy_node_provenance, create_context, Context, Graph, Node, NodeAnnotation,
+    Operation,
 };
-use crate::inline::inline_ops::{inline_operations, InlineConfig};
+use crate::inline::inline_ops::{
+    inline_operations, inline_operations_with_depth_budget, InlineConfig, InlineMode, InlineReport,
+};
+use crate::mpc::garbled_circuits::{get_garbled_nodes, Garbler};
 use crate::mpc::mpc_arithmetic::{
     AddMPC, DotMPC, MatmulMPC, MixedMultiplyMPC, MultiplyMPC, SubtractMPC,
 };
-use crate::mpc::mpc_conversion::{A2BMPC, B2AMPC};
-use crate::mpc::mpc_truncate::{TruncateMPC, TruncateMPC2K};
-use crate::optimizer::optimize::optimize_context;
+use crate::mpc::mpc_beacon::RandomBeaconMPC;
+use crate::mpc::mpc_conversion::{ConvertRingMPC, A2BMPC, B2AMPC};
+use crate::mpc::mpc_equivalence_class::{verify_privacy, verify_privacy_with_output_policy};
+use crate::mpc::mpc_truncate::TruncatePrMPC;
+use crate::mpc::output_policy::{add_output_noise, apply_output_policy, OutputPolicy};
+use crate::optimizer::pass_manager::{InliningPass, InstantiationPass, Pass, PassManager};
 
 use std::collections::HashMap;
 use std::collections::HashSet;
 
+use super::low_mc::LowMCBlockSize;
 use super::mpc_arithmetic::GemmMPC;
-use super::mpc_psi::SetIntersectionMPC;
+use super::mpc_psi::{
+    PsiConfig, PsiMode, PsiPreprocessingMPC, PsiPrf, ScatterMPC, SetIntersectionMPC,
+};
+use super::mpc_two_party::compile_to_two_party;
+use super::shamir::compile_to_shamir;
+use super::spdz::compile_to_spdz;
 
 // We implement the ABY3 protocol, which has 3 parties involved
 pub const PARTIES: usize = 3;
@@ -31,6 +44,29 @@ pub enum IOStatus {
     Shared,     // input/output is shared / unknown to all the parties
 }
 
+/// MPC protocol targeted by [compile_context_with_backend].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MpcBackend {
+    /// Honest-majority three-party protocol ABY3
+    #[default]
+    ABY3,
+    /// Semi-honest two-party protocol with additive sharing and Beaver triples generated by parties 0 and 1 via OT, see [mpc_two_party](crate::mpc::mpc_two_party).
+    ///
+    /// Input and output parties must be 0 or 1.
+    /// The compiled graph takes the triples of both parties returned by [generate_triples](crate::mpc::mpc_two_party::generate_triples) as its last two inputs.
+    /// Conversions between arithmetic and binary shares and SetIntersection aren't supported.
+    TwoParty,
+    /// Two-party protocol with MAC-authenticated shares secure against a malicious party, see [spdz](crate::mpc::spdz).
+    ///
+    /// The output of the compiled graph must be checked by [verify_mac_checks](crate::mpc::spdz::verify_mac_checks).
+    Spdz,
+    /// Threshold protocol among a given number of parties with Shamir sharing over Galois rings, see [shamir](crate::mpc::shamir).
+    ///
+    /// Any `threshold` semi-honest parties learn nothing, as long as `parties >= 2 * threshold + 1`.
+    /// Input and output parties must be computing parties; shared inputs aren't supported.
+    Shamir { parties: u64, threshold: u64 },
+}
+
 // Bitsize of PRF keys
 pub const KEY_LENGTH: u64 = 128;
 
@@ -210,7 +246,7 @@ fn propagate_private_annotations(
     let mut private_nodes: HashSet<Node> = HashSet::new();
     let mut use_prf_for_mul = false;
     let mut use_prf_for_b2a = false;
-    let mut use_prf_for_truncate2k = false;
+    let mut use_prf_for_truncate = false;
     let mut input_id = 0usize;
     for node in graph.get_nodes() {
         let op = node.get_operation();
@@ -231,6 +267,7 @@ fn propagate_private_annotations(
             | Operation::SetIntersection(_)
             | Operation::A2B
             | Operation::B2A(_)
+            | Operation::ConvertRing(_)
             | Operation::PermuteAxes(_)
             | Operation::ArrayToVector
             | Operation::TupleGet(_)
@@ -244,6 +281,12 @@ fn propagate_private_annotations(
             | Operation::CreateNamedTuple(_)
             | Operation::CreateVector(_)
             | Operation::Stack(_)
+            | Operation::Concatenate(_)
+            | Operation::Split(_, _)
+            | Operation::BroadcastTo(_)
+            | Operation::Tile(_)
+            | Operation::CumSum(_)
+            | Operation::Scatter(_)
             | Operation::Zip
             | Operation::Repeat(_) => {
                 let dependencies = node.get_node_dependencies();
@@ -253,6 +296,10 @@ fn propagate_private_annotations(
                         use_prf_for_mul = true;
                     }
                 }
+                // Scatter with private indices is performed by an oblivious protocol with multiplications
+                if matches!(op, Operation::Scatter(_)) && private_nodes.contains(&dependencies[1]) {
+                    use_prf_for_mul = true;
+                }
                 if ([
                     Operation::Multiply,
                     Operation::Dot,
@@ -276,8 +323,18 @@ fn propagate_private_annotations(
                 {
                     use_prf_for_mul = true;
                 }
+                if let Operation::ConvertRing(st) = &op {
+                    // Only conversion of private data to a bigger ring requires communication
+                    if are_all_nodes_private(&dependencies, &private_nodes)
+                        && st.size_in_bits()
+                            > dependencies[0].get_type()?.get_scalar_type().size_in_bits()
+                    {
+                        use_prf_for_mul = true;
+                        use_prf_for_b2a = true;
+                    }
+                }
             }
-            Operation::Truncate(scale) => {
+            Operation::Truncate(_) => {
                 let dependencies = node.get_node_dependencies();
                 if is_one_node_private(&dependencies, &private_nodes) {
                     private_nodes.insert(node.clone());
@@ -285,14 +342,21 @@ fn propagate_private_annotations(
 
                 if are_all_nodes_private(&dependencies, &private_nodes) {
                     use_prf_for_mul = true;
-                    if scale.is_power_of_two() {
-                        use_prf_for_truncate2k = true;
-                    }
+                    use_prf_for_truncate = true;
                 }
             }
             Operation::Constant(_, _) => {
                 // Constants are always public
             }
+            Operation::RandomBeacon(_) => {
+                // Random beacons are public, but their generation invokes PRFs
+                use_prf_for_mul = true;
+            }
+            Operation::Random(_) => {
+                // Random values are shared, i.e. unknown to all the parties
+                private_nodes.insert(node.clone());
+                use_prf_for_mul = true;
+            }
             Operation::VectorGet => {
                 let dependencies = node.get_node_dependencies();
                 if private_nodes.contains(&dependencies[1]) {
@@ -314,20 +378,32 @@ fn propagate_private_annotations(
         private_nodes,
         use_prf_for_mul,
         use_prf_for_b2a,
-        use_prf_for_truncate2k,
+        use_prf_for_truncate,
     ))
 }
 
+/// Compiles a graph to MPC and adds it to a given context.
+///
+/// If `garbling_depth_threshold` is given, binary subgraphs of at least this multiplicative depth are lowered to garbled circuits (see [garbled_circuits](super::garbled_circuits)).
 pub(super) fn compile_to_mpc_graph(
     in_graph: Graph,
     is_input_private: Vec<bool>,
     out_context: Context,
     out_mapping: &mut ContextMappings,
+    garbling_depth_threshold: Option<u64>,
 ) -> Result<Graph> {
     let out_graph = out_context.create_graph()?;
 
-    let (private_nodes, use_prf_for_mul, use_prf_for_b2a, use_prf_for_truncate2k) =
+    let (private_nodes, use_prf_for_mul, use_prf_for_b2a, use_prf_for_truncate) =
         propagate_private_annotations(in_graph.clone(), is_input_private)?;
+    // Garbling works with IDs of private nodes
+    let private_node_ids: HashSet<u64> = private_nodes.iter().map(|node| node.get_id()).collect();
+    let (garbled_nodes, decoded_nodes) = match garbling_depth_threshold {
+        Some(threshold) => get_garbled_nodes(in_graph.clone(), &private_node_ids, threshold)?,
+        None => (HashSet::new(), HashSet::new()),
+    };
+    // Garbling needs the PRF keys shared by pairs of parties
+    let use_prf_for_mul = use_prf_for_mul || !garbled_nodes.is_empty();
     // Input tuple of PRF keys for multiplication if needed
     // If created, these are the first input node of a graph
     let prf_keys_mul = if use_prf_for_mul {
@@ -357,7 +433,7 @@ pub(super) fn compile_to_mpc_graph(
     };
     // Input tuple of PRF keys for Truncate if needed
     // If created, these are the second input node of a graph
-    let prf_keys_truncate2k = if use_prf_for_truncate2k {
+    let prf_keys_truncate = if use_prf_for_truncate {
         // PRF key type
         let key_t = array_type(vec![KEY_LENGTH], BIT);
         let node = out_graph.input(key_t)?;
@@ -427,7 +503,27 @@ pub(super) fn compile_to_mpc_graph(
         out_graph.create_tuple(result_shares)
     };
 
+    // Preprocessed second inputs of unbalanced set intersections indexed by the input node ID, its key headers and the PSI configuration
+    let mut preprocessed_psi_inputs: HashMap<(u64, Vec<String>, PsiConfig), Node> = HashMap::new();
+
+    let mut garbler = match prf_keys_mul {
+        Some(ref keys) if !garbled_nodes.is_empty() => {
+            Some(Garbler::new(out_graph.clone(), keys.clone())?)
+        }
+        _ => None,
+    };
+
     for node in in_graph.get_nodes() {
+        if garbled_nodes.contains(&node.get_id()) {
+            let garbler = garbler.as_mut().unwrap();
+            garbler.garble(node.clone(), out_mapping, &private_node_ids)?;
+            if decoded_nodes.contains(&node.get_id()) {
+                let shares = garbler.decode(node.clone())?;
+                shares.add_annotation(NodeAnnotation::Private)?;
+                out_mapping.insert_node(node, shares);
+            }
+            continue;
+        }
         let op = node.get_operation();
         let new_node = match op.clone() {
             Operation::Input(_) => apply_op(node.clone(), op, vec![], vec![])?,
@@ -517,8 +613,68 @@ pub(super) fn compile_to_mpc_graph(
                 for headers_pair in headers {
                     headers_vec.push(headers_pair);
                 }
+                // Sort headers along the second set to get the same order of its key columns in all intersections with it
+                headers_vec.sort_by(|(h_x0, h_y0), (h_x1, h_y1)| (h_y0, h_x0).cmp(&(h_y1, h_x1)));
+                // Bloom filter, unbalanced and oblivious intersections are chosen by the corresponding annotations
+                let mode = node
+                    .get_annotations()?
+                    .into_iter()
+                    .find_map(|annotation| match annotation {
+                        NodeAnnotation::BloomFilterIntersection(
+                            num_hash_functions,
+                            bits_per_entry,
+                        ) => Some(PsiMode::BloomFilter {
+                            num_hash_functions,
+                            bits_per_entry,
+                        }),
+                        NodeAnnotation::UnbalancedIntersection => Some(PsiMode::Unbalanced),
+                        NodeAnnotation::ObliviousIntersection => Some(PsiMode::Oblivious),
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+                // The PRF, Cuckoo hashing and padding parameters of the protocol are chosen by the corresponding annotations
+                let mut config = PsiConfig::default();
+                for annotation in node.get_annotations()? {
+                    match annotation {
+                        NodeAnnotation::PsiLowMC(block_size, s_boxes_per_round, rounds) => {
+                            let block_size = match block_size {
+                                80 => LowMCBlockSize::SIZE80,
+                                128 => LowMCBlockSize::SIZE128,
+                                _ => {
+                                    return Err(runtime_error!(
+                                        "LowMC doesn't support block size {}",
+                                        block_size
+                                    ));
+                                }
+                            };
+                            config.prf = PsiPrf::LowMC {
+                                block_size,
+                                s_boxes_per_round,
+                                rounds,
+                            };
+                        }
+                        NodeAnnotation::PsiAes128 => {
+                            config.prf = PsiPrf::Aes128;
+                        }
+                        NodeAnnotation::PsiCuckooParameters(expansion_factor, stash_size) => {
+                            config.cuckoo_expansion_factor = expansion_factor;
+                            config.cuckoo_stash_size = stash_size;
+                        }
+                        NodeAnnotation::PsiCuckooHashFunctions(num_hash_functions) => {
+                            config.cuckoo_num_hash_functions = num_hash_functions;
+                        }
+                        NodeAnnotation::PsiPadding(padding) => {
+                            config.padding = padding;
+                        }
+                        _ => {}
+                    }
+                }
+                let key_headers_y: Vec<String> =
+                    headers_vec.iter().map(|(_, h_y)| h_y.clone()).collect();
                 let custom_op = CustomOperation::new(SetIntersectionMPC {
                     headers: headers_vec,
+                    mode,
+                    config: config.clone(),
                 });
 
                 if private_nodes.contains(&node) {
@@ -530,10 +686,26 @@ pub(super) fn compile_to_mpc_graph(
                             panic!("Propagation of annotations failed")
                         }
                     };
-                    out_graph.custom_op(
-                        custom_op,
-                        vec![new_input0.clone(), new_input1.clone(), keys],
-                    )?
+                    let second_input = if mode == PsiMode::Unbalanced {
+                        // The second set is preprocessed only once for all intersections with the same key columns
+                        let preprocessing_key =
+                            (input1.get_id(), key_headers_y.clone(), config.clone());
+                        if !preprocessed_psi_inputs.contains_key(&preprocessing_key) {
+                            let preprocessed_input = out_graph.custom_op(
+                                CustomOperation::new(PsiPreprocessingMPC {
+                                    key_headers: key_headers_y,
+                                    config,
+                                }),
+                                vec![new_input1.clone(), keys.clone()],
+                            )?;
+                            preprocessed_psi_inputs
+                                .insert(preprocessing_key.clone(), preprocessed_input);
+                        }
+                        preprocessed_psi_inputs[&preprocessing_key].clone()
+                    } else {
+                        new_input1.clone()
+                    };
+                    out_graph.custom_op(custom_op, vec![new_input0.clone(), second_input, keys])?
                 } else {
                     out_graph.custom_op(custom_op, vec![new_input0.clone(), new_input1.clone()])?
                 }
@@ -542,12 +714,7 @@ pub(super) fn compile_to_mpc_graph(
                 let dependencies = node.get_node_dependencies();
                 let input = dependencies[0].clone();
                 let new_input = out_mapping.get_node(input.clone());
-                let custom_op = if scale.is_power_of_two() {
-                    let k = scale.trailing_zeros() as u64;
-                    CustomOperation::new(TruncateMPC2K { k })
-                } else {
-                    CustomOperation::new(TruncateMPC { scale })
-                };
+                let custom_op = CustomOperation::new(TruncatePrMPC { scale });
                 if private_nodes.contains(&input) {
                     // If input is private, the MPC protocol requires invoking PRFs.
                     // Thus, PRF keys must be provided.
@@ -557,22 +724,16 @@ pub(super) fn compile_to_mpc_graph(
                             panic!("Propagation of annotations failed")
                         }
                     };
-
-                    if scale.is_power_of_two() {
-                        let prf_truncate_keys = match prf_keys_truncate2k {
-                            Some(ref k) => k.clone(),
-                            None => {
-                                panic!("Propagation of annotations failed")
-                            }
-                        };
-
-                        out_graph.custom_op(
-                            custom_op,
-                            vec![new_input.clone(), prf_mul_keys, prf_truncate_keys],
-                        )?
-                    } else {
-                        out_graph.custom_op(custom_op, vec![new_input.clone(), prf_mul_keys])?
-                    }
+                    let prf_truncate_keys = match prf_keys_truncate {
+                        Some(ref k) => k.clone(),
+                        None => {
+                            panic!("Propagation of annotations failed")
+                        }
+                    };
+                    out_graph.custom_op(
+                        custom_op,
+                        vec![new_input.clone(), prf_mul_keys, prf_truncate_keys],
+                    )?
                 } else {
                     out_graph.custom_op(custom_op, vec![new_input.clone()])?
                 }
@@ -621,7 +782,57 @@ pub(super) fn compile_to_mpc_graph(
                     out_graph.custom_op(custom_op, vec![new_input.clone()])?
                 }
             }
+            Operation::ConvertRing(st) => {
+                let dependencies = node.get_node_dependencies();
+                let input = dependencies[0].clone();
+                let new_input = out_mapping.get_node(input.clone());
+                let is_upcast =
+                    st.size_in_bits() > input.get_type()?.get_scalar_type().size_in_bits();
+                let custom_op = CustomOperation::new(ConvertRingMPC { st });
+                if private_nodes.contains(&input) && is_upcast {
+                    // If private input is converted to a bigger ring, the MPC protocol requires invoking PRFs.
+                    // Thus, PRF keys must be provided.
+                    let keys_mul = match prf_keys_mul {
+                        Some(ref k) => k.clone(),
+                        None => {
+                            panic!("Propagation of annotations failed")
+                        }
+                    };
+                    let keys_b2a = match prf_keys_b2a {
+                        Some(ref k) => k.clone(),
+                        None => {
+                            panic!("Propagation of annotations failed")
+                        }
+                    };
+                    out_graph.custom_op(custom_op, vec![new_input.clone(), keys_mul, keys_b2a])?
+                } else {
+                    out_graph.custom_op(custom_op, vec![new_input.clone()])?
+                }
+            }
             Operation::Constant(t, v) => out_graph.constant(t, v)?,
+            Operation::RandomBeacon(t) => {
+                let keys = match prf_keys_mul {
+                    Some(ref k) => k.clone(),
+                    None => {
+                        panic!("Propagation of annotations failed")
+                    }
+                };
+                out_graph.custom_op(CustomOperation::new(RandomBeaconMPC { t }), vec![keys])?
+            }
+            Operation::Random(t) => {
+                let keys = match prf_keys_mul {
+                    Some(ref k) => k.clone(),
+                    None => {
+                        panic!("Propagation of annotations failed")
+                    }
+                };
+                // Share i is PRF(k_i), which is known only to the parties holding k_i
+                let mut shares = vec![];
+                for i in 0..PARTIES as u64 {
+                    shares.push(keys.tuple_get(i)?.prf(0, t.clone())?);
+                }
+                out_graph.create_tuple(shares)?
+            }
             Operation::PermuteAxes(_)
             | Operation::ArrayToVector
             | Operation::VectorToArray
@@ -631,12 +842,72 @@ pub(super) fn compile_to_mpc_graph(
             | Operation::Reshape(_)
             | Operation::Sum(_)
             | Operation::Get(_)
+            | Operation::Split(_, _)
+            | Operation::BroadcastTo(_)
+            | Operation::Tile(_)
+            | Operation::CumSum(_)
             | Operation::Repeat(_) => {
                 let dependencies = node.get_node_dependencies();
                 let input = dependencies[0].clone();
                 let new_input = out_mapping.get_node(input.clone());
                 apply_op(input, op, vec![new_input], dependencies)?
             }
+            Operation::Scatter(axis) => {
+                let dependencies = node.get_node_dependencies();
+                let new_dependencies: Vec<Node> = dependencies
+                    .iter()
+                    .map(|x| out_mapping.get_node((*x).clone()))
+                    .collect();
+                if !private_nodes.contains(&node) {
+                    out_graph.add_node(new_dependencies, vec![], op)?
+                } else {
+                    // Public arrays are promoted to private by splitting into (node, 0, 0)
+                    let mut shared_arrays = vec![];
+                    for j in [0, 2] {
+                        if private_nodes.contains(&dependencies[j]) {
+                            shared_arrays.push(new_dependencies[j].clone());
+                        } else {
+                            let t = new_dependencies[j].get_type()?;
+                            let zero = out_graph.constant(t.clone(), Value::zero_of_type(t))?;
+                            let mut shares = vec![new_dependencies[j].clone()];
+                            shares.extend(vec![zero; PARTIES - 1]);
+                            shared_arrays.push(out_graph.create_tuple(shares)?);
+                        }
+                    }
+                    let new_indices = new_dependencies[1].clone();
+                    if private_nodes.contains(&dependencies[1]) {
+                        // If indices are private, the MPC protocol requires invoking PRFs.
+                        // Thus, PRF keys must be provided.
+                        let keys = match prf_keys_mul {
+                            Some(ref k) => k.clone(),
+                            None => {
+                                panic!("Propagation of annotations failed")
+                            }
+                        };
+                        out_graph.custom_op(
+                            CustomOperation::new(ScatterMPC { axis }),
+                            vec![
+                                shared_arrays[0].clone(),
+                                new_indices,
+                                shared_arrays[1].clone(),
+                                keys,
+                            ],
+                        )?
+                    } else {
+                        // Scatter is linear, so public indices are applied to every share locally
+                        let mut result_shares = vec![];
+                        for i in 0..PARTIES as u64 {
+                            result_shares.push(out_graph.scatter(
+                                shared_arrays[0].tuple_get(i)?,
+                                new_indices.clone(),
+                                shared_arrays[1].tuple_get(i)?,
+                                axis,
+                            )?);
+                        }
+                        out_graph.create_tuple(result_shares)?
+                    }
+                }
+            }
             Operation::VectorGet => {
                 let dependencies = node.get_node_dependencies();
                 let vector = dependencies[0].clone();
@@ -650,6 +921,7 @@ pub(super) fn compile_to_mpc_graph(
             | Operation::CreateNamedTuple(_)
             | Operation::CreateVector(_)
             | Operation::Stack(_)
+            | Operation::Concatenate(_)
             | Operation::Zip => {
                 let dependencies = node.get_node_dependencies();
                 let new_dependencies: Vec<Node> = dependencies
@@ -688,7 +960,7 @@ fn contains_node_annotation(g: Graph, annotation: NodeAnnotation) -> Result<bool
     Ok(false)
 }
 
-fn share_node(g: Graph, node: Node, prf_keys: Node, status: IOStatus) -> Result<Node> {
+pub(super) fn share_node(g: Graph, node: Node, prf_keys: Node, status: IOStatus) -> Result<Node> {
     let mut outputs = vec![];
     let t = node.get_type()?;
     let node_shares = get_node_shares(g.clone(), prf_keys, t, Some((node, status)))?;
@@ -862,7 +1134,11 @@ pub(super) fn recursively_sum_shares(g: Graph, shares: Vec<Node>) -> Result<Node
 }
 
 /// Output parties ids must be in the range 0..PARTIES.
-fn reveal_output(g: Graph, out_node: Node, output_parties: Vec<IOStatus>) -> Result<Node> {
+pub(super) fn reveal_output(
+    g: Graph,
+    out_node: Node,
+    output_parties: Vec<IOStatus>,
+) -> Result<Node> {
     // If there are no parties obtaining revealed output, return output in the shared form
     if output_parties.is_empty() {
         return Ok(out_node);
@@ -901,6 +1177,11 @@ fn reveal_output(g: Graph, out_node: Node, output_parties: Vec<IOStatus>) -> Res
         } else {
             revealed_node
         };
+        for status in &output_parties {
+            if let IOStatus::Party(id) = status {
+                result_node.add_annotation(NodeAnnotation::Reveal(*id))?;
+            }
+        }
         return Ok(result_node);
     }
     panic!("Shouldn't be here");
@@ -912,13 +1193,16 @@ fn reveal_output(g: Graph, out_node: Node, output_parties: Vec<IOStatus>) -> Res
 /// - public,
 /// - already shared,
 /// - should be shared by certain party.
-/// The `output_parties` argument contains ids of the parties (from 0..PARTIES) that obtain the revealed result of MPC computation.
+///
+/// The `output_policies` argument describes which parties (from 0..PARTIES) obtain the revealed result of MPC computation.
+/// If `garbling_depth_threshold` is given, deep binary subgraphs are lowered to garbled circuits.
 fn compile_to_mpc_context(
     in_context: Context,
     input_party_map: Vec<Vec<IOStatus>>,
-    output_parties: Vec<Vec<IOStatus>>,
+    output_policies: Vec<OutputPolicy>,
     out_context: Context,
     out_mapping: &mut ContextMappings,
+    garbling_depth_threshold: Option<u64>,
 ) -> Result<()> {
     in_context.check_finalized()?;
 
@@ -933,6 +1217,7 @@ fn compile_to_mpc_context(
             is_input_private.clone(),
             out_context.clone(),
             out_mapping,
+            garbling_depth_threshold,
         )?;
 
         let new_graph = out_context.create_graph()?;
@@ -968,8 +1253,8 @@ fn compile_to_mpc_context(
             out_anno.contains(&NodeAnnotation::Private)
         };
         let result = if is_output_private {
-            reveal_output(new_graph.clone(), shared_result, output_parties[i].clone())?
-        } else if output_parties[i].is_empty() {
+            apply_output_policy(new_graph.clone(), shared_result, &output_policies[i])?
+        } else if output_policies[i].get_parties().is_empty() {
             // if output is public and it should be secretly shared (no output parties), party 0 creates its secret sharing
             let node = share_node(
                 new_graph.clone(),
@@ -1000,6 +1285,21 @@ fn compile_to_mpc(
     context: Context,
     input_party_map: Vec<Vec<IOStatus>>,
     output_parties: Vec<Vec<IOStatus>>,
+) -> Result<MappedContext> {
+    let output_policies = output_parties
+        .iter()
+        .map(|statuses| OutputPolicy::from_statuses(statuses))
+        .collect::<Result<Vec<OutputPolicy>>>()?;
+    compile_to_mpc_with_output_policy(context, input_party_map, output_policies, None)
+}
+
+/// Same as [compile_to_mpc], but outputs are processed according to given policies
+/// and binary subgraphs of multiplicative depth at least `garbling_depth_threshold` (if given) are garbled.
+fn compile_to_mpc_with_output_policy(
+    context: Context,
+    input_party_map: Vec<Vec<IOStatus>>,
+    output_policies: Vec<OutputPolicy>,
+    garbling_depth_threshold: Option<u64>,
 ) -> Result<MappedContext> {
     for sub_map in &input_party_map {
         for status in sub_map {
@@ -1010,27 +1310,18 @@ fn compile_to_mpc(
             }
         }
     }
-    for sub_parties in &output_parties {
-        for status in sub_parties {
-            if let IOStatus::Party(id) = *status {
-                if id >= PARTIES as u64 {
-                    return Err(runtime_error!("Output party should have a valid party ID"));
-                }
-            } else {
-                return Err(runtime_error!(
-                    "Output status should be a party id or shared"
-                ));
-            }
-        }
+    for (graph, policy) in context.get_graphs().iter().zip(&output_policies) {
+        policy.check(&graph.get_output_node()?.get_type()?)?;
     }
     let new_context = create_context()?;
     let mut context_map = ContextMappings::default();
     compile_to_mpc_context(
         context.clone(),
         input_party_map,
-        output_parties,
+        output_policies,
         new_context.clone(),
         &mut context_map,
+        garbling_depth_threshold,
     )?;
     let old_main_graph = context.get_main_graph()?;
     let main_graph = context_map.get_graph(old_main_graph);
@@ -1076,6 +1367,7 @@ pub fn uniquify_prf_id(context: Context) -> Result<Context> {
                 new_node.add_annotation(anno)?;
             }
             copy_node_name(node.clone(), new_node.clone())?;
+            copy_node_provenance(node.clone(), new_node.clone())?;
             context_map.insert_node(node, new_node);
         }
         let output_node = graph.get_output_node()?;
@@ -1102,11 +1394,61 @@ pub fn prepare_for_mpc_evaluation(
     inline_config: InlineConfig,
 ) -> Result<Context> {
     let mpc_context = compile_to_mpc(context, input_party_map, output_parties)?.get_context();
+    instantiate_and_inline_mpc_context(mpc_context, inline_config)
+}
+
+fn instantiate_and_inline_mpc_context(
+    mpc_context: Context,
+    inline_config: InlineConfig,
+) -> Result<Context> {
     let instantiated_context = run_instantiation_pass(mpc_context)?.get_context();
     let inlined_context = inline_operations(instantiated_context, inline_config)?;
     uniquify_prf_id(inlined_context)
 }
 
+/// Same as [prepare_for_mpc_evaluation], but the outputs of graphs are processed according to given [policies](OutputPolicy).
+///
+/// Outputs of graphs with [OutputPolicy::Tuple] are tuples whose elements are either revealed values or tuples of shares.
+/// Noise of [OutputPolicy::RevealWithNoise] isn't added by this function, see [add_output_noise].
+pub fn prepare_for_mpc_evaluation_with_output_policy(
+    context: Context,
+    input_party_map: Vec<Vec<IOStatus>>,
+    output_policies: Vec<OutputPolicy>,
+    inline_config: InlineConfig,
+) -> Result<Context> {
+    let mpc_context =
+        compile_to_mpc_with_output_policy(context, input_party_map, output_policies, None)?
+            .get_context();
+    instantiate_and_inline_mpc_context(mpc_context, inline_config)
+}
+
+/// Same as [prepare_for_mpc_evaluation], but connected binary subgraphs with multiplicative depth at least `depth_threshold`
+/// are evaluated as garbled circuits in a constant number of communication rounds.
+///
+/// Parties 0 and 1 garble such subgraphs and party 2 evaluates them.
+/// Garbling reduces the number of rounds of deep binary circuits (e.g., comparisons or [LowMC](crate::mpc::low_mc::LowMC) encryption),
+/// but increases the amount of communication and local computation.
+pub fn prepare_for_mpc_evaluation_with_garbling(
+    context: Context,
+    input_party_map: Vec<Vec<IOStatus>>,
+    output_parties: Vec<Vec<IOStatus>>,
+    inline_config: InlineConfig,
+    depth_threshold: u64,
+) -> Result<Context> {
+    let output_policies = output_parties
+        .iter()
+        .map(|statuses| OutputPolicy::from_statuses(statuses))
+        .collect::<Result<Vec<OutputPolicy>>>()?;
+    let mpc_context = compile_to_mpc_with_output_policy(
+        context,
+        input_party_map,
+        output_policies,
+        Some(depth_threshold),
+    )?
+    .get_context();
+    instantiate_and_inline_mpc_context(mpc_context, inline_config)
+}
+
 fn print_stats(graph: Graph) -> Result<()> {
     let mut cnt = HashMap::<String, u64>::new();
     for node in graph.get_nodes() {
@@ -1132,15 +1474,47 @@ pub fn prepare_context<E>(
 where
     E: Evaluator + Sized,
 {
-    eprintln!("Instantiating...");
-    let context2 = run_instantiation_pass(context)?.get_context();
-    eprintln!("Inlining...");
-    let context3 = inline_operations(context2, inline_config)?;
+    let mut pass_manager = PassManager::new()
+        .add_pass(InstantiationPass)
+        .add_pass(InliningPass {
+            config: inline_config,
+        });
+    let context3 = pass_manager.run(context)?;
+    print_pass_statistics(&pass_manager);
     if print_unoptimized_stats {
         print_stats(context3.get_main_graph()?)?;
     }
-    eprintln!("Optimizing...");
-    optimize_context(context3, evaluator)
+    let mut pass_manager = PassManager::new().add_optimization_passes(evaluator);
+    let optimized_context = pass_manager.run(context3)?;
+    print_pass_statistics(&pass_manager);
+    Ok(optimized_context)
+}
+
+fn print_pass_statistics(pass_manager: &PassManager) {
+    for statistics in pass_manager.get_statistics() {
+        eprintln!("{}", statistics);
+    }
+}
+
+/// Returns an inlining config that inlines all Call and Iterate nodes.
+///
+/// The MPC compiler and the optimizer work only on fully inlined graphs,
+/// so graph calls and bounded iterations left intact by a given config are unrolled in the simple mode.
+fn get_full_inline_config(config: InlineConfig) -> InlineConfig {
+    let resolve = |mode: Option<InlineMode>| match mode.unwrap_or(config.default_mode.clone()) {
+        InlineMode::Noop => InlineMode::Simple,
+        mode => mode,
+    };
+    InlineConfig {
+        override_call_mode: Some(resolve(config.override_call_mode.clone())),
+        override_iterate_mode: Some(resolve(config.override_iterate_mode.clone())),
+        override_graph_modes: config
+            .override_graph_modes
+            .iter()
+            .map(|(id, mode)| (*id, resolve(Some(mode.clone()))))
+            .collect(),
+        default_mode: config.default_mode.clone(),
+    }
 }
 
 /// Takes raw context (no inlining, etc.), and runs the whole pipeline (instantiation+inlining+MPC) on it,
@@ -1156,11 +1530,272 @@ where
     T: Fn() -> Result<E>,
     E: Evaluator + Sized,
 {
-    let evaluator0 = get_evaluator()?;
-    let context4 = prepare_context(context, inline_config.clone(), evaluator0, true)?;
-    print_stats(context4.get_main_graph()?)?;
+    compile_context_with_backend(
+        context,
+        input_parties,
+        output_parties,
+        inline_config,
+        get_evaluator,
+        MpcBackend::ABY3,
+    )
+}
+
+/// Same as [compile_context], but chooses the inlining mode of every iterated graph to fit into a given multiplicative depth (see [inline_operations_with_depth_budget]).
+///
+/// Smaller budgets decrease the number of communication rounds, while larger budgets decrease the size of the compiled graph and the amount of communication.
+/// The returned report contains the multiplicative depth of the inlined context before MPC compilation and the inlining configuration resulting in it,
+/// which can be passed to [compile_context] to compile the context in the same way.
+pub fn compile_context_with_depth_budget<T, E>(
+    context: Context,
+    input_parties: Vec<IOStatus>,
+    output_parties: Vec<IOStatus>,
+    max_depth: u64,
+    get_evaluator: T,
+) -> Result<(Context, InlineReport)>
+where
+    T: Fn() -> Result<E>,
+    E: Evaluator + Sized,
+{
+    // Instantiation is deterministic, so graph IDs of the report match those of the context instantiated by compile_context
+    let instantiated_context = run_instantiation_pass(context.clone())?.get_context();
+    let (_, report) = inline_operations_with_depth_budget(instantiated_context, max_depth)?;
+    let compiled_context = compile_context(
+        context,
+        input_parties,
+        output_parties,
+        report.config.clone(),
+        get_evaluator,
+    )?;
+    Ok((compiled_context, report))
+}
+
+/// Same as [compile_context], but compiles the context into a given MPC protocol.
+///
+/// Contexts compiled into ABY3 are checked by [verify_privacy] before optimization,
+/// so compilation fails if an intermediate value would be revealed to a party that is not an output party.
+pub fn compile_context_with_backend<T, E>(
+    context: Context,
+    input_parties: Vec<IOStatus>,
+    output_parties: Vec<IOStatus>,
+    inline_config: InlineConfig,
+    get_evaluator: T,
+    backend: MpcBackend,
+) -> Result<Context>
+where
+    T: Fn() -> Result<E>,
+    E: Evaluator + Sized,
+{
+    let inline_config = get_full_inline_config(inline_config);
+    eprintln!("input_parties = {:?}", input_parties);
+    eprintln!("output_parties = {:?}", output_parties);
+    let mpc_pass = MpcCompilationPass {
+        input_parties,
+        output_parties,
+        inline_config: inline_config.clone(),
+        backend,
+    };
+    compile_context_with_mpc_pass(context, inline_config, get_evaluator, mpc_pass)
+}
+
+// Prepares a context for MPC compilation (instantiation, inlining and optimization),
+// applies a given MPC compilation pass and optimizes the compiled context.
+fn compile_context_with_mpc_pass<T, E, P>(
+    context: Context,
+    inline_config: InlineConfig,
+    get_evaluator: T,
+    mpc_pass: P,
+) -> Result<Context>
+where
+    T: Fn() -> Result<E>,
+    E: Evaluator + Sized,
+    P: Pass,
+{
+    let prepared_context = prepare_context(context, inline_config, get_evaluator()?, true)?;
+    print_stats(prepared_context.get_main_graph()?)?;
+    let mut pass_manager = PassManager::new()
+        .add_pass(mpc_pass)
+        .add_optimization_passes(get_evaluator()?);
+    let compiled_context = pass_manager.run(prepared_context)?;
+    print_pass_statistics(&pass_manager);
+    print_stats(compiled_context.get_main_graph()?)?;
+    Ok(compiled_context)
+}
+
+/// Pass compiling a fully inlined context into a given MPC protocol (see [compile_context_with_backend]).
+///
+/// Contexts compiled into ABY3 are checked by [verify_privacy].
+pub struct MpcCompilationPass {
+    /// Parties providing the inputs of the main graph
+    pub input_parties: Vec<IOStatus>,
+    /// Parties receiving the output of the main graph
+    pub output_parties: Vec<IOStatus>,
+    /// Inlining configuration used for the graphs created during compilation
+    pub inline_config: InlineConfig,
+    /// Target MPC protocol
+    pub backend: MpcBackend,
+}
+
+impl Pass for MpcCompilationPass {
+    fn get_name(&self) -> String {
+        format!("MpcCompilation({:?})", self.backend)
+    }
+
+    fn run(&mut self, context: Context) -> Result<Context> {
+        check_number_of_inputs(&context, &self.input_parties)?;
+        let input_parties = self.input_parties.clone();
+        let output_parties = self.output_parties.clone();
+        match self.backend {
+            MpcBackend::ABY3 => {
+                let compiled_context = prepare_for_mpc_evaluation(
+                    context,
+                    vec![input_parties.clone()],
+                    vec![output_parties.clone()],
+                    get_full_inline_config(self.inline_config.clone()),
+                )?;
+                verify_privacy(compiled_context.clone(), input_parties, output_parties)?;
+                Ok(compiled_context)
+            }
+            MpcBackend::TwoParty => compile_to_two_party(context, input_parties, output_parties),
+            MpcBackend::Spdz => compile_to_spdz(context, input_parties, output_parties),
+            MpcBackend::Shamir { parties, threshold } => {
+                compile_to_shamir(context, input_parties, output_parties, parties, threshold)
+            }
+        }
+    }
+}
+
+/// Pass compiling a fully inlined context into ABY3 with the output processed according to a given [policy](OutputPolicy)
+/// (see [compile_context_with_output_policy]).
+///
+/// The compiled context is checked by [verify_privacy_with_output_policy].
+pub struct MpcCompilationWithOutputPolicyPass {
+    /// Parties providing the inputs of the main graph
+    pub input_parties: Vec<IOStatus>,
+    /// Policy of the output of the main graph
+    pub output_policy: OutputPolicy,
+    /// Inlining configuration used for the graphs created during compilation
+    pub inline_config: InlineConfig,
+}
+
+impl Pass for MpcCompilationWithOutputPolicyPass {
+    fn get_name(&self) -> String {
+        "MpcCompilationWithOutputPolicy".to_owned()
+    }
+
+    fn run(&mut self, context: Context) -> Result<Context> {
+        check_number_of_inputs(&context, &self.input_parties)?;
+        let compiled_context = prepare_for_mpc_evaluation_with_output_policy(
+            context,
+            vec![self.input_parties.clone()],
+            vec![self.output_policy.clone()],
+            get_full_inline_config(self.inline_config.clone()),
+        )?;
+        verify_privacy_with_output_policy(
+            compiled_context.clone(),
+            self.input_parties.clone(),
+            self.output_policy.clone(),
+        )?;
+        Ok(compiled_context)
+    }
+}
+
+/// Pass compiling a fully inlined context into ABY3, in which binary subgraphs with multiplicative depth at least `depth_threshold` are garbled
+/// (see [compile_context_with_garbling]).
+///
+/// The compiled context is checked by [verify_privacy].
+pub struct GarbledMpcCompilationPass {
+    /// Parties providing the inputs of the main graph
+    pub input_parties: Vec<IOStatus>,
+    /// Parties receiving the output of the main graph
+    pub output_parties: Vec<IOStatus>,
+    /// Inlining configuration used for the graphs created during compilation
+    pub inline_config: InlineConfig,
+    /// Minimal multiplicative depth of garbled binary subgraphs
+    pub depth_threshold: u64,
+}
+
+impl Pass for GarbledMpcCompilationPass {
+    fn get_name(&self) -> String {
+        format!("GarbledMpcCompilation({})", self.depth_threshold)
+    }
+
+    fn run(&mut self, context: Context) -> Result<Context> {
+        check_number_of_inputs(&context, &self.input_parties)?;
+        let compiled_context = prepare_for_mpc_evaluation_with_garbling(
+            context,
+            vec![self.input_parties.clone()],
+            vec![self.output_parties.clone()],
+            get_full_inline_config(self.inline_config.clone()),
+            self.depth_threshold,
+        )?;
+        verify_privacy(
+            compiled_context.clone(),
+            self.input_parties.clone(),
+            self.output_parties.clone(),
+        )?;
+        Ok(compiled_context)
+    }
+}
+
+/// Same as [compile_context], but the output is processed according to a given [policy](OutputPolicy) instead of a flat vector of output statuses.
+///
+/// Noise required by [OutputPolicy::RevealWithNoise] is added before compilation (see [add_output_noise]),
+/// and the compiled context is checked by [verify_privacy_with_output_policy] before optimization.
+/// Only the ABY3 backend is supported.
+pub fn compile_context_with_output_policy<T, E>(
+    context: Context,
+    input_parties: Vec<IOStatus>,
+    output_policy: OutputPolicy,
+    inline_config: InlineConfig,
+    get_evaluator: T,
+) -> Result<Context>
+where
+    T: Fn() -> Result<E>,
+    E: Evaluator + Sized,
+{
+    let noisy_context = add_output_noise(context, &output_policy)?;
+    let inline_config = get_full_inline_config(inline_config);
+    eprintln!("input_parties = {:?}", input_parties);
+    eprintln!("output_policy = {:?}", output_policy);
+    let mpc_pass = MpcCompilationWithOutputPolicyPass {
+        input_parties,
+        output_policy,
+        inline_config: inline_config.clone(),
+    };
+    compile_context_with_mpc_pass(noisy_context, inline_config, get_evaluator, mpc_pass)
+}
+
+/// Same as [compile_context], but binary subgraphs with multiplicative depth at least `depth_threshold` are garbled
+/// (see [prepare_for_mpc_evaluation_with_garbling]).
+///
+/// The compiled context is checked by [verify_privacy] before optimization.
+pub fn compile_context_with_garbling<T, E>(
+    context: Context,
+    input_parties: Vec<IOStatus>,
+    output_parties: Vec<IOStatus>,
+    inline_config: InlineConfig,
+    depth_threshold: u64,
+    get_evaluator: T,
+) -> Result<Context>
+where
+    T: Fn() -> Result<E>,
+    E: Evaluator + Sized,
+{
+    let inline_config = get_full_inline_config(inline_config);
+    eprintln!("input_parties = {:?}", input_parties);
+    eprintln!("output_parties = {:?}", output_parties);
+    let mpc_pass = GarbledMpcCompilationPass {
+        input_parties,
+        output_parties,
+        inline_config: inline_config.clone(),
+        depth_threshold,
+    };
+    compile_context_with_mpc_pass(context, inline_config, get_evaluator, mpc_pass)
+}
+
+fn check_number_of_inputs(context: &Context, input_parties: &[IOStatus]) -> Result<()> {
     let mut number_of_inputs = 0;
-    for node in context4.get_main_graph()?.get_nodes() {
+    for node in context.get_main_graph()?.get_nodes() {
         if let Operation::Input(_) = node.get_operation() {
             number_of_inputs += 1;
         }
@@ -1172,20 +1807,7 @@ where
             input_parties.len()
         ));
     }
-    eprintln!("input_parties = {:?}", input_parties);
-    eprintln!("output_parties = {:?}", output_parties);
-    let compiled_context0 = prepare_for_mpc_evaluation(
-        context4,
-        vec![input_parties],
-        vec![output_parties],
-        inline_config,
-    )?;
-    print_stats(compiled_context0.get_main_graph()?)?;
-
-    let evaluator1 = get_evaluator()?;
-    let compiled_context = optimize_context(compiled_context0, evaluator1)?;
-    print_stats(compiled_context.get_main_graph()?)?;
-    Ok(compiled_context)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -1198,9 +1820,11 @@ mod tests {
     };
     use crate::data_values::Value;
     use crate::evaluators::random_evaluate;
-    use crate::evaluators::simple_evaluator::evaluate_add_subtract_multiply;
+    use crate::evaluators::simple_evaluator::{evaluate_add_subtract_multiply, SimpleEvaluator};
     use crate::graphs::SliceElement::{Ellipsis, SubArray};
     use crate::inline::inline_ops::{inline_operations, InlineConfig, InlineMode};
+    use crate::mpc::cost::estimate;
+    use crate::ops::comparisons::LessThan;
     use crate::random::PRNG;
 
     use std::collections::HashMap;
@@ -1619,6 +2243,39 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn test_split() {
+        test_helper_one_input(
+            array_type(vec![10, 128], INT32),
+            Operation::Split(1, vec![100, 28]),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_broadcast_to() {
+        test_helper_one_input(
+            array_type(vec![10, 1], INT32),
+            Operation::BroadcastTo(vec![3, 10, 4]),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_tile() {
+        test_helper_one_input(
+            array_type(vec![10, 4], INT32),
+            Operation::Tile(vec![3, 1, 2]),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_cumsum() {
+        test_helper_one_input(array_type(vec![10, 4], INT32), Operation::CumSum(0)).unwrap();
+        test_helper_one_input(array_type(vec![10, 4], UINT8), Operation::CumSum(1)).unwrap();
+    }
+
     #[test]
     fn test_reshape() {
         test_helper_one_input(
@@ -1887,6 +2544,100 @@ mod tests {
         test_helper_create_ops(vec![t.clone(); 3], Operation::Stack(vec![3])).unwrap();
     }
 
+    #[test]
+    fn test_concatenate() {
+        let t = array_type(vec![10, 128], INT32);
+        test_helper_create_ops(
+            vec![t.clone(), array_type(vec![5, 128], INT32), t],
+            Operation::Concatenate(0),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_scatter() {
+        let helper = |input_party_map: Vec<IOStatus>,
+                      output_parties: Vec<IOStatus>|
+         -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let input_types = vec![
+                array_type(vec![2, 5], INT32),
+                array_type(vec![3], UINT64),
+                array_type(vec![2, 3], INT32),
+            ];
+            let mut input_nodes = vec![];
+            for t in &input_types {
+                input_nodes.push(g.input(t.clone())?);
+            }
+            let o = input_nodes[0].scatter(input_nodes[1].clone(), input_nodes[2].clone(), 1)?;
+            let output_type = o.get_type()?;
+            g.set_output_node(o)?;
+            g.finalize()?;
+            c.set_main_graph(g.clone())?;
+            c.finalize()?;
+
+            let inline_config = InlineConfig {
+                default_mode: InlineMode::Simple,
+                ..Default::default()
+            };
+            let mpc_c = prepare_for_mpc_evaluation(
+                c.clone(),
+                vec![input_party_map.clone()],
+                vec![output_parties.clone()],
+                inline_config,
+            )?;
+
+            let plain_inputs = vec![
+                Value::from_flattened_array(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10], INT32)?,
+                Value::from_flattened_array(&[4, 0, 2], UINT64)?,
+                Value::from_flattened_array(&[-1, -2, -3, -4, -5, -6], INT32)?,
+            ];
+            let mut mpc_inputs = vec![];
+            for i in 0..input_types.len() {
+                mpc_inputs.push(prepare_value(
+                    plain_inputs[i].clone(),
+                    input_types[i].clone(),
+                    input_party_map[i] == IOStatus::Shared,
+                )?);
+            }
+            check_output(
+                g,
+                mpc_c.get_main_graph()?,
+                plain_inputs,
+                mpc_inputs,
+                output_parties,
+                output_type,
+            )
+        };
+        || -> Result<()> {
+            // Public indices
+            helper(
+                vec![IOStatus::Party(0), IOStatus::Public, IOStatus::Party(1)],
+                vec![IOStatus::Party(0)],
+            )?;
+            helper(
+                vec![IOStatus::Public, IOStatus::Public, IOStatus::Party(2)],
+                vec![],
+            )?;
+            // Private indices
+            helper(
+                vec![IOStatus::Party(0), IOStatus::Party(1), IOStatus::Party(2)],
+                vec![IOStatus::Party(0)],
+            )?;
+            helper(
+                vec![IOStatus::Public, IOStatus::Party(2), IOStatus::Public],
+                vec![IOStatus::Party(1)],
+            )?;
+            helper(
+                vec![IOStatus::Shared, IOStatus::Shared, IOStatus::Shared],
+                vec![],
+            )?;
+            Ok(())
+        }()
+        .unwrap();
+    }
+
     // Checks that every PRF node of a context has a unique input.
     fn check_prf_id(context: Context) -> Result<()> {
         let mut iv_node_map: HashMap<u64, Node> = HashMap::new();
@@ -1954,4 +2705,230 @@ mod tests {
         }()
         .unwrap()
     }
+
+    #[test]
+    fn test_bounded_iteration() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let t = scalar_type(INT32);
+            // One step of an affine recurrence w <- w * x + x; the state is (w, x)
+            let step = c.create_graph()?;
+            {
+                let state = step.input(tuple_type(vec![t.clone(), t.clone()]))?;
+                step.input(scalar_type(UINT64))?;
+                let w = state.tuple_get(0)?;
+                let x = state.tuple_get(1)?;
+                let new_w = w.multiply(x.clone())?.add(x.clone())?;
+                let new_state = step.create_tuple(vec![new_w, x])?;
+                let empty = step.create_tuple(vec![])?;
+                step.create_tuple(vec![new_state, empty])?.set_as_output()?;
+                step.finalize()?;
+            }
+            let g = c.create_graph()?;
+            let w = g.input(t.clone())?;
+            let x = g.input(t)?;
+            let state = g.create_tuple(vec![w, x])?;
+            g.iterate_fixed(step, state, 3)?
+                .tuple_get(0)?
+                .tuple_get(0)?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let inputs = vec![
+                Value::from_scalar(2, INT32)?,
+                Value::from_scalar(-3, INT32)?,
+            ];
+            let expected = random_evaluate(g, inputs.clone())?;
+            assert_eq!(expected.to_i32(INT32)?, -75);
+            // Graph calls and iterations are unrolled even if the config doesn't inline them
+            let compiled = compile_context(
+                c,
+                vec![IOStatus::Party(0), IOStatus::Party(1)],
+                vec![IOStatus::Party(2)],
+                InlineConfig::default(),
+                || SimpleEvaluator::new(None),
+            )?;
+            let result = random_evaluate(compiled.get_main_graph()?, inputs)?;
+            assert_eq!(result, expected);
+            Ok(())
+        }()
+        .unwrap()
+    }
+    #[test]
+    fn test_oblivious_if() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let t = tuple_type(vec![scalar_type(INT32), scalar_type(BIT)]);
+            let then_graph = c.create_graph()?;
+            {
+                let x = then_graph.input(scalar_type(INT32))?;
+                let b = then_graph.input(scalar_type(BIT))?;
+                then_graph
+                    .create_tuple(vec![x.multiply(x.clone())?, b])?
+                    .set_as_output()?;
+                then_graph.finalize()?;
+            }
+            let else_graph = c.create_graph()?;
+            {
+                let x = else_graph.input(scalar_type(INT32))?;
+                let b = else_graph.input(scalar_type(BIT))?;
+                let one = else_graph.constant(scalar_type(BIT), Value::from_scalar(1, BIT)?)?;
+                else_graph
+                    .create_tuple(vec![x.add(x.clone())?, b.add(one)?])?
+                    .set_as_output()?;
+                else_graph.finalize()?;
+            }
+            let g = c.create_graph()?;
+            let flag = g.input(scalar_type(BIT))?;
+            let x = g.input(scalar_type(INT32))?;
+            let b = g.input(scalar_type(BIT))?;
+            let result = g.if_else(flag, then_graph, else_graph, vec![x, b])?;
+            assert_eq!(result.get_type()?, t);
+            result.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let compiled = compile_context(
+                c.clone(),
+                vec![IOStatus::Party(0), IOStatus::Party(1), IOStatus::Party(1)],
+                vec![IOStatus::Party(2)],
+                InlineConfig::default(),
+                || SimpleEvaluator::new(None),
+            )?;
+            for flag in [0, 1] {
+                let inputs = vec![
+                    Value::from_scalar(flag, BIT)?,
+                    Value::from_scalar(-7, INT32)?,
+                    Value::from_scalar(1, BIT)?,
+                ];
+                let expected = random_evaluate(g.clone(), inputs.clone())?;
+                let result = random_evaluate(compiled.get_main_graph()?, inputs)?;
+                assert_eq!(result, expected);
+            }
+            Ok(())
+        }()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_depth_budget() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![4, 32], BIT);
+            let a = g.input(t.clone())?;
+            let b = g.input(t)?;
+            g.custom_op(
+                CustomOperation::new(LessThan {
+                    signed_comparison: false,
+                }),
+                vec![a, b],
+            )?
+            .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let compile = |max_depth: u64| {
+                compile_context_with_depth_budget(
+                    c.clone(),
+                    vec![IOStatus::Party(0), IOStatus::Party(1)],
+                    vec![IOStatus::Party(2)],
+                    max_depth,
+                    || SimpleEvaluator::new(None),
+                )
+            };
+            let (simple_c, simple_report) = compile(1000)?;
+            assert!(simple_report
+                .config
+                .override_graph_modes
+                .values()
+                .all(|mode| *mode == InlineMode::Simple));
+            let (optimized_c, optimized_report) = compile(0)?;
+            assert!(optimized_report.multiplicative_depth < simple_report.multiplicative_depth);
+            assert!(estimate(optimized_c.clone())?.rounds < estimate(simple_c.clone())?.rounds);
+
+            let a = [0, 5, 7, u32::MAX as u64];
+            let b = [1, 5, 6, 0];
+            let to_bits = |values: &[u64]| -> Result<Value> {
+                Value::from_flattened_array(
+                    &values
+                        .iter()
+                        .flat_map(|x| (0..32).map(move |i| (x >> i) & 1))
+                        .collect::<Vec<u64>>(),
+                    BIT,
+                )
+            };
+            let inputs = vec![to_bits(&a)?, to_bits(&b)?];
+            for compiled in [simple_c, optimized_c] {
+                let result = random_evaluate(compiled.get_main_graph()?, inputs.clone())?;
+                assert_eq!(
+                    result.to_flattened_array_u64(array_type(vec![4], BIT))?,
+                    vec![1, 0, 0, 0]
+                );
+            }
+            Ok(())
+        }()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compile_context_with_output_policy() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![100], INT64);
+            let x = g.input(t.clone())?;
+            let y = g.input(t.clone())?;
+            g.create_tuple(vec![x.add(y.clone())?, x.multiply(y)?])?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let compiled_c = compile_context_with_output_policy(
+                c,
+                vec![IOStatus::Party(0), IOStatus::Party(1)],
+                OutputPolicy::Tuple(vec![
+                    OutputPolicy::RevealWithNoise {
+                        parties: vec![0, 1],
+                        noise_bits: 8,
+                    },
+                    OutputPolicy::Shared,
+                ]),
+                InlineConfig::default(),
+                || SimpleEvaluator::new(None),
+            )?;
+            let x_value: Vec<i64> = (0..100).map(|i| i * 7 - 300).collect();
+            let y_value: Vec<i64> = (0..100).map(|i| 50 - i).collect();
+            let result = random_evaluate(
+                compiled_c.get_main_graph()?,
+                vec![
+                    Value::from_flattened_array(&x_value, INT64)?,
+                    Value::from_flattened_array(&y_value, INT64)?,
+                ],
+            )?
+            .to_vector()?;
+            // The sum is revealed with noise between -4 and 4
+            let noisy_sum = result[0].to_flattened_array_i64(t.clone())?;
+            let mut noise = vec![];
+            for i in 0..100 {
+                noise.push(noisy_sum[i] - x_value[i] - y_value[i]);
+            }
+            assert!(noise.iter().all(|e| e.abs() <= 4));
+            assert!(noise.iter().any(|e| *e != 0));
+            // The product stays shared
+            let shares = result[1].to_vector()?;
+            assert_eq!(shares.len(), PARTIES);
+            let mut product = vec![0i64; 100];
+            for share in shares {
+                for (p, e) in product
+                    .iter_mut()
+                    .zip(share.to_flattened_array_i64(t.clone())?)
+                {
+                    *p = p.wrapping_add(e);
+                }
+            }
+            for i in 0..100 {
+                assert_eq!(product[i], x_value[i] * y_value[i]);
+            }
+            Ok(())
+        }()
+        .unwrap()
+    }
 }
@@ -1,7 +1,7 @@
 use crate::custom_ops::{
     run_instantiation_pass, ContextMappings, CustomOperation, CustomOperationBody,
 };
-use crate::data_types::{array_type, scalar_type, tuple_type, ScalarType, Type, BIT};
+use crate::data_types::{array_type, scalar_type, tuple_type, vector_type, ScalarType, Type, BIT};
 use crate::data_values::Value;
 use crate::errors::Result;
 use crate::graphs::SliceElement::{Ellipsis, SingleIndex};
@@ -12,7 +12,7 @@ use crate::inline::inline_ops::{
 use crate::mpc::mpc_arithmetic::{AddMPC, MultiplyMPC};
 use crate::mpc::mpc_compiler::{check_private_tuple, compile_to_mpc_graph, PARTIES};
 use crate::ops::adder::BinaryAdd;
-use crate::ops::utils::put_in_bits;
+use crate::ops::utils::{pull_out_bits, put_in_bits};
 use crate::type_inference::a2b_type_inference;
 
 use serde::{Deserialize, Serialize};
@@ -133,6 +133,8 @@ impl CustomOperationBody for A2BMPC {
     }
 }
 
+register_custom_operation!(A2BMPC, [Shared], [Shared, PrfKeys]);
+
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub(super) struct B2AMPC {
     pub st: ScalarType,
@@ -313,6 +315,117 @@ impl CustomOperationBody for B2AMPC {
     }
 }
 
+register_custom_operation!(B2AMPC, [Shared], [Shared, PrfKeys, Any]);
+
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub(super) struct ConvertRingMPC {
+    pub st: ScalarType,
+}
+
+/// ConvertRing MPC operation for public and private data with the following arguments:
+/// 1. data to be converted to another ring (public values or private shares);
+/// 2. PRF keys for MPC multiplication (only when private data is converted to a bigger ring);
+/// 3. special PRF keys for B2A (only when private data is converted to a bigger ring).
+///
+/// Conversion of private data to a ring of the same or smaller size is local: the sum of shares modulo a smaller power of two is equal to the input modulo the same power.
+/// Conversion to a bigger ring can't be done share-wise since the sum of lifted shares overflows the original modulus up to two times.
+/// Thus, the input is converted to binary shares via [A2BMPC], the bits are extended by copies of the sign bit or zeros and then converted back via [B2AMPC].
+#[typetag::serde]
+impl CustomOperationBody for ConvertRingMPC {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 1 && argument_types.len() != 3 {
+            // Panics since:
+            // - the user has no direct access to this function.
+            // - the MPC compiler should pass the correct number of arguments
+            // and this panic should never happen.
+            panic!("ConvertRingMPC should have either 1 or 3 inputs.");
+        }
+        let t = argument_types[0].clone();
+        if let Type::Array(_, _) | Type::Scalar(_) = t {
+            let g = context.create_graph()?;
+            let input = g.input(t)?;
+            g.convert_ring(input, self.st.clone())?.set_as_output()?;
+            g.finalize()?;
+            return Ok(g);
+        }
+        let input_t = if let Type::Tuple(v) = t.clone() {
+            check_private_tuple(v.clone())?;
+            (*v[0]).clone()
+        } else {
+            panic!("ConvertRingMPC should have a private tuple or a public value as input");
+        };
+        let input_st = input_t.get_scalar_type();
+        let input_size = input_st.size_in_bits();
+        let output_size = self.st.size_in_bits();
+
+        if argument_types.len() == 1 {
+            if output_size > input_size {
+                return Err(runtime_error!(
+                    "ConvertRingMPC needs PRF keys to convert private data to a bigger ring"
+                ));
+            }
+            // Every party converts its shares locally
+            let g = context.create_graph()?;
+            let input = g.input(t)?;
+            let mut output_shares = vec![];
+            for i in 0..PARTIES as u64 {
+                output_shares.push(input.tuple_get(i)?.convert_ring(self.st.clone())?);
+            }
+            g.create_tuple(output_shares)?.set_as_output()?;
+            g.finalize()?;
+            return Ok(g);
+        }
+
+        let g = context.create_graph()?;
+        let input = g.input(t)?;
+        let prf_for_mul_keys = g.input(argument_types[1].clone())?;
+        let prf_for_b2a_keys = g.input(argument_types[2].clone())?;
+
+        let bits = g.custom_op(
+            CustomOperation::new(A2BMPC {}),
+            vec![input, prf_for_mul_keys.clone()],
+        )?;
+        // Bit extension is linear, so it can be applied to every binary share separately.
+        // The sign bit of the input is the XOR of the most significant bits of the shares.
+        let mut extended_shares = vec![];
+        for i in 0..PARTIES as u64 {
+            let share_bits = pull_out_bits(bits.tuple_get(i)?)?;
+            let bit_row_t = share_bits.get(vec![0])?.get_type()?;
+            let num_extra_bits = output_size - input_size;
+            let extra_bits = if input_st.get_signed() {
+                share_bits
+                    .get(vec![input_size - 1])?
+                    .repeat(num_extra_bits)?
+            } else {
+                let zero_row =
+                    g.constant(bit_row_t.clone(), Value::zero_of_type(bit_row_t.clone()))?;
+                zero_row.repeat(num_extra_bits)?
+            };
+            let extended_bits = g
+                .create_tuple(vec![share_bits.array_to_vector()?, extra_bits])?
+                .reshape(vector_type(output_size, bit_row_t))?
+                .vector_to_array()?;
+            extended_shares.push(put_in_bits(extended_bits)?);
+        }
+        let extended = g.create_tuple(extended_shares)?;
+        g.custom_op(
+            CustomOperation::new(B2AMPC {
+                st: self.st.clone(),
+            }),
+            vec![extended, prf_for_mul_keys, prf_for_b2a_keys],
+        )?
+        .set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!("ConvertRingMPC({})", self.st)
+    }
+}
+
+register_custom_operation!(ConvertRingMPC, [Shared], [Shared, PrfKeys, Any]);
+
 fn get_left_shift_graph(context: Context, bits_t: Type) -> Result<Graph> {
     let shift_g = context.create_graph()?;
     {
@@ -377,8 +490,13 @@ fn get_binary_adder_graph(context: Context, bits_t: Type) -> Result<Graph> {
 
     // Compile adder to MPC
     let adder_g_inlined = inlined_adder_context.get_main_graph()?;
-    let adder_mpc_g =
-        compile_to_mpc_graph(adder_g_inlined, vec![true, true], context, &mut context_map)?;
+    let adder_mpc_g = compile_to_mpc_graph(
+        adder_g_inlined,
+        vec![true, true],
+        context,
+        &mut context_map,
+        None,
+    )?;
     Ok(adder_mpc_g)
 }
 
@@ -428,7 +546,7 @@ fn add_3_bitstrings(
 mod tests {
     use super::*;
     use crate::bytes::subtract_vectors_u64;
-    use crate::data_types::{array_type, ScalarType, INT32, UINT32};
+    use crate::data_types::{array_type, ScalarType, INT16, INT32, INT64, INT8, UINT32};
     use crate::data_values::Value;
     use crate::evaluators::random_evaluate;
     use crate::graphs::{create_context, Operation};
@@ -691,4 +809,94 @@ mod tests {
         conversion_test(Operation::B2A(UINT32), UINT32).unwrap();
         conversion_test(Operation::B2A(INT32), INT32).unwrap();
     }
+
+    fn convert_ring_test(
+        input: Vec<i64>,
+        input_st: ScalarType,
+        st: ScalarType,
+        input_status: IOStatus,
+        expected: Vec<i64>,
+    ) -> Result<()> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let t = array_type(vec![input.len() as u64], input_st.clone());
+        g.input(t.clone())?
+            .convert_ring(st.clone())?
+            .set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        let mpc_context = prepare_for_mpc_evaluation(
+            c,
+            vec![vec![input_status]],
+            vec![vec![
+                IOStatus::Party(0),
+                IOStatus::Party(1),
+                IOStatus::Party(2),
+            ]],
+            InlineConfig {
+                default_mode: InlineMode::Simple,
+                ..Default::default()
+            },
+        )?;
+        let output = random_evaluate(
+            mpc_context.get_main_graph()?,
+            vec![Value::from_flattened_array(&input, input_st)?],
+        )?;
+        // Compare entries in the two's complement form
+        let mask = u64::MAX >> (64 - st.size_in_bits());
+        let expected: Vec<u64> = expected.iter().map(|x| (*x as u64) & mask).collect();
+        assert_eq!(
+            output.to_flattened_array_u64(array_type(vec![expected.len() as u64], st))?,
+            expected
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_ring_mpc() {
+        || -> Result<()> {
+            let input = vec![-1, 0, 5, -70000, 1 << 40];
+            for status in [IOStatus::Party(2), IOStatus::Public] {
+                // Downcasting
+                convert_ring_test(
+                    input.clone(),
+                    INT64,
+                    INT16,
+                    status.clone(),
+                    vec![-1, 0, 5, -4464, 0],
+                )?;
+                convert_ring_test(
+                    input.clone(),
+                    INT64,
+                    UINT32,
+                    status.clone(),
+                    vec![u32::MAX as i64, 0, 5, (-70000i32) as u32 as i64, 0],
+                )?;
+                // Upcasting
+                convert_ring_test(
+                    vec![-1, 0, 5, -70000, i32::MAX as i64],
+                    INT32,
+                    INT64,
+                    status.clone(),
+                    vec![-1, 0, 5, -70000, i32::MAX as i64],
+                )?;
+                convert_ring_test(
+                    vec![-1, 0, 5, -128],
+                    INT8,
+                    UINT32,
+                    status.clone(),
+                    vec![u32::MAX as i64, 0, 5, u32::MAX as i64 - 127],
+                )?;
+                convert_ring_test(
+                    vec![u32::MAX as i64, 0, 5, 1 << 31],
+                    UINT32,
+                    INT64,
+                    status.clone(),
+                    vec![u32::MAX as i64, 0, 5, 1 << 31],
+                )?;
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
 }
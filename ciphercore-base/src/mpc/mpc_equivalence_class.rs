@@ -1,8 +1,9 @@
 use crate::data_types::{get_types_vector, Type};
 use crate::errors::Result;
-use crate::graphs::{Context, Node, NodeAnnotation, Operation};
-use crate::mpc::mpc_compiler::IOStatus;
-use std::collections::HashMap;
+use crate::graphs::{Context, Graph, Node, NodeAnnotation, Operation};
+use crate::mpc::mpc_compiler::{IOStatus, PARTIES};
+use crate::mpc::output_policy::OutputPolicy;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 //The Vec<Vec<u64>> represents which party(ies) holds the same value/share,
@@ -204,231 +205,276 @@ pub(super) fn generate_equivalence_class(
 ) -> Result<HashMap<(u64, u64), EquivalenceClasses>> {
     let mut equivalence_classes: HashMap<(u64, u64), EquivalenceClasses> = HashMap::new();
     let mut input_count = 0;
-    let graphs = context.get_graphs();
-    for graph in graphs {
-        let nodes = graph.get_nodes();
-        for node in nodes {
-            let dependencies = node.get_node_dependencies();
-            let mut dependencies_class = vec![];
-            for dependence_node in &dependencies {
-                let node_id = dependence_node.get_global_id();
-                let op = dependence_node.get_operation();
-                dependencies_class.push(
-                    equivalence_classes
-                        .get(&node_id)
-                        .unwrap_or_else(|| {
-                            panic!(
-                                "{} node {:?} wasn't added to equivalence classes",
-                                op, node_id
-                            )
-                        })
-                        .clone(),
-                );
+    for graph in context.get_graphs() {
+        add_graph_equivalence_classes(
+            graph,
+            &input_party_map[0],
+            &mut input_count,
+            &mut equivalence_classes,
+        )?;
+    }
+    Ok(equivalence_classes)
+}
+
+// Generates equivalence classes for all nodes of a given graph.
+// Statuses of its inputs are taken from `input_statuses` starting from the position `input_count`, which is updated accordingly.
+fn add_graph_equivalence_classes(
+    graph: Graph,
+    input_statuses: &[IOStatus],
+    input_count: &mut usize,
+    equivalence_classes: &mut HashMap<(u64, u64), EquivalenceClasses>,
+) -> Result<()> {
+    for node in graph.get_nodes() {
+        let dependencies = node.get_node_dependencies();
+        let mut dependencies_class = vec![];
+        for dependence_node in &dependencies {
+            let node_id = dependence_node.get_global_id();
+            let op = dependence_node.get_operation();
+            dependencies_class.push(
+                equivalence_classes
+                    .get(&node_id)
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "{} node {:?} wasn't added to equivalence classes",
+                            op, node_id
+                        )
+                    })
+                    .clone(),
+            );
+        }
+        let result_class = match node.get_operation() {
+            Operation::Input(input_type) => {
+                let result_class = get_input_class(input_type, &input_statuses[*input_count])?;
+                *input_count += 1;
+                result_class
             }
-            let result_class = match node.get_operation() {
-                Operation::Input(input_type) => {
-                    let result_class =
-                        get_input_class(input_type, &input_party_map[0][input_count])?;
-                    input_count += 1;
-                    result_class
-                }
-                Operation::CreateTuple
-                | Operation::CreateNamedTuple(_)
-                | Operation::CreateVector(_) => vector_class(dependencies_class),
-
-                Operation::TupleGet(field_id) => {
-                    let input_class = dependencies_class[0].clone();
-                    if !input_class.is_vector() {
-                        panic!("TupleGet input class should be Vector")
-                    }
-                    (*input_class.get_class_vector()[field_id as usize]).clone()
+            Operation::CreateTuple
+            | Operation::CreateNamedTuple(_)
+            | Operation::CreateVector(_) => vector_class(dependencies_class),
+
+            Operation::TupleGet(field_id) => {
+                let input_class = dependencies_class[0].clone();
+                if !input_class.is_vector() {
+                    panic!("TupleGet input class should be Vector")
                 }
+                (*input_class.get_class_vector()[field_id as usize]).clone()
+            }
 
-                Operation::NamedTupleGet(ref field_name) => {
-                    let tuple_type = dependencies[0].get_type()?;
-                    let mut field_id: Option<u64> = None;
-                    if let Type::NamedTuple(ref v) = tuple_type {
-                        for (id, (current_field_name, _)) in v.iter().enumerate() {
-                            if current_field_name.eq(field_name) {
-                                field_id = Some(id as u64);
-                                break;
-                            }
+            Operation::NamedTupleGet(ref field_name) => {
+                let tuple_type = dependencies[0].get_type()?;
+                let mut field_id: Option<u64> = None;
+                if let Type::NamedTuple(ref v) = tuple_type {
+                    for (id, (current_field_name, _)) in v.iter().enumerate() {
+                        if current_field_name.eq(field_name) {
+                            field_id = Some(id as u64);
+                            break;
                         }
                     }
-                    let field_id_raw = field_id.unwrap();
-                    let input_class = dependencies_class[0].clone();
-                    if !input_class.is_vector() {
-                        panic!("NamedTupleGet input class should be Vector")
-                    }
-                    (*input_class.get_class_vector()[field_id_raw as usize]).clone()
                 }
-
-                Operation::Random(t) => recursive_class_filler(
-                    t,
-                    EquivalenceClasses::Atomic(vec![vec![0], vec![1], vec![2]]),
-                )?,
-
-                Operation::NOP => {
-                    let mut previous_class = dependencies_class[0].clone();
-                    let annotation = context.get_node_annotations(node.clone())?;
-                    for single_communication in annotation {
-                        if let NodeAnnotation::Send(source_party, destination_party) =
-                            single_communication
-                        {
-                            previous_class =
-                                get_nop_class(previous_class, source_party, destination_party)?;
-                        }
-                    }
-                    previous_class
+                let field_id_raw = field_id.unwrap();
+                let input_class = dependencies_class[0].clone();
+                if !input_class.is_vector() {
+                    panic!("NamedTupleGet input class should be Vector")
                 }
+                (*input_class.get_class_vector()[field_id_raw as usize]).clone()
+            }
 
-                Operation::PRF(_, t) => recursive_class_filler(t, dependencies_class[0].clone())?,
-
-                Operation::Add
-                | Operation::Subtract
-                | Operation::Multiply
-                | Operation::MixedMultiply
-                | Operation::Dot
-                | Operation::Matmul
-                | Operation::CuckooHash
-                | Operation::Gather(_) => {
-                    if !dependencies_class[0].is_atomic() {
-                        panic!(
-                            "{} first input class should be Atomic",
-                            node.get_operation()
-                        )
+            Operation::Random(t) => recursive_class_filler(
+                t,
+                EquivalenceClasses::Atomic(vec![vec![0], vec![1], vec![2]]),
+            )?,
+
+            Operation::NOP => {
+                let mut previous_class = dependencies_class[0].clone();
+                let annotation = node.get_annotations()?;
+                for single_communication in annotation {
+                    if let NodeAnnotation::Send(source_party, destination_party) =
+                        single_communication
+                    {
+                        previous_class =
+                            get_nop_class(previous_class, source_party, destination_party)?;
                     }
-                    if !dependencies_class[1].is_atomic() {
-                        panic!(
-                            "{} second input class should be Atomic",
-                            node.get_operation()
-                        )
-                    }
-                    combine_class(dependencies_class[0].clone(), dependencies_class[1].clone())?
                 }
+                previous_class
+            }
 
-                Operation::Truncate(_)
-                | Operation::Sum(_)
-                | Operation::Get(_)
-                | Operation::GetSlice(_)
-                | Operation::A2B
-                | Operation::B2A(_)
-                | Operation::InversePermutation
-                | Operation::PermuteAxes(_) => {
-                    if !dependencies_class[0].is_atomic() {
-                        panic!("{} input class should be Atomic", node.get_operation())
-                    }
-                    dependencies_class[0].clone()
+            Operation::PRF(_, t) => recursive_class_filler(t, dependencies_class[0].clone())?,
+
+            Operation::Add
+            | Operation::Subtract
+            | Operation::Multiply
+            | Operation::MixedMultiply
+            | Operation::Dot
+            | Operation::Matmul
+            | Operation::Gemm(_, _)
+            | Operation::CuckooHash(_)
+            | Operation::BloomFilter
+            | Operation::Gather(_) => {
+                if !dependencies_class[0].is_atomic() {
+                    panic!(
+                        "{} first input class should be Atomic",
+                        node.get_operation()
+                    )
+                }
+                if !dependencies_class[1].is_atomic() {
+                    panic!(
+                        "{} second input class should be Atomic",
+                        node.get_operation()
+                    )
                 }
+                combine_class(dependencies_class[0].clone(), dependencies_class[1].clone())?
+            }
 
-                Operation::Reshape(result_type) => {
-                    let input_classes = flatten_classes(dependencies_class[0].clone());
-                    unflatten_classes(&input_classes, result_type, &mut 0)
+            Operation::Truncate(_)
+            | Operation::Sum(_)
+            | Operation::Get(_)
+            | Operation::GetSlice(_)
+            | Operation::A2B
+            | Operation::B2A(_)
+            | Operation::ConvertRing(_)
+            | Operation::InversePermutation
+            | Operation::PermuteAxes(_)
+            | Operation::BroadcastTo(_)
+            | Operation::Tile(_)
+            | Operation::CumSum(_) => {
+                if !dependencies_class[0].is_atomic() {
+                    panic!("{} input class should be Atomic", node.get_operation())
                 }
+                dependencies_class[0].clone()
+            }
 
-                Operation::Stack(_) => {
-                    let mut result_class = dependencies_class[0].clone();
-                    if !result_class.is_atomic() {
-                        panic!("Stack input classes must be Atomic");
-                    }
-                    for class in dependencies_class.iter().skip(1) {
-                        if !class.is_atomic() {
-                            panic!("Stack input classes must be Atomic");
-                        }
-                        result_class = combine_class(result_class, (*class).clone())?;
+            Operation::Reshape(result_type) => {
+                let input_classes = flatten_classes(dependencies_class[0].clone());
+                unflatten_classes(&input_classes, result_type, &mut 0)
+            }
+
+            Operation::Stack(_) | Operation::Concatenate(_) => {
+                let mut result_class = dependencies_class[0].clone();
+                if !result_class.is_atomic() {
+                    panic!("{} input classes must be Atomic", node.get_operation());
+                }
+                for class in dependencies_class.iter().skip(1) {
+                    if !class.is_atomic() {
+                        panic!("{} input classes must be Atomic", node.get_operation());
                     }
-                    result_class
+                    result_class = combine_class(result_class, (*class).clone())?;
                 }
+                result_class
+            }
 
-                Operation::VectorToArray => {
-                    let input_class = dependencies_class[0].clone();
-                    if !input_class.is_vector() {
-                        panic!("VectorToArray input class must be Vector");
+            Operation::Scatter(_) => {
+                for class in &dependencies_class {
+                    if !class.is_atomic() {
+                        panic!("Scatter input classes must be Atomic");
                     }
-                    let class_vec = input_class.get_class_vector();
-                    let mut result_class = (*class_vec[0]).clone();
-                    for e in class_vec.iter().skip(1) {
-                        result_class = combine_class(result_class, (**e).clone())?;
-                    }
-                    result_class
                 }
+                combine_class(
+                    combine_class(dependencies_class[0].clone(), dependencies_class[1].clone())?,
+                    dependencies_class[2].clone(),
+                )?
+            }
+
+            Operation::Split(_, parts) => {
+                let input_class = dependencies_class[0].clone();
+                if !input_class.is_atomic() {
+                    panic!("Split input class should be Atomic");
+                }
+                vector_class(vec![input_class; parts.len()])
+            }
+
+            Operation::VectorToArray => {
+                let input_class = dependencies_class[0].clone();
+                if !input_class.is_vector() {
+                    panic!("VectorToArray input class must be Vector");
+                }
+                let class_vec = input_class.get_class_vector();
+                let mut result_class = (*class_vec[0]).clone();
+                for e in class_vec.iter().skip(1) {
+                    result_class = combine_class(result_class, (**e).clone())?;
+                }
+                result_class
+            }
 
-                Operation::Zip => {
-                    let mut result_classes = vec![];
-                    let mut index = 0;
-                    'result_entries: loop {
-                        let mut row = vec![];
-                        for dependency_class in dependencies_class.clone() {
-                            if !dependency_class.is_vector() {
-                                panic!("Zip input class must be Vector");
-                            }
-                            let v = dependency_class.get_class_vector();
-                            if v.len() <= index {
-                                break 'result_entries;
-                            }
-                            row.push(v[index].clone());
+            Operation::Zip => {
+                let mut result_classes = vec![];
+                let mut index = 0;
+                'result_entries: loop {
+                    let mut row = vec![];
+                    for dependency_class in dependencies_class.clone() {
+                        if !dependency_class.is_vector() {
+                            panic!("Zip input class must be Vector");
                         }
-                        result_classes.push(EquivalenceClasses::Vector(row));
-                        index += 1;
+                        let v = dependency_class.get_class_vector();
+                        if v.len() <= index {
+                            break 'result_entries;
+                        }
+                        row.push(v[index].clone());
                     }
-
-                    vector_class(result_classes)
+                    result_classes.push(EquivalenceClasses::Vector(row));
+                    index += 1;
                 }
 
-                Operation::Constant(t, _) => recursive_class_filler(t, public_class())?,
+                vector_class(result_classes)
+            }
 
-                Operation::Repeat(n) => {
-                    let mut result_classes = vec![];
-                    for _ in 0..n {
-                        result_classes.push(dependencies_class[0].clone());
-                    }
-                    vector_class(result_classes)
+            Operation::Constant(t, _) => recursive_class_filler(t, public_class())?,
+
+            Operation::Repeat(n) => {
+                let mut result_classes = vec![];
+                for _ in 0..n {
+                    result_classes.push(dependencies_class[0].clone());
                 }
+                vector_class(result_classes)
+            }
 
-                Operation::ArrayToVector => {
-                    let input_class = dependencies_class[0].clone();
-                    if !input_class.is_atomic() {
-                        panic!("ArrayToVector input class should be Atomic");
-                    }
-                    let mut classes = vec![];
-                    let dependency_node = dependencies[0].clone();
-                    let shape = dependency_node.get_type()?.get_shape();
-                    for _ in 0..shape[0] {
-                        classes.push(input_class.clone());
-                    }
-                    vector_class(classes)
+            Operation::ArrayToVector => {
+                let input_class = dependencies_class[0].clone();
+                if !input_class.is_atomic() {
+                    panic!("ArrayToVector input class should be Atomic");
                 }
+                let mut classes = vec![];
+                let dependency_node = dependencies[0].clone();
+                let shape = dependency_node.get_type()?.get_shape();
+                for _ in 0..shape[0] {
+                    classes.push(input_class.clone());
+                }
+                vector_class(classes)
+            }
 
-                Operation::VectorGet => {
-                    let input_class = dependencies_class[0].clone();
-                    if !input_class.is_vector() {
-                        panic!("VectorGet input class should be Vector");
-                    }
-                    let v = input_class.get_class_vector();
-                    let result_class = (*v[0]).clone();
-                    for class in v {
-                        if result_class != *class {
-                            panic!("VectorGet input class contains different EquivalenceClasses");
-                        }
+            Operation::VectorGet => {
+                let input_class = dependencies_class[0].clone();
+                if !input_class.is_vector() {
+                    panic!("VectorGet input class should be Vector");
+                }
+                let v = input_class.get_class_vector();
+                let result_class = (*v[0]).clone();
+                for class in v {
+                    if result_class != *class {
+                        panic!("VectorGet input class contains different EquivalenceClasses");
                     }
-                    result_class
                 }
-                Operation::RandomPermutation(_) | Operation::CuckooToPermutation => private_class(),
-                Operation::DecomposeSwitchingMap(_) => vector_class(vec![
-                    private_class(),
-                    vector_class(vec![private_class(), private_class()]),
-                    private_class(),
-                ]),
-                Operation::SegmentCumSum => combine_class(
-                    combine_class(dependencies_class[0].clone(), dependencies_class[1].clone())?,
-                    dependencies_class[2].clone(),
-                )?,
-                _ => return Err(runtime_error!("Operation is not supported")),
-            };
-            equivalence_classes.insert(node.get_global_id(), result_class);
-        }
+                result_class
+            }
+            Operation::RandomPermutation(_) | Operation::CuckooToPermutation => private_class(),
+            Operation::DecomposeSwitchingMap(_) => vector_class(vec![
+                private_class(),
+                vector_class(vec![private_class(), private_class()]),
+                private_class(),
+            ]),
+            Operation::SegmentCumSum => combine_class(
+                combine_class(dependencies_class[0].clone(), dependencies_class[1].clone())?,
+                dependencies_class[2].clone(),
+            )?,
+            _ => {
+                return Err(
+                    runtime_error!("Operation {} is not supported", node.get_operation())
+                        .with_node(&node),
+                )
+            }
+        };
+        equivalence_classes.insert(node.get_global_id(), result_class);
     }
-    Ok(equivalence_classes)
+    Ok(())
 }
 
 fn get_input_class(t: Type, input_party: &IOStatus) -> Result<EquivalenceClasses> {
@@ -612,15 +658,263 @@ fn check_equivalence_class_nop(
     }
 }
 
+// Returns whether values of nodes are masked by fresh randomness, i.e. depend on an output of a randomized operation via addition or subtraction.
+// Values sent to another party are considered safe if they're masked.
+fn get_masked_flags(graph: Graph) -> Result<HashMap<(u64, u64), bool>> {
+    let mut flags: HashMap<(u64, u64), bool> = HashMap::new();
+    for node in graph.get_nodes() {
+        let mut dependencies_flags = vec![];
+        for dependency in node.get_node_dependencies() {
+            dependencies_flags.push(*flags.get(&dependency.get_global_id()).ok_or_else(|| {
+                runtime_error!("Node {:?} wasn't processed", dependency.get_global_id())
+            })?);
+        }
+        let flag = match node.get_operation() {
+            Operation::Random(_)
+            | Operation::PRF(_, _)
+            | Operation::RandomPermutation(_)
+            | Operation::CuckooToPermutation
+            | Operation::DecomposeSwitchingMap(_) => true,
+            Operation::Add | Operation::Subtract => dependencies_flags.iter().any(|f| *f),
+            Operation::NOP
+            | Operation::Truncate(_)
+            | Operation::Sum(_)
+            | Operation::PermuteAxes(_)
+            | Operation::Get(_)
+            | Operation::GetSlice(_)
+            | Operation::Reshape(_)
+            | Operation::TupleGet(_)
+            | Operation::NamedTupleGet(_)
+            | Operation::VectorGet
+            | Operation::Repeat(_)
+            | Operation::ArrayToVector
+            | Operation::VectorToArray
+            | Operation::Split(_, _)
+            | Operation::BroadcastTo(_)
+            | Operation::Tile(_)
+            | Operation::CumSum(_)
+            | Operation::Gather(_) => dependencies_flags[0],
+            // The result contains rows of both the input and the updates
+            Operation::Scatter(_) => dependencies_flags[0] && dependencies_flags[2],
+            Operation::CreateTuple
+            | Operation::CreateNamedTuple(_)
+            | Operation::CreateVector(_)
+            | Operation::Stack(_)
+            | Operation::Concatenate(_)
+            | Operation::Zip => {
+                !dependencies_flags.is_empty() && dependencies_flags.iter().all(|f| *f)
+            }
+            _ => false,
+        };
+        flags.insert(node.get_global_id(), flag);
+    }
+    Ok(flags)
+}
+
+// Returns the party that obtains the whole secret value if a given class is sent from `source_party` to `destination_party`.
+// This happens if the class is a share of a shared value and the destination party doesn't hold this share, since every party holds the other two shares.
+fn get_revealing_party(
+    class: &EquivalenceClasses,
+    source_party: u64,
+    destination_party: u64,
+) -> Option<u64> {
+    match class {
+        EquivalenceClasses::Atomic(_) => {
+            for share_class in [share0_class(), share1_class(), share2_class()] {
+                if *class == share_class
+                    && check_equivalence_class_nop(class, source_party, destination_party)
+                        .unwrap_or(false)
+                {
+                    return Some(destination_party);
+                }
+            }
+            None
+        }
+        EquivalenceClasses::Vector(v) => v
+            .iter()
+            .find_map(|c| get_revealing_party(c, source_party, destination_party)),
+    }
+}
+
+/// Checks that a compiled context doesn't leak secret values to unintended parties.
+///
+/// Every node of the context is assigned a class describing which parties hold the same value.
+/// The check fails if
+///
+/// * a node annotated with [NodeAnnotation::Send] transfers a value to a party that already holds it (which indicates a malformed protocol), or
+/// * a node transfers a share of a secret value not masked by randomness to the party that doesn't hold this share, unless this party is one of the declared output parties.
+///
+/// Only the main graph of the context is checked, so the context must be fully inlined, e.g. it should be produced by [prepare_for_mpc_evaluation](crate::mpc::mpc_compiler::prepare_for_mpc_evaluation).
+///
+/// # Arguments
+///
+/// * `context` - compiled context
+/// * `input_statuses` - ownership statuses of the inputs of the context before compilation
+/// * `output_statuses` - parties that may learn the output of the context (empty or [IOStatus::Shared] if the output stays secret-shared)
+///
+/// # Returns
+///
+/// Error identifying the leaking node if the check fails
+pub fn verify_privacy(
+    context: Context,
+    input_statuses: Vec<IOStatus>,
+    output_statuses: Vec<IOStatus>,
+) -> Result<()> {
+    let output_policy = if output_statuses.contains(&IOStatus::Public) {
+        OutputPolicy::RevealTo((0..PARTIES as u64).collect())
+    } else {
+        let parties: Vec<u64> = output_statuses
+            .iter()
+            .filter_map(|status| match status {
+                IOStatus::Party(id) => Some(*id),
+                _ => None,
+            })
+            .collect();
+        if parties.is_empty() {
+            OutputPolicy::Shared
+        } else {
+            OutputPolicy::RevealTo(parties)
+        }
+    };
+    verify_privacy_with_output_policy(context, input_statuses, output_policy)
+}
+
+/// Same as [verify_privacy], but parties that may learn the output are given by an [output policy](OutputPolicy).
+///
+/// A secret value can be revealed to a party only if this party obtains one of the output elements depending on this value.
+/// In addition to unmasked shares sent between parties, this applies to nodes annotated with [NodeAnnotation::Reveal].
+/// Thus, if the policy is [OutputPolicy::Tuple], secret values contributing only to shared elements are never revealed,
+/// and values contributing only to elements revealed to some parties are never revealed to the other parties.
+/// The elements are matched with the policy only if the output node is a tuple created by the compiler, e.g. by [prepare_for_mpc_evaluation_with_output_policy](crate::mpc::mpc_compiler::prepare_for_mpc_evaluation_with_output_policy);
+/// otherwise, all the parties of the policy may learn every value.
+pub fn verify_privacy_with_output_policy(
+    context: Context,
+    input_statuses: Vec<IOStatus>,
+    output_policy: OutputPolicy,
+) -> Result<()> {
+    let graph = context.get_main_graph()?;
+    let num_inputs = graph
+        .get_nodes()
+        .iter()
+        .filter(|node| matches!(node.get_operation(), Operation::Input(_)))
+        .count();
+    if num_inputs != input_statuses.len() {
+        return Err(runtime_error!(
+            "Invalid number of input statuses: {} expected, but {} found",
+            num_inputs,
+            input_statuses.len()
+        ));
+    }
+    let mut classes = HashMap::new();
+    add_graph_equivalence_classes(graph.clone(), &input_statuses, &mut 0, &mut classes)?;
+    let masked_flags = get_masked_flags(graph.clone())?;
+    let output_parties = get_output_parties(graph.clone(), &output_policy)?;
+    let all_output_parties = output_policy.get_parties();
+    for node in graph.get_nodes() {
+        if !check_equivalence_class(context.clone(), &classes, node.clone())? {
+            return Err(
+                runtime_error!("Value is sent to a party that already holds it").with_node(&node),
+            );
+        }
+        // Nodes that don't contribute to the output can be revealed to any output party
+        let allowed_parties = output_parties
+            .get(&node.get_global_id())
+            .cloned()
+            .unwrap_or_else(|| all_output_parties.iter().copied().collect());
+        // Values revealed by the compiler are explicitly annotated
+        for annotation in node.get_annotations()? {
+            if let NodeAnnotation::Reveal(party) = annotation {
+                if !allowed_parties.contains(&party) {
+                    return Err(runtime_error!(
+                        "Secret value is revealed to party {}, which is not an output party",
+                        party
+                    )
+                    .with_node(&node));
+                }
+            }
+        }
+        let dependencies = node.get_node_dependencies();
+        if node.get_operation() != Operation::NOP || masked_flags[&dependencies[0].get_global_id()]
+        {
+            continue;
+        }
+        let mut class = classes[&dependencies[0].get_global_id()].clone();
+        for annotation in node.get_annotations()? {
+            if let NodeAnnotation::Send(source_party, destination_party) = annotation {
+                if let Some(party) = get_revealing_party(&class, source_party, destination_party) {
+                    if !allowed_parties.contains(&party) {
+                        return Err(runtime_error!(
+                            "Secret value is revealed to party {}, which is not an output party",
+                            party
+                        )
+                        .with_node(&node));
+                    }
+                }
+                class = get_nop_class(class, source_party, destination_party)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Returns the parties that obtain some output element depending on a node for all the nodes contributing to the output.
+fn get_output_parties(
+    graph: Graph,
+    output_policy: &OutputPolicy,
+) -> Result<HashMap<(u64, u64), HashSet<u64>>> {
+    fn assign_element_parties(
+        node: Node,
+        policy: &OutputPolicy,
+        parties: &mut HashMap<(u64, u64), HashSet<u64>>,
+    ) {
+        let dependencies = node.get_node_dependencies();
+        match policy {
+            OutputPolicy::Tuple(policies)
+                if node.get_operation() == Operation::CreateTuple
+                    && dependencies.len() == policies.len() =>
+            {
+                for (dependency, element_policy) in dependencies.into_iter().zip(policies) {
+                    assign_element_parties(dependency, element_policy, parties);
+                }
+            }
+            _ => {
+                parties
+                    .entry(node.get_global_id())
+                    .or_default()
+                    .extend(policy.get_parties());
+            }
+        }
+    }
+    let mut parties = HashMap::new();
+    assign_element_parties(graph.get_output_node()?, output_policy, &mut parties);
+    // Graph nodes are sorted topologically, so dependents are processed before their dependencies
+    for node in graph.get_nodes().into_iter().rev() {
+        if let Some(node_parties) = parties.get(&node.get_global_id()).cloned() {
+            for dependency in node.get_node_dependencies() {
+                parties
+                    .entry(dependency.get_global_id())
+                    .or_default()
+                    .extend(node_parties.iter().copied());
+            }
+        }
+    }
+    Ok(parties)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::custom_ops::CustomOperation;
     use crate::data_types::{array_type, scalar_type, tuple_type, vector_type, BIT, UINT64};
     use crate::data_values::Value;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
     use crate::graphs::{create_context, create_unchecked_context, Graph, SliceElement};
     use crate::inline::inline_common::DepthOptimizationLevel;
     use crate::inline::inline_ops::{InlineConfig, InlineMode};
-    use crate::mpc::mpc_compiler::{prepare_for_mpc_evaluation, IOStatus};
+    use crate::mpc::mpc_compiler::{
+        compile_context, compile_context_with_output_policy, prepare_for_mpc_evaluation, IOStatus,
+    };
+    use crate::ops::comparisons::GreaterThan;
     use std::collections::HashMap;
 
     type ClassesMap = HashMap<(u64, u64), EquivalenceClasses>;
@@ -1846,4 +2140,127 @@ mod tests {
         }
         Ok(result)
     }
+
+    fn privacy_test_context() -> Result<Context> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let t = array_type(vec![4], UINT64);
+        let a = g.input(t.clone())?;
+        let b = g.input(t)?;
+        let product = a.multiply(b)?.truncate(4)?;
+        let greater = g.custom_op(
+            CustomOperation::new(GreaterThan {
+                signed_comparison: false,
+            }),
+            vec![product.a2b()?, a.a2b()?],
+        )?;
+        g.create_tuple(vec![product, greater])?.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        Ok(c)
+    }
+
+    fn privacy_test_inline_config() -> InlineConfig {
+        InlineConfig {
+            default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+            ..Default::default()
+        }
+    }
+
+    fn compile_for_privacy_test(output_parties: Vec<IOStatus>) -> Result<Context> {
+        compile_context(
+            privacy_test_context()?,
+            vec![IOStatus::Party(0), IOStatus::Party(1)],
+            output_parties,
+            privacy_test_inline_config(),
+            || SimpleEvaluator::new(None),
+        )
+    }
+
+    #[test]
+    fn test_verify_privacy() {
+        || -> Result<()> {
+            let inputs = vec![IOStatus::Party(0), IOStatus::Party(1)];
+            let shared_c = compile_for_privacy_test(vec![])?;
+            verify_privacy(shared_c.clone(), inputs.clone(), vec![])?;
+            verify_privacy(shared_c.clone(), inputs.clone(), vec![IOStatus::Shared])?;
+
+            let revealed_c = compile_for_privacy_test(vec![IOStatus::Party(2)])?;
+            verify_privacy(revealed_c.clone(), inputs.clone(), vec![IOStatus::Party(2)])?;
+            verify_privacy(revealed_c.clone(), inputs.clone(), vec![IOStatus::Public])?;
+            // The output is revealed to party 2, which isn't declared as an output party
+            let e = verify_privacy(revealed_c.clone(), inputs.clone(), vec![IOStatus::Party(0)])
+                .unwrap_err();
+            assert!(e.get_message().contains("revealed to party 2"));
+            let leaking_node = revealed_c
+                .get_main_graph()?
+                .get_nodes()
+                .into_iter()
+                .find(|node| Some(node.get_global_id()) == e.get_node_global_id())
+                .unwrap();
+            assert_eq!(leaking_node.get_operation(), Operation::NOP);
+            assert!(
+                leaking_node
+                    .get_annotations()?
+                    .contains(&NodeAnnotation::Send(1, 2))
+                    || leaking_node
+                        .get_annotations()?
+                        .contains(&NodeAnnotation::Send(0, 2))
+            );
+
+            assert!(verify_privacy(revealed_c, vec![IOStatus::Party(0)], vec![]).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_verify_privacy_with_output_policy() {
+        || -> Result<()> {
+            let inputs = vec![IOStatus::Party(0), IOStatus::Party(1)];
+            // The product is revealed to party 2 and the comparison result to party 0
+            let policy = OutputPolicy::Tuple(vec![
+                OutputPolicy::RevealTo(vec![2]),
+                OutputPolicy::RevealTo(vec![0]),
+            ]);
+            let c = compile_context_with_output_policy(
+                privacy_test_context()?,
+                inputs.clone(),
+                policy.clone(),
+                privacy_test_inline_config(),
+                || SimpleEvaluator::new(None),
+            )?;
+            verify_privacy_with_output_policy(c.clone(), inputs.clone(), policy)?;
+            verify_privacy_with_output_policy(
+                c.clone(),
+                inputs.clone(),
+                OutputPolicy::RevealTo(vec![0, 2]),
+            )?;
+            verify_privacy(c.clone(), inputs.clone(), vec![IOStatus::Public])?;
+            for (wrong_policy, party) in [
+                (
+                    OutputPolicy::Tuple(vec![
+                        OutputPolicy::RevealTo(vec![2]),
+                        OutputPolicy::Shared,
+                    ]),
+                    0,
+                ),
+                (
+                    OutputPolicy::Tuple(vec![
+                        OutputPolicy::RevealTo(vec![0]),
+                        OutputPolicy::RevealTo(vec![0]),
+                    ]),
+                    2,
+                ),
+            ] {
+                let e = verify_privacy_with_output_policy(c.clone(), inputs.clone(), wrong_policy)
+                    .unwrap_err();
+                assert!(e
+                    .get_message()
+                    .contains(&format!("revealed to party {}", party)));
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
 }
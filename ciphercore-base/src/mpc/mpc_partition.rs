@@ -0,0 +1,696 @@
+//! Partitioning of a computation into parts executed locally in plaintext by input owners and a part executed in MPC.
+//!
+//! Every node of an inlined graph is assigned to the equivalence class of parties that can compute it in plaintext:
+//! public nodes (depending only on public inputs and constants) can be computed by anyone,
+//! nodes depending only on the inputs of party `i` and public nodes can be computed locally by party `i`,
+//! and all other nodes (depending on inputs of several parties, shared inputs or randomness) require MPC.
+//!
+//! Local nodes are split into those computed by their owners and those recomputed in MPC from values shared by owners.
+//! The values shared by owners form the boundary of the MPC part, which is chosen to minimize its total size in bits
+//! (computed as a minimum vertex cut between local inputs and the nodes consumed by MPC).
+//! Local nodes whose recomputation in MPC requires communication (e.g. products of two private values) are always computed by their owners,
+//! and among cuts of the same size, the one closest to MPC is chosen, i.e. as much as possible is computed locally.
+//!
+//! [compile_partitioned_context] compiles the MPC part and stitches all the parts into one context,
+//! where the MPC part obtains local results as private inputs of their owners, i.e. they're shared and revealed by the MPC compiler.
+use crate::data_types::get_size_in_bits;
+use crate::errors::Result;
+use crate::evaluators::Evaluator;
+use crate::graphs::{copy_node_name, create_context, Context, Graph, Node, Operation};
+use crate::inline::inline_ops::InlineConfig;
+use crate::mpc::mpc_compiler::{compile_context, prepare_context, IOStatus};
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Input of the MPC part of a [partitioned context](PartitionedContext).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PartitionedInput {
+    /// Input of the original context with a given index
+    Input(u64),
+    /// Element of the output tuple of the local part of a given party: `Local(party, index)`
+    Local(u64, u64),
+}
+
+/// Part of a [partitioned context](PartitionedContext) computed in plaintext by one party.
+#[derive(Clone)]
+pub struct LocalPartition {
+    /// Party computing the local part.
+    pub party: u64,
+    /// Context whose main graph returns a tuple of values shared with the MPC part.
+    pub context: Context,
+    /// Indices of the inputs of the original context passed to the main graph; they're inputs of the party and public inputs.
+    pub inputs: Vec<u64>,
+}
+
+/// Result of [partition_context].
+#[derive(Clone)]
+pub struct PartitionedContext {
+    /// Local parts of parties that compute a part of the boundary of the MPC part.
+    pub local_partitions: Vec<LocalPartition>,
+    /// Context computing the output of the original context from the boundary values and non-local inputs.
+    pub mpc_context: Context,
+    /// Sources of the inputs of the main graph of `mpc_context`.
+    pub mpc_inputs: Vec<PartitionedInput>,
+    /// Statuses of the inputs of the main graph of `mpc_context` to be passed to the MPC compiler.
+    pub mpc_input_parties: Vec<IOStatus>,
+}
+
+/// Parties able to compute a node in plaintext
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Ownership {
+    Public,
+    Party(u64),
+    Mpc,
+}
+
+fn combine_ownership(a: Ownership, b: Ownership) -> Ownership {
+    match (a, b) {
+        (Ownership::Public, x) | (x, Ownership::Public) => x,
+        (Ownership::Party(i), Ownership::Party(j)) if i == j => a,
+        _ => Ownership::Mpc,
+    }
+}
+
+// Returns the ownership of the nodes of a graph indexed by node IDs
+fn get_ownership(graph: &Graph, input_parties: &[IOStatus]) -> Result<HashMap<u64, Ownership>> {
+    let mut ownership = HashMap::new();
+    let mut input_index = 0;
+    for node in graph.get_nodes() {
+        if !node.get_graph_dependencies().is_empty() {
+            return Err(runtime_error!(
+                "Graph must be fully inlined to be partitioned"
+            ));
+        }
+        let class = match node.get_operation() {
+            Operation::Input(_) => {
+                let status = input_parties
+                    .get(input_index)
+                    .ok_or_else(|| runtime_error!("Not enough input statuses"))?;
+                input_index += 1;
+                match status {
+                    IOStatus::Public => Ownership::Public,
+                    IOStatus::Party(id) => Ownership::Party(*id),
+                    IOStatus::Shared => Ownership::Mpc,
+                }
+            }
+            // Random values must stay secret from every party
+            Operation::Random(_) | Operation::RandomPermutation(_) => Ownership::Mpc,
+            _ => node
+                .get_node_dependencies()
+                .iter()
+                .fold(Ownership::Public, |class, dependency| {
+                    combine_ownership(class, ownership[&dependency.get_id()])
+                }),
+        };
+        ownership.insert(node.get_id(), class);
+    }
+    if input_index != input_parties.len() {
+        return Err(runtime_error!(
+            "Invalid number of input parties: {} expected, but {} found",
+            input_index,
+            input_parties.len()
+        ));
+    }
+    Ok(ownership)
+}
+
+/// Flow network with vertices `0..n` for the Edmonds-Karp algorithm
+struct FlowNetwork {
+    // (head, residual capacity) of every edge; the reverse of the edge `e` is `e ^ 1`
+    edges: Vec<(usize, u64)>,
+    adjacency: Vec<Vec<usize>>,
+}
+
+const INFINITE_CAPACITY: u64 = u64::MAX / 4;
+
+impl FlowNetwork {
+    fn new(n: usize) -> Self {
+        FlowNetwork {
+            edges: vec![],
+            adjacency: vec![vec![]; n],
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, capacity: u64) {
+        self.adjacency[from].push(self.edges.len());
+        self.edges.push((to, capacity));
+        self.adjacency[to].push(self.edges.len());
+        self.edges.push((from, 0));
+    }
+
+    fn max_flow(&mut self, source: usize, sink: usize) {
+        loop {
+            // Breadth-first search of a shortest augmenting path
+            let mut parent_edge = vec![None; self.adjacency.len()];
+            let mut queue = VecDeque::from([source]);
+            while let Some(u) = queue.pop_front() {
+                for e in &self.adjacency[u] {
+                    let (v, capacity) = self.edges[*e];
+                    if capacity > 0 && v != source && parent_edge[v].is_none() {
+                        parent_edge[v] = Some(*e);
+                        queue.push_back(v);
+                    }
+                }
+            }
+            if parent_edge[sink].is_none() {
+                return;
+            }
+            let mut path = vec![];
+            let mut v = sink;
+            while let Some(e) = parent_edge[v] {
+                path.push(e);
+                v = self.edges[e ^ 1].0;
+            }
+            let flow = path.iter().map(|e| self.edges[*e].1).min().unwrap();
+            for e in path {
+                self.edges[e].1 -= flow;
+                self.edges[e ^ 1].1 += flow;
+            }
+        }
+    }
+
+    /// Returns the vertices from which the sink is reachable in the residual network.
+    fn get_sink_side(&self, sink: usize) -> Vec<bool> {
+        let mut reachable = vec![false; self.adjacency.len()];
+        reachable[sink] = true;
+        let mut queue = VecDeque::from([sink]);
+        while let Some(v) = queue.pop_front() {
+            for e in &self.adjacency[v] {
+                // The reverse edge e ^ 1 goes from u to v
+                let u = self.edges[*e].0;
+                if self.edges[*e ^ 1].1 > 0 && !reachable[u] {
+                    reachable[u] = true;
+                    queue.push_back(u);
+                }
+            }
+        }
+        reachable
+    }
+}
+
+/// Checks whether a local node can be recomputed in MPC without communication, i.e. it's a linear or structural operation on private values.
+fn is_free_in_mpc(node: &Node, ownership: &HashMap<u64, Ownership>) -> bool {
+    match node.get_operation() {
+        Operation::Multiply
+        | Operation::MixedMultiply
+        | Operation::Dot
+        | Operation::Matmul
+        | Operation::Gemm(_, _) => node
+            .get_node_dependencies()
+            .iter()
+            .any(|dependency| ownership[&dependency.get_id()] == Ownership::Public),
+        // Scatter is linear if its indices are public
+        Operation::Scatter(_) => {
+            ownership[&node.get_node_dependencies()[1].get_id()] == Ownership::Public
+        }
+        Operation::Input(_)
+        | Operation::Add
+        | Operation::Subtract
+        | Operation::Sum(_)
+        | Operation::Get(_)
+        | Operation::GetSlice(_)
+        | Operation::Reshape(_)
+        | Operation::PermuteAxes(_)
+        | Operation::Repeat(_)
+        | Operation::ArrayToVector
+        | Operation::VectorToArray
+        | Operation::CreateTuple
+        | Operation::CreateNamedTuple(_)
+        | Operation::CreateVector(_)
+        | Operation::TupleGet(_)
+        | Operation::NamedTupleGet(_)
+        | Operation::Stack(_)
+        | Operation::Concatenate(_)
+        | Operation::Split(_, _)
+        | Operation::BroadcastTo(_)
+        | Operation::Tile(_)
+        | Operation::CumSum(_)
+        | Operation::Zip => true,
+        _ => false,
+    }
+}
+
+/// Location of a local node in a partitioned graph
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Placement {
+    // Computed only by its owner
+    Local,
+    // Computed by its owner and shared with the MPC part
+    Boundary,
+    // Recomputed in the MPC part
+    Mpc,
+}
+
+/// Places local nodes by a minimum vertex cut between local inputs and local nodes used by non-local nodes.
+///
+/// Nodes are indexed by their IDs.
+fn place_local_nodes(
+    graph: &Graph,
+    ownership: &HashMap<u64, Ownership>,
+) -> Result<HashMap<u64, Placement>> {
+    let local_nodes: Vec<Node> = graph
+        .get_nodes()
+        .into_iter()
+        .filter(|node| matches!(ownership[&node.get_id()], Ownership::Party(_)))
+        .collect();
+    let indices: HashMap<u64, usize> = local_nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.get_id(), i))
+        .collect();
+    // Node i is split into vertices 2i (incoming edges) and 2i + 1 (outgoing edges) connected by an edge with capacity equal to its size
+    let source = 2 * local_nodes.len();
+    let sink = source + 1;
+    let mut network = FlowNetwork::new(sink + 1);
+    let output_node = graph.get_output_node()?;
+    for (i, node) in local_nodes.iter().enumerate() {
+        network.add_edge(2 * i, 2 * i + 1, get_size_in_bits(node.get_type()?)?);
+        // Inputs and nodes that can't be recomputed for free are kept on the source side
+        if !is_free_in_mpc(node, ownership) || matches!(node.get_operation(), Operation::Input(_)) {
+            network.add_edge(source, 2 * i, INFINITE_CAPACITY);
+        }
+        for dependency in node.get_node_dependencies() {
+            if let Some(j) = indices.get(&dependency.get_id()) {
+                network.add_edge(2 * j + 1, 2 * i, INFINITE_CAPACITY);
+            }
+        }
+        if *node == output_node {
+            network.add_edge(2 * i + 1, sink, INFINITE_CAPACITY);
+        }
+    }
+    for node in graph.get_nodes() {
+        if let Ownership::Party(_) = ownership[&node.get_id()] {
+            continue;
+        }
+        for dependency in node.get_node_dependencies() {
+            if let Some(j) = indices.get(&dependency.get_id()) {
+                network.add_edge(2 * j + 1, sink, INFINITE_CAPACITY);
+            }
+        }
+    }
+    network.max_flow(source, sink);
+    let sink_side = network.get_sink_side(sink);
+    let mut placement = HashMap::new();
+    for (i, node) in local_nodes.into_iter().enumerate() {
+        let node_placement = match (sink_side[2 * i], sink_side[2 * i + 1]) {
+            (true, _) => Placement::Mpc,
+            (false, true) => Placement::Boundary,
+            (false, false) => Placement::Local,
+        };
+        placement.insert(node.get_id(), node_placement);
+    }
+    Ok(placement)
+}
+
+/// Copies given nodes of a graph in their topological order; nodes of `inputs` are replaced by new input nodes.
+///
+/// Nodes are given by their IDs. Returns the copied nodes indexed by IDs of the original nodes.
+fn copy_nodes(
+    graph: &Graph,
+    out_graph: &Graph,
+    nodes: &HashSet<u64>,
+    inputs: &HashSet<u64>,
+) -> Result<HashMap<u64, Node>> {
+    let mut mapping: HashMap<u64, Node> = HashMap::new();
+    for node in graph.get_nodes() {
+        if !nodes.contains(&node.get_id()) {
+            continue;
+        }
+        let new_node = if inputs.contains(&node.get_id()) {
+            out_graph.input(node.get_type()?)?
+        } else {
+            let dependencies = node
+                .get_node_dependencies()
+                .iter()
+                .map(|dependency| mapping[&dependency.get_id()].clone())
+                .collect();
+            let new_node = out_graph.add_node(dependencies, vec![], node.get_operation())?;
+            for annotation in node.get_annotations()? {
+                new_node.add_annotation(annotation)?;
+            }
+            new_node
+        };
+        copy_node_name(node.clone(), new_node.clone())?;
+        mapping.insert(node.get_id(), new_node);
+    }
+    Ok(mapping)
+}
+
+/// Returns IDs of given nodes and all their ancestors.
+fn get_ancestors(roots: &[Node]) -> HashSet<u64> {
+    let mut ancestors: HashSet<u64> = roots.iter().map(|node| node.get_id()).collect();
+    let mut stack = roots.to_vec();
+    while let Some(node) = stack.pop() {
+        for dependency in node.get_node_dependencies() {
+            if ancestors.insert(dependency.get_id()) {
+                stack.push(dependency);
+            }
+        }
+    }
+    ancestors
+}
+
+/// Splits the main graph of a context into graphs computed locally by input owners and a graph computed in MPC
+/// (see the [module documentation](self)).
+///
+/// # Arguments
+///
+/// * `context` - finalized context whose main graph is fully inlined (e.g. by [prepare_context])
+/// * `input_parties` - statuses of the inputs of the main graph as in [compile_context]
+///
+/// # Returns
+///
+/// Partitioned context
+pub fn partition_context(
+    context: Context,
+    input_parties: &[IOStatus],
+) -> Result<PartitionedContext> {
+    context.check_finalized()?;
+    let graph = context.get_main_graph()?;
+    let ownership = get_ownership(&graph, input_parties)?;
+    let placement = place_local_nodes(&graph, &ownership)?;
+    let mut input_indices = HashMap::new();
+    for node in graph.get_nodes() {
+        if let Operation::Input(_) = node.get_operation() {
+            let index = input_indices.len() as u64;
+            input_indices.insert(node.get_id(), index);
+        }
+    }
+
+    // Boundary nodes of every party except for inputs, which are passed to the MPC part directly
+    let mut computed_boundaries: Vec<(u64, Vec<Node>)> = vec![];
+    for node in graph.get_nodes() {
+        if let (Some(Placement::Boundary), Ownership::Party(party)) =
+            (placement.get(&node.get_id()), ownership[&node.get_id()])
+        {
+            if input_indices.contains_key(&node.get_id()) {
+                continue;
+            }
+            match computed_boundaries.iter_mut().find(|(p, _)| *p == party) {
+                Some((_, nodes)) => nodes.push(node),
+                None => computed_boundaries.push((party, vec![node])),
+            }
+        }
+    }
+    computed_boundaries.sort_by_key(|(party, _)| *party);
+
+    let mut local_partitions = vec![];
+    let mut local_outputs = HashMap::new();
+    for (party, boundary) in computed_boundaries {
+        let nodes = get_ancestors(&boundary);
+        let inputs: HashSet<u64> = nodes
+            .iter()
+            .filter(|node_id| input_indices.contains_key(*node_id))
+            .copied()
+            .collect();
+        let local_context = create_context()?;
+        let local_graph = local_context.create_graph()?;
+        let mapping = copy_nodes(&graph, &local_graph, &nodes, &inputs)?;
+        let outputs = boundary
+            .iter()
+            .map(|node| mapping[&node.get_id()].clone())
+            .collect();
+        local_graph.create_tuple(outputs)?.set_as_output()?;
+        local_graph.finalize()?.set_as_main()?;
+        local_context.finalize()?;
+        let mut local_inputs: Vec<u64> = inputs
+            .iter()
+            .map(|node_id| input_indices[node_id])
+            .collect();
+        local_inputs.sort();
+        for (index, node) in boundary.into_iter().enumerate() {
+            local_outputs.insert(node.get_id(), PartitionedInput::Local(party, index as u64));
+        }
+        local_partitions.push(LocalPartition {
+            party,
+            context: local_context,
+            inputs: local_inputs,
+        });
+    }
+
+    // The MPC part contains non-local nodes, boundary nodes (as inputs) and local nodes recomputed in MPC
+    let mut mpc_nodes = HashSet::new();
+    let mut mpc_graph_inputs = HashSet::new();
+    let mut mpc_inputs = vec![];
+    let mut mpc_input_parties = vec![];
+    for node in graph.get_nodes() {
+        let node_placement = placement.get(&node.get_id()).copied();
+        let is_input = input_indices.contains_key(&node.get_id());
+        if node_placement == Some(Placement::Local) {
+            continue;
+        }
+        mpc_nodes.insert(node.get_id());
+        if node_placement == Some(Placement::Boundary) || is_input {
+            mpc_graph_inputs.insert(node.get_id());
+            mpc_inputs.push(match local_outputs.get(&node.get_id()) {
+                Some(local_output) => local_output.clone(),
+                None => PartitionedInput::Input(input_indices[&node.get_id()]),
+            });
+            mpc_input_parties.push(match ownership[&node.get_id()] {
+                Ownership::Public => IOStatus::Public,
+                Ownership::Party(party) => IOStatus::Party(party),
+                Ownership::Mpc => input_parties[input_indices[&node.get_id()] as usize].clone(),
+            });
+        }
+    }
+    let mpc_context = create_context()?;
+    let mpc_graph = mpc_context.create_graph()?;
+    let mapping = copy_nodes(&graph, &mpc_graph, &mpc_nodes, &mpc_graph_inputs)?;
+    mapping[&graph.get_output_node()?.get_id()].set_as_output()?;
+    mpc_graph.finalize()?.set_as_main()?;
+    mpc_context.finalize()?;
+    Ok(PartitionedContext {
+        local_partitions,
+        mpc_context,
+        mpc_inputs,
+        mpc_input_parties,
+    })
+}
+
+/// Copies a fully inlined graph to another context.
+fn copy_graph(graph: Graph, context: &Context) -> Result<Graph> {
+    let out_graph = context.create_graph()?;
+    let nodes: HashSet<u64> = graph.get_nodes().iter().map(|node| node.get_id()).collect();
+    let mapping = copy_nodes(&graph, &out_graph, &nodes, &HashSet::new())?;
+    mapping[&graph.get_output_node()?.get_id()].set_as_output()?;
+    for annotation in graph.get_annotations()? {
+        out_graph.add_annotation(annotation)?;
+    }
+    out_graph.finalize()
+}
+
+/// Same as [compile_context], but the parts of the computation that can be performed by a single party in plaintext are excluded from MPC
+/// (see [partition_context]).
+///
+/// The main graph of the returned context calls the graphs of local parts and the graph compiled from the MPC part,
+/// so it computes the same output as the context returned by [compile_context].
+/// The nodes calling the graph of the local part of party `i` are named `local_part_i`.
+pub fn compile_partitioned_context<T, E>(
+    context: Context,
+    input_parties: Vec<IOStatus>,
+    output_parties: Vec<IOStatus>,
+    inline_config: InlineConfig,
+    get_evaluator: T,
+) -> Result<Context>
+where
+    T: Fn() -> Result<E>,
+    E: Evaluator + Sized,
+{
+    let prepared_context =
+        prepare_context(context, inline_config.clone(), get_evaluator()?, false)?;
+    let partitioned = partition_context(prepared_context.clone(), &input_parties)?;
+    let compiled_mpc_context = compile_context(
+        partitioned.mpc_context,
+        partitioned.mpc_input_parties,
+        output_parties,
+        inline_config,
+        get_evaluator,
+    )?;
+
+    let stitched_context = create_context()?;
+    let mut local_graphs = vec![];
+    for partition in &partitioned.local_partitions {
+        local_graphs.push(copy_graph(
+            partition.context.get_main_graph()?,
+            &stitched_context,
+        )?);
+    }
+    let mpc_graph = copy_graph(compiled_mpc_context.get_main_graph()?, &stitched_context)?;
+    let main_graph = stitched_context.create_graph()?;
+    let mut inputs = vec![];
+    for node in prepared_context.get_main_graph()?.get_nodes() {
+        if let Operation::Input(t) = node.get_operation() {
+            inputs.push(main_graph.input(t)?);
+        }
+    }
+    let mut local_results = HashMap::new();
+    for (partition, local_graph) in partitioned.local_partitions.iter().zip(local_graphs) {
+        let arguments = partition
+            .inputs
+            .iter()
+            .map(|i| inputs[*i as usize].clone())
+            .collect();
+        let result = main_graph.call(local_graph, arguments)?;
+        result.set_name(&format!("local_part_{}", partition.party))?;
+        local_results.insert(partition.party, result);
+    }
+    let mut mpc_arguments = vec![];
+    for input in partitioned.mpc_inputs {
+        mpc_arguments.push(match input {
+            PartitionedInput::Input(i) => inputs[i as usize].clone(),
+            PartitionedInput::Local(party, i) => local_results[&party].tuple_get(i)?,
+        });
+    }
+    main_graph.call(mpc_graph, mpc_arguments)?.set_as_output()?;
+    main_graph.finalize()?.set_as_main()?;
+    stitched_context.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{array_type, scalar_type, INT32, UINT64};
+    use crate::data_values::Value;
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::inline::inline_ops::InlineMode;
+
+    fn simple_config() -> InlineConfig {
+        InlineConfig {
+            default_mode: InlineMode::Simple,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_partition_boundary() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(array_type(vec![100], UINT64))?;
+            let y = g.input(scalar_type(UINT64))?;
+            let z = g.input(array_type(vec![100], UINT64))?;
+            let w = g.input(scalar_type(UINT64))?;
+            // Party 0 shares the sum of its array, while party 1 shares its scalar instead of the expanded array
+            let x_sum = x.multiply(x.clone())?.sum(vec![0])?;
+            let y_array = y.repeat(100)?.vector_to_array()?;
+            let product = x_sum.multiply(y_array.add(z)?)?;
+            product.add(w)?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let partitioned = partition_context(
+                c,
+                &[
+                    IOStatus::Party(0),
+                    IOStatus::Party(1),
+                    IOStatus::Shared,
+                    IOStatus::Public,
+                ],
+            )?;
+            assert_eq!(partitioned.local_partitions.len(), 1);
+            let partition = &partitioned.local_partitions[0];
+            assert_eq!(partition.party, 0);
+            assert_eq!(partition.inputs, vec![0]);
+            assert_eq!(
+                partitioned.mpc_inputs,
+                vec![
+                    PartitionedInput::Input(1),
+                    PartitionedInput::Input(2),
+                    PartitionedInput::Input(3),
+                    PartitionedInput::Local(0, 0),
+                ]
+            );
+            assert_eq!(
+                partitioned.mpc_input_parties,
+                vec![
+                    IOStatus::Party(1),
+                    IOStatus::Shared,
+                    IOStatus::Public,
+                    IOStatus::Party(0),
+                ]
+            );
+            let local_result = random_evaluate(
+                partition.context.get_main_graph()?,
+                vec![Value::from_flattened_array(&[2; 100], UINT64)?],
+            )?;
+            assert_eq!(
+                local_result,
+                Value::from_vector(vec![Value::from_scalar(400, UINT64)?])
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_compile_partitioned_context() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(array_type(vec![4], INT32))?;
+            let y = g.input(array_type(vec![4], INT32))?;
+            let public = g.input(scalar_type(INT32))?;
+            let x_local = x.multiply(x.clone())?.add(public.clone())?.sum(vec![0])?;
+            let y_local = y.dot(y.clone())?;
+            x_local
+                .multiply(y_local)?
+                .add(x.multiply(y)?.sum(vec![0])?)?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let inputs = vec![
+                Value::from_flattened_array(&[1, -2, 3, 4], INT32)?,
+                Value::from_flattened_array(&[5, 6, -7, 8], INT32)?,
+                Value::from_scalar(3, INT32)?,
+            ];
+            let expected = random_evaluate(g, inputs.clone())?;
+            let input_parties = vec![IOStatus::Party(0), IOStatus::Party(2), IOStatus::Public];
+            let compiled = compile_partitioned_context(
+                c,
+                input_parties,
+                vec![IOStatus::Party(1)],
+                simple_config(),
+                || SimpleEvaluator::new(None),
+            )?;
+            let main_graph = compiled.get_main_graph()?;
+            assert!(compiled
+                .retrieve_node(main_graph.clone(), "local_part_0")
+                .is_ok());
+            assert!(compiled
+                .retrieve_node(main_graph.clone(), "local_part_2")
+                .is_ok());
+            let result = random_evaluate(main_graph, inputs)?;
+            assert_eq!(result, expected);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_partition_errors() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(scalar_type(UINT64))?;
+            x.add(x.clone())?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            assert!(partition_context(c.clone(), &[]).is_err());
+            assert!(partition_context(c, &[IOStatus::Public, IOStatus::Public]).is_err());
+
+            let c = create_context()?;
+            let callee = c.create_graph()?;
+            let y = callee.input(scalar_type(UINT64))?;
+            y.set_as_output()?;
+            callee.finalize()?;
+            let g = c.create_graph()?;
+            let x = g.input(scalar_type(UINT64))?;
+            g.call(callee, vec![x])?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            assert!(partition_context(c, &[IOStatus::Party(0)]).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
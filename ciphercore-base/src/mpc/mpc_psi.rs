@@ -1,3 +1,4 @@
+use std::cmp::max;
 use std::collections::HashMap;
 
 use crate::custom_ops::{
@@ -5,18 +6,25 @@ use crate::custom_ops::{
 };
 use crate::data_types::{
     array_type, get_size_in_bits, get_types_vector, named_tuple_type, scalar_type, tuple_type,
-    vector_type, Type, BIT, UINT64,
+    vector_type, ArrayShape, Type, BIT, UINT64,
 };
+use crate::data_values::Value;
 use crate::errors::Result;
 use crate::graphs::{create_context, Context, Graph, Node, NodeAnnotation, SliceElement};
 use crate::inline::inline_common::DepthOptimizationLevel;
 use crate::inline::inline_ops::{inline_operations, InlineConfig, InlineMode};
+use crate::io::padding::PaddingPolicy;
 use crate::ops::comparisons::Equal;
-use crate::ops::utils::{pull_out_bits, put_in_bits, zeros, zeros_like};
+use crate::ops::sorting::Sort;
+use crate::ops::utils::{
+    concatenate_first_axis, constant_scalar, multiply_by_bits, pull_out_bits, put_in_bits, zeros,
+    zeros_like,
+};
 use crate::type_inference::NULL_HEADER;
 
 use serde::{Deserialize, Serialize};
 
+use super::aes::{Aes128, AES_BLOCK_SIZE, AES_KEY_SIZE};
 use super::low_mc::{LowMC, LowMCBlockSize, LOW_MC_KEY_SIZE};
 use super::mpc_arithmetic::{AddMPC, GemmMPC, MixedMultiplyMPC, MultiplyMPC, SubtractMPC};
 use super::mpc_compiler::{check_private_tuple, compile_to_mpc_graph, KEY_LENGTH, PARTIES};
@@ -24,17 +32,21 @@ use super::utils::select_node;
 
 type ColumnHeaderTypes = Vec<(String, Type)>;
 
-const PRF_OUTPUT_SIZE: u64 = 80;
+// Name of the column containing Bloom filter bits in the approximate PSI protocol
+const BLOOM_FILTER_HEADER: &str = "bloom_filter";
 
-fn get_named_types(t: Type) -> Vec<(String, Type)> {
+fn get_named_types(t: Type) -> Result<Vec<(String, Type)>> {
     if let Type::NamedTuple(v) = t {
         let mut res = vec![];
         for (name, t) in v {
             res.push((name, (*t).clone()));
         }
-        res
+        Ok(res)
     } else {
-        panic!("Can't get named types. Input type must be NamedTuple.")
+        Err(runtime_error!(
+            "Can't get named types. Input type must be NamedTuple, got {:?}",
+            t
+        ))
     }
 }
 
@@ -56,7 +68,10 @@ fn get_column(named_tuple_shares: &[Node], header: String) -> Result<Node> {
     } else if named_tuple_shares.len() == 1 {
         named_tuple_shares[0].named_tuple_get(header)
     } else {
-        panic!("Shouldn't be here");
+        Err(runtime_error!(
+            "Database must be public or shared among {} parties",
+            PARTIES
+        ))
     }
 }
 
@@ -109,6 +124,21 @@ fn mixed_multiply_mpc(a: Node, b: Node, prf_keys: Node) -> Result<Node> {
         .custom_op(CustomOperation::new(MixedMultiplyMPC {}), args)
 }
 
+/// MPC counterpart of [multiply_by_bits].
+fn multiply_by_bits_mpc(a: Node, bits: Node, prf_keys: Node) -> Result<Node> {
+    let t = a.get_type()?;
+    let share_type = if let Type::Tuple(share_types) = &t {
+        (*share_types[0]).clone()
+    } else {
+        t
+    };
+    if share_type.get_scalar_type() == BIT {
+        multiply_mpc(a, bits, prf_keys)
+    } else {
+        mixed_multiply_mpc(a, bits, prf_keys)
+    }
+}
+
 fn add_mpc(a: Node, b: Node) -> Result<Node> {
     a.get_graph()
         .custom_op(CustomOperation::new(AddMPC {}), vec![a, b])
@@ -136,7 +166,7 @@ fn reveal_array(a: Node, party_id: u64) -> Result<Node> {
 }
 
 fn sum_named_columns(a: Node, b: Node) -> Result<Node> {
-    let header_types = get_named_types(a.get_type()?);
+    let header_types = get_named_types(a.get_type()?)?;
     let mut result_columns = vec![];
     for (header, _) in header_types {
         let c = a
@@ -148,7 +178,7 @@ fn sum_named_columns(a: Node, b: Node) -> Result<Node> {
 }
 
 fn subtract_named_columns(a: Node, b: Node) -> Result<Node> {
-    let header_types = get_named_types(a.get_type()?);
+    let header_types = get_named_types(a.get_type()?)?;
     let mut result_columns = vec![];
     for (header, _) in header_types {
         let c = a
@@ -163,7 +193,7 @@ fn pad_columns(columns: Node, num_extra_rows: u64, prf_keys: &[Node]) -> Result<
     let graph = columns.get_graph();
     let header_types = {
         let tuple_types_vec = get_types_vector(columns.get_type()?)?;
-        get_named_types((*tuple_types_vec[0]).clone())
+        get_named_types((*tuple_types_vec[0]).clone())?
     };
     let mut shares = vec![];
     for (share_id, prf_key) in prf_keys.iter().enumerate() {
@@ -171,28 +201,17 @@ fn pad_columns(columns: Node, num_extra_rows: u64, prf_keys: &[Node]) -> Result<
         let mut result_columns = vec![];
         for (header, t) in header_types.clone() {
             let column = data_share.named_tuple_get(header.clone())?;
-            let num_input_entries = t.get_shape()[0];
             let mut extra_rows_shape = t.get_shape();
             extra_rows_shape[0] = num_extra_rows;
-            let st = t.get_scalar_type();
-            let extra_rows = prf_key.prf(0, array_type(extra_rows_shape.clone(), st.clone()))?;
-            // Merge input rows and extra rows
-            let mut padded_column = graph.create_tuple(vec![
-                column.array_to_vector()?,
-                extra_rows.array_to_vector()?,
-            ])?;
-            padded_column = if extra_rows_shape.len() > 1 {
-                padded_column.reshape(vector_type(
-                    num_extra_rows + num_input_entries,
-                    array_type(extra_rows_shape[1..].to_vec(), st),
-                ))?
+            let extra_rows_t = array_type(extra_rows_shape, t.get_scalar_type());
+            // Extra rows must be empty, otherwise their random content can match other rows
+            let extra_rows = if header == NULL_HEADER {
+                zeros(&graph, extra_rows_t)?
             } else {
-                padded_column.reshape(vector_type(
-                    num_extra_rows + num_input_entries,
-                    scalar_type(st),
-                ))?
-            }
-            .vector_to_array()?;
+                prf_key.prf(0, extra_rows_t)?
+            };
+            // Merge input rows and extra rows
+            let padded_column = graph.concatenate(vec![column, extra_rows], 0)?;
             result_columns.push((header, padded_column));
         }
         let share = graph.create_named_tuple(result_columns)?;
@@ -201,6 +220,86 @@ fn pad_columns(columns: Node, num_extra_rows: u64, prf_keys: &[Node]) -> Result<
     graph.create_tuple(shares)
 }
 
+// Computes the OPRF of merged key columns of a dataset S using a block cipher with a shared key.
+// Entries with zero values in the null column are mapped to random strings, i.e.
+//
+// OPRF(S) = (PRF(key columns of S) - R) * S_null_column XOR R where R is a random matrix obliviously generated by all parties.
+//
+// Merged columns are first hashed to the block size by multiplication by a shared random matrix.
+fn compute_oprf(
+    merged_columns: Node,
+    null_column: Node,
+    prf_graph: Graph,
+    random_hash_matrix: Node,
+    oprf_key: Node,
+    prf_keys: Node,
+    prf_keys_vec: &[Node],
+) -> Result<Node> {
+    let g = merged_columns.get_graph();
+    let num_entries = match null_column.get_type()? {
+        Type::Tuple(share_types) => share_types[0].get_shape()[0],
+        t => t.get_shape()[0],
+    };
+    let hashed_columns = gemm_mpc(merged_columns, random_hash_matrix, prf_keys.clone())?;
+
+    let oprf_set = g.call(prf_graph, vec![prf_keys.clone(), hashed_columns, oprf_key])?;
+    let r = generate_shared_random_array(
+        (*get_types_vector(oprf_set.get_type()?)?[0]).clone(),
+        prf_keys_vec,
+    )?;
+    add_mpc(
+        multiply_mpc(
+            subtract_mpc(oprf_set, r.clone())?,
+            reshape_shared_array(null_column, array_type(vec![num_entries, 1], BIT))?,
+            prf_keys,
+        )?,
+        r,
+    )
+}
+
+// Converts 2-out-of-2 shares of a named tuple owned by parties 2 (share 0) and 0 (share 1) to 2-out-of-3 shares.
+fn convert_2outof2_to_2outof3_shares(shares: Node, prf_keys_vec: &[Node]) -> Result<Node> {
+    // One named tuple corresponding to one 2-out-of-2 share
+    let share_2outof2_t = (*get_types_vector(shares.get_type()?)?[0]).clone();
+    // Parties 0 and 2 generate common randomness R to mask the second share known to Party 0. The first PRF key is used since it's owned by both parties.
+    let r = prf_keys_vec[0].prf(0, share_2outof2_t)?;
+    // Party 0 computes (share 1 - R) and sends it to Party 1.
+    // This is the first share of 2-out-of-3 shares.
+    let dif = subtract_named_columns(shares.tuple_get(1)?, r.clone())?
+        .nop()?
+        .add_annotation(NodeAnnotation::Send(0, 1))?;
+    // Party 2 sends its 2-out-of-2 share to Party 1.
+    // This is the third share of 2-out-of-3 shares.
+    let last_share = shares
+        .tuple_get(0)?
+        .nop()?
+        .add_annotation(NodeAnnotation::Send(2, 1))?;
+    shares.get_graph().create_tuple(vec![r, dif, last_share])
+}
+
+// Converts 2-out-of-2 shares of a named tuple owned by parties 2 (share 0) and 1 (share 1) to 2-out-of-3 shares.
+fn convert_2outof2_to_2outof3_shares_of_parties_2_1(
+    shares: Node,
+    prf_keys_vec: &[Node],
+) -> Result<Node> {
+    let share_2outof2_t = (*get_types_vector(shares.get_type()?)?[0]).clone();
+    // Parties 1 and 2 generate common randomness R, which is the third share of 2-out-of-3 shares.
+    // The third PRF key is used since it's owned by both parties.
+    let r = prf_keys_vec[2].prf(0, share_2outof2_t)?;
+    // Party 2 computes (share 0 - R) and sends it to Party 0.
+    // This is the first share of 2-out-of-3 shares.
+    let dif = subtract_named_columns(shares.tuple_get(0)?, r.clone())?
+        .nop()?
+        .add_annotation(NodeAnnotation::Send(2, 0))?;
+    // Party 1 sends its 2-out-of-2 share to Party 0.
+    // This is the second share of 2-out-of-3 shares.
+    let second_share = shares
+        .tuple_get(1)?
+        .nop()?
+        .add_annotation(NodeAnnotation::Send(1, 0))?;
+    shares.get_graph().create_tuple(vec![dif, second_share, r])
+}
+
 fn convert_main_graph_to_mpc(
     in_context: Context,
     out_context: Context,
@@ -224,6 +323,7 @@ fn convert_main_graph_to_mpc(
         is_input_private,
         out_context,
         &mut context_map,
+        None,
     )?;
     Ok(main_mpc_g)
 }
@@ -269,6 +369,80 @@ fn get_equality_graph(
     )
 }
 
+// Creates a graph that compares entries of X with all stash entries of the Cuckoo table at once and updates the null column of the intersection.
+//
+// The stash table contains `stash_size` blocks of `num_entries` rows, where the i-th block is filled with copies of the i-th stash entry.
+// The graph returns the updated null column of the intersection and the bits selecting the rows of the stash table.
+// A row is selected if it matches the corresponding entry of X that hasn't been matched by previous Y_h or stash entries;
+// hence, at most one row is selected for every entry of X, as in the comparison of Y_h.
+fn get_stash_equality_graph(
+    context: Context,
+    stash_table_type: Type,
+    merged_key_columns_x_type: Type,
+    key_header: String,
+    stash_size: u64,
+    is_x_private: bool,
+) -> Result<Graph> {
+    let eq_context = create_context()?;
+    let g = eq_context.create_graph()?;
+
+    let stash_table = g.input(stash_table_type)?;
+    let merged_key_columns_x = g.input(merged_key_columns_x_type)?;
+
+    let key_columns_x = merged_key_columns_x.named_tuple_get(key_header.clone())?;
+    let null_x = merged_key_columns_x.named_tuple_get(NULL_HEADER.to_owned())?;
+    let key_shape_x = key_columns_x.get_type()?.get_shape();
+    let num_entries = key_shape_x[0];
+
+    let found = g.input(array_type(vec![num_entries], BIT))?;
+
+    // Split the stash table into blocks broadcast against X
+    let key_columns_stash = stash_table
+        .named_tuple_get(key_header)?
+        .reshape(array_type(
+            vec![stash_size, num_entries, key_shape_x[1]],
+            BIT,
+        ))?;
+    let null_stash = stash_table
+        .named_tuple_get(NULL_HEADER.to_owned())?
+        .reshape(array_type(vec![stash_size, num_entries], BIT))?;
+
+    // All stash entries are compared with X in parallel
+    let eq_bits = g
+        .custom_op(
+            CustomOperation::new(Equal {}),
+            vec![key_columns_stash, key_columns_x],
+        )?
+        .multiply(null_stash)?
+        .multiply(null_x)?;
+
+    // Only one bit per entry of X is propagated through stash blocks,
+    // so this loop is much cheaper than comparing stash entries one after another.
+    let one = constant_scalar(&g, 1, BIT)?;
+    let mut found = found;
+    let mut select_bits = vec![];
+    for i in 0..stash_size {
+        let eq_bits_i = eq_bits.get(vec![i])?;
+        let select_bits_i = eq_bits_i.multiply(found.add(one.clone())?)?;
+        // found OR eq_bits_i
+        found = found.add(select_bits_i.clone())?;
+        select_bits.push(select_bits_i);
+    }
+    let select_bits = g
+        .create_vector(array_type(vec![num_entries], BIT), select_bits)?
+        .vector_to_array()?
+        .reshape(array_type(vec![stash_size * num_entries], BIT))?;
+
+    g.create_tuple(vec![found, select_bits])?.set_as_output()?;
+
+    g.finalize()?;
+
+    eq_context.set_main_graph(g)?;
+    eq_context.finalize()?;
+
+    convert_main_graph_to_mpc(eq_context, context, vec![true, is_x_private, true])
+}
+
 fn get_or_graph(context: Context, num_entries: u64) -> Result<Graph> {
     let or_context = create_context()?;
     let g = or_context.create_graph()?;
@@ -319,11 +493,7 @@ fn get_select_graph(
         }
         let column_mask = mask.reshape(array_type(mask_shape, BIT))?;
         // Multiply the column by the mask
-        let result_column = if t.get_scalar_type() == BIT {
-            column.multiply(column_mask)?
-        } else {
-            column.mixed_multiply(column_mask)?
-        };
+        let result_column = multiply_by_bits(column, column_mask)?;
 
         result_columns.push((header, result_column));
     }
@@ -337,39 +507,40 @@ fn get_select_graph(
     convert_main_graph_to_mpc(select_context, context, vec![true, true])
 }
 
-fn get_lowmc_graph(context: Context, input_t: Type, key_t: Type) -> Result<Graph> {
-    let lowmc_context = create_context()?;
-    let g = lowmc_context.create_graph()?;
-
-    // Compute OPRF of hashed key columns in both sets
-    // Set the parameters of the LowMC block cipher serving here as PRF.
-    // TODO: these parameters can be further optimized with great caution.
-    // See `low_mc.rs` for guidelines.
-    let block_size = match PRF_OUTPUT_SIZE {
-        80 => LowMCBlockSize::SIZE80,
-        128 => LowMCBlockSize::SIZE128,
-        _ => {
-            panic!("LowMC doesn't support this block size");
-        }
+// Creates a graph computing the PRF of a given config on an array of hashed entries
+fn get_prf_graph(context: Context, num_entries: u64, config: &PsiConfig) -> Result<Graph> {
+    let prf_context = create_context()?;
+    let g = prf_context.create_graph()?;
+
+    // Compute OPRF of hashed key columns
+    let prf_op = match config.prf {
+        PsiPrf::LowMC {
+            block_size,
+            s_boxes_per_round,
+            rounds,
+        } => CustomOperation::new(LowMC {
+            s_boxes_per_round,
+            rounds,
+            block_size,
+        }),
+        PsiPrf::Aes128 => CustomOperation::new(Aes128),
     };
-    let low_mc_op = CustomOperation::new(LowMC {
-        s_boxes_per_round: 16,
-        rounds: 11,
-        block_size,
-    });
 
-    let input_data = g.input(input_t)?;
-    let key = g.input(key_t)?;
+    let input_data = g.input(array_type(
+        vec![num_entries, config.get_prf_output_size()],
+        BIT,
+    ))?;
+    let key = g.input(array_type(vec![config.get_prf_key_size()], BIT))?;
 
-    g.custom_op(low_mc_op, vec![input_data, key])?
+    g.custom_op(prf_op, vec![input_data, key])?
         .set_as_output()?;
 
     g.finalize()?;
 
-    lowmc_context.set_main_graph(g)?;
-    lowmc_context.finalize()?;
+    prf_context.set_main_graph(g)?;
+    prf_context.finalize()?;
 
-    convert_main_graph_to_mpc(lowmc_context, context, vec![true, true])
+    convert_main_graph_to_mpc(prf_context, context, vec![true, true])
 }
 
 // Convert key columns to binary and merge them for each input database
@@ -394,7 +565,9 @@ fn get_merging_graph(
 
     let mut bit_columns = vec![];
     for header in key_headers {
-        let t = headers_map.get(header).unwrap();
+        let t = headers_map
+            .get(header)
+            .ok_or_else(|| runtime_error!("Key column {} doesn't exist", header))?;
 
         let column = data.named_tuple_get((*header).clone())?;
         let mut bit_column = if t.get_scalar_type() != BIT {
@@ -402,7 +575,8 @@ fn get_merging_graph(
         } else {
             column
         };
-        // Flatten all the bits per entry
+        // Flatten all the bits per entry.
+        // Multi-dimensional columns, e.g. string columns, are merged as a whole row including string padding and length.
         let flattened_shape = vec![num_entries, get_size_in_bits((*t).clone())? / num_entries];
         key_entry_bitlength += flattened_shape[1];
         bit_column = bit_column.reshape(array_type(flattened_shape, BIT))?;
@@ -431,8 +605,10 @@ fn get_merging_graph(
 /// Adds a node returning the intersection of given databases along given column keys.
 ///
 /// Databases are represented as named tuples of integer arrays.
+/// Key columns can also contain fixed-length strings represented as [string columns](crate::data_types::string_column_type).
 /// Each database should contain a special binary column named "null" that contains bits indicating whether the corresponding row has a zero content after previous operations (0 if yes).
 /// Non-key column names must be unique in both databases.
+/// Key columns with missing values in some rows can be intersected by [NullableSetIntersection](crate::ops::nullable_join::NullableSetIntersection).
 ///
 /// The intersection of these named tuples is another named tuple containing the inner join of both input databases.
 /// Namely, it contains only the database rows whose values are equal in given key columns.
@@ -441,16 +617,16 @@ fn get_merging_graph(
 /// The protocol follows the description of the InnerJoin protocol from <https://eprint.iacr.org/2019/518.pdf>.
 /// Let X be the first database and Y be the second one.
 /// 1. Key columns of both sets are converted to binary and merged row-wise.
-/// 2. If the bitsize of merged entries is bigger than the block size of the LowMC block cipher, hash them via multiplication by a random matrix obliviously generated by all parties.
-/// 3. Compute the oblivious pseudo random function (OPRF) on the merged columns of both sets using the LowMC block cipher with a random key obliviously generated by all parties.
+/// 2. If the bitsize of merged entries is bigger than the block size of the block cipher, hash them via multiplication by a random matrix obliviously generated by all parties.
+/// 3. Compute the oblivious pseudo random function (OPRF) on the merged columns of both sets using the block cipher with a random key obliviously generated by all parties; the block cipher is defined by `config` (LowMC by default, see [PsiConfig]).
 /// This operation returns random string on entries with zero values in the "null" column, i.e.
 ///
 /// OPRF(S) = (PRF(key columns of S) - R) * S_null_column XOR R where R is a random matrix obliviously  generated by all parties.
 ///
 /// 4. OPRF(X) is revealed to party 2.
 /// 5. OPRF(Y) is revealed to party 1.
-/// 6. Parties 1 and 2 sample 3 hash functions that they will use for hashing using their common PRF key (key 2 in the multiplication PRF key triple).
-/// 7. Party 1 computes a Cuckoo hash map from OPRF(Y) using the above hash functions and randomizes it to a permutation.
+/// 6. Parties 1 and 2 sample hash functions that they will use for hashing using their common PRF key (key 2 in the multiplication PRF key triple); their number is defined by `config` (3 by default).
+/// 7. Party 1 computes a Cuckoo hash map from OPRF(Y) using the above hash functions and randomizes it to a permutation; the size of the hash map is defined by the expansion factor of `config`, and entries that can't be inserted are put into a stash of the size given by `config` and appended to the hash map.
 /// 8. All parties attach merged key columns of Y to Y and get Y'.
 /// 9. All parties pad Y' with obliviously sampled random strings such that the number of entries in Y' is equal to the length of the Cuckoo map created in step 7.
 /// 10. Parties 0 and 1 convert 2-out-of-3 shares of Y' to 2-out-of-2 shares.
@@ -459,15 +635,24 @@ fn get_merging_graph(
 /// 12. Party 2 computes a simple hash map of OPRF(X) using the hash functions generated in step 6.
 /// 13. For each simple hash map h, parties 2 and 1 perform the Switching protocol (SwitchingMPC) to get 2-out-of-2 shares of Y_h, which is an arrangement of several Cuckoo table elements such that elements of the intersection are located at the same positions as elements of X belonging to the intersection.
 /// As a result, Parties 2 and 0 have 2-out-of-2 shares of Y_h.
-/// 14. All parties convert the 2-out-of-2 shares of each Y_h to 2-out-of-3 shares.
+/// 14. All parties convert the 2-out-of-2 shares of each Y_h to 2-out-of-3 shares; in addition, each stash entry of the Cuckoo table is copied to a block of rows of a stash table Y_s, one row per entry of X, whose 2-out-of-2 shares are converted to 2-out-of-3 shares as well.
 /// 15. Compare X with all Y_h row-wise and select the rows of Y_h that match rows in X.
-/// The resulting "null" column has 1 entry if there is at least one Y_h, whose corresponding entry is equal to X in key columns and whose "null" column values is 1.
+///     Then, compare X with all blocks of Y_s at once and select the rows of Y_s that match rows of X not matched by any Y_h.
+///     The resulting "null" column has 1 entry if there is at least one Y_h or stash entry, whose corresponding entry is equal to X in key columns and whose "null" column values is 1.
 /// 16. Combine the selected rows along the columns of X and Y.
 ///
+/// If `mode` is [PsiMode::BloomFilter], the approximate protocol of [SetIntersectionBloomMPC] is used instead.
+///
+/// If `mode` is [PsiMode::Oblivious], the protocol of [SetIntersectionObliviousMPC] is used instead.
+/// It doesn't reveal OPRF values to parties 1 and 2 at the cost of sorting both databases in MPC.
+///
+/// If `mode` is [PsiMode::Unbalanced], the second argument is the result of [PsiPreprocessingMPC] on the second database, which already contains the results of steps 2, 3, 5-11 for this database.
+/// The remaining steps involve hashing and evaluating the block cipher only on the first database, which is much cheaper if the first database is much smaller than the second one.
+///
 /// # Custom operation arguments
 ///
 /// - a named tuple containing the first database
-/// - a named tuple containing the second database
+/// - a named tuple containing the second database (or its preprocessing in the unbalanced mode)
 /// - a tuple of PRF keys for multiplication
 ///
 /// # Custom operation returns
@@ -477,189 +662,1181 @@ fn get_merging_graph(
 pub struct SetIntersectionMPC {
     // Instead of HashMap, Vector is used to support the Hash trait
     pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub mode: PsiMode,
+    #[serde(default)]
+    pub config: PsiConfig,
+}
+
+/// Protocol used by [SetIntersectionMPC].
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub enum PsiMode {
+    /// Exact intersection based on Cuckoo hashing.
+    #[default]
+    Cuckoo,
+    /// Approximate intersection based on a secret-shared Bloom filter of the second database (see [SetIntersectionBloomMPC]).
+    BloomFilter {
+        num_hash_functions: u64,
+        bits_per_entry: u64,
+    },
+    /// Exact intersection with the second database preprocessed by [PsiPreprocessingMPC].
+    Unbalanced,
+    /// Exact intersection based on oblivious sorting (see [SetIntersectionObliviousMPC]); no party learns PRF outputs of key columns.
+    Oblivious,
+}
+
+/// Pseudo-random function (PRF) used by set intersection protocols to compute OPRF values of key columns.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub enum PsiPrf {
+    /// [LowMC] block cipher with given parameters; see [LowMC] for the parameters supporting 128-bit security.
+    LowMC {
+        block_size: LowMCBlockSize,
+        s_boxes_per_round: u64,
+        rounds: u64,
+    },
+    /// [AES-128](Aes128) block cipher; it needs much more communication than LowMC, but relies on a standard cipher.
+    Aes128,
+}
+
+/// Parameters of set intersection protocols.
+///
+/// The parameters used to preprocess a database (see [PsiPreprocessingMPC]) should be the same as those used to intersect with it.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[serde(default)]
+pub struct PsiConfig {
+    pub prf: PsiPrf,
+    /// Minimal ratio of the Cuckoo table size and the number of entries of the second database; the table size is rounded up to a power of two.
+    pub cuckoo_expansion_factor: u64,
+    /// Maximal number of entries of the second database that are put into the stash of the Cuckoo table if they can't be inserted into the table.
+    /// Each stash entry is compared with every entry of the first database.
+    pub cuckoo_stash_size: u64,
+    /// Number of hash functions of Cuckoo hashing, from 2 to 5.
+    /// More hash functions make hashing succeed with a smaller expansion factor or stash, but every hash function adds a run of the Switching protocol and a comparison with the first database.
+    pub cuckoo_num_hash_functions: u64,
+    /// Policy that the numbers of rows of all databases must comply with, so that they are revealed only up to a bucket (see [pad_database](crate::io::padding::pad_database)).
+    pub padding: PaddingPolicy,
+}
+
+impl Default for PsiConfig {
+    fn default() -> Self {
+        // TODO: these parameters can be further optimized with great caution.
+        // See `low_mc.rs` for guidelines.
+        PsiConfig {
+            prf: PsiPrf::LowMC {
+                block_size: LowMCBlockSize::SIZE80,
+                s_boxes_per_round: 16,
+                rounds: 11,
+            },
+            cuckoo_expansion_factor: 2,
+            cuckoo_stash_size: 0,
+            cuckoo_num_hash_functions: 3,
+            padding: PaddingPolicy::Exact,
+        }
+    }
+}
+
+impl PsiConfig {
+    // Returns the bit length of PRF inputs and outputs
+    fn get_prf_output_size(&self) -> u64 {
+        match self.prf {
+            PsiPrf::LowMC {
+                block_size: LowMCBlockSize::SIZE80,
+                ..
+            } => 80,
+            PsiPrf::LowMC {
+                block_size: LowMCBlockSize::SIZE128,
+                ..
+            } => 128,
+            PsiPrf::Aes128 => AES_BLOCK_SIZE,
+        }
+    }
+
+    fn get_prf_key_size(&self) -> u64 {
+        match self.prf {
+            PsiPrf::LowMC { .. } => LOW_MC_KEY_SIZE,
+            PsiPrf::Aes128 => AES_KEY_SIZE,
+        }
+    }
+
+    fn get_cuckoo_num_hash_functions(&self) -> Result<u64> {
+        if !(2..=5).contains(&self.cuckoo_num_hash_functions) {
+            return Err(runtime_error!(
+                "Number of Cuckoo hash functions must be between 2 and 5, got {}",
+                self.cuckoo_num_hash_functions
+            ));
+        }
+        Ok(self.cuckoo_num_hash_functions)
+    }
+
+    // Returns the binary logarithm of the size of the Cuckoo table (without the stash) for a given number of entries
+    fn get_log_cuckoo_table_size(&self, num_entries: u64) -> Result<u64> {
+        if self.cuckoo_expansion_factor == 0 {
+            return Err(runtime_error!("Cuckoo expansion factor must be positive"));
+        }
+        let min_table_size = num_entries
+            .checked_mul(self.cuckoo_expansion_factor)
+            .ok_or_else(|| runtime_error!("Cuckoo table is too big"))?;
+        Ok(min_table_size.next_power_of_two().trailing_zeros() as u64)
+    }
+}
+
+// Returns the bit length of one entry containing only key columns and whether some key columns are non-binary.
+fn get_key_columns_parameters(
+    column_header_types: &[(String, Type)],
+    key_headers: &[String],
+    num_entries: u64,
+) -> Result<(u64, bool)> {
+    let mut key_columns_entry_bitlength = 0;
+    let mut is_a2b_needed = false;
+    for (header, t) in column_header_types {
+        if key_headers.contains(header) {
+            key_columns_entry_bitlength += get_size_in_bits((*t).clone())? / num_entries;
+            if t.get_scalar_type() != BIT {
+                is_a2b_needed = true;
+            }
+        }
+    }
+    Ok((key_columns_entry_bitlength, is_a2b_needed))
+}
+
+// Multiplies columns of a dataset row-wise by a shared binary mask and appends them to the result shares.
+// The null column and the column with a given key header are skipped.
+fn attach_masked_columns(
+    result_shares: &mut [Vec<(String, Node)>],
+    data_shares: &[Node],
+    column_header_types: &[(String, Type)],
+    key_header: &str,
+    mask: Node,
+    prf_keys: Node,
+) -> Result<()> {
+    for (header, t) in column_header_types {
+        if header == NULL_HEADER || header == key_header {
+            continue;
+        }
+        let mut column = get_column(data_shares, header.clone())?;
+
+        let column_shape = t.get_shape();
+        // Reshape the mask to multiply row-wise
+        let mut mask_shape = vec![column_shape[0]];
+        if column_shape.len() > 1 {
+            mask_shape.extend(vec![1; column_shape.len() - 1]);
+        }
+        let column_mask = reshape_shared_array(mask.clone(), array_type(mask_shape, BIT))?;
+
+        column = multiply_by_bits_mpc(column, column_mask, prf_keys.clone())?;
+        for (share_id, share_vec) in result_shares.iter_mut().enumerate() {
+            share_vec.push(((*header).clone(), column.tuple_get(share_id as u64)?));
+        }
+    }
+    Ok(())
 }
 
 fn check_and_extract_dataset_parameters(
     t: Type,
     is_private: bool,
+    padding: &PaddingPolicy,
 ) -> Result<(u64, ColumnHeaderTypes)> {
     let column_header_types = if is_private {
         if !t.is_tuple() {
-            panic!("Private database must be a tuple of shares");
+            return Err(runtime_error!("Private database must be a tuple of shares"));
         }
 
         let t_vec = get_types_vector(t)?;
 
         check_private_tuple(t_vec.clone())?;
-        get_named_types((*t_vec[0]).clone())
+        get_named_types((*t_vec[0]).clone())?
     } else {
-        get_named_types(t)
+        get_named_types(t)?
     };
     let num_entries = column_header_types[0].1.get_shape()[0];
+    padding.check_num_rows(num_entries)?;
 
     Ok((num_entries, column_header_types))
 }
 
-#[typetag::serde]
-impl CustomOperationBody for SetIntersectionMPC {
-    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
-        if argument_types.len() == 2 {
-            if argument_types[0].is_named_tuple() && argument_types[1].is_named_tuple() {
-                let g = context.create_graph()?;
-                let set0 = g.input(argument_types[0].clone())?;
-                let set1 = g.input(argument_types[1].clone())?;
-                let mut headers = HashMap::new();
-                for (h0, h1) in &self.headers {
-                    headers.insert((*h0).clone(), (*h1).clone());
-                }
-                set0.set_intersection(set1, headers)?.set_as_output()?;
-                g.finalize()?;
-                return Ok(g);
-            } else {
-                // Panics since:
-                // - the user has no direct access to this function.
-                // - the MPC compiler should pass the correct number of arguments
-                // and this panic should never happen.
-                panic!("Inconsistency with type checker");
-            }
-        }
-        if argument_types.len() != 3 {
-            panic!("PSI protocol should have 3 inputs");
+// Headers of the named tuple returned by the preprocessing of the second database
+const HASH_MATRIX_HEADER: &str = "hash_matrix";
+const OPRF_KEY_HEADER: &str = "oprf_key";
+const HASH_FUNCTIONS_HEADER: &str = "hash_functions";
+const CUCKOO_TABLE_HEADER: &str = "cuckoo_table";
+
+// Returns the headers of the columns of a database preprocessed by PsiPreprocessingMPC in their original order
+pub(super) fn get_preprocessed_database_headers(preprocessed_t: Type) -> Result<Vec<String>> {
+    let cuckoo_table_t = get_named_types(preprocessed_t)?
+        .into_iter()
+        .find(|(header, _)| header == CUCKOO_TABLE_HEADER)
+        .map(|(_, t)| t)
+        .ok_or_else(|| runtime_error!("Preprocessed database must contain a Cuckoo table"))?;
+    // The first column of the Cuckoo table contains the merged key columns
+    Ok(
+        get_named_types((*get_types_vector(cuckoo_table_t)?[0]).clone())?
+            .into_iter()
+            .skip(1)
+            .map(|(header, _)| header)
+            .collect(),
+    )
+}
+
+fn extract_shares(data: Node, is_private: bool) -> Result<Vec<Node>> {
+    let mut shares = vec![];
+    if is_private {
+        for share_id in 0..PARTIES as u64 {
+            shares.push(data.tuple_get(share_id)?);
         }
+    } else {
+        shares.push(data);
+    }
+    Ok(shares)
+}
 
-        let data_x_t = argument_types[0].clone();
-        let data_y_t = argument_types[1].clone();
-        let prf_t = argument_types[2].clone();
+// Creates a graph performing the steps of the PSI protocol that depend only on the second database Y,
+// i.e. steps 1-3 restricted to Y and steps 5-11 of SetIntersectionMPC.
+//
+// The graph takes Y and PRF keys for multiplication and returns a named tuple containing
+// - the shared random matrix hashing merged key columns to the block size of the PRF,
+// - the shared key of the OPRF,
+// - the hash functions sampled by parties 1 and 2,
+// - the Cuckoo table of Y shared between parties 2 (share 0) and 1 (share 1).
+// The first column of the Cuckoo table contains merged key columns of Y.
+fn get_preprocessing_graph(
+    context: Context,
+    data_y_t: Type,
+    prf_t: Type,
+    key_headers_y: &[String],
+    config: &PsiConfig,
+) -> Result<Graph> {
+    let is_y_private = data_y_t.is_tuple();
+    let (num_entries_y, column_header_types_y) =
+        check_and_extract_dataset_parameters(data_y_t.clone(), is_y_private, &config.padding)?;
+
+    // Name of the "key" column containing bits of compared columns
+    // To avoid a collision with the headers of Y, the key header is the join of all these headers
+    let key_header = column_header_types_y
+        .iter()
+        .map(|v| v.0.clone())
+        .collect::<Vec<String>>()
+        .join("-");
+
+    // Compute the bit length of one entry containing only key columns.
+    // In addition, checks whether non-binary key columns are present.
+    // This defines whether the merging graph below needs PRF keys.
+    let (key_columns_entry_bitlength, is_a2b_needed) =
+        get_key_columns_parameters(&column_header_types_y, key_headers_y, num_entries_y)?;
+    // Graph that merges the key columns of the dataset Y
+    let merging_g_y = get_merging_graph(
+        context.clone(),
+        column_header_types_y.clone(),
+        key_headers_y,
+        is_y_private,
+    )?;
+    // Graph that computes the PRF on the dataset Y
+    let prf_g_y = get_prf_graph(context.clone(), num_entries_y, config)?;
 
-        let is_x_private = data_x_t.is_tuple();
-        let is_y_private = data_y_t.is_tuple();
+    let g = context.create_graph()?;
 
-        let (num_entries_x, column_header_types_x) =
-            check_and_extract_dataset_parameters(data_x_t.clone(), is_x_private)?;
-        let (num_entries_y, column_header_types_y) =
-            check_and_extract_dataset_parameters(data_y_t.clone(), is_y_private)?;
+    let data_y = g.input(data_y_t)?;
+    let prf_keys = g.input(prf_t)?;
 
-        // Name of the "key" column containing bits of compared columns
-        // To avoid a collision with input headers, the key header is the join of all input headers
-        let mut all_headers: Vec<String> = column_header_types_x
-            .iter()
-            .map(|v| ((*v).0).clone())
-            .collect();
-        let headers_y: Vec<String> = column_header_types_y
-            .iter()
-            .map(|v| ((*v).0).clone())
-            .collect();
-        all_headers.extend(headers_y);
-        let key_header = all_headers.join("-");
+    let data_y_shares = extract_shares(data_y.clone(), is_y_private)?;
+    let prf_keys_vec = extract_shares(prf_keys.clone(), true)?;
 
-        let mut key_headers_x = vec![];
-        let mut key_headers_y = vec![];
-        for (h_x, h_y) in &self.headers {
-            key_headers_x.push((*h_x).clone());
-            key_headers_y.push((*h_y).clone());
-        }
+    // 1. Key columns of Y are converted to binary and merged row-wise.
+    g.push_region("step 1")?;
+    let merged_columns_y = g.call(
+        merging_g_y,
+        if is_y_private && is_a2b_needed {
+            vec![prf_keys.clone(), data_y]
+        } else {
+            vec![data_y]
+        },
+    )?;
+    g.pop_region()?;
 
-        // Compute the bit length of one entry containing only key columns.
-        // This value is the same for both input sets.
-        // In addition, checks whether non-binary key columns are present.
-        // This defines the merging graphs below need PRF keys.
-        let mut key_columns_entry_bitlength = 0;
-        let mut is_a2b_needed = false;
-        for (header, t) in &column_header_types_x {
-            if key_headers_x.contains(header) {
-                let column_entry_bitlength = get_size_in_bits((*t).clone())? / num_entries_x;
-                key_columns_entry_bitlength += column_entry_bitlength;
-                if t.get_scalar_type() != BIT {
-                    is_a2b_needed = true;
-                }
+    // 2. If the bitsize of merged entries is bigger than the block size of the block cipher, hash them via multiplication by a random matrix obliviously generated by all parties.
+    g.push_region("step 2")?;
+    let random_hash_matrix = generate_shared_random_array(
+        array_type(
+            vec![config.get_prf_output_size(), key_columns_entry_bitlength],
+            BIT,
+        ),
+        &prf_keys_vec,
+    )?;
+    g.pop_region()?;
+
+    // 3. Compute OPRF(Y) = PRF(key columns of Y) * Y_null_column XOR R_Y * ~Y_null_column where R_Y is a random matrix generated by all parties
+    g.push_region("step 3")?;
+    let oprf_key = generate_shared_random_array(
+        array_type(vec![config.get_prf_key_size()], BIT),
+        &prf_keys_vec,
+    )?;
+    let null_y = get_column(&data_y_shares, NULL_HEADER.to_owned())?;
+    let oprf_set_y = compute_oprf(
+        merged_columns_y.clone(),
+        null_y,
+        prf_g_y,
+        random_hash_matrix.clone(),
+        oprf_key.clone(),
+        prf_keys.clone(),
+        &prf_keys_vec,
+    )?;
+    g.pop_region()?;
+
+    // 5. Reveal OPRF(Y) to party 1
+    g.push_region("step 5")?;
+    let revealed_oprf_set_y = reveal_array(oprf_set_y, 1)?;
+    g.pop_region()?;
+
+    // 6. Parties 1 and 2 generate random matrices for hashing of shape [h, m, PRF output size],
+    // where h is the number of hash functions of `config` and m = ceil(log(num_entries_y * expansion_factor)).
+    // TODO: quantify probability of success of Cuckoo hashing with these parameters
+    g.push_region("step 6")?;
+    let log_num_cuckoo_entries = config.get_log_cuckoo_table_size(num_entries_y)?;
+    let num_hash_functions = config.get_cuckoo_num_hash_functions()?;
+    let hash_matrices = prf_keys_vec[2].prf(
+        0,
+        array_type(
+            vec![
+                num_hash_functions,
+                log_num_cuckoo_entries,
+                config.get_prf_output_size(),
+            ],
+            BIT,
+        ),
+    )?;
+    g.pop_region()?;
+
+    // 7. Party 1 computes a Cuckoo hash map with a stash from OPRF(Y) and randomizes it to a permutation
+    g.push_region("step 7")?;
+    let cuckoo_map = revealed_oprf_set_y
+        .cuckoo_hash_with_stash(hash_matrices.clone(), config.cuckoo_stash_size)?;
+    let cuckoo_permutation = cuckoo_map.cuckoo_to_permutation()?;
+    g.pop_region()?;
+
+    // 8. Attach the merged key columns to Y
+    // HACK: If Y is public, we create fake shares containing zeros such that the next operation generating random padding can accept it
+    g.push_region("step 8")?;
+    let extended_shares_y = if is_y_private {
+        let mut res = vec![];
+        for (share_id, share) in data_y_shares.iter().enumerate() {
+            let mut columns_vec = vec![(
+                key_header.clone(),
+                merged_columns_y.tuple_get(share_id as u64)?,
+            )];
+            for (header, _) in &column_header_types_y {
+                let column = share.named_tuple_get((*header).clone())?;
+                columns_vec.push(((*header).clone(), column));
             }
+            let share = g.create_named_tuple(columns_vec)?;
+            res.push(share);
         }
-        let prf_needed_to_merge_x = is_x_private && is_a2b_needed;
-        let prf_needed_to_merge_y = is_y_private && is_a2b_needed;
-        // Graph that merges the key columns of the dataset X
-        let merging_g_x = get_merging_graph(
-            context.clone(),
-            column_header_types_x.clone(),
-            &key_headers_x,
-            is_x_private,
-        )?;
-        // Graph that merges the key columns of the dataset Y
-        let merging_g_y = get_merging_graph(
-            context.clone(),
-            column_header_types_y.clone(),
-            &key_headers_y,
-            is_y_private,
-        )?;
+        g.create_tuple(res)?
+    } else {
+        let mut columns_vec = vec![(key_header.clone(), merged_columns_y)];
+        for (header, _) in &column_header_types_y {
+            let column = data_y_shares[0].named_tuple_get((*header).clone())?;
+            columns_vec.push(((*header).clone(), column));
+        }
+        let first_share = g.create_named_tuple(columns_vec)?;
+        let zero_share = zeros_like(first_share.clone())?;
+        g.create_tuple(vec![first_share, zero_share.clone(), zero_share])?
+    };
+    g.pop_region()?;
+
+    // 9. Pad columns of Y with random data such that the number of entries is equal to the cuckoo table size including the stash
+    g.push_region("step 9")?;
+    let padded_shares_y = {
+        let num_extra_rows =
+            (1 << log_num_cuckoo_entries) + config.cuckoo_stash_size - num_entries_y;
+        pad_columns(extended_shares_y, num_extra_rows, &prf_keys_vec)?
+    };
+    g.pop_region()?;
+
+    // 10. Switch from 2-out-of-3 shares of dataset Y to 2-out-of-2 shares owned by parties 0 and 1
+    g.push_region("step 10")?;
+    let data_y_2of2shares = {
+        // Share of party 0 is the sum of its 2-out-of-3 shares
+        let party0_share =
+            sum_named_columns(padded_shares_y.tuple_get(0)?, padded_shares_y.tuple_get(1)?)?;
+        // Share of party 1 is the third 2-out-of-3 share
+        // Share of party 1 goes first to support the contract of the consecutive PermutationMPC operation, which demands that the first share and a permutation is owned by the same party.
+        g.create_tuple(vec![padded_shares_y.tuple_get(2)?, party0_share])?
+    };
+    g.pop_region()?;
+
+    // 11. Create a Cuckoo table of Y by applying the above Cuckoo permutation to the shares of Y.
+    // The Cuckoo table will be shared between parties 1 (share 0) and 2 (share 1).
+    g.push_region("step 11")?;
+    let cuckoo_table = g.custom_op(
+        CustomOperation::new(PermutationMPC {
+            programmer_id: 1,
+            sender_id: 0,
+        }),
+        vec![data_y_2of2shares, cuckoo_permutation, prf_keys],
+    )?;
 
-        // Graph that computes LowMC on the dataset X
-        let lowmc_g_x = get_lowmc_graph(
-            context.clone(),
-            array_type(vec![num_entries_x, PRF_OUTPUT_SIZE], BIT),
-            array_type(vec![LOW_MC_KEY_SIZE], BIT),
-        )?;
-        // Graph that computes LowMC on the dataset Y
-        let lowmc_g_y = get_lowmc_graph(
-            context.clone(),
-            array_type(vec![num_entries_y, PRF_OUTPUT_SIZE], BIT),
-            array_type(vec![LOW_MC_KEY_SIZE], BIT),
-        )?;
-        // Graph that compares null and merged key columns of X and compatible datasets created from Y containing, in addition, merged key columns of Y (Y_h)
-        let mut y_h_types = vec![(
+    // Repack the Cuckoo table such that party 2 has share 0 and party has share 1
+    // This is necessary by the contract of SwitchingMPC that requires the first share to be given by Programmer (party 2 having the switching map)
+    let cuckoo_table =
+        g.create_tuple(vec![cuckoo_table.tuple_get(1)?, cuckoo_table.tuple_get(0)?])?;
+
+    g.create_named_tuple(vec![
+        (HASH_MATRIX_HEADER.to_owned(), random_hash_matrix),
+        (OPRF_KEY_HEADER.to_owned(), oprf_key),
+        (HASH_FUNCTIONS_HEADER.to_owned(), hash_matrices),
+        (CUCKOO_TABLE_HEADER.to_owned(), cuckoo_table),
+    ])?
+    .set_as_output()?;
+
+    g.pop_region()?;
+    g.finalize()?;
+    Ok(g)
+}
+
+// Creates a graph performing the steps of the PSI protocol that involve the first database X,
+// i.e. steps 1-4 restricted to X and steps 12-16 of SetIntersectionMPC.
+//
+// The graph takes X, the result of the preprocessing graph of the second database Y and PRF keys for multiplication.
+// It returns the inner join of X and Y.
+fn get_online_graph(
+    context: Context,
+    data_x_t: Type,
+    preprocessed_y_t: Type,
+    prf_t: Type,
+    headers: &[(String, String)],
+    config: &PsiConfig,
+) -> Result<Graph> {
+    let is_x_private = data_x_t.is_tuple();
+    let (num_entries_x, column_header_types_x) =
+        check_and_extract_dataset_parameters(data_x_t.clone(), is_x_private, &config.padding)?;
+
+    let mut key_headers_x = vec![];
+    let mut key_headers_y = vec![];
+    for (h_x, h_y) in headers {
+        key_headers_x.push((*h_x).clone());
+        key_headers_y.push((*h_y).clone());
+    }
+
+    // Extract the parameters of the Cuckoo table of Y.
+    // Its first column contains the merged key columns of Y, the other ones are the columns of Y.
+    let preprocessed_y_types: HashMap<String, Type> = get_named_types(preprocessed_y_t.clone())?
+        .into_iter()
+        .collect();
+    let (cuckoo_table_t, hash_matrices_t) = match (
+        preprocessed_y_types.get(CUCKOO_TABLE_HEADER),
+        preprocessed_y_types.get(HASH_FUNCTIONS_HEADER),
+    ) {
+        (Some(table_t), Some(hash_t)) => (table_t.clone(), hash_t.clone()),
+        _ => {
+            return Err(runtime_error!(
+                "Preprocessed database must contain a Cuckoo table and hash functions"
+            ));
+        }
+    };
+    let mut column_header_types_y =
+        get_named_types((*get_types_vector(cuckoo_table_t)?[0]).clone())?;
+    let (key_header, key_t) = column_header_types_y.remove(0);
+    let num_hash_functions = hash_matrices_t.get_shape()[0];
+    if hash_matrices_t.get_shape()[2] != config.get_prf_output_size() {
+        return Err(runtime_error!(
+            "Preprocessed database was computed with another PRF"
+        ));
+    }
+    // The stash is located after the hash map in the Cuckoo table
+    let hash_map_size = 1 << hash_matrices_t.get_shape()[1];
+    let num_cuckoo_table_entries = key_t.get_shape()[0];
+    if num_cuckoo_table_entries < hash_map_size {
+        return Err(runtime_error!(
+            "Cuckoo table of the preprocessed database is too small"
+        ));
+    }
+    let stash_size = num_cuckoo_table_entries - hash_map_size;
+
+    let (key_columns_entry_bitlength, is_a2b_needed) =
+        get_key_columns_parameters(&column_header_types_x, &key_headers_x, num_entries_x)?;
+    if key_t.get_shape()[1] != key_columns_entry_bitlength {
+        return Err(runtime_error!(
+            "Key columns of the first database are incompatible with the preprocessed database"
+        ));
+    }
+    if column_header_types_x.iter().any(|(h, _)| *h == key_header) {
+        return Err(runtime_error!(
+            "Column header {} of the first database is reserved",
+            key_header
+        ));
+    }
+
+    // Graph that merges the key columns of the dataset X
+    let merging_g_x = get_merging_graph(
+        context.clone(),
+        column_header_types_x.clone(),
+        &key_headers_x,
+        is_x_private,
+    )?;
+    // Graph that computes the PRF on the dataset X
+    let prf_g_x = get_prf_graph(context.clone(), num_entries_x, config)?;
+    // Graph that compares null and merged key columns of X and compatible datasets created from Y containing, in addition, merged key columns of Y (Y_h)
+    let mut y_h_types = vec![(
+        key_header.clone(),
+        array_type(vec![num_entries_x, key_columns_entry_bitlength], BIT),
+    )];
+    for (header, t) in &column_header_types_y {
+        let mut column_shape = t.get_shape();
+        column_shape[0] = num_entries_x;
+        y_h_types.push((
+            (*header).clone(),
+            array_type(column_shape, t.get_scalar_type()),
+        ))
+    }
+    let y_h_type = named_tuple_type(y_h_types.clone());
+    let merged_key_columns_x_type = named_tuple_type(vec![
+        (NULL_HEADER.to_owned(), array_type(vec![num_entries_x], BIT)),
+        (
             key_header.clone(),
             array_type(vec![num_entries_x, key_columns_entry_bitlength], BIT),
-        )];
-        for (header, t) in &column_header_types_y {
-            let mut column_shape = t.get_shape();
-            column_shape[0] = num_entries_x;
-            y_h_types.push((
-                (*header).clone(),
-                array_type(column_shape, t.get_scalar_type()),
-            ))
-        }
-        let y_h_type = named_tuple_type(y_h_types.clone());
-        let merged_key_columns_x_type = named_tuple_type(vec![
-            (NULL_HEADER.to_owned(), array_type(vec![num_entries_x], BIT)),
-            (
-                key_header.clone(),
-                array_type(vec![num_entries_x, key_columns_entry_bitlength], BIT),
-            ),
-        ]);
-        let eq_g = get_equality_graph(
+        ),
+    ]);
+    let eq_g = get_equality_graph(
+        context.clone(),
+        y_h_type,
+        merged_key_columns_x_type.clone(),
+        key_header.clone(),
+        true,
+        is_x_private,
+    )?;
+    // Graph that computes OR of bit columns
+    let or_g = get_or_graph(context.clone(), num_entries_x)?;
+    // Graph that selects rows of Y_h according to the given mask
+    let select_g_y = get_select_graph(
+        context.clone(),
+        y_h_types.clone(),
+        num_entries_x,
+        key_header.clone(),
+    )?;
+    // Graphs that compare X with the table of stash entries and select its rows.
+    // The stash table consists of blocks of rows of Y_h, one block per stash entry.
+    let stash_graphs = if stash_size > 0 {
+        let num_stash_table_entries = stash_size * num_entries_x;
+        let stash_table_types: Vec<(String, Type)> = y_h_types
+            .iter()
+            .map(|(header, t)| {
+                let mut column_shape = t.get_shape();
+                column_shape[0] = num_stash_table_entries;
+                (
+                    header.clone(),
+                    array_type(column_shape, t.get_scalar_type()),
+                )
+            })
+            .collect();
+        let stash_eq_g = get_stash_equality_graph(
             context.clone(),
-            y_h_type,
+            named_tuple_type(stash_table_types.clone()),
             merged_key_columns_x_type,
             key_header.clone(),
-            true,
+            stash_size,
             is_x_private,
         )?;
-        // Graph that computes OR of bit columns
-        let or_g = get_or_graph(context.clone(), num_entries_x)?;
-        // Graph that selects rows of Y_h according to the given mask
-        let select_g_y = get_select_graph(
+        let stash_select_g = get_select_graph(
             context.clone(),
-            y_h_types,
-            num_entries_x,
+            stash_table_types,
+            num_stash_table_entries,
             key_header.clone(),
         )?;
+        Some((stash_eq_g, stash_select_g))
+    } else {
+        None
+    };
 
-        // Main graph computing PSI
-        let g = context.create_graph()?;
+    let g = context.create_graph()?;
 
-        let data_x = g.input(data_x_t)?;
-        let data_y = g.input(data_y_t)?;
-        let prf_keys = g.input(prf_t)?;
+    let data_x = g.input(data_x_t)?;
+    let preprocessed_y = g.input(preprocessed_y_t)?;
+    let prf_keys = g.input(prf_t)?;
 
-        // Extract input shares
-        let mut data_x_shares = vec![];
-        let mut data_y_shares = vec![];
-        if is_x_private {
-            for share_id in 0..PARTIES as u64 {
-                data_x_shares.push(data_x.tuple_get(share_id)?);
-            }
+    let data_x_shares = extract_shares(data_x.clone(), is_x_private)?;
+    let prf_keys_vec = extract_shares(prf_keys.clone(), true)?;
+
+    // 1. Key columns of X are converted to binary and merged row-wise.
+    g.push_region("step 1")?;
+    let merged_columns_x = g.call(
+        merging_g_x,
+        if is_x_private && is_a2b_needed {
+            vec![prf_keys.clone(), data_x]
         } else {
-            data_x_shares.push(data_x.clone());
+            vec![data_x]
+        },
+    )?;
+    g.pop_region()?;
+
+    // 2-3. Compute OPRF(X) = (PRF(key columns of X) - R_X) * X_null_column XOR R_X using the hash matrix and the PRF key of the preprocessing
+    g.push_region("step 2-3")?;
+    let null_x = get_column(&data_x_shares, NULL_HEADER.to_owned())?;
+    let oprf_set_x = compute_oprf(
+        merged_columns_x.clone(),
+        null_x.clone(),
+        prf_g_x,
+        preprocessed_y.named_tuple_get(HASH_MATRIX_HEADER.to_owned())?,
+        preprocessed_y.named_tuple_get(OPRF_KEY_HEADER.to_owned())?,
+        prf_keys.clone(),
+        &prf_keys_vec,
+    )?;
+    g.pop_region()?;
+
+    // 4. Reveal OPRF(X) to party 2
+    g.push_region("step 4")?;
+    let revealed_oprf_set_x = reveal_array(oprf_set_x, 2)?;
+    g.pop_region()?;
+
+    // 12. Party 2 computes a simple hash map from OPRF(X) for each of the hash functions
+    g.push_region("step 12")?;
+    let simple_hash_map = g.custom_op(
+        CustomOperation::new(SimpleHash {}),
+        vec![
+            revealed_oprf_set_x,
+            preprocessed_y.named_tuple_get(HASH_FUNCTIONS_HEADER.to_owned())?,
+        ],
+    )?;
+    g.pop_region()?;
+
+    // 13. For each simple hash map h, parties 2 and 1 perform the switching protocol to get 2-out-of-2 shares of Y_h, which is an arrangement of several Cuckoo table elements such that elements of the intersection are located at the same positions as elements of X belonging to the intersection.
+    // As a result, Parties 2 and 0 have 2-out-of-2 shares of Y_h
+    g.push_region("step 13")?;
+    let cuckoo_table = preprocessed_y.named_tuple_get(CUCKOO_TABLE_HEADER.to_owned())?;
+    let mut all_y_h = vec![];
+    for h in 0..num_hash_functions {
+        let switch_map = simple_hash_map.get(vec![h])?;
+        let switched_cuckoo = g.custom_op(
+            CustomOperation::new(SwitchingMPC {
+                sender_id: 1,
+                programmer_id: 2,
+            }),
+            vec![cuckoo_table.clone(), switch_map, prf_keys.clone()],
+        )?;
+        all_y_h.push(switched_cuckoo);
+    }
+    g.pop_region()?;
+
+    // 14. Convert the 2-out-of-2 shares of Y_h to 2-out-of-3 shares
+    g.push_region("step 14")?;
+    let mut y_h_shares = vec![];
+    for y_h in all_y_h {
+        y_h_shares.push(convert_2outof2_to_2outof3_shares(y_h, &prf_keys_vec)?);
+    }
+    // Each stash entry of the Cuckoo table is copied to a block of rows of the stash table, whose length is equal to the number of entries of X.
+    // This is done locally by parties 2 and 1 that own the shares of the Cuckoo table.
+    let stash_table = if stash_size > 0 {
+        let mut stash_indices = vec![];
+        for stash_i in 0..stash_size {
+            stash_indices.push(g.constant(
+                array_type(vec![num_entries_x], UINT64),
+                Value::from_flattened_array(
+                    &vec![hash_map_size + stash_i; num_entries_x as usize],
+                    UINT64,
+                )?,
+            )?);
         }
-        if is_y_private {
+        let cuckoo_table_headers: Vec<String> =
+            get_named_types((*get_types_vector(cuckoo_table.get_type()?)?[0]).clone())?
+                .into_iter()
+                .map(|(header, _)| header)
+                .collect();
+        let mut stash_table_shares = vec![];
+        for share_id in 0..2 {
+            let share = cuckoo_table.tuple_get(share_id)?;
+            let mut columns = vec![];
+            for header in &cuckoo_table_headers {
+                let column = share.named_tuple_get(header.clone())?;
+                let mut blocks = vec![];
+                for indices in &stash_indices {
+                    blocks.push(column.gather(indices.clone(), 0)?);
+                }
+                columns.push((header.clone(), concatenate_first_axis(blocks)?));
+            }
+            stash_table_shares.push(g.create_named_tuple(columns)?);
+        }
+        Some(convert_2outof2_to_2outof3_shares_of_parties_2_1(
+            g.create_tuple(stash_table_shares)?,
+            &prf_keys_vec,
+        )?)
+    } else {
+        None
+    };
+    g.pop_region()?;
+
+    // 15. Compare X with all Y_h and Y_s and select the rows of Y_h and Y_s that match rows in X.
+    // The resulting null column has 1 entry if there is at least one Y_h or stash entry, whose corresponding entry is equal to X in key columns.
+    g.push_region("step 15")?;
+
+    // Attach the null column to the merged key columns of X.
+    let null_merged_columns_x_shares = if is_x_private {
+        let mut res = vec![];
+        for share_id in 0..PARTIES as u64 {
+            let share = g.create_named_tuple(vec![
+                (NULL_HEADER.to_owned(), null_x.tuple_get(share_id)?),
+                (key_header.clone(), merged_columns_x.tuple_get(share_id)?),
+            ])?;
+            res.push(share);
+        }
+        g.create_tuple(res)?
+    } else {
+        g.create_named_tuple(vec![
+            (NULL_HEADER.to_owned(), null_x),
+            (key_header.clone(), merged_columns_x),
+        ])?
+    };
+
+    let mut res_null_column = g.call(
+        eq_g.clone(),
+        vec![
+            prf_keys.clone(),
+            y_h_shares[0].clone(),
+            null_merged_columns_x_shares.clone(),
+        ],
+    )?;
+    let mut selected_columns_y = g.call(
+        select_g_y.clone(),
+        vec![
+            prf_keys.clone(),
+            y_h_shares[0].clone(),
+            res_null_column.clone(),
+        ],
+    )?;
+    for shares in y_h_shares.iter().skip(1) {
+        // Compare elements of Y_h and X
+        let eq_bits = g.call(
+            eq_g.clone(),
+            vec![
+                prf_keys.clone(),
+                (*shares).clone(),
+                null_merged_columns_x_shares.clone(),
+            ],
+        )?;
+        // Compute selection bits.
+        // Selection bits must satisfy the following rules:
+        // - if the current null column entry is 0 (the corresponding entry of X hasn't been matched) and the corresponding equality bits is 1 (matching occurred in this iteration), then the corresponding selection bit should be 1;
+        // - in other cases, the selection bit must be 0.
+        // This can be computed as select_bit = eq_bit AND null_column_bit XOR eq_bit.
+        let select_bits = add_mpc(
+            multiply_mpc(eq_bits.clone(), res_null_column.clone(), prf_keys.clone())?,
+            eq_bits.clone(),
+        )?;
+        // Select rows of Y_h
+        let selected_rows = g.call(
+            select_g_y.clone(),
+            vec![prf_keys.clone(), (*shares).clone(), select_bits],
+        )?;
+        // Sum named tuples
+        selected_columns_y = {
+            let mut columns_shares = vec![];
+            for share_id in 0..PARTIES as u64 {
+                let share = sum_named_columns(
+                    selected_rows.tuple_get(share_id)?,
+                    selected_columns_y.tuple_get(share_id)?,
+                )?;
+                columns_shares.push(share);
+            }
+            g.create_tuple(columns_shares)?
+        };
+        // OR equality bits
+        res_null_column = g.call(
+            or_g.clone(),
+            vec![prf_keys.clone(), res_null_column.clone(), eq_bits],
+        )?;
+    }
+    if let (Some(stash_table), Some((stash_eq_g, stash_select_g))) = (stash_table, stash_graphs) {
+        // Compare X with all stash entries at once.
+        // The stash table is compared with the entries of X that haven't been matched by Y_h.
+        let stash_result = g.call(
+            stash_eq_g,
+            vec![
+                prf_keys.clone(),
+                stash_table.clone(),
+                null_merged_columns_x_shares,
+                res_null_column,
+            ],
+        )?;
+        let get_stash_result_element = |index: u64| -> Result<Node> {
+            let mut shares = vec![];
+            for share_id in 0..PARTIES as u64 {
+                shares.push(stash_result.tuple_get(share_id)?.tuple_get(index)?);
+            }
+            g.create_tuple(shares)
+        };
+        res_null_column = get_stash_result_element(0)?;
+        let stash_select_bits = get_stash_result_element(1)?;
+        let selected_stash_rows = g.call(
+            stash_select_g,
+            vec![prf_keys.clone(), stash_table, stash_select_bits],
+        )?;
+        // At most one row is selected in every column of stash blocks, so the selected rows are summed over blocks
+        let selected_stash_header_types =
+            get_named_types((*get_types_vector(selected_stash_rows.get_type()?)?[0]).clone())?;
+        selected_columns_y = {
+            let mut columns_shares = vec![];
+            for share_id in 0..PARTIES as u64 {
+                let selected_rows_share = selected_stash_rows.tuple_get(share_id)?;
+                let mut summed_columns = vec![];
+                for (header, t) in &selected_stash_header_types {
+                    let mut blocks_shape = vec![stash_size, num_entries_x];
+                    blocks_shape.extend_from_slice(&t.get_shape()[1..]);
+                    let summed_column = selected_rows_share
+                        .named_tuple_get(header.clone())?
+                        .reshape(array_type(blocks_shape, t.get_scalar_type()))?
+                        .sum(vec![0])?;
+                    summed_columns.push((header.clone(), summed_column));
+                }
+                let share = sum_named_columns(
+                    g.create_named_tuple(summed_columns)?,
+                    selected_columns_y.tuple_get(share_id)?,
+                )?;
+                columns_shares.push(share);
+            }
+            g.create_tuple(columns_shares)?
+        };
+    }
+    g.pop_region()?;
+
+    // 16. Combine the selected rows along the columns of X and Y
+    g.push_region("step 16")?;
+    let mut res_named_tuple_vec = vec![];
+    for share_id in 0..PARTIES as u64 {
+        res_named_tuple_vec.push(vec![(
+            NULL_HEADER.to_owned(),
+            res_null_column.tuple_get(share_id)?,
+        )]);
+    }
+    // Multiply columns of X by the intersection null column
+    attach_masked_columns(
+        &mut res_named_tuple_vec,
+        &data_x_shares,
+        &column_header_types_x,
+        &key_header,
+        res_null_column,
+        prf_keys,
+    )?;
+    // Attach selected rows of Y
+    for (header, _) in &column_header_types_y {
+        // If the current column has been already attached to the result, ignore it
+        if key_headers_y.contains(header) || NULL_HEADER == header {
+            continue;
+        }
+        for (share_id, share_vec) in res_named_tuple_vec.iter_mut().enumerate() {
+            share_vec.push((
+                (*header).clone(),
+                selected_columns_y
+                    .tuple_get(share_id as u64)?
+                    .named_tuple_get((*header).clone())?,
+            ));
+        }
+    }
+
+    let mut result_shares = vec![];
+    for share_vec in res_named_tuple_vec {
+        let share = g.create_named_tuple(share_vec)?;
+        result_shares.push(share);
+    }
+    let result = g.create_tuple(result_shares)?;
+    result.set_as_output()?;
+
+    g.pop_region()?;
+    g.finalize()?;
+    Ok(g)
+}
+
+#[typetag::serde]
+impl CustomOperationBody for SetIntersectionMPC {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() == 2 {
+            if argument_types[0].is_named_tuple() && argument_types[1].is_named_tuple() {
+                let g = context.create_graph()?;
+                let set0 = g.input(argument_types[0].clone())?;
+                let set1 = g.input(argument_types[1].clone())?;
+                let mut headers = HashMap::new();
+                for (h0, h1) in &self.headers {
+                    headers.insert((*h0).clone(), (*h1).clone());
+                }
+                set0.set_intersection(set1, headers)?.set_as_output()?;
+                g.finalize()?;
+                return Ok(g);
+            } else {
+                return Err(runtime_error!(
+                    "Public databases must be named tuples, got {:?} and {:?}",
+                    argument_types[0],
+                    argument_types[1]
+                ));
+            }
+        }
+        if argument_types.len() != 3 {
+            return Err(runtime_error!("PSI protocol should have 3 inputs"));
+        }
+        match self.mode {
+            PsiMode::BloomFilter {
+                num_hash_functions,
+                bits_per_entry,
+            } => {
+                return SetIntersectionBloomMPC {
+                    headers: self.headers.clone(),
+                    num_hash_functions,
+                    bits_per_entry,
+                    config: self.config.clone(),
+                }
+                .instantiate(context, argument_types);
+            }
+            PsiMode::Unbalanced => {
+                // The second argument is the preprocessed second database
+                return get_online_graph(
+                    context,
+                    argument_types[0].clone(),
+                    argument_types[1].clone(),
+                    argument_types[2].clone(),
+                    &self.headers,
+                    &self.config,
+                );
+            }
+            PsiMode::Oblivious => {
+                return SetIntersectionObliviousMPC {
+                    headers: self.headers.clone(),
+                    config: self.config.clone(),
+                }
+                .instantiate(context, argument_types);
+            }
+            PsiMode::Cuckoo => {}
+        }
+
+        let data_x_t = argument_types[0].clone();
+        let data_y_t = argument_types[1].clone();
+        let prf_t = argument_types[2].clone();
+
+        let key_headers_y: Vec<String> = self.headers.iter().map(|(_, h_y)| h_y.clone()).collect();
+
+        // Graph that performs the steps depending only on Y
+        let preprocessing_g = get_preprocessing_graph(
+            context.clone(),
+            data_y_t.clone(),
+            prf_t.clone(),
+            &key_headers_y,
+            &self.config,
+        )?;
+        // Graph that performs the remaining steps
+        let online_g = get_online_graph(
+            context.clone(),
+            data_x_t.clone(),
+            preprocessing_g.get_output_node()?.get_type()?,
+            prf_t.clone(),
+            &self.headers,
+            &self.config,
+        )?;
+
+        // Main graph computing PSI
+        let g = context.create_graph()?;
+
+        let data_x = g.input(data_x_t)?;
+        let data_y = g.input(data_y_t)?;
+        let prf_keys = g.input(prf_t)?;
+
+        let preprocessed_y = g.call(preprocessing_g, vec![data_y, prf_keys.clone()])?;
+        g.call(online_g, vec![data_x, preprocessed_y, prf_keys])?
+            .set_as_output()?;
+
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!("PSI(keys:{:?})", self.headers)
+    }
+}
+
+register_custom_operation!(
+    SetIntersectionMPC,
+    [NamedTuple, NamedTuple],
+    [Shared, Shared, PrfKeys]
+);
+
+/// Adds a node preprocessing a database for the unbalanced mode of [SetIntersectionMPC].
+///
+/// The preprocessing performs the steps of [SetIntersectionMPC] that depend only on the second database Y, whose cost grows with the size of Y.
+/// Namely, parties sample a random hash matrix and a LowMC key, compute OPRF(Y), reveal it to party 1 and create a Cuckoo table of Y shared between parties 2 and 1 (steps 1-3 restricted to Y and steps 5-11).
+/// The result can be passed to several intersections with Y in [PsiMode::Unbalanced], so these steps are performed only once.
+/// It can also be stored as [PreprocessedDatabase](crate::mpc::preprocessing::PreprocessedDatabase) to be reused across computations.
+///
+/// **WARNING**: all these intersections reveal OPRF values of the first databases to party 2 using the same OPRF key.
+/// Thus, party 2 learns which rows of different first databases have equal keys.
+///
+/// # Custom operation arguments
+///
+/// - a named tuple containing the database
+/// - a tuple of PRF keys for multiplication
+///
+/// # Custom operation returns
+///
+/// Node containing a named tuple with the preprocessed database
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct PsiPreprocessingMPC {
+    pub key_headers: Vec<String>,
+    #[serde(default)]
+    pub config: PsiConfig,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for PsiPreprocessingMPC {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 2 {
+            return Err(runtime_error!(
+                "PSI preprocessing should have 2 inputs: a database and PRF keys"
+            ));
+        }
+        get_preprocessing_graph(
+            context,
+            argument_types[0].clone(),
+            argument_types[1].clone(),
+            &self.key_headers,
+            &self.config,
+        )
+    }
+
+    fn get_name(&self) -> String {
+        format!("PsiPreprocessing(keys:{:?})", self.key_headers)
+    }
+}
+
+register_custom_operation!(PsiPreprocessingMPC, [Shared, PrfKeys]);
+
+/// Adds a node returning an approximate intersection of given databases along given column keys.
+///
+/// Databases are represented as in [SetIntersectionMPC].
+/// The second database should contain only key columns and the null column, since its other columns can't be attached to the result.
+///
+/// In contrast to [SetIntersectionMPC], membership of rows of the first database in the second one is tested via a secret-shared Bloom filter instead of Cuckoo hashing.
+/// This avoids the Permutation protocol on the second database and the comparison of long key entries, so the protocol has fewer communication rounds and needs less memory on very large sets.
+/// However, a row of the first database not belonging to the intersection is falsely included in the result with probability approximately `(1 - e^(-k/b))^k`, where `k` is the number of hash functions and `b` is the number of filter bits per entry of the second database.
+///
+/// The protocol proceeds as follows.
+/// Let X be the first database and Y be the second one.
+/// 1. Parties compute OPRF(X) and OPRF(Y) on merged key columns as in steps 1-3 of [SetIntersectionMPC].
+/// 2. OPRF(X) is revealed to party 2.
+/// 3. OPRF(Y) is revealed to party 1.
+/// 4. Parties 1 and 2 sample `k` hash functions using their common PRF key (key 2 in the multiplication PRF key triple).
+/// 5. Party 1 computes a Bloom filter of OPRF(Y) using the above hash functions; the filter is shared between parties 2 (share 0 equal to zero) and 1 (share 1).
+/// 6. Party 2 computes a simple hash map of OPRF(X) using the same hash functions.
+/// 7. For each hash function h, parties 2 and 1 perform the Switching protocol (SwitchingMPC) to get 2-out-of-2 shares of the filter bits at the positions of h(OPRF(X)) owned by parties 2 and 0.
+/// 8. All parties convert these shares to 2-out-of-3 shares.
+/// 9. The resulting null column is the AND of all these bits and the null column of X.
+/// 10. Columns of X are multiplied by the resulting null column.
+///
+/// # Custom operation arguments
+///
+/// - a named tuple containing the first database
+/// - a named tuple containing the second database
+/// - a tuple of PRF keys for multiplication
+///
+/// # Custom operation returns
+///
+/// Node containing a named tuple containing the approximate inner join of both databases
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct SetIntersectionBloomMPC {
+    // Instead of HashMap, Vector is used to support the Hash trait
+    pub headers: Vec<(String, String)>,
+    pub num_hash_functions: u64,
+    pub bits_per_entry: u64,
+    #[serde(default)]
+    pub config: PsiConfig,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for SetIntersectionBloomMPC {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() == 2 {
+            // Public databases are intersected exactly
+            return SetIntersectionMPC {
+                headers: self.headers.clone(),
+                mode: PsiMode::Cuckoo,
+                config: self.config.clone(),
+            }
+            .instantiate(context, argument_types);
+        }
+        if argument_types.len() != 3 {
+            return Err(runtime_error!("PSI protocol should have 3 inputs"));
+        }
+        if self.num_hash_functions == 0 || self.bits_per_entry == 0 {
+            return Err(runtime_error!(
+                "Number of hash functions and filter bits per entry must be positive"
+            ));
+        }
+
+        let data_x_t = argument_types[0].clone();
+        let data_y_t = argument_types[1].clone();
+        let prf_t = argument_types[2].clone();
+
+        let is_x_private = data_x_t.is_tuple();
+        let is_y_private = data_y_t.is_tuple();
+
+        let (num_entries_x, column_header_types_x) = check_and_extract_dataset_parameters(
+            data_x_t.clone(),
+            is_x_private,
+            &self.config.padding,
+        )?;
+        let (num_entries_y, column_header_types_y) = check_and_extract_dataset_parameters(
+            data_y_t.clone(),
+            is_y_private,
+            &self.config.padding,
+        )?;
+
+        let mut key_headers_x = vec![];
+        let mut key_headers_y = vec![];
+        for (h_x, h_y) in &self.headers {
+            key_headers_x.push((*h_x).clone());
+            key_headers_y.push((*h_y).clone());
+        }
+        for (header, _) in &column_header_types_y {
+            if header != NULL_HEADER && !key_headers_y.contains(header) {
+                return Err(runtime_error!(
+                    "Bloom filter intersection can't attach non-key column {} of the second database",
+                    header
+                ));
+            }
+        }
+
+        let (key_columns_entry_bitlength, is_a2b_needed) =
+            get_key_columns_parameters(&column_header_types_x, &key_headers_x, num_entries_x)?;
+
+        // The filter should contain at least as many bits as the number of entries of X to support the Switching protocol
+        let filter_bits = max(num_entries_y * self.bits_per_entry, num_entries_x);
+        let log_filter_size = (filter_bits as f64).log2().ceil() as u64;
+        if log_filter_size > 63 {
+            return Err(runtime_error!("Bloom filter is too big"));
+        }
+
+        let merging_g_x = get_merging_graph(
+            context.clone(),
+            column_header_types_x.clone(),
+            &key_headers_x,
+            is_x_private,
+        )?;
+        let merging_g_y = get_merging_graph(
+            context.clone(),
+            column_header_types_y.clone(),
+            &key_headers_y,
+            is_y_private,
+        )?;
+        let prf_g_x = get_prf_graph(context.clone(), num_entries_x, &self.config)?;
+        let prf_g_y = get_prf_graph(context.clone(), num_entries_y, &self.config)?;
+
+        let g = context.create_graph()?;
+
+        let data_x = g.input(data_x_t)?;
+        let data_y = g.input(data_y_t)?;
+        let prf_keys = g.input(prf_t)?;
+
+        let mut data_x_shares = vec![];
+        let mut data_y_shares = vec![];
+        if is_x_private {
+            for share_id in 0..PARTIES as u64 {
+                data_x_shares.push(data_x.tuple_get(share_id)?);
+            }
+        } else {
+            data_x_shares.push(data_x.clone());
+        }
+        if is_y_private {
             for share_id in 0..PARTIES as u64 {
                 data_y_shares.push(data_y.tuple_get(share_id)?);
             }
@@ -667,16 +1844,15 @@ impl CustomOperationBody for SetIntersectionMPC {
             data_y_shares.push(data_y.clone());
         }
 
-        // Extract PRF keys
         let mut prf_keys_vec = vec![];
         for key_id in 0..PARTIES as u64 {
             prf_keys_vec.push(prf_keys.tuple_get(key_id)?);
         }
 
-        // 1. Key columns of both sets are converted to binary and merged row-wise.
+        // 1. Compute OPRF(X) and OPRF(Y) on merged key columns
         let merged_columns_x = g.call(
             merging_g_x,
-            if prf_needed_to_merge_x {
+            if is_x_private && is_a2b_needed {
                 vec![prf_keys.clone(), data_x]
             } else {
                 vec![data_x]
@@ -684,278 +1860,953 @@ impl CustomOperationBody for SetIntersectionMPC {
         )?;
         let merged_columns_y = g.call(
             merging_g_y,
-            if prf_needed_to_merge_y {
-                vec![prf_keys.clone(), data_y.clone()]
+            if is_y_private && is_a2b_needed {
+                vec![prf_keys.clone(), data_y]
             } else {
-                vec![data_y.clone()]
+                vec![data_y]
             },
         )?;
-
-        // 2. If the bitsize of merged entries is bigger than the block size of the LowMC block cipher, hash them via multiplication by a random matrix obliviously generated by all parties.
-        //  - Generate a random matrix shared by all the parties
         let random_hash_matrix = generate_shared_random_array(
-            array_type(vec![PRF_OUTPUT_SIZE, key_columns_entry_bitlength], BIT),
+            array_type(
+                vec![
+                    self.config.get_prf_output_size(),
+                    key_columns_entry_bitlength,
+                ],
+                BIT,
+            ),
+            &prf_keys_vec,
+        )?;
+        let oprf_key = generate_shared_random_array(
+            array_type(vec![self.config.get_prf_key_size()], BIT),
             &prf_keys_vec,
         )?;
-
-        // 3. Compute the oblivious pseudo random function (OPRF) on the merged columns of both sets using the LowMC block cipher with a random key obliviously generated by all parties.
-        // This operation returns random string on entries with zero values in the "null" column, i.e.
-        //
-        // OPRF(S) = (PRF(key columns of S) - R) * S_null_column XOR R where R is a random matrix obliviously  generated by all parties.
-        let oprf_key =
-            generate_shared_random_array(array_type(vec![LOW_MC_KEY_SIZE], BIT), &prf_keys_vec)?;
-
-        let compute_oprf = |merged_columns: Node,
-                            null_column: Node,
-                            lowmc_graph: Graph,
-                            num_entries: u64|
-         -> Result<Node> {
-            let hashed_columns =
-                gemm_mpc(merged_columns, random_hash_matrix.clone(), prf_keys.clone())?;
-
-            let oprf_set = g.call(
-                lowmc_graph,
-                vec![prf_keys.clone(), hashed_columns, oprf_key.clone()],
-            )?;
-            let r = generate_shared_random_array(
-                array_type(vec![num_entries, PRF_OUTPUT_SIZE], BIT),
-                &prf_keys_vec,
-            )?;
-            add_mpc(
-                multiply_mpc(
-                    subtract_mpc(oprf_set, r.clone())?,
-                    reshape_shared_array(null_column, array_type(vec![num_entries, 1], BIT))?,
-                    prf_keys.clone(),
-                )?,
-                r,
-            )
-        };
-
-        // Compute OPRF(X) = (PRF(key columns of X) - R_X) * X_null_column XOR R_X where R_X is a random matrix generated by all parties
         let null_x = get_column(&data_x_shares, NULL_HEADER.to_owned())?;
         let oprf_set_x = compute_oprf(
-            merged_columns_x.clone(),
+            merged_columns_x,
             null_x.clone(),
-            lowmc_g_x,
-            num_entries_x,
+            prf_g_x,
+            random_hash_matrix.clone(),
+            oprf_key.clone(),
+            prf_keys.clone(),
+            &prf_keys_vec,
         )?;
-
-        // Compute OPRF(Y) = PRF(key columns of Y) * Y_null_column XOR R_Y * ~Y_null_column where R_Y is a random matrix generated by all parties
         let null_y = get_column(&data_y_shares, NULL_HEADER.to_owned())?;
-        let oprf_set_y = compute_oprf(merged_columns_y.clone(), null_y, lowmc_g_y, num_entries_y)?;
+        let oprf_set_y = compute_oprf(
+            merged_columns_y,
+            null_y,
+            prf_g_y,
+            random_hash_matrix,
+            oprf_key,
+            prf_keys.clone(),
+            &prf_keys_vec,
+        )?;
 
-        // 4. Reveal OPRF(X) to party 2
+        // 2. Reveal OPRF(X) to party 2
         let revealed_oprf_set_x = reveal_array(oprf_set_x, 2)?;
-        // 5. Reveal OPRF(Y) to party 1
+        // 3. Reveal OPRF(Y) to party 1
         let revealed_oprf_set_y = reveal_array(oprf_set_y, 1)?;
 
-        // 6. Parties 1 and 2 generate random matrices for hashing of shape [3, m, LOW_MC_BLOCK_SIZE],
-        // where m = ceil(log(num_entries_y)+1).
-        // TODO: quantify probability of success of Cuckoo hashing with these parameters
-        let log_num_cuckoo_entries = ((num_entries_y as f64).log2() + 1f64).ceil() as u64;
-        let num_hash_functions = 3;
+        // 4. Parties 1 and 2 generate random matrices for hashing of shape [k, log_filter_size, PRF output size]
         let hash_matrices = prf_keys_vec[2].prf(
             0,
             array_type(
-                vec![num_hash_functions, log_num_cuckoo_entries, PRF_OUTPUT_SIZE],
+                vec![
+                    self.num_hash_functions,
+                    log_filter_size,
+                    self.config.get_prf_output_size(),
+                ],
                 BIT,
             ),
         )?;
 
-        // 7. Party 1 computes a Cuckoo hash map from OPRF(Y) and randomizes it to a permutation
-        let cuckoo_map = revealed_oprf_set_y.cuckoo_hash(hash_matrices.clone())?;
-        let cuckoo_permutation = cuckoo_map.cuckoo_to_permutation()?;
-
-        // 8. Attach the merged key columns to Y
-        // HACK: If Y is public, we create fake shares containing zeros such that the next operation generating random padding can accept it
-        let extended_shares_y = if is_y_private {
-            let mut res = vec![];
-            for (share_id, share) in data_y_shares.iter().enumerate() {
-                let mut columns_vec = vec![(
-                    key_header.clone(),
-                    merged_columns_y.tuple_get(share_id as u64)?,
-                )];
-                for (header, _) in &column_header_types_y {
-                    let column = share.named_tuple_get((*header).clone())?;
-                    columns_vec.push(((*header).clone(), column));
-                }
-                let share = g.create_named_tuple(columns_vec)?;
-                res.push(share);
-            }
-            g.create_tuple(res)?
-        } else {
-            let mut columns_vec = vec![(key_header.clone(), merged_columns_y)];
-            for (header, _) in &column_header_types_y {
-                let column = data_y.named_tuple_get((*header).clone())?;
-                columns_vec.push(((*header).clone(), column));
+        // 5. Party 1 computes a Bloom filter of OPRF(Y).
+        // By the contract of SwitchingMPC, the first share is given by Programmer (party 2) and the second one by Sender (party 1).
+        let filter_share = g.create_named_tuple(vec![(
+            BLOOM_FILTER_HEADER.to_owned(),
+            revealed_oprf_set_y.bloom_filter(hash_matrices.clone())?,
+        )])?;
+        let filter_shares =
+            g.create_tuple(vec![zeros_like(filter_share.clone())?, filter_share])?;
+
+        // 6. Party 2 computes a simple hash map of OPRF(X)
+        let simple_hash_map = g.custom_op(
+            CustomOperation::new(SimpleHash {}),
+            vec![revealed_oprf_set_x, hash_matrices],
+        )?;
+
+        // 7-8. For each hash function, extract the filter bits at the hash positions of X and convert them to 2-out-of-3 shares
+        let mut filter_bits = vec![];
+        for h in 0..self.num_hash_functions {
+            let switch_map = simple_hash_map.get(vec![h])?;
+            let switched_filter = g.custom_op(
+                CustomOperation::new(SwitchingMPC {
+                    sender_id: 1,
+                    programmer_id: 2,
+                }),
+                vec![filter_shares.clone(), switch_map, prf_keys.clone()],
+            )?;
+            let filter_bits_shares =
+                convert_2outof2_to_2outof3_shares(switched_filter, &prf_keys_vec)?;
+            filter_bits.push(get_column(
+                &[
+                    filter_bits_shares.tuple_get(0)?,
+                    filter_bits_shares.tuple_get(1)?,
+                    filter_bits_shares.tuple_get(2)?,
+                ],
+                BLOOM_FILTER_HEADER.to_owned(),
+            )?);
+        }
+
+        // 9. Compute the AND of all filter bits and the null column of X in a balanced tree to minimize the number of rounds
+        filter_bits.push(null_x);
+        while filter_bits.len() > 1 {
+            let mut next_level = vec![];
+            for pair in filter_bits.chunks(2) {
+                if pair.len() == 2 {
+                    next_level.push(multiply_mpc(
+                        pair[0].clone(),
+                        pair[1].clone(),
+                        prf_keys.clone(),
+                    )?);
+                } else {
+                    next_level.push(pair[0].clone());
+                }
             }
-            let first_share = g.create_named_tuple(columns_vec)?;
-            let zero_share = zeros_like(first_share.clone())?;
-            g.create_tuple(vec![first_share, zero_share.clone(), zero_share])?
+            filter_bits = next_level;
+        }
+        let res_null_column = filter_bits[0].clone();
+
+        // 10. Multiply columns of X by the resulting null column
+        let mut res_named_tuple_vec = vec![];
+        for share_id in 0..PARTIES as u64 {
+            res_named_tuple_vec.push(vec![(
+                NULL_HEADER.to_owned(),
+                res_null_column.tuple_get(share_id)?,
+            )]);
+        }
+        attach_masked_columns(
+            &mut res_named_tuple_vec,
+            &data_x_shares,
+            &column_header_types_x,
+            NULL_HEADER,
+            res_null_column,
+            prf_keys,
+        )?;
+
+        let mut result_shares = vec![];
+        for share_vec in res_named_tuple_vec {
+            result_shares.push(g.create_named_tuple(share_vec)?);
+        }
+        g.create_tuple(result_shares)?.set_as_output()?;
+
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "BloomPSI(keys:{:?},hashes:{},bits_per_entry:{})",
+            self.headers, self.num_hash_functions, self.bits_per_entry
+        )
+    }
+}
+
+register_custom_operation!(
+    SetIntersectionBloomMPC,
+    [NamedTuple, NamedTuple],
+    [Shared, Shared, PrfKeys]
+);
+
+// Creates a graph that sorts the merged key columns of X and Y together and finds matching rows.
+//
+// Rows of X, Y and zero padding rows are merged into an array with N rows, where N is the smallest power of two not less than the total number of rows.
+// Each row contains (starting from the least significant bit) the bits of its index, the origin bit (0 for X and 1 otherwise), the null bit and the merged key bits.
+// Sorting these rows puts every non-null row of X right before the non-null row of Y with the same key.
+//
+// The graph takes the merged key columns and the null columns of X and Y and returns a tuple containing
+// - the sorting permutation, i.e. a UINT64 array of length N whose i-th element is the index of the i-th sorted row,
+// - the match bits, i.e. a binary array of length N whose i-th element is 1 if and only if the i-th sorted row is a non-null row of X and the next row is a non-null row of Y with the same key.
+fn get_oblivious_matching_graph(
+    context: Context,
+    num_entries_x: u64,
+    num_entries_y: u64,
+    key_columns_entry_bitlength: u64,
+    is_x_private: bool,
+    is_y_private: bool,
+) -> Result<Graph> {
+    let matching_context = create_context()?;
+    let g = matching_context.create_graph()?;
+
+    let merged_x = g.input(array_type(
+        vec![num_entries_x, key_columns_entry_bitlength],
+        BIT,
+    ))?;
+    let null_x = g.input(array_type(vec![num_entries_x], BIT))?;
+    let merged_y = g.input(array_type(
+        vec![num_entries_y, key_columns_entry_bitlength],
+        BIT,
+    ))?;
+    let null_y = g.input(array_type(vec![num_entries_y], BIT))?;
+
+    let num_entries = num_entries_x + num_entries_y;
+    let num_sorted_entries = num_entries.next_power_of_two();
+    let log_num_sorted_entries = num_sorted_entries.trailing_zeros() as u64;
+
+    let mut key_columns = vec![merged_x, merged_y];
+    let mut null_columns = vec![null_x, null_y];
+    if num_sorted_entries > num_entries {
+        let num_padding_entries = num_sorted_entries - num_entries;
+        key_columns.push(zeros(
+            &g,
+            array_type(vec![num_padding_entries, key_columns_entry_bitlength], BIT),
+        )?);
+        null_columns.push(zeros(&g, array_type(vec![num_padding_entries], BIT))?);
+    }
+    let mut index_bits = vec![];
+    for bit_id in 0..log_num_sorted_entries {
+        for row_id in 0..num_sorted_entries {
+            index_bits.push((row_id >> bit_id) & 1);
+        }
+    }
+    let origin_bits: Vec<u64> = (0..num_sorted_entries)
+        .map(|row_id| (row_id >= num_entries_x) as u64)
+        .collect();
+    // Bits of all rows are pulled out, so that the i-th row of the following array contains the i-th bit of all rows
+    let pulled_out_rows = concatenate_first_axis(vec![
+        g.constant(
+            array_type(vec![log_num_sorted_entries, num_sorted_entries], BIT),
+            Value::from_flattened_array(&index_bits, BIT)?,
+        )?,
+        g.constant(
+            array_type(vec![1, num_sorted_entries], BIT),
+            Value::from_flattened_array(&origin_bits, BIT)?,
+        )?,
+        concatenate_first_axis(null_columns)?
+            .reshape(array_type(vec![1, num_sorted_entries], BIT))?,
+        pull_out_bits(concatenate_first_axis(key_columns)?)?,
+    ])?;
+    let row_bitlength = log_num_sorted_entries + 2 + key_columns_entry_bitlength;
+    let sorted_rows = g.custom_op(
+        CustomOperation::new(Sort {
+            k: log_num_sorted_entries as u32,
+            b: row_bitlength,
+            signed_comparison: false,
+        }),
+        vec![put_in_bits(pulled_out_rows)?],
+    )?;
+    let pulled_out_sorted_rows = pull_out_bits(sorted_rows)?;
+
+    // Index bits are converted to integers via multiplication by powers of two to avoid binary-to-arithmetic conversion
+    let powers_of_two: Vec<u64> = (0..log_num_sorted_entries).map(|i| 1 << i).collect();
+    let permutation = g
+        .constant(
+            array_type(vec![log_num_sorted_entries, 1], UINT64),
+            Value::from_flattened_array(&powers_of_two, UINT64)?,
+        )?
+        .mixed_multiply(
+            pulled_out_sorted_rows.get_slice(vec![SliceElement::SubArray(
+                None,
+                Some(log_num_sorted_entries as i64),
+                None,
+            )])?,
+        )?
+        .sum(vec![0])?;
+
+    let origin_bits = pulled_out_sorted_rows.get(vec![log_num_sorted_entries])?;
+    let null_bits = pulled_out_sorted_rows.get(vec![log_num_sorted_entries + 1])?;
+    let key_bits = put_in_bits(
+        pulled_out_sorted_rows.get_slice(vec![SliceElement::SubArray(
+            Some(log_num_sorted_entries as i64 + 2),
+            None,
+            None,
+        )])?,
+    )?;
+    let current_rows = |a: Node| -> Result<Node> {
+        a.get_slice(vec![SliceElement::SubArray(None, Some(-1), None)])
+    };
+    let next_rows = |a: Node| -> Result<Node> {
+        a.get_slice(vec![SliceElement::SubArray(Some(1), None, None)])
+    };
+    let equal_keys = g.custom_op(
+        CustomOperation::new(Equal {}),
+        vec![current_rows(key_bits.clone())?, next_rows(key_bits)?],
+    )?;
+    let one = g.constant(scalar_type(BIT), Value::from_scalar(1, BIT)?)?;
+    let match_bits = equal_keys
+        .multiply(current_rows(null_bits.clone())?)?
+        .multiply(next_rows(null_bits)?)?
+        .multiply(current_rows(origin_bits.clone())?.add(one)?)?
+        .multiply(next_rows(origin_bits)?)?;
+    // The last row has no next row
+    let match_bits =
+        concatenate_first_axis(vec![match_bits, zeros(&g, array_type(vec![1], BIT))?])?;
+
+    g.create_tuple(vec![permutation, match_bits])?
+        .set_as_output()?;
+    g.finalize()?;
+    matching_context.set_main_graph(g)?;
+    matching_context.finalize()?;
+
+    convert_main_graph_to_mpc(
+        matching_context,
+        context,
+        vec![is_x_private, is_x_private, is_y_private, is_y_private],
+    )
+}
+
+/// Adds a node returning the intersection of given databases along given column keys without revealing PRF outputs to any party.
+///
+/// Databases are represented as in [SetIntersectionMPC] and the result has the same columns as in [SetIntersectionMPC].
+/// As in [SetIntersectionMPC], key values should be unique within each database (see [DedupMPC]).
+///
+/// In contrast to [SetIntersectionMPC], no party learns the OPRF values of the key columns, which leak the equality pattern of the keys and the size of the intersection to parties 1 and 2.
+/// Instead, the protocol relies on oblivious sorting, so its communication grows as O(N log<sup>2</sup> N) with the total number of rows N.
+/// Let X be the first database and Y be the second one.
+/// 1. Key columns of both sets are converted to binary and merged row-wise.
+/// 2. The rows of X and Y are sorted together along their merged key columns by the Batcher's sorting network, such that every non-null row of X is followed by the non-null row of Y with the same key, if any.
+/// 3. Parties compute the shares of the sorting permutation and the bits indicating the rows of X followed by a matching row of Y.
+/// 4. Every non-key column of Y is padded with zero rows, sorted by [ApplySharedPermutationMPC] with the sorting permutation and shifted by one row, so that it is aligned with the matching rows of X.
+/// 5. These columns and the matching bits are multiplied and unsorted by [ApplySharedPermutationMPC] with the inverse of the sorting permutation.
+/// 6. The unsorted matching bits of the rows of X form the resulting null column; the columns of X are multiplied by it and combined with the unsorted columns of Y.
+///
+/// Only the uniformly random shuffled permutations of [ApplySharedPermutationMPC] are revealed during the protocol.
+///
+/// **WARNING**: this function should not be used before MPC compilation.
+///
+/// # Custom operation arguments
+///
+/// - a named tuple containing the first database
+/// - a named tuple containing the second database
+/// - a tuple of PRF keys for multiplication
+///
+/// # Custom operation returns
+///
+/// Node containing a named tuple containing the inner join of both databases
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct SetIntersectionObliviousMPC {
+    // Instead of HashMap, Vector is used to support the Hash trait
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub config: PsiConfig,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for SetIntersectionObliviousMPC {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() == 2 {
+            return SetIntersectionMPC {
+                headers: self.headers.clone(),
+                mode: PsiMode::Cuckoo,
+                config: self.config.clone(),
+            }
+            .instantiate(context, argument_types);
+        }
+        if argument_types.len() != 3 {
+            return Err(runtime_error!("PSI protocol should have 3 inputs"));
+        }
+
+        let data_x_t = argument_types[0].clone();
+        let data_y_t = argument_types[1].clone();
+        let prf_t = argument_types[2].clone();
+
+        let is_x_private = data_x_t.is_tuple();
+        let is_y_private = data_y_t.is_tuple();
+
+        let (num_entries_x, column_header_types_x) = check_and_extract_dataset_parameters(
+            data_x_t.clone(),
+            is_x_private,
+            &self.config.padding,
+        )?;
+        let (num_entries_y, column_header_types_y) = check_and_extract_dataset_parameters(
+            data_y_t.clone(),
+            is_y_private,
+            &self.config.padding,
+        )?;
+
+        let mut key_headers_x = vec![];
+        let mut key_headers_y = vec![];
+        for (h_x, h_y) in &self.headers {
+            key_headers_x.push((*h_x).clone());
+            key_headers_y.push((*h_y).clone());
+        }
+
+        let (key_columns_entry_bitlength, is_a2b_needed_x) =
+            get_key_columns_parameters(&column_header_types_x, &key_headers_x, num_entries_x)?;
+        let (key_columns_entry_bitlength_y, is_a2b_needed_y) =
+            get_key_columns_parameters(&column_header_types_y, &key_headers_y, num_entries_y)?;
+        if key_columns_entry_bitlength != key_columns_entry_bitlength_y {
+            return Err(runtime_error!(
+                "Key columns of both databases must have the same size"
+            ));
+        }
+
+        let merging_g_x = get_merging_graph(
+            context.clone(),
+            column_header_types_x.clone(),
+            &key_headers_x,
+            is_x_private,
+        )?;
+        let merging_g_y = get_merging_graph(
+            context.clone(),
+            column_header_types_y.clone(),
+            &key_headers_y,
+            is_y_private,
+        )?;
+        let matching_g = get_oblivious_matching_graph(
+            context.clone(),
+            num_entries_x,
+            num_entries_y,
+            key_columns_entry_bitlength,
+            is_x_private,
+            is_y_private,
+        )?;
+
+        let g = context.create_graph()?;
+
+        let data_x = g.input(data_x_t)?;
+        let data_y = g.input(data_y_t)?;
+        let prf_keys = g.input(prf_t)?;
+
+        let data_x_shares = extract_shares(data_x.clone(), is_x_private)?;
+        let data_y_shares = extract_shares(data_y.clone(), is_y_private)?;
+
+        // 1. Merge key columns of both sets
+        let merged_columns_x = g.call(
+            merging_g_x,
+            if is_x_private && is_a2b_needed_x {
+                vec![prf_keys.clone(), data_x]
+            } else {
+                vec![data_x]
+            },
+        )?;
+        let merged_columns_y = g.call(
+            merging_g_y,
+            if is_y_private && is_a2b_needed_y {
+                vec![prf_keys.clone(), data_y]
+            } else {
+                vec![data_y]
+            },
+        )?;
+
+        // 2-3. Sort both sets and compute the sorting permutation and matching bits
+        let null_x = get_column(&data_x_shares, NULL_HEADER.to_owned())?;
+        let null_y = get_column(&data_y_shares, NULL_HEADER.to_owned())?;
+        let matching = g.call(
+            matching_g,
+            vec![
+                prf_keys.clone(),
+                merged_columns_x,
+                null_x,
+                merged_columns_y,
+                null_y,
+            ],
+        )?;
+        let get_matching_element = |index: u64| -> Result<Node> {
+            let mut shares = vec![];
+            for share_id in 0..PARTIES as u64 {
+                shares.push(matching.tuple_get(share_id)?.tuple_get(index)?);
+            }
+            g.create_tuple(shares)
         };
+        let permutation = get_matching_element(0)?;
+        let match_bits = get_matching_element(1)?;
+        let num_sorted_entries = match_bits.tuple_get(0)?.get_type()?.get_shape()[0];
+
+        // Applies a given function to every share of a shared array
+        let map_shares = |a: Node, f: &dyn Fn(Node) -> Result<Node>| -> Result<Node> {
+            let mut shares = vec![];
+            for share_id in 0..PARTIES as u64 {
+                shares.push(f(a.tuple_get(share_id)?)?);
+            }
+            g.create_tuple(shares)
+        };
+        let permute = |a: Node, inverse: bool| -> Result<Node> {
+            g.custom_op(
+                CustomOperation::new(ApplySharedPermutationMPC { inverse }),
+                vec![a, permutation.clone(), prf_keys.clone()],
+            )
+        };
+        // 5. Unsort a shared array and extract the rows of X
+        let unsort = |a: Node| -> Result<Node> {
+            map_shares(permute(a, true)?, &|share| {
+                share.get_slice(vec![SliceElement::SubArray(
+                    None,
+                    Some(num_entries_x as i64),
+                    None,
+                )])
+            })
+        };
+
+        let res_null_column = unsort(match_bits.clone())?;
+
+        let mut res_named_tuple_vec = vec![];
+        for share_id in 0..PARTIES as u64 {
+            res_named_tuple_vec.push(vec![(
+                NULL_HEADER.to_owned(),
+                res_null_column.tuple_get(share_id)?,
+            )]);
+        }
+        // 6. Multiply columns of X by the resulting null column
+        attach_masked_columns(
+            &mut res_named_tuple_vec,
+            &data_x_shares,
+            &column_header_types_x,
+            NULL_HEADER,
+            res_null_column,
+            prf_keys.clone(),
+        )?;
+        for (header, t) in &column_header_types_y {
+            if key_headers_y.contains(header) || NULL_HEADER == header {
+                continue;
+            }
+            // 4. Pad the column with zero rows in place of the rows of X and the padding rows
+            let shape = t.get_shape();
+            let st = t.get_scalar_type();
+            let zero_rows = |num_rows: u64| -> Result<Node> {
+                let mut zeros_shape = shape.clone();
+                zeros_shape[0] = num_rows;
+                zeros(&g, array_type(zeros_shape, st.clone()))
+            };
+            let column = get_column(&data_y_shares, header.clone())?;
+            // If the second database is public, its columns are shared as (column, 0, 0)
+            let column = if is_y_private {
+                column
+            } else {
+                let zero_share = zeros_like(column.clone())?;
+                g.create_tuple(vec![column, zero_share.clone(), zero_share])?
+            };
+            let padded_column = map_shares(column, &|share| {
+                let mut parts = vec![zero_rows(num_entries_x)?, share];
+                if num_sorted_entries > num_entries_x + num_entries_y {
+                    parts.push(zero_rows(
+                        num_sorted_entries - num_entries_x - num_entries_y,
+                    )?);
+                }
+                concatenate_first_axis(parts)
+            })?;
+            // Sort the column and align each row of Y with the preceding row
+            let shifted_column = map_shares(permute(padded_column, false)?, &|share| {
+                concatenate_first_axis(vec![
+                    share.get_slice(vec![SliceElement::SubArray(Some(1), None, None)])?,
+                    zero_rows(1)?,
+                ])
+            })?;
+            let mut mask_shape = vec![num_sorted_entries];
+            mask_shape.extend(vec![1; shape.len() - 1]);
+            let masked_column = multiply_by_bits_mpc(
+                shifted_column,
+                reshape_shared_array(match_bits.clone(), array_type(mask_shape, BIT))?,
+                prf_keys.clone(),
+            )?;
+            let result_column = unsort(masked_column)?;
+            for (share_id, share_vec) in res_named_tuple_vec.iter_mut().enumerate() {
+                share_vec.push((header.clone(), result_column.tuple_get(share_id as u64)?));
+            }
+        }
+
+        let mut result_shares = vec![];
+        for share_vec in res_named_tuple_vec {
+            result_shares.push(g.create_named_tuple(share_vec)?);
+        }
+        g.create_tuple(result_shares)?.set_as_output()?;
+
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!("ObliviousPSI(keys:{:?})", self.headers)
+    }
+}
+
+register_custom_operation!(
+    SetIntersectionObliviousMPC,
+    [NamedTuple, NamedTuple],
+    [Shared, Shared, PrfKeys]
+);
+
+// Returns bits indicating the first occurrences of binary strings in a public array of shape [n, b].
+// The i-th bit is 1 if and only if no row with index j < i is equal to the i-th row.
+// Since all pairs of rows are compared, this takes O(n^2 * b) local operations.
+fn get_first_occurrence_bits(strings: Node) -> Result<Node> {
+    let g = strings.get_graph();
+    let shape = strings.get_type()?.get_shape();
+    let (num_entries, string_length) = (shape[0], shape[1]);
+    // Compare every pair of rows; the result is a binary matrix of shape [n, n]
+    let equal_pairs = g.custom_op(
+        CustomOperation::new(Equal {}),
+        vec![
+            strings.reshape(array_type(vec![num_entries, 1, string_length], BIT))?,
+            strings.reshape(array_type(vec![1, num_entries, string_length], BIT))?,
+        ],
+    )?;
+    // Keep only the comparisons with the preceding rows
+    let mut preceding_mask = vec![0u64; (num_entries * num_entries) as usize];
+    for i in 0..num_entries {
+        for j in 0..i {
+            preceding_mask[(i * num_entries + j) as usize] = 1;
+        }
+    }
+    let equal_preceding = equal_pairs.multiply(g.constant(
+        array_type(vec![num_entries, num_entries], BIT),
+        Value::from_flattened_array(&preceding_mask, BIT)?,
+    )?)?;
+    // A row occurs for the first time if it isn't equal to any preceding row
+    g.custom_op(
+        CustomOperation::new(Equal {}),
+        vec![
+            equal_preceding,
+            zeros(&g, array_type(vec![num_entries], BIT))?,
+        ],
+    )
+}
+
+/// Adds a node removing duplicate rows of a shared database along given key columns.
+///
+/// The database is represented as in [SetIntersectionMPC], i.e. as a named tuple with a binary "null" column.
+/// Two rows are duplicates if they are both non-null and have equal values in all key columns.
+/// The result contains all columns of the input database, but only one row of every group of duplicates keeps the null bit equal to 1;
+/// all the other rows, as well as the rows that were null already, get zero null bits and zero content.
+/// The rows of the result are shuffled, so its row order is unrelated to the input.
+///
+/// The protocol reuses the OPRF and permutation machinery of [SetIntersectionMPC].
+/// Let X be the input database.
+/// 1. Parties 1 and 0 shuffle X by the Permutation protocol (PermutationMPC) with a random permutation of party 1, then parties 2 and 1 shuffle the result with a random permutation of party 2, so none of the parties knows the composition of these permutations.
+/// 2. The 2-out-of-2 shares of the shuffled database owned by parties 2 and 0 are converted to 2-out-of-3 shares.
+/// 3. Key columns of the shuffled database are converted to binary and merged row-wise.
+/// 4. Parties compute the OPRF of the merged key columns as in steps 2-3 of [SetIntersectionMPC], so null rows are mapped to random strings.
+/// 5. The OPRF values are revealed to party 2.
+/// 6. Party 2 locally computes bits indicating the first occurrence of every OPRF value by comparing all pairs of values.
+/// 7. Party 2 secret-shares these bits with all parties.
+/// 8. The resulting null column is the AND of these bits and the null column of the shuffled database.
+/// 9. Columns of the shuffled database are multiplied by the resulting null column.
+///
+/// **WARNING**: party 2 learns the number of rows in every group of duplicates (including null rows, which form groups of size 1), but not the positions of these rows in the input database.
+/// Step 6 takes quadratic time in the number of rows.
+///
+/// **WARNING**: this function should not be used before MPC compilation.
+///
+/// # Custom operation arguments
+///
+/// - a tuple of 2-out-of-3 shares of a named tuple containing the database
+/// - a tuple of PRF keys for multiplication
+///
+/// # Custom operation returns
+///
+/// Node containing a tuple of 2-out-of-3 shares of the deduplicated database;
+/// its first column is the null column, followed by the other columns of the input database in the same order
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct DedupMPC {
+    pub key_headers: Vec<String>,
+    #[serde(default)]
+    pub config: PsiConfig,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for DedupMPC {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 2 {
+            return Err(runtime_error!(
+                "Deduplication should have 2 inputs: a shared database and PRF keys"
+            ));
+        }
+        if self.key_headers.is_empty() {
+            return Err(runtime_error!("At least one key column must be given"));
+        }
+        let data_t = argument_types[0].clone();
+        let prf_t = argument_types[1].clone();
+        let (num_entries, column_header_types) =
+            check_and_extract_dataset_parameters(data_t.clone(), true, &self.config.padding)?;
+        if !column_header_types
+            .iter()
+            .any(|(h, t)| h == NULL_HEADER && *t == array_type(vec![num_entries], BIT))
+        {
+            return Err(runtime_error!(
+                "Database must contain a binary null column of length {}",
+                num_entries
+            ));
+        }
+        let (key_columns_entry_bitlength, is_a2b_needed) =
+            get_key_columns_parameters(&column_header_types, &self.key_headers, num_entries)?;
+
+        // Graph that merges the key columns of the database
+        let merging_g = get_merging_graph(
+            context.clone(),
+            column_header_types.clone(),
+            &self.key_headers,
+            true,
+        )?;
+        // Graph that computes the PRF on the database
+        let prf_g = get_prf_graph(context.clone(), num_entries, &self.config)?;
+
+        let g = context.create_graph()?;
+
+        let data = g.input(data_t)?;
+        let prf_keys = g.input(prf_t)?;
+
+        let prf_keys_vec = extract_shares(prf_keys.clone(), true)?;
+
+        // 1. Shuffle the database with random permutations of parties 1 and 2.
+        // The share of party 1 goes first as demanded by PermutationMPC with party 1 as Programmer.
+        let data_2of2shares = g.create_tuple(vec![
+            data.tuple_get(2)?,
+            sum_named_columns(data.tuple_get(0)?, data.tuple_get(1)?)?,
+        ])?;
+        let shuffled_data = g.custom_op(
+            CustomOperation::new(PermutationMPC {
+                programmer_id: 1,
+                sender_id: 0,
+            }),
+            vec![
+                data_2of2shares,
+                g.random_permutation(num_entries)?,
+                prf_keys.clone(),
+            ],
+        )?;
+        // The result is shared between parties 1 (share 0) and 2 (share 1); party 2 is Programmer of the next permutation
+        let shuffled_data = g.custom_op(
+            CustomOperation::new(PermutationMPC {
+                programmer_id: 2,
+                sender_id: 1,
+            }),
+            vec![
+                g.create_tuple(vec![
+                    shuffled_data.tuple_get(1)?,
+                    shuffled_data.tuple_get(0)?,
+                ])?,
+                g.random_permutation(num_entries)?,
+                prf_keys.clone(),
+            ],
+        )?;
+
+        // 2. The shuffled database is shared between parties 2 (share 0) and 0 (share 1), so convert its shares to 2-out-of-3 shares
+        let shuffled_data = convert_2outof2_to_2outof3_shares(shuffled_data, &prf_keys_vec)?;
+        let shuffled_shares = extract_shares(shuffled_data.clone(), true)?;
+
+        // 3. Key columns are converted to binary and merged row-wise.
+        let merged_columns = g.call(
+            merging_g,
+            if is_a2b_needed {
+                vec![prf_keys.clone(), shuffled_data]
+            } else {
+                vec![shuffled_data]
+            },
+        )?;
+
+        // 4. Compute the OPRF of merged key columns; null rows are mapped to random strings
+        let random_hash_matrix = generate_shared_random_array(
+            array_type(
+                vec![
+                    self.config.get_prf_output_size(),
+                    key_columns_entry_bitlength,
+                ],
+                BIT,
+            ),
+            &prf_keys_vec,
+        )?;
+        let oprf_key = generate_shared_random_array(
+            array_type(vec![self.config.get_prf_key_size()], BIT),
+            &prf_keys_vec,
+        )?;
+        let null_column = get_column(&shuffled_shares, NULL_HEADER.to_owned())?;
+        let oprf_set = compute_oprf(
+            merged_columns,
+            null_column.clone(),
+            prf_g,
+            random_hash_matrix,
+            oprf_key,
+            prf_keys.clone(),
+            &prf_keys_vec,
+        )?;
+
+        // 5. Reveal the OPRF values to party 2
+        let revealed_oprf_set = reveal_array(oprf_set, 2)?;
+
+        // 6. Party 2 finds the first occurrences of the OPRF values
+        let first_occurrence_bits = get_first_occurrence_bits(revealed_oprf_set)?;
+
+        // 7. The bits are shared between parties 2 (share 0) and 0 (share 1) and converted to 2-out-of-3 shares
+        let first_occurrence_t = array_type(vec![num_entries], BIT);
+        let first_occurrence_shares = convert_2outof2_to_2outof3_shares(
+            g.create_tuple(vec![
+                g.create_named_tuple(vec![(NULL_HEADER.to_owned(), first_occurrence_bits)])?,
+                g.create_named_tuple(vec![(
+                    NULL_HEADER.to_owned(),
+                    zeros(&g, first_occurrence_t)?,
+                )])?,
+            ])?,
+            &prf_keys_vec,
+        )?;
+        let first_occurrence_column = get_column(
+            &extract_shares(first_occurrence_shares, true)?,
+            NULL_HEADER.to_owned(),
+        )?;
+
+        // 8. Compute the resulting null column
+        let res_null_column = multiply_mpc(null_column, first_occurrence_column, prf_keys.clone())?;
+
+        // 9. Multiply the other columns by the resulting null column
+        let mut res_named_tuple_vec = vec![];
+        for share_id in 0..PARTIES as u64 {
+            res_named_tuple_vec.push(vec![(
+                NULL_HEADER.to_owned(),
+                res_null_column.tuple_get(share_id)?,
+            )]);
+        }
+        attach_masked_columns(
+            &mut res_named_tuple_vec,
+            &shuffled_shares,
+            &column_header_types,
+            NULL_HEADER,
+            res_null_column,
+            prf_keys,
+        )?;
+
+        let mut result_shares = vec![];
+        for share_vec in res_named_tuple_vec {
+            result_shares.push(g.create_named_tuple(share_vec)?);
+        }
+        g.create_tuple(result_shares)?.set_as_output()?;
+
+        g.finalize()?;
+        Ok(g)
+    }
 
-        // 9. Pad columns of Y with random data such that the number of entries is equal to the cuckoo table size
-        let padded_shares_y = {
-            let num_extra_rows = (1 << log_num_cuckoo_entries) - num_entries_y;
-            pad_columns(extended_shares_y, num_extra_rows, &prf_keys_vec)?
-        };
+    fn get_name(&self) -> String {
+        format!("Dedup(keys:{:?})", self.key_headers)
+    }
+}
 
-        // 10. Switch from 2-out-of-3 shares of dataset Y to 2-out-of-2 shares owned by parties 0 and 1
-        let data_y_2of2shares = {
-            // Share of party 0 is the sum of its 2-out-of-3 shares
-            let party0_share =
-                sum_named_columns(padded_shares_y.tuple_get(0)?, padded_shares_y.tuple_get(1)?)?;
-            // Share of party 1 is the third 2-out-of-3 share
-            // Share of party 1 goes first to support the contract of the consecutive PermutationMPC operation, which demands that the first share and a permutation is owned by the same party.
-            g.create_tuple(vec![padded_shares_y.tuple_get(2)?, party0_share])?
-        };
+register_custom_operation!(DedupMPC, [Shared, PrfKeys]);
 
-        // 11. Create a Cuckoo table of Y by applying the above Cuckoo permutation to the shares of Y.
-        // The Cuckoo table will be shared between parties 1 (share 0) and 2 (share 1).
-        let mut cuckoo_table = g.custom_op(
-            CustomOperation::new(PermutationMPC {
-                programmer_id: 1,
-                sender_id: 0,
-            }),
-            vec![data_y_2of2shares, cuckoo_permutation, prf_keys.clone()],
-        )?;
+// Returns a database containing only given columns of a public or shared database
+fn select_columns(data: Node, headers: &[String]) -> Result<Node> {
+    let g = data.get_graph();
+    let select = |share: Node| -> Result<Node> {
+        let mut columns = vec![];
+        for header in headers {
+            columns.push((header.clone(), share.named_tuple_get(header.clone())?));
+        }
+        g.create_named_tuple(columns)
+    };
+    if data.get_type()?.is_tuple() {
+        let mut shares = vec![];
+        for share_id in 0..PARTIES as u64 {
+            shares.push(select(data.tuple_get(share_id)?)?);
+        }
+        g.create_tuple(shares)
+    } else {
+        select(data)
+    }
+}
 
-        // 12. Party 2 computes a simple hash map from OPRF(X) for each of 3 hash functions
-        let simple_hash_map = g.custom_op(
-            CustomOperation::new(SimpleHash {}),
-            vec![revealed_oprf_set_x, hash_matrices],
-        )?;
+// Concatenates two arrays along the first axis
+fn concatenate_rows(a: Node, b: Node) -> Result<Node> {
+    let a_shape = a.get_type()?.get_shape();
+    let b_shape = b.get_type()?.get_shape();
+    let st = a.get_type()?.get_scalar_type();
+    let row_t = if a_shape.len() > 1 {
+        array_type(a_shape[1..].to_vec(), st)
+    } else {
+        scalar_type(st)
+    };
+    a.get_graph()
+        .create_tuple(vec![a.array_to_vector()?, b.array_to_vector()?])?
+        .reshape(vector_type(a_shape[0] + b_shape[0], row_t))?
+        .vector_to_array()
+}
 
-        // 13. For each simple hash map h, parties 2 and 1 perform the switching protocol to get 2-out-of-2 shares of Y_h, which is an arrangement of several Cuckoo table elements such that elements of the intersection are located at the same positions as elements of X belonging to the intersection.
-        // As a result, Parties 2 and 0 have 2-out-of-2 shares of Y_h
+/// Adds a node returning the difference of given databases along given column keys.
+///
+/// Databases are represented as in [SetIntersectionMPC].
+/// The difference contains all columns of the first database and has the same number of rows.
+/// Its null column is 1 only for the rows of the first database that have the null bit 1 and don't match any non-null row of the second database in key columns;
+/// the content of all other rows is zero.
+/// As in [SetIntersectionMPC], key values should be unique within each database (see [DedupMPC]).
+///
+/// The protocol computes the intersection of the first database with the key and null columns of the second database by [SetIntersectionMPC] with the same `config`.
+/// The resulting null column is the XOR of the null column of the first database and the null column of the intersection.
+/// Then, columns of the first database are multiplied by the resulting null column.
+/// Thus, the difference reveals the same information to the parties as the intersection.
+///
+/// **WARNING**: this function should not be used before MPC compilation.
+///
+/// # Custom operation arguments
+///
+/// - a named tuple containing the first database
+/// - a named tuple containing the second database
+/// - a tuple of PRF keys for multiplication
+///
+/// # Custom operation returns
+///
+/// Node containing a tuple of 2-out-of-3 shares of the difference;
+/// its first column is the null column, followed by the other columns of the first database in the same order
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct SetDifferenceMPC {
+    // Instead of HashMap, Vector is used to support the Hash trait
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub config: PsiConfig,
+}
 
-        // Repack the Cuckoo table such that party 2 has share 0 and party has share 1
-        // This is necessary by the contract of SwitchingMPC that requires the first share to be given by Programmer (party 2 having the switching map)
-        cuckoo_table =
-            g.create_tuple(vec![cuckoo_table.tuple_get(1)?, cuckoo_table.tuple_get(0)?])?;
+#[typetag::serde]
+impl CustomOperationBody for SetDifferenceMPC {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 3 {
+            return Err(runtime_error!(
+                "Set difference protocol should have 3 inputs"
+            ));
+        }
+        if self.headers.is_empty() {
+            return Err(runtime_error!(
+                "At least one pair of key columns must be given"
+            ));
+        }
+        let data_x_t = argument_types[0].clone();
+        let data_y_t = argument_types[1].clone();
+        let prf_t = argument_types[2].clone();
 
-        let mut all_y_h = vec![];
-        for h in 0..num_hash_functions {
-            let switch_map = simple_hash_map.get(vec![h])?;
-            let switched_cuckoo = g.custom_op(
-                CustomOperation::new(SwitchingMPC {
-                    sender_id: 1,
-                    programmer_id: 2,
-                }),
-                vec![cuckoo_table.clone(), switch_map, prf_keys.clone()],
-            )?;
-            all_y_h.push(switched_cuckoo);
-        }
-
-        // 14. Convert the 2-out-of-2 shares of Y_h to 2-out-of-3 shares
-        let y_h_shares = {
-            let mut res = vec![];
-            // Tuple of two named tuples
-            let y_h_t = all_y_h[0].get_type()?;
-            // One named tuple corresponding to one 2-out-of-2 share
-            let share_2outof2_t = (*get_types_vector(y_h_t)?[0]).clone();
-            for y_h in all_y_h {
-                // Parties 0 and 2 generate common randomness R_h to mask the second share of Y_h known to Party 0. The first PRF key is used since it's owned by both parties.
-                let r_h = prf_keys_vec[0].prf(0, share_2outof2_t.clone())?;
-                // Party 0 computes (share 1 of Y_h - R_h) and sends it to Party 1.
-                // This is the first share of 2-out-of-3 shares of Y_h.
-                let dif = subtract_named_columns(y_h.tuple_get(1)?, r_h.clone())?
-                    .nop()?
-                    .add_annotation(NodeAnnotation::Send(0, 1))?;
-                // Party 2 sends its 2-out-of-2 share to Party 1.
-                // This is the third share of 2-out-of-3 shares of Y_h.
-                let last_share = y_h
-                    .tuple_get(0)?
-                    .nop()?
-                    .add_annotation(NodeAnnotation::Send(2, 1))?;
-                // Create 2-out-of-3 shares of one Y_h
-                let y_h_share = g.create_tuple(vec![r_h, dif, last_share])?;
-                res.push(y_h_share);
-            }
-            res
-        };
+        let is_x_private = data_x_t.is_tuple();
+        let (_, column_header_types_x) = check_and_extract_dataset_parameters(
+            data_x_t.clone(),
+            is_x_private,
+            &self.config.padding,
+        )?;
+        check_and_extract_dataset_parameters(
+            data_y_t.clone(),
+            data_y_t.is_tuple(),
+            &self.config.padding,
+        )?;
 
-        // 15. Compare X with all Y_h and select the rows of Y_h that match rows in X.
-        // The resulting null column has 1 entry if there is at least one Y_h, whose corresponding entry is equal to X in key columns.
+        let g = context.create_graph()?;
 
-        // Attach the null column to the merged key columns of X.
-        let null_merged_columns_x_shares = if is_x_private {
-            let mut res = vec![];
-            for share_id in 0..PARTIES as u64 {
-                let share = g.create_named_tuple(vec![
-                    (NULL_HEADER.to_owned(), null_x.tuple_get(share_id)?),
-                    (key_header.clone(), merged_columns_x.tuple_get(share_id)?),
-                ])?;
-                res.push(share);
-            }
-            g.create_tuple(res)?
-        } else {
-            g.create_named_tuple(vec![
-                (NULL_HEADER.to_owned(), null_x),
-                (key_header.clone(), merged_columns_x),
-            ])?
-        };
+        let data_x = g.input(data_x_t)?;
+        let data_y = g.input(data_y_t)?;
+        let prf_keys = g.input(prf_t)?;
 
-        let mut res_null_column = g.call(
-            eq_g.clone(),
-            vec![
-                prf_keys.clone(),
-                y_h_shares[0].clone(),
-                null_merged_columns_x_shares.clone(),
-            ],
+        // Only the null and key columns of the second database are needed
+        let mut selected_headers_y = vec![NULL_HEADER.to_owned()];
+        selected_headers_y.extend(self.headers.iter().map(|(_, h_y)| h_y.clone()));
+        let key_columns_y = select_columns(data_y, &selected_headers_y)?;
+
+        let intersection = g.custom_op(
+            CustomOperation::new(SetIntersectionMPC {
+                headers: self.headers.clone(),
+                mode: PsiMode::Cuckoo,
+                config: self.config.clone(),
+            }),
+            vec![data_x.clone(), key_columns_y, prf_keys.clone()],
         )?;
-        let mut selected_columns_y = g.call(
-            select_g_y.clone(),
-            vec![
-                prf_keys.clone(),
-                y_h_shares[0].clone(),
-                res_null_column.clone(),
-            ],
+        let intersection_shares = extract_shares(intersection, true)?;
+        let data_x_shares = extract_shares(data_x, is_x_private)?;
+
+        // Rows of the intersection are aligned with the rows of the first database,
+        // so the rows of the difference are exactly the non-null rows of the first database that are null in the intersection.
+        let res_null_column = add_mpc(
+            get_column(&data_x_shares, NULL_HEADER.to_owned())?,
+            get_column(&intersection_shares, NULL_HEADER.to_owned())?,
         )?;
-        for shares in y_h_shares.iter().skip(1) {
-            // Compare elements of Y_h and X
-            let eq_bits = g.call(
-                eq_g.clone(),
-                vec![
-                    prf_keys.clone(),
-                    (*shares).clone(),
-                    null_merged_columns_x_shares.clone(),
-                ],
-            )?;
-            // Compute selection bits.
-            // Selection bits must satisfy the following rules:
-            // - if the current null column entry is 0 (the corresponding entry of X hasn't been matched) and the corresponding equality bits is 1 (matching occurred in this iteration), then the corresponding selection bit should be 1;
-            // - in other cases, the selection bit must be 0.
-            // This can be computed as select_bit = eq_bit AND null_column_bit XOR eq_bit.
-            let select_bits = add_mpc(
-                multiply_mpc(eq_bits.clone(), res_null_column.clone(), prf_keys.clone())?,
-                eq_bits.clone(),
-            )?;
-            // Select rows of Y_h
-            let selected_rows = g.call(
-                select_g_y.clone(),
-                vec![prf_keys.clone(), (*shares).clone(), select_bits],
-            )?;
-            // Sum named tuples
-            selected_columns_y = {
-                let mut columns_shares = vec![];
-                for share_id in 0..PARTIES as u64 {
-                    let share = sum_named_columns(
-                        selected_rows.tuple_get(share_id)?,
-                        selected_columns_y.tuple_get(share_id)?,
-                    )?;
-                    columns_shares.push(share);
-                }
-                g.create_tuple(columns_shares)?
-            };
-            // OR equality bits
-            res_null_column = g.call(
-                or_g.clone(),
-                vec![prf_keys.clone(), res_null_column.clone(), eq_bits],
-            )?;
-        }
 
-        // 16. Combine the selected rows along the columns of X and Y
         let mut res_named_tuple_vec = vec![];
         for share_id in 0..PARTIES as u64 {
             res_named_tuple_vec.push(vec![(
@@ -963,64 +2814,176 @@ impl CustomOperationBody for SetIntersectionMPC {
                 res_null_column.tuple_get(share_id)?,
             )]);
         }
-        // Multiply columns of X by the intersection null column
-        for (header, t) in &column_header_types_x {
-            if header == NULL_HEADER || header == &key_header {
-                continue;
-            }
-            let mut column = get_column(&data_x_shares, header.clone())?;
+        attach_masked_columns(
+            &mut res_named_tuple_vec,
+            &data_x_shares,
+            &column_header_types_x,
+            NULL_HEADER,
+            res_null_column,
+            prf_keys,
+        )?;
 
-            let column_shape = t.get_shape();
-            // Reshape the mask to multiply row-wise
-            let mut mask_shape = vec![num_entries_x];
-            if column_shape.len() > 1 {
-                mask_shape.extend(vec![1; column_shape.len() - 1]);
-            }
-            let column_mask =
-                reshape_shared_array(res_null_column.clone(), array_type(mask_shape, BIT))?;
+        let mut result_shares = vec![];
+        for share_vec in res_named_tuple_vec {
+            result_shares.push(g.create_named_tuple(share_vec)?);
+        }
+        g.create_tuple(result_shares)?.set_as_output()?;
 
-            column = if t.get_scalar_type() == BIT {
-                multiply_mpc(column, column_mask, prf_keys.clone())?
-            } else {
-                mixed_multiply_mpc(column, column_mask, prf_keys.clone())?
-            };
-            for (share_id, share_vec) in res_named_tuple_vec.iter_mut().enumerate() {
-                share_vec.push(((*header).clone(), column.tuple_get(share_id as u64)?));
-            }
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!("SetDifference(keys:{:?})", self.headers)
+    }
+}
+
+register_custom_operation!(SetDifferenceMPC, [Shared, Shared, PrfKeys]);
+
+/// Adds a node returning the union of given databases along given column keys.
+///
+/// Databases are represented as in [SetIntersectionMPC].
+/// Both databases must have the same columns up to the names of key columns, i.e. every non-key column of the second database must have a column of the first database with the same name and type (except for the number of rows), and key columns are matched according to `headers`.
+/// As in [SetIntersectionMPC], key values should be unique within each database (see [DedupMPC]).
+///
+/// The union contains the columns of the first database.
+/// Its rows are the rows of the first database followed by the rows of the difference of the second and first databases computed by [SetDifferenceMPC].
+/// Thus, the number of rows of the union is always the sum of the numbers of rows of input databases,
+/// and the rows of the second database whose keys occur in the first database are marked by zero null bits and have zero content.
+///
+/// **WARNING**: this function should not be used before MPC compilation.
+///
+/// # Custom operation arguments
+///
+/// - a named tuple containing the first database
+/// - a named tuple containing the second database
+/// - a tuple of PRF keys for multiplication
+///
+/// # Custom operation returns
+///
+/// Node containing a tuple of 2-out-of-3 shares of the union whose columns are ordered as in the first database
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct SetUnionMPC {
+    // Instead of HashMap, Vector is used to support the Hash trait
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub config: PsiConfig,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for SetUnionMPC {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 3 {
+            return Err(runtime_error!("Set union protocol should have 3 inputs"));
         }
-        // Attach selected rows of Y
-        for (header, _) in &column_header_types_y {
-            // If the current column has been already attached to the result, ignore it
-            if key_headers_y.contains(header) || NULL_HEADER == header {
-                continue;
+        let data_x_t = argument_types[0].clone();
+        let data_y_t = argument_types[1].clone();
+        let prf_t = argument_types[2].clone();
+
+        let is_x_private = data_x_t.is_tuple();
+        let (_, column_header_types_x) = check_and_extract_dataset_parameters(
+            data_x_t.clone(),
+            is_x_private,
+            &self.config.padding,
+        )?;
+        let (_, column_header_types_y) = check_and_extract_dataset_parameters(
+            data_y_t.clone(),
+            data_y_t.is_tuple(),
+            &self.config.padding,
+        )?;
+
+        // Match every column of the first database with a column of the second one
+        let headers_x_to_y: HashMap<String, String> = self.headers.iter().cloned().collect();
+        let headers_y_to_x: HashMap<String, String> = self
+            .headers
+            .iter()
+            .map(|(h_x, h_y)| (h_y.clone(), h_x.clone()))
+            .collect();
+        let types_y: HashMap<String, Type> = column_header_types_y.iter().cloned().collect();
+        if column_header_types_x.len() != column_header_types_y.len() {
+            return Err(runtime_error!(
+                "Databases must have the same number of columns"
+            ));
+        }
+        let mut matched_headers = vec![];
+        for (h_x, t_x) in &column_header_types_x {
+            let h_y = headers_x_to_y.get(h_x).unwrap_or(h_x).clone();
+            if headers_y_to_x.contains_key(&h_y) && !headers_x_to_y.contains_key(h_x) {
+                return Err(runtime_error!(
+                    "Column {} of the second database is a key column matched with another column",
+                    h_y
+                ));
             }
-            for (share_id, share_vec) in res_named_tuple_vec.iter_mut().enumerate() {
-                share_vec.push((
-                    (*header).clone(),
-                    selected_columns_y
-                        .tuple_get(share_id as u64)?
-                        .named_tuple_get((*header).clone())?,
+            let t_y = types_y.get(&h_y).ok_or_else(|| {
+                runtime_error!("Column {} doesn't exist in the second database", h_y)
+            })?;
+            if t_x.get_scalar_type() != t_y.get_scalar_type()
+                || t_x.get_shape()[1..] != t_y.get_shape()[1..]
+            {
+                return Err(runtime_error!(
+                    "Columns {} and {} have incompatible types",
+                    h_x,
+                    h_y
                 ));
             }
+            matched_headers.push((h_x.clone(), h_y));
         }
 
+        let g = context.create_graph()?;
+
+        let data_x = g.input(data_x_t)?;
+        let data_y = g.input(data_y_t)?;
+        let prf_keys = g.input(prf_t)?;
+
+        // Rows of the second database that don't belong to the first one
+        let difference = g.custom_op(
+            CustomOperation::new(SetDifferenceMPC {
+                headers: self
+                    .headers
+                    .iter()
+                    .map(|(h_x, h_y)| (h_y.clone(), h_x.clone()))
+                    .collect(),
+                config: self.config.clone(),
+            }),
+            vec![data_y, data_x.clone(), prf_keys],
+        )?;
+
+        // HACK: If the first database is public, we create fake shares containing zeros to merge it with the shared difference
+        let data_x_shares = if is_x_private {
+            extract_shares(data_x, true)?
+        } else {
+            let zero_share = zeros_like(data_x.clone())?;
+            vec![data_x, zero_share.clone(), zero_share]
+        };
+
         let mut result_shares = vec![];
-        for share_vec in res_named_tuple_vec {
-            let share = g.create_named_tuple(share_vec)?;
-            result_shares.push(share);
+        for (share_id, share_x) in data_x_shares.iter().enumerate() {
+            let share_difference = difference.tuple_get(share_id as u64)?;
+            let mut columns = vec![];
+            for (h_x, h_y) in &matched_headers {
+                columns.push((
+                    h_x.clone(),
+                    concatenate_rows(
+                        share_x.named_tuple_get(h_x.clone())?,
+                        share_difference.named_tuple_get(h_y.clone())?,
+                    )?,
+                ));
+            }
+            result_shares.push(g.create_named_tuple(columns)?);
         }
-        let result = g.create_tuple(result_shares)?;
-        result.set_as_output()?;
+        g.create_tuple(result_shares)?.set_as_output()?;
 
         g.finalize()?;
         Ok(g)
     }
 
     fn get_name(&self) -> String {
-        format!("PSI(keys:{:?})", self.headers)
+        format!("SetUnion(keys:{:?})", self.headers)
     }
 }
 
+register_custom_operation!(SetUnionMPC, [Shared, Shared, PrfKeys]);
+
 /// Adds a node returning hash values of an input array of binary strings using provided hash functions.
 ///
 /// Hash functions are defined as an array of binary matrices.
@@ -1050,11 +3013,7 @@ struct SimpleHash;
 impl CustomOperationBody for SimpleHash {
     fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
         if argument_types.len() != 2 {
-            // Panics since:
-            // - the user has no direct access to this function.
-            // - the MPC compiler should pass the correct number of arguments
-            // and this panic should never happen.
-            panic!("SimpleHash should have 2 inputs.");
+            return Err(runtime_error!("SimpleHash should have 2 inputs"));
         }
 
         let input_type = argument_types[0].clone();
@@ -1156,6 +3115,8 @@ impl CustomOperationBody for SimpleHash {
     }
 }
 
+register_custom_operation!(SimpleHash, [Binary, Binary]);
+
 // Checks inputs of permutation, duplication and switching network maps and returns the number of entries and a vector of column types.
 fn check_and_extract_map_input_parameters(
     argument_types: &[Type],
@@ -1163,55 +3124,61 @@ fn check_and_extract_map_input_parameters(
     programmer_id: u64,
 ) -> Result<(u64, ColumnHeaderTypes)> {
     if argument_types.len() != 3 {
-        panic!("This map should have 3 input types");
+        return Err(runtime_error!("This map should have 3 input types"));
     }
     let shares_t = argument_types[0].clone();
     if !shares_t.is_tuple() {
-        panic!("Input shares must be a tuple of 2 elements");
+        return Err(runtime_error!("Input shares must be a tuple of 2 elements"));
     }
     let shares_type_vector = get_types_vector(shares_t)?;
     if shares_type_vector.len() != 2 {
-        panic!("There should be only 2 shares in the input tuple");
+        return Err(runtime_error!(
+            "There should be only 2 shares in the input tuple"
+        ));
     }
     let share_t = (*shares_type_vector[0]).clone();
     if share_t != (*shares_type_vector[1]).clone() {
-        panic!("Input shares must be of the same type");
+        return Err(runtime_error!("Input shares must be of the same type"));
     }
     if !share_t.is_named_tuple() {
-        panic!("Each share must be a named tuple");
+        return Err(runtime_error!("Each share must be a named tuple"));
     }
-    let column_header_types = get_named_types(share_t);
+    let column_header_types = get_named_types(share_t)?;
     let mut num_entries = 0;
     for v in &column_header_types {
         let column_type = v.1.clone();
         if !column_type.is_array() {
-            panic!("Column must be an array");
+            return Err(runtime_error!("Column must be an array"));
         }
         let column_shape = column_type.get_dimensions();
         if num_entries == 0 {
             num_entries = column_shape[0];
         }
         if num_entries != column_shape[0] {
-            panic!("Number of entries should be the same in all columns");
+            return Err(runtime_error!(
+                "Number of entries should be the same in all columns"
+            ));
         }
     }
 
     let prf_t = argument_types[2].clone();
     let expected_key_type = tuple_type(vec![array_type(vec![KEY_LENGTH], BIT); 3]);
     if prf_t != expected_key_type {
-        panic!(
+        return Err(runtime_error!(
             "PRF key type should be a tuple of 3 binary arrays of length {}",
             KEY_LENGTH
-        );
+        ));
     }
     if sender_id >= PARTIES as u64 {
-        panic!("Sender ID is incorrect");
+        return Err(runtime_error!("Sender ID is incorrect"));
     }
     if programmer_id >= PARTIES as u64 {
-        panic!("Programmer ID is incorrect");
+        return Err(runtime_error!("Programmer ID is incorrect"));
     }
     if sender_id == programmer_id {
-        panic!("Programmer ID should be different from the Sender ID");
+        return Err(runtime_error!(
+            "Programmer ID should be different from the Sender ID"
+        ));
     }
 
     Ok((num_entries, column_header_types))
@@ -1247,19 +3214,45 @@ fn get_hidden_prf_key(prf_keys: Node, party_id: u64) -> Result<Node> {
 /// 5. Receiver computes its share of the output `Y_r = perm_r(B) - T`.
 /// 6. Programmer computes its share of the output `Y_p = perm_r(S) + T + perm(X_p)`.
 ///
+/// The permutation acts as [Graph::gather] along the first axis, i.e. the i-th row of the output is the `permutation[i]`-th row of the input.
+/// The permutation array may be shorter than the number of rows; then only the first rows of the result are returned.
+///
 /// **WARNING**: this function should not be used before MPC compilation.
 ///
 /// # Custom operation arguments
 ///
-/// - tuple of 2-out-of-2 shares owned by Sender and Programmer
-/// - permutation array known to Programmer
+/// - tuple of 2-out-of-2 shares: the share of Programmer goes first, then the share of Sender
+/// - permutation array of type UINT64 known to Programmer
 /// - tuple of 3 PRF keys used for multiplication
 ///
 /// # Custom operation returns
 ///
-/// Tuple of permuted 2-out-of-2 shares known to Receiver and Programmer
+/// Tuple of permuted 2-out-of-2 shares: the share of Programmer goes first, then the share of Receiver
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, named_tuple_type, INT32, UINT64};
+/// # use ciphercore_base::custom_ops::CustomOperation;
+/// # use ciphercore_base::data_types::{tuple_type, BIT};
+/// # use ciphercore_base::mpc::mpc_compiler::KEY_LENGTH;
+/// # use ciphercore_base::mpc::mpc_psi::PermutationMPC;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let share_t = named_tuple_type(vec![("a".to_owned(), array_type(vec![4], INT32))]);
+/// let programmer_share = g.input(share_t.clone()).unwrap();
+/// let sender_share = g.input(share_t).unwrap();
+/// let permutation = g.input(array_type(vec![4], UINT64)).unwrap();
+/// let prf_keys = g.input(tuple_type(vec![array_type(vec![KEY_LENGTH], BIT); 3])).unwrap();
+/// let shares = g.create_tuple(vec![programmer_share, sender_share]).unwrap();
+/// let n = g.custom_op(
+///     CustomOperation::new(PermutationMPC { sender_id: 0, programmer_id: 1 }),
+///     vec![shares, permutation, prf_keys],
+/// ).unwrap();
+/// ```
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
-struct PermutationMPC {
+pub struct PermutationMPC {
     pub sender_id: u64,
     pub programmer_id: u64, // The receiver ID is defined automatically
 }
@@ -1276,10 +3269,12 @@ impl CustomOperationBody for PermutationMPC {
         // Check that the permutation map is of the correct form
         let permutation_t = argument_types[1].clone();
         if !permutation_t.is_array() {
-            panic!("Permutation map must be an array");
+            return Err(runtime_error!("Permutation map must be an array"));
         }
         if permutation_t.get_shape()[0] > num_entries {
-            panic!("Permutation map length can't be bigger than the number of entries");
+            return Err(runtime_error!(
+                "Permutation map length can't be bigger than the number of entries"
+            ));
         }
 
         let shares_t = argument_types[0].clone();
@@ -1363,24 +3358,282 @@ impl CustomOperationBody for PermutationMPC {
             receiver_columns.push((column_header.clone(), receiver_result_column));
             programmer_columns.push((column_header, programmer_result_column));
         }
-        let receiver_result_share = g.create_named_tuple(receiver_columns)?;
-        let programmer_result_share = g.create_named_tuple(programmer_columns)?;
-
-        g.create_tuple(vec![programmer_result_share, receiver_result_share])?
-            .set_as_output()?;
+        let receiver_result_share = g.create_named_tuple(receiver_columns)?;
+        let programmer_result_share = g.create_named_tuple(programmer_columns)?;
+
+        g.create_tuple(vec![programmer_result_share, receiver_result_share])?
+            .set_as_output()?;
+
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "Permutation(sender:{},programming:{})",
+            self.sender_id, self.programmer_id
+        )
+    }
+}
+
+register_custom_operation!(PermutationMPC, [Shared, Array, PrfKeys]);
+
+// Permutes 2-out-of-3 shares of a named tuple with a permutation known to a given Programmer via PermutationMPC.
+// The input shares are converted to 2-out-of-2 shares of Programmer and the next party, which is Sender.
+// The output is shared between Programmer (share 0) and the previous party (share 1).
+fn permute_shares(
+    shares: Node,
+    permutation: Node,
+    programmer_id: u64,
+    prf_keys: Node,
+) -> Result<Node> {
+    let sender_id = (programmer_id + 1) % PARTIES as u64;
+    let receiver_id = get_receiver_id(sender_id, programmer_id);
+    // Party i knows shares i and i+1, so Sender knows the share unknown to Programmer
+    let programmer_share = sum_named_columns(
+        shares.tuple_get(programmer_id)?,
+        shares.tuple_get(sender_id)?,
+    )?;
+    let sender_share = shares.tuple_get(receiver_id)?;
+    let g = shares.get_graph();
+    g.custom_op(
+        CustomOperation::new(PermutationMPC {
+            sender_id,
+            programmer_id,
+        }),
+        vec![
+            g.create_tuple(vec![programmer_share, sender_share])?,
+            permutation,
+            prf_keys,
+        ],
+    )
+}
+
+// Converts 2-out-of-2 shares of a named tuple returned by permute_shares to 2-out-of-3 shares.
+// The output of permute_shares is shared between Programmer (share 0) and the previous party (share 1).
+fn convert_permuted_shares(
+    shares: Node,
+    programmer_id: u64,
+    prf_keys_vec: &[Node],
+) -> Result<Node> {
+    let parties = PARTIES as u64;
+    let previous_id = (programmer_id + parties - 1) % parties;
+    let third_id = (programmer_id + 1) % parties;
+    let share_2outof2_t = (*get_types_vector(shares.get_type()?)?[0]).clone();
+    // Programmer and the previous party generate common randomness R, which is the 2-out-of-3 share unknown to the third party.
+    // The PRF key with the index of Programmer is hidden from the third party.
+    let r = prf_keys_vec[programmer_id as usize].prf(0, share_2outof2_t)?;
+    // Programmer masks its share with R and sends it to the third party.
+    // This is the share with the index of the third party, which is also known to Programmer.
+    let masked_share = subtract_named_columns(shares.tuple_get(0)?, r.clone())?
+        .nop()?
+        .add_annotation(NodeAnnotation::Send(programmer_id, third_id))?;
+    // The previous party sends its share to the third party.
+    // This is the share with the index of the previous party.
+    let previous_share = shares
+        .tuple_get(1)?
+        .nop()?
+        .add_annotation(NodeAnnotation::Send(previous_id, third_id))?;
+    let mut result = vec![r.clone(), r.clone(), r];
+    result[third_id as usize] = masked_share;
+    result[previous_id as usize] = previous_share;
+    shares.get_graph().create_tuple(result)
+}
+
+/// Adds a node that applies a secret-shared permutation to a secret-shared array.
+///
+/// If `inverse` is `false`, the permutation acts as [Graph::gather] along the first axis, i.e. the i-th row of the output is the `permutation[i]`-th row of the input.
+/// Otherwise, the inverse permutation is applied, i.e. the i-th row of the input becomes the `permutation[i]`-th row of the output.
+/// The permutation must contain all the indices from 0 to `n-1`, where `n` is the number of rows of the array; otherwise, the result is undefined.
+///
+/// The protocol hides the permutation from every party using the technique of <https://eprint.iacr.org/2019/518.pdf>.
+/// 1. Parties 1 and 2 generate random permutations `perm_1` and `perm_2` and shuffle the shared permutation with their composition `rho` using [PermutationMPC] twice; in the inverse mode, the array is shuffled together with the permutation.
+/// 2. The shuffled permutation `permutation(rho)` is revealed to all parties; it is a uniformly random permutation, since none of the parties knows `rho`.
+/// 3. Parties locally gather the shares of the array with the revealed permutation (or with its inverse in the inverse mode).
+/// 4. If `inverse` is `false`, the result is unshuffled with the inverse of `rho` by parties 2 and 1 using [PermutationMPC] twice.
+///
+/// **WARNING**: this function should not be used before MPC compilation.
+///
+/// # Custom operation arguments
+///
+/// - tuple of 2-out-of-3 shares of an integer or binary array with `n` rows
+/// - tuple of 2-out-of-3 shares of a UINT64 array of length `n` containing a permutation
+/// - tuple of 3 PRF keys used for multiplication
+///
+/// # Custom operation returns
+///
+/// Tuple of 2-out-of-3 shares of the permuted array
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, tuple_type, INT32, UINT64};
+/// # use ciphercore_base::custom_ops::CustomOperation;
+/// # use ciphercore_base::data_types::BIT;
+/// # use ciphercore_base::mpc::mpc_compiler::KEY_LENGTH;
+/// # use ciphercore_base::mpc::mpc_psi::ApplySharedPermutationMPC;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let data = g.input(tuple_type(vec![array_type(vec![4, 2], INT32); 3])).unwrap();
+/// let permutation = g.input(tuple_type(vec![array_type(vec![4], UINT64); 3])).unwrap();
+/// let prf_keys = g.input(tuple_type(vec![array_type(vec![KEY_LENGTH], BIT); 3])).unwrap();
+/// let n = g.custom_op(
+///     CustomOperation::new(ApplySharedPermutationMPC { inverse: false }),
+///     vec![data, permutation, prf_keys],
+/// ).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct ApplySharedPermutationMPC {
+    pub inverse: bool,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for ApplySharedPermutationMPC {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 3 {
+            return Err(runtime_error!(
+                "Shared permutation should have 3 inputs: a shared array, a shared permutation and PRF keys"
+            ));
+        }
+        let data_t = argument_types[0].clone();
+        let permutation_t = argument_types[1].clone();
+        let prf_t = argument_types[2].clone();
+        let share_t = match &data_t {
+            Type::Tuple(share_types) if share_types.len() == PARTIES => (*share_types[0]).clone(),
+            _ => {
+                return Err(runtime_error!(
+                    "Input data must be a tuple of {} shares of an array",
+                    PARTIES
+                ));
+            }
+        };
+        if !share_t.is_array() || data_t != tuple_type(vec![share_t.clone(); PARTIES]) {
+            return Err(runtime_error!(
+                "Input data must be a tuple of {} shares of an array",
+                PARTIES
+            ));
+        }
+        let num_entries = share_t.get_shape()[0];
+        if permutation_t != tuple_type(vec![array_type(vec![num_entries], UINT64); PARTIES]) {
+            return Err(runtime_error!(
+                "Permutation must be a tuple of {} shares of a UINT64 array of length {}",
+                PARTIES,
+                num_entries
+            ));
+        }
+        let expected_key_type = tuple_type(vec![array_type(vec![KEY_LENGTH], BIT); PARTIES]);
+        if prf_t != expected_key_type {
+            return Err(runtime_error!(
+                "PRF key type should be a tuple of 3 binary arrays of length {}",
+                KEY_LENGTH
+            ));
+        }
 
+        let g = context.create_graph()?;
+        let data = g.input(data_t)?;
+        let permutation = g.input(permutation_t)?;
+        let prf_keys = g.input(prf_t)?;
+        let prf_keys_vec = extract_shares(prf_keys.clone(), true)?;
+
+        const DATA_HEADER: &str = "data";
+        const PERMUTATION_HEADER: &str = "permutation";
+        // Every share is converted to a named tuple as demanded by PermutationMPC.
+        // In the inverse mode, data is shuffled together with the permutation.
+        let mut columns_shares = vec![];
+        for i in 0..PARTIES as u64 {
+            let mut columns = vec![(PERMUTATION_HEADER.to_owned(), permutation.tuple_get(i)?)];
+            if self.inverse {
+                columns.push((DATA_HEADER.to_owned(), data.tuple_get(i)?));
+            }
+            columns_shares.push(g.create_named_tuple(columns)?);
+        }
+        let columns = g.create_tuple(columns_shares)?;
+
+        // 1. Shuffle with random permutations of parties 1 and 2
+        let perm_1 = g.random_permutation(num_entries)?;
+        let perm_2 = g.random_permutation(num_entries)?;
+        let shuffled = permute_shares(columns, perm_1.clone(), 1, prf_keys.clone())?;
+        let shuffled = convert_permuted_shares(shuffled, 1, &prf_keys_vec)?;
+        let shuffled = permute_shares(shuffled, perm_2.clone(), 2, prf_keys.clone())?;
+
+        // 2. Reveal the shuffled permutation to all parties.
+        // It is shared between parties 2 (share 0) and 1 (share 1), which broadcast their shares.
+        let share_2 = shuffled
+            .tuple_get(0)?
+            .named_tuple_get(PERMUTATION_HEADER.to_owned())?
+            .nop()?
+            .add_annotation(NodeAnnotation::Send(2, 0))?
+            .add_annotation(NodeAnnotation::Send(2, 1))?;
+        let share_1 = shuffled
+            .tuple_get(1)?
+            .named_tuple_get(PERMUTATION_HEADER.to_owned())?
+            .nop()?
+            .add_annotation(NodeAnnotation::Send(1, 0))?
+            .add_annotation(NodeAnnotation::Send(1, 2))?;
+        let revealed_permutation = share_2.add(share_1)?;
+
+        // 3. Gather the shares locally
+        if self.inverse {
+            let mut data_shares = vec![];
+            for i in 0..2 {
+                data_shares.push(g.create_named_tuple(vec![(
+                    DATA_HEADER.to_owned(),
+                    shuffled
+                        .tuple_get(i)?
+                        .named_tuple_get(DATA_HEADER.to_owned())?,
+                )])?);
+            }
+            let shuffled_data =
+                convert_permuted_shares(g.create_tuple(data_shares)?, 2, &prf_keys_vec)?;
+            let inverse_permutation = revealed_permutation.inverse_permutation()?;
+            let mut result_shares = vec![];
+            for i in 0..PARTIES as u64 {
+                result_shares.push(
+                    shuffled_data
+                        .tuple_get(i)?
+                        .named_tuple_get(DATA_HEADER.to_owned())?
+                        .gather(inverse_permutation.clone(), 0)?,
+                );
+            }
+            g.create_tuple(result_shares)?.set_as_output()?;
+        } else {
+            let mut gathered_shares = vec![];
+            for i in 0..PARTIES as u64 {
+                gathered_shares.push(g.create_named_tuple(vec![(
+                    DATA_HEADER.to_owned(),
+                    data.tuple_get(i)?.gather(revealed_permutation.clone(), 0)?,
+                )])?);
+            }
+            let gathered = g.create_tuple(gathered_shares)?;
+            // 4. Unshuffle with the inverse permutations of parties 2 and 1
+            let unshuffled =
+                permute_shares(gathered, perm_2.inverse_permutation()?, 2, prf_keys.clone())?;
+            let unshuffled = convert_permuted_shares(unshuffled, 2, &prf_keys_vec)?;
+            let unshuffled =
+                permute_shares(unshuffled, perm_1.inverse_permutation()?, 1, prf_keys)?;
+            let unshuffled = convert_permuted_shares(unshuffled, 1, &prf_keys_vec)?;
+            let mut result_shares = vec![];
+            for i in 0..PARTIES as u64 {
+                result_shares.push(
+                    unshuffled
+                        .tuple_get(i)?
+                        .named_tuple_get(DATA_HEADER.to_owned())?,
+                );
+            }
+            g.create_tuple(result_shares)?.set_as_output()?;
+        }
         g.finalize()?;
         Ok(g)
     }
 
     fn get_name(&self) -> String {
-        format!(
-            "Permutation(sender:{},programming:{})",
-            self.sender_id, self.programmer_id
-        )
+        format!("ApplySharedPermutation(inverse:{})", self.inverse)
     }
 }
 
+register_custom_operation!(ApplySharedPermutationMPC, [Shared, Shared, PrfKeys]);
+
 /// Adds a node that duplicates some elements of an array shared between Sender and Programmer using a duplication map known to Programmer.
 /// The output shares are returned only to Receiver and Programmer.
 ///
@@ -1435,7 +3688,7 @@ impl CustomOperationBody for PermutationMPC {
 ///
 /// Tuple of duplicated 2-out-of-2 shares known to Receiver and Programmer
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
-struct DuplicationMPC {
+pub(crate) struct DuplicationMPC {
     pub sender_id: u64,
     pub programmer_id: u64, // The receiver ID is defined automatically
 }
@@ -1455,26 +3708,32 @@ impl CustomOperationBody for DuplicationMPC {
             let dup_indices_t = dup_map_types[0].clone();
             let dup_bits_t = dup_map_types[1].clone();
             if !dup_indices_t.is_array() || !dup_bits_t.is_array() {
-                panic!("Duplication map should contain two arrays");
+                return Err(runtime_error!("Duplication map should contain two arrays"));
             }
             if dup_indices_t.get_scalar_type() != UINT64 {
-                panic!("Duplication map indices should be of the UINT64 type");
+                return Err(runtime_error!(
+                    "Duplication map indices should be of the UINT64 type"
+                ));
             }
             if dup_bits_t.get_scalar_type() != BIT {
-                panic!("Duplication map bits should be of the BIT type");
+                return Err(runtime_error!(
+                    "Duplication map bits should be of the BIT type"
+                ));
             }
             let num_dup_indices = dup_indices_t.get_shape()[0];
             let num_dup_bits = dup_bits_t.get_shape()[0];
             if num_dup_indices != num_entries {
-                panic!(
+                return Err(runtime_error!(
                     "Duplication map indices should be of length equal to the number of entries"
-                );
+                ));
             }
             if num_dup_bits != num_entries {
-                panic!("Duplication map bits should be of length equal to the number of entries");
+                return Err(runtime_error!(
+                    "Duplication map bits should be of length equal to the number of entries"
+                ));
             }
         } else {
-            panic!("Duplication map should be a tuple");
+            return Err(runtime_error!("Duplication map should be a tuple"));
         }
 
         let sender_id = self.sender_id;
@@ -1636,157 +3895,753 @@ impl CustomOperationBody for DuplicationMPC {
                 b0_p.clone(),
             )?;
 
-            // Compute the share of Programmer which is equal to
-            // B_p - R + duplication_map(programmer column share)
-            let programmer_result_column = b_p.subtract(r.clone())?.add(
-                programmer_share
-                    .named_tuple_get(column_header.clone())?
-                    .gather(duplication_indices.clone(), 0)?,
-            )?;
+            // Compute the share of Programmer which is equal to
+            // B_p - R + duplication_map(programmer column share)
+            let programmer_result_column = b_p.subtract(r.clone())?.add(
+                programmer_share
+                    .named_tuple_get(column_header.clone())?
+                    .gather(duplication_indices.clone(), 0)?,
+            )?;
+
+            // Receiver's share B_r + R
+            let receiver_result_column = b_r.add(r)?;
+
+            receiver_columns.push((column_header.clone(), receiver_result_column));
+            programmer_columns.push((column_header, programmer_result_column));
+        }
+        let receiver_result_share = g.create_named_tuple(receiver_columns)?;
+        let programmer_result_share = g.create_named_tuple(programmer_columns)?;
+
+        g.create_tuple(vec![programmer_result_share, receiver_result_share])?
+            .set_as_output()?;
+
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "Duplication(sender:{},programming:{})",
+            self.sender_id, self.programmer_id
+        )
+    }
+}
+
+register_custom_operation!(DuplicationMPC, [Shared, Any, PrfKeys]);
+
+/// Adds a node that computes a switching network on data shared by Sender and Programmer using a switching map known to Programmer.
+///
+/// The output shares are returned only to Receiver and Programmer.
+///
+/// A switching network map is a one-dimensional array of length `m` that contains non-unique indices of an array of length `n`, which is not smaller than `m`.
+///
+/// Input shares are assumed to be a tuple of 2-out-of-2 shares.
+/// Each share must be a named tuple containing integer or binary arrays.
+/// So databases converted to such named tuples are handled column-wise.
+///
+/// The protocol follows the Switch protocol from <https://eprint.iacr.org/2019/518.pdf>.
+/// For each column header, the following steps are performed.
+/// 1. Programmer decomposes a given switching map into a permutation-with-deletion, duplication and permutation maps using the `DecomposeSwitchingMap` operation.
+/// 2. Sender and Programmer engage in the Permutation protocol using the permutation-with-deletion map.
+/// 3. Receiver and Programmer engage in the Duplication protocol using the duplication map.
+///
+/// **WARNING**: this function should not be used before MPC compilation.
+///
+/// # Custom operation arguments
+///
+/// - tuple of 2-out-of-2 shares owned by Sender and Programmer
+/// - an UINT64 array containing a switching map
+/// - tuple of 3 PRF keys used for multiplication
+///
+/// # Custom operation returns
+///
+/// Tuple of permuted 2-out-of-2 shares known to Receiver and Programmer
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+struct SwitchingMPC {
+    pub sender_id: u64,
+    pub programmer_id: u64, // The receiver ID is defined automatically
+}
+
+#[typetag::serde]
+impl CustomOperationBody for SwitchingMPC {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        // Check permutation and input types
+        let (num_entries, _) = check_and_extract_map_input_parameters(
+            &argument_types,
+            self.sender_id,
+            self.programmer_id,
+        )?;
+        // An additional check that the switching map is of the correct form
+        let switch_map_t = argument_types[1].clone();
+        if !switch_map_t.is_array() {
+            return Err(runtime_error!("Switching map should be an array"));
+        }
+        if switch_map_t.get_scalar_type() != UINT64 {
+            return Err(runtime_error!(
+                "Switching map indices should be of the UINT64 type"
+            ));
+        }
+        let num_switch_indices = switch_map_t.get_shape()[0];
+        if num_switch_indices > num_entries {
+            return Err(runtime_error!(
+                "Switching map cannot have more than {} indices",
+                num_entries
+            ));
+        }
+
+        let receiver_id = get_receiver_id(self.sender_id, self.programmer_id);
+
+        let shares_t = argument_types[0].clone();
+        let prf_t = argument_types[2].clone();
+
+        let g = context.create_graph()?;
+
+        let shares = g.input(shares_t)?;
+        let switch_map = g.input(switch_map_t)?;
+
+        // Generate randomness between all possible pairs of parties.
+        let prf_keys = g.input(prf_t)?;
+
+        // Programmer decomposes a given switching map into a permutation with deletion, duplication and permutation maps using the `DecomposeSwitchingMap` operation
+        let switch_decomposition = switch_map.decompose_switching_map(num_entries)?;
+
+        let permutation_with_deletion = switch_decomposition.tuple_get(0)?;
+        let duplication = switch_decomposition.tuple_get(1)?;
+        let permutation = switch_decomposition.tuple_get(2)?;
+
+        // Sender and Programmer engage in the Permutation protocol using the permutation-with-deletion map
+        let permuted_and_reduced_shares = g.custom_op(
+            CustomOperation::new(PermutationMPC {
+                sender_id: self.sender_id,
+                programmer_id: self.programmer_id,
+            }),
+            vec![shares, permutation_with_deletion, prf_keys.clone()],
+        )?;
+
+        // Receiver and Programmer engage in the Duplication protocol using the duplication map
+        let duplicated_shares = g.custom_op(
+            CustomOperation::new(DuplicationMPC {
+                sender_id: receiver_id,
+                programmer_id: self.programmer_id,
+            }),
+            vec![permuted_and_reduced_shares, duplication, prf_keys.clone()],
+        )?;
+
+        // Sender and Programmer engage in the Permutation protocol using the last permutation to produce the output of the switching map
+        let switched_shares = g.custom_op(
+            CustomOperation::new(PermutationMPC {
+                sender_id: self.sender_id,
+                programmer_id: self.programmer_id,
+            }),
+            vec![duplicated_shares, permutation, prf_keys],
+        )?;
+
+        switched_shares.set_as_output()?;
+
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "Switching(sender:{},programming:{})",
+            self.sender_id, self.programmer_id
+        )
+    }
+}
+
+register_custom_operation!(SwitchingMPC, [Shared, Array, PrfKeys]);
+
+// Creates a graph that sorts the rows of an array of length `num_entries` together with the indices of a shared switching map of length `num_indices`.
+//
+// The graph takes 2-out-of-3 shares of the switching map and returns shares of the sorting permutation and the bits indicating the rows that must copy the value of the preceding row.
+fn get_switching_sorting_graph(
+    context: Context,
+    num_entries: u64,
+    num_indices: u64,
+) -> Result<Graph> {
+    let sorting_context = create_context()?;
+    let g = sorting_context.create_graph()?;
+
+    let switching_map = g.input(array_type(vec![num_indices], UINT64))?;
+
+    let num_rows = num_entries + num_indices;
+    let num_sorted_rows = num_rows.next_power_of_two();
+    let log_num_sorted_rows = num_sorted_rows.trailing_zeros() as u64;
+    // Number of bits enough to represent indices from 0 to num_entries - 1
+    let index_bitlength = max(1, 64 - (num_entries - 1).leading_zeros() as u64);
+
+    let mut entry_index_bits = vec![];
+    for row_id in 0..num_entries {
+        for bit_id in 0..index_bitlength {
+            entry_index_bits.push((row_id >> bit_id) & 1);
+        }
+    }
+    let mut key_columns = vec![
+        g.constant(
+            array_type(vec![num_entries, index_bitlength], BIT),
+            Value::from_flattened_array(&entry_index_bits, BIT)?,
+        )?,
+        switching_map.a2b()?.get_slice(vec![
+            SliceElement::Ellipsis,
+            SliceElement::SubArray(None, Some(index_bitlength as i64), None),
+        ])?,
+    ];
+    if num_sorted_rows > num_rows {
+        key_columns.push(zeros(
+            &g,
+            array_type(vec![num_sorted_rows - num_rows, index_bitlength], BIT),
+        )?);
+    }
+    let mut position_bits = vec![];
+    for bit_id in 0..log_num_sorted_rows {
+        for row_id in 0..num_sorted_rows {
+            position_bits.push((row_id >> bit_id) & 1);
+        }
+    }
+    // Rows of the switching map and padding rows copy the value of the preceding row after sorting
+    let copy_bits: Vec<u64> = (0..num_sorted_rows)
+        .map(|row_id| (row_id >= num_entries) as u64)
+        .collect();
+    // Rows are sorted by indices, then by copy bits and then by positions
+    let pulled_out_rows = concatenate_first_axis(vec![
+        g.constant(
+            array_type(vec![log_num_sorted_rows, num_sorted_rows], BIT),
+            Value::from_flattened_array(&position_bits, BIT)?,
+        )?,
+        g.constant(
+            array_type(vec![1, num_sorted_rows], BIT),
+            Value::from_flattened_array(&copy_bits, BIT)?,
+        )?,
+        pull_out_bits(concatenate_first_axis(key_columns)?)?,
+    ])?;
+    let sorted_rows = g.custom_op(
+        CustomOperation::new(Sort {
+            k: log_num_sorted_rows as u32,
+            b: log_num_sorted_rows + 1 + index_bitlength,
+            signed_comparison: false,
+        }),
+        vec![put_in_bits(pulled_out_rows)?],
+    )?;
+    let pulled_out_sorted_rows = pull_out_bits(sorted_rows)?;
+
+    // Position bits are converted to integers via multiplication by powers of two to avoid binary-to-arithmetic conversion
+    let powers_of_two: Vec<u64> = (0..log_num_sorted_rows).map(|i| 1 << i).collect();
+    let permutation = g
+        .constant(
+            array_type(vec![log_num_sorted_rows, 1], UINT64),
+            Value::from_flattened_array(&powers_of_two, UINT64)?,
+        )?
+        .mixed_multiply(
+            pulled_out_sorted_rows.get_slice(vec![SliceElement::SubArray(
+                None,
+                Some(log_num_sorted_rows as i64),
+                None,
+            )])?,
+        )?
+        .sum(vec![0])?;
+    let sorted_copy_bits = pulled_out_sorted_rows.get(vec![log_num_sorted_rows])?;
+
+    g.create_tuple(vec![permutation, sorted_copy_bits])?
+        .set_as_output()?;
+    g.finalize()?;
+    sorting_context.set_main_graph(g)?;
+    sorting_context.finalize()?;
+
+    convert_main_graph_to_mpc(sorting_context, context, vec![true])
+}
+
+/// Adds a node that applies a secret-shared switching map to a secret-shared database, such that no party learns the switching map.
+///
+/// A switching network map is a one-dimensional array of length `m` that contains non-unique indices of an array of length `n`.
+/// The i-th row of the output is the `switching_map[i]`-th row of the input, as in [Graph::gather] along the first axis.
+/// In contrast to [SwitchingMPC], where Programmer decomposes the switching map in the clear with the `DecomposeSwitchingMap` operation,
+/// both the database and the switching map are 2-out-of-3 shared, so oblivious joins can be built without any party learning the hash maps.
+/// Indices of the switching map must be smaller than `n`; otherwise, the result is undefined.
+///
+/// Input shares are assumed to be a tuple of 2-out-of-3 shares.
+/// Each share must be a named tuple containing integer or binary arrays with `n` rows.
+///
+/// The decomposition of the switching map is computed obliviously by sorting.
+/// 1. The indices from 0 to `n-1` of the input rows and the indices of the switching map are sorted together by the Batcher's sorting network,
+///    such that every input row is followed by the entries of the switching map pointing to it.
+///    Parties get the shares of the sorting permutation and of the bits indicating the sorted entries that come from the switching map.
+///    These play the roles of the permutation-with-deletion and duplication maps of `DecomposeSwitchingMap`.
+/// 2. Every column is padded with `m` zero rows (and zero rows up to the next power of two) and sorted by [ApplySharedPermutationMPC] with the sorting permutation.
+/// 3. Every padding row copies the value of the nearest preceding input row by a segmented prefix computation with the above bits, which takes `log(n + m)` rounds of multiplications.
+/// 4. The columns are unsorted by [ApplySharedPermutationMPC] with the inverse of the sorting permutation, and the padding rows in place of the switching map form the result.
+///
+/// Only the uniformly random shuffled permutations of [ApplySharedPermutationMPC] are revealed during the protocol.
+/// The communication grows as O(N log<sup>2</sup> N) with N = n + m due to sorting.
+///
+/// **WARNING**: this function should not be used before MPC compilation.
+///
+/// # Custom operation arguments
+///
+/// - tuple of 2-out-of-3 shares of a named tuple containing integer or binary arrays with `n` rows
+/// - tuple of 2-out-of-3 shares of a UINT64 array of length `m` containing a switching map
+/// - tuple of 3 PRF keys used for multiplication
+///
+/// # Custom operation returns
+///
+/// Tuple of 2-out-of-3 shares of a named tuple containing the switched arrays with `m` rows
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, named_tuple_type, tuple_type, INT32, UINT64};
+/// # use ciphercore_base::custom_ops::CustomOperation;
+/// # use ciphercore_base::data_types::BIT;
+/// # use ciphercore_base::mpc::mpc_compiler::KEY_LENGTH;
+/// # use ciphercore_base::mpc::mpc_psi::SharedSwitchingMPC;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let share_t = named_tuple_type(vec![("a".to_owned(), array_type(vec![4, 2], INT32))]);
+/// let data = g.input(tuple_type(vec![share_t; 3])).unwrap();
+/// let switching_map = g.input(tuple_type(vec![array_type(vec![6], UINT64); 3])).unwrap();
+/// let prf_keys = g.input(tuple_type(vec![array_type(vec![KEY_LENGTH], BIT); 3])).unwrap();
+/// let n = g.custom_op(
+///     CustomOperation::new(SharedSwitchingMPC {}),
+///     vec![data, switching_map, prf_keys],
+/// ).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct SharedSwitchingMPC {}
+
+#[typetag::serde]
+impl CustomOperationBody for SharedSwitchingMPC {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 3 {
+            return Err(runtime_error!(
+                "Shared switching should have 3 inputs: a shared database, a shared switching map and PRF keys"
+            ));
+        }
+        let data_t = argument_types[0].clone();
+        let switching_map_t = argument_types[1].clone();
+        let prf_t = argument_types[2].clone();
+        let share_t = match &data_t {
+            Type::Tuple(share_types) if share_types.len() == PARTIES => (*share_types[0]).clone(),
+            _ => {
+                return Err(runtime_error!(
+                    "Input data must be a tuple of {} shares of a named tuple",
+                    PARTIES
+                ));
+            }
+        };
+        if !share_t.is_named_tuple() || data_t != tuple_type(vec![share_t.clone(); PARTIES]) {
+            return Err(runtime_error!(
+                "Input data must be a tuple of {} shares of a named tuple",
+                PARTIES
+            ));
+        }
+        let column_header_types = get_named_types(share_t)?;
+        let mut num_entries = 0;
+        for (_, column_t) in &column_header_types {
+            if !column_t.is_array() {
+                return Err(runtime_error!("Column must be an array"));
+            }
+            let num_column_entries = column_t.get_shape()[0];
+            if num_entries == 0 {
+                num_entries = num_column_entries;
+            }
+            if num_entries != num_column_entries {
+                return Err(runtime_error!(
+                    "Number of entries should be the same in all columns"
+                ));
+            }
+        }
+        if num_entries == 0 {
+            return Err(runtime_error!("Input data must have at least one column"));
+        }
+        let num_indices = match &switching_map_t {
+            Type::Tuple(share_types) if share_types.len() == PARTIES => match &*share_types[0] {
+                Type::Array(shape, UINT64) if shape.len() == 1 => shape[0],
+                _ => 0,
+            },
+            _ => 0,
+        };
+        if num_indices == 0
+            || switching_map_t != tuple_type(vec![array_type(vec![num_indices], UINT64); PARTIES])
+        {
+            return Err(runtime_error!(
+                "Switching map must be a tuple of {} shares of a one-dimensional UINT64 array",
+                PARTIES
+            ));
+        }
+        let expected_key_type = tuple_type(vec![array_type(vec![KEY_LENGTH], BIT); PARTIES]);
+        if prf_t != expected_key_type {
+            return Err(runtime_error!(
+                "PRF key type should be a tuple of 3 binary arrays of length {}",
+                KEY_LENGTH
+            ));
+        }
+
+        let sorting_g = get_switching_sorting_graph(context.clone(), num_entries, num_indices)?;
+
+        let g = context.create_graph()?;
+        let data = g.input(data_t)?;
+        let switching_map = g.input(switching_map_t)?;
+        let prf_keys = g.input(prf_t)?;
+
+        // 1. Sort the input rows together with the indices of the switching map
+        let sorting = g.call(sorting_g, vec![prf_keys.clone(), switching_map])?;
+        let get_sorting_element = |index: u64| -> Result<Node> {
+            let mut shares = vec![];
+            for share_id in 0..PARTIES as u64 {
+                shares.push(sorting.tuple_get(share_id)?.tuple_get(index)?);
+            }
+            g.create_tuple(shares)
+        };
+        let permutation = get_sorting_element(0)?;
+        let copy_bits = get_sorting_element(1)?;
+        let num_sorted_rows = copy_bits.tuple_get(0)?.get_type()?.get_shape()[0];
+
+        // Applies a given function to every share of a shared array
+        let map_shares = |a: Node, f: &dyn Fn(Node) -> Result<Node>| -> Result<Node> {
+            let mut shares = vec![];
+            for share_id in 0..PARTIES as u64 {
+                shares.push(f(a.tuple_get(share_id)?)?);
+            }
+            g.create_tuple(shares)
+        };
+        // Shifts the rows of a shared array down by a given number of rows filling the first rows with zeros
+        let shift_rows = |a: Node, num_shifted_rows: u64| -> Result<Node> {
+            let t = a.tuple_get(0)?.get_type()?;
+            let mut zeros_shape = t.get_shape();
+            zeros_shape[0] = num_shifted_rows;
+            map_shares(a, &|share| {
+                concatenate_first_axis(vec![
+                    zeros(&g, array_type(zeros_shape.clone(), t.get_scalar_type()))?,
+                    share.get_slice(vec![SliceElement::SubArray(
+                        None,
+                        Some((num_sorted_rows - num_shifted_rows) as i64),
+                        None,
+                    )])?,
+                ])
+            })
+        };
+        let permute = |a: Node, inverse: bool| -> Result<Node> {
+            g.custom_op(
+                CustomOperation::new(ApplySharedPermutationMPC { inverse }),
+                vec![a, permutation.clone(), prf_keys.clone()],
+            )
+        };
 
-            // Receiver's share B_r + R
-            let receiver_result_column = b_r.add(r)?;
+        // Bits of the segmented prefix computation: the row i copies the row i - 2^j at the j-th round if the j-th bit array has 1 in the i-th position
+        let mut copy_bits_per_round = vec![copy_bits];
+        while (1 << copy_bits_per_round.len()) < num_sorted_rows {
+            let last_bits = copy_bits_per_round[copy_bits_per_round.len() - 1].clone();
+            let shifted_bits = shift_rows(last_bits.clone(), 1 << (copy_bits_per_round.len() - 1))?;
+            copy_bits_per_round.push(multiply_mpc(last_bits, shifted_bits, prf_keys.clone())?);
+        }
 
-            receiver_columns.push((column_header.clone(), receiver_result_column));
-            programmer_columns.push((column_header, programmer_result_column));
+        let mut result_columns = vec![];
+        for (header, column_t) in &column_header_types {
+            let shape = column_t.get_shape();
+            let mut column_shares = vec![];
+            for share_id in 0..PARTIES as u64 {
+                column_shares.push(data.tuple_get(share_id)?.named_tuple_get(header.clone())?);
+            }
+            // 2. Pad the column with zero rows in place of the switching map and sort it
+            let mut padding_shape = shape.clone();
+            padding_shape[0] = num_sorted_rows - num_entries;
+            let padding = zeros(&g, array_type(padding_shape, column_t.get_scalar_type()))?;
+            let padded_column = map_shares(g.create_tuple(column_shares)?, &|share| {
+                concatenate_first_axis(vec![share, padding.clone()])
+            })?;
+            let mut sorted_column = permute(padded_column, false)?;
+
+            // 3. Copy the values of the input rows to the following rows of the switching map
+            let mut mask_shape = vec![num_sorted_rows];
+            mask_shape.extend(vec![1; shape.len() - 1]);
+            for (round, bits) in copy_bits_per_round.iter().enumerate() {
+                let shifted_column = shift_rows(sorted_column.clone(), 1 << round)?;
+                let difference = subtract_mpc(shifted_column, sorted_column.clone())?;
+                let copied_difference = multiply_by_bits_mpc(
+                    difference,
+                    reshape_shared_array(bits.clone(), array_type(mask_shape.clone(), BIT))?,
+                    prf_keys.clone(),
+                )?;
+                sorted_column = add_mpc(sorted_column, copied_difference)?;
+            }
+
+            // 4. Unsort the column and extract the rows in place of the switching map
+            let result_column = map_shares(permute(sorted_column, true)?, &|share| {
+                share.get_slice(vec![SliceElement::SubArray(
+                    Some(num_entries as i64),
+                    Some((num_entries + num_indices) as i64),
+                    None,
+                )])
+            })?;
+            result_columns.push((header.clone(), result_column));
         }
-        let receiver_result_share = g.create_named_tuple(receiver_columns)?;
-        let programmer_result_share = g.create_named_tuple(programmer_columns)?;
 
-        g.create_tuple(vec![programmer_result_share, receiver_result_share])?
-            .set_as_output()?;
+        let mut result_shares = vec![];
+        for share_id in 0..PARTIES as u64 {
+            let mut columns = vec![];
+            for (header, column) in &result_columns {
+                columns.push((header.clone(), column.tuple_get(share_id)?));
+            }
+            result_shares.push(g.create_named_tuple(columns)?);
+        }
+        g.create_tuple(result_shares)?.set_as_output()?;
 
         g.finalize()?;
         Ok(g)
     }
 
     fn get_name(&self) -> String {
-        format!(
-            "Duplication(sender:{},programming:{})",
-            self.sender_id, self.programmer_id
-        )
+        "SharedSwitching".to_owned()
     }
 }
 
-/// Adds a node that computes a switching network on data shared by Sender and Programmer using a switching map known to Programmer.
-///
-/// The output shares are returned only to Receiver and Programmer.
+register_custom_operation!(SharedSwitchingMPC, [Shared, Shared, PrfKeys]);
+
+/// Adds a node that writes the rows of a secret-shared array to another secret-shared array at secret-shared indices, such that no party learns the indices.
 ///
-/// A switching network map is a one-dimensional array of length `m` that contains non-unique indices of an array of length `n`, which is not smaller than `m`.
+/// This is the MPC counterpart of [Graph::scatter](crate::graphs::Graph::scatter) with private indices.
+/// Given an input array with `n` rows along the axis `axis`, `k` indices and an array of updates with `k` rows along this axis,
+/// the `i`-th row of the updates replaces the `indices[i]`-th row of the input.
+/// Indices must be unique and smaller than `n`; otherwise, the result is undefined.
 ///
-/// Input shares are assumed to be a tuple of 2-out-of-2 shares.
-/// Each share must be a named tuple containing integer or binary arrays.
-/// So databases converted to such named tuples are handled column-wise.
+/// The rows are arranged by sorting as in [SharedSwitchingMPC], but values are copied in the opposite direction.
+/// 1. The indices from 0 to `n-1` of the input rows and the indices of the updates are sorted together by the Batcher's sorting network,
+///    such that every input row is followed by the update pointing to it, if any.
+///    Parties get the shares of the sorting permutation and of the bits indicating the sorted entries that come from the updates.
+/// 2. The input array is padded with the updates and with copies of its first row up to the next power of two and sorted by [ApplySharedPermutationMPC] with the sorting permutation.
+///    The copies of the first row are sorted right after this row and the update pointing to it, so they never change it.
+/// 3. Every row followed by an update takes the value of this update, which takes one round of multiplications.
+/// 4. The array is unsorted by [ApplySharedPermutationMPC] with the inverse of the sorting permutation, and its first `n` rows form the result.
 ///
-/// The protocol follows the Switch protocol from <https://eprint.iacr.org/2019/518.pdf>.
-/// For each column header, the following steps are performed.
-/// 1. Programmer decomposes a given switching map into a permutation-with-deletion, duplication and permutation maps using the `DecomposeSwitchingMap` operation.
-/// 2. Sender and Programmer engage in the Permutation protocol using the permutation-with-deletion map.
-/// 3. Receiver and Programmer engage in the Duplication protocol using the duplication map.
+/// Only the uniformly random shuffled permutations of [ApplySharedPermutationMPC] are revealed during the protocol.
+/// The communication grows as O(N log<sup>2</sup> N) with N = n + k due to sorting.
 ///
 /// **WARNING**: this function should not be used before MPC compilation.
 ///
 /// # Custom operation arguments
 ///
-/// - tuple of 2-out-of-2 shares owned by Sender and Programmer
-/// - an UINT64 array containing a switching map
+/// - tuple of 2-out-of-3 shares of an integer or binary input array
+/// - tuple of 2-out-of-3 shares of a UINT64 array of length `k` containing unique indices
+/// - tuple of 2-out-of-3 shares of an array of updates, whose shape is the input shape with `k` rows along `axis`
 /// - tuple of 3 PRF keys used for multiplication
 ///
 /// # Custom operation returns
 ///
-/// Tuple of permuted 2-out-of-2 shares known to Receiver and Programmer
+/// Tuple of 2-out-of-3 shares of the updated input array
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, tuple_type, INT32, UINT64};
+/// # use ciphercore_base::custom_ops::CustomOperation;
+/// # use ciphercore_base::data_types::BIT;
+/// # use ciphercore_base::mpc::mpc_compiler::KEY_LENGTH;
+/// # use ciphercore_base::mpc::mpc_psi::ScatterMPC;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let input = g.input(tuple_type(vec![array_type(vec![6, 2], INT32); 3])).unwrap();
+/// let indices = g.input(tuple_type(vec![array_type(vec![3], UINT64); 3])).unwrap();
+/// let updates = g.input(tuple_type(vec![array_type(vec![3, 2], INT32); 3])).unwrap();
+/// let prf_keys = g.input(tuple_type(vec![array_type(vec![KEY_LENGTH], BIT); 3])).unwrap();
+/// let n = g.custom_op(
+///     CustomOperation::new(ScatterMPC { axis: 0 }),
+///     vec![input, indices, updates, prf_keys],
+/// ).unwrap();
+/// ```
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
-struct SwitchingMPC {
-    pub sender_id: u64,
-    pub programmer_id: u64, // The receiver ID is defined automatically
+pub struct ScatterMPC {
+    pub axis: u64,
 }
 
 #[typetag::serde]
-impl CustomOperationBody for SwitchingMPC {
+impl CustomOperationBody for ScatterMPC {
     fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
-        // Check permutation and input types
-        let (num_entries, _) = check_and_extract_map_input_parameters(
-            &argument_types,
-            self.sender_id,
-            self.programmer_id,
-        )?;
-        // An additional check that the switching map is of the correct form
-        let switch_map_t = argument_types[1].clone();
-        if !switch_map_t.is_array() {
-            panic!("Switching map should be an array");
+        if argument_types.len() != 4 {
+            return Err(runtime_error!(
+                "Shared scatter should have 4 inputs: a shared array, shared indices, shared updates and PRF keys"
+            ));
         }
-        if switch_map_t.get_scalar_type() != UINT64 {
-            panic!("Switching map indices should be of the UINT64 type");
+        let input_t = argument_types[0].clone();
+        let indices_t = argument_types[1].clone();
+        let updates_t = argument_types[2].clone();
+        let prf_t = argument_types[3].clone();
+        let share_t = match &input_t {
+            Type::Tuple(share_types) if share_types.len() == PARTIES => (*share_types[0]).clone(),
+            _ => {
+                return Err(runtime_error!(
+                    "Input must be a tuple of {} shares of an array",
+                    PARTIES
+                ));
+            }
+        };
+        if !share_t.is_array() || input_t != tuple_type(vec![share_t.clone(); PARTIES]) {
+            return Err(runtime_error!(
+                "Input must be a tuple of {} shares of an array",
+                PARTIES
+            ));
         }
-        let num_switch_indices = switch_map_t.get_shape()[0];
-        if num_switch_indices > num_entries {
-            panic!(
-                "Switching map cannot have more than {} indices",
+        let shape = share_t.get_shape();
+        let axis = self.axis as usize;
+        if axis >= shape.len() {
+            return Err(runtime_error!(
+                "Invalid axis. The axis index should be smaller than {}",
+                shape.len()
+            ));
+        }
+        let num_entries = shape[axis];
+        let num_indices = match &indices_t {
+            Type::Tuple(share_types) if share_types.len() == PARTIES => match &*share_types[0] {
+                Type::Array(indices_shape, UINT64) if indices_shape.len() == 1 => indices_shape[0],
+                _ => 0,
+            },
+            _ => 0,
+        };
+        if num_indices == 0
+            || num_indices > num_entries
+            || indices_t != tuple_type(vec![array_type(vec![num_indices], UINT64); PARTIES])
+        {
+            return Err(runtime_error!(
+                "Indices must be a tuple of {} shares of a one-dimensional UINT64 array of length at most {}",
+                PARTIES,
                 num_entries
-            );
+            ));
+        }
+        let mut updates_shape = shape.clone();
+        updates_shape[axis] = num_indices;
+        let expected_updates_t =
+            tuple_type(vec![
+                array_type(updates_shape, share_t.get_scalar_type());
+                PARTIES
+            ]);
+        if updates_t != expected_updates_t {
+            return Err(runtime_error!(
+                "Updates must be of type {:?}, but {:?} is given",
+                expected_updates_t,
+                updates_t
+            ));
+        }
+        let expected_key_type = tuple_type(vec![array_type(vec![KEY_LENGTH], BIT); PARTIES]);
+        if prf_t != expected_key_type {
+            return Err(runtime_error!(
+                "PRF key type should be a tuple of 3 binary arrays of length {}",
+                KEY_LENGTH
+            ));
         }
 
-        let receiver_id = get_receiver_id(self.sender_id, self.programmer_id);
-
-        let shares_t = argument_types[0].clone();
-        let prf_t = argument_types[2].clone();
+        let sorting_g = get_switching_sorting_graph(context.clone(), num_entries, num_indices)?;
 
         let g = context.create_graph()?;
-
-        let shares = g.input(shares_t)?;
-        let switch_map = g.input(switch_map_t)?;
-
-        // Generate randomness between all possible pairs of parties.
+        let input = g.input(input_t)?;
+        let indices = g.input(indices_t)?;
+        let updates = g.input(updates_t)?;
         let prf_keys = g.input(prf_t)?;
 
-        // Programmer decomposes a given switching map into a permutation with deletion, duplication and permutation maps using the `DecomposeSwitchingMap` operation
-        let switch_decomposition = switch_map.decompose_switching_map(num_entries)?;
-
-        let permutation_with_deletion = switch_decomposition.tuple_get(0)?;
-        let duplication = switch_decomposition.tuple_get(1)?;
-        let permutation = switch_decomposition.tuple_get(2)?;
-
-        // Sender and Programmer engage in the Permutation protocol using the permutation-with-deletion map
-        let permuted_and_reduced_shares = g.custom_op(
-            CustomOperation::new(PermutationMPC {
-                sender_id: self.sender_id,
-                programmer_id: self.programmer_id,
-            }),
-            vec![shares, permutation_with_deletion, prf_keys.clone()],
-        )?;
-
-        // Receiver and Programmer engage in the Duplication protocol using the duplication map
-        let duplicated_shares = g.custom_op(
-            CustomOperation::new(DuplicationMPC {
-                sender_id: receiver_id,
-                programmer_id: self.programmer_id,
-            }),
-            vec![permuted_and_reduced_shares, duplication, prf_keys.clone()],
-        )?;
+        // 1. Sort the input rows together with the indices of the updates
+        let sorting = g.call(sorting_g, vec![prf_keys.clone(), indices])?;
+        let get_sorting_element = |index: u64| -> Result<Node> {
+            let mut shares = vec![];
+            for share_id in 0..PARTIES as u64 {
+                shares.push(sorting.tuple_get(share_id)?.tuple_get(index)?);
+            }
+            g.create_tuple(shares)
+        };
+        let permutation = get_sorting_element(0)?;
+        let copy_bits = get_sorting_element(1)?;
+        let num_sorted_rows = copy_bits.tuple_get(0)?.get_type()?.get_shape()[0];
 
-        // Sender and Programmer engage in the Permutation protocol using the last permutation to produce the output of the switching map
-        let switched_shares = g.custom_op(
-            CustomOperation::new(PermutationMPC {
-                sender_id: self.sender_id,
-                programmer_id: self.programmer_id,
-            }),
-            vec![duplicated_shares, permutation, prf_keys],
-        )?;
+        // Applies a given function to every share of a shared array
+        let map_shares = |a: Node, f: &dyn Fn(Node) -> Result<Node>| -> Result<Node> {
+            let mut shares = vec![];
+            for share_id in 0..PARTIES as u64 {
+                shares.push(f(a.tuple_get(share_id)?)?);
+            }
+            g.create_tuple(shares)
+        };
+        // Shifts the rows of a shared array up by one row filling the last row with zeros
+        let shift_rows_up = |a: Node| -> Result<Node> {
+            let t = a.tuple_get(0)?.get_type()?;
+            let mut zeros_shape = t.get_shape();
+            zeros_shape[0] = 1;
+            map_shares(a, &|share| {
+                concatenate_first_axis(vec![
+                    share.get_slice(vec![SliceElement::SubArray(Some(1), None, None)])?,
+                    zeros(&g, array_type(zeros_shape.clone(), t.get_scalar_type()))?,
+                ])
+            })
+        };
+        let permute = |a: Node, inverse: bool| -> Result<Node> {
+            g.custom_op(
+                CustomOperation::new(ApplySharedPermutationMPC { inverse }),
+                vec![a, permutation.clone(), prf_keys.clone()],
+            )
+        };
+        // Rows are indexed along the first axis during the protocol
+        let mut front_axes = vec![self.axis];
+        front_axes.extend((0..shape.len() as u64).filter(|i| *i != self.axis));
+        let mut back_axes = vec![0; shape.len()];
+        for (i, front_axis) in front_axes.iter().enumerate() {
+            back_axes[*front_axis as usize] = i as u64;
+        }
+        let permute_axes = |a: Node, axes: &ArrayShape| -> Result<Node> {
+            if axis == 0 {
+                return Ok(a);
+            }
+            map_shares(a, &|share| share.permute_axes(axes.clone()))
+        };
 
-        switched_shares.set_as_output()?;
+        // 2. Pad the input with the updates and copies of the first input row and sort it
+        let input_rows = permute_axes(input, &front_axes)?;
+        let updates_rows = permute_axes(updates, &front_axes)?;
+        let num_padding_rows = num_sorted_rows - num_entries - num_indices;
+        let mut padded_shares = vec![];
+        for share_id in 0..PARTIES as u64 {
+            let input_share = input_rows.tuple_get(share_id)?;
+            let mut parts = vec![input_share.clone(), updates_rows.tuple_get(share_id)?];
+            if num_padding_rows > 0 {
+                let mut reps = vec![1; shape.len()];
+                reps[0] = num_padding_rows;
+                parts.push(
+                    input_share
+                        .get_slice(vec![SliceElement::SubArray(None, Some(1), None)])?
+                        .tile(reps)?,
+                );
+            }
+            padded_shares.push(concatenate_first_axis(parts)?);
+        }
+        let sorted_rows = permute(g.create_tuple(padded_shares)?, false)?;
+
+        // 3. Copy the values of the updates to the preceding input rows
+        let mut mask_shape = vec![num_sorted_rows];
+        mask_shape.extend(vec![1; shape.len() - 1]);
+        let next_copy_bits =
+            reshape_shared_array(shift_rows_up(copy_bits)?, array_type(mask_shape, BIT))?;
+        let difference = subtract_mpc(shift_rows_up(sorted_rows.clone())?, sorted_rows.clone())?;
+        let copied_difference = multiply_by_bits_mpc(difference, next_copy_bits, prf_keys.clone())?;
+        let updated_rows = add_mpc(sorted_rows, copied_difference)?;
+
+        // 4. Unsort the rows and extract the input rows
+        let result_rows = map_shares(permute(updated_rows, true)?, &|share| {
+            share.get_slice(vec![SliceElement::SubArray(
+                None,
+                Some(num_entries as i64),
+                None,
+            )])
+        })?;
+        permute_axes(result_rows, &back_axes)?.set_as_output()?;
 
         g.finalize()?;
         Ok(g)
     }
 
     fn get_name(&self) -> String {
-        format!(
-            "Switching(sender:{},programming:{})",
-            self.sender_id, self.programmer_id
-        )
+        format!("Scatter(axis:{})", self.axis)
     }
 }
 
+register_custom_operation!(ScatterMPC, [Shared, Shared, Shared, PrfKeys]);
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -1796,17 +4651,22 @@ mod tests {
     use super::*;
 
     use crate::custom_ops::{run_instantiation_pass, CustomOperation};
-    use crate::data_types::{scalar_type, ArrayShape, INT16, INT32, INT64};
+    use crate::data_types::{
+        scalar_type, string_column_type, ArrayShape, ScalarType, INT16, INT32, INT64,
+    };
     use crate::data_values::Value;
-    use crate::evaluators::{evaluate_simple_evaluator, random_evaluate};
-    use crate::graphs::create_context;
+    use crate::evaluators::{
+        evaluate_simple_evaluator, evaluate_simple_evaluator_with_retries, random_evaluate,
+    };
+    use crate::graphs::{create_context, Operation};
     use crate::inline::inline_ops::{inline_operations, InlineConfig, InlineMode};
     use crate::mpc::mpc_compiler::{generate_prf_key_triple, prepare_for_mpc_evaluation, IOStatus};
     use crate::mpc::mpc_equivalence_class::{
         generate_equivalence_class, private_class, share0_class, share1_class, share2_class,
-        vector_class, EquivalenceClasses,
+        vector_class, verify_privacy, EquivalenceClasses,
     };
-    use crate::random::SEED_SIZE;
+    use crate::random::{PRNG, SEED_SIZE};
+    use crate::typed_value::TypedValue;
 
     fn simple_hash_helper(
         input_shape: ArrayShape,
@@ -1938,6 +4798,59 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn test_malformed_map_inputs() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let share = g.input(named_tuple_type(vec![(
+                "a".to_owned(),
+                array_type(vec![4], INT32),
+            )]))?;
+            let keys = g.create_tuple(generate_prf_key_triple(g.clone())?)?;
+            let map = g.input(array_type(vec![4], UINT64))?;
+            let shares = g.create_tuple(vec![share.clone(), share.clone()])?;
+            // Malformed inputs result in errors pointing to the custom operation instead of panics
+            let custom_node_id = g.get_nodes().len() as u64;
+            let e = g
+                .custom_op(
+                    CustomOperation::new(PermutationMPC {
+                        sender_id: 0,
+                        programmer_id: 0,
+                    }),
+                    vec![shares.clone(), map.clone(), keys.clone()],
+                )
+                .err()
+                .unwrap();
+            assert_eq!(
+                e.get_custom_operation_name(),
+                Some("Permutation(sender:0,programming:0)".to_owned())
+            );
+            assert_eq!(e.get_node_global_id(), Some((0, custom_node_id)));
+            let three_shares = g.create_tuple(vec![share.clone(), share.clone(), share])?;
+            assert!(g
+                .custom_op(
+                    CustomOperation::new(SwitchingMPC {
+                        sender_id: 0,
+                        programmer_id: 1,
+                    }),
+                    vec![three_shares, map.clone(), keys.clone()],
+                )
+                .is_err());
+            assert!(g
+                .custom_op(
+                    CustomOperation::new(DuplicationMPC {
+                        sender_id: 0,
+                        programmer_id: 1,
+                    }),
+                    vec![shares, map, keys],
+                )
+                .is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
     #[test]
     fn test_permutation() {
         let data_helper = |a_type: Type,
@@ -2239,15 +5152,436 @@ mod tests {
         )
         .unwrap();
 
-        data_helper(
-            array_type(vec![5, 2], BIT),
-            array_type(vec![5], UINT64),
-            &[0, 0, 0, 1, 1, 0, 1, 1, 0, 1],
-            &[10, 20, 30, 40, 50],
-            &[0, 2, 4, 1],
-            &[0, 0, 1, 0, 0, 1, 0, 1],
-            &[10, 30, 50, 20],
-        )
+        data_helper(
+            array_type(vec![5, 2], BIT),
+            array_type(vec![5], UINT64),
+            &[0, 0, 0, 1, 1, 0, 1, 1, 0, 1],
+            &[10, 20, 30, 40, 50],
+            &[0, 2, 4, 1],
+            &[0, 0, 1, 0, 0, 1, 0, 1],
+            &[10, 30, 50, 20],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_apply_shared_permutation() {
+        let helper = |shape: Vec<u64>,
+                      values: &[u64],
+                      permutation_values: &[u64],
+                      inverse: bool|
+         -> Result<Vec<u64>> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let num_entries = permutation_values.len() as u64;
+            let data_t = array_type(shape, UINT64);
+            let permutation_t = array_type(vec![num_entries], UINT64);
+            let data = g.input(tuple_type(vec![data_t.clone(); PARTIES]))?;
+            let permutation = g.input(tuple_type(vec![permutation_t.clone(); PARTIES]))?;
+            let prf_keys = g.create_tuple(generate_prf_key_triple(g.clone())?)?;
+            g.custom_op(
+                CustomOperation::new(ApplySharedPermutationMPC { inverse }),
+                vec![data, permutation, prf_keys],
+            )?
+            .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let instantiated_c = run_instantiation_pass(c)?.context;
+            let inlined_c = inline_operations(
+                instantiated_c,
+                InlineConfig {
+                    default_mode: InlineMode::Simple,
+                    ..Default::default()
+                },
+            )?;
+            // Values are sent only to parties that don't hold them yet, and secret shares are masked before sending
+            verify_privacy(
+                inlined_c.clone(),
+                vec![IOStatus::Shared, IOStatus::Shared],
+                vec![IOStatus::Shared],
+            )?;
+
+            // Split plaintext values into 3 shares
+            let share = |values: &[u64]| -> Result<Value> {
+                let share0: Vec<u64> = (0..values.len() as u64).map(|i| i * 7 + 3).collect();
+                let share1: Vec<u64> = (0..values.len() as u64).map(|i| u64::MAX - i * 5).collect();
+                let share2: Vec<u64> = values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| v.wrapping_sub(share0[i]).wrapping_sub(share1[i]))
+                    .collect();
+                Ok(Value::from_vector(vec![
+                    Value::from_flattened_array(&share0, UINT64)?,
+                    Value::from_flattened_array(&share1, UINT64)?,
+                    Value::from_flattened_array(&share2, UINT64)?,
+                ]))
+            };
+            let result = random_evaluate(
+                inlined_c.get_main_graph()?,
+                vec![share(values)?, share(permutation_values)?],
+            )?
+            .to_vector()?;
+            let mut revealed = vec![0u64; values.len()];
+            for result_share in result {
+                let share_values = result_share.to_flattened_array_u64(data_t.clone())?;
+                for (r, v) in revealed.iter_mut().zip(share_values) {
+                    *r = r.wrapping_add(v);
+                }
+            }
+            Ok(revealed)
+        };
+        || -> Result<()> {
+            let permutation = [2, 0, 3, 1];
+            assert_eq!(
+                helper(vec![4], &[10, 20, 30, 40], &permutation, false)?,
+                vec![30, 10, 40, 20]
+            );
+            assert_eq!(
+                helper(vec![4], &[10, 20, 30, 40], &permutation, true)?,
+                vec![20, 40, 10, 30]
+            );
+            assert_eq!(
+                helper(vec![4, 2], &[1, 2, 3, 4, 5, 6, 7, 8], &permutation, false)?,
+                vec![5, 6, 1, 2, 7, 8, 3, 4]
+            );
+            assert_eq!(
+                helper(vec![4, 2], &[1, 2, 3, 4, 5, 6, 7, 8], &permutation, true)?,
+                vec![3, 4, 7, 8, 1, 2, 5, 6]
+            );
+            assert_eq!(helper(vec![1], &[5], &[0], true)?, vec![5]);
+            // Inversion of a permutation by applying its inverse to the identity
+            assert_eq!(
+                helper(vec![4], &[0, 1, 2, 3], &permutation, true)?,
+                vec![1, 3, 0, 2]
+            );
+            Ok(())
+        }()
+        .unwrap();
+
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let prf_keys = g.create_tuple(generate_prf_key_triple(g.clone())?)?;
+            let data = g.input(tuple_type(vec![array_type(vec![4], INT32); PARTIES]))?;
+            let op = CustomOperation::new(ApplySharedPermutationMPC { inverse: false });
+            let short_permutation =
+                g.input(tuple_type(vec![array_type(vec![3], UINT64); PARTIES]))?;
+            assert!(g
+                .custom_op(
+                    op.clone(),
+                    vec![data.clone(), short_permutation, prf_keys.clone()]
+                )
+                .is_err());
+            let public_permutation = g.input(array_type(vec![4], UINT64))?;
+            assert!(g
+                .custom_op(
+                    op.clone(),
+                    vec![data.clone(), public_permutation, prf_keys.clone()]
+                )
+                .is_err());
+            let two_shares = g.input(tuple_type(vec![array_type(vec![4], INT32); 2]))?;
+            let permutation = g.input(tuple_type(vec![array_type(vec![4], UINT64); PARTIES]))?;
+            assert!(g
+                .custom_op(op, vec![two_shares, permutation, prf_keys])
+                .is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_shared_switching() {
+        // Returns revealed columns `a` (UINT64 [n]) and `b` (BIT [n, 3]) after switching
+        let helper = |a_values: &[u64],
+                      b_values: &[u64],
+                      switching_map_values: &[u64]|
+         -> Result<(Vec<u64>, Vec<u64>)> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let num_entries = a_values.len() as u64;
+            let num_indices = switching_map_values.len() as u64;
+            let data_t = named_tuple_type(vec![
+                ("a".to_owned(), array_type(vec![num_entries], UINT64)),
+                ("b".to_owned(), array_type(vec![num_entries, 3], BIT)),
+            ]);
+            let switching_map_t = array_type(vec![num_indices], UINT64);
+            let data = g.input(tuple_type(vec![data_t.clone(); PARTIES]))?;
+            let switching_map = g.input(tuple_type(vec![switching_map_t.clone(); PARTIES]))?;
+            let prf_keys = g.create_tuple(generate_prf_key_triple(g.clone())?)?;
+            g.custom_op(
+                CustomOperation::new(SharedSwitchingMPC {}),
+                vec![data, switching_map, prf_keys],
+            )?
+            .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let instantiated_c = run_instantiation_pass(c)?.context;
+            let inlined_c = inline_operations(
+                instantiated_c,
+                InlineConfig {
+                    default_mode: InlineMode::Simple,
+                    ..Default::default()
+                },
+            )?;
+            // Neither the database nor the switching map is revealed to any party
+            verify_privacy(
+                inlined_c.clone(),
+                vec![IOStatus::Shared, IOStatus::Shared],
+                vec![IOStatus::Shared],
+            )?;
+
+            let mut prng = PRNG::new(None)?;
+            let data_value = Value::from_vector(vec![
+                Value::from_flattened_array(a_values, UINT64)?,
+                Value::from_flattened_array(b_values, BIT)?,
+            ]);
+            let shared_data = TypedValue::new(data_t, data_value)?.secret_share(&mut prng)?;
+            let shared_switching_map = TypedValue::new(
+                switching_map_t,
+                Value::from_flattened_array(switching_map_values, UINT64)?,
+            )?
+            .secret_share(&mut prng)?;
+            let main_g = inlined_c.get_main_graph()?;
+            let result = random_evaluate(
+                main_g.clone(),
+                vec![shared_data.value, shared_switching_map.value],
+            )?;
+            let revealed = TypedValue::new(main_g.get_output_node()?.get_type()?, result)?
+                .secret_share_reveal()?
+                .value
+                .to_vector()?;
+            Ok((
+                revealed[0].to_flattened_array_u64(array_type(vec![num_indices], UINT64))?,
+                revealed[1].to_flattened_array_u64(array_type(vec![num_indices, 3], BIT))?,
+            ))
+        };
+        || -> Result<()> {
+            let a = [10, 20, 30, 40, 50];
+            let b = [1, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0, 0, 1, 1];
+            assert_eq!(
+                helper(&a, &b, &[3, 0, 3, 4, 3, 1])?,
+                (
+                    vec![40, 10, 40, 50, 40, 20],
+                    vec![1, 1, 0, 1, 0, 0, 1, 1, 0, 0, 1, 1, 1, 1, 0, 0, 1, 0]
+                )
+            );
+            assert_eq!(helper(&a, &b, &[4])?, (vec![50], vec![0, 1, 1]));
+            assert_eq!(
+                helper(&[7], &[1, 0, 1], &[0, 0, 0])?,
+                (vec![7, 7, 7], vec![1, 0, 1, 1, 0, 1, 1, 0, 1])
+            );
+            Ok(())
+        }()
+        .unwrap();
+
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let prf_keys = g.create_tuple(generate_prf_key_triple(g.clone())?)?;
+            let data_t = named_tuple_type(vec![("a".to_owned(), array_type(vec![4], INT32))]);
+            let data = g.input(tuple_type(vec![data_t.clone(); PARTIES]))?;
+            let switching_map = g.input(tuple_type(vec![array_type(vec![6], UINT64); PARTIES]))?;
+            let op = CustomOperation::new(SharedSwitchingMPC {});
+            let public_switching_map = g.input(array_type(vec![6], UINT64))?;
+            assert!(g
+                .custom_op(
+                    op.clone(),
+                    vec![data.clone(), public_switching_map, prf_keys.clone()]
+                )
+                .is_err());
+            let signed_switching_map =
+                g.input(tuple_type(vec![array_type(vec![6], INT64); PARTIES]))?;
+            assert!(g
+                .custom_op(
+                    op.clone(),
+                    vec![data.clone(), signed_switching_map, prf_keys.clone()]
+                )
+                .is_err());
+            let two_shares = g.input(tuple_type(vec![data_t; 2]))?;
+            assert!(g
+                .custom_op(
+                    op.clone(),
+                    vec![two_shares, switching_map.clone(), prf_keys.clone()]
+                )
+                .is_err());
+            let array_data = g.input(tuple_type(vec![array_type(vec![4], INT32); PARTIES]))?;
+            assert!(g
+                .custom_op(
+                    op.clone(),
+                    vec![array_data, switching_map.clone(), prf_keys]
+                )
+                .is_err());
+            let wrong_keys = g.input(tuple_type(vec![array_type(vec![KEY_LENGTH], BIT); 2]))?;
+            assert!(g
+                .custom_op(op, vec![data, switching_map, wrong_keys])
+                .is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_scatter_mpc() {
+        let helper = |shape: ArrayShape,
+                      axis: u64,
+                      st: ScalarType,
+                      input_values: &[u64],
+                      indices_values: &[u64],
+                      updates_values: &[u64]|
+         -> Result<Vec<u64>> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let num_indices = indices_values.len() as u64;
+            let mut updates_shape = shape.clone();
+            updates_shape[axis as usize] = num_indices;
+            let input_t = array_type(shape, st.clone());
+            let indices_t = array_type(vec![num_indices], UINT64);
+            let updates_t = array_type(updates_shape, st);
+            let input = g.input(tuple_type(vec![input_t.clone(); PARTIES]))?;
+            let indices = g.input(tuple_type(vec![indices_t.clone(); PARTIES]))?;
+            let updates = g.input(tuple_type(vec![updates_t.clone(); PARTIES]))?;
+            let prf_keys = g.create_tuple(generate_prf_key_triple(g.clone())?)?;
+            g.custom_op(
+                CustomOperation::new(ScatterMPC { axis }),
+                vec![input, indices, updates, prf_keys],
+            )?
+            .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let instantiated_c = run_instantiation_pass(c)?.context;
+            let inlined_c = inline_operations(
+                instantiated_c,
+                InlineConfig {
+                    default_mode: InlineMode::Simple,
+                    ..Default::default()
+                },
+            )?;
+            // Neither the arrays nor the indices are revealed to any party
+            verify_privacy(
+                inlined_c.clone(),
+                vec![IOStatus::Shared, IOStatus::Shared, IOStatus::Shared],
+                vec![IOStatus::Shared],
+            )?;
+
+            let mut prng = PRNG::new(None)?;
+            let mut inputs = vec![];
+            for (t, values) in [
+                (input_t.clone(), input_values),
+                (indices_t, indices_values),
+                (updates_t, updates_values),
+            ] {
+                inputs.push(
+                    TypedValue::new(
+                        t.clone(),
+                        Value::from_flattened_array(values, t.get_scalar_type())?,
+                    )?
+                    .secret_share(&mut prng)?
+                    .value,
+                );
+            }
+            let main_g = inlined_c.get_main_graph()?;
+            let result = random_evaluate(main_g.clone(), inputs)?;
+            TypedValue::new(main_g.get_output_node()?.get_type()?, result)?
+                .secret_share_reveal()?
+                .value
+                .to_flattened_array_u64(input_t)
+        };
+        || -> Result<()> {
+            let input = [10, 20, 30, 40, 50];
+            assert_eq!(
+                helper(vec![5], 0, UINT64, &input, &[3, 0], &[1, 2])?,
+                vec![2, 20, 30, 1, 50]
+            );
+            assert_eq!(
+                helper(
+                    vec![5],
+                    0,
+                    UINT64,
+                    &input,
+                    &[4, 2, 0, 1, 3],
+                    &[1, 2, 3, 4, 5]
+                )?,
+                vec![3, 4, 2, 5, 1]
+            );
+            assert_eq!(
+                helper(vec![5], 0, UINT64, &input, &[4], &[7])?,
+                vec![10, 20, 30, 40, 7]
+            );
+            assert_eq!(helper(vec![1], 0, UINT64, &[5], &[0], &[6])?, vec![6]);
+            // [2,3,2]-array along the axis 1
+            assert_eq!(
+                helper(
+                    vec![2, 3, 2],
+                    1,
+                    INT32,
+                    &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+                    &[2, 0],
+                    &[13, 14, 15, 16, 17, 18, 19, 20]
+                )?,
+                vec![15, 16, 3, 4, 13, 14, 19, 20, 9, 10, 17, 18]
+            );
+            // Binary [3,2]-array along the axis 0
+            assert_eq!(
+                helper(vec![3, 2], 0, BIT, &[1, 0, 1, 1, 0, 1], &[1], &[0, 0])?,
+                vec![1, 0, 0, 0, 0, 1]
+            );
+            Ok(())
+        }()
+        .unwrap();
+
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let prf_keys = g.create_tuple(generate_prf_key_triple(g.clone())?)?;
+            let input = g.input(tuple_type(vec![array_type(vec![4, 2], INT32); PARTIES]))?;
+            let indices = g.input(tuple_type(vec![array_type(vec![2], UINT64); PARTIES]))?;
+            let updates = g.input(tuple_type(vec![array_type(vec![2, 2], INT32); PARTIES]))?;
+            let op = CustomOperation::new(ScatterMPC { axis: 0 });
+            let public_indices = g.input(array_type(vec![2], UINT64))?;
+            assert!(g
+                .custom_op(
+                    op.clone(),
+                    vec![
+                        input.clone(),
+                        public_indices,
+                        updates.clone(),
+                        prf_keys.clone()
+                    ]
+                )
+                .is_err());
+            let long_indices = g.input(tuple_type(vec![array_type(vec![5], UINT64); PARTIES]))?;
+            assert!(g
+                .custom_op(
+                    op.clone(),
+                    vec![
+                        input.clone(),
+                        long_indices,
+                        updates.clone(),
+                        prf_keys.clone()
+                    ]
+                )
+                .is_err());
+            let wrong_updates =
+                g.input(tuple_type(vec![array_type(vec![2, 2], INT64); PARTIES]))?;
+            assert!(g
+                .custom_op(
+                    op,
+                    vec![
+                        input.clone(),
+                        indices.clone(),
+                        wrong_updates,
+                        prf_keys.clone()
+                    ]
+                )
+                .is_err());
+            assert!(g
+                .custom_op(
+                    CustomOperation::new(ScatterMPC { axis: 2 }),
+                    vec![input, indices, updates, prf_keys]
+                )
+                .is_err());
+            Ok(())
+        }()
         .unwrap();
     }
 
@@ -2766,7 +6100,7 @@ mod tests {
         let prng_seed: [u8; SEED_SIZE] = core::array::from_fn(|i| i as u8);
         let result = evaluate_simple_evaluator(inlined_g.clone(), input_values, Some(prng_seed))?;
 
-        let result_type_vec = get_named_types(inlined_g.get_output_node()?.get_type()?);
+        let result_type_vec = get_named_types(inlined_g.get_output_node()?.get_type()?)?;
 
         let result_columns = result.to_vector()?;
         for i in 0..result_type_vec.len() {
@@ -3024,88 +6358,732 @@ mod tests {
         )
         .unwrap();
 
-        data_helper(
-            vec![
-                (NULL_HEADER.to_owned(), array_type(vec![5], BIT)),
-                ("a".to_owned(), array_type(vec![5], INT64)),
-                ("b".to_owned(), array_type(vec![5], INT32)),
-                ("c".to_owned(), array_type(vec![5], INT16)),
-            ],
-            vec![
-                (NULL_HEADER.to_owned(), array_type(vec![6], BIT)),
-                ("b".to_owned(), array_type(vec![6], INT32)),
-                ("c".to_owned(), array_type(vec![6], INT16)),
-                ("d".to_owned(), array_type(vec![6], BIT)),
-            ],
-            vec![
-                ("b".to_owned(), "b".to_owned()),
-                ("c".to_owned(), "c".to_owned()),
-            ],
-            vec![
-                vec![0, 0, 0, 1, 1],
-                vec![1, 2, 3, 4, 5],
-                vec![10, 20, 30, 40, 50],
-                vec![100, 200, 300, 400, 500],
-            ],
-            vec![
-                vec![1, 1, 1, 0, 0, 0],
-                vec![10, 20, 30, 40, 50, 60],
-                vec![100, 200, 300, 400, 500, 600],
-                vec![0, 1, 1, 0, 1, 0],
-            ],
-            vec![
-                (NULL_HEADER.to_owned(), vec![0, 0, 0, 0, 0]),
-                ("a".to_owned(), vec![0, 0, 0, 0, 0]),
-                ("b".to_owned(), vec![0, 0, 0, 0, 0]),
-                ("c".to_owned(), vec![0, 0, 0, 0, 0]),
-                ("d".to_owned(), vec![0, 0, 0, 0, 0]),
-            ],
-        )
+        data_helper(
+            vec![
+                (NULL_HEADER.to_owned(), array_type(vec![5], BIT)),
+                ("a".to_owned(), array_type(vec![5], INT64)),
+                ("b".to_owned(), array_type(vec![5], INT32)),
+                ("c".to_owned(), array_type(vec![5], INT16)),
+            ],
+            vec![
+                (NULL_HEADER.to_owned(), array_type(vec![6], BIT)),
+                ("b".to_owned(), array_type(vec![6], INT32)),
+                ("c".to_owned(), array_type(vec![6], INT16)),
+                ("d".to_owned(), array_type(vec![6], BIT)),
+            ],
+            vec![
+                ("b".to_owned(), "b".to_owned()),
+                ("c".to_owned(), "c".to_owned()),
+            ],
+            vec![
+                vec![0, 0, 0, 1, 1],
+                vec![1, 2, 3, 4, 5],
+                vec![10, 20, 30, 40, 50],
+                vec![100, 200, 300, 400, 500],
+            ],
+            vec![
+                vec![1, 1, 1, 0, 0, 0],
+                vec![10, 20, 30, 40, 50, 60],
+                vec![100, 200, 300, 400, 500, 600],
+                vec![0, 1, 1, 0, 1, 0],
+            ],
+            vec![
+                (NULL_HEADER.to_owned(), vec![0, 0, 0, 0, 0]),
+                ("a".to_owned(), vec![0, 0, 0, 0, 0]),
+                ("b".to_owned(), vec![0, 0, 0, 0, 0]),
+                ("c".to_owned(), vec![0, 0, 0, 0, 0]),
+                ("d".to_owned(), vec![0, 0, 0, 0, 0]),
+            ],
+        )
+        .unwrap();
+        data_helper(
+            vec![
+                (NULL_HEADER.to_owned(), array_type(vec![1], BIT)),
+                ("a".to_owned(), array_type(vec![1], INT64)),
+            ],
+            vec![
+                (NULL_HEADER.to_owned(), array_type(vec![1], BIT)),
+                ("b".to_owned(), array_type(vec![1], INT64)),
+            ],
+            vec![("a".to_owned(), "b".to_owned())],
+            vec![vec![1], vec![10]],
+            vec![vec![1], vec![10]],
+            vec![
+                (NULL_HEADER.to_owned(), vec![1]),
+                ("a".to_owned(), vec![10]),
+            ],
+        )
+        .unwrap();
+
+        data_helper(
+            vec![
+                (NULL_HEADER.to_owned(), array_type(vec![1], BIT)),
+                ("a".to_owned(), array_type(vec![1], INT64)),
+                ("b".to_owned(), array_type(vec![1], INT64)),
+                ("c".to_owned(), array_type(vec![1], INT64)),
+            ],
+            vec![
+                (NULL_HEADER.to_owned(), array_type(vec![1], BIT)),
+                ("b".to_owned(), array_type(vec![1], INT64)),
+                ("a".to_owned(), array_type(vec![1], INT64)),
+            ],
+            vec![
+                ("a".to_owned(), "a".to_owned()),
+                ("b".to_owned(), "b".to_owned()),
+            ],
+            vec![vec![1], vec![2], vec![3], vec![4]],
+            vec![vec![1], vec![3], vec![2]],
+            vec![
+                (NULL_HEADER.to_owned(), vec![1]),
+                ("a".to_owned(), vec![2]),
+                ("b".to_owned(), vec![3]),
+                ("c".to_owned(), vec![4]),
+            ],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_string_key_psi() {
+        let string_entries = |strings: &[&str]| -> Vec<u64> {
+            Value::from_strings(strings, 12)
+                .unwrap()
+                .access_bytes(|bytes| Ok(bytes.iter().map(|x| *x as u64).collect()))
+                .unwrap()
+        };
+        for (is_x_private, is_y_private) in [(true, true), (false, true), (false, false)] {
+            psi_helper(
+                vec![
+                    (NULL_HEADER.to_owned(), array_type(vec![4], BIT)),
+                    ("email".to_owned(), string_column_type(4, 12).unwrap()),
+                    ("age".to_owned(), array_type(vec![4], INT32)),
+                ],
+                vec![
+                    (NULL_HEADER.to_owned(), array_type(vec![3], BIT)),
+                    ("mail".to_owned(), string_column_type(3, 12).unwrap()),
+                    ("score".to_owned(), array_type(vec![3], INT32)),
+                ],
+                vec![("email".to_owned(), "mail".to_owned())],
+                vec![
+                    vec![1, 1, 1, 1],
+                    string_entries(&["a@b.c", "x@y.z", "a@b.c\0", "bob@mail.io"]),
+                    vec![20, 30, 40, 50],
+                ],
+                vec![
+                    vec![1, 1, 1],
+                    string_entries(&["bob@mail.io", "a@b.c", "x@y"]),
+                    vec![7, 8, 9],
+                ],
+                vec![
+                    (NULL_HEADER.to_owned(), vec![1, 0, 0, 1]),
+                    (
+                        "email".to_owned(),
+                        string_entries(&["a@b.c", "", "", "bob@mail.io"]),
+                    ),
+                    ("age".to_owned(), vec![20, 0, 0, 50]),
+                    ("score".to_owned(), vec![8, 0, 0, 7]),
+                ],
+                is_x_private,
+                is_y_private,
+            )
+            .unwrap();
+        }
+    }
+
+    fn annotated_psi_helper(
+        types_x: Vec<(String, Type)>,
+        types_y: Vec<(String, Type)>,
+        headers: Vec<(String, String)>,
+        values_x: Vec<Vec<u64>>,
+        values_y: Vec<Vec<u64>>,
+        (is_x_private, is_y_private): (bool, bool),
+        annotations: &[NodeAnnotation],
+    ) -> Result<Vec<(String, Vec<u64>)>> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let compose_set = |types: &[(String, Type)]| -> Result<Node> {
+            let mut columns = vec![];
+            for (header, t) in types {
+                columns.push((header.clone(), g.input(t.clone())?));
+            }
+            g.create_named_tuple(columns)
+        };
+        let data_x = compose_set(&types_x)?;
+        let data_y = compose_set(&types_y)?;
+        let psi = data_x.set_intersection(data_y, headers.into_iter().collect())?;
+        for annotation in annotations {
+            psi.add_annotation(annotation.clone())?;
+        }
+        psi.set_as_output()?;
+        g.finalize()?;
+        g.set_as_main()?;
+        c.finalize()?;
+
+        let status = |is_private: bool| {
+            if is_private {
+                IOStatus::Party(0)
+            } else {
+                IOStatus::Public
+            }
+        };
+        let mut input_parties = vec![status(is_x_private); types_x.len()];
+        input_parties.extend(vec![status(is_y_private); types_y.len()]);
+        let inlined_c = prepare_for_mpc_evaluation(
+            c,
+            vec![input_parties],
+            vec![vec![IOStatus::Party(0)]],
+            InlineConfig {
+                default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                ..Default::default()
+            },
+        )?;
+
+        let mut input_values = vec![];
+        for ((_, t), column_value) in types_x
+            .iter()
+            .chain(types_y.iter())
+            .zip(values_x.iter().chain(values_y.iter()))
+        {
+            input_values.push(Value::from_flattened_array(
+                column_value,
+                t.get_scalar_type(),
+            )?);
+        }
+        let inlined_g = inlined_c.get_main_graph()?;
+        let prng_seed: [u8; SEED_SIZE] = core::array::from_fn(|i| i as u8);
+        // Cuckoo hashing with random hash functions can fail, so the evaluation is retried
+        let result = evaluate_simple_evaluator_with_retries(
+            inlined_g.clone(),
+            input_values,
+            Some(prng_seed),
+            10,
+        )?;
+        let result_types = get_named_types(inlined_g.get_output_node()?.get_type()?)?;
+        let mut result_columns = vec![];
+        for ((header, t), column) in result_types.into_iter().zip(result.to_vector()?) {
+            result_columns.push((header, column.to_flattened_array_u64(t)?));
+        }
+        Ok(result_columns)
+    }
+
+    #[test]
+    fn test_bloom_filter_psi() {
+        || -> Result<()> {
+            let types_x = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![5], BIT)),
+                ("a".to_owned(), array_type(vec![5], INT32)),
+                ("b".to_owned(), array_type(vec![5, 2], INT64)),
+            ];
+            let types_y = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![6], BIT)),
+                ("c".to_owned(), array_type(vec![6], INT32)),
+            ];
+            let headers = vec![("a".to_owned(), "c".to_owned())];
+            let values_x = vec![
+                vec![1, 1, 0, 1, 1],
+                vec![1, 2, 3, 4, 5],
+                vec![10, 11, 20, 21, 30, 31, 40, 41, 50, 51],
+            ];
+            let values_y = vec![vec![1, 1, 1, 0, 1, 1], vec![3, 5, 7, 2, 9, 11]];
+            let expected = vec![
+                (NULL_HEADER.to_owned(), vec![0, 0, 0, 0, 1]),
+                ("a".to_owned(), vec![0, 0, 0, 0, 5]),
+                ("b".to_owned(), vec![0, 0, 0, 0, 0, 0, 0, 0, 50, 51]),
+            ];
+            for (is_x_private, is_y_private) in [(true, true), (false, true), (true, false)] {
+                let result = annotated_psi_helper(
+                    types_x.clone(),
+                    types_y.clone(),
+                    headers.clone(),
+                    values_x.clone(),
+                    values_y.clone(),
+                    (is_x_private, is_y_private),
+                    &[NodeAnnotation::BloomFilterIntersection(3, 32)],
+                )?;
+                assert_eq!(result, expected);
+            }
+
+            // Non-key columns of the second database can't be attached
+            let mut types_y_with_payload = types_y.clone();
+            types_y_with_payload.push(("d".to_owned(), array_type(vec![6], INT32)));
+            let mut values_y_with_payload = values_y.clone();
+            values_y_with_payload.push(vec![0; 6]);
+            assert!(annotated_psi_helper(
+                types_x,
+                types_y_with_payload,
+                headers,
+                values_x,
+                values_y_with_payload,
+                (true, true),
+                &[NodeAnnotation::BloomFilterIntersection(3, 32)],
+            )
+            .is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_oblivious_psi() {
+        || -> Result<()> {
+            let types_x = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![5], BIT)),
+                ("a".to_owned(), array_type(vec![5], INT32)),
+                ("b".to_owned(), array_type(vec![5, 2], INT64)),
+            ];
+            let types_y = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![6], BIT)),
+                ("c".to_owned(), array_type(vec![6], INT32)),
+                ("d".to_owned(), array_type(vec![6, 2], INT32)),
+            ];
+            let headers = vec![("a".to_owned(), "c".to_owned())];
+            let values_x = vec![
+                vec![1, 1, 0, 1, 1],
+                vec![1, 2, 3, 4, 5],
+                vec![10, 11, 20, 21, 30, 31, 40, 41, 50, 51],
+            ];
+            let values_y = vec![
+                vec![1, 1, 1, 0, 1, 1],
+                vec![3, 5, 1, 2, 9, 4],
+                vec![10, 11, 20, 21, 30, 31, 40, 41, 50, 51, 60, 61],
+            ];
+            let expected = vec![
+                (NULL_HEADER.to_owned(), vec![1, 0, 0, 1, 1]),
+                ("a".to_owned(), vec![1, 0, 0, 4, 5]),
+                ("b".to_owned(), vec![10, 11, 0, 0, 0, 0, 40, 41, 50, 51]),
+                ("d".to_owned(), vec![30, 31, 0, 0, 0, 0, 60, 61, 20, 21]),
+            ];
+            for (is_x_private, is_y_private) in [(true, true), (false, true), (true, false)] {
+                let result = annotated_psi_helper(
+                    types_x.clone(),
+                    types_y.clone(),
+                    headers.clone(),
+                    values_x.clone(),
+                    values_y.clone(),
+                    (is_x_private, is_y_private),
+                    &[NodeAnnotation::ObliviousIntersection],
+                )?;
+                assert_eq!(result, expected);
+            }
+
+            // The total number of rows is a power of two
+            let result = annotated_psi_helper(
+                vec![
+                    (NULL_HEADER.to_owned(), array_type(vec![2], BIT)),
+                    ("a".to_owned(), array_type(vec![2], INT32)),
+                ],
+                vec![
+                    (NULL_HEADER.to_owned(), array_type(vec![2], BIT)),
+                    ("c".to_owned(), array_type(vec![2], INT32)),
+                    ("e".to_owned(), array_type(vec![2], INT32)),
+                ],
+                headers,
+                vec![vec![1, 1], vec![7, 8]],
+                vec![vec![1, 1], vec![8, 6], vec![100, 200]],
+                (true, true),
+                &[NodeAnnotation::ObliviousIntersection],
+            )?;
+            assert_eq!(
+                result,
+                vec![
+                    (NULL_HEADER.to_owned(), vec![0, 1]),
+                    ("a".to_owned(), vec![0, 8]),
+                    ("e".to_owned(), vec![0, 100]),
+                ]
+            );
+
+            // No unmasked values are revealed to any party
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let data_x = g.input(named_tuple_type(vec![
+                (NULL_HEADER.to_owned(), array_type(vec![3], BIT)),
+                ("a".to_owned(), array_type(vec![3], INT32)),
+            ]))?;
+            let data_y = g.input(named_tuple_type(vec![
+                (NULL_HEADER.to_owned(), array_type(vec![2], BIT)),
+                ("c".to_owned(), array_type(vec![2], INT32)),
+                ("e".to_owned(), array_type(vec![2], INT32)),
+            ]))?;
+            data_x
+                .set_intersection(data_y, HashMap::from([("a".to_owned(), "c".to_owned())]))?
+                .add_annotation(NodeAnnotation::ObliviousIntersection)?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let inlined_c = prepare_for_mpc_evaluation(
+                c,
+                vec![vec![IOStatus::Shared, IOStatus::Shared]],
+                vec![vec![]],
+                InlineConfig {
+                    default_mode: InlineMode::Simple,
+                    ..Default::default()
+                },
+            )?;
+            verify_privacy(
+                inlined_c,
+                vec![IOStatus::Shared, IOStatus::Shared],
+                vec![IOStatus::Shared],
+            )?;
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_psi_prf_config() {
+        || -> Result<()> {
+            let types_x = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![4], BIT)),
+                ("a".to_owned(), array_type(vec![4], INT32)),
+                ("b".to_owned(), array_type(vec![4], INT16)),
+            ];
+            let types_y = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![5], BIT)),
+                ("c".to_owned(), array_type(vec![5], INT32)),
+            ];
+            let headers = vec![("a".to_owned(), "c".to_owned())];
+            let values_x = vec![vec![1, 1, 0, 1], vec![1, 2, 3, 4], vec![10, 20, 30, 40]];
+            let values_y = vec![vec![1, 1, 1, 0, 1], vec![4, 3, 1, 2, 7]];
+            let expected = vec![
+                (NULL_HEADER.to_owned(), vec![1, 0, 0, 1]),
+                ("a".to_owned(), vec![1, 0, 0, 4]),
+                ("b".to_owned(), vec![10, 0, 0, 40]),
+            ];
+            let test_annotations = |annotations: &[NodeAnnotation]| -> Result<()> {
+                let result = annotated_psi_helper(
+                    types_x.clone(),
+                    types_y.clone(),
+                    headers.clone(),
+                    values_x.clone(),
+                    values_y.clone(),
+                    (true, true),
+                    annotations,
+                )?;
+                assert_eq!(result, expected);
+                Ok(())
+            };
+            test_annotations(&[NodeAnnotation::PsiAes128])?;
+            test_annotations(&[NodeAnnotation::PsiLowMC(128, 10, 20)])?;
+            test_annotations(&[
+                NodeAnnotation::PsiLowMC(128, 10, 20),
+                NodeAnnotation::UnbalancedIntersection,
+            ])?;
+            test_annotations(&[
+                NodeAnnotation::PsiAes128,
+                NodeAnnotation::BloomFilterIntersection(3, 32),
+            ])?;
+            // LowMC supports only 80- and 128-bit blocks
+            assert!(test_annotations(&[NodeAnnotation::PsiLowMC(64, 10, 20)]).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_psi_cuckoo_parameters() {
+        || -> Result<()> {
+            let types_x = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![5], BIT)),
+                ("a".to_owned(), array_type(vec![5], INT32)),
+            ];
+            let types_y = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![8], BIT)),
+                ("b".to_owned(), array_type(vec![8], INT32)),
+                ("c".to_owned(), array_type(vec![8], INT16)),
+            ];
+            let headers = vec![("a".to_owned(), "b".to_owned())];
+            let values_x = vec![vec![1, 1, 1, 0, 1], vec![3, 10, 8, 1, 6]];
+            let values_y = vec![
+                vec![1, 1, 1, 1, 1, 1, 0, 1],
+                vec![1, 2, 3, 4, 5, 6, 7, 8],
+                vec![10, 20, 30, 40, 50, 60, 70, 80],
+            ];
+            let expected = vec![
+                (NULL_HEADER.to_owned(), vec![1, 0, 1, 0, 1]),
+                ("a".to_owned(), vec![3, 0, 8, 0, 6]),
+                ("c".to_owned(), vec![30, 0, 80, 0, 60]),
+            ];
+            let test_annotations = |annotations: &[NodeAnnotation]| -> Result<()> {
+                for is_private in [(true, true), (false, true), (true, false)] {
+                    let result = annotated_psi_helper(
+                        types_x.clone(),
+                        types_y.clone(),
+                        headers.clone(),
+                        values_x.clone(),
+                        values_y.clone(),
+                        is_private,
+                        annotations,
+                    )?;
+                    assert_eq!(result, expected);
+                }
+                Ok(())
+            };
+            // The Cuckoo table has as many entries as Y, so the stash is likely to be used
+            test_annotations(&[NodeAnnotation::PsiCuckooParameters(1, 4)])?;
+            test_annotations(&[
+                NodeAnnotation::PsiCuckooParameters(1, 4),
+                NodeAnnotation::UnbalancedIntersection,
+            ])?;
+            test_annotations(&[NodeAnnotation::PsiCuckooParameters(4, 0)])?;
+            assert!(test_annotations(&[NodeAnnotation::PsiCuckooParameters(0, 1)]).is_err());
+            // Fewer hash functions need a bigger table
+            test_annotations(&[
+                NodeAnnotation::PsiCuckooHashFunctions(2),
+                NodeAnnotation::PsiCuckooParameters(4, 1),
+            ])?;
+            test_annotations(&[NodeAnnotation::PsiCuckooHashFunctions(5)])?;
+            assert!(test_annotations(&[NodeAnnotation::PsiCuckooHashFunctions(1)]).is_err());
+            assert!(test_annotations(&[NodeAnnotation::PsiCuckooHashFunctions(6)]).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_psi_provenance() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let mut columns = vec![];
+            for (header, t) in [
+                (NULL_HEADER, array_type(vec![4], BIT)),
+                ("a", array_type(vec![4], INT32)),
+            ] {
+                columns.push((header.to_owned(), g.input(t)?));
+            }
+            let data_x = g.create_named_tuple(columns)?;
+            let mut columns = vec![];
+            for (header, t) in [
+                (NULL_HEADER, array_type(vec![6], BIT)),
+                ("b", array_type(vec![6], INT32)),
+            ] {
+                columns.push((header.to_owned(), g.input(t)?));
+            }
+            let data_y = g.create_named_tuple(columns)?;
+            data_x
+                .set_intersection(data_y, HashMap::from([("a".to_owned(), "b".to_owned())]))?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let inlined_c = prepare_for_mpc_evaluation(
+                c,
+                vec![vec![IOStatus::Party(0); 4]],
+                vec![vec![IOStatus::Party(0)]],
+                InlineConfig {
+                    default_mode: InlineMode::Simple,
+                    ..Default::default()
+                },
+            )?;
+            let inlined_g = inlined_c.get_main_graph()?;
+            let step13 = inlined_g.get_nodes_in_region(&["SetIntersectionMPC", "step 13"])?;
+            assert!(!step13.is_empty());
+            // Most of step 13 is the switching protocol
+            let mut num_switching_nodes = 0;
+            for node in &step13 {
+                let provenance = node.get_provenance()?;
+                assert_eq!(provenance[..2], ["SetIntersectionMPC", "step 13"]);
+                if provenance.get(2).map(String::as_str) == Some("SwitchingMPC") {
+                    num_switching_nodes += 1;
+                }
+            }
+            assert!(num_switching_nodes * 2 > step13.len());
+            let step16 = inlined_g.get_nodes_in_region(&["SetIntersectionMPC", "step 16"])?;
+            assert!(!step16.is_empty());
+            assert!(step16.iter().all(|node| !step13.contains(node)));
+            assert!(!inlined_g
+                .get_nodes_in_region(&["SetIntersectionMPC", "step 11", "PermutationMPC"])?
+                .is_empty());
+            Ok(())
+        }()
         .unwrap();
-        data_helper(
-            vec![
-                (NULL_HEADER.to_owned(), array_type(vec![1], BIT)),
-                ("a".to_owned(), array_type(vec![1], INT64)),
-            ],
-            vec![
-                (NULL_HEADER.to_owned(), array_type(vec![1], BIT)),
-                ("b".to_owned(), array_type(vec![1], INT64)),
-            ],
-            vec![("a".to_owned(), "b".to_owned())],
-            vec![vec![1], vec![10]],
-            vec![vec![1], vec![10]],
-            vec![
-                (NULL_HEADER.to_owned(), vec![1]),
-                ("a".to_owned(), vec![10]),
-            ],
-        )
+    }
+
+    #[test]
+    fn test_psi_padding() {
+        || -> Result<()> {
+            let types_x = |num_entries: u64| {
+                vec![
+                    (NULL_HEADER.to_owned(), array_type(vec![num_entries], BIT)),
+                    ("a".to_owned(), array_type(vec![num_entries], INT32)),
+                ]
+            };
+            let types_y = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![8], BIT)),
+                ("b".to_owned(), array_type(vec![8], INT32)),
+            ];
+            let headers = vec![("a".to_owned(), "b".to_owned())];
+            let values_x = vec![vec![1, 1, 1, 0, 1], vec![3, 10, 8, 1, 6]];
+            let values_y = vec![vec![1; 8], vec![1, 2, 3, 4, 5, 6, 7, 8]];
+            let psi = |values_x: Vec<Vec<u64>>, padding: PaddingPolicy| {
+                annotated_psi_helper(
+                    types_x(values_x[0].len() as u64),
+                    types_y.clone(),
+                    headers.clone(),
+                    values_x,
+                    values_y.clone(),
+                    (true, true),
+                    &[NodeAnnotation::PsiPadding(padding)],
+                )
+            };
+            let expected = |padding: Vec<u64>| {
+                vec![
+                    (
+                        NULL_HEADER.to_owned(),
+                        [vec![1, 0, 1, 0, 1], padding.clone()].concat(),
+                    ),
+                    ("a".to_owned(), [vec![3, 0, 8, 0, 6], padding].concat()),
+                ]
+            };
+            assert_eq!(
+                psi(values_x.clone(), PaddingPolicy::Buckets(vec![5, 8]))?,
+                expected(vec![])
+            );
+            // The first database has 5 rows, which is not a power of two
+            assert!(psi(values_x.clone(), PaddingPolicy::PowerOfTwo).is_err());
+            assert!(psi(values_x.clone(), PaddingPolicy::Buckets(vec![8])).is_err());
+            // Padded database passes the check
+            let padded_x = values_x
+                .iter()
+                .map(|column| [column.clone(), vec![0; 3]].concat())
+                .collect();
+            assert_eq!(
+                psi(padded_x, PaddingPolicy::PowerOfTwo)?,
+                expected(vec![0; 3])
+            );
+            Ok(())
+        }()
         .unwrap();
+    }
 
-        data_helper(
-            vec![
-                (NULL_HEADER.to_owned(), array_type(vec![1], BIT)),
-                ("a".to_owned(), array_type(vec![1], INT64)),
-                ("b".to_owned(), array_type(vec![1], INT64)),
-                ("c".to_owned(), array_type(vec![1], INT64)),
-            ],
-            vec![
-                (NULL_HEADER.to_owned(), array_type(vec![1], BIT)),
-                ("b".to_owned(), array_type(vec![1], INT64)),
-                ("a".to_owned(), array_type(vec![1], INT64)),
-            ],
-            vec![
-                ("a".to_owned(), "a".to_owned()),
-                ("b".to_owned(), "b".to_owned()),
-            ],
-            vec![vec![1], vec![2], vec![3], vec![4]],
-            vec![vec![1], vec![3], vec![2]],
-            vec![
-                (NULL_HEADER.to_owned(), vec![1]),
-                ("a".to_owned(), vec![2]),
-                ("b".to_owned(), vec![3]),
-                ("c".to_owned(), vec![4]),
-            ],
-        )
+    #[test]
+    fn test_unbalanced_psi() {
+        || -> Result<()> {
+            let types_x0 = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![5], BIT)),
+                ("a".to_owned(), array_type(vec![5], INT32)),
+            ];
+            let types_x1 = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![3], BIT)),
+                ("b".to_owned(), array_type(vec![3], INT32)),
+                ("w".to_owned(), array_type(vec![3], INT16)),
+            ];
+            let types_y = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![8], BIT)),
+                ("k".to_owned(), array_type(vec![8], INT32)),
+                ("v".to_owned(), array_type(vec![8], INT32)),
+            ];
+            let values = [
+                vec![1, 1, 1, 1, 1],
+                vec![2, 5, 9, 8, 3],
+                vec![1, 0, 1],
+                vec![7, 1, 4],
+                vec![100, 200, 300],
+                vec![1, 1, 1, 1, 0, 1, 1, 1],
+                vec![1, 2, 3, 4, 5, 6, 7, 8],
+                vec![10, 20, 30, 40, 50, 60, 70, 80],
+            ];
+            let expected = [
+                vec![
+                    (NULL_HEADER.to_owned(), vec![1, 0, 0, 1, 1]),
+                    ("a".to_owned(), vec![2, 0, 0, 8, 3]),
+                    ("v".to_owned(), vec![20, 0, 0, 80, 30]),
+                ],
+                vec![
+                    (NULL_HEADER.to_owned(), vec![1, 0, 1]),
+                    ("b".to_owned(), vec![7, 0, 4]),
+                    ("w".to_owned(), vec![100, 0, 300]),
+                    ("v".to_owned(), vec![70, 0, 40]),
+                ],
+            ];
+            for (is_x_private, is_y_private) in [(true, true), (false, true), (true, false)] {
+                // Both sets X0 and X1 are intersected with Y
+                let c = create_context()?;
+                let g = c.create_graph()?;
+                let compose_set = |types: &[(String, Type)]| -> Result<Node> {
+                    let mut columns = vec![];
+                    for (header, t) in types {
+                        columns.push((header.clone(), g.input(t.clone())?));
+                    }
+                    g.create_named_tuple(columns)
+                };
+                let data_x0 = compose_set(&types_x0)?;
+                let data_x1 = compose_set(&types_x1)?;
+                let data_y = compose_set(&types_y)?;
+                let psi0 = data_x0
+                    .set_intersection(
+                        data_y.clone(),
+                        HashMap::from([("a".to_owned(), "k".to_owned())]),
+                    )?
+                    .add_annotation(NodeAnnotation::UnbalancedIntersection)?;
+                let psi1 = data_x1
+                    .set_intersection(data_y, HashMap::from([("b".to_owned(), "k".to_owned())]))?
+                    .add_annotation(NodeAnnotation::UnbalancedIntersection)?;
+                g.create_tuple(vec![psi0, psi1])?.set_as_output()?;
+                g.finalize()?;
+                g.set_as_main()?;
+                c.finalize()?;
+
+                let status = |is_private: bool, num_columns: usize| {
+                    if is_private {
+                        vec![IOStatus::Party(0); num_columns]
+                    } else {
+                        vec![IOStatus::Public; num_columns]
+                    }
+                };
+                let mut input_parties = status(is_x_private, types_x0.len() + types_x1.len());
+                input_parties.extend(status(is_y_private, types_y.len()));
+                let inlined_c = prepare_for_mpc_evaluation(
+                    c,
+                    vec![input_parties],
+                    vec![vec![IOStatus::Party(0)]],
+                    InlineConfig {
+                        default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                        ..Default::default()
+                    },
+                )?;
+                let inlined_g = inlined_c.get_main_graph()?;
+
+                // Y is preprocessed, in particular Cuckoo hashed, only once
+                let num_cuckoo_hashes = inlined_g
+                    .get_nodes()
+                    .iter()
+                    .filter(|node| matches!(node.get_operation(), Operation::CuckooHash(_)))
+                    .count();
+                assert_eq!(num_cuckoo_hashes, 1);
+
+                let mut input_values = vec![];
+                for ((_, t), column_value) in types_x0
+                    .iter()
+                    .chain(types_x1.iter())
+                    .chain(types_y.iter())
+                    .zip(values.iter())
+                {
+                    input_values.push(Value::from_flattened_array(
+                        column_value,
+                        t.get_scalar_type(),
+                    )?);
+                }
+                let prng_seed: [u8; SEED_SIZE] = core::array::from_fn(|i| i as u8);
+                let result =
+                    evaluate_simple_evaluator(inlined_g.clone(), input_values, Some(prng_seed))?;
+                let result_types = get_types_vector(inlined_g.get_output_node()?.get_type()?)?;
+                for ((result_t, result_value), expected_columns) in result_types
+                    .into_iter()
+                    .zip(result.to_vector()?)
+                    .zip(expected.iter())
+                {
+                    let mut result_columns = vec![];
+                    for ((header, t), column) in get_named_types((*result_t).clone())?
+                        .into_iter()
+                        .zip(result_value.to_vector()?)
+                    {
+                        result_columns.push((header, column.to_flattened_array_u64(t)?));
+                    }
+                    assert_eq!(&result_columns, expected_columns);
+                }
+            }
+            Ok(())
+        }()
         .unwrap();
     }
 
@@ -3200,4 +7178,353 @@ mod tests {
         }()
         .unwrap();
     }
+
+    // Evaluates a custom operation on public or shared databases followed by PRF keys and returns the revealed columns of the resulting shared database.
+    // Values of all columns of all databases are given in one vector.
+    fn shared_databases_op_helper(
+        databases: Vec<(Vec<(String, Type)>, bool)>,
+        values: Vec<Vec<u64>>,
+        op: CustomOperation,
+    ) -> Result<Vec<(String, Vec<u64>)>> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let mut args = vec![];
+        let mut types = vec![];
+        for (database_types, is_private) in databases {
+            let mut columns = vec![];
+            for (header, t) in &database_types {
+                columns.push((header.clone(), g.input(t.clone())?));
+            }
+            types.extend(database_types);
+            let data = g.create_named_tuple(columns)?;
+            if is_private {
+                let zero_share = zeros_like(data.clone())?;
+                args.push(g.create_tuple(vec![data, zero_share.clone(), zero_share])?);
+            } else {
+                args.push(data);
+            }
+        }
+        args.push(g.create_tuple(generate_prf_key_triple(g.clone())?)?);
+        let result = g.custom_op(op, args)?;
+        sum_named_columns(
+            sum_named_columns(result.tuple_get(0)?, result.tuple_get(1)?)?,
+            result.tuple_get(2)?,
+        )?
+        .set_as_output()?;
+        g.finalize()?;
+        g.set_as_main()?;
+        c.finalize()?;
+
+        let instantiated_c = run_instantiation_pass(c)?.context;
+        let inlined_c = inline_operations(
+            instantiated_c,
+            InlineConfig {
+                default_mode: InlineMode::Simple,
+                ..Default::default()
+            },
+        )?;
+        let inlined_g = inlined_c.get_main_graph()?;
+        let mut input_values = vec![];
+        for ((_, t), column_value) in types.iter().zip(values.iter()) {
+            input_values.push(Value::from_flattened_array(
+                column_value,
+                t.get_scalar_type(),
+            )?);
+        }
+        let prng_seed: [u8; SEED_SIZE] = core::array::from_fn(|i| i as u8);
+        let result = evaluate_simple_evaluator(inlined_g.clone(), input_values, Some(prng_seed))?;
+        let mut result_columns = vec![];
+        for ((header, t), column) in get_named_types(inlined_g.get_output_node()?.get_type()?)?
+            .into_iter()
+            .zip(result.to_vector()?)
+        {
+            result_columns.push((header, column.to_flattened_array_u64(t)?));
+        }
+        Ok(result_columns)
+    }
+
+    fn dedup_helper(
+        types: Vec<(String, Type)>,
+        key_headers: Vec<String>,
+        values: Vec<Vec<u64>>,
+    ) -> Result<Vec<(String, Vec<u64>)>> {
+        shared_databases_op_helper(
+            vec![(types, true)],
+            values,
+            CustomOperation::new(DedupMPC {
+                key_headers,
+                config: PsiConfig::default(),
+            }),
+        )
+    }
+
+    // Returns the sorted rows of a deduplicated database whose null bit is 1 and checks that the other rows are zero
+    fn get_dedup_rows(columns: &[(String, Vec<u64>)]) -> Vec<Vec<u64>> {
+        assert_eq!(columns[0].0, NULL_HEADER);
+        let num_entries = columns[0].1.len();
+        let mut rows = vec![];
+        for i in 0..num_entries {
+            let row: Vec<u64> = columns[1..].iter().map(|(_, c)| c[i]).collect();
+            if columns[0].1[i] == 1 {
+                rows.push(row);
+            } else {
+                assert!(row.iter().all(|x| *x == 0));
+            }
+        }
+        rows.sort();
+        rows
+    }
+
+    #[test]
+    fn test_dedup() {
+        || -> Result<()> {
+            let types = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![8], BIT)),
+                ("k".to_owned(), array_type(vec![8], INT32)),
+                ("w".to_owned(), array_type(vec![8], INT16)),
+                ("v".to_owned(), array_type(vec![8], INT64)),
+            ];
+            let values = vec![
+                vec![1, 1, 1, 1, 0, 1, 1, 1],
+                vec![3, 1, 3, 2, 1, 5, 1, 3],
+                vec![7, 7, 7, 8, 7, 9, 8, 8],
+                vec![10, 20, 30, 40, 50, 60, 70, 80],
+            ];
+            // One row per key value
+            let result = dedup_helper(types.clone(), vec!["k".to_owned()], values.clone())?;
+            let headers: Vec<String> = result.iter().map(|(h, _)| h.clone()).collect();
+            assert_eq!(headers, vec![NULL_HEADER, "k", "w", "v"]);
+            let rows = get_dedup_rows(&result);
+            let keys: Vec<u64> = rows.iter().map(|row| row[0]).collect();
+            assert_eq!(keys, vec![1, 2, 3, 5]);
+            for row in rows {
+                assert!((0..8).any(|i| values[0][i] == 1
+                    && values[1][i] == row[0]
+                    && values[2][i] == row[1]
+                    && values[3][i] == row[2]));
+            }
+            // One row per pair of key values
+            let result = dedup_helper(types, vec!["k".to_owned(), "w".to_owned()], values)?;
+            let keys: Vec<Vec<u64>> = get_dedup_rows(&result)
+                .into_iter()
+                .map(|row| row[0..2].to_vec())
+                .collect();
+            assert_eq!(
+                keys,
+                vec![
+                    vec![1, 7],
+                    vec![1, 8],
+                    vec![2, 8],
+                    vec![3, 7],
+                    vec![3, 8],
+                    vec![5, 9]
+                ]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_dedup() {
+        let dedup_fails = |t: Type, key_headers: Vec<String>| -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let data = g.input(t)?;
+            let prf_keys = g.create_tuple(generate_prf_key_triple(g.clone())?)?;
+            g.custom_op(
+                CustomOperation::new(DedupMPC {
+                    key_headers,
+                    config: PsiConfig::default(),
+                }),
+                vec![data, prf_keys],
+            )?
+            .set_as_output()?;
+            g.finalize()?;
+            g.set_as_main()?;
+            c.finalize()?;
+            run_instantiation_pass(c)?;
+            Ok(())
+        };
+        let shared_t =
+            |columns: Vec<(String, Type)>| tuple_type(vec![named_tuple_type(columns); 3]);
+        let null_t = (NULL_HEADER.to_owned(), array_type(vec![4], BIT));
+        let key_t = ("k".to_owned(), array_type(vec![4], INT32));
+        // Public database
+        assert!(dedup_fails(
+            named_tuple_type(vec![null_t.clone(), key_t.clone()]),
+            vec!["k".to_owned()]
+        )
+        .is_err());
+        // No key columns
+        assert!(dedup_fails(shared_t(vec![null_t.clone(), key_t.clone()]), vec![]).is_err());
+        // Missing key column
+        assert!(dedup_fails(
+            shared_t(vec![null_t.clone(), key_t.clone()]),
+            vec!["a".to_owned()]
+        )
+        .is_err());
+        // Missing null column
+        assert!(dedup_fails(shared_t(vec![key_t.clone()]), vec!["k".to_owned()]).is_err());
+        // Malformed null column
+        assert!(dedup_fails(
+            shared_t(vec![
+                (NULL_HEADER.to_owned(), array_type(vec![4], INT32)),
+                key_t
+            ]),
+            vec!["k".to_owned()]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_set_difference() {
+        || -> Result<()> {
+            let types_x = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![5], BIT)),
+                ("a".to_owned(), array_type(vec![5], INT32)),
+                ("b".to_owned(), array_type(vec![5], INT64)),
+            ];
+            let types_y = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![4], BIT)),
+                ("k".to_owned(), array_type(vec![4], INT32)),
+                ("b".to_owned(), array_type(vec![4], INT16)),
+            ];
+            let values = vec![
+                vec![1, 1, 1, 0, 1],
+                vec![1, 2, 3, 4, 5],
+                vec![10, 20, 30, 40, 50],
+                vec![1, 1, 0, 1],
+                vec![2, 5, 1, 4],
+                vec![200, 500, 100, 400],
+            ];
+            let expected = vec![
+                (NULL_HEADER.to_owned(), vec![1, 0, 1, 0, 0]),
+                ("a".to_owned(), vec![1, 0, 3, 0, 0]),
+                ("b".to_owned(), vec![10, 0, 30, 0, 0]),
+            ];
+            for (is_x_private, is_y_private) in [(true, true), (false, true), (true, false)] {
+                let result = shared_databases_op_helper(
+                    vec![
+                        (types_x.clone(), is_x_private),
+                        (types_y.clone(), is_y_private),
+                    ],
+                    values.clone(),
+                    CustomOperation::new(SetDifferenceMPC {
+                        headers: vec![("a".to_owned(), "k".to_owned())],
+                        config: PsiConfig::default(),
+                    }),
+                )?;
+                assert_eq!(result, expected);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_set_union() {
+        || -> Result<()> {
+            let types_x = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![3], BIT)),
+                ("a".to_owned(), array_type(vec![3], INT32)),
+                ("b".to_owned(), array_type(vec![3], INT64)),
+            ];
+            let types_y = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![4], BIT)),
+                ("b".to_owned(), array_type(vec![4], INT64)),
+                ("k".to_owned(), array_type(vec![4], INT32)),
+            ];
+            let values = vec![
+                vec![1, 1, 0],
+                vec![1, 2, 0],
+                vec![10, 20, 0],
+                vec![1, 1, 1, 0],
+                vec![200, 400, 300, 500],
+                vec![2, 4, 3, 5],
+            ];
+            let expected = vec![
+                (NULL_HEADER.to_owned(), vec![1, 1, 0, 0, 1, 1, 0]),
+                ("a".to_owned(), vec![1, 2, 0, 0, 4, 3, 0]),
+                ("b".to_owned(), vec![10, 20, 0, 0, 400, 300, 0]),
+            ];
+            for (is_x_private, is_y_private) in [(true, true), (false, true), (true, false)] {
+                let result = shared_databases_op_helper(
+                    vec![
+                        (types_x.clone(), is_x_private),
+                        (types_y.clone(), is_y_private),
+                    ],
+                    values.clone(),
+                    CustomOperation::new(SetUnionMPC {
+                        headers: vec![("a".to_owned(), "k".to_owned())],
+                        config: PsiConfig::default(),
+                    }),
+                )?;
+                assert_eq!(result, expected);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_set_union() {
+        let union_helper = |types_y: Vec<(String, Type)>| -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let shared_t =
+                |columns: Vec<(String, Type)>| tuple_type(vec![named_tuple_type(columns); 3]);
+            let data_x = g.input(shared_t(vec![
+                (NULL_HEADER.to_owned(), array_type(vec![3], BIT)),
+                ("a".to_owned(), array_type(vec![3], INT32)),
+                ("b".to_owned(), array_type(vec![3], INT64)),
+            ]))?;
+            let data_y = g.input(shared_t(types_y))?;
+            let prf_keys = g.create_tuple(generate_prf_key_triple(g.clone())?)?;
+            g.custom_op(
+                CustomOperation::new(SetUnionMPC {
+                    headers: vec![("a".to_owned(), "k".to_owned())],
+                    config: PsiConfig::default(),
+                }),
+                vec![data_x, data_y, prf_keys],
+            )?
+            .set_as_output()?;
+            g.finalize()?;
+            g.set_as_main()?;
+            c.finalize()?;
+            run_instantiation_pass(c)?;
+            Ok(())
+        };
+        let null_t = (NULL_HEADER.to_owned(), array_type(vec![4], BIT));
+        let key_t = ("k".to_owned(), array_type(vec![4], INT32));
+        assert!(union_helper(vec![
+            null_t.clone(),
+            key_t.clone(),
+            ("b".to_owned(), array_type(vec![4], INT64)),
+        ])
+        .is_ok());
+        // Missing column
+        assert!(union_helper(vec![null_t.clone(), key_t.clone()]).is_err());
+        // Unknown column
+        assert!(union_helper(vec![
+            null_t.clone(),
+            key_t.clone(),
+            ("c".to_owned(), array_type(vec![4], INT64)),
+        ])
+        .is_err());
+        // Incompatible column types
+        assert!(union_helper(vec![
+            null_t.clone(),
+            key_t,
+            ("b".to_owned(), array_type(vec![4], INT32)),
+        ])
+        .is_err());
+        // Key column matched with a non-key column
+        assert!(union_helper(vec![
+            null_t,
+            ("a".to_owned(), array_type(vec![4], INT32)),
+            ("b".to_owned(), array_type(vec![4], INT64)),
+        ])
+        .is_err());
+    }
 }
@@ -7,7 +7,7 @@ use crate::mpc::mpc_compiler::{check_private_tuple, KEY_LENGTH, PARTIES};
 
 use serde::{Deserialize, Serialize};
 
-fn get_unsigned_counterpart(st: ScalarType) -> ScalarType {
+pub(super) fn get_unsigned_counterpart(st: ScalarType) -> ScalarType {
     if !st.get_signed() {
         return st;
     }
@@ -34,6 +34,7 @@ fn get_unsigned_counterpart(st: ScalarType) -> ScalarType {
 ///    Therefore, this operation supports only signed types with a warning
 ///    that it fails with probability < 2^(l-m) when |a| < 2^l.
 ///
+/// The MPC compiler lowers Truncate to [TruncatePrMPC] instead, which doesn't have the second type of errors.
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub(super) struct TruncateMPC {
     pub scale: u64,
@@ -146,19 +147,26 @@ impl CustomOperationBody for TruncateMPC {
     }
 }
 
-/// Truncate MPC operation for public and private data by a power of 2.
+register_custom_operation!(TruncateMPC, [Shared], [Shared, PrfKeys]);
+
+/// Probabilistic truncation MPC operation for public and private data.
+///
+/// This operation is used by the MPC compiler to lower [Truncate](crate::graphs::Operation::Truncate) of private values.
 ///
-/// Signed input integers must be from the range [-modulus/4, modulus/4)
+/// Let `S` be the largest multiple of the scale not exceeding modulus/4.
+/// Signed input integers must be from the range [-S, modulus/4)
 /// and unsigned integers must be in the range [0, modulus/2) where modulus is the modulus of the input scalar type.
-///  
-/// This algorithm returns floor(x/2^k) + w where w = 1 with probability (x mod 2^k)/2^k, otherwise w=0.
+///
+/// This algorithm returns floor(x/scale) + w where w is in {-1, 0, 1}.
+/// If the scale is a power of 2, i.e. scale = 2^k, then w = 1 with probability (x mod 2^k)/2^k, otherwise w=0.
 /// So the result is biased to round(x/2^k).
 ///
-/// The corresponding protocol is described [here](https://eprint.iacr.org/2019/131.pdf#page=10) and runs as follows.
+/// The corresponding protocol for powers of 2 is described [here](https://eprint.iacr.org/2019/131.pdf#page=10).
+/// It is generalized to any scale and runs as follows.
 ///     0. The below protocol works correctly for integers in the range [0, modulus/2).
-///        For signed inputs, we add modulus/4 to input resulting in [0, modulus/2).
-///        For correctness, we should remove modulus/2^(k+2) after truncation since
-///        Truncate(input + modulus/4, 2^k) = Truncate(input, 2^k) + modulus/2^(k+2).
+///        For signed inputs, we add S to input resulting in [0, modulus/2).
+///        For correctness, we should remove S/scale after truncation since
+///        Truncate(input + S, scale) = Truncate(input, scale) + S/scale.
 ///
 /// Let x = (x0, x1, x2) is the 2-out-of-3 sharing of the (possibly, shifted) input.
 /// k_2 is a PRF key that is held only by party 2.
@@ -167,7 +175,7 @@ impl CustomOperationBody for TruncateMPC {
 /// The keys k_02 and k_12 are re-used multiplication keys.
 ///     1. Party 2 generates a random integer r of the input scalar type.
 ///     2. Party 2 extracts the MSB of r in the arithmetic form (r_msb).
-///     3. Party 2 removes the MSB of r and truncates the result by k bits (r_truncated = sum_(i=k)^(s-2) r_i * 2^(i-k) where s is the bitsize of the input scalar type)
+///     3. Party 2 removes the MSB of r and truncates the result (r_truncated = floor((r mod 2^(s-1))/scale) where s is the bitsize of the input scalar type)
 ///     4. Party 2 creates 2-out-of-2 shares of r, r_msb and r_truncated.
 ///        Such shares for a value val have the form (val0, val1) such that val = val0 + val1.
 ///        The corresponding share val0 = PRF(k_02, iv_val0) of the aforementioned 3 values is generated by parties 0 and 2.
@@ -179,40 +187,42 @@ impl CustomOperationBody for TruncateMPC {
 ///        To obtain its share, party 0 sums its 2-out-of-3 shares to get z0 = x0 + x1.
 ///        Party 1 takes z1 = x2.
 ///     7. Given r from party 2, parties 0 and 1 compute 2-out-of-2 shares of c = x + r via c0 = z0 + r0 and c1 = z1 + r1.
-///     8. Parties 0 and 1 reveal c to each other and compute c_truncated_mod = (c/2^k) mod 2^(s-k-1).
-///        This is c truncated by k bits without its MSB.
+///     8. Parties 0 and 1 reveal c to each other and compute c_truncated_mod = floor((c mod 2^(s-1))/scale).
+///        This is c without its MSB truncated by the scale.
 ///     9. Parties 0 and 1 compute the MSB of c via c/2^(s-1).
 ///     10. Parties 0 and 1 compute 2-out-of-2 shares of b = r_msb XOR c_msb using the following expressions:
 ///             b0 = r_msb0 + c_msb - 2 * c_msb * r_msb0,
 ///             b1 = r_msb1 - 2 * c_msb * r_msb1.
 ///         Note that b0 + b1 = r_msb + c_msb - 2*c_msb*r_msb = r_msb XOR c_msb.
 ///         All the above operations can be done locally as c_msb is known to parties 0 and 1.
-///     11. Parties 0 and 1 compute 2-out-of-2 shares of y' = c_truncated_mod - r_truncated + b * 2^(st_size-1-k).
-///         This value is equal to the desired result floor(x/2^k) + w.
+///     11. Parties 0 and 1 compute 2-out-of-2 shares of y' = c_truncated_mod - r_truncated + b * floor(2^(s-1)/scale).
+///         Since x = (c mod 2^(s-1)) - (r mod 2^(s-1)) + b * 2^(s-1), this value is equal to the desired result floor(x/scale) + w.
 ///     12. Party 0 masks y'0 with a random value y0 from party 2 as y_tilde0 = y'0 - y0 and sends it to party 1.
 ///     13. Party 1 masks y'1 with a random value y2 from party 2 as y_tilde1 = y'1 - y2 and sends it to party 0.
 ///     14. Parties 0 and 1 compute y1 = y_tilde0 + y_tilde1 = y' - y0 - y2.
 ///         Together with y0 and y2 this value constitute the sharing of the truncation output.
-///     14!. If input is signed, we should remove modulus/2^(k+2) after truncation since
-///          Truncate(input + modulus/4, 2^k) = Truncate(input, 2^k) + modulus/2^(k+2) as in Step 0.
+///     14!. If input is signed, we should remove S/scale after truncation as in Step 0.
 ///     15. The protocol returns (y0, y1, y2).
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
-pub(super) struct TruncateMPC2K {
-    pub k: u64,
+pub(super) struct TruncatePrMPC {
+    pub scale: u64,
 }
 
 #[typetag::serde]
-impl CustomOperationBody for TruncateMPC2K {
+impl CustomOperationBody for TruncatePrMPC {
     fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if self.scale == 0 {
+            return Err(runtime_error!("Scale of TruncatePrMPC can't be zero"));
+        }
         if argument_types.len() == 1 {
             if let Type::Array(_, _) | Type::Scalar(_) = argument_types[0].clone() {
                 let g = context.create_graph()?;
                 let input = g.input(argument_types[0].clone())?;
-                let o = if self.k == 0 {
+                let o = if self.scale == 1 {
                     // Do nothing if scale is 1
                     input
                 } else {
-                    input.truncate(1 << self.k)?
+                    input.truncate(self.scale)?
                 };
                 o.set_as_output()?;
                 g.finalize()?;
@@ -230,7 +240,7 @@ impl CustomOperationBody for TruncateMPC2K {
             // - the user has no direct access to this function.
             // - the MPC compiler should pass the correct number of arguments
             // and this panic should never happen.
-            panic!("TruncateMPC2K should have 3 inputs.");
+            panic!("TruncatePrMPC should have 3 inputs.");
         }
         if let Type::Tuple(v0) = argument_types[0].clone() {
             check_private_tuple(v0)?;
@@ -244,7 +254,7 @@ impl CustomOperationBody for TruncateMPC2K {
             }
             let g = context.create_graph()?;
             let input = g.input(argument_types[0].clone())?;
-            let o = input.truncate(1 << self.k)?;
+            let o = input.truncate(self.scale)?;
             o.set_as_output()?;
             g.finalize()?;
             return Ok(g);
@@ -302,7 +312,7 @@ impl CustomOperationBody for TruncateMPC2K {
         // PRF key k_2
         let key_2 = g.input(prf_truncate_type)?;
 
-        if self.k == 0 {
+        if self.scale == 1 {
             input_node.set_as_output()?;
             g.finalize()?;
             return Ok(g);
@@ -314,20 +324,21 @@ impl CustomOperationBody for TruncateMPC2K {
 
         let st = input_t.get_scalar_type();
         let st_size = scalar_size_in_bits(st.clone());
+        // S, the largest multiple of the scale not exceeding modulus/4
+        let shift = (1u64 << (st_size - 2)) / self.scale * self.scale;
 
         let x0 = {
             let share = input_node.tuple_get(0)?;
             // 0. The below protocol works correctly for integers in the range [0, modulus/2).
-            //    For signed inputs, we add modulus/4 to input resulting in input + modulus/4 in [0, modulus/2)
-            //    For correctness, we should remove modulus/2^(k+2) after truncation since
-            //    Truncate(input + modulus/4, 2^k) = Truncate(input, 2^k) + modulus/2^(k+2)
+            //    For signed inputs, we add S to input resulting in input + S in [0, modulus/2)
+            //    For correctness, we should remove S/scale after truncation since
+            //    Truncate(input + S, scale) = Truncate(input, scale) + S/scale
             if st.get_signed() {
-                // modulus/4
-                let mod_fraction = g.constant(
+                let shift_constant = g.constant(
                     scalar_type(st.clone()),
-                    Value::from_scalar(1u64 << (st_size - 2), st.clone())?,
+                    Value::from_scalar(shift, st.clone())?,
                 )?;
-                share.add(mod_fraction)?
+                share.add(shift_constant)?
             } else {
                 share
             }
@@ -339,6 +350,13 @@ impl CustomOperationBody for TruncateMPC2K {
         let r = g.prf(key_2, 0, input_t.clone())?;
 
         let unsigned_st = get_unsigned_counterpart(st.clone());
+        // (1,1, ..., 1, 0) to remove the MSB, i.e. to compute mod 2^(st_size-1)
+        let mod_mask = g
+            .constant(
+                scalar_type(unsigned_st.clone()),
+                Value::from_scalar((1u64 << (st_size - 1)) - 1, unsigned_st.clone())?,
+            )?
+            .a2b()?;
         // 2. Party 2 extracts the MSB of r in the arithmetic form (r_msb).
         let r_msb = {
             // (0,0, ..., 1)
@@ -357,24 +375,12 @@ impl CustomOperationBody for TruncateMPC2K {
                 .b2a(st.clone())?
         };
 
-        // 3. Party 2 removes the MSB of r and truncates the result by k bits (r_truncated = sum_(i=k)^(st_size-2) r_i * 2^(i-k))
-        let r_truncated = {
-            // (0, ..., 0, 1, ..., 1, 0, ..., 0) to extract r_k, r_(k+1), ..., r_(st_size-2)
-            let mask = g
-                .constant(
-                    scalar_type(unsigned_st.clone()),
-                    Value::from_scalar(
-                        (1u64 << (st_size - 1)) - (1u64 << self.k),
-                        unsigned_st.clone(),
-                    )?,
-                )?
-                .a2b()?;
-            // r_k + r_(k+1) * 2 + ... + r_(st_size-2) * 2^(st_size-2-k)
-            r.a2b()?
-                .multiply(mask)?
-                .b2a(st.clone())?
-                .truncate(1 << self.k)?
-        };
+        // 3. Party 2 removes the MSB of r and truncates the result (r_truncated = floor((r mod 2^(st_size-1))/scale))
+        let r_truncated = r
+            .a2b()?
+            .multiply(mod_mask.clone())?
+            .b2a(st.clone())?
+            .truncate(self.scale)?;
 
         // 4. Party 2 creates 2-out-of-2 shares of r, r_msb and r_truncated.
         //    Such shares for a value val have the form (val0, val1) such that val = val0 + val1.
@@ -408,31 +414,18 @@ impl CustomOperationBody for TruncateMPC2K {
         let c_share0 = z0.add(r0)?;
         let c_share1 = z1.add(r1)?;
 
-        // 8. Parties 0 and 1 reveal c to each other and compute c_truncated_mod = (c/2^k) mod 2^(st_size-k-1).
-        //    This is c truncated by k bits without its MSB.
+        // 8. Parties 0 and 1 reveal c to each other and compute c_truncated_mod = floor((c mod 2^(st_size-1))/scale).
+        //    This is c without its MSB truncated by the scale.
         let c_share0_sent = c_share0.nop()?;
         c_share0_sent.add_annotation(NodeAnnotation::Send(0, 1))?;
         let c_share1_sent = c_share1.nop()?;
         c_share1_sent.add_annotation(NodeAnnotation::Send(1, 0))?;
         let c = c_share0_sent.add(c_share1_sent)?;
-        // Interpret c as unsigned integer and truncate
-        // (c / scale) mod 2^(st_size-1-k)
-        let c_truncated = c
-            .a2b()?
-            .b2a(unsigned_st.clone())?
-            .truncate(1 << self.k)?
+        let c_truncated_mod = c
             .a2b()?
-            .b2a(st.clone())?;
-        let c_truncated_mod = {
-            // (1,1, ..., 1, 0, ..., 0) to perform mod 2^(st_size-1-k)
-            let mask = g
-                .constant(
-                    scalar_type(st.clone()),
-                    Value::from_scalar((1u64 << (st_size - 1 - self.k)) - 1, st.clone())?,
-                )?
-                .a2b()?;
-            c_truncated.a2b()?.multiply(mask)?.b2a(st.clone())?
-        };
+            .multiply(mod_mask)?
+            .b2a(st.clone())?
+            .truncate(self.scale)?;
 
         // 9. Parties 0 and 1 compute the MSB of c via c/2^(st_size-1).
         let c_msb = c
@@ -453,20 +446,20 @@ impl CustomOperationBody for TruncateMPC2K {
             .add(c_msb.clone())?;
         let b1 = r_msb1.subtract(r_msb1.multiply(c_msb)?.multiply(two)?)?;
 
-        // 11. Parties 0 and 1 compute 2-out-of-2 shares of y' = c_truncated_mod - r_truncated + b * 2^(st_size-1-k).
-        //     This value is equal to the desired result floor(x/2^k) + w.
-        // 2^(st_size-1-k)
-        let power2 = g.constant(
+        // 11. Parties 0 and 1 compute 2-out-of-2 shares of y' = c_truncated_mod - r_truncated + b * floor(2^(st_size-1)/scale).
+        //     This value is equal to the desired result floor(x/scale) + w.
+        // floor(2^(st_size-1)/scale)
+        let msb_quotient = g.constant(
             scalar_type(st.clone()),
-            Value::from_scalar(1u64 << (st_size - 1 - self.k), st.clone())?,
+            Value::from_scalar((1u64 << (st_size - 1)) / self.scale, st.clone())?,
         )?;
-        // y' = c_truncated_mod - r_truncated + b * 2^(st_size-1-k)
+        // y' = c_truncated_mod - r_truncated + b * floor(2^(st_size-1)/scale)
         // This is 2-out-of-2 sharing of the result
         let y_prime0 = b0
-            .multiply(power2.clone())?
+            .multiply(msb_quotient.clone())?
             .subtract(r_truncated0)?
             .add(c_truncated_mod)?;
-        let y_prime1 = b1.multiply(power2)?.subtract(r_truncated1)?;
+        let y_prime1 = b1.multiply(msb_quotient)?.subtract(r_truncated1)?;
 
         // 12. Party 0 masks y'0 with a random value y0 from party 2 as y_tilde0 = y'0 - y0 and sends it to party 1.
         let y_tilde0 = y_prime0.subtract(y0.clone())?;
@@ -482,13 +475,13 @@ impl CustomOperationBody for TruncateMPC2K {
         let y1 = {
             let sum01 = y_tilde0_sent.add(y_tilde1_sent)?;
             if st.get_signed() {
-                // 14!. If input is signed, we should remove modulus/2^(k+2) after truncation since
-                //      Truncate(input + modulus/4, 2^k) = Truncate(input, 2^k) + modulus/2^(k+2)
-                let mod_fraction = g.constant(
+                // 14!. If input is signed, we should remove S/scale after truncation since
+                //      Truncate(input + S, scale) = Truncate(input, scale) + S/scale
+                let shift_quotient = g.constant(
                     scalar_type(st.clone()),
-                    Value::from_scalar(1u64 << (st_size - 2 - self.k), st)?,
+                    Value::from_scalar(shift / self.scale, st)?,
                 )?;
-                sum01.subtract(mod_fraction)?
+                sum01.subtract(shift_quotient)?
             } else {
                 sum01
             }
@@ -501,11 +494,34 @@ impl CustomOperationBody for TruncateMPC2K {
         Ok(g)
     }
 
+    fn get_name(&self) -> String {
+        format!("TruncatePrMPC({})", self.scale)
+    }
+}
+
+register_custom_operation!(TruncatePrMPC, [Shared], [Shared, PrfKeys, Any]);
+
+/// Truncate MPC operation for public and private data by a power of 2.
+///
+/// This operation is equivalent to [TruncatePrMPC] with scale 2^k.
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub(super) struct TruncateMPC2K {
+    pub k: u64,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for TruncateMPC2K {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        TruncatePrMPC { scale: 1 << self.k }.instantiate(context, argument_types)
+    }
+
     fn get_name(&self) -> String {
         format!("TruncateMPC2K({})", self.k)
     }
 }
 
+register_custom_operation!(TruncateMPC2K, [Shared], [Shared, PrfKeys, Any]);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -600,7 +616,7 @@ mod tests {
             }
         } else {
             for (i, out_value) in output.iter().enumerate() {
-                let dif = (*out_value) - expected[i];
+                let dif = out_value.abs_diff(expected[i]);
                 if equal && dif > 1 {
                     return Err(runtime_error!("Output is too far from expected"));
                 }
@@ -686,7 +702,7 @@ mod tests {
                     .iter()
                     .map(|x| {
                         let val = *x as i64;
-                        let res = val / (scale as i64);
+                        let res = val.div_euclid(scale as i64);
                         res as u64
                     })
                     .collect()
@@ -765,8 +781,8 @@ mod tests {
         helper_runs(vec![1000], scalar_type(st.clone()))?;
         helper_runs(vec![0, 0], array_type(vec![2], st.clone()))?;
         helper_runs(vec![2000, 255], array_type(vec![2], st.clone()))?;
-        if scale.is_power_of_two() && !st.get_signed() {
-            // 2^63 - 1, this is a maximal UINT64 value that can be truncated without errors by TruncateMPC2K
+        if !st.get_signed() {
+            // 2^63 - 1, this is a maximal UINT64 value that can be truncated without errors by TruncatePrMPC
             helper_runs(vec![(1u64 << 63) - 1], scalar_type(st.clone()))?;
         }
 
@@ -781,15 +797,15 @@ mod tests {
                 array_type(vec![2], st.clone()),
             )?;
             if scale.is_power_of_two() {
-                // - 2^62, this is a minimal INT32 value that can be truncated without errors by TruncateMPC2K
+                // - 2^62, this is a minimal INT32 value that can be truncated without errors by TruncatePrMPC
                 helper_runs(vec![1u64 << 62], scalar_type(st.clone()))?;
-                // 2^62-1, this is a maximal INT32 value that can be truncated without errors by TruncateMPC2K
+                // 2^62-1, this is a maximal INT32 value that can be truncated without errors by TruncatePrMPC
                 helper_runs(vec![(1u64 << 62) - 1], scalar_type(st.clone()))?;
             }
         }
 
-        // Probabilistic tests of TruncateMPC for big values in absolute size
-        if scale != 1 && !scale.is_power_of_two() {
+        // Probabilistic tests of TruncatePrMPC for big values in absolute size
+        if scale != 1 && st.get_signed() {
             // 2^63 - 1, should fail with probability 1 - 2^(-40)
             assert!(helper_malformed(vec![i64::MAX as u64], scalar_type(st.clone()), 40).is_err());
             // -2^63, should fail with probability 1 - 2^(-40)
@@ -827,6 +843,8 @@ mod tests {
         truncate_helper(INT64, 1 << 29).unwrap();
         truncate_helper(INT64, (1 << 29) - 1).unwrap();
 
-        assert!(truncate_helper(UINT64, 15).is_err());
+        truncate_helper(UINT64, 15).unwrap();
+        truncate_helper(UINT64, 1000).unwrap();
+        truncate_helper(INT64, 1000).unwrap();
     }
 }
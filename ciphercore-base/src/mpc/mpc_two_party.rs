@@ -0,0 +1,1263 @@
+//! Compilation of graphs into the two-party semi-honest protocol.
+//!
+//! A private value `x` is additively shared between parties 0 and 1, i.e. it is represented by a pair `(x_0, x_1)` with `x = x_0 + x_1`,
+//! where `x_i` is known only to party `i`. Binary values are shared via XOR, which coincides with the addition of bits.
+//! Linear operations are computed locally on shares.
+//! Products of private values (Multiply, Dot, Matmul, Gemm) are computed with [Beaver triples](https://doi.org/10.1007/3-540-46766-1_34):
+//! given shares of random `a`, `b` and `c = a * b`, the parties open `e = x - a` and `f = y - b` and compute
+//! `x * y = c + e * b + a * f + e * f` locally.
+//! Boolean subgraphs (e.g. comparisons produced by the instantiation of custom operations) are thus evaluated in the GMW style with binary triples.
+//!
+//! Triples are generated by the computing parties themselves: party `i` samples `a_i` and `b_i`, so `c = op(a, b)` is the sum of
+//! the local products `op(a_i, b_i)` and the cross terms `op(a_0, b_1)` and `op(a_1, b_0)`.
+//! The cross terms are shared via the OT-based multiplication of [Gilboa](https://doi.org/10.1007/3-540-48405-1_8):
+//! for every bit `v_t` of an entry `v` of the receiver, a correlated OT gives the receiver `r + v_t * 2^t * u` for an entry `u` of the sender, who keeps `-r`.
+//! These OTs are run with the [IKNP extension](crate::mpc::oblivious_transfer::IknpSender).
+//! As OT is interactive, triples can't be computed by a graph.
+//! Instead, the last two inputs of a compiled graph are the triples of parties 0 and 1, which are returned by [generate_triples].
+//! Triples don't depend on inputs, so they can be generated ahead of time.
+//!
+//! Truncate is computed locally as in [SecureML](https://eprint.iacr.org/2017/396.pdf):
+//! party 0 truncates `x_0` and party 1 negates the truncation of `-x_1`, where shares are truncated as unsigned integers.
+//! If `|x| < 2^l` in the ring `Z_{2^k}`, the result differs from the result of Truncate by at most 1 with probability at least `1 - 2^(l + 1 - k)`.
+//!
+//! Products and truncation of private values are supported for moduli that are powers of two up to `2^64`.
+//! Operations mixing arithmetic and binary shares (A2B, B2A, MixedMultiply of private bits) and SetIntersection aren't supported by this backend.
+#[cfg(not(target_arch = "wasm32"))]
+use crate::broadcast::broadcast_shapes;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::data_types::get_types_vector;
+use crate::data_types::{get_u64_modulus, scalar_type, tuple_type, ScalarType, Type};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{
+    copy_node_name, create_context, Context, Graph, Node, NodeAnnotation, Operation,
+};
+use crate::mpc::mpc_compiler::IOStatus;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mpc::oblivious_transfer::{
+    create_local_channels, Channel, IknpReceiver, IknpSender, OtBlock, OtReceiver, OtSender,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::random::PRNG;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::type_inference::transpose_shape;
+
+use super::mpc_truncate::get_unsigned_counterpart;
+
+use std::collections::{HashMap, HashSet};
+
+/// Number of parties holding shares
+const COMPUTING_PARTIES: u64 = 2;
+
+#[derive(Clone)]
+enum LoweredNode {
+    Public(Node),
+    Private([Node; 2]),
+}
+
+pub(super) fn send(node: Node, sender: u64, receiver: u64) -> Result<Node> {
+    node.nop()?
+        .add_annotation(NodeAnnotation::Send(sender, receiver))
+}
+
+/// Returns the shares of a lowered node; public values are shared as `(x, 0)`.
+fn get_shares(g: &Graph, node: &LoweredNode) -> Result<[Node; 2]> {
+    match node {
+        LoweredNode::Public(x) => {
+            let t = x.get_type()?;
+            let zero = g.constant(t.clone(), Value::zero_of_type(t))?;
+            Ok([x.clone(), zero])
+        }
+        LoweredNode::Private(shares) => Ok(shares.clone()),
+    }
+}
+
+/// Returns `k` for a scalar type with the modulus `2^k`, in which triples are generated and shares are truncated.
+fn get_ring_bits(st: &ScalarType) -> Result<u64> {
+    match get_u64_modulus(st)? {
+        None => Ok(64),
+        Some(modulus) if modulus.is_power_of_two() => Ok(modulus.trailing_zeros() as u64),
+        Some(_) => Err(runtime_error!(
+            "Two-party protocol supports only moduli that are powers of two, but {} is given",
+            st
+        )),
+    }
+}
+
+/// Multiplies two private values using a Beaver triple.
+///
+/// `op` must be bilinear, e.g. Multiply, Dot or Matmul.
+/// `triple[i]` is the triple input of party `i` containing `(a_i, b_i, d_i)`, where `d_0 + d_1 = op(a_0, b_1) + op(a_1, b_0)`.
+fn multiply_shares(
+    x: [Node; 2],
+    y: [Node; 2],
+    op: &Operation,
+    triple: [Node; 2],
+) -> Result<[Node; 2]> {
+    let g = x[0].get_graph();
+    get_ring_bits(&x[0].get_type()?.get_scalar_type())?;
+    let apply = |a: Node, b: Node| g.add_node(vec![a, b], vec![], op.clone());
+    let mut a = vec![];
+    let mut b = vec![];
+    let mut c = vec![];
+    for (i, triple_share) in triple.iter().enumerate() {
+        let a_i = triple_share.tuple_get(0)?;
+        let b_i = triple_share.tuple_get(1)?;
+        let product = apply(a_i.clone(), b_i.clone())?;
+        if i == 0 {
+            // Every triple is counted once
+            product.add_annotation(NodeAnnotation::BeaverTriple)?;
+        }
+        c.push(product.add(triple_share.tuple_get(2)?)?);
+        a.push(a_i);
+        b.push(b_i);
+    }
+    // Opening of e = x - a and f = y - b
+    let e0 = x[0].subtract(a[0].clone())?;
+    let e1 = x[1].subtract(a[1].clone())?;
+    let e = send(e0, 0, 1)?.add(send(e1, 1, 0)?)?;
+    let f0 = y[0].subtract(b[0].clone())?;
+    let f1 = y[1].subtract(b[1].clone())?;
+    let f = send(f0, 0, 1)?.add(send(f1, 1, 0)?)?;
+    // z_0 = c_0 + e * b_0 + a_0 * f + e * f, z_1 = c_1 + e * b_1 + a_1 * f
+    let z0 = c[0]
+        .add(apply(e.clone(), b[0].clone())?)?
+        .add(apply(a[0].clone(), f.clone())?)?
+        .add(apply(e.clone(), f.clone())?)?;
+    let z1 = c[1]
+        .add(apply(e, b[1].clone())?)?
+        .add(apply(a[1].clone(), f)?)?;
+    Ok([z0, z1])
+}
+
+/// Truncates shares of a private value locally (see the [module documentation](self)).
+fn truncate_shares(x: [Node; 2], scale: u64) -> Result<[Node; 2]> {
+    let st = x[0].get_type()?.get_scalar_type();
+    get_ring_bits(&st)?;
+    let unsigned_st = get_unsigned_counterpart(st.clone());
+    let truncate_unsigned = |share: Node| -> Result<Node> {
+        if st == unsigned_st {
+            share.truncate(scale)
+        } else {
+            share
+                .convert_ring(unsigned_st.clone())?
+                .truncate(scale)?
+                .convert_ring(st.clone())
+        }
+    };
+    let g = x[0].get_graph();
+    let zero_t = scalar_type(st.clone());
+    let zero = g.constant(zero_t.clone(), Value::zero_of_type(zero_t))?;
+    let share0 = truncate_unsigned(x[0].clone())?;
+    let share1 = zero.subtract(truncate_unsigned(zero.subtract(x[1].clone())?)?)?;
+    Ok([share0, share1])
+}
+
+fn lower_input(g: &Graph, node: &Node, t: Type, status: &IOStatus) -> Result<LoweredNode> {
+    match status {
+        IOStatus::Public => {
+            let input = g.input(t)?;
+            copy_node_name(node.clone(), input.clone())?;
+            Ok(LoweredNode::Public(input))
+        }
+        IOStatus::Party(id) => {
+            if *id >= COMPUTING_PARTIES {
+                return Err(runtime_error!(
+                    "Input party should have a valid party ID for the two-party protocol"
+                ));
+            }
+            let input = g.input(t.clone())?;
+            copy_node_name(node.clone(), input.clone())?;
+            // The owner keeps x - r and sends a random mask r to the other party
+            let mask = g.random(t)?;
+            let own_share = input.subtract(mask.clone())?;
+            let other_share = send(mask, *id, 1 - *id)?;
+            if *id == 0 {
+                Ok(LoweredNode::Private([own_share, other_share]))
+            } else {
+                Ok(LoweredNode::Private([other_share, own_share]))
+            }
+        }
+        IOStatus::Shared => {
+            let input = g.input(tuple_type(vec![t; COMPUTING_PARTIES as usize]))?;
+            copy_node_name(node.clone(), input.clone())?;
+            Ok(LoweredNode::Private([
+                input.tuple_get(0)?,
+                input.tuple_get(1)?,
+            ]))
+        }
+    }
+}
+
+fn unsupported(op: &Operation) -> crate::errors::CiphercoreBaseError {
+    runtime_error!("Two-party compilation for private {} is not supported", op)
+}
+
+/// Checks whether an operation is a bilinear product that consumes a triple if both its arguments are private.
+fn is_product(op: &Operation) -> bool {
+    matches!(
+        op,
+        Operation::Multiply | Operation::Dot | Operation::Matmul | Operation::Gemm(_, _)
+    )
+}
+
+/// Compiles the main graph of an instantiated and inlined context into a graph of the two-party protocol.
+///
+/// # Arguments
+///
+/// * `context` - context without custom operations and graph calls
+/// * `input_parties` - statuses of the inputs of the main graph; party IDs must be 0 or 1
+/// * `output_parties` - parties (0 or 1) that obtain the revealed output; if empty, the output is returned as a pair of shares
+///
+/// # Returns
+///
+/// New context whose main graph computes the output of the original main graph.
+/// Its inputs are the inputs of the original main graph followed by the triples of parties 0 and 1 (see [generate_triples]).
+pub(super) fn compile_to_two_party(
+    context: Context,
+    input_parties: Vec<IOStatus>,
+    output_parties: Vec<IOStatus>,
+) -> Result<Context> {
+    context.check_finalized()?;
+    let mut output_ids = vec![];
+    for status in &output_parties {
+        match status {
+            IOStatus::Party(id) if *id < COMPUTING_PARTIES => output_ids.push(*id),
+            _ => {
+                return Err(runtime_error!(
+                    "Output status should be a party ID of the two-party protocol or shared"
+                ))
+            }
+        }
+    }
+    let in_graph = context.get_main_graph()?;
+    let out_context = create_context()?;
+    let g = out_context.create_graph()?;
+    // Lowered nodes indexed by the IDs of the nodes of the input graph
+    let mut lowered: HashMap<u64, LoweredNode> = HashMap::new();
+    // Inputs are lowered first, so that the triple inputs follow them.
+    // Meanwhile, private nodes are found to collect the types of the triples.
+    let mut private_ids = HashSet::new();
+    let mut triple_types = vec![];
+    let mut input_id = 0;
+    for node in in_graph.get_nodes() {
+        let op = node.get_operation();
+        let dependencies = node.get_node_dependencies();
+        let private = if let Operation::Input(t) = op.clone() {
+            let status = input_parties
+                .get(input_id)
+                .ok_or_else(|| runtime_error!("Not enough input statuses"))?;
+            input_id += 1;
+            let input = lower_input(&g, &node, t, status)?;
+            let private = matches!(input, LoweredNode::Private(_));
+            lowered.insert(node.get_id(), input);
+            private
+        } else {
+            dependencies
+                .iter()
+                .any(|d| private_ids.contains(&d.get_id()))
+        };
+        if !private {
+            continue;
+        }
+        private_ids.insert(node.get_id());
+        if is_product(&op)
+            && dependencies
+                .iter()
+                .all(|d| private_ids.contains(&d.get_id()))
+        {
+            triple_types.push(tuple_type(vec![
+                dependencies[0].get_type()?,
+                dependencies[1].get_type()?,
+                node.get_type()?,
+            ]));
+        }
+    }
+    if input_id != input_parties.len() {
+        return Err(runtime_error!(
+            "Invalid number of input parties: {} expected, but {} found",
+            input_id,
+            input_parties.len()
+        ));
+    }
+    let mut triples = vec![];
+    for _ in 0..COMPUTING_PARTIES {
+        triples.push(g.input(tuple_type(triple_types.clone()))?);
+    }
+    let mut triple_id = 0;
+    for node in in_graph.get_nodes() {
+        let op = node.get_operation();
+        if let Operation::Input(_) = op {
+            continue;
+        }
+        let dependencies: Vec<LoweredNode> = node
+            .get_node_dependencies()
+            .iter()
+            .map(|d| lowered[&d.get_id()].clone())
+            .collect();
+        let all_public = dependencies
+            .iter()
+            .all(|d| matches!(d, LoweredNode::Public(_)));
+        let new_node = match op.clone() {
+            Operation::Random(_) | Operation::RandomBeacon(_) => {
+                return Err(runtime_error!(
+                    "Two-party compilation of random values is not supported"
+                ))
+            }
+            _ if all_public => {
+                // Public operations are computed by every party in the clear
+                let public_dependencies = dependencies
+                    .iter()
+                    .map(|d| match d {
+                        LoweredNode::Public(x) => x.clone(),
+                        LoweredNode::Private(_) => unreachable!(),
+                    })
+                    .collect();
+                let graph_dependencies = node.get_graph_dependencies();
+                if !graph_dependencies.is_empty() {
+                    return Err(runtime_error!(
+                        "Two-party compilation requires an inlined context"
+                    ));
+                }
+                LoweredNode::Public(g.add_node(public_dependencies, vec![], op)?)
+            }
+            Operation::Add | Operation::Subtract => {
+                let x = get_shares(&g, &dependencies[0])?;
+                let y = get_shares(&g, &dependencies[1])?;
+                let mut shares = vec![];
+                for i in 0..2 {
+                    shares.push(g.add_node(
+                        vec![x[i].clone(), y[i].clone()],
+                        vec![],
+                        op.clone(),
+                    )?);
+                }
+                LoweredNode::Private([shares[0].clone(), shares[1].clone()])
+            }
+            Operation::Multiply
+            | Operation::Dot
+            | Operation::Matmul
+            | Operation::Gemm(_, _)
+            | Operation::MixedMultiply => match (&dependencies[0], &dependencies[1]) {
+                (LoweredNode::Private(x), LoweredNode::Public(y)) => {
+                    let mut shares = vec![];
+                    for x_share in x {
+                        shares.push(g.add_node(
+                            vec![x_share.clone(), y.clone()],
+                            vec![],
+                            op.clone(),
+                        )?);
+                    }
+                    LoweredNode::Private([shares[0].clone(), shares[1].clone()])
+                }
+                (LoweredNode::Public(x), LoweredNode::Private(y))
+                    if op != Operation::MixedMultiply =>
+                {
+                    let mut shares = vec![];
+                    for y_share in y {
+                        shares.push(g.add_node(
+                            vec![x.clone(), y_share.clone()],
+                            vec![],
+                            op.clone(),
+                        )?);
+                    }
+                    LoweredNode::Private([shares[0].clone(), shares[1].clone()])
+                }
+                (LoweredNode::Private(x), LoweredNode::Private(y))
+                    if op != Operation::MixedMultiply =>
+                {
+                    let triple = [
+                        triples[0].tuple_get(triple_id)?,
+                        triples[1].tuple_get(triple_id)?,
+                    ];
+                    triple_id += 1;
+                    LoweredNode::Private(multiply_shares(x.clone(), y.clone(), &op, triple)?)
+                }
+                _ => return Err(unsupported(&op)),
+            },
+            Operation::Truncate(scale) => {
+                LoweredNode::Private(truncate_shares(get_shares(&g, &dependencies[0])?, scale)?)
+            }
+            Operation::VectorGet => {
+                let index = match &dependencies[1] {
+                    LoweredNode::Public(index) => index.clone(),
+                    LoweredNode::Private(_) => return Err(unsupported(&op)),
+                };
+                let x = get_shares(&g, &dependencies[0])?;
+                let mut shares = vec![];
+                for x_share in x {
+                    shares.push(g.add_node(vec![x_share, index.clone()], vec![], op.clone())?);
+                }
+                LoweredNode::Private([shares[0].clone(), shares[1].clone()])
+            }
+            Operation::PermuteAxes(_)
+            | Operation::ArrayToVector
+            | Operation::VectorToArray
+            | Operation::TupleGet(_)
+            | Operation::NamedTupleGet(_)
+            | Operation::GetSlice(_)
+            | Operation::Reshape(_)
+            | Operation::Sum(_)
+            | Operation::Get(_)
+            | Operation::Repeat(_)
+            | Operation::CreateTuple
+            | Operation::CreateNamedTuple(_)
+            | Operation::CreateVector(_)
+            | Operation::Stack(_)
+            | Operation::Concatenate(_)
+            | Operation::Split(_, _)
+            | Operation::BroadcastTo(_)
+            | Operation::Tile(_)
+            | Operation::CumSum(_)
+            | Operation::Zip => {
+                // Linear operations are applied to every share
+                let mut shares = [vec![], vec![]];
+                for dependency in &dependencies {
+                    let dependency_shares = get_shares(&g, dependency)?;
+                    for i in 0..2 {
+                        shares[i].push(dependency_shares[i].clone());
+                    }
+                }
+                let share0 = g.add_node(shares[0].clone(), vec![], op.clone())?;
+                let share1 = g.add_node(shares[1].clone(), vec![], op.clone())?;
+                LoweredNode::Private([share0, share1])
+            }
+            _ => return Err(unsupported(&op)),
+        };
+        lowered.insert(node.get_id(), new_node);
+    }
+    let output = match &lowered[&in_graph.get_output_node()?.get_id()] {
+        LoweredNode::Public(x) => x.clone(),
+        LoweredNode::Private(shares) => {
+            if output_ids.is_empty() {
+                g.create_tuple(shares.to_vec())?
+                    .add_annotation(NodeAnnotation::Private)?
+            } else {
+                let mut shares = shares.clone();
+                for id in output_ids {
+                    let other = (1 - id) as usize;
+                    shares[other] = send(shares[other].clone(), 1 - id, id)?;
+                }
+                shares[0].add(shares[1].clone())?
+            }
+        }
+    };
+    g.set_output_node(output)?;
+    g.finalize()?;
+    out_context.set_main_graph(g)?;
+    out_context.finalize()?;
+    Ok(out_context)
+}
+
+/// Triple consumed by a product of private values.
+#[cfg(not(target_arch = "wasm32"))]
+struct TripleLayout {
+    /// Types of `a`, `b` and `c`
+    types: Vec<Type>,
+    /// Bit size `k` of the ring `Z_{2^k}`
+    bits: u64,
+    /// Products `a[i] * b[j]` added to `c[k]` by the bilinear operation, given as `(i, j, k)` with indices of flattened arrays
+    products: Vec<(usize, usize, usize)>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn get_shape(t: &Type) -> Vec<u64> {
+    if t.is_array() {
+        t.get_shape()
+    } else {
+        vec![]
+    }
+}
+
+// Index of an entry of a flattened array
+#[cfg(not(target_arch = "wasm32"))]
+fn flatten_index(shape: &[u64], index: &[u64]) -> usize {
+    shape
+        .iter()
+        .zip(index)
+        .fold(0, |flat_index, (dimension, i)| flat_index * dimension + i) as usize
+}
+
+// Indices of all the entries of an array in the row-major order
+#[cfg(not(target_arch = "wasm32"))]
+fn get_all_indices(shape: &[u64]) -> Vec<Vec<u64>> {
+    let mut indices = vec![vec![]];
+    for dimension in shape {
+        indices = indices
+            .into_iter()
+            .flat_map(|index: Vec<u64>| {
+                (0..*dimension).map(move |i| [index.clone(), vec![i]].concat())
+            })
+            .collect();
+    }
+    indices
+}
+
+// Index of the entry of an array of a given shape that is broadcast to a given index of a larger array
+#[cfg(not(target_arch = "wasm32"))]
+fn broadcast_index(shape: &[u64], index: &[u64]) -> Vec<u64> {
+    let offset = index.len() - shape.len();
+    shape
+        .iter()
+        .zip(&index[offset..])
+        .map(|(dimension, i)| if *dimension == 1 { 0 } else { *i })
+        .collect()
+}
+
+/// Returns the products of entries of `a` and `b` summed up in every entry of `op(a, b)`.
+#[cfg(not(target_arch = "wasm32"))]
+fn get_products(
+    op: &Operation,
+    a_shape: &[u64],
+    b_shape: &[u64],
+) -> Result<Vec<(usize, usize, usize)>> {
+    let mut products = vec![];
+    match op {
+        Operation::Multiply => {
+            let shape = broadcast_shapes(a_shape.to_vec(), b_shape.to_vec())?;
+            for (k, index) in get_all_indices(&shape).iter().enumerate() {
+                products.push((
+                    flatten_index(a_shape, &broadcast_index(a_shape, index)),
+                    flatten_index(b_shape, &broadcast_index(b_shape, index)),
+                    k,
+                ));
+            }
+        }
+        Operation::Dot if a_shape.is_empty() || b_shape.is_empty() => {
+            return get_products(&Operation::Multiply, a_shape, b_shape);
+        }
+        Operation::Dot if b_shape.len() == 1 => {
+            // Sum over the last axis of `a`
+            let a_batch = &a_shape[..a_shape.len() - 1];
+            for (k, index) in get_all_indices(a_batch).iter().enumerate() {
+                for j in 0..b_shape[0] {
+                    let a_index = [index.clone(), vec![j]].concat();
+                    products.push((flatten_index(a_shape, &a_index), j as usize, k));
+                }
+            }
+        }
+        Operation::Dot => {
+            // Sum over the last axis of `a` and the second last axis of `b`
+            let a_batch = &a_shape[..a_shape.len() - 1];
+            let b_batch = &b_shape[..b_shape.len() - 2];
+            let m = b_shape[b_shape.len() - 2];
+            let p = b_shape[b_shape.len() - 1];
+            let mut k = 0;
+            for a_batch_index in get_all_indices(a_batch) {
+                for b_batch_index in get_all_indices(b_batch) {
+                    for l in 0..p {
+                        for j in 0..m {
+                            let a_index = [a_batch_index.clone(), vec![j]].concat();
+                            let b_index = [b_batch_index.clone(), vec![j, l]].concat();
+                            products.push((
+                                flatten_index(a_shape, &a_index),
+                                flatten_index(b_shape, &b_index),
+                                k,
+                            ));
+                        }
+                        k += 1;
+                    }
+                }
+            }
+        }
+        Operation::Matmul | Operation::Gemm(_, _) => {
+            let (transpose_a, transpose_b) = match op {
+                Operation::Gemm(transpose_a, transpose_b) => (*transpose_a, *transpose_b),
+                _ => (false, false),
+            };
+            // Vectors are multiplied as a matrix with one row (`a`) or one column (`b`)
+            let a_matrix_shape = if a_shape.len() == 1 {
+                vec![1, a_shape[0]]
+            } else {
+                transpose_shape(a_shape.to_vec(), transpose_a)
+            };
+            let b_matrix_shape = if b_shape.len() == 1 {
+                vec![b_shape[0], 1]
+            } else {
+                transpose_shape(b_shape.to_vec(), transpose_b)
+            };
+            let a_batch = &a_matrix_shape[..a_matrix_shape.len() - 2];
+            let b_batch = &b_matrix_shape[..b_matrix_shape.len() - 2];
+            let n = a_matrix_shape[a_matrix_shape.len() - 2];
+            let m = a_matrix_shape[a_matrix_shape.len() - 1];
+            let p = b_matrix_shape[b_matrix_shape.len() - 1];
+            let mut k = 0;
+            for index in get_all_indices(&broadcast_shapes(a_batch.to_vec(), b_batch.to_vec())?) {
+                let a_batch_index = broadcast_index(a_batch, &index);
+                let b_batch_index = broadcast_index(b_batch, &index);
+                for i in 0..n {
+                    for l in 0..p {
+                        for j in 0..m {
+                            let a_index = if transpose_a {
+                                flatten_index(
+                                    a_shape,
+                                    &[a_batch_index.clone(), vec![j, i]].concat(),
+                                )
+                            } else {
+                                flatten_index(
+                                    &a_matrix_shape,
+                                    &[a_batch_index.clone(), vec![i, j]].concat(),
+                                )
+                            };
+                            let b_index = if transpose_b {
+                                flatten_index(
+                                    b_shape,
+                                    &[b_batch_index.clone(), vec![l, j]].concat(),
+                                )
+                            } else {
+                                flatten_index(
+                                    &b_matrix_shape,
+                                    &[b_batch_index.clone(), vec![j, l]].concat(),
+                                )
+                            };
+                            products.push((a_index, b_index, k));
+                        }
+                        k += 1;
+                    }
+                }
+            }
+        }
+        _ => {
+            return Err(runtime_error!(
+                "Two-party triples for {} aren't supported",
+                op
+            ))
+        }
+    }
+    Ok(products)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn not_two_party_context() -> crate::errors::CiphercoreBaseError {
+    runtime_error!("Context isn't compiled by the two-party backend")
+}
+
+/// Returns the layouts of the triples consumed by a context compiled by the two-party backend.
+#[cfg(not(target_arch = "wasm32"))]
+fn get_triple_layouts(context: &Context) -> Result<Vec<TripleLayout>> {
+    let nodes = context.get_main_graph()?.get_nodes();
+    let inputs: Vec<&Node> = nodes
+        .iter()
+        .filter(|node| matches!(node.get_operation(), Operation::Input(_)))
+        .collect();
+    let triples_input = inputs.last().ok_or_else(not_two_party_context)?;
+    let triple_types = get_types_vector(triples_input.get_type()?)?;
+    let mut operations = vec![None; triple_types.len()];
+    for node in &nodes {
+        if !node
+            .get_annotations()?
+            .contains(&NodeAnnotation::BeaverTriple)
+        {
+            continue;
+        }
+        // The local product of party 0 multiplies a_0 extracted from the triple
+        let triple = node
+            .get_node_dependencies()
+            .first()
+            .and_then(|a| a.get_node_dependencies().first().cloned())
+            .ok_or_else(not_two_party_context)?;
+        match triple.get_operation() {
+            Operation::TupleGet(id) if (id as usize) < operations.len() => {
+                operations[id as usize] = Some(node.get_operation());
+            }
+            _ => return Err(not_two_party_context()),
+        }
+    }
+    let mut layouts = vec![];
+    for (triple_type, op) in triple_types.into_iter().zip(operations) {
+        let op = op.ok_or_else(not_two_party_context)?;
+        let types: Vec<Type> = get_types_vector((*triple_type).clone())?
+            .into_iter()
+            .map(|t| (*t).clone())
+            .collect();
+        if types.len() != 3 {
+            return Err(not_two_party_context());
+        }
+        let products = get_products(&op, &get_shape(&types[0]), &get_shape(&types[1]))?;
+        let sizes: Vec<usize> = types
+            .iter()
+            .map(|t| get_shape(t).iter().product::<u64>() as usize)
+            .collect();
+        if products
+            .iter()
+            .any(|(i, j, k)| *i >= sizes[0] || *j >= sizes[1] || *k >= sizes[2])
+        {
+            return Err(not_two_party_context());
+        }
+        layouts.push(TripleLayout {
+            bits: get_ring_bits(&types[0].get_scalar_type())?,
+            types,
+            products,
+        });
+    }
+    Ok(layouts)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn value_to_entries(value: &Value, t: &Type) -> Result<Vec<u64>> {
+    if t.is_scalar() {
+        Ok(vec![value.to_u64(t.get_scalar_type())?])
+    } else {
+        value.to_flattened_array_u64(t.clone())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn value_from_entries(entries: &[u64], t: &Type, bits: u64) -> Result<Value> {
+    let mask = if bits == 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    };
+    let entries: Vec<u64> = entries.iter().map(|x| x & mask).collect();
+    if t.is_scalar() {
+        Value::from_scalar(entries[0], t.get_scalar_type())
+    } else {
+        Value::from_flattened_array(&entries, t.get_scalar_type())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn block_to_u64(block: &OtBlock) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&block[..8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Runs the sender side of the OT-based multiplication of `u` known to the sender by `v` known to the receiver.
+///
+/// For every OT with the random messages `r_0` and `r_1`, the sender sends `r_0 + u[i] * 2^t - r_1`,
+/// so the receiver with the choice bit `v[j]_t` obtains `r_0 + v[j]_t * u[i] * 2^t`.
+///
+/// # Returns
+///
+/// Shares of `op(u, v)` of the sender for every triple
+#[cfg(not(target_arch = "wasm32"))]
+fn multiply_as_sender(
+    sender: &mut IknpSender,
+    channel: &mut dyn Channel,
+    layouts: &[TripleLayout],
+    u: &[Vec<u64>],
+) -> Result<Vec<Vec<u64>>> {
+    let num_ots = layouts
+        .iter()
+        .map(|layout| layout.products.len() * layout.bits as usize)
+        .sum();
+    let mut keys = sender.send_random(channel, num_ots)?.into_iter();
+    let mut corrections = vec![];
+    let mut shares = vec![];
+    for (layout, u) in layouts.iter().zip(u) {
+        let width = layout.bits.div_ceil(8) as usize;
+        let mut share = vec![0u64; get_shape(&layout.types[2]).iter().product::<u64>() as usize];
+        for (i, _, k) in &layout.products {
+            for t in 0..layout.bits {
+                let (key0, key1) = keys
+                    .next()
+                    .ok_or_else(|| runtime_error!("Not enough OTs"))?;
+                let r0 = block_to_u64(&key0);
+                let correction = r0
+                    .wrapping_add(u[*i] << t)
+                    .wrapping_sub(block_to_u64(&key1));
+                corrections.extend_from_slice(&correction.to_le_bytes()[..width]);
+                share[*k] = share[*k].wrapping_sub(r0);
+            }
+        }
+        shares.push(share);
+    }
+    channel.send_message(corrections)?;
+    Ok(shares)
+}
+
+/// Runs the receiver side of the OT-based multiplication (see [multiply_as_sender]).
+///
+/// # Returns
+///
+/// Shares of `op(u, v)` of the receiver for every triple
+#[cfg(not(target_arch = "wasm32"))]
+fn multiply_as_receiver(
+    receiver: &mut IknpReceiver,
+    channel: &mut dyn Channel,
+    layouts: &[TripleLayout],
+    v: &[Vec<u64>],
+) -> Result<Vec<Vec<u64>>> {
+    let mut choices = vec![];
+    for (layout, v) in layouts.iter().zip(v) {
+        for (_, j, _) in &layout.products {
+            for t in 0..layout.bits {
+                choices.push((v[*j] >> t) & 1 == 1);
+            }
+        }
+    }
+    let keys = receiver.receive_random(channel, &choices)?;
+    let corrections = channel.receive_message()?;
+    let expected_length: usize = layouts
+        .iter()
+        .map(|layout| layout.products.len() * (layout.bits * layout.bits.div_ceil(8)) as usize)
+        .sum();
+    if corrections.len() != expected_length {
+        return Err(runtime_error!("Malformed OT-based multiplication message"));
+    }
+    let mut ots = choices.iter().zip(keys);
+    let mut offset = 0;
+    let mut shares = vec![];
+    for layout in layouts {
+        let width = layout.bits.div_ceil(8) as usize;
+        let mut share = vec![0u64; get_shape(&layout.types[2]).iter().product::<u64>() as usize];
+        for (_, _, k) in &layout.products {
+            for _ in 0..layout.bits {
+                let (choice, key) = ots.next().ok_or_else(|| runtime_error!("Not enough OTs"))?;
+                let mut received = block_to_u64(&key);
+                if *choice {
+                    let mut correction = [0u8; 8];
+                    correction[..width].copy_from_slice(&corrections[offset..offset + width]);
+                    received = received.wrapping_add(u64::from_le_bytes(correction));
+                }
+                offset += width;
+                share[*k] = share[*k].wrapping_add(received);
+            }
+        }
+        shares.push(share);
+    }
+    Ok(shares)
+}
+
+/// Generates the triples of a party for a context compiled by the two-party backend.
+///
+/// Parties 0 and 1 must call this function simultaneously with the same compiled context and connected channels.
+/// The triples are generated via IKNP OT extension without any other party.
+///
+/// # Arguments
+///
+/// * `context` - context compiled by [compile_context_with_backend](crate::mpc::mpc_compiler::compile_context_with_backend) with [TwoParty](crate::mpc::mpc_compiler::MpcBackend::TwoParty)
+/// * `party` - ID of the calling party, 0 or 1
+/// * `channel` - channel to the other party
+///
+/// # Returns
+///
+/// Value of the triple input of the calling party, i.e. the second last input of the compiled graph for party 0 and the last one for party 1
+#[cfg(not(target_arch = "wasm32"))]
+pub fn generate_triples(context: Context, party: u64, channel: &mut dyn Channel) -> Result<Value> {
+    if party >= COMPUTING_PARTIES {
+        return Err(runtime_error!(
+            "Triples are generated only by parties 0 and 1"
+        ));
+    }
+    let layouts = get_triple_layouts(&context)?;
+    let mut prng = PRNG::new(None)?;
+    let mut a = vec![];
+    let mut b = vec![];
+    for layout in &layouts {
+        let a_value = prng.get_random_value(layout.types[0].clone())?;
+        a.push(value_to_entries(&a_value, &layout.types[0])?);
+        let b_value = prng.get_random_value(layout.types[1].clone())?;
+        b.push(value_to_entries(&b_value, &layout.types[1])?);
+    }
+    // Shares of op(a_0, b_1) and op(a_1, b_0)
+    let (first_cross_terms, second_cross_terms) = if party == 0 {
+        let mut sender = IknpSender::new(channel)?;
+        let mut receiver = IknpReceiver::new(channel)?;
+        (
+            multiply_as_sender(&mut sender, channel, &layouts, &a)?,
+            multiply_as_receiver(&mut receiver, channel, &layouts, &b)?,
+        )
+    } else {
+        let mut receiver = IknpReceiver::new(channel)?;
+        let mut sender = IknpSender::new(channel)?;
+        (
+            multiply_as_receiver(&mut receiver, channel, &layouts, &b)?,
+            multiply_as_sender(&mut sender, channel, &layouts, &a)?,
+        )
+    };
+    let mut triples = vec![];
+    for (i, layout) in layouts.iter().enumerate() {
+        let cross_terms: Vec<u64> = first_cross_terms[i]
+            .iter()
+            .zip(&second_cross_terms[i])
+            .map(|(x, y)| x.wrapping_add(*y))
+            .collect();
+        triples.push(Value::from_vector(vec![
+            value_from_entries(&a[i], &layout.types[0], layout.bits)?,
+            value_from_entries(&b[i], &layout.types[1], layout.bits)?,
+            value_from_entries(&cross_terms, &layout.types[2], layout.bits)?,
+        ]));
+    }
+    Ok(Value::from_vector(triples))
+}
+
+/// Runs both parties of [generate_triples] in-process, e.g. to evaluate a compiled graph locally.
+///
+/// # Arguments
+///
+/// `context` - context compiled by [compile_context_with_backend](crate::mpc::mpc_compiler::compile_context_with_backend) with [TwoParty](crate::mpc::mpc_compiler::MpcBackend::TwoParty)
+///
+/// # Returns
+///
+/// Values of the last two inputs of the compiled graph
+#[cfg(not(target_arch = "wasm32"))]
+pub fn generate_triples_locally(context: Context) -> Result<Vec<Value>> {
+    let (mut channel0, mut channel1) = create_local_channels();
+    let context1 = context.clone();
+    let party1 = std::thread::spawn(move || generate_triples(context1, 1, &mut channel1));
+    let triples0 = generate_triples(context, 0, &mut channel0)?;
+    let triples1 = party1
+        .join()
+        .map_err(|_| runtime_error!("Triple generation of party 1 panicked"))??;
+    Ok(vec![triples0, triples1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::{run_instantiation_pass, CustomOperation};
+    use crate::data_types::{array_type, ScalarType, BIT, INT128, INT32, INT64, UINT32, UINT8};
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::inline::inline_ops::{InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{compile_context_with_backend, MpcBackend};
+    use crate::mpc::preprocessing::count_triples;
+    use crate::ops::comparisons::GreaterThan;
+
+    fn compile(
+        c: Context,
+        input_parties: Vec<IOStatus>,
+        output_parties: Vec<IOStatus>,
+    ) -> Result<Context> {
+        compile_context_with_backend(
+            c,
+            input_parties,
+            output_parties,
+            InlineConfig {
+                default_mode: InlineMode::Simple,
+                ..Default::default()
+            },
+            || SimpleEvaluator::new(None),
+            MpcBackend::TwoParty,
+        )
+    }
+
+    // Evaluates a compiled graph with triples generated by both parties
+    fn evaluate(compiled: &Context, mut inputs: Vec<Value>) -> Result<Value> {
+        inputs.extend(generate_triples_locally(compiled.clone())?);
+        random_evaluate(compiled.get_main_graph()?, inputs)
+    }
+
+    fn count_sends(c: &Context, sender: u64) -> Result<usize> {
+        let mut count = 0;
+        for node in c.get_main_graph()?.get_nodes() {
+            for annotation in node.get_annotations()? {
+                if let NodeAnnotation::Send(s, _) = annotation {
+                    if s == sender {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(array_type(vec![2, 2], INT32))?;
+            let y = g.input(array_type(vec![2, 2], INT32))?;
+            let z = g.input(scalar_type(INT32))?;
+            let prod = x.multiply(y.clone())?.add(x.matmul(y)?)?;
+            prod.subtract(z)?.sum(vec![0])?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let inputs = vec![
+                Value::from_flattened_array(&[1, 2, 3, 4], INT32)?,
+                Value::from_flattened_array(&[-5, 6, 7, 8], INT32)?,
+                Value::from_scalar(10, INT32)?,
+            ];
+            let expected = random_evaluate(g, inputs.clone())?;
+
+            for output_parties in [
+                vec![IOStatus::Party(1)],
+                vec![IOStatus::Party(0), IOStatus::Party(1)],
+            ] {
+                let compiled = compile(
+                    c.clone(),
+                    vec![IOStatus::Party(0), IOStatus::Party(1), IOStatus::Public],
+                    output_parties,
+                )?;
+                let result = evaluate(&compiled, inputs.clone())?;
+                assert_eq!(result, expected);
+                // Two multiplications of private values consume two triples generated without party 2
+                let counts = count_triples(compiled.clone())?;
+                assert_eq!(counts.iter().map(|c| c.count).sum::<u64>(), 2);
+                assert_eq!(count_sends(&compiled, 2)?, 0);
+            }
+
+            // Shared output
+            let compiled = compile(
+                c,
+                vec![IOStatus::Party(0), IOStatus::Shared, IOStatus::Public],
+                vec![],
+            )?;
+            let y_shares = Value::from_vector(vec![
+                Value::from_flattened_array(&[-6, 6, 7, 8], INT32)?,
+                Value::from_flattened_array(&[1, 0, 0, 0], INT32)?,
+            ]);
+            let result = evaluate(
+                &compiled,
+                vec![inputs[0].clone(), y_shares, inputs[2].clone()],
+            )?
+            .to_vector()?;
+            let t = array_type(vec![2], INT32);
+            let revealed: Vec<i32> = result[0]
+                .to_flattened_array_i32(t.clone())?
+                .iter()
+                .zip(result[1].to_flattened_array_i32(t.clone())?)
+                .map(|(a, b)| a.wrapping_add(b))
+                .collect();
+            assert_eq!(revealed, expected.to_flattened_array_i32(t)?);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_boolean() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(array_type(vec![3, 64], BIT))?;
+            let y = g.input(array_type(vec![3, 64], BIT))?;
+            g.custom_op(
+                CustomOperation::new(GreaterThan {
+                    signed_comparison: false,
+                }),
+                vec![x, y],
+            )?
+            .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            // Little-endian binary representation of 64-bit integers
+            let to_bits = |v: &[u64]| -> Result<Value> {
+                let bits: Vec<u64> = v
+                    .iter()
+                    .flat_map(|x| (0..64).map(move |j| (x >> j) & 1))
+                    .collect();
+                Value::from_flattened_array(&bits, BIT)
+            };
+            let inputs = vec![to_bits(&[5, 7, 1 << 40])?, to_bits(&[3, 7, 1 << 41])?];
+            let instantiated = run_instantiation_pass(c.clone())?.get_context();
+            let expected = random_evaluate(instantiated.get_main_graph()?, inputs.clone())?;
+            let compiled = compile(
+                c,
+                vec![IOStatus::Party(0), IOStatus::Party(1)],
+                vec![IOStatus::Party(0)],
+            )?;
+            let result = evaluate(&compiled, inputs)?;
+            assert_eq!(result, expected);
+            assert_eq!(
+                result.to_flattened_array_u8(array_type(vec![3], BIT))?,
+                vec![1, 0, 0]
+            );
+            assert!(!count_triples(compiled.clone())?.is_empty());
+            assert_eq!(count_sends(&compiled, 2)?, 0);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_products() {
+        || -> Result<()> {
+            let test_product = |op: Operation, x_t: Type, y_t: Type| -> Result<()> {
+                let c = create_context()?;
+                let g = c.create_graph()?;
+                let x = g.input(x_t.clone())?;
+                let y = g.input(y_t.clone())?;
+                g.add_node(vec![x, y], vec![], op)?.set_as_output()?;
+                g.finalize()?.set_as_main()?;
+                c.finalize()?;
+                let mut prng = PRNG::new(None)?;
+                let inputs = vec![prng.get_random_value(x_t)?, prng.get_random_value(y_t)?];
+                let expected = random_evaluate(g, inputs.clone())?;
+                let compiled = compile(
+                    c,
+                    vec![IOStatus::Party(0), IOStatus::Party(1)],
+                    vec![IOStatus::Party(1)],
+                )?;
+                assert_eq!(evaluate(&compiled, inputs)?, expected);
+                Ok(())
+            };
+            let array = |shape: Vec<u64>| array_type(shape, INT32);
+            test_product(Operation::Multiply, array(vec![2, 3]), array(vec![3]))?;
+            test_product(
+                Operation::Multiply,
+                scalar_type(UINT8),
+                array_type(vec![2], UINT8),
+            )?;
+            test_product(
+                Operation::Multiply,
+                array_type(vec![5], BIT),
+                array_type(vec![5], BIT),
+            )?;
+            test_product(Operation::Dot, array(vec![4]), array(vec![4]))?;
+            test_product(Operation::Dot, array(vec![2, 3]), array(vec![3]))?;
+            test_product(Operation::Dot, array(vec![2, 3]), array(vec![3, 2]))?;
+            test_product(Operation::Dot, array(vec![2, 2, 3]), array(vec![2, 3, 2]))?;
+            test_product(Operation::Dot, scalar_type(INT32), array(vec![3]))?;
+            test_product(Operation::Matmul, array(vec![3]), array(vec![3, 2]))?;
+            test_product(Operation::Matmul, array(vec![2, 3]), array(vec![3]))?;
+            test_product(Operation::Matmul, array(vec![2, 2, 3]), array(vec![3, 2]))?;
+            test_product(
+                Operation::Matmul,
+                array_type(vec![1, 2, 3], INT64),
+                array_type(vec![2, 3, 2], INT64),
+            )?;
+            test_product(
+                Operation::Gemm(true, false),
+                array(vec![3, 2]),
+                array(vec![3, 4]),
+            )?;
+            test_product(
+                Operation::Gemm(false, true),
+                array(vec![2, 3]),
+                array(vec![4, 3]),
+            )?;
+            test_product(
+                Operation::Gemm(true, true),
+                array(vec![2, 3, 2]),
+                array(vec![4, 3]),
+            )?;
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_truncate() {
+        || -> Result<()> {
+            let test_truncate = |st: ScalarType, values: &[i64], scale: u64| -> Result<()> {
+                let c = create_context()?;
+                let g = c.create_graph()?;
+                let t = array_type(vec![values.len() as u64], st.clone());
+                let x = g.input(t.clone())?;
+                let y = g.input(t.clone())?;
+                // Fixed-point multiplication
+                x.multiply(y)?.truncate(scale)?.set_as_output()?;
+                g.finalize()?.set_as_main()?;
+                c.finalize()?;
+                let ones = vec![1; values.len()];
+                let inputs = vec![
+                    Value::from_flattened_array(values, st.clone())?,
+                    Value::from_flattened_array(&ones, st.clone())?,
+                ];
+                let expected =
+                    random_evaluate(g, inputs.clone())?.to_flattened_array_i64(t.clone())?;
+                let compiled = compile(
+                    c,
+                    vec![IOStatus::Party(0), IOStatus::Shared],
+                    vec![IOStatus::Party(0)],
+                )?;
+                let y_shares = Value::from_vector(vec![
+                    Value::from_flattened_array(&ones, st)?,
+                    Value::zero_of_type(t.clone()),
+                ]);
+                for _ in 0..10 {
+                    let result = evaluate(&compiled, vec![inputs[0].clone(), y_shares.clone()])?
+                        .to_flattened_array_i64(t.clone())?;
+                    for (r, e) in result.iter().zip(&expected) {
+                        assert!((r - e).abs() <= 1, "{} != {}", r, e);
+                    }
+                }
+                assert_eq!(count_sends(&compiled, 2)?, 0);
+                Ok(())
+            };
+            test_truncate(INT32, &[-1000, -17, 0, 5, 123456], 16)?;
+            test_truncate(INT64, &[-1 << 40, -3, 7, 1 << 50], 1 << 20)?;
+            test_truncate(UINT32, &[0, 100, 12345], 10)?;
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_generate_triples() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(array_type(vec![3], INT32))?;
+            x.multiply(x.clone())?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let compiled = compile(c, vec![IOStatus::Party(0)], vec![IOStatus::Party(0)])?;
+            let triples = generate_triples_locally(compiled.clone())?;
+            let t = array_type(vec![3], INT32);
+            let mut shares = vec![];
+            for triple in triples {
+                let triple = triple.to_vector()?[0].to_vector()?;
+                let mut entries = vec![];
+                for value in triple {
+                    entries.push(value.to_flattened_array_u64(t.clone())?);
+                }
+                shares.push(entries);
+            }
+            // c = a * b for a = a_0 + a_1, b = b_0 + b_1 and c = a_0 * b_0 + a_1 * b_1 + d_0 + d_1
+            let entry = |party: usize, j: usize, i: usize| shares[party][j][i] as u32;
+            for i in 0..3 {
+                let a = entry(0, 0, i).wrapping_add(entry(1, 0, i));
+                let b = entry(0, 1, i).wrapping_add(entry(1, 1, i));
+                let c = entry(0, 0, i)
+                    .wrapping_mul(entry(0, 1, i))
+                    .wrapping_add(entry(1, 0, i).wrapping_mul(entry(1, 1, i)))
+                    .wrapping_add(entry(0, 2, i))
+                    .wrapping_add(entry(1, 2, i));
+                assert_eq!(c, a.wrapping_mul(b));
+            }
+            let (mut channel, _) = create_local_channels();
+            assert!(generate_triples(compiled, 2, &mut channel).is_err());
+            let uncompiled = create_context()?;
+            let g = uncompiled.create_graph()?;
+            g.input(scalar_type(INT32))?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            uncompiled.finalize()?;
+            assert!(generate_triples(uncompiled, 0, &mut channel).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_unsupported() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(scalar_type(INT32))?;
+            x.a2b()?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            assert!(compile(
+                c.clone(),
+                vec![IOStatus::Party(0)],
+                vec![IOStatus::Party(0)]
+            )
+            .is_err());
+            assert!(compile(
+                c.clone(),
+                vec![IOStatus::Party(2)],
+                vec![IOStatus::Party(0)]
+            )
+            .is_err());
+            assert!(compile(c, vec![IOStatus::Public], vec![IOStatus::Party(2)]).is_err());
+
+            // Triples are generated only for moduli of at most 64 bits
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(scalar_type(INT128))?;
+            x.multiply(x.clone())?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            assert!(compile(c, vec![IOStatus::Party(0)], vec![IOStatus::Party(0)]).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
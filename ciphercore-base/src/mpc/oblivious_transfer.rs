@@ -0,0 +1,621 @@
+//! 1-out-of-2 oblivious transfer (OT) between two parties.
+//!
+//! In an OT, the sender holds a pair of messages `(x_0, x_1)` and the receiver holds a choice bit `c`.
+//! The receiver learns `x_c`, while the sender learns nothing about `c` and the receiver learns nothing about `x_{1-c}`.
+//! OT is the main building block of two-party protocols without a dealer, e.g. the generation of multiplication triples or
+//! the two-party variants of the Switching and Duplication protocols.
+//!
+//! - [BaseOtSender] and [BaseOtReceiver] implement the [simplest OT](https://eprint.iacr.org/2015/267.pdf) of Chou and Orlandi over the NIST P-256 curve.
+//!   Every base OT takes several elliptic curve operations, so base OTs are mostly used to bootstrap OT extension.
+//! - [IknpSender] and [IknpReceiver] implement the OT extension of [IKNP03](https://www.iacr.org/archive/crypto2003/27290145/27290145.pdf).
+//!   It turns [BASE_OT_COUNT] base OTs with swapped roles into any number of OTs using only the AES-based [PRNG] and SHA-256 as a correlation-robust hash function.
+//!
+//! All the protocols are secure against semi-honest adversaries.
+//! They communicate via the [Channel] trait, so evaluators can plug in any network transport; [create_local_channels] returns a pair of connected in-process channels.
+//!
+//! # Example
+//!
+//! ```
+//! # use ciphercore_base::mpc::oblivious_transfer::{create_local_channels, IknpReceiver, IknpSender, OtReceiver, OtSender};
+//! let (mut sender_channel, mut receiver_channel) = create_local_channels();
+//! let sender_thread = std::thread::spawn(move || {
+//!     let mut sender = IknpSender::new(&mut sender_channel).unwrap();
+//!     let messages = vec![(b"zero".to_vec(), b"one!".to_vec()); 3];
+//!     sender.send(&mut sender_channel, &messages).unwrap();
+//! });
+//! let mut receiver = IknpReceiver::new(&mut receiver_channel).unwrap();
+//! let received = receiver
+//!     .receive(&mut receiver_channel, &[false, true, true])
+//!     .unwrap();
+//! sender_thread.join().unwrap();
+//! assert_eq!(received, vec![b"zero".to_vec(), b"one!".to_vec(), b"one!".to_vec()]);
+//! ```
+use crate::errors::{CiphercoreBaseError, Result};
+use crate::random::{PRNG, SEED_SIZE};
+
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcPoint, PointConversionForm};
+use openssl::error::ErrorStack;
+use openssl::nid::Nid;
+use sha2::{Digest, Sha256};
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Byte size of the messages of random OTs.
+pub const OT_BLOCK_SIZE: usize = SEED_SIZE;
+
+/// Message of a random OT.
+pub type OtBlock = [u8; OT_BLOCK_SIZE];
+
+/// Number of base OTs used by OT extension, i.e. its computational security parameter.
+pub const BASE_OT_COUNT: usize = 8 * OT_BLOCK_SIZE;
+
+const BASE_OT_DOMAIN: &[u8] = b"ciphercore base ot";
+const IKNP_DOMAIN: &[u8] = b"ciphercore iknp ot extension";
+
+/// Byte size of a compressed P-256 point
+const POINT_SIZE: usize = 33;
+
+/// Reliable ordered channel between the sender and the receiver of OT.
+///
+/// Implementations can use any transport (e.g. TCP or gRPC), as long as messages are delivered in the order they were sent.
+pub trait Channel {
+    /// Sends a message to the other party.
+    fn send_message(&mut self, message: Vec<u8>) -> Result<()>;
+
+    /// Waits for the next message of the other party.
+    fn receive_message(&mut self) -> Result<Vec<u8>>;
+}
+
+/// In-process channel created by [create_local_channels].
+pub struct LocalChannel {
+    sender: Sender<Vec<u8>>,
+    receiver: Receiver<Vec<u8>>,
+}
+
+impl Channel for LocalChannel {
+    fn send_message(&mut self, message: Vec<u8>) -> Result<()> {
+        self.sender
+            .send(message)
+            .map_err(|_| runtime_error!("Channel is closed"))
+    }
+
+    fn receive_message(&mut self) -> Result<Vec<u8>> {
+        self.receiver
+            .recv()
+            .map_err(|_| runtime_error!("Channel is closed"))
+    }
+}
+
+/// Returns two connected in-process channels, e.g. to run both parties of OT in different threads.
+pub fn create_local_channels() -> (LocalChannel, LocalChannel) {
+    let (sender0, receiver1) = channel();
+    let (sender1, receiver0) = channel();
+    (
+        LocalChannel {
+            sender: sender0,
+            receiver: receiver0,
+        },
+        LocalChannel {
+            sender: sender1,
+            receiver: receiver1,
+        },
+    )
+}
+
+/// Sender of 1-out-of-2 OT.
+///
+/// Calls of the sender must match the calls of the receiver on the other end of the channel.
+pub trait OtSender {
+    /// Runs `n` random OTs.
+    ///
+    /// # Returns
+    ///
+    /// Pairs of random messages, one of which is learned by the receiver
+    fn send_random(
+        &mut self,
+        channel: &mut dyn Channel,
+        n: usize,
+    ) -> Result<Vec<(OtBlock, OtBlock)>>;
+
+    /// Runs OTs of given pairs of messages.
+    ///
+    /// All the messages must have the same length.
+    /// Messages are encrypted by the messages of random OTs expanded with [PRNG].
+    fn send(&mut self, channel: &mut dyn Channel, messages: &[(Vec<u8>, Vec<u8>)]) -> Result<()> {
+        let message_length = messages.first().map(|m| m.0.len()).unwrap_or(0);
+        if messages
+            .iter()
+            .any(|(m0, m1)| m0.len() != message_length || m1.len() != message_length)
+        {
+            return Err(runtime_error!("All OT messages must have the same length"));
+        }
+        let keys = self.send_random(channel, messages.len())?;
+        let mut ciphertexts = vec![];
+        for ((m0, m1), (k0, k1)) in messages.iter().zip(keys) {
+            ciphertexts.extend(encrypt(m0, k0)?);
+            ciphertexts.extend(encrypt(m1, k1)?);
+        }
+        channel.send_message(ciphertexts)
+    }
+}
+
+/// Receiver of 1-out-of-2 OT.
+///
+/// Calls of the receiver must match the calls of the sender on the other end of the channel.
+pub trait OtReceiver {
+    /// Runs random OTs with given choice bits.
+    ///
+    /// # Returns
+    ///
+    /// Messages of the sender selected by the choice bits
+    fn receive_random(
+        &mut self,
+        channel: &mut dyn Channel,
+        choices: &[bool],
+    ) -> Result<Vec<OtBlock>>;
+
+    /// Runs OTs of messages sent by [OtSender::send] with given choice bits.
+    ///
+    /// # Returns
+    ///
+    /// Messages of the sender selected by the choice bits
+    fn receive(&mut self, channel: &mut dyn Channel, choices: &[bool]) -> Result<Vec<Vec<u8>>> {
+        let keys = self.receive_random(channel, choices)?;
+        let ciphertexts = channel.receive_message()?;
+        if choices.is_empty() {
+            return Ok(vec![]);
+        }
+        if ciphertexts.len() % (2 * choices.len()) != 0 {
+            return Err(runtime_error!("Malformed OT ciphertexts"));
+        }
+        let message_length = ciphertexts.len() / (2 * choices.len());
+        let mut messages = vec![];
+        for (i, (choice, key)) in choices.iter().zip(keys).enumerate() {
+            let offset = (2 * i + *choice as usize) * message_length;
+            messages.push(encrypt(&ciphertexts[offset..offset + message_length], key)?);
+        }
+        Ok(messages)
+    }
+}
+
+// Encrypts or decrypts a message with a key expanded by PRNG
+fn encrypt(message: &[u8], key: OtBlock) -> Result<Vec<u8>> {
+    let pad = PRNG::new(Some(key))?.get_random_bytes(message.len())?;
+    Ok(message.iter().zip(pad).map(|(m, p)| m ^ p).collect())
+}
+
+fn get_bit(bytes: &[u8], index: usize) -> bool {
+    (bytes[index / 8] >> (index % 8)) & 1 == 1
+}
+
+fn pack_bits(bits: &[bool]) -> Vec<u8> {
+    let mut bytes = vec![0u8; bits.len().div_ceil(8)];
+    for (i, bit) in bits.iter().enumerate() {
+        bytes[i / 8] |= (*bit as u8) << (i % 8);
+    }
+    bytes
+}
+
+fn xor_in_place(a: &mut [u8], b: &[u8]) {
+    for (x, y) in a.iter_mut().zip(b) {
+        *x ^= y;
+    }
+}
+
+fn hash_to_block(domain: &[u8], index: u64, data: &[&[u8]]) -> OtBlock {
+    let mut hasher = Sha256::new();
+    hasher.update(domain);
+    hasher.update(index.to_le_bytes());
+    for bytes in data {
+        hasher.update(bytes);
+    }
+    let mut block = [0u8; OT_BLOCK_SIZE];
+    block.copy_from_slice(&hasher.finalize()[..OT_BLOCK_SIZE]);
+    block
+}
+
+fn ec_error(e: ErrorStack) -> CiphercoreBaseError {
+    runtime_error!("Elliptic curve operation failed: {}", e)
+}
+
+// Group of the base OT, its order and a context for computations
+fn get_curve() -> Result<(EcGroup, BigNum, BigNumContext)> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).map_err(ec_error)?;
+    let mut context = BigNumContext::new().map_err(ec_error)?;
+    let mut order = BigNum::new().map_err(ec_error)?;
+    group.order(&mut order, &mut context).map_err(ec_error)?;
+    Ok((group, order, context))
+}
+
+fn random_scalar(order: &BigNum) -> Result<BigNum> {
+    let mut scalar = BigNum::new().map_err(ec_error)?;
+    order.rand_range(&mut scalar).map_err(ec_error)?;
+    Ok(scalar)
+}
+
+fn point_to_bytes(
+    group: &EcGroup,
+    point: &EcPoint,
+    context: &mut BigNumContext,
+) -> Result<Vec<u8>> {
+    point
+        .to_bytes(group, PointConversionForm::COMPRESSED, context)
+        .map_err(ec_error)
+}
+
+fn point_from_bytes(group: &EcGroup, bytes: &[u8], context: &mut BigNumContext) -> Result<EcPoint> {
+    EcPoint::from_bytes(group, bytes, context)
+        .map_err(|e| runtime_error!("Invalid elliptic curve point: {}", e))
+}
+
+/// Sender of the base OT of Chou and Orlandi.
+///
+/// The sender samples `a` and sends `A = aG`.
+/// The receiver with a choice bit `c` samples `b` and sends `B = bG + cA`.
+/// Then, the messages of the sender are `H(A, B, aB)` and `H(A, B, a(B - A))`, and the receiver computes `H(A, B, bA)`.
+#[derive(Default)]
+pub struct BaseOtSender {}
+
+impl OtSender for BaseOtSender {
+    fn send_random(
+        &mut self,
+        channel: &mut dyn Channel,
+        n: usize,
+    ) -> Result<Vec<(OtBlock, OtBlock)>> {
+        let (group, order, mut context) = get_curve()?;
+        let a = random_scalar(&order)?;
+        let mut point_a = EcPoint::new(&group).map_err(ec_error)?;
+        point_a
+            .mul_generator2(&group, &a, &mut context)
+            .map_err(ec_error)?;
+        let bytes_a = point_to_bytes(&group, &point_a, &mut context)?;
+        channel.send_message(bytes_a.clone())?;
+
+        let received = channel.receive_message()?;
+        if received.len() != n * POINT_SIZE {
+            return Err(runtime_error!("Malformed base OT message"));
+        }
+        // -aA
+        let mut minus_aa = EcPoint::new(&group).map_err(ec_error)?;
+        minus_aa
+            .mul2(&group, &point_a, &a, &mut context)
+            .map_err(ec_error)?;
+        minus_aa.invert2(&group, &mut context).map_err(ec_error)?;
+        let mut keys = vec![];
+        for (i, bytes_b) in received.chunks(POINT_SIZE).enumerate() {
+            let point_b = point_from_bytes(&group, bytes_b, &mut context)?;
+            let mut ab = EcPoint::new(&group).map_err(ec_error)?;
+            ab.mul2(&group, &point_b, &a, &mut context)
+                .map_err(ec_error)?;
+            let mut ab_minus_aa = EcPoint::new(&group).map_err(ec_error)?;
+            ab_minus_aa
+                .add(&group, &ab, &minus_aa, &mut context)
+                .map_err(ec_error)?;
+            let bytes_ab = point_to_bytes(&group, &ab, &mut context)?;
+            let bytes_ab_minus_aa = point_to_bytes(&group, &ab_minus_aa, &mut context)?;
+            keys.push((
+                hash_to_block(BASE_OT_DOMAIN, i as u64, &[&bytes_a, bytes_b, &bytes_ab]),
+                hash_to_block(
+                    BASE_OT_DOMAIN,
+                    i as u64,
+                    &[&bytes_a, bytes_b, &bytes_ab_minus_aa],
+                ),
+            ));
+        }
+        Ok(keys)
+    }
+}
+
+/// Receiver of the base OT of Chou and Orlandi (see [BaseOtSender]).
+#[derive(Default)]
+pub struct BaseOtReceiver {}
+
+impl OtReceiver for BaseOtReceiver {
+    fn receive_random(
+        &mut self,
+        channel: &mut dyn Channel,
+        choices: &[bool],
+    ) -> Result<Vec<OtBlock>> {
+        let (group, order, mut context) = get_curve()?;
+        let bytes_a = channel.receive_message()?;
+        let point_a = point_from_bytes(&group, &bytes_a, &mut context)?;
+        let mut message = vec![];
+        let mut keys = vec![];
+        for (i, choice) in choices.iter().enumerate() {
+            let b = random_scalar(&order)?;
+            let mut bg = EcPoint::new(&group).map_err(ec_error)?;
+            bg.mul_generator2(&group, &b, &mut context)
+                .map_err(ec_error)?;
+            let mut bg_plus_a = EcPoint::new(&group).map_err(ec_error)?;
+            bg_plus_a
+                .add(&group, &bg, &point_a, &mut context)
+                .map_err(ec_error)?;
+            // Both points are computed, so that the choice bit affects only the selection of bytes
+            let bytes_bg = point_to_bytes(&group, &bg, &mut context)?;
+            let bytes_bg_plus_a = point_to_bytes(&group, &bg_plus_a, &mut context)?;
+            let mask = 0u8.wrapping_sub(*choice as u8);
+            let bytes_b: Vec<u8> = bytes_bg
+                .iter()
+                .zip(&bytes_bg_plus_a)
+                .map(|(x, y)| x ^ (mask & (x ^ y)))
+                .collect();
+            let mut ba = EcPoint::new(&group).map_err(ec_error)?;
+            ba.mul2(&group, &point_a, &b, &mut context)
+                .map_err(ec_error)?;
+            let bytes_ba = point_to_bytes(&group, &ba, &mut context)?;
+            keys.push(hash_to_block(
+                BASE_OT_DOMAIN,
+                i as u64,
+                &[&bytes_a, &bytes_b, &bytes_ba],
+            ));
+            message.extend(bytes_b);
+        }
+        channel.send_message(message)?;
+        Ok(keys)
+    }
+}
+
+// Transposes a matrix of BASE_OT_COUNT bit columns of length n into n rows of BASE_OT_COUNT bits
+fn transpose(columns: &[Vec<u8>], n: usize) -> Vec<OtBlock> {
+    let mut rows = vec![[0u8; OT_BLOCK_SIZE]; n];
+    for (j, column) in columns.iter().enumerate() {
+        for (i, row) in rows.iter_mut().enumerate() {
+            row[j / 8] |= (get_bit(column, i) as u8) << (j % 8);
+        }
+    }
+    rows
+}
+
+/// Sender of the IKNP OT extension.
+///
+/// The extension sender acts as the receiver of [BASE_OT_COUNT] base OTs with a random choice vector `s`, learning seeds `k_j^{s_j}`.
+/// For `n` OTs, the extension receiver with choice bits `r` sends columns `u_j = G(k_j^0) + G(k_j^1) + r` of a matrix `U`, where `G` is [PRNG].
+/// The rows of the matrix `Q` with columns `q_j = G(k_j^{s_j}) + s_j * u_j` satisfy `q_i = t_i + r_i * s`, where `t_i` are the rows of the matrix with columns `G(k_j^0)`.
+/// Then, the messages of the i-th OT are `H(i, q_i)` and `H(i, q_i + s)` and the receiver computes `H(i, t_i)`.
+///
+/// Seeds are expanded further in subsequent calls, so base OTs are run only once.
+pub struct IknpSender {
+    base_choices: OtBlock,
+    prngs: Vec<PRNG>,
+    num_ots: u64,
+}
+
+impl IknpSender {
+    /// Runs base OTs with the [IknpReceiver] on the other end of the channel.
+    pub fn new(channel: &mut dyn Channel) -> Result<Self> {
+        let mut base_choices = [0u8; OT_BLOCK_SIZE];
+        base_choices.copy_from_slice(&PRNG::new(None)?.get_random_bytes(OT_BLOCK_SIZE)?);
+        let choices: Vec<bool> = (0..BASE_OT_COUNT)
+            .map(|j| get_bit(&base_choices, j))
+            .collect();
+        let seeds = BaseOtReceiver::default().receive_random(channel, &choices)?;
+        let prngs = seeds
+            .into_iter()
+            .map(|seed| PRNG::new(Some(seed)))
+            .collect::<Result<Vec<PRNG>>>()?;
+        Ok(IknpSender {
+            base_choices,
+            prngs,
+            num_ots: 0,
+        })
+    }
+}
+
+impl OtSender for IknpSender {
+    fn send_random(
+        &mut self,
+        channel: &mut dyn Channel,
+        n: usize,
+    ) -> Result<Vec<(OtBlock, OtBlock)>> {
+        let column_size = n.div_ceil(8);
+        let matrix_u = channel.receive_message()?;
+        if matrix_u.len() != BASE_OT_COUNT * column_size {
+            return Err(runtime_error!("Malformed OT extension message"));
+        }
+        let mut columns = vec![];
+        for (j, (prng, column_u)) in self
+            .prngs
+            .iter_mut()
+            .zip(matrix_u.chunks(column_size.max(1)))
+            .enumerate()
+        {
+            let mut column = prng.get_random_bytes(column_size)?;
+            let mask = 0u8.wrapping_sub(get_bit(&self.base_choices, j) as u8);
+            for (q, u) in column.iter_mut().zip(column_u) {
+                *q ^= mask & u;
+            }
+            columns.push(column);
+        }
+        let mut keys = vec![];
+        for (i, row) in transpose(&columns, n).into_iter().enumerate() {
+            let mut shifted_row = row;
+            xor_in_place(&mut shifted_row, &self.base_choices);
+            let index = self.num_ots + i as u64;
+            keys.push((
+                hash_to_block(IKNP_DOMAIN, index, &[&row]),
+                hash_to_block(IKNP_DOMAIN, index, &[&shifted_row]),
+            ));
+        }
+        self.num_ots += n as u64;
+        Ok(keys)
+    }
+}
+
+/// Receiver of the IKNP OT extension (see [IknpSender]).
+pub struct IknpReceiver {
+    prngs: Vec<(PRNG, PRNG)>,
+    num_ots: u64,
+}
+
+impl IknpReceiver {
+    /// Runs base OTs with the [IknpSender] on the other end of the channel.
+    pub fn new(channel: &mut dyn Channel) -> Result<Self> {
+        let seeds = BaseOtSender::default().send_random(channel, BASE_OT_COUNT)?;
+        let prngs = seeds
+            .into_iter()
+            .map(|(seed0, seed1)| Ok((PRNG::new(Some(seed0))?, PRNG::new(Some(seed1))?)))
+            .collect::<Result<Vec<(PRNG, PRNG)>>>()?;
+        Ok(IknpReceiver { prngs, num_ots: 0 })
+    }
+}
+
+impl OtReceiver for IknpReceiver {
+    fn receive_random(
+        &mut self,
+        channel: &mut dyn Channel,
+        choices: &[bool],
+    ) -> Result<Vec<OtBlock>> {
+        let n = choices.len();
+        let column_size = n.div_ceil(8);
+        let packed_choices = pack_bits(choices);
+        let mut columns = vec![];
+        let mut matrix_u = vec![];
+        for (prng0, prng1) in self.prngs.iter_mut() {
+            let column = prng0.get_random_bytes(column_size)?;
+            let mut column_u = prng1.get_random_bytes(column_size)?;
+            xor_in_place(&mut column_u, &column);
+            xor_in_place(&mut column_u, &packed_choices);
+            matrix_u.extend(column_u);
+            columns.push(column);
+        }
+        channel.send_message(matrix_u)?;
+        let keys = transpose(&columns, n)
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| hash_to_block(IKNP_DOMAIN, self.num_ots + i as u64, &[&row]))
+            .collect();
+        self.num_ots += n as u64;
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Random OT keys received in every batch and the messages received by the chosen-message OT
+    type ReceivedOts = (Vec<Vec<OtBlock>>, Vec<Vec<u8>>);
+
+    fn get_random_choices(n: usize) -> Result<Vec<bool>> {
+        let bytes = PRNG::new(None)?.get_random_bytes(n.div_ceil(8))?;
+        Ok((0..n).map(|i| get_bit(&bytes, i)).collect())
+    }
+
+    fn check_random_ots(sent: &[(OtBlock, OtBlock)], received: &[OtBlock], choices: &[bool]) {
+        assert_eq!(sent.len(), choices.len());
+        assert_eq!(received.len(), choices.len());
+        for ((keys, received_key), choice) in sent.iter().zip(received).zip(choices) {
+            let (chosen_key, other_key) = if *choice {
+                (keys.1, keys.0)
+            } else {
+                (keys.0, keys.1)
+            };
+            assert_eq!(chosen_key, *received_key);
+            assert_ne!(other_key, *received_key);
+        }
+    }
+
+    #[test]
+    fn test_base_ot() {
+        || -> Result<()> {
+            let (mut sender_channel, mut receiver_channel) = create_local_channels();
+            let choices = get_random_choices(20)?;
+            let (sent, received) = std::thread::scope(|s| {
+                let sender = s.spawn(|| {
+                    BaseOtSender::default().send_random(&mut sender_channel, choices.len())
+                });
+                let received =
+                    BaseOtReceiver::default().receive_random(&mut receiver_channel, &choices);
+                (sender.join().unwrap(), received)
+            });
+            check_random_ots(&sent?, &received?, &choices);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_iknp() {
+        || -> Result<()> {
+            let (mut sender_channel, mut receiver_channel) = create_local_channels();
+            let batch_sizes = [1003, 0, 64];
+            let choices = batch_sizes
+                .iter()
+                .map(|n| get_random_choices(*n))
+                .collect::<Result<Vec<Vec<bool>>>>()?;
+            let messages: Vec<(Vec<u8>, Vec<u8>)> = (0..100u8)
+                .map(|i| (vec![i; 20], vec![255 - i; 20]))
+                .collect();
+            let message_choices = get_random_choices(messages.len())?;
+            let (sent, received) = std::thread::scope(|s| {
+                let sender = s.spawn(|| -> Result<Vec<Vec<(OtBlock, OtBlock)>>> {
+                    let mut sender = IknpSender::new(&mut sender_channel)?;
+                    let mut sent = vec![];
+                    for n in batch_sizes {
+                        sent.push(sender.send_random(&mut sender_channel, n)?);
+                    }
+                    sender.send(&mut sender_channel, &messages)?;
+                    Ok(sent)
+                });
+                let mut receiver = || -> Result<ReceivedOts> {
+                    let mut receiver = IknpReceiver::new(&mut receiver_channel)?;
+                    let mut received = vec![];
+                    for batch_choices in &choices {
+                        received
+                            .push(receiver.receive_random(&mut receiver_channel, batch_choices)?);
+                    }
+                    let received_messages =
+                        receiver.receive(&mut receiver_channel, &message_choices)?;
+                    Ok((received, received_messages))
+                };
+                let received = receiver();
+                (sender.join().unwrap(), received)
+            });
+            let sent = sent?;
+            let (received, received_messages) = received?;
+            for ((batch_sent, batch_received), batch_choices) in
+                sent.iter().zip(&received).zip(&choices)
+            {
+                check_random_ots(batch_sent, batch_received, batch_choices);
+            }
+            // OTs of different batches are independent
+            assert_ne!(sent[0][0], sent[2][0]);
+            for ((message, choice), received_message) in messages
+                .iter()
+                .zip(&message_choices)
+                .zip(&received_messages)
+            {
+                let expected = if *choice { &message.1 } else { &message.0 };
+                assert_eq!(expected, received_message);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_ot() {
+        let (mut sender_channel, mut receiver_channel) = create_local_channels();
+        let messages = vec![(vec![0u8; 4], vec![1u8; 5])];
+        assert!(BaseOtSender::default()
+            .send(&mut sender_channel, &messages)
+            .is_err());
+        // Wrong number of points
+        receiver_channel
+            .send_message(vec![2u8; POINT_SIZE])
+            .unwrap();
+        assert!(BaseOtSender::default()
+            .send_random(&mut sender_channel, 2)
+            .is_err());
+        // Skip the point sent by the sender
+        receiver_channel.receive_message().unwrap();
+        // Invalid point
+        sender_channel.send_message(vec![7u8; POINT_SIZE]).unwrap();
+        assert!(BaseOtReceiver::default()
+            .receive_random(&mut receiver_channel, &[true])
+            .is_err());
+        drop(receiver_channel);
+        assert!(sender_channel.receive_message().is_err());
+    }
+}
@@ -0,0 +1,353 @@
+//! Policies defining which parties obtain the outputs of a compiled computation.
+use crate::custom_ops::ContextMappings;
+use crate::data_types::{array_type, Type, BIT};
+use crate::errors::Result;
+use crate::graphs::{copy_node_name, copy_node_provenance, create_context, Context, Graph, Node};
+use crate::mpc::mpc_compiler::{reveal_output, IOStatus, PARTIES};
+use crate::ops::utils::{constant_scalar, single_bit_to_arithmetic};
+
+/// Maximal number of random bits summed up to obtain the noise of [OutputPolicy::RevealWithNoise].
+pub const MAX_NOISE_BITS: u64 = 1024;
+
+/// Output policy of a compiled computation, which is a finer-grained alternative to a flat vector of output [statuses](IOStatus).
+///
+/// A policy is enforced by [compile_context_with_output_policy](crate::mpc::mpc_compiler::compile_context_with_output_policy)
+/// and verified by [verify_privacy_with_output_policy](crate::mpc::mpc_equivalence_class::verify_privacy_with_output_policy).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum OutputPolicy {
+    /// The output stays secret-shared among the parties.
+    Shared,
+    /// The output is revealed to a non-empty subset of parties with given ids.
+    RevealTo(Vec<u64>),
+    /// Integer output is revealed to a non-empty subset of parties after adding noise for differential privacy.
+    ///
+    /// The noise of every entry is the sum of `noise_bits` secret random bits minus `noise_bits / 2`,
+    /// i.e. it follows the centered binomial distribution with variance `noise_bits / 4` (the binomial mechanism).
+    /// The number of bits must be even and at most [MAX_NOISE_BITS].
+    RevealWithNoise { parties: Vec<u64>, noise_bits: u64 },
+    /// The output is a tuple whose elements have given policies.
+    Tuple(Vec<OutputPolicy>),
+}
+
+impl OutputPolicy {
+    /// Converts output statuses accepted by [prepare_for_mpc_evaluation](crate::mpc::mpc_compiler::prepare_for_mpc_evaluation) to a policy.
+    ///
+    /// The output is revealed to all the given parties or stays shared if no parties are given.
+    pub fn from_statuses(statuses: &[IOStatus]) -> Result<OutputPolicy> {
+        if statuses.is_empty() {
+            return Ok(OutputPolicy::Shared);
+        }
+        let mut parties = vec![];
+        for status in statuses {
+            if let IOStatus::Party(id) = *status {
+                parties.push(id);
+            } else {
+                return Err(runtime_error!(
+                    "Output status should be a party id or shared"
+                ));
+            }
+        }
+        Ok(OutputPolicy::RevealTo(parties))
+    }
+
+    /// Returns the sorted ids of all the parties that obtain (some part of) the output.
+    pub fn get_parties(&self) -> Vec<u64> {
+        let mut parties = match self {
+            OutputPolicy::Shared => vec![],
+            OutputPolicy::RevealTo(parties) | OutputPolicy::RevealWithNoise { parties, .. } => {
+                parties.clone()
+            }
+            OutputPolicy::Tuple(policies) => {
+                policies.iter().flat_map(|p| p.get_parties()).collect()
+            }
+        };
+        parties.sort_unstable();
+        parties.dedup();
+        parties
+    }
+
+    /// Checks that the policy is well-formed and can be applied to the output of a given type.
+    pub fn check(&self, t: &Type) -> Result<()> {
+        match self {
+            OutputPolicy::Shared => Ok(()),
+            OutputPolicy::RevealTo(parties) => check_parties(parties),
+            OutputPolicy::RevealWithNoise {
+                parties,
+                noise_bits,
+            } => {
+                check_parties(parties)?;
+                if *noise_bits == 0 || noise_bits % 2 != 0 || *noise_bits > MAX_NOISE_BITS {
+                    return Err(runtime_error!(
+                        "Number of noise bits must be even and between 2 and {}, got {}",
+                        MAX_NOISE_BITS,
+                        noise_bits
+                    ));
+                }
+                if !(t.is_scalar() || t.is_array()) || t.get_scalar_type() == BIT {
+                    return Err(runtime_error!(
+                        "Noise can be added only to integer scalars or arrays, got {}",
+                        t
+                    ));
+                }
+                Ok(())
+            }
+            OutputPolicy::Tuple(policies) => {
+                if let Type::Tuple(element_types) = t {
+                    if element_types.len() != policies.len() {
+                        return Err(runtime_error!(
+                            "Output tuple has {} elements, but {} policies are given",
+                            element_types.len(),
+                            policies.len()
+                        ));
+                    }
+                    for (policy, element_t) in policies.iter().zip(element_types) {
+                        policy.check(element_t)?;
+                    }
+                    Ok(())
+                } else {
+                    Err(runtime_error!(
+                        "Tuple policy can be applied only to tuples, got {}",
+                        t
+                    ))
+                }
+            }
+        }
+    }
+
+    fn has_noise(&self) -> bool {
+        match self {
+            OutputPolicy::RevealWithNoise { .. } => true,
+            OutputPolicy::Tuple(policies) => policies.iter().any(|p| p.has_noise()),
+            _ => false,
+        }
+    }
+}
+
+fn check_parties(parties: &[u64]) -> Result<()> {
+    if parties.is_empty() {
+        return Err(runtime_error!(
+            "No output parties are given, use the shared policy instead"
+        ));
+    }
+    for id in parties {
+        if *id >= PARTIES as u64 {
+            return Err(runtime_error!("Output party should have a valid party ID"));
+        }
+    }
+    Ok(())
+}
+
+/// Applies a policy to the secret-shared output of a compiled graph, i.e. a tuple of shares.
+///
+/// Elements of tuples with [OutputPolicy::Tuple] are processed separately, so the result is a tuple of processed elements.
+pub(super) fn apply_output_policy(g: Graph, shares: Node, policy: &OutputPolicy) -> Result<Node> {
+    match policy {
+        OutputPolicy::Shared => Ok(shares),
+        OutputPolicy::RevealTo(parties) | OutputPolicy::RevealWithNoise { parties, .. } => {
+            let statuses = parties.iter().map(|id| IOStatus::Party(*id)).collect();
+            reveal_output(g, shares, statuses)
+        }
+        OutputPolicy::Tuple(policies) => {
+            let mut elements = vec![];
+            for (i, element_policy) in policies.iter().enumerate() {
+                let mut element_shares = vec![];
+                for party_id in 0..PARTIES as u64 {
+                    element_shares.push(shares.tuple_get(party_id)?.tuple_get(i as u64)?);
+                }
+                let element_shares = g.create_tuple(element_shares)?;
+                elements.push(apply_output_policy(
+                    g.clone(),
+                    element_shares,
+                    element_policy,
+                )?);
+            }
+            g.create_tuple(elements)
+        }
+    }
+}
+
+// Adds secret binomial noise to the parts of the output with the policy RevealWithNoise
+fn add_noise(node: Node, policy: &OutputPolicy) -> Result<Node> {
+    match policy {
+        OutputPolicy::RevealWithNoise { noise_bits, .. } => {
+            let g = node.get_graph();
+            let t = node.get_type()?;
+            let st = t.get_scalar_type();
+            let mut bits_shape = if t.is_array() { t.get_shape() } else { vec![] };
+            let sum_axis = bits_shape.len() as u64;
+            bits_shape.push(*noise_bits);
+            let bits = g.random(array_type(bits_shape, BIT))?;
+            let noise = single_bit_to_arithmetic(bits, st.clone())?
+                .sum(vec![sum_axis])?
+                .subtract(constant_scalar(&g, noise_bits / 2, st)?)?;
+            node.add(noise)
+        }
+        OutputPolicy::Tuple(policies) => {
+            let mut elements = vec![];
+            for (i, element_policy) in policies.iter().enumerate() {
+                elements.push(add_noise(node.tuple_get(i as u64)?, element_policy)?);
+            }
+            node.get_graph().create_tuple(elements)
+        }
+        _ => Ok(node),
+    }
+}
+
+/// Returns a copy of a given context whose main graph adds noise to its output as required by the policy [OutputPolicy::RevealWithNoise].
+///
+/// Since noise is generated by [Operation::Random](crate::graphs::Operation::Random), it stays secret after MPC compilation.
+/// If the policy requires no noise, the context is returned as is.
+pub fn add_output_noise(context: Context, policy: &OutputPolicy) -> Result<Context> {
+    context.check_finalized()?;
+    let main_graph = context.get_main_graph()?;
+    policy.check(&main_graph.get_output_node()?.get_type()?)?;
+    if !policy.has_noise() {
+        return Ok(context);
+    }
+    let new_context = create_context()?;
+    let mut context_map = ContextMappings::default();
+    for graph in context.get_graphs() {
+        let out_graph = new_context.create_graph()?;
+        for node in graph.get_nodes() {
+            let new_dependencies = node
+                .get_node_dependencies()
+                .into_iter()
+                .map(|x| context_map.get_node(x))
+                .collect();
+            let new_graph_dependencies = node
+                .get_graph_dependencies()
+                .into_iter()
+                .map(|x| context_map.get_graph(x))
+                .collect();
+            let new_node = out_graph.add_node(
+                new_dependencies,
+                new_graph_dependencies,
+                node.get_operation(),
+            )?;
+            for annotation in node.get_annotations()? {
+                new_node.add_annotation(annotation)?;
+            }
+            copy_node_name(node.clone(), new_node.clone())?;
+            copy_node_provenance(node.clone(), new_node.clone())?;
+            context_map.insert_node(node, new_node);
+        }
+        let output_node = context_map.get_node(graph.get_output_node()?);
+        let output_node = if graph == main_graph {
+            add_noise(output_node, policy)?
+        } else {
+            output_node
+        };
+        out_graph.set_output_node(output_node)?;
+        out_graph.finalize()?;
+        context_map.insert_graph(graph, out_graph);
+    }
+    new_context.set_main_graph(context_map.get_graph(main_graph))?;
+    new_context.finalize()?;
+    Ok(new_context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{scalar_type, tuple_type, INT64, UINT64};
+    use crate::data_values::Value;
+    use crate::evaluators::random_evaluate;
+
+    #[test]
+    fn test_output_policy() {
+        || -> Result<()> {
+            assert_eq!(OutputPolicy::from_statuses(&[])?, OutputPolicy::Shared);
+            assert_eq!(
+                OutputPolicy::from_statuses(&[IOStatus::Party(2), IOStatus::Party(0)])?,
+                OutputPolicy::RevealTo(vec![2, 0])
+            );
+            assert!(OutputPolicy::from_statuses(&[IOStatus::Public]).is_err());
+            assert!(OutputPolicy::from_statuses(&[IOStatus::Shared]).is_err());
+
+            let t = tuple_type(vec![array_type(vec![5], INT64), scalar_type(BIT)]);
+            let policy = OutputPolicy::Tuple(vec![
+                OutputPolicy::RevealWithNoise {
+                    parties: vec![1, 0],
+                    noise_bits: 16,
+                },
+                OutputPolicy::RevealTo(vec![1]),
+            ]);
+            assert_eq!(policy.get_parties(), vec![0, 1]);
+            policy.check(&t)?;
+            assert!(OutputPolicy::Shared.check(&t).is_ok());
+            assert!(OutputPolicy::RevealTo(vec![]).check(&t).is_err());
+            assert!(OutputPolicy::RevealTo(vec![3]).check(&t).is_err());
+            assert!(OutputPolicy::Tuple(vec![OutputPolicy::Shared])
+                .check(&t)
+                .is_err());
+            assert!(OutputPolicy::Tuple(vec![])
+                .check(&scalar_type(BIT))
+                .is_err());
+            for (noise_bits, t) in [
+                (16, scalar_type(BIT)),
+                (16, t.clone()),
+                (0, scalar_type(INT64)),
+                (3, scalar_type(INT64)),
+                (MAX_NOISE_BITS + 2, scalar_type(INT64)),
+            ] {
+                assert!(OutputPolicy::RevealWithNoise {
+                    parties: vec![0],
+                    noise_bits
+                }
+                .check(&t)
+                .is_err());
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_add_output_noise() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![1000], INT64);
+            let x = g.input(t.clone())?;
+            let y = g.input(scalar_type(UINT64))?;
+            g.create_tuple(vec![x, y])?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+
+            let policy = OutputPolicy::Tuple(vec![
+                OutputPolicy::RevealWithNoise {
+                    parties: vec![0],
+                    noise_bits: 16,
+                },
+                OutputPolicy::RevealTo(vec![0]),
+            ]);
+            let noisy_c = add_output_noise(c.clone(), &policy)?;
+            let x_value: Vec<i64> = (0..1000).map(|i| i * 10 - 5000).collect();
+            let result = random_evaluate(
+                noisy_c.get_main_graph()?,
+                vec![
+                    Value::from_flattened_array(&x_value, INT64)?,
+                    Value::from_scalar(7, UINT64)?,
+                ],
+            )?
+            .to_vector()?;
+            let noisy_x = result[0].to_flattened_array_i64(t)?;
+            let noise: Vec<i64> = noisy_x.iter().zip(&x_value).map(|(a, b)| a - b).collect();
+            assert!(noise.iter().all(|e| e.abs() <= 8));
+            assert!(noise.iter().any(|e| *e != 0));
+            // The noise is centered with variance 4
+            let mean = noise.iter().sum::<i64>() as f64 / 1000.0;
+            let variance = noise.iter().map(|e| (e * e) as f64).sum::<f64>() / 1000.0;
+            assert!(mean.abs() < 0.5);
+            assert!((variance - 4.0).abs() < 1.0);
+            assert_eq!(result[1].to_u64(UINT64)?, 7);
+
+            // Contexts without noise aren't copied
+            let c2 = add_output_noise(c.clone(), &OutputPolicy::RevealTo(vec![1]))?;
+            assert!(c2 == c);
+            assert!(add_output_noise(c, &OutputPolicy::RevealTo(vec![5])).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
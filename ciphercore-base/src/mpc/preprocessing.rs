@@ -0,0 +1,1109 @@
+//! Separation of compiled MPC graphs into an offline (preprocessing) phase and an online phase.
+//!
+//! Nodes of a compiled graph that don't depend on inputs, but depend on randomness, can be evaluated before the inputs are known.
+//! These are, for instance, Beaver triples and masks generated by a dealer, PRF keys and the values derived from them.
+//! Such nodes form the offline phase, which usually contains the most expensive local computations of a protocol
+//! (e.g. the products of random matrices in Beaver triples).
+//!
+//! The offline phase is extracted by [get_offline_context] and can be evaluated by a trusted dealer or locally via [generate_preprocessing].
+//! Its result is stored in [PreprocessedData] that can be serialized and later plugged into the online phase by [get_online_context].
+//! The online phase contains only the nodes depending on inputs, so its evaluation doesn't spend time on preprocessing.
+//!
+//! Triples required by a compiled graph can be counted by [count_triples].
+//!
+//! In addition, a static database intersected many times with other databases can be preprocessed once by the context of [get_psi_preprocessing_context].
+//! The result is stored in [PreprocessedDatabase] that can be serialized and later passed as an input to the context of [get_psi_context_with_preprocessed_database].
+//! Preprocessed chunks of two append-only databases form an [IncrementalJoinState], which allows the context of [get_incremental_psi_context] to compute only the new rows of their join.
+use crate::custom_ops::{run_instantiation_pass, CustomOperation};
+use crate::data_types::{tuple_type, Type};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::evaluators::Evaluator;
+use crate::graphs::{
+    copy_node_name, copy_node_provenance, create_context, Context, Graph, Node, NodeAnnotation,
+    Operation,
+};
+use crate::inline::inline_ops::{inline_operations, InlineConfig};
+use crate::mpc::mpc_compiler::{
+    generate_prf_key_triple, reveal_output, share_node, uniquify_prf_id, IOStatus, PARTIES,
+};
+use crate::mpc::mpc_psi::{
+    get_preprocessed_database_headers, PsiConfig, PsiMode, PsiPreprocessingMPC, SetIntersectionMPC,
+};
+use crate::ops::utils::concatenate_first_axis;
+use crate::type_inference::NULL_HEADER;
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Number of Beaver triples of a given kind consumed by a compiled graph.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TripleCount {
+    /// Bilinear operation computing `c` from `a` and `b` (e.g. Multiply or Matmul)
+    pub operation: Operation,
+    /// Type of `a`
+    pub a_type: Type,
+    /// Type of `b`
+    pub b_type: Type,
+    /// Number of triples
+    pub count: u64,
+}
+
+/// Result of the offline phase of a compiled graph.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PreprocessedData {
+    t: Type,
+    value: Value,
+}
+
+impl PreprocessedData {
+    /// Creates preprocessed data from the output of the offline phase returned by [get_offline_context].
+    pub fn new(t: Type, value: Value) -> Result<Self> {
+        if !value.check_type(t.clone())? {
+            return Err(runtime_error!("Preprocessed value doesn't match its type"));
+        }
+        Ok(PreprocessedData { t, value })
+    }
+
+    /// Returns the type of the preprocessed values.
+    pub fn get_type(&self) -> Type {
+        self.t.clone()
+    }
+
+    /// Returns the preprocessed values.
+    pub fn get_value(&self) -> Value {
+        self.value.clone()
+    }
+}
+
+/// Secret-shared database preprocessed for repeated set intersections with it.
+///
+/// It contains the OPRF parameters, the hash functions and the Cuckoo table of the database computed by the context of [get_psi_preprocessing_context].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PreprocessedDatabase {
+    t: Type,
+    value: Value,
+}
+
+impl PreprocessedDatabase {
+    /// Creates a preprocessed database from the output of the context returned by [get_psi_preprocessing_context].
+    pub fn new(t: Type, value: Value) -> Result<Self> {
+        if !value.check_type(t.clone())? {
+            return Err(runtime_error!(
+                "Preprocessed database doesn't match its type"
+            ));
+        }
+        Ok(PreprocessedDatabase { t, value })
+    }
+
+    /// Returns the type of the preprocessed database.
+    pub fn get_type(&self) -> Type {
+        self.t.clone()
+    }
+
+    /// Returns the value of the preprocessed database.
+    pub fn get_value(&self) -> Value {
+        self.value.clone()
+    }
+}
+
+/// Database of an inner join to which rows are appended (see [IncrementalJoinState]).
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum JoinSide {
+    /// The first database, whose columns go first in the join
+    First,
+    /// The second database
+    Second,
+}
+
+/// State of an inner join of two append-only databases that allows computing only the new rows of the join when rows are appended to either database.
+///
+/// The state contains all the appended chunks of both databases preprocessed by the context of [get_psi_preprocessing_context], i.e. their OPRF keys, hash functions and Cuckoo tables.
+/// When a chunk is appended to one database, the context of [get_incremental_psi_context] intersects it only with the preprocessed chunks of the other database.
+/// Then, the chunk is preprocessed and added to the state by [IncrementalJoinState::append].
+/// Thus, neither the join of old rows nor the OPRF values and Cuckoo tables of old chunks are recomputed.
+///
+/// If chunks are appended to both databases at once, they should be processed one after another, so that the join of these two chunks is computed by the second update.
+/// As in [set_intersection](crate::graphs::Graph::set_intersection), key values must be unique within each database, i.e. within all its chunks.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct IncrementalJoinState {
+    first_chunks: Vec<PreprocessedDatabase>,
+    second_chunks: Vec<PreprocessedDatabase>,
+}
+
+impl IncrementalJoinState {
+    /// Creates a state of the join of two empty databases.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a preprocessed chunk of rows appended to a given database.
+    pub fn append(&mut self, side: JoinSide, chunk: PreprocessedDatabase) {
+        match side {
+            JoinSide::First => self.first_chunks.push(chunk),
+            JoinSide::Second => self.second_chunks.push(chunk),
+        }
+    }
+
+    /// Returns the preprocessed chunks of a given database in the order of their appending.
+    pub fn get_chunks(&self, side: JoinSide) -> &[PreprocessedDatabase] {
+        match side {
+            JoinSide::First => &self.first_chunks,
+            JoinSide::Second => &self.second_chunks,
+        }
+    }
+
+    /// Returns the types of the preprocessed chunks of a given database.
+    pub fn get_chunk_types(&self, side: JoinSide) -> Vec<Type> {
+        self.get_chunks(side)
+            .iter()
+            .map(|chunk| chunk.get_type())
+            .collect()
+    }
+
+    /// Returns the values of the preprocessed chunks of a given database.
+    pub fn get_chunk_values(&self, side: JoinSide) -> Vec<Value> {
+        self.get_chunks(side)
+            .iter()
+            .map(|chunk| chunk.get_value())
+            .collect()
+    }
+}
+
+/// Partition of the nodes of a graph into offline and online ones.
+struct Partition {
+    /// IDs of nodes that don't depend on inputs
+    input_independent: HashSet<u64>,
+    /// IDs of input-independent nodes that depend on randomness
+    offline: HashSet<u64>,
+    /// Offline nodes used by the online phase
+    boundary: Vec<Node>,
+}
+
+fn partition_graph(graph: &Graph) -> Result<Partition> {
+    graph.check_finalized()?;
+    let mut input_independent = HashSet::new();
+    let mut offline = HashSet::new();
+    for node in graph.get_nodes() {
+        if !node.get_graph_dependencies().is_empty() {
+            return Err(runtime_error!(
+                "Graph must be fully inlined to be split into preprocessing and online phases"
+            ));
+        }
+        let deps = node.get_node_dependencies();
+        let is_independent = !matches!(node.get_operation(), Operation::Input(_))
+            && deps
+                .iter()
+                .all(|dep| input_independent.contains(&dep.get_id()));
+        if !is_independent {
+            continue;
+        }
+        input_independent.insert(node.get_id());
+        let is_random = matches!(
+            node.get_operation(),
+            Operation::Random(_) | Operation::RandomPermutation(_)
+        );
+        if is_random || deps.iter().any(|dep| offline.contains(&dep.get_id())) {
+            offline.insert(node.get_id());
+        }
+    }
+    let output_id = graph.get_output_node()?.get_id();
+    let mut boundary_ids = HashSet::new();
+    if offline.contains(&output_id) {
+        boundary_ids.insert(output_id);
+    }
+    for node in graph.get_nodes() {
+        if offline.contains(&node.get_id()) {
+            continue;
+        }
+        for dep in node.get_node_dependencies() {
+            if offline.contains(&dep.get_id()) {
+                boundary_ids.insert(dep.get_id());
+            }
+        }
+    }
+    let boundary = graph
+        .get_nodes()
+        .into_iter()
+        .filter(|node| boundary_ids.contains(&node.get_id()))
+        .collect();
+    Ok(Partition {
+        input_independent,
+        offline,
+        boundary,
+    })
+}
+
+fn get_boundary_type(partition: &Partition) -> Result<Type> {
+    let types = partition
+        .boundary
+        .iter()
+        .map(|node| node.get_type())
+        .collect::<Result<Vec<Type>>>()?;
+    Ok(tuple_type(types))
+}
+
+pub(super) fn copy_node(
+    node: &Node,
+    out_graph: &Graph,
+    mapping: &HashMap<u64, Node>,
+) -> Result<Node> {
+    let deps = node
+        .get_node_dependencies()
+        .iter()
+        .map(|dep| mapping[&dep.get_id()].clone())
+        .collect();
+    let new_node = out_graph.add_node(deps, vec![], node.get_operation())?;
+    for annotation in node.get_annotations()? {
+        new_node.add_annotation(annotation)?;
+    }
+    copy_node_name(node.clone(), new_node.clone())?;
+    copy_node_provenance(node.clone(), new_node.clone())?;
+    Ok(new_node)
+}
+
+/// Counts the Beaver triples consumed by the main graph of a compiled context.
+///
+/// Triples are grouped by the multiplication operation and the types of their factors.
+///
+/// # Arguments
+///
+/// `context` - context compiled by an MPC backend that uses Beaver triples (e.g. [TwoParty](crate::mpc::mpc_compiler::MpcBackend::TwoParty) or [Spdz](crate::mpc::mpc_compiler::MpcBackend::Spdz))
+///
+/// # Returns
+///
+/// Numbers of triples of every kind in the order of their first use
+pub fn count_triples(context: Context) -> Result<Vec<TripleCount>> {
+    let mut counts: Vec<TripleCount> = vec![];
+    for node in context.get_main_graph()?.get_nodes() {
+        if !node
+            .get_annotations()?
+            .contains(&NodeAnnotation::BeaverTriple)
+        {
+            continue;
+        }
+        let deps = node.get_node_dependencies();
+        let operation = node.get_operation();
+        let a_type = deps[0].get_type()?;
+        let b_type = deps[1].get_type()?;
+        match counts
+            .iter_mut()
+            .find(|c| c.operation == operation && c.a_type == a_type && c.b_type == b_type)
+        {
+            Some(c) => c.count += 1,
+            None => counts.push(TripleCount {
+                operation,
+                a_type,
+                b_type,
+                count: 1,
+            }),
+        }
+    }
+    Ok(counts)
+}
+
+/// Extracts the offline phase of the main graph of a compiled context.
+///
+/// The main graph of the resulting context has no inputs and returns a tuple of all the preprocessed values needed by the online phase.
+/// It can be sent to a trusted dealer, whose evaluation result should be wrapped into [PreprocessedData].
+///
+/// # Arguments
+///
+/// `context` - compiled and fully inlined context
+///
+/// # Returns
+///
+/// Context of the offline phase
+pub fn get_offline_context(context: Context) -> Result<Context> {
+    let graph = context.get_main_graph()?;
+    let partition = partition_graph(&graph)?;
+    let out_context = create_context()?;
+    let out_graph = out_context.create_graph()?;
+    let mut mapping = HashMap::new();
+    for node in graph.get_nodes() {
+        if partition.input_independent.contains(&node.get_id()) {
+            let new_node = copy_node(&node, &out_graph, &mapping)?;
+            mapping.insert(node.get_id(), new_node);
+        }
+    }
+    let outputs = partition
+        .boundary
+        .iter()
+        .map(|node| mapping[&node.get_id()].clone())
+        .collect();
+    out_graph.create_tuple(outputs)?.set_as_output()?;
+    out_graph.finalize()?.set_as_main()?;
+    out_context.finalize()?;
+    Ok(out_context)
+}
+
+/// Evaluates the offline phase of the main graph of a compiled context locally.
+///
+/// # Arguments
+///
+/// * `context` - compiled and fully inlined context
+/// * `evaluator` - evaluator of the offline phase
+///
+/// # Returns
+///
+/// Preprocessed data to be consumed by [get_online_context]
+pub fn generate_preprocessing<E: Evaluator>(
+    context: Context,
+    mut evaluator: E,
+) -> Result<PreprocessedData> {
+    let offline_context = get_offline_context(context)?;
+    evaluator.preprocess(offline_context.clone())?;
+    let t = offline_context
+        .get_main_graph()?
+        .get_output_node()?
+        .get_type()?;
+    let value = evaluator.evaluate_context(offline_context, vec![])?;
+    PreprocessedData::new(t, value)
+}
+
+/// Creates the online phase of the main graph of a compiled context, where all the offline nodes are replaced by preprocessed values.
+///
+/// The resulting graph has the same inputs and output as the original one.
+///
+/// # Arguments
+///
+/// * `context` - compiled and fully inlined context
+/// * `data` - output of the offline phase of the same context
+///
+/// # Returns
+///
+/// Context of the online phase
+pub fn get_online_context(context: Context, data: &PreprocessedData) -> Result<Context> {
+    let graph = context.get_main_graph()?;
+    let partition = partition_graph(&graph)?;
+    if get_boundary_type(&partition)? != data.t {
+        return Err(runtime_error!(
+            "Preprocessed data doesn't match the offline phase of the context"
+        ));
+    }
+    let preprocessed_values = data.value.to_vector()?;
+    let out_context = create_context()?;
+    let out_graph = out_context.create_graph()?;
+    let mut mapping = HashMap::new();
+    let mut boundary_values = HashMap::new();
+    for (node, value) in partition.boundary.iter().zip(preprocessed_values) {
+        boundary_values.insert(node.get_id(), value);
+    }
+    for node in graph.get_nodes() {
+        let new_node = if partition.offline.contains(&node.get_id()) {
+            match boundary_values.remove(&node.get_id()) {
+                Some(value) => {
+                    let constant = out_graph.constant(node.get_type()?, value)?;
+                    copy_node_name(node.clone(), constant.clone())?;
+                    constant
+                }
+                None => continue,
+            }
+        } else {
+            copy_node(&node, &out_graph, &mapping)?
+        };
+        mapping.insert(node.get_id(), new_node);
+    }
+    out_graph.set_output_node(mapping[&graph.get_output_node()?.get_id()].clone())?;
+    out_graph.finalize()?.set_as_main()?;
+    out_context.finalize()?;
+    Ok(out_context)
+}
+
+// Adds an input node containing a database of a given type and secret-shares it according to its status
+fn add_database_input(g: &Graph, t: Type, status: IOStatus, prf_keys: Node) -> Result<Node> {
+    match status {
+        IOStatus::Party(_) => {
+            let plain_input = g.input(t)?;
+            share_node(g.clone(), plain_input, prf_keys, status)
+        }
+        IOStatus::Shared => g.input(tuple_type(vec![t; PARTIES])),
+        IOStatus::Public => g.input(t),
+    }
+}
+
+// Finalizes a context with a given main graph containing MPC custom operations and prepares it for evaluation
+fn prepare_psi_context(context: Context, g: Graph, inline_config: InlineConfig) -> Result<Context> {
+    g.finalize()?.set_as_main()?;
+    context.finalize()?;
+    let instantiated_context = run_instantiation_pass(context)?.get_context();
+    let inlined_context = inline_operations(instantiated_context, inline_config)?;
+    uniquify_prf_id(inlined_context)
+}
+
+/// Creates a context preprocessing a database for repeated set intersections with it.
+///
+/// The main graph of the resulting context takes the database and returns its secret-shared preprocessing.
+/// This preprocessing includes OPRF values of the database computed via the block cipher given by `config` and its Cuckoo table.
+/// The output of this graph should be wrapped into [PreprocessedDatabase].
+///
+/// Key columns are merged in the alphabetical order of their headers.
+/// Note that a PRF key used to compute OPRF values is shared by all the intersections with the same preprocessed database.
+/// Thus, party 2 learns which rows of different databases intersected with it have equal keys.
+///
+/// # Arguments
+///
+/// * `database_t` - named tuple type of the database (see [set_intersection](crate::graphs::Graph::set_intersection))
+/// * `key_headers` - headers of key columns of the database
+/// * `input_status` - status of the database input (public, already shared or owned by a party)
+/// * `config` - parameters of the set intersection protocol
+/// * `inline_config` - configuration of inlining
+///
+/// # Returns
+///
+/// Compiled and fully inlined context
+pub fn get_psi_preprocessing_context(
+    database_t: Type,
+    mut key_headers: Vec<String>,
+    input_status: IOStatus,
+    config: PsiConfig,
+    inline_config: InlineConfig,
+) -> Result<Context> {
+    key_headers.sort();
+    let context = create_context()?;
+    let g = context.create_graph()?;
+    let prf_keys = g.create_tuple(generate_prf_key_triple(g.clone())?)?;
+    let database = add_database_input(&g, database_t, input_status, prf_keys.clone())?;
+    g.custom_op(
+        CustomOperation::new(PsiPreprocessingMPC {
+            key_headers,
+            config,
+        }),
+        vec![database, prf_keys],
+    )?
+    .set_as_output()?;
+    prepare_psi_context(context, g, inline_config)
+}
+
+/// Creates a context computing the intersection of a database with a preprocessed one.
+///
+/// The main graph of the resulting context takes the database and the value of a [PreprocessedDatabase] and returns their intersection as [set_intersection](crate::graphs::Graph::set_intersection) does.
+/// In contrast to the usual intersection, OPRF values and the Cuckoo table of the preprocessed database are not recomputed.
+///
+/// # Arguments
+///
+/// * `database_t` - named tuple type of the database
+/// * `input_status` - status of the database input (public, already shared or owned by a party)
+/// * `preprocessed_database_t` - type of the preprocessed database
+/// * `headers` - pairs of key headers of the database and the preprocessed one; the latter should coincide with those passed to [get_psi_preprocessing_context]
+/// * `output_parties` - parties obtaining the revealed intersection; if empty, the intersection is returned in the shared form
+/// * `config` - parameters of the set intersection protocol; should coincide with those passed to [get_psi_preprocessing_context]
+/// * `inline_config` - configuration of inlining
+///
+/// # Returns
+///
+/// Compiled and fully inlined context
+pub fn get_psi_context_with_preprocessed_database(
+    database_t: Type,
+    input_status: IOStatus,
+    preprocessed_database_t: Type,
+    mut headers: Vec<(String, String)>,
+    output_parties: Vec<IOStatus>,
+    config: PsiConfig,
+    inline_config: InlineConfig,
+) -> Result<Context> {
+    // Merge key columns in the same order as in the preprocessing
+    headers.sort_by(|(h_x0, h_y0), (h_x1, h_y1)| (h_y0, h_x0).cmp(&(h_y1, h_x1)));
+    let context = create_context()?;
+    let g = context.create_graph()?;
+    let prf_keys = g.create_tuple(generate_prf_key_triple(g.clone())?)?;
+    let database = add_database_input(&g, database_t, input_status, prf_keys.clone())?;
+    let preprocessed_database = g.input(preprocessed_database_t)?;
+    let result = g.custom_op(
+        CustomOperation::new(SetIntersectionMPC {
+            headers,
+            mode: PsiMode::Unbalanced,
+            config,
+        }),
+        vec![database, preprocessed_database, prf_keys],
+    )?;
+    reveal_output(g.clone(), result, output_parties)?.set_as_output()?;
+    prepare_psi_context(context, g, inline_config)
+}
+
+/// Creates a context computing the new rows of an inner join of two append-only databases after rows are appended to one of them.
+///
+/// The main graph of the resulting context takes the appended rows of the database given by `delta_side` and the values of the preprocessed chunks of the other database in the order of `chunk_types` (see [IncrementalJoinState]).
+/// It returns the intersection of the appended rows with these chunks, whose columns are the same as in [set_intersection](crate::graphs::Graph::set_intersection) of the first database with the second one.
+/// The intersections with different chunks are concatenated in the order of chunks; the rows of each of them are aligned with the appended rows.
+///
+/// Note that OPRF values of the appended rows are revealed to party 2 using the OPRF keys of the chunks.
+/// Thus, as for [get_psi_context_with_preprocessed_database], party 2 learns which appended rows of different updates have equal keys.
+///
+/// # Arguments
+///
+/// * `delta_t` - named tuple type of the appended rows
+/// * `input_status` - status of the input with the appended rows (public, already shared or owned by a party)
+/// * `delta_side` - database to which the rows are appended
+/// * `chunk_types` - types of the preprocessed chunks of the other database; there should be at least one chunk
+/// * `headers` - pairs of key headers of the first and the second databases; the headers of the preprocessed database should coincide with those passed to [get_psi_preprocessing_context]
+/// * `output_parties` - parties obtaining the revealed new rows; if empty, they are returned in the shared form
+/// * `config` - parameters of the set intersection protocol; should coincide with those passed to [get_psi_preprocessing_context]
+/// * `inline_config` - configuration of inlining
+///
+/// # Returns
+///
+/// Compiled and fully inlined context
+#[allow(clippy::too_many_arguments)]
+pub fn get_incremental_psi_context(
+    delta_t: Type,
+    input_status: IOStatus,
+    delta_side: JoinSide,
+    chunk_types: Vec<Type>,
+    headers: Vec<(String, String)>,
+    output_parties: Vec<IOStatus>,
+    config: PsiConfig,
+    inline_config: InlineConfig,
+) -> Result<Context> {
+    if chunk_types.is_empty() {
+        return Err(runtime_error!(
+            "Incremental join needs at least one preprocessed chunk of the other database"
+        ));
+    }
+    // Key headers of the appended rows go first in the intersection with a chunk
+    let mut psi_headers: Vec<(String, String)> = match delta_side {
+        JoinSide::First => headers.clone(),
+        JoinSide::Second => headers
+            .iter()
+            .map(|(h_x, h_y)| (h_y.clone(), h_x.clone()))
+            .collect(),
+    };
+    // Merge key columns in the same order as in the preprocessing
+    psi_headers.sort_by(|(h_x0, h_y0), (h_x1, h_y1)| (h_y0, h_x0).cmp(&(h_y1, h_x1)));
+    let delta_headers: Vec<String> = match &delta_t {
+        Type::NamedTuple(header_types) => header_types
+            .iter()
+            .map(|(header, _)| header.clone())
+            .collect(),
+        _ => return Err(runtime_error!("Database must be a named tuple")),
+    };
+    let context = create_context()?;
+    let g = context.create_graph()?;
+    let prf_keys = g.create_tuple(generate_prf_key_triple(g.clone())?)?;
+    let delta = add_database_input(&g, delta_t, input_status, prf_keys.clone())?;
+    let mut parts = vec![];
+    for chunk_t in chunk_types {
+        let chunk = g.input(chunk_t.clone())?;
+        let part = g.custom_op(
+            CustomOperation::new(SetIntersectionMPC {
+                headers: psi_headers.clone(),
+                mode: PsiMode::Unbalanced,
+                config: config.clone(),
+            }),
+            vec![delta.clone(), chunk, prf_keys.clone()],
+        )?;
+        let part = match delta_side {
+            JoinSide::First => part,
+            JoinSide::Second => reorder_swapped_join(
+                part,
+                &get_preprocessed_database_headers(chunk_t)?,
+                &delta_headers,
+                &headers,
+            )?,
+        };
+        parts.push(part);
+    }
+    let result = concatenate_shared_databases(&g, parts)?;
+    reveal_output(g.clone(), result, output_parties)?.set_as_output()?;
+    prepare_psi_context(context, g, inline_config)
+}
+
+// Renames and reorders the columns of the shared join of the second database Y with the first one X
+// such that they coincide with the columns of the join of X with Y, i.e. the columns of X followed by the non-key columns of Y
+fn reorder_swapped_join(
+    join: Node,
+    headers_x: &[String],
+    headers_y: &[String],
+    key_headers: &[(String, String)],
+) -> Result<Node> {
+    let g = join.get_graph();
+    // Key columns of the join are named after the key columns of Y
+    let key_headers_map: HashMap<&String, &String> =
+        key_headers.iter().map(|(h_x, h_y)| (h_x, h_y)).collect();
+    let mut shares = vec![];
+    for share_id in 0..PARTIES as u64 {
+        let share = join.tuple_get(share_id)?;
+        let mut columns = vec![];
+        for h_x in headers_x {
+            let source_header = key_headers_map.get(h_x).copied().unwrap_or(h_x);
+            columns.push((h_x.clone(), share.named_tuple_get(source_header.clone())?));
+        }
+        for h_y in headers_y {
+            if h_y != NULL_HEADER && !key_headers_map.values().any(|h| *h == h_y) {
+                columns.push((h_y.clone(), share.named_tuple_get(h_y.clone())?));
+            }
+        }
+        shares.push(g.create_named_tuple(columns)?);
+    }
+    g.create_tuple(shares)
+}
+
+// Concatenates the rows of shared databases with the same columns
+fn concatenate_shared_databases(g: &Graph, databases: Vec<Node>) -> Result<Node> {
+    if databases.len() == 1 {
+        return Ok(databases[0].clone());
+    }
+    let headers = match databases[0].get_type()? {
+        Type::Tuple(share_types) => match &*share_types[0] {
+            Type::NamedTuple(header_types) => header_types
+                .iter()
+                .map(|(header, _)| header.clone())
+                .collect::<Vec<String>>(),
+            _ => return Err(runtime_error!("Shares of a database must be named tuples")),
+        },
+        _ => return Err(runtime_error!("Database must be secret-shared")),
+    };
+    let mut shares = vec![];
+    for share_id in 0..PARTIES as u64 {
+        let mut columns = vec![];
+        for header in &headers {
+            let mut parts = vec![];
+            for database in &databases {
+                parts.push(
+                    database
+                        .tuple_get(share_id)?
+                        .named_tuple_get(header.clone())?,
+                );
+            }
+            columns.push((header.clone(), concatenate_first_axis(parts)?));
+        }
+        shares.push(g.create_named_tuple(columns)?);
+    }
+    g.create_tuple(shares)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{
+        array_type, named_tuple_type, scalar_type, ScalarType, BIT, INT16, INT32,
+    };
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::inline::inline_common::DepthOptimizationLevel;
+    use crate::inline::inline_ops::InlineMode;
+    use crate::mpc::mpc_compiler::{compile_context_with_backend, IOStatus, MpcBackend};
+    use crate::mpc::mpc_two_party::generate_triples_locally;
+    use crate::mpc::spdz::verify_mac_checks;
+    use crate::type_inference::NULL_HEADER;
+    use crate::typed_value::TypedValue;
+
+    fn compile(c: Context, output_parties: Vec<IOStatus>, backend: MpcBackend) -> Result<Context> {
+        compile_context_with_backend(
+            c,
+            vec![IOStatus::Party(0), IOStatus::Party(1)],
+            output_parties,
+            InlineConfig {
+                default_mode: InlineMode::Simple,
+                ..Default::default()
+            },
+            || SimpleEvaluator::new(None),
+            backend,
+        )
+    }
+
+    fn create_test_context() -> Result<Context> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let x = g.input(array_type(vec![2, 2], INT32))?;
+        let y = g.input(array_type(vec![2, 2], INT32))?;
+        x.multiply(y.clone())?
+            .multiply(x.clone())?
+            .add(x.matmul(y)?)?
+            .set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        Ok(c)
+    }
+
+    fn run_phases(compiled: Context, inputs: Vec<Value>) -> Result<Value> {
+        let data = generate_preprocessing(compiled.clone(), SimpleEvaluator::new(None)?)?;
+        // Preprocessed data survives serialization
+        let data: PreprocessedData = serde_json::from_str(&serde_json::to_string(&data)?)?;
+        let online = get_online_context(compiled, &data)?;
+        for node in online.get_main_graph()?.get_nodes() {
+            assert!(!matches!(node.get_operation(), Operation::Random(_)));
+        }
+        random_evaluate(online.get_main_graph()?, inputs)
+    }
+
+    #[test]
+    fn test_two_party_preprocessing() {
+        || -> Result<()> {
+            let c = create_test_context()?;
+            let inputs = vec![
+                Value::from_flattened_array(&[1, -2, 3, 4], INT32)?,
+                Value::from_flattened_array(&[-5, 6, 7, 8], INT32)?,
+            ];
+            let expected = random_evaluate(c.get_main_graph()?, inputs.clone())?;
+            let compiled = compile(c, vec![IOStatus::Party(0)], MpcBackend::TwoParty)?;
+            let t = array_type(vec![2, 2], INT32);
+            assert_eq!(
+                count_triples(compiled.clone())?,
+                vec![
+                    TripleCount {
+                        operation: Operation::Multiply,
+                        a_type: t.clone(),
+                        b_type: t.clone(),
+                        count: 2,
+                    },
+                    TripleCount {
+                        operation: Operation::Matmul,
+                        a_type: t.clone(),
+                        b_type: t,
+                        count: 1,
+                    },
+                ]
+            );
+            let offline = get_offline_context(compiled.clone())?;
+            for node in offline.get_main_graph()?.get_nodes() {
+                assert!(!matches!(node.get_operation(), Operation::Input(_)));
+            }
+            let inputs = [inputs, generate_triples_locally(compiled.clone())?].concat();
+            assert_eq!(run_phases(compiled, inputs)?, expected);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_spdz_preprocessing() {
+        || -> Result<()> {
+            let c = create_test_context()?;
+            let inputs = vec![
+                Value::from_flattened_array(&[3, 0, -1, 2], INT32)?,
+                Value::from_flattened_array(&[4, 5, 6, -7], INT32)?,
+            ];
+            let expected = random_evaluate(c.get_main_graph()?, inputs.clone())?;
+            let compiled = compile(
+                c,
+                vec![IOStatus::Party(0), IOStatus::Party(1)],
+                MpcBackend::Spdz,
+            )?;
+            assert_eq!(count_triples(compiled.clone())?.len(), 2);
+            let t = compiled.get_main_graph()?.get_output_node()?.get_type()?;
+            let result = run_phases(compiled, inputs)?;
+            assert_eq!(
+                verify_mac_checks(TypedValue::new(t, result)?)?.value,
+                expected
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_mismatched_preprocessing() {
+        || -> Result<()> {
+            let c = create_test_context()?;
+            let two_party = compile(c.clone(), vec![IOStatus::Party(0)], MpcBackend::TwoParty)?;
+            let aby3 = compile(c, vec![IOStatus::Party(0)], MpcBackend::ABY3)?;
+            assert!(count_triples(aby3.clone())?.is_empty());
+            let data = generate_preprocessing(two_party, SimpleEvaluator::new(None)?)?;
+            assert!(get_online_context(aby3, &data).is_err());
+            assert!(PreprocessedData::new(scalar_type(INT32), data.get_value()).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_preprocessed_database_psi() {
+        || -> Result<()> {
+            let inline_config = InlineConfig {
+                default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                ..Default::default()
+            };
+            let database_t = |columns: Vec<(&str, Type)>| {
+                named_tuple_type(
+                    columns
+                        .into_iter()
+                        .map(|(header, t)| (header.to_owned(), t))
+                        .collect(),
+                )
+            };
+            let database = |columns: Vec<(Vec<u64>, ScalarType)>| -> Result<Value> {
+                let mut values = vec![];
+                for (column, st) in columns {
+                    values.push(Value::from_flattened_array(&column, st)?);
+                }
+                Ok(Value::from_vector(values))
+            };
+            let y_t = database_t(vec![
+                (NULL_HEADER, array_type(vec![8], BIT)),
+                ("k", array_type(vec![8], INT32)),
+                ("v", array_type(vec![8], INT32)),
+            ]);
+            let y = database(vec![
+                (vec![1, 1, 1, 1, 0, 1, 1, 1], BIT),
+                (vec![1, 2, 3, 4, 5, 6, 7, 8], INT32),
+                (vec![10, 20, 30, 40, 50, 60, 70, 80], INT32),
+            ])?;
+            let preprocessing_c = get_psi_preprocessing_context(
+                y_t,
+                vec!["k".to_owned()],
+                IOStatus::Party(0),
+                PsiConfig::default(),
+                inline_config.clone(),
+            )?;
+            let preprocessing_g = preprocessing_c.get_main_graph()?;
+            let preprocessed_y = PreprocessedDatabase::new(
+                preprocessing_g.get_output_node()?.get_type()?,
+                random_evaluate(preprocessing_g, vec![y])?,
+            )?;
+            // Preprocessed database survives serialization
+            let preprocessed_y: PreprocessedDatabase =
+                serde_json::from_str(&serde_json::to_string(&preprocessed_y)?)?;
+
+            let x0_t = database_t(vec![
+                (NULL_HEADER, array_type(vec![5], BIT)),
+                ("a", array_type(vec![5], INT32)),
+            ]);
+            let x0 = database(vec![
+                (vec![1, 1, 1, 1, 1], BIT),
+                (vec![2, 5, 9, 8, 3], INT32),
+            ])?;
+            let x1_t = database_t(vec![
+                (NULL_HEADER, array_type(vec![3], BIT)),
+                ("b", array_type(vec![3], INT32)),
+                ("w", array_type(vec![3], INT16)),
+            ]);
+            let x1 = database(vec![
+                (vec![1, 0, 1], BIT),
+                (vec![7, 1, 4], INT32),
+                (vec![100, 200, 300], INT16),
+            ])?;
+            let expected = [
+                vec![
+                    vec![1, 0, 0, 1, 1],
+                    vec![2, 0, 0, 8, 3],
+                    vec![20, 0, 0, 80, 30],
+                ],
+                vec![
+                    vec![1, 0, 1],
+                    vec![7, 0, 4],
+                    vec![100, 0, 300],
+                    vec![70, 0, 40],
+                ],
+            ];
+            // The same preprocessed database is intersected with two different databases
+            for ((x_t, x, key_header), expected_columns) in [(x0_t, x0, "a"), (x1_t, x1, "b")]
+                .into_iter()
+                .zip(expected.iter())
+            {
+                let psi_c = get_psi_context_with_preprocessed_database(
+                    x_t,
+                    IOStatus::Party(1),
+                    preprocessed_y.get_type(),
+                    vec![(key_header.to_owned(), "k".to_owned())],
+                    vec![IOStatus::Party(0)],
+                    PsiConfig::default(),
+                    inline_config.clone(),
+                )?;
+                let psi_g = psi_c.get_main_graph()?;
+                // LowMC and Cuckoo hashing aren't performed on the preprocessed database
+                assert!(!psi_g
+                    .get_nodes()
+                    .iter()
+                    .any(|node| matches!(node.get_operation(), Operation::CuckooHash(_))));
+                let column_types = match psi_g.get_output_node()?.get_type()? {
+                    Type::NamedTuple(column_types) => column_types,
+                    _ => panic!("Intersection must be a named tuple"),
+                };
+                let result = random_evaluate(psi_g, vec![x, preprocessed_y.get_value()])?;
+                let mut result_columns = vec![];
+                for ((_, t), column) in column_types.into_iter().zip(result.to_vector()?) {
+                    result_columns.push(column.to_flattened_array_u64((*t).clone())?);
+                }
+                assert_eq!(&result_columns, expected_columns);
+            }
+
+            // Wrong types are rejected
+            assert!(
+                PreprocessedDatabase::new(scalar_type(INT32), preprocessed_y.get_value()).is_err()
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_incremental_psi() {
+        || -> Result<()> {
+            let inline_config = InlineConfig {
+                default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                ..Default::default()
+            };
+            let database = |num_entries: u64,
+                            columns: Vec<(&str, Vec<u64>, ScalarType)>|
+             -> Result<(Type, Value)> {
+                let mut header_types = vec![];
+                let mut values = vec![];
+                for (header, column, st) in columns {
+                    header_types
+                        .push((header.to_owned(), array_type(vec![num_entries], st.clone())));
+                    values.push(Value::from_flattened_array(&column, st)?);
+                }
+                Ok((named_tuple_type(header_types), Value::from_vector(values)))
+            };
+            let headers = vec![("a".to_owned(), "k".to_owned())];
+            let mut state = IncrementalJoinState::new();
+            // Preprocesses a chunk and adds it to the state
+            let append = |state: &mut IncrementalJoinState,
+                          side: JoinSide,
+                          (t, value): (Type, Value)|
+             -> Result<()> {
+                let key_header = match side {
+                    JoinSide::First => "a",
+                    JoinSide::Second => "k",
+                };
+                let preprocessing_c = get_psi_preprocessing_context(
+                    t,
+                    vec![key_header.to_owned()],
+                    IOStatus::Party(0),
+                    PsiConfig::default(),
+                    inline_config.clone(),
+                )?;
+                let preprocessing_g = preprocessing_c.get_main_graph()?;
+                state.append(
+                    side,
+                    PreprocessedDatabase::new(
+                        preprocessing_g.get_output_node()?.get_type()?,
+                        random_evaluate(preprocessing_g, vec![value])?,
+                    )?,
+                );
+                Ok(())
+            };
+            // Returns the new rows of the join
+            let join = |state: &IncrementalJoinState,
+                        side: JoinSide,
+                        (t, value): (Type, Value)|
+             -> Result<Vec<(String, Vec<u64>)>> {
+                let other_side = match side {
+                    JoinSide::First => JoinSide::Second,
+                    JoinSide::Second => JoinSide::First,
+                };
+                let c = get_incremental_psi_context(
+                    t,
+                    IOStatus::Party(1),
+                    side,
+                    state.get_chunk_types(other_side),
+                    headers.clone(),
+                    vec![IOStatus::Party(0)],
+                    PsiConfig::default(),
+                    inline_config.clone(),
+                )?;
+                let g = c.get_main_graph()?;
+                let column_types = match g.get_output_node()?.get_type()? {
+                    Type::NamedTuple(column_types) => column_types,
+                    _ => panic!("Join must be a named tuple"),
+                };
+                let mut inputs = vec![value];
+                inputs.extend(state.get_chunk_values(other_side));
+                let result = random_evaluate(g, inputs)?;
+                let mut columns = vec![];
+                for ((header, t), column) in column_types.into_iter().zip(result.to_vector()?) {
+                    columns.push((header, column.to_flattened_array_u64((*t).clone())?));
+                }
+                Ok(columns)
+            };
+            let expected = |columns: Vec<Vec<u64>>| -> Vec<(String, Vec<u64>)> {
+                [NULL_HEADER, "a", "w", "v"]
+                    .iter()
+                    .map(|header| header.to_string())
+                    .zip(columns)
+                    .collect()
+            };
+
+            let y0 = database(
+                4,
+                vec![
+                    (NULL_HEADER, vec![1, 1, 1, 1], BIT),
+                    ("k", vec![1, 2, 3, 4], INT32),
+                    ("v", vec![10, 20, 30, 40], INT32),
+                ],
+            )?;
+            append(&mut state, JoinSide::Second, y0)?;
+
+            let x0 = database(
+                3,
+                vec![
+                    (NULL_HEADER, vec![1, 1, 1], BIT),
+                    ("a", vec![2, 5, 4], INT32),
+                    ("w", vec![100, 200, 300], INT16),
+                ],
+            )?;
+            assert_eq!(
+                join(&state, JoinSide::First, x0.clone())?,
+                expected(vec![
+                    vec![1, 0, 1],
+                    vec![2, 0, 4],
+                    vec![100, 0, 300],
+                    vec![20, 0, 40]
+                ])
+            );
+            append(&mut state, JoinSide::First, x0)?;
+
+            // Rows appended to the second database are intersected with the first one, but the columns of the first database go first
+            let y1 = database(
+                3,
+                vec![
+                    ("k", vec![5, 6, 7], INT32),
+                    (NULL_HEADER, vec![1, 1, 0], BIT),
+                    ("v", vec![50, 60, 70], INT32),
+                ],
+            )?;
+            assert_eq!(
+                join(&state, JoinSide::Second, y1.clone())?,
+                expected(vec![
+                    vec![1, 0, 0],
+                    vec![5, 0, 0],
+                    vec![200, 0, 0],
+                    vec![50, 0, 0]
+                ])
+            );
+            append(&mut state, JoinSide::Second, y1)?;
+
+            // Intersections with two chunks are concatenated
+            let x1 = database(
+                3,
+                vec![
+                    (NULL_HEADER, vec![1, 1, 1], BIT),
+                    ("a", vec![7, 1, 6], INT32),
+                    ("w", vec![400, 500, 600], INT16),
+                ],
+            )?;
+            assert_eq!(
+                join(&state, JoinSide::First, x1.clone())?,
+                expected(vec![
+                    vec![0, 1, 0, 0, 0, 1],
+                    vec![0, 1, 0, 0, 0, 6],
+                    vec![0, 500, 0, 0, 0, 600],
+                    vec![0, 10, 0, 0, 0, 60]
+                ])
+            );
+            append(&mut state, JoinSide::First, x1.clone())?;
+            assert_eq!(state.get_chunks(JoinSide::First).len(), 2);
+
+            // State survives serialization
+            let state: IncrementalJoinState =
+                serde_json::from_str(&serde_json::to_string(&state)?)?;
+            assert_eq!(state.get_chunk_types(JoinSide::Second).len(), 2);
+
+            // At least one chunk of the other database is needed
+            assert!(get_incremental_psi_context(
+                x1.0,
+                IOStatus::Party(1),
+                JoinSide::First,
+                vec![],
+                headers.clone(),
+                vec![],
+                PsiConfig::default(),
+                inline_config.clone(),
+            )
+            .is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
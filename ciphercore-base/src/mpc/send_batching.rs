@@ -0,0 +1,319 @@
+//! Batching of messages sent between the same parties in the same communication round.
+//!
+//! Compiled protocols often send many small values at once, e.g. every column of a database is sent separately by the permutation and duplication protocols of [set intersection](crate::graphs::Graph::set_intersection).
+//! Each node annotated with [NodeAnnotation::Send] results in a separate network message, so grouping such nodes reduces the number of messages without changing the number of rounds.
+use crate::errors::Result;
+use crate::graphs::{
+    copy_node_name, copy_node_provenance, create_context, Context, Graph, Node, NodeAnnotation,
+    Operation,
+};
+use crate::mpc::preprocessing::copy_node;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+
+/// Descriptor of messages sent from one party to another in the same communication round.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferBatch {
+    /// Communication round starting from 1, i.e. the maximal number of nodes annotated with `Send` on a path to the sent nodes (inclusive).
+    pub round: u64,
+    /// Sending party.
+    pub sender: u64,
+    /// Receiving party.
+    pub receiver: u64,
+    /// IDs of the sent nodes in the main graph in the order of their appearance.
+    pub node_ids: Vec<u64>,
+}
+
+/// Returns the sender and the receiver of a node that can be batched with other nodes,
+/// i.e. a NOP node whose only annotation is `Send`.
+fn get_batchable_transfer(node: &Node) -> Result<Option<(u64, u64)>> {
+    if let Operation::NOP = node.get_operation() {
+        if let [NodeAnnotation::Send(sender, receiver)] = node.get_annotations()?[..] {
+            return Ok(Some((sender, receiver)));
+        }
+    }
+    Ok(None)
+}
+
+/// Computes the communication round of every node of a fully inlined graph.
+fn get_rounds(graph: &Graph) -> Result<Vec<u64>> {
+    let mut rounds: Vec<u64> = vec![];
+    for node in graph.get_nodes() {
+        if !node.get_graph_dependencies().is_empty() {
+            return Err(runtime_error!(
+                "Graph must be fully inlined to batch messages"
+            ));
+        }
+        let mut round = node
+            .get_node_dependencies()
+            .iter()
+            .map(|dep| rounds[dep.get_id() as usize])
+            .max()
+            .unwrap_or(0);
+        let has_send = node
+            .get_annotations()?
+            .iter()
+            .any(|annotation| matches!(annotation, NodeAnnotation::Send(_, _)));
+        if has_send {
+            round += 1;
+        }
+        rounds.push(round);
+    }
+    Ok(rounds)
+}
+
+fn get_graph_transfer_batches(graph: &Graph, rounds: &[u64]) -> Result<Vec<TransferBatch>> {
+    let mut batches: Vec<TransferBatch> = vec![];
+    let mut positions = HashMap::new();
+    for node in graph.get_nodes() {
+        if let Some((sender, receiver)) = get_batchable_transfer(&node)? {
+            let round = rounds[node.get_id() as usize];
+            let position = *positions
+                .entry((round, sender, receiver))
+                .or_insert_with(|| {
+                    batches.push(TransferBatch {
+                        round,
+                        sender,
+                        receiver,
+                        node_ids: vec![],
+                    });
+                    batches.len() - 1
+                });
+            batches[position].node_ids.push(node.get_id());
+        }
+    }
+    batches.sort_by_key(|batch| batch.round);
+    Ok(batches)
+}
+
+/// Groups the messages of the main graph of a compiled context by communication round, sender and receiver.
+///
+/// Only NOP nodes, whose only annotation is `Send`, are grouped; other nodes annotated with `Send` (e.g. broadcasts to several parties) are sent separately.
+/// Nodes of one batch don't depend on each other, so they can be sent together.
+///
+/// # Arguments
+///
+/// `context` - compiled and fully inlined context
+///
+/// # Returns
+///
+/// Batches sorted by round
+pub fn get_transfer_batches(context: Context) -> Result<Vec<TransferBatch>> {
+    context.check_finalized()?;
+    let graph = context.get_main_graph()?;
+    let rounds = get_rounds(&graph)?;
+    get_graph_transfer_batches(&graph, &rounds)
+}
+
+/// Rewrites the main graph of a compiled context, such that every batch of messages returned by [get_transfer_batches] is sent as a single message.
+///
+/// Values of a batch are combined into a tuple, which is sent by one NOP node annotated with `Send` and then split back.
+/// Any evaluator or runtime processing `Send` annotations thus transfers every batch at once.
+/// The number of communication rounds and the amount of sent data don't change.
+///
+/// # Arguments
+///
+/// `context` - compiled and fully inlined context
+///
+/// # Returns
+///
+/// New context with batched messages
+pub fn batch_send_messages(context: Context) -> Result<Context> {
+    context.check_finalized()?;
+    let graph = context.get_main_graph()?;
+    let rounds = get_rounds(&graph)?;
+    let batches = get_graph_transfer_batches(&graph, &rounds)?;
+    let max_round = rounds.iter().copied().max().unwrap_or(0) as usize;
+    // Batches are sent at the beginning of their round, when all their dependencies are computed.
+    // Other nodes of a round may depend on the sent values and are copied afterwards in the original order.
+    let mut batches_per_round = vec![vec![]; max_round + 1];
+    let mut batched_ids = vec![false; rounds.len()];
+    for batch in batches {
+        for id in &batch.node_ids {
+            batched_ids[*id as usize] = true;
+        }
+        batches_per_round[batch.round as usize].push(batch);
+    }
+    let graph_nodes = graph.get_nodes();
+    let mut nodes_per_round = vec![vec![]; max_round + 1];
+    for node in graph_nodes.iter().cloned() {
+        if !batched_ids[node.get_id() as usize] {
+            nodes_per_round[rounds[node.get_id() as usize] as usize].push(node);
+        }
+    }
+
+    let out_context = create_context()?;
+    let out_graph = out_context.create_graph()?;
+    for annotation in graph.get_annotations()? {
+        out_graph.add_annotation(annotation)?;
+    }
+    let mut mapping = HashMap::new();
+    for (round_batches, round_nodes) in batches_per_round.iter().zip(nodes_per_round) {
+        for batch in round_batches {
+            let nodes: Vec<Node> = batch
+                .node_ids
+                .iter()
+                .map(|id| graph_nodes[*id as usize].clone())
+                .collect();
+            if let [node] = &nodes[..] {
+                let new_node = copy_node(node, &out_graph, &mapping)?;
+                mapping.insert(node.get_id(), new_node);
+                continue;
+            }
+            let values = nodes
+                .iter()
+                .map(|node| mapping[&node.get_node_dependencies()[0].get_id()].clone())
+                .collect();
+            let sent = out_graph.create_tuple(values)?.nop()?;
+            sent.add_annotation(NodeAnnotation::Send(batch.sender, batch.receiver))?;
+            for (i, node) in nodes.iter().enumerate() {
+                let new_node = sent.tuple_get(i as u64)?;
+                copy_node_name(node.clone(), new_node.clone())?;
+                copy_node_provenance(node.clone(), new_node.clone())?;
+                mapping.insert(node.get_id(), new_node);
+            }
+        }
+        for node in round_nodes {
+            let new_node = copy_node(&node, &out_graph, &mapping)?;
+            mapping.insert(node.get_id(), new_node);
+        }
+    }
+    mapping[&graph.get_output_node()?.get_id()].set_as_output()?;
+    out_graph.finalize()?.set_as_main()?;
+    out_context.finalize()?;
+    Ok(out_context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{array_type, scalar_type, INT32};
+    use crate::data_values::Value;
+    use crate::evaluators::random_evaluate;
+    use crate::inline::inline_ops::{InlineConfig, InlineMode};
+    use crate::mpc::cost::estimate;
+    use crate::mpc::mpc_compiler::{prepare_for_mpc_evaluation, IOStatus};
+
+    #[test]
+    fn test_transfer_batches() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let a = g.input(scalar_type(INT32))?;
+            let b = g.input(scalar_type(INT32))?;
+            let send = |node: &Node, sender: u64, receiver: u64| -> Result<Node> {
+                node.nop()?
+                    .add_annotation(NodeAnnotation::Send(sender, receiver))
+            };
+            let a_sent = send(&a, 0, 1)?;
+            let b_sent = send(&b, 0, 1)?;
+            let a_sent_back = send(&a, 1, 0)?;
+            let sum = a_sent.add(b_sent)?;
+            let sum_sent = send(&sum, 0, 1)?;
+            // Broadcasts are not batched but still take a round
+            let broadcast = b.nop()?;
+            broadcast.add_annotation(NodeAnnotation::Send(0, 1))?;
+            broadcast.add_annotation(NodeAnnotation::Send(0, 2))?;
+            g.create_tuple(vec![sum_sent, a_sent_back, broadcast])?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let batches = get_transfer_batches(c.clone())?;
+            assert_eq!(
+                batches,
+                vec![
+                    TransferBatch {
+                        round: 1,
+                        sender: 0,
+                        receiver: 1,
+                        node_ids: vec![2, 3],
+                    },
+                    TransferBatch {
+                        round: 1,
+                        sender: 1,
+                        receiver: 0,
+                        node_ids: vec![4],
+                    },
+                    TransferBatch {
+                        round: 2,
+                        sender: 0,
+                        receiver: 1,
+                        node_ids: vec![6],
+                    },
+                ]
+            );
+
+            let batched = batch_send_messages(c.clone())?;
+            let cost = estimate(c)?;
+            let batched_cost = estimate(batched.clone())?;
+            assert_eq!(batched_cost.rounds, cost.rounds);
+            assert_eq!(batched_cost.bytes_sent, cost.bytes_sent);
+            assert_eq!(batched_cost.messages, cost.messages - 1);
+            let result = random_evaluate(
+                batched.get_main_graph()?,
+                vec![Value::from_scalar(2, INT32)?, Value::from_scalar(3, INT32)?],
+            )?
+            .to_vector()?;
+            assert_eq!(result[0].to_i32(INT32)?, 5);
+            assert_eq!(result[1].to_i32(INT32)?, 2);
+            assert_eq!(result[2].to_i32(INT32)?, 3);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_batch_compiled() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![4], INT32);
+            let a = g.input(t.clone())?;
+            let b = g.input(t.clone())?;
+            let x = g.input(t.clone())?;
+            let y = g.input(t.clone())?;
+            g.create_tuple(vec![a.multiply(b)?, x.multiply(y)?])?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let compiled = prepare_for_mpc_evaluation(
+                c,
+                vec![vec![
+                    IOStatus::Party(0),
+                    IOStatus::Party(1),
+                    IOStatus::Party(2),
+                    IOStatus::Party(0),
+                ]],
+                vec![vec![IOStatus::Party(1)]],
+                InlineConfig {
+                    default_mode: InlineMode::Simple,
+                    ..Default::default()
+                },
+            )?;
+            let batched = batch_send_messages(compiled.clone())?;
+            let cost = estimate(compiled)?;
+            let batched_cost = estimate(batched.clone())?;
+            assert_eq!(batched_cost.rounds, cost.rounds);
+            assert_eq!(batched_cost.bytes_sent, cost.bytes_sent);
+            assert!(batched_cost.messages < cost.messages);
+            let batches = get_transfer_batches(batched.clone())?;
+            assert_eq!(batches.len() as u64, batched_cost.messages);
+            assert!(batches.iter().all(|batch| batch.node_ids.len() == 1));
+
+            let inputs = [[1, 2, 3, 4], [5, 6, 7, 8], [-1, 0, 1, 2], [3, 3, 3, 3]]
+                .iter()
+                .map(|entries| Value::from_flattened_array(entries, INT32))
+                .collect::<Result<Vec<Value>>>()?;
+            let result = random_evaluate(batched.get_main_graph()?, inputs)?.to_vector()?;
+            assert_eq!(
+                result[0].to_flattened_array_i32(t.clone())?,
+                vec![5, 12, 21, 32]
+            );
+            assert_eq!(result[1].to_flattened_array_i32(t)?, vec![-3, 0, 3, 6]);
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
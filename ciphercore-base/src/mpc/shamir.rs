@@ -0,0 +1,947 @@
+//! Compilation of graphs into a threshold protocol with Shamir secret sharing among `n` parties.
+//!
+//! A private value of a scalar type with modulus `2^k` is shared with a random polynomial `P` of degree `t` (the threshold)
+//! such that `P(0)` is the value and `P(a_i)` is the share of party `i`, so any `t` parties learn nothing about the value,
+//! while any `t + 1` parties can reconstruct it via Lagrange interpolation.
+//! Since `Z_{2^k}` isn't a field, polynomials are defined over the Galois ring `GR(2^k, d) = Z_{2^k}[x] / f(x)`,
+//! where `f` is a monic polynomial of degree `d` irreducible modulo 2 (see [Abspoel et al.](https://eprint.iacr.org/2019/832.pdf)).
+//! The evaluation points `a_i` are polynomials with binary coefficients, so their differences are invertible
+//! and `d` is the smallest integer with `2^d > n`.
+//! Bits are shared in the same way over the binary field `GF(2^d)`.
+//!
+//! An element of the Galois ring is represented by `d` nodes containing its coefficients, so a share of party `i`
+//! is a vector of `d` nodes of the same type as the shared value.
+//! Linear operations are computed locally on the coefficients of shares.
+//! Products of private values (Multiply, Dot, Matmul, Gemm) are computed locally, which doubles the degree of the sharing polynomial,
+//! and then the degree is reduced by resharing as in [Gennaro, Rabin and Rabin](https://doi.org/10.1145/277697.277716).
+//! Thus, the protocol is secure against `t` semi-honest parties as long as `n >= 2t + 1`.
+//!
+//! Operations mixing arithmetic and binary shares (A2B, B2A, MixedMultiply of private bits), Truncate, random values and SetIntersection aren't supported by this backend.
+//!
+//! [ShamirSharing] also provides helpers to convert additive shares (e.g. the shares of the 2-out-of-3 replicated sharing of ABY3) to Shamir shares and back.
+use crate::data_types::{scalar_type, ScalarType, Type};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{copy_node_name, create_context, Context, Graph, Node, Operation};
+use crate::mpc::mpc_compiler::IOStatus;
+
+use super::mpc_two_party::send;
+
+use std::collections::HashMap;
+
+/// Exponents of the lower terms of monic polynomials of degree `d = 2, ..., 8` irreducible modulo 2,
+/// e.g. `[0, 1]` for `d = 2` stands for `x^2 + x + 1`.
+const IRREDUCIBLE_POLYNOMIALS: [&[usize]; 7] = [
+    &[0, 1],
+    &[0, 1],
+    &[0, 1],
+    &[0, 2],
+    &[0, 1],
+    &[0, 1],
+    &[0, 1, 3, 4],
+];
+
+/// Maximal number of parties supported by [ShamirSharing]
+pub const MAX_SHAMIR_PARTIES: u64 = (1 << 8) - 1;
+
+/// Galois ring `GR(2^128, d)`; its elements are reduced modulo `2^k` to get elements of `GR(2^k, d)`.
+#[derive(Clone, Debug)]
+struct GaloisRing {
+    degree: usize,
+    // Exponents of the lower terms of the irreducible polynomial, i.e. x^d = -sum(x^e)
+    lower_terms: &'static [usize],
+}
+
+impl GaloisRing {
+    fn for_parties(parties: u64) -> Result<Self> {
+        let mut degree = 2;
+        while (1 << degree) <= parties {
+            degree += 1;
+        }
+        if degree - 2 >= IRREDUCIBLE_POLYNOMIALS.len() {
+            return Err(runtime_error!(
+                "Shamir sharing supports at most {} parties, but {} are given",
+                MAX_SHAMIR_PARTIES,
+                parties
+            ));
+        }
+        Ok(GaloisRing {
+            degree,
+            lower_terms: IRREDUCIBLE_POLYNOMIALS[degree - 2],
+        })
+    }
+
+    /// Reduces a polynomial of degree less than `2d - 1` modulo the irreducible polynomial.
+    fn reduce<T: Clone>(
+        &self,
+        mut coefficients: Vec<T>,
+        subtract: &dyn Fn(&T, &T) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        for k in (self.degree..coefficients.len()).rev() {
+            for e in self.lower_terms {
+                let i = k - self.degree + e;
+                coefficients[i] = subtract(&coefficients[i], &coefficients[k])?;
+            }
+        }
+        coefficients.truncate(self.degree);
+        Ok(coefficients)
+    }
+
+    fn one(&self) -> Vec<u128> {
+        let mut one = vec![0; self.degree];
+        one[0] = 1;
+        one
+    }
+
+    /// Evaluation point of a party, i.e. the polynomial whose coefficients are the bits of `party + 1`.
+    fn point(&self, party: u64) -> Vec<u128> {
+        (0..self.degree)
+            .map(|i| ((party + 1) >> i) as u128 & 1)
+            .collect()
+    }
+
+    fn subtract(&self, a: &[u128], b: &[u128]) -> Vec<u128> {
+        a.iter().zip(b).map(|(x, y)| x.wrapping_sub(*y)).collect()
+    }
+
+    fn multiply(&self, a: &[u128], b: &[u128]) -> Vec<u128> {
+        let mut product = vec![0u128; 2 * self.degree - 1];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                product[i + j] = product[i + j].wrapping_add(x.wrapping_mul(*y));
+            }
+        }
+        self.reduce(product, &|x, y| Ok(x.wrapping_sub(*y)))
+            .expect("Reduction of integers can't fail")
+    }
+
+    /// Inverts an element by brute force modulo 2 and then lifts the inverse by Newton's iteration `b = b(2 - ab)`,
+    /// which doubles the number of correct bits.
+    fn inverse(&self, a: &[u128]) -> Result<Vec<u128>> {
+        let mut inverse = None;
+        for candidate in 0..(1u128 << self.degree) {
+            let b: Vec<u128> = (0..self.degree).map(|i| (candidate >> i) & 1).collect();
+            let product: Vec<u128> = self.multiply(a, &b).iter().map(|x| x & 1).collect();
+            if product == self.one() {
+                inverse = Some(b);
+                break;
+            }
+        }
+        let mut b =
+            inverse.ok_or_else(|| runtime_error!("Galois ring element isn't invertible"))?;
+        let two: Vec<u128> = self.one().iter().map(|x| x * 2).collect();
+        for _ in 0..7 {
+            b = self.multiply(&b, &self.subtract(&two, &self.multiply(a, &b)));
+        }
+        Ok(b)
+    }
+
+    /// Returns `l_i = prod_{j != i} a_j / (a_j - a_i)` for every party `i` of a given set,
+    /// so that `P(0) = sum_i l_i * P(a_i)` for every polynomial `P` of degree less than the size of the set.
+    fn lagrange_coefficients(&self, parties: &[u64]) -> Result<Vec<Vec<u128>>> {
+        let mut coefficients = vec![];
+        for i in parties {
+            let mut coefficient = self.one();
+            for j in parties {
+                if i != j {
+                    let difference = self.subtract(&self.point(*j), &self.point(*i));
+                    coefficient = self.multiply(&coefficient, &self.point(*j));
+                    coefficient = self.multiply(&coefficient, &self.inverse(&difference)?);
+                }
+            }
+            coefficients.push(coefficient);
+        }
+        Ok(coefficients)
+    }
+}
+
+fn get_mask(st: &ScalarType) -> u128 {
+    let bits = st.size_in_bits();
+    if bits == 128 {
+        u128::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+fn scalar_constant(g: &Graph, c: u128, st: ScalarType) -> Result<Node> {
+    let value = if st.size_in_bits() == 128 {
+        Value::from_flattened_array_u128(&[c], st.clone())?
+    } else {
+        Value::from_scalar(c as u64, st.clone())?
+    };
+    g.constant(scalar_type(st), value)
+}
+
+fn zero(g: &Graph, t: Type) -> Result<Node> {
+    g.constant(t.clone(), Value::zero_of_type(t))
+}
+
+/// Shamir shares of a value: the share of party `i` is given by the coefficients of an element of a Galois ring
+/// (see the [module documentation](self)).
+#[derive(Clone)]
+pub struct ShamirShares {
+    shares: Vec<Vec<Node>>,
+}
+
+impl ShamirShares {
+    /// Returns the coefficient nodes of the share of a given party.
+    pub fn get_share(&self, party: u64) -> Result<Vec<Node>> {
+        self.shares
+            .get(party as usize)
+            .cloned()
+            .ok_or_else(|| runtime_error!("Party {} doesn't hold a Shamir share", party))
+    }
+}
+
+/// Shamir secret sharing among a given number of parties with a given threshold.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{scalar_type, INT32};
+/// # use ciphercore_base::data_values::Value;
+/// # use ciphercore_base::evaluators::random_evaluate;
+/// # use ciphercore_base::mpc::shamir::ShamirSharing;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let sharing = ShamirSharing::new(5, 2).unwrap();
+/// let x = g.input(scalar_type(INT32)).unwrap();
+/// // Party 0 shares its input, and the value is reconstructed by party 4
+/// let shares = sharing.share(&g, x, 0).unwrap();
+/// sharing.reveal(&g, &shares, &[4]).unwrap().set_as_output().unwrap();
+/// g.finalize().unwrap().set_as_main().unwrap();
+/// c.finalize().unwrap();
+/// let result = random_evaluate(g, vec![Value::from_scalar(-7, INT32).unwrap()]).unwrap();
+/// assert_eq!(result.to_i32(INT32).unwrap(), -7);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ShamirSharing {
+    parties: u64,
+    threshold: u64,
+    ring: GaloisRing,
+}
+
+impl ShamirSharing {
+    /// Creates a Shamir sharing scheme.
+    ///
+    /// Multiplication of private values requires an honest majority, i.e. `parties >= 2 * threshold + 1`.
+    ///
+    /// # Arguments
+    ///
+    /// * `parties` - number of parties holding shares, from 3 to [MAX_SHAMIR_PARTIES]
+    /// * `threshold` - maximal number of parties that learn nothing about shared values, at least 1
+    ///
+    /// # Returns
+    ///
+    /// New sharing scheme
+    pub fn new(parties: u64, threshold: u64) -> Result<Self> {
+        if threshold == 0 || parties < 2 * threshold + 1 {
+            return Err(runtime_error!(
+                "Shamir sharing requires 1 <= threshold and 2 * threshold + 1 <= parties, but {}-out-of-{} is given",
+                threshold,
+                parties
+            ));
+        }
+        Ok(ShamirSharing {
+            parties,
+            threshold,
+            ring: GaloisRing::for_parties(parties)?,
+        })
+    }
+
+    pub fn get_parties(&self) -> u64 {
+        self.parties
+    }
+
+    pub fn get_threshold(&self) -> u64 {
+        self.threshold
+    }
+
+    /// Multiplies an element of the Galois ring by a public element known at compile time.
+    fn multiply_by_constant(&self, g: &Graph, a: &[Node], c: &[u128]) -> Result<Vec<Node>> {
+        let st = a[0].get_type()?.get_scalar_type();
+        let mask = get_mask(&st);
+        // Column u contains the coefficients of c * x^u
+        let mut columns = vec![];
+        let mut monomial = self.ring.one();
+        for _ in 0..self.ring.degree {
+            columns.push(self.ring.multiply(c, &monomial));
+            monomial.rotate_right(1);
+        }
+        let mut result = vec![];
+        for w in 0..self.ring.degree {
+            let mut terms = vec![];
+            for (u, column) in columns.iter().enumerate() {
+                match column[w] & mask {
+                    0 => (),
+                    1 => terms.push(a[u].clone()),
+                    factor => terms.push(a[u].multiply(scalar_constant(g, factor, st.clone())?)?),
+                }
+            }
+            let mut sum = match terms.pop() {
+                Some(term) => term,
+                None => zero(g, a[0].get_type()?)?,
+            };
+            for term in terms {
+                sum = sum.add(term)?;
+            }
+            result.push(sum);
+        }
+        Ok(result)
+    }
+
+    fn add_elements(&self, a: &[Node], b: &[Node]) -> Result<Vec<Node>> {
+        a.iter().zip(b).map(|(x, y)| x.add(y.clone())).collect()
+    }
+
+    /// Returns the coefficients of the Galois ring element `(x, 0, ..., 0)`.
+    fn embed(&self, g: &Graph, x: Node) -> Result<Vec<Node>> {
+        let zero = zero(g, x.get_type()?)?;
+        let mut element = vec![x];
+        element.extend(vec![zero; self.ring.degree - 1]);
+        Ok(element)
+    }
+
+    /// Shares a public value as a constant polynomial.
+    pub fn share_public(&self, g: &Graph, x: Node) -> Result<ShamirShares> {
+        let element = self.embed(g, x)?;
+        Ok(ShamirShares {
+            shares: vec![element; self.parties as usize],
+        })
+    }
+
+    /// Shares a value of an owner with a random polynomial of degree equal to the threshold.
+    ///
+    /// # Arguments
+    ///
+    /// * `g` - graph where sharing nodes are added
+    /// * `x` - scalar or array known to the owner
+    /// * `owner` - party that samples the polynomial and sends shares to other parties
+    ///
+    /// # Returns
+    ///
+    /// Shares of `x`
+    pub fn share(&self, g: &Graph, x: Node, owner: u64) -> Result<ShamirShares> {
+        if owner >= self.parties {
+            return Err(runtime_error!("Invalid owner of a shared value: {}", owner));
+        }
+        let element = self.embed(g, x)?;
+        self.share_element(g, element, owner)
+    }
+
+    /// Applies a linear operation to shared values.
+    fn apply_linear(&self, op: &Operation, dependencies: &[ShamirShares]) -> Result<ShamirShares> {
+        let g = dependencies[0].shares[0][0].get_graph();
+        let mut shares = vec![];
+        for party in 0..self.parties as usize {
+            let mut share = vec![];
+            for coefficient in 0..self.ring.degree {
+                let nodes = dependencies
+                    .iter()
+                    .map(|d| d.shares[party][coefficient].clone())
+                    .collect();
+                share.push(g.add_node(nodes, vec![], op.clone())?);
+            }
+            shares.push(share);
+        }
+        Ok(ShamirShares { shares })
+    }
+
+    /// Applies a bilinear operation to a private value and a public one.
+    fn apply_public(
+        &self,
+        x: &ShamirShares,
+        public: Node,
+        op: &Operation,
+        public_first: bool,
+    ) -> Result<ShamirShares> {
+        let g = public.get_graph();
+        let mut shares = vec![];
+        for share in &x.shares {
+            let mut new_share = vec![];
+            for node in share {
+                let arguments = if public_first {
+                    vec![public.clone(), node.clone()]
+                } else {
+                    vec![node.clone(), public.clone()]
+                };
+                new_share.push(g.add_node(arguments, vec![], op.clone())?);
+            }
+            shares.push(new_share);
+        }
+        Ok(ShamirShares { shares })
+    }
+
+    /// Multiplies two private values: every party multiplies its shares locally,
+    /// and the resulting sharing of degree `2t` is reshared by the first `2t + 1` parties and recombined.
+    ///
+    /// `op` must be bilinear, e.g. Multiply, Dot or Matmul.
+    fn multiply(
+        &self,
+        g: &Graph,
+        x: &ShamirShares,
+        y: &ShamirShares,
+        op: &Operation,
+    ) -> Result<ShamirShares> {
+        let reshared_parties: Vec<u64> = (0..2 * self.threshold + 1).collect();
+        let lagrange_coefficients = self.ring.lagrange_coefficients(&reshared_parties)?;
+        let mut result: Option<ShamirShares> = None;
+        for (party, lagrange_coefficient) in reshared_parties.iter().zip(lagrange_coefficients) {
+            let a = &x.shares[*party as usize];
+            let b = &y.shares[*party as usize];
+            let mut product: Vec<Option<Node>> = vec![None; 2 * self.ring.degree - 1];
+            for (i, a_i) in a.iter().enumerate() {
+                for (j, b_j) in b.iter().enumerate() {
+                    let term = g.add_node(vec![a_i.clone(), b_j.clone()], vec![], op.clone())?;
+                    product[i + j] = Some(match product[i + j].take() {
+                        Some(sum) => sum.add(term)?,
+                        None => term,
+                    });
+                }
+            }
+            let product: Vec<Node> = product.into_iter().flatten().collect();
+            let product = self.ring.reduce(product, &|a, b| a.subtract(b.clone()))?;
+            // Each party shares its product share multiplied by its Lagrange coefficient,
+            // so that the sum of these sharings is a sharing of the product of degree t.
+            let weighted_product = self.multiply_by_constant(g, &product, &lagrange_coefficient)?;
+            let reshared = self.share_element(g, weighted_product, *party)?;
+            result = Some(match result {
+                Some(sum) => self.apply_linear(&Operation::Add, &[sum, reshared])?,
+                None => reshared,
+            });
+        }
+        Ok(result.unwrap())
+    }
+
+    /// Shares an element of the Galois ring known to the owner with a random polynomial of degree equal to the threshold.
+    fn share_element(&self, g: &Graph, element: Vec<Node>, owner: u64) -> Result<ShamirShares> {
+        let t = element[0].get_type()?;
+        let mut shares = vec![];
+        let mut random_coefficients = vec![];
+        for _ in 0..self.threshold {
+            let mut coefficient = vec![];
+            for _ in 0..self.ring.degree {
+                coefficient.push(g.random(t.clone())?);
+            }
+            random_coefficients.push(coefficient);
+        }
+        for party in 0..self.parties {
+            let point = self.ring.point(party);
+            let mut power = point.clone();
+            let mut share = element.clone();
+            for coefficient in &random_coefficients {
+                let term = self.multiply_by_constant(g, coefficient, &power)?;
+                share = self.add_elements(&share, &term)?;
+                power = self.ring.multiply(&power, &point);
+            }
+            if party != owner {
+                share = share
+                    .into_iter()
+                    .map(|node| send(node, owner, party))
+                    .collect::<Result<_>>()?;
+            }
+            shares.push(share);
+        }
+        Ok(ShamirShares { shares })
+    }
+
+    /// Reveals a shared value to given parties.
+    ///
+    /// The first `t + 1` parties send their shares to the first receiver, which interpolates the value and forwards it to the other receivers.
+    ///
+    /// # Arguments
+    ///
+    /// * `g` - graph where reconstruction nodes are added
+    /// * `x` - shares of a scalar or an array
+    /// * `receivers` - parties obtaining the value
+    ///
+    /// # Returns
+    ///
+    /// Node containing the revealed value
+    pub fn reveal(&self, g: &Graph, x: &ShamirShares, receivers: &[u64]) -> Result<Node> {
+        if receivers.is_empty() || receivers.iter().any(|r| *r >= self.parties) {
+            return Err(runtime_error!(
+                "Shamir shares must be revealed to at least one party of the sharing scheme"
+            ));
+        }
+        let additive_shares = self.to_additive(g, x)?;
+        let mut value: Option<Node> = None;
+        for (party, share) in additive_shares.into_iter().enumerate() {
+            let share = if party as u64 != receivers[0] {
+                send(share, party as u64, receivers[0])?
+            } else {
+                share
+            };
+            value = Some(match value {
+                Some(sum) => sum.add(share)?,
+                None => share,
+            });
+        }
+        let mut value = value.unwrap();
+        for receiver in &receivers[1..] {
+            value = send(value, receivers[0], *receiver)?;
+        }
+        Ok(value)
+    }
+
+    /// Converts Shamir shares to additive shares held by the first `t + 1` parties,
+    /// i.e. the sum of the returned nodes is equal to the shared value.
+    ///
+    /// Party `i` multiplies its share by its Lagrange coefficient and takes the constant coefficient of the product.
+    ///
+    /// # Arguments
+    ///
+    /// * `g` - graph where conversion nodes are added
+    /// * `x` - shares of a scalar or an array
+    ///
+    /// # Returns
+    ///
+    /// Additive shares; the share `i` is known to party `i`
+    pub fn to_additive(&self, g: &Graph, x: &ShamirShares) -> Result<Vec<Node>> {
+        let parties: Vec<u64> = (0..self.threshold + 1).collect();
+        let lagrange_coefficients = self.ring.lagrange_coefficients(&parties)?;
+        let mut additive_shares = vec![];
+        for (party, lagrange_coefficient) in parties.iter().zip(lagrange_coefficients) {
+            let weighted_share =
+                self.multiply_by_constant(g, &x.shares[*party as usize], &lagrange_coefficient)?;
+            additive_shares.push(weighted_share[0].clone());
+        }
+        Ok(additive_shares)
+    }
+
+    /// Converts additive shares to Shamir shares: every party shares its additive share, and the sharings are summed.
+    ///
+    /// The three shares of the 2-out-of-3 replicated sharing of ABY3 are additive shares,
+    /// where the share `i` is known to parties `i` and `i - 1`, so it can be passed as the additive share of party `i`.
+    ///
+    /// # Arguments
+    ///
+    /// * `g` - graph where conversion nodes are added
+    /// * `additive_shares` - scalars or arrays of the same type; the share `i` is known to party `i`
+    ///
+    /// # Returns
+    ///
+    /// Shamir shares of the sum of the additive shares
+    pub fn from_additive(&self, g: &Graph, additive_shares: &[Node]) -> Result<ShamirShares> {
+        if additive_shares.is_empty() || additive_shares.len() as u64 > self.parties {
+            return Err(runtime_error!(
+                "Invalid number of additive shares: {}",
+                additive_shares.len()
+            ));
+        }
+        let mut result: Option<ShamirShares> = None;
+        for (party, share) in additive_shares.iter().enumerate() {
+            let shares = self.share(g, share.clone(), party as u64)?;
+            result = Some(match result {
+                Some(sum) => self.apply_linear(&Operation::Add, &[sum, shares])?,
+                None => shares,
+            });
+        }
+        Ok(result.unwrap())
+    }
+}
+
+#[derive(Clone)]
+enum LoweredNode {
+    Public(Node),
+    Private(ShamirShares),
+}
+
+fn unsupported(op: &Operation) -> crate::errors::CiphercoreBaseError {
+    runtime_error!(
+        "Compilation to Shamir sharing for private {} is not supported",
+        op
+    )
+}
+
+/// Compiles the main graph of an instantiated and inlined context into a graph of the protocol with Shamir sharing.
+///
+/// # Arguments
+///
+/// * `context` - context without custom operations and graph calls
+/// * `input_parties` - statuses of the inputs of the main graph; shared inputs aren't supported
+/// * `output_parties` - parties that obtain the revealed output
+/// * `parties` - number of parties holding shares
+/// * `threshold` - threshold of the sharing, see [ShamirSharing::new]
+///
+/// # Returns
+///
+/// New context whose main graph returns the output of the original main graph
+pub(super) fn compile_to_shamir(
+    context: Context,
+    input_parties: Vec<IOStatus>,
+    output_parties: Vec<IOStatus>,
+    parties: u64,
+    threshold: u64,
+) -> Result<Context> {
+    context.check_finalized()?;
+    let sharing = ShamirSharing::new(parties, threshold)?;
+    let mut output_ids = vec![];
+    for status in &output_parties {
+        match status {
+            IOStatus::Party(id) if *id < parties => output_ids.push(*id),
+            _ => {
+                return Err(runtime_error!(
+                    "Output status should be a party ID of a computing party"
+                ))
+            }
+        }
+    }
+    let in_graph = context.get_main_graph()?;
+    let out_context = create_context()?;
+    let g = out_context.create_graph()?;
+    // Lowered nodes indexed by the IDs of the nodes of the input graph
+    let mut lowered: HashMap<u64, LoweredNode> = HashMap::new();
+    let mut input_id = 0;
+    for node in in_graph.get_nodes() {
+        let op = node.get_operation();
+        let dependencies: Vec<LoweredNode> = node
+            .get_node_dependencies()
+            .iter()
+            .map(|d| lowered[&d.get_id()].clone())
+            .collect();
+        let all_public = dependencies
+            .iter()
+            .all(|d| matches!(d, LoweredNode::Public(_)));
+        let new_node = match op.clone() {
+            Operation::Input(t) => {
+                let status = input_parties
+                    .get(input_id)
+                    .ok_or_else(|| runtime_error!("Not enough input statuses"))?;
+                input_id += 1;
+                let input = g.input(t)?;
+                copy_node_name(node.clone(), input.clone())?;
+                match status {
+                    IOStatus::Public => LoweredNode::Public(input),
+                    IOStatus::Party(id) if *id < parties => {
+                        LoweredNode::Private(sharing.share(&g, input, *id)?)
+                    }
+                    _ => {
+                        return Err(runtime_error!(
+                            "Compilation to Shamir sharing supports only public inputs and inputs of computing parties"
+                        ))
+                    }
+                }
+            }
+            Operation::Random(_) | Operation::RandomBeacon(_) => {
+                return Err(runtime_error!(
+                    "Compilation to Shamir sharing of random values is not supported"
+                ))
+            }
+            _ if all_public => {
+                if !node.get_graph_dependencies().is_empty() {
+                    return Err(runtime_error!(
+                        "Compilation to Shamir sharing requires an inlined context"
+                    ));
+                }
+                let public_dependencies = dependencies
+                    .iter()
+                    .map(|d| match d {
+                        LoweredNode::Public(x) => x.clone(),
+                        LoweredNode::Private(_) => unreachable!(),
+                    })
+                    .collect();
+                LoweredNode::Public(g.add_node(public_dependencies, vec![], op)?)
+            }
+            Operation::Multiply
+            | Operation::Dot
+            | Operation::Matmul
+            | Operation::Gemm(_, _)
+            | Operation::MixedMultiply => match (&dependencies[0], &dependencies[1]) {
+                (LoweredNode::Private(x), LoweredNode::Public(y)) => {
+                    LoweredNode::Private(sharing.apply_public(x, y.clone(), &op, false)?)
+                }
+                (LoweredNode::Public(x), LoweredNode::Private(y))
+                    if op != Operation::MixedMultiply =>
+                {
+                    LoweredNode::Private(sharing.apply_public(y, x.clone(), &op, true)?)
+                }
+                (LoweredNode::Private(x), LoweredNode::Private(y))
+                    if op != Operation::MixedMultiply =>
+                {
+                    LoweredNode::Private(sharing.multiply(&g, x, y, &op)?)
+                }
+                _ => return Err(unsupported(&op)),
+            },
+            Operation::Add
+            | Operation::Subtract
+            | Operation::PermuteAxes(_)
+            | Operation::TupleGet(_)
+            | Operation::NamedTupleGet(_)
+            | Operation::GetSlice(_)
+            | Operation::Reshape(_)
+            | Operation::Sum(_)
+            | Operation::Get(_)
+            | Operation::Repeat(_)
+            | Operation::CreateTuple
+            | Operation::CreateNamedTuple(_)
+            | Operation::Stack(_)
+            | Operation::Concatenate(_)
+            | Operation::Split(_, _)
+            | Operation::BroadcastTo(_)
+            | Operation::Tile(_)
+            | Operation::CumSum(_) => {
+                let shares = dependencies
+                    .iter()
+                    .map(|d| match d {
+                        LoweredNode::Public(x) => sharing.share_public(&g, x.clone()),
+                        LoweredNode::Private(shares) => Ok(shares.clone()),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                LoweredNode::Private(sharing.apply_linear(&op, &shares)?)
+            }
+            _ => return Err(unsupported(&op)),
+        };
+        lowered.insert(node.get_id(), new_node);
+    }
+    if input_id != input_parties.len() {
+        return Err(runtime_error!(
+            "Invalid number of input parties: {} expected, but {} found",
+            input_id,
+            input_parties.len()
+        ));
+    }
+    let output = match &lowered[&in_graph.get_output_node()?.get_id()] {
+        LoweredNode::Public(x) => x.clone(),
+        LoweredNode::Private(shares) => {
+            if output_ids.is_empty() {
+                return Err(runtime_error!(
+                    "Private outputs of Shamir sharing must be revealed to a computing party"
+                ));
+            }
+            sharing.reveal(&g, shares, &output_ids)?
+        }
+    };
+    output.set_as_output()?;
+    g.finalize()?;
+    out_context.set_main_graph(g)?;
+    out_context.finalize()?;
+    Ok(out_context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{array_type, BIT, INT32, INT64, UINT128, UINT8};
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::inline::inline_ops::{InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{compile_context_with_backend, MpcBackend};
+
+    fn compile(
+        c: Context,
+        input_parties: Vec<IOStatus>,
+        output_parties: Vec<IOStatus>,
+        parties: u64,
+        threshold: u64,
+    ) -> Result<Context> {
+        compile_context_with_backend(
+            c,
+            input_parties,
+            output_parties,
+            InlineConfig {
+                default_mode: InlineMode::Simple,
+                ..Default::default()
+            },
+            || SimpleEvaluator::new(None),
+            MpcBackend::Shamir { parties, threshold },
+        )
+    }
+
+    #[test]
+    fn test_galois_ring() {
+        || -> Result<()> {
+            for parties in [3, 7, 8, 100, MAX_SHAMIR_PARTIES] {
+                let ring = GaloisRing::for_parties(parties)?;
+                assert!(1 << ring.degree > parties);
+                // Every non-zero polynomial modulo 2 is invertible iff the reduction polynomial is irreducible
+                for value in 1..(1u128 << ring.degree) {
+                    let a: Vec<u128> = (0..ring.degree).map(|i| (value >> i) & 1).collect();
+                    let a_inverse = ring.inverse(&a)?;
+                    assert_eq!(ring.multiply(&a, &a_inverse), ring.one());
+                }
+                let points: Vec<u64> = (0..parties.min(9)).collect();
+                let lagrange_coefficients = ring.lagrange_coefficients(&points)?;
+                // Interpolation of the polynomial 5 + 3z at 0
+                let mut sum = vec![0u128; ring.degree];
+                for (party, coefficient) in points.iter().zip(lagrange_coefficients) {
+                    let mut evaluation = ring.multiply(&ring.point(*party), &[3]);
+                    evaluation[0] = evaluation[0].wrapping_add(5);
+                    let term = ring.multiply(&coefficient, &evaluation);
+                    sum = sum
+                        .iter()
+                        .zip(term)
+                        .map(|(x, y)| x.wrapping_add(y))
+                        .collect();
+                }
+                let mut expected = vec![0; ring.degree];
+                expected[0] = 5;
+                assert_eq!(sum, expected);
+            }
+            assert!(GaloisRing::for_parties(MAX_SHAMIR_PARTIES + 1).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        || -> Result<()> {
+            for (parties, threshold) in [(3, 1), (5, 2)] {
+                let c = create_context()?;
+                let g = c.create_graph()?;
+                let x = g.input(array_type(vec![2, 2], INT32))?;
+                let y = g.input(array_type(vec![2, 2], INT32))?;
+                let z = g.input(scalar_type(INT32))?;
+                let prod = x.multiply(y.clone())?.add(x.matmul(y)?)?;
+                prod.subtract(z)?
+                    .sum(vec![0])?
+                    .reshape(array_type(vec![2, 1], INT32))?
+                    .set_as_output()?;
+                g.finalize()?.set_as_main()?;
+                c.finalize()?;
+                let inputs = vec![
+                    Value::from_flattened_array(&[1, -2, 3, 4], INT32)?,
+                    Value::from_flattened_array(&[-5, 6, 7, 1 << 20], INT32)?,
+                    Value::from_scalar(10, INT32)?,
+                ];
+                let expected = random_evaluate(g, inputs.clone())?;
+                let compiled = compile(
+                    c,
+                    vec![
+                        IOStatus::Party(0),
+                        IOStatus::Party(parties - 1),
+                        IOStatus::Public,
+                    ],
+                    vec![IOStatus::Party(1), IOStatus::Party(2)],
+                    parties,
+                    threshold,
+                )?;
+                let result = random_evaluate(compiled.get_main_graph()?, inputs)?;
+                assert_eq!(result, expected);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_small_types() {
+        || -> Result<()> {
+            for st in [BIT, UINT8, INT64, UINT128] {
+                let c = create_context()?;
+                let g = c.create_graph()?;
+                let x = g.input(array_type(vec![3], st.clone()))?;
+                let y = g.input(array_type(vec![3], st.clone()))?;
+                x.multiply(y.clone())?
+                    .multiply(x)?
+                    .add(y)?
+                    .set_as_output()?;
+                g.finalize()?.set_as_main()?;
+                c.finalize()?;
+                let inputs = vec![
+                    Value::from_flattened_array(&[0, 1, 1], st.clone())?,
+                    Value::from_flattened_array(&[1, 1, 0], st.clone())?,
+                ];
+                let expected = random_evaluate(g, inputs.clone())?;
+                let compiled = compile(
+                    c,
+                    vec![IOStatus::Party(0), IOStatus::Party(1)],
+                    vec![IOStatus::Party(3)],
+                    7,
+                    3,
+                )?;
+                let result = random_evaluate(compiled.get_main_graph()?, inputs)?;
+                assert_eq!(result, expected);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_additive_conversion() {
+        || -> Result<()> {
+            let sharing = ShamirSharing::new(4, 1)?;
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![2], INT32);
+            let additive_shares = vec![g.input(t.clone())?, g.input(t.clone())?, g.input(t)?];
+            let shares = sharing.from_additive(&g, &additive_shares)?;
+            assert_eq!(shares.get_share(3)?.len(), 3);
+            assert!(shares.get_share(4).is_err());
+            let converted = sharing.to_additive(&g, &shares)?;
+            assert_eq!(converted.len(), 2);
+            let revealed = sharing.reveal(&g, &shares, &[2, 0])?;
+            g.create_tuple(vec![converted[0].add(converted[1].clone())?, revealed])?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let result = random_evaluate(
+                g,
+                vec![
+                    Value::from_flattened_array(&[1, 2], INT32)?,
+                    Value::from_flattened_array(&[10, -20], INT32)?,
+                    Value::from_flattened_array(&[100, 200], INT32)?,
+                ],
+            )?;
+            let expected = Value::from_flattened_array(&[111, 182], INT32)?;
+            assert_eq!(result, Value::from_vector(vec![expected.clone(), expected]));
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        || -> Result<()> {
+            assert!(ShamirSharing::new(4, 2).is_err());
+            assert!(ShamirSharing::new(3, 0).is_err());
+            let build = || -> Result<Context> {
+                let c = create_context()?;
+                let g = c.create_graph()?;
+                let x = g.input(scalar_type(INT32))?;
+                x.truncate(4)?.set_as_output()?;
+                g.finalize()?.set_as_main()?;
+                c.finalize()?;
+                Ok(c)
+            };
+            assert!(compile(
+                build()?,
+                vec![IOStatus::Party(0)],
+                vec![IOStatus::Party(0)],
+                3,
+                1
+            )
+            .is_err());
+            assert!(compile(
+                build()?,
+                vec![IOStatus::Shared],
+                vec![IOStatus::Party(0)],
+                3,
+                1
+            )
+            .is_err());
+            assert!(compile(
+                build()?,
+                vec![IOStatus::Party(3)],
+                vec![IOStatus::Party(0)],
+                3,
+                1
+            )
+            .is_err());
+            assert!(compile(
+                build()?,
+                vec![IOStatus::Party(0)],
+                vec![IOStatus::Shared],
+                3,
+                1
+            )
+            .is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -0,0 +1,915 @@
+//! Compilation of graphs into a dishonest-majority protocol with MAC-authenticated additive shares.
+//!
+//! The protocol follows [SPDZ2k](https://eprint.iacr.org/2018/482.pdf) for two computing parties (0 and 1),
+//! so it remains secure if one of them is corrupted and deviates from the protocol arbitrarily.
+//! Every private integer of `k` ≤ 32 bits is lifted to the ring of 64-bit integers and additively shared together with the shares of its MAC `m = alpha * x`,
+//! where `alpha` is a global MAC key unknown to the computing parties.
+//! Linear operations are computed locally on the shares of values and MACs;
+//! multiplications consume MAC-authenticated Beaver triples.
+//!
+//! Every opened value `v` is checked by computing `sigma_i = m_i - alpha_i * v` for each party `i`;
+//! an honest evaluation results in `sigma_0 + sigma_1 = 0`, while a party tampering with its shares is caught
+//! with probability at least `1 - 2^(k-64)` (up to the number of checks).
+//! Parties commit to their `sigma_i` before opening them, so a party can't choose its `sigma_i` after seeing the other one.
+//! A commitment to `v` consists of `PRF_key(1)`, which binds a party to a random key, and `v + PRF_key(0)`;
+//! it is opened by sending the key after receiving the commitment of the other party.
+//! The output mask of the dealer is released only if both parties confirm that all the checks have passed,
+//! so a party deviating from the protocol learns nothing about private outputs.
+//! The MAC checks are also returned along with the output and must be verified by [verify_mac_checks].
+//!
+//! The preprocessing phase (MAC key, triples and input masks) is performed by party 2 acting as a trusted dealer
+//! that never receives private data. Its nodes don't depend on inputs, so they can be evaluated ahead of time.
+//!
+//! Only arithmetic types with at most 32 bits are supported for private values;
+//! inputs must be owned by party 0 or 1 or be public, and private outputs must be revealed to at least one of these parties.
+use crate::data_types::{array_type, scalar_type, ScalarType, Type, BIT, UINT64};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{
+    copy_node_name, create_context, Context, Graph, Node, NodeAnnotation, Operation, SliceElement,
+};
+use crate::mpc::mpc_compiler::{IOStatus, KEY_LENGTH};
+use crate::typed_value::TypedValue;
+
+use super::mpc_two_party::send;
+
+use std::collections::HashMap;
+
+/// Bit size of the ring in which shares and MACs are computed
+const RING_BITS: u64 = 64;
+
+/// Party that performs the preprocessing phase
+const DEALER: u64 = 2;
+
+/// Number of 64-bit words of the PRF output binding a commitment to its key
+const KEY_CHECK_WORDS: u64 = 4;
+
+/// MAC-authenticated shares of a value: `values[i]` and `macs[i]` are known to party `i`
+#[derive(Clone)]
+struct AuthenticatedShares {
+    values: [Node; 2],
+    macs: [Node; 2],
+}
+
+#[derive(Clone)]
+enum LoweredNode {
+    Public(Node),
+    Private(AuthenticatedShares),
+}
+
+/// Returns the bit size of a scalar type supported for private values.
+fn get_supported_bits(st: &ScalarType) -> Result<u64> {
+    let bits = st.size_in_bits();
+    if *st == BIT || bits > RING_BITS / 2 {
+        return Err(runtime_error!(
+            "SPDZ compilation supports only private integers of at most {} bits, but {} is given",
+            RING_BITS / 2,
+            st
+        ));
+    }
+    Ok(bits)
+}
+
+/// Changes the scalar type of a scalar or array type to UINT64.
+fn lift_type(t: &Type) -> Result<Type> {
+    match t {
+        Type::Scalar(st) => {
+            get_supported_bits(st)?;
+            Ok(scalar_type(UINT64))
+        }
+        Type::Array(shape, st) => {
+            get_supported_bits(st)?;
+            Ok(array_type(shape.clone(), UINT64))
+        }
+        _ => Err(runtime_error!(
+            "SPDZ compilation supports only private scalars and arrays, but {} is given",
+            t
+        )),
+    }
+}
+
+/// Zero-extends a public integer scalar or array to 64 bits.
+fn lift(g: &Graph, x: Node) -> Result<Node> {
+    let t = x.get_type()?;
+    let bits = get_supported_bits(&t.get_scalar_type())?;
+    let shape = if t.is_scalar() { vec![] } else { t.get_shape() };
+    let binary = x.a2b()?;
+    let binary_type = binary.get_type()?;
+    let zeros = g.constant(binary_type.clone(), Value::zero_of_type(binary_type))?;
+    // Stack the bits with zeros along a new axis and move this axis to the second last position,
+    // so that the bits of x become the least significant bits of a 64-bit integer.
+    let copies = (RING_BITS / bits) as usize;
+    let mut parts = vec![binary];
+    parts.extend(vec![zeros; copies - 1]);
+    let stacked = g.stack(parts, vec![copies as u64])?;
+    let rank = shape.len() as u64 + 1;
+    let mut axes: Vec<u64> = (1..rank).collect();
+    axes.push(0);
+    axes.push(rank);
+    let mut new_shape = shape;
+    new_shape.push(RING_BITS);
+    stacked
+        .permute_axes(axes)?
+        .reshape(array_type(new_shape, BIT))?
+        .b2a(UINT64)
+}
+
+/// Truncates a 64-bit integer to a given scalar type.
+fn lower(x: Node, st: ScalarType) -> Result<Node> {
+    let bits = get_supported_bits(&st)?;
+    x.a2b()?
+        .get_slice(vec![
+            SliceElement::Ellipsis,
+            SliceElement::SubArray(Some(0), Some(bits as i64), None),
+        ])?
+        .b2a(st)
+}
+
+/// Returns a BIT scalar equal to 1 if and only if all the entries of given UINT64 scalars or arrays are zero.
+fn all_zero(g: &Graph, values: &[Node]) -> Result<Node> {
+    let mut flattened = vec![];
+    for value in values {
+        let t = value.get_type()?;
+        let size = if t.is_scalar() {
+            1
+        } else {
+            t.get_shape().iter().product()
+        };
+        flattened.push(value.reshape(array_type(vec![size], UINT64))?);
+    }
+    let mut bits = g.concatenate(flattened, 0)?.a2b()?;
+    let mut n: u64 = bits.get_type()?.get_shape().iter().product();
+    bits = bits.reshape(array_type(vec![n], BIT))?;
+    // OR of all the bits is computed by halving the array: a OR b = a + b + a * b
+    while n > 1 {
+        let half = n / 2;
+        let slice = |begin: u64, end: u64| {
+            bits.get_slice(vec![SliceElement::SubArray(
+                Some(begin as i64),
+                Some(end as i64),
+                None,
+            )])
+        };
+        let a = slice(0, half)?;
+        let b = slice(half, 2 * half)?;
+        let mut or = a.add(b.clone())?.add(a.multiply(b)?)?;
+        if n % 2 == 1 {
+            or = g.concatenate(vec![or, slice(2 * half, n)?], 0)?;
+        }
+        bits = or;
+        n -= half;
+    }
+    let one = g.constant(scalar_type(BIT), Value::from_scalar(1, BIT)?)?;
+    bits.reshape(scalar_type(BIT))?.add(one)
+}
+
+/// Commitment of a party to a value `v`: `check = PRF_key(1)` binds the party to a random key, and `masked = v + PRF_key(0)`.
+struct Commitment {
+    key: Node,
+    check: Node,
+    masked: Node,
+}
+
+/// Material produced by the dealer in the preprocessing phase.
+struct Dealer {
+    graph: Graph,
+    /// Shares of the MAC key
+    mac_key: [Node; 2],
+    /// MAC key known only to the dealer
+    global_mac_key: Node,
+}
+
+impl Dealer {
+    fn new(g: &Graph) -> Result<Self> {
+        let global_mac_key = g.random(scalar_type(UINT64))?;
+        let mac_key = Self::share(g, global_mac_key.clone())?;
+        Ok(Dealer {
+            graph: g.clone(),
+            mac_key,
+            global_mac_key,
+        })
+    }
+
+    fn share(g: &Graph, value: Node) -> Result<[Node; 2]> {
+        let share1 = g.random(value.get_type()?)?;
+        let share0 = value.subtract(share1.clone())?;
+        Ok([send(share0, DEALER, 0)?, send(share1, DEALER, 1)?])
+    }
+
+    /// Returns MAC-authenticated shares of a value known to the dealer.
+    fn authenticate(&self, value: Node) -> Result<AuthenticatedShares> {
+        let mac = self.global_mac_key.multiply(value.clone())?;
+        Ok(AuthenticatedShares {
+            values: Self::share(&self.graph, value)?,
+            macs: Self::share(&self.graph, mac)?,
+        })
+    }
+
+    /// Returns a random value known to the dealer and its MAC-authenticated shares.
+    fn random(&self, t: Type) -> Result<(Node, AuthenticatedShares)> {
+        let value = self.graph.random(t)?;
+        Ok((value.clone(), self.authenticate(value)?))
+    }
+}
+
+struct SpdzCompiler {
+    graph: Graph,
+    dealer: Dealer,
+    /// Public sums of MAC checks of opened values
+    mac_checks: Vec<Node>,
+}
+
+impl SpdzCompiler {
+    /// Shares a lifted public value as `(x, 0)` with MACs `(alpha_0 * x, alpha_1 * x)`.
+    fn share_public(&self, x: Node) -> Result<AuthenticatedShares> {
+        let t = x.get_type()?;
+        let zero = self.graph.constant(t.clone(), Value::zero_of_type(t))?;
+        Ok(AuthenticatedShares {
+            values: [x.clone(), zero],
+            macs: [
+                self.dealer.mac_key[0].multiply(x.clone())?,
+                self.dealer.mac_key[1].multiply(x)?,
+            ],
+        })
+    }
+
+    fn get_shares(&self, node: &LoweredNode) -> Result<AuthenticatedShares> {
+        match node {
+            LoweredNode::Public(x) => self.share_public(lift(&self.graph, x.clone())?),
+            LoweredNode::Private(shares) => Ok(shares.clone()),
+        }
+    }
+
+    fn commit(&self, value: Node) -> Result<Commitment> {
+        let key = self.graph.random(array_type(vec![KEY_LENGTH], BIT))?;
+        let check = key.prf(1, array_type(vec![KEY_CHECK_WORDS], UINT64))?;
+        let masked = value.add(key.prf(0, value.get_type()?)?)?;
+        Ok(Commitment { key, check, masked })
+    }
+
+    /// Exchanges commitments of parties 0 and 1 and opens them.
+    ///
+    /// Every commitment is sent as one message, and every party sends its key only after receiving the commitment of the other party,
+    /// whose masked value it echoes back. The differences between the received and expected checks are added to the MAC checks.
+    ///
+    /// Returns the opened values of parties 0 and 1.
+    fn exchange_commitments(&mut self, commitments: &[Commitment; 2]) -> Result<[Node; 2]> {
+        let mut received = vec![];
+        for (i, commitment) in commitments.iter().enumerate() {
+            let message = self
+                .graph
+                .create_tuple(vec![commitment.check.clone(), commitment.masked.clone()])?;
+            let message = send(message, i as u64, 1 - i as u64)?;
+            received.push((message.tuple_get(0)?, message.tuple_get(1)?));
+        }
+        let mut values = vec![];
+        for (i, commitment) in commitments.iter().enumerate() {
+            let (check, masked) = received[i].clone();
+            let opening = self
+                .graph
+                .create_tuple(vec![commitment.key.clone(), received[1 - i].1.clone()])?;
+            let opening = send(opening, i as u64, 1 - i as u64)?;
+            let key = opening.tuple_get(0)?;
+            let echo = opening.tuple_get(1)?;
+            self.mac_checks
+                .push(key.prf(1, check.get_type()?)?.subtract(check)?);
+            self.mac_checks
+                .push(echo.subtract(commitments[1 - i].masked.clone())?);
+            values.push(masked.subtract(key.prf(0, masked.get_type()?)?)?);
+        }
+        Ok([values[0].clone(), values[1].clone()])
+    }
+
+    /// Adds the MAC check of an opened value.
+    fn check_mac(&mut self, shares: &AuthenticatedShares, opened: Node) -> Result<()> {
+        let sigma =
+            |i: usize| shares.macs[i].subtract(self.dealer.mac_key[i].multiply(opened.clone())?);
+        let commitments = [self.commit(sigma(0)?)?, self.commit(sigma(1)?)?];
+        let sigmas = self.exchange_commitments(&commitments)?;
+        self.mac_checks.push(sigmas[0].add(sigmas[1].clone())?);
+        Ok(())
+    }
+
+    /// Returns a bit equal to 1 if both parties confirm to the dealer that all the MAC checks have passed.
+    fn confirm_mac_checks(&self) -> Result<Node> {
+        let passed = all_zero(&self.graph, &self.mac_checks)?;
+        send(passed.clone(), 0, DEALER)?.multiply(send(passed, 1, DEALER)?)
+    }
+
+    /// Opens a private value to both parties and checks its MAC.
+    fn open(&mut self, shares: &AuthenticatedShares) -> Result<Node> {
+        let opened =
+            send(shares.values[0].clone(), 0, 1)?.add(send(shares.values[1].clone(), 1, 0)?)?;
+        self.check_mac(shares, opened.clone())?;
+        Ok(opened)
+    }
+
+    fn apply_linear(
+        &self,
+        op: &Operation,
+        dependencies: &[AuthenticatedShares],
+    ) -> Result<AuthenticatedShares> {
+        let op = match op {
+            Operation::Reshape(t) => Operation::Reshape(lift_type(t)?),
+            _ => op.clone(),
+        };
+        let apply = |get: &dyn Fn(&AuthenticatedShares) -> Node| {
+            let nodes = dependencies.iter().map(get).collect();
+            self.graph.add_node(nodes, vec![], op.clone())
+        };
+        Ok(AuthenticatedShares {
+            values: [
+                apply(&|s| s.values[0].clone())?,
+                apply(&|s| s.values[1].clone())?,
+            ],
+            macs: [
+                apply(&|s| s.macs[0].clone())?,
+                apply(&|s| s.macs[1].clone())?,
+            ],
+        })
+    }
+
+    fn subtract(
+        &self,
+        x: &AuthenticatedShares,
+        y: &AuthenticatedShares,
+    ) -> Result<AuthenticatedShares> {
+        self.apply_linear(&Operation::Subtract, &[x.clone(), y.clone()])
+    }
+
+    /// Multiplies two private values using a MAC-authenticated Beaver triple.
+    fn multiply(
+        &mut self,
+        x: &AuthenticatedShares,
+        y: &AuthenticatedShares,
+        op: &Operation,
+    ) -> Result<AuthenticatedShares> {
+        let graph = self.graph.clone();
+        let apply = |a: Node, b: Node| graph.add_node(vec![a, b], vec![], op.clone());
+        let (a, a_shares) = self.dealer.random(x.values[0].get_type()?)?;
+        let (b, b_shares) = self.dealer.random(y.values[0].get_type()?)?;
+        let c = apply(a, b)?.add_annotation(NodeAnnotation::BeaverTriple)?;
+        let c_shares = self.dealer.authenticate(c)?;
+        let e = self.open(&self.subtract(x, &a_shares)?)?;
+        let f = self.open(&self.subtract(y, &b_shares)?)?;
+        let ef = apply(e.clone(), f.clone())?;
+        // z = c + e * b + a * f + e * f, where e * f is added by party 0 only
+        let mut values = vec![];
+        let mut macs = vec![];
+        for i in 0..2 {
+            let value = c_shares.values[i]
+                .add(apply(e.clone(), b_shares.values[i].clone())?)?
+                .add(apply(a_shares.values[i].clone(), f.clone())?)?;
+            values.push(if i == 0 {
+                value.add(ef.clone())?
+            } else {
+                value
+            });
+            let mac = c_shares.macs[i]
+                .add(apply(e.clone(), b_shares.macs[i].clone())?)?
+                .add(apply(a_shares.macs[i].clone(), f.clone())?)?
+                .add(self.dealer.mac_key[i].multiply(ef.clone())?)?;
+            macs.push(mac);
+        }
+        Ok(AuthenticatedShares {
+            values: [values[0].clone(), values[1].clone()],
+            macs: [macs[0].clone(), macs[1].clone()],
+        })
+    }
+
+    fn lower_input(&self, node: &Node, t: Type, status: &IOStatus) -> Result<LoweredNode> {
+        let input = self.graph.input(t.clone())?;
+        copy_node_name(node.clone(), input.clone())?;
+        match status {
+            IOStatus::Public => Ok(LoweredNode::Public(input)),
+            IOStatus::Party(id) if *id < 2 => {
+                // The dealer reveals a random mask r to the owner, who broadcasts d = x - r
+                let (mask, mask_shares) = self.dealer.random(lift_type(&t)?)?;
+                let mask = send(mask, DEALER, *id)?;
+                let d = send(lift(&self.graph, input)?.subtract(mask)?, *id, 1 - *id)?;
+                let d_shares = self.share_public(d)?;
+                Ok(LoweredNode::Private(
+                    self.apply_linear(&Operation::Add, &[mask_shares, d_shares])?,
+                ))
+            }
+            _ => Err(runtime_error!(
+                "SPDZ compilation supports only public inputs and inputs of parties 0 and 1"
+            )),
+        }
+    }
+
+    /// Applies a bilinear operation to a private value and a public one.
+    fn apply_public(
+        &self,
+        x: &AuthenticatedShares,
+        public: Node,
+        op: &Operation,
+        public_first: bool,
+    ) -> Result<AuthenticatedShares> {
+        let apply = |share: &Node| {
+            let arguments = if public_first {
+                vec![public.clone(), share.clone()]
+            } else {
+                vec![share.clone(), public.clone()]
+            };
+            self.graph.add_node(arguments, vec![], op.clone())
+        };
+        Ok(AuthenticatedShares {
+            values: [apply(&x.values[0])?, apply(&x.values[1])?],
+            macs: [apply(&x.macs[0])?, apply(&x.macs[1])?],
+        })
+    }
+
+    /// Reveals a private output of a given scalar type to given parties.
+    fn reveal(&mut self, x: &AuthenticatedShares, st: ScalarType, parties: &[u64]) -> Result<Node> {
+        let bits = get_supported_bits(&st)?;
+        let t = x.values[0].get_type()?;
+        // The opened value w = x + 2^k * s - r is uniformly random;
+        // r is sent to the output parties, and 2^k * s hides the bits of x above the k bits of the output.
+        let (r, r_shares) = self.dealer.random(t.clone())?;
+        let (_, s_shares) = self.dealer.random(t)?;
+        let scale = self.graph.constant(
+            scalar_type(UINT64),
+            Value::from_scalar(1u64 << bits, UINT64)?,
+        )?;
+        let scaled_s = self.apply_public(&s_shares, scale, &Operation::Multiply, false)?;
+        let masked = self.apply_linear(&Operation::Add, &[x.clone(), scaled_s])?;
+        let w = self.open(&self.subtract(&masked, &r_shares)?)?;
+        // The dealer sends r only if all the MAC checks have passed, including the one of w;
+        // otherwise, zero is sent and the output parties obtain the uniformly random w.
+        let mut r_sent = r.mixed_multiply(self.confirm_mac_checks()?)?;
+        for id in parties {
+            r_sent = send(r_sent, DEALER, *id)?;
+        }
+        lower(w.add(r_sent)?, st)
+    }
+}
+
+/// Compiles the main graph of an instantiated and inlined context into a graph of the SPDZ protocol.
+///
+/// # Arguments
+///
+/// * `context` - context without custom operations and graph calls
+/// * `input_parties` - statuses of the inputs of the main graph; party IDs must be 0 or 1, shared inputs aren't supported
+/// * `output_parties` - parties (0 or 1) that obtain the revealed output
+///
+/// # Returns
+///
+/// New context whose main graph returns a tuple of the output of the original main graph and the tuple of MAC checks,
+/// see [verify_mac_checks]
+pub(super) fn compile_to_spdz(
+    context: Context,
+    input_parties: Vec<IOStatus>,
+    output_parties: Vec<IOStatus>,
+) -> Result<Context> {
+    context.check_finalized()?;
+    let mut output_ids = vec![];
+    for status in &output_parties {
+        match status {
+            IOStatus::Party(id) if *id < 2 => output_ids.push(*id),
+            _ => {
+                return Err(runtime_error!(
+                    "Output status should be a party ID of a computing party"
+                ))
+            }
+        }
+    }
+    let in_graph = context.get_main_graph()?;
+    let out_context = create_context()?;
+    let g = out_context.create_graph()?;
+    let mut compiler = SpdzCompiler {
+        graph: g.clone(),
+        dealer: Dealer::new(&g)?,
+        mac_checks: vec![],
+    };
+    // Lowered nodes indexed by the IDs of the nodes of the input graph
+    let mut lowered: HashMap<u64, LoweredNode> = HashMap::new();
+    let mut input_id = 0;
+    for node in in_graph.get_nodes() {
+        let op = node.get_operation();
+        let dependencies: Vec<LoweredNode> = node
+            .get_node_dependencies()
+            .iter()
+            .map(|d| lowered[&d.get_id()].clone())
+            .collect();
+        let all_public = dependencies
+            .iter()
+            .all(|d| matches!(d, LoweredNode::Public(_)));
+        let new_node = match op.clone() {
+            Operation::Input(t) => {
+                let status = input_parties
+                    .get(input_id)
+                    .ok_or_else(|| runtime_error!("Not enough input statuses"))?;
+                input_id += 1;
+                compiler.lower_input(&node, t, status)?
+            }
+            Operation::Random(_) | Operation::RandomBeacon(_) => {
+                return Err(runtime_error!(
+                    "SPDZ compilation of random values is not supported"
+                ))
+            }
+            _ if all_public => {
+                if !node.get_graph_dependencies().is_empty() {
+                    return Err(runtime_error!(
+                        "SPDZ compilation requires an inlined context"
+                    ));
+                }
+                let public_dependencies = dependencies
+                    .iter()
+                    .map(|d| match d {
+                        LoweredNode::Public(x) => x.clone(),
+                        LoweredNode::Private(_) => unreachable!(),
+                    })
+                    .collect();
+                LoweredNode::Public(g.add_node(public_dependencies, vec![], op)?)
+            }
+            Operation::Multiply
+            | Operation::Dot
+            | Operation::Matmul
+            | Operation::Gemm(_, _)
+            | Operation::MixedMultiply => match (&dependencies[0], &dependencies[1]) {
+                (LoweredNode::Private(x), LoweredNode::Public(y)) => {
+                    // Bits of MixedMultiply are used as is
+                    let y = if op == Operation::MixedMultiply {
+                        y.clone()
+                    } else {
+                        lift(&g, y.clone())?
+                    };
+                    LoweredNode::Private(compiler.apply_public(x, y, &op, false)?)
+                }
+                (LoweredNode::Public(x), LoweredNode::Private(y))
+                    if op != Operation::MixedMultiply =>
+                {
+                    let x = lift(&g, x.clone())?;
+                    LoweredNode::Private(compiler.apply_public(y, x, &op, true)?)
+                }
+                (LoweredNode::Private(x), LoweredNode::Private(y))
+                    if op != Operation::MixedMultiply =>
+                {
+                    LoweredNode::Private(compiler.multiply(x, y, &op)?)
+                }
+                _ => return Err(unsupported(&op)),
+            },
+            Operation::Add
+            | Operation::Subtract
+            | Operation::PermuteAxes(_)
+            | Operation::TupleGet(_)
+            | Operation::NamedTupleGet(_)
+            | Operation::GetSlice(_)
+            | Operation::Reshape(_)
+            | Operation::Sum(_)
+            | Operation::Get(_)
+            | Operation::Repeat(_)
+            | Operation::CreateTuple
+            | Operation::CreateNamedTuple(_)
+            | Operation::Stack(_)
+            | Operation::Concatenate(_)
+            | Operation::Split(_, _)
+            | Operation::BroadcastTo(_)
+            | Operation::Tile(_)
+            | Operation::CumSum(_) => {
+                let shares = dependencies
+                    .iter()
+                    .map(|d| compiler.get_shares(d))
+                    .collect::<Result<Vec<_>>>()?;
+                LoweredNode::Private(compiler.apply_linear(&op, &shares)?)
+            }
+            _ => return Err(unsupported(&op)),
+        };
+        lowered.insert(node.get_id(), new_node);
+    }
+    if input_id != input_parties.len() {
+        return Err(runtime_error!(
+            "Invalid number of input parties: {} expected, but {} found",
+            input_id,
+            input_parties.len()
+        ));
+    }
+    let in_output = in_graph.get_output_node()?;
+    let output = match &lowered[&in_output.get_id()] {
+        LoweredNode::Public(x) => x.clone(),
+        LoweredNode::Private(shares) => {
+            if output_ids.is_empty() {
+                return Err(runtime_error!(
+                    "Private outputs of SPDZ must be revealed to a computing party"
+                ));
+            }
+            let st = in_output.get_type()?.get_scalar_type();
+            compiler.reveal(shares, st, &output_ids)?
+        }
+    };
+    let mac_checks = g.create_tuple(compiler.mac_checks)?;
+    g.create_tuple(vec![output, mac_checks])?.set_as_output()?;
+    g.finalize()?;
+    out_context.set_main_graph(g)?;
+    out_context.finalize()?;
+    Ok(out_context)
+}
+
+fn unsupported(op: &Operation) -> crate::errors::CiphercoreBaseError {
+    runtime_error!("SPDZ compilation for private {} is not supported", op)
+}
+
+/// Verifies the MAC checks of the output of a graph compiled to SPDZ and returns the output if they pass.
+///
+/// If some check fails, a private output is replaced by a random value during evaluation, since the output mask isn't released;
+/// this function detects such outputs.
+///
+/// # Arguments
+///
+/// `result` - typed value of the output of a compiled graph, i.e. a tuple of the actual output and MAC checks
+///
+/// # Returns
+///
+/// Actual output of the compiled graph or an error if some party has deviated from the protocol
+pub fn verify_mac_checks(result: TypedValue) -> Result<TypedValue> {
+    let types = match &result.t {
+        Type::Tuple(types) if types.len() == 2 => types.clone(),
+        _ => return Err(runtime_error!("Not an output of a SPDZ graph")),
+    };
+    let values = result.value.to_vector()?;
+    if !matches!(&*types[1], Type::Tuple(_)) {
+        return Err(runtime_error!("Not an output of a SPDZ graph"));
+    }
+    // Every MAC check must be zero
+    for check in values[1].to_vector()? {
+        if check.access_bytes(|bytes| Ok(bytes.iter().any(|b| *b != 0)))? {
+            return Err(runtime_error!("MAC check failed"));
+        }
+    }
+    TypedValue::new((*types[0]).clone(), values[0].clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{INT16, INT32, UINT8};
+    use crate::evaluators::evaluate_simple_evaluator;
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::inline::inline_ops::{InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{compile_context_with_backend, MpcBackend};
+    use crate::mpc::send_batching::get_transfer_batches;
+
+    fn compile(
+        c: Context,
+        input_parties: Vec<IOStatus>,
+        output_parties: Vec<IOStatus>,
+    ) -> Result<Context> {
+        compile_context_with_backend(
+            c,
+            input_parties,
+            output_parties,
+            InlineConfig {
+                default_mode: InlineMode::Simple,
+                ..Default::default()
+            },
+            || SimpleEvaluator::new(None),
+            MpcBackend::Spdz,
+        )
+    }
+
+    fn evaluate(c: &Context, inputs: Vec<Value>) -> Result<Result<TypedValue>> {
+        let g = c.get_main_graph()?;
+        let t = g.get_output_node()?.get_type()?;
+        let result = random_evaluate(g, inputs)?;
+        Ok(verify_mac_checks(TypedValue::new(t, result)?))
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(array_type(vec![2, 2], INT32))?;
+            let y = g.input(array_type(vec![2, 2], INT32))?;
+            let z = g.input(scalar_type(INT32))?;
+            let prod = x.multiply(y.clone())?.add(x.matmul(y)?)?;
+            prod.subtract(z)?
+                .sum(vec![0])?
+                .reshape(array_type(vec![2, 1], INT32))?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let inputs = vec![
+                Value::from_flattened_array(&[1, -2, 3, 4], INT32)?,
+                Value::from_flattened_array(&[-5, 6, 7, 1 << 20], INT32)?,
+                Value::from_scalar(10, INT32)?,
+            ];
+            let expected = random_evaluate(g, inputs.clone())?;
+            let compiled = compile(
+                c,
+                vec![IOStatus::Party(0), IOStatus::Party(1), IOStatus::Public],
+                vec![IOStatus::Party(0), IOStatus::Party(1)],
+            )?;
+            let result = evaluate(&compiled, inputs)??;
+            assert_eq!(result.value, expected);
+            assert_eq!(result.t, array_type(vec![2, 1], INT32));
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_small_types() {
+        || -> Result<()> {
+            for st in [UINT8, INT16] {
+                let c = create_context()?;
+                let g = c.create_graph()?;
+                let x = g.input(array_type(vec![3], st.clone()))?;
+                let y = g.input(array_type(vec![3], st.clone()))?;
+                let bits = g.input(array_type(vec![3], BIT))?;
+                x.dot(y.clone())?
+                    .add(x.mixed_multiply(bits)?.sum(vec![0])?)?
+                    .set_as_output()?;
+                g.finalize()?.set_as_main()?;
+                c.finalize()?;
+                let inputs = vec![
+                    Value::from_flattened_array(&[100, 20, 3], st.clone())?,
+                    Value::from_flattened_array(&[7, 5, 11], st.clone())?,
+                    Value::from_flattened_array(&[1, 0, 1], BIT)?,
+                ];
+                let expected = random_evaluate(g, inputs.clone())?;
+                let compiled = compile(
+                    c,
+                    vec![IOStatus::Party(1), IOStatus::Party(0), IOStatus::Public],
+                    vec![IOStatus::Party(1)],
+                )?;
+                assert_eq!(evaluate(&compiled, inputs)??.value, expected);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_tampering() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let mut compiler = SpdzCompiler {
+                graph: g.clone(),
+                dealer: Dealer::new(&g)?,
+                mac_checks: vec![],
+            };
+            let x = g.constant(scalar_type(INT32), Value::from_scalar(5, INT32)?)?;
+            let shares = compiler.share_public(lift(&g, x)?)?;
+            // Party 0 adds an error to its share before opening
+            let error = g.constant(scalar_type(UINT64), Value::from_scalar(1, UINT64)?)?;
+            let tampered = AuthenticatedShares {
+                values: [shares.values[0].add(error)?, shares.values[1].clone()],
+                macs: shares.macs,
+            };
+            let opened = compiler.open(&tampered)?;
+            let mac_checks = g.create_tuple(compiler.mac_checks)?;
+            g.create_tuple(vec![opened, mac_checks])?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            assert!(evaluate(&c, vec![])?.is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_adaptive_sigma() {
+        // Party 1 tampers with its share and tries to cancel the MAC check by choosing its sigma
+        // after seeing the sigma of party 0; it can only change the key opening its commitment.
+        let helper = |forge_key: bool| -> Result<Result<TypedValue>> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let mut compiler = SpdzCompiler {
+                graph: g.clone(),
+                dealer: Dealer::new(&g)?,
+                mac_checks: vec![],
+            };
+            let x = g.constant(scalar_type(INT32), Value::from_scalar(5, INT32)?)?;
+            let shares = compiler.share_public(lift(&g, x)?)?;
+            let error = g.constant(scalar_type(UINT64), Value::from_scalar(1, UINT64)?)?;
+            let opened = send(shares.values[0].clone(), 0, 1)?.add(send(
+                shares.values[1].add(error)?,
+                1,
+                0,
+            )?)?;
+            let sigma = |i: usize| {
+                shares.macs[i].subtract(compiler.dealer.mac_key[i].multiply(opened.clone())?)
+            };
+            let honest = compiler.commit(sigma(0)?)?;
+            let mut adversarial = compiler.commit(sigma(1)?)?;
+            if forge_key {
+                // The key is derived from sigma_0 = masked_0 - PRF_key_0(0) revealed by party 0
+                let sigma_0 = honest
+                    .masked
+                    .subtract(honest.key.prf(0, scalar_type(UINT64))?)?;
+                let target = g
+                    .constant(scalar_type(UINT64), Value::from_scalar(0, UINT64)?)?
+                    .subtract(sigma_0)?
+                    .a2b()?;
+                adversarial.key = g.concatenate(vec![target; 2], 0)?;
+            }
+            let sigmas = compiler.exchange_commitments(&[honest, adversarial])?;
+            let check = sigmas[0].add(sigmas[1].clone())?;
+            compiler.mac_checks.push(check);
+            let mac_checks = g.create_tuple(compiler.mac_checks)?;
+            g.create_tuple(vec![opened, mac_checks])?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            evaluate(&c, vec![])
+        };
+        || -> Result<()> {
+            assert!(helper(false)?.is_err());
+            assert!(helper(true)?.is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_commitment_rounds() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let mut compiler = SpdzCompiler {
+                graph: g.clone(),
+                dealer: Dealer::new(&g)?,
+                mac_checks: vec![],
+            };
+            let x = g.constant(scalar_type(INT32), Value::from_scalar(5, INT32)?)?;
+            let shares = compiler.share_public(lift(&g, x)?)?;
+            let opened = compiler.open(&shares)?;
+            let mac_checks = g.create_tuple(compiler.mac_checks)?;
+            g.create_tuple(vec![opened, mac_checks])?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            // Shares are opened in round 1, sigmas are committed in round 2 and the commitments are opened in round 3
+            let batches = get_transfer_batches(c)?;
+            for (sender, receiver) in [(0, 1), (1, 0)] {
+                let rounds: Vec<u64> = batches
+                    .iter()
+                    .filter(|b| b.sender == sender && b.receiver == receiver)
+                    .map(|b| b.round)
+                    .collect();
+                assert_eq!(rounds, vec![1, 2, 3]);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_withheld_output() {
+        // If a MAC check fails, the dealer doesn't release the output mask
+        let helper = |tamper: bool| -> Result<Value> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let mut compiler = SpdzCompiler {
+                graph: g.clone(),
+                dealer: Dealer::new(&g)?,
+                mac_checks: vec![],
+            };
+            let x = g.constant(scalar_type(INT32), Value::from_scalar(5, INT32)?)?;
+            let mut shares = compiler.share_public(lift(&g, x)?)?;
+            if tamper {
+                let error = g.constant(scalar_type(UINT64), Value::from_scalar(1, UINT64)?)?;
+                shares.values[1] = shares.values[1].add(error)?;
+            }
+            compiler.reveal(&shares, INT32, &[0])?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            evaluate_simple_evaluator(g, vec![], Some([7; 16]))
+        };
+        || -> Result<()> {
+            assert_eq!(helper(false)?, Value::from_scalar(5, INT32)?);
+            let tampered = helper(true)?;
+            assert_ne!(tampered, Value::from_scalar(5, INT32)?);
+            assert_ne!(tampered, Value::from_scalar(6, INT32)?);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_unsupported() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(scalar_type(INT32))?;
+            x.multiply(x.clone())?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            // Shared inputs and outputs
+            assert!(compile(c.clone(), vec![IOStatus::Shared], vec![IOStatus::Party(0)]).is_err());
+            assert!(compile(c.clone(), vec![IOStatus::Party(0)], vec![]).is_err());
+            assert!(compile(c, vec![IOStatus::Party(2)], vec![IOStatus::Party(0)]).is_err());
+
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            g.input(scalar_type(UINT64))?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            assert!(compile(c, vec![IOStatus::Party(0)], vec![IOStatus::Party(0)]).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -4,10 +4,13 @@ use serde::{Deserialize, Serialize};
 
 use crate::bytes::{add_vectors_u64, subtract_vectors_u64};
 use crate::custom_ops::CustomOperationBody;
-use crate::data_types::{array_type, scalar_size_in_bytes, ScalarType, Type, BIT, UINT8};
+use crate::data_types::{
+    array_type, get_u64_modulus, scalar_size_in_bytes, ScalarType, Type, BIT, UINT8,
+};
 use crate::data_values::Value;
 use crate::errors::Result;
 use crate::graphs::{Context, Graph, Node, NodeAnnotation};
+use crate::ops::utils::multiply_by_bits;
 use crate::random::PRNG;
 
 use super::mpc_compiler::{KEY_LENGTH, PARTIES};
@@ -127,6 +130,8 @@ impl CustomOperationBody for ObliviousTransfer {
     }
 }
 
+register_custom_operation!(ObliviousTransfer, [Array, Array, Binary, Binary]);
+
 /// Utility function for preparing secret-shared data.
 ///
 /// TODO: in the future, we need to make secret-sharing more generic.
@@ -157,10 +162,10 @@ pub fn share_vector<T: TryInto<u64> + Not<Output = T> + TryInto<u8> + Copy>(
     let r1 = Value::from_bytes(r1_bytes)
         .to_flattened_array_u64(array_type(vec![n as u64], scalar_type.clone()))?;
     // third share (r2) is r2 = data - (r0 + r1)
-    let r0r1 = add_vectors_u64(&r0, &r1, scalar_type.get_modulus())?;
+    let r0r1 = add_vectors_u64(&r0, &r1, get_u64_modulus(&scalar_type)?)?;
     let data_u64 = Value::from_flattened_array(data, scalar_type.clone())?
         .to_flattened_array_u64(array_type(vec![n as u64], scalar_type.clone()))?;
-    let r2 = subtract_vectors_u64(&data_u64, &r0r1, scalar_type.get_modulus())?;
+    let r2 = subtract_vectors_u64(&data_u64, &r0r1, get_u64_modulus(&scalar_type)?)?;
 
     let shares = vec![
         Value::from_flattened_array(&r0, scalar_type.clone())?,
@@ -211,11 +216,7 @@ pub fn share_vector<T: TryInto<u64> + Not<Output = T> + TryInto<u8> + Copy>(
 /// Node containing an array with selected elements of `a0` or `a1`
 pub fn select_node(b: Node, a1: Node, a0: Node) -> Result<Node> {
     let dif = a1.subtract(a0.clone())?;
-    if dif.get_type()?.get_scalar_type() == BIT {
-        dif.multiply(b)?.add(a0)
-    } else {
-        dif.mixed_multiply(b)?.add(a0)
-    }
+    multiply_by_bits(dif, b)?.add(a0)
 }
 
 #[cfg(test)]
@@ -4,12 +4,24 @@
 pub mod adder;
 pub mod clip;
 pub mod comparisons;
+pub mod convolution;
+pub mod decision_tree;
 pub mod inverse_sqrt;
+pub mod knn;
+pub mod linalg;
 pub mod min_max;
 pub mod multiplexer;
 pub mod newton_inversion;
+pub mod nullable_join;
+pub mod private_id;
 pub mod pwl;
+pub mod random_sampling;
+pub mod record_linkage;
+pub mod softmax;
 pub mod sorting;
+pub mod sparse;
+pub mod statistics;
+pub mod table;
 pub mod taylor_exponent;
 #[doc(hidden)]
 pub mod utils;
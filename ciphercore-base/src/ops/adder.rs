@@ -120,6 +120,8 @@ impl CustomOperationBody for BinaryAdd {
     }
 }
 
+register_custom_operation!(BinaryAdd, [Binary, Binary]);
+
 #[cfg(test)]
 mod tests {
     use super::*;
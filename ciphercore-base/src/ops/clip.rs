@@ -129,6 +129,8 @@ impl CustomOperationBody for Clip2K {
     }
 }
 
+register_custom_operation!(Clip2K, [Binary]);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,7 +1,11 @@
 //! Various comparison functions for signed and unsigned integers including greater-than, less-than, greater-than-equal-to, less-than-equal-to, equal, not-equal.
+//!
+//! Integers are compared either as bitstrings or as arrays of a non-binary scalar type, which are decomposed into bits by [Graph::a2b].
 use crate::broadcast::broadcast_shapes;
 use crate::custom_ops::{CustomOperation, CustomOperationBody, Not, Or};
-use crate::data_types::{array_type, scalar_type, tuple_type, vector_type, ArrayShape, Type, BIT};
+use crate::data_types::{
+    array_type, scalar_type, tuple_type, vector_type, ArrayShape, ScalarType, Type, BIT,
+};
 use crate::errors::Result;
 use crate::graphs::*;
 use crate::ops::utils::pull_out_bits;
@@ -52,6 +56,59 @@ fn preprocess_comparison_args(argument_type: Type) -> Result<(u64, ArrayShape, T
     Ok((bit_vect_len, new_arg_shape, new_arg_type, mult_dim_op))
 }
 
+/// Returns the scalar type of the arguments of a comparison if both of them are integer (i.e. non-binary) arrays or scalars and `None` otherwise.
+///
+/// If `signed_comparison` is given, it must match the signedness of the scalar type.
+pub(super) fn get_integer_arguments_scalar_type(
+    arguments_types: &[Type],
+    signed_comparison: Option<bool>,
+    name: &str,
+) -> Result<Option<ScalarType>> {
+    if arguments_types.len() != 2 {
+        return Ok(None);
+    }
+    let st = match (&arguments_types[0], &arguments_types[1]) {
+        (Type::Array(_, st0) | Type::Scalar(st0), Type::Array(_, st1) | Type::Scalar(st1))
+            if *st0 != BIT && *st1 != BIT =>
+        {
+            if st0 != st1 {
+                return Err(runtime_error!(
+                    "{}: integer arguments must be of the same scalar type, got {} and {}",
+                    name,
+                    st0,
+                    st1
+                ));
+            }
+            st0.clone()
+        }
+        _ => return Ok(None),
+    };
+    if let Some(signed_comparison) = signed_comparison {
+        if signed_comparison != st.get_signed() {
+            return Err(runtime_error!(
+                "{}: signed_comparison doesn't match the scalar type {} of the arguments",
+                name,
+                st
+            ));
+        }
+    }
+    Ok(Some(st))
+}
+
+/// Creates a graph that decomposes two integer arguments into bits and applies a given comparison to the resulting bitstrings.
+fn instantiate_integer_comparison(
+    context: Context,
+    arguments_types: Vec<Type>,
+    comparison: CustomOperation,
+) -> Result<Graph> {
+    let g = context.create_graph()?;
+    let a = g.input(arguments_types[0].clone())?.a2b()?;
+    let b = g.input(arguments_types[1].clone())?.a2b()?;
+    g.custom_op(comparison, vec![a, b])?.set_as_output()?;
+    g.finalize()?;
+    Ok(g)
+}
+
 /// This trait has to be implemented by any comparison custom operation
 #[typetag::serde(tag = "type")]
 trait ComparisonCustomOperation: CustomOperationBody {
@@ -378,14 +435,17 @@ trait PrimitiveComparisonCustomOperation: ComparisonCustomOperation {
 /// If input shapes are different, the broadcasting rules are applied (see [the NumPy broadcasting rules](https://numpy.org/doc/stable/user/basics.broadcasting.html)).
 /// For example, if input arrays are of shapes `[2,3]`, and `[1,3]`, the resulting array has shape `[2]`.
 ///
-/// To compare signed numbers, `signed_comparison` should be set `true`.
+/// To compare signed numbers, `signed_comparison` should be set `true`; for integer inputs, it must match the signedness of their scalar type.
 ///
 /// To use this and other custom operations in computation graphs, see [Graph::custom_op].
 ///
+/// Instead of bitstrings, the inputs can be arrays or scalars of the same integer type, which are decomposed into bits with [Graph::a2b].
+/// In this case, the result has the broadcast shape of the inputs.
+///
 /// # Custom operation arguments
 ///
-/// - Node containing a binary array or scalar
-/// - Node containing a binary array or scalar
+/// - Node containing a binary or integer array or scalar
+/// - Node containing a binary or integer array or scalar
 ///
 /// # Custom operation returns
 ///
@@ -414,6 +474,21 @@ pub struct GreaterThan {
 #[typetag::serde]
 impl CustomOperationBody for GreaterThan {
     fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if get_integer_arguments_scalar_type(
+            &arguments_types,
+            Some(self.signed_comparison),
+            &self.get_name(),
+        )?
+        .is_some()
+        {
+            return instantiate_integer_comparison(
+                context,
+                arguments_types,
+                CustomOperation::new(GreaterThan {
+                    signed_comparison: self.signed_comparison,
+                }),
+            );
+        }
         self.create_comparison_custom_op(context, arguments_types)
     }
 
@@ -422,6 +497,8 @@ impl CustomOperationBody for GreaterThan {
     }
 }
 
+register_custom_operation!(GreaterThan, [Array, Array]);
+
 #[typetag::serde]
 impl ComparisonCustomOperation for GreaterThan {
     fn validate_c_op_specific_arguments(&self, arguments_types: Vec<Type>) -> Result<()> {
@@ -502,10 +579,13 @@ impl PrimitiveComparisonCustomOperation for GreaterThan {
 ///
 /// To use this and other custom operations in computation graphs, see [Graph::custom_op].
 ///
+/// Instead of bitstrings, the inputs can be arrays or scalars of the same integer type, which are decomposed into bits with [Graph::a2b].
+/// In this case, the result has the broadcast shape of the inputs.
+///
 /// # Custom operation arguments
 ///
-/// - Node containing a binary array or scalar
-/// - Node containing a binary array or scalar
+/// - Node containing a binary or integer array or scalar
+/// - Node containing a binary or integer array or scalar
 ///
 /// # Custom operation returns
 ///
@@ -531,6 +611,13 @@ pub struct NotEqual {}
 #[typetag::serde]
 impl CustomOperationBody for NotEqual {
     fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if get_integer_arguments_scalar_type(&arguments_types, None, &self.get_name())?.is_some() {
+            return instantiate_integer_comparison(
+                context,
+                arguments_types,
+                CustomOperation::new(NotEqual {}),
+            );
+        }
         self.create_comparison_custom_op(context, arguments_types)
     }
 
@@ -539,6 +626,8 @@ impl CustomOperationBody for NotEqual {
     }
 }
 
+register_custom_operation!(NotEqual, [Array, Array]);
+
 #[typetag::serde]
 impl ComparisonCustomOperation for NotEqual {}
 
@@ -598,14 +687,17 @@ impl PrimitiveComparisonCustomOperation for NotEqual {
 /// If input shapes are different, the broadcasting rules are applied (see [the NumPy broadcasting rules](https://numpy.org/doc/stable/user/basics.broadcasting.html)).
 /// For example, if input arrays are of shapes `[2,3]`, and `[1,3]`, the resulting array has shape `[2]`.
 ///
-/// To compare signed numbers, `signed_comparison` should be set `true`.
+/// To compare signed numbers, `signed_comparison` should be set `true`; for integer inputs, it must match the signedness of their scalar type.
 ///
 /// To use this and other custom operations in computation graphs, see [Graph::custom_op].
 ///
+/// Instead of bitstrings, the inputs can be arrays or scalars of the same integer type, which are decomposed into bits with [Graph::a2b].
+/// In this case, the result has the broadcast shape of the inputs.
+///
 /// # Custom operation arguments
 ///
-/// - Node containing a binary array or scalar
-/// - Node containing a binary array or scalar
+/// - Node containing a binary or integer array or scalar
+/// - Node containing a binary or integer array or scalar
 ///
 /// # Custom operation returns
 ///
@@ -634,6 +726,21 @@ pub struct LessThan {
 #[typetag::serde]
 impl CustomOperationBody for LessThan {
     fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if get_integer_arguments_scalar_type(
+            &arguments_types,
+            Some(self.signed_comparison),
+            &self.get_name(),
+        )?
+        .is_some()
+        {
+            return instantiate_integer_comparison(
+                context,
+                arguments_types,
+                CustomOperation::new(LessThan {
+                    signed_comparison: self.signed_comparison,
+                }),
+            );
+        }
         self.validate_comparison_arguments(arguments_types.clone())?;
         //Graph to compare two n bit numbers as arrays
         let less_than_graph = context.create_graph()?;
@@ -655,6 +762,8 @@ impl CustomOperationBody for LessThan {
     }
 }
 
+register_custom_operation!(LessThan, [Array, Array]);
+
 #[typetag::serde]
 impl ComparisonCustomOperation for LessThan {
     fn validate_c_op_specific_arguments(&self, arguments_types: Vec<Type>) -> Result<()> {
@@ -677,14 +786,17 @@ impl NeedsSignedPreprocessing for LessThan {
 /// If input shapes are different, the broadcasting rules are applied (see [the NumPy broadcasting rules](https://numpy.org/doc/stable/user/basics.broadcasting.html)).
 /// For example, if input arrays are of shapes `[2,3]`, and `[1,3]`, the resulting array has shape `[2]`.
 ///
-/// To compare signed numbers, `signed_comparison` should be set `true`.
+/// To compare signed numbers, `signed_comparison` should be set `true`; for integer inputs, it must match the signedness of their scalar type.
 ///
 /// To use this and other custom operations in computation graphs, see [Graph::custom_op].
 ///
+/// Instead of bitstrings, the inputs can be arrays or scalars of the same integer type, which are decomposed into bits with [Graph::a2b].
+/// In this case, the result has the broadcast shape of the inputs.
+///
 /// # Custom operation arguments
 ///
-/// - Node containing a binary array or scalar
-/// - Node containing a binary array or scalar
+/// - Node containing a binary or integer array or scalar
+/// - Node containing a binary or integer array or scalar
 ///
 /// # Custom operation returns
 ///
@@ -713,6 +825,21 @@ pub struct LessThanEqualTo {
 #[typetag::serde]
 impl CustomOperationBody for LessThanEqualTo {
     fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if get_integer_arguments_scalar_type(
+            &arguments_types,
+            Some(self.signed_comparison),
+            &self.get_name(),
+        )?
+        .is_some()
+        {
+            return instantiate_integer_comparison(
+                context,
+                arguments_types,
+                CustomOperation::new(LessThanEqualTo {
+                    signed_comparison: self.signed_comparison,
+                }),
+            );
+        }
         self.validate_comparison_arguments(arguments_types.clone())?;
         let less_than_equal_to = context.create_graph()?;
         let a = less_than_equal_to.input(arguments_types[0].clone())?;
@@ -737,6 +864,8 @@ impl CustomOperationBody for LessThanEqualTo {
     }
 }
 
+register_custom_operation!(LessThanEqualTo, [Array, Array]);
+
 #[typetag::serde]
 impl ComparisonCustomOperation for LessThanEqualTo {
     fn validate_c_op_specific_arguments(&self, arguments_types: Vec<Type>) -> Result<()> {
@@ -759,14 +888,17 @@ impl NeedsSignedPreprocessing for LessThanEqualTo {
 /// If input shapes are different, the broadcasting rules are applied (see [the NumPy broadcasting rules](https://numpy.org/doc/stable/user/basics.broadcasting.html)).
 /// For example, if input arrays are of shapes `[2,3]`, and `[1,3]`, the resulting array has shape `[2]`.
 ///
-/// To compare signed numbers, `signed_comparison` should be set `true`.
+/// To compare signed numbers, `signed_comparison` should be set `true`; for integer inputs, it must match the signedness of their scalar type.
 ///
 /// To use this and other custom operations in computation graphs, see [Graph::custom_op].
 ///
+/// Instead of bitstrings, the inputs can be arrays or scalars of the same integer type, which are decomposed into bits with [Graph::a2b].
+/// In this case, the result has the broadcast shape of the inputs.
+///
 /// # Custom operation arguments
 ///
-/// - Node containing a binary array or scalar
-/// - Node containing a binary array or scalar
+/// - Node containing a binary or integer array or scalar
+/// - Node containing a binary or integer array or scalar
 ///
 /// # Custom operation returns
 ///
@@ -795,6 +927,21 @@ pub struct GreaterThanEqualTo {
 #[typetag::serde]
 impl CustomOperationBody for GreaterThanEqualTo {
     fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if get_integer_arguments_scalar_type(
+            &arguments_types,
+            Some(self.signed_comparison),
+            &self.get_name(),
+        )?
+        .is_some()
+        {
+            return instantiate_integer_comparison(
+                context,
+                arguments_types,
+                CustomOperation::new(GreaterThanEqualTo {
+                    signed_comparison: self.signed_comparison,
+                }),
+            );
+        }
         self.validate_comparison_arguments(arguments_types.clone())?;
         let greater_than_equal_to = context.create_graph()?;
         let a = greater_than_equal_to.input(arguments_types[0].clone())?;
@@ -819,6 +966,8 @@ impl CustomOperationBody for GreaterThanEqualTo {
     }
 }
 
+register_custom_operation!(GreaterThanEqualTo, [Array, Array]);
+
 #[typetag::serde]
 impl ComparisonCustomOperation for GreaterThanEqualTo {
     fn validate_c_op_specific_arguments(&self, arguments_types: Vec<Type>) -> Result<()> {
@@ -843,10 +992,13 @@ impl NeedsSignedPreprocessing for GreaterThanEqualTo {
 ///
 /// To use this and other custom operations in computation graphs, see [Graph::custom_op].
 ///
+/// Instead of bitstrings, the inputs can be arrays or scalars of the same integer type, which are decomposed into bits with [Graph::a2b].
+/// In this case, the result has the broadcast shape of the inputs.
+///
 /// # Custom operation arguments
 ///
-/// - Node containing a binary array or scalar
-/// - Node containing a binary array or scalar
+/// - Node containing a binary or integer array or scalar
+/// - Node containing a binary or integer array or scalar
 ///
 /// # Custom operation returns
 ///
@@ -872,6 +1024,13 @@ pub struct Equal {}
 #[typetag::serde]
 impl CustomOperationBody for Equal {
     fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if get_integer_arguments_scalar_type(&arguments_types, None, &self.get_name())?.is_some() {
+            return instantiate_integer_comparison(
+                context,
+                arguments_types,
+                CustomOperation::new(Equal {}),
+            );
+        }
         self.validate_comparison_arguments(arguments_types.clone())?;
         //Graph to compare two n bit numbers as arrays
         let equal_to = context.create_graph()?;
@@ -889,6 +1048,8 @@ impl CustomOperationBody for Equal {
     }
 }
 
+register_custom_operation!(Equal, [Array, Array]);
+
 #[typetag::serde]
 impl ComparisonCustomOperation for Equal {}
 
@@ -2455,4 +2616,101 @@ mod tests {
         }()
         .unwrap();
     }
+
+    #[test]
+    fn test_integer_comparisons() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let a = g.input(array_type(vec![5], INT32))?;
+            let b = g.input(scalar_type(INT32))?;
+            let ops = vec![
+                CustomOperation::new(GreaterThan {
+                    signed_comparison: true,
+                }),
+                CustomOperation::new(LessThan {
+                    signed_comparison: true,
+                }),
+                CustomOperation::new(GreaterThanEqualTo {
+                    signed_comparison: true,
+                }),
+                CustomOperation::new(LessThanEqualTo {
+                    signed_comparison: true,
+                }),
+                CustomOperation::new(Equal {}),
+                CustomOperation::new(NotEqual {}),
+            ];
+            let mut outputs = vec![];
+            for op in ops {
+                outputs.push(g.custom_op(op, vec![a.clone(), b.clone()])?);
+            }
+            g.create_tuple(outputs)?.set_as_output()?;
+            g.finalize()?;
+            c.set_main_graph(g.clone())?;
+            c.finalize()?;
+            let mapped_c = run_instantiation_pass(c)?;
+            let result = random_evaluate(
+                mapped_c.mappings.get_graph(g),
+                vec![
+                    Value::from_flattened_array(&[-5, -1, 0, 7, i32::MAX], INT32)?,
+                    Value::from_scalar(-1, INT32)?,
+                ],
+            )?
+            .to_vector()?;
+            let t = array_type(vec![5], BIT);
+            let expected = vec![
+                vec![0, 0, 1, 1, 1],
+                vec![1, 0, 0, 0, 0],
+                vec![0, 1, 1, 1, 1],
+                vec![1, 1, 0, 0, 0],
+                vec![0, 1, 0, 0, 0],
+                vec![1, 0, 1, 1, 1],
+            ];
+            for (value, expected_bits) in result.iter().zip(expected) {
+                assert_eq!(value.to_flattened_array_u64(t.clone())?, expected_bits);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_integer_comparisons() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let a = g.input(array_type(vec![5], INT32))?;
+            let b = g.input(scalar_type(UINT32))?;
+            let u = g.input(array_type(vec![5], UINT32))?;
+            assert!(g
+                .custom_op(CustomOperation::new(Equal {}), vec![a.clone(), b.clone()])
+                .is_err());
+            assert!(g
+                .custom_op(
+                    CustomOperation::new(GreaterThan {
+                        signed_comparison: false
+                    }),
+                    vec![a.clone(), a.clone()]
+                )
+                .is_err());
+            assert!(g
+                .custom_op(
+                    CustomOperation::new(LessThan {
+                        signed_comparison: true
+                    }),
+                    vec![u.clone(), b.clone()]
+                )
+                .is_err());
+            assert!(g
+                .custom_op(
+                    CustomOperation::new(LessThan {
+                        signed_comparison: false
+                    }),
+                    vec![u, b]
+                )
+                .is_ok());
+            Ok(())
+        }()
+        .unwrap();
+    }
 }
@@ -0,0 +1,619 @@
+//! 2D convolution and pooling of integer arrays in the NHWC layout, i.e. of shape `[batch, height, width, channels]`.
+//!
+//! Windows of an input are extracted by strided slices, so convolution is computed by [Gemm](crate::graphs::Operation::Gemm) on the [im2col](https://en.wikipedia.org/wiki/Im2col) matrix,
+//! max pooling by a tree of [Max] operations and average pooling by summation followed by [Truncate](crate::graphs::Operation::Truncate).
+use crate::custom_ops::{CustomOperation, CustomOperationBody};
+use crate::data_types::{array_type, ArrayShape, ScalarType, Type, BIT};
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, Node, SliceElement};
+
+use super::min_max::Max;
+
+use serde::{Deserialize, Serialize};
+
+/// A structure that defines the custom operation Conv2D that computes the 2D convolution (more precisely, cross-correlation as in most ML frameworks) of an input with a kernel without padding.
+///
+/// The input must be an integer array of shape `[batch, height, width, in_channels]`.
+/// The kernel must be an array of the same scalar type and of shape `[out_channels, kernel_height, kernel_width, in_channels]`.
+/// The output has shape `[batch, (height - kernel_height) / stride + 1, (width - kernel_width) / stride + 1, out_channels]`.
+///
+/// To use this and other custom operations in computation graphs, see [Graph::custom_op].
+///
+/// # Custom operation arguments
+///
+/// - Node containing an integer array of shape `[batch, height, width, in_channels]`
+/// - Node containing an integer array of shape `[out_channels, kernel_height, kernel_width, in_channels]`
+///
+/// # Custom operation returns
+///
+/// New Conv2D node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT32};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::convolution::Conv2D;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let x = g.input(array_type(vec![1, 28, 28, 1], INT32)).unwrap();
+/// let kernel = g.input(array_type(vec![8, 3, 3, 1], INT32)).unwrap();
+/// let y = g.custom_op(CustomOperation::new(Conv2D {stride: 1}), vec![x, kernel]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct Conv2D {
+    /// Step between windows in both spatial dimensions
+    pub stride: u64,
+}
+
+/// Checks that a type is an integer array of shape `[batch, height, width, channels]` and returns its shape and scalar type.
+fn get_nhwc_shape(t: &Type, name: &str) -> Result<(ArrayShape, ScalarType)> {
+    if !t.is_array() || t.get_scalar_type() == BIT || t.get_shape().len() != 4 {
+        return Err(runtime_error!(
+            "{} expects an integer array of shape [batch, height, width, channels], got {}",
+            name,
+            t
+        ));
+    }
+    Ok((t.get_shape(), t.get_scalar_type()))
+}
+
+/// Returns the number of windows along the spatial dimensions of an input.
+fn get_output_size(
+    shape: &[u64],
+    window_height: u64,
+    window_width: u64,
+    stride: u64,
+    name: &str,
+) -> Result<(u64, u64)> {
+    if stride == 0 {
+        return Err(runtime_error!("{}: stride must be positive", name));
+    }
+    if window_height == 0
+        || window_width == 0
+        || window_height > shape[1]
+        || window_width > shape[2]
+    {
+        return Err(runtime_error!(
+            "{}: window of size {}x{} doesn't fit the input of shape {:?}",
+            name,
+            window_height,
+            window_width,
+            shape
+        ));
+    }
+    Ok((
+        (shape[1] - window_height) / stride + 1,
+        (shape[2] - window_width) / stride + 1,
+    ))
+}
+
+/// Returns `window_height * window_width` arrays of shape `[batch, out_height, out_width, channels]`,
+/// such that the array with index `i * window_width + j` contains the elements with offset `(i, j)` of all the windows.
+fn get_window_elements(
+    x: Node,
+    window_height: u64,
+    window_width: u64,
+    stride: u64,
+) -> Result<Vec<Node>> {
+    let shape = x.get_type()?.get_shape();
+    let out_height = (shape[1] - window_height) / stride + 1;
+    let out_width = (shape[2] - window_width) / stride + 1;
+    let strided_range = |offset: u64, size: u64| {
+        SliceElement::SubArray(
+            Some(offset as i64),
+            Some((offset + stride * (size - 1) + 1) as i64),
+            Some(stride as i64),
+        )
+    };
+    let mut elements = vec![];
+    for i in 0..window_height {
+        for j in 0..window_width {
+            elements.push(x.get_slice(vec![
+                SliceElement::SubArray(None, None, None),
+                strided_range(i, out_height),
+                strided_range(j, out_width),
+            ])?);
+        }
+    }
+    Ok(elements)
+}
+
+#[typetag::serde]
+impl CustomOperationBody for Conv2D {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 2 {
+            return Err(runtime_error!("Invalid number of arguments for Conv2D"));
+        }
+        let (input_shape, st) = get_nhwc_shape(&arguments_types[0], "Conv2D")?;
+        let (kernel_shape, kernel_st) = get_nhwc_shape(&arguments_types[1], "Conv2D")?;
+        if st != kernel_st || input_shape[3] != kernel_shape[3] {
+            return Err(runtime_error!(
+                "Conv2D: kernel of type {} doesn't match the input of type {}",
+                arguments_types[1],
+                arguments_types[0]
+            ));
+        }
+        let (out_height, out_width) = get_output_size(
+            &input_shape,
+            kernel_shape[1],
+            kernel_shape[2],
+            self.stride,
+            "Conv2D",
+        )?;
+        let g = context.create_graph()?;
+        let x = g.input(arguments_types[0].clone())?;
+        let kernel = g.input(arguments_types[1].clone())?;
+        let window_size = kernel_shape[1] * kernel_shape[2];
+        let elements = get_window_elements(x, kernel_shape[1], kernel_shape[2], self.stride)?;
+        // im2col: [window_size, batch, out_height, out_width, in_channels] -> [batch * out_height * out_width, window_size * in_channels]
+        let patches = g
+            .stack(elements, vec![window_size])?
+            .permute_axes(vec![1, 2, 3, 0, 4])?
+            .reshape(array_type(
+                vec![
+                    input_shape[0] * out_height * out_width,
+                    window_size * input_shape[3],
+                ],
+                st.clone(),
+            ))?;
+        let kernel = kernel.reshape(array_type(
+            vec![kernel_shape[0], window_size * input_shape[3]],
+            st.clone(),
+        ))?;
+        patches
+            .gemm(kernel, false, true)?
+            .reshape(array_type(
+                vec![input_shape[0], out_height, out_width, kernel_shape[0]],
+                st,
+            ))?
+            .set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!("Conv2D(stride={})", self.stride)
+    }
+}
+
+register_custom_operation!(Conv2D, [Array, Array]);
+
+/// A structure that defines the custom operation MaxPool2D that computes the maximum of every `pool_size x pool_size` window of an input without padding.
+///
+/// The input must be an integer array of shape `[batch, height, width, channels]`.
+/// The output has shape `[batch, (height - pool_size) / stride + 1, (width - pool_size) / stride + 1, channels]`.
+///
+/// Maxima are computed by a balanced tree of [Max] operations, so the number of comparison layers is logarithmic in the window size.
+///
+/// To compare signed numbers, `signed_comparison` should be set `true`; it must match the signedness of the input scalar type.
+///
+/// To use this and other custom operations in computation graphs, see [Graph::custom_op].
+///
+/// # Custom operation arguments
+///
+/// - Node containing an integer array of shape `[batch, height, width, channels]`
+///
+/// # Custom operation returns
+///
+/// New MaxPool2D node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT32};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::convolution::MaxPool2D;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let x = g.input(array_type(vec![1, 28, 28, 8], INT32)).unwrap();
+/// let op = MaxPool2D {pool_size: 2, stride: 2, signed_comparison: true};
+/// let y = g.custom_op(CustomOperation::new(op), vec![x]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct MaxPool2D {
+    /// Height and width of windows
+    pub pool_size: u64,
+    /// Step between windows in both spatial dimensions
+    pub stride: u64,
+    /// Boolean value indicating whether input integers are signed
+    pub signed_comparison: bool,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for MaxPool2D {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 1 {
+            return Err(runtime_error!("Invalid number of arguments for MaxPool2D"));
+        }
+        let (input_shape, _) = get_nhwc_shape(&arguments_types[0], "MaxPool2D")?;
+        get_output_size(
+            &input_shape,
+            self.pool_size,
+            self.pool_size,
+            self.stride,
+            "MaxPool2D",
+        )?;
+        let g = context.create_graph()?;
+        let x = g.input(arguments_types[0].clone())?;
+        let mut elements = get_window_elements(x, self.pool_size, self.pool_size, self.stride)?;
+        // Every layer of the tree compares the first half of the remaining elements with the second half by one Max operation
+        while elements.len() > 1 {
+            let half = elements.len() / 2;
+            let maxima = g.custom_op(
+                CustomOperation::new(Max {
+                    signed_comparison: self.signed_comparison,
+                }),
+                vec![
+                    g.stack(elements[..half].to_vec(), vec![half as u64])?,
+                    g.stack(elements[half..2 * half].to_vec(), vec![half as u64])?,
+                ],
+            )?;
+            let mut next_elements = vec![];
+            for i in 0..half {
+                next_elements.push(maxima.get(vec![i as u64])?);
+            }
+            if elements.len() % 2 == 1 {
+                next_elements.push(elements[elements.len() - 1].clone());
+            }
+            elements = next_elements;
+        }
+        elements[0].set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "MaxPool2D(pool_size={}, stride={}, signed_comparison={})",
+            self.pool_size, self.stride, self.signed_comparison
+        )
+    }
+}
+
+register_custom_operation!(MaxPool2D, [Array]);
+
+/// A structure that defines the custom operation AvgPool2D that computes the average of every `pool_size x pool_size` window of an input without padding.
+///
+/// The input must be an integer array of shape `[batch, height, width, channels]`.
+/// The output has shape `[batch, (height - pool_size) / stride + 1, (width - pool_size) / stride + 1, channels]`.
+///
+/// The sum of every window is divided by `pool_size^2` via [Truncate](crate::graphs::Operation::Truncate), which rounds towards zero for signed integers.
+/// Within MPC, the result might be off by 1 and the sums must satisfy the range requirements of truncation.
+///
+/// To use this and other custom operations in computation graphs, see [Graph::custom_op].
+///
+/// # Custom operation arguments
+///
+/// - Node containing an integer array of shape `[batch, height, width, channels]`
+///
+/// # Custom operation returns
+///
+/// New AvgPool2D node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT32};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::convolution::AvgPool2D;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let x = g.input(array_type(vec![1, 28, 28, 8], INT32)).unwrap();
+/// let y = g.custom_op(CustomOperation::new(AvgPool2D {pool_size: 2, stride: 2}), vec![x]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct AvgPool2D {
+    /// Height and width of windows
+    pub pool_size: u64,
+    /// Step between windows in both spatial dimensions
+    pub stride: u64,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for AvgPool2D {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 1 {
+            return Err(runtime_error!("Invalid number of arguments for AvgPool2D"));
+        }
+        let (input_shape, _) = get_nhwc_shape(&arguments_types[0], "AvgPool2D")?;
+        get_output_size(
+            &input_shape,
+            self.pool_size,
+            self.pool_size,
+            self.stride,
+            "AvgPool2D",
+        )?;
+        let g = context.create_graph()?;
+        let x = g.input(arguments_types[0].clone())?;
+        let window_size = self.pool_size * self.pool_size;
+        let elements = get_window_elements(x, self.pool_size, self.pool_size, self.stride)?;
+        let mut sums = g.stack(elements, vec![window_size])?.sum(vec![0])?;
+        if window_size > 1 {
+            sums = sums.truncate(window_size)?;
+        }
+        sums.set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "AvgPool2D(pool_size={}, stride={})",
+            self.pool_size, self.stride
+        )
+    }
+}
+
+register_custom_operation!(AvgPool2D, [Array]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::run_instantiation_pass;
+    use crate::data_types::{INT32, INT64, UINT32};
+    use crate::data_values::Value;
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::create_context;
+    use crate::inline::inline_ops::InlineConfig;
+    use crate::mpc::mpc_compiler::{compile_context, IOStatus};
+
+    fn evaluate_op(
+        op: CustomOperation,
+        types: Vec<Type>,
+        values: Vec<Vec<i64>>,
+        output_shape: ArrayShape,
+    ) -> Result<Vec<i64>> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let st = types[0].get_scalar_type();
+        let mut inputs = vec![];
+        for t in types {
+            inputs.push(g.input(t)?);
+        }
+        g.custom_op(op, inputs)?.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        let instantiated_c = run_instantiation_pass(c)?.get_context();
+        let mut input_values = vec![];
+        for v in values {
+            input_values.push(Value::from_flattened_array(&v, st.clone())?);
+        }
+        random_evaluate(instantiated_c.get_main_graph()?, input_values)?
+            .to_flattened_array_i64(array_type(output_shape, st))
+    }
+
+    // Input of shape [2, 4, 5, 2] with the values 100 * b + 10 * h + w for the first channel and their negations for the second one
+    fn get_input() -> Vec<i64> {
+        let mut x = vec![];
+        for b in 0..2 {
+            for h in 0..4 {
+                for w in 0..5 {
+                    let v = 100 * b + 10 * h + w;
+                    x.extend([v, -v]);
+                }
+            }
+        }
+        x
+    }
+
+    #[test]
+    fn test_conv2d() {
+        || -> Result<()> {
+            // Two output channels: the sum of the first channel over a 2x3 window and the difference of its top-left and bottom-right elements in the second channel
+            let mut kernel = vec![0; 2 * 2 * 3 * 2];
+            for i in 0..6 {
+                kernel[2 * i] = 1;
+            }
+            kernel[12 + 1] = 1;
+            kernel[12 + 11] = -1;
+            let result = evaluate_op(
+                CustomOperation::new(Conv2D { stride: 2 }),
+                vec![
+                    array_type(vec![2, 4, 5, 2], INT64),
+                    array_type(vec![2, 2, 3, 2], INT64),
+                ],
+                vec![get_input(), kernel],
+                vec![2, 2, 2, 2],
+            )?;
+            let mut expected = vec![];
+            for b in 0..2 {
+                for h in [0, 2] {
+                    for w in [0, 2] {
+                        let top_left = 100 * b + 10 * h + w;
+                        expected.push(6 * top_left + 3 * 10 + 2 * 3);
+                        expected.push(-top_left + (top_left + 10 + 2));
+                    }
+                }
+            }
+            assert_eq!(result, expected);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_pooling() {
+        || -> Result<()> {
+            let t = array_type(vec![2, 4, 5, 2], INT64);
+            // 3x3 windows with stride 1 exercise a tree with an odd number of elements
+            let result = evaluate_op(
+                CustomOperation::new(MaxPool2D {
+                    pool_size: 3,
+                    stride: 1,
+                    signed_comparison: true,
+                }),
+                vec![t.clone()],
+                vec![get_input()],
+                vec![2, 2, 3, 2],
+            )?;
+            let mut expected = vec![];
+            for b in 0..2 {
+                for h in 0..2 {
+                    for w in 0..3 {
+                        let top_left = 100 * b + 10 * h + w;
+                        expected.extend([top_left + 22, -top_left]);
+                    }
+                }
+            }
+            assert_eq!(result, expected);
+            let result = evaluate_op(
+                CustomOperation::new(AvgPool2D {
+                    pool_size: 2,
+                    stride: 2,
+                }),
+                vec![t],
+                vec![get_input()],
+                vec![2, 2, 2, 2],
+            )?;
+            let mut expected = vec![];
+            for b in 0..2 {
+                for h in [0, 2] {
+                    for w in [0, 2] {
+                        // (4 * top_left + 22) / 4 rounded towards zero
+                        let top_left = 100 * b + 10 * h + w;
+                        expected.extend([top_left + 5, -top_left - 5]);
+                    }
+                }
+            }
+            assert_eq!(result, expected);
+            // Windows of size 1 return the input
+            let x = vec![5, -3, 7, 1];
+            let t = array_type(vec![1, 2, 2, 1], INT64);
+            let result = evaluate_op(
+                CustomOperation::new(MaxPool2D {
+                    pool_size: 1,
+                    stride: 1,
+                    signed_comparison: true,
+                }),
+                vec![t.clone()],
+                vec![x.clone()],
+                vec![1, 2, 2, 1],
+            )?;
+            assert_eq!(result, x);
+            let result = evaluate_op(
+                CustomOperation::new(MaxPool2D {
+                    pool_size: 2,
+                    stride: 1,
+                    signed_comparison: true,
+                }),
+                vec![t],
+                vec![x],
+                vec![1, 1, 1, 1],
+            )?;
+            assert_eq!(result, vec![7]);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_convolution_compiles() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(array_type(vec![1, 5, 5, 2], INT64))?;
+            let kernel = g.input(array_type(vec![3, 3, 3, 2], INT64))?;
+            let y = g.custom_op(CustomOperation::new(Conv2D { stride: 1 }), vec![x, kernel])?;
+            let max_pooled = g.custom_op(
+                CustomOperation::new(MaxPool2D {
+                    pool_size: 2,
+                    stride: 1,
+                    signed_comparison: true,
+                }),
+                vec![y.clone()],
+            )?;
+            let avg_pooled = g.custom_op(
+                CustomOperation::new(AvgPool2D {
+                    pool_size: 3,
+                    stride: 1,
+                }),
+                vec![y],
+            )?;
+            g.create_tuple(vec![max_pooled, avg_pooled])?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            compile_context(
+                c,
+                vec![IOStatus::Party(0), IOStatus::Party(1)],
+                vec![IOStatus::Party(0)],
+                InlineConfig::default(),
+                || SimpleEvaluator::new(None),
+            )?;
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed() {
+        let t = array_type(vec![1, 4, 4, 2], INT32);
+        let kernel_t = array_type(vec![3, 2, 2, 2], INT32);
+        let check = |op: CustomOperation, types: Vec<Type>| -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let mut inputs = vec![];
+            for t in types {
+                inputs.push(g.input(t)?);
+            }
+            g.custom_op(op, inputs)?;
+            Ok(())
+        };
+        let conv = || CustomOperation::new(Conv2D { stride: 1 });
+        assert!(check(conv(), vec![t.clone(), kernel_t.clone()]).is_ok());
+        assert!(check(conv(), vec![t.clone()]).is_err());
+        assert!(check(
+            CustomOperation::new(Conv2D { stride: 0 }),
+            vec![t.clone(), kernel_t.clone()]
+        )
+        .is_err());
+        // Mismatching channels, scalar types and ranks
+        assert!(check(conv(), vec![t.clone(), array_type(vec![3, 2, 2, 1], INT32)]).is_err());
+        assert!(check(conv(), vec![t.clone(), array_type(vec![3, 2, 2, 2], INT64)]).is_err());
+        assert!(check(
+            conv(),
+            vec![array_type(vec![4, 4, 2], INT32), kernel_t.clone()]
+        )
+        .is_err());
+        assert!(check(conv(), vec![array_type(vec![1, 4, 4, 2], BIT), kernel_t]).is_err());
+        // Kernel larger than the input
+        assert!(check(conv(), vec![t.clone(), array_type(vec![3, 5, 2, 2], INT32)]).is_err());
+        let max_pool = |pool_size: u64, signed_comparison: bool| {
+            CustomOperation::new(MaxPool2D {
+                pool_size,
+                stride: 1,
+                signed_comparison,
+            })
+        };
+        assert!(check(max_pool(4, true), vec![t.clone()]).is_ok());
+        assert!(check(max_pool(5, true), vec![t.clone()]).is_err());
+        assert!(check(max_pool(0, true), vec![t.clone()]).is_err());
+        assert!(check(max_pool(2, false), vec![t.clone()]).is_err());
+        assert!(check(
+            max_pool(2, false),
+            vec![array_type(vec![1, 4, 4, 2], UINT32)]
+        )
+        .is_ok());
+        assert!(check(
+            CustomOperation::new(AvgPool2D {
+                pool_size: 2,
+                stride: 0
+            }),
+            vec![t.clone()]
+        )
+        .is_err());
+        assert!(check(
+            CustomOperation::new(AvgPool2D {
+                pool_size: 2,
+                stride: 3
+            }),
+            vec![t, array_type(vec![1, 4, 4, 2], INT32)]
+        )
+        .is_err());
+    }
+}
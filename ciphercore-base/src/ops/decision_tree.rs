@@ -0,0 +1,391 @@
+//! Oblivious inference of decision trees and tree ensembles (e.g. random forests).
+use crate::custom_ops::{CustomOperation, CustomOperationBody};
+use crate::data_types::{array_type, Type, BIT};
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, SliceElement};
+
+use serde::{Deserialize, Serialize};
+
+use super::comparisons::GreaterThan;
+use super::utils::constant_scalar;
+
+/// A structure that defines the custom operation DecisionTreeInference that evaluates complete binary decision trees on a batch of samples
+/// without revealing the path taken by any sample.
+///
+/// A tree of depth `d` has `m = 2^d - 1` internal nodes and `2^d` leaves, both enumerated in the heap order,
+/// i.e. the children of the internal node `i` are nodes `2i + 1` and `2i + 2`, while the leaves follow the last level of internal nodes.
+/// Every internal node compares one feature of a sample with a threshold;
+/// if the feature is greater than the threshold, the evaluation proceeds to the right child, otherwise to the left one.
+/// The prediction of a tree is the value of the reached leaf.
+///
+/// To hide which features are compared, the feature of every internal node is given by a one-hot row of a selection matrix.
+/// Thus, all the inputs (including the tree structure) can be either public or secret-shared.
+///
+/// All the nodes of a tree are evaluated at once, so the operation performs a single batch of comparisons regardless of the tree depth.
+/// Then, the reached leaf is selected by multiplying the comparison bits along every root-to-leaf path.
+///
+/// To evaluate an ensemble of `t` trees (e.g. a random forest), the tree inputs should have an additional first dimension `t`.
+/// In this case, the predictions of all the trees are summed; e.g. to average them, leaf values should be divided by `t` in advance.
+///
+/// All the inputs must have the same non-binary scalar type; comparisons are signed if this type is signed.
+///
+/// # Custom operation arguments
+///
+/// - Node containing an array of shape `[n, f]` with `f` features of `n` samples
+/// - Node containing an array of shape `[m, f]` or `[t, m, f]` with one-hot rows selecting the feature of every internal node
+/// - Node containing an array of shape `[m]` or `[t, m]` with thresholds of internal nodes
+/// - Node containing an array of shape `[m + 1]` or `[t, m + 1]` with leaf values
+///
+/// # Custom operation returns
+///
+/// New DecisionTreeInference node containing an array of shape `[n]` with predictions
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT64};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::decision_tree::DecisionTreeInference;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let features = g.input(array_type(vec![10, 4], INT64)).unwrap();
+/// let selectors = g.input(array_type(vec![5, 7, 4], INT64)).unwrap();
+/// let thresholds = g.input(array_type(vec![5, 7], INT64)).unwrap();
+/// let leaves = g.input(array_type(vec![5, 8], INT64)).unwrap();
+/// let predictions = g.custom_op(CustomOperation::new(DecisionTreeInference {}), vec![features, selectors, thresholds, leaves]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct DecisionTreeInference {}
+
+#[typetag::serde]
+impl CustomOperationBody for DecisionTreeInference {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 4 {
+            return Err(runtime_error!(
+                "Invalid number of arguments for DecisionTreeInference"
+            ));
+        }
+        for t in arguments_types.iter() {
+            if !t.is_array() {
+                return Err(runtime_error!(
+                    "Arguments of DecisionTreeInference must be arrays"
+                ));
+            }
+        }
+        let st = arguments_types[0].get_scalar_type();
+        if st == BIT {
+            return Err(runtime_error!(
+                "Arguments of DecisionTreeInference can't be binary"
+            ));
+        }
+        if arguments_types.iter().any(|t| t.get_scalar_type() != st) {
+            return Err(runtime_error!(
+                "Arguments of DecisionTreeInference must have the same scalar type"
+            ));
+        }
+        let features_shape = arguments_types[0].get_shape();
+        let selectors_shape = arguments_types[1].get_shape();
+        let thresholds_shape = arguments_types[2].get_shape();
+        let leaves_shape = arguments_types[3].get_shape();
+        if features_shape.len() != 2 {
+            return Err(runtime_error!("Features must be a 2-dimensional array"));
+        }
+        let num_samples = features_shape[0];
+        let num_features = features_shape[1];
+        if selectors_shape.len() != 2 && selectors_shape.len() != 3 {
+            return Err(runtime_error!(
+                "Feature selectors must be a 2- or 3-dimensional array"
+            ));
+        }
+        // Shape of the ensemble, i.e. [t] or [] for a single tree
+        let ensemble_shape = selectors_shape[..selectors_shape.len() - 2].to_vec();
+        let num_internal_nodes = selectors_shape[selectors_shape.len() - 2];
+        let num_leaves = num_internal_nodes + 1;
+        if selectors_shape[selectors_shape.len() - 1] != num_features {
+            return Err(runtime_error!(
+                "Feature selectors must have {} columns",
+                num_features
+            ));
+        }
+        if !num_leaves.is_power_of_two() {
+            return Err(runtime_error!(
+                "Number of internal nodes must be 2^d - 1, but {} is given",
+                num_internal_nodes
+            ));
+        }
+        let mut expected_thresholds_shape = ensemble_shape.clone();
+        expected_thresholds_shape.push(num_internal_nodes);
+        let mut expected_leaves_shape = ensemble_shape.clone();
+        expected_leaves_shape.push(num_leaves);
+        if thresholds_shape != expected_thresholds_shape || leaves_shape != expected_leaves_shape {
+            return Err(runtime_error!(
+                "Thresholds and leaves must have shapes {:?} and {:?}",
+                expected_thresholds_shape,
+                expected_leaves_shape
+            ));
+        }
+        let depth = num_leaves.trailing_zeros() as u64;
+
+        let g = context.create_graph()?;
+        let features = g.input(arguments_types[0].clone())?;
+        let selectors = g.input(arguments_types[1].clone())?;
+        let thresholds = g.input(arguments_types[2].clone())?;
+        let leaves = g.input(arguments_types[3].clone())?;
+
+        // Shape [t, 1, k] or [k] of per-node values broadcast over samples
+        let broadcast_per_node = |shape_suffix: u64| -> Type {
+            let mut shape = ensemble_shape.clone();
+            if !shape.is_empty() {
+                shape.push(1);
+            }
+            shape.push(shape_suffix);
+            array_type(shape, st.clone())
+        };
+
+        // Features compared in every internal node, shape [t, n, m] or [n, m]
+        let rank = selectors_shape.len() as u64;
+        let mut transposition: Vec<u64> = (0..rank).collect();
+        transposition.swap(rank as usize - 2, rank as usize - 1);
+        let node_features = features.matmul(selectors.permute_axes(transposition)?)?;
+        let node_thresholds = thresholds.reshape(broadcast_per_node(num_internal_nodes))?;
+        let goes_right = g.custom_op(
+            CustomOperation::new(GreaterThan {
+                signed_comparison: st.get_signed(),
+            }),
+            vec![node_features.a2b()?, node_thresholds.a2b()?],
+        )?;
+
+        // Indicators of reaching nodes of the current level, shape [t, n, 2^level] or [n, 2^level]
+        let mut level_shape = ensemble_shape.clone();
+        level_shape.extend([num_samples, 1]);
+        // The root is reached by all the samples; the scalar is broadcast at the first level
+        let mut reached = constant_scalar(&g, 1, BIT)?;
+        for level in 0..depth {
+            let level_size = 1 << level;
+            let level_goes_right = goes_right.get_slice(vec![
+                SliceElement::Ellipsis,
+                SliceElement::SubArray(Some(level_size - 1), Some(2 * level_size - 1), None),
+            ])?;
+            let reached_right = reached.multiply(level_goes_right)?;
+            let reached_left = reached.add(reached_right.clone())?;
+            // Interleave children: node j of the current level has children 2j and 2j + 1 on the next level
+            let children = g.stack(vec![reached_left, reached_right], vec![2])?;
+            let mut interleaving: Vec<u64> = (1..=level_shape.len() as u64).collect();
+            interleaving.push(0);
+            let last_axis = level_shape.len() - 1;
+            level_shape[last_axis] *= 2;
+            reached = children
+                .permute_axes(interleaving)?
+                .reshape(array_type(level_shape.clone(), BIT))?;
+        }
+
+        let leaves = leaves.reshape(broadcast_per_node(num_leaves))?;
+        let tree_predictions = leaves
+            .mixed_multiply(reached)?
+            .sum(vec![level_shape.len() as u64 - 1])?;
+        let predictions = if ensemble_shape.is_empty() {
+            tree_predictions
+        } else {
+            tree_predictions.sum(vec![0])?
+        };
+        predictions.set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        "DecisionTreeInference".to_owned()
+    }
+}
+
+register_custom_operation!(DecisionTreeInference, [Array, Array, Array, Array]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::custom_ops::run_instantiation_pass;
+    use crate::data_types::{ScalarType, INT32, INT64, UINT32};
+    use crate::data_values::Value;
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::create_context;
+    use crate::inline::inline_common::DepthOptimizationLevel;
+    use crate::inline::inline_ops::{InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{compile_context, IOStatus};
+
+    fn one_hot_rows(columns: &[u64], num_features: u64) -> Vec<u64> {
+        let mut rows = vec![0; columns.len() * num_features as usize];
+        for (i, column) in columns.iter().enumerate() {
+            rows[i * num_features as usize + *column as usize] = 1;
+        }
+        rows
+    }
+
+    fn create_inference_context(
+        st: ScalarType,
+        num_samples: u64,
+        num_features: u64,
+        ensemble_shape: Vec<u64>,
+        depth: u64,
+    ) -> Result<Context> {
+        let num_leaves = 1 << depth;
+        let tree_type = |suffix: Vec<u64>| {
+            let mut shape = ensemble_shape.clone();
+            shape.extend(suffix);
+            array_type(shape, st.clone())
+        };
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let features = g.input(array_type(vec![num_samples, num_features], st.clone()))?;
+        let selectors = g.input(tree_type(vec![num_leaves - 1, num_features]))?;
+        let thresholds = g.input(tree_type(vec![num_leaves - 1]))?;
+        let leaves = g.input(tree_type(vec![num_leaves]))?;
+        g.custom_op(
+            CustomOperation::new(DecisionTreeInference {}),
+            vec![features, selectors, thresholds, leaves],
+        )?
+        .set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        Ok(c)
+    }
+
+    #[test]
+    fn test_decision_tree() {
+        || -> Result<()> {
+            // Depth-2 tree:
+            //   node 0: x1 > 5?
+            //   node 1 (left): x0 > 0? leaves 10 / 20
+            //   node 2 (right): x2 > -3? leaves 30 / 40
+            let c = create_inference_context(INT64, 5, 3, vec![], 2)?;
+            let instantiated_c = run_instantiation_pass(c)?.get_context();
+            let features = vec![
+                -1, 5, 0, // left, left
+                1, 5, 0, // left, right
+                0, 6, -3, // right, left
+                0, 6, -2, // right, right
+                7, -8, 9, // left, right
+            ];
+            let result = random_evaluate(
+                instantiated_c.get_main_graph()?,
+                vec![
+                    Value::from_flattened_array(&features, INT64)?,
+                    Value::from_flattened_array(&one_hot_rows(&[1, 0, 2], 3), INT64)?,
+                    Value::from_flattened_array(&[5, 0, -3], INT64)?,
+                    Value::from_flattened_array(&[10, 20, 30, 40], INT64)?,
+                ],
+            )?;
+            assert_eq!(
+                result.to_flattened_array_i64(array_type(vec![5], INT64))?,
+                vec![10, 20, 30, 40, 20]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_random_forest() {
+        || -> Result<()> {
+            // Two trees of depth 1 comparing different features
+            let c = create_inference_context(UINT32, 4, 2, vec![2], 1)?;
+            let instantiated_c = run_instantiation_pass(c)?.get_context();
+            let result = random_evaluate(
+                instantiated_c.get_main_graph()?,
+                vec![
+                    Value::from_flattened_array(&[1, 1, 3, 1, 1, 5, 3, 5], UINT32)?,
+                    Value::from_flattened_array(&one_hot_rows(&[0, 1], 2), UINT32)?,
+                    Value::from_flattened_array(&[2, 4], UINT32)?,
+                    Value::from_flattened_array(&[1, 2, 10, 20], UINT32)?,
+                ],
+            )?;
+            assert_eq!(
+                result.to_flattened_array_u64(array_type(vec![4], UINT32))?,
+                vec![11, 12, 21, 22]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_decision_tree_compiles_end2end() {
+        || -> Result<()> {
+            let c = create_inference_context(INT32, 3, 4, vec![2], 3)?;
+            compile_context(
+                c,
+                vec![
+                    IOStatus::Shared,
+                    IOStatus::Party(0),
+                    IOStatus::Party(0),
+                    IOStatus::Public,
+                ],
+                vec![IOStatus::Party(1)],
+                InlineConfig {
+                    default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                    ..Default::default()
+                },
+                || SimpleEvaluator::new(None),
+            )?;
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_decision_tree() {
+        let check = |types: Vec<Type>| -> bool {
+            || -> Result<()> {
+                let c = create_context()?;
+                let g = c.create_graph()?;
+                let mut nodes = vec![];
+                for t in types {
+                    nodes.push(g.input(t)?);
+                }
+                g.custom_op(CustomOperation::new(DecisionTreeInference {}), nodes)?;
+                Ok(())
+            }()
+            .is_err()
+        };
+        let t = |shape: Vec<u64>| array_type(shape, INT32);
+        assert!(!check(vec![
+            t(vec![2, 3]),
+            t(vec![3, 3]),
+            t(vec![3]),
+            t(vec![4])
+        ]));
+        assert!(check(vec![t(vec![2, 3]), t(vec![3, 3]), t(vec![3])]));
+        assert!(check(vec![
+            t(vec![2, 3]),
+            t(vec![2, 3]),
+            t(vec![2]),
+            t(vec![3])
+        ]));
+        assert!(check(vec![
+            t(vec![2, 3]),
+            t(vec![3, 2]),
+            t(vec![3]),
+            t(vec![4])
+        ]));
+        assert!(check(vec![
+            t(vec![2, 3]),
+            t(vec![2, 3, 3]),
+            t(vec![3]),
+            t(vec![4])
+        ]));
+        assert!(check(vec![
+            t(vec![2, 3]),
+            t(vec![3, 3]),
+            t(vec![3]),
+            array_type(vec![4], INT64)
+        ]));
+        assert!(check(vec![
+            array_type(vec![2, 3], BIT),
+            array_type(vec![3, 3], BIT),
+            array_type(vec![3], BIT),
+            array_type(vec![4], BIT)
+        ]));
+    }
+}
@@ -194,6 +194,8 @@ impl CustomOperationBody for InverseSqrt {
     }
 }
 
+register_custom_operation!(InverseSqrt, [Array], [Array, Array]);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,363 @@
+//! Search of k nearest neighbors of a vector in a dataset.
+use crate::custom_ops::{CustomOperation, CustomOperationBody};
+use crate::data_types::{array_type, ScalarType, Type, BIT, UINT64};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, Node, SliceElement};
+use crate::ops::sorting::Sort;
+use crate::ops::utils::{concatenate_first_axis, pull_out_bits, put_in_bits, zeros};
+
+use serde::{Deserialize, Serialize};
+
+// Converts an array of numbers to bits with shape [bits, ...]
+fn to_bits_first(x: Node) -> Result<Node> {
+    if x.get_type()?.get_scalar_type() == BIT {
+        let mut shape = vec![1];
+        shape.extend(x.get_type()?.get_shape());
+        x.reshape(array_type(shape, BIT))
+    } else {
+        pull_out_bits(x.a2b()?)
+    }
+}
+
+/// A structure that defines the custom operation Knn that finds the `k` nearest neighbors of a query vector in a dataset of vectors
+/// with respect to the Euclidean distance.
+///
+/// The squared distances are computed via a matrix product of the dataset and the query.
+/// Then, the distances (together with the indices and, optionally, labels of the dataset vectors) are sorted obliviously
+/// by [Batcher's sorting network](https://math.mit.edu/~shor/18.310/batcher.pdf) (see [Sort]),
+/// so the order of the dataset vectors is not revealed even if the operation is compiled to MPC.
+/// Ties are broken in favor of smaller indices.
+///
+/// The dataset is padded to a power-of-two size, so the size of the sorting network is the same for all dataset sizes between 2<sup>l-1</sup> and 2<sup>l</sup>.
+///
+/// The squared distances must fit into the scalar type of the vectors interpreted as unsigned integers, otherwise the result is undefined.
+///
+/// # Custom operation arguments
+///
+/// - Node containing an array of shape `[d]` with the query vector
+/// - Node containing an array of shape `[n, d]` with the dataset vectors; it must have the same non-binary scalar type as the query
+/// - (optional) Node containing an array of shape `[n]` with labels of the dataset vectors
+///
+/// # Custom operation returns
+///
+/// New Knn node containing a UINT64 array of shape `[k]` with the indices of the nearest neighbors sorted by distance,
+/// or a tuple of such an array and an array of shape `[k]` with their labels if labels are given.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT32, BIT};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::knn::Knn;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let query = g.input(array_type(vec![4], INT32)).unwrap();
+/// let dataset = g.input(array_type(vec![10, 4], INT32)).unwrap();
+/// let labels = g.input(array_type(vec![10], BIT)).unwrap();
+/// let neighbors = g.custom_op(CustomOperation::new(Knn {k: 3}), vec![query, dataset, labels]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct Knn {
+    /// Number of neighbors to find
+    pub k: u64,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for Knn {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 2 && arguments_types.len() != 3 {
+            return Err(runtime_error!("Invalid number of arguments for Knn"));
+        }
+        let query_t = arguments_types[0].clone();
+        let dataset_t = arguments_types[1].clone();
+        if !query_t.is_array() || query_t.get_shape().len() != 1 {
+            return Err(runtime_error!("Query of Knn must be a 1-dimensional array"));
+        }
+        let st = query_t.get_scalar_type();
+        if st == BIT {
+            return Err(runtime_error!("Vectors in Knn can't be binary"));
+        }
+        let d = query_t.get_shape()[0];
+        if !dataset_t.is_array() || dataset_t.get_scalar_type() != st {
+            return Err(runtime_error!(
+                "Dataset of Knn must be an array of the same scalar type as the query"
+            ));
+        }
+        let dataset_shape = dataset_t.get_shape();
+        if dataset_shape.len() != 2 || dataset_shape[1] != d {
+            return Err(runtime_error!(
+                "Dataset of Knn must have shape [n, {}], but {:?} is given",
+                d,
+                dataset_shape
+            ));
+        }
+        let n = dataset_shape[0];
+        if self.k == 0 || self.k > n {
+            return Err(runtime_error!(
+                "Number of neighbors must be between 1 and {}",
+                n
+            ));
+        }
+        let labels_st = if arguments_types.len() == 3 {
+            let labels_t = arguments_types[2].clone();
+            if labels_t != array_type(vec![n], labels_t.get_scalar_type()) {
+                return Err(runtime_error!(
+                    "Labels of Knn must be an array of shape [{}]",
+                    n
+                ));
+            }
+            Some(labels_t.get_scalar_type())
+        } else {
+            None
+        };
+
+        let g = context.create_graph()?;
+        let query = g.input(query_t.clone())?;
+        let dataset = g.input(dataset_t)?;
+        let labels = match labels_st {
+            Some(_) => Some(g.input(arguments_types[2].clone())?),
+            None => None,
+        };
+
+        // Squared distances ||x||^2 - 2 <x, q> + ||q||^2, shape [n]
+        let dataset_norms = dataset.multiply(dataset.clone())?.sum(vec![1])?;
+        let products = dataset
+            .gemm(
+                query.reshape(array_type(vec![1, d], st.clone()))?,
+                false,
+                true,
+            )?
+            .reshape(array_type(vec![n], st.clone()))?;
+        let query_norm = query.dot(query.clone())?;
+        let distances = dataset_norms
+            .subtract(products.add(products.clone())?)?
+            .add(query_norm)?;
+
+        // Pad the dataset to a power-of-two size with the largest possible distances
+        let log_padded_n = n.next_power_of_two().trailing_zeros();
+        let padded_n = 1 << log_padded_n;
+        let pad = |x: Node, padding_value: Value, padding_st: ScalarType| -> Result<Node> {
+            if padded_n == n {
+                return Ok(x);
+            }
+            let padding = g.constant(array_type(vec![padded_n - n], padding_st), padding_value)?;
+            concatenate_first_axis(vec![x, padding])
+        };
+        let max_distance =
+            Value::from_flattened_array(&vec![u64::MAX; (padded_n - n) as usize], st.clone())?;
+        let distances = pad(distances, max_distance, st.clone())?;
+
+        // Sorting keys consist of (from least to most significant bits) labels, indices and distances
+        let index_bits_len = (log_padded_n as u64).max(1);
+        let mut index_bits = vec![];
+        for bit in 0..index_bits_len {
+            for i in 0..padded_n {
+                index_bits.push((i >> bit) & 1);
+            }
+        }
+        let index_bits = g.constant(
+            array_type(vec![index_bits_len, padded_n], BIT),
+            Value::from_flattened_array(&index_bits, BIT)?,
+        )?;
+        let mut key_parts = vec![];
+        let mut labels_bits_len = 0;
+        if let (Some(labels), Some(labels_st)) = (labels, labels_st.clone()) {
+            let padding = Value::zero_of_type(array_type(vec![padded_n - n], labels_st.clone()));
+            let labels_bits = to_bits_first(pad(labels, padding, labels_st)?)?;
+            labels_bits_len = labels_bits.get_type()?.get_shape()[0];
+            key_parts.push(labels_bits);
+        }
+        key_parts.push(index_bits);
+        key_parts.push(to_bits_first(distances)?);
+        let keys = put_in_bits(concatenate_first_axis(key_parts)?)?;
+        let key_len = keys.get_type()?.get_shape()[1];
+
+        let sorted = g.custom_op(
+            CustomOperation::new(Sort {
+                k: log_padded_n,
+                b: key_len,
+                signed_comparison: false,
+            }),
+            vec![keys],
+        )?;
+        let nearest = pull_out_bits(sorted.get_slice(vec![SliceElement::SubArray(
+            None,
+            Some(self.k as i64),
+            None,
+        )])?)?;
+        let bits_range = |start: u64, end: u64| -> Result<Node> {
+            nearest.get_slice(vec![SliceElement::SubArray(
+                Some(start as i64),
+                Some(end as i64),
+                None,
+            )])
+        };
+
+        let index_padding = zeros(&g, array_type(vec![64 - index_bits_len, self.k], BIT))?;
+        let indices = put_in_bits(concatenate_first_axis(vec![
+            bits_range(labels_bits_len, labels_bits_len + index_bits_len)?,
+            index_padding,
+        ])?)?
+        .b2a(UINT64)?;
+        let output = match labels_st {
+            Some(labels_st) => {
+                let labels_bits = put_in_bits(bits_range(0, labels_bits_len)?)?;
+                let nearest_labels = if labels_st == BIT {
+                    labels_bits.reshape(array_type(vec![self.k], BIT))?
+                } else {
+                    labels_bits.b2a(labels_st)?
+                };
+                g.create_tuple(vec![indices, nearest_labels])?
+            }
+            None => indices,
+        };
+        output.set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!("Knn(k={})", self.k)
+    }
+}
+
+register_custom_operation!(Knn, [Array, Array], [Array, Array, Array]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::custom_ops::run_instantiation_pass;
+    use crate::data_types::{tuple_type, INT32, UINT32};
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::create_context;
+    use crate::inline::inline_common::DepthOptimizationLevel;
+    use crate::inline::inline_ops::{InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{compile_context, IOStatus};
+
+    fn create_knn_context(input_types: Vec<Type>, k: u64) -> Result<Context> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let mut inputs = vec![];
+        for t in input_types {
+            inputs.push(g.input(t)?);
+        }
+        g.custom_op(CustomOperation::new(Knn { k }), inputs)?
+            .set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        Ok(c)
+    }
+
+    #[test]
+    fn test_knn() {
+        || -> Result<()> {
+            let query_t = array_type(vec![2], INT32);
+            let dataset_t = array_type(vec![5, 2], INT32);
+            let labels_t = array_type(vec![5], INT32);
+            let c = create_knn_context(vec![query_t, dataset_t, labels_t], 3)?;
+            let instantiated_c = run_instantiation_pass(c)?.get_context();
+            let result = random_evaluate(
+                instantiated_c.get_main_graph()?,
+                vec![
+                    Value::from_flattened_array(&[1, -1], INT32)?,
+                    Value::from_flattened_array(&[10, 10, 2, -1, -5, 3, 1, 0, 0, -1], INT32)?,
+                    Value::from_flattened_array(&[-7, 8, 9, -10, 11], INT32)?,
+                ],
+            )?;
+            let output_t = tuple_type(vec![
+                array_type(vec![3], UINT64),
+                array_type(vec![3], INT32),
+            ]);
+            let output = result.to_vector()?;
+            // Distances are 202, 1, 52, 1, 1
+            assert_eq!(
+                output[0].to_flattened_array_u64(array_type(vec![3], UINT64))?,
+                vec![1, 3, 4]
+            );
+            assert_eq!(
+                output[1].to_flattened_array_i32(array_type(vec![3], INT32))?,
+                vec![8, -10, 11]
+            );
+            assert!(result.check_type(output_t)?);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_knn_without_labels() {
+        || -> Result<()> {
+            let query_t = array_type(vec![3], UINT32);
+            let dataset_t = array_type(vec![4, 3], UINT32);
+            let c = create_knn_context(vec![query_t, dataset_t], 2)?;
+            let instantiated_c = run_instantiation_pass(c)?.get_context();
+            let result = random_evaluate(
+                instantiated_c.get_main_graph()?,
+                vec![
+                    Value::from_flattened_array(&[5, 5, 5], UINT32)?,
+                    Value::from_flattened_array(&[0, 0, 0, 5, 6, 7, 9, 9, 9, 4, 5, 5], UINT32)?,
+                ],
+            )?;
+            assert_eq!(
+                result.to_flattened_array_u64(array_type(vec![2], UINT64))?,
+                vec![3, 1]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_knn_compiles_end2end() {
+        || -> Result<()> {
+            let c = create_knn_context(
+                vec![
+                    array_type(vec![2], INT32),
+                    array_type(vec![3, 2], INT32),
+                    array_type(vec![3], BIT),
+                ],
+                2,
+            )?;
+            compile_context(
+                c,
+                vec![IOStatus::Party(0), IOStatus::Shared, IOStatus::Party(1)],
+                vec![IOStatus::Party(0)],
+                InlineConfig {
+                    default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                    ..Default::default()
+                },
+                || SimpleEvaluator::new(None),
+            )?;
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_knn() {
+        let query_t = array_type(vec![2], INT32);
+        let dataset_t = array_type(vec![5, 2], INT32);
+        assert!(create_knn_context(vec![query_t.clone(), dataset_t.clone()], 5).is_ok());
+        assert!(create_knn_context(vec![query_t.clone(), dataset_t.clone()], 0).is_err());
+        assert!(create_knn_context(vec![query_t.clone(), dataset_t.clone()], 6).is_err());
+        assert!(create_knn_context(vec![query_t.clone()], 1).is_err());
+        assert!(
+            create_knn_context(vec![query_t.clone(), array_type(vec![5, 3], INT32)], 1).is_err()
+        );
+        assert!(
+            create_knn_context(vec![query_t.clone(), array_type(vec![5, 2], UINT32)], 1).is_err()
+        );
+        assert!(
+            create_knn_context(vec![query_t, dataset_t, array_type(vec![4], INT32)], 1).is_err()
+        );
+        assert!(create_knn_context(
+            vec![array_type(vec![2], BIT), array_type(vec![5, 2], BIT)],
+            1
+        )
+        .is_err());
+    }
+}
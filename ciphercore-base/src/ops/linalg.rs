@@ -0,0 +1,510 @@
+//! Matrix inversion and solving linear systems via [the Newton-Schulz iteration](https://en.wikipedia.org/wiki/Invertible_matrix#Newton's_method).
+use crate::custom_ops::{CustomOperation, CustomOperationBody};
+use crate::data_types::{array_type, Type, INT64};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, Node};
+
+use serde::{Deserialize, Serialize};
+
+use super::newton_inversion::NewtonInversion;
+
+// Checks that the given type is an INT64 array of shape [..., n, n] and returns n
+fn get_square_matrix_size(t: &Type, op_name: &str) -> Result<u64> {
+    if !t.is_array() || t.get_scalar_type() != INT64 {
+        return Err(runtime_error!(
+            "Matrix in {} must be an array of INT64's, but {} is given",
+            op_name,
+            t
+        ));
+    }
+    let shape = t.get_shape();
+    if shape.len() < 2 || shape[shape.len() - 1] != shape[shape.len() - 2] {
+        return Err(runtime_error!(
+            "Matrix in {} must be square, but its shape is {:?}",
+            op_name,
+            shape
+        ));
+    }
+    Ok(shape[shape.len() - 1])
+}
+
+// Returns an identity matrix of size n x n whose diagonal elements are equal to `diagonal_value`
+fn identity_matrix(g: &Graph, n: u64, diagonal_value: u64) -> Result<Node> {
+    let mut entries = vec![0u64; (n * n) as usize];
+    for i in 0..n {
+        entries[(i * n + i) as usize] = diagonal_value;
+    }
+    g.constant(
+        array_type(vec![n, n], INT64),
+        Value::from_flattened_array(&entries, INT64)?,
+    )
+}
+
+/// A structure that defines the custom operation MatrixInverse that computes an approximate inverse of a matrix in fixed-point arithmetic.
+///
+/// The inverse is computed by the Newton-Schulz iteration
+///   X_{i + 1} = X_i * (2 * I - A * X_i),
+/// which consists only of matrix multiplications and truncations and, thus, is cheap in MPC.
+///
+/// Input must be an INT64 array of shape `[..., n, n]` with fixed-point entries with denominator 2<sup>fixed_precision_points</sup>.
+/// If the input has more than two dimensions, every matrix along the last two axes is inverted independently.
+///
+/// Without an initial approximation, input matrices must be symmetric positive definite (e.g. X<sup>T</sup>X + λI as in ridge regression)
+/// and the initial approximation I / trace(A) is used.
+/// In this case, the trace of every matrix must be less than 2<sup>fixed_precision_points - 1</sup>;
+/// the number of iterations needed for convergence is roughly log<sub>2</sub>(trace(A) / λ<sub>min</sub>(A)) + log<sub>2</sub>(fixed_precision_points),
+/// where λ<sub>min</sub>(A) is the smallest eigenvalue of A.
+///
+/// Optionally, an initial approximation X<sub>0</sub> can be provided, then the input can be an arbitrary invertible matrix.
+/// The iteration converges if the spectral radius of I - A * X<sub>0</sub> is less than 1.
+///
+/// Entries of the input and the output must be small enough to avoid integer overflows in products of fixed-point matrices.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a signed 64-bit array of shape `[..., n, n]` to invert
+/// - (optional) Node containing an array of the same type that serves as an initial approximation
+///
+/// # Custom operation returns
+///
+/// New MatrixInverse node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT64};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::linalg::MatrixInverse;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let t = array_type(vec![3, 3], INT64);
+/// let a = g.input(t.clone()).unwrap();
+/// let inv = g.custom_op(CustomOperation::new(MatrixInverse {iterations: 10, fixed_precision_points: 15}), vec![a]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct MatrixInverse {
+    /// Number of iterations of the Newton-Schulz method
+    pub iterations: u64,
+    /// Assume that we're operating in fixed precision arithmetic with denominator 2 ** fixed_precision_points.
+    pub fixed_precision_points: u64,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for MatrixInverse {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 1 && arguments_types.len() != 2 {
+            return Err(runtime_error!(
+                "Invalid number of arguments for MatrixInverse"
+            ));
+        }
+        let t = arguments_types[0].clone();
+        let n = get_square_matrix_size(&t, "MatrixInverse")?;
+        let has_initial_approximation = arguments_types.len() == 2;
+        if has_initial_approximation && arguments_types[1] != t {
+            return Err(runtime_error!(
+                "Matrix and initial approximation must have the same type"
+            ));
+        }
+        if self.fixed_precision_points == 0 || self.fixed_precision_points > 15 {
+            return Err(runtime_error!(
+                "fixed_precision_points must be between 1 and 15"
+            ));
+        }
+        let precision = self.fixed_precision_points;
+
+        let g = context.create_graph()?;
+        let a = g.input(t.clone())?;
+        let mut approximation = if has_initial_approximation {
+            g.input(t.clone())?
+        } else {
+            // X_0 = I / trace(A). For a symmetric positive definite matrix,
+            // the eigenvalues of A * X_0 lie in (0, 1], so the iteration converges.
+            let shape = t.get_shape();
+            let rank = shape.len() as u64;
+            let ones_on_diagonal = identity_matrix(&g, n, 1)?;
+            let trace = a
+                .multiply(ones_on_diagonal.clone())?
+                .sum(vec![rank - 2, rank - 1])?;
+            // 2 ** (2 * precision) / trace is the fixed-point representation of 1 / trace
+            let inverse_trace = g.custom_op(
+                CustomOperation::new(NewtonInversion {
+                    iterations: (2.0 * precision as f64).log2().ceil() as u64 + 1,
+                    denominator_cap_2k: 2 * precision,
+                }),
+                vec![trace],
+            )?;
+            let inverse_trace = if rank == 2 {
+                inverse_trace
+            } else {
+                let mut broadcast_shape = shape[..shape.len() - 2].to_vec();
+                broadcast_shape.extend([1, 1]);
+                inverse_trace.reshape(array_type(broadcast_shape, INT64))?
+            };
+            inverse_trace.multiply(ones_on_diagonal)?
+        };
+        let two_identity = identity_matrix(&g, n, 2 << precision)?;
+        for _ in 0..self.iterations {
+            let x = approximation;
+            let ax = a.matmul(x.clone())?.truncate(1 << precision)?;
+            approximation = x
+                .matmul(two_identity.subtract(ax)?)?
+                .truncate(1 << precision)?;
+        }
+        approximation.set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "MatrixInverse(iterations={}, fixed_precision_points={})",
+            self.iterations, self.fixed_precision_points
+        )
+    }
+}
+
+register_custom_operation!(MatrixInverse, [Array], [Array, Array]);
+
+/// A structure that defines the custom operation LinearSolve that approximately solves a linear system A * x = b in fixed-point arithmetic.
+///
+/// The solution is computed as A<sup>-1</sup> * b, where the inverse is approximated by [MatrixInverse] without an initial approximation.
+/// Thus, the same restrictions apply: the matrix A must be symmetric positive definite and its trace must be less than 2<sup>fixed_precision_points - 1</sup>.
+///
+/// A typical use case is the closed-form solution of ridge regression w = (X<sup>T</sup>X + λI)<sup>-1</sup>X<sup>T</sup>y.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a signed 64-bit array of shape `[..., n, n]` with fixed-point entries representing A
+/// - Node containing a signed 64-bit array of shape `[..., n]` or `[..., n, m]` with fixed-point entries representing b
+///
+/// # Custom operation returns
+///
+/// New LinearSolve node containing the fixed-point solution of shape `[..., n]` or `[..., n, m]`
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT64};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::linalg::LinearSolve;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let a = g.input(array_type(vec![3, 3], INT64)).unwrap();
+/// let b = g.input(array_type(vec![3], INT64)).unwrap();
+/// let x = g.custom_op(CustomOperation::new(LinearSolve {iterations: 10, fixed_precision_points: 15}), vec![a, b]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct LinearSolve {
+    /// Number of iterations of the Newton-Schulz method
+    pub iterations: u64,
+    /// Assume that we're operating in fixed precision arithmetic with denominator 2 ** fixed_precision_points.
+    pub fixed_precision_points: u64,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for LinearSolve {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 2 {
+            return Err(runtime_error!(
+                "Invalid number of arguments for LinearSolve"
+            ));
+        }
+        let a_t = arguments_types[0].clone();
+        let b_t = arguments_types[1].clone();
+        let n = get_square_matrix_size(&a_t, "LinearSolve")?;
+        if !b_t.is_array() || b_t.get_scalar_type() != INT64 {
+            return Err(runtime_error!(
+                "Right-hand side in LinearSolve must be an array of INT64's"
+            ));
+        }
+        let b_shape = b_t.get_shape();
+        let b_rows = if b_shape.len() + 1 == a_t.get_shape().len() {
+            b_shape[b_shape.len() - 1]
+        } else if b_shape.len() == a_t.get_shape().len() {
+            b_shape[b_shape.len() - 2]
+        } else {
+            return Err(runtime_error!(
+                "Right-hand side in LinearSolve must have shape [..., n] or [..., n, m]"
+            ));
+        };
+        if b_rows != n {
+            return Err(runtime_error!(
+                "Matrix and right-hand side in LinearSolve have incompatible dimensions"
+            ));
+        }
+
+        let g = context.create_graph()?;
+        let a = g.input(a_t)?;
+        let b = g.input(b_t)?;
+        let inverse = g.custom_op(
+            CustomOperation::new(MatrixInverse {
+                iterations: self.iterations,
+                fixed_precision_points: self.fixed_precision_points,
+            }),
+            vec![a],
+        )?;
+        // Matmul of a batch of matrices with a batch of vectors requires an explicit trailing axis
+        let result = if b_shape.len() == 1 || b_shape.len() == 2 {
+            inverse.matmul(b)?
+        } else {
+            let mut column_shape = b_shape.clone();
+            column_shape.push(1);
+            inverse
+                .matmul(b.reshape(array_type(column_shape, INT64))?)?
+                .reshape(array_type(b_shape, INT64))?
+        };
+        result
+            .truncate(1 << self.fixed_precision_points)?
+            .set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "LinearSolve(iterations={}, fixed_precision_points={})",
+            self.iterations, self.fixed_precision_points
+        )
+    }
+}
+
+register_custom_operation!(LinearSolve, [Array, Array]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::custom_ops::run_instantiation_pass;
+    use crate::evaluators::random_evaluate;
+    use crate::graphs::create_context;
+    use crate::inline::inline_common::DepthOptimizationLevel;
+    use crate::inline::inline_ops::{inline_operations, InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{prepare_for_mpc_evaluation, IOStatus};
+
+    const PRECISION: u64 = 15;
+
+    fn to_fixed(x: &[f64]) -> Vec<i64> {
+        x.iter()
+            .map(|v| (v * (1 << PRECISION) as f64).round() as i64)
+            .collect()
+    }
+
+    fn from_fixed(x: &[i64]) -> Vec<f64> {
+        x.iter()
+            .map(|v| *v as f64 / (1 << PRECISION) as f64)
+            .collect()
+    }
+
+    fn evaluate_op(
+        op: CustomOperation,
+        input_types: Vec<Type>,
+        inputs: Vec<Vec<f64>>,
+    ) -> Result<Vec<f64>> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let mut input_nodes = vec![];
+        for t in input_types.iter() {
+            input_nodes.push(g.input(t.clone())?);
+        }
+        let o = g.custom_op(op, input_nodes)?;
+        let output_type = o.get_type()?;
+        o.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        let mapped_c = run_instantiation_pass(c)?;
+        let mut input_values = vec![];
+        for input in inputs {
+            input_values.push(Value::from_flattened_array(&to_fixed(&input), INT64)?);
+        }
+        let result = random_evaluate(mapped_c.get_context().get_main_graph()?, input_values)?;
+        Ok(from_fixed(&result.to_flattened_array_i64(output_type)?))
+    }
+
+    fn assert_close(actual: &[f64], expected: &[f64], tolerance: f64) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() <= tolerance, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_matrix_inverse() {
+        || -> Result<()> {
+            let op = CustomOperation::new(MatrixInverse {
+                iterations: 12,
+                fixed_precision_points: PRECISION,
+            });
+            // Symmetric positive definite matrix
+            let a = vec![4.0, 1.0, 0.5, 1.0, 3.0, -1.0, 0.5, -1.0, 2.0];
+            // Adjugate matrix divided by the determinant
+            let expected: Vec<f64> = [5.0, -2.5, -2.5, -2.5, 7.75, 4.5, -2.5, 4.5, 11.0]
+                .iter()
+                .map(|x| x / 16.25)
+                .collect();
+            let result = evaluate_op(op, vec![array_type(vec![3, 3], INT64)], vec![a])?;
+            assert_close(&result, &expected, 1e-3);
+
+            // Batch of matrices
+            let op = CustomOperation::new(MatrixInverse {
+                iterations: 12,
+                fixed_precision_points: PRECISION,
+            });
+            let a = vec![2.0, 0.0, 0.0, 8.0, 5.0, 2.0, 2.0, 1.0];
+            let expected = vec![0.5, 0.0, 0.0, 0.125, 1.0, -2.0, -2.0, 5.0];
+            let result = evaluate_op(op, vec![array_type(vec![2, 2, 2], INT64)], vec![a])?;
+            assert_close(&result, &expected, 1e-3);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_matrix_inverse_with_initial_approximation() {
+        || -> Result<()> {
+            // Non-symmetric matrix with X_0 = A^T / (||A||_1 * ||A||_inf)
+            let a = vec![2.0, 1.0, -1.0, 3.0];
+            let initial_approximation = vec![2.0 / 20.0, -1.0 / 20.0, 1.0 / 20.0, 3.0 / 20.0];
+            let expected = vec![3.0 / 7.0, -1.0 / 7.0, 1.0 / 7.0, 2.0 / 7.0];
+            let t = array_type(vec![2, 2], INT64);
+            let op = CustomOperation::new(MatrixInverse {
+                iterations: 10,
+                fixed_precision_points: PRECISION,
+            });
+            let result = evaluate_op(op, vec![t.clone(), t], vec![a, initial_approximation])?;
+            assert_close(&result, &expected, 1e-3);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_ridge_regression_solve() {
+        || -> Result<()> {
+            // Closed-form ridge regression w = (X^T X + lambda * I)^(-1) X^T y
+            // with X = [[1, 0], [1, 1], [1, 2], [1, 3]], y = [1, 3, 5, 7] and lambda = 0.5.
+            let xtx_plus_lambda = vec![4.5, 6.0, 6.0, 14.5];
+            let xty = vec![16.0, 34.0];
+            // w = [[14.5, -6], [-6, 4.5]] / 29.25 * [16, 34]
+            let expected = vec![
+                (14.5 * 16.0 - 6.0 * 34.0) / 29.25,
+                (-6.0 * 16.0 + 4.5 * 34.0) / 29.25,
+            ];
+            let op = CustomOperation::new(LinearSolve {
+                iterations: 12,
+                fixed_precision_points: PRECISION,
+            });
+            let result = evaluate_op(
+                op,
+                vec![array_type(vec![2, 2], INT64), array_type(vec![2], INT64)],
+                vec![xtx_plus_lambda.clone(), xty.clone()],
+            )?;
+            assert_close(&result, &expected, 1e-2);
+
+            // Several right-hand sides at once
+            let op = CustomOperation::new(LinearSolve {
+                iterations: 12,
+                fixed_precision_points: PRECISION,
+            });
+            let result = evaluate_op(
+                op,
+                vec![array_type(vec![2, 2], INT64), array_type(vec![2, 2], INT64)],
+                vec![xtx_plus_lambda, vec![16.0, 4.5, 34.0, 6.0]],
+            )?;
+            assert_close(&result, &[expected[0], 1.0, expected[1], 0.0], 1e-2);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_linalg() {
+        let check = |op: CustomOperation, types: Vec<Type>| -> bool {
+            || -> Result<()> {
+                let c = create_context()?;
+                let g = c.create_graph()?;
+                let mut nodes = vec![];
+                for t in types {
+                    nodes.push(g.input(t)?);
+                }
+                g.custom_op(op, nodes)?;
+                Ok(())
+            }()
+            .is_err()
+        };
+        let inverse = || {
+            CustomOperation::new(MatrixInverse {
+                iterations: 5,
+                fixed_precision_points: PRECISION,
+            })
+        };
+        let solve = || {
+            CustomOperation::new(LinearSolve {
+                iterations: 5,
+                fixed_precision_points: PRECISION,
+            })
+        };
+        assert!(check(inverse(), vec![array_type(vec![2, 3], INT64)]));
+        assert!(check(inverse(), vec![array_type(vec![3], INT64)]));
+        assert!(check(
+            inverse(),
+            vec![array_type(vec![2, 2], crate::data_types::INT32)]
+        ));
+        assert!(check(
+            inverse(),
+            vec![array_type(vec![2, 2], INT64), array_type(vec![3, 3], INT64)]
+        ));
+        assert!(check(
+            CustomOperation::new(MatrixInverse {
+                iterations: 5,
+                fixed_precision_points: 20,
+            }),
+            vec![array_type(vec![2, 2], INT64)]
+        ));
+        assert!(check(solve(), vec![array_type(vec![2, 2], INT64)]));
+        assert!(check(
+            solve(),
+            vec![array_type(vec![2, 2], INT64), array_type(vec![3], INT64)]
+        ));
+        assert!(check(
+            solve(),
+            vec![
+                array_type(vec![2, 2], INT64),
+                array_type(vec![2, 2, 2, 2], INT64)
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_linear_solve_compiles_end2end() -> Result<()> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let a = g.input(array_type(vec![3, 3], INT64))?;
+        let b = g.input(array_type(vec![3], INT64))?;
+        let o = g.custom_op(
+            CustomOperation::new(LinearSolve {
+                iterations: 5,
+                fixed_precision_points: PRECISION,
+            }),
+            vec![a, b],
+        )?;
+        o.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        let inline_config = InlineConfig {
+            default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+            ..Default::default()
+        };
+        let instantiated_context = run_instantiation_pass(c)?.get_context();
+        let inlined_context = inline_operations(instantiated_context, inline_config.clone())?;
+        let _unused = prepare_for_mpc_evaluation(
+            inlined_context,
+            vec![vec![IOStatus::Shared, IOStatus::Party(0)]],
+            vec![vec![]],
+            inline_config,
+        )?;
+        Ok(())
+    }
+}
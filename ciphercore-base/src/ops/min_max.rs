@@ -1,6 +1,6 @@
-//! Minimum and maximum operations. They operate on unsigned integers represented as bitstrings.
+//! Minimum and maximum operations. They operate on integers represented as bitstrings or as arrays of a non-binary scalar type.
 use crate::custom_ops::{CustomOperation, CustomOperationBody};
-use crate::data_types::{array_type, Type};
+use crate::data_types::{array_type, Type, BIT};
 use crate::errors::Result;
 use crate::graphs::{Context, Graph, Node};
 
@@ -15,14 +15,17 @@ use serde::{Deserialize, Serialize};
 /// If input shapes are different, the broadcasting rules are applied (see [the NumPy broadcasting rules](https://numpy.org/doc/stable/user/basics.broadcasting.html)).
 /// For example, if input arrays are of shapes `[2,3]`, and `[1,3]`, the resulting array has shape `[2,3]`.
 ///
-/// To compare signed numbers, `signed_comparison` should be set `true`.
+/// To compare signed numbers, `signed_comparison` should be set `true`; for integer inputs, it must match the signedness of their scalar type.
 ///
 /// To use this and other custom operations in computation graphs, see [Graph::custom_op].
 ///
+/// Instead of bitstrings, the inputs can be arrays or scalars of the same integer type.
+/// In this case, the result has the same integer type.
+///
 /// # Custom operation arguments
 ///
-/// - Node containing a binary array or scalar
-/// - Node containing a binary array or scalar
+/// - Node containing a binary or integer array or scalar
+/// - Node containing a binary or integer array or scalar
 ///
 /// # Custom operation returns
 ///
@@ -66,6 +69,22 @@ fn normalize_cmp(cmp: Node) -> Result<Node> {
     Ok(normalized_cmp)
 }
 
+/// Returns `on_true` in the positions where `cmp` is set and `on_false` in the other positions.
+///
+/// Bitstrings are selected by [Mux], while integers are selected by [Node::mixed_multiply].
+fn select(cmp: Node, on_true: Node, on_false: Node) -> Result<Node> {
+    if on_true.get_type()?.get_scalar_type() == BIT {
+        let g = cmp.get_graph();
+        let normalized_cmp = normalize_cmp(cmp)?;
+        g.custom_op(
+            CustomOperation::new(Mux {}),
+            vec![normalized_cmp, on_true, on_false],
+        )
+    } else {
+        on_false.add(on_true.subtract(on_false.clone())?.mixed_multiply(cmp)?)
+    }
+}
+
 #[typetag::serde]
 impl CustomOperationBody for Min {
     fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
@@ -81,8 +100,7 @@ impl CustomOperationBody for Min {
             }),
             vec![i1.clone(), i2.clone()],
         )?;
-        let normalized_cmp = normalize_cmp(cmp)?;
-        let o = g.custom_op(CustomOperation::new(Mux {}), vec![normalized_cmp, i2, i1])?;
+        let o = select(cmp, i2, i1)?;
         g.set_output_node(o)?;
         g.finalize()?;
         Ok(g)
@@ -93,20 +111,25 @@ impl CustomOperationBody for Min {
     }
 }
 
+register_custom_operation!(Min, [Array, Array]);
+
 /// A structure that defines the custom operation Max that computes the maximum of length-n bitstring arrays elementwise.
 ///
 /// The last dimension of both inputs must be the same; it defines the length of input bitstrings.
 /// If input shapes are different, the broadcasting rules are applied (see [the NumPy broadcasting rules](https://numpy.org/doc/stable/user/basics.broadcasting.html)).
 /// For example, if input arrays are of shapes `[2,3]`, and `[1,3]`, the resulting array has shape `[2,3]`.
 ///
-/// To compare signed numbers, `signed_comparison` should be set `true`.
+/// To compare signed numbers, `signed_comparison` should be set `true`; for integer inputs, it must match the signedness of their scalar type.
 ///
 /// To use this and other custom operations in computation graphs, see [Graph::custom_op].
 ///
+/// Instead of bitstrings, the inputs can be arrays or scalars of the same integer type.
+/// In this case, the result has the same integer type.
+///
 /// # Custom operation arguments
 ///
-/// - Node containing a binary array or scalar
-/// - Node containing a binary array or scalar
+/// - Node containing a binary or integer array or scalar
+/// - Node containing a binary or integer array or scalar
 ///
 /// # Custom operation returns
 ///
@@ -147,8 +170,7 @@ impl CustomOperationBody for Max {
             }),
             vec![i1.clone(), i2.clone()],
         )?;
-        let normalized_cmp = normalize_cmp(cmp)?;
-        let o = g.custom_op(CustomOperation::new(Mux {}), vec![normalized_cmp, i1, i2])?;
+        let o = select(cmp, i1, i2)?;
         g.set_output_node(o)?;
         g.finalize()?;
         Ok(g)
@@ -159,14 +181,19 @@ impl CustomOperationBody for Max {
     }
 }
 
+register_custom_operation!(Max, [Array, Array]);
+
 #[cfg(test)]
 mod tests {
 
     use crate::custom_ops::run_instantiation_pass;
-    use crate::data_types::{array_type, scalar_type, BIT, INT64, UINT64};
+    use crate::data_types::{array_type, scalar_type, BIT, INT32, INT64, UINT64};
     use crate::data_values::Value;
     use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
     use crate::graphs::create_context;
+    use crate::inline::inline_ops::InlineConfig;
+    use crate::mpc::mpc_compiler::{compile_context, IOStatus};
 
     use super::*;
 
@@ -303,4 +330,58 @@ mod tests {
         }()
         .unwrap();
     }
+
+    #[test]
+    fn test_integers() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let i1 = g.input(array_type(vec![4], INT32))?;
+            let i2 = g.input(scalar_type(INT32))?;
+            let o = g.create_tuple(vec![
+                g.custom_op(
+                    CustomOperation::new(Min {
+                        signed_comparison: true,
+                    }),
+                    vec![i1.clone(), i2.clone()],
+                )?,
+                g.custom_op(
+                    CustomOperation::new(Max {
+                        signed_comparison: true,
+                    }),
+                    vec![i1, i2],
+                )?,
+            ])?;
+            g.set_output_node(o)?;
+            g.finalize()?;
+            c.set_main_graph(g)?;
+            c.finalize()?;
+            let inputs = vec![
+                Value::from_flattened_array(&[-100, -3, 0, i32::MAX], INT32)?,
+                Value::from_scalar(-3, INT32)?,
+            ];
+            let instantiated_c = run_instantiation_pass(c.clone())?.get_context();
+            let compiled_c = compile_context(
+                c,
+                vec![IOStatus::Party(0), IOStatus::Party(1)],
+                vec![IOStatus::Party(2)],
+                InlineConfig::default(),
+                || SimpleEvaluator::new(None),
+            )?;
+            let t = array_type(vec![4], INT32);
+            for context in [instantiated_c, compiled_c] {
+                let v = random_evaluate(context.get_main_graph()?, inputs.clone())?.to_vector()?;
+                assert_eq!(
+                    v[0].to_flattened_array_i32(t.clone())?,
+                    vec![-100, -3, -3, -3]
+                );
+                assert_eq!(
+                    v[1].to_flattened_array_i32(t.clone())?,
+                    vec![-3, -3, 0, i32::MAX]
+                );
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
 }
@@ -73,6 +73,8 @@ impl CustomOperationBody for Mux {
     }
 }
 
+register_custom_operation!(Mux, [Binary, Any, Any]);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -148,7 +148,8 @@ impl CustomOperationBody for NewtonInversion {
         };
         // Now, we do Newton approximation for computing 1 / x, where x = divisor / (2 ** cap).
         // The formula for the Newton method is x_{i + 1} = x_i * (2 - d * x_i).
-        let two_power_cap_plus_one = constant_scalar(&g, 1 << (self.denominator_cap_2k + 1), sc)?;
+        let two_power_cap_plus_one =
+            constant_scalar(&g, 1u64 << (self.denominator_cap_2k + 1), sc)?;
         for _ in 0..self.iterations {
             let x = approximation;
             let mult = two_power_cap_plus_one.subtract(x.multiply(divisor.clone())?)?;
@@ -167,6 +168,8 @@ impl CustomOperationBody for NewtonInversion {
     }
 }
 
+register_custom_operation!(NewtonInversion, [Array], [Array, Array]);
+
 #[cfg(test)]
 mod tests {
     use super::*;
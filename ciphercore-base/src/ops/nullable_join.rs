@@ -0,0 +1,516 @@
+//! Set intersection of databases with nullable key columns.
+use std::collections::HashMap;
+
+use crate::custom_ops::CustomOperationBody;
+use crate::data_types::{array_type, Type, BIT};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, Node};
+use crate::type_inference::NULL_HEADER;
+
+use serde::{Deserialize, Serialize};
+
+use super::utils::multiply_rows_by_bits;
+
+/// Returns the header of the binary column containing null bits of a nullable column with a given header.
+///
+/// For example, null bits of the column "ID" are contained in the column "ID_null".
+/// As for the [null column](NULL_HEADER) of a database, the null bit 0 means that the corresponding entry is missing.
+pub fn get_column_null_header(header: &str) -> String {
+    format!("{}_{}", header, NULL_HEADER)
+}
+
+/// Defines whether missing values of key columns are equal to each other in [NullableSetIntersection].
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub enum NullEquality {
+    /// As the SQL `=` operator, a missing key value isn't equal to any value including another missing value.
+    /// Thus, rows with missing values in some key columns never belong to the intersection.
+    #[default]
+    Sql,
+    /// As the SQL `IS NOT DISTINCT FROM` operator, a missing key value is equal only to another missing value.
+    NullSafe,
+}
+
+/// A structure that defines the custom operation NullableSetIntersection that computes the intersection of two databases whose key columns can contain missing values.
+///
+/// Databases are represented as named tuples of arrays as in [Graph::set_intersection].
+/// A column with header `h` is nullable if the database contains a binary column of shape `[n]` with the header [get_column_null_header(h)](get_column_null_header).
+/// Such columns are treated as ordinary columns unless they're attached to key columns.
+///
+/// Two rows match if they are non-null and their key columns are equal with respect to `null_equality`.
+/// The content of a missing key value is ignored, i.e. it can be arbitrary.
+/// The result contains the same columns as [Graph::set_intersection] of the input databases, except for the null bits of key columns of the second database, which are equal to those of the first database for all matched rows.
+///
+/// The intersection is computed by [Graph::set_intersection] after the following rewriting of the databases:
+/// - for [NullEquality::Sql], the null column of each database is multiplied by the null bits of its key columns;
+/// - for [NullEquality::NullSafe], missing key values are replaced by zeros and the null bits of key columns become additional key columns (non-nullable key columns get null bits equal to 1).
+///
+/// As for [Graph::set_intersection], key values (including missing values in the [NullEquality::NullSafe] mode) should be unique within each database.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a named tuple with the first database
+/// - Node containing a named tuple with the second database
+///
+/// # Custom operation returns
+///
+/// New NullableSetIntersection node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, named_tuple_type, BIT, INT32};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::nullable_join::{get_column_null_header, NullEquality, NullableSetIntersection};
+/// # use ciphercore_base::type_inference::NULL_HEADER;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let t1 = named_tuple_type(vec![
+///     (NULL_HEADER.to_owned(), array_type(vec![10], BIT)),
+///     ("ID".to_owned(), array_type(vec![10], INT32)),
+///     (get_column_null_header("ID"), array_type(vec![10], BIT)),
+/// ]);
+/// let t2 = named_tuple_type(vec![
+///     (NULL_HEADER.to_owned(), array_type(vec![20], BIT)),
+///     ("ID".to_owned(), array_type(vec![20], INT32)),
+///     ("Age".to_owned(), array_type(vec![20], INT32)),
+/// ]);
+/// let n1 = g.input(t1).unwrap();
+/// let n2 = g.input(t2).unwrap();
+/// let op = NullableSetIntersection {
+///     headers: vec![("ID".to_owned(), "ID".to_owned())],
+///     null_equality: NullEquality::Sql,
+/// };
+/// let n3 = g.custom_op(CustomOperation::new(op), vec![n1, n2]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct NullableSetIntersection {
+    // Instead of HashMap, Vector is used to support the Hash trait
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub null_equality: NullEquality,
+}
+
+// Database columns as a vector of headers and nodes
+type Columns = Vec<(String, Node)>;
+
+fn get_columns(data: Node, t: &Type) -> Result<Columns> {
+    let mut columns = vec![];
+    if let Type::NamedTuple(header_types) = t {
+        for (header, _) in header_types {
+            columns.push((header.clone(), data.named_tuple_get(header.clone())?));
+        }
+        Ok(columns)
+    } else {
+        Err(runtime_error!(
+            "Only named tuples can be intersected, got {:?}",
+            t
+        ))
+    }
+}
+
+fn get_column(columns: &Columns, header: &str) -> Option<Node> {
+    columns
+        .iter()
+        .find(|(h, _)| h == header)
+        .map(|(_, column)| column.clone())
+}
+
+fn replace_column(columns: &mut Columns, header: &str, new_column: Node) {
+    for (h, column) in columns.iter_mut() {
+        if h == header {
+            *column = new_column.clone();
+        }
+    }
+}
+
+// Returns the null bits of a key column or None if this column isn't nullable
+fn get_key_null_bits(columns: &Columns, header: &str) -> Result<Option<Node>> {
+    let key_column = get_column(columns, header)
+        .ok_or_else(|| runtime_error!("There is no key column {}", header))?;
+    let num_entries = key_column.get_type()?.get_shape()[0];
+    match get_column(columns, &get_column_null_header(header)) {
+        Some(null_bits) => {
+            if null_bits.get_type()? != array_type(vec![num_entries], BIT) {
+                return Err(runtime_error!(
+                    "Null bits of column {} must be a binary array of shape {:?}",
+                    header,
+                    vec![num_entries]
+                ));
+            }
+            Ok(Some(null_bits))
+        }
+        None => Ok(None),
+    }
+}
+
+#[typetag::serde]
+impl CustomOperationBody for NullableSetIntersection {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 2 {
+            return Err(runtime_error!(
+                "NullableSetIntersection should have 2 inputs"
+            ));
+        }
+        if self.headers.is_empty() {
+            return Err(runtime_error!("No column headers provided"));
+        }
+        let g = context.create_graph()?;
+        let data_x = g.input(argument_types[0].clone())?;
+        let data_y = g.input(argument_types[1].clone())?;
+        let mut columns_x = get_columns(data_x, &argument_types[0])?;
+        let mut columns_y = get_columns(data_y, &argument_types[1])?;
+        for columns in [&columns_x, &columns_y] {
+            if get_column(columns, NULL_HEADER).is_none() {
+                return Err(runtime_error!("Named tuple should contain the null column"));
+            }
+        }
+
+        let mut headers: HashMap<String, String> = self.headers.iter().cloned().collect();
+        // Columns added to the first database that should be removed from the result
+        let mut extra_headers_x = vec![];
+        for (h_x, h_y) in &self.headers {
+            let null_bits_x = get_key_null_bits(&columns_x, h_x)?;
+            let null_bits_y = get_key_null_bits(&columns_y, h_y)?;
+            if null_bits_x.is_none() && null_bits_y.is_none() {
+                continue;
+            }
+            let null_header_x = get_column_null_header(h_x);
+            let null_header_y = get_column_null_header(h_y);
+            match self.null_equality {
+                NullEquality::Sql => {
+                    // Rows with missing keys are removed from both databases
+                    for (columns, null_bits) in
+                        [(&mut columns_x, null_bits_x), (&mut columns_y, null_bits_y)]
+                    {
+                        if let Some(bits) = null_bits {
+                            let null_column = get_column(columns, NULL_HEADER).unwrap();
+                            replace_column(columns, NULL_HEADER, null_column.multiply(bits)?);
+                        }
+                    }
+                    // Null bits of key columns of the second database are redundant
+                    columns_y.retain(|(h, _)| *h != null_header_y);
+                }
+                NullEquality::NullSafe => {
+                    // Missing keys are replaced by zeros and their null bits become a key column
+                    for (columns, null_bits, header, null_header, is_x) in [
+                        (&mut columns_x, null_bits_x, h_x, &null_header_x, true),
+                        (&mut columns_y, null_bits_y, h_y, &null_header_y, false),
+                    ] {
+                        match null_bits {
+                            Some(bits) => {
+                                let key_column = get_column(columns, header).unwrap();
+                                replace_column(
+                                    columns,
+                                    header,
+                                    multiply_rows_by_bits(key_column, bits)?,
+                                );
+                            }
+                            None => {
+                                let num_entries =
+                                    get_column(columns, header).unwrap().get_type()?.get_shape()[0];
+                                let ones = g.constant(
+                                    array_type(vec![num_entries], BIT),
+                                    Value::from_flattened_array(
+                                        &vec![1; num_entries as usize],
+                                        BIT,
+                                    )?,
+                                )?;
+                                columns.push((null_header.clone(), ones));
+                                if is_x {
+                                    extra_headers_x.push(null_header.clone());
+                                }
+                            }
+                        }
+                    }
+                    headers.insert(null_header_x, null_header_y);
+                }
+            }
+        }
+
+        let intersection = g
+            .create_named_tuple(columns_x)?
+            .set_intersection(g.create_named_tuple(columns_y)?, headers)?;
+        let mut result_columns = vec![];
+        for (header, column) in get_columns(intersection.clone(), &intersection.get_type()?)? {
+            if !extra_headers_x.contains(&header) {
+                result_columns.push((header, column));
+            }
+        }
+        g.create_named_tuple(result_columns)?.set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "NullableSetIntersection(keys:{:?},null_equality:{:?})",
+            self.headers, self.null_equality
+        )
+    }
+}
+
+register_custom_operation!(NullableSetIntersection, [NamedTuple, NamedTuple]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::{run_instantiation_pass, CustomOperation};
+    use crate::data_types::{named_tuple_type, INT32};
+    use crate::evaluators::{evaluate_simple_evaluator, random_evaluate};
+    use crate::graphs::create_context;
+    use crate::inline::inline_common::DepthOptimizationLevel;
+    use crate::inline::inline_ops::{inline_operations, InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{prepare_for_mpc_evaluation, IOStatus};
+
+    type Database = Vec<(String, Vec<u64>)>;
+
+    fn get_type(data: &Database) -> Type {
+        let mut header_types = vec![];
+        for (header, column) in data {
+            let st = if header == NULL_HEADER || header.ends_with(NULL_HEADER) {
+                BIT
+            } else {
+                INT32
+            };
+            header_types.push((header.clone(), array_type(vec![column.len() as u64], st)));
+        }
+        named_tuple_type(header_types)
+    }
+
+    fn get_value(data: &Database) -> Result<Value> {
+        let mut columns = vec![];
+        for ((_, column), (_, t)) in data.iter().zip(get_named_types(&get_type(data))) {
+            columns.push(Value::from_flattened_array(column, t.get_scalar_type())?);
+        }
+        Ok(Value::from_vector(columns))
+    }
+
+    fn get_named_types(t: &Type) -> Vec<(String, Type)> {
+        if let Type::NamedTuple(header_types) = t {
+            header_types
+                .iter()
+                .map(|(h, t)| (h.clone(), (**t).clone()))
+                .collect()
+        } else {
+            panic!("Named tuple expected")
+        }
+    }
+
+    fn intersection_helper(
+        data_x: &Database,
+        data_y: &Database,
+        null_equality: NullEquality,
+        is_mpc: bool,
+    ) -> Result<Database> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let x = g.input(get_type(data_x))?;
+        let y = g.input(get_type(data_y))?;
+        g.custom_op(
+            CustomOperation::new(NullableSetIntersection {
+                headers: vec![("ID".to_owned(), "ID".to_owned())],
+                null_equality,
+            }),
+            vec![x, y],
+        )?
+        .set_as_output()?;
+        g.finalize()?;
+        g.set_as_main()?;
+        c.finalize()?;
+        let inputs = vec![get_value(data_x)?, get_value(data_y)?];
+        let (result_t, result) = if is_mpc {
+            let inline_config = InlineConfig {
+                default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                ..Default::default()
+            };
+            let instantiated_c = run_instantiation_pass(c)?.get_context();
+            let inlined_c = inline_operations(instantiated_c, inline_config.clone())?;
+            let mpc_c = prepare_for_mpc_evaluation(
+                inlined_c,
+                vec![vec![IOStatus::Party(0), IOStatus::Party(1)]],
+                vec![vec![IOStatus::Party(0)]],
+                inline_config,
+            )?;
+            let mpc_g = mpc_c.get_main_graph()?;
+            (
+                mpc_g.get_output_node()?.get_type()?,
+                evaluate_simple_evaluator(mpc_g, inputs, None)?,
+            )
+        } else {
+            let instantiated_c = run_instantiation_pass(c)?.get_context();
+            let instantiated_g = instantiated_c.get_main_graph()?;
+            (
+                instantiated_g.get_output_node()?.get_type()?,
+                random_evaluate(instantiated_g, inputs)?,
+            )
+        };
+        let mut result_columns = vec![];
+        for ((header, t), column) in get_named_types(&result_t)
+            .into_iter()
+            .zip(result.to_vector()?)
+        {
+            result_columns.push((header, column.to_flattened_array_u64(t)?));
+        }
+        Ok(result_columns)
+    }
+
+    fn database(columns: Vec<(&str, Vec<u64>)>) -> Database {
+        columns
+            .into_iter()
+            .map(|(h, column)| (h.to_owned(), column))
+            .collect()
+    }
+
+    #[test]
+    fn test_sql_null_equality() {
+        || -> Result<()> {
+            let data_x = database(vec![
+                (NULL_HEADER, vec![1, 1, 1, 1]),
+                ("ID", vec![1, 2, 3, 9]),
+                ("ID_null", vec![1, 1, 0, 0]),
+                ("v", vec![10, 20, 30, 40]),
+            ]);
+            let data_y = database(vec![
+                (NULL_HEADER, vec![1, 1, 1]),
+                ("ID", vec![2, 3, 5]),
+                ("ID_null", vec![1, 0, 1]),
+                ("w", vec![200, 300, 500]),
+            ]);
+            let expected = database(vec![
+                (NULL_HEADER, vec![0, 1, 0, 0]),
+                ("ID", vec![0, 2, 0, 0]),
+                ("ID_null", vec![0, 1, 0, 0]),
+                ("v", vec![0, 20, 0, 0]),
+                ("w", vec![0, 200, 0, 0]),
+            ]);
+            for is_mpc in [false, true] {
+                assert_eq!(
+                    intersection_helper(&data_x, &data_y, NullEquality::Sql, is_mpc)?,
+                    expected
+                );
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_null_safe_equality() {
+        || -> Result<()> {
+            let data_x = database(vec![
+                (NULL_HEADER, vec![1, 1, 1, 1]),
+                ("ID", vec![1, 2, 3, 9]),
+                ("ID_null", vec![1, 1, 0, 1]),
+                ("v", vec![10, 20, 30, 40]),
+            ]);
+            let data_y = database(vec![
+                (NULL_HEADER, vec![1, 1, 1]),
+                ("ID", vec![2, 7, 5]),
+                ("ID_null", vec![1, 0, 1]),
+                ("w", vec![200, 700, 500]),
+            ]);
+            assert_eq!(
+                intersection_helper(&data_x, &data_y, NullEquality::NullSafe, false)?,
+                database(vec![
+                    (NULL_HEADER, vec![0, 1, 1, 0]),
+                    ("ID", vec![0, 2, 0, 0]),
+                    ("ID_null", vec![0, 1, 0, 0]),
+                    ("v", vec![0, 20, 30, 0]),
+                    ("w", vec![0, 200, 700, 0]),
+                ])
+            );
+            // Missing keys of the second database don't match non-nullable keys
+            let data_x = database(vec![
+                (NULL_HEADER, vec![1, 1, 1, 1]),
+                ("ID", vec![1, 2, 0, 9]),
+                ("v", vec![10, 20, 30, 40]),
+            ]);
+            let data_y = database(vec![
+                (NULL_HEADER, vec![1, 1, 1]),
+                ("ID", vec![2, 0, 9]),
+                ("ID_null", vec![1, 0, 1]),
+                ("w", vec![200, 700, 900]),
+            ]);
+            let expected = database(vec![
+                (NULL_HEADER, vec![0, 1, 0, 1]),
+                ("ID", vec![0, 2, 0, 9]),
+                ("v", vec![0, 20, 0, 40]),
+                ("w", vec![0, 200, 0, 900]),
+            ]);
+            for is_mpc in [false, true] {
+                assert_eq!(
+                    intersection_helper(&data_x, &data_y, NullEquality::NullSafe, is_mpc)?,
+                    expected
+                );
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_nullable_set_intersection() {
+        let helper = |t_x: Type, t_y: Type, headers: Vec<(String, String)>| -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(t_x)?;
+            let y = g.input(t_y)?;
+            g.custom_op(
+                CustomOperation::new(NullableSetIntersection {
+                    headers,
+                    null_equality: NullEquality::NullSafe,
+                }),
+                vec![x, y],
+            )?
+            .set_as_output()?;
+            g.finalize()?;
+            g.set_as_main()?;
+            c.finalize()?;
+            run_instantiation_pass(c)?;
+            Ok(())
+        };
+        let t = |columns: Vec<(&str, Type)>| {
+            named_tuple_type(
+                columns
+                    .into_iter()
+                    .map(|(h, t)| (h.to_owned(), t))
+                    .collect(),
+            )
+        };
+        let null_t = (NULL_HEADER, array_type(vec![3], BIT));
+        let key_t = ("ID", array_type(vec![3], INT32));
+        let id_headers = vec![("ID".to_owned(), "ID".to_owned())];
+        let good_t = t(vec![
+            null_t.clone(),
+            key_t.clone(),
+            ("ID_null", array_type(vec![3], BIT)),
+        ]);
+        assert!(helper(good_t.clone(), good_t.clone(), id_headers.clone()).is_ok());
+        assert!(helper(good_t.clone(), good_t.clone(), vec![]).is_err());
+        assert!(helper(
+            good_t.clone(),
+            good_t.clone(),
+            vec![("a".to_owned(), "ID".to_owned())]
+        )
+        .is_err());
+        assert!(helper(
+            good_t.clone(),
+            t(vec![
+                null_t.clone(),
+                key_t.clone(),
+                ("ID_null", array_type(vec![3], INT32)),
+            ]),
+            id_headers.clone()
+        )
+        .is_err());
+        assert!(helper(
+            good_t.clone(),
+            t(vec![key_t, ("ID_null", array_type(vec![3], BIT))]),
+            id_headers.clone()
+        )
+        .is_err());
+        assert!(helper(good_t, array_type(vec![3], INT32), id_headers).is_err());
+    }
+}
@@ -0,0 +1,409 @@
+//! Private ID mapping of two databases that reveals matched row indices instead of data columns.
+use std::collections::HashMap;
+
+use crate::custom_ops::CustomOperationBody;
+use crate::data_types::{array_type, Type, BIT, UINT64};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, Node};
+use crate::type_inference::NULL_HEADER;
+
+use serde::{Deserialize, Serialize};
+
+/// Header of the column containing indices of matched rows of the first database.
+pub const ROW_X_HEADER: &str = "row_x";
+/// Header of the column containing indices of matched rows of the second database.
+pub const ROW_Y_HEADER: &str = "row_y";
+/// Header of the column containing pseudonyms of matched rows.
+pub const PSEUDONYM_HEADER: &str = "pseudonym";
+
+// Header of the salt column of the second database within the intersected tuples
+const SALT_Y_HEADER: &str = "pseudonym_salt_y";
+
+/// A structure that defines the custom operation PrivateIdMapping that computes the mapping between matching rows of two databases without attaching their data columns.
+///
+/// Databases are represented as named tuples of arrays as in [Graph::set_intersection].
+/// Only the null columns and the key columns given by `headers` participate in the computation; other columns are ignored.
+/// Thus, downstream systems can fetch matched records out-of-band, while data columns never enter the MPC graph.
+///
+/// The result is a named tuple with the following columns of length `n`, the number of rows of the first database:
+/// - the [null column](NULL_HEADER) containing ones in matched rows;
+/// - [ROW_X_HEADER] containing indices (of type [UINT64]) of matched rows in the first database;
+/// - [ROW_Y_HEADER] containing indices (of type [UINT64]) of the corresponding rows in the second database;
+/// - [PSEUDONYM_HEADER], only if `pseudonym_headers` is given (see below).
+///
+/// As for [Graph::set_intersection], matched rows keep the order of the first database and all the other rows are zero.
+///
+/// If `pseudonym_headers` is equal to `Some((h_x, h_y))`, the databases must contain binary salt columns with headers `h_x` and `h_y` of shapes `[n, k]` and `[m, k]`.
+/// Each party should fill its salt column with fresh random bits.
+/// Then, the pseudonym of a matched pair of rows is the XOR of their salts, which is uniformly random for each party as long as the parties don't collude.
+/// Revealing the pseudonyms to both parties along with [ROW_X_HEADER] only to the first party and [ROW_Y_HEADER] only to the second party gives each party a pseudonymized mapping of its own rows.
+///
+/// Note that the result is aligned with the rows of the first database.
+/// Hence, revealing [ROW_Y_HEADER] in this order to the second party discloses which rows of the first database were matched; the result should be kept shared or shuffled otherwise.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a named tuple with the first database
+/// - Node containing a named tuple with the second database
+///
+/// # Custom operation returns
+///
+/// New PrivateIdMapping node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, named_tuple_type, BIT, INT32};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::private_id::PrivateIdMapping;
+/// # use ciphercore_base::type_inference::NULL_HEADER;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let t1 = named_tuple_type(vec![
+///     (NULL_HEADER.to_owned(), array_type(vec![10], BIT)),
+///     ("ID".to_owned(), array_type(vec![10], INT32)),
+///     ("Salt".to_owned(), array_type(vec![10, 64], BIT)),
+/// ]);
+/// let t2 = named_tuple_type(vec![
+///     (NULL_HEADER.to_owned(), array_type(vec![20], BIT)),
+///     ("ID".to_owned(), array_type(vec![20], INT32)),
+///     ("Salt".to_owned(), array_type(vec![20, 64], BIT)),
+///     ("Age".to_owned(), array_type(vec![20], INT32)),
+/// ]);
+/// let n1 = g.input(t1).unwrap();
+/// let n2 = g.input(t2).unwrap();
+/// let op = PrivateIdMapping {
+///     headers: vec![("ID".to_owned(), "ID".to_owned())],
+///     pseudonym_headers: Some(("Salt".to_owned(), "Salt".to_owned())),
+/// };
+/// let n3 = g.custom_op(CustomOperation::new(op), vec![n1, n2]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct PrivateIdMapping {
+    // Instead of HashMap, Vector is used to support the Hash trait
+    pub headers: Vec<(String, String)>,
+    pub pseudonym_headers: Option<(String, String)>,
+}
+
+fn get_column(data: &Node, t: &Type, header: &str) -> Result<(Node, Type)> {
+    if let Type::NamedTuple(header_types) = t {
+        for (h, column_t) in header_types {
+            if h == header {
+                return Ok((
+                    data.named_tuple_get(header.to_owned())?,
+                    (**column_t).clone(),
+                ));
+            }
+        }
+        Err(runtime_error!("There is no column {}", header))
+    } else {
+        Err(runtime_error!(
+            "Only named tuples can be intersected, got {:?}",
+            t
+        ))
+    }
+}
+
+fn get_row_indices(g: &Graph, num_entries: u64) -> Result<Node> {
+    let indices: Vec<u64> = (0..num_entries).collect();
+    g.constant(
+        array_type(vec![num_entries], UINT64),
+        Value::from_flattened_array(&indices, UINT64)?,
+    )
+}
+
+#[typetag::serde]
+impl CustomOperationBody for PrivateIdMapping {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 2 {
+            return Err(runtime_error!("PrivateIdMapping should have 2 inputs"));
+        }
+        if self.headers.is_empty() {
+            return Err(runtime_error!("No column headers provided"));
+        }
+        let mut headers_x: Vec<&String> = self.headers.iter().map(|(h, _)| h).collect();
+        let mut headers_y: Vec<&String> = self.headers.iter().map(|(_, h)| h).collect();
+        if let Some((salt_x, salt_y)) = &self.pseudonym_headers {
+            headers_x.push(salt_x);
+            headers_y.push(salt_y);
+        }
+        for h in headers_x.iter().chain(headers_y.iter()) {
+            if [ROW_X_HEADER, ROW_Y_HEADER, SALT_Y_HEADER].contains(&h.as_str()) {
+                return Err(runtime_error!("Column header {} is reserved", h));
+            }
+        }
+        let mut unique_headers_x = headers_x.clone();
+        unique_headers_x.sort();
+        unique_headers_x.dedup();
+        let mut unique_headers_y = headers_y.clone();
+        unique_headers_y.sort();
+        unique_headers_y.dedup();
+        if unique_headers_x.len() != headers_x.len() || unique_headers_y.len() != headers_y.len() {
+            return Err(runtime_error!("Column headers should be distinct"));
+        }
+
+        let g = context.create_graph()?;
+        let data_x = g.input(argument_types[0].clone())?;
+        let data_y = g.input(argument_types[1].clone())?;
+
+        let mut columns = vec![];
+        for (data, t, row_header) in [
+            (&data_x, &argument_types[0], ROW_X_HEADER),
+            (&data_y, &argument_types[1], ROW_Y_HEADER),
+        ] {
+            let (null_column, null_t) = get_column(data, t, NULL_HEADER)?;
+            if !null_t.is_array() || null_t.get_shape().len() != 1 {
+                return Err(runtime_error!(
+                    "Null column should be a one-dimensional array"
+                ));
+            }
+            let num_entries = null_t.get_shape()[0];
+            columns.push(vec![
+                (NULL_HEADER.to_owned(), null_column),
+                (row_header.to_owned(), get_row_indices(&g, num_entries)?),
+            ]);
+        }
+        let mut key_headers = HashMap::new();
+        for (h_x, h_y) in &self.headers {
+            columns[0].push((h_x.clone(), get_column(&data_x, &argument_types[0], h_x)?.0));
+            columns[1].push((h_y.clone(), get_column(&data_y, &argument_types[1], h_y)?.0));
+            key_headers.insert(h_x.clone(), h_y.clone());
+        }
+        if let Some((salt_x, salt_y)) = &self.pseudonym_headers {
+            let (salt_column_x, salt_t_x) = get_column(&data_x, &argument_types[0], salt_x)?;
+            let (salt_column_y, salt_t_y) = get_column(&data_y, &argument_types[1], salt_y)?;
+            for t in [&salt_t_x, &salt_t_y] {
+                if !t.is_array() || t.get_scalar_type() != BIT || t.get_shape().len() != 2 {
+                    return Err(runtime_error!(
+                        "Salt columns should be two-dimensional binary arrays, got {:?}",
+                        t
+                    ));
+                }
+            }
+            if salt_t_x.get_shape()[1] != salt_t_y.get_shape()[1] {
+                return Err(runtime_error!("Salt columns should have the same length"));
+            }
+            columns[0].push((salt_x.clone(), salt_column_x));
+            columns[1].push((SALT_Y_HEADER.to_owned(), salt_column_y));
+        }
+
+        let columns_y = columns.pop().unwrap();
+        let columns_x = columns.pop().unwrap();
+        let intersection = g
+            .create_named_tuple(columns_x)?
+            .set_intersection(g.create_named_tuple(columns_y)?, key_headers)?;
+        let mut result_columns = vec![];
+        for header in [NULL_HEADER, ROW_X_HEADER, ROW_Y_HEADER] {
+            result_columns.push((
+                header.to_owned(),
+                intersection.named_tuple_get(header.to_owned())?,
+            ));
+        }
+        if let Some((salt_x, _)) = &self.pseudonym_headers {
+            // Salts of unmatched rows are zeroed by the intersection, so are their pseudonyms
+            let pseudonyms = intersection
+                .named_tuple_get(salt_x.clone())?
+                .add(intersection.named_tuple_get(SALT_Y_HEADER.to_owned())?)?;
+            result_columns.push((PSEUDONYM_HEADER.to_owned(), pseudonyms));
+        }
+        g.create_named_tuple(result_columns)?.set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "PrivateIdMapping(keys:{:?},pseudonyms:{:?})",
+            self.headers, self.pseudonym_headers
+        )
+    }
+}
+
+register_custom_operation!(PrivateIdMapping, [NamedTuple, NamedTuple]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::{run_instantiation_pass, CustomOperation};
+    use crate::data_types::{named_tuple_type, INT32};
+    use crate::evaluators::{evaluate_simple_evaluator, random_evaluate};
+    use crate::graphs::create_context;
+    use crate::inline::inline_common::DepthOptimizationLevel;
+    use crate::inline::inline_ops::{inline_operations, InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{prepare_for_mpc_evaluation, IOStatus};
+
+    // Columns of a database with a null column, an INT32 key column "ID" and a salt column "Salt" with 2-bit salts
+    fn get_database(null: &[u64], ids: &[u64], salts: &[u64]) -> Result<(Type, Value)> {
+        let n = null.len() as u64;
+        let t = named_tuple_type(vec![
+            (NULL_HEADER.to_owned(), array_type(vec![n], BIT)),
+            ("ID".to_owned(), array_type(vec![n], INT32)),
+            ("Salt".to_owned(), array_type(vec![n, 2], BIT)),
+            ("Data".to_owned(), array_type(vec![n], INT32)),
+        ]);
+        let data: Vec<u64> = (0..n).map(|i| 100 + i).collect();
+        let v = Value::from_vector(vec![
+            Value::from_flattened_array(null, BIT)?,
+            Value::from_flattened_array(ids, INT32)?,
+            Value::from_flattened_array(salts, BIT)?,
+            Value::from_flattened_array(&data, INT32)?,
+        ]);
+        Ok((t, v))
+    }
+
+    fn mapping_helper(
+        data_x: (Type, Value),
+        data_y: (Type, Value),
+        pseudonyms: bool,
+        is_mpc: bool,
+    ) -> Result<Vec<Vec<u64>>> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let x = g.input(data_x.0)?;
+        let y = g.input(data_y.0)?;
+        let pseudonym_headers = if pseudonyms {
+            Some(("Salt".to_owned(), "Salt".to_owned()))
+        } else {
+            None
+        };
+        g.custom_op(
+            CustomOperation::new(PrivateIdMapping {
+                headers: vec![("ID".to_owned(), "ID".to_owned())],
+                pseudonym_headers,
+            }),
+            vec![x, y],
+        )?
+        .set_as_output()?;
+        g.finalize()?;
+        g.set_as_main()?;
+        c.finalize()?;
+        let inputs = vec![data_x.1, data_y.1];
+        let instantiated_c = run_instantiation_pass(c)?.get_context();
+        let (result_t, result) = if is_mpc {
+            let inline_config = InlineConfig {
+                default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                ..Default::default()
+            };
+            let inlined_c = inline_operations(instantiated_c, inline_config.clone())?;
+            let mpc_c = prepare_for_mpc_evaluation(
+                inlined_c,
+                vec![vec![IOStatus::Party(0), IOStatus::Party(1)]],
+                vec![vec![IOStatus::Party(0)]],
+                inline_config,
+            )?;
+            let mpc_g = mpc_c.get_main_graph()?;
+            (
+                mpc_g.get_output_node()?.get_type()?,
+                evaluate_simple_evaluator(mpc_g, inputs, None)?,
+            )
+        } else {
+            let instantiated_g = instantiated_c.get_main_graph()?;
+            (
+                instantiated_g.get_output_node()?.get_type()?,
+                random_evaluate(instantiated_g, inputs)?,
+            )
+        };
+        let mut result_columns = vec![];
+        if let Type::NamedTuple(header_types) = result_t {
+            for ((_, t), column) in header_types.into_iter().zip(result.to_vector()?) {
+                result_columns.push(column.to_flattened_array_u64((*t).clone())?);
+            }
+        }
+        Ok(result_columns)
+    }
+
+    #[test]
+    fn test_private_id_mapping() {
+        || -> Result<()> {
+            let data_x = get_database(&[1, 1, 1, 1], &[1, 2, 3, 9], &[0, 1, 1, 0, 1, 1, 0, 0])?;
+            let data_y = get_database(&[1, 1, 1], &[9, 3, 5], &[1, 1, 0, 1, 1, 0])?;
+            for is_mpc in [false, true] {
+                assert_eq!(
+                    mapping_helper(data_x.clone(), data_y.clone(), false, is_mpc)?,
+                    vec![vec![0, 0, 1, 1], vec![0, 0, 2, 3], vec![0, 0, 1, 0]]
+                );
+                assert_eq!(
+                    mapping_helper(data_x.clone(), data_y.clone(), true, is_mpc)?,
+                    vec![
+                        vec![0, 0, 1, 1],
+                        vec![0, 0, 2, 3],
+                        vec![0, 0, 1, 0],
+                        vec![0, 0, 0, 0, 1, 0, 1, 1]
+                    ]
+                );
+            }
+            // Rows with zero null bits aren't matched
+            let data_y = get_database(&[1, 0, 1], &[9, 3, 5], &[1, 1, 0, 1, 1, 0])?;
+            assert_eq!(
+                mapping_helper(data_x, data_y, false, false)?,
+                vec![vec![0, 0, 0, 1], vec![0, 0, 0, 3], vec![0, 0, 0, 0]]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_private_id_mapping() {
+        let helper = |t_x: Type,
+                      t_y: Type,
+                      headers: Vec<(&str, &str)>,
+                      pseudonym_headers: Option<(&str, &str)>|
+         -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(t_x)?;
+            let y = g.input(t_y)?;
+            g.custom_op(
+                CustomOperation::new(PrivateIdMapping {
+                    headers: headers
+                        .into_iter()
+                        .map(|(h_x, h_y)| (h_x.to_owned(), h_y.to_owned()))
+                        .collect(),
+                    pseudonym_headers: pseudonym_headers
+                        .map(|(h_x, h_y)| (h_x.to_owned(), h_y.to_owned())),
+                }),
+                vec![x, y],
+            )?
+            .set_as_output()?;
+            g.finalize()?;
+            g.set_as_main()?;
+            c.finalize()?;
+            run_instantiation_pass(c)?;
+            Ok(())
+        };
+        let t = |n: u64, salt_length: u64| {
+            named_tuple_type(vec![
+                (NULL_HEADER.to_owned(), array_type(vec![n], BIT)),
+                ("ID".to_owned(), array_type(vec![n], INT32)),
+                ("Salt".to_owned(), array_type(vec![n, salt_length], BIT)),
+            ])
+        };
+        assert!(helper(t(3, 2), t(4, 2), vec![("ID", "ID")], Some(("Salt", "Salt"))).is_ok());
+        assert!(helper(t(3, 2), t(4, 2), vec![], None).is_err());
+        assert!(helper(t(3, 2), t(4, 2), vec![("ID", "Age")], None).is_err());
+        assert!(helper(t(3, 2), t(4, 3), vec![("ID", "ID")], Some(("Salt", "Salt"))).is_err());
+        assert!(helper(t(3, 2), t(4, 2), vec![("ID", "ID")], Some(("ID", "Salt"))).is_err());
+        assert!(helper(t(3, 2), t(4, 2), vec![("ID", "ID")], Some(("Salt", "ID"))).is_err());
+        assert!(helper(
+            t(3, 2),
+            array_type(vec![4], INT32),
+            vec![("ID", "ID")],
+            None
+        )
+        .is_err());
+        let t_reserved = named_tuple_type(vec![
+            (NULL_HEADER.to_owned(), array_type(vec![3], BIT)),
+            (ROW_X_HEADER.to_owned(), array_type(vec![3], INT32)),
+        ]);
+        assert!(helper(
+            t_reserved.clone(),
+            t_reserved,
+            vec![(ROW_X_HEADER, ROW_X_HEADER)],
+            None
+        )
+        .is_err());
+    }
+}
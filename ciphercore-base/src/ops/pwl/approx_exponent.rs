@@ -97,6 +97,8 @@ impl CustomOperationBody for ApproxExponent {
     }
 }
 
+register_custom_operation!(ApproxExponent, [Array]);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -95,6 +95,8 @@ impl CustomOperationBody for ApproxGelu {
     }
 }
 
+register_custom_operation!(ApproxGelu, [Array]);
+
 fn approximate_gelu(x: f32) -> f32 {
     // It appears there is no Erf in Rust without additional crates. So we use an approximation.
     // See also <https://paperswithcode.com/method/gelu>.
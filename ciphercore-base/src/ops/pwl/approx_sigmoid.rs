@@ -97,6 +97,8 @@ impl CustomOperationBody for ApproxSigmoid {
     }
 }
 
+register_custom_operation!(ApproxSigmoid, [Array]);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,499 @@
+//! Sampling of random values from non-uniform distributions: Bernoulli, uniform over an integer range and an approximate discrete Gaussian.
+//!
+//! All the samplers are built from uniformly random bits generated by [Graph::random].
+//! Thus, they can be evaluated locally, and after MPC compilation the underlying random bits are generated jointly by the parties,
+//! so the samples are secret-shared and unknown to every party until they are revealed.
+use crate::custom_ops::{CustomOperation, CustomOperationBody};
+use crate::data_types::{array_type, ArrayShape, ScalarType, Type, BIT};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, Node, SliceElement};
+
+use serde::{Deserialize, Serialize};
+
+use super::comparisons::LessThan;
+use super::utils::{constant_scalar, single_bit_to_arithmetic, zeros};
+
+fn validate_integer_scalar_type(st: &ScalarType, op_name: &str) -> Result<()> {
+    if *st == BIT {
+        return Err(runtime_error!(
+            "{} can only generate integers of a non-binary scalar type",
+            op_name
+        ));
+    }
+    Ok(())
+}
+
+// Returns an array of shape `shape + [num_bits]` whose last axis contains `random_bits` uniformly random bits followed by zeros
+fn random_bits_padded_with_zeros(
+    g: &Graph,
+    shape: &ArrayShape,
+    random_bits: u64,
+    num_bits: u64,
+) -> Result<Node> {
+    let mut random_shape = shape.clone();
+    random_shape.push(random_bits);
+    let random = g.random(array_type(random_shape, BIT))?;
+    if random_bits == num_bits {
+        return Ok(random);
+    }
+    let mut zeros_shape = shape.clone();
+    zeros_shape.push(num_bits - random_bits);
+    g.concatenate(
+        vec![random, zeros(g, array_type(zeros_shape, BIT))?],
+        shape.len() as u64,
+    )
+}
+
+/// A structure that defines the custom operation Bernoulli that samples independent bits equal to 1 with a given probability `p`.
+///
+/// The probability is given in the fixed-point representation as `p = numerator / 2^precision`.
+/// Each bit is computed as the comparison `r < numerator`, where `r` is a uniformly random `precision`-bit unsigned integer.
+///
+/// The operation has no arguments; it returns an array of the given shape or a scalar if the shape is empty.
+///
+/// To use this and other custom operations in computation graphs, see [Graph::custom_op].
+///
+/// # Custom operation returns
+///
+/// New Bernoulli node containing a binary array or scalar
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, BIT};
+/// # use ciphercore_base::custom_ops::CustomOperation;
+/// # use ciphercore_base::ops::random_sampling::Bernoulli;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// // Bits equal to 1 with probability 1/4
+/// let n = g.custom_op(CustomOperation::new(Bernoulli {numerator: 1, precision: 2, shape: vec![10]}), vec![]).unwrap();
+/// assert_eq!(n.get_type().unwrap(), array_type(vec![10], BIT));
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct Bernoulli {
+    /// Numerator of the probability of sampling 1, must be less than 2<sup>precision</sup>
+    pub numerator: u64,
+    /// Number of fractional bits of the probability, must be between 1 and 64
+    pub precision: u64,
+    /// Shape of the sampled array
+    pub shape: ArrayShape,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for Bernoulli {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if !arguments_types.is_empty() {
+            return Err(runtime_error!("Bernoulli doesn't take arguments"));
+        }
+        if self.precision == 0 || self.precision > 64 {
+            return Err(runtime_error!(
+                "Bernoulli precision must be between 1 and 64, got {}",
+                self.precision
+            ));
+        }
+        if self.precision < 64 && self.numerator >> self.precision != 0 {
+            return Err(runtime_error!(
+                "Bernoulli probability numerator must be less than 2^{}",
+                self.precision
+            ));
+        }
+        let g = context.create_graph()?;
+        let mut random_shape = self.shape.clone();
+        random_shape.push(self.precision);
+        let random = g.random(array_type(random_shape, BIT))?;
+        let numerator_bits: Vec<u64> = (0..self.precision)
+            .map(|i| (self.numerator >> i) & 1)
+            .collect();
+        let numerator = g.constant(
+            array_type(vec![self.precision], BIT),
+            Value::from_flattened_array(&numerator_bits, BIT)?,
+        )?;
+        let output = g.custom_op(
+            CustomOperation::new(LessThan {
+                signed_comparison: false,
+            }),
+            vec![random, numerator],
+        )?;
+        g.set_output_node(output)?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "Bernoulli(numerator={}, precision={}, shape={:?})",
+            self.numerator, self.precision, self.shape
+        )
+    }
+}
+
+register_custom_operation!(Bernoulli, []);
+
+/// A structure that defines the custom operation UniformRange that samples independent integers from the range `[low, high)`.
+///
+/// Let `n = high - low`, `m` be the number of bits of `n` and `b` be the bit size of the output scalar type.
+/// Every sample is computed as `low + floor(r * n / 2^(b - m))`, where `r` is a uniformly random `(b - m)`-bit unsigned integer.
+/// This avoids rejection sampling, which would leak the number of rejected samples, at the cost of a small bias:
+/// the statistical distance between the distribution of every sample and the uniform distribution over `[low, high)` is at most `n / 2^(b - m)`.
+/// Thus, the range should be much smaller than the range of the output scalar type.
+///
+/// The operation has no arguments; it returns an array of the given shape or a scalar if the shape is empty.
+///
+/// To use this and other custom operations in computation graphs, see [Graph::custom_op].
+///
+/// # Custom operation returns
+///
+/// New UniformRange node containing an array or scalar of the given integer type
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT64};
+/// # use ciphercore_base::custom_ops::CustomOperation;
+/// # use ciphercore_base::ops::random_sampling::UniformRange;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let n = g.custom_op(CustomOperation::new(UniformRange {low: -5, high: 10, st: INT64, shape: vec![10]}), vec![]).unwrap();
+/// assert_eq!(n.get_type().unwrap(), array_type(vec![10], INT64));
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct UniformRange {
+    /// Lower bound of the range (inclusive)
+    pub low: i64,
+    /// Upper bound of the range (exclusive)
+    pub high: i64,
+    /// Scalar type of the sampled integers
+    pub st: ScalarType,
+    /// Shape of the sampled array
+    pub shape: ArrayShape,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for UniformRange {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if !arguments_types.is_empty() {
+            return Err(runtime_error!("UniformRange doesn't take arguments"));
+        }
+        validate_integer_scalar_type(&self.st, "UniformRange")?;
+        if self.low >= self.high {
+            return Err(runtime_error!(
+                "UniformRange range [{}, {}) is empty",
+                self.low,
+                self.high
+            ));
+        }
+        let n = (self.high as i128 - self.low as i128) as u64;
+        let num_bits = self.st.size_in_bits();
+        let range_bits = 64 - n.leading_zeros() as u64;
+        if range_bits >= num_bits {
+            return Err(runtime_error!(
+                "UniformRange range [{}, {}) is too large for {}",
+                self.low,
+                self.high,
+                self.st
+            ));
+        }
+        let random_bits = num_bits - range_bits;
+        let g = context.create_graph()?;
+        // r is uniform in [0, 2^random_bits), so r * n doesn't overflow
+        let r = random_bits_padded_with_zeros(&g, &self.shape, random_bits, num_bits)?
+            .b2a(self.st.clone())?;
+        let product_bits = r
+            .multiply(constant_scalar(&g, n, self.st.clone())?)?
+            .a2b()?;
+        // Shift the product right by random_bits
+        let high_bits = product_bits.get_slice(vec![
+            SliceElement::Ellipsis,
+            SliceElement::SubArray(Some(random_bits as i64), None, None),
+        ])?;
+        let mut zeros_shape = self.shape.clone();
+        zeros_shape.push(random_bits);
+        let shifted = g
+            .concatenate(
+                vec![high_bits, zeros(&g, array_type(zeros_shape, BIT))?],
+                self.shape.len() as u64,
+            )?
+            .b2a(self.st.clone())?;
+        let output = shifted.add(constant_scalar(&g, self.low, self.st.clone())?)?;
+        g.set_output_node(output)?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "UniformRange(low={}, high={}, st={}, shape={:?})",
+            self.low, self.high, self.st, self.shape
+        )
+    }
+}
+
+register_custom_operation!(UniformRange, []);
+
+/// A structure that defines the custom operation DiscreteGaussian that samples independent integers from an approximation of the discrete Gaussian distribution with mean 0 and a given variance.
+///
+/// The Gaussian distribution is approximated by the centered binomial distribution: every sample is computed as `c_1 + ... + c_k - k/2`,
+/// where `c_1, ..., c_k` are `k = 4 * variance` uniformly random bits.
+/// The variance of this distribution is exactly `variance`, and it converges to the Gaussian as `k` grows.
+/// This distribution is commonly used to generate noise for differential privacy in MPC (the binomial mechanism) since it requires no comparisons.
+///
+/// The operation has no arguments; it returns an array of the given shape or a scalar if the shape is empty.
+/// Note that the number of random bits, and thus the cost of the operation in MPC, is proportional to `variance`.
+///
+/// To use this and other custom operations in computation graphs, see [Graph::custom_op].
+///
+/// # Custom operation returns
+///
+/// New DiscreteGaussian node containing an array or scalar of the given integer type
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT32};
+/// # use ciphercore_base::custom_ops::CustomOperation;
+/// # use ciphercore_base::ops::random_sampling::DiscreteGaussian;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let n = g.custom_op(CustomOperation::new(DiscreteGaussian {variance: 16, st: INT32, shape: vec![10]}), vec![]).unwrap();
+/// assert_eq!(n.get_type().unwrap(), array_type(vec![10], INT32));
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct DiscreteGaussian {
+    /// Variance of the sampled integers, must be positive
+    pub variance: u64,
+    /// Scalar type of the sampled integers
+    pub st: ScalarType,
+    /// Shape of the sampled array
+    pub shape: ArrayShape,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for DiscreteGaussian {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if !arguments_types.is_empty() {
+            return Err(runtime_error!("DiscreteGaussian doesn't take arguments"));
+        }
+        validate_integer_scalar_type(&self.st, "DiscreteGaussian")?;
+        let num_coins = match self.variance.checked_mul(4) {
+            Some(k) if k > 0 && 64 - k.leading_zeros() < self.st.size_in_bits() as u32 => k,
+            _ => {
+                return Err(runtime_error!(
+                    "DiscreteGaussian variance {} is not supported for {}",
+                    self.variance,
+                    self.st
+                ));
+            }
+        };
+        let g = context.create_graph()?;
+        let mut coins_shape = self.shape.clone();
+        coins_shape.push(num_coins);
+        let coins = g.random(array_type(coins_shape, BIT))?;
+        let sum =
+            single_bit_to_arithmetic(coins, self.st.clone())?.sum(vec![self.shape.len() as u64])?;
+        let output = sum.subtract(constant_scalar(&g, 2 * self.variance, self.st.clone())?)?;
+        g.set_output_node(output)?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "DiscreteGaussian(variance={}, st={}, shape={:?})",
+            self.variance, self.st, self.shape
+        )
+    }
+}
+
+register_custom_operation!(DiscreteGaussian, []);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::custom_ops::run_instantiation_pass;
+    use crate::data_types::{scalar_type, INT32, INT64, UINT16, UINT64};
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::create_context;
+    use crate::inline::inline_common::DepthOptimizationLevel;
+    use crate::inline::inline_ops::{InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{compile_context, IOStatus};
+
+    fn sample(op: CustomOperation, compile: bool) -> Result<(Type, Value)> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let o = g.custom_op(op, vec![])?;
+        let output_type = o.get_type()?;
+        o.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        let evaluated_c = if compile {
+            compile_context(
+                c,
+                vec![],
+                vec![IOStatus::Party(0)],
+                InlineConfig {
+                    default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                    ..Default::default()
+                },
+                || SimpleEvaluator::new(None),
+            )?
+        } else {
+            run_instantiation_pass(c)?.get_context()
+        };
+        let result = random_evaluate(evaluated_c.get_main_graph()?, vec![])?;
+        Ok((output_type, result))
+    }
+
+    #[test]
+    fn test_bernoulli() {
+        || -> Result<()> {
+            for compile in [false, true] {
+                let (t, result) = sample(
+                    CustomOperation::new(Bernoulli {
+                        numerator: 1,
+                        precision: 2,
+                        shape: vec![4000],
+                    }),
+                    compile,
+                )?;
+                assert_eq!(t, array_type(vec![4000], BIT));
+                let ones: u64 = result.to_flattened_array_u64(t)?.iter().sum();
+                assert!((800..1200).contains(&ones), "{} ones", ones);
+            }
+            let (t, result) = sample(
+                CustomOperation::new(Bernoulli {
+                    numerator: 0,
+                    precision: 64,
+                    shape: vec![],
+                }),
+                false,
+            )?;
+            assert_eq!(t, scalar_type(BIT));
+            assert_eq!(result.to_u64(BIT)?, 0);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_uniform_range() {
+        || -> Result<()> {
+            for compile in [false, true] {
+                let (t, result) = sample(
+                    CustomOperation::new(UniformRange {
+                        low: -3,
+                        high: 7,
+                        st: INT32,
+                        shape: vec![2, 500],
+                    }),
+                    compile,
+                )?;
+                assert_eq!(t, array_type(vec![2, 500], INT32));
+                let mut counts = [0; 10];
+                for x in result.to_flattened_array_i32(t)? {
+                    assert!((-3..7).contains(&x));
+                    counts[(x + 3) as usize] += 1;
+                }
+                for count in counts {
+                    assert!((50..150).contains(&count), "{:?}", counts);
+                }
+            }
+            let (t, result) = sample(
+                CustomOperation::new(UniformRange {
+                    low: 100,
+                    high: 101,
+                    st: UINT16,
+                    shape: vec![],
+                }),
+                false,
+            )?;
+            assert_eq!(result.to_u64(t.get_scalar_type())?, 100);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_discrete_gaussian() {
+        || -> Result<()> {
+            for compile in [false, true] {
+                let (t, result) = sample(
+                    CustomOperation::new(DiscreteGaussian {
+                        variance: 4,
+                        st: INT32,
+                        shape: vec![2000],
+                    }),
+                    compile,
+                )?;
+                assert_eq!(t, array_type(vec![2000], INT32));
+                let samples = result.to_flattened_array_i32(t)?;
+                assert!(samples.iter().all(|x| (-8..=8).contains(x)));
+                let mean = samples.iter().sum::<i32>() as f64 / 2000.0;
+                let variance = samples.iter().map(|x| (x * x) as f64).sum::<f64>() / 2000.0;
+                assert!(mean.abs() < 0.3, "mean {}", mean);
+                assert!((3.0..5.0).contains(&variance), "variance {}", variance);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_sampling() {
+        let check = |op: CustomOperation| -> bool {
+            || -> Result<()> {
+                let c = create_context()?;
+                let g = c.create_graph()?;
+                g.custom_op(op, vec![])?;
+                Ok(())
+            }()
+            .is_err()
+        };
+        let bernoulli = |numerator, precision| {
+            CustomOperation::new(Bernoulli {
+                numerator,
+                precision,
+                shape: vec![5],
+            })
+        };
+        assert!(check(bernoulli(4, 2)));
+        assert!(check(bernoulli(0, 0)));
+        assert!(check(bernoulli(1, 65)));
+        let uniform = |low, high, st| {
+            CustomOperation::new(UniformRange {
+                low,
+                high,
+                st,
+                shape: vec![5],
+            })
+        };
+        assert!(check(uniform(3, 3, INT32)));
+        assert!(check(uniform(0, 5, BIT)));
+        assert!(check(uniform(0, 1 << 31, INT32)));
+        assert!(check(uniform(i64::MIN, i64::MAX, UINT64)));
+        let gaussian = |variance, st| {
+            CustomOperation::new(DiscreteGaussian {
+                variance,
+                st,
+                shape: vec![5],
+            })
+        };
+        assert!(check(gaussian(0, INT32)));
+        assert!(check(gaussian(1, BIT)));
+        assert!(check(gaussian(1 << 62, INT64)));
+
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let i = g.input(scalar_type(BIT))?;
+            assert!(g.custom_op(bernoulli(1, 2), vec![i]).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
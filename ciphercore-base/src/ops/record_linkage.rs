@@ -0,0 +1,493 @@
+//! Private record linkage: similarity scoring of candidate pairs of records from two datasets.
+//!
+//! Unlike [SetIntersection](crate::graphs::Operation::SetIntersection), which matches records with equal keys, record linkage finds records that are similar, e.g. names with typos.
+//! Every record is encoded by its owner as a bitstring of a fixed length, for example, a Bloom filter of tokens (e.g. bigrams) of its fields
+//! or the bits of phonetic encodings of its fields.
+//! Comparing all pairs of records is quadratic, so only candidate pairs are scored.
+//! They are usually produced by blocking, i.e. by pairing records with equal blocking keys (e.g. keyed hashes of postal codes), see [get_blocking_candidate_pairs].
+use crate::custom_ops::{CustomOperation, CustomOperationBody};
+use crate::data_types::{array_type, Type, BIT, INT64};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, Node};
+
+use super::comparisons::GreaterThan;
+use super::newton_inversion::NewtonInversion;
+use super::utils::{constant_scalar, single_bit_to_arithmetic};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+
+/// Similarity of two records encoded as bitstrings `a` and `b` of length k.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub enum SimilarityMetric {
+    /// [Jaccard index](https://en.wikipedia.org/wiki/Jaccard_index) |a AND b| / |a OR b| of the sets of bits equal to 1, e.g. of token sets.
+    /// Two empty sets have similarity 0.
+    Jaccard,
+    /// Fraction of equal bits 1 - d(a, b) / k, where d is [the Hamming distance](https://en.wikipedia.org/wiki/Hamming_distance), e.g. of phonetic encodings.
+    Hamming,
+}
+
+/// Returns all the pairs of indices of records with equal blocking keys sorted lexicographically.
+///
+/// Blocking keys must be known to the party building the graph, e.g. be keyed hashes of coarse attributes (postal codes, birth years etc.) exchanged by the data owners.
+///
+/// # Arguments
+///
+/// * `keys_x` - blocking keys of the records of the first dataset
+/// * `keys_y` - blocking keys of the records of the second dataset
+///
+/// # Returns
+///
+/// Candidate pairs `(i, j)` such that `keys_x[i] == keys_y[j]`
+pub fn get_blocking_candidate_pairs(keys_x: &[u64], keys_y: &[u64]) -> Vec<(u64, u64)> {
+    let mut y_indices = HashMap::<u64, Vec<u64>>::new();
+    for (j, key) in keys_y.iter().enumerate() {
+        y_indices.entry(*key).or_default().push(j as u64);
+    }
+    let mut pairs = vec![];
+    for (i, key) in keys_x.iter().enumerate() {
+        if let Some(indices) = y_indices.get(key) {
+            pairs.extend(indices.iter().map(|j| (i as u64, *j)));
+        }
+    }
+    pairs
+}
+
+/// A structure that defines the custom operation RecordLinkage that computes similarity scores of given candidate pairs of records
+/// and compares them with a threshold.
+///
+/// Records of both datasets are given by binary arrays of shapes `[n, k]` and `[m, k]`, where every row is a bitstring encoding a record.
+/// Candidate pairs `(i, j)` refer to the `i`-th row of the first array and the `j`-th row of the second one; they are public parameters of the operation,
+/// e.g. produced by [get_blocking_candidate_pairs].
+///
+/// The output is a tuple of two arrays of length `candidate_pairs.len()`:
+///
+/// - INT64 similarity scores of matching pairs in the fixed-point representation with denominator 2<sup>fixed_precision_points</sup>
+///   (the scores of the other pairs are replaced by zeros),
+/// - bits indicating whether pairs match, i.e. their scores are positive and at least `threshold`.
+///
+/// Both outputs are private if the records are, so they can be revealed only to the parties that should learn the linkage.
+/// The comparison with the threshold is exact, while Jaccard scores are computed by [NewtonInversion] and might be slightly smaller than the exact values.
+///
+/// The precision must be between 1 and 30 and the length of bitstrings must be less than 2<sup>fixed_precision_points - 1</sup>.
+///
+/// To use this and other custom operations in computation graphs, see [Graph::custom_op].
+///
+/// # Custom operation arguments
+///
+/// - Node containing a binary array of shape `[n, k]` with the records of the first dataset
+/// - Node containing a binary array of shape `[m, k]` with the records of the second dataset
+///
+/// # Custom operation returns
+///
+/// New RecordLinkage node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, BIT};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::record_linkage::{get_blocking_candidate_pairs, RecordLinkage, SimilarityMetric};
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let x = g.input(array_type(vec![4, 64], BIT)).unwrap();
+/// let y = g.input(array_type(vec![3, 64], BIT)).unwrap();
+/// let op = RecordLinkage {
+///     metric: SimilarityMetric::Jaccard,
+///     candidate_pairs: get_blocking_candidate_pairs(&[1, 2, 1, 3], &[1, 3, 2]),
+///     threshold: 3 << 13,
+///     fixed_precision_points: 15,
+/// };
+/// let linkage = g.custom_op(CustomOperation::new(op), vec![x, y]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct RecordLinkage {
+    pub metric: SimilarityMetric,
+    /// Pairs of row indices of records to be compared
+    pub candidate_pairs: Vec<(u64, u64)>,
+    /// Minimal score of matching pairs in the fixed-point representation; at most 2<sup>fixed_precision_points</sup>
+    pub threshold: u64,
+    /// Assume that we're operating in fixed precision arithmetic with denominator 2 ** fixed_precision_points.
+    pub fixed_precision_points: u64,
+}
+
+// Returns the rows of `x` selected by `indices` computed as the product with a public one-hot matrix,
+// which is local in MPC, unlike selection by private-dependent operations
+fn select_rows(x: Node, indices: &[u64]) -> Result<Node> {
+    let g = x.get_graph();
+    let num_rows = x.get_type()?.get_shape()[0];
+    let mut one_hot = vec![0u64; indices.len() * num_rows as usize];
+    for (row, index) in indices.iter().enumerate() {
+        one_hot[row * num_rows as usize + *index as usize] = 1;
+    }
+    let selection = g.constant(
+        array_type(vec![indices.len() as u64, num_rows], INT64),
+        Value::from_flattened_array(&one_hot, INT64)?,
+    )?;
+    selection.matmul(x)
+}
+
+#[typetag::serde]
+impl CustomOperationBody for RecordLinkage {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 2 {
+            return Err(runtime_error!(
+                "Invalid number of arguments for RecordLinkage"
+            ));
+        }
+        let t_x = arguments_types[0].clone();
+        let t_y = arguments_types[1].clone();
+        for t in [&t_x, &t_y] {
+            if !t.is_array() || t.get_scalar_type() != BIT || t.get_shape().len() != 2 {
+                return Err(runtime_error!(
+                    "Records in RecordLinkage must be binary arrays of shape [number of records, number of bits], but {} is given",
+                    t
+                ));
+            }
+        }
+        let (num_x, num_y) = (t_x.get_shape()[0], t_y.get_shape()[0]);
+        let num_bits = t_x.get_shape()[1];
+        if t_y.get_shape()[1] != num_bits {
+            return Err(runtime_error!(
+                "Records of both datasets must have the same number of bits"
+            ));
+        }
+        let precision = self.fixed_precision_points;
+        if precision == 0 || precision > 30 {
+            return Err(runtime_error!(
+                "fixed_precision_points must be between 1 and 30"
+            ));
+        }
+        if num_bits >= 1 << (precision - 1) {
+            return Err(runtime_error!(
+                "Number of bits {} is too large for {} fixed-point bits",
+                num_bits,
+                precision
+            ));
+        }
+        if self.threshold > 1 << precision {
+            return Err(runtime_error!("Threshold must be at most 1"));
+        }
+        if self.candidate_pairs.is_empty() {
+            return Err(runtime_error!("There must be at least one candidate pair"));
+        }
+        if self
+            .candidate_pairs
+            .iter()
+            .any(|(i, j)| *i >= num_x || *j >= num_y)
+        {
+            return Err(runtime_error!("Candidate pair index is out of range"));
+        }
+        let num_pairs = self.candidate_pairs.len() as u64;
+
+        let g = context.create_graph()?;
+        let x = single_bit_to_arithmetic(g.input(t_x)?, INT64)?;
+        let y = single_bit_to_arithmetic(g.input(t_y)?, INT64)?;
+        let indices_x: Vec<u64> = self.candidate_pairs.iter().map(|(i, _)| *i).collect();
+        let indices_y: Vec<u64> = self.candidate_pairs.iter().map(|(_, j)| *j).collect();
+        let pairs_x = select_rows(x, &indices_x)?;
+        let pairs_y = select_rows(y, &indices_y)?;
+        // All the scores are ratios of integers that are derived from the sizes of both sets and their intersection
+        let intersection_sizes = pairs_x.multiply(pairs_y.clone())?.sum(vec![1])?;
+        let sizes_sum = pairs_x.sum(vec![1])?.add(pairs_y.sum(vec![1])?)?;
+        let (numerators, denominators, scores) = match self.metric {
+            SimilarityMetric::Jaccard => {
+                let union_sizes = sizes_sum.subtract(intersection_sizes.clone())?;
+                // 2 ** precision / union_size is the fixed-point representation of 1 / union_size
+                let inverse_union_sizes = g.custom_op(
+                    CustomOperation::new(NewtonInversion {
+                        iterations: (precision as f64).log2().ceil() as u64 + 1,
+                        denominator_cap_2k: precision,
+                    }),
+                    vec![union_sizes.clone()],
+                )?;
+                let scores = intersection_sizes.multiply(inverse_union_sizes)?;
+                (intersection_sizes, union_sizes, scores)
+            }
+            SimilarityMetric::Hamming => {
+                // k - d(a, b) = k - |a| - |b| + 2 * |a AND b|
+                let num_bits_node = constant_scalar(&g, num_bits, INT64)?;
+                let equal_bits = num_bits_node
+                    .subtract(sizes_sum)?
+                    .add(intersection_sizes.add(intersection_sizes.clone())?)?;
+                let mut scores =
+                    equal_bits.multiply(constant_scalar(&g, 1u64 << precision, INT64)?)?;
+                if num_bits > 1 {
+                    scores = scores.truncate(num_bits)?;
+                }
+                let denominators = g.constant(
+                    array_type(vec![num_pairs], INT64),
+                    Value::from_flattened_array(&vec![num_bits; num_pairs as usize], INT64)?,
+                )?;
+                (equal_bits, denominators, scores)
+            }
+        };
+        // A pair matches if numerator * 2^precision > threshold * denominator - 1 and numerator > 0;
+        // both comparisons are done by one GreaterThan operation
+        let left = g.stack(
+            vec![
+                numerators.multiply(constant_scalar(&g, 1u64 << precision, INT64)?)?,
+                numerators,
+            ],
+            vec![2],
+        )?;
+        let right = g.stack(
+            vec![
+                denominators
+                    .multiply(constant_scalar(&g, self.threshold, INT64)?)?
+                    .subtract(constant_scalar(&g, 1, INT64)?)?,
+                g.constant(
+                    array_type(vec![num_pairs], INT64),
+                    Value::zero_of_type(array_type(vec![num_pairs], INT64)),
+                )?,
+            ],
+            vec![2],
+        )?;
+        let comparisons = g.custom_op(
+            CustomOperation::new(GreaterThan {
+                signed_comparison: true,
+            }),
+            vec![left, right],
+        )?;
+        let matches = comparisons
+            .get(vec![0])?
+            .multiply(comparisons.get(vec![1])?)?;
+        g.create_tuple(vec![scores.mixed_multiply(matches.clone())?, matches])?
+            .set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "RecordLinkage(metric={:?}, candidate_pairs={:?}, threshold={}, fixed_precision_points={})",
+            self.metric, self.candidate_pairs, self.threshold, self.fixed_precision_points
+        )
+    }
+}
+
+register_custom_operation!(RecordLinkage, [Array, Array]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::run_instantiation_pass;
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::create_context;
+    use crate::inline::inline_ops::InlineConfig;
+    use crate::mpc::mpc_compiler::{compile_context, IOStatus};
+
+    const PRECISION: u64 = 15;
+    const NUM_BITS: u64 = 12;
+
+    fn create_linkage_context(op: RecordLinkage, num_x: u64, num_y: u64) -> Result<Context> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let x = g.input(array_type(vec![num_x, NUM_BITS], BIT))?;
+        let y = g.input(array_type(vec![num_y, NUM_BITS], BIT))?;
+        g.custom_op(CustomOperation::new(op), vec![x, y])?
+            .set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        Ok(c)
+    }
+
+    // Records given by the positions of their bits equal to 1
+    fn encode(records: &[Vec<usize>]) -> Vec<u8> {
+        let mut bits = vec![0u8; records.len() * NUM_BITS as usize];
+        for (i, record) in records.iter().enumerate() {
+            for position in record {
+                bits[i * NUM_BITS as usize + position] = 1;
+            }
+        }
+        bits
+    }
+
+    fn get_records() -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+        let x = vec![
+            vec![0, 1, 2, 3],
+            vec![4, 5, 6],
+            vec![],
+            vec![0, 2, 4, 6, 8, 10],
+        ];
+        let y = vec![
+            vec![0, 1, 2, 4],
+            vec![4, 5, 6],
+            vec![],
+            vec![1, 3, 5, 7, 9, 11],
+            vec![0, 2, 4, 6, 8],
+        ];
+        (x, y)
+    }
+
+    fn expected_score(metric: SimilarityMetric, a: &[usize], b: &[usize]) -> f64 {
+        let intersection = a.iter().filter(|v| b.contains(v)).count() as f64;
+        match metric {
+            SimilarityMetric::Jaccard => {
+                let union = a.len() as f64 + b.len() as f64 - intersection;
+                if union == 0.0 {
+                    0.0
+                } else {
+                    intersection / union
+                }
+            }
+            SimilarityMetric::Hamming => {
+                let distance = a.len() as f64 + b.len() as f64 - 2.0 * intersection;
+                1.0 - distance / NUM_BITS as f64
+            }
+        }
+    }
+
+    fn test_metric(metric: SimilarityMetric, threshold: f64) -> Result<()> {
+        let (x, y) = get_records();
+        let mut candidate_pairs = vec![];
+        for i in 0..x.len() as u64 {
+            for j in 0..y.len() as u64 {
+                candidate_pairs.push((i, j));
+            }
+        }
+        let fixed_threshold = (threshold * (1 << PRECISION) as f64) as u64;
+        let c = create_linkage_context(
+            RecordLinkage {
+                metric,
+                candidate_pairs: candidate_pairs.clone(),
+                threshold: fixed_threshold,
+                fixed_precision_points: PRECISION,
+            },
+            x.len() as u64,
+            y.len() as u64,
+        )?;
+        let instantiated_c = run_instantiation_pass(c)?.get_context();
+        let result = random_evaluate(
+            instantiated_c.get_main_graph()?,
+            vec![
+                Value::from_flattened_array(&encode(&x), BIT)?,
+                Value::from_flattened_array(&encode(&y), BIT)?,
+            ],
+        )?
+        .to_vector()?;
+        let num_pairs = candidate_pairs.len() as u64;
+        let scores = result[0].to_flattened_array_i64(array_type(vec![num_pairs], INT64))?;
+        let matches = result[1].to_flattened_array_u8(array_type(vec![num_pairs], BIT))?;
+        let mut num_matches = 0;
+        for (pair_index, (i, j)) in candidate_pairs.iter().enumerate() {
+            let expected = expected_score(metric, &x[*i as usize], &y[*j as usize]);
+            let expected_fixed = expected * (1 << PRECISION) as f64;
+            let expected_match = expected > 0.0 && expected_fixed >= fixed_threshold as f64;
+            assert_eq!(
+                matches[pair_index] == 1,
+                expected_match,
+                "pair {:?}",
+                (i, j)
+            );
+            if expected_match {
+                num_matches += 1;
+                let score = scores[pair_index] as f64 / (1 << PRECISION) as f64;
+                assert!((score - expected).abs() < 1e-3, "{} vs {}", score, expected);
+            } else {
+                assert_eq!(scores[pair_index], 0);
+            }
+        }
+        assert!(num_matches > 0 && num_matches < num_pairs);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_linkage() {
+        // The fixed-point threshold is just below the Jaccard index 3 / 5 of the first records
+        test_metric(SimilarityMetric::Jaccard, 0.6).unwrap();
+        test_metric(SimilarityMetric::Jaccard, 0.5).unwrap();
+        test_metric(SimilarityMetric::Hamming, 0.75).unwrap();
+        // Empty records never match, even with the zero threshold
+        test_metric(SimilarityMetric::Jaccard, 0.0).unwrap();
+    }
+
+    #[test]
+    fn test_blocking_candidate_pairs() {
+        assert_eq!(
+            get_blocking_candidate_pairs(&[7, 3, 7, 5], &[3, 7, 1, 7]),
+            vec![(0, 1), (0, 3), (1, 0), (2, 1), (2, 3)]
+        );
+        assert!(get_blocking_candidate_pairs(&[1, 2], &[3]).is_empty());
+    }
+
+    #[test]
+    fn test_record_linkage_compiles() {
+        || -> Result<()> {
+            let c = create_linkage_context(
+                RecordLinkage {
+                    metric: SimilarityMetric::Jaccard,
+                    candidate_pairs: get_blocking_candidate_pairs(&[1, 2, 1], &[2, 1]),
+                    threshold: 1 << (PRECISION - 1),
+                    fixed_precision_points: PRECISION,
+                },
+                3,
+                2,
+            )?;
+            compile_context(
+                c,
+                vec![IOStatus::Party(0), IOStatus::Party(1)],
+                vec![IOStatus::Party(0)],
+                InlineConfig::default(),
+                || SimpleEvaluator::new(None),
+            )?;
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed() {
+        let op = || RecordLinkage {
+            metric: SimilarityMetric::Hamming,
+            candidate_pairs: vec![(0, 1), (2, 0)],
+            threshold: 1 << PRECISION,
+            fixed_precision_points: PRECISION,
+        };
+        assert!(create_linkage_context(op(), 3, 2).is_ok());
+        // Indices out of range
+        assert!(create_linkage_context(op(), 2, 2).is_err());
+        assert!(create_linkage_context(op(), 3, 1).is_err());
+        let mut bad_op = op();
+        bad_op.candidate_pairs = vec![];
+        assert!(create_linkage_context(bad_op, 3, 2).is_err());
+        let mut bad_op = op();
+        bad_op.threshold = (1 << PRECISION) + 1;
+        assert!(create_linkage_context(bad_op, 3, 2).is_err());
+        let mut bad_op = op();
+        bad_op.fixed_precision_points = 31;
+        assert!(create_linkage_context(bad_op, 3, 2).is_err());
+        // Too many bits for the precision
+        let mut bad_op = op();
+        bad_op.fixed_precision_points = 4;
+        bad_op.threshold = 1;
+        assert!(create_linkage_context(bad_op, 3, 2).is_err());
+        let check_types = |t_x: Type, t_y: Type| -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(t_x)?;
+            let y = g.input(t_y)?;
+            g.custom_op(CustomOperation::new(op()), vec![x, y])?;
+            Ok(())
+        };
+        assert!(check_types(
+            array_type(vec![3, NUM_BITS], BIT),
+            array_type(vec![2, NUM_BITS + 1], BIT)
+        )
+        .is_err());
+        assert!(check_types(
+            array_type(vec![3, NUM_BITS], INT64),
+            array_type(vec![2, NUM_BITS], INT64)
+        )
+        .is_err());
+        assert!(check_types(
+            array_type(vec![3, NUM_BITS, 1], BIT),
+            array_type(vec![2, NUM_BITS], BIT)
+        )
+        .is_err());
+    }
+}
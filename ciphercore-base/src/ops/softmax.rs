@@ -0,0 +1,530 @@
+//! Softmax and cross-entropy loss in fixed-point arithmetic.
+//!
+//! Both operations subtract the maximum of every row before exponentiation, so that exponents are computed by [ApproxExponent] on non-positive values
+//! and the sum of every row lies in [1, n], where n is the number of classes.
+//! This sum is inverted by [NewtonInversion] for softmax and its logarithm is approximated piecewise-linearly for cross-entropy.
+//! Thus, the depth of both operations is bounded and depends only on the number of classes and the precision.
+use crate::custom_ops::{CustomOperation, CustomOperationBody};
+use crate::data_types::{array_type, Type, INT64};
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, Node, SliceElement};
+
+use super::min_max::Max;
+use super::newton_inversion::NewtonInversion;
+use super::pwl::approx_exponent::ApproxExponent;
+use super::pwl::approx_pointwise::{create_approximation, PWLConfig};
+use super::utils::{concatenate_first_axis, multiply_fixed_point};
+
+use serde::{Deserialize, Serialize};
+
+// Checks that the given type is an INT64 array whose last dimension (the number of classes) is small enough for the given precision and returns this dimension
+fn get_num_classes(t: &Type, fixed_precision_points: u64, op_name: &str) -> Result<u64> {
+    if !t.is_array() || t.get_scalar_type() != INT64 {
+        return Err(runtime_error!(
+            "Argument of {} must be an array of INT64's, but {} is given",
+            op_name,
+            t
+        ));
+    }
+    if fixed_precision_points == 0 || fixed_precision_points > 20 {
+        return Err(runtime_error!(
+            "fixed_precision_points must be between 1 and 20"
+        ));
+    }
+    let shape = t.get_shape();
+    let num_classes = shape[shape.len() - 1];
+    // The sum of exponents, which is at most num_classes, must be less than 2^(fixed_precision_points - 1) to be inverted
+    if num_classes >= 1 << (fixed_precision_points - 1) {
+        return Err(runtime_error!(
+            "{}: number of classes {} is too large for {} fixed-point bits",
+            op_name,
+            num_classes,
+            fixed_precision_points
+        ));
+    }
+    Ok(num_classes)
+}
+
+// Returns the reduction along the last axis of an array of shape [..., n] as an array of shape [..., 1] that can be broadcast back.
+// For one-dimensional arrays, the scalar reduction is returned as is.
+fn keep_last_axis(reduced: Node, t: &Type) -> Result<Node> {
+    let shape = t.get_shape();
+    if shape.len() == 1 {
+        return Ok(reduced);
+    }
+    let mut new_shape = shape[..shape.len() - 1].to_vec();
+    new_shape.push(1);
+    reduced.reshape(array_type(new_shape, t.get_scalar_type()))
+}
+
+// Subtracts the maximum along the last axis from every element of a signed array.
+fn subtract_max(x: Node) -> Result<Node> {
+    let g = x.get_graph();
+    let t = x.get_type()?;
+    let shape = t.get_shape();
+    let rank = shape.len() as u64;
+    // The last axis is moved to the front, so that every layer of the tree compares its halves by one Max operation
+    let mut values = if rank > 1 {
+        let mut permutation = vec![rank - 1];
+        permutation.extend(0..rank - 1);
+        x.permute_axes(permutation)?
+    } else {
+        x.clone()
+    };
+    let mut length = shape[shape.len() - 1] as i64;
+    while length > 1 {
+        let half = length / 2;
+        let maxima = g.custom_op(
+            CustomOperation::new(Max {
+                signed_comparison: true,
+            }),
+            vec![
+                values.get_slice(vec![SliceElement::SubArray(Some(0), Some(half), None)])?,
+                values.get_slice(vec![SliceElement::SubArray(
+                    Some(half),
+                    Some(2 * half),
+                    None,
+                )])?,
+            ],
+        )?;
+        values = if length % 2 == 1 {
+            concatenate_first_axis(vec![
+                maxima,
+                values.get_slice(vec![SliceElement::SubArray(Some(length - 1), None, None)])?,
+            ])?
+        } else {
+            maxima
+        };
+        length = half + length % 2;
+    }
+    // [1, ...] -> [..., 1]
+    let mut max_shape = shape[..shape.len() - 1].to_vec();
+    max_shape.push(1);
+    x.subtract(values.reshape(array_type(max_shape, t.get_scalar_type()))?)
+}
+
+/// A structure that defines the custom operation ApproxSoftmax that computes an approximation of [softmax](https://en.wikipedia.org/wiki/Softmax_function) along the last axis of an array in fixed-point arithmetic.
+///
+/// Input must be an INT64 array of shape `[..., n]` with fixed-point entries with denominator 2<sup>fixed_precision_points</sup>.
+/// The output has the same type and contains the probabilities in the same representation.
+///
+/// Softmax is computed as exp(x<sub>i</sub> - max(x)) / sum<sub>j</sub> exp(x<sub>j</sub> - max(x)),
+/// where the exponent is approximated by [ApproxExponent] and the inverse of the denominator is computed by [NewtonInversion].
+/// Exponents of values smaller than max(x) - 10 are approximated by a small positive constant.
+///
+/// The precision must be between 1 and 20 and the number of classes n must be less than 2<sup>fixed_precision_points - 1</sup>.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a signed 64-bit array
+///
+/// # Custom operation returns
+///
+/// New ApproxSoftmax node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT64};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::softmax::ApproxSoftmax;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let t = array_type(vec![4, 10], INT64);
+/// let logits = g.input(t).unwrap();
+/// let probabilities = g.custom_op(CustomOperation::new(ApproxSoftmax {fixed_precision_points: 15}), vec![logits]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct ApproxSoftmax {
+    /// Assume that we're operating in fixed precision arithmetic with denominator 2 ** fixed_precision_points.
+    pub fixed_precision_points: u64,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for ApproxSoftmax {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 1 {
+            return Err(runtime_error!(
+                "Invalid number of arguments for ApproxSoftmax"
+            ));
+        }
+        let t = arguments_types[0].clone();
+        let precision = self.fixed_precision_points;
+        get_num_classes(&t, precision, "ApproxSoftmax")?;
+        let rank = t.get_shape().len() as u64;
+
+        let g = context.create_graph()?;
+        let x = g.input(t.clone())?;
+        let exponents = g.custom_op(
+            CustomOperation::new(ApproxExponent { precision }),
+            vec![subtract_max(x)?],
+        )?;
+        let sums = exponents.sum(vec![rank - 1])?;
+        // 2 ** (2 * precision) / sum is the fixed-point representation of 1 / sum
+        let inverse_sums = g.custom_op(
+            CustomOperation::new(NewtonInversion {
+                iterations: (2.0 * precision as f64).log2().ceil() as u64 + 1,
+                denominator_cap_2k: 2 * precision,
+            }),
+            vec![sums],
+        )?;
+        multiply_fixed_point(exponents, keep_last_axis(inverse_sums, &t)?, precision)?
+            .set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "ApproxSoftmax(fixed_precision_points={})",
+            self.fixed_precision_points
+        )
+    }
+}
+
+register_custom_operation!(ApproxSoftmax, [Array]);
+
+/// A structure that defines the custom operation ApproxCrossEntropy that computes an approximation of [the cross-entropy loss](https://en.wikipedia.org/wiki/Cross-entropy#Cross-entropy_loss_function_and_logistic_regression)
+/// between the softmax of given logits and given target probabilities in fixed-point arithmetic.
+///
+/// Both inputs must be INT64 arrays of the same shape `[..., n]` with fixed-point entries with denominator 2<sup>fixed_precision_points</sup>.
+/// Every row of targets along the last axis should be a probability distribution, e.g. one-hot encoded labels.
+/// The output is an INT64 array of shape `[...]` (or a scalar for one-dimensional inputs) containing the loss of every row in the same representation.
+///
+/// The loss of logits x and targets y is computed without explicit probabilities as
+/// sum<sub>i</sub> y<sub>i</sub> * (log(sum<sub>j</sub> exp(x<sub>j</sub> - max(x))) - (x<sub>i</sub> - max(x))),
+/// where the exponent is approximated by [ApproxExponent] and the logarithm is approximated piecewise-linearly on [1, n].
+/// Thus, the loss is finite even for very small probabilities, but its accuracy decreases with the number of classes.
+///
+/// The precision must be between 1 and 20 and the number of classes n must be between 2 and 2<sup>fixed_precision_points - 1</sup> - 1.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a signed 64-bit array of logits
+/// - Node containing a signed 64-bit array of target probabilities
+///
+/// # Custom operation returns
+///
+/// New ApproxCrossEntropy node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT64};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::softmax::ApproxCrossEntropy;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let t = array_type(vec![4, 10], INT64);
+/// let logits = g.input(t.clone()).unwrap();
+/// let labels = g.input(t).unwrap();
+/// let losses = g.custom_op(CustomOperation::new(ApproxCrossEntropy {fixed_precision_points: 15}), vec![logits, labels]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct ApproxCrossEntropy {
+    /// Assume that we're operating in fixed precision arithmetic with denominator 2 ** fixed_precision_points.
+    pub fixed_precision_points: u64,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for ApproxCrossEntropy {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 2 {
+            return Err(runtime_error!(
+                "Invalid number of arguments for ApproxCrossEntropy"
+            ));
+        }
+        let t = arguments_types[0].clone();
+        if arguments_types[1] != t {
+            return Err(runtime_error!(
+                "Logits and targets in ApproxCrossEntropy must have the same type"
+            ));
+        }
+        let precision = self.fixed_precision_points;
+        let num_classes = get_num_classes(&t, precision, "ApproxCrossEntropy")?;
+        if num_classes < 2 {
+            return Err(runtime_error!(
+                "ApproxCrossEntropy requires at least 2 classes"
+            ));
+        }
+        let rank = t.get_shape().len() as u64;
+
+        let g = context.create_graph()?;
+        let logits = g.input(t.clone())?;
+        let targets = g.input(t.clone())?;
+        let shifted_logits = subtract_max(logits)?;
+        let sums = g
+            .custom_op(
+                CustomOperation::new(ApproxExponent { precision }),
+                vec![shifted_logits.clone()],
+            )?
+            .sum(vec![rank - 1])?;
+        // The maximal element contributes exp(0) = 1 to every sum, so sums lie in [1, num_classes].
+        // The logarithm is concave, so the approximation error is largest near 1 and grows with the length of the segment.
+        let log_sums = create_approximation(
+            sums,
+            |x| x.ln(),
+            1.0,
+            num_classes as f32,
+            precision,
+            PWLConfig {
+                log_buckets: 6,
+                flatten_left: true,
+                flatten_right: false,
+            },
+        )?;
+        targets
+            .multiply(keep_last_axis(log_sums, &t)?.subtract(shifted_logits)?)?
+            .sum(vec![rank - 1])?
+            .truncate(1 << precision)?
+            .set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "ApproxCrossEntropy(fixed_precision_points={})",
+            self.fixed_precision_points
+        )
+    }
+}
+
+register_custom_operation!(ApproxCrossEntropy, [Array, Array]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::run_instantiation_pass;
+    use crate::data_types::INT32;
+    use crate::data_values::Value;
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::create_context;
+    use crate::inline::inline_common::DepthOptimizationLevel;
+    use crate::inline::inline_ops::{InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{compile_context, IOStatus};
+
+    const PRECISION: u64 = 15;
+
+    fn to_fixed(x: &[f64]) -> Vec<i64> {
+        x.iter()
+            .map(|v| (v * (1 << PRECISION) as f64).round() as i64)
+            .collect()
+    }
+
+    fn evaluate_op(op: CustomOperation, shape: Vec<u64>, inputs: Vec<&[f64]>) -> Result<Vec<f64>> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let t = array_type(shape.clone(), INT64);
+        let mut input_nodes = vec![];
+        for _ in 0..inputs.len() {
+            input_nodes.push(g.input(t.clone())?);
+        }
+        let o = g.custom_op(op, input_nodes)?;
+        let output_type = o.get_type()?;
+        o.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        let instantiated_c = run_instantiation_pass(c)?.get_context();
+        let mut values = vec![];
+        for input in inputs {
+            values.push(Value::from_flattened_array(&to_fixed(input), INT64)?);
+        }
+        let result = random_evaluate(instantiated_c.get_main_graph()?, values)?;
+        let result = if output_type.is_scalar() {
+            vec![result.to_i64(INT64)?]
+        } else {
+            result.to_flattened_array_i64(output_type)?
+        };
+        Ok(result
+            .iter()
+            .map(|v| *v as f64 / (1 << PRECISION) as f64)
+            .collect())
+    }
+
+    fn softmax(row: &[f64]) -> Vec<f64> {
+        let max = row.iter().cloned().fold(f64::MIN, f64::max);
+        let sum: f64 = row.iter().map(|x| (x - max).exp()).sum();
+        row.iter().map(|x| (x - max).exp() / sum).collect()
+    }
+
+    // Rows of logits: small, large, equal, negative and with a dominant entry
+    fn get_logits() -> Vec<f64> {
+        vec![
+            0.5, -0.25, 1.0, 0.0, 0.75, //
+            20.0, 18.5, 21.0, 19.0, 20.5, //
+            3.0, 3.0, 3.0, 3.0, 3.0, //
+            -7.0, -5.5, -6.0, -8.0, -5.0, //
+            12.0, -3.0, 0.0, 1.0, -12.0,
+        ]
+    }
+
+    #[test]
+    fn test_softmax() {
+        || -> Result<()> {
+            let logits = get_logits();
+            let result = evaluate_op(
+                CustomOperation::new(ApproxSoftmax {
+                    fixed_precision_points: PRECISION,
+                }),
+                vec![5, 5],
+                vec![&logits],
+            )?;
+            for (row, result_row) in logits.chunks(5).zip(result.chunks(5)) {
+                let expected = softmax(row);
+                for (p, q) in expected.iter().zip(result_row.iter()) {
+                    assert!((p - q).abs() < 0.02, "{:?} vs {:?}", expected, result_row);
+                }
+                let total: f64 = result_row.iter().sum();
+                assert!((total - 1.0).abs() < 0.01, "{:?}", result_row);
+            }
+            // One-dimensional input and an odd number of classes in the tree of maxima
+            let logits = vec![1.0, 2.0, 3.0];
+            let result = evaluate_op(
+                CustomOperation::new(ApproxSoftmax {
+                    fixed_precision_points: PRECISION,
+                }),
+                vec![3],
+                vec![&logits],
+            )?;
+            for (p, q) in softmax(&logits).iter().zip(result.iter()) {
+                assert!((p - q).abs() < 0.02, "{:?}", result);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_cross_entropy() {
+        || -> Result<()> {
+            let logits = get_logits();
+            // One-hot labels and a soft distribution in the last row
+            let mut targets = vec![0.0; 25];
+            for (row, class) in [2, 0, 4, 1].iter().enumerate() {
+                targets[row * 5 + class] = 1.0;
+            }
+            targets[20..].copy_from_slice(&[0.5, 0.0, 0.25, 0.25, 0.0]);
+            let result = evaluate_op(
+                CustomOperation::new(ApproxCrossEntropy {
+                    fixed_precision_points: PRECISION,
+                }),
+                vec![5, 5],
+                vec![&logits, &targets],
+            )?;
+            assert_eq!(result.len(), 5);
+            for (i, loss) in result.iter().enumerate() {
+                let probabilities = softmax(&logits[i * 5..(i + 1) * 5]);
+                let expected: f64 = targets[i * 5..(i + 1) * 5]
+                    .iter()
+                    .zip(probabilities.iter())
+                    .map(|(y, p)| -y * p.ln())
+                    .sum();
+                // The error is dominated by the piecewise-linear approximation of the exponent
+                assert!(
+                    (loss - expected).abs() < 0.05,
+                    "row {}: {} vs {}",
+                    i,
+                    loss,
+                    expected
+                );
+            }
+            // One-dimensional input
+            let result = evaluate_op(
+                CustomOperation::new(ApproxCrossEntropy {
+                    fixed_precision_points: PRECISION,
+                }),
+                vec![2],
+                vec![&[0.0, 1.0], &[1.0, 0.0]],
+            )?;
+            let expected = -softmax(&[0.0, 1.0])[0].ln();
+            assert!((result[0] - expected).abs() < 0.05, "{:?}", result);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_softmax_compiles() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![2, 3], INT64);
+            let logits = g.input(t.clone())?;
+            let labels = g.input(t)?;
+            let probabilities = g.custom_op(
+                CustomOperation::new(ApproxSoftmax {
+                    fixed_precision_points: PRECISION,
+                }),
+                vec![logits.clone()],
+            )?;
+            let losses = g.custom_op(
+                CustomOperation::new(ApproxCrossEntropy {
+                    fixed_precision_points: PRECISION,
+                }),
+                vec![logits, labels],
+            )?;
+            g.create_tuple(vec![probabilities, losses])?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            compile_context(
+                c,
+                vec![IOStatus::Shared, IOStatus::Party(0)],
+                vec![IOStatus::Party(1)],
+                InlineConfig {
+                    default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                    ..Default::default()
+                },
+                || SimpleEvaluator::new(None),
+            )?;
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed() {
+        let check = |op: CustomOperation, types: Vec<Type>| -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let mut inputs = vec![];
+            for t in types {
+                inputs.push(g.input(t)?);
+            }
+            g.custom_op(op, inputs)?;
+            Ok(())
+        };
+        let softmax = |fixed_precision_points| {
+            CustomOperation::new(ApproxSoftmax {
+                fixed_precision_points,
+            })
+        };
+        let cross_entropy = |fixed_precision_points| {
+            CustomOperation::new(ApproxCrossEntropy {
+                fixed_precision_points,
+            })
+        };
+        let t = array_type(vec![2, 3], INT64);
+        assert!(check(softmax(PRECISION), vec![t.clone()]).is_ok());
+        assert!(check(softmax(PRECISION), vec![t.clone(), t.clone()]).is_err());
+        assert!(check(softmax(0), vec![t.clone()]).is_err());
+        assert!(check(softmax(21), vec![t.clone()]).is_err());
+        assert!(check(softmax(PRECISION), vec![array_type(vec![2, 3], INT32)]).is_err());
+        assert!(check(softmax(PRECISION), vec![Type::Scalar(INT64)]).is_err());
+        // Too many classes for the precision
+        assert!(check(softmax(3), vec![array_type(vec![4], INT64)]).is_err());
+        assert!(check(cross_entropy(PRECISION), vec![t.clone(), t.clone()]).is_ok());
+        assert!(check(cross_entropy(PRECISION), vec![t.clone()]).is_err());
+        assert!(check(
+            cross_entropy(PRECISION),
+            vec![t, array_type(vec![3, 2], INT64)]
+        )
+        .is_err());
+        let t = array_type(vec![2, 1], INT64);
+        assert!(check(cross_entropy(PRECISION), vec![t.clone(), t]).is_err());
+    }
+}
@@ -521,6 +521,8 @@ impl CustomOperationBody for Sort {
     }
 }
 
+register_custom_operation!(Sort, [Binary]);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,883 @@
+//! Sparse matrices represented by named tuples of arrays and operations multiplying them by dense arrays.
+//!
+//! A sparse matrix of shape `[m, n]` with `k` stored entries is represented in one of two formats:
+//! - coordinate (COO) format: named tuple with columns `rows`, `columns` and `values` of length `k`;
+//!   the matrix is the sum of `values[i]` placed at `(rows[i], columns[i])`, so duplicate coordinates are summed;
+//! - compressed sparse row (CSR) format: named tuple with columns `row_offsets` of length `m + 1`, `columns` and `values` of length `k`;
+//!   entries of row `r` are stored at positions `row_offsets[r]..row_offsets[r + 1]`, and entries after `row_offsets[m]` are ignored.
+//!
+//! Indices are of type [UINT64], and values can be of any type except for [BIT] and 128-bit ones.
+//! Since only `k` entries are stored, secret-sharing a sparse matrix takes memory proportional to `k` instead of `m * n`.
+//!
+//! The number of stored entries is a part of the type, so it's known to all the parties even if the entries are secret-shared.
+//! To hide the density of a matrix, [SparseMatrix] pads the stored entries with zeros according to [SparsePadding].
+//!
+//! The operations [SparseToDense] and [SparseGemm] accept both formats and compute one-hot encodings of indices via [Equal],
+//! so they don't reveal the positions of the entries and their cost is proportional to `k * (m + n)` comparisons.
+use crate::custom_ops::{CustomOperation, CustomOperationBody};
+use crate::data_types::{array_type, named_tuple_type, ScalarType, Type, BIT, UINT64};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, Node, SliceElement};
+use crate::typed_value::TypedValue;
+
+use serde::{Deserialize, Serialize};
+
+use super::comparisons::{Equal, LessThanEqualTo};
+use super::utils::{constant, single_bit_to_arithmetic};
+
+/// Header of the column of row indices of a sparse matrix in the COO format
+pub const ROWS_HEADER: &str = "rows";
+/// Header of the column of column indices of a sparse matrix
+pub const COLUMNS_HEADER: &str = "columns";
+/// Header of the column of values of a sparse matrix
+pub const VALUES_HEADER: &str = "values";
+/// Header of the column of row offsets of a sparse matrix in the CSR format
+pub const ROW_OFFSETS_HEADER: &str = "row_offsets";
+
+/// Returns the type of a sparse matrix in the COO format with a given number of stored entries.
+pub fn coo_type(num_entries: u64, st: ScalarType) -> Type {
+    named_tuple_type(vec![
+        (
+            ROWS_HEADER.to_owned(),
+            array_type(vec![num_entries], UINT64),
+        ),
+        (
+            COLUMNS_HEADER.to_owned(),
+            array_type(vec![num_entries], UINT64),
+        ),
+        (VALUES_HEADER.to_owned(), array_type(vec![num_entries], st)),
+    ])
+}
+
+/// Returns the type of a sparse matrix in the CSR format with a given number of rows and stored entries.
+pub fn csr_type(num_rows: u64, num_entries: u64, st: ScalarType) -> Type {
+    named_tuple_type(vec![
+        (
+            ROW_OFFSETS_HEADER.to_owned(),
+            array_type(vec![num_rows + 1], UINT64),
+        ),
+        (
+            COLUMNS_HEADER.to_owned(),
+            array_type(vec![num_entries], UINT64),
+        ),
+        (VALUES_HEADER.to_owned(), array_type(vec![num_entries], st)),
+    ])
+}
+
+/// Number of entries stored in the representation of a sparse matrix.
+///
+/// Padding entries are zeros at the coordinate `(0, 0)`, which don't change the matrix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SparsePadding {
+    /// Only non-zero entries are stored, which reveals their number
+    None,
+    /// Exactly a given number of entries is stored; the matrix must have at most this number of non-zero entries
+    Capacity(u64),
+    /// The number of non-zero entries is rounded up to a power of two, which reveals only its logarithm
+    PowerOfTwo,
+    /// All the `m * n` entries are stored, which hides the density completely
+    Dense,
+}
+
+/// Sparse matrix with plaintext entries supporting arithmetic and conversions to and from the [COO](coo_type) and [CSR](csr_type) representations.
+///
+/// Entries are integers modulo 2<sup>b</sup>, where `b` is the size of the scalar type; signed values are stored in the two's complement form.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::data_types::INT32;
+/// # use ciphercore_base::ops::sparse::{SparseMatrix, SparsePadding};
+/// let m = SparseMatrix::from_dense(&[0, 5, 0, 0, 0, 7], 2, 3, INT32).unwrap();
+/// assert_eq!(m.get_entries(), &[(0, 1, 5), (1, 2, 7)]);
+/// let coo = m.to_coo_value(SparsePadding::Capacity(4)).unwrap();
+/// assert_eq!(SparseMatrix::from_coo_value(&coo, 2, 3).unwrap(), m);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SparseMatrix {
+    num_rows: u64,
+    num_columns: u64,
+    // Non-zero entries (row, column, value) sorted by coordinates
+    entries: Vec<(u64, u64, u64)>,
+    st: ScalarType,
+}
+
+fn get_mask(st: &ScalarType) -> Result<u64> {
+    let bits = st.size_in_bits();
+    if bits > 64 || *st == BIT {
+        return Err(runtime_error!(
+            "Sparse matrices don't support BIT and 128-bit types"
+        ));
+    }
+    Ok(if bits == 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    })
+}
+
+impl SparseMatrix {
+    /// Creates a sparse matrix from entries `(row, column, value)`; values at the same coordinates are summed.
+    pub fn from_entries(
+        num_rows: u64,
+        num_columns: u64,
+        entries: Vec<(u64, u64, u64)>,
+        st: ScalarType,
+    ) -> Result<Self> {
+        let mask = get_mask(&st)?;
+        let mut entries = entries;
+        entries.sort_by_key(|(row, column, _)| (*row, *column));
+        let mut merged: Vec<(u64, u64, u64)> = vec![];
+        for (row, column, value) in entries {
+            if row >= num_rows || column >= num_columns {
+                return Err(runtime_error!(
+                    "Entry ({}, {}) is out of the matrix of shape [{}, {}]",
+                    row,
+                    column,
+                    num_rows,
+                    num_columns
+                ));
+            }
+            match merged.last_mut() {
+                Some(last) if last.0 == row && last.1 == column => {
+                    last.2 = last.2.wrapping_add(value) & mask;
+                }
+                _ => merged.push((row, column, value & mask)),
+            }
+        }
+        merged.retain(|(_, _, value)| *value != 0);
+        Ok(SparseMatrix {
+            num_rows,
+            num_columns,
+            entries: merged,
+            st,
+        })
+    }
+
+    /// Creates a sparse matrix from its dense representation in the row-major order.
+    pub fn from_dense(
+        values: &[u64],
+        num_rows: u64,
+        num_columns: u64,
+        st: ScalarType,
+    ) -> Result<Self> {
+        if values.len() as u64 != num_rows * num_columns {
+            return Err(runtime_error!(
+                "Dense matrix of shape [{}, {}] must have {} elements, but {} are given",
+                num_rows,
+                num_columns,
+                num_rows * num_columns,
+                values.len()
+            ));
+        }
+        let entries = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (i as u64 / num_columns, i as u64 % num_columns, *value))
+            .collect();
+        Self::from_entries(num_rows, num_columns, entries, st)
+    }
+
+    pub fn get_shape(&self) -> (u64, u64) {
+        (self.num_rows, self.num_columns)
+    }
+
+    pub fn get_scalar_type(&self) -> ScalarType {
+        self.st.clone()
+    }
+
+    /// Returns the non-zero entries `(row, column, value)` sorted by coordinates.
+    pub fn get_entries(&self) -> &[(u64, u64, u64)] {
+        &self.entries
+    }
+
+    /// Returns the dense representation of the matrix in the row-major order.
+    pub fn to_dense(&self) -> Vec<u64> {
+        let mut dense = vec![0; (self.num_rows * self.num_columns) as usize];
+        for (row, column, value) in &self.entries {
+            dense[(row * self.num_columns + column) as usize] = *value;
+        }
+        dense
+    }
+
+    /// Adds two sparse matrices of the same shape and scalar type.
+    pub fn add(&self, other: &SparseMatrix) -> Result<SparseMatrix> {
+        if self.get_shape() != other.get_shape() || self.st != other.st {
+            return Err(runtime_error!(
+                "Only sparse matrices of the same shape and scalar type can be added"
+            ));
+        }
+        let mut entries = self.entries.clone();
+        entries.extend(other.entries.iter().cloned());
+        Self::from_entries(self.num_rows, self.num_columns, entries, self.st.clone())
+    }
+
+    /// Multiplies all the entries by a scalar.
+    pub fn multiply_by_scalar(&self, scalar: u64) -> Result<SparseMatrix> {
+        let entries = self
+            .entries
+            .iter()
+            .map(|(row, column, value)| (*row, *column, value.wrapping_mul(scalar)))
+            .collect();
+        Self::from_entries(self.num_rows, self.num_columns, entries, self.st.clone())
+    }
+
+    /// Multiplies the matrix by a dense matrix of shape `[n, p]` given in the row-major order.
+    ///
+    /// # Returns
+    ///
+    /// Dense product of shape `[m, p]` in the row-major order
+    pub fn multiply_dense(&self, dense: &[u64], num_columns: u64) -> Result<Vec<u64>> {
+        if dense.len() as u64 != self.num_columns * num_columns {
+            return Err(runtime_error!(
+                "Dense matrix must have shape [{}, {}]",
+                self.num_columns,
+                num_columns
+            ));
+        }
+        let mask = get_mask(&self.st)?;
+        let p = num_columns as usize;
+        let mut result = vec![0u64; self.num_rows as usize * p];
+        for (row, column, value) in &self.entries {
+            for j in 0..p {
+                let product = value.wrapping_mul(dense[*column as usize * p + j]);
+                let position = *row as usize * p + j;
+                result[position] = result[position].wrapping_add(product) & mask;
+            }
+        }
+        Ok(result)
+    }
+
+    fn get_padded_size(&self, padding: SparsePadding) -> Result<u64> {
+        let nnz = self.entries.len() as u64;
+        match padding {
+            SparsePadding::None => Ok(nnz),
+            SparsePadding::Capacity(capacity) => {
+                if nnz > capacity {
+                    return Err(runtime_error!(
+                        "Sparse matrix has {} non-zero entries, which exceeds the capacity {}",
+                        nnz,
+                        capacity
+                    ));
+                }
+                Ok(capacity)
+            }
+            SparsePadding::PowerOfTwo => Ok(nnz.max(1).next_power_of_two()),
+            SparsePadding::Dense => Ok(self.num_rows * self.num_columns),
+        }
+    }
+
+    fn get_padded_columns(&self, padding: SparsePadding) -> Result<(Vec<u64>, Vec<u64>)> {
+        let size = self.get_padded_size(padding)? as usize;
+        let mut columns: Vec<u64> = self.entries.iter().map(|e| e.1).collect();
+        let mut values: Vec<u64> = self.entries.iter().map(|e| e.2).collect();
+        columns.resize(size, 0);
+        values.resize(size, 0);
+        Ok((columns, values))
+    }
+
+    /// Returns the representation of the matrix in the COO format padded according to a given option.
+    pub fn to_coo_value(&self, padding: SparsePadding) -> Result<TypedValue> {
+        let (columns, values) = self.get_padded_columns(padding)?;
+        let mut rows: Vec<u64> = self.entries.iter().map(|e| e.0).collect();
+        rows.resize(columns.len(), 0);
+        TypedValue::new(
+            coo_type(columns.len() as u64, self.st.clone()),
+            Value::from_vector(vec![
+                Value::from_flattened_array(&rows, UINT64)?,
+                Value::from_flattened_array(&columns, UINT64)?,
+                Value::from_flattened_array(&values, self.st.clone())?,
+            ]),
+        )
+    }
+
+    /// Returns the representation of the matrix in the CSR format padded according to a given option.
+    pub fn to_csr_value(&self, padding: SparsePadding) -> Result<TypedValue> {
+        let (columns, values) = self.get_padded_columns(padding)?;
+        let mut row_offsets = vec![0u64; self.num_rows as usize + 1];
+        for (row, _, _) in &self.entries {
+            row_offsets[*row as usize + 1] += 1;
+        }
+        for i in 0..self.num_rows as usize {
+            row_offsets[i + 1] += row_offsets[i];
+        }
+        TypedValue::new(
+            csr_type(self.num_rows, columns.len() as u64, self.st.clone()),
+            Value::from_vector(vec![
+                Value::from_flattened_array(&row_offsets, UINT64)?,
+                Value::from_flattened_array(&columns, UINT64)?,
+                Value::from_flattened_array(&values, self.st.clone())?,
+            ]),
+        )
+    }
+
+    /// Creates a sparse matrix of a given shape from its representation in the COO format.
+    pub fn from_coo_value(
+        coo: &TypedValue,
+        num_rows: u64,
+        num_columns: u64,
+    ) -> Result<SparseMatrix> {
+        let (num_entries, st) = validate_sparse_type(&coo.t, false, "SparseMatrix")?;
+        let columns = coo.value.to_vector()?;
+        let index_t = array_type(vec![num_entries], UINT64);
+        let rows = columns[0].to_flattened_array_u64(index_t.clone())?;
+        let column_indices = columns[1].to_flattened_array_u64(index_t)?;
+        let values =
+            columns[2].to_flattened_array_u64(array_type(vec![num_entries], st.clone()))?;
+        let entries = (0..num_entries as usize)
+            .map(|i| (rows[i], column_indices[i], values[i]))
+            .collect();
+        Self::from_entries(num_rows, num_columns, entries, st)
+    }
+
+    /// Creates a sparse matrix with a given number of columns from its representation in the CSR format.
+    pub fn from_csr_value(csr: &TypedValue, num_columns: u64) -> Result<SparseMatrix> {
+        let (num_entries, st) = validate_sparse_type(&csr.t, true, "SparseMatrix")?;
+        let columns = csr.value.to_vector()?;
+        let num_rows = get_csr_num_rows(&csr.t)?;
+        let row_offsets =
+            columns[0].to_flattened_array_u64(array_type(vec![num_rows + 1], UINT64))?;
+        let column_indices =
+            columns[1].to_flattened_array_u64(array_type(vec![num_entries], UINT64))?;
+        let values =
+            columns[2].to_flattened_array_u64(array_type(vec![num_entries], st.clone()))?;
+        let mut entries = vec![];
+        for row in 0..num_rows as usize {
+            let end = row_offsets[row + 1].min(num_entries);
+            for i in row_offsets[row]..end {
+                entries.push((row as u64, column_indices[i as usize], values[i as usize]));
+            }
+        }
+        Self::from_entries(num_rows, num_columns, entries, st)
+    }
+}
+
+// Checks that a type represents a sparse matrix in a given format and returns the number of stored entries and the scalar type of values
+fn validate_sparse_type(t: &Type, csr: bool, op_name: &str) -> Result<(u64, ScalarType)> {
+    let first_header = if csr { ROW_OFFSETS_HEADER } else { ROWS_HEADER };
+    if let Type::NamedTuple(header_types) = t {
+        let headers: Vec<&str> = header_types.iter().map(|(h, _)| h.as_str()).collect();
+        if headers == [first_header, COLUMNS_HEADER, VALUES_HEADER] {
+            let values_t = &header_types[2].1;
+            if values_t.is_array() && values_t.get_shape().len() == 1 {
+                let num_entries = values_t.get_shape()[0];
+                let st = values_t.get_scalar_type();
+                get_mask(&st)?;
+                let expected_t = if csr {
+                    csr_type(get_csr_num_rows(t)?, num_entries, st.clone())
+                } else {
+                    coo_type(num_entries, st.clone())
+                };
+                if *t == expected_t {
+                    return Ok((num_entries, st));
+                }
+            }
+        }
+    }
+    Err(runtime_error!(
+        "{} expects a sparse matrix in the {} format, but {} is given",
+        op_name,
+        if csr { "CSR" } else { "COO" },
+        t
+    ))
+}
+
+fn get_csr_num_rows(t: &Type) -> Result<u64> {
+    if let Type::NamedTuple(header_types) = t {
+        if let Some((_, offsets_t)) = header_types.first() {
+            if offsets_t.is_array() && offsets_t.get_shape().len() == 1 {
+                return Ok(offsets_t.get_shape()[0] - 1);
+            }
+        }
+    }
+    Err(runtime_error!("Invalid CSR type: {}", t))
+}
+
+fn is_csr_type(t: &Type) -> bool {
+    matches!(t, Type::NamedTuple(header_types) if header_types.first().map(|(h, _)| h.as_str()) == Some(ROW_OFFSETS_HEADER))
+}
+
+fn range_constant(g: &Graph, size: u64) -> Result<Node> {
+    let range: Vec<u64> = (0..size).collect();
+    constant(
+        g,
+        TypedValue::new(
+            array_type(vec![size], UINT64),
+            Value::from_flattened_array(&range, UINT64)?,
+        )?,
+    )
+}
+
+// Returns bits of shape [k, size], where the bit (i, j) indicates whether indices[i] == j
+fn one_hot(indices: Node, size: u64) -> Result<Node> {
+    let g = indices.get_graph();
+    let k = indices.get_type()?.get_shape()[0];
+    let column = indices.reshape(array_type(vec![k, 1], UINT64))?;
+    g.custom_op(
+        CustomOperation::new(Equal {}),
+        vec![column, range_constant(&g, size)?],
+    )
+}
+
+// Returns the row indices and the values of the entries of a sparse matrix in the CSR format.
+// The row of the entry i is the number of rows r < m - 1 with row_offsets[r + 1] <= i;
+// entries with row_offsets[m] <= i are ignored, so their values are replaced by zeros.
+fn csr_rows(
+    row_offsets: Node,
+    values: Node,
+    num_rows: u64,
+    num_entries: u64,
+) -> Result<(Node, Node)> {
+    let g = row_offsets.get_graph();
+    let ends = row_offsets
+        .get_slice(vec![SliceElement::SubArray(Some(1), None, None)])?
+        .reshape(array_type(vec![1, num_rows], UINT64))?;
+    let positions =
+        range_constant(&g, num_entries)?.reshape(array_type(vec![num_entries, 1], UINT64))?;
+    let bits = g.custom_op(
+        CustomOperation::new(LessThanEqualTo {
+            signed_comparison: false,
+        }),
+        vec![ends, positions],
+    )?;
+    let ignored = bits.get_slice(vec![SliceElement::Ellipsis, SliceElement::SingleIndex(-1)])?;
+    let rows = single_bit_to_arithmetic(bits, UINT64)?
+        .sum(vec![1])?
+        .subtract(single_bit_to_arithmetic(ignored.clone(), UINT64)?)?;
+    let values = values.subtract(values.mixed_multiply(ignored)?)?;
+    Ok((rows, values))
+}
+
+// Returns the row indices, the column indices and the values of a sparse matrix in any format
+fn get_coo_columns(matrix: Node, op_name: &str) -> Result<(Node, Node, Node)> {
+    let t = matrix.get_type()?;
+    let csr = is_csr_type(&t);
+    let (num_entries, _) = validate_sparse_type(&t, csr, op_name)?;
+    let columns = matrix.named_tuple_get(COLUMNS_HEADER.to_owned())?;
+    let values = matrix.named_tuple_get(VALUES_HEADER.to_owned())?;
+    let (rows, values) = if csr {
+        csr_rows(
+            matrix.named_tuple_get(ROW_OFFSETS_HEADER.to_owned())?,
+            values,
+            get_csr_num_rows(&t)?,
+            num_entries,
+        )?
+    } else {
+        (matrix.named_tuple_get(ROWS_HEADER.to_owned())?, values)
+    };
+    Ok((rows, columns, values))
+}
+
+// Returns the product of the transposed one-hot encoding of rows of shape [k, m] and a given array of shape [k, p]
+fn aggregate_rows(rows: Node, num_rows: u64, entries: Node) -> Result<Node> {
+    let st = entries.get_type()?.get_scalar_type();
+    let row_bits = single_bit_to_arithmetic(one_hot(rows, num_rows)?, st)?;
+    row_bits.gemm(entries, true, false)
+}
+
+/// A structure that defines the custom operation CsrToCoo that converts a sparse matrix from the [CSR](csr_type) format to the [COO](coo_type) format.
+///
+/// The row of every entry is computed obliviously from the row offsets; entries stored after the last row offset become zeros in the last row.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a sparse matrix in the CSR format
+///
+/// # Custom operation returns
+///
+/// New CsrToCoo node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::INT32;
+/// # use ciphercore_base::custom_ops::CustomOperation;
+/// # use ciphercore_base::ops::sparse::{csr_type, CsrToCoo};
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let x = g.input(csr_type(10, 16, INT32)).unwrap();
+/// let coo = g.custom_op(CustomOperation::new(CsrToCoo {}), vec![x]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct CsrToCoo {}
+
+#[typetag::serde]
+impl CustomOperationBody for CsrToCoo {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 1 {
+            return Err(runtime_error!("Invalid number of arguments for CsrToCoo"));
+        }
+        validate_sparse_type(&arguments_types[0], true, "CsrToCoo")?;
+        let g = context.create_graph()?;
+        let matrix = g.input(arguments_types[0].clone())?;
+        let (rows, columns, values) = get_coo_columns(matrix, "CsrToCoo")?;
+        g.create_named_tuple(vec![
+            (ROWS_HEADER.to_owned(), rows),
+            (COLUMNS_HEADER.to_owned(), columns),
+            (VALUES_HEADER.to_owned(), values),
+        ])?
+        .set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        "CsrToCoo".to_owned()
+    }
+}
+
+register_custom_operation!(CsrToCoo, [NamedTuple]);
+
+/// A structure that defines the custom operation SparseToDense that converts a sparse matrix in the [COO](coo_type) or [CSR](csr_type) format to a dense array of shape `[num_rows, num_columns]`.
+///
+/// Entries outside of the matrix are ignored.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a sparse matrix
+///
+/// # Custom operation returns
+///
+/// New SparseToDense node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::INT32;
+/// # use ciphercore_base::custom_ops::CustomOperation;
+/// # use ciphercore_base::ops::sparse::{coo_type, SparseToDense};
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let x = g.input(coo_type(16, INT32)).unwrap();
+/// let op = SparseToDense {num_rows: 10, num_columns: 20};
+/// let dense = g.custom_op(CustomOperation::new(op), vec![x]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct SparseToDense {
+    /// Number of rows of the matrix
+    pub num_rows: u64,
+    /// Number of columns of the matrix
+    pub num_columns: u64,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for SparseToDense {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 1 {
+            return Err(runtime_error!(
+                "Invalid number of arguments for SparseToDense"
+            ));
+        }
+        if self.num_rows == 0 || self.num_columns == 0 {
+            return Err(runtime_error!("SparseToDense requires a non-empty shape"));
+        }
+        let g = context.create_graph()?;
+        let matrix = g.input(arguments_types[0].clone())?;
+        let (rows, columns, values) = get_coo_columns(matrix, "SparseToDense")?;
+        let k = values.get_type()?.get_shape()[0];
+        // Row i contains values[i] at the position columns[i]
+        let scattered = values
+            .reshape(array_type(vec![k, 1], values.get_type()?.get_scalar_type()))?
+            .mixed_multiply(one_hot(columns, self.num_columns)?)?;
+        aggregate_rows(rows, self.num_rows, scattered)?.set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "SparseToDense(num_rows={}, num_columns={})",
+            self.num_rows, self.num_columns
+        )
+    }
+}
+
+register_custom_operation!(SparseToDense, [NamedTuple]);
+
+/// A structure that defines the custom operation SparseGemm that multiplies a sparse matrix of shape `[num_rows, n]` by a dense array of shape `[n, p]`.
+///
+/// The sparse matrix can be given in the [COO](coo_type) or [CSR](csr_type) format.
+/// Every stored entry selects a row of the dense array by a one-hot vector, so the cost is proportional to `k * n * p` multiplications, where `k` is the number of stored entries,
+/// instead of `num_rows * n * p` for the dense product.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a sparse matrix
+/// - Node containing a 2-dimensional array of the same scalar type as the values of the sparse matrix
+///
+/// # Custom operation returns
+///
+/// New SparseGemm node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT32};
+/// # use ciphercore_base::custom_ops::CustomOperation;
+/// # use ciphercore_base::ops::sparse::{coo_type, SparseGemm};
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let x = g.input(coo_type(16, INT32)).unwrap();
+/// let y = g.input(array_type(vec![20, 3], INT32)).unwrap();
+/// let product = g.custom_op(CustomOperation::new(SparseGemm {num_rows: 10}), vec![x, y]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct SparseGemm {
+    /// Number of rows of the sparse matrix
+    pub num_rows: u64,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for SparseGemm {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 2 {
+            return Err(runtime_error!("Invalid number of arguments for SparseGemm"));
+        }
+        if self.num_rows == 0 {
+            return Err(runtime_error!("SparseGemm requires a non-empty shape"));
+        }
+        let dense_t = arguments_types[1].clone();
+        if !dense_t.is_array() || dense_t.get_shape().len() != 2 {
+            return Err(runtime_error!(
+                "SparseGemm expects a 2-dimensional dense array, but {} is given",
+                dense_t
+            ));
+        }
+        let g = context.create_graph()?;
+        let matrix = g.input(arguments_types[0].clone())?;
+        let dense = g.input(dense_t.clone())?;
+        let (rows, columns, values) = get_coo_columns(matrix, "SparseGemm")?;
+        let values_t = values.get_type()?;
+        if values_t.get_scalar_type() != dense_t.get_scalar_type() {
+            return Err(runtime_error!(
+                "SparseGemm expects a dense array of the same scalar type as the sparse matrix"
+            ));
+        }
+        let k = values_t.get_shape()[0];
+        // Row i of the selected array is values[i] * dense[columns[i]]
+        let selected = values
+            .reshape(array_type(vec![k, 1], values_t.get_scalar_type()))?
+            .mixed_multiply(one_hot(columns, dense_t.get_shape()[0])?)?
+            .matmul(dense)?;
+        aggregate_rows(rows, self.num_rows, selected)?.set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!("SparseGemm(num_rows={})", self.num_rows)
+    }
+}
+
+register_custom_operation!(SparseGemm, [NamedTuple, Array]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::run_instantiation_pass;
+    use crate::data_types::{INT128, INT32, INT64, UINT8};
+    use crate::evaluators::random_evaluate;
+    use crate::graphs::create_context;
+    use crate::inline::inline_ops::{inline_operations, InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{prepare_for_mpc_evaluation, IOStatus};
+
+    fn evaluate_op(op: CustomOperation, inputs: Vec<TypedValue>) -> Result<Value> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let nodes = inputs
+            .iter()
+            .map(|input| g.input(input.t.clone()))
+            .collect::<Result<_>>()?;
+        g.custom_op(op, nodes)?.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        let mapped_c = run_instantiation_pass(c)?;
+        let values = inputs.into_iter().map(|input| input.value).collect();
+        random_evaluate(mapped_c.mappings.get_graph(g), values)
+    }
+
+    #[test]
+    fn test_sparse_matrix() {
+        || -> Result<()> {
+            let dense = [0, 3, 0, 0, 0, 0, u64::MAX, 2];
+            let m = SparseMatrix::from_dense(&dense, 2, 4, INT64)?;
+            assert_eq!(m.get_entries(), &[(0, 1, 3), (1, 2, u64::MAX), (1, 3, 2)]);
+            assert_eq!(m.to_dense(), dense.to_vec());
+            let doubled = m.add(&m)?;
+            assert_eq!(doubled, m.multiply_by_scalar(2)?);
+            assert_eq!(
+                doubled.get_entries(),
+                &[(0, 1, 6), (1, 2, u64::MAX - 1), (1, 3, 4)]
+            );
+            // Entries cancelling each other are removed
+            let negated = m.multiply_by_scalar(u64::MAX)?;
+            assert!(m.add(&negated)?.get_entries().is_empty());
+            assert_eq!(
+                m.multiply_dense(&[1, 2, 3, 4, 5, 6, 7, 8], 2)?,
+                vec![9, 12, 9, 10]
+            );
+
+            let small = SparseMatrix::from_entries(2, 2, vec![(0, 0, 200), (0, 0, 100)], UINT8)?;
+            assert_eq!(small.get_entries(), &[(0, 0, 44)]);
+
+            for padding in [
+                SparsePadding::None,
+                SparsePadding::Capacity(5),
+                SparsePadding::PowerOfTwo,
+                SparsePadding::Dense,
+            ] {
+                let coo = m.to_coo_value(padding)?;
+                let csr = m.to_csr_value(padding)?;
+                assert_eq!(SparseMatrix::from_coo_value(&coo, 2, 4)?, m);
+                assert_eq!(SparseMatrix::from_csr_value(&csr, 4)?, m);
+                let expected_entries = match padding {
+                    SparsePadding::None => 3,
+                    SparsePadding::Capacity(c) => c,
+                    SparsePadding::PowerOfTwo => 4,
+                    SparsePadding::Dense => 8,
+                };
+                assert_eq!(coo.t, coo_type(expected_entries, INT64));
+                assert_eq!(csr.t, csr_type(2, expected_entries, INT64));
+            }
+
+            assert!(m.to_coo_value(SparsePadding::Capacity(2)).is_err());
+            assert!(SparseMatrix::from_entries(2, 2, vec![(2, 0, 1)], INT32).is_err());
+            assert!(SparseMatrix::from_dense(&[1, 2, 3], 2, 2, INT32).is_err());
+            assert!(SparseMatrix::from_dense(&[1], 1, 1, INT128).is_err());
+            assert!(m.add(&small).is_err());
+            assert!(m.multiply_dense(&[1, 2], 1).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_sparse_ops() {
+        || -> Result<()> {
+            let dense = [0, 3, 0, 0, 0, 0, (-5i64) as u64, 2, 0, 0, 0, 0];
+            let m = SparseMatrix::from_dense(&dense, 3, 4, INT64)?;
+            let b: Vec<u64> = (1..=8).collect();
+            let b_value = TypedValue::new(
+                array_type(vec![4, 2], INT64),
+                Value::from_flattened_array(&b, INT64)?,
+            )?;
+            let expected_product = m.multiply_dense(&b, 2)?;
+            for padding in [SparsePadding::None, SparsePadding::Capacity(6)] {
+                for sparse_value in [m.to_coo_value(padding)?, m.to_csr_value(padding)?] {
+                    let result = evaluate_op(
+                        CustomOperation::new(SparseToDense {
+                            num_rows: 3,
+                            num_columns: 4,
+                        }),
+                        vec![sparse_value.clone()],
+                    )?;
+                    assert_eq!(
+                        result.to_flattened_array_u64(array_type(vec![3, 4], INT64))?,
+                        dense.to_vec()
+                    );
+                    let result = evaluate_op(
+                        CustomOperation::new(SparseGemm { num_rows: 3 }),
+                        vec![sparse_value, b_value.clone()],
+                    )?;
+                    assert_eq!(
+                        result.to_flattened_array_u64(array_type(vec![3, 2], INT64))?,
+                        expected_product
+                    );
+                }
+                let coo = evaluate_op(
+                    CustomOperation::new(CsrToCoo {}),
+                    vec![m.to_csr_value(padding)?],
+                )?;
+                let coo = TypedValue::new(coo_type(m.get_padded_size(padding)?, INT64), coo)?;
+                assert_eq!(SparseMatrix::from_coo_value(&coo, 3, 4)?, m);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_sparse_errors() {
+        || -> Result<()> {
+            let coo = SparseMatrix::from_dense(&[1, 0, 0, 1], 2, 2, INT32)?
+                .to_coo_value(SparsePadding::None)?;
+            let b = TypedValue::new(
+                array_type(vec![2, 2], INT64),
+                Value::from_flattened_array(&[1, 2, 3, 4], INT64)?,
+            )?;
+            assert!(evaluate_op(
+                CustomOperation::new(SparseGemm { num_rows: 2 }),
+                vec![coo.clone(), b]
+            )
+            .is_err());
+            assert!(evaluate_op(CustomOperation::new(CsrToCoo {}), vec![coo.clone()]).is_err());
+            assert!(evaluate_op(
+                CustomOperation::new(SparseToDense {
+                    num_rows: 0,
+                    num_columns: 2
+                }),
+                vec![coo]
+            )
+            .is_err());
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(array_type(vec![4], INT32))?;
+            let op = SparseToDense {
+                num_rows: 2,
+                num_columns: 2,
+            };
+            assert!(g.custom_op(CustomOperation::new(op), vec![x]).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_sparse_gemm_mpc() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(coo_type(4, INT32))?;
+            let y = g.input(array_type(vec![3, 2], INT32))?;
+            g.custom_op(CustomOperation::new(SparseGemm { num_rows: 2 }), vec![x, y])?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let c = run_instantiation_pass(c)?.get_context();
+            let c = inline_operations(
+                c,
+                InlineConfig {
+                    default_mode: InlineMode::Simple,
+                    ..Default::default()
+                },
+            )?;
+            let compiled = prepare_for_mpc_evaluation(
+                c,
+                vec![vec![IOStatus::Party(0), IOStatus::Party(1)]],
+                vec![vec![IOStatus::Party(0)]],
+                InlineConfig {
+                    default_mode: InlineMode::Simple,
+                    ..Default::default()
+                },
+            )?;
+            let m = SparseMatrix::from_entries(2, 3, vec![(0, 2, 2), (1, 0, 3)], INT32)?;
+            let result = random_evaluate(
+                compiled.get_main_graph()?,
+                vec![
+                    m.to_coo_value(SparsePadding::Capacity(4))?.value,
+                    Value::from_flattened_array(&[1, 2, 3, 4, 5, 6], INT32)?,
+                ],
+            )?;
+            assert_eq!(
+                result.to_flattened_array_u64(array_type(vec![2, 2], INT32))?,
+                vec![10, 12, 3, 6]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -0,0 +1,860 @@
+//! Descriptive statistics of columns: mean, variance, covariance matrix, histograms and quantiles.
+//!
+//! All the statistics are computed obliviously, so their results are secret-shared if the input data is.
+//! They are revealed only to the output parties chosen at compilation (see [compile_context](crate::mpc::mpc_compiler::compile_context)).
+use crate::custom_ops::{CustomOperation, CustomOperationBody};
+use crate::data_types::{array_type, Type, BIT, UINT64};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, Node, SliceElement};
+use crate::typed_value::TypedValue;
+
+use serde::{Deserialize, Serialize};
+
+use super::comparisons::GreaterThanEqualTo;
+use super::sorting::Sort;
+use super::utils::{constant, single_bit_to_arithmetic};
+
+// Checks that the given type is an array of a signed type and returns the number of rows
+fn validate_columns(t: &Type, op_name: &str) -> Result<u64> {
+    if !t.is_array() || !t.get_scalar_type().get_signed() {
+        return Err(runtime_error!(
+            "Argument of {} must be an array of a signed type, but {} is given",
+            op_name,
+            t
+        ));
+    }
+    Ok(t.get_shape()[0])
+}
+
+// Returns (n << fixed_precision_points) checking for overflow
+fn get_scale(n: u64, fixed_precision_points: u64) -> Result<u64> {
+    if fixed_precision_points >= 63 || n.leading_zeros() as u64 <= fixed_precision_points {
+        return Err(runtime_error!(
+            "Number of rows and fixed_precision_points are too large"
+        ));
+    }
+    Ok(n << fixed_precision_points)
+}
+
+// Subtracts the mean over the first axis from every row
+fn center(columns: Node, n: u64) -> Result<Node> {
+    let mean = columns.sum(vec![0])?.truncate(n)?;
+    columns.subtract(mean)
+}
+
+/// A structure that defines the custom operation Mean that computes the mean of an array along the first axis.
+///
+/// For example, if the input is an array of shape `[n, c]` containing `c` columns of length `n`, the result is an array of shape `[c]` with column means.
+/// The mean is computed by a single [Truncate](crate::graphs::Operation::Truncate) of the column sums by `n`, so it is rounded towards zero.
+/// The input can be in the fixed-point representation; the output has the same representation.
+///
+/// The input must be of a signed type (which is required by the MPC truncation).
+///
+/// # Custom operation arguments
+///
+/// - Node containing an array of a signed type
+///
+/// # Custom operation returns
+///
+/// New Mean node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT64};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::statistics::Mean;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let x = g.input(array_type(vec![100, 3], INT64)).unwrap();
+/// let mean = g.custom_op(CustomOperation::new(Mean {}), vec![x]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct Mean {}
+
+#[typetag::serde]
+impl CustomOperationBody for Mean {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 1 {
+            return Err(runtime_error!("Invalid number of arguments for Mean"));
+        }
+        let n = validate_columns(&arguments_types[0], "Mean")?;
+        let g = context.create_graph()?;
+        let columns = g.input(arguments_types[0].clone())?;
+        columns.sum(vec![0])?.truncate(n)?.set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        "Mean".to_owned()
+    }
+}
+
+register_custom_operation!(Mean, [Array]);
+
+/// A structure that defines the custom operation Variance that computes the (population) variance of an array along the first axis.
+///
+/// The variance is computed as the mean of squared deviations from the mean, which is more accurate than E\[X<sup>2</sup>\] - E\[X\]<sup>2</sup> in fixed-point arithmetic.
+/// The input and the output are in the fixed-point representation with denominator 2<sup>fixed_precision_points</sup>.
+/// Sums of squared deviations are computed with denominator 2<sup>2 * fixed_precision_points</sup>, so they must fit into the input type.
+///
+/// # Custom operation arguments
+///
+/// - Node containing an array of a signed type
+///
+/// # Custom operation returns
+///
+/// New Variance node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT64};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::statistics::Variance;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let x = g.input(array_type(vec![100, 3], INT64)).unwrap();
+/// let variance = g.custom_op(CustomOperation::new(Variance {fixed_precision_points: 15}), vec![x]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct Variance {
+    /// Assume that we're operating in fixed precision arithmetic with denominator 2 ** fixed_precision_points.
+    pub fixed_precision_points: u64,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for Variance {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 1 {
+            return Err(runtime_error!("Invalid number of arguments for Variance"));
+        }
+        let n = validate_columns(&arguments_types[0], "Variance")?;
+        let scale = get_scale(n, self.fixed_precision_points)?;
+        let g = context.create_graph()?;
+        let columns = g.input(arguments_types[0].clone())?;
+        let deviations = center(columns, n)?;
+        deviations
+            .multiply(deviations.clone())?
+            .sum(vec![0])?
+            .truncate(scale)?
+            .set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "Variance(fixed_precision_points={})",
+            self.fixed_precision_points
+        )
+    }
+}
+
+register_custom_operation!(Variance, [Array]);
+
+/// A structure that defines the custom operation Covariance that computes the (population) covariance matrix of columns.
+///
+/// The input is an array of shape `[n, c]` containing `c` columns of length `n`; the output is the covariance matrix of shape `[c, c]`.
+/// The input and the output are in the fixed-point representation with denominator 2<sup>fixed_precision_points</sup>.
+/// Products of deviations from the mean are summed with denominator 2<sup>2 * fixed_precision_points</sup>, so the sums must fit into the input type.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a 2-dimensional array of a signed type
+///
+/// # Custom operation returns
+///
+/// New Covariance node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT64};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::statistics::Covariance;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let x = g.input(array_type(vec![100, 3], INT64)).unwrap();
+/// let covariance = g.custom_op(CustomOperation::new(Covariance {fixed_precision_points: 15}), vec![x]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct Covariance {
+    /// Assume that we're operating in fixed precision arithmetic with denominator 2 ** fixed_precision_points.
+    pub fixed_precision_points: u64,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for Covariance {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 1 {
+            return Err(runtime_error!("Invalid number of arguments for Covariance"));
+        }
+        let n = validate_columns(&arguments_types[0], "Covariance")?;
+        if arguments_types[0].get_shape().len() != 2 {
+            return Err(runtime_error!(
+                "Argument of Covariance must be a 2-dimensional array"
+            ));
+        }
+        let scale = get_scale(n, self.fixed_precision_points)?;
+        let g = context.create_graph()?;
+        let columns = g.input(arguments_types[0].clone())?;
+        let deviations = center(columns, n)?;
+        deviations
+            .permute_axes(vec![1, 0])?
+            .matmul(deviations)?
+            .truncate(scale)?
+            .set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "Covariance(fixed_precision_points={})",
+            self.fixed_precision_points
+        )
+    }
+}
+
+register_custom_operation!(Covariance, [Array]);
+
+/// A structure that defines the custom operation Histogram that counts the elements of an array falling into given bins.
+///
+/// Bins are defined by an increasing array of `b + 1` edges `e`; the `i`-th bin contains elements `x` such that e<sub>i</sub> <= x < e<sub>i+1</sub>.
+/// Elements outside of `[e_0, e_b)` are not counted.
+/// Edges are given as a node, so they can be private as well as the data.
+///
+/// Comparisons are signed if the input type is signed.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a 1-dimensional array of a non-binary type
+/// - Node containing a 1-dimensional array of the same type with at least 2 increasing bin edges
+///
+/// # Custom operation returns
+///
+/// New Histogram node containing a UINT64 array with `b` counts
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT32};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::statistics::Histogram;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let x = g.input(array_type(vec![100], INT32)).unwrap();
+/// let edges = g.input(array_type(vec![5], INT32)).unwrap();
+/// let counts = g.custom_op(CustomOperation::new(Histogram {}), vec![x, edges]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct Histogram {}
+
+#[typetag::serde]
+impl CustomOperationBody for Histogram {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 2 {
+            return Err(runtime_error!("Invalid number of arguments for Histogram"));
+        }
+        let t = arguments_types[0].clone();
+        let edges_t = arguments_types[1].clone();
+        if !t.is_array() || t.get_shape().len() != 1 || t.get_scalar_type() == BIT {
+            return Err(runtime_error!(
+                "Data of Histogram must be a 1-dimensional array of a non-binary type"
+            ));
+        }
+        if !edges_t.is_array()
+            || edges_t.get_shape().len() != 1
+            || edges_t.get_scalar_type() != t.get_scalar_type()
+        {
+            return Err(runtime_error!(
+                "Bin edges of Histogram must be a 1-dimensional array of the same type as data"
+            ));
+        }
+        let n = t.get_shape()[0];
+        let num_edges = edges_t.get_shape()[0];
+        if num_edges < 2 {
+            return Err(runtime_error!("Histogram must have at least 2 bin edges"));
+        }
+
+        let g = context.create_graph()?;
+        let x = g.input(t.clone())?;
+        let edges = g.input(edges_t)?;
+        // above_edges[i, j] = 1 iff x[i] >= edges[j]
+        let above_edges = g.custom_op(
+            CustomOperation::new(GreaterThanEqualTo {
+                signed_comparison: t.get_scalar_type().get_signed(),
+            }),
+            vec![
+                x.reshape(array_type(vec![n, 1], t.get_scalar_type()))?
+                    .a2b()?,
+                edges.a2b()?,
+            ],
+        )?;
+        // Number of elements greater than or equal to every edge
+        let counts_above = single_bit_to_arithmetic(above_edges, UINT64)?.sum(vec![0])?;
+        let slice = |start: Option<i64>, end: Option<i64>| -> Result<Node> {
+            counts_above.get_slice(vec![SliceElement::SubArray(start, end, None)])
+        };
+        slice(None, Some(-1))?
+            .subtract(slice(Some(1), None)?)?
+            .set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        "Histogram".to_owned()
+    }
+}
+
+register_custom_operation!(Histogram, [Array, Array]);
+
+/// A structure that defines the custom operation EquiWidthHistogram that counts the elements of an array falling into bins of equal width.
+///
+/// The `i`-th bin contains elements `x` such that `lower + i * width <= x < lower + (i + 1) * width` for `i` in `0..num_bins`.
+/// Elements outside of all the bins are not counted.
+/// Unlike [Histogram], the bins are public, so the bin edges must fit into the input type.
+///
+/// Comparisons are signed if the input type is signed.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a 1-dimensional array of a non-binary type with at most 64 bits
+///
+/// # Custom operation returns
+///
+/// New EquiWidthHistogram node containing a UINT64 array with `num_bins` counts
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT32};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::statistics::EquiWidthHistogram;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let x = g.input(array_type(vec![100], INT32)).unwrap();
+/// let op = EquiWidthHistogram {lower: -50, width: 10, num_bins: 10};
+/// let counts = g.custom_op(CustomOperation::new(op), vec![x]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct EquiWidthHistogram {
+    /// lower edge of the first bin
+    pub lower: i64,
+    /// width of every bin
+    pub width: u64,
+    /// number of bins
+    pub num_bins: u64,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for EquiWidthHistogram {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 1 {
+            return Err(runtime_error!(
+                "Invalid number of arguments for EquiWidthHistogram"
+            ));
+        }
+        let t = arguments_types[0].clone();
+        let st = t.get_scalar_type();
+        if !t.is_array() || st == BIT || st.size_in_bits() > 64 {
+            return Err(runtime_error!(
+                "Data of EquiWidthHistogram must be an array of a non-binary type with at most 64 bits"
+            ));
+        }
+        if self.width == 0 || self.num_bins == 0 {
+            return Err(runtime_error!(
+                "EquiWidthHistogram must have at least one bin of positive width"
+            ));
+        }
+        // Edges are computed in i128 to detect values that don't fit into the input type
+        let bits = st.size_in_bits();
+        let (min_value, max_value) = if st.get_signed() {
+            (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+        } else {
+            (0, (1i128 << bits) - 1)
+        };
+        let mut edges = vec![];
+        for i in 0..=self.num_bins {
+            let edge = self.lower as i128 + i as i128 * self.width as i128;
+            if edge < min_value || edge > max_value {
+                return Err(runtime_error!(
+                    "Bin edge {} of EquiWidthHistogram doesn't fit into {}",
+                    edge,
+                    st
+                ));
+            }
+            // Values are stored in the two's complement form
+            edges.push(edge as u64);
+        }
+
+        let g = context.create_graph()?;
+        let x = g.input(t)?;
+        let edges = constant(
+            &g,
+            TypedValue::new(
+                array_type(vec![self.num_bins + 1], st.clone()),
+                Value::from_flattened_array(&edges, st)?,
+            )?,
+        )?;
+        g.custom_op(CustomOperation::new(Histogram {}), vec![x, edges])?
+            .set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "EquiWidthHistogram(lower={},width={},num_bins={})",
+            self.lower, self.width, self.num_bins
+        )
+    }
+}
+
+register_custom_operation!(EquiWidthHistogram, [Array]);
+
+/// A structure that defines the custom operation Quantiles that approximates given percentiles of an array.
+///
+/// The input array of length `n` is sampled systematically: the sample consists of every `s`-th element starting from the first one, where `s = floor(n / sample_size)`.
+/// The sample is sorted obliviously by [Sort] and the `p`-th percentile is approximated by the element of the sorted sample with index `round(p * (sample_size - 1) / 100)`.
+/// If `sample_size` equals `n`, the percentiles are exact (up to rounding of indices).
+///
+/// Systematic sampling approximates the distribution well if the order of elements doesn't depend on their values; otherwise, the array should be shuffled beforehand.
+/// Sorting dominates the cost: it takes O(sample_size * log<sup>2</sup>(sample_size)) comparisons.
+///
+/// Comparisons are signed if the input type is signed.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a 1-dimensional array of a non-binary type
+///
+/// # Custom operation returns
+///
+/// New Quantiles node containing an array of the input type with the approximated percentiles
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, INT64};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::statistics::Quantiles;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let x = g.input(array_type(vec![1000], INT64)).unwrap();
+/// let op = Quantiles {percentiles: vec![25, 50, 75], sample_size: 64};
+/// let quartiles = g.custom_op(CustomOperation::new(op), vec![x]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct Quantiles {
+    /// percentiles to approximate, from 0 to 100
+    pub percentiles: Vec<u64>,
+    /// number of sampled elements; must be a power of two not exceeding the input length
+    pub sample_size: u64,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for Quantiles {
+    fn instantiate(&self, context: Context, arguments_types: Vec<Type>) -> Result<Graph> {
+        if arguments_types.len() != 1 {
+            return Err(runtime_error!("Invalid number of arguments for Quantiles"));
+        }
+        let t = arguments_types[0].clone();
+        let st = t.get_scalar_type();
+        if !t.is_array() || t.get_shape().len() != 1 || st == BIT {
+            return Err(runtime_error!(
+                "Data of Quantiles must be a 1-dimensional array of a non-binary type"
+            ));
+        }
+        let n = t.get_shape()[0];
+        if !self.sample_size.is_power_of_two() || self.sample_size > n {
+            return Err(runtime_error!(
+                "Sample size of Quantiles must be a power of two not exceeding the data length {}",
+                n
+            ));
+        }
+        if self.percentiles.is_empty() || self.percentiles.iter().any(|p| *p > 100) {
+            return Err(runtime_error!(
+                "Quantiles must have at least one percentile between 0 and 100"
+            ));
+        }
+        let m = self.sample_size;
+        let step = n / m;
+        let bits = st.size_in_bits();
+
+        let g = context.create_graph()?;
+        let x = g.input(t)?;
+        let sample = x.get_slice(vec![SliceElement::SubArray(
+            None,
+            Some((step * m) as i64),
+            Some(step as i64),
+        )])?;
+        let sorted_sample = g.custom_op(
+            CustomOperation::new(Sort {
+                k: m.trailing_zeros(),
+                b: bits,
+                signed_comparison: st.get_signed(),
+            }),
+            vec![sample.a2b()?],
+        )?;
+        let mut selected = vec![];
+        for p in &self.percentiles {
+            let index = (p * (m - 1) + 50) / 100;
+            selected.push(sorted_sample.get(vec![index])?);
+        }
+        g.create_vector(array_type(vec![bits], BIT), selected)?
+            .vector_to_array()?
+            .b2a(st)?
+            .set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "Quantiles(percentiles={:?},sample_size={})",
+            self.percentiles, self.sample_size
+        )
+    }
+}
+
+register_custom_operation!(Quantiles, [Array]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::custom_ops::run_instantiation_pass;
+    use crate::data_types::{ScalarType, INT32, INT64, UINT32};
+    use crate::data_values::Value;
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::create_context;
+    use crate::inline::inline_common::DepthOptimizationLevel;
+    use crate::inline::inline_ops::{InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{compile_context, IOStatus};
+
+    const PRECISION: u64 = 10;
+
+    fn evaluate_op(op: CustomOperation, inputs: Vec<(Type, Vec<i64>)>) -> Result<(Type, Value)> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let mut nodes = vec![];
+        let mut values = vec![];
+        for (t, entries) in inputs {
+            values.push(Value::from_flattened_array(&entries, t.get_scalar_type())?);
+            nodes.push(g.input(t)?);
+        }
+        let o = g.custom_op(op, nodes)?;
+        let output_type = o.get_type()?;
+        o.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        let instantiated_c = run_instantiation_pass(c)?.get_context();
+        let result = random_evaluate(instantiated_c.get_main_graph()?, values)?;
+        Ok((output_type, result))
+    }
+
+    fn to_fixed(x: &[f64]) -> Vec<i64> {
+        x.iter()
+            .map(|v| (v * (1 << PRECISION) as f64).round() as i64)
+            .collect()
+    }
+
+    // Columns [1, 2, 3, 4] and [2, 0, 1, -3] as a [4, 2] array
+    fn columns() -> Vec<f64> {
+        vec![1.0, 2.0, 2.0, 0.0, 3.0, 1.0, 4.0, -3.0]
+    }
+
+    #[test]
+    fn test_mean() {
+        || -> Result<()> {
+            let (t, result) = evaluate_op(
+                CustomOperation::new(Mean {}),
+                vec![(array_type(vec![4, 2], INT64), to_fixed(&columns()))],
+            )?;
+            assert_eq!(result.to_flattened_array_i64(t)?, to_fixed(&[2.5, 0.0]));
+
+            let (t, result) = evaluate_op(
+                CustomOperation::new(Mean {}),
+                vec![(array_type(vec![3], INT32), vec![-4, 10, 3])],
+            )?;
+            assert_eq!(result.to_i32(t.get_scalar_type())?, 3);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_variance_and_covariance() {
+        || -> Result<()> {
+            let (t, result) = evaluate_op(
+                CustomOperation::new(Variance {
+                    fixed_precision_points: PRECISION,
+                }),
+                vec![(array_type(vec![4, 2], INT64), to_fixed(&columns()))],
+            )?;
+            assert_eq!(result.to_flattened_array_i64(t)?, to_fixed(&[1.25, 3.5]));
+
+            let (t, result) = evaluate_op(
+                CustomOperation::new(Covariance {
+                    fixed_precision_points: PRECISION,
+                }),
+                vec![(array_type(vec![4, 2], INT64), to_fixed(&columns()))],
+            )?;
+            assert_eq!(
+                result.to_flattened_array_i64(t)?,
+                to_fixed(&[1.25, -1.75, -1.75, 3.5])
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    fn histogram_helper(st: ScalarType, x: Vec<i64>, edges: Vec<i64>) -> Result<Vec<u64>> {
+        let (t, result) = evaluate_op(
+            CustomOperation::new(Histogram {}),
+            vec![
+                (array_type(vec![x.len() as u64], st.clone()), x),
+                (array_type(vec![edges.len() as u64], st), edges),
+            ],
+        )?;
+        result.to_flattened_array_u64(t)
+    }
+
+    #[test]
+    fn test_histogram() {
+        assert_eq!(
+            histogram_helper(INT32, vec![-5, 0, 1, 7, 3, 10, 2, -1], vec![-1, 1, 3, 10]).unwrap(),
+            vec![2, 2, 2]
+        );
+        assert_eq!(
+            histogram_helper(UINT32, vec![5, 0, 100, 7], vec![0, 8]).unwrap(),
+            vec![3]
+        );
+    }
+
+    #[test]
+    fn test_equi_width_histogram() {
+        || -> Result<()> {
+            let op = EquiWidthHistogram {
+                lower: -10,
+                width: 5,
+                num_bins: 4,
+            };
+            let (t, result) = evaluate_op(
+                CustomOperation::new(op),
+                vec![(
+                    array_type(vec![9], INT32),
+                    vec![-11, -10, -6, -5, 0, 4, 9, 10, 100],
+                )],
+            )?;
+            assert_eq!(result.to_flattened_array_u64(t)?, vec![2, 1, 2, 1]);
+            let op = EquiWidthHistogram {
+                lower: 0,
+                width: 100,
+                num_bins: 2,
+            };
+            let (t, result) = evaluate_op(
+                CustomOperation::new(op),
+                vec![(array_type(vec![4], UINT32), vec![5, 150, 99, 200])],
+            )?;
+            assert_eq!(result.to_flattened_array_u64(t)?, vec![2, 1]);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_quantiles() {
+        || -> Result<()> {
+            // Permutation of 0..16 multiplied by -3
+            let x: Vec<i64> = (0..16).map(|i| -3 * ((i * 7) % 16)).collect();
+            let op = Quantiles {
+                percentiles: vec![0, 25, 50, 100],
+                sample_size: 16,
+            };
+            let (t, result) = evaluate_op(
+                CustomOperation::new(op),
+                vec![(array_type(vec![16], INT64), x.clone())],
+            )?;
+            assert_eq!(result.to_flattened_array_i64(t)?, vec![-45, -33, -21, 0]);
+            // The sample consists of every 4th element, i.e. 0, -12, -24 and -36
+            let op = Quantiles {
+                percentiles: vec![50],
+                sample_size: 4,
+            };
+            let (t, result) = evaluate_op(
+                CustomOperation::new(op),
+                vec![(array_type(vec![17], INT64), [x, vec![-100]].concat())],
+            )?;
+            assert_eq!(result.to_flattened_array_i64(t)?, vec![-12]);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_statistics_compile_end2end() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let columns = g.input(array_type(vec![5, 2], INT64))?;
+            let edges = g.input(array_type(vec![3], INT64))?;
+            let mean = g.custom_op(CustomOperation::new(Mean {}), vec![columns.clone()])?;
+            let variance = g.custom_op(
+                CustomOperation::new(Variance {
+                    fixed_precision_points: PRECISION,
+                }),
+                vec![columns.clone()],
+            )?;
+            let covariance = g.custom_op(
+                CustomOperation::new(Covariance {
+                    fixed_precision_points: PRECISION,
+                }),
+                vec![columns.clone()],
+            )?;
+            let first_column =
+                columns.get_slice(vec![SliceElement::Ellipsis, SliceElement::SingleIndex(0)])?;
+            let histogram = g.custom_op(
+                CustomOperation::new(Histogram {}),
+                vec![first_column.clone(), edges],
+            )?;
+            let equi_width_histogram = g.custom_op(
+                CustomOperation::new(EquiWidthHistogram {
+                    lower: -100,
+                    width: 50,
+                    num_bins: 4,
+                }),
+                vec![first_column.clone()],
+            )?;
+            let quantiles = g.custom_op(
+                CustomOperation::new(Quantiles {
+                    percentiles: vec![10, 50, 90],
+                    sample_size: 4,
+                }),
+                vec![first_column.clone()],
+            )?;
+            g.create_tuple(vec![
+                mean,
+                variance,
+                covariance,
+                histogram,
+                equi_width_histogram,
+                quantiles,
+            ])?
+            .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            compile_context(
+                c,
+                vec![IOStatus::Shared, IOStatus::Public],
+                vec![IOStatus::Party(0)],
+                InlineConfig {
+                    default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                    ..Default::default()
+                },
+                || SimpleEvaluator::new(None),
+            )?;
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_statistics() {
+        let check = |op: CustomOperation, types: Vec<Type>| -> bool {
+            || -> Result<()> {
+                let c = create_context()?;
+                let g = c.create_graph()?;
+                let mut nodes = vec![];
+                for t in types {
+                    nodes.push(g.input(t)?);
+                }
+                g.custom_op(op, nodes)?;
+                Ok(())
+            }()
+            .is_err()
+        };
+        assert!(check(
+            CustomOperation::new(Mean {}),
+            vec![array_type(vec![4], UINT32)]
+        ));
+        assert!(check(
+            CustomOperation::new(Variance {
+                fixed_precision_points: 70
+            }),
+            vec![array_type(vec![4], INT64)]
+        ));
+        assert!(check(
+            CustomOperation::new(Covariance {
+                fixed_precision_points: PRECISION
+            }),
+            vec![array_type(vec![4], INT64)]
+        ));
+        assert!(check(
+            CustomOperation::new(Histogram {}),
+            vec![array_type(vec![4], INT64), array_type(vec![1], INT64)]
+        ));
+        assert!(check(
+            CustomOperation::new(Histogram {}),
+            vec![array_type(vec![4], INT64), array_type(vec![3], INT32)]
+        ));
+        assert!(check(
+            CustomOperation::new(Histogram {}),
+            vec![array_type(vec![4], BIT), array_type(vec![3], BIT)]
+        ));
+        let equi_width = |lower: i64, width: u64, num_bins: u64| {
+            CustomOperation::new(EquiWidthHistogram {
+                lower,
+                width,
+                num_bins,
+            })
+        };
+        assert!(check(equi_width(0, 0, 4), vec![array_type(vec![4], INT64)]));
+        assert!(check(
+            equi_width(-1, 1, 4),
+            vec![array_type(vec![4], UINT32)]
+        ));
+        assert!(check(
+            equi_width(i32::MAX as i64, 1, 1),
+            vec![array_type(vec![4], INT32)]
+        ));
+        assert!(check(equi_width(0, 1, 1), vec![array_type(vec![4], BIT)]));
+        let quantiles = |percentiles: Vec<u64>, sample_size: u64| {
+            CustomOperation::new(Quantiles {
+                percentiles,
+                sample_size,
+            })
+        };
+        assert!(check(
+            quantiles(vec![50], 3),
+            vec![array_type(vec![4], INT64)]
+        ));
+        assert!(check(
+            quantiles(vec![50], 8),
+            vec![array_type(vec![4], INT64)]
+        ));
+        assert!(check(
+            quantiles(vec![101], 4),
+            vec![array_type(vec![4], INT64)]
+        ));
+        assert!(check(
+            quantiles(vec![], 4),
+            vec![array_type(vec![4], INT64)]
+        ));
+        assert!(check(
+            quantiles(vec![50], 4),
+            vec![array_type(vec![4, 2], INT64)]
+        ));
+    }
+}
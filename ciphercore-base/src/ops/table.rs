@@ -0,0 +1,1171 @@
+//! Operations shaping databases represented as named tuples of columns: projection, renaming, reordering and casting of columns, filtering and grouping of rows.
+use std::collections::{HashMap, HashSet};
+
+use crate::custom_ops::{CustomOperation, CustomOperationBody, Not, Or};
+use crate::data_types::{array_type, vector_type, ScalarType, Type, BIT, INT64};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{Context, Graph, Node, SliceElement};
+use crate::type_inference::NULL_HEADER;
+
+use serde::{Deserialize, Serialize};
+
+use super::comparisons::{
+    Equal, GreaterThan, GreaterThanEqualTo, LessThan, LessThanEqualTo, NotEqual,
+};
+use super::utils::{
+    constant_scalar, multiply_by_bits, multiply_rows_by_bits, pull_out_bits, put_in_bits,
+    single_bit_to_arithmetic, zeros,
+};
+
+// Returns headers and types of columns of a database
+fn get_column_types(t: &Type, op_name: &str) -> Result<Vec<(String, Type)>> {
+    if let Type::NamedTuple(header_types) = t {
+        let mut column_types = vec![];
+        for (header, column_t) in header_types {
+            if !column_t.is_array() {
+                return Err(runtime_error!(
+                    "{} can be applied only to named tuples of arrays, but column {} has type {}",
+                    op_name,
+                    header,
+                    column_t
+                ));
+            }
+            column_types.push((header.clone(), (**column_t).clone()));
+        }
+        Ok(column_types)
+    } else {
+        Err(runtime_error!(
+            "{} can be applied only to named tuples, but {} is given",
+            op_name,
+            t
+        ))
+    }
+}
+
+/// A structure that defines the custom operation SelectColumns that selects, renames and reorders columns of a database.
+///
+/// A database is represented by a named tuple of arrays (see [Graph::set_intersection]).
+/// The operation is defined by a list of pairs `(input header, output header)`.
+/// The result is a named tuple whose columns are the input columns with given input headers, renamed to the corresponding output headers and ordered as in the list.
+/// An input column can be selected several times, but output headers must be unique.
+///
+/// Since only [named tuple operations](crate::graphs::Operation::NamedTupleGet) are involved, this operation costs nothing when the database is secret-shared.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a named tuple of arrays
+///
+/// # Custom operation returns
+///
+/// New SelectColumns node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, named_tuple_type, BIT, INT32};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::table::SelectColumns;
+/// # use ciphercore_base::type_inference::NULL_HEADER;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let t = named_tuple_type(vec![
+///     (NULL_HEADER.to_owned(), array_type(vec![10], BIT)),
+///     ("ID".to_owned(), array_type(vec![10], INT32)),
+///     ("Age".to_owned(), array_type(vec![10], INT32)),
+/// ]);
+/// let n1 = g.input(t).unwrap();
+/// let op = SelectColumns {
+///     columns: vec![
+///         (NULL_HEADER.to_owned(), NULL_HEADER.to_owned()),
+///         ("Age".to_owned(), "Years".to_owned()),
+///     ],
+/// };
+/// let n2 = g.custom_op(CustomOperation::new(op), vec![n1]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct SelectColumns {
+    pub columns: Vec<(String, String)>,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for SelectColumns {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 1 {
+            return Err(runtime_error!("SelectColumns should have 1 input"));
+        }
+        if self.columns.is_empty() {
+            return Err(runtime_error!("At least one column must be selected"));
+        }
+        let input_headers: HashSet<String> = get_column_types(&argument_types[0], "SelectColumns")?
+            .into_iter()
+            .map(|(header, _)| header)
+            .collect();
+        let mut output_headers = HashSet::new();
+        for (input_header, output_header) in &self.columns {
+            if !input_headers.contains(input_header) {
+                return Err(runtime_error!("There is no column {}", input_header));
+            }
+            if !output_headers.insert(output_header.clone()) {
+                return Err(runtime_error!(
+                    "Output header {} is used several times",
+                    output_header
+                ));
+            }
+        }
+
+        let g = context.create_graph()?;
+        let data = g.input(argument_types[0].clone())?;
+        let mut result_columns = vec![];
+        for (input_header, output_header) in &self.columns {
+            result_columns.push((
+                output_header.clone(),
+                data.named_tuple_get(input_header.clone())?,
+            ));
+        }
+        g.create_named_tuple(result_columns)?.set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!("SelectColumns({:?})", self.columns)
+    }
+}
+
+register_custom_operation!(SelectColumns, [NamedTuple]);
+
+// Converts an array to a given scalar type as described in CastColumns
+fn cast_array(a: Node, st: ScalarType) -> Result<Node> {
+    let t = a.get_type()?;
+    let input_st = t.get_scalar_type();
+    if input_st == st {
+        return Ok(a);
+    }
+    // ConvertRing doesn't support bits and 128-bit integers
+    if input_st != BIT && st != BIT && input_st.size_in_bits() <= 64 && st.size_in_bits() <= 64 {
+        return a.convert_ring(st);
+    }
+    let shape = t.get_shape();
+    let g = a.get_graph();
+    // Bits of the input entries with the bit axis in front
+    let bits = if input_st == BIT {
+        let mut bits_shape = shape.clone();
+        bits_shape.push(1);
+        pull_out_bits(a.reshape(array_type(bits_shape, BIT))?)?
+    } else {
+        pull_out_bits(a.a2b()?)?
+    };
+    let input_size = input_st.size_in_bits();
+    let output_size = st.size_in_bits();
+    let bits = if output_size < input_size {
+        // Keep the least significant bits
+        bits.get_slice(vec![SliceElement::SubArray(
+            None,
+            Some(output_size as i64),
+            None,
+        )])?
+    } else {
+        let bit_row_t = array_type(shape.clone(), BIT);
+        let num_extra_bits = output_size - input_size;
+        // Signed integers are extended by copies of the sign bit, other values are extended by zeros
+        let extra_bits = if input_st.get_signed() {
+            bits.get(vec![input_size - 1])?.repeat(num_extra_bits)?
+        } else {
+            zeros(&g, vector_type(num_extra_bits, bit_row_t.clone()))?
+        };
+        g.create_tuple(vec![bits.array_to_vector()?, extra_bits])?
+            .reshape(vector_type(output_size, bit_row_t))?
+            .vector_to_array()?
+    };
+    let bits = put_in_bits(bits)?;
+    if st == BIT {
+        bits.reshape(array_type(shape, BIT))
+    } else {
+        bits.b2a(st)
+    }
+}
+
+/// A structure that defines the custom operation CastColumns that converts given columns of a database to other scalar types.
+///
+/// A database is represented by a named tuple of arrays (see [Graph::set_intersection]).
+/// The operation is defined by a list of pairs `(header, scalar type)`; the columns that are not in the list are unchanged.
+///
+/// Integers are converted as by the `as` operator of Rust:
+/// - conversion to a type of smaller bit size keeps the least significant bits of the input;
+/// - conversion to a type of bigger bit size extends signed inputs by copies of the sign bit and unsigned inputs by zeros;
+/// - conversion between types of the same bit size keeps all the bits.
+///
+/// Binary columns are treated as 1-bit unsigned integers, i.e. they are converted to 0 or 1, and conversion to [BIT] keeps only the least significant bit.
+///
+/// If the database is secret-shared, integer columns are converted by [ConvertRing](crate::graphs::Operation::ConvertRing), which is local for conversions to smaller types.
+/// Conversions from and to binary columns require one [A2B](crate::graphs::Operation::A2B) or one [B2A](crate::graphs::Operation::B2A) conversion.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a named tuple of arrays
+///
+/// # Custom operation returns
+///
+/// New CastColumns node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, named_tuple_type, BIT, INT32, INT64};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::table::CastColumns;
+/// # use ciphercore_base::type_inference::NULL_HEADER;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let t = named_tuple_type(vec![
+///     (NULL_HEADER.to_owned(), array_type(vec![10], BIT)),
+///     ("Age".to_owned(), array_type(vec![10], INT32)),
+/// ]);
+/// let n1 = g.input(t).unwrap();
+/// let op = CastColumns {
+///     columns: vec![("Age".to_owned(), INT64)],
+/// };
+/// let n2 = g.custom_op(CustomOperation::new(op), vec![n1]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct CastColumns {
+    pub columns: Vec<(String, ScalarType)>,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for CastColumns {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 1 {
+            return Err(runtime_error!("CastColumns should have 1 input"));
+        }
+        let column_types = get_column_types(&argument_types[0], "CastColumns")?;
+        let mut output_types = HashMap::new();
+        for (header, st) in &self.columns {
+            if !column_types.iter().any(|(h, _)| h == header) {
+                return Err(runtime_error!("There is no column {}", header));
+            }
+            if output_types.insert(header.clone(), st.clone()).is_some() {
+                return Err(runtime_error!("Column {} is cast several times", header));
+            }
+        }
+
+        let g = context.create_graph()?;
+        let data = g.input(argument_types[0].clone())?;
+        let mut result_columns = vec![];
+        for (header, _) in column_types {
+            let mut column = data.named_tuple_get(header.clone())?;
+            if let Some(st) = output_types.get(&header) {
+                column = cast_array(column, st.clone())?;
+            }
+            result_columns.push((header, column));
+        }
+        g.create_named_tuple(result_columns)?.set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!("CastColumns({:?})", self.columns)
+    }
+}
+
+register_custom_operation!(CastColumns, [NamedTuple]);
+
+/// Comparison operators that can be used in a [Predicate].
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanEqualTo,
+    GreaterThan,
+    GreaterThanEqualTo,
+}
+
+/// Right-hand side of a comparison in a [Predicate].
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub enum Operand {
+    /// Column with a given header; it must have the same type as the left-hand side column.
+    Column(String),
+    /// Integer constant cast to the scalar type of the left-hand side column as by the `as` operator of Rust.
+    Constant(i64),
+}
+
+/// Boolean condition on rows of a database used by [Filter].
+///
+/// A predicate is built from comparisons of columns with constants or other columns combined by AND, OR and NOT.
+///
+/// Comparisons are applied to the entries of the same row.
+/// Thus, the compared columns must be either one-dimensional arrays of any scalar type or two-dimensional binary arrays.
+/// Rows of the latter are interpreted as binary strings (little-endian unsigned integers for [Comparison::LessThan] and other order comparisons) and can't be compared with constants.
+/// Integers are compared as signed integers if their scalar type is signed.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::ops::table::{Comparison, Operand, Predicate};
+/// // Age >= 18 AND NOT (Country = Residence)
+/// let p = Predicate::compare("Age", Comparison::GreaterThanEqualTo, Operand::Constant(18))
+///     .and(!Predicate::compare("Country", Comparison::Equal, Operand::Column("Residence".to_owned())));
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub enum Predicate {
+    Compare {
+        column: String,
+        comparison: Comparison,
+        operand: Operand,
+    },
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+    Not(Box<Predicate>),
+}
+
+impl Predicate {
+    /// Returns the predicate comparing the column with a given header with an operand.
+    pub fn compare(column: &str, comparison: Comparison, operand: Operand) -> Predicate {
+        Predicate::Compare {
+            column: column.to_owned(),
+            comparison,
+            operand,
+        }
+    }
+
+    /// Returns the conjunction of `self` and `other`.
+    pub fn and(self, other: Predicate) -> Predicate {
+        Predicate::And(Box::new(self), Box::new(other))
+    }
+
+    /// Returns the disjunction of `self` and `other`.
+    pub fn or(self, other: Predicate) -> Predicate {
+        Predicate::Or(Box::new(self), Box::new(other))
+    }
+}
+
+impl std::ops::Not for Predicate {
+    type Output = Predicate;
+
+    fn not(self) -> Predicate {
+        Predicate::Not(Box::new(self))
+    }
+}
+
+// Returns the rows of a column as binary strings, i.e. an array of shape [n, b]
+fn get_row_bits(column: Node, header: &str) -> Result<Node> {
+    let t = column.get_type()?;
+    let shape = t.get_shape();
+    match (shape.len(), t.get_scalar_type() == BIT) {
+        (1, true) => column.reshape(array_type(vec![shape[0], 1], BIT)),
+        (1, false) => column.a2b(),
+        (2, true) => Ok(column),
+        _ => Err(runtime_error!(
+            "Column {} can't be compared: only one-dimensional arrays and two-dimensional binary arrays are supported, got {}",
+            header,
+            t
+        )),
+    }
+}
+
+// Returns a binary array of shape [n] whose bits indicate rows satisfying the predicate
+fn get_predicate_bits(data: &Node, types: &HashMap<String, Type>, p: &Predicate) -> Result<Node> {
+    let g = data.get_graph();
+    match p {
+        Predicate::Compare {
+            column,
+            comparison,
+            operand,
+        } => {
+            let t = types
+                .get(column)
+                .ok_or_else(|| runtime_error!("There is no column {}", column))?;
+            let st = t.get_scalar_type();
+            let lhs = get_row_bits(data.named_tuple_get(column.clone())?, column)?;
+            let rhs = match operand {
+                Operand::Column(header) => {
+                    let operand_t = types
+                        .get(header)
+                        .ok_or_else(|| runtime_error!("There is no column {}", header))?;
+                    if operand_t != t {
+                        return Err(runtime_error!(
+                            "Columns {} and {} can't be compared: types {} and {} are different",
+                            column,
+                            header,
+                            t,
+                            operand_t
+                        ));
+                    }
+                    get_row_bits(data.named_tuple_get(header.clone())?, header)?
+                }
+                Operand::Constant(value) => {
+                    if t.get_shape().len() != 1 {
+                        return Err(runtime_error!(
+                            "Column {} can't be compared with a constant: only one-dimensional columns are supported",
+                            column
+                        ));
+                    }
+                    let c = constant_scalar(&g, *value, st.clone())?;
+                    if st == BIT {
+                        c.reshape(array_type(vec![1], BIT))?
+                    } else {
+                        c.a2b()?
+                    }
+                }
+            };
+            let signed_comparison = st.get_signed();
+            let op = match comparison {
+                Comparison::Equal => CustomOperation::new(Equal {}),
+                Comparison::NotEqual => CustomOperation::new(NotEqual {}),
+                Comparison::LessThan => CustomOperation::new(LessThan { signed_comparison }),
+                Comparison::LessThanEqualTo => {
+                    CustomOperation::new(LessThanEqualTo { signed_comparison })
+                }
+                Comparison::GreaterThan => CustomOperation::new(GreaterThan { signed_comparison }),
+                Comparison::GreaterThanEqualTo => {
+                    CustomOperation::new(GreaterThanEqualTo { signed_comparison })
+                }
+            };
+            g.custom_op(op, vec![lhs, rhs])
+        }
+        Predicate::And(p0, p1) => {
+            get_predicate_bits(data, types, p0)?.multiply(get_predicate_bits(data, types, p1)?)
+        }
+        Predicate::Or(p0, p1) => g.custom_op(
+            CustomOperation::new(Or {}),
+            vec![
+                get_predicate_bits(data, types, p0)?,
+                get_predicate_bits(data, types, p1)?,
+            ],
+        ),
+        Predicate::Not(p0) => g.custom_op(
+            CustomOperation::new(Not {}),
+            vec![get_predicate_bits(data, types, p0)?],
+        ),
+    }
+}
+
+/// A structure that defines the custom operation Filter that removes rows of a database that don't satisfy a given [Predicate], as the SQL `WHERE` clause.
+///
+/// A database is represented by a named tuple of arrays containing the [null column](crate::type_inference::NULL_HEADER) (see [Graph::set_intersection]).
+/// The size of the database is unchanged, but the rows that don't satisfy the predicate are removed by zeroing their entries in all the columns including the null column.
+///
+/// If the database is secret-shared, the predicate is evaluated on the shares, so neither the predicate values nor the number of remaining rows are revealed.
+/// Every comparison requires [A2B](crate::graphs::Operation::A2B) conversions of the compared integer columns.
+///
+/// This operation can be also applied via [Node::filter](crate::graphs::Node::filter).
+///
+/// # Custom operation arguments
+///
+/// - Node containing a named tuple of arrays
+///
+/// # Custom operation returns
+///
+/// New Filter node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, named_tuple_type, BIT, INT32};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::table::{Comparison, Filter, Operand, Predicate};
+/// # use ciphercore_base::type_inference::NULL_HEADER;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let t = named_tuple_type(vec![
+///     (NULL_HEADER.to_owned(), array_type(vec![10], BIT)),
+///     ("ID".to_owned(), array_type(vec![10], INT32)),
+///     ("Age".to_owned(), array_type(vec![10], INT32)),
+/// ]);
+/// let n1 = g.input(t).unwrap();
+/// let op = Filter {
+///     predicate: Predicate::compare("Age", Comparison::GreaterThan, Operand::Constant(30)),
+/// };
+/// let n2 = g.custom_op(CustomOperation::new(op), vec![n1]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct Filter {
+    pub predicate: Predicate,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for Filter {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 1 {
+            return Err(runtime_error!("Filter should have 1 input"));
+        }
+        let column_types = get_column_types(&argument_types[0], "Filter")?;
+        let types: HashMap<String, Type> = column_types.iter().cloned().collect();
+        if !types.contains_key(NULL_HEADER) {
+            return Err(runtime_error!("Named tuple should contain the null column"));
+        }
+
+        let g = context.create_graph()?;
+        let data = g.input(argument_types[0].clone())?;
+        let null_column = data
+            .named_tuple_get(NULL_HEADER.to_owned())?
+            .multiply(get_predicate_bits(&data, &types, &self.predicate)?)?;
+        let mut result_columns = vec![];
+        for (header, _) in column_types {
+            let column = if header == NULL_HEADER {
+                null_column.clone()
+            } else {
+                multiply_rows_by_bits(data.named_tuple_get(header.clone())?, null_column.clone())?
+            };
+            result_columns.push((header, column));
+        }
+        g.create_named_tuple(result_columns)?.set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!("Filter({:?})", self.predicate)
+    }
+}
+
+register_custom_operation!(Filter, [NamedTuple]);
+
+/// Aggregate functions computed by [GroupBy].
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub enum Aggregate {
+    /// Sum of the entries of the column with a given header over the rows of a group, as the SQL `SUM` function
+    Sum(String),
+    /// Number of rows of a group as an [INT64] integer, as the SQL `COUNT(*)` function
+    Count,
+}
+
+/// A structure that defines the custom operation GroupBy that groups rows of a database by key columns and computes aggregates of every group, as the SQL `GROUP BY` clause.
+///
+/// A database is represented by a named tuple of arrays containing the [null column](crate::type_inference::NULL_HEADER) (see [Graph::set_intersection]).
+/// Two non-null rows belong to the same group if they have equal values in all the key columns.
+/// Key columns must be one-dimensional arrays or two-dimensional binary arrays as in [Predicate].
+///
+/// The operation is defined by a list of key headers and a list of pairs `(aggregate, output header)`.
+/// The result is a named tuple containing the null column, the key columns and the aggregate columns with given output headers.
+/// It has the same number of rows as the input: the first row of every group contains the keys and aggregates of the group, while the other rows are null and zeroed.
+/// Sums have the same type as the summed columns, so they can overflow.
+///
+/// If no key headers are given, all the non-null rows form a single group and the result has one non-null row, even if the input has no non-null rows.
+///
+/// All pairs of rows are compared, so the operation takes O(n<sup>2</sup>) comparisons of keys, where n is the number of rows.
+/// This is affordable for moderate n, but makes the operation quadratic in MPC.
+///
+/// # Custom operation arguments
+///
+/// - Node containing a named tuple of arrays
+///
+/// # Custom operation returns
+///
+/// New GroupBy node
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, named_tuple_type, BIT, INT32};
+/// # use ciphercore_base::custom_ops::{CustomOperation};
+/// # use ciphercore_base::ops::table::{Aggregate, GroupBy};
+/// # use ciphercore_base::type_inference::NULL_HEADER;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let t = named_tuple_type(vec![
+///     (NULL_HEADER.to_owned(), array_type(vec![10], BIT)),
+///     ("Country".to_owned(), array_type(vec![10], INT32)),
+///     ("Revenue".to_owned(), array_type(vec![10], INT32)),
+/// ]);
+/// let n1 = g.input(t).unwrap();
+/// let op = GroupBy {
+///     keys: vec!["Country".to_owned()],
+///     aggregates: vec![
+///         (Aggregate::Sum("Revenue".to_owned()), "Total".to_owned()),
+///         (Aggregate::Count, "Customers".to_owned()),
+///     ],
+/// };
+/// let n2 = g.custom_op(CustomOperation::new(op), vec![n1]).unwrap();
+/// ```
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct GroupBy {
+    pub keys: Vec<String>,
+    pub aggregates: Vec<(Aggregate, String)>,
+}
+
+#[typetag::serde]
+impl CustomOperationBody for GroupBy {
+    fn instantiate(&self, context: Context, argument_types: Vec<Type>) -> Result<Graph> {
+        if argument_types.len() != 1 {
+            return Err(runtime_error!("GroupBy should have 1 input"));
+        }
+        if self.keys.is_empty() && self.aggregates.is_empty() {
+            return Err(runtime_error!("No keys or aggregates provided"));
+        }
+        let types: HashMap<String, Type> = get_column_types(&argument_types[0], "GroupBy")?
+            .into_iter()
+            .collect();
+        let null_t = types
+            .get(NULL_HEADER)
+            .ok_or_else(|| runtime_error!("Named tuple should contain the null column"))?;
+        let mut output_headers = HashSet::from([NULL_HEADER.to_owned()]);
+        let aggregate_headers = self.aggregates.iter().map(|(_, header)| header);
+        for header in self.keys.iter().chain(aggregate_headers) {
+            if !output_headers.insert(header.clone()) {
+                return Err(runtime_error!(
+                    "Output header {} is used several times",
+                    header
+                ));
+            }
+        }
+        for header in &self.keys {
+            if !types.contains_key(header) {
+                return Err(runtime_error!("There is no column {}", header));
+            }
+        }
+        for (aggregate, _) in &self.aggregates {
+            if let Aggregate::Sum(header) = aggregate {
+                let t = types
+                    .get(header)
+                    .ok_or_else(|| runtime_error!("There is no column {}", header))?;
+                if t.get_scalar_type() == BIT {
+                    return Err(runtime_error!("Binary column {} can't be summed", header));
+                }
+            }
+        }
+
+        let g = context.create_graph()?;
+        let data = g.input(argument_types[0].clone())?;
+        let null_column = data.named_tuple_get(NULL_HEADER.to_owned())?;
+        let num_entries = null_t.get_shape()[0];
+        // Binary matrix of shape [number of groups, n] whose (i, j)-th bit indicates that the j-th row belongs to the i-th group
+        let (membership, group_null_column) = if self.keys.is_empty() {
+            let group_null_column =
+                zeros(&g, array_type(vec![1], BIT))?.add(constant_scalar(&g, 1, BIT)?)?;
+            (
+                null_column.reshape(array_type(vec![1, num_entries], BIT))?,
+                group_null_column,
+            )
+        } else {
+            // Every row defines a group of the rows with the same keys
+            let mut equal_pairs = None;
+            for header in &self.keys {
+                let bits = get_row_bits(data.named_tuple_get(header.clone())?, header)?;
+                let string_length = bits.get_type()?.get_shape()[1];
+                let equal_keys = g.custom_op(
+                    CustomOperation::new(Equal {}),
+                    vec![
+                        bits.reshape(array_type(vec![num_entries, 1, string_length], BIT))?,
+                        bits.reshape(array_type(vec![1, num_entries, string_length], BIT))?,
+                    ],
+                )?;
+                equal_pairs = Some(match equal_pairs {
+                    Some(pairs) => equal_keys.multiply(pairs)?,
+                    None => equal_keys,
+                });
+            }
+            let membership = equal_pairs
+                .unwrap()
+                .multiply(null_column.reshape(array_type(vec![1, num_entries], BIT))?)?;
+            // Only the first row of every group is kept, i.e. a row that has no preceding rows in its group
+            let mut preceding_mask = vec![0u64; (num_entries * num_entries) as usize];
+            for i in 0..num_entries {
+                for j in 0..i {
+                    preceding_mask[(i * num_entries + j) as usize] = 1;
+                }
+            }
+            let preceding_members = membership.multiply(g.constant(
+                array_type(vec![num_entries, num_entries], BIT),
+                Value::from_flattened_array(&preceding_mask, BIT)?,
+            )?)?;
+            let is_first = g.custom_op(
+                CustomOperation::new(Equal {}),
+                vec![
+                    preceding_members,
+                    zeros(&g, array_type(vec![num_entries], BIT))?,
+                ],
+            )?;
+            (membership, null_column.multiply(is_first)?)
+        };
+        let num_groups = group_null_column.get_type()?.get_shape()[0];
+
+        let mut result_columns = vec![(NULL_HEADER.to_owned(), group_null_column.clone())];
+        for header in &self.keys {
+            result_columns.push((
+                header.clone(),
+                multiply_rows_by_bits(
+                    data.named_tuple_get(header.clone())?,
+                    group_null_column.clone(),
+                )?,
+            ));
+        }
+        for (aggregate, output_header) in &self.aggregates {
+            let aggregate_column = match aggregate {
+                Aggregate::Sum(header) => {
+                    let column_t = &types[header];
+                    let shape = column_t.get_shape();
+                    // Broadcast the column of shape [n, ...] and the membership matrix to shape [number of groups, n, ...]
+                    let mut column_shape = vec![1];
+                    column_shape.extend(shape.clone());
+                    let mut membership_shape = vec![num_groups, num_entries];
+                    membership_shape.extend(vec![1; shape.len() - 1]);
+                    let column = data
+                        .named_tuple_get(header.clone())?
+                        .reshape(array_type(column_shape, column_t.get_scalar_type()))?;
+                    multiply_by_bits(
+                        column,
+                        membership.reshape(array_type(membership_shape, BIT))?,
+                    )?
+                    .sum(vec![1])?
+                }
+                Aggregate::Count => {
+                    single_bit_to_arithmetic(membership.clone(), INT64)?.sum(vec![1])?
+                }
+            };
+            result_columns.push((
+                output_header.clone(),
+                multiply_rows_by_bits(aggregate_column, group_null_column.clone())?,
+            ));
+        }
+        g.create_named_tuple(result_columns)?.set_as_output()?;
+        g.finalize()?;
+        Ok(g)
+    }
+
+    fn get_name(&self) -> String {
+        format!(
+            "GroupBy(keys={:?}, aggregates={:?})",
+            self.keys, self.aggregates
+        )
+    }
+}
+
+register_custom_operation!(GroupBy, [NamedTuple]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::{run_instantiation_pass, CustomOperation};
+    use crate::data_types::{named_tuple_type, scalar_type, INT16, INT32, INT64, UINT64, UINT8};
+    use crate::data_values::Value;
+    use crate::evaluators::{evaluate_simple_evaluator, random_evaluate};
+    use crate::graphs::create_context;
+    use crate::inline::inline_common::DepthOptimizationLevel;
+    use crate::inline::inline_ops::{inline_operations, InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{prepare_for_mpc_evaluation, IOStatus};
+
+    fn evaluate_op(
+        op: CustomOperation,
+        column_types: Vec<(String, Type)>,
+        columns: Vec<Value>,
+        is_mpc: bool,
+    ) -> Result<Vec<Value>> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let data = g.input(named_tuple_type(column_types))?;
+        g.custom_op(op, vec![data])?.set_as_output()?;
+        g.finalize()?;
+        g.set_as_main()?;
+        c.finalize()?;
+        let instantiated_c = run_instantiation_pass(c)?.get_context();
+        let result = if is_mpc {
+            let inline_config = InlineConfig {
+                default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                ..Default::default()
+            };
+            let inlined_c = inline_operations(instantiated_c, inline_config.clone())?;
+            let mpc_c = prepare_for_mpc_evaluation(
+                inlined_c,
+                vec![vec![IOStatus::Party(0)]],
+                vec![vec![IOStatus::Party(0)]],
+                inline_config,
+            )?;
+            evaluate_simple_evaluator(
+                mpc_c.get_main_graph()?,
+                vec![Value::from_vector(columns)],
+                None,
+            )?
+        } else {
+            random_evaluate(
+                instantiated_c.get_main_graph()?,
+                vec![Value::from_vector(columns)],
+            )?
+        };
+        result.to_vector()
+    }
+
+    #[test]
+    fn test_select_columns() {
+        || -> Result<()> {
+            let op = || {
+                CustomOperation::new(SelectColumns {
+                    columns: vec![
+                        ("c".to_owned(), "x".to_owned()),
+                        ("a".to_owned(), "a".to_owned()),
+                        ("a".to_owned(), "y".to_owned()),
+                    ],
+                })
+            };
+            let column_types = vec![
+                ("a".to_owned(), array_type(vec![2], INT32)),
+                ("b".to_owned(), array_type(vec![2], BIT)),
+                ("c".to_owned(), array_type(vec![2, 3], INT64)),
+            ];
+            let a = Value::from_flattened_array(&[1, 2], INT32)?;
+            let b = Value::from_flattened_array(&[0, 1], BIT)?;
+            let c = Value::from_flattened_array(&[1, 2, 3, 4, 5, 6], INT64)?;
+            for is_mpc in [false, true] {
+                let result = evaluate_op(
+                    op(),
+                    column_types.clone(),
+                    vec![a.clone(), b.clone(), c.clone()],
+                    is_mpc,
+                )?;
+                assert_eq!(result, vec![c.clone(), a.clone(), a.clone()]);
+            }
+            // Check the output headers
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let data = g.input(named_tuple_type(column_types))?;
+            let result_t = g.custom_op(op(), vec![data])?.get_type()?;
+            assert_eq!(
+                result_t,
+                named_tuple_type(vec![
+                    ("x".to_owned(), array_type(vec![2, 3], INT64)),
+                    ("a".to_owned(), array_type(vec![2], INT32)),
+                    ("y".to_owned(), array_type(vec![2], INT32)),
+                ])
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_cast_columns() {
+        || -> Result<()> {
+            let column_types = vec![
+                ("a".to_owned(), array_type(vec![4], INT32)),
+                ("b".to_owned(), array_type(vec![2], UINT8)),
+                ("c".to_owned(), array_type(vec![2, 2], BIT)),
+            ];
+            let columns = vec![
+                Value::from_flattened_array(&[-1, 5, -100000, 70001], INT32)?,
+                Value::from_flattened_array(&[255, 1], UINT8)?,
+                Value::from_flattened_array(&[0, 1, 1, 0], BIT)?,
+            ];
+            let cast_helper = |casts: Vec<(&str, ScalarType)>, is_mpc: bool| {
+                evaluate_op(
+                    CustomOperation::new(CastColumns {
+                        columns: casts
+                            .into_iter()
+                            .map(|(h, st)| (h.to_owned(), st))
+                            .collect(),
+                    }),
+                    column_types.clone(),
+                    columns.clone(),
+                    is_mpc,
+                )
+            };
+            for is_mpc in [false, true] {
+                // Extension
+                assert_eq!(
+                    cast_helper(vec![("a", INT64), ("b", INT32), ("c", INT16)], is_mpc)?,
+                    vec![
+                        Value::from_flattened_array(&[-1, 5, -100000, 70001], INT64)?,
+                        Value::from_flattened_array(&[255, 1], INT32)?,
+                        Value::from_flattened_array(&[0, 1, 1, 0], INT16)?,
+                    ]
+                );
+                // Truncation
+                assert_eq!(
+                    cast_helper(vec![("a", INT16), ("b", BIT)], is_mpc)?,
+                    vec![
+                        Value::from_flattened_array(
+                            &[-1, 5, -100000i32 as i16, 70001i32 as i16],
+                            INT16
+                        )?,
+                        Value::from_flattened_array(&[1, 1], BIT)?,
+                        columns[2].clone(),
+                    ]
+                );
+                // Change of signedness
+                assert_eq!(
+                    cast_helper(vec![("a", UINT64), ("b", INT32)], is_mpc)?[0],
+                    Value::from_flattened_array(
+                        &[-1i32 as u64, 5, -100000i32 as u64, 70001],
+                        UINT64
+                    )?
+                );
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_filter() {
+        || -> Result<()> {
+            let column_types = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![5], BIT)),
+                ("a".to_owned(), array_type(vec![5], INT32)),
+                ("b".to_owned(), array_type(vec![5], INT32)),
+                ("c".to_owned(), array_type(vec![5], BIT)),
+                ("s".to_owned(), array_type(vec![5, 2], BIT)),
+                ("t".to_owned(), array_type(vec![5, 2], BIT)),
+            ];
+            let null = [1, 1, 1, 0, 1];
+            let a = [5, -3, 10, 7, 0];
+            let b = [5, 2, -1, 7, 1];
+            let c = [0, 1, 0, 1, 1];
+            // Binary strings encoding 1, 2, 3, 0, 2 and 2, 2, 1, 1, 0
+            let s = [1, 0, 0, 1, 1, 1, 0, 0, 0, 1];
+            let t = [0, 1, 0, 1, 1, 0, 1, 0, 0, 0];
+            let columns = vec![
+                Value::from_flattened_array(&null, BIT)?,
+                Value::from_flattened_array(&a, INT32)?,
+                Value::from_flattened_array(&b, INT32)?,
+                Value::from_flattened_array(&c, BIT)?,
+                Value::from_flattened_array(&s, BIT)?,
+                Value::from_flattened_array(&t, BIT)?,
+            ];
+            let expected = |mask: [i32; 5]| -> Result<Vec<Value>> {
+                let filter = |column: &[i32], row_size: usize| -> Vec<i32> {
+                    column
+                        .iter()
+                        .enumerate()
+                        .map(|(i, x)| x * mask[i / row_size])
+                        .collect()
+                };
+                Ok(vec![
+                    Value::from_flattened_array(&mask, BIT)?,
+                    Value::from_flattened_array(&filter(&a, 1), INT32)?,
+                    Value::from_flattened_array(&filter(&b, 1), INT32)?,
+                    Value::from_flattened_array(&filter(&c, 1), BIT)?,
+                    Value::from_flattened_array(&filter(&s, 2), BIT)?,
+                    Value::from_flattened_array(&filter(&t, 2), BIT)?,
+                ])
+            };
+            let column = |h: &str| Operand::Column(h.to_owned());
+            let test_cases = vec![
+                (
+                    Predicate::compare("a", Comparison::GreaterThanEqualTo, Operand::Constant(5)),
+                    [1, 0, 1, 0, 0],
+                ),
+                (
+                    Predicate::compare("a", Comparison::Equal, column("b")).or(Predicate::compare(
+                        "c",
+                        Comparison::NotEqual,
+                        Operand::Constant(0),
+                    )),
+                    [1, 1, 0, 0, 1],
+                ),
+                (
+                    !Predicate::compare("a", Comparison::LessThan, Operand::Constant(-1)),
+                    [1, 0, 1, 0, 1],
+                ),
+                (
+                    Predicate::compare("s", Comparison::LessThan, column("t")).and(
+                        Predicate::compare("b", Comparison::GreaterThan, Operand::Constant(0)),
+                    ),
+                    [1, 0, 0, 0, 0],
+                ),
+                (
+                    Predicate::compare("a", Comparison::LessThanEqualTo, column("b"))
+                        .and(!Predicate::compare("s", Comparison::Equal, column("t"))),
+                    [1, 0, 0, 0, 1],
+                ),
+            ];
+            for (predicate, mask) in test_cases {
+                for is_mpc in [false, true] {
+                    let result = evaluate_op(
+                        CustomOperation::new(Filter {
+                            predicate: predicate.clone(),
+                        }),
+                        column_types.clone(),
+                        columns.clone(),
+                        is_mpc,
+                    )?;
+                    assert_eq!(result, expected(mask)?);
+                }
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_group_by() {
+        || -> Result<()> {
+            let column_types = vec![
+                (NULL_HEADER.to_owned(), array_type(vec![6], BIT)),
+                ("k".to_owned(), array_type(vec![6], INT32)),
+                ("s".to_owned(), array_type(vec![6, 2], BIT)),
+                ("v".to_owned(), array_type(vec![6], INT32)),
+                ("w".to_owned(), array_type(vec![6, 2], INT64)),
+            ];
+            let columns = vec![
+                Value::from_flattened_array(&[1, 1, 1, 0, 1, 1], BIT)?,
+                Value::from_flattened_array(&[3, -1, 3, 5, 3, 5], INT32)?,
+                Value::from_flattened_array(&[0, 1, 0, 1, 1, 0, 0, 1, 0, 1, 1, 1], BIT)?,
+                Value::from_flattened_array(&[10, 20, 30, 40, 50, 60], INT32)?,
+                Value::from_flattened_array(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12], INT64)?,
+            ];
+            let group_by = |keys: Vec<&str>| {
+                CustomOperation::new(GroupBy {
+                    keys: keys.into_iter().map(|h| h.to_owned()).collect(),
+                    aggregates: vec![
+                        (Aggregate::Count, "count".to_owned()),
+                        (Aggregate::Sum("v".to_owned()), "sum_v".to_owned()),
+                        (Aggregate::Sum("w".to_owned()), "sum_w".to_owned()),
+                    ],
+                })
+            };
+            for is_mpc in [false, true] {
+                // Groups of rows with k = 3: {0, 2, 4}, k = -1: {1}, k = 5: {5}; row 3 is null
+                let result = evaluate_op(
+                    group_by(vec!["k"]),
+                    column_types.clone(),
+                    columns.clone(),
+                    is_mpc,
+                )?;
+                assert_eq!(
+                    result,
+                    vec![
+                        Value::from_flattened_array(&[1, 1, 0, 0, 0, 1], BIT)?,
+                        Value::from_flattened_array(&[3, -1, 0, 0, 0, 5], INT32)?,
+                        Value::from_flattened_array(&[3, 1, 0, 0, 0, 1], INT64)?,
+                        Value::from_flattened_array(&[90, 20, 0, 0, 0, 60], INT32)?,
+                        Value::from_flattened_array(
+                            &[15, 18, 3, 4, 0, 0, 0, 0, 0, 0, 11, 12],
+                            INT64
+                        )?,
+                    ]
+                );
+                // Groups of rows with (k, s) = (3, 01): {0, 4}, (-1, 01): {1}, (3, 10): {2}, (5, 11): {5}
+                let result = evaluate_op(
+                    group_by(vec!["k", "s"]),
+                    column_types.clone(),
+                    columns.clone(),
+                    is_mpc,
+                )?;
+                assert_eq!(
+                    result[0],
+                    Value::from_flattened_array(&[1, 1, 1, 0, 0, 1], BIT)?
+                );
+                assert_eq!(
+                    result[3],
+                    Value::from_flattened_array(&[2, 1, 1, 0, 0, 1], INT64)?
+                );
+                assert_eq!(
+                    result[4],
+                    Value::from_flattened_array(&[60, 20, 30, 0, 0, 60], INT32)?
+                );
+                // A single group of all non-null rows
+                let result = evaluate_op(
+                    group_by(vec![]),
+                    column_types.clone(),
+                    columns.clone(),
+                    is_mpc,
+                )?;
+                assert_eq!(
+                    result,
+                    vec![
+                        Value::from_flattened_array(&[1], BIT)?,
+                        Value::from_flattened_array(&[5], INT64)?,
+                        Value::from_flattened_array(&[170], INT32)?,
+                        Value::from_flattened_array(&[29, 34], INT64)?,
+                    ]
+                );
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_table_ops() {
+        let helper = |op: CustomOperation, t: Type| -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let data = g.input(t)?;
+            g.custom_op(op, vec![data])?;
+            Ok(())
+        };
+        let t = named_tuple_type(vec![
+            ("a".to_owned(), array_type(vec![2], INT32)),
+            ("b".to_owned(), array_type(vec![2], BIT)),
+        ]);
+        let select = |columns: Vec<(&str, &str)>| {
+            CustomOperation::new(SelectColumns {
+                columns: columns
+                    .into_iter()
+                    .map(|(h0, h1)| (h0.to_owned(), h1.to_owned()))
+                    .collect(),
+            })
+        };
+        let cast = |columns: Vec<(&str, ScalarType)>| {
+            CustomOperation::new(CastColumns {
+                columns: columns
+                    .into_iter()
+                    .map(|(h, st)| (h.to_owned(), st))
+                    .collect(),
+            })
+        };
+        assert!(helper(select(vec![("a", "b"), ("b", "a")]), t.clone()).is_ok());
+        assert!(helper(select(vec![]), t.clone()).is_err());
+        assert!(helper(select(vec![("c", "c")]), t.clone()).is_err());
+        assert!(helper(select(vec![("a", "c"), ("b", "c")]), t.clone()).is_err());
+        assert!(helper(select(vec![("a", "a")]), array_type(vec![2], INT32)).is_err());
+        assert!(helper(cast(vec![("a", INT64)]), t.clone()).is_ok());
+        assert!(helper(cast(vec![("c", INT64)]), t.clone()).is_err());
+        assert!(helper(cast(vec![("a", INT64), ("a", INT16)]), t).is_err());
+        assert!(helper(
+            cast(vec![("a", INT64)]),
+            named_tuple_type(vec![("a".to_owned(), scalar_type(INT32))])
+        )
+        .is_err());
+
+        let t = named_tuple_type(vec![
+            (NULL_HEADER.to_owned(), array_type(vec![2], BIT)),
+            ("a".to_owned(), array_type(vec![2], INT32)),
+            ("b".to_owned(), array_type(vec![2], INT64)),
+            ("c".to_owned(), array_type(vec![2, 3], INT32)),
+            ("s".to_owned(), array_type(vec![2, 3], BIT)),
+        ]);
+        let filter_helper = |predicate: Predicate, t: Type| -> Result<Node> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            g.input(t)?.filter(predicate)
+        };
+        let compare =
+            |h0: &str, operand: Operand| Predicate::compare(h0, Comparison::Equal, operand);
+        assert!(filter_helper(compare("a", Operand::Constant(1)), t.clone()).is_ok());
+        assert!(filter_helper(compare("d", Operand::Constant(1)), t.clone()).is_err());
+        assert!(filter_helper(compare("a", Operand::Column("d".to_owned())), t.clone()).is_err());
+        assert!(filter_helper(compare("a", Operand::Column("b".to_owned())), t.clone()).is_err());
+        assert!(filter_helper(compare("c", Operand::Constant(1)), t.clone()).is_err());
+        assert!(filter_helper(compare("s", Operand::Constant(1)), t.clone()).is_err());
+        assert!(filter_helper(
+            compare("a", Operand::Constant(1)),
+            named_tuple_type(vec![("a".to_owned(), array_type(vec![2], INT32))])
+        )
+        .is_err());
+        let group_by = |keys: Vec<&str>, aggregates: Vec<(Aggregate, &str)>| {
+            CustomOperation::new(GroupBy {
+                keys: keys.into_iter().map(|h| h.to_owned()).collect(),
+                aggregates: aggregates
+                    .into_iter()
+                    .map(|(a, h)| (a, h.to_owned()))
+                    .collect(),
+            })
+        };
+        let sum = |h: &str| Aggregate::Sum(h.to_owned());
+        assert!(helper(group_by(vec!["a", "s"], vec![(sum("c"), "x")]), t.clone()).is_ok());
+        assert!(helper(group_by(vec![], vec![]), t.clone()).is_err());
+        assert!(helper(group_by(vec!["d"], vec![]), t.clone()).is_err());
+        assert!(helper(group_by(vec!["c"], vec![]), t.clone()).is_err());
+        assert!(helper(group_by(vec!["a"], vec![(sum("d"), "x")]), t.clone()).is_err());
+        assert!(helper(group_by(vec!["a"], vec![(sum("s"), "x")]), t.clone()).is_err());
+        assert!(helper(group_by(vec!["a"], vec![(sum("b"), "a")]), t.clone()).is_err());
+        assert!(helper(group_by(vec![], vec![(Aggregate::Count, NULL_HEADER)]), t).is_err());
+    }
+}
@@ -178,6 +178,8 @@ impl CustomOperationBody for TaylorExponent {
     }
 }
 
+register_custom_operation!(TaylorExponent, [Array]);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,6 +1,6 @@
 use std::ops::Not;
 
-use crate::data_types::{array_type, scalar_type, ScalarType, Type, BIT};
+use crate::data_types::{array_type, scalar_type, ScalarType, Type, BIT, UINT64};
 use crate::data_values::Value;
 use crate::errors::Result;
 use crate::graphs::{Graph, Node};
@@ -91,6 +91,86 @@ pub fn multiply_fixed_point(node1: Node, node2: Node, precision: u64) -> Result<
     node1.multiply(node2)?.truncate(1 << precision)
 }
 
+/// Multiplies `x` by the binary `bits`, using [Node::multiply] for binary `x` and
+/// [Node::mixed_multiply] otherwise.
+pub fn multiply_by_bits(x: Node, bits: Node) -> Result<Node> {
+    if x.get_type()?.get_scalar_type() == BIT {
+        x.multiply(bits)
+    } else {
+        x.mixed_multiply(bits)
+    }
+}
+
+/// Multiplies each row of the array `x` (i.e. its subarray along the first axis) by the corresponding bit of the one-dimensional binary array `bits`.
+pub fn multiply_rows_by_bits(x: Node, bits: Node) -> Result<Node> {
+    let shape = x.get_type()?.get_shape();
+    let mut bits_shape = vec![shape[0]];
+    bits_shape.extend(vec![1; shape.len() - 1]);
+    multiply_by_bits(x, bits.reshape(array_type(bits_shape, BIT))?)
+}
+
+/// Concatenates arrays along the first axis; all the other dimensions must coincide.
+pub fn concatenate_first_axis(nodes: Vec<Node>) -> Result<Node> {
+    let g = nodes[0].get_graph();
+    g.concatenate(nodes, 0)
+}
+
+/// Returns `then_value` if the binary scalar `flag` is 1 and `else_value` otherwise,
+/// computed as `else_value + flag * (then_value - else_value)` without branching.
+///
+/// Both values must have the same type; tuples, named tuples and vectors are
+/// selected element-wise.
+pub fn select(flag: Node, then_value: Node, else_value: Node) -> Result<Node> {
+    let t = then_value.get_type()?;
+    if t != else_value.get_type()? {
+        return Err(runtime_error!("Selected values must have the same type"));
+    }
+    let g = then_value.get_graph();
+    match t {
+        Type::Scalar(_) | Type::Array(_, _) => {
+            let difference = then_value.subtract(else_value.clone())?;
+            else_value.add(multiply_by_bits(difference, flag)?)
+        }
+        Type::Tuple(types) => {
+            let mut elements = vec![];
+            for i in 0..types.len() as u64 {
+                elements.push(select(
+                    flag.clone(),
+                    then_value.tuple_get(i)?,
+                    else_value.tuple_get(i)?,
+                )?);
+            }
+            g.create_tuple(elements)
+        }
+        Type::NamedTuple(names_types) => {
+            let mut elements = vec![];
+            for (name, _) in names_types {
+                elements.push((
+                    name.clone(),
+                    select(
+                        flag.clone(),
+                        then_value.named_tuple_get(name.clone())?,
+                        else_value.named_tuple_get(name)?,
+                    )?,
+                ));
+            }
+            g.create_named_tuple(elements)
+        }
+        Type::Vector(n, element_type) => {
+            let mut elements = vec![];
+            for i in 0..n {
+                let index = constant_scalar(&g, i, UINT64)?;
+                elements.push(select(
+                    flag.clone(),
+                    then_value.vector_get(index.clone())?,
+                    else_value.vector_get(index)?,
+                )?);
+            }
+            g.create_vector((*element_type).clone(), elements)
+        }
+    }
+}
+
 /// Converts (individual) bits to 0/1 in arithmetic form.
 pub fn single_bit_to_arithmetic(node: Node, st: ScalarType) -> Result<Node> {
     let ones = if node.get_type()?.is_array() {
@@ -3,3 +3,5 @@ mod dangling_nodes_optimizer;
 mod duplicates_optimizer;
 mod meta_operation_optimizer;
 pub mod optimize;
+pub mod pass_manager;
+mod prf_optimizer;
@@ -2,7 +2,7 @@ use crate::data_types::Type;
 use crate::data_values::Value;
 use crate::errors::Result;
 use crate::evaluators::Evaluator;
-use crate::graphs::{copy_node_name, Graph, Node, Operation};
+use crate::graphs::{copy_node_name, copy_node_provenance, Graph, Node, Operation};
 use std::cmp::Eq;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
@@ -79,8 +79,10 @@ pub(super) fn optimize_graph_constants(
             }
             _ => {
                 let mut deps = vec![];
-                let mut is_const_node = !matches!(node.get_operation(), Operation::Input(_))
-                    && !matches!(node.get_operation(), Operation::Random(_));
+                let mut is_const_node = !matches!(
+                    node.get_operation(),
+                    Operation::Input(_) | Operation::Random(_) | Operation::RandomBeacon(_)
+                );
                 for dep in node.get_node_dependencies() {
                     let resolved_dep = node_mapping.get(&dep);
                     match resolved_dep {
@@ -108,6 +110,7 @@ pub(super) fn optimize_graph_constants(
                         result.add_annotation(annotation)?;
                     }
                     copy_node_name(node.clone(), result.clone())?;
+                    copy_node_provenance(node.clone(), result.clone())?;
                     result
                 }
             }
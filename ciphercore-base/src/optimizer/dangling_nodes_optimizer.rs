@@ -1,5 +1,5 @@
 use crate::errors::Result;
-use crate::graphs::{copy_node_name, Graph, Node, Operation};
+use crate::graphs::{copy_node_name, copy_node_provenance, Graph, Node, Operation};
 use std::collections::{HashMap, HashSet};
 
 /// This optimization removes the nodes from which the output node is not reachable.
@@ -42,6 +42,7 @@ pub(super) fn optimize_graph_dangling_nodes(graph: Graph, out_graph: Graph) -> R
             new_node.add_annotation(annotation)?;
         }
         copy_node_name(node.clone(), new_node.clone())?;
+        copy_node_provenance(node.clone(), new_node.clone())?;
         if node == graph.get_output_node()? {
             new_node.set_as_output()?;
         }
@@ -1,5 +1,5 @@
 use crate::errors::Result;
-use crate::graphs::{copy_node_name, Graph, Node, NodeAnnotation, Operation};
+use crate::graphs::{copy_node_name, copy_node_provenance, Graph, Node, NodeAnnotation, Operation};
 use std::cmp::Eq;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
@@ -66,6 +66,7 @@ pub(super) fn optimize_graph_duplicates(graph: Graph, out_graph: Graph) -> Resul
             Operation::Constant(_, _)
             | Operation::Input(_)
             | Operation::Random(_)
+            | Operation::RandomBeacon(_)
             | Operation::PRF(_, _) => {
                 // Don't try to de-duplicate these operations.
                 None
@@ -95,6 +96,7 @@ pub(super) fn optimize_graph_duplicates(graph: Graph, out_graph: Graph) -> Resul
                 new_node.add_annotation(annotation)?;
             }
             copy_node_name(node.clone(), new_node.clone())?;
+            copy_node_provenance(node.clone(), new_node.clone())?;
             new_node
         };
 
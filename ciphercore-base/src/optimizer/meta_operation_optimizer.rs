@@ -1,7 +1,7 @@
 use crate::bytes::vec_from_bytes;
 use crate::data_types::UINT64;
 use crate::errors::Result;
-use crate::graphs::{copy_node_name, Graph, Node, Operation, SliceElement};
+use crate::graphs::{copy_node_name, copy_node_provenance, Graph, Node, Operation, SliceElement};
 use std::collections::HashMap;
 use std::sync::Arc;
 
@@ -66,6 +66,7 @@ pub(super) fn optimize_graph_meta_operations(graph: Graph, out_graph: Graph) ->
         }
         let simple_node = out_graph.add_node(deps.clone(), vec![], node.get_operation())?;
         copy_node_name(node.clone(), simple_node.clone())?;
+        copy_node_provenance(node.clone(), simple_node.clone())?;
         let meta_node = match node.get_operation() {
             Operation::Constant(t, v) => {
                 // Note: for constants we're only caring about UINT64, since these can be
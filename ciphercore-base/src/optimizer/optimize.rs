@@ -1,58 +1,19 @@
 use crate::errors::Result;
 use crate::evaluators::Evaluator;
-use crate::graphs::{create_context, Context, Graph, Operation};
-use crate::optimizer::constant_optimizer::optimize_graph_constants;
-use crate::optimizer::dangling_nodes_optimizer::optimize_graph_dangling_nodes;
-use crate::optimizer::meta_operation_optimizer::optimize_graph_meta_operations;
+use crate::graphs::{Context, Operation};
+use crate::optimizer::pass_manager::PassManager;
 use crate::random::PRNG;
 
-use super::duplicates_optimizer::optimize_graph_duplicates;
-
 /// Applies common optimizations to all graphs in the context.
 /// The graphs must be fully inlined.
 /// The primary targets of the optimizations here are to remove inefficiencies
 /// which happen because of the boilerplate from Iterate inlining.
-pub fn optimize_context<T: Evaluator>(context: Context, mut evaluator: T) -> Result<Context> {
-    context.check_finalized()?;
-    evaluator.preprocess(context.clone())?;
-    let output_context = create_context()?;
-    for graph in context.get_graphs() {
-        let (_const_context, const_graph) = graph_in_new_context(graph.clone())?;
-        optimize_graph_constants(graph.clone(), const_graph.clone(), &mut evaluator)?;
-        const_graph.finalize()?;
-
-        let (_meta_context, meta_graph) = graph_in_new_context(graph.clone())?;
-        optimize_graph_meta_operations(const_graph.clone(), meta_graph.clone())?;
-        meta_graph.finalize()?;
-
-        let (_dup_context, dup_graph) = graph_in_new_context(graph.clone())?;
-        optimize_graph_duplicates(meta_graph.clone(), dup_graph.clone())?;
-        dup_graph.finalize()?;
-
-        let final_graph = add_graph_to_context(output_context.clone(), graph.clone())?;
-        optimize_graph_dangling_nodes(dup_graph.clone(), final_graph.clone())?;
-        final_graph.finalize()?;
-
-        if graph == context.get_main_graph()? {
-            final_graph.set_as_main()?;
-        }
-    }
-    output_context.finalize()?;
-    Ok(output_context)
-}
-
-fn add_graph_to_context(context: Context, source_graph: Graph) -> Result<Graph> {
-    let new_graph = context.create_graph()?;
-    for annotation in source_graph.get_annotations()? {
-        new_graph.add_annotation(annotation)?;
-    }
-    Ok(new_graph)
-}
-
-fn graph_in_new_context(source_graph: Graph) -> Result<(Context, Graph)> {
-    let context = create_context()?;
-    let graph = add_graph_to_context(context.clone(), source_graph)?;
-    Ok((context, graph))
+///
+/// The optimizations are applied as passes of a [PassManager] (see [PassManager::add_optimization_passes]).
+pub fn optimize_context<T: Evaluator>(context: Context, evaluator: T) -> Result<Context> {
+    PassManager::new()
+        .add_optimization_passes(evaluator)
+        .run(context)
 }
 
 #[doc(hidden)]
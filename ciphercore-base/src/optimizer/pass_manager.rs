@@ -0,0 +1,485 @@
+//! Composition of compiler passes with per-pass statistics.
+//!
+//! A [Pass] transforms a finalized context into a new finalized context.
+//! A [PassManager] applies a sequence of passes and records the number of nodes before and after every pass.
+//! The built-in passes cover custom operation instantiation, inlining and the optimizations applied by [optimize_context](crate::optimizer::optimize::optimize_context);
+//! MPC compilation is provided by [MpcCompilationPass](crate::mpc::mpc_compiler::MpcCompilationPass),
+//! [MpcCompilationWithOutputPolicyPass](crate::mpc::mpc_compiler::MpcCompilationWithOutputPolicyPass) and [GarbledMpcCompilationPass](crate::mpc::mpc_compiler::GarbledMpcCompilationPass).
+//! New passes can be added by implementing [Pass].
+//!
+//! # Example
+//!
+//! ```
+//! # use ciphercore_base::graphs::create_context;
+//! # use ciphercore_base::data_types::{scalar_type, INT32};
+//! # use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
+//! # use ciphercore_base::inline::inline_ops::InlineConfig;
+//! # use ciphercore_base::optimizer::pass_manager::{InliningPass, InstantiationPass, PassManager};
+//! let c = create_context().unwrap();
+//! let g = c.create_graph().unwrap();
+//! let x = g.input(scalar_type(INT32)).unwrap();
+//! x.add(x.clone()).unwrap().add(x.add(x.clone()).unwrap()).unwrap().set_as_output().unwrap();
+//! g.finalize().unwrap().set_as_main().unwrap();
+//! c.finalize().unwrap();
+//! let mut pass_manager = PassManager::new()
+//!     .add_pass(InstantiationPass)
+//!     .add_pass(InliningPass { config: InlineConfig::default() })
+//!     .add_optimization_passes(SimpleEvaluator::new(None).unwrap());
+//! let optimized_c = pass_manager.run(c).unwrap();
+//! let statistics = pass_manager.get_statistics();
+//! assert_eq!(statistics[0].nodes_before, 4);
+//! assert_eq!(statistics.last().unwrap().nodes_after, 3);
+//! ```
+use crate::custom_ops::run_instantiation_pass;
+use crate::errors::Result;
+use crate::evaluators::Evaluator;
+use crate::graphs::{create_context, Context, Graph};
+use crate::inline::inline_ops::{inline_operations, InlineConfig};
+use crate::optimizer::constant_optimizer::optimize_graph_constants;
+use crate::optimizer::dangling_nodes_optimizer::optimize_graph_dangling_nodes;
+use crate::optimizer::duplicates_optimizer::optimize_graph_duplicates;
+use crate::optimizer::meta_operation_optimizer::optimize_graph_meta_operations;
+use crate::optimizer::prf_optimizer::optimize_graph_prfs;
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Transformation of a finalized context into a new finalized context.
+pub trait Pass {
+    /// Returns the name of the pass used in statistics.
+    fn get_name(&self) -> String;
+
+    /// Applies the pass to a finalized context.
+    fn run(&mut self, context: Context) -> Result<Context>;
+}
+
+/// Statistics of a single pass run by a [PassManager].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PassStatistics {
+    /// Name of the pass
+    pub name: String,
+    /// Number of nodes in all the graphs of the context before the pass
+    pub nodes_before: u64,
+    /// Number of nodes in all the graphs of the context after the pass
+    pub nodes_after: u64,
+    /// Running time of the pass
+    pub duration: Duration,
+}
+
+impl fmt::Display for PassStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {} nodes in {:.3}s",
+            self.name,
+            self.nodes_before,
+            self.nodes_after,
+            self.duration.as_secs_f64()
+        )
+    }
+}
+
+/// Sequence of passes applied to a context one after another.
+///
+/// Statistics of the last [run](PassManager::run) are available via [get_statistics](PassManager::get_statistics).
+#[derive(Default)]
+pub struct PassManager<'a> {
+    passes: Vec<Box<dyn Pass + 'a>>,
+    statistics: Vec<PassStatistics>,
+}
+
+impl<'a> PassManager<'a> {
+    /// Creates a pass manager without passes.
+    pub fn new() -> Self {
+        PassManager {
+            passes: vec![],
+            statistics: vec![],
+        }
+    }
+
+    /// Appends a pass to the sequence.
+    pub fn add_pass<P: Pass + 'a>(mut self, pass: P) -> Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// Appends the passes of [optimize_context](crate::optimizer::optimize::optimize_context):
+    /// constant folding, meta-operation optimization, common subexpression elimination, dead node elimination and PRF optimization.
+    ///
+    /// These passes work only on fully inlined contexts.
+    pub fn add_optimization_passes<E: Evaluator + 'a>(self, evaluator: E) -> Self {
+        self.add_pass(ConstantFoldingPass { evaluator })
+            .add_pass(MetaOperationPass)
+            .add_pass(CommonSubexpressionEliminationPass)
+            .add_pass(DeadNodeEliminationPass)
+            .add_pass(PrfOptimizationPass)
+    }
+
+    /// Returns the names of the passes in the order of their application.
+    pub fn get_pass_names(&self) -> Vec<String> {
+        self.passes.iter().map(|pass| pass.get_name()).collect()
+    }
+
+    /// Applies all the passes to a finalized context and returns the resulting context.
+    pub fn run(&mut self, context: Context) -> Result<Context> {
+        context.check_finalized()?;
+        self.statistics.clear();
+        let mut context = context;
+        for pass in self.passes.iter_mut() {
+            let nodes_before = count_nodes(&context);
+            let _span = tracing::info_span!(
+                "compiler_pass",
+                pass = %pass.get_name(),
+                nodes_before
+            )
+            .entered();
+            let start = Instant::now();
+            context = pass.run(context)?;
+            let duration = start.elapsed();
+            context.check_finalized()?;
+            self.statistics.push(PassStatistics {
+                name: pass.get_name(),
+                nodes_before,
+                nodes_after: count_nodes(&context),
+                duration,
+            });
+        }
+        Ok(context)
+    }
+
+    /// Returns the statistics of the passes applied by the last [run](PassManager::run).
+    pub fn get_statistics(&self) -> Vec<PassStatistics> {
+        self.statistics.clone()
+    }
+}
+
+fn count_nodes(context: &Context) -> u64 {
+    context
+        .get_graphs()
+        .iter()
+        .map(|graph| graph.get_nodes().len() as u64)
+        .sum()
+}
+
+fn add_graph_to_context(context: Context, source_graph: Graph) -> Result<Graph> {
+    let new_graph = context.create_graph()?;
+    for annotation in source_graph.get_annotations()? {
+        new_graph.add_annotation(annotation)?;
+    }
+    Ok(new_graph)
+}
+
+// Copies every graph of a fully inlined context to a new context with a given graph optimization
+fn optimize_graphs<F>(context: Context, mut optimize_graph: F) -> Result<Context>
+where
+    F: FnMut(Graph, Graph) -> Result<()>,
+{
+    let output_context = create_context()?;
+    let main_graph = context.get_main_graph()?;
+    for graph in context.get_graphs() {
+        let new_graph = add_graph_to_context(output_context.clone(), graph.clone())?;
+        optimize_graph(graph.clone(), new_graph.clone())?;
+        new_graph.finalize()?;
+        if graph == main_graph {
+            new_graph.set_as_main()?;
+        }
+    }
+    output_context.finalize()?;
+    Ok(output_context)
+}
+
+/// Pass that instantiates all custom operations (see [run_instantiation_pass]).
+pub struct InstantiationPass;
+
+impl Pass for InstantiationPass {
+    fn get_name(&self) -> String {
+        "Instantiation".to_owned()
+    }
+
+    fn run(&mut self, context: Context) -> Result<Context> {
+        Ok(run_instantiation_pass(context)?.get_context())
+    }
+}
+
+/// Pass that inlines graph calls and iterations (see [inline_operations]).
+pub struct InliningPass {
+    /// Inlining configuration
+    pub config: InlineConfig,
+}
+
+impl Pass for InliningPass {
+    fn get_name(&self) -> String {
+        "Inlining".to_owned()
+    }
+
+    fn run(&mut self, context: Context) -> Result<Context> {
+        inline_operations(context, self.config.clone())
+    }
+}
+
+/// Pass that evaluates the nodes depending only on constants and replaces them with constants.
+pub struct ConstantFoldingPass<E: Evaluator> {
+    /// Evaluator computing the values of constant nodes
+    pub evaluator: E,
+}
+
+impl<E: Evaluator> Pass for ConstantFoldingPass<E> {
+    fn get_name(&self) -> String {
+        "ConstantFolding".to_owned()
+    }
+
+    fn run(&mut self, context: Context) -> Result<Context> {
+        self.evaluator.preprocess(context.clone())?;
+        let evaluator = &mut self.evaluator;
+        optimize_graphs(context, |graph, out_graph| {
+            optimize_graph_constants(graph, out_graph, &mut *evaluator)
+        })
+    }
+}
+
+/// Pass that removes redundant meta-operations, e.g. chains of tuple creation and extraction.
+pub struct MetaOperationPass;
+
+impl Pass for MetaOperationPass {
+    fn get_name(&self) -> String {
+        "MetaOperations".to_owned()
+    }
+
+    fn run(&mut self, context: Context) -> Result<Context> {
+        optimize_graphs(context, optimize_graph_meta_operations)
+    }
+}
+
+/// Pass that merges nodes computing the same operation on the same dependencies.
+pub struct CommonSubexpressionEliminationPass;
+
+impl Pass for CommonSubexpressionEliminationPass {
+    fn get_name(&self) -> String {
+        "CommonSubexpressionElimination".to_owned()
+    }
+
+    fn run(&mut self, context: Context) -> Result<Context> {
+        optimize_graphs(context, optimize_graph_duplicates)
+    }
+}
+
+/// Pass that removes the nodes the output doesn't depend on.
+pub struct DeadNodeEliminationPass;
+
+impl Pass for DeadNodeEliminationPass {
+    fn get_name(&self) -> String {
+        "DeadNodeElimination".to_owned()
+    }
+
+    fn run(&mut self, context: Context) -> Result<Context> {
+        optimize_graphs(context, optimize_graph_dangling_nodes)
+    }
+}
+
+/// Pass that merges PRF invocations with the same key.
+pub struct PrfOptimizationPass;
+
+impl Pass for PrfOptimizationPass {
+    fn get_name(&self) -> String {
+        "PrfOptimization".to_owned()
+    }
+
+    fn run(&mut self, context: Context) -> Result<Context> {
+        optimize_graphs(context, optimize_graph_prfs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use crate::data_types::{scalar_type, INT32};
+    use crate::data_values::Value;
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::graphs::Node;
+    use crate::mpc::mpc_compiler::{
+        GarbledMpcCompilationPass, IOStatus, MpcBackend, MpcCompilationPass,
+        MpcCompilationWithOutputPolicyPass,
+    };
+    use crate::mpc::output_policy::OutputPolicy;
+    use crate::optimizer::optimize::stress_test;
+
+    // Doubles the output of every graph
+    struct DoublingPass;
+
+    impl Pass for DoublingPass {
+        fn get_name(&self) -> String {
+            "Doubling".to_owned()
+        }
+
+        fn run(&mut self, context: Context) -> Result<Context> {
+            optimize_graphs(context, |graph, out_graph| {
+                let mut node_mapping = HashMap::<u64, Node>::new();
+                for node in graph.get_nodes() {
+                    let deps = node
+                        .get_node_dependencies()
+                        .iter()
+                        .map(|dep| node_mapping[&dep.get_id()].clone())
+                        .collect();
+                    let new_node = out_graph.add_node(deps, vec![], node.get_operation())?;
+                    node_mapping.insert(node.get_id(), new_node);
+                }
+                let output = node_mapping[&graph.get_output_node()?.get_id()].clone();
+                output.add(output.clone())?.set_as_output()?;
+                Ok(())
+            })
+        }
+    }
+
+    fn get_test_context() -> Result<Context> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let x = g.input(scalar_type(INT32))?;
+        let y = x.add(x.clone())?;
+        let z = x.add(x.clone())?;
+        y.multiply(z)?.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        Ok(c)
+    }
+
+    #[test]
+    fn test_pass_manager_statistics() {
+        || -> Result<()> {
+            let c = get_test_context()?;
+            let mut pass_manager = PassManager::new()
+                .add_pass(DoublingPass)
+                .add_optimization_passes(SimpleEvaluator::new(None)?);
+            assert_eq!(
+                pass_manager.get_pass_names(),
+                vec![
+                    "Doubling",
+                    "ConstantFolding",
+                    "MetaOperations",
+                    "CommonSubexpressionElimination",
+                    "DeadNodeElimination",
+                    "PrfOptimization"
+                ]
+            );
+            let optimized_c = pass_manager.run(c.clone())?;
+            let statistics = pass_manager.get_statistics();
+            assert_eq!(statistics.len(), 6);
+            assert_eq!(statistics[0].nodes_before, 4);
+            assert!(statistics[0].nodes_after > statistics[0].nodes_before);
+            for pair in statistics.windows(2) {
+                assert_eq!(pair[0].nodes_after, pair[1].nodes_before);
+            }
+            assert!(statistics[5].nodes_after < statistics[0].nodes_after);
+            assert!(format!("{}", statistics[0]).starts_with("Doubling: 4 -> "));
+
+            let x = Value::from_scalar(5, INT32)?;
+            let result = random_evaluate(optimized_c.get_main_graph()?, vec![x])?;
+            assert_eq!(result.to_i32(INT32)?, 200);
+
+            // Statistics are reset by every run
+            pass_manager.run(c)?;
+            assert_eq!(pass_manager.get_statistics().len(), 6);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_pass_manager_compilation() {
+        || -> Result<()> {
+            let c = get_test_context()?;
+            let optimized_c = PassManager::new()
+                .add_pass(InstantiationPass)
+                .add_pass(InliningPass {
+                    config: InlineConfig::default(),
+                })
+                .add_optimization_passes(SimpleEvaluator::new(None)?)
+                .run(c.clone())?;
+            stress_test(
+                c.clone(),
+                optimized_c,
+                SimpleEvaluator::new(None)?,
+                SimpleEvaluator::new(None)?,
+            )?;
+
+            let mut pass_manager = PassManager::new()
+                .add_pass(MpcCompilationPass {
+                    input_parties: vec![IOStatus::Party(0)],
+                    output_parties: vec![IOStatus::Party(1)],
+                    inline_config: InlineConfig::default(),
+                    backend: MpcBackend::ABY3,
+                })
+                .add_optimization_passes(SimpleEvaluator::new(None)?);
+            let compiled_c = pass_manager.run(c)?;
+            let statistics = pass_manager.get_statistics();
+            assert_eq!(statistics[0].name, "MpcCompilation(ABY3)");
+            assert!(statistics[0].nodes_after > statistics[0].nodes_before);
+            let x = Value::from_scalar(3, INT32)?;
+            let result = random_evaluate(compiled_c.get_main_graph()?, vec![x])?;
+            assert_eq!(result.to_i32(INT32)?, 36);
+
+            assert!(PassManager::new()
+                .add_pass(MpcCompilationPass {
+                    input_parties: vec![],
+                    output_parties: vec![IOStatus::Party(1)],
+                    inline_config: InlineConfig::default(),
+                    backend: MpcBackend::ABY3,
+                })
+                .run(get_test_context()?)
+                .is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_pass_manager_compilation_variants() {
+        || -> Result<()> {
+            let c = get_test_context()?;
+            let mut pass_manager = PassManager::new()
+                .add_pass(InstantiationPass)
+                .add_pass(InliningPass {
+                    config: InlineConfig::default(),
+                })
+                .add_pass(MpcCompilationWithOutputPolicyPass {
+                    input_parties: vec![IOStatus::Party(0)],
+                    output_policy: OutputPolicy::RevealTo(vec![1, 2]),
+                    inline_config: InlineConfig::default(),
+                })
+                .add_optimization_passes(SimpleEvaluator::new(None)?);
+            let compiled_c = pass_manager.run(c.clone())?;
+            assert_eq!(
+                pass_manager.get_statistics()[2].name,
+                "MpcCompilationWithOutputPolicy"
+            );
+            let x = Value::from_scalar(3, INT32)?;
+            let result = random_evaluate(compiled_c.get_main_graph()?, vec![x.clone()])?;
+            assert_eq!(result.to_i32(INT32)?, 36);
+
+            let mut pass_manager = PassManager::new()
+                .add_pass(InstantiationPass)
+                .add_pass(InliningPass {
+                    config: InlineConfig::default(),
+                })
+                .add_pass(GarbledMpcCompilationPass {
+                    input_parties: vec![IOStatus::Party(0)],
+                    output_parties: vec![IOStatus::Party(1)],
+                    inline_config: InlineConfig::default(),
+                    depth_threshold: 1,
+                })
+                .add_optimization_passes(SimpleEvaluator::new(None)?);
+            let compiled_c = pass_manager.run(c)?;
+            assert_eq!(
+                pass_manager.get_statistics()[2].name,
+                "GarbledMpcCompilation(1)"
+            );
+            let result = random_evaluate(compiled_c.get_main_graph()?, vec![x])?;
+            assert_eq!(result.to_i32(INT32)?, 36);
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
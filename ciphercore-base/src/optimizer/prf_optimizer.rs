@@ -0,0 +1,149 @@
+use crate::data_types::{array_type, ScalarType, Type};
+use crate::errors::Result;
+use crate::graphs::{copy_node_name, copy_node_provenance, Graph, Node, Operation, SliceElement};
+use std::collections::HashMap;
+
+fn get_number_of_elements(t: &Type) -> u64 {
+    if t.is_array() {
+        t.get_shape().iter().product()
+    } else {
+        1
+    }
+}
+
+// PRF nodes are grouped by the ID of their key node and the scalar type of their output
+fn get_prf_group(node: &Node) -> Option<(u64, ScalarType)> {
+    match node.get_operation() {
+        Operation::PRF(_, t) if t.is_scalar() || t.is_array() => Some((
+            node.get_node_dependencies()[0].get_id(),
+            t.get_scalar_type(),
+        )),
+        _ => None,
+    }
+}
+
+/// This optimization assumes that the graph is fully inlined.
+/// It merges PRF nodes that have the same key and generate scalars or arrays of the same scalar type
+/// into one PRF node generating a flat array. Every merged node is replaced by a slice of this array.
+/// Thus, masks generated in bulk (e.g., by the Permutation and Duplication protocols) are expanded by one PRF call per key,
+/// which reduces the evaluation overhead and the number of PRF events in transcripts.
+///
+/// The merged node takes the IV of the first node of its group, so IVs stay unique if they were unique before.
+/// This function preserves annotations and names of nodes.
+pub(super) fn optimize_graph_prfs(graph: Graph, out_graph: Graph) -> Result<()> {
+    graph.check_finalized()?;
+    // Number of nodes and total number of generated elements of every group of PRF nodes
+    let mut group_sizes = HashMap::<(u64, ScalarType), (u64, u64)>::new();
+    for node in graph.get_nodes() {
+        if let Some(group) = get_prf_group(&node) {
+            let size = get_number_of_elements(&node.get_type()?);
+            let entry = group_sizes.entry(group).or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 += size;
+        }
+    }
+    // Nodes of the output graph indexed by IDs of the corresponding input nodes
+    let mut node_mapping = HashMap::<u64, Node>::new();
+    // Merged PRF nodes and the number of their elements that have been used
+    let mut merged_prfs = HashMap::<(u64, ScalarType), (Node, u64)>::new();
+    for node in graph.get_nodes() {
+        if !node.get_graph_dependencies().is_empty() {
+            return Err(runtime_error!(
+                "Graph must be fully inlined to use the optimizer"
+            ));
+        }
+        let mut deps = vec![];
+        for dep in node.get_node_dependencies() {
+            deps.push(node_mapping.get(&dep.get_id()).unwrap().clone());
+        }
+        let group = get_prf_group(&node).filter(|group| group_sizes[group].0 > 1);
+        let new_node = match (group, node.get_operation()) {
+            (Some(group), Operation::PRF(iv, t)) => {
+                if !merged_prfs.contains_key(&group) {
+                    let merged_t = array_type(vec![group_sizes[&group].1], group.1.clone());
+                    let merged_prf = out_graph.prf(deps[0].clone(), iv, merged_t)?;
+                    merged_prfs.insert(group.clone(), (merged_prf, 0));
+                }
+                let (merged_prf, offset) = merged_prfs.get_mut(&group).unwrap();
+                let size = get_number_of_elements(&t);
+                let slice = merged_prf.get_slice(vec![SliceElement::SubArray(
+                    Some(*offset as i64),
+                    Some((*offset + size) as i64),
+                    None,
+                )])?;
+                *offset += size;
+                slice.reshape(t)?
+            }
+            (_, op) => out_graph.add_node(deps, vec![], op)?,
+        };
+        for annotation in node.get_annotations()? {
+            new_node.add_annotation(annotation)?;
+        }
+        copy_node_name(node.clone(), new_node.clone())?;
+        copy_node_provenance(node.clone(), new_node.clone())?;
+        if node == graph.get_output_node()? {
+            new_node.set_as_output()?;
+        }
+        node_mapping.insert(node.get_id(), new_node);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{scalar_type, tuple_type, BIT, UINT64};
+    use crate::data_values::Value;
+    use crate::evaluators::random_evaluate;
+    use crate::graphs::create_context;
+
+    fn count_prfs(graph: &Graph) -> usize {
+        graph
+            .get_nodes()
+            .iter()
+            .filter(|node| matches!(node.get_operation(), Operation::PRF(_, _)))
+            .count()
+    }
+
+    #[test]
+    fn test_merge_prfs() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let key_t = array_type(vec![128], BIT);
+            let key0 = g.input(key_t.clone())?;
+            let key1 = g.input(key_t)?;
+            let a = key0.prf(1, array_type(vec![2, 3], UINT64))?;
+            a.set_name("a")?;
+            let b = key0.prf(2, scalar_type(UINT64))?;
+            let bits = key0.prf(3, array_type(vec![5], BIT))?;
+            let other_key = key1.prf(4, scalar_type(UINT64))?;
+            let tuple = key0.prf(5, tuple_type(vec![scalar_type(UINT64)]))?;
+            let more_bits = key0.prf(6, array_type(vec![7], BIT))?;
+            g.create_tuple(vec![a, b, bits, other_key, tuple, more_bits])?
+                .set_as_output()?;
+            g.finalize()?;
+            g.set_as_main()?;
+            c.finalize()?;
+
+            let new_c = create_context()?;
+            let new_g = new_c.create_graph()?;
+            optimize_graph_prfs(g.clone(), new_g.clone())?;
+            new_g.finalize()?;
+            new_g.set_as_main()?;
+            new_c.finalize()?;
+
+            assert_eq!(count_prfs(&g), 6);
+            // UINT64 and BIT nodes with key0 are merged, while the node with another key and the tuple node are kept
+            assert_eq!(count_prfs(&new_g), 4);
+            assert!(new_c.retrieve_node(new_g.clone(), "a").is_ok());
+            let output_type = g.get_output_node()?.get_type()?;
+            assert_eq!(new_g.get_output_node()?.get_type()?, output_type);
+            let key_value = Value::from_bytes(vec![7; 16]);
+            let result = random_evaluate(new_g, vec![key_value.clone(), key_value])?;
+            assert!(result.check_type(output_type)?);
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
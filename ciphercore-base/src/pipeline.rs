@@ -0,0 +1,363 @@
+//! High-level interface covering the whole lifecycle of a computation: building, compiling, serializing and evaluating.
+//!
+//! A [Pipeline] wraps a context and tracks at the type level whether the context is compiled,
+//! so that only the operations valid at the current stage are available, e.g.
+//!
+//! ```
+//! # use ciphercore_base::prelude::*;
+//! # fn main() -> Result<()> {
+//! let c = create_context()?;
+//! let g = c.create_graph()?;
+//! let x = g.input(scalar_type(INT32))?;
+//! let y = g.input(scalar_type(INT32))?;
+//! x.multiply(y)?.set_as_output()?;
+//! g.finalize()?.set_as_main()?;
+//! c.finalize()?;
+//!
+//! // Party 0 provides x, party 1 provides y, and the product is revealed to party 2.
+//! let compiled = Pipeline::new(c)?
+//!     .inline_mode(InlineMode::Simple)
+//!     .compile(
+//!         vec![IOStatus::Party(0), IOStatus::Party(1)],
+//!         vec![IOStatus::Party(2)],
+//!     )?;
+//! let serialized = compiled.serialize()?;
+//!
+//! let compiled = Pipeline::deserialize(&serialized, vec![IOStatus::Party(2)])?;
+//! let inputs = vec![
+//!     TypedValue::new(scalar_type(INT32), Value::from_scalar(6, INT32)?)?,
+//!     TypedValue::new(scalar_type(INT32), Value::from_scalar(-7, INT32)?)?,
+//! ];
+//! let result = compiled.evaluate(inputs)?;
+//! assert_eq!(result.value.to_i32(INT32)?, -42);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Compilation targets the ABY3 protocol.
+//! The serialized form of a compiled pipeline is the serialized compiled context, i.e. the same as the output of the `ciphercore_compile` binary.
+//!
+//! Contexts are specialized to fixed shapes of inputs.
+//! To serve databases of variable sizes with one compiled context, build it for the maximal numbers of rows and evaluate it with [Pipeline::evaluate_padded].
+use crate::data_types::Type;
+use crate::errors::Result;
+use crate::evaluators::get_result_util::get_evaluator_result;
+use crate::evaluators::simple_evaluator::SimpleEvaluator;
+use crate::graphs::{Context, Operation};
+use crate::inline::inline_ops::{InlineConfig, InlineMode};
+use crate::io::padding::pad_database_to_type;
+use crate::mpc::mpc_compiler::{compile_context, IOStatus, PARTIES};
+use crate::typed_value::TypedValue;
+
+/// Stage of a [Pipeline] whose context is built, but not compiled yet.
+pub struct Built {
+    inline_config: InlineConfig,
+}
+
+/// Stage of a [Pipeline] whose context is compiled into an MPC protocol.
+pub struct Compiled {
+    reveal_output: bool,
+}
+
+/// Finalized context at a given stage of its lifecycle (either [Built] or [Compiled]).
+pub struct Pipeline<S> {
+    context: Context,
+    stage: S,
+}
+
+impl<S> Pipeline<S> {
+    /// Returns the context at the current stage.
+    pub fn get_context(&self) -> Context {
+        self.context.clone()
+    }
+
+    // Pads databases with fewer rows than the corresponding inputs of the main graph.
+    // Secret-shared inputs of a compiled context are given in plaintext, so databases are padded to the type of one share.
+    fn pad_inputs(&self, inputs: Vec<TypedValue>) -> Result<Vec<TypedValue>> {
+        let mut input_types = vec![];
+        for node in self.context.get_main_graph()?.get_nodes() {
+            if let Operation::Input(t) = node.get_operation() {
+                input_types.push(t);
+            }
+        }
+        if inputs.len() != input_types.len() {
+            return Err(runtime_error!(
+                "Incorrect number of inputs: {} expected, but {} provided",
+                input_types.len(),
+                inputs.len()
+            ));
+        }
+        let mut padded_inputs = vec![];
+        for (input, input_t) in inputs.into_iter().zip(input_types) {
+            if !input.t.is_named_tuple() || input.t == input_t {
+                padded_inputs.push(input);
+                continue;
+            }
+            let target_t = match &input_t {
+                Type::Tuple(share_types) if share_types.len() == PARTIES => {
+                    (*share_types[0]).clone()
+                }
+                _ => input_t,
+            };
+            let padded_value = pad_database_to_type(input.t, input.value, target_t.clone())?;
+            padded_inputs.push(TypedValue::new(target_t, padded_value)?);
+        }
+        Ok(padded_inputs)
+    }
+}
+
+impl Pipeline<Built> {
+    /// Creates a pipeline from a finalized context.
+    ///
+    /// By default, the context is inlined in the simple mode during compilation.
+    pub fn new(context: Context) -> Result<Self> {
+        context.check_finalized()?;
+        Ok(Pipeline {
+            context,
+            stage: Built {
+                inline_config: InlineConfig {
+                    default_mode: InlineMode::Simple,
+                    ..Default::default()
+                },
+            },
+        })
+    }
+
+    /// Sets the inlining mode of all the graphs called or iterated in the context.
+    pub fn inline_mode(self, mode: InlineMode) -> Self {
+        self.inline_config(InlineConfig {
+            default_mode: mode,
+            ..Default::default()
+        })
+    }
+
+    /// Sets the inlining configuration used during compilation.
+    pub fn inline_config(mut self, config: InlineConfig) -> Self {
+        self.stage.inline_config = config;
+        self
+    }
+
+    /// Evaluates the context in plaintext on given inputs.
+    ///
+    /// The result serves as a reference for the result of the compiled protocol.
+    pub fn evaluate(&self, inputs: Vec<TypedValue>) -> Result<TypedValue> {
+        get_evaluator_result(
+            self.context.clone(),
+            inputs,
+            false,
+            SimpleEvaluator::new(None)?,
+        )
+    }
+
+    /// Evaluates the context in plaintext on given inputs padding databases with fewer rows than the corresponding inputs.
+    ///
+    /// The result serves as a reference for the result of [Pipeline::evaluate_padded] of the compiled pipeline.
+    pub fn evaluate_padded(&self, inputs: Vec<TypedValue>) -> Result<TypedValue> {
+        self.evaluate(self.pad_inputs(inputs)?)
+    }
+
+    /// Compiles the context into the ABY3 protocol.
+    ///
+    /// # Arguments
+    ///
+    /// * `input_parties` - owners of the inputs of the main graph, one per input
+    /// * `output_parties` - parties receiving the output; if empty, the output stays secret-shared
+    pub fn compile(
+        self,
+        input_parties: Vec<IOStatus>,
+        output_parties: Vec<IOStatus>,
+    ) -> Result<Pipeline<Compiled>> {
+        let reveal_output = output_parties.is_empty();
+        let context = compile_context(
+            self.context,
+            input_parties,
+            output_parties,
+            self.stage.inline_config,
+            || SimpleEvaluator::new(None),
+        )?;
+        Ok(Pipeline {
+            context,
+            stage: Compiled { reveal_output },
+        })
+    }
+}
+
+impl Pipeline<Compiled> {
+    /// Restores a compiled pipeline from its serialized form.
+    ///
+    /// `output_parties` must be the same as the ones the context was compiled with.
+    pub fn deserialize(serialized: &str, output_parties: Vec<IOStatus>) -> Result<Self> {
+        let context = serde_json::from_str::<Context>(serialized)?;
+        context.check_finalized()?;
+        Ok(Pipeline {
+            context,
+            stage: Compiled {
+                reveal_output: output_parties.is_empty(),
+            },
+        })
+    }
+
+    /// Serializes the compiled context into a JSON string.
+    pub fn serialize(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self.context)?)
+    }
+
+    /// Simulates the execution of the compiled protocol by all the parties on given inputs.
+    ///
+    /// Values of secret-shared inputs are given in plaintext and shared at random.
+    /// Secret-shared outputs are revealed, so the result can be compared to the one of [Pipeline::evaluate] before compilation.
+    pub fn evaluate(&self, inputs: Vec<TypedValue>) -> Result<TypedValue> {
+        get_evaluator_result(
+            self.context.clone(),
+            inputs,
+            self.stage.reveal_output,
+            SimpleEvaluator::new(None)?,
+        )
+    }
+
+    /// Simulates the execution of the compiled protocol on given inputs, where databases can have fewer rows than the corresponding inputs of the context.
+    ///
+    /// This allows one compiled context to serve databases of variable sizes up to the numbers of rows the context was built for.
+    /// Databases, i.e. named tuples with the null column, are padded with empty rows to the types of the inputs of the context (see [pad_database_to_type]).
+    /// Other inputs must have the same types as the inputs of the context.
+    ///
+    /// Operations respecting the null column (e.g. set intersection) ignore empty rows.
+    /// Outputs have the shapes of the compiled context; rows of output databases originating from padding rows are empty.
+    pub fn evaluate_padded(&self, inputs: Vec<TypedValue>) -> Result<TypedValue> {
+        self.evaluate(self.pad_inputs(inputs)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{array_type, named_tuple_type, scalar_type, BIT, INT32, INT64};
+    use crate::data_values::Value;
+    use crate::graphs::{contexts_deep_equal, create_context};
+    use crate::type_inference::NULL_HEADER;
+    use std::collections::HashMap;
+
+    fn build_context() -> Result<Context> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        let x = g.input(array_type(vec![4], INT64))?;
+        let y = g.input(scalar_type(INT64))?;
+        x.multiply(y)?.sum(vec![0])?.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        Ok(c)
+    }
+
+    #[test]
+    fn test_pipeline() {
+        || -> Result<()> {
+            let inputs = vec![
+                TypedValue::new(
+                    array_type(vec![4], INT64),
+                    Value::from_flattened_array(&[1, -2, 3, 4], INT64)?,
+                )?,
+                TypedValue::new(scalar_type(INT64), Value::from_scalar(-5, INT64)?)?,
+            ];
+            let pipeline = Pipeline::new(build_context()?)?;
+            let expected = pipeline.evaluate(inputs.clone())?;
+            assert_eq!(expected.value.to_i64(INT64)?, -30);
+            for output_parties in [vec![], vec![IOStatus::Party(1)]] {
+                let compiled = Pipeline::new(build_context()?)?.compile(
+                    vec![IOStatus::Party(0), IOStatus::Shared],
+                    output_parties.clone(),
+                )?;
+                assert_eq!(compiled.evaluate(inputs.clone())?, expected);
+                let restored = Pipeline::deserialize(&compiled.serialize()?, output_parties)?;
+                assert!(contexts_deep_equal(
+                    restored.get_context(),
+                    compiled.get_context()
+                ));
+                assert_eq!(restored.evaluate(inputs.clone())?, expected);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_evaluate_padded() {
+        || -> Result<()> {
+            let database_type = |num_rows: u64, header: &str| {
+                named_tuple_type(vec![
+                    (NULL_HEADER.to_owned(), array_type(vec![num_rows], BIT)),
+                    (header.to_owned(), array_type(vec![num_rows], INT32)),
+                    (
+                        format!("{}_value", header),
+                        array_type(vec![num_rows], INT32),
+                    ),
+                ])
+            };
+            let database = |header: &str, keys: &[i32]| -> Result<TypedValue> {
+                let num_rows = keys.len() as u64;
+                let values: Vec<i32> = keys.iter().map(|key| key * 10).collect();
+                TypedValue::new(
+                    database_type(num_rows, header),
+                    Value::from_vector(vec![
+                        Value::from_flattened_array(&vec![1; num_rows as usize], BIT)?,
+                        Value::from_flattened_array(keys, INT32)?,
+                        Value::from_flattened_array(&values, INT32)?,
+                    ]),
+                )
+            };
+            // The context is built for databases with at most 4 and 5 rows
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let x = g.input(database_type(4, "a"))?;
+            let y = g.input(database_type(5, "b"))?;
+            x.set_intersection(y, HashMap::from([("a".to_owned(), "b".to_owned())]))?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let pipeline = Pipeline::new(c.clone())?;
+            let compiled = Pipeline::new(c)?.compile(
+                vec![IOStatus::Party(0), IOStatus::Shared],
+                vec![IOStatus::Party(0)],
+            )?;
+
+            for (keys_x, keys_y) in [
+                (vec![1, 2, 3], vec![3, 4]),
+                (vec![5, 6, 7, 8], vec![1, 8, 2, 3, 5]),
+                (vec![2], vec![2]),
+            ] {
+                let inputs = vec![database("a", &keys_x)?, database("b", &keys_y)?];
+                let expected = pipeline.evaluate_padded(inputs.clone())?;
+                assert_eq!(compiled.evaluate_padded(inputs)?, expected);
+                // Rows of X present in Y are followed by empty padding rows
+                let columns = expected.value.to_vector()?;
+                let null_column = columns[0].to_flattened_array_u64(array_type(vec![4], BIT))?;
+                let expected_null_column: Vec<u64> = (0..4)
+                    .map(|i| keys_x.get(i).map_or(0, |key| keys_y.contains(key) as u64))
+                    .collect();
+                assert_eq!(null_column, expected_null_column);
+            }
+
+            // Databases with more rows than the context inputs
+            let inputs = vec![database("a", &[1, 2, 3, 4, 5])?, database("b", &[1])?];
+            assert!(pipeline.evaluate_padded(inputs.clone()).is_err());
+            assert!(compiled.evaluate_padded(inputs).is_err());
+            assert!(compiled
+                .evaluate_padded(vec![database("a", &[1])?])
+                .is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed() {
+        || -> Result<()> {
+            let c = create_context()?;
+            assert!(Pipeline::new(c.clone()).is_err());
+            assert!(Pipeline::deserialize("{}", vec![]).is_err());
+            let pipeline = Pipeline::new(build_context()?)?;
+            assert!(pipeline.compile(vec![IOStatus::Party(0)], vec![]).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
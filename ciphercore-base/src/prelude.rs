@@ -0,0 +1,19 @@
+//! Commonly used items of CipherCore, which can be imported at once via `use ciphercore_base::prelude::*`.
+//!
+//! The prelude is the stable part of the API: its items are changed only in a backward-compatible way within a major version.
+//! Items of modules hidden from the documentation (e.g. the MPC compiler or the inliner) are implementation details
+//! and may change between any versions, so downstream code should reach them via [Pipeline] instead.
+pub use crate::custom_ops::{CustomOperation, CustomOperationBody};
+pub use crate::data_types::{
+    array_type, named_tuple_type, scalar_type, tuple_type, vector_type, ArrayShape, ScalarType,
+    Type, BIT, INT128, INT16, INT32, INT64, INT8, UINT128, UINT16, UINT32, UINT64, UINT8,
+};
+pub use crate::data_values::Value;
+pub use crate::errors::Result;
+pub use crate::graph_builder::{Expr, GraphBuilder};
+pub use crate::graphs::{create_context, Context, Graph, Node, SliceElement};
+pub use crate::inline::inline_common::DepthOptimizationLevel;
+pub use crate::inline::inline_ops::{InlineConfig, InlineMode};
+pub use crate::mpc::mpc_compiler::IOStatus;
+pub use crate::pipeline::{Built, Compiled, Pipeline};
+pub use crate::typed_value::TypedValue;
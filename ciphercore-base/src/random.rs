@@ -3,10 +3,16 @@ use crate::data_types::{get_size_in_bits, get_types_vector, Type, UINT64};
 use crate::data_values::Value;
 use crate::errors::Result;
 
+#[cfg(target_arch = "wasm32")]
+use aes::cipher::{BlockEncrypt, KeyInit};
+#[cfg(not(target_arch = "wasm32"))]
 use openssl::symm::{Cipher, Crypter, Mode};
 use rand::rngs::OsRng;
 use rand::RngCore;
 
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
 /// It is possible that when used during early boot
 /// the first call to OsRng will block until the system’s RNG is initialised.
 /// It is also possible (though highly unlikely) for OsRng to fail on some platforms,
@@ -21,7 +27,392 @@ pub fn get_bytes_from_os(bytes: &mut [u8]) -> Result<()> {
 /// Byte size of PRNG seed.
 pub const SEED_SIZE: usize = 16;
 
-/// Cryptographic pseudo-random generator based on AES-128 in the counter mode.
+/// AES-128 encryption of single blocks.
+///
+/// OpenSSL is used on native targets; the pure Rust implementation is used on WebAssembly, where OpenSSL is unavailable.
+struct Aes128 {
+    #[cfg(not(target_arch = "wasm32"))]
+    crypter: Crypter,
+    #[cfg(target_arch = "wasm32")]
+    cipher: aes::Aes128,
+}
+
+impl Aes128 {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new(key: &[u8; SEED_SIZE]) -> Result<Self> {
+        let mut crypter = Crypter::new(Cipher::aes_128_ecb(), Mode::Encrypt, key, None)
+            .map_err(|_| runtime_error!("Crypter didn't initialize"))?;
+        crypter.pad(false);
+        Ok(Aes128 { crypter })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn new(key: &[u8; SEED_SIZE]) -> Result<Self> {
+        Ok(Aes128 {
+            cipher: aes::Aes128::new(key.into()),
+        })
+    }
+
+    /// Encrypts `block` writing the result to the first SEED_SIZE bytes of `out`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn encrypt_block(&mut self, block: &[u8; SEED_SIZE], out: &mut [u8]) -> Result<()> {
+        // additional block is needed to perform encryption,
+        // check here https://www.openssl.org/docs/manmaster/man3/EVP_CipherUpdate.html
+        if out.len() < 2 * SEED_SIZE {
+            return Err(runtime_error!("AES output buffer is too small"));
+        }
+        let count = self
+            .crypter
+            .update(block, out)
+            .map_err(|_| runtime_error!("Crypter didn't manage to update"))?;
+        // finalization of Crypter is unnecessary since padding is turned off
+        // check here https://www.openssl.org/docs/manmaster/man3/EVP_CipherUpdate.html
+        if count != SEED_SIZE {
+            return Err(runtime_error!(
+                "AES encryption returned a wrong number of bytes"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Encrypts `block` writing the result to the first SEED_SIZE bytes of `out`.
+    #[cfg(target_arch = "wasm32")]
+    fn encrypt_block(&mut self, block: &[u8; SEED_SIZE], out: &mut [u8]) -> Result<()> {
+        let mut b = aes::Block::clone_from_slice(block);
+        self.cipher.encrypt_block(&mut b);
+        out[..SEED_SIZE].copy_from_slice(&b);
+        Ok(())
+    }
+}
+
+impl BlockFunction for Aes128 {
+    fn evaluate_block(&mut self, block: &[u8; SEED_SIZE]) -> Result<[u8; SEED_SIZE]> {
+        let mut out = [0u8; 2 * SEED_SIZE];
+        self.encrypt_block(block, &mut out)?;
+        let mut result = [0u8; SEED_SIZE];
+        result.copy_from_slice(&out[..SEED_SIZE]);
+        Ok(result)
+    }
+}
+
+/// Keyed function mapping 128-bit blocks to 128-bit blocks, on which [PRNG] and the PRF of compiled graphs are built.
+///
+/// The function must be pseudo-random if its key is uniformly random and secret.
+pub trait BlockFunction: Send + Sync {
+    fn evaluate_block(&mut self, block: &[u8; SEED_SIZE]) -> Result<[u8; SEED_SIZE]>;
+}
+
+/// Backend providing the primitives of [PRNG] and the PRF of compiled graphs.
+///
+/// The default backend of the process is set by [set_default_prf_backend].
+/// Parties evaluating the same compiled graph must use the same backend, since PRF keys are shared by several parties.
+/// Besides the built-in backends ([AesBackend], [AesNiBackend], [ChaCha20Backend] and [DeterministicTestBackend]),
+/// any vetted primitive can be plugged in by implementing this trait.
+pub trait PrfBackend: Send + Sync {
+    fn get_name(&self) -> String;
+
+    /// Creates a block function with a given key.
+    fn create_block_function(&self, key: &[u8; SEED_SIZE]) -> Result<Box<dyn BlockFunction>>;
+
+    /// Fills the seed of a [PRNG] created without a seed; the OS random generator is used by default.
+    fn fill_seed(&self, seed: &mut [u8]) -> Result<()> {
+        get_bytes_from_os(seed)
+    }
+}
+
+/// AES-128 computed by OpenSSL on native targets (which uses AES-NI if the CPU supports it) and by a pure Rust implementation on WebAssembly.
+///
+/// This is the default backend.
+pub struct AesBackend {}
+
+impl PrfBackend for AesBackend {
+    fn get_name(&self) -> String {
+        "AES".to_owned()
+    }
+
+    fn create_block_function(&self, key: &[u8; SEED_SIZE]) -> Result<Box<dyn BlockFunction>> {
+        Ok(Box::new(Aes128::new(key)?))
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod aes_ni {
+    use super::{BlockFunction, SEED_SIZE};
+    use crate::errors::Result;
+    use std::arch::x86_64::*;
+
+    /// AES-128 with round keys expanded by AES-NI instructions.
+    pub(super) struct AesNi {
+        round_keys: [__m128i; 11],
+    }
+
+    #[target_feature(enable = "aes")]
+    unsafe fn expand_key_step(key: __m128i, assist: __m128i) -> __m128i {
+        let assist = _mm_shuffle_epi32(assist, 0xff);
+        let key = _mm_xor_si128(key, _mm_slli_si128(key, 4));
+        let key = _mm_xor_si128(key, _mm_slli_si128(key, 4));
+        let key = _mm_xor_si128(key, _mm_slli_si128(key, 4));
+        _mm_xor_si128(key, assist)
+    }
+
+    #[target_feature(enable = "aes")]
+    unsafe fn expand_key(key: &[u8; SEED_SIZE]) -> [__m128i; 11] {
+        let mut keys = [_mm_setzero_si128(); 11];
+        keys[0] = _mm_loadu_si128(key.as_ptr() as *const __m128i);
+        macro_rules! expand_round {
+            ($i:expr, $rcon:expr) => {
+                keys[$i] =
+                    expand_key_step(keys[$i - 1], _mm_aeskeygenassist_si128(keys[$i - 1], $rcon));
+            };
+        }
+        expand_round!(1, 0x01);
+        expand_round!(2, 0x02);
+        expand_round!(3, 0x04);
+        expand_round!(4, 0x08);
+        expand_round!(5, 0x10);
+        expand_round!(6, 0x20);
+        expand_round!(7, 0x40);
+        expand_round!(8, 0x80);
+        expand_round!(9, 0x1b);
+        expand_round!(10, 0x36);
+        keys
+    }
+
+    #[target_feature(enable = "aes")]
+    unsafe fn encrypt(round_keys: &[__m128i; 11], block: &[u8; SEED_SIZE]) -> [u8; SEED_SIZE] {
+        let mut state = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+        state = _mm_xor_si128(state, round_keys[0]);
+        for round_key in &round_keys[1..10] {
+            state = _mm_aesenc_si128(state, *round_key);
+        }
+        state = _mm_aesenclast_si128(state, round_keys[10]);
+        let mut result = [0u8; SEED_SIZE];
+        _mm_storeu_si128(result.as_mut_ptr() as *mut __m128i, state);
+        result
+    }
+
+    impl AesNi {
+        pub(super) fn new(key: &[u8; SEED_SIZE]) -> Result<Self> {
+            if !is_x86_feature_detected!("aes") {
+                return Err(runtime_error!("CPU doesn't support AES-NI"));
+            }
+            // SAFETY: the CPU supports AES-NI as checked above
+            let round_keys = unsafe { expand_key(key) };
+            Ok(AesNi { round_keys })
+        }
+    }
+
+    impl BlockFunction for AesNi {
+        fn evaluate_block(&mut self, block: &[u8; SEED_SIZE]) -> Result<[u8; SEED_SIZE]> {
+            // SAFETY: AesNi is created only if the CPU supports AES-NI
+            Ok(unsafe { encrypt(&self.round_keys, block) })
+        }
+    }
+}
+
+/// AES-128 computed directly by the AES-NI instructions of x86-64 CPUs, which avoids the call overhead of OpenSSL.
+///
+/// Creation of block functions fails if AES-NI is not available.
+/// The output coincides with that of [AesBackend].
+pub struct AesNiBackend {}
+
+impl PrfBackend for AesNiBackend {
+    fn get_name(&self) -> String {
+        "AES-NI".to_owned()
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn create_block_function(&self, key: &[u8; SEED_SIZE]) -> Result<Box<dyn BlockFunction>> {
+        Ok(Box::new(aes_ni::AesNi::new(key)?))
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn create_block_function(&self, _key: &[u8; SEED_SIZE]) -> Result<Box<dyn BlockFunction>> {
+        Err(runtime_error!("AES-NI is available only on x86-64"))
+    }
+}
+
+// ChaCha20 block function with a given initial state (see RFC 8439)
+fn chacha20_block(input: &[u32; 16]) -> [u32; 16] {
+    fn quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+        x[a] = x[a].wrapping_add(x[b]);
+        x[d] = (x[d] ^ x[a]).rotate_left(16);
+        x[c] = x[c].wrapping_add(x[d]);
+        x[b] = (x[b] ^ x[c]).rotate_left(12);
+        x[a] = x[a].wrapping_add(x[b]);
+        x[d] = (x[d] ^ x[a]).rotate_left(8);
+        x[c] = x[c].wrapping_add(x[d]);
+        x[b] = (x[b] ^ x[c]).rotate_left(7);
+    }
+    let mut x = *input;
+    for _ in 0..10 {
+        quarter_round(&mut x, 0, 4, 8, 12);
+        quarter_round(&mut x, 1, 5, 9, 13);
+        quarter_round(&mut x, 2, 6, 10, 14);
+        quarter_round(&mut x, 3, 7, 11, 15);
+        quarter_round(&mut x, 0, 5, 10, 15);
+        quarter_round(&mut x, 1, 6, 11, 12);
+        quarter_round(&mut x, 2, 7, 8, 13);
+        quarter_round(&mut x, 3, 4, 9, 14);
+    }
+    for (y, z) in x.iter_mut().zip(input) {
+        *y = y.wrapping_add(*z);
+    }
+    x
+}
+
+fn bytes_to_words(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks(4)
+        .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+struct ChaCha20 {
+    key: [u32; 4],
+}
+
+impl BlockFunction for ChaCha20 {
+    fn evaluate_block(&mut self, block: &[u8; SEED_SIZE]) -> Result<[u8; SEED_SIZE]> {
+        // Constants of 128-bit keys ("expand 16-byte k"), the key repeated twice and the block in place of the counter and nonce
+        let mut input = [
+            0x61707865, 0x3120646e, 0x79622d36, 0x6b206574, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        input[4..8].copy_from_slice(&self.key);
+        input[8..12].copy_from_slice(&self.key);
+        input[12..16].copy_from_slice(&bytes_to_words(block));
+        let output = chacha20_block(&input);
+        let mut result = [0u8; SEED_SIZE];
+        for (chunk, word) in result.chunks_mut(4).zip(output) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        Ok(result)
+    }
+}
+
+/// ChaCha20 with a 128-bit key, whose output is truncated to 128 bits.
+///
+/// It is a portable alternative to AES, which is fast on CPUs without hardware AES.
+pub struct ChaCha20Backend {}
+
+impl PrfBackend for ChaCha20Backend {
+    fn get_name(&self) -> String {
+        "ChaCha20".to_owned()
+    }
+
+    fn create_block_function(&self, key: &[u8; SEED_SIZE]) -> Result<Box<dyn BlockFunction>> {
+        let mut key_words = [0u32; 4];
+        key_words.copy_from_slice(&bytes_to_words(key));
+        Ok(Box::new(ChaCha20 { key: key_words }))
+    }
+}
+
+// Finalizer of SplitMix64
+fn mix64(x: u64) -> u64 {
+    let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+fn split_block(block: &[u8; SEED_SIZE]) -> (u64, u64) {
+    let value = u128::from_le_bytes(*block);
+    (value as u64, (value >> 64) as u64)
+}
+
+struct DeterministicBlockFunction {
+    key: (u64, u64),
+}
+
+impl BlockFunction for DeterministicBlockFunction {
+    fn evaluate_block(&mut self, block: &[u8; SEED_SIZE]) -> Result<[u8; SEED_SIZE]> {
+        let (b0, b1) = split_block(block);
+        let low = mix64(self.key.0 ^ mix64(b0 ^ mix64(b1 ^ self.key.1)));
+        let high = mix64(self.key.1 ^ mix64(low ^ b1));
+        Ok((((high as u128) << 64) | low as u128).to_le_bytes())
+    }
+}
+
+/// Fast non-cryptographic backend for tests and debugging.
+///
+/// PRNGs created without a seed are seeded with a counter instead of the OS random generator,
+/// so evaluation is reproducible within a process given the same sequence of operations.
+///
+/// **WARNING**: this backend is insecure and must not be used in production.
+#[derive(Default)]
+pub struct DeterministicTestBackend {
+    num_seeds: AtomicU64,
+}
+
+impl PrfBackend for DeterministicTestBackend {
+    fn get_name(&self) -> String {
+        "DeterministicTest".to_owned()
+    }
+
+    fn create_block_function(&self, key: &[u8; SEED_SIZE]) -> Result<Box<dyn BlockFunction>> {
+        Ok(Box::new(DeterministicBlockFunction {
+            key: split_block(key),
+        }))
+    }
+
+    fn fill_seed(&self, seed: &mut [u8]) -> Result<()> {
+        let index = self.num_seeds.fetch_add(1, Ordering::SeqCst);
+        for (i, chunk) in seed.chunks_mut(8).enumerate() {
+            let bytes = mix64(index.wrapping_mul(0x9e3779b97f4a7c15) ^ i as u64).to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
+
+/// Names of the built-in backends accepted by [get_prf_backend_by_name].
+pub const PRF_BACKEND_NAMES: [&str; 4] = ["aes", "aes-ni", "chacha20", "deterministic-test"];
+
+/// Returns a built-in backend by its name from [PRF_BACKEND_NAMES], e.g. to choose a backend via a command line flag.
+pub fn get_prf_backend_by_name(name: &str) -> Result<Arc<dyn PrfBackend>> {
+    match name {
+        "aes" => Ok(Arc::new(AesBackend {})),
+        "aes-ni" => Ok(Arc::new(AesNiBackend {})),
+        "chacha20" => Ok(Arc::new(ChaCha20Backend {})),
+        "deterministic-test" => Ok(Arc::new(DeterministicTestBackend::default())),
+        _ => Err(runtime_error!(
+            "Unknown PRF backend {}, expected one of {:?}",
+            name,
+            PRF_BACKEND_NAMES
+        )),
+    }
+}
+
+static DEFAULT_PRF_BACKEND: RwLock<Option<Arc<dyn PrfBackend>>> = RwLock::new(None);
+
+/// Sets the backend used by [PRNG::new] and by the evaluation of PRF nodes in this process.
+///
+/// It should be called before any evaluation, since PRNGs and PRFs keep the backend they were created with.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::random::{get_default_prf_backend, set_default_prf_backend, ChaCha20Backend, PRNG};
+/// # use std::sync::Arc;
+/// set_default_prf_backend(Arc::new(ChaCha20Backend {})).unwrap();
+/// assert_eq!(get_default_prf_backend().unwrap().get_name(), "ChaCha20");
+/// let bytes = PRNG::new(Some([0; 16])).unwrap().get_random_bytes(10).unwrap();
+/// ```
+pub fn set_default_prf_backend(backend: Arc<dyn PrfBackend>) -> Result<()> {
+    *DEFAULT_PRF_BACKEND
+        .write()
+        .map_err(|_| runtime_error!("PRF backend lock is poisoned"))? = Some(backend);
+    Ok(())
+}
+
+/// Returns the backend set by [set_default_prf_backend] or [AesBackend] if no backend has been set.
+pub fn get_default_prf_backend() -> Result<Arc<dyn PrfBackend>> {
+    let backend = DEFAULT_PRF_BACKEND
+        .read()
+        .map_err(|_| runtime_error!("PRF backend lock is poisoned"))?;
+    Ok(backend.clone().unwrap_or_else(|| Arc::new(AesBackend {})))
+}
+
+/// Cryptographic pseudo-random generator based on a [block function](BlockFunction) (AES-128 by default) in the counter mode.
 /// If the seed is private, the security is based on the key-recovery hardness assumption of AES
 /// and [the PRP/PRF(Prf) switching lemma](https://eprint.iacr.org/2004/331.pdf).
 /// Less than 2^64 128-bit strings should be sampled to avoid distinguishing attacks due to birthday paradox.
@@ -30,57 +421,38 @@ pub const SEED_SIZE: usize = 16;
 pub struct PRNG {
     counter: u128,
     random_bytes: Vec<u8>,
-    aes: Crypter,
+    block_function: Box<dyn BlockFunction>,
 }
 /// The following implementation is not thread-safe as several copies of PRNG
 /// can concurrently access the system random generator
 impl PRNG {
+    /// Creates a PRNG with the [default backend](get_default_prf_backend).
     pub fn new(seed: Option<[u8; SEED_SIZE]>) -> Result<PRNG> {
-        let err = |_| runtime_error!("Crypter didn't initialize");
-        match seed {
-            Some(bytes) => {
-                let mut c = Crypter::new(Cipher::aes_128_ecb(), Mode::Encrypt, &bytes, None)
-                    .map_err(err)?;
-                c.pad(false);
-                Ok(PRNG {
-                    counter: 0u128,
-                    random_bytes: vec![],
-                    aes: c,
-                })
-            }
+        PRNG::new_with_backend(seed, get_default_prf_backend()?.as_ref())
+    }
+
+    pub fn new_with_backend(
+        seed: Option<[u8; SEED_SIZE]>,
+        backend: &dyn PrfBackend,
+    ) -> Result<PRNG> {
+        let bytes = match seed {
+            Some(bytes) => bytes,
             None => {
                 let mut bytes = [0u8; SEED_SIZE];
-                get_bytes_from_os(&mut bytes)?;
-                let mut c = Crypter::new(Cipher::aes_128_ecb(), Mode::Encrypt, &bytes, None)
-                    .map_err(err)?;
-                c.pad(false);
-                Ok(PRNG {
-                    counter: 0u128,
-                    random_bytes: vec![],
-                    aes: c,
-                })
+                backend.fill_seed(&mut bytes)?;
+                bytes
             }
-        }
+        };
+        Ok(PRNG {
+            counter: 0u128,
+            random_bytes: vec![],
+            block_function: backend.create_block_function(&bytes)?,
+        })
     }
 
     fn refill_random(&mut self) -> Result<()> {
         let counter_bytes = self.counter.to_le_bytes();
-        // additional block is needed to perform encryption,
-        // check here https://www.openssl.org/docs/manmaster/man3/EVP_CipherUpdate.html
-        let mut res = vec![0; 2 * SEED_SIZE];
-        let count = self
-            .aes
-            .update(&counter_bytes, &mut res)
-            .map_err(|_| runtime_error!("Crypter didn't manage to update"))?;
-        // finalization of Crypter is unnecessary since padding is turned off
-        // check here https://www.openssl.org/docs/manmaster/man3/EVP_CipherUpdate.html
-        if count != SEED_SIZE {
-            return Err(runtime_error!(
-                "AES encryption returned a wrong number of bytes"
-            ));
-        }
-        res.truncate(count);
-        self.random_bytes = res;
+        self.random_bytes = self.block_function.evaluate_block(&counter_bytes)?.to_vec();
         self.counter += 1;
         Ok(())
     }
@@ -158,6 +530,17 @@ impl PRNG {
     }
 }
 
+/// Derives a seed from a given seed and an index as F_seed(1|index), where F is the block function of the [default backend](get_default_prf_backend) (AES by default).
+///
+/// Seeds derived with different indices are independent, so seeds can be derived hierarchically, e.g. for every node of every graph from a seed of the whole evaluation.
+/// The PRNG seeded with `seed` encrypts counters below 2^64, so its output doesn't overlap with the derived seeds.
+pub fn derive_seed(seed: [u8; SEED_SIZE], index: u64) -> Result<[u8; SEED_SIZE]> {
+    let block = ((1u128 << 64) | index as u128).to_le_bytes();
+    get_default_prf_backend()?
+        .create_block_function(&seed)?
+        .evaluate_block(&block)
+}
+
 /// Pseudo-random function (Prf/PRF) based on AES-128.
 /// PRF keys are sampled via the above PRNG.
 /// As for the above PRNG, the security is based on the key-recovery hardness assumption of AES
@@ -165,51 +548,33 @@ impl PRNG {
 /// Less than 2^64 128-bit strings should be sampled to avoid distinguishing attacks due to birthday paradox.
 /// PRF(Prf) output is extended by computing AES_k(0|input)|...|AES_k(n-1|input)
 /// (see e.g. p.16 of [Kolesnikov et al.](https://eprint.iacr.org/2016/799.pdf)).
+///
+/// AES can be replaced by another [backend](PrfBackend).
 pub(super) struct Prf {
-    aes: Crypter,
-    out_vec: Vec<u8>,
+    block_function: Box<dyn BlockFunction>,
+    out_vec: [u8; SEED_SIZE],
 }
 
 impl Prf {
+    /// Creates a PRF with the [default backend](get_default_prf_backend).
     pub fn new(key: Option<[u8; SEED_SIZE]>) -> Result<Prf> {
-        let err = |_| runtime_error!("Crypter didn't initialize");
-        match key {
-            Some(bytes) => {
-                let mut c = Crypter::new(Cipher::aes_128_ecb(), Mode::Encrypt, &bytes, None)
-                    .map_err(err)?;
-                c.pad(false);
-                Ok(Prf {
-                    aes: c,
-                    out_vec: vec![0u8; 2 * SEED_SIZE],
-                })
-            }
-            None => {
-                let mut gen = PRNG::new(None)?;
-                let key_bytes = gen.get_random_key()?;
-                let mut c = Crypter::new(Cipher::aes_128_ecb(), Mode::Encrypt, &key_bytes, None)
-                    .map_err(err)?;
-                c.pad(false);
-                Ok(Prf {
-                    aes: c,
-                    out_vec: vec![0u8; 2 * SEED_SIZE],
-                })
-            }
-        }
+        Prf::new_with_backend(key, get_default_prf_backend()?.as_ref())
+    }
+
+    pub fn new_with_backend(key: Option<[u8; SEED_SIZE]>, backend: &dyn PrfBackend) -> Result<Prf> {
+        let key_bytes = match key {
+            Some(bytes) => bytes,
+            None => PRNG::new_with_backend(None, backend)?.get_random_key()?,
+        };
+        Ok(Prf {
+            block_function: backend.create_block_function(&key_bytes)?,
+            out_vec: [0u8; SEED_SIZE],
+        })
     }
 
     fn generate_one_batch(&mut self, input: u128) -> Result<()> {
         let i_bytes = input.to_le_bytes();
-        let count = self
-            .aes
-            .update(&i_bytes, &mut self.out_vec)
-            .map_err(|_| runtime_error!("Crypter didn't manage to update"))?;
-        // finalization of Crypter is unnecessary since padding is turned off
-        // check here https://www.openssl.org/docs/manmaster/man3/EVP_CipherUpdate.html
-        if count != SEED_SIZE {
-            return Err(runtime_error!(
-                "AES encryption returned a wrong number of bytes"
-            ));
-        }
+        self.out_vec = self.block_function.evaluate_block(&i_bytes)?;
         Ok(())
     }
 
@@ -317,7 +682,103 @@ mod tests {
     };
 
     #[test]
+    fn test_aes_known_answer() {
+        // Test vector from Appendix C.1 of FIPS 197
+        let key = b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0A\x0B\x0C\x0D\x0E\x0F";
+        let block = b"\x00\x11\x22\x33\x44\x55\x66\x77\x88\x99\xAA\xBB\xCC\xDD\xEE\xFF";
+        let mut out = vec![0u8; 2 * SEED_SIZE];
+        Aes128::new(key)
+            .unwrap()
+            .encrypt_block(block, &mut out)
+            .unwrap();
+        assert_eq!(
+            out[..SEED_SIZE],
+            *b"\x69\xC4\xE0\xD8\x6A\x7B\x04\x30\xD8\xCD\xB7\x80\x70\xB4\xC5\x5A"
+        );
+    }
+
+    #[test]
+    fn test_chacha20_known_answer() {
+        // Test vector from Section 2.3.2 of RFC 8439
+        let key: Vec<u8> = (0..32).collect();
+        let mut input = [
+            0x61707865, 0x3320646e, 0x79622d32, 0x6b206574, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0x09000000,
+            0x4a000000, 0,
+        ];
+        input[4..12].copy_from_slice(&bytes_to_words(&key));
+        let output = chacha20_block(&input);
+        let bytes: Vec<u8> = output[..4].iter().flat_map(|w| w.to_le_bytes()).collect();
+        assert_eq!(
+            bytes,
+            b"\x10\xf1\xe7\xe4\xd1\x3b\x59\x15\x50\x0f\xdd\x1f\xa3\x20\x71\xc4"
+        );
+    }
 
+    #[test]
+    fn test_aes_ni_backend() {
+        || -> Result<()> {
+            let key = [3u8; SEED_SIZE];
+            let aes_ni = AesNiBackend {}.create_block_function(&key);
+            if !cfg!(target_arch = "x86_64") || !std::is_x86_feature_detected!("aes") {
+                assert!(aes_ni.is_err());
+                return Ok(());
+            }
+            let mut aes_ni = aes_ni?;
+            let mut aes = AesBackend {}.create_block_function(&key)?;
+            for i in 0..100u128 {
+                let block = (i * 0x1234567890abcdef).to_le_bytes();
+                assert_eq!(aes_ni.evaluate_block(&block)?, aes.evaluate_block(&block)?);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_prf_backends() {
+        || -> Result<()> {
+            let backends: Vec<Box<dyn PrfBackend>> = vec![
+                Box::new(AesBackend {}),
+                Box::new(ChaCha20Backend {}),
+                Box::new(DeterministicTestBackend::default()),
+            ];
+            let seed = [9u8; SEED_SIZE];
+            let mut outputs = vec![];
+            for backend in &backends {
+                let bytes = PRNG::new_with_backend(Some(seed), backend.as_ref())?
+                    .get_random_bytes(1_000_000)?;
+                let mut counters = [0; 256];
+                for byte in &bytes {
+                    counters[*byte as usize] += 1;
+                }
+                assert!(entropy_test(counters, bytes.len() as u64));
+                assert_eq!(
+                    PRNG::new_with_backend(Some(seed), backend.as_ref())?
+                        .get_random_bytes(bytes.len())?,
+                    bytes
+                );
+                let mut prf = Prf::new_with_backend(Some(seed), backend.as_ref())?;
+                let t = array_type(vec![10], UINT64);
+                assert!(prf.output_value(5, t.clone())?.check_type(t)?);
+                outputs.push(bytes);
+            }
+            assert_ne!(outputs[0], outputs[1]);
+            assert_ne!(outputs[1], outputs[2]);
+            // Unseeded PRNGs of the test backend are reproducible
+            let random_bytes = |backend: &DeterministicTestBackend| -> Result<Vec<u8>> {
+                PRNG::new_with_backend(None, backend)?.get_random_bytes(32)
+            };
+            let backend1 = DeterministicTestBackend::default();
+            let backend2 = DeterministicTestBackend::default();
+            let first_bytes = random_bytes(&backend1)?;
+            assert_ne!(first_bytes, random_bytes(&backend1)?);
+            assert_eq!(first_bytes, random_bytes(&backend2)?);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
     fn test_prng_fixed_seed() {
         let helper = |n: usize| -> Result<()> {
             let seed = b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0A\x0B\x0C\x0D\x0E\x0F";
@@ -333,6 +794,26 @@ mod tests {
         helper(1000).unwrap();
     }
 
+    #[test]
+    fn test_derive_seed() {
+        || -> Result<()> {
+            let seed = [5u8; SEED_SIZE];
+            assert_eq!(derive_seed(seed, 3)?, derive_seed(seed, 3)?);
+            assert_ne!(derive_seed(seed, 3)?, derive_seed(seed, 4)?);
+            assert_ne!(derive_seed(seed, 3)?, derive_seed([6u8; SEED_SIZE], 3)?);
+            // Derived seeds don't overlap with the PRNG output
+            let prng_bytes = PRNG::new(Some(seed))?.get_random_bytes(10 * SEED_SIZE)?;
+            for i in 0..10 {
+                assert_ne!(
+                    derive_seed(seed, i)?.to_vec(),
+                    prng_bytes[i as usize * SEED_SIZE..(i as usize + 1) * SEED_SIZE]
+                );
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
     #[test]
     fn test_prng_random_seed() {
         let mut prng = PRNG::new(None).unwrap();
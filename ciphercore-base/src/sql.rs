@@ -0,0 +1,636 @@
+//! Translation of SQL queries into computation graphs on databases represented as named tuples of columns.
+//!
+//! A database is a named tuple of arrays with the same number of rows containing the binary column [NULL_HEADER] (see [Graph::set_intersection](crate::graphs::Graph::set_intersection)).
+//! The following subset of SQL is supported:
+//!
+//! - `SELECT` with column names, `*`, the aggregate functions `SUM(column)`, `COUNT(*)` and `COUNT(column)`, and aliases via `AS`;
+//! - `FROM` with a single table followed by any number of `[INNER] JOIN ... ON` clauses whose conditions are conjunctions of column equalities;
+//! - `WHERE` with comparisons of columns with columns or integer constants combined by `AND`, `OR` and `NOT` (see [Predicate]);
+//! - `GROUP BY` with column names (see [GroupBy]).
+//!
+//! Joins are computed by [Graph::set_intersection](crate::graphs::Graph::set_intersection), so the key values of the joined tables must be unique, and rows of the result are aligned with the rows of the left table.
+//! The query is evaluated obliviously: the result has a fixed number of rows and rows that are filtered out are marked as empty in the null column instead of being removed.
+//! Column names are case-sensitive and must be unique in the resulting table unless they are key columns of a join.
+//!
+//! The resulting graphs consist of ordinary operations, so they can be compiled to MPC via [compile_context](crate::mpc::mpc_compiler::compile_context) as any other graph.
+//! In particular, joins are compiled to the private set intersection protocol.
+use std::collections::HashMap;
+
+use crate::custom_ops::CustomOperation;
+use crate::data_types::Type;
+use crate::errors::Result;
+use crate::graphs::{create_context, Context, Node};
+use crate::ops::table::{Aggregate, Comparison, GroupBy, Operand, Predicate, SelectColumns};
+use crate::type_inference::NULL_HEADER;
+
+use sqlparser::ast::{
+    BinaryOperator, Expr, FunctionArg, FunctionArgExpr, FunctionArguments, GroupByExpr, Join,
+    JoinConstraint, JoinOperator, Query, Select, SelectItem, SetExpr, Statement, TableFactor,
+    UnaryOperator, Value,
+};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+// Column of an intermediate table that can be referenced as `table.column` or `column`
+struct ScopeColumn {
+    table: String,
+    column: String,
+    header: String,
+}
+
+// Intermediate table of a query
+struct Relation {
+    node: Node,
+    scope: Vec<ScopeColumn>,
+}
+
+fn get_headers(node: &Node) -> Result<Vec<String>> {
+    match node.get_type()? {
+        Type::NamedTuple(header_types) => Ok(header_types
+            .into_iter()
+            .map(|(header, _)| header)
+            .filter(|header| header != NULL_HEADER)
+            .collect()),
+        t => Err(runtime_error!(
+            "Tables should be named tuples, but {} is given",
+            t
+        )),
+    }
+}
+
+fn get_table(factor: &TableFactor, tables: &[(String, Node)]) -> Result<Relation> {
+    if let TableFactor::Table { name, alias, .. } = factor {
+        let table_name = name.to_string();
+        let node = tables
+            .iter()
+            .find(|(t, _)| *t == table_name)
+            .map(|(_, node)| node.clone())
+            .ok_or_else(|| runtime_error!("There is no table {}", table_name))?;
+        let scope_name = match alias {
+            Some(alias) => alias.name.value.clone(),
+            None => table_name,
+        };
+        let scope = get_headers(&node)?
+            .into_iter()
+            .map(|header| ScopeColumn {
+                table: scope_name.clone(),
+                column: header.clone(),
+                header,
+            })
+            .collect();
+        Ok(Relation { node, scope })
+    } else {
+        Err(runtime_error!(
+            "Only tables are supported in FROM, got {}",
+            factor
+        ))
+    }
+}
+
+// Returns the header of a column referenced by an expression
+fn resolve_column(scope: &[ScopeColumn], expr: &Expr) -> Result<String> {
+    let matches: Vec<&ScopeColumn> = match expr {
+        Expr::Identifier(ident) => scope.iter().filter(|c| c.column == ident.value).collect(),
+        Expr::CompoundIdentifier(idents) if idents.len() == 2 => scope
+            .iter()
+            .filter(|c| c.table == idents[0].value && c.column == idents[1].value)
+            .collect(),
+        _ => return Err(runtime_error!("Expected a column, got {}", expr)),
+    };
+    let header = match matches.first() {
+        Some(c) => c.header.clone(),
+        None => return Err(runtime_error!("Unknown column {}", expr)),
+    };
+    if matches.iter().any(|c| c.header != header) {
+        return Err(runtime_error!("Column {} is ambiguous", expr));
+    }
+    Ok(header)
+}
+
+// Collects pairs of expressions compared in a conjunction of equalities
+fn get_equalities(expr: &Expr, equalities: &mut Vec<(Expr, Expr)>) -> Result<()> {
+    match expr {
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            get_equalities(left, equalities)?;
+            get_equalities(right, equalities)
+        }
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::Eq,
+            right,
+        } => {
+            equalities.push(((**left).clone(), (**right).clone()));
+            Ok(())
+        }
+        Expr::Nested(e) => get_equalities(e, equalities),
+        _ => Err(runtime_error!(
+            "Join condition should be a conjunction of column equalities, got {}",
+            expr
+        )),
+    }
+}
+
+fn join(left: Relation, join: &Join, tables: &[(String, Node)]) -> Result<Relation> {
+    let condition = match &join.join_operator {
+        JoinOperator::Inner(JoinConstraint::On(condition)) => condition,
+        _ => {
+            return Err(runtime_error!(
+                "Only INNER JOIN with ON is supported, got {}",
+                join
+            ))
+        }
+    };
+    let right = get_table(&join.relation, tables)?;
+    let mut equalities = vec![];
+    get_equalities(condition, &mut equalities)?;
+    let mut key_headers = HashMap::new();
+    for (e0, e1) in equalities {
+        let (h0, h1) = match (
+            resolve_column(&left.scope, &e0),
+            resolve_column(&right.scope, &e1),
+        ) {
+            (Ok(h0), Ok(h1)) => (h0, h1),
+            _ => (
+                resolve_column(&left.scope, &e1)?,
+                resolve_column(&right.scope, &e0)?,
+            ),
+        };
+        key_headers.insert(h0, h1);
+    }
+    let node = left
+        .node
+        .set_intersection(right.node, key_headers.clone())?;
+    // Key columns of the right table are replaced by the corresponding columns of the left table
+    let mut scope = left.scope;
+    for c in right.scope {
+        let header = key_headers
+            .iter()
+            .find(|(_, h1)| **h1 == c.header)
+            .map(|(h0, _)| h0.clone())
+            .unwrap_or(c.header);
+        scope.push(ScopeColumn { header, ..c });
+    }
+    Ok(Relation { node, scope })
+}
+
+fn get_constant(expr: &Expr) -> Option<i64> {
+    match expr {
+        Expr::Value(Value::Number(s, _)) => s.parse().ok(),
+        Expr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr,
+        } => get_constant(expr).map(|x| x.wrapping_neg()),
+        Expr::Nested(e) => get_constant(e),
+        _ => None,
+    }
+}
+
+fn get_predicate(expr: &Expr, scope: &[ScopeColumn]) -> Result<Predicate> {
+    match expr {
+        Expr::BinaryOp { left, op, right } => {
+            let (comparison, flipped_comparison) = match op {
+                BinaryOperator::And => {
+                    return Ok(get_predicate(left, scope)?.and(get_predicate(right, scope)?))
+                }
+                BinaryOperator::Or => {
+                    return Ok(get_predicate(left, scope)?.or(get_predicate(right, scope)?))
+                }
+                BinaryOperator::Eq => (Comparison::Equal, Comparison::Equal),
+                BinaryOperator::NotEq => (Comparison::NotEqual, Comparison::NotEqual),
+                BinaryOperator::Lt => (Comparison::LessThan, Comparison::GreaterThan),
+                BinaryOperator::LtEq => {
+                    (Comparison::LessThanEqualTo, Comparison::GreaterThanEqualTo)
+                }
+                BinaryOperator::Gt => (Comparison::GreaterThan, Comparison::LessThan),
+                BinaryOperator::GtEq => {
+                    (Comparison::GreaterThanEqualTo, Comparison::LessThanEqualTo)
+                }
+                _ => return Err(runtime_error!("Unsupported operator {}", op)),
+            };
+            // A constant on the left-hand side is moved to the right-hand side
+            if let Some(value) = get_constant(left) {
+                let column = resolve_column(scope, right)?;
+                return Ok(Predicate::compare(
+                    &column,
+                    flipped_comparison,
+                    Operand::Constant(value),
+                ));
+            }
+            let column = resolve_column(scope, left)?;
+            let operand = match get_constant(right) {
+                Some(value) => Operand::Constant(value),
+                None => Operand::Column(resolve_column(scope, right)?),
+            };
+            Ok(Predicate::compare(&column, comparison, operand))
+        }
+        Expr::UnaryOp {
+            op: UnaryOperator::Not,
+            expr,
+        } => Ok(!get_predicate(expr, scope)?),
+        Expr::Nested(e) => get_predicate(e, scope),
+        _ => Err(runtime_error!("Unsupported condition {}", expr)),
+    }
+}
+
+// Returns the aggregate computed by an expression or None if the expression isn't a function call
+fn get_aggregate(expr: &Expr, scope: &[ScopeColumn]) -> Result<Option<Aggregate>> {
+    let function = match expr {
+        Expr::Function(function) => function,
+        _ => return Ok(None),
+    };
+    let arg = match &function.args {
+        FunctionArguments::List(list)
+            if list.args.len() == 1 && list.duplicate_treatment.is_none() =>
+        {
+            &list.args[0]
+        }
+        _ => return Err(runtime_error!("Unsupported arguments of {}", function)),
+    };
+    let name = function.name.to_string().to_uppercase();
+    match (name.as_str(), arg) {
+        ("SUM", FunctionArg::Unnamed(FunctionArgExpr::Expr(e))) => {
+            Ok(Some(Aggregate::Sum(resolve_column(scope, e)?)))
+        }
+        ("COUNT", FunctionArg::Unnamed(FunctionArgExpr::Wildcard)) => Ok(Some(Aggregate::Count)),
+        // Columns contain no missing values, so COUNT(column) is equal to COUNT(*)
+        ("COUNT", FunctionArg::Unnamed(FunctionArgExpr::Expr(e))) => {
+            resolve_column(scope, e)?;
+            Ok(Some(Aggregate::Count))
+        }
+        _ => Err(runtime_error!("Unsupported function {}", function)),
+    }
+}
+
+fn get_select(query: &Query) -> Result<&Select> {
+    if query.with.is_some()
+        || query.order_by.is_some()
+        || query.limit.is_some()
+        || query.offset.is_some()
+        || query.fetch.is_some()
+    {
+        return Err(runtime_error!(
+            "WITH, ORDER BY, LIMIT, OFFSET and FETCH are not supported"
+        ));
+    }
+    let select = match query.body.as_ref() {
+        SetExpr::Select(select) => select,
+        _ => return Err(runtime_error!("Only SELECT queries are supported")),
+    };
+    if select.distinct.is_some() || select.having.is_some() {
+        return Err(runtime_error!("DISTINCT and HAVING are not supported"));
+    }
+    if select.from.len() != 1 {
+        return Err(runtime_error!(
+            "FROM should contain exactly one table with optional joins"
+        ));
+    }
+    Ok(select)
+}
+
+/// Adds nodes computing the result of an SQL query to the graph of given tables.
+///
+/// See [the module documentation](self) for the supported subset of SQL.
+///
+/// # Arguments
+///
+/// * `query` - SQL query
+/// * `tables` - names of tables referenced in the query and nodes containing these tables
+///
+/// # Returns
+///
+/// Node containing the resulting table, i.e. a named tuple containing the null column followed by the selected columns
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::graphs::create_context;
+/// # use ciphercore_base::data_types::{array_type, named_tuple_type, BIT, INT32};
+/// # use ciphercore_base::sql::sql_query;
+/// # use ciphercore_base::type_inference::NULL_HEADER;
+/// let c = create_context().unwrap();
+/// let g = c.create_graph().unwrap();
+/// let t = named_tuple_type(vec![
+///     (NULL_HEADER.to_owned(), array_type(vec![10], BIT)),
+///     ("Age".to_owned(), array_type(vec![10], INT32)),
+///     ("Revenue".to_owned(), array_type(vec![10], INT32)),
+/// ]);
+/// let customers = g.input(t).unwrap();
+/// let n = sql_query(
+///     "SELECT Age, SUM(Revenue) AS Total FROM customers WHERE Age > 30 GROUP BY Age",
+///     &[("customers".to_owned(), customers)],
+/// ).unwrap();
+/// ```
+pub fn sql_query(query: &str, tables: &[(String, Node)]) -> Result<Node> {
+    let statements = Parser::parse_sql(&GenericDialect {}, query)
+        .map_err(|e| runtime_error!("SQL parsing error: {}", e))?;
+    let query = match statements.as_slice() {
+        [Statement::Query(query)] => query,
+        _ => return Err(runtime_error!("Expected a single SELECT query")),
+    };
+    let select = get_select(query)?;
+
+    let mut relation = get_table(&select.from[0].relation, tables)?;
+    for j in &select.from[0].joins {
+        relation = join(relation, j, tables)?;
+    }
+    let mut node = relation.node;
+    let scope = relation.scope;
+    if let Some(condition) = &select.selection {
+        node = node.filter(get_predicate(condition, &scope)?)?;
+    }
+
+    let group_keys = match &select.group_by {
+        GroupByExpr::Expressions(exprs, modifiers) if modifiers.is_empty() => exprs
+            .iter()
+            .map(|e| resolve_column(&scope, e))
+            .collect::<Result<Vec<String>>>()?,
+        _ => return Err(runtime_error!("Unsupported GROUP BY clause")),
+    };
+    // Pairs of headers of the current table and output headers
+    let mut columns = vec![(NULL_HEADER.to_owned(), NULL_HEADER.to_owned())];
+    let mut aggregates = vec![];
+    for item in &select.projection {
+        let (expr, alias) = match item {
+            SelectItem::UnnamedExpr(expr) => (expr, None),
+            SelectItem::ExprWithAlias { expr, alias } => (expr, Some(alias.value.clone())),
+            SelectItem::Wildcard(_) => {
+                for header in get_headers(&node)? {
+                    columns.push((header.clone(), header));
+                }
+                continue;
+            }
+            _ => return Err(runtime_error!("Unsupported SELECT item {}", item)),
+        };
+        if let Some(aggregate) = get_aggregate(expr, &scope)? {
+            let output_header = alias.unwrap_or_else(|| expr.to_string());
+            aggregates.push((aggregate, output_header.clone()));
+            columns.push((output_header.clone(), output_header));
+        } else {
+            let header = resolve_column(&scope, expr)?;
+            let output_header = alias.unwrap_or_else(|| match expr {
+                Expr::CompoundIdentifier(idents) => idents[1].value.clone(),
+                _ => header.clone(),
+            });
+            columns.push((header, output_header));
+        }
+    }
+    if !group_keys.is_empty() || !aggregates.is_empty() {
+        for (header, _) in &columns[1..] {
+            let is_aggregate = aggregates.iter().any(|(_, h)| h == header);
+            if !is_aggregate && !group_keys.contains(header) {
+                return Err(runtime_error!(
+                    "Column {} must appear in GROUP BY or be used in an aggregate function",
+                    header
+                ));
+            }
+        }
+        node = node.get_graph().custom_op(
+            CustomOperation::new(GroupBy {
+                keys: group_keys,
+                aggregates,
+            }),
+            vec![node],
+        )?;
+    }
+    node.get_graph()
+        .custom_op(CustomOperation::new(SelectColumns { columns }), vec![node])
+}
+
+/// Creates a context whose main graph computes the result of an SQL query.
+///
+/// The main graph takes the tables as inputs in the given order and returns the resulting table (see [sql_query]).
+/// To evaluate the query with MPC, compile the context with [compile_context](crate::mpc::mpc_compiler::compile_context).
+///
+/// # Arguments
+///
+/// * `query` - SQL query
+/// * `tables` - names and types of tables referenced in the query
+///
+/// # Returns
+///
+/// New finalized context
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::data_types::{array_type, named_tuple_type, BIT, INT32};
+/// # use ciphercore_base::sql::create_sql_context;
+/// # use ciphercore_base::type_inference::NULL_HEADER;
+/// let users_t = named_tuple_type(vec![
+///     (NULL_HEADER.to_owned(), array_type(vec![10], BIT)),
+///     ("ID".to_owned(), array_type(vec![10], INT32)),
+///     ("Age".to_owned(), array_type(vec![10], INT32)),
+/// ]);
+/// let purchases_t = named_tuple_type(vec![
+///     (NULL_HEADER.to_owned(), array_type(vec![20], BIT)),
+///     ("UserID".to_owned(), array_type(vec![20], INT32)),
+///     ("Amount".to_owned(), array_type(vec![20], INT32)),
+/// ]);
+/// let c = create_sql_context(
+///     "SELECT u.Age, SUM(p.Amount) FROM users u JOIN purchases p ON u.ID = p.UserID GROUP BY u.Age",
+///     vec![("users".to_owned(), users_t), ("purchases".to_owned(), purchases_t)],
+/// ).unwrap();
+/// ```
+pub fn create_sql_context(query: &str, tables: Vec<(String, Type)>) -> Result<Context> {
+    let c = create_context()?;
+    let g = c.create_graph()?;
+    let mut table_nodes = vec![];
+    for (name, t) in tables {
+        table_nodes.push((name, g.input(t)?));
+    }
+    sql_query(query, &table_nodes)?.set_as_output()?;
+    g.finalize()?.set_as_main()?;
+    c.finalize()?;
+    Ok(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::run_instantiation_pass;
+    use crate::data_types::{array_type, named_tuple_type, BIT, INT32, INT64};
+    use crate::data_values::Value;
+    use crate::evaluators::{evaluate_simple_evaluator, random_evaluate};
+    use crate::inline::inline_common::DepthOptimizationLevel;
+    use crate::inline::inline_ops::{inline_operations, InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{prepare_for_mpc_evaluation, IOStatus};
+
+    fn get_tables() -> Result<Vec<(String, Type, Vec<Value>)>> {
+        let users_t = named_tuple_type(vec![
+            (NULL_HEADER.to_owned(), array_type(vec![5], BIT)),
+            ("id".to_owned(), array_type(vec![5], INT32)),
+            ("age".to_owned(), array_type(vec![5], INT32)),
+            ("country".to_owned(), array_type(vec![5], INT32)),
+        ]);
+        let users = vec![
+            Value::from_flattened_array(&[1, 1, 1, 1, 0], BIT)?,
+            Value::from_flattened_array(&[1, 2, 3, 4, 5], INT32)?,
+            Value::from_flattened_array(&[25, 17, 40, 33, 50], INT32)?,
+            Value::from_flattened_array(&[7, 7, 8, 7, 8], INT32)?,
+        ];
+        let accounts_t = named_tuple_type(vec![
+            (NULL_HEADER.to_owned(), array_type(vec![4], BIT)),
+            ("user_id".to_owned(), array_type(vec![4], INT32)),
+            ("balance".to_owned(), array_type(vec![4], INT64)),
+        ]);
+        let accounts = vec![
+            Value::from_flattened_array(&[1, 1, 1, 1], BIT)?,
+            Value::from_flattened_array(&[4, 1, 2, 5], INT32)?,
+            Value::from_flattened_array(&[100, 200, 300, 400], INT64)?,
+        ];
+        Ok(vec![
+            ("users".to_owned(), users_t, users),
+            ("accounts".to_owned(), accounts_t, accounts),
+        ])
+    }
+
+    fn evaluate_query(query: &str, is_mpc: bool) -> Result<Vec<Value>> {
+        let tables = get_tables()?;
+        let table_types = tables
+            .iter()
+            .map(|(name, t, _)| (name.clone(), t.clone()))
+            .collect();
+        let inputs: Vec<Value> = tables
+            .into_iter()
+            .map(|(_, _, columns)| Value::from_vector(columns))
+            .collect();
+        let c = create_sql_context(query, table_types)?;
+        let instantiated_c = run_instantiation_pass(c)?.get_context();
+        let result = if is_mpc {
+            let inline_config = InlineConfig {
+                default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                ..Default::default()
+            };
+            let inlined_c = inline_operations(instantiated_c, inline_config.clone())?;
+            let mpc_c = prepare_for_mpc_evaluation(
+                inlined_c,
+                vec![vec![IOStatus::Party(0), IOStatus::Party(1)]],
+                vec![vec![IOStatus::Party(0)]],
+                inline_config,
+            )?;
+            evaluate_simple_evaluator(mpc_c.get_main_graph()?, inputs, None)?
+        } else {
+            random_evaluate(instantiated_c.get_main_graph()?, inputs)?
+        };
+        result.to_vector()
+    }
+
+    fn get_result_type(query: &str) -> Result<Type> {
+        let tables = get_tables()?;
+        let c = create_sql_context(
+            query,
+            tables.into_iter().map(|(name, t, _)| (name, t)).collect(),
+        )?;
+        c.get_main_graph()?.get_output_node()?.get_type()
+    }
+
+    #[test]
+    fn test_sql_select_where() {
+        || -> Result<()> {
+            let query = "SELECT age AS a, id FROM users WHERE NOT (age < 18) AND (country = 7 OR 35 <= age)";
+            let result = evaluate_query(query, false)?;
+            assert_eq!(
+                result,
+                vec![
+                    Value::from_flattened_array(&[1, 0, 1, 1, 0], BIT)?,
+                    Value::from_flattened_array(&[25, 0, 40, 33, 0], INT32)?,
+                    Value::from_flattened_array(&[1, 0, 3, 4, 0], INT32)?,
+                ]
+            );
+            assert_eq!(
+                get_result_type(query)?,
+                named_tuple_type(vec![
+                    (NULL_HEADER.to_owned(), array_type(vec![5], BIT)),
+                    ("a".to_owned(), array_type(vec![5], INT32)),
+                    ("id".to_owned(), array_type(vec![5], INT32)),
+                ])
+            );
+            let result = evaluate_query("SELECT * FROM users WHERE id <> country", false)?;
+            // The null row is zeroed
+            assert_eq!(
+                result,
+                vec![
+                    Value::from_flattened_array(&[1, 1, 1, 1, 0], BIT)?,
+                    Value::from_flattened_array(&[1, 2, 3, 4, 0], INT32)?,
+                    Value::from_flattened_array(&[25, 17, 40, 33, 0], INT32)?,
+                    Value::from_flattened_array(&[7, 7, 8, 7, 0], INT32)?,
+                ]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_sql_join_group_by() {
+        || -> Result<()> {
+            let query = "SELECT u.country, SUM(a.balance) AS total, COUNT(*) \
+                FROM users AS u INNER JOIN accounts a ON a.user_id = u.id \
+                WHERE u.age >= 18 GROUP BY country";
+            // Adult users with accounts: 1 (country 7, balance 200), 4 (country 7, balance 100)
+            let expected = vec![
+                Value::from_flattened_array(&[1, 0, 0, 0, 0], BIT)?,
+                Value::from_flattened_array(&[7, 0, 0, 0, 0], INT32)?,
+                Value::from_flattened_array(&[300, 0, 0, 0, 0], INT64)?,
+                Value::from_flattened_array(&[2, 0, 0, 0, 0], INT64)?,
+            ];
+            for is_mpc in [false, true] {
+                assert_eq!(evaluate_query(query, is_mpc)?, expected);
+            }
+            let headers: Vec<String> = match get_result_type(query)? {
+                Type::NamedTuple(header_types) => {
+                    header_types.into_iter().map(|(h, _)| h).collect()
+                }
+                _ => panic!("Named tuple expected"),
+            };
+            assert_eq!(headers, vec![NULL_HEADER, "country", "total", "COUNT(*)"]);
+            // Aggregation without GROUP BY
+            let result = evaluate_query(
+                "SELECT SUM(balance), COUNT(user_id) FROM accounts JOIN users ON user_id = id",
+                false,
+            )?;
+            assert_eq!(
+                result,
+                vec![
+                    Value::from_flattened_array(&[1], BIT)?,
+                    Value::from_flattened_array(&[600], INT64)?,
+                    Value::from_flattened_array(&[3], INT64)?,
+                ]
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_sql() {
+        for query in [
+            "SELECT age FROM users WHERE",
+            "SELECT age FROM users; SELECT id FROM users",
+            "INSERT INTO users VALUES (1, 2, 3)",
+            "SELECT age FROM customers",
+            "SELECT name FROM users",
+            "SELECT x.age FROM users",
+            "SELECT age FROM users ORDER BY age",
+            "SELECT DISTINCT age FROM users",
+            "SELECT age FROM users, accounts",
+            "SELECT age FROM users LEFT JOIN accounts ON id = user_id",
+            "SELECT age FROM users JOIN accounts ON id < user_id",
+            "SELECT age FROM users JOIN accounts ON id = 1",
+            "SELECT age FROM users WHERE age + 1 > 2",
+            "SELECT age FROM users WHERE 1 = 2",
+            "SELECT age, SUM(id) FROM users",
+            "SELECT age FROM users GROUP BY country",
+            "SELECT MAX(age) FROM users",
+            "SELECT SUM(*) FROM users",
+            "SELECT age AS x, id AS x FROM users",
+            "SELECT age + 1 FROM users",
+        ] {
+            assert!(get_result_type(query).is_err(), "{}", query);
+        }
+    }
+}
@@ -0,0 +1,3 @@
+//! Ready-made contexts for common machine learning tasks that would otherwise require building large graphs by hand.
+pub mod neural_network;
+pub mod regression;
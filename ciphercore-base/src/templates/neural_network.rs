@@ -0,0 +1,718 @@
+//! Inference of int8-quantized neural networks, e.g. multilayer perceptrons and convolutional networks.
+//!
+//! Linear layers are computed by [Gemm](crate::graphs::Operation::Gemm) with the int32-style accumulator rescaled by [Truncate](crate::graphs::Operation::Truncate)
+//! and saturated back to the int8 range. Convolutions and pooling are computed by [Conv2D] and [MaxPool2D]
+//! and ReLU is computed by a signed comparison with zero.
+use crate::custom_ops::CustomOperation;
+use crate::data_types::{array_type, ArrayShape, INT64};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::graphs::{create_context, Context, Graph, Node};
+use crate::ops::comparisons::GreaterThan;
+use crate::ops::convolution::{Conv2D, MaxPool2D};
+use crate::ops::min_max::{Max, Min};
+use crate::ops::utils::constant_scalar;
+
+const INT8_MIN: i64 = -128;
+const INT8_MAX: i64 = 127;
+
+/// Layer of a quantized neural network.
+///
+/// Dense and convolution layers compute `(input * kernel + bias) / 2^shift` (rounded towards zero)
+/// and saturate the result to the int8 range `[-128, 127]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QuantizedLayer {
+    /// Fully connected layer applied to an input of shape `[batch, input_size]`.
+    ///
+    /// Its kernel has shape `[output_size, input_size]` and its bias has shape `[output_size]`.
+    Dense { output_size: u64, shift: u64 },
+    /// 2D convolution without padding applied to an input of shape `[batch, height, width, in_channels]`.
+    ///
+    /// Its kernel has shape `[out_channels, kernel_size, kernel_size, in_channels]` and its bias has shape `[out_channels]`.
+    /// The output has shape `[batch, (height - kernel_size) / stride + 1, (width - kernel_size) / stride + 1, out_channels]`.
+    Conv2d {
+        out_channels: u64,
+        kernel_size: u64,
+        stride: u64,
+        shift: u64,
+    },
+    /// Maximum of every `pool_size x pool_size` window of an input of shape `[batch, height, width, channels]` without padding.
+    MaxPool2d { pool_size: u64, stride: u64 },
+    /// Elementwise `max(x, 0)`.
+    Relu,
+    /// Reshapes an input of shape `[batch, ...]` to `[batch, size]`, e.g. between convolutions and dense layers.
+    Flatten,
+}
+
+/// Kernel and bias of a dense or convolution layer flattened in the row-major order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuantizedLayerWeights {
+    /// int8 values
+    pub kernel: Vec<i64>,
+    /// int32 values already scaled to the accumulator of the layer
+    pub bias: Vec<i64>,
+}
+
+/// Source of the weights of a quantized network.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetworkWeights {
+    /// Weights of every dense and convolution layer in the order of layers are embedded into the graph as constants,
+    /// e.g. if the model is public or owned by the party compiling the graph.
+    Constants(Vec<QuantizedLayerWeights>),
+    /// Weights are additional inputs of the graph following the network input:
+    /// the kernel and the bias of every dense and convolution layer in the order of layers.
+    /// This allows them to be secret-shared or owned by a computing party.
+    Inputs,
+}
+
+/// Description of a quantized network.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuantizedNetworkConfig {
+    /// Shape of the input of the network, e.g. `[batch, features]` or `[batch, height, width, channels]`
+    pub input_shape: ArrayShape,
+    pub layers: Vec<QuantizedLayer>,
+    pub weights: NetworkWeights,
+}
+
+// Shapes of the kernel and the bias of a layer with weights
+struct WeightShapes {
+    kernel: ArrayShape,
+    bias: ArrayShape,
+}
+
+// Returns the output shape of a layer and the shapes of its weights if it has any
+fn get_layer_shapes(
+    layer: &QuantizedLayer,
+    input_shape: &[u64],
+) -> Result<(ArrayShape, Option<WeightShapes>)> {
+    match layer {
+        QuantizedLayer::Dense { output_size, shift } => {
+            validate_shift(*shift)?;
+            if input_shape.len() != 2 {
+                return Err(runtime_error!(
+                    "Dense layer expects an input of shape [batch, input_size], got {:?}",
+                    input_shape
+                ));
+            }
+            if *output_size == 0 {
+                return Err(runtime_error!(
+                    "Output size of a dense layer must be positive"
+                ));
+            }
+            Ok((
+                vec![input_shape[0], *output_size],
+                Some(WeightShapes {
+                    kernel: vec![*output_size, input_shape[1]],
+                    bias: vec![*output_size],
+                }),
+            ))
+        }
+        QuantizedLayer::Conv2d {
+            out_channels,
+            kernel_size,
+            stride,
+            shift,
+        } => {
+            validate_shift(*shift)?;
+            if input_shape.len() != 4 {
+                return Err(runtime_error!(
+                    "Conv2d layer expects an input of shape [batch, height, width, channels], got {:?}",
+                    input_shape
+                ));
+            }
+            if *out_channels == 0 || *stride == 0 {
+                return Err(runtime_error!(
+                    "Number of output channels and stride of a convolution must be positive"
+                ));
+            }
+            if *kernel_size == 0 || *kernel_size > input_shape[1] || *kernel_size > input_shape[2] {
+                return Err(runtime_error!(
+                    "Kernel size {} doesn't fit the input of shape {:?}",
+                    kernel_size,
+                    input_shape
+                ));
+            }
+            Ok((
+                vec![
+                    input_shape[0],
+                    (input_shape[1] - kernel_size) / stride + 1,
+                    (input_shape[2] - kernel_size) / stride + 1,
+                    *out_channels,
+                ],
+                Some(WeightShapes {
+                    kernel: vec![*out_channels, *kernel_size, *kernel_size, input_shape[3]],
+                    bias: vec![*out_channels],
+                }),
+            ))
+        }
+        QuantizedLayer::MaxPool2d { pool_size, stride } => {
+            if input_shape.len() != 4 {
+                return Err(runtime_error!(
+                    "MaxPool2d layer expects an input of shape [batch, height, width, channels], got {:?}",
+                    input_shape
+                ));
+            }
+            if *stride == 0
+                || *pool_size == 0
+                || *pool_size > input_shape[1]
+                || *pool_size > input_shape[2]
+            {
+                return Err(runtime_error!(
+                    "Pooling with size {} and stride {} doesn't fit the input of shape {:?}",
+                    pool_size,
+                    stride,
+                    input_shape
+                ));
+            }
+            Ok((
+                vec![
+                    input_shape[0],
+                    (input_shape[1] - pool_size) / stride + 1,
+                    (input_shape[2] - pool_size) / stride + 1,
+                    input_shape[3],
+                ],
+                None,
+            ))
+        }
+        QuantizedLayer::Relu => Ok((input_shape.to_vec(), None)),
+        QuantizedLayer::Flatten => {
+            if input_shape.len() < 2 {
+                return Err(runtime_error!(
+                    "Flatten expects an input with a batch dimension, got {:?}",
+                    input_shape
+                ));
+            }
+            Ok((
+                vec![input_shape[0], input_shape[1..].iter().product()],
+                None,
+            ))
+        }
+    }
+}
+
+fn validate_shift(shift: u64) -> Result<()> {
+    if shift > 31 {
+        return Err(runtime_error!("Requantization shift must be at most 31"));
+    }
+    Ok(())
+}
+
+fn validate_values(values: &[i64], expected_shape: &[u64], min: i64, max: i64) -> Result<()> {
+    let expected_size: u64 = expected_shape.iter().product();
+    if values.len() as u64 != expected_size {
+        return Err(runtime_error!(
+            "Expected {} weights of shape {:?}, got {}",
+            expected_size,
+            expected_shape,
+            values.len()
+        ));
+    }
+    if values.iter().any(|v| *v < min || *v > max) {
+        return Err(runtime_error!(
+            "Weights must be between {} and {}",
+            min,
+            max
+        ));
+    }
+    Ok(())
+}
+
+// Checks the config and returns the weight shapes of all the dense and convolution layers
+fn validate_config(config: &QuantizedNetworkConfig) -> Result<Vec<WeightShapes>> {
+    if config.input_shape.is_empty() || config.input_shape.contains(&0) {
+        return Err(runtime_error!(
+            "Input shape must be non-empty with positive dimensions"
+        ));
+    }
+    if config.layers.is_empty() {
+        return Err(runtime_error!("Network must have at least one layer"));
+    }
+    let mut shape = config.input_shape.clone();
+    let mut weight_shapes = vec![];
+    for layer in &config.layers {
+        let (output_shape, layer_weight_shapes) = get_layer_shapes(layer, &shape)?;
+        shape = output_shape;
+        weight_shapes.extend(layer_weight_shapes);
+    }
+    if let NetworkWeights::Constants(weights) = &config.weights {
+        if weights.len() != weight_shapes.len() {
+            return Err(runtime_error!(
+                "Expected weights of {} layers, got {}",
+                weight_shapes.len(),
+                weights.len()
+            ));
+        }
+        for (layer_weights, shapes) in weights.iter().zip(weight_shapes.iter()) {
+            validate_values(&layer_weights.kernel, &shapes.kernel, INT8_MIN, INT8_MAX)?;
+            validate_values(
+                &layer_weights.bias,
+                &shapes.bias,
+                i32::MIN as i64,
+                i32::MAX as i64,
+            )?;
+        }
+    }
+    Ok(weight_shapes)
+}
+
+// Adds the bias to the accumulator of a linear layer, rescales it and saturates it to int8
+fn requantize(accumulator: Node, bias: Node, shift: u64) -> Result<Node> {
+    let g = accumulator.get_graph();
+    let mut result = accumulator.add(bias)?;
+    if shift > 0 {
+        result = result.truncate(1 << shift)?;
+    }
+    let result = g.custom_op(
+        CustomOperation::new(Max {
+            signed_comparison: true,
+        }),
+        vec![result, constant_scalar(&g, INT8_MIN, INT64)?],
+    )?;
+    g.custom_op(
+        CustomOperation::new(Min {
+            signed_comparison: true,
+        }),
+        vec![result, constant_scalar(&g, INT8_MAX, INT64)?],
+    )
+}
+
+fn relu(x: Node) -> Result<Node> {
+    let g = x.get_graph();
+    let is_positive = g.custom_op(
+        CustomOperation::new(GreaterThan {
+            signed_comparison: true,
+        }),
+        vec![x.clone(), constant_scalar(&g, 0, INT64)?],
+    )?;
+    x.mixed_multiply(is_positive)
+}
+
+// Adds nodes applying a layer and returns its output
+fn add_layer(x: Node, layer: &QuantizedLayer, weights: Option<(Node, Node)>) -> Result<Node> {
+    let shape = x.get_type()?.get_shape();
+    let (output_shape, _) = get_layer_shapes(layer, &shape)?;
+    match (layer, weights) {
+        (QuantizedLayer::Dense { shift, .. }, Some((kernel, bias))) => {
+            requantize(x.gemm(kernel, false, true)?, bias, *shift)
+        }
+        (QuantizedLayer::Conv2d { stride, shift, .. }, Some((kernel, bias))) => {
+            let g = x.get_graph();
+            let accumulator = g.custom_op(
+                CustomOperation::new(Conv2D { stride: *stride }),
+                vec![x, kernel],
+            )?;
+            requantize(accumulator, bias, *shift)
+        }
+        (QuantizedLayer::MaxPool2d { pool_size, stride }, None) => x.get_graph().custom_op(
+            CustomOperation::new(MaxPool2D {
+                pool_size: *pool_size,
+                stride: *stride,
+                signed_comparison: true,
+            }),
+            vec![x],
+        ),
+        (QuantizedLayer::Relu, None) => relu(x),
+        (QuantizedLayer::Flatten, None) => x.reshape(array_type(output_shape, INT64)),
+        _ => Err(runtime_error!("Unexpected weights of layer {:?}", layer)),
+    }
+}
+
+/// Creates a graph that computes the inference of an int8-quantized neural network.
+///
+/// The first input of the graph is a signed 64-bit array of shape `config.input_shape` with int8 values.
+/// If weights are given by [NetworkWeights::Inputs], the graph takes a signed 64-bit kernel and bias of every dense and convolution layer as further inputs.
+/// The output is a signed 64-bit array with int8 values produced by the last layer.
+///
+/// Scales of quantized values are restricted to powers of two, so that the accumulator of every linear layer is rescaled by [Truncate](crate::graphs::Operation::Truncate).
+/// Within MPC, truncation might introduce an error of 1 in the least significant bit of the result.
+///
+/// # Arguments
+///
+/// * `context` - context where an inference graph should be created
+/// * `config` - description of the network
+///
+/// # Returns
+///
+/// Graph that computes the output of the network
+pub fn create_quantized_inference_graph(
+    context: Context,
+    config: QuantizedNetworkConfig,
+) -> Result<Graph> {
+    let weight_shapes = validate_config(&config)?;
+    let g = context.create_graph()?;
+    let mut x = g.input(array_type(config.input_shape.clone(), INT64))?;
+    let mut weights = vec![];
+    for (i, shapes) in weight_shapes.into_iter().enumerate() {
+        let (kernel, bias) = match &config.weights {
+            NetworkWeights::Constants(values) => (
+                g.constant(
+                    array_type(shapes.kernel.clone(), INT64),
+                    Value::from_flattened_array(&values[i].kernel, INT64)?,
+                )?,
+                g.constant(
+                    array_type(shapes.bias.clone(), INT64),
+                    Value::from_flattened_array(&values[i].bias, INT64)?,
+                )?,
+            ),
+            NetworkWeights::Inputs => (
+                g.input(array_type(shapes.kernel, INT64))?,
+                g.input(array_type(shapes.bias, INT64))?,
+            ),
+        };
+        weights.push((kernel, bias));
+    }
+    let mut weights = weights.into_iter();
+    for layer in &config.layers {
+        let layer_weights = match layer {
+            QuantizedLayer::Dense { .. } | QuantizedLayer::Conv2d { .. } => weights.next(),
+            QuantizedLayer::MaxPool2d { .. } | QuantizedLayer::Relu | QuantizedLayer::Flatten => {
+                None
+            }
+        };
+        x = add_layer(x, layer, layer_weights)?;
+    }
+    x.set_as_output()?;
+    g.finalize()?;
+    Ok(g)
+}
+
+/// Creates a finalized context whose main graph computes the inference of a quantized network as described in [create_quantized_inference_graph].
+///
+/// The context contains custom operations and should be compiled before evaluation, e.g. by [compile_context](crate::mpc::mpc_compiler::compile_context)
+/// with the input owned by a client and the weights owned by a model provider.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::templates::neural_network::{create_quantized_inference_context, NetworkWeights, QuantizedLayer, QuantizedNetworkConfig};
+/// let config = QuantizedNetworkConfig {
+///     input_shape: vec![1, 8, 8, 1],
+///     layers: vec![
+///         QuantizedLayer::Conv2d { out_channels: 4, kernel_size: 3, stride: 1, shift: 4 },
+///         QuantizedLayer::Relu,
+///         QuantizedLayer::Flatten,
+///         QuantizedLayer::Dense { output_size: 10, shift: 6 },
+///     ],
+///     weights: NetworkWeights::Inputs,
+/// };
+/// let c = create_quantized_inference_context(config).unwrap();
+/// ```
+pub fn create_quantized_inference_context(config: QuantizedNetworkConfig) -> Result<Context> {
+    let context = create_context()?;
+    let g = create_quantized_inference_graph(context.clone(), config)?;
+    g.set_as_main()?;
+    context.finalize()?;
+    Ok(context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::run_instantiation_pass;
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::inline::inline_common::DepthOptimizationLevel;
+    use crate::inline::inline_ops::{InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{compile_context, IOStatus};
+
+    fn requantize_plaintext(accumulator: i64, shift: u64) -> i64 {
+        (accumulator / (1 << shift)).clamp(INT8_MIN, INT8_MAX)
+    }
+
+    // Plaintext reference: returns the flattened output and its shape
+    fn infer_plaintext(
+        config: &QuantizedNetworkConfig,
+        input: &[i64],
+        weights: &[QuantizedLayerWeights],
+    ) -> (Vec<i64>, ArrayShape) {
+        let mut x = input.to_vec();
+        let mut shape = config.input_shape.clone();
+        let mut weights = weights.iter();
+        for layer in &config.layers {
+            match layer {
+                QuantizedLayer::Dense { output_size, shift } => {
+                    let w = weights.next().unwrap();
+                    let (n, m, k) = (shape[0] as usize, shape[1] as usize, *output_size as usize);
+                    let mut y = vec![0; n * k];
+                    for b in 0..n {
+                        for o in 0..k {
+                            let mut acc = w.bias[o];
+                            for i in 0..m {
+                                acc += x[b * m + i] * w.kernel[o * m + i];
+                            }
+                            y[b * k + o] = requantize_plaintext(acc, *shift);
+                        }
+                    }
+                    x = y;
+                    shape = vec![shape[0], *output_size];
+                }
+                QuantizedLayer::Conv2d {
+                    out_channels,
+                    kernel_size,
+                    stride,
+                    shift,
+                } => {
+                    let w = weights.next().unwrap();
+                    let (n, h, wd, c) = (
+                        shape[0] as usize,
+                        shape[1] as usize,
+                        shape[2] as usize,
+                        shape[3] as usize,
+                    );
+                    let (oc, k, s) = (
+                        *out_channels as usize,
+                        *kernel_size as usize,
+                        *stride as usize,
+                    );
+                    let (oh, ow) = ((h - k) / s + 1, (wd - k) / s + 1);
+                    let mut y = vec![0; n * oh * ow * oc];
+                    for b in 0..n {
+                        for r in 0..oh {
+                            for col in 0..ow {
+                                for o in 0..oc {
+                                    let mut acc = w.bias[o];
+                                    for i in 0..k {
+                                        for j in 0..k {
+                                            for ch in 0..c {
+                                                let input_index =
+                                                    ((b * h + r * s + i) * wd + col * s + j) * c
+                                                        + ch;
+                                                let kernel_index = ((o * k + i) * k + j) * c + ch;
+                                                acc += x[input_index] * w.kernel[kernel_index];
+                                            }
+                                        }
+                                    }
+                                    y[((b * oh + r) * ow + col) * oc + o] =
+                                        requantize_plaintext(acc, *shift);
+                                }
+                            }
+                        }
+                    }
+                    x = y;
+                    shape = vec![shape[0], oh as u64, ow as u64, *out_channels];
+                }
+                QuantizedLayer::MaxPool2d { pool_size, stride } => {
+                    let (n, h, wd, c) = (
+                        shape[0] as usize,
+                        shape[1] as usize,
+                        shape[2] as usize,
+                        shape[3] as usize,
+                    );
+                    let (k, s) = (*pool_size as usize, *stride as usize);
+                    let (oh, ow) = ((h - k) / s + 1, (wd - k) / s + 1);
+                    let mut y = vec![INT8_MIN; n * oh * ow * c];
+                    for b in 0..n {
+                        for r in 0..oh {
+                            for col in 0..ow {
+                                for ch in 0..c {
+                                    let output_index = ((b * oh + r) * ow + col) * c + ch;
+                                    for i in 0..k {
+                                        for j in 0..k {
+                                            let input_index =
+                                                ((b * h + r * s + i) * wd + col * s + j) * c + ch;
+                                            y[output_index] = y[output_index].max(x[input_index]);
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    x = y;
+                    shape = vec![shape[0], oh as u64, ow as u64, shape[3]];
+                }
+                QuantizedLayer::Relu => {
+                    x = x.iter().map(|v| (*v).max(0)).collect();
+                }
+                QuantizedLayer::Flatten => {
+                    shape = vec![shape[0], shape[1..].iter().product()];
+                }
+            }
+        }
+        (x, shape)
+    }
+
+    // Deterministic pseudo-random values in [min, max]
+    fn get_values(n: u64, seed: i64, min: i64, max: i64) -> Vec<i64> {
+        (0..n as i64)
+            .map(|i| min + (i * 37 + seed * 101 + i * i * 13).rem_euclid(max - min + 1))
+            .collect()
+    }
+
+    fn get_weights(config: &QuantizedNetworkConfig) -> Vec<QuantizedLayerWeights> {
+        let mut config = config.clone();
+        config.weights = NetworkWeights::Inputs;
+        let weight_shapes = validate_config(&config).unwrap();
+        weight_shapes
+            .iter()
+            .enumerate()
+            .map(|(i, shapes)| QuantizedLayerWeights {
+                kernel: get_values(shapes.kernel.iter().product(), i as i64, INT8_MIN, INT8_MAX),
+                bias: get_values(shapes.bias.iter().product(), i as i64 + 7, -2000, 2000),
+            })
+            .collect()
+    }
+
+    fn get_cnn_config(weights: NetworkWeights) -> QuantizedNetworkConfig {
+        QuantizedNetworkConfig {
+            input_shape: vec![2, 6, 5, 2],
+            layers: vec![
+                QuantizedLayer::Conv2d {
+                    out_channels: 3,
+                    kernel_size: 3,
+                    stride: 1,
+                    shift: 6,
+                },
+                QuantizedLayer::MaxPool2d {
+                    pool_size: 2,
+                    stride: 1,
+                },
+                QuantizedLayer::Relu,
+                QuantizedLayer::Flatten,
+                QuantizedLayer::Dense {
+                    output_size: 4,
+                    shift: 8,
+                },
+            ],
+            weights,
+        }
+    }
+
+    fn get_mlp_config(weights: NetworkWeights) -> QuantizedNetworkConfig {
+        QuantizedNetworkConfig {
+            input_shape: vec![3, 10],
+            layers: vec![
+                QuantizedLayer::Dense {
+                    output_size: 6,
+                    shift: 7,
+                },
+                QuantizedLayer::Relu,
+                QuantizedLayer::Dense {
+                    output_size: 2,
+                    shift: 6,
+                },
+            ],
+            weights,
+        }
+    }
+
+    fn test_network(mut config: QuantizedNetworkConfig) -> Result<()> {
+        let weights = get_weights(&config);
+        let input = get_values(config.input_shape.iter().product(), 42, INT8_MIN, INT8_MAX);
+        let (expected, expected_shape) = infer_plaintext(&config, &input, &weights);
+        let mut inputs = vec![Value::from_flattened_array(&input, INT64)?];
+        match config.weights {
+            NetworkWeights::Inputs => {
+                for layer_weights in &weights {
+                    inputs.push(Value::from_flattened_array(&layer_weights.kernel, INT64)?);
+                    inputs.push(Value::from_flattened_array(&layer_weights.bias, INT64)?);
+                }
+            }
+            NetworkWeights::Constants(_) => {
+                config.weights = NetworkWeights::Constants(weights);
+            }
+        }
+        let c = create_quantized_inference_context(config)?;
+        let instantiated_c = run_instantiation_pass(c)?.get_context();
+        let result = random_evaluate(instantiated_c.get_main_graph()?, inputs)?
+            .to_flattened_array_i64(array_type(expected_shape, INT64))?;
+        assert_eq!(result, expected);
+        // Saturation and ReLU are exercised
+        assert!(expected.iter().any(|v| *v == INT8_MAX || *v == INT8_MIN));
+        assert!(expected.iter().any(|v| *v != INT8_MAX && *v != INT8_MIN));
+        Ok(())
+    }
+
+    #[test]
+    fn test_quantized_mlp() {
+        test_network(get_mlp_config(NetworkWeights::Inputs)).unwrap();
+        test_network(get_mlp_config(NetworkWeights::Constants(vec![]))).unwrap();
+    }
+
+    #[test]
+    fn test_quantized_cnn() {
+        test_network(get_cnn_config(NetworkWeights::Inputs)).unwrap();
+        test_network(get_cnn_config(NetworkWeights::Constants(vec![]))).unwrap();
+    }
+
+    #[test]
+    fn test_quantized_inference_compiles() {
+        || -> Result<()> {
+            let c = create_quantized_inference_context(get_cnn_config(NetworkWeights::Inputs))?;
+            compile_context(
+                c,
+                vec![
+                    IOStatus::Party(0),
+                    IOStatus::Party(1),
+                    IOStatus::Party(1),
+                    IOStatus::Shared,
+                    IOStatus::Shared,
+                ],
+                vec![IOStatus::Party(0)],
+                InlineConfig {
+                    default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                    ..Default::default()
+                },
+                || SimpleEvaluator::new(None),
+            )?;
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_network_config() {
+        let config = get_mlp_config(NetworkWeights::Inputs);
+        assert!(create_quantized_inference_context(config.clone()).is_ok());
+        let mut bad_config = config.clone();
+        bad_config.layers = vec![];
+        assert!(create_quantized_inference_context(bad_config).is_err());
+        let mut bad_config = config.clone();
+        bad_config.input_shape = vec![3, 0];
+        assert!(create_quantized_inference_context(bad_config).is_err());
+        let mut bad_config = config.clone();
+        bad_config.layers[0] = QuantizedLayer::Dense {
+            output_size: 6,
+            shift: 40,
+        };
+        assert!(create_quantized_inference_context(bad_config).is_err());
+        // Convolution of a 2-dimensional input
+        let mut bad_config = config.clone();
+        bad_config.layers[0] = QuantizedLayer::Conv2d {
+            out_channels: 2,
+            kernel_size: 1,
+            stride: 1,
+            shift: 0,
+        };
+        assert!(create_quantized_inference_context(bad_config).is_err());
+        // Kernel larger than the input
+        let mut bad_config = get_cnn_config(NetworkWeights::Inputs);
+        bad_config.layers[0] = QuantizedLayer::Conv2d {
+            out_channels: 2,
+            kernel_size: 6,
+            stride: 1,
+            shift: 0,
+        };
+        assert!(create_quantized_inference_context(bad_config).is_err());
+        let mut bad_config = get_cnn_config(NetworkWeights::Inputs);
+        bad_config.layers[1] = QuantizedLayer::MaxPool2d {
+            pool_size: 2,
+            stride: 0,
+        };
+        assert!(create_quantized_inference_context(bad_config).is_err());
+        // Wrong number of constant weights and out-of-range values
+        let mut weights = get_weights(&config);
+        let mut bad_config = config.clone();
+        bad_config.weights = NetworkWeights::Constants(weights[..1].to_vec());
+        assert!(create_quantized_inference_context(bad_config).is_err());
+        let mut bad_config = config.clone();
+        bad_config.weights = NetworkWeights::Constants(weights.clone());
+        assert!(create_quantized_inference_context(bad_config.clone()).is_ok());
+        weights[0].kernel[0] = 128;
+        bad_config.weights = NetworkWeights::Constants(weights.clone());
+        assert!(create_quantized_inference_context(bad_config.clone()).is_err());
+        weights[0].kernel[0] = 0;
+        weights[1].bias.pop();
+        bad_config.weights = NetworkWeights::Constants(weights);
+        assert!(create_quantized_inference_context(bad_config).is_err());
+    }
+}
@@ -0,0 +1,348 @@
+//! Training of linear and logistic regression via [mini-batch stochastic gradient descent](https://en.wikipedia.org/wiki/Stochastic_gradient_descent).
+use crate::custom_ops::CustomOperation;
+use crate::data_types::{array_type, INT64};
+use crate::errors::Result;
+use crate::graphs::{create_context, Context, Graph, Node, SliceElement};
+use crate::ops::pwl::approx_sigmoid::ApproxSigmoid;
+use crate::ops::utils::{constant_scalar, zeros};
+
+/// Type of a regression model.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegressionModel {
+    /// Linear regression minimizing the mean squared error.
+    Linear,
+    /// Logistic regression minimizing the cross-entropy loss; the sigmoid is computed by [ApproxSigmoid].
+    Logistic,
+}
+
+/// Parameters of regression training.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SgdRegressionConfig {
+    pub model: RegressionModel,
+    /// Number of passes over the training data
+    pub epochs: u64,
+    /// Number of samples used to compute one gradient step; the last batch of an epoch can be smaller
+    pub batch_size: u64,
+    pub learning_rate: f64,
+    /// Assume that we're operating in fixed precision arithmetic with denominator 2 ** fixed_precision_points.
+    pub fixed_precision_points: u64,
+}
+
+// Adds nodes performing one gradient descent step on a given batch and returns the updated weights
+fn add_gradient_step(
+    weights: Node,
+    features: Node,
+    labels: Node,
+    config: &SgdRegressionConfig,
+) -> Result<Node> {
+    let g = weights.get_graph();
+    let precision = config.fixed_precision_points;
+    let batch_size = features.get_type()?.get_shape()[0];
+
+    let mut predictions = features.matmul(weights.clone())?.truncate(1 << precision)?;
+    if config.model == RegressionModel::Logistic {
+        predictions = g.custom_op(
+            CustomOperation::new(ApproxSigmoid { precision }),
+            vec![predictions],
+        )?;
+    }
+    // For both models, the gradient of the loss function is X^T * (predictions - labels) / batch_size
+    let errors = predictions.subtract(labels)?;
+    let gradient = features
+        .permute_axes(vec![1, 0])?
+        .matmul(errors)?
+        .truncate(1 << precision)?;
+    let step_size = get_fixed_point_step_size(config, batch_size)?;
+    let step = gradient
+        .multiply(constant_scalar(&g, step_size, INT64)?)?
+        .truncate(1 << precision)?;
+    weights.subtract(step)
+}
+
+// Returns learning_rate / batch_size in the fixed-point representation
+fn get_fixed_point_step_size(config: &SgdRegressionConfig, batch_size: u64) -> Result<i64> {
+    let step_size = (config.learning_rate / batch_size as f64
+        * (1u64 << config.fixed_precision_points) as f64)
+        .round() as i64;
+    if step_size <= 0 {
+        return Err(runtime_error!(
+            "Learning rate {} is too small to be represented with {} fixed-point bits",
+            config.learning_rate,
+            config.fixed_precision_points
+        ));
+    }
+    Ok(step_size)
+}
+
+fn validate_config(
+    num_samples: u64,
+    num_features: u64,
+    config: &SgdRegressionConfig,
+) -> Result<()> {
+    if num_samples == 0 || num_features == 0 {
+        return Err(runtime_error!(
+            "Number of samples and features must be positive"
+        ));
+    }
+    if config.epochs == 0 {
+        return Err(runtime_error!("Number of epochs must be positive"));
+    }
+    if config.batch_size == 0 || config.batch_size > num_samples {
+        return Err(runtime_error!(
+            "Batch size must be between 1 and the number of samples"
+        ));
+    }
+    if config.fixed_precision_points == 0 || config.fixed_precision_points > 30 {
+        return Err(runtime_error!(
+            "fixed_precision_points must be between 1 and 30"
+        ));
+    }
+    if config.learning_rate.is_nan() || config.learning_rate <= 0.0 {
+        return Err(runtime_error!("Learning rate must be positive"));
+    }
+    for batch_size in [config.batch_size, num_samples % config.batch_size] {
+        if batch_size > 0 {
+            get_fixed_point_step_size(config, batch_size)?;
+        }
+    }
+    Ok(())
+}
+
+/// Creates a graph that trains a linear or logistic regression model via mini-batch stochastic gradient descent.
+///
+/// The graph takes the following inputs:
+///
+/// * a signed 64-bit array of shape `[num_samples, num_features]` containing features of training samples,
+/// * a signed 64-bit array of shape `[num_samples]` containing labels (for logistic regression, labels must be 0 or 1).
+///
+/// All values are in the fixed-point representation with denominator 2<sup>fixed_precision_points</sup>.
+/// The output is a signed 64-bit array of shape `[num_features]` with the trained weights in the same representation.
+///
+/// Weights are initialized with zeros, and batches are taken in the order of samples, so the graph is deterministic
+/// (apart from the randomness used by the MPC protocol).
+/// To train a model with a bias term, add a feature column filled with ones.
+///
+/// The whole training is unrolled, so the graph size is proportional to `epochs * num_samples / batch_size`.
+/// Features, labels and weights must be small enough to avoid integer overflows in fixed-point products.
+///
+/// # Arguments
+///
+/// * `context` - context where a training graph should be created
+/// * `num_samples` - number of training samples
+/// * `num_features` - number of features of every sample
+/// * `config` - parameters of training
+///
+/// # Returns
+///
+/// Graph that trains a regression model
+pub fn create_regression_training_graph(
+    context: Context,
+    num_samples: u64,
+    num_features: u64,
+    config: SgdRegressionConfig,
+) -> Result<Graph> {
+    validate_config(num_samples, num_features, &config)?;
+    let g = context.create_graph()?;
+    let features = g.input(array_type(vec![num_samples, num_features], INT64))?;
+    let labels = g.input(array_type(vec![num_samples], INT64))?;
+    let mut weights = zeros(&g, array_type(vec![num_features], INT64))?;
+    for _ in 0..config.epochs {
+        for start in (0..num_samples).step_by(config.batch_size as usize) {
+            let end = num_samples.min(start + config.batch_size);
+            let batch = vec![SliceElement::SubArray(
+                Some(start as i64),
+                Some(end as i64),
+                None,
+            )];
+            weights = add_gradient_step(
+                weights,
+                features.get_slice(batch.clone())?,
+                labels.get_slice(batch)?,
+                &config,
+            )?;
+        }
+    }
+    weights.set_as_output()?;
+    g.finalize()?;
+    Ok(g)
+}
+
+/// Creates a finalized context whose main graph trains a regression model as described in [create_regression_training_graph].
+///
+/// The context contains custom operations and should be compiled before evaluation, e.g. by [compile_context](crate::mpc::mpc_compiler::compile_context)
+/// with features and labels either secret-shared or owned by the computing parties.
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::templates::regression::{create_regression_training_context, RegressionModel, SgdRegressionConfig};
+/// let config = SgdRegressionConfig {
+///     model: RegressionModel::Logistic,
+///     epochs: 2,
+///     batch_size: 16,
+///     learning_rate: 0.5,
+///     fixed_precision_points: 15,
+/// };
+/// let c = create_regression_training_context(64, 5, config).unwrap();
+/// ```
+pub fn create_regression_training_context(
+    num_samples: u64,
+    num_features: u64,
+    config: SgdRegressionConfig,
+) -> Result<Context> {
+    let context = create_context()?;
+    let g = create_regression_training_graph(context.clone(), num_samples, num_features, config)?;
+    g.set_as_main()?;
+    context.finalize()?;
+    Ok(context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::run_instantiation_pass;
+    use crate::data_values::Value;
+    use crate::evaluators::random_evaluate;
+    use crate::evaluators::simple_evaluator::SimpleEvaluator;
+    use crate::inline::inline_common::DepthOptimizationLevel;
+    use crate::inline::inline_ops::{InlineConfig, InlineMode};
+    use crate::mpc::mpc_compiler::{compile_context, IOStatus};
+
+    const PRECISION: u64 = 15;
+
+    fn to_fixed(x: &[f64]) -> Vec<i64> {
+        x.iter()
+            .map(|v| (v * (1 << PRECISION) as f64).round() as i64)
+            .collect()
+    }
+
+    // Returns samples with features [1, t] for t in [-1, 1]
+    fn get_features() -> (Vec<f64>, Vec<f64>) {
+        let ts = vec![-1.0, 0.25, -0.5, 1.0, -0.75, 0.5, -0.25, 0.75];
+        let mut features = vec![];
+        for t in ts.iter() {
+            features.extend([1.0, *t]);
+        }
+        (ts, features)
+    }
+
+    fn train(config: SgdRegressionConfig, features: &[f64], labels: &[f64]) -> Result<Vec<f64>> {
+        let c = create_regression_training_context(labels.len() as u64, 2, config)?;
+        let instantiated_c = run_instantiation_pass(c)?.get_context();
+        let result = random_evaluate(
+            instantiated_c.get_main_graph()?,
+            vec![
+                Value::from_flattened_array(&to_fixed(features), INT64)?,
+                Value::from_flattened_array(&to_fixed(labels), INT64)?,
+            ],
+        )?;
+        Ok(result
+            .to_flattened_array_i64(array_type(vec![2], INT64))?
+            .iter()
+            .map(|w| *w as f64 / (1 << PRECISION) as f64)
+            .collect())
+    }
+
+    #[test]
+    fn test_linear_regression() {
+        || -> Result<()> {
+            let (ts, features) = get_features();
+            let labels: Vec<f64> = ts.iter().map(|t| 0.5 + 2.0 * t).collect();
+            let weights = train(
+                SgdRegressionConfig {
+                    model: RegressionModel::Linear,
+                    epochs: 30,
+                    batch_size: 3,
+                    learning_rate: 0.5,
+                    fixed_precision_points: PRECISION,
+                },
+                &features,
+                &labels,
+            )?;
+            assert!((weights[0] - 0.5).abs() < 0.01, "{:?}", weights);
+            assert!((weights[1] - 2.0).abs() < 0.01, "{:?}", weights);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_logistic_regression() {
+        || -> Result<()> {
+            let (ts, features) = get_features();
+            let labels: Vec<f64> = ts
+                .iter()
+                .map(|t| if *t > 0.1 { 1.0 } else { 0.0 })
+                .collect();
+            let weights = train(
+                SgdRegressionConfig {
+                    model: RegressionModel::Logistic,
+                    epochs: 20,
+                    batch_size: 4,
+                    learning_rate: 1.0,
+                    fixed_precision_points: PRECISION,
+                },
+                &features,
+                &labels,
+            )?;
+            for (t, label) in ts.iter().zip(labels.iter()) {
+                let logit = weights[0] + weights[1] * t;
+                assert_eq!(logit > 0.0, *label == 1.0, "{:?}", weights);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_regression_training_compiles() {
+        || -> Result<()> {
+            let c = create_regression_training_context(
+                4,
+                2,
+                SgdRegressionConfig {
+                    model: RegressionModel::Logistic,
+                    epochs: 1,
+                    batch_size: 2,
+                    learning_rate: 0.1,
+                    fixed_precision_points: PRECISION,
+                },
+            )?;
+            compile_context(
+                c,
+                vec![IOStatus::Shared, IOStatus::Party(0)],
+                vec![IOStatus::Party(1)],
+                InlineConfig {
+                    default_mode: InlineMode::DepthOptimized(DepthOptimizationLevel::Default),
+                    ..Default::default()
+                },
+                || SimpleEvaluator::new(None),
+            )?;
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_malformed_regression_config() {
+        let config = SgdRegressionConfig {
+            model: RegressionModel::Linear,
+            epochs: 1,
+            batch_size: 4,
+            learning_rate: 0.1,
+            fixed_precision_points: PRECISION,
+        };
+        assert!(create_regression_training_context(8, 2, config.clone()).is_ok());
+        assert!(create_regression_training_context(0, 2, config.clone()).is_err());
+        assert!(create_regression_training_context(2, 2, config.clone()).is_err());
+        let mut bad_config = config.clone();
+        bad_config.epochs = 0;
+        assert!(create_regression_training_context(8, 2, bad_config).is_err());
+        let mut bad_config = config.clone();
+        bad_config.learning_rate = 1e-9;
+        assert!(create_regression_training_context(8, 2, bad_config).is_err());
+        let mut bad_config = config;
+        bad_config.fixed_precision_points = 40;
+        assert!(create_regression_training_context(8, 2, bad_config).is_err());
+    }
+}
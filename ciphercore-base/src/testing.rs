@@ -0,0 +1,771 @@
+//! Property-based testing of the MPC compiler.
+//!
+//! [MpcEquivalenceChecker] compiles a given context into the ABY3 protocol and checks that the compiled context
+//! computes the same function as the plaintext one on random inputs generated by [proptest].
+//! Inputs are generated from the input types of the context via [value_strategy],
+//! or by a custom strategy if inputs must satisfy some constraints (e.g. keys of set intersection must be unique).
+//! MPC protocols that the compiler can't produce from plaintext operations (e.g. custom operations on shares)
+//! are checked against a hand-written MPC context via [MpcEquivalenceChecker::new_with_mpc_context].
+//!
+//! The plaintext context must be deterministic, i.e. it can't contain random nodes.
+//!
+//! This module is available in tests of this crate and in downstream crates with the `testing` feature enabled.
+use crate::custom_ops::run_instantiation_pass;
+use crate::data_types::{ScalarType, Type, BIT};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::evaluators::evaluate_simple_evaluator_with_retries;
+use crate::evaluators::simple_evaluator::SimpleEvaluator;
+use crate::graphs::{Context, Operation};
+use crate::inline::inline_ops::{inline_operations, InlineConfig};
+use crate::mpc::mpc_compiler::{compile_context, IOStatus};
+use crate::random::PRNG;
+use crate::typed_value::TypedValue;
+
+use proptest::prelude::{any, BoxedStrategy, Strategy};
+use proptest::test_runner::{TestCaseError, TestError, TestRunner};
+
+pub use proptest::test_runner::Config as ProptestConfig;
+
+// Cuckoo hashing of set intersection fails with a small probability, so such evaluations are retried
+const MAX_EVALUATION_ATTEMPTS: u64 = 10;
+
+fn scalar_strategy(st: ScalarType, n: usize) -> BoxedStrategy<Vec<u64>> {
+    proptest::collection::vec(any::<u64>(), n)
+        .prop_map(move |v| {
+            if st == BIT {
+                v.iter().map(|x| x & 1).collect()
+            } else {
+                v
+            }
+        })
+        .boxed()
+}
+
+/// Returns a strategy generating uniformly random values of a given type.
+///
+/// Entries of 128-bit scalars are sampled from the 64-bit range.
+///
+/// # Arguments
+///
+/// `t` - type of generated values
+///
+/// # Returns
+///
+/// Strategy generating values of type `t`
+pub fn value_strategy(t: Type) -> BoxedStrategy<Value> {
+    match t {
+        Type::Scalar(st) => scalar_strategy(st.clone(), 1)
+            .prop_filter_map("Invalid scalar", move |v| {
+                Value::from_scalar(v[0], st.clone()).ok()
+            })
+            .boxed(),
+        Type::Array(shape, st) => {
+            scalar_strategy(st.clone(), shape.iter().product::<u64>() as usize)
+                .prop_filter_map("Invalid array", move |v| {
+                    Value::from_flattened_array(&v, st.clone()).ok()
+                })
+                .boxed()
+        }
+        Type::Vector(n, element_type) => {
+            let strategies = vec![value_strategy((*element_type).clone()); n as usize];
+            strategies.prop_map(Value::from_vector).boxed()
+        }
+        Type::Tuple(element_types) => {
+            let strategies: Vec<BoxedStrategy<Value>> = element_types
+                .iter()
+                .map(|t| value_strategy((**t).clone()))
+                .collect();
+            strategies.prop_map(Value::from_vector).boxed()
+        }
+        Type::NamedTuple(element_types) => {
+            let strategies: Vec<BoxedStrategy<Value>> = element_types
+                .iter()
+                .map(|(_, t)| value_strategy((**t).clone()))
+                .collect();
+            strategies.prop_map(Value::from_vector).boxed()
+        }
+    }
+}
+
+fn get_input_types(context: &Context) -> Result<Vec<Type>> {
+    let mut input_types = vec![];
+    for node in context.get_main_graph()?.get_nodes() {
+        if let Operation::Input(t) = node.get_operation() {
+            input_types.push(t);
+        }
+    }
+    Ok(input_types)
+}
+
+/// Compares the results of a context and its MPC counterpart on the same inputs.
+pub struct MpcEquivalenceChecker {
+    plain_context: Context,
+    compiled_context: Context,
+    input_types: Vec<Type>,
+    input_parties: Vec<IOStatus>,
+    reveal_output: bool,
+}
+
+impl MpcEquivalenceChecker {
+    /// Compiles a given context into the ABY3 protocol.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - finalized deterministic context
+    /// * `input_parties` - owners of the inputs of the main graph, one per input
+    /// * `output_parties` - parties receiving the output; if empty, the output stays secret-shared
+    /// * `inline_config` - inlining configuration used by the compiler
+    ///
+    /// # Returns
+    ///
+    /// New checker
+    pub fn new(
+        context: Context,
+        input_parties: Vec<IOStatus>,
+        output_parties: Vec<IOStatus>,
+        inline_config: InlineConfig,
+    ) -> Result<Self> {
+        let plain_context = run_instantiation_pass(context.clone())?.get_context();
+        let input_types = get_input_types(&plain_context)?;
+        let reveal_output = output_parties.is_empty();
+        let compiled_context = compile_context(
+            context,
+            input_parties.clone(),
+            output_parties,
+            inline_config,
+            || SimpleEvaluator::new(None),
+        )?;
+        Ok(MpcEquivalenceChecker {
+            plain_context,
+            compiled_context,
+            input_types,
+            input_parties,
+            reveal_output,
+        })
+    }
+
+    /// Creates a checker comparing a plaintext context with a given MPC context.
+    ///
+    /// The MPC context takes the inputs of the plaintext context in the same order,
+    /// where every secret-shared input is given as a tuple of 3 shares.
+    /// It is instantiated and inlined, so it can contain MPC custom operations working on shares.
+    ///
+    /// # Arguments
+    ///
+    /// * `plain_context` - finalized deterministic context
+    /// * `mpc_context` - finalized context computing the same function in MPC
+    /// * `input_parties` - owners of the inputs of the main graph, one per input
+    /// * `reveal_output` - if true, the output of the MPC context is a tuple of 3 shares revealed before comparison
+    /// * `inline_config` - inlining configuration of the MPC context
+    ///
+    /// # Returns
+    ///
+    /// New checker
+    pub fn new_with_mpc_context(
+        plain_context: Context,
+        mpc_context: Context,
+        input_parties: Vec<IOStatus>,
+        reveal_output: bool,
+        inline_config: InlineConfig,
+    ) -> Result<Self> {
+        let plain_context = run_instantiation_pass(plain_context)?.get_context();
+        let input_types = get_input_types(&plain_context)?;
+        if input_parties.len() != input_types.len() {
+            return Err(runtime_error!(
+                "Invalid number of input parties: {} expected, but {} found",
+                input_types.len(),
+                input_parties.len()
+            ));
+        }
+        let compiled_context = inline_operations(
+            run_instantiation_pass(mpc_context)?.get_context(),
+            inline_config,
+        )?;
+        Ok(MpcEquivalenceChecker {
+            plain_context,
+            compiled_context,
+            input_types,
+            input_parties,
+            reveal_output,
+        })
+    }
+
+    /// Returns a strategy generating uniformly random inputs of the context.
+    pub fn inputs_strategy(&self) -> BoxedStrategy<Vec<Value>> {
+        let strategies: Vec<BoxedStrategy<Value>> = self
+            .input_types
+            .iter()
+            .map(|t| value_strategy(t.clone()))
+            .collect();
+        strategies.boxed()
+    }
+
+    /// Evaluates the plaintext and compiled contexts on given inputs and returns an error if their results differ.
+    ///
+    /// Secret-shared inputs are given in plaintext and shared at random.
+    /// Secret-shared outputs are revealed before comparison.
+    pub fn check(&self, inputs: Vec<Value>) -> Result<()> {
+        if inputs.len() != self.input_types.len() {
+            return Err(runtime_error!(
+                "Invalid number of inputs: {} expected, but {} found",
+                self.input_types.len(),
+                inputs.len()
+            ));
+        }
+        let mut prng = PRNG::new(None)?;
+        let mut compiled_inputs = vec![];
+        for (i, input) in inputs.iter().enumerate() {
+            let typed_input = TypedValue::new(self.input_types[i].clone(), input.clone())?;
+            if self.input_parties[i] == IOStatus::Shared {
+                compiled_inputs.push(typed_input.secret_share(&mut prng)?.value);
+            } else {
+                compiled_inputs.push(typed_input.value);
+            }
+        }
+        let plain_graph = self.plain_context.get_main_graph()?;
+        let expected = TypedValue::new(
+            plain_graph.get_output_node()?.get_type()?,
+            evaluate_simple_evaluator_with_retries(
+                plain_graph,
+                inputs,
+                None,
+                MAX_EVALUATION_ATTEMPTS,
+            )?,
+        )?;
+        let compiled_graph = self.compiled_context.get_main_graph()?;
+        let mut result = TypedValue::new(
+            compiled_graph.get_output_node()?.get_type()?,
+            evaluate_simple_evaluator_with_retries(
+                compiled_graph,
+                compiled_inputs,
+                None,
+                MAX_EVALUATION_ATTEMPTS,
+            )?,
+        )?;
+        if self.reveal_output {
+            result = result.secret_share_reveal()?;
+        }
+        if result != expected {
+            return Err(runtime_error!(
+                "MPC result {:?} differs from plaintext result {:?}",
+                result.value,
+                expected.value
+            ));
+        }
+        Ok(())
+    }
+
+    /// Runs [MpcEquivalenceChecker::check] on inputs generated by a given strategy.
+    ///
+    /// If a check fails, the returned error contains the simplest failing inputs found by shrinking.
+    pub fn run_with_strategy<S: Strategy<Value = Vec<Value>>>(
+        &self,
+        config: ProptestConfig,
+        strategy: S,
+    ) -> Result<()> {
+        let mut runner = TestRunner::new(config);
+        runner
+            .run(&strategy, |inputs| {
+                self.check(inputs)
+                    .map_err(|e| TestCaseError::fail(e.to_string()))
+            })
+            .map_err(|e| match e {
+                TestError::Fail(reason, inputs) => {
+                    runtime_error!("Check failed on inputs {:?}: {}", inputs, reason)
+                }
+                TestError::Abort(reason) => runtime_error!("Check aborted: {}", reason),
+            })
+    }
+
+    /// Runs [MpcEquivalenceChecker::check] on uniformly random inputs.
+    pub fn run(&self, config: ProptestConfig) -> Result<()> {
+        self.run_with_strategy(config, self.inputs_strategy())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_ops::CustomOperation;
+    use crate::data_types::{
+        array_type, named_tuple_type, scalar_type, tuple_type, INT32, INT64, UINT64, UINT8,
+    };
+    use crate::graphs::{create_context, Graph, Node};
+    use crate::inline::inline_ops::InlineMode;
+    use crate::mpc::mpc_compiler::{KEY_LENGTH, PARTIES};
+    use crate::mpc::mpc_psi::{
+        ApplySharedPermutationMPC, DuplicationMPC, PermutationMPC, SharedSwitchingMPC,
+    };
+    use crate::ops::comparisons::LessThan;
+    use crate::type_inference::NULL_HEADER;
+
+    use proptest::sample::subsequence;
+    use proptest::strategy::Just;
+    use std::collections::HashMap;
+
+    fn get_config(cases: u32) -> ProptestConfig {
+        ProptestConfig {
+            cases,
+            failure_persistence: None,
+            ..ProptestConfig::default()
+        }
+    }
+
+    fn get_inline_config() -> InlineConfig {
+        InlineConfig {
+            default_mode: InlineMode::Simple,
+            ..Default::default()
+        }
+    }
+
+    // Returns 3 shares of an input of an MPC graph; a public input is shared as (input, 0, 0).
+    fn share_input(g: &Graph, t: Type, status: IOStatus) -> Result<Vec<Node>> {
+        if status == IOStatus::Shared {
+            let shares = g.input(tuple_type(vec![t; PARTIES]))?;
+            (0..PARTIES as u64).map(|i| shares.tuple_get(i)).collect()
+        } else {
+            let input = g.input(t.clone())?;
+            let zero = g.constant(t.clone(), Value::zero_of_type(t))?;
+            Ok(vec![input, zero.clone(), zero])
+        }
+    }
+
+    fn prf_keys(g: &Graph) -> Result<Node> {
+        let keys = (0..PARTIES)
+            .map(|_| g.random(array_type(vec![KEY_LENGTH], BIT)))
+            .collect::<Result<Vec<Node>>>()?;
+        g.create_tuple(keys)
+    }
+
+    // MPC protocols on shared databases work with named tuples, so arrays are packed into single columns.
+    fn to_column(share: Node) -> Result<Node> {
+        share
+            .get_graph()
+            .create_named_tuple(vec![("x".to_owned(), share)])
+    }
+
+    // Returns 2-out-of-2 shares of an array by parties 1 and 0 packed into columns,
+    // as demanded by Programmer 1 and Sender 0.
+    fn to_two_shares(shares: Vec<Node>) -> Result<Node> {
+        // Party 1 holds shares 1 and 2, while party 0 holds share 0.
+        let programmer_share = shares[1].add(shares[2].clone())?;
+        shares[0].get_graph().create_tuple(vec![
+            to_column(programmer_share)?,
+            to_column(shares[0].clone())?,
+        ])
+    }
+
+    // Pads 2-out-of-2 shares of a column with a zero share, so that they are revealed by the checker.
+    fn pad_two_shares(shares: Node) -> Result<Node> {
+        let mut columns = vec![];
+        for i in 0..2 {
+            columns.push(shares.tuple_get(i)?.named_tuple_get("x".to_owned())?);
+        }
+        let t = columns[0].get_type()?;
+        columns.push(
+            shares
+                .get_graph()
+                .constant(t.clone(), Value::zero_of_type(t))?,
+        );
+        shares.get_graph().create_tuple(columns)
+    }
+
+    // Generates the first `m` entries of a random permutation of `n` indices.
+    fn permutation_strategy(n: u64, m: u64) -> BoxedStrategy<Value> {
+        Just((0..n).collect::<Vec<u64>>())
+            .prop_shuffle()
+            .prop_filter_map("Invalid permutation", move |v| {
+                Value::from_flattened_array(&v[..m as usize], UINT64).ok()
+            })
+            .boxed()
+    }
+
+    // Generates a duplication map of length `n`, where every index is either equal to its position
+    // or duplicates the previous index, together with the bits indicating duplicated indices.
+    fn duplication_strategy(n: u64) -> BoxedStrategy<(Vec<u64>, Vec<u64>)> {
+        proptest::collection::vec(any::<bool>(), n as usize)
+            .prop_map(|duplicated| {
+                let mut indices: Vec<u64> = vec![];
+                let mut bits = vec![];
+                for (i, d) in duplicated.iter().enumerate() {
+                    if i > 0 && *d {
+                        indices.push(indices[i - 1]);
+                        bits.push(1);
+                    } else {
+                        indices.push(i as u64);
+                        bits.push(0);
+                    }
+                }
+                (indices, bits)
+            })
+            .boxed()
+    }
+
+    fn finalize_main_graph(c: Context, g: Graph) -> Result<Context> {
+        g.finalize()?.set_as_main()?;
+        c.finalize()
+    }
+
+    #[test]
+    fn test_value_strategy() {
+        || -> Result<()> {
+            let t = named_tuple_type(vec![
+                ("a".to_owned(), array_type(vec![3, 5], BIT)),
+                ("b".to_owned(), scalar_type(INT64)),
+                (
+                    "c".to_owned(),
+                    Type::Vector(2, array_type(vec![4], UINT8).into()),
+                ),
+            ]);
+            let mut runner = TestRunner::default();
+            for _ in 0..10 {
+                let v = value_strategy(t.clone())
+                    .new_tree(&mut runner)
+                    .map_err(|e| runtime_error!("{}", e))?
+                    .current();
+                assert!(v.check_type(t.clone())?);
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let a = g.input(array_type(vec![4, 3], INT32))?;
+            let b = g.input(array_type(vec![3, 2], INT32))?;
+            let s = g.input(scalar_type(INT32))?;
+            let bits = g.input(array_type(vec![4, 3], BIT))?;
+            let product = a.matmul(b.clone())?.add(s.clone())?;
+            let masked = a.mixed_multiply(bits)?.subtract(a.multiply(a.clone())?)?;
+            let comparison = g.custom_op(
+                CustomOperation::new(LessThan {
+                    signed_comparison: true,
+                }),
+                vec![a.a2b()?, b.permute_axes(vec![1, 0])?.sum(vec![0])?.a2b()?],
+            )?;
+            let converted = a.add(s)?.a2b()?.b2a(INT32)?.sum(vec![0, 1])?;
+            g.create_tuple(vec![product, masked, comparison, converted])?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let input_parties = vec![
+                IOStatus::Party(0),
+                IOStatus::Shared,
+                IOStatus::Public,
+                IOStatus::Party(1),
+            ];
+            for output_parties in [vec![], vec![IOStatus::Party(2)]] {
+                let checker = MpcEquivalenceChecker::new(
+                    c.clone(),
+                    input_parties.clone(),
+                    output_parties,
+                    get_inline_config(),
+                )?;
+                checker.run(get_config(8))?;
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_set_intersection() {
+        || -> Result<()> {
+            let n = 6;
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let mut sets = vec![];
+            for (key, value) in [("a", "b"), ("c", "d")] {
+                let null = g.input(array_type(vec![n], BIT))?;
+                let keys = g.input(array_type(vec![n], INT32))?;
+                let values = g.input(array_type(vec![n], INT64))?;
+                sets.push(g.create_named_tuple(vec![
+                    (NULL_HEADER.to_owned(), null),
+                    (key.to_owned(), keys),
+                    (value.to_owned(), values),
+                ])?);
+            }
+            sets[0]
+                .set_intersection(
+                    sets[1].clone(),
+                    HashMap::from([("a".to_owned(), "c".to_owned())]),
+                )?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            // Keys must be unique, so they are sampled from a small range to produce many matches.
+            let keys_strategy = || {
+                subsequence((0..(2 * n as i32)).collect::<Vec<i32>>(), n as usize)
+                    .prop_shuffle()
+                    .prop_filter_map("Invalid keys", |v| {
+                        Value::from_flattened_array(&v, INT32).ok()
+                    })
+            };
+            let set_strategy = || {
+                (
+                    value_strategy(array_type(vec![n], BIT)),
+                    keys_strategy(),
+                    value_strategy(array_type(vec![n], INT64)),
+                )
+                    .prop_map(|(null, keys, values)| vec![null, keys, values])
+            };
+            let strategy = (set_strategy(), set_strategy())
+                .prop_map(|(x, y)| x.into_iter().chain(y).collect::<Vec<Value>>());
+            for (input_parties, output_parties) in [
+                (
+                    vec![IOStatus::Party(0); 3]
+                        .into_iter()
+                        .chain(vec![IOStatus::Party(1); 3])
+                        .collect::<Vec<IOStatus>>(),
+                    vec![],
+                ),
+                (
+                    vec![IOStatus::Shared; 3]
+                        .into_iter()
+                        .chain(vec![IOStatus::Public; 3])
+                        .collect(),
+                    vec![IOStatus::Party(2)],
+                ),
+            ] {
+                let checker = MpcEquivalenceChecker::new(
+                    c.clone(),
+                    input_parties,
+                    output_parties,
+                    get_inline_config(),
+                )?;
+                checker.run_with_strategy(get_config(4), strategy.clone())?;
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_mismatch() {
+        || -> Result<()> {
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let t = array_type(vec![8], INT64);
+            g.input(t.clone())?
+                .add(g.random_beacon(t)?)?
+                .set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let checker = MpcEquivalenceChecker::new(
+                c,
+                vec![IOStatus::Party(0)],
+                vec![],
+                get_inline_config(),
+            )?;
+            let config = ProptestConfig {
+                max_shrink_iters: 10,
+                ..get_config(1)
+            };
+            assert!(checker.run(config).is_err());
+            assert!(checker
+                .run_with_strategy(get_config(1), Just(vec![]))
+                .is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_permutation() {
+        || -> Result<()> {
+            let (n, m) = (5, 3);
+            let data_t = array_type(vec![n, 2], INT32);
+            let permutation_t = array_type(vec![m], UINT64);
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            g.input(data_t.clone())?
+                .gather(g.input(permutation_t.clone())?, 0)?
+                .set_as_output()?;
+            let c = finalize_main_graph(c, g)?;
+            let strategy = (value_strategy(data_t.clone()), permutation_strategy(n, m))
+                .prop_map(|(data, permutation)| vec![data, permutation]);
+            for data_status in [IOStatus::Public, IOStatus::Shared] {
+                // Party 1 permutes the data shared with party 0 by its permutation
+                let mpc_c = create_context()?;
+                let mpc_g = mpc_c.create_graph()?;
+                let data = share_input(&mpc_g, data_t.clone(), data_status.clone())?;
+                let permutation = mpc_g.input(permutation_t.clone())?;
+                let permuted = mpc_g.custom_op(
+                    CustomOperation::new(PermutationMPC {
+                        sender_id: 0,
+                        programmer_id: 1,
+                    }),
+                    vec![to_two_shares(data)?, permutation, prf_keys(&mpc_g)?],
+                )?;
+                pad_two_shares(permuted)?.set_as_output()?;
+                let checker = MpcEquivalenceChecker::new_with_mpc_context(
+                    c.clone(),
+                    finalize_main_graph(mpc_c, mpc_g)?,
+                    vec![data_status, IOStatus::Party(1)],
+                    true,
+                    get_inline_config(),
+                )?;
+                checker.run_with_strategy(get_config(4), strategy.clone())?;
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_shared_permutation() {
+        || -> Result<()> {
+            let n = 5;
+            let data_t = array_type(vec![n, 3], BIT);
+            let permutation_t = array_type(vec![n], UINT64);
+            let strategy = (value_strategy(data_t.clone()), permutation_strategy(n, n))
+                .prop_map(|(data, permutation)| vec![data, permutation]);
+            for inverse in [false, true] {
+                let c = create_context()?;
+                let g = c.create_graph()?;
+                let data = g.input(data_t.clone())?;
+                let mut permutation = g.input(permutation_t.clone())?;
+                if inverse {
+                    permutation = permutation.inverse_permutation()?;
+                }
+                data.gather(permutation, 0)?.set_as_output()?;
+                let c = finalize_main_graph(c, g)?;
+                for data_status in [IOStatus::Public, IOStatus::Shared] {
+                    let mpc_c = create_context()?;
+                    let mpc_g = mpc_c.create_graph()?;
+                    let data = share_input(&mpc_g, data_t.clone(), data_status.clone())?;
+                    let permutation = share_input(&mpc_g, permutation_t.clone(), IOStatus::Shared)?;
+                    mpc_g
+                        .custom_op(
+                            CustomOperation::new(ApplySharedPermutationMPC { inverse }),
+                            vec![
+                                mpc_g.create_tuple(data)?,
+                                mpc_g.create_tuple(permutation)?,
+                                prf_keys(&mpc_g)?,
+                            ],
+                        )?
+                        .set_as_output()?;
+                    let checker = MpcEquivalenceChecker::new_with_mpc_context(
+                        c.clone(),
+                        finalize_main_graph(mpc_c, mpc_g)?,
+                        vec![data_status, IOStatus::Shared],
+                        true,
+                        get_inline_config(),
+                    )?;
+                    checker.run_with_strategy(get_config(4), strategy.clone())?;
+                }
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_duplication() {
+        || -> Result<()> {
+            let n = 6;
+            let data_t = array_type(vec![n], INT64);
+            let duplication_t =
+                tuple_type(vec![array_type(vec![n], UINT64), array_type(vec![n], BIT)]);
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            g.input(data_t.clone())?
+                .gather(g.input(duplication_t.clone())?.tuple_get(0)?, 0)?
+                .set_as_output()?;
+            let c = finalize_main_graph(c, g)?;
+            let strategy = (value_strategy(data_t.clone()), duplication_strategy(n))
+                .prop_filter_map("Invalid duplication map", |(data, (indices, bits))| {
+                    let duplication = Value::from_vector(vec![
+                        Value::from_flattened_array(&indices, UINT64).ok()?,
+                        Value::from_flattened_array(&bits, BIT).ok()?,
+                    ]);
+                    Some(vec![data, duplication])
+                });
+            for data_status in [IOStatus::Public, IOStatus::Shared] {
+                // Party 1 duplicates the data shared with party 0 by its duplication map
+                let mpc_c = create_context()?;
+                let mpc_g = mpc_c.create_graph()?;
+                let data = share_input(&mpc_g, data_t.clone(), data_status.clone())?;
+                let duplication = mpc_g.input(duplication_t.clone())?;
+                let duplicated = mpc_g.custom_op(
+                    CustomOperation::new(DuplicationMPC {
+                        sender_id: 0,
+                        programmer_id: 1,
+                    }),
+                    vec![to_two_shares(data)?, duplication, prf_keys(&mpc_g)?],
+                )?;
+                pad_two_shares(duplicated)?.set_as_output()?;
+                let checker = MpcEquivalenceChecker::new_with_mpc_context(
+                    c.clone(),
+                    finalize_main_graph(mpc_c, mpc_g)?,
+                    vec![data_status, IOStatus::Party(1)],
+                    true,
+                    get_inline_config(),
+                )?;
+                checker.run_with_strategy(get_config(4), strategy.clone())?;
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_shared_duplication() {
+        || -> Result<()> {
+            let n = 6;
+            let data_t = array_type(vec![n, 2], INT32);
+            let duplication_t = array_type(vec![n], UINT64);
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            g.input(data_t.clone())?
+                .gather(g.input(duplication_t.clone())?, 0)?
+                .set_as_output()?;
+            let c = finalize_main_graph(c, g)?;
+            let strategy = (value_strategy(data_t.clone()), duplication_strategy(n))
+                .prop_filter_map("Invalid duplication map", |(data, (indices, _))| {
+                    Some(vec![
+                        data,
+                        Value::from_flattened_array(&indices, UINT64).ok()?,
+                    ])
+                });
+            for data_status in [IOStatus::Public, IOStatus::Shared] {
+                // A shared duplication map is applied by the shared switching protocol
+                let mpc_c = create_context()?;
+                let mpc_g = mpc_c.create_graph()?;
+                let data = share_input(&mpc_g, data_t.clone(), data_status.clone())?
+                    .into_iter()
+                    .map(to_column)
+                    .collect::<Result<Vec<Node>>>()?;
+                let duplication = share_input(&mpc_g, duplication_t.clone(), IOStatus::Shared)?;
+                let duplicated = mpc_g.custom_op(
+                    CustomOperation::new(SharedSwitchingMPC {}),
+                    vec![
+                        mpc_g.create_tuple(data)?,
+                        mpc_g.create_tuple(duplication)?,
+                        prf_keys(&mpc_g)?,
+                    ],
+                )?;
+                let mut result_shares = vec![];
+                for i in 0..PARTIES as u64 {
+                    result_shares.push(duplicated.tuple_get(i)?.named_tuple_get("x".to_owned())?);
+                }
+                mpc_g.create_tuple(result_shares)?.set_as_output()?;
+                let checker = MpcEquivalenceChecker::new_with_mpc_context(
+                    c.clone(),
+                    finalize_main_graph(mpc_c, mpc_g)?,
+                    vec![data_status, IOStatus::Shared],
+                    true,
+                    get_inline_config(),
+                )?;
+                checker.run_with_strategy(get_config(4), strategy.clone())?;
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -1,8 +1,8 @@
 use crate::broadcast::{broadcast_arrays, broadcast_shapes};
 use crate::custom_ops::Instantiation;
 use crate::data_types::{
-    array_type, is_valid_shape, named_tuple_type, scalar_size_in_bits, scalar_type, tuple_type,
-    vector_type, ArrayShape, ScalarType, Type, BIT, UINT32, UINT64,
+    array_type, get_u64_modulus, is_valid_shape, named_tuple_type, scalar_size_in_bits,
+    scalar_type, tuple_type, vector_type, ArrayShape, ScalarType, Type, BIT, UINT32, UINT64,
 };
 use crate::errors::Result;
 use crate::graphs::{create_context, Context, Node, Operation, WeakContext};
@@ -256,10 +256,79 @@ fn b2a_type_inference(t: Type, st: ScalarType) -> Result<Type> {
     }
 }
 
+fn convert_ring_type_inference(t: Type, st: ScalarType) -> Result<Type> {
+    if !t.is_scalar() && !t.is_array() {
+        return Err(runtime_error!(
+            "Invalid type for ConvertRing: can only be array or scalar"
+        ));
+    }
+    if t.get_scalar_type() == BIT || st == BIT {
+        return Err(runtime_error!("ConvertRing can't be applied to bits"));
+    }
+    for ring_st in [t.get_scalar_type(), st.clone()] {
+        if let Some(m) = get_u64_modulus(&ring_st)? {
+            if !m.is_power_of_two() {
+                return Err(runtime_error!(
+                    "ConvertRing supports only moduli that are powers of two"
+                ));
+            }
+        }
+    }
+    if t.is_scalar() {
+        Ok(scalar_type(st))
+    } else {
+        Ok(array_type(t.get_shape(), st))
+    }
+}
+
 /// Name of the "null" column that contains bits indicating whether the corresponding row is void of content.
 /// If the "null" bit is zero, the row is empty.
 pub const NULL_HEADER: &str = "null";
 
+// Checks the input array of binary strings and hash matrices of a hashing operation and returns their shapes.
+fn check_hashing_types(
+    op_name: &str,
+    input_t: Type,
+    hash_t: Type,
+) -> Result<(ArrayShape, ArrayShape)> {
+    if !matches!(input_t, Type::Array(_, BIT)) {
+        return Err(runtime_error!(
+            "{} can't be applied to a non-binary arrays",
+            op_name
+        ));
+    }
+    let input_shape = input_t.get_shape();
+    if input_shape.len() < 2 {
+        return Err(runtime_error!(
+            "Input shape must have at least 2 dimensions"
+        ));
+    }
+    if !matches!(hash_t, Type::Array(_, BIT)) {
+        return Err(runtime_error!(
+            "{} needs a binary array as a hash matrix",
+            op_name
+        ));
+    }
+    let hash_shape = hash_t.get_shape();
+    if hash_shape.len() != 3 {
+        return Err(runtime_error!("Hash array should have 3 dimensions"));
+    }
+    if hash_shape[1] > 63 {
+        return Err(runtime_error!(
+            "Hash map is too big. Decrease the number of rows of hash matrices"
+        ));
+    }
+    let input_element_length = input_shape[input_shape.len() - 1];
+    if hash_shape[2] != input_element_length {
+        return Err(runtime_error!(
+            "Hash matrix accepts bitstrings of length {}, but input strings are of length {}",
+            hash_shape[2],
+            input_element_length
+        ));
+    }
+    Ok((input_shape, hash_shape))
+}
+
 fn set_intersection_inference(
     t0: Type,
     t1: Type,
@@ -388,11 +457,16 @@ fn get_number_of_node_dependencies(operation: Operation) -> Option<u64> {
     match operation {
         Operation::Input(_)
         | Operation::Random(_)
+        | Operation::RandomBeacon(_)
         | Operation::Constant(_, _)
         | Operation::RandomPermutation(_) => Some(0),
         Operation::Truncate(_)
         | Operation::Sum(_)
         | Operation::PermuteAxes(_)
+        | Operation::Split(_, _)
+        | Operation::BroadcastTo(_)
+        | Operation::Tile(_)
+        | Operation::CumSum(_)
         | Operation::InversePermutation
         | Operation::CuckooToPermutation
         | Operation::Get(_)
@@ -402,6 +476,7 @@ fn get_number_of_node_dependencies(operation: Operation) -> Option<u64> {
         | Operation::PRF(_, _)
         | Operation::A2B
         | Operation::B2A(_)
+        | Operation::ConvertRing(_)
         | Operation::TupleGet(_)
         | Operation::NamedTupleGet(_)
         | Operation::Repeat(_)
@@ -417,16 +492,19 @@ fn get_number_of_node_dependencies(operation: Operation) -> Option<u64> {
         | Operation::VectorGet
         | Operation::Gather(_)
         | Operation::Iterate
-        | Operation::CuckooHash
+        | Operation::CuckooHash(_)
+        | Operation::BloomFilter
         | Operation::SetIntersection(_)
         | Operation::Gemm(_, _) => Some(2),
-        Operation::SegmentCumSum => Some(3),
+        Operation::SegmentCumSum | Operation::Scatter(_) => Some(3),
         Operation::Stack(_)
+        | Operation::Concatenate(_)
         | Operation::CreateTuple
         | Operation::CreateNamedTuple(_)
         | Operation::CreateVector(_)
         | Operation::Zip
         | Operation::Call
+        | Operation::If
         | Operation::Custom(_) => None,
     }
 }
@@ -436,10 +514,50 @@ fn get_number_of_node_dependencies(operation: Operation) -> Option<u64> {
 fn get_number_of_graph_dependencies(operation: Operation) -> Option<u64> {
     match operation {
         Operation::Call | Operation::Iterate => Some(1),
+        Operation::If => Some(2),
         _ => Some(0),
     }
 }
 
+/// 128-bit scalars can't be processed as u64 words, so they're only supported
+/// by arithmetic and by operations that don't access individual array entries.
+fn supports_128_bit_scalars(operation: &Operation) -> bool {
+    matches!(
+        operation,
+        Operation::Add
+            | Operation::Subtract
+            | Operation::Multiply
+            | Operation::Sum(_)
+            | Operation::CumSum(_)
+            | Operation::Reshape(_)
+            | Operation::NOP
+            | Operation::A2B
+            | Operation::B2A(_)
+            | Operation::CreateTuple
+            | Operation::CreateNamedTuple(_)
+            | Operation::CreateVector(_)
+            | Operation::TupleGet(_)
+            | Operation::NamedTupleGet(_)
+            | Operation::VectorGet
+            | Operation::Zip
+            | Operation::Call
+            | Operation::Iterate
+            | Operation::If
+            | Operation::Custom(_)
+    )
+}
+
+fn contains_128_bit_scalars(t: &Type) -> bool {
+    match t {
+        Type::Scalar(st) | Type::Array(_, st) => st.size_in_bits() == 128,
+        Type::Vector(_, element_type) => contains_128_bit_scalars(element_type),
+        Type::Tuple(types) => types.iter().any(|t| contains_128_bit_scalars(t)),
+        Type::NamedTuple(names_types) => {
+            names_types.iter().any(|(_, t)| contains_128_bit_scalars(t))
+        }
+    }
+}
+
 fn flatten_type_size(t: Type) -> Result<u64> {
     let err = || runtime_error!("Overflow during flatten_type_size()");
     match t {
@@ -582,6 +700,14 @@ impl TypeInferenceWorker {
         for dependency in &node_dependencies {
             node_dependencies_types.push(self.process_node(dependency.clone())?);
         }
+        if !supports_128_bit_scalars(&node.get_operation())
+            && node_dependencies_types.iter().any(contains_128_bit_scalars)
+        {
+            return Err(runtime_error!(
+                "Operation {} doesn't support 128-bit scalar types",
+                node.get_operation()
+            ));
+        }
         let graph_dependencies = node.get_graph_dependencies();
         let number_of_graph_dependencies = get_number_of_graph_dependencies(node.get_operation());
         if let Some(n) = number_of_graph_dependencies {
@@ -844,6 +970,15 @@ impl TypeInferenceWorker {
                 self.register_result(node, t.clone())?;
                 Ok(t)
             }
+            Operation::RandomBeacon(t) => {
+                if !t.is_scalar() && !t.is_array() {
+                    return Err(runtime_error!(
+                        "Random beacon can only generate an array or a scalar"
+                    ));
+                }
+                self.register_result(node, t.clone())?;
+                Ok(t)
+            }
             Operation::RandomPermutation(n) => {
                 if n == 0 {
                     return Err(runtime_error!("Permutation length should be non-zero"));
@@ -890,6 +1025,169 @@ impl TypeInferenceWorker {
                 self.register_result(node, result.clone())?;
                 Ok(result)
             }
+            Operation::Concatenate(axis) => {
+                if node_dependencies_types.is_empty() {
+                    return Err(runtime_error!("Concatenate requires at least one array"));
+                }
+                let mut shape = vec![];
+                let mut st = None;
+                for t in &node_dependencies_types {
+                    if !t.is_array() {
+                        return Err(runtime_error!("Concatenate can only be applied to arrays"));
+                    }
+                    let dep_shape = t.get_shape();
+                    if axis >= dep_shape.len() as u64 {
+                        return Err(runtime_error!(
+                            "Concatenate axis {} is out of range for shape {:?}",
+                            axis,
+                            dep_shape
+                        ));
+                    }
+                    match st {
+                        None => {
+                            st = Some(t.get_scalar_type());
+                            shape = dep_shape;
+                        }
+                        Some(ref st) => {
+                            if *st != t.get_scalar_type() {
+                                return Err(runtime_error!(
+                                    "Concatenate requires arrays of the same scalar type"
+                                ));
+                            }
+                            let mut matching_shape = dep_shape.clone();
+                            matching_shape[axis as usize] = shape[axis as usize];
+                            if matching_shape != shape {
+                                return Err(runtime_error!(
+                                    "Concatenate requires arrays of the same shape except for the axis {}: {:?} vs {:?}",
+                                    axis,
+                                    shape,
+                                    dep_shape
+                                ));
+                            }
+                            shape[axis as usize] = shape[axis as usize]
+                                .checked_add(dep_shape[axis as usize])
+                                .ok_or_else(|| runtime_error!("Concatenate result is too large"))?;
+                        }
+                    }
+                }
+                if !is_valid_shape(shape.clone()) {
+                    return Err(runtime_error!("Concatenate result is too large"));
+                }
+                let result = array_type(shape, st.unwrap());
+                self.register_result(node, result.clone())?;
+                Ok(result)
+            }
+            Operation::Split(axis, parts) => {
+                let t = node_dependencies_types[0].clone();
+                if !t.is_array() {
+                    return Err(runtime_error!("Split can only be applied to an array"));
+                }
+                let shape = t.get_shape();
+                if axis >= shape.len() as u64 {
+                    return Err(runtime_error!(
+                        "Split axis {} is out of range for shape {:?}",
+                        axis,
+                        shape
+                    ));
+                }
+                if parts.is_empty() || parts.contains(&0) {
+                    return Err(runtime_error!("Split requires non-empty parts"));
+                }
+                let total = parts
+                    .iter()
+                    .try_fold(0u64, |acc, part| acc.checked_add(*part));
+                if total != Some(shape[axis as usize]) {
+                    return Err(runtime_error!(
+                        "Split parts {:?} don't sum up to the dimension {} of the axis {}",
+                        parts,
+                        shape[axis as usize],
+                        axis
+                    ));
+                }
+                let st = t.get_scalar_type();
+                let result = tuple_type(
+                    parts
+                        .iter()
+                        .map(|part| {
+                            let mut part_shape = shape.clone();
+                            part_shape[axis as usize] = *part;
+                            array_type(part_shape, st.clone())
+                        })
+                        .collect(),
+                );
+                self.register_result(node, result.clone())?;
+                Ok(result)
+            }
+            Operation::BroadcastTo(shape) => {
+                let t = node_dependencies_types[0].clone();
+                if !t.is_scalar() && !t.is_array() {
+                    return Err(runtime_error!(
+                        "BroadcastTo can only be applied to a scalar or an array"
+                    ));
+                }
+                if !is_valid_shape(shape.clone()) {
+                    return Err(runtime_error!("Invalid shape: {:?}", shape));
+                }
+                if t.is_array() {
+                    let input_shape = t.get_shape();
+                    if input_shape.len() > shape.len()
+                        || broadcast_shapes(input_shape.clone(), shape.clone())? != shape
+                    {
+                        return Err(runtime_error!(
+                            "Shape {:?} can't be broadcast to {:?}",
+                            input_shape,
+                            shape
+                        ));
+                    }
+                }
+                let result = array_type(shape, t.get_scalar_type());
+                self.register_result(node, result.clone())?;
+                Ok(result)
+            }
+            Operation::Tile(reps) => {
+                let t = node_dependencies_types[0].clone();
+                if !t.is_scalar() && !t.is_array() {
+                    return Err(runtime_error!(
+                        "Tile can only be applied to a scalar or an array"
+                    ));
+                }
+                if !is_valid_shape(reps.clone()) {
+                    return Err(runtime_error!("Invalid repetitions: {:?}", reps));
+                }
+                let input_shape = if t.is_array() { t.get_shape() } else { vec![] };
+                let rank = input_shape.len().max(reps.len());
+                let mut shape = vec![1; rank - input_shape.len()];
+                shape.extend(input_shape);
+                let mut padded_reps = vec![1; rank - reps.len()];
+                padded_reps.extend(reps);
+                for (d, r) in shape.iter_mut().zip(padded_reps) {
+                    *d = d
+                        .checked_mul(r)
+                        .ok_or_else(|| runtime_error!("Tile result is too large"))?;
+                }
+                if !is_valid_shape(shape.clone()) {
+                    return Err(runtime_error!("Tile result is too large"));
+                }
+                let result = array_type(shape, t.get_scalar_type());
+                self.register_result(node, result.clone())?;
+                Ok(result)
+            }
+            Operation::CumSum(axis) => {
+                let t = node_dependencies_types[0].clone();
+                if !t.is_array() {
+                    return Err(runtime_error!("CumSum can only be applied to an array"));
+                }
+                let shape = t.get_shape();
+                if axis >= shape.len() as u64 {
+                    return Err(runtime_error!(
+                        "CumSum axis {} is out of range for shape {:?}",
+                        axis,
+                        shape
+                    ));
+                }
+                self.register_result(node, t.clone())?;
+                Ok(t)
+            }
             Operation::Constant(t, ref value) => {
                 if !value.check_type(t.clone())? {
                     return Err(runtime_error!("Invalid constant type"));
@@ -909,6 +1207,12 @@ impl TypeInferenceWorker {
                 self.register_result(node, result.clone())?;
                 Ok(result)
             }
+            Operation::ConvertRing(scalar_type) => {
+                let original_type = node_dependencies_types[0].clone();
+                let result = convert_ring_type_inference(original_type, scalar_type)?;
+                self.register_result(node, result.clone())?;
+                Ok(result)
+            }
             Operation::CreateTuple => {
                 let mut types = vec![];
                 for dependency_type in node_dependencies_types {
@@ -1121,6 +1425,38 @@ impl TypeInferenceWorker {
                     _ => Err(runtime_error!("Iterate graph must output a tuple")),
                 }
             }
+            Operation::If => {
+                if node_dependencies_types.is_empty()
+                    || node_dependencies_types[0] != scalar_type(BIT)
+                {
+                    return Err(runtime_error!("Condition of If must be a binary scalar"));
+                }
+                let argument_types = node_dependencies_types[1..].to_vec();
+                let mut output_types = vec![];
+                for graph in &graph_dependencies {
+                    graph.check_finalized()?;
+                    let mut input_types = vec![];
+                    for graph_node in &graph.get_nodes() {
+                        if let Operation::Input(t) = graph_node.get_operation() {
+                            input_types.push(t.clone());
+                        }
+                    }
+                    if input_types != argument_types {
+                        return Err(runtime_error!(
+                            "Arguments of If don't match the inputs of its branches"
+                        ));
+                    }
+                    output_types.push(self.process_node(graph.get_output_node()?)?);
+                }
+                if output_types[0] != output_types[1] {
+                    return Err(runtime_error!(
+                        "Branches of If must have the same output type"
+                    ));
+                }
+                let result = output_types[0].clone();
+                self.register_result(node, result.clone())?;
+                Ok(result)
+            }
             Operation::ArrayToVector => {
                 let t = node_dependencies_types[0].clone();
                 if !t.is_array() {
@@ -1200,55 +1536,78 @@ impl TypeInferenceWorker {
                 self.register_result(node, result.clone())?;
                 Ok(result)
             }
-            Operation::CuckooHash => {
+            Operation::Scatter(axis) => {
                 let input_t = node_dependencies_types[0].clone();
-                let hash_t = node_dependencies_types[1].clone();
-                if !matches!(input_t, Type::Array(_, BIT)) {
-                    return Err(runtime_error!(
-                        "CuckooHash can't be applied to a non-binary arrays"
-                    ));
+                if !input_t.is_array() {
+                    return Err(runtime_error!("Scatter can be only applied to an array"));
                 }
+                let indices_t = node_dependencies_types[1].clone();
+                let num_indices = match &indices_t {
+                    Type::Array(shape, UINT64) if shape.len() == 1 => shape[0],
+                    _ => {
+                        return Err(runtime_error!(
+                            "Indices must be a one-dimensional array of UINT64"
+                        ));
+                    }
+                };
                 let input_shape = input_t.get_shape();
-                if input_shape.len() < 2 {
-                    return Err(runtime_error!(
-                        "Input shape must have at least 2 dimensions"
-                    ));
-                }
-                if !matches!(hash_t, Type::Array(_, BIT)) {
+                if axis >= input_shape.len() as u64 {
                     return Err(runtime_error!(
-                        "CuckooHash needs a binary array as a hash matrix"
+                        "Invalid axis. The axis index should be smaller than {}",
+                        input_shape.len()
                     ));
                 }
-                let hash_shape = hash_t.get_shape();
-                if hash_shape.len() != 3 {
-                    return Err(runtime_error!("Hash array should have 3 dimensions"));
-                }
-                if hash_shape[0] < 3 {
+                if num_indices > input_shape[axis as usize] {
                     return Err(runtime_error!(
-                        "At least 3 hash matrices should be provided"
+                        "Number of indices is too big. At most {} rows can be written.",
+                        input_shape[axis as usize]
                     ));
                 }
-                if hash_shape[1] > 63 {
+                let mut updates_shape = input_shape.clone();
+                updates_shape[axis as usize] = num_indices;
+                let expected_updates_t = array_type(updates_shape, input_t.get_scalar_type());
+                if node_dependencies_types[2] != expected_updates_t {
                     return Err(runtime_error!(
-                        "Hash map is too big. Decrease the number of rows of hash matrices"
+                        "Updates must be of type {:?}, but {:?} is given",
+                        expected_updates_t,
+                        node_dependencies_types[2]
                     ));
                 }
-                let input_element_length = input_shape[input_shape.len() - 1];
-                if hash_shape[2] != input_element_length {
+                self.register_result(node, input_t.clone())?;
+                Ok(input_t)
+            }
+            Operation::CuckooHash(stash_size) => {
+                let (input_shape, hash_shape) = check_hashing_types(
+                    "CuckooHash",
+                    node_dependencies_types[0].clone(),
+                    node_dependencies_types[1].clone(),
+                )?;
+                if hash_shape[0] < 2 {
                     return Err(runtime_error!(
-                        "Hash matrix accepts bitstrings of length {}, but input strings are of length {}",
-                        hash_shape[2],
-                        input_element_length
+                        "At least 2 hash matrices should be provided"
                     ));
                 }
-                // For each subarray, the output hash map contains indices of this array
+                // For each subarray, the output hash map and its stash contain indices of this array
                 let mut output_shape = input_shape[0..input_shape.len() - 2].to_vec();
                 let hash_map_size = 1 << hash_shape[1];
-                output_shape.push(hash_map_size);
+                output_shape.push(hash_map_size + stash_size);
                 let result = array_type(output_shape, UINT64);
                 self.register_result(node, result.clone())?;
                 Ok(result)
             }
+            Operation::BloomFilter => {
+                let (input_shape, hash_shape) = check_hashing_types(
+                    "BloomFilter",
+                    node_dependencies_types[0].clone(),
+                    node_dependencies_types[1].clone(),
+                )?;
+                // For each subarray, the output filter contains bits of hashed elements
+                let mut output_shape = input_shape[0..input_shape.len() - 2].to_vec();
+                output_shape.push(1 << hash_shape[1]);
+                let result = array_type(output_shape, BIT);
+                self.register_result(node, result.clone())?;
+                Ok(result)
+            }
             Operation::SegmentCumSum => {
                 let input_t = node_dependencies_types[0].clone();
                 let binary_t = node_dependencies_types[1].clone();
@@ -1298,8 +1657,9 @@ impl TypeInferenceWorker {
                     return Ok(t);
                 }
                 let fake_context = create_context()?;
-                let instantiated_graph =
-                    op.instantiate(fake_context.clone(), node_dependencies_types)?;
+                let instantiated_graph = op
+                    .instantiate(fake_context.clone(), node_dependencies_types)
+                    .map_err(|e| e.with_custom_operation(&op.get_name()).with_node(&node))?;
                 let result = instantiated_graph.get_output_node()?.get_type()?;
                 instantiated_graph.set_as_main()?;
                 fake_context.finalize()?;
@@ -1314,7 +1674,7 @@ impl TypeInferenceWorker {
 mod tests {
     use super::*;
     use crate::data_types::{
-        create_scalar_type, ArrayShape, Type, BIT, INT32, INT8, UINT32, UINT8,
+        create_scalar_type, ArrayShape, Type, BIT, INT128, INT32, INT64, INT8, UINT32, UINT8,
     };
     use crate::data_values::Value;
     use crate::graphs::{create_unchecked_context, Graph, Slice, SliceElement};
@@ -2051,6 +2411,138 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_concatenate_split() {
+        || -> Result<()> {
+            let context = create_unchecked_context()?;
+            let mut worker = create_type_inference_worker(context.clone());
+            let graph = context.create_graph()?;
+            let a = graph.input(array_type(vec![2, 3, 4], INT32))?;
+            let b = graph.input(array_type(vec![2, 5, 4], INT32))?;
+            let c = graph.input(array_type(vec![2, 5, 4], INT64))?;
+            let s = graph.input(scalar_type(INT32))?;
+
+            let concatenated = graph.concatenate(vec![a.clone(), b.clone(), a.clone()], 1)?;
+            assert_eq!(
+                worker.process_node(concatenated.clone())?,
+                array_type(vec![2, 11, 4], INT32)
+            );
+            assert_eq!(
+                worker.process_node(graph.concatenate(vec![a.clone()], 2)?)?,
+                array_type(vec![2, 3, 4], INT32)
+            );
+            assert_eq!(
+                worker.process_node(concatenated.split(1, vec![3, 8])?)?,
+                tuple_type(vec![
+                    array_type(vec![2, 3, 4], INT32),
+                    array_type(vec![2, 8, 4], INT32)
+                ])
+            );
+            assert_eq!(
+                worker.process_node(concatenated.split(2, vec![1, 1, 2])?)?,
+                tuple_type(vec![
+                    array_type(vec![2, 11, 1], INT32),
+                    array_type(vec![2, 11, 1], INT32),
+                    array_type(vec![2, 11, 2], INT32)
+                ])
+            );
+
+            let failing_nodes = vec![
+                graph.concatenate(vec![], 0)?,
+                graph.concatenate(vec![a.clone(), b.clone()], 0)?,
+                graph.concatenate(vec![a.clone(), b.clone()], 3)?,
+                graph.concatenate(vec![b.clone(), c], 1)?,
+                graph.concatenate(vec![a.clone(), s.clone()], 0)?,
+                a.split(1, vec![1, 1])?,
+                a.split(1, vec![3, 0])?,
+                a.split(1, vec![])?,
+                a.split(3, vec![2])?,
+                a.split(0, vec![u64::MAX, 3])?,
+                s.split(0, vec![1])?,
+            ];
+            for node in failing_nodes {
+                assert!(worker.process_node(node).is_err());
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_broadcast_to_tile() {
+        || -> Result<()> {
+            let context = create_unchecked_context()?;
+            let mut worker = create_type_inference_worker(context.clone());
+            let graph = context.create_graph()?;
+            let a = graph.input(array_type(vec![3, 1], INT32))?;
+            let s = graph.input(scalar_type(BIT))?;
+            let v = graph.input(vector_type(2, scalar_type(BIT)))?;
+
+            assert_eq!(
+                worker.process_node(a.broadcast_to(vec![2, 3, 4])?)?,
+                array_type(vec![2, 3, 4], INT32)
+            );
+            assert_eq!(
+                worker.process_node(s.broadcast_to(vec![5])?)?,
+                array_type(vec![5], BIT)
+            );
+            assert_eq!(
+                worker.process_node(a.tile(vec![2, 3])?)?,
+                array_type(vec![6, 3], INT32)
+            );
+            assert_eq!(
+                worker.process_node(a.tile(vec![2])?)?,
+                array_type(vec![3, 2], INT32)
+            );
+            assert_eq!(
+                worker.process_node(a.tile(vec![4, 1, 1])?)?,
+                array_type(vec![4, 3, 1], INT32)
+            );
+            assert_eq!(
+                worker.process_node(s.tile(vec![2, 3])?)?,
+                array_type(vec![2, 3], BIT)
+            );
+
+            let failing_nodes = vec![
+                a.broadcast_to(vec![2, 4])?,
+                a.broadcast_to(vec![3])?,
+                a.broadcast_to(vec![3, 0])?,
+                v.broadcast_to(vec![2])?,
+                a.tile(vec![])?,
+                a.tile(vec![2, 0])?,
+                a.tile(vec![u64::MAX, 2])?,
+                v.tile(vec![2])?,
+            ];
+            for node in failing_nodes {
+                assert!(worker.process_node(node).is_err());
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_cumsum() {
+        || -> Result<()> {
+            let context = create_unchecked_context()?;
+            let mut worker = create_type_inference_worker(context.clone());
+            let graph = context.create_graph()?;
+            let a = graph.input(array_type(vec![3, 4], INT32))?;
+            let b = graph.input(array_type(vec![5], BIT))?;
+            let s = graph.input(scalar_type(INT32))?;
+
+            assert_eq!(
+                worker.process_node(a.cumsum(1)?)?,
+                array_type(vec![3, 4], INT32)
+            );
+            assert_eq!(worker.process_node(b.cumsum(0)?)?, array_type(vec![5], BIT));
+            assert!(worker.process_node(a.cumsum(2)?).is_err());
+            assert!(worker.process_node(s.cumsum(0)?).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
     fn test_constant_worker(t: Type, v: Value) {
         let context = create_unchecked_context().unwrap();
         let mut worker = create_type_inference_worker(context.clone());
@@ -2156,6 +2648,36 @@ mod tests {
         }
     }
 
+    fn test_convert_ring_worker(t0: Type, st: ScalarType) -> Result<Type> {
+        let context = create_unchecked_context()?;
+        let mut worker = create_type_inference_worker(context.clone());
+        let graph = context.create_graph()?;
+        let i = graph.input(t0)?;
+        let o = graph.convert_ring(i, st)?;
+        worker.process_node(o)
+    }
+
+    #[test]
+    fn test_convert_ring() {
+        assert_eq!(
+            test_convert_ring_worker(array_type(vec![10, 20], INT64), INT32).unwrap(),
+            array_type(vec![10, 20], INT32)
+        );
+        assert_eq!(
+            test_convert_ring_worker(scalar_type(UINT8), INT64).unwrap(),
+            scalar_type(INT64)
+        );
+        assert!(test_convert_ring_worker(array_type(vec![10, 20], BIT), INT32).is_err());
+        assert!(test_convert_ring_worker(array_type(vec![10, 20], INT32), BIT).is_err());
+        assert!(test_convert_ring_worker(tuple_type(vec![]), INT32).is_err());
+        assert!(test_convert_ring_worker(scalar_type(INT32), INT128).is_err());
+        assert!(test_convert_ring_worker(
+            scalar_type(INT32),
+            create_scalar_type(false, Some(1000))
+        )
+        .is_err());
+    }
+
     fn test_create_tuple_worker(elements: Vec<Type>, expected_result: Type) {
         let context = create_unchecked_context().unwrap();
         let mut worker = create_type_inference_worker(context.clone());
@@ -2825,6 +3347,102 @@ mod tests {
         .unwrap();
     }
 
+    fn scatter_helper(
+        input_t: Type,
+        indices_t: Type,
+        updates_t: Type,
+        axis: u64,
+        expected: Option<Type>,
+    ) -> Result<()> {
+        let context = create_unchecked_context()?;
+        let graph = context.create_graph()?;
+        let mut worker = create_type_inference_worker(context.clone());
+        let inp = graph.input(input_t)?;
+        let ind = graph.input(indices_t)?;
+        let upd = graph.input(updates_t)?;
+        let o = graph.scatter(inp, ind, upd, axis)?;
+        let t = worker.process_node(o);
+        if let Some(expected_t) = expected {
+            assert_eq!(t?, expected_t);
+        } else {
+            assert!(t.is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_scatter() {
+        || -> Result<()> {
+            scatter_helper(
+                array_type(vec![2, 3, 4], INT32),
+                array_type(vec![2], UINT64),
+                array_type(vec![2, 2, 4], INT32),
+                1,
+                Some(array_type(vec![2, 3, 4], INT32)),
+            )?;
+            scatter_helper(
+                array_type(vec![4], BIT),
+                array_type(vec![4], UINT64),
+                array_type(vec![4], BIT),
+                0,
+                Some(array_type(vec![4], BIT)),
+            )?;
+
+            scatter_helper(
+                scalar_type(BIT),
+                array_type(vec![1], UINT64),
+                scalar_type(BIT),
+                0,
+                None,
+            )?;
+            scatter_helper(
+                array_type(vec![2, 3, 4], INT32),
+                array_type(vec![2, 1], UINT64),
+                array_type(vec![2, 2, 4], INT32),
+                1,
+                None,
+            )?;
+            scatter_helper(
+                array_type(vec![2, 3, 4], INT32),
+                array_type(vec![2], UINT32),
+                array_type(vec![2, 2, 4], INT32),
+                1,
+                None,
+            )?;
+            scatter_helper(
+                array_type(vec![2, 3, 4], INT32),
+                array_type(vec![2], UINT64),
+                array_type(vec![2, 2, 4], INT32),
+                3,
+                None,
+            )?;
+            scatter_helper(
+                array_type(vec![2, 3, 4], INT32),
+                array_type(vec![4], UINT64),
+                array_type(vec![2, 4, 4], INT32),
+                1,
+                None,
+            )?;
+            scatter_helper(
+                array_type(vec![2, 3, 4], INT32),
+                array_type(vec![2], UINT64),
+                array_type(vec![2, 2, 4], INT64),
+                1,
+                None,
+            )?;
+            scatter_helper(
+                array_type(vec![2, 3, 4], INT32),
+                array_type(vec![2], UINT64),
+                array_type(vec![2, 2, 3], INT32),
+                1,
+                None,
+            )?;
+
+            Ok(())
+        }()
+        .unwrap();
+    }
+
     fn test_cuckoo_hash_worker(t0: Type, t1: Type, expected: Type) -> Result<()> {
         let context = create_unchecked_context()?;
         let graph = context.create_graph()?;
@@ -2867,6 +3485,19 @@ mod tests {
                 array_type(vec![4, 5, 6], BIT),
                 array_type(vec![11, 32], UINT64),
             )?;
+            test_cuckoo_hash_worker(
+                array_type(vec![4, 6], BIT),
+                array_type(vec![2, 4, 6], BIT),
+                array_type(vec![16], UINT64),
+            )?;
+            // The stash is appended to the hash map
+            let context = create_unchecked_context()?;
+            let graph = context.create_graph()?;
+            let mut worker = create_type_inference_worker(context.clone());
+            let o = graph
+                .input(array_type(vec![11, 4, 6], BIT))?
+                .cuckoo_hash_with_stash(graph.input(array_type(vec![4, 5, 6], BIT))?, 3)?;
+            assert_eq!(worker.process_node(o)?, array_type(vec![11, 35], UINT64));
 
             test_cuckoo_hash_fail(scalar_type(BIT), array_type(vec![3, 3, 6], BIT))?;
             test_cuckoo_hash_fail(array_type(vec![4, 6], INT8), array_type(vec![3, 4, 6], BIT))?;
@@ -2880,7 +3511,7 @@ mod tests {
                 array_type(vec![3, 4, 6], UINT64),
             )?;
             test_cuckoo_hash_fail(array_type(vec![4, 6], BIT), array_type(vec![3, 6], BIT))?;
-            test_cuckoo_hash_fail(array_type(vec![4, 6], BIT), array_type(vec![2, 4, 6], BIT))?;
+            test_cuckoo_hash_fail(array_type(vec![4, 6], BIT), array_type(vec![1, 4, 6], BIT))?;
             test_cuckoo_hash_fail(array_type(vec![4, 6], BIT), array_type(vec![3, 4, 7], BIT))?;
             test_cuckoo_hash_fail(array_type(vec![4, 6], BIT), array_type(vec![3, 64, 6], BIT))?;
 
@@ -2889,6 +3520,58 @@ mod tests {
         .unwrap();
     }
 
+    fn test_bloom_filter_worker(t0: Type, t1: Type) -> Result<Type> {
+        let context = create_unchecked_context()?;
+        let graph = context.create_graph()?;
+        let mut worker = create_type_inference_worker(context.clone());
+        let i = graph.input(t0)?;
+        let h = graph.input(t1)?;
+        let o = graph.bloom_filter(i, h)?;
+        worker.process_node(o)
+    }
+
+    #[test]
+    fn test_bloom_filter() {
+        || -> Result<()> {
+            assert_eq!(
+                test_bloom_filter_worker(
+                    array_type(vec![5, 6], BIT),
+                    array_type(vec![2, 4, 6], BIT)
+                )?,
+                array_type(vec![16], BIT)
+            );
+            assert_eq!(
+                test_bloom_filter_worker(
+                    array_type(vec![11, 4, 6], BIT),
+                    array_type(vec![4, 5, 6], BIT)
+                )?,
+                array_type(vec![11, 32], BIT)
+            );
+            assert!(test_bloom_filter_worker(
+                array_type(vec![4, 6], INT8),
+                array_type(vec![3, 4, 6], BIT)
+            )
+            .is_err());
+            assert!(test_bloom_filter_worker(
+                array_type(vec![6], BIT),
+                array_type(vec![3, 4, 6], BIT)
+            )
+            .is_err());
+            assert!(test_bloom_filter_worker(
+                array_type(vec![4, 6], BIT),
+                array_type(vec![3, 4, 7], BIT)
+            )
+            .is_err());
+            assert!(test_bloom_filter_worker(
+                array_type(vec![4, 6], BIT),
+                array_type(vec![3, 64, 6], BIT)
+            )
+            .is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
     fn test_random_permutation_worker(n: u64) -> Result<Type> {
         let context = create_unchecked_context()?;
         let graph = context.create_graph()?;
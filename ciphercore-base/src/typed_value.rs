@@ -1,11 +1,11 @@
 use crate::bytes::{add_vectors_u64, subtract_vectors_u64, vec_from_bytes, vec_to_bytes};
 use crate::data_types::{
-    array_type, get_size_in_bits, get_types_vector, is_valid_shape, named_tuple_type, scalar_type,
-    tuple_type, vector_type, ArrayShape, ScalarType, Type, BIT, INT16, INT32, INT64, INT8, UINT16,
-    UINT32, UINT64, UINT8,
+    array_type, get_size_in_bits, get_types_vector, get_u64_modulus, is_valid_shape,
+    named_tuple_type, scalar_type, tuple_type, vector_type, ArrayShape, ScalarType, Type, BIT,
+    INT16, INT32, INT64, INT8, UINT16, UINT32, UINT64, UINT8,
 };
 use crate::data_values;
-use crate::data_values::Value;
+use crate::data_values::{NdarrayViewElement, Value};
 use crate::errors::Result;
 use crate::random::PRNG;
 use crate::typed_value_operations::{
@@ -737,6 +737,31 @@ impl TypedValue {
         }
     }
 
+    /// Runs a given closure on a multi-dimensional view of the entries of an array or scalar typed value.
+    ///
+    /// See [Value::with_ndarray_view] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use ciphercore_base::data_types::UINT16;
+    /// # use ciphercore_base::typed_value::TypedValue;
+    /// # use ciphercore_base::typed_value_operations::TypedValueArrayOperations;
+    /// # use ndarray::array;
+    /// let v = TypedValue::from_ndarray(array![[1u16, 2], [3, 4]].into_dyn(), UINT16).unwrap();
+    /// let entry = v
+    ///     .with_ndarray_view(|view: ndarray::ArrayViewD<u16>| Ok(view[[1, 0]]))
+    ///     .unwrap();
+    /// assert_eq!(entry, 3);
+    /// ```
+    pub fn with_ndarray_view<T, F, R>(&self, f: F) -> Result<R>
+    where
+        T: NdarrayViewElement,
+        F: FnOnce(ndarray::ArrayViewD<T>) -> Result<R>,
+    {
+        self.value.with_ndarray_view(self.t.clone(), f)
+    }
+
     pub fn from_json(j: &JsonValue) -> Result<Self> {
         if let JsonValue::Object(o) = j {
             let kind = o
@@ -1133,7 +1158,7 @@ pub fn generalized_subtract(v: Value, v0: Value, t: Type) -> Result<Value> {
         Type::Scalar(st) | Type::Array(_, st) => {
             let v_raw = v.access_bytes(|bytes| vec_from_bytes(bytes, st.clone()))?;
             let v0_raw = v0.access_bytes(|bytes| vec_from_bytes(bytes, st.clone()))?;
-            let result = subtract_vectors_u64(&v_raw, &v0_raw, st.get_modulus())?;
+            let result = subtract_vectors_u64(&v_raw, &v0_raw, get_u64_modulus(&st)?)?;
             Ok(Value::from_bytes(vec_to_bytes(&result, st)?))
         }
         Type::Tuple(tv) => {
@@ -1183,7 +1208,7 @@ pub fn generalized_add(v: Value, v0: Value, t: Type) -> Result<Value> {
         Type::Scalar(st) | Type::Array(_, st) => {
             let v_raw = v.access_bytes(|bytes| vec_from_bytes(bytes, st.clone()))?;
             let v0_raw = v0.access_bytes(|bytes| vec_from_bytes(bytes, st.clone()))?;
-            let result = add_vectors_u64(&v_raw, &v0_raw, st.get_modulus())?;
+            let result = add_vectors_u64(&v_raw, &v0_raw, get_u64_modulus(&st)?)?;
             Ok(Value::from_bytes(vec_to_bytes(&result, st)?))
         }
         Type::Tuple(tv) => {
@@ -1,3 +1,4 @@
+pub mod data_owner;
 pub mod replicated_shares;
 use crate::errors::Result;
 use crate::random::PRNG;
@@ -0,0 +1,192 @@
+//! Secret sharing of inputs by data owners that don't take part in MPC evaluation.
+//!
+//! A data owner holding a plaintext value `x` splits it into 2-out-of-3 replicated shares `(x_0, x_1, x_2)` with `x = x_0 + x_1 + x_2`,
+//! where party `i` obtains the shares `x_i` and `x_{i+1}` (the index is taken modulo 3).
+//! The random shares `x_0` and `x_1` are derived from a secret PRF key of the data owner,
+//! so the shares of different parties can be generated independently and stay consistent.
+//!
+//! The output of [share_for_party] is a tuple of 3 shares that can be directly fed to a compiled graph
+//! as an input with the [IOStatus::Shared](crate::mpc::mpc_compiler::IOStatus::Shared) status;
+//! the share unknown to the party is replaced by zero.
+use crate::data_types::{array_type, tuple_type, Type, BIT};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::mpc::mpc_compiler::{KEY_LENGTH, PARTIES};
+use crate::random::{Prf, PRNG, SEED_SIZE};
+use crate::typed_value::{generalized_add, generalized_subtract, TypedValue};
+
+/// Generates a random PRF key of a data owner.
+pub fn generate_data_owner_key() -> Result<[u8; SEED_SIZE]> {
+    let bytes = PRNG::new(None)?.get_random_bytes(SEED_SIZE)?;
+    let mut key = [0u8; SEED_SIZE];
+    key.copy_from_slice(&bytes);
+    Ok(key)
+}
+
+/// Returns a PRF key of a data owner as a binary array of length [KEY_LENGTH](crate::mpc::mpc_compiler::KEY_LENGTH).
+///
+/// This is the format of PRF keys in compiled graphs, i.e. the random share `x_j` (`j` = 0, 1) of an input with a given ID
+/// is equal to the output of the PRF operation with this key and `iv = 2 * input_id + j`.
+pub fn get_prf_key_typed_value(key: [u8; SEED_SIZE]) -> Result<TypedValue> {
+    TypedValue::new(
+        array_type(vec![KEY_LENGTH], BIT),
+        Value::from_bytes(key.to_vec()),
+    )
+}
+
+/// Computes all 3 replicated shares of a value.
+fn get_shares(tv: &TypedValue, key: [u8; SEED_SIZE], input_id: u64) -> Result<Vec<Value>> {
+    let iv = input_id
+        .checked_mul(2)
+        .ok_or_else(|| runtime_error!("Input ID is too large"))?;
+    let mut prf = Prf::new(Some(key))?;
+    let x0 = prf.output_value(iv, tv.t.clone())?;
+    let x1 = prf.output_value(iv + 1, tv.t.clone())?;
+    let x2 = generalized_subtract(
+        generalized_subtract(tv.value.clone(), x0.clone(), tv.t.clone())?,
+        x1.clone(),
+        tv.t.clone(),
+    )?;
+    Ok(vec![x0, x1, x2])
+}
+
+/// Secret-shares a value and returns the shares of a given party in the format expected by compiled graphs.
+///
+/// Every value shared with the same key must have a unique input ID; otherwise, the shares of different values are correlated.
+///
+/// # Arguments
+///
+/// * `tv` - typed value to be shared
+/// * `party_id` - index of the party (0, 1 or 2)
+/// * `key` - secret PRF key of the data owner, see [generate_data_owner_key]
+/// * `input_id` - unique ID of the value
+///
+/// # Returns
+///
+/// Tuple `(x_0, x_1, x_2)` where the share unknown to the party (`x_{party_id+2}`) is replaced by zero
+pub fn share_for_party(
+    tv: &TypedValue,
+    party_id: u64,
+    key: [u8; SEED_SIZE],
+    input_id: u64,
+) -> Result<TypedValue> {
+    if party_id >= PARTIES as u64 {
+        return Err(runtime_error!("Invalid party ID: {}", party_id));
+    }
+    let mut shares = get_shares(tv, key, input_id)?;
+    let missing_share = (party_id as usize + PARTIES - 1) % PARTIES;
+    shares[missing_share] = Value::zero_of_type(tv.t.clone());
+    TypedValue::new(
+        tuple_type(vec![tv.t.clone(); PARTIES]),
+        Value::from_vector(shares),
+    )
+}
+
+/// Reconstructs a value from the shares of at least two parties produced by [share_for_party] or by a compiled graph with a shared output.
+///
+/// # Arguments
+///
+/// `party_shares` - pairs of party indices and tuples of their shares
+///
+/// # Returns
+///
+/// Reconstructed typed value
+pub fn reconstruct(party_shares: &[(u64, TypedValue)]) -> Result<TypedValue> {
+    let mut shares: Vec<Option<Value>> = vec![None; PARTIES];
+    let mut share_type = None;
+    for (party_id, tv) in party_shares {
+        if *party_id >= PARTIES as u64 {
+            return Err(runtime_error!("Invalid party ID: {}", party_id));
+        }
+        let t = match &tv.t {
+            Type::Tuple(ts) if ts.len() == PARTIES => (*ts[0]).clone(),
+            _ => {
+                return Err(runtime_error!(
+                    "Shares must be a tuple of {} values",
+                    PARTIES
+                ))
+            }
+        };
+        if share_type.get_or_insert(t.clone()) != &t {
+            return Err(runtime_error!(
+                "Shares of different parties have different types"
+            ));
+        }
+        let values = tv.value.to_vector()?;
+        for j in [*party_id as usize, (*party_id as usize + 1) % PARTIES] {
+            match &shares[j] {
+                Some(v) if *v != values[j] => {
+                    return Err(runtime_error!("Inconsistent shares"));
+                }
+                _ => shares[j] = Some(values[j].clone()),
+            }
+        }
+    }
+    let t = share_type.ok_or_else(|| runtime_error!("No shares provided"))?;
+    let mut result = Value::zero_of_type(t.clone());
+    for share in shares {
+        let share = share.ok_or_else(|| runtime_error!("Not enough shares to reconstruct"))?;
+        result = generalized_add(result, share, t.clone())?;
+    }
+    TypedValue::new(t, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{scalar_type, INT32, UINT64};
+    use crate::evaluators::random_evaluate;
+    use crate::graphs::create_context;
+
+    #[test]
+    fn test_share_and_reconstruct() {
+        || -> Result<()> {
+            let key = generate_data_owner_key()?;
+            let tv = TypedValue::new(
+                array_type(vec![3], INT32),
+                Value::from_flattened_array(&[-1, 5, 100], INT32)?,
+            )?;
+            let shares: Vec<(u64, TypedValue)> = (0..3)
+                .map(|i| Ok((i, share_for_party(&tv, i, key, 7)?)))
+                .collect::<Result<_>>()?;
+            for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+                let pair = vec![shares[i].clone(), shares[j].clone()];
+                assert_eq!(reconstruct(&pair)?, tv);
+            }
+            assert_eq!(reconstruct(&shares)?, tv);
+            // Shares of a party alone are not enough
+            assert!(reconstruct(&shares[..1]).is_err());
+            // A different input ID yields different shares
+            assert!(share_for_party(&tv, 0, key, 8)? != shares[0].1);
+            // Shares of a different key are inconsistent
+            let other = share_for_party(&tv, 1, generate_data_owner_key()?, 7)?;
+            assert!(reconstruct(&[shares[0].clone(), (1, other)]).is_err());
+            assert!(share_for_party(&tv, 3, key, 7).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_prf_key_format() {
+        || -> Result<()> {
+            let key = generate_data_owner_key()?;
+            let t = scalar_type(UINT64);
+            let tv = TypedValue::new(t.clone(), Value::from_scalar(42, UINT64)?)?;
+            let shares = share_for_party(&tv, 0, key, 3)?.value.to_vector()?;
+
+            let c = create_context()?;
+            let g = c.create_graph()?;
+            let k = g.input(array_type(vec![KEY_LENGTH], BIT))?;
+            let x0 = g.prf(k.clone(), 6, t.clone())?;
+            let x1 = g.prf(k, 7, t)?;
+            g.create_tuple(vec![x0, x1])?.set_as_output()?;
+            g.finalize()?.set_as_main()?;
+            c.finalize()?;
+            let result = random_evaluate(g, vec![get_prf_key_typed_value(key)?.value])?;
+            assert_eq!(result.to_vector()?, shares[..2].to_vec());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -0,0 +1,376 @@
+//! Compact binary encoding of values for storage and for messages exchanged between parties.
+//!
+//! Every array (or scalar) of a value is encoded separately ("per column"):
+//! binary and 128-bit arrays are stored as they are, while other integer arrays are stored with
+//! the smallest width among 1, 2, 4 and 8 bytes that fits all their entries.
+//! Signed entries are zigzag-encoded first, so that small negative integers stay small.
+//! The resulting bytes can be additionally compressed with [zstd](https://github.com/facebook/zstd)
+//! if the crate is built with the `zstd` feature.
+use crate::data_types::{get_size_in_bits, get_types_vector, ScalarType, Type, BIT};
+use crate::data_values::Value;
+use crate::errors::Result;
+
+use serde::{Deserialize, Serialize};
+
+const TYPED_PACKING_FLAG: u8 = 1;
+const ZSTD_FLAG: u8 = 2;
+
+/// Parameters of [compress_value].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    /// Store integer arrays with the smallest width fitting their entries.
+    pub typed_packing: bool,
+    /// Compression level of zstd applied on top of packing, if any.
+    ///
+    /// Requires the `zstd` feature.
+    pub zstd_level: Option<i32>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            typed_packing: true,
+            zstd_level: None,
+        }
+    }
+}
+
+/// Encodes a value of a given type into bytes.
+///
+/// # Arguments
+///
+/// * `value` - value to encode
+/// * `t` - type of `value`
+/// * `config` - encoding parameters
+///
+/// # Returns
+///
+/// Bytes that can be decoded back with [decompress_value]
+///
+/// # Example
+///
+/// ```
+/// # use ciphercore_base::data_types::{array_type, INT64};
+/// # use ciphercore_base::data_values::Value;
+/// # use ciphercore_base::value_compression::{compress_value, decompress_value, CompressionConfig};
+/// let t = array_type(vec![100], INT64);
+/// let v = Value::from_flattened_array(&vec![-1; 100], INT64).unwrap();
+/// let bytes = compress_value(&v, t.clone(), &CompressionConfig::default()).unwrap();
+/// assert!(bytes.len() < 200);
+/// assert_eq!(decompress_value(&bytes, t).unwrap(), v);
+/// ```
+pub fn compress_value(value: &Value, t: Type, config: &CompressionConfig) -> Result<Vec<u8>> {
+    if !value.check_type(t.clone())? {
+        return Err(runtime_error!("Type and value mismatch"));
+    }
+    let mut payload = vec![];
+    encode_value(value, t, config.typed_packing, &mut payload)?;
+    let mut flags = 0;
+    if config.typed_packing {
+        flags |= TYPED_PACKING_FLAG;
+    }
+    if let Some(level) = config.zstd_level {
+        flags |= ZSTD_FLAG;
+        payload = zstd_compress(&payload, level)?;
+    }
+    let mut result = vec![flags];
+    result.extend(payload);
+    Ok(result)
+}
+
+/// Decodes bytes produced by [compress_value].
+///
+/// # Arguments
+///
+/// * `bytes` - encoded value
+/// * `t` - type of the encoded value
+///
+/// # Returns
+///
+/// Decoded value
+pub fn decompress_value(bytes: &[u8], t: Type) -> Result<Value> {
+    if bytes.is_empty() {
+        return Err(runtime_error!("Compressed value can't be empty"));
+    }
+    let flags = bytes[0];
+    if flags & !(TYPED_PACKING_FLAG | ZSTD_FLAG) != 0 {
+        return Err(runtime_error!("Unknown compression flags"));
+    }
+    let payload = if flags & ZSTD_FLAG != 0 {
+        zstd_decompress(&bytes[1..])?
+    } else {
+        bytes[1..].to_vec()
+    };
+    let mut position = 0;
+    let value = decode_value(&payload, &mut position, t, flags & TYPED_PACKING_FLAG != 0)?;
+    if position != payload.len() {
+        return Err(runtime_error!("Compressed value has trailing bytes"));
+    }
+    Ok(value)
+}
+
+fn encode_value(value: &Value, t: Type, typed_packing: bool, out: &mut Vec<u8>) -> Result<()> {
+    match t {
+        Type::Scalar(st) | Type::Array(_, st) => value.access_bytes(|bytes| {
+            if !typed_packing || st == BIT || st.size_in_bits() > 64 {
+                out.extend_from_slice(bytes);
+                return Ok(());
+            }
+            let entries = bytes_to_entries(bytes, &st)
+                .into_iter()
+                .map(|x| to_packable(x, &st))
+                .collect::<Vec<u64>>();
+            let max_entry = entries.iter().copied().max().unwrap_or(0);
+            let width = get_packing_width(max_entry);
+            out.push(width as u8);
+            for entry in entries {
+                out.extend_from_slice(&entry.to_le_bytes()[..width]);
+            }
+            Ok(())
+        }),
+        _ => {
+            let values = value.to_vector()?;
+            let types = get_types_vector(t)?;
+            if values.len() != types.len() {
+                return Err(runtime_error!("Type and value mismatch"));
+            }
+            for (element, element_type) in values.iter().zip(types) {
+                encode_value(element, (*element_type).clone(), typed_packing, out)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn decode_value(bytes: &[u8], position: &mut usize, t: Type, typed_packing: bool) -> Result<Value> {
+    match t.clone() {
+        Type::Scalar(st) | Type::Array(_, st) => {
+            let raw_length = get_size_in_bits(t.clone())?.div_ceil(8) as usize;
+            if !typed_packing || st == BIT || st.size_in_bits() > 64 {
+                let raw = read_bytes(bytes, position, raw_length)?;
+                return Ok(Value::from_bytes(raw.to_vec()));
+            }
+            let width = read_bytes(bytes, position, 1)?[0] as usize;
+            if ![1, 2, 4, 8].contains(&width) {
+                return Err(runtime_error!("Invalid packing width: {}", width));
+            }
+            let entry_length = st.size_in_bits().div_ceil(8) as usize;
+            let num_entries = raw_length / entry_length;
+            let packed = read_bytes(bytes, position, num_entries * width)?;
+            let mut raw = Vec::with_capacity(raw_length);
+            for chunk in packed.chunks_exact(width) {
+                let mut entry_bytes = [0u8; 8];
+                entry_bytes[..width].copy_from_slice(chunk);
+                let entry = from_packable(u64::from_le_bytes(entry_bytes), &st);
+                raw.extend_from_slice(&entry.to_le_bytes()[..entry_length]);
+            }
+            let value = Value::from_bytes(raw);
+            if !value.check_type(t)? {
+                return Err(runtime_error!("Decoded value doesn't match its type"));
+            }
+            Ok(value)
+        }
+        _ => {
+            let mut values = vec![];
+            for element_type in get_types_vector(t)? {
+                values.push(decode_value(
+                    bytes,
+                    position,
+                    (*element_type).clone(),
+                    typed_packing,
+                )?);
+            }
+            Ok(Value::from_vector(values))
+        }
+    }
+}
+
+fn read_bytes<'a>(bytes: &'a [u8], position: &mut usize, length: usize) -> Result<&'a [u8]> {
+    if bytes.len() - *position < length {
+        return Err(runtime_error!("Compressed value is truncated"));
+    }
+    let result = &bytes[*position..*position + length];
+    *position += length;
+    Ok(result)
+}
+
+fn bytes_to_entries(bytes: &[u8], st: &ScalarType) -> Vec<u64> {
+    let entry_length = st.size_in_bits().div_ceil(8) as usize;
+    bytes
+        .chunks_exact(entry_length)
+        .map(|chunk| {
+            let mut entry_bytes = [0u8; 8];
+            entry_bytes[..entry_length].copy_from_slice(chunk);
+            u64::from_le_bytes(entry_bytes)
+        })
+        .collect()
+}
+
+/// Zigzag-encodes signed entries, so that entries of small absolute value have few significant bytes.
+fn to_packable(x: u64, st: &ScalarType) -> u64 {
+    if !st.get_signed() {
+        return x;
+    }
+    let shift = 64 - st.size_in_bits();
+    let signed = ((x << shift) as i64) >> shift;
+    ((signed << 1) ^ (signed >> 63)) as u64
+}
+
+fn from_packable(x: u64, st: &ScalarType) -> u64 {
+    if !st.get_signed() {
+        return x;
+    }
+    let signed = ((x >> 1) as i64) ^ -((x & 1) as i64);
+    let size = st.size_in_bits();
+    if size == 64 {
+        signed as u64
+    } else {
+        (signed as u64) & ((1 << size) - 1)
+    }
+}
+
+fn get_packing_width(max_entry: u64) -> usize {
+    if max_entry <= u8::MAX as u64 {
+        1
+    } else if max_entry <= u16::MAX as u64 {
+        2
+    } else if max_entry <= u32::MAX as u64 {
+        4
+    } else {
+        8
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_compress(bytes: &[u8], level: i32) -> Result<Vec<u8>> {
+    zstd::bulk::compress(bytes, level).map_err(|e| runtime_error!("zstd error: {}", e))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_compress(_bytes: &[u8], _level: i32) -> Result<Vec<u8>> {
+    Err(runtime_error!(
+        "zstd compression requires the `zstd` feature"
+    ))
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_decompress(bytes: &[u8]) -> Result<Vec<u8>> {
+    zstd::stream::decode_all(bytes).map_err(|e| runtime_error!("zstd error: {}", e))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_decompress(_bytes: &[u8]) -> Result<Vec<u8>> {
+    Err(runtime_error!(
+        "zstd decompression requires the `zstd` feature"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{
+        array_type, named_tuple_type, scalar_type, tuple_type, vector_type, INT128, INT16, INT32,
+        INT64, UINT32, UINT64, UINT8,
+    };
+
+    fn roundtrip(value: Value, t: Type, config: &CompressionConfig) -> Result<usize> {
+        let bytes = compress_value(&value, t.clone(), config)?;
+        assert_eq!(decompress_value(&bytes, t)?, value);
+        Ok(bytes.len())
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        || -> Result<()> {
+            let configs = vec![
+                CompressionConfig::default(),
+                CompressionConfig {
+                    typed_packing: false,
+                    zstd_level: None,
+                },
+            ];
+            for config in configs {
+                for st in [BIT, UINT8, INT16, INT32, UINT32, INT64, UINT64, INT128] {
+                    let t = array_type(vec![2, 3], st.clone());
+                    let entries: Vec<u64> = if st == BIT {
+                        vec![0, 1, 1, 0, 1, 0]
+                    } else {
+                        vec![0, 1, u64::MAX, 1 << 40, 255, (-300i64) as u64]
+                    };
+                    let value = Value::from_flattened_array(&entries, st.clone())?;
+                    roundtrip(value, t, &config)?;
+                    let value = Value::from_scalar(entries[2], st.clone())?;
+                    roundtrip(value, scalar_type(st), &config)?;
+                }
+                let t = named_tuple_type(vec![
+                    ("a".to_owned(), array_type(vec![3], BIT)),
+                    (
+                        "b".to_owned(),
+                        tuple_type(vec![
+                            scalar_type(INT32),
+                            vector_type(2, scalar_type(UINT64)),
+                        ]),
+                    ),
+                ]);
+                let value = Value::from_vector(vec![
+                    Value::from_flattened_array(&[1, 0, 1], BIT)?,
+                    Value::from_vector(vec![
+                        Value::from_scalar(-5, INT32)?,
+                        Value::from_vector(vec![
+                            Value::from_scalar(7, UINT64)?,
+                            Value::from_scalar(1u64 << 50, UINT64)?,
+                        ]),
+                    ]),
+                ]);
+                roundtrip(value, t, &config)?;
+            }
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_packing_size() {
+        || -> Result<()> {
+            let t = array_type(vec![1000], INT64);
+            let entries: Vec<i64> = (0..1000).map(|i| i % 100 - 50).collect();
+            let value = Value::from_flattened_array(&entries, INT64)?;
+            let packed = roundtrip(value.clone(), t.clone(), &CompressionConfig::default())?;
+            // Every entry fits into one byte after zigzag encoding
+            assert_eq!(packed, 1 + 1 + 1000);
+            let unpacked = roundtrip(
+                value.clone(),
+                t.clone(),
+                &CompressionConfig {
+                    typed_packing: false,
+                    zstd_level: None,
+                },
+            )?;
+            assert_eq!(unpacked, 1 + 8000);
+            let bytes = compress_value(&value, t.clone(), &CompressionConfig::default())?;
+            assert!(decompress_value(&bytes[..bytes.len() - 1], t.clone()).is_err());
+            assert!(decompress_value(&bytes, array_type(vec![999], INT64)).is_err());
+            assert!(
+                compress_value(&value, array_type(vec![10], INT64), &Default::default()).is_err()
+            );
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_zstd() {
+        let t = array_type(vec![1000], UINT32);
+        let value = Value::from_flattened_array(&vec![123456789u32; 1000], UINT32).unwrap();
+        let config = CompressionConfig {
+            typed_packing: true,
+            zstd_level: Some(3),
+        };
+        if cfg!(feature = "zstd") {
+            let size = roundtrip(value, t, &config).unwrap();
+            assert!(size < 100);
+        } else {
+            assert!(compress_value(&value, t, &config).is_err());
+        }
+    }
+}
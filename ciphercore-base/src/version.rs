@@ -1,5 +1,6 @@
 use crate::errors::Result;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 //DATA_VERSION represents the current version of serializable data types, i.e., Value and Context. We only bump this const when we make backwards-incompatible changes of Value and Context.
 pub(crate) const DATA_VERSION: u64 = 1;
 
@@ -25,3 +26,51 @@ impl VersionedData {
         &self.data
     }
 }
+
+/// Returns the hex-encoded SHA-256 hash of a given string.
+pub(crate) fn get_data_hash(data: &str) -> String {
+    Sha256::digest(data.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// Versioned data with the hash of the serialized data, which is used for Contexts.
+// The hash is missing in Contexts serialized by older versions.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct HashedVersionedData {
+    version: u64,
+    data: String,
+    #[serde(default)]
+    hash: Option<String>,
+}
+
+impl HashedVersionedData {
+    pub(crate) fn create_hashed_data(ver: u64, serialized_data: String) -> Self {
+        HashedVersionedData {
+            version: ver,
+            hash: Some(get_data_hash(&serialized_data)),
+            data: serialized_data,
+        }
+    }
+
+    pub(crate) fn check_version(&self, version_req: u64) -> bool {
+        self.version == version_req
+    }
+
+    pub(crate) fn get_version(&self) -> u64 {
+        self.version
+    }
+
+    /// Checks that the data matches its hash if the hash is present.
+    pub(crate) fn check_hash(&self) -> bool {
+        match &self.hash {
+            Some(hash) => *hash == get_data_hash(&self.data),
+            None => true,
+        }
+    }
+
+    pub(crate) fn get_data_string(&self) -> &str {
+        &self.data
+    }
+}
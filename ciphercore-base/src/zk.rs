@@ -0,0 +1,710 @@
+//! Export of graphs to rank-1 constraint systems (R1CS) for zero-knowledge proofs of correct evaluation.
+//!
+//! A party evaluating a local subgraph (e.g. a public part of a computation or the preprocessing of its own data)
+//! can prove to others that the output was computed correctly without revealing private inputs.
+//! To this end, a graph is converted into an [R1cs] instance and an evaluation of the graph, recorded in an [EvaluationTrace],
+//! is converted into a satisfying assignment of its variables (a witness) that can be fed to an R1CS-based proof system.
+//!
+//! Constraints are defined over the ring of integers modulo `2^k`, where `k` is the bit size of the scalar type of the graph,
+//! i.e. they hold exactly when the graph is evaluated correctly.
+//! Proof systems working over prime fields should emulate this ring, e.g. by adding range checks.
+//!
+//! Only graphs with a single scalar type consisting of inputs, constants, additions, subtractions, multiplications,
+//! dot and matrix products, sums and operations moving array entries (Reshape, PermuteAxes, Get, GetSlice) are supported.
+use crate::bytes::vec_from_bytes;
+use crate::data_types::{array_type, scalar_type, ScalarType, Type, UINT64};
+use crate::data_values::Value;
+use crate::errors::Result;
+use crate::evaluators::simple_evaluator::SimpleEvaluator;
+use crate::evaluators::{evaluate_simple_evaluator, Evaluator};
+use crate::graphs::{create_context, Context, Graph, Node, Operation};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Sparse linear combination of variables given by pairs of variable indices and coefficients.
+pub type LinearCombination = Vec<(u64, u64)>;
+
+/// Constraint `<a, z> * <b, z> = <c, z>`, where `z` is the vector of all variables.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct R1csConstraint {
+    pub a: LinearCombination,
+    pub b: LinearCombination,
+    pub c: LinearCombination,
+}
+
+/// Rank-1 constraint system describing the evaluation of a graph.
+///
+/// Variables are ordered as follows:
+/// * variable 0 is equal to 1,
+/// * public variables: entries of public inputs (in the order of inputs) followed by the entries of the output,
+/// * private variables: entries of private inputs followed by intermediate products.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct R1cs {
+    /// Scalar type of the graph; all computations are performed modulo `2^k`, where `k` is its size in bits
+    pub scalar_type: ScalarType,
+    /// Total number of variables including the constant one
+    pub num_variables: u64,
+    /// Number of public variables excluding the constant one
+    pub num_public_variables: u64,
+    pub constraints: Vec<R1csConstraint>,
+    /// IDs of input nodes and the variables of their entries
+    input_variables: Vec<(u64, Vec<u64>)>,
+    /// Variable equal to the product in every constraint
+    defined_variables: Vec<u64>,
+    output_node_id: u64,
+    output_variables: Vec<u64>,
+}
+
+/// Values of the nodes of a graph computed during its evaluation.
+pub struct EvaluationTrace {
+    graph_id: u64,
+    values: HashMap<u64, Value>,
+}
+
+/// Evaluator wrapper recording the values of the nodes of a given graph.
+struct TracingEvaluator {
+    evaluator: SimpleEvaluator,
+    graph_id: u64,
+    values: HashMap<u64, Value>,
+}
+
+impl Evaluator for TracingEvaluator {
+    fn preprocess(&mut self, context: Context) -> Result<()> {
+        self.evaluator.preprocess(context)
+    }
+
+    fn evaluate_node(&mut self, node: Node, dependencies_values: Vec<Value>) -> Result<Value> {
+        let result = self
+            .evaluator
+            .evaluate_node(node.clone(), dependencies_values)?;
+        if node.get_graph().get_id() == self.graph_id {
+            self.values.insert(node.get_id(), result.clone());
+        }
+        Ok(result)
+    }
+
+    fn on_value_stored(&mut self, value: &Value) -> Result<()> {
+        self.evaluator.on_value_stored(value)
+    }
+
+    fn on_value_released(&mut self, value: &Value) {
+        self.evaluator.on_value_released(value)
+    }
+
+    fn on_graph_call_started(&mut self, node: &Node, iteration: u64) -> Result<()> {
+        self.evaluator.on_graph_call_started(node, iteration)
+    }
+
+    fn on_graph_call_finished(&mut self) {
+        self.evaluator.on_graph_call_finished()
+    }
+}
+
+impl EvaluationTrace {
+    /// Evaluates a graph and records the values of its nodes.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph` - graph of a finalized context
+    /// * `inputs` - values of the inputs of the graph
+    ///
+    /// # Returns
+    ///
+    /// Evaluation trace
+    pub fn new(graph: Graph, inputs: Vec<Value>) -> Result<Self> {
+        let graph_id = graph.get_id();
+        let mut evaluator = TracingEvaluator {
+            evaluator: SimpleEvaluator::new(None)?,
+            graph_id,
+            values: HashMap::new(),
+        };
+        evaluator.preprocess(graph.get_context())?;
+        evaluator.evaluate_graph(graph.clone(), inputs.clone())?;
+        let mut values = evaluator.values;
+        let input_nodes = graph
+            .get_nodes()
+            .into_iter()
+            .filter(|node| matches!(node.get_operation(), Operation::Input(_)));
+        for (node, value) in input_nodes.zip(inputs) {
+            values.insert(node.get_id(), value);
+        }
+        Ok(EvaluationTrace { graph_id, values })
+    }
+
+    /// Returns the value of a node recorded in the trace.
+    pub fn get_node_value(&self, node: Node) -> Result<Value> {
+        if node.get_graph().get_id() != self.graph_id {
+            return Err(runtime_error!("Node doesn't belong to the traced graph"));
+        }
+        self.values
+            .get(&node.get_id())
+            .cloned()
+            .ok_or_else(|| runtime_error!("Node value is missing in the trace"))
+    }
+}
+
+fn get_mask(st: &ScalarType) -> u64 {
+    let bits = st.size_in_bits();
+    if bits == 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+fn get_num_entries(t: &Type) -> u64 {
+    t.get_dimensions().iter().product()
+}
+
+/// Returns the entries of a scalar or an array as integers modulo `2^k`.
+fn flatten_value(value: &Value, t: &Type) -> Result<Vec<u64>> {
+    let st = t.get_scalar_type();
+    let mask = get_mask(&st);
+    let mut entries = value.access_bytes(|bytes| vec_from_bytes(bytes, st.clone()))?;
+    entries.truncate(get_num_entries(t) as usize);
+    Ok(entries.into_iter().map(|x| x & mask).collect())
+}
+
+fn get_index_type(t: &Type) -> Type {
+    if t.is_scalar() {
+        scalar_type(UINT64)
+    } else {
+        array_type(t.get_shape(), UINT64)
+    }
+}
+
+/// Computes the positions of the entries of an output of an operation in its input
+/// by applying the operation to the array of input indices.
+fn get_index_map(t: &Type, apply: impl FnOnce(Node) -> Result<Node>) -> Result<Vec<usize>> {
+    let index_type = get_index_type(t);
+    let indices: Vec<u64> = (0..get_num_entries(t)).collect();
+    let c = create_context()?;
+    let g = c.create_graph()?;
+    let input = g.input(index_type.clone())?;
+    let output = apply(input)?;
+    let output_type = output.get_type()?;
+    output.set_as_output()?;
+    g.finalize()?.set_as_main()?;
+    c.finalize()?;
+    let input_value = if index_type.is_scalar() {
+        Value::from_scalar(0, UINT64)?
+    } else {
+        Value::from_flattened_array(&indices, UINT64)?
+    };
+    let result = evaluate_simple_evaluator(g, vec![input_value], None)?;
+    Ok(flatten_value(&result, &output_type)?
+        .into_iter()
+        .map(|i| i as usize)
+        .collect())
+}
+
+struct R1csBuilder {
+    scalar_type: ScalarType,
+    mask: u64,
+    num_variables: u64,
+    constraints: Vec<R1csConstraint>,
+    defined_variables: Vec<u64>,
+}
+
+impl R1csBuilder {
+    fn normalize(&self, terms: impl Iterator<Item = (u64, u64)>) -> LinearCombination {
+        let mut merged = BTreeMap::new();
+        for (variable, coefficient) in terms {
+            let entry = merged.entry(variable).or_insert(0u64);
+            *entry = entry.wrapping_add(coefficient) & self.mask;
+        }
+        merged.into_iter().filter(|(_, c)| *c != 0).collect()
+    }
+
+    fn constant(&self, x: u64) -> LinearCombination {
+        self.normalize([(0, x)].into_iter())
+    }
+
+    fn add(&self, a: &LinearCombination, b: &LinearCombination) -> LinearCombination {
+        self.normalize(a.iter().chain(b.iter()).cloned())
+    }
+
+    fn scale(&self, a: &LinearCombination, x: u64) -> LinearCombination {
+        self.normalize(a.iter().map(|(v, c)| (*v, c.wrapping_mul(x))))
+    }
+
+    fn subtract(&self, a: &LinearCombination, b: &LinearCombination) -> LinearCombination {
+        self.add(a, &self.scale(b, self.mask))
+    }
+
+    /// Returns the value of a linear combination that doesn't depend on variables other than the constant one.
+    fn get_constant(a: &LinearCombination) -> Option<u64> {
+        match a.as_slice() {
+            [] => Some(0),
+            [(0, c)] => Some(*c),
+            _ => None,
+        }
+    }
+
+    /// Adds a constraint `a * b = v` for a new variable `v`.
+    fn define_product(&mut self, a: LinearCombination, b: LinearCombination) -> u64 {
+        let variable = self.num_variables;
+        self.num_variables += 1;
+        self.constraints.push(R1csConstraint {
+            a,
+            b,
+            c: vec![(variable, 1)],
+        });
+        self.defined_variables.push(variable);
+        variable
+    }
+
+    fn multiply(&mut self, a: &LinearCombination, b: &LinearCombination) -> LinearCombination {
+        if let Some(x) = Self::get_constant(a) {
+            return self.scale(b, x);
+        }
+        if let Some(x) = Self::get_constant(b) {
+            return self.scale(a, x);
+        }
+        vec![(self.define_product(a.clone(), b.clone()), 1)]
+    }
+
+    fn broadcast(
+        &self,
+        entries: &[LinearCombination],
+        t: &Type,
+        output_type: &Type,
+    ) -> Result<Vec<LinearCombination>> {
+        if t.get_dimensions() == output_type.get_dimensions() {
+            return Ok(entries.to_vec());
+        }
+        let output_index_type = get_index_type(output_type);
+        let map = get_index_map(t, |x| {
+            let zero = x.get_graph().constant(
+                output_index_type.clone(),
+                Value::zero_of_type(output_index_type),
+            )?;
+            x.add(zero)
+        })?;
+        Ok(map.into_iter().map(|i| entries[i].clone()).collect())
+    }
+
+    fn matmul(
+        &mut self,
+        a: &[LinearCombination],
+        b: &[LinearCombination],
+        m: u64,
+        k: u64,
+        n: u64,
+    ) -> Vec<LinearCombination> {
+        let mut result = vec![];
+        for i in 0..m {
+            for j in 0..n {
+                let mut sum = vec![];
+                for l in 0..k {
+                    let product = self.multiply(&a[(i * k + l) as usize], &b[(l * n + j) as usize]);
+                    sum = self.add(&sum, &product);
+                }
+                result.push(sum);
+            }
+        }
+        result
+    }
+
+    fn sum(&self, entries: &[LinearCombination], t: &Type, axes: &[u64]) -> Vec<LinearCombination> {
+        let shape = t.get_dimensions();
+        let output_shape: Vec<u64> = (0..shape.len() as u64)
+            .filter(|axis| !axes.contains(axis))
+            .map(|axis| shape[axis as usize])
+            .collect();
+        let mut result = vec![vec![]; output_shape.iter().product::<u64>() as usize];
+        for (index, entry) in entries.iter().enumerate() {
+            // Compute the index of the entry in the output by dropping the summed axes
+            let mut remainder = index as u64;
+            let mut output_index = 0;
+            let mut output_stride = 1;
+            for axis in (0..shape.len()).rev() {
+                let coordinate = remainder % shape[axis];
+                remainder /= shape[axis];
+                if !axes.contains(&(axis as u64)) {
+                    output_index += coordinate * output_stride;
+                    output_stride *= shape[axis];
+                }
+            }
+            let sum = &mut result[output_index as usize];
+            *sum = self.add(sum, entry);
+        }
+        result
+    }
+
+    fn lower_node(
+        &mut self,
+        node: &Node,
+        lowered: &HashMap<u64, Vec<LinearCombination>>,
+    ) -> Result<Vec<LinearCombination>> {
+        let t = node.get_type()?;
+        let deps = node.get_node_dependencies();
+        let dep_types = deps
+            .iter()
+            .map(|dep| dep.get_type())
+            .collect::<Result<Vec<Type>>>()?;
+        let dep_entries: Vec<&Vec<LinearCombination>> =
+            deps.iter().map(|dep| &lowered[&dep.get_id()]).collect();
+        let op = node.get_operation();
+        match op {
+            Operation::Constant(_, value) => Ok(flatten_value(&value, &t)?
+                .into_iter()
+                .map(|x| self.constant(x))
+                .collect()),
+            Operation::Add | Operation::Subtract | Operation::Multiply => {
+                let a = self.broadcast(dep_entries[0], &dep_types[0], &t)?;
+                let b = self.broadcast(dep_entries[1], &dep_types[1], &t)?;
+                let mut result = vec![];
+                for (x, y) in a.iter().zip(b.iter()) {
+                    result.push(match op {
+                        Operation::Add => self.add(x, y),
+                        Operation::Subtract => self.subtract(x, y),
+                        _ => self.multiply(x, y),
+                    });
+                }
+                Ok(result)
+            }
+            Operation::Dot | Operation::Matmul => {
+                let a_shape = dep_types[0].get_dimensions();
+                let b_shape = dep_types[1].get_dimensions();
+                let is_vector_dot = matches!(op, Operation::Dot)
+                    && dep_types[0].is_array()
+                    && a_shape.len() == 1
+                    && b_shape.len() == 1;
+                if is_vector_dot {
+                    Ok(self.matmul(dep_entries[0], dep_entries[1], 1, a_shape[0], 1))
+                } else if dep_types[0].is_array() && a_shape.len() == 2 && b_shape.len() == 2 {
+                    Ok(self.matmul(
+                        dep_entries[0],
+                        dep_entries[1],
+                        a_shape[0],
+                        a_shape[1],
+                        b_shape[1],
+                    ))
+                } else {
+                    Err(runtime_error!(
+                        "R1CS export of {} is supported only for vectors and matrices",
+                        op
+                    ))
+                }
+            }
+            Operation::Sum(axes) => Ok(self.sum(dep_entries[0], &dep_types[0], &axes)),
+            Operation::Reshape(_) | Operation::NOP => Ok(dep_entries[0].clone()),
+            Operation::PermuteAxes(_) | Operation::Get(_) | Operation::GetSlice(_) => {
+                let map = get_index_map(&dep_types[0], |x| {
+                    x.get_graph().add_node(vec![x], vec![], op.clone())
+                })?;
+                Ok(map.into_iter().map(|i| dep_entries[0][i].clone()).collect())
+            }
+            _ => Err(runtime_error!("R1CS export of {} is not supported", op)),
+        }
+    }
+
+    fn evaluate(&self, a: &LinearCombination, z: &[u64]) -> u64 {
+        a.iter().fold(0u64, |sum, (v, c)| {
+            sum.wrapping_add(c.wrapping_mul(z[*v as usize])) & self.mask
+        })
+    }
+}
+
+impl R1cs {
+    fn get_builder(&self) -> R1csBuilder {
+        R1csBuilder {
+            scalar_type: self.scalar_type.clone(),
+            mask: get_mask(&self.scalar_type),
+            num_variables: self.num_variables,
+            constraints: vec![],
+            defined_variables: vec![],
+        }
+    }
+
+    /// Computes the assignment of all the variables from an evaluation trace of the exported graph.
+    ///
+    /// # Arguments
+    ///
+    /// `trace` - evaluation trace of the graph passed to [export_r1cs]
+    ///
+    /// # Returns
+    ///
+    /// Values of all the variables starting from the constant one
+    pub fn generate_witness(&self, trace: &EvaluationTrace) -> Result<Vec<u64>> {
+        let builder = self.get_builder();
+        let mut z = vec![0u64; self.num_variables as usize];
+        z[0] = 1;
+        let get_entries = |node_id: u64, n: usize| -> Result<Vec<u64>> {
+            let value = trace
+                .values
+                .get(&node_id)
+                .ok_or_else(|| runtime_error!("Node value is missing in the trace"))?;
+            let entries =
+                flatten_value(value, &array_type(vec![n as u64], self.scalar_type.clone()))?;
+            if entries.len() != n {
+                return Err(runtime_error!("Trace doesn't match the circuit"));
+            }
+            Ok(entries)
+        };
+        for (node_id, variables) in &self.input_variables {
+            for (v, x) in variables
+                .iter()
+                .zip(get_entries(*node_id, variables.len())?)
+            {
+                z[*v as usize] = x;
+            }
+        }
+        for (constraint, v) in self.constraints.iter().zip(self.defined_variables.iter()) {
+            let a = builder.evaluate(&constraint.a, &z);
+            let b = builder.evaluate(&constraint.b, &z);
+            z[*v as usize] = a.wrapping_mul(b) & builder.mask;
+        }
+        let output = get_entries(self.output_node_id, self.output_variables.len())?;
+        for (v, x) in self.output_variables.iter().zip(output) {
+            if z[*v as usize] != x {
+                return Err(runtime_error!(
+                    "Output in the trace is inconsistent with the circuit"
+                ));
+            }
+        }
+        Ok(z)
+    }
+
+    /// Returns the values of the public variables (excluding the constant one) of an assignment.
+    pub fn get_public_values(&self, z: &[u64]) -> Vec<u64> {
+        z[1..=self.num_public_variables as usize].to_vec()
+    }
+
+    /// Checks whether an assignment of variables satisfies all the constraints.
+    pub fn is_satisfied(&self, z: &[u64]) -> bool {
+        if z.len() as u64 != self.num_variables || z[0] != 1 {
+            return false;
+        }
+        let builder = self.get_builder();
+        self.constraints.iter().all(|constraint| {
+            let a = builder.evaluate(&constraint.a, z);
+            let b = builder.evaluate(&constraint.b, z);
+            a.wrapping_mul(b) & builder.mask == builder.evaluate(&constraint.c, z)
+        })
+    }
+}
+
+/// Converts a graph into a rank-1 constraint system.
+///
+/// # Arguments
+///
+/// * `graph` - graph of a finalized context with the same scalar type in all the nodes and without custom operations
+/// * `public_inputs` - flags indicating whether the corresponding inputs of the graph are public; private inputs are only known to the prover
+///
+/// # Returns
+///
+/// R1CS whose satisfying assignments correspond to evaluations of the graph
+pub fn export_r1cs(graph: Graph, public_inputs: Vec<bool>) -> Result<R1cs> {
+    graph.get_context().check_finalized()?;
+    let nodes = graph.get_nodes();
+    let inputs: Vec<Node> = nodes
+        .iter()
+        .filter(|node| matches!(node.get_operation(), Operation::Input(_)))
+        .cloned()
+        .collect();
+    if inputs.len() != public_inputs.len() {
+        return Err(runtime_error!(
+            "Publicity of {} inputs expected, but {} provided",
+            inputs.len(),
+            public_inputs.len()
+        ));
+    }
+    let output_node = graph.get_output_node()?;
+    let output_type = output_node.get_type()?;
+    if !output_type.is_scalar() && !output_type.is_array() {
+        return Err(runtime_error!("Output must be a scalar or an array"));
+    }
+    let st = output_type.get_scalar_type();
+    for node in &nodes {
+        let t = node.get_type()?;
+        if !(t.is_scalar() || t.is_array()) || t.get_scalar_type() != st {
+            return Err(runtime_error!(
+                "All the nodes must be scalars or arrays of {}",
+                st
+            ));
+        }
+    }
+
+    // Allocate input and output variables in the order defined in the description of R1cs
+    let mut num_variables = 1;
+    let mut input_variables = HashMap::new();
+    let mut allocate_inputs = |is_public: bool, num_variables: &mut u64| -> Result<()> {
+        for (node, flag) in inputs.iter().zip(public_inputs.iter()) {
+            if *flag == is_public {
+                let n = get_num_entries(&node.get_type()?);
+                input_variables.insert(
+                    node.get_id(),
+                    (*num_variables..*num_variables + n).collect(),
+                );
+                *num_variables += n;
+            }
+        }
+        Ok(())
+    };
+    allocate_inputs(true, &mut num_variables)?;
+    let num_outputs = get_num_entries(&output_type);
+    let output_variables: Vec<u64> = (num_variables..num_variables + num_outputs).collect();
+    num_variables += num_outputs;
+    let num_public_variables = num_variables - 1;
+    allocate_inputs(false, &mut num_variables)?;
+
+    let mut builder = R1csBuilder {
+        scalar_type: st.clone(),
+        mask: get_mask(&st),
+        num_variables,
+        constraints: vec![],
+        defined_variables: vec![],
+    };
+    let mut lowered: HashMap<u64, Vec<LinearCombination>> = HashMap::new();
+    for node in &nodes {
+        let entries = match node.get_operation() {
+            Operation::Input(_) => {
+                let variables: &Vec<u64> = &input_variables[&node.get_id()];
+                variables.iter().map(|v| vec![(*v, 1)]).collect()
+            }
+            _ => builder.lower_node(node, &lowered)?,
+        };
+        lowered.insert(node.get_id(), entries);
+    }
+    // Every output variable is equal to the corresponding linear combination
+    let mut constraints = builder.constraints;
+    let mut defined_variables = builder.defined_variables;
+    for (entry, v) in lowered[&output_node.get_id()]
+        .iter()
+        .zip(output_variables.iter())
+    {
+        constraints.push(R1csConstraint {
+            a: entry.clone(),
+            b: vec![(0, 1)],
+            c: vec![(*v, 1)],
+        });
+        defined_variables.push(*v);
+    }
+    let mut input_variables: Vec<(u64, Vec<u64>)> = input_variables.into_iter().collect();
+    input_variables.sort();
+    Ok(R1cs {
+        scalar_type: builder.scalar_type,
+        num_variables: builder.num_variables,
+        num_public_variables,
+        constraints,
+        input_variables,
+        defined_variables,
+        output_node_id: output_node.get_id(),
+        output_variables,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{BIT, INT32};
+    use crate::graphs::SliceElement;
+
+    fn build_context(build: impl FnOnce(&Graph) -> Result<Node>) -> Result<Context> {
+        let c = create_context()?;
+        let g = c.create_graph()?;
+        build(&g)?.set_as_output()?;
+        g.finalize()?.set_as_main()?;
+        c.finalize()?;
+        Ok(c)
+    }
+
+    fn prove(g: Graph, public_inputs: Vec<bool>, inputs: Vec<Value>) -> Result<(R1cs, Vec<u64>)> {
+        let r1cs = export_r1cs(g.clone(), public_inputs)?;
+        let trace = EvaluationTrace::new(g, inputs)?;
+        let z = r1cs.generate_witness(&trace)?;
+        assert!(r1cs.is_satisfied(&z));
+        Ok((r1cs, z))
+    }
+
+    #[test]
+    fn test_arithmetic_r1cs() {
+        || -> Result<()> {
+            let c = build_context(|g| {
+                let x = g.input(array_type(vec![2, 3], INT32))?;
+                let y = g.input(array_type(vec![3, 2], INT32))?;
+                let s = g.input(scalar_type(INT32))?;
+                let p = x.matmul(y.clone())?;
+                let q = x
+                    .permute_axes(vec![1, 0])?
+                    .multiply(y)?
+                    .subtract(s.clone())?
+                    .get_slice(vec![SliceElement::SubArray(Some(1), None, None)])?
+                    .sum(vec![0])?;
+                let three = g.constant(scalar_type(INT32), Value::from_scalar(3, INT32)?)?;
+                p.get(vec![0])?.multiply(three)?.add(q)?.multiply(s)
+            })?;
+            let g = c.get_main_graph()?;
+            let inputs = vec![
+                Value::from_flattened_array(&[1, -2, 3, 4, 5, -6], INT32)?,
+                Value::from_flattened_array(&[7, 8, -9, 10, 11, 12], INT32)?,
+                Value::from_scalar(-13, INT32)?,
+            ];
+            let expected = evaluate_simple_evaluator(g.clone(), inputs.clone(), None)?;
+            let (r1cs, z) = prove(g, vec![false, true, false], inputs)?;
+            // 12 products of the matmul, 6 elementwise products and 2 products with `s`
+            assert_eq!(r1cs.constraints.len(), 12 + 6 + 2 + 2);
+            // Public variables contain `y` and the output
+            let public_values = r1cs.get_public_values(&z);
+            assert_eq!(public_values.len(), 6 + 2);
+            assert_eq!(
+                public_values[6..].to_vec(),
+                flatten_value(&expected, &array_type(vec![2], INT32))?
+            );
+            // Any change of the output breaks the constraints
+            let mut forged = z.clone();
+            forged[7] = forged[7].wrapping_add(1) & 0xffffffff;
+            assert!(!r1cs.is_satisfied(&forged));
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_binary_r1cs() {
+        || -> Result<()> {
+            let c = build_context(|g| {
+                let x = g.input(array_type(vec![4], BIT))?;
+                let y = g.input(array_type(vec![4], BIT))?;
+                x.multiply(y.clone())?.add(x)?.dot(y)
+            })?;
+            let g = c.get_main_graph()?;
+            let inputs = vec![
+                Value::from_flattened_array(&[1, 0, 1, 1], BIT)?,
+                Value::from_flattened_array(&[1, 1, 0, 1], BIT)?,
+            ];
+            let (r1cs, z) = prove(g, vec![false, false], inputs)?;
+            assert_eq!(r1cs.num_public_variables, 1);
+            assert_eq!(r1cs.get_public_values(&z), vec![0]);
+            Ok(())
+        }()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_r1cs_errors() {
+        || -> Result<()> {
+            let c = build_context(|g| {
+                let x = g.input(array_type(vec![4], INT32))?;
+                x.a2b()
+            })?;
+            assert!(export_r1cs(c.get_main_graph()?, vec![true]).is_err());
+            let c = build_context(|g| g.input(array_type(vec![4], INT32)))?;
+            let g = c.get_main_graph()?;
+            assert!(export_r1cs(g.clone(), vec![]).is_err());
+            // Trace of another graph doesn't match the circuit
+            let r1cs = export_r1cs(g, vec![true])?;
+            let other = build_context(|g| g.input(array_type(vec![2], INT32)))?;
+            let trace = EvaluationTrace::new(
+                other.get_main_graph()?,
+                vec![Value::from_flattened_array(&[1, 2], INT32)?],
+            )?;
+            assert!(r1cs.generate_witness(&trace).is_err());
+            Ok(())
+        }()
+        .unwrap();
+    }
+}
@@ -6,6 +6,10 @@ use std::fmt;
 #[repr(C)]
 pub enum CiphercoreErrorKind {
     RuntimeError,
+    // Randomized Cuckoo hashing failed; evaluation can be retried with fresh hash functions
+    CuckooHashingFailure,
+    // Evaluation would exceed the memory budget of the evaluator
+    MemoryLimitExceeded,
 }
 
 mod custom_date_time_format {
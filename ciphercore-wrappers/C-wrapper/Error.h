@@ -6,6 +6,8 @@
 
 typedef enum CiphercoreErrorKind {
   RuntimeError,
+  CuckooHashingFailure,
+  MemoryLimitExceeded,
 } CiphercoreErrorKind;
 
 typedef struct CiphercoreError {
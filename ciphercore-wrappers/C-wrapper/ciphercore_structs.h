@@ -29,5 +29,9 @@ extern const struct ScalarType INT32;
 
 extern const struct ScalarType INT64;
 
+extern const struct ScalarType UINT128;
+
+extern const struct ScalarType INT128;
+
 #endif// CIPHERCORE_STRUCTS
 
@@ -34,6 +34,12 @@ pub static INT32: ScalarType = data_types::INT32;
 #[no_mangle]
 #[used]
 pub static INT64: ScalarType = data_types::INT64;
+#[no_mangle]
+#[used]
+pub static UINT128: ScalarType = data_types::UINT128;
+#[no_mangle]
+#[used]
+pub static INT128: ScalarType = data_types::INT128;
 
 fn type_method_helper<T, F, R: CResultTrait<T>>(type_ptr: *mut Type, op: F) -> R
 where
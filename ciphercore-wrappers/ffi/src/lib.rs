@@ -0,0 +1,361 @@
+//! Stable C ABI for embedding compiled CipherCore protocols into non-Rust services.
+//!
+//! All the objects are passed as opaque handles that must be released with the corresponding `*_free` function.
+//! Every fallible function returns a [CiphercoreStatus]; in case of an error, a human-readable description
+//! can be obtained via [ciphercore_last_error] on the same thread.
+//!
+//! A typical usage from C looks as follows:
+//! ```c
+//! CiphercoreContext *context;
+//! CiphercoreInputs *inputs = ciphercore_inputs_new();
+//! CiphercoreResult *result;
+//! char *json;
+//! if (ciphercore_context_load(serialized_context, &context) != CIPHERCORE_STATUS_OK) { ... }
+//! ciphercore_inputs_bind(inputs, "x", "{\"kind\":\"scalar\",\"type\":\"i32\",\"value\":5}");
+//! ciphercore_evaluate(context, inputs, true, &result);
+//! ciphercore_result_to_json(result, &json);
+//! ciphercore_string_free(json);
+//! ciphercore_result_free(result);
+//! ciphercore_inputs_free(inputs);
+//! ciphercore_context_free(context);
+//! ```
+
+use ciphercore_base::errors::Result;
+use ciphercore_base::evaluators::get_result_util::get_evaluator_result;
+use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
+use ciphercore_base::graphs::Context;
+use ciphercore_base::typed_value::TypedValue;
+use ciphercore_utils::errors::ErrorWithBody;
+use ciphercore_utils::execute_main::extract_panic_message;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+/// Status codes returned by the functions of this library.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CiphercoreStatus {
+    Ok = 0,
+    /// A required pointer argument is null.
+    NullPointer = 1,
+    /// A string argument is not valid UTF-8.
+    InvalidString = 2,
+    /// A serialized context can't be deserialized.
+    InvalidContext = 3,
+    /// An input value is malformed or inputs are bound inconsistently.
+    InvalidInput = 4,
+    /// Evaluation failed.
+    EvaluationError = 5,
+    /// An unexpected internal panic has been caught.
+    Panic = 6,
+}
+
+/// Opaque handle to a deserialized context.
+pub struct CiphercoreContext {
+    context: Context,
+}
+
+/// Opaque handle to a set of inputs bound either by position or by the names of input nodes.
+#[derive(Default)]
+pub struct CiphercoreInputs {
+    positional: Vec<TypedValue>,
+    named: HashMap<String, TypedValue>,
+}
+
+/// Opaque handle to an evaluation result.
+pub struct CiphercoreResult {
+    value: TypedValue,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Runs `f` converting errors and panics into status codes and recording the error message.
+fn run_with_status<F>(f: F) -> CiphercoreStatus
+where
+    F: FnOnce() -> std::result::Result<(), (CiphercoreStatus, String)>,
+{
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => CiphercoreStatus::Ok,
+        Ok(Err((status, message))) => {
+            set_last_error(message);
+            status
+        }
+        Err(e) => {
+            let message = extract_panic_message(e).unwrap_or_else(|| "unknown panic".to_owned());
+            set_last_error(format!("panic: {}", message));
+            CiphercoreStatus::Panic
+        }
+    }
+}
+
+fn with_status<T>(
+    result: Result<T>,
+    status: CiphercoreStatus,
+) -> std::result::Result<T, (CiphercoreStatus, String)> {
+    result.map_err(|e| (status, e.get_body().message))
+}
+
+unsafe fn read_string(s: *const c_char) -> std::result::Result<String, (CiphercoreStatus, String)> {
+    if s.is_null() {
+        return Err((
+            CiphercoreStatus::NullPointer,
+            "Null string pointer".to_owned(),
+        ));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map(|s| s.to_owned())
+        .map_err(|e| {
+            (
+                CiphercoreStatus::InvalidString,
+                format!("Invalid UTF-8: {}", e),
+            )
+        })
+}
+
+unsafe fn read_ref<'a, T>(p: *const T) -> std::result::Result<&'a T, (CiphercoreStatus, String)> {
+    p.as_ref()
+        .ok_or_else(|| (CiphercoreStatus::NullPointer, "Null handle".to_owned()))
+}
+
+unsafe fn check_out<T>(out: *mut *mut T) -> std::result::Result<(), (CiphercoreStatus, String)> {
+    if out.is_null() {
+        return Err((
+            CiphercoreStatus::NullPointer,
+            "Null output pointer".to_owned(),
+        ));
+    }
+    *out = ptr::null_mut();
+    Ok(())
+}
+
+fn parse_typed_value(json: &str) -> std::result::Result<TypedValue, (CiphercoreStatus, String)> {
+    serde_json::from_str::<TypedValue>(json).map_err(|e| {
+        (
+            CiphercoreStatus::InvalidInput,
+            format!("Invalid input value: {}", e),
+        )
+    })
+}
+
+/// Returns the message of the last error occurred on the current thread or null if there was none.
+///
+/// The returned string is owned by the library and stays valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn ciphercore_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| match &*e.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Deserializes a context from a null-terminated JSON string (e.g., produced by `ciphercore_compile`).
+///
+/// # Safety
+///
+/// `serialized_context` must be a valid null-terminated string and `out` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn ciphercore_context_load(
+    serialized_context: *const c_char,
+    out: *mut *mut CiphercoreContext,
+) -> CiphercoreStatus {
+    run_with_status(|| {
+        check_out(out)?;
+        let serialized_context = read_string(serialized_context)?;
+        let context = serde_json::from_str::<Context>(&serialized_context).map_err(|e| {
+            (
+                CiphercoreStatus::InvalidContext,
+                format!("Invalid context: {}", e),
+            )
+        })?;
+        with_status(context.check_finalized(), CiphercoreStatus::InvalidContext)?;
+        *out = Box::into_raw(Box::new(CiphercoreContext { context }));
+        Ok(())
+    })
+}
+
+/// Releases a context handle. Null is ignored.
+///
+/// # Safety
+///
+/// `context` must be null or a handle returned by [ciphercore_context_load] that hasn't been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn ciphercore_context_free(context: *mut CiphercoreContext) {
+    if !context.is_null() {
+        drop(Box::from_raw(context));
+    }
+}
+
+/// Creates an empty set of inputs.
+#[no_mangle]
+pub extern "C" fn ciphercore_inputs_new() -> *mut CiphercoreInputs {
+    Box::into_raw(Box::new(CiphercoreInputs::default()))
+}
+
+/// Appends an input given as a JSON-serialized typed value (the format of `ciphercore_evaluate` inputs).
+///
+/// Inputs appended this way are matched to the input nodes of the main graph in the order of their creation.
+///
+/// # Safety
+///
+/// `inputs` must be a valid handle and `typed_value` a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ciphercore_inputs_push(
+    inputs: *mut CiphercoreInputs,
+    typed_value: *const c_char,
+) -> CiphercoreStatus {
+    run_with_status(|| {
+        let inputs = inputs
+            .as_mut()
+            .ok_or_else(|| (CiphercoreStatus::NullPointer, "Null handle".to_owned()))?;
+        let value = parse_typed_value(&read_string(typed_value)?)?;
+        inputs.positional.push(value);
+        Ok(())
+    })
+}
+
+/// Binds an input given as a JSON-serialized typed value to the input node of the main graph with a given name.
+///
+/// # Safety
+///
+/// `inputs` must be a valid handle; `name` and `typed_value` must be valid null-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn ciphercore_inputs_bind(
+    inputs: *mut CiphercoreInputs,
+    name: *const c_char,
+    typed_value: *const c_char,
+) -> CiphercoreStatus {
+    run_with_status(|| {
+        let inputs = inputs
+            .as_mut()
+            .ok_or_else(|| (CiphercoreStatus::NullPointer, "Null handle".to_owned()))?;
+        let name = read_string(name)?;
+        let value = parse_typed_value(&read_string(typed_value)?)?;
+        inputs.named.insert(name, value);
+        Ok(())
+    })
+}
+
+/// Releases an inputs handle. Null is ignored.
+///
+/// # Safety
+///
+/// `inputs` must be null or a handle returned by [ciphercore_inputs_new] that hasn't been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn ciphercore_inputs_free(inputs: *mut CiphercoreInputs) {
+    if !inputs.is_null() {
+        drop(Box::from_raw(inputs));
+    }
+}
+
+/// Evaluates the main graph of a context on given inputs with the reference evaluator.
+///
+/// Inputs must be either all pushed by position or all bound by name.
+/// If `reveal_output` is true and the output is secret-shared, the shares are combined into the revealed value.
+///
+/// # Safety
+///
+/// `context` and `inputs` must be valid handles and `out` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn ciphercore_evaluate(
+    context: *const CiphercoreContext,
+    inputs: *const CiphercoreInputs,
+    reveal_output: bool,
+    out: *mut *mut CiphercoreResult,
+) -> CiphercoreStatus {
+    run_with_status(|| {
+        check_out(out)?;
+        let context = read_ref(context)?.context.clone();
+        let inputs = read_ref(inputs)?;
+        let ordered_inputs = if inputs.named.is_empty() {
+            inputs.positional.clone()
+        } else {
+            if !inputs.positional.is_empty() {
+                return Err((
+                    CiphercoreStatus::InvalidInput,
+                    "Inputs can't be bound both by position and by name".to_owned(),
+                ));
+            }
+            let named = inputs
+                .named
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.clone()))
+                .collect();
+            let main_graph =
+                with_status(context.get_main_graph(), CiphercoreStatus::InvalidContext)?;
+            with_status(
+                main_graph.prepare_input_values(named),
+                CiphercoreStatus::InvalidInput,
+            )?
+        };
+        let evaluator = with_status(
+            SimpleEvaluator::new(None),
+            CiphercoreStatus::EvaluationError,
+        )?;
+        let value = with_status(
+            get_evaluator_result(context, ordered_inputs, reveal_output, evaluator),
+            CiphercoreStatus::EvaluationError,
+        )?;
+        *out = Box::into_raw(Box::new(CiphercoreResult { value }));
+        Ok(())
+    })
+}
+
+/// Serializes an evaluation result into a JSON typed value.
+///
+/// The returned string must be released with [ciphercore_string_free].
+///
+/// # Safety
+///
+/// `result` must be a valid handle and `out` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn ciphercore_result_to_json(
+    result: *const CiphercoreResult,
+    out: *mut *mut c_char,
+) -> CiphercoreStatus {
+    run_with_status(|| {
+        check_out(out)?;
+        let result = read_ref(result)?;
+        let json = serde_json::to_string(&result.value)
+            .map_err(|e| (CiphercoreStatus::EvaluationError, e.to_string()))?;
+        let json =
+            CString::new(json).map_err(|e| (CiphercoreStatus::EvaluationError, e.to_string()))?;
+        *out = json.into_raw();
+        Ok(())
+    })
+}
+
+/// Releases a result handle. Null is ignored.
+///
+/// # Safety
+///
+/// `result` must be null or a handle returned by [ciphercore_evaluate] that hasn't been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn ciphercore_result_free(result: *mut CiphercoreResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
+
+/// Releases a string returned by this library. Null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library that hasn't been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn ciphercore_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
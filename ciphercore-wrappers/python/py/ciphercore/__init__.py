@@ -7,6 +7,8 @@ from .internal import (BIT,
                        UINT16,
                        UINT32,
                        UINT64,
+                       INT128,
+                       UINT128,
                        Type,
                        ScalarType,
                        array_type,
@@ -20,4 +22,6 @@ from .internal import (BIT,
                        Node,
                        CustomOperation,
                        TypedValue,
-                       Value)
+                       Value,
+                       compile_context,
+                       evaluate)
@@ -10,6 +10,8 @@ UINT8 = cc.UINT8
 UINT16 = cc.UINT16
 UINT32 = cc.UINT32
 UINT64 = cc.UINT64
+INT128 = cc.INT128
+UINT128 = cc.UINT128
 
 # Re-export type-related primitives.
 Value = cc.Value
@@ -224,6 +226,11 @@ def _from_numpy(a):
     return cc.TypedValue.from_str(f(a))
 
 
+def _to_numpy(self):
+    """Converts typed value of an array type to numpy array."""
+    return cc.to_numpy(self)
+
+
 def _tv_new(_cls, *args):
     """Creates new typed value from serialized string or from numpy array."""
     assert len(args) in [1, 2], 'Unexpected number of args'
@@ -240,8 +247,32 @@ def _tv_new(_cls, *args):
 
 TypedValue = cc.TypedValue
 TypedValue.from_numpy = _from_numpy
+TypedValue.to_numpy = _to_numpy
 TypedValue.__new__ = _tv_new
 
+# MPC compiler and evaluator.
+
+
+def _party_token(party):
+    return str(party) if isinstance(party, int) else party
+
+
+def compile_context(context, input_parties, output_parties, inline_mode='simple'):
+    """Compiles a context into an MPC protocol ready for evaluation by three parties.
+
+    Parties are given by their IDs (0, 1, 2) or as 'public' or 'secret-shared'.
+    """
+    return Context(cc.compile_context(context._inner,
+                                      [_party_token(p) for p in input_parties],
+                                      [_party_token(p) for p in output_parties],
+                                      inline_mode))
+
+
+def evaluate(context, inputs, reveal_output=False):
+    """Evaluates the main graph of a context on given typed values or numpy arrays."""
+    inputs = [TypedValue(x) if type(x).__name__ == 'ndarray' else x for x in inputs]
+    return cc.evaluate(context._inner, inputs, reveal_output)
+
 
 def get_slice(self, array_slice):
     if isinstance(array_slice, slice):
@@ -4,8 +4,8 @@ use ciphercore_base::data_types::{
     py_binding_tuple_type, py_binding_vector_type,
 };
 use ciphercore_base::data_types::{
-    PyBindingScalarType, PyBindingType, BIT, INT16, INT32, INT64, INT8, UINT16, UINT32, UINT64,
-    UINT8,
+    PyBindingScalarType, PyBindingType, BIT, INT128, INT16, INT32, INT64, INT8, UINT128, UINT16,
+    UINT32, UINT64, UINT8,
 };
 use ciphercore_base::data_values::PyBindingValue;
 use ciphercore_base::graphs::{
@@ -14,7 +14,71 @@ use ciphercore_base::graphs::{
 };
 use ciphercore_base::typed_value::PyBindingTypedValue;
 use numpy::PyReadonlyArrayDyn;
-use pyo3::prelude::{pymodule, wrap_pyfunction, PyModule, PyResult, Python};
+use pyo3::prelude::{pymodule, wrap_pyfunction, PyModule, PyObject, PyRef, PyResult, Python};
+
+/// Compiles a context into an MPC protocol ready for evaluation by three parties.
+///
+/// # Arguments
+///
+/// `context` - finalized context with the main graph
+/// `input_parties` - owner of every input of the main graph: `"0"`, `"1"`, `"2"`, `"public"` or `"secret-shared"`
+/// `output_parties` - parties receiving the output (`"0"`, `"1"`, `"2"`), or a single `"public"` or `"secret-shared"`
+/// `inline_mode` - `"simple"`, `"depth-optimized-default"` or `"depth-optimized-extreme"`
+///
+/// # Returns
+///
+/// Compiled context
+#[pyo3::pyfunction]
+#[pyo3(text_signature = "(context, input_parties, output_parties, inline_mode, /)")]
+fn compile_context(
+    context: &PyBindingContext,
+    input_parties: Vec<String>,
+    output_parties: Vec<String>,
+    inline_mode: &str,
+) -> PyResult<PyBindingContext> {
+    Ok(PyBindingContext {
+        inner: rust::compile_context(
+            context.inner.clone(),
+            input_parties,
+            output_parties,
+            inline_mode,
+        )?,
+    })
+}
+
+/// Evaluates the main graph of a context on given inputs with the simple evaluator.
+///
+/// # Arguments
+///
+/// `context` - finalized context with the main graph
+/// `inputs` - typed values of the inputs of the main graph in the order of their creation
+/// `reveal_output` - if true, a secret-shared output is revealed
+///
+/// # Returns
+///
+/// Typed value of the output
+#[pyo3::pyfunction]
+#[pyo3(text_signature = "(context, inputs, reveal_output, /)")]
+fn evaluate(
+    context: &PyBindingContext,
+    inputs: Vec<PyRef<PyBindingTypedValue>>,
+    reveal_output: bool,
+) -> PyResult<PyBindingTypedValue> {
+    Ok(PyBindingTypedValue {
+        inner: rust::evaluate(
+            context.inner.clone(),
+            inputs.into_iter().map(|x| x.inner.clone()).collect(),
+            reveal_output,
+        )?,
+    })
+}
+
+/// Converts a typed value of an array type to a numpy array of the corresponding dtype.
+#[pyo3::pyfunction]
+#[pyo3(text_signature = "(typed_value, /)")]
+fn to_numpy(py: Python<'_>, typed_value: &PyBindingTypedValue) -> PyResult<PyObject> {
+    Ok(rust::to_numpy(py, &typed_value.inner)?)
+}
 
 #[pymodule]
 fn ciphercore_internal(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
@@ -44,6 +108,9 @@ fn ciphercore_internal(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(py_binding_array_type, m)?)?;
     m.add_function(wrap_pyfunction!(py_binding_vector_type, m)?)?;
     m.add_function(wrap_pyfunction!(py_binding_named_tuple_type, m)?)?;
+    m.add_function(wrap_pyfunction!(compile_context, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluate, m)?)?;
+    m.add_function(wrap_pyfunction!(to_numpy, m)?)?;
 
     m.add("BIT", PyBindingScalarType { inner: BIT })?;
     m.add("UINT8", PyBindingScalarType { inner: UINT8 })?;
@@ -54,6 +121,8 @@ fn ciphercore_internal(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add("INT32", PyBindingScalarType { inner: INT32 })?;
     m.add("UINT64", PyBindingScalarType { inner: UINT64 })?;
     m.add("INT64", PyBindingScalarType { inner: INT64 })?;
+    m.add("UINT128", PyBindingScalarType { inner: UINT128 })?;
+    m.add("INT128", PyBindingScalarType { inner: INT128 })?;
     m.add_class::<PyBindingScalarType>()?;
     m.add_class::<PyBindingType>()?;
     m.add_class::<PyBindingContext>()?;
@@ -69,11 +138,21 @@ fn ciphercore_internal(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
 mod rust {
     use std::ops::Not;
 
-    use ciphercore_base::data_types::ScalarType;
+    use ciphercore_base::data_types::{
+        ScalarType, Type, BIT, INT16, INT32, INT64, INT8, UINT16, UINT32, UINT64, UINT8,
+    };
     use ciphercore_base::errors::Result;
+    use ciphercore_base::evaluators::get_result_util::get_evaluator_result;
+    use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
+    use ciphercore_base::graphs::Context;
+    use ciphercore_base::inline::inline_common::DepthOptimizationLevel;
+    use ciphercore_base::inline::inline_ops::{InlineConfig, InlineMode};
+    use ciphercore_base::mpc::mpc_compiler::{self, IOStatus, PARTIES};
+    use ciphercore_base::runtime_error;
     use ciphercore_base::typed_value::TypedValue;
-    use ciphercore_base::typed_value_operations::TypedValueArrayOperations;
-    use numpy::PyReadonlyArrayDyn;
+    use ciphercore_base::typed_value_operations::{ToNdarray, TypedValueArrayOperations};
+    use numpy::{IntoPyArray, PyReadonlyArrayDyn};
+    use pyo3::{IntoPy, PyObject, Python};
 
     pub(crate) fn serialize_to_str<
         T: numpy::Element + TryInto<u64> + Not<Output = T> + TryInto<u8> + Copy,
@@ -85,4 +164,101 @@ mod rust {
         let tv = TypedValue::from_ndarray(array.to_owned(), st)?;
         Ok(serde_json::to_string(&tv)?)
     }
+
+    fn parse_party(token: &str) -> Result<IOStatus> {
+        match token {
+            "public" => Ok(IOStatus::Public),
+            "secret-shared" => Ok(IOStatus::Shared),
+            _ => match token.parse::<u64>() {
+                Ok(id) if id < PARTIES as u64 => Ok(IOStatus::Party(id)),
+                _ => Err(runtime_error!("Invalid party: {}", token)),
+            },
+        }
+    }
+
+    fn parse_output_parties(tokens: Vec<String>) -> Result<Vec<IOStatus>> {
+        if tokens.len() == 1 {
+            match parse_party(&tokens[0])? {
+                IOStatus::Public => return Ok((0..PARTIES as u64).map(IOStatus::Party).collect()),
+                IOStatus::Shared => return Ok(vec![]),
+                IOStatus::Party(id) => return Ok(vec![IOStatus::Party(id)]),
+            }
+        }
+        let mut parties = vec![];
+        for token in tokens {
+            match parse_party(&token)? {
+                IOStatus::Party(id) if !parties.contains(&IOStatus::Party(id)) => {
+                    parties.push(IOStatus::Party(id))
+                }
+                _ => return Err(runtime_error!("Invalid output parties")),
+            }
+        }
+        Ok(parties)
+    }
+
+    fn parse_inline_mode(mode: &str) -> Result<InlineMode> {
+        match mode {
+            "simple" => Ok(InlineMode::Simple),
+            "depth-optimized-default" => {
+                Ok(InlineMode::DepthOptimized(DepthOptimizationLevel::Default))
+            }
+            "depth-optimized-extreme" => {
+                Ok(InlineMode::DepthOptimized(DepthOptimizationLevel::Extreme))
+            }
+            _ => Err(runtime_error!("Invalid inline mode: {}", mode)),
+        }
+    }
+
+    pub(crate) fn compile_context(
+        context: Context,
+        input_parties: Vec<String>,
+        output_parties: Vec<String>,
+        inline_mode: &str,
+    ) -> Result<Context> {
+        let input_parties = input_parties
+            .iter()
+            .map(|token| parse_party(token))
+            .collect::<Result<Vec<IOStatus>>>()?;
+        mpc_compiler::compile_context(
+            context,
+            input_parties,
+            parse_output_parties(output_parties)?,
+            InlineConfig {
+                default_mode: parse_inline_mode(inline_mode)?,
+                ..Default::default()
+            },
+            || SimpleEvaluator::new(None),
+        )
+    }
+
+    pub(crate) fn evaluate(
+        context: Context,
+        inputs: Vec<TypedValue>,
+        reveal_output: bool,
+    ) -> Result<TypedValue> {
+        get_evaluator_result(context, inputs, reveal_output, SimpleEvaluator::new(None)?)
+    }
+
+    fn array_to_numpy<T: numpy::Element>(py: Python<'_>, a: ndarray::ArrayD<T>) -> PyObject {
+        a.into_pyarray(py).into_py(py)
+    }
+
+    pub(crate) fn to_numpy(py: Python<'_>, tv: &TypedValue) -> Result<PyObject> {
+        let st = match &tv.t {
+            Type::Array(_, st) => st.clone(),
+            _ => return Err(runtime_error!("Only arrays can be converted to numpy")),
+        };
+        Ok(match st {
+            BIT => array_to_numpy(py, ToNdarray::<bool>::to_ndarray(tv)?),
+            UINT8 => array_to_numpy(py, ToNdarray::<u8>::to_ndarray(tv)?),
+            INT8 => array_to_numpy(py, ToNdarray::<i8>::to_ndarray(tv)?),
+            UINT16 => array_to_numpy(py, ToNdarray::<u16>::to_ndarray(tv)?),
+            INT16 => array_to_numpy(py, ToNdarray::<i16>::to_ndarray(tv)?),
+            UINT32 => array_to_numpy(py, ToNdarray::<u32>::to_ndarray(tv)?),
+            INT32 => array_to_numpy(py, ToNdarray::<i32>::to_ndarray(tv)?),
+            UINT64 => array_to_numpy(py, ToNdarray::<u64>::to_ndarray(tv)?),
+            INT64 => array_to_numpy(py, ToNdarray::<i64>::to_ndarray(tv)?),
+            _ => return Err(runtime_error!("Unsupported scalar type: {}", st)),
+        })
+    }
 }
@@ -534,6 +534,16 @@ mod macro_backend {
                         .map_err(|err| pyo3::exceptions::PyRuntimeError::new_err(err.to_string()))
                 }
             )),
+            "filter" => Some(quote!(
+                pub fn filter(&self, predicate: String) -> pyo3::PyResult<PyBindingNode> {
+                    let predicate = serde_json::from_str(&predicate).map_err(|err| {
+                        pyo3::exceptions::PyRuntimeError::new_err(err.to_string())
+                    })?;
+                    Ok(PyBindingNode {
+                        inner: self.inner.filter(predicate)?,
+                    })
+                }
+            )),
             "named_tuple_type" => Some(quote!(
                 pub fn py_binding_named_tuple_type(
                     v: Vec<(String, pyo3::PyRef<PyBindingType>)>,
@@ -0,0 +1,68 @@
+//! WebAssembly bindings of the CipherCore evaluator.
+//!
+//! The crate is meant to be built for the `wasm32-unknown-unknown` target, e.g. via
+//! ```bash
+//! wasm-pack build ciphercore-wrappers/wasm --target web
+//! ```
+//! which allows a browser-based party to evaluate a compiled graph on its own inputs locally.
+//!
+//! Contexts and values are passed as JSON strings in the same format as the one used by the `ciphercore_evaluate` and `ciphercore_split_parties` binaries.
+//! Contexts must not contain custom operations, which is always the case for contexts produced by the MPC compiler.
+use ciphercore_base::errors::Result;
+use ciphercore_base::evaluators::get_result_util::get_evaluator_result;
+use ciphercore_base::evaluators::simple_evaluator::SimpleEvaluator;
+use ciphercore_base::graphs::Context;
+use ciphercore_base::random::PRNG;
+use ciphercore_base::typed_value::TypedValue;
+use ciphercore_utils::errors::ErrorWithBody;
+
+use wasm_bindgen::prelude::*;
+
+fn to_js_error<T>(result: Result<T>) -> std::result::Result<T, JsValue> {
+    result.map_err(|e| JsValue::from_str(&e.get_body().to_string()))
+}
+
+/// Evaluates the main graph of a serialized context on serialized typed inputs.
+///
+/// # Arguments
+///
+/// * `serialized_context` - JSON of a finalized context
+/// * `serialized_inputs` - JSON array of typed values of the inputs of the main graph
+/// * `reveal_output` - if true, a secret-shared output is revealed
+///
+/// # Returns
+///
+/// JSON of the typed output value
+#[wasm_bindgen]
+pub fn evaluate(
+    serialized_context: &str,
+    serialized_inputs: &str,
+    reveal_output: bool,
+) -> std::result::Result<String, JsValue> {
+    to_js_error((|| -> Result<String> {
+        let context = serde_json::from_str::<Context>(serialized_context)?;
+        let inputs = serde_json::from_str::<Vec<TypedValue>>(serialized_inputs)?;
+        let result =
+            get_evaluator_result(context, inputs, reveal_output, SimpleEvaluator::new(None)?)?;
+        Ok(serde_json::to_string(&result)?)
+    })())
+}
+
+/// Splits a serialized typed value into replicated secret shares to be sent to the parties.
+///
+/// # Arguments
+///
+/// `serialized_value` - JSON of a typed value
+///
+/// # Returns
+///
+/// JSON array of typed values; the i-th one is the local share of party i
+#[wasm_bindgen]
+pub fn secret_share(serialized_value: &str) -> std::result::Result<String, JsValue> {
+    to_js_error((|| -> Result<String> {
+        let value = serde_json::from_str::<TypedValue>(serialized_value)?;
+        let mut prng = PRNG::new(None)?;
+        let shares = value.get_local_shares_for_each_party(&mut prng)?;
+        Ok(serde_json::to_string(&shares)?)
+    })())
+}
@@ -1,2 +1,3 @@
 #!/bin/sh
 cbindgen --config cbindgen.toml --crate cadapter --output ciphercore-wrappers/C-wrapper/raw.h
+cbindgen --config ciphercore-wrappers/ffi/cbindgen.toml --crate ciphercore-ffi --output ciphercore-wrappers/ffi/include/ciphercore_ffi.h